@@ -16,7 +16,8 @@ use near_chain_primitives::error::{BlockKnownError, Error, ErrorKind, LogTransie
 use near_primitives::block::{genesis_chunks, Tip};
 use near_primitives::challenge::{
     BlockDoubleSign, Challenge, ChallengeBody, ChallengesResult, ChunkProofs, ChunkState,
-    MaybeEncodedShardChunk, SlashedValidator,
+    ChunkStateReceipts, ChunkStateV2, MaybeEncodedShardChunk, SlashedValidator,
+    MAX_CHUNK_STATE_CHALLENGE_SIZE,
 };
 use near_primitives::checked_feature;
 use near_primitives::hash::{hash, CryptoHash};
@@ -24,6 +25,7 @@ use near_primitives::merkle::{
     combine_hash, merklize, verify_path, Direction, MerklePath, MerklePathItem,
 };
 use near_primitives::receipt::Receipt;
+use near_primitives::receipt_proof;
 use near_primitives::sharding::{
     ChunkHash, ChunkHashHeight, ReceiptList, ReceiptProof, ShardChunk, ShardChunkHeader, ShardInfo,
     ShardProof, StateSyncInfo,
@@ -32,8 +34,9 @@ use near_primitives::state_part::PartId;
 use near_primitives::syncing::{
     get_num_state_parts, ReceiptProofResponse, RootProof, ShardStateSyncResponseHeader,
     ShardStateSyncResponseHeaderV1, ShardStateSyncResponseHeaderV2, StateHeaderKey, StatePartKey,
+    StateSyncProgress,
 };
-use near_primitives::transaction::ExecutionOutcomeWithIdAndProof;
+use near_primitives::transaction::{ExecutionMetadata, ExecutionOutcomeWithIdAndProof};
 use near_primitives::types::chunk_extra::ChunkExtra;
 use near_primitives::types::{
     AccountId, Balance, BlockExtra, BlockHeight, BlockHeightDelta, EpochId, Gas, MerkleHash,
@@ -44,9 +47,11 @@ use near_primitives::utils::MaybeValidated;
 use near_primitives::views::{
     ExecutionOutcomeWithIdView, ExecutionStatusView, FinalExecutionOutcomeView,
     FinalExecutionOutcomeWithReceiptView, FinalExecutionStatus, LightClientBlockView,
-    SignedTransactionView,
+    RefundOriginView, RefundView, SignedTransactionView,
+};
+use near_store::{
+    ColState, ColStateHeaders, ColStateParts, ColStateSyncProgress, ShardTries, StoreUpdate,
 };
-use near_store::{ColState, ColStateHeaders, ColStateParts, ShardTries, StoreUpdate};
 
 use near_primitives::state_record::StateRecord;
 
@@ -61,16 +66,14 @@ use crate::types::{
     ChainGenesis, Provenance, RuntimeAdapter,
 };
 use crate::validate::{
-    validate_challenge, validate_chunk_proofs, validate_chunk_with_chunk_extra,
-    validate_transactions_order,
+    validate_challenge, validate_chunk_proofs, validate_chunk_state_receipts,
+    validate_chunk_with_chunk_extra, validate_transactions_order,
 };
 use crate::{byzantine_assert, create_light_client_block_view, Doomslug};
 use crate::{metrics, DoomslugThresholdMode};
 use actix::Message;
 use delay_detector::DelayDetector;
-use near_primitives::shard_layout::{
-    account_id_to_shard_id, account_id_to_shard_uid, ShardLayout, ShardUId,
-};
+use near_primitives::shard_layout::{account_id_to_shard_uid, ShardLayout, ShardUId};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
 /// Maximum number of orphans chain can store.
@@ -852,6 +855,14 @@ impl Chain {
         Ok(())
     }
 
+    /// Makes progress on pruning execution outcomes of blocks older than `epochs` epochs. Meant
+    /// to be called periodically together with `clear_data`.
+    pub fn prune_outcomes_step(&mut self, epochs: u64) -> Result<(), Error> {
+        let _d = DelayDetector::new(|| "prune outcomes".into());
+        let min_height = self.store.outcomes_prune_height(epochs, self.epoch_length)?;
+        self.store.prune_outcomes_step(min_height)
+    }
+
     pub fn clear_forks_data(
         &mut self,
         tries: ShardTries,
@@ -2089,20 +2100,64 @@ impl Chain {
         let state_root = *chunk.take_header().take_inner().prev_state_root();
         if !self.runtime_adapter.validate_state_part(&state_root, part_id, data) {
             byzantine_assert!(false);
-            return Err(ErrorKind::Other(
-                "set_state_part failed: validate_state_part failed".into(),
-            )
-            .into());
+            return Err(ErrorKind::InvalidStatePayload.into());
         }
 
-        // Saving the part data.
+        // Saving the part data along with the download progress.
+        let mut progress = self.get_state_sync_progress(shard_id, sync_hash, part_id.total)?;
+        progress.part_hashes[part_id.idx as usize] = Some(hash(data));
         let mut store_update = self.store.store().store_update();
         let key = StatePartKey(sync_hash, shard_id, part_id.idx).try_to_vec()?;
         store_update.set(ColStateParts, &key, data);
+        let key = StateHeaderKey(shard_id, sync_hash).try_to_vec()?;
+        store_update.set_ser(ColStateSyncProgress, &key, &progress)?;
         store_update.commit()?;
         Ok(())
     }
 
+    /// Returns which state parts of the shard have been downloaded so far.
+    pub fn get_state_sync_progress(
+        &self,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+        num_parts: u64,
+    ) -> Result<StateSyncProgress, Error> {
+        Ok(self
+            .store
+            .get_state_sync_progress(shard_id, sync_hash)?
+            .filter(|progress| progress.part_hashes.len() as u64 == num_parts)
+            .unwrap_or_else(|| StateSyncProgress::new(num_parts)))
+    }
+
+    /// Checks the downloaded state parts of the shard against the hashes they had when they were
+    /// validated.  Parts which are missing from the store or got corrupted are forgotten, so
+    /// that they are downloaded again.  Returns the progress after the check and the ids of the
+    /// forgotten parts.
+    pub fn check_downloaded_state_parts(
+        &mut self,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+        num_parts: u64,
+    ) -> Result<(StateSyncProgress, Vec<u64>), Error> {
+        let mut progress = self.get_state_sync_progress(shard_id, sync_hash, num_parts)?;
+        let mut corrupted_parts = vec![];
+        for part_id in progress.done_parts().collect::<Vec<_>>() {
+            let key = StatePartKey(sync_hash, shard_id, part_id).try_to_vec()?;
+            let data = self.store.store().get(ColStateParts, &key)?;
+            if data.map(|data| hash(&data)) != progress.part_hashes[part_id as usize] {
+                progress.part_hashes[part_id as usize] = None;
+                corrupted_parts.push(part_id);
+            }
+        }
+        if !corrupted_parts.is_empty() {
+            let mut store_update = self.store.store().store_update();
+            let key = StateHeaderKey(shard_id, sync_hash).try_to_vec()?;
+            store_update.set_ser(ColStateSyncProgress, &key, &progress)?;
+            store_update.commit()?;
+        }
+        Ok((progress, corrupted_parts))
+    }
+
     pub fn schedule_apply_state_parts(
         &mut self,
         shard_id: ShardId,
@@ -2340,7 +2395,7 @@ impl Chain {
         &mut self,
         id: &CryptoHash,
     ) -> Result<Vec<ExecutionOutcomeWithIdView>, Error> {
-        Ok(self.store.get_outcomes_by_id(id)?.into_iter().map(Into::into).collect())
+        Ok(self.store.get_outcomes_for_id(id)?.into_iter().map(Into::into).collect())
     }
 
     fn get_recursive_transaction_results(
@@ -2403,29 +2458,45 @@ impl Chain {
 
     pub fn get_final_transaction_result_with_receipt(
         &mut self,
-        final_outcome: FinalExecutionOutcomeView,
+        mut final_outcome: FinalExecutionOutcomeView,
     ) -> Result<FinalExecutionOutcomeWithReceiptView, Error> {
         let receipt_id_from_transaction =
             final_outcome.transaction_outcome.outcome.receipt_ids.get(0).cloned();
         let is_local_receipt =
             final_outcome.transaction.signer_id == final_outcome.transaction.receiver_id;
 
-        let receipts = final_outcome
-            .receipts_outcome
-            .iter()
-            .filter_map(|outcome| {
-                if Some(outcome.id) == receipt_id_from_transaction && is_local_receipt {
-                    None
-                } else {
-                    Some(self.store.get_receipt(&outcome.id).and_then(|r| {
-                        r.cloned().map(Into::into).ok_or_else(|| {
-                            ErrorKind::DBNotFoundErr(format!("Receipt {} is not found", outcome.id))
-                                .into()
-                        })
-                    }))
+        // Outcomes stored with `ExecutionMetadata::V3` list the refunds they generated, the
+        // origin of refunds generated before is unknown.
+        let mut refund_origins = HashMap::new();
+        for outcome in final_outcome.receipts_outcome.iter() {
+            if let ExecutionMetadata::V3 { refunds, .. } =
+                self.get_execution_outcome(&outcome.id)?.outcome_with_id.outcome.metadata
+            {
+                for refund in refunds {
+                    refund_origins.insert(refund.receipt_id, (outcome.id, refund.kind));
                 }
-            })
-            .collect::<Result<Vec<_>, _>>()?;
+            }
+        }
+
+        let mut receipts = vec![];
+        for outcome in final_outcome.receipts_outcome.iter_mut() {
+            // The receipt of a local transaction isn't stored, and is never a refund.
+            if Some(outcome.id) == receipt_id_from_transaction && is_local_receipt {
+                continue;
+            }
+            let receipt = self.store.get_receipt(&outcome.id)?.cloned().ok_or_else(|| {
+                ErrorKind::DBNotFoundErr(format!("Receipt {} is not found", outcome.id))
+            })?;
+            if let Some(kind) = receipt.refund_kind() {
+                let (origin, kind) = match refund_origins.get(&outcome.id) {
+                    Some((origin_id, kind)) => (RefundOriginView::ReceiptId(*origin_id), *kind),
+                    None => (RefundOriginView::Unknown, kind),
+                };
+                outcome.outcome.refund =
+                    Some(RefundView { is_refund: true, origin, kind: kind.into() });
+            }
+            receipts.push(receipt.into());
+        }
 
         Ok(FinalExecutionOutcomeWithReceiptView { final_outcome, receipts })
     }
@@ -2454,7 +2525,12 @@ impl Chain {
         block_hash: &CryptoHash,
     ) -> Result<(), Error> {
         let last_final_block_hash = *self.head_header()?.last_final_block();
-        let last_final_height = self.get_block_header(&last_final_block_hash)?.height();
+        // Until the first blocks after genesis are finalized, the genesis is the only final block.
+        let last_final_height = if last_final_block_hash == CryptoHash::default() {
+            self.genesis().height()
+        } else {
+            self.get_block_header(&last_final_block_hash)?.height()
+        };
         let block_header = self.get_block_header(block_hash)?.clone();
         if block_header.height() <= last_final_height {
             self.is_on_current_chain(&block_header)
@@ -2479,10 +2555,7 @@ impl Chain {
                 .get_outcomes_by_block_hash_and_shard_id(block_hash, shard_id)?
                 .into_iter()
                 .flat_map(|id| {
-                    let mut outcomes =
-                        self.store.get_outcomes_by_id(&id).unwrap_or_else(|_| vec![]);
-                    outcomes.retain(|outcome| &outcome.block_hash == block_hash);
-                    outcomes
+                    self.store.get_outcome_by_id_and_block_hash(&id, block_hash).unwrap_or(None)
                 })
                 .collect::<Vec<_>>();
             res.insert(shard_id, outcomes);
@@ -2800,6 +2873,27 @@ impl Chain {
         self.store.get_earliest_block_hash()
     }
 
+    /// Returns hash of the first available block at or above `min_height`, for data which is
+    /// garbage collected before the blocks themselves.
+    pub fn get_earliest_block_hash_from(
+        &mut self,
+        min_height: BlockHeight,
+    ) -> Result<Option<CryptoHash>, Error> {
+        let earliest_block_hash = match self.get_earliest_block_hash()? {
+            Some(block_hash) => block_hash,
+            None => return Ok(None),
+        };
+        if self.get_block_header(&earliest_block_hash)?.height() >= min_height {
+            return Ok(Some(earliest_block_hash));
+        }
+        for height in min_height..=self.head()?.height {
+            if let Ok(block_hash) = self.get_block_hash_by_height(height) {
+                return Ok(Some(block_hash));
+            }
+        }
+        Ok(None)
+    }
+
     /// Check if block exists.
     #[inline]
     pub fn block_exists(&self, hash: &CryptoHash) -> Result<bool, Error> {
@@ -2962,16 +3056,8 @@ impl Chain {
         &mut self,
         id: &CryptoHash,
     ) -> Result<ExecutionOutcomeWithIdAndProof, Error> {
-        let outcomes = self.store.get_outcomes_by_id(id)?;
-        outcomes
-            .into_iter()
-            .find(|outcome| match self.get_block_header(&outcome.block_hash) {
-                Ok(header) => {
-                    let header = header.clone();
-                    self.is_on_current_chain(&header).is_ok()
-                }
-                Err(_) => false,
-            })
+        self.store
+            .get_outcome_for_canonical_chain(id)?
             .ok_or_else(|| ErrorKind::DBNotFoundErr(format!("EXECUTION OUTCOME: {}", id)).into())
     }
 
@@ -3006,6 +3092,27 @@ impl Chain {
         Ok(headers)
     }
 
+    /// Returns the canonical headers with heights in `start_height..=end_height`.  At most
+    /// `max_headers_returned` heights are looked at, so a range that is too long is truncated.
+    pub fn retrieve_headers_by_height(
+        &mut self,
+        start_height: BlockHeight,
+        end_height: BlockHeight,
+        max_headers_returned: u64,
+    ) -> Result<Vec<BlockHeader>, Error> {
+        let header_head_height = self.header_head()?.height;
+        let end_height = end_height
+            .min(header_head_height)
+            .min(start_height.saturating_add(max_headers_returned.saturating_sub(1)));
+        let mut headers = vec![];
+        for h in start_height..=end_height {
+            if let Ok(header) = self.get_header_by_height(h) {
+                headers.push(header.clone());
+            }
+        }
+        Ok(headers)
+    }
+
     /// Returns a vector of chunk headers, each of which corresponds to the previous chunk of
     /// a chunk in the block after `prev_block`
     /// This function is important when the block after `prev_block` has different number of chunks
@@ -3046,43 +3153,14 @@ impl Chain {
         receipts: Vec<Receipt>,
         shard_layout: &ShardLayout,
     ) -> HashMap<ShardId, Vec<Receipt>> {
-        let mut result = HashMap::with_capacity(shard_layout.num_shards() as usize);
-        for receipt in receipts {
-            let shard_id = account_id_to_shard_id(&receipt.receiver_id, shard_layout);
-            let entry = result.entry(shard_id).or_insert_with(Vec::new);
-            entry.push(receipt)
-        }
-        result
+        receipt_proof::group_receipts_by_shard(receipts, shard_layout)
     }
 
     pub fn build_receipts_hashes(
         receipts: &[Receipt],
         shard_layout: &ShardLayout,
     ) -> Vec<CryptoHash> {
-        if shard_layout.num_shards() == 1 {
-            return vec![hash(&ReceiptList(0, receipts).try_to_vec().unwrap())];
-        }
-        let mut account_id_to_shard_id_map = HashMap::new();
-        let mut shard_receipts: Vec<_> =
-            (0..shard_layout.num_shards()).map(|i| (i, Vec::new())).collect();
-        for receipt in receipts.iter() {
-            let shard_id = match account_id_to_shard_id_map.get(&receipt.receiver_id) {
-                Some(id) => *id,
-                None => {
-                    let id = account_id_to_shard_id(&receipt.receiver_id, shard_layout);
-                    account_id_to_shard_id_map.insert(receipt.receiver_id.clone(), id);
-                    id
-                }
-            };
-            shard_receipts[shard_id as usize].1.push(receipt);
-        }
-        shard_receipts
-            .into_iter()
-            .map(|(i, rs)| {
-                let bytes = (i, rs).try_to_vec().unwrap();
-                hash(&bytes)
-            })
-            .collect()
+        receipt_proof::build_receipts_hashes(receipts, shard_layout)
     }
 }
 
@@ -3385,76 +3463,115 @@ impl<'a> ChainUpdate<'a> {
         }
 
         for (shard_id, mut receipt_proofs) in receipt_proofs_by_shard_id {
-            let mut slice = [0u8; 32];
-            slice.copy_from_slice(block.hash().as_ref());
-            let mut rng: StdRng = SeedableRng::from_seed(slice);
-            receipt_proofs.shuffle(&mut rng);
+            shuffle_receipt_proofs(&mut receipt_proofs, block.hash());
             self.chain_store_update.save_incoming_receipt(block.hash(), shard_id, receipt_proofs);
         }
 
         Ok(())
     }
 
+    /// Creates a challenge showing that the post-state of the previous chunk of the shard which
+    /// `chunk_header` claims doesn't match the result of applying that chunk.
+    ///
+    /// Only the case when the previous chunk is included in `prev_block` is covered: otherwise
+    /// the state of the shard has also been changed by applying the blocks without a new chunk.
     pub fn create_chunk_state_challenge(
         &mut self,
         prev_block: &Block,
         block: &Block,
         chunk_header: &ShardChunkHeader,
-    ) -> Result<ChunkState, Error> {
+    ) -> Result<ChunkStateV2, Error> {
         let chunk_shard_id = chunk_header.shard_id();
         let prev_chunk_header = &prev_block.chunks()[chunk_shard_id as usize];
+        if prev_chunk_header.height_included() != prev_block.header().height() {
+            return Err(ErrorKind::Other(
+                "Previous chunk is not included in the previous block".to_string(),
+            )
+            .into());
+        }
+        let shard_layout = self.runtime_adapter.get_shard_layout(block.header().epoch_id())?;
+        if shard_layout != self.runtime_adapter.get_shard_layout(prev_block.header().epoch_id())? {
+            return Err(ErrorKind::Other(
+                "Shard layout changes after the previous chunk".to_string(),
+            )
+            .into());
+        }
+        let is_first_block_with_chunk_of_version = check_if_block_is_first_with_chunk_of_version(
+            &mut self.chain_store_update,
+            self.runtime_adapter.as_ref(),
+            prev_block.hash(),
+            chunk_shard_id,
+        )?;
+        if is_first_block_with_chunk_of_version {
+            return Err(ErrorKind::Other(
+                "Previous chunk applies receipts restored by a protocol upgrade".to_string(),
+            )
+            .into());
+        }
         let prev_merkle_proofs = Block::compute_chunk_headers_root(prev_block.chunks().iter()).1;
         let merkle_proofs = Block::compute_chunk_headers_root(block.chunks().iter()).1;
         let prev_chunk = self
             .chain_store_update
             .get_chain_store()
-            .get_chunk_clone_from_header(&prev_block.chunks()[chunk_shard_id as usize].clone())
-            .unwrap();
-        let receipt_proof_response: Vec<ReceiptProofResponse> =
-            self.chain_store_update.get_incoming_receipts_for_shard(
-                chunk_shard_id,
-                *prev_block.hash(),
-                prev_chunk_header.height_included(),
-            )?;
-        let receipts = collect_receipts_from_response(&receipt_proof_response);
+            .get_chunk_clone_from_header(&prev_chunk_header.clone())?;
 
-        let challenges_result = self.verify_challenges(
-            block.challenges(),
-            block.header().epoch_id(),
-            block.header().prev_hash(),
-            Some(block.hash()),
+        // The previous chunk was applied with the receipts sent by the blocks since the chunk
+        // before it, down to the block with that chunk.
+        let mut prev_chunk_receipts = vec![];
+        let mut receipts_block = prev_block.clone();
+        loop {
+            let height = receipts_block.header().height();
+            let is_last = !prev_chunk_receipts.is_empty()
+                && receipts_block.chunks()[chunk_shard_id as usize].height_included() == height;
+            let mut receipt_proofs = if is_last {
+                vec![]
+            } else {
+                self.chain_store_update
+                    .get_incoming_receipts(receipts_block.hash(), chunk_shard_id)
+                    .map(|receipt_proofs| receipt_proofs.clone())
+                    .unwrap_or_default()
+            };
+            // The proofs are stored shuffled, the challenge has them in the order of the chunks.
+            receipt_proofs.sort_by_key(|ReceiptProof(_, shard_proof)| shard_proof.from_shard_id);
+            prev_chunk_receipts.push(ChunkStateReceipts {
+                block_header: receipts_block.header().try_to_vec()?,
+                chunk_headers: receipts_block.chunks().iter().cloned().collect(),
+                receipt_proofs,
+            });
+            if is_last {
+                break;
+            }
+            receipts_block =
+                self.chain_store_update.get_block(receipts_block.header().prev_hash())?.clone();
+        }
+        let (receipts, gas_price) = validate_chunk_state_receipts(
+            prev_block.header(),
+            &prev_chunk.cloned_header(),
+            &prev_chunk_receipts,
         )?;
+
         let prev_chunk_inner = prev_chunk.cloned_header().take_inner();
-        let is_first_block_with_chunk_of_version = check_if_block_is_first_with_chunk_of_version(
-            &mut self.chain_store_update,
-            self.runtime_adapter.as_ref(),
-            prev_block.hash(),
+        let apply_result = self.runtime_adapter.apply_transactions_with_optional_storage_proof(
             chunk_shard_id,
+            prev_chunk_inner.prev_state_root(),
+            prev_block.header().height(),
+            prev_block.header().raw_timestamp(),
+            prev_block.header().prev_hash(),
+            prev_block.hash(),
+            &receipts,
+            prev_chunk.transactions(),
+            prev_chunk_inner.validator_proposals(),
+            gas_price,
+            prev_chunk_inner.gas_limit(),
+            prev_block.header().challenges_result(),
+            *prev_block.header().random_value(),
+            true,
+            true,
+            false,
+            None,
         )?;
-        let apply_result = self
-            .runtime_adapter
-            .apply_transactions_with_optional_storage_proof(
-                chunk_shard_id,
-                prev_chunk_inner.prev_state_root(),
-                prev_chunk.height_included(),
-                prev_block.header().raw_timestamp(),
-                prev_chunk_inner.prev_block_hash(),
-                prev_block.hash(),
-                &receipts,
-                prev_chunk.transactions(),
-                prev_chunk_inner.validator_proposals(),
-                prev_block.header().gas_price(),
-                prev_chunk_inner.gas_limit(),
-                &challenges_result,
-                *block.header().random_value(),
-                true,
-                true,
-                is_first_block_with_chunk_of_version,
-                None,
-            )
-            .unwrap();
-        let partial_state = apply_result.proof.unwrap().nodes;
-        Ok(ChunkState {
+        let partial_state = apply_result.proof.expect("storage proof is requested").nodes;
+        let chunk_state = ChunkStateV2 {
             prev_block_header: prev_block.header().try_to_vec()?,
             block_header: block.header().try_to_vec()?,
             prev_merkle_proof: prev_merkle_proofs[chunk_shard_id as usize].clone(),
@@ -3462,7 +3579,17 @@ impl<'a> ChainUpdate<'a> {
             prev_chunk,
             chunk_header: chunk_header.clone(),
             partial_state,
-        })
+            prev_chunk_receipts,
+        };
+        let size = chunk_state.try_to_vec()?.len();
+        if size > MAX_CHUNK_STATE_CHALLENGE_SIZE {
+            return Err(ErrorKind::Other(format!(
+                "Chunk state challenge of {} bytes exceeds the limit of {} bytes",
+                size, MAX_CHUNK_STATE_CHALLENGE_SIZE
+            ))
+            .into());
+        }
+        Ok(chunk_state)
     }
 
     /// Applies chunks and processes results
@@ -3516,6 +3643,16 @@ impl<'a> ChainUpdate<'a> {
         mode: ApplyChunksMode,
     ) -> Result<Vec<Box<dyn FnOnce() -> Result<ApplyChunkResult, Error> + Send + 'static>>, Error>
     {
+        let _span = tracing::info_span!(
+            target: "chain",
+            "preprocess_block",
+            height = block.header().height(),
+            block_hash = %block.hash(),
+        )
+        .entered();
+        // The chunks are applied in parallel on other threads, their spans are children of
+        // this one.
+        let parent_span = tracing::Span::current();
         let mut result: Vec<Box<dyn FnOnce() -> Result<ApplyChunkResult, Error> + Send + 'static>> =
             Vec::new();
         let challenges_result = self.verify_challenges(
@@ -3625,9 +3762,15 @@ impl<'a> ChainUpdate<'a> {
                         byzantine_assert!(false);
                         match self.create_chunk_state_challenge(prev_block, block, chunk_header) {
                             Ok(chunk_state) => {
-                                Error::from(ErrorKind::InvalidChunkState(Box::new(chunk_state)))
+                                Error::from(ErrorKind::InvalidChunkState(Box::new(
+                                    ChunkState::V2(chunk_state),
+                                )))
+                            }
+                            // The chunk is invalid regardless, just there is no challenge for it.
+                            Err(err) => {
+                                warn!(target: "chain", "Failed to create chunk state challenge: {}", err);
+                                e
                             }
-                            Err(err) => err,
                         }
                     })?;
                     let receipt_proof_response: Vec<ReceiptProofResponse> =
@@ -3699,7 +3842,16 @@ impl<'a> ChainUpdate<'a> {
                     #[cfg(feature = "sandbox")]
                     let states_to_patch = self.states_to_patch.take();
 
+                    let parent_span = parent_span.clone();
                     result.push(Box::new(move || -> Result<ApplyChunkResult, Error> {
+                        let _span = tracing::info_span!(
+                            target: "chain",
+                            parent: &parent_span,
+                            "apply_chunk",
+                            shard_id,
+                            chunk_hash = %chunk.chunk_hash().0,
+                        )
+                        .entered();
                         let _timer = CryptoHashTimer::new(chunk.chunk_hash().0);
                         match runtime_adapter.apply_transactions(
                             shard_id,
@@ -3764,7 +3916,16 @@ impl<'a> ChainUpdate<'a> {
                     #[cfg(not(feature = "sandbox"))]
                     let _ = self.states_to_patch;
 
+                    let parent_span = parent_span.clone();
                     result.push(Box::new(move || -> Result<ApplyChunkResult, Error> {
+                        let _span = tracing::info_span!(
+                            target: "chain",
+                            parent: &parent_span,
+                            "apply_chunk",
+                            shard_id,
+                            is_new_chunk = false,
+                        )
+                        .entered();
                         match runtime_adapter.apply_transactions(
                             shard_id,
                             new_extra.state_root(),
@@ -4889,6 +5050,15 @@ pub fn do_apply_chunks(
     work.into_par_iter().map(|task| task()).collect::<Vec<_>>()
 }
 
+/// Shuffles the receipts sent to a shard by the chunks of the block with `block_hash` into the
+/// order they are applied in.
+pub fn shuffle_receipt_proofs(receipt_proofs: &mut Vec<ReceiptProof>, block_hash: &CryptoHash) {
+    let mut slice = [0u8; 32];
+    slice.copy_from_slice(block_hash.as_ref());
+    let mut rng: StdRng = SeedableRng::from_seed(slice);
+    receipt_proofs.shuffle(&mut rng);
+}
+
 pub fn collect_receipts<'a, T>(receipt_proofs: T) -> Vec<Receipt>
 where
     T: IntoIterator<Item = &'a ReceiptProof>,
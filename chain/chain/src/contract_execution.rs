@@ -0,0 +1,185 @@
+//! Wall-clock execution time and gas burnt by the receipts of each account during the current
+//! epoch, so that validators can tell which contracts their CPU time goes to.
+//!
+//! The accounts are bucketed by shard, so that chunks of different shards applied in parallel
+//! don't contend for the same lock.  The buckets are allocated once and cleared at epoch
+//! boundaries, recording a receipt only allocates the first time an account is seen.
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+
+use near_primitives::types::{AccountId, EpochId, Gas, ShardId};
+use near_primitives::views::{ContractExecutionView, TopContractsView};
+
+const NUM_BUCKETS: usize = 16;
+/// Number of accounts each bucket has room for before it reallocates.
+const BUCKET_CAPACITY: usize = 1024;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct ContractExecution {
+    time: Duration,
+    gas_burnt: Gas,
+    receipts: u64,
+}
+
+impl ContractExecution {
+    fn add(&mut self, other: &ContractExecution) {
+        self.time += other.time;
+        self.gas_burnt = self.gas_burnt.saturating_add(other.gas_burnt);
+        self.receipts += other.receipts;
+    }
+
+    fn to_view(&self, account_id: Option<AccountId>) -> ContractExecutionView {
+        ContractExecutionView {
+            account_id,
+            execution_time_us: self.time.as_micros() as u64,
+            gas_burnt: self.gas_burnt,
+            receipts: self.receipts,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Epochs {
+    current: Option<EpochId>,
+    /// Chunks of the previous epoch applied late, e.g. while catching up, are ignored rather
+    /// than resetting the statistics of the current one.
+    previous: Option<EpochId>,
+}
+
+pub struct ContractExecutionTracker {
+    epochs: RwLock<Epochs>,
+    buckets: Vec<Mutex<HashMap<AccountId, ContractExecution>>>,
+}
+
+impl Default for ContractExecutionTracker {
+    fn default() -> Self {
+        Self {
+            epochs: Default::default(),
+            buckets: (0..NUM_BUCKETS)
+                .map(|_| Mutex::new(HashMap::with_capacity(BUCKET_CAPACITY)))
+                .collect(),
+        }
+    }
+}
+
+impl ContractExecutionTracker {
+    /// Records the receipts of a chunk of `shard_id` applied in `epoch_id`, given as the
+    /// executor, the execution time and the gas burnt of each receipt.  The statistics are reset
+    /// when `epoch_id` is a new epoch.
+    pub fn record<'a>(
+        &self,
+        epoch_id: &EpochId,
+        shard_id: ShardId,
+        receipts: impl Iterator<Item = (&'a AccountId, Duration, Gas)>,
+    ) {
+        let mut epochs = self.epochs.read().unwrap();
+        if epochs.previous.as_ref() == Some(epoch_id) {
+            return;
+        }
+        if epochs.current.as_ref() != Some(epoch_id) {
+            drop(epochs);
+            self.start_epoch(epoch_id);
+            epochs = self.epochs.read().unwrap();
+            if epochs.current.as_ref() != Some(epoch_id) {
+                return;
+            }
+        }
+        let mut bucket = self.buckets[shard_id as usize % NUM_BUCKETS].lock().unwrap();
+        for (account_id, time, gas_burnt) in receipts {
+            let receipt = ContractExecution { time, gas_burnt, receipts: 1 };
+            match bucket.get_mut(account_id) {
+                Some(execution) => execution.add(&receipt),
+                None => {
+                    bucket.insert(account_id.clone(), receipt);
+                }
+            }
+        }
+    }
+
+    fn start_epoch(&self, epoch_id: &EpochId) {
+        let mut epochs = self.epochs.write().unwrap();
+        // Another shard may have started the epoch meanwhile.
+        if epochs.current.as_ref() == Some(epoch_id) || epochs.previous.as_ref() == Some(epoch_id) {
+            return;
+        }
+        for bucket in &self.buckets {
+            // Keeps the capacity of the bucket.
+            bucket.lock().unwrap().clear();
+        }
+        epochs.previous = epochs.current.replace(epoch_id.clone());
+    }
+
+    /// Returns the `limit` accounts whose receipts took the most time to execute during the
+    /// current epoch, the other ones aggregated together.
+    pub fn top(&self, limit: usize) -> TopContractsView {
+        let epochs = self.epochs.read().unwrap();
+        let mut contracts: HashMap<AccountId, ContractExecution> = HashMap::new();
+        for bucket in &self.buckets {
+            for (account_id, execution) in bucket.lock().unwrap().iter() {
+                contracts.entry(account_id.clone()).or_default().add(execution);
+            }
+        }
+        let mut contracts: Vec<_> = contracts.into_iter().collect();
+        contracts.sort_by(|(lhs_account, lhs), (rhs_account, rhs)| {
+            rhs.time.cmp(&lhs.time).then_with(|| lhs_account.cmp(rhs_account))
+        });
+        let mut other = ContractExecution::default();
+        for (_, execution) in contracts.iter().skip(limit) {
+            other.add(execution);
+        }
+        contracts.truncate(limit);
+        TopContractsView {
+            epoch_id: epochs.current.clone(),
+            contracts: contracts
+                .into_iter()
+                .map(|(account_id, execution)| execution.to_view(Some(account_id)))
+                .collect(),
+            other: other.to_view(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use near_primitives::hash::hash;
+    use near_primitives::types::{AccountId, EpochId};
+
+    use super::ContractExecutionTracker;
+
+    #[test]
+    fn test_top_contracts() {
+        let tracker = ContractExecutionTracker::default();
+        let (alice, bob, carol): (AccountId, AccountId, AccountId) = (
+            "alice.near".parse().unwrap(),
+            "bob.near".parse().unwrap(),
+            "carol.near".parse().unwrap(),
+        );
+        let ms = Duration::from_millis;
+        let epoch1 = EpochId(hash(&[1]));
+        let epoch2 = EpochId(hash(&[2]));
+
+        tracker.record(&epoch1, 0, vec![(&alice, ms(3), 30), (&bob, ms(1), 10)].into_iter());
+        tracker.record(&epoch1, 1, vec![(&carol, ms(2), 20), (&alice, ms(3), 30)].into_iter());
+        let top = tracker.top(2);
+        assert_eq!(top.epoch_id, Some(epoch1.clone()));
+        let top_accounts: Vec<_> =
+            top.contracts.iter().map(|contract| contract.account_id.clone().unwrap()).collect();
+        assert_eq!(top_accounts, vec![alice.clone(), carol.clone()]);
+        assert_eq!(top.contracts[0].execution_time_us, 6000);
+        assert_eq!(top.contracts[0].gas_burnt, 60);
+        assert_eq!(top.contracts[0].receipts, 2);
+        assert_eq!((top.other.execution_time_us, top.other.receipts), (1000, 1));
+
+        tracker.record(&epoch2, 0, vec![(&bob, ms(1), 10)].into_iter());
+        // A late chunk of the previous epoch is ignored.
+        tracker.record(&epoch1, 1, vec![(&carol, ms(5), 50)].into_iter());
+        let top = tracker.top(2);
+        assert_eq!(top.epoch_id, Some(epoch2));
+        assert_eq!(top.contracts.len(), 1);
+        assert_eq!(top.contracts[0].account_id, Some(bob));
+        assert_eq!(top.other.receipts, 0);
+    }
+}
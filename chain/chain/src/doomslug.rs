@@ -38,6 +38,7 @@ pub enum DoomslugBlockProductionReadiness {
     ReadySince(Instant),
 }
 
+#[cfg_attr(feature = "adversarial", derive(Clone))]
 struct DoomslugTimer {
     started: Instant,
     last_endorsement_sent: Instant,
@@ -48,11 +49,13 @@ struct DoomslugTimer {
     max_delay: Duration,
 }
 
+#[cfg_attr(feature = "adversarial", derive(Clone))]
 struct DoomslugTip {
     block_hash: CryptoHash,
     height: BlockHeight,
 }
 
+#[cfg_attr(feature = "adversarial", derive(Clone))]
 struct DoomslugApprovalsTracker {
     witness: HashMap<AccountId, Approval>,
     account_id_to_stakes: HashMap<AccountId, (Balance, Balance)>,
@@ -78,6 +81,7 @@ struct DoomslugApprovalsTracker {
 /// ensures that only instances within the horizon are kept, and the user of the `Doomslug` is
 /// responsible for ensuring that only approvals for proper account_ids with valid signatures are
 /// provided.
+#[cfg_attr(feature = "adversarial", derive(Clone))]
 struct DoomslugApprovalsTrackersAtHeight {
     approval_trackers: HashMap<ApprovalInner, DoomslugApprovalsTracker>,
     last_approval_per_account: HashMap<AccountId, ApprovalInner>,
@@ -86,6 +90,7 @@ struct DoomslugApprovalsTrackersAtHeight {
 /// Contains all the logic for Doomslug, but no integration with chain or storage. The integration
 /// happens via `PersistentDoomslug` struct. The split is to simplify testing of the logic separate
 /// from the chain.
+#[cfg_attr(feature = "adversarial", derive(Clone))]
 pub struct Doomslug {
     approval_tracking: HashMap<BlockHeight, DoomslugApprovalsTrackersAtHeight>,
     /// Largest target height for which we issued an approval
@@ -104,6 +109,9 @@ pub struct Doomslug {
     /// How many approvals to have before producing a block. In production should be always `HalfStake`,
     ///    but for many tests we use `NoApprovals` to invoke more forkfulness
     threshold_mode: DoomslugThresholdMode,
+    /// Whether to stop sending skip approvals when the timer expires
+    #[cfg(feature = "adversarial")]
+    adv_disable_timeouts: bool,
 }
 
 impl DoomslugTimer {
@@ -307,6 +315,8 @@ impl Doomslug {
             },
             signer,
             threshold_mode,
+            #[cfg(feature = "adversarial")]
+            adv_disable_timeouts: false,
         }
     }
 
@@ -315,6 +325,12 @@ impl Doomslug {
         self.threshold_mode = DoomslugThresholdMode::NoApprovals
     }
 
+    /// Keeps sending endorsements, but never skips a height the block of which didn't arrive.
+    #[cfg(feature = "adversarial")]
+    pub fn adv_disable_timeouts(&mut self) {
+        self.adv_disable_timeouts = true
+    }
+
     /// Returns the `(hash, height)` of the current tip. Currently is only used by tests.
     pub fn get_tip(&self) -> (CryptoHash, BlockHeight) {
         (self.tip.block_hash, self.tip.height)
@@ -387,6 +403,11 @@ impl Doomslug {
                 self.endorsement_pending = false;
             }
 
+            #[cfg(feature = "adversarial")]
+            if self.adv_disable_timeouts {
+                break;
+            }
+
             if cur_time >= self.timer.started + skip_delay {
                 debug_assert!(!self.endorsement_pending);
 
@@ -556,12 +577,15 @@ impl Doomslug {
     /// * `target_height`     - the height for which the readiness is checked
     /// * `has_enough_chunks` - if not, we will wait for T(h' / 6) even if we have 2/3 approvals &
     ///                         have the previous block ds-final.
+    /// * `chunk_wait_extension` - how much longer than T(h' / 6) to wait if there are not enough
+    ///                         chunks.
     #[must_use]
     pub fn ready_to_produce_block(
         &mut self,
         now: Instant,
         target_height: BlockHeight,
         has_enough_chunks: bool,
+        chunk_wait_extension: Duration,
     ) -> bool {
         let hash_or_height =
             ApprovalInner::new(&self.tip.block_hash, self.tip.height, target_height);
@@ -581,7 +605,7 @@ impl Doomslug {
                                 self.timer.height.saturating_sub(self.largest_final_height),
                             ) / 6;
 
-                            now > when + delay
+                            now > when + delay + chunk_wait_extension
                         }
                     }
                 }
@@ -743,6 +767,36 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "adversarial")]
+    #[test]
+    fn test_adv_disable_timeouts() {
+        let mut ds = Doomslug::new(
+            0,
+            Duration::from_millis(400),
+            Duration::from_millis(1000),
+            Duration::from_millis(100),
+            Duration::from_millis(3000),
+            Some(Arc::new(InMemoryValidatorSigner::from_seed(
+                "test".parse().unwrap(),
+                KeyType::ED25519,
+                "test",
+            ))),
+            DoomslugThresholdMode::TwoThirds,
+        );
+        ds.adv_disable_timeouts();
+
+        let now = Clock::instant();
+        ds.set_tip(now, hash(&[1]), 1, 1);
+        // The endorsement is still sent
+        let approvals = ds.process_timer(now + Duration::from_millis(400));
+        assert_eq!(approvals.len(), 1);
+        assert_eq!(approvals[0].inner, ApprovalInner::Endorsement(hash(&[1])));
+
+        // But no height is ever skipped
+        assert_eq!(ds.process_timer(now + Duration::from_millis(100_000)), vec![]);
+        assert_eq!(ds.get_timer_height(), 2);
+    }
+
     #[test]
     fn test_doomslug_approvals() {
         let accounts: Vec<(&str, u128, u128)> =
@@ -1105,4 +1159,64 @@ mod tests {
             5
         );
     }
+
+    #[test]
+    fn test_ready_to_produce_block_with_missing_chunks() {
+        let signer = Arc::new(InMemoryValidatorSigner::from_seed(
+            "test".parse().unwrap(),
+            KeyType::ED25519,
+            "test",
+        ));
+        let stakes = vec![(
+            ApprovalStake {
+                account_id: "test".parse().unwrap(),
+                stake_this_epoch: 1,
+                stake_next_epoch: 1,
+                public_key: signer.public_key(),
+            },
+            false,
+        )];
+        let mut ds = Doomslug::new(
+            0,
+            Duration::from_millis(400),
+            Duration::from_millis(1200),
+            Duration::from_millis(100),
+            Duration::from_millis(3000),
+            Some(signer.clone()),
+            DoomslugThresholdMode::TwoThirds,
+        );
+        let now = Clock::instant();
+        ds.set_tip(now, hash(&[1]), 1, 1);
+        ds.on_approval_message(now, &Approval::new(hash(&[1]), 1, 2, &*signer), &stakes);
+
+        // With all chunks the block can be produced right away, regardless of the extension
+        assert!(ds.ready_to_produce_block(now, 2, true, Duration::ZERO));
+        assert!(ds.ready_to_produce_block(now, 2, true, Duration::from_millis(100)));
+
+        // Without them we wait for T / 6 = 200ms, plus the extension
+        assert!(!ds.ready_to_produce_block(
+            now + Duration::from_millis(200),
+            2,
+            false,
+            Duration::ZERO
+        ));
+        assert!(ds.ready_to_produce_block(
+            now + Duration::from_millis(201),
+            2,
+            false,
+            Duration::ZERO
+        ));
+        assert!(!ds.ready_to_produce_block(
+            now + Duration::from_millis(300),
+            2,
+            false,
+            Duration::from_millis(100)
+        ));
+        assert!(ds.ready_to_produce_block(
+            now + Duration::from_millis(301),
+            2,
+            false,
+            Duration::from_millis(100)
+        ));
+    }
 }
@@ -11,46 +11,53 @@ use near_primitives::block::{Approval, Tip};
 use near_primitives::errors::InvalidTxError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{MerklePath, PartialMerkleTree};
-use near_primitives::receipt::Receipt;
+use near_primitives::receipt::{Receipt, ReceiptQueueChanges};
+use near_primitives::runtime::apply_state::AppliedChunkInfo;
 use near_primitives::shard_layout::{account_id_to_shard_id, get_block_shard_uid, ShardUId};
 use near_primitives::sharding::{
-    ChunkHash, EncodedShardChunk, PartialEncodedChunk, ReceiptProof, ShardChunk, ShardChunkHeader,
-    StateSyncInfo,
+    ChunkHash, ChunkSummary, EncodedShardChunk, PartialEncodedChunk, ReceiptProof, ShardChunk,
+    ShardChunkHeader, StateSyncInfo,
 };
 use near_primitives::syncing::{
     get_num_state_parts, ReceiptProofResponse, ShardStateSyncResponseHeader, StateHeaderKey,
-    StatePartKey,
+    StatePartKey, StateSyncProgress,
 };
 use near_primitives::transaction::{
-    ExecutionOutcomeWithId, ExecutionOutcomeWithIdAndProof, SignedTransaction,
+    ExecutionOutcomeWithId, ExecutionOutcomeWithIdAndProof, ExecutionOutcomeWithProof,
+    SignedTransaction,
 };
 use near_primitives::trie_key::{trie_key_parsers, TrieKey};
 use near_primitives::types::chunk_extra::ChunkExtra;
 use near_primitives::types::{
-    AccountId, BlockExtra, BlockHeight, EpochId, GCCount, NumBlocks, ShardId, StateChanges,
-    StateChangesExt, StateChangesForSplitStates, StateChangesKinds, StateChangesKindsExt,
-    StateChangesRequest,
+    AccountId, BlockExtra, BlockHeight, BlockHeightDelta, EpochId, GCCount, NumBlocks, ShardId,
+    StateChanges, StateChangesExt, StateChangesForSplitStates, StateChangesKinds,
+    StateChangesKindsExt, StateChangesRequest,
+};
+use near_primitives::utils::{
+    get_block_shard_id, get_outcome_id_block_hash, get_outcome_id_block_hash_rev, index_to_bytes,
+    to_timestamp,
 };
-use near_primitives::utils::{get_block_shard_id, index_to_bytes, to_timestamp};
 use near_primitives::views::LightClientBlockView;
 use near_store::{
-    read_with_cache, ColBlock, ColBlockExtra, ColBlockHeader, ColBlockHeight, ColBlockInfo,
-    ColBlockMerkleTree, ColBlockMisc, ColBlockOrdinal, ColBlockPerHeight, ColBlockRefCount,
-    ColBlocksToCatchup, ColChallengedBlocks, ColChunkExtra, ColChunkHashesByHeight,
-    ColChunkPerHeightShard, ColChunks, ColEpochLightClientBlocks, ColGCCount,
-    ColHeaderHashesByHeight, ColIncomingReceipts, ColInvalidChunks, ColNextBlockHashes,
-    ColOutcomeIds, ColOutgoingReceipts, ColPartialChunks, ColProcessedBlockHeights,
-    ColReceiptIdToShardId, ColReceipts, ColState, ColStateChanges, ColStateDlInfos,
-    ColStateHeaders, ColStateParts, ColTransactionResult, ColTransactions, ColTrieChanges, DBCol,
-    KeyForStateChanges, ShardTries, Store, StoreUpdate, TrieChanges, WrappedTrieChanges,
-    CHUNK_TAIL_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY,
-    LARGEST_TARGET_HEIGHT_KEY, LATEST_KNOWN_KEY, SHOULD_COL_GC, TAIL_KEY,
+    read_with_cache, ColAppliedChunkInfo, ColBlock, ColBlockExtra, ColBlockHeader, ColBlockHeight,
+    ColBlockInfo, ColBlockMerkleTree, ColBlockMisc, ColBlockOrdinal, ColBlockPerHeight,
+    ColBlockRefCount, ColBlocksToCatchup, ColChallengedBlocks, ColChunkExtra,
+    ColChunkHashesByHeight, ColChunkPerHeightShard, ColChunkSummaries, ColChunks,
+    ColEpochLightClientBlocks, ColGCCount, ColHeaderHashesByHeight, ColIncomingReceipts,
+    ColInvalidChunks, ColNextBlockHashes, ColOutcomeIds, ColOutgoingReceipts, ColPartialChunks,
+    ColProcessedBlockHeights, ColReceiptIdToShardId, ColReceiptQueueChanges, ColReceipts, ColState,
+    ColStateChanges, ColStateDlInfos, ColStateHeaders, ColStateParts, ColStateSyncProgress,
+    ColTransactionResultForBlock, ColTransactions, ColTrieChanges, DBCol, KeyForStateChanges,
+    ShardTries, Store, StoreUpdate, TrieChanges, WrappedTrieChanges, CHUNK_TAIL_KEY,
+    FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY, LARGEST_TARGET_HEIGHT_KEY,
+    LATEST_KNOWN_KEY, OUTCOMES_PRUNED_HEIGHT_KEY, OUTCOMES_PRUNE_PROGRESS_KEY,
+    PRUNED_OUTCOME_BLOCK_HASH, SHOULD_COL_GC, TAIL_KEY,
 };
 
 use crate::types::{Block, BlockHeader, LatestKnown};
 use crate::{byzantine_assert, RuntimeAdapter};
 use near_store::db::DBCol::ColStateChangesForSplitStates;
-use near_store::db::StoreStatistics;
+use near_store::db::{StoreProperties, StoreStatistics};
 #[cfg(feature = "mock_network")]
 use std::sync::Arc;
 
@@ -358,6 +365,13 @@ pub fn option_to_not_found<T>(res: io::Result<Option<T>>, field_name: &str) -> R
     }
 }
 
+/// Progress of the outcomes pruning pass, see `ChainStore::prune_outcomes_step`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy)]
+struct OutcomesPruneProgress {
+    min_height: BlockHeight,
+    next_prefix: u8,
+}
+
 impl ChainStore {
     pub fn new(store: Store, genesis_height: BlockHeight) -> ChainStore {
         ChainStore {
@@ -546,12 +560,82 @@ impl ChainStore {
 }
 
 impl ChainStore {
-    /// Returns all outcomes generated by applying transaction or receipt with the given id.
-    pub fn get_outcomes_by_id(
+    /// Returns the outcomes of applying the transaction or receipt with the given id in every
+    /// block it was applied in, which are several blocks if it was applied on several forks.
+    pub fn get_outcomes_for_id(
         &self,
         id: &CryptoHash,
     ) -> Result<Vec<ExecutionOutcomeWithIdAndProof>, Error> {
-        Ok(self.store.get_ser(ColTransactionResult, id.as_ref())?.unwrap_or_else(|| vec![]))
+        let mut outcomes = vec![];
+        for (key, value) in self.store.iter_prefix(ColTransactionResultForBlock, id.as_ref()) {
+            let (_, block_hash) = get_outcome_id_block_hash_rev(&key).map_err(|e| e.to_string())?;
+            if block_hash == PRUNED_OUTCOME_BLOCK_HASH {
+                continue;
+            }
+            let outcome = ExecutionOutcomeWithProof::try_from_slice(&value)?;
+            outcomes.push(outcome.into_outcome_with_id_and_proof(*id, block_hash));
+        }
+        Ok(outcomes)
+    }
+
+    /// Returns the outcome of applying the transaction or receipt with the given id in the given
+    /// block.
+    pub fn get_outcome_by_id_and_block_hash(
+        &self,
+        id: &CryptoHash,
+        block_hash: &CryptoHash,
+    ) -> Result<Option<ExecutionOutcomeWithIdAndProof>, Error> {
+        let outcome: Option<ExecutionOutcomeWithProof> = self
+            .store
+            .get_ser(ColTransactionResultForBlock, &get_outcome_id_block_hash(id, block_hash))?;
+        Ok(outcome.map(|outcome| outcome.into_outcome_with_id_and_proof(*id, *block_hash)))
+    }
+
+    /// Returns the outcome of the transaction or receipt with the given id on the canonical
+    /// chain, `None` if it wasn't applied there.  A block is on the canonical chain if it is the
+    /// block with its ordinal, which is updated on reorgs.
+    pub fn get_outcome_for_canonical_chain(
+        &mut self,
+        id: &CryptoHash,
+    ) -> Result<Option<ExecutionOutcomeWithIdAndProof>, Error> {
+        for outcome in self.get_outcomes_for_id(id)? {
+            let block_ordinal = match self.get_block_merkle_tree(&outcome.block_hash) {
+                Ok(block_merkle_tree) => block_merkle_tree.size(),
+                Err(_) => continue,
+            };
+            match self.get_block_hash_from_ordinal(block_ordinal) {
+                Ok(block_hash) if block_hash == &outcome.block_hash => return Ok(Some(outcome)),
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the summary of a chunk, `None` if the chunk isn't stored.
+    pub fn get_chunk_summary(&self, chunk_hash: &ChunkHash) -> Result<Option<ChunkSummary>, Error> {
+        Ok(self.store.get_ser(ColChunkSummaries, chunk_hash.as_ref())?)
+    }
+
+    /// Returns the receipt queue changes made by the chunk of the block, `None` if the node
+    /// doesn't save them.
+    pub fn get_receipt_queue_changes(
+        &self,
+        block_hash: &CryptoHash,
+        shard_uid: &ShardUId,
+    ) -> Result<Option<ReceiptQueueChanges>, Error> {
+        Ok(self
+            .store
+            .get_ser(ColReceiptQueueChanges, &get_block_shard_uid(block_hash, shard_uid))?)
+    }
+
+    /// Returns the parameters the chunk of the shard was applied with in the block, `None` if
+    /// the block wasn't applied by this node or was applied before they were saved.
+    pub fn get_applied_chunk_info(
+        &self,
+        block_hash: &CryptoHash,
+        shard_uid: &ShardUId,
+    ) -> Result<Option<AppliedChunkInfo>, Error> {
+        Ok(self.store.get_ser(ColAppliedChunkInfo, &get_block_shard_uid(block_hash, shard_uid))?)
     }
 
     /// Returns a vector of Outcome ids for given block and shard id
@@ -566,6 +650,138 @@ impl ChainStore {
             .unwrap_or_default())
     }
 
+    /// Height below which execution outcomes may have been pruned, if outcomes pruning ever ran
+    /// on this node.
+    pub fn outcomes_pruned_height(&self) -> Result<Option<BlockHeight>, Error> {
+        Ok(self.store.get_ser(ColBlockMisc, OUTCOMES_PRUNED_HEIGHT_KEY)?)
+    }
+
+    /// Whether the outcomes with given id have been removed by outcomes pruning.
+    pub fn is_outcome_pruned(&self, id: &CryptoHash) -> Result<bool, Error> {
+        Ok(self.store.exists(
+            ColTransactionResultForBlock,
+            &get_outcome_id_block_hash(id, &PRUNED_OUTCOME_BLOCK_HASH),
+        )?)
+    }
+
+    /// Height below which outcomes are pruned when keeping `epochs` epochs of history.
+    pub fn outcomes_prune_height(
+        &self,
+        epochs: u64,
+        epoch_length: BlockHeightDelta,
+    ) -> Result<BlockHeight, Error> {
+        Ok(self.head()?.height.saturating_sub(epochs * epoch_length))
+    }
+
+    /// Deletes execution outcomes of blocks below `min_height` in one pass over the whole column.
+    /// Returns the number of deleted outcome entries. Transactions and receipts are reference
+    /// counted by the chunks which include them, so they are left to garbage collection.
+    pub fn prune_outcomes(&mut self, min_height: BlockHeight) -> Result<u64, Error> {
+        self.set_outcomes_pruned_height(min_height)?;
+        let mut pruned = 0;
+        for prefix in 0..=u8::MAX {
+            pruned += self.prune_outcomes_with_prefix(min_height, prefix)?;
+        }
+        let mut store_update = self.store.store_update();
+        store_update.delete(ColBlockMisc, OUTCOMES_PRUNE_PROGRESS_KEY);
+        store_update.commit()?;
+        Ok(pruned)
+    }
+
+    /// Makes progress on pruning outcomes of blocks below `min_height`, so that a pass over the
+    /// whole column is spread over many calls. Each call goes through the outcomes whose ids
+    /// start with the next byte. A new pass starts only once `min_height` moves past the height
+    /// of the previous one.
+    pub fn prune_outcomes_step(&mut self, min_height: BlockHeight) -> Result<(), Error> {
+        let progress: Option<OutcomesPruneProgress> =
+            self.store.get_ser(ColBlockMisc, OUTCOMES_PRUNE_PROGRESS_KEY)?;
+        let progress = match progress {
+            Some(progress) => progress,
+            None => {
+                let pruned_height = self.outcomes_pruned_height()?.unwrap_or(self.genesis_height);
+                if min_height <= pruned_height {
+                    return Ok(());
+                }
+                self.set_outcomes_pruned_height(min_height)?;
+                OutcomesPruneProgress { min_height, next_prefix: 0 }
+            }
+        };
+        self.prune_outcomes_with_prefix(progress.min_height, progress.next_prefix)?;
+        let mut store_update = self.store.store_update();
+        if progress.next_prefix == u8::MAX {
+            store_update.delete(ColBlockMisc, OUTCOMES_PRUNE_PROGRESS_KEY);
+        } else {
+            store_update.set_ser(
+                ColBlockMisc,
+                OUTCOMES_PRUNE_PROGRESS_KEY,
+                &OutcomesPruneProgress { next_prefix: progress.next_prefix + 1, ..progress },
+            )?;
+        }
+        store_update.commit()?;
+        Ok(())
+    }
+
+    fn set_outcomes_pruned_height(&self, height: BlockHeight) -> Result<(), Error> {
+        let mut store_update = self.store.store_update();
+        store_update.set_ser(ColBlockMisc, OUTCOMES_PRUNED_HEIGHT_KEY, &height)?;
+        Ok(store_update.commit()?)
+    }
+
+    fn prune_outcomes_with_prefix(
+        &mut self,
+        min_height: BlockHeight,
+        prefix: u8,
+    ) -> Result<u64, Error> {
+        let mut is_old_block = HashMap::new();
+        let mut store_update = self.store.store_update();
+        let mut pruned = 0;
+        // The keys start with the outcome id, so the outcomes of an id in all the blocks are
+        // next to each other.
+        let mut outcomes_of_id = vec![];
+        let mut keys = self.store.iter_prefix(ColTransactionResultForBlock, &[prefix]).peekable();
+        while let Some((key, _)) = keys.next() {
+            let (id, block_hash) =
+                get_outcome_id_block_hash_rev(&key).map_err(|e| e.to_string())?;
+            let is_old = match is_old_block.entry(block_hash) {
+                Entry::Occupied(entry) => *entry.get(),
+                Entry::Vacant(entry) => {
+                    // Headers of unknown blocks, including the marker of pruned outcomes, are
+                    // treated as old.
+                    let header: Option<BlockHeader> =
+                        self.store.get_ser(ColBlockHeader, block_hash.as_ref())?;
+                    *entry.insert(header.map_or(true, |header| header.height() < min_height))
+                }
+            };
+            outcomes_of_id.push((key, block_hash, is_old));
+            if keys.peek().map_or(false, |(next_key, _)| next_key[..32] == key[..32]) {
+                continue;
+            }
+            let outcomes = std::mem::take(&mut outcomes_of_id);
+            if outcomes.iter().all(|(_, block_hash, _)| block_hash == &PRUNED_OUTCOME_BLOCK_HASH)
+                || outcomes.iter().all(|(_, _, is_old)| !is_old)
+            {
+                continue;
+            }
+            for (key, _, is_old) in &outcomes {
+                if *is_old {
+                    store_update.delete(ColTransactionResultForBlock, key);
+                }
+            }
+            if outcomes.iter().all(|(_, _, is_old)| *is_old) {
+                // A marker is left behind so that queries can tell pruned outcomes from unknown
+                // ones.
+                store_update.set(
+                    ColTransactionResultForBlock,
+                    &get_outcome_id_block_hash(&id, &PRUNED_OUTCOME_BLOCK_HASH),
+                    &[],
+                );
+                pruned += 1;
+            }
+        }
+        store_update.commit()?;
+        Ok(pruned)
+    }
+
     /// Returns a hashmap of epoch id -> set of all blocks got for current (height, epoch_id)
     pub fn get_all_block_hashes_by_height(
         &mut self,
@@ -613,6 +829,15 @@ impl ChainStore {
         }
     }
 
+    pub fn get_state_sync_progress(
+        &self,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+    ) -> Result<Option<StateSyncProgress>, Error> {
+        let key = StateHeaderKey(shard_id, sync_hash).try_to_vec()?;
+        Ok(self.store.get_ser(ColStateSyncProgress, &key)?)
+    }
+
     /// Returns latest known height and time it was seen.
     pub fn get_latest_known(&mut self) -> Result<LatestKnown, Error> {
         if self.latest_known.is_none() {
@@ -768,6 +993,10 @@ impl ChainStore {
     pub fn get_store_statistics(&self) -> Option<StoreStatistics> {
         self.store.get_store_statistics()
     }
+
+    pub fn get_store_properties(&self) -> Option<StoreProperties> {
+        self.store.get_store_properties()
+    }
 }
 
 impl ChainStoreAccess for ChainStore {
@@ -2008,6 +2237,7 @@ impl<'a> ChainStoreUpdate<'a> {
                 // 2. Delete chunk_hash-indexed data
                 let chunk_header_hash = chunk_hash.clone().into();
                 self.gc_col(ColChunks, &chunk_header_hash);
+                self.gc_col(ColChunkSummaries, &chunk_header_hash);
                 self.gc_col(ColPartialChunks, &chunk_header_hash);
                 self.gc_col(ColInvalidChunks, &chunk_header_hash);
             }
@@ -2077,11 +2307,10 @@ impl<'a> ChainStoreUpdate<'a> {
                                 tries
                                     .revert_insertions(&trie_changes, shard_uid, &mut store_update)
                                     .map(|_| {
-                                        self.gc_col(
-                                            ColTrieChanges,
+                                        self.finish_trie_changes_unit(
+                                            &mut store_update,
                                             &get_block_shard_uid(&block_hash, &shard_uid),
                                         );
-                                        self.inc_gc_col_state();
                                     })
                                     .map_err(|err| ErrorKind::Other(err.to_string()))
                             })
@@ -2097,11 +2326,10 @@ impl<'a> ChainStoreUpdate<'a> {
                                 tries
                                     .apply_deletions(&trie_changes, shard_uid, &mut store_update)
                                     .map(|_| {
-                                        self.gc_col(
-                                            ColTrieChanges,
+                                        self.finish_trie_changes_unit(
+                                            &mut store_update,
                                             &get_block_shard_uid(&block_hash, &shard_uid),
                                         );
-                                        self.inc_gc_col_state();
                                     })
                                     .map_err(|err| ErrorKind::Other(err.to_string()))
                             })
@@ -2118,6 +2346,7 @@ impl<'a> ChainStoreUpdate<'a> {
                 }
             }
         }
+        self.merge(store_update);
 
         let block = self
             .get_block(&block_hash)
@@ -2150,6 +2379,8 @@ impl<'a> ChainStoreUpdate<'a> {
         for shard_uid in self.get_shard_uids_to_gc(runtime_adapter, &block_hash) {
             let block_shard_uid = get_block_shard_uid(&block_hash, &shard_uid);
             self.gc_col(ColChunkExtra, &block_shard_uid);
+            self.gc_col(ColReceiptQueueChanges, &block_shard_uid);
+            self.gc_col(ColAppliedChunkInfo, &block_shard_uid);
         }
 
         // 3. Delete block_hash-indexed data
@@ -2201,10 +2432,19 @@ impl<'a> ChainStoreUpdate<'a> {
                 // Chunks deleted separately
             }
         };
-        self.merge(store_update);
         Ok(())
     }
 
+    /// Deletes the trie changes of a shard in the same unit as the state changes made from them.
+    /// Each unit is committed at once by `StoreUpdate::commit_chunked`, so garbage collection of
+    /// a block which was interrupted applies the trie changes of every shard exactly once.
+    fn finish_trie_changes_unit(&mut self, store_update: &mut StoreUpdate, key: &Vec<u8>) {
+        store_update.delete(ColTrieChanges, key);
+        store_update.finish_unit();
+        self.inc_gc(ColTrieChanges);
+        self.inc_gc_col_state();
+    }
+
     pub fn inc_gc_col_state(&mut self) {
         self.inc_gc(ColState);
     }
@@ -2254,6 +2494,8 @@ impl<'a> ChainStoreUpdate<'a> {
             let key = StatePartKey(sync_hash, shard_id, part_id).try_to_vec()?;
             self.gc_col(ColStateParts, &key);
         }
+        let key = StateHeaderKey(shard_id, sync_hash).try_to_vec()?;
+        self.gc_col(ColStateSyncProgress, &key);
         Ok(())
     }
 
@@ -2293,7 +2535,6 @@ impl<'a> ChainStoreUpdate<'a> {
 
     pub fn gc_outcomes(&mut self, block: &Block) -> Result<(), Error> {
         let block_hash = block.hash();
-        let mut store_update = self.store().store_update();
         for chunk_header in
             block.chunks().iter().filter(|h| h.height_included() == block.header().height())
         {
@@ -2301,21 +2542,13 @@ impl<'a> ChainStoreUpdate<'a> {
             let outcome_ids =
                 self.chain_store.get_outcomes_by_block_hash_and_shard_id(block_hash, shard_id)?;
             for outcome_id in outcome_ids {
-                let mut outcomes_with_id = self.chain_store.get_outcomes_by_id(&outcome_id)?;
-                outcomes_with_id.retain(|outcome| &outcome.block_hash != block_hash);
-                if outcomes_with_id.is_empty() {
-                    self.gc_col(ColTransactionResult, &outcome_id.as_ref().into());
-                } else {
-                    store_update.set_ser(
-                        ColTransactionResult,
-                        outcome_id.as_ref(),
-                        &outcomes_with_id,
-                    )?;
-                }
+                self.gc_col(
+                    ColTransactionResultForBlock,
+                    &get_outcome_id_block_hash(&outcome_id, block_hash),
+                );
             }
             self.gc_col(ColOutcomeIds, &get_block_shard_id(block_hash, shard_id));
         }
-        self.merge(store_update);
         Ok(())
     }
 
@@ -2383,6 +2616,15 @@ impl<'a> ChainStoreUpdate<'a> {
                 store_update.delete(col, key);
                 self.chain_store.chunks.pop(key);
             }
+            DBCol::ColChunkSummaries => {
+                store_update.delete(col, key);
+            }
+            DBCol::ColReceiptQueueChanges => {
+                store_update.delete(col, key);
+            }
+            DBCol::ColAppliedChunkInfo => {
+                store_update.delete(col, key);
+            }
             DBCol::ColChunkExtra => {
                 store_update.delete(col, key);
                 self.chain_store.chunk_extras.pop(key);
@@ -2401,6 +2643,9 @@ impl<'a> ChainStoreUpdate<'a> {
             DBCol::ColStateParts => {
                 store_update.delete(col, key);
             }
+            DBCol::ColStateSyncProgress => {
+                store_update.delete(col, key);
+            }
             DBCol::ColState => {
                 panic!("Actual gc happens elsewhere, call inc_gc_col_state to increase gc count");
             }
@@ -2410,7 +2655,7 @@ impl<'a> ChainStoreUpdate<'a> {
             DBCol::ColBlockPerHeight => {
                 panic!("Must use gc_col_glock_per_height method to gc ColBlockPerHeight");
             }
-            DBCol::ColTransactionResult => {
+            DBCol::ColTransactionResultForBlock => {
                 store_update.delete(col, key);
             }
             DBCol::ColOutcomeIds => {
@@ -2447,8 +2692,10 @@ impl<'a> ChainStoreUpdate<'a> {
             | DBCol::_ColNextBlockWithNewChunk
             | DBCol::_ColLastBlockWithNewChunk
             | DBCol::_ColTransactionRefCount
+            | DBCol::_ColTransactionResult
             | DBCol::ColStateChangesForSplitStates
-            | DBCol::ColCachedContractCode => {
+            | DBCol::ColCachedContractCode
+            | DBCol::ColEpochHeightToEpochId => {
                 unreachable!();
             }
         }
@@ -2551,7 +2798,7 @@ impl<'a> ChainStoreUpdate<'a> {
             let outcome_ids =
                 source_store.get_outcomes_by_block_hash_and_shard_id(block_hash, shard_id)?;
             for id in outcome_ids.iter() {
-                let existing_outcomes = source_store.get_outcomes_by_id(id)?;
+                let existing_outcomes = source_store.get_outcomes_for_id(id)?;
                 chain_store_update.chain_store_cache_update.outcomes.insert(*id, existing_outcomes);
             }
             chain_store_update
@@ -2588,6 +2835,11 @@ impl<'a> ChainStoreUpdate<'a> {
 
     fn finalize(&mut self) -> Result<StoreUpdate, Error> {
         let mut store_update = self.store().store_update();
+        // The merged updates go first: the units declared by garbage collection must be
+        // committed before the heads and tails, which are written with the last unit.
+        for other in self.store_updates.drain(..) {
+            store_update.merge(other);
+        }
         Self::write_col_misc(&mut store_update, HEAD_KEY, &mut self.head)?;
         Self::write_col_misc(&mut store_update, TAIL_KEY, &mut self.tail)?;
         Self::write_col_misc(&mut store_update, CHUNK_TAIL_KEY, &mut self.chunk_tail)?;
@@ -2699,6 +2951,11 @@ impl<'a> ChainStoreUpdate<'a> {
             }
 
             store_update.set_ser(ColChunks, chunk_hash.as_ref(), chunk)?;
+            store_update.set_ser(
+                ColChunkSummaries,
+                chunk_hash.as_ref(),
+                &ChunkSummary::from(chunk),
+            )?;
         }
         for (height, hash_set) in chunk_hashes_by_height {
             store_update.set_ser(ColChunkHashesByHeight, &index_to_bytes(height), &hash_set)?;
@@ -2743,10 +3000,14 @@ impl<'a> ChainStoreUpdate<'a> {
                 receipt,
             )?;
         }
-        for (hash, outcomes) in self.chain_store_cache_update.outcomes.iter() {
-            let mut existing_outcomes = self.chain_store.get_outcomes_by_id(hash)?;
-            existing_outcomes.extend_from_slice(outcomes);
-            store_update.set_ser(ColTransactionResult, hash.as_ref(), &existing_outcomes)?;
+        for (id, outcomes) in self.chain_store_cache_update.outcomes.iter() {
+            for outcome in outcomes {
+                store_update.set_ser(
+                    ColTransactionResultForBlock,
+                    &get_outcome_id_block_hash(id, &outcome.block_hash),
+                    &ExecutionOutcomeWithProof::from(outcome.clone()),
+                )?;
+            }
         }
         for ((block_hash, shard_id), ids) in self.chain_store_cache_update.outcome_ids.iter() {
             store_update.set_ser(
@@ -2880,15 +3141,12 @@ impl<'a> ChainStoreUpdate<'a> {
                 &gc_count,
             )?;
         }
-        for other in self.store_updates.drain(..) {
-            store_update.merge(other);
-        }
         Ok(store_update)
     }
 
     pub fn commit(mut self) -> Result<(), Error> {
         let store_update = self.finalize()?;
-        store_update.commit()?;
+        store_update.commit_chunked()?;
         let ChainStoreCacheUpdate {
             blocks,
             headers,
@@ -3073,9 +3331,13 @@ mod tests {
     use near_primitives::epoch_manager::block_info::BlockInfo;
     use near_primitives::errors::InvalidTxError;
     use near_primitives::hash::hash;
+    use near_primitives::runtime::apply_state::AppliedChunkInfo;
+    use near_primitives::shard_layout::{get_block_shard_uid, ShardUId};
+    use near_primitives::transaction::ExecutionOutcomeWithId;
     use near_primitives::types::{BlockHeight, EpochId, GCCount, NumBlocks};
     use near_primitives::utils::index_to_bytes;
     use near_primitives::validator_signer::InMemoryValidatorSigner;
+    use near_primitives::version::PROTOCOL_VERSION;
     use near_store::test_utils::create_test_store;
     use near_store::DBCol;
 
@@ -3312,6 +3574,19 @@ mod tests {
         assert_ne!(epoch_id_to_hash, epoch_id_to_hash1);
     }
 
+    fn applied_chunk_info(height: BlockHeight) -> AppliedChunkInfo {
+        AppliedChunkInfo {
+            block_height: height,
+            gas_price: 100,
+            gas_limit: Some(1000),
+            random_seed: hash(&[height as u8]),
+            protocol_version: PROTOCOL_VERSION,
+            is_new_chunk: true,
+            is_first_block_of_version: false,
+            is_first_block_with_chunk_of_version: false,
+        }
+    }
+
     /// Test that garbage collection works properly. The blocks behind gc head should be garbage
     /// collected while the blocks that are ahead of it should not.
     #[test]
@@ -3344,6 +3619,15 @@ mod tests {
                 .insert(i, Some(*block.header().hash()));
             store_update.save_next_block_hash(prev_block.hash(), *block.hash());
             store_update.commit().unwrap();
+            let mut store_update = chain.store().store().store_update();
+            store_update
+                .set_ser(
+                    DBCol::ColAppliedChunkInfo,
+                    &get_block_shard_uid(block.hash(), &ShardUId::single_shard()),
+                    &applied_chunk_info(i),
+                )
+                .unwrap();
+            store_update.commit().unwrap();
 
             prev_block = block.clone();
         }
@@ -3355,13 +3639,19 @@ mod tests {
         // epoch didn't change so no data is garbage collected.
         for i in 0..15 {
             println!("height = {} hash = {}", i, blocks[i].hash());
+            let applied_chunk_info = chain
+                .store()
+                .get_applied_chunk_info(blocks[i].hash(), &ShardUId::single_shard())
+                .unwrap();
             if i < 8 {
+                assert_eq!(applied_chunk_info, None);
                 assert!(chain.get_block(blocks[i].hash()).is_err());
                 assert!(chain
                     .mut_store()
                     .get_all_block_hashes_by_height(i as BlockHeight)
                     .is_err());
             } else {
+                assert_eq!(applied_chunk_info, Some(self::applied_chunk_info(i as BlockHeight)));
                 assert!(chain.get_block(blocks[i].hash()).is_ok());
                 assert!(chain.mut_store().get_all_block_hashes_by_height(i as BlockHeight).is_ok());
             }
@@ -3382,6 +3672,8 @@ mod tests {
             DBCol::ColBlockRefCount,
             DBCol::ColOutcomeIds,
             DBCol::ColChunkExtra,
+            DBCol::ColReceiptQueueChanges,
+            DBCol::ColAppliedChunkInfo,
         ];
         for col in DBCol::iter() {
             println!("current column is {:?}", col);
@@ -3415,6 +3707,67 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_prune_outcomes() {
+        let mut chain = get_chain_with_epoch_length(1);
+        let genesis = chain.get_block_by_height(0).unwrap().clone();
+        let signer = Arc::new(InMemoryValidatorSigner::from_seed(
+            "test1".parse().unwrap(),
+            KeyType::ED25519,
+            "test1",
+        ));
+        let mut prev_block = genesis;
+        let mut blocks = vec![prev_block.clone()];
+        for i in 1..10 {
+            let block = Block::empty_with_height(&prev_block, i, &*signer.clone());
+            blocks.push(block.clone());
+            let mut store_update = chain.mut_store().store_update();
+            store_update.save_block(block.clone());
+            store_update.save_block_header(block.header().clone()).unwrap();
+            store_update.save_head(&Tip::from_header(block.header())).unwrap();
+            store_update.commit().unwrap();
+            prev_block = block;
+        }
+
+        let old_id = hash(b"old");
+        let new_id = hash(b"new");
+        // Executed on two forks, one of which is old.
+        let forked_id = hash(b"forked");
+        let mut store_update = chain.mut_store().store_update();
+        for (block, id) in [
+            (&blocks[2], old_id),
+            (&blocks[8], new_id),
+            (&blocks[3], forked_id),
+            (&blocks[7], forked_id),
+        ] {
+            store_update.save_outcomes_with_proofs(
+                block.hash(),
+                0,
+                vec![ExecutionOutcomeWithId { id, outcome: Default::default() }],
+                vec![vec![]],
+            );
+        }
+        store_update.commit().unwrap();
+
+        let store = chain.mut_store();
+        let min_height = store.outcomes_prune_height(5, 1).unwrap();
+        assert_eq!(min_height, 4);
+        assert_eq!(store.prune_outcomes(min_height).unwrap(), 1);
+        assert_eq!(store.outcomes_pruned_height().unwrap(), Some(4));
+        assert!(store.get_outcomes_for_id(&old_id).unwrap().is_empty());
+        assert!(store.is_outcome_pruned(&old_id).unwrap());
+        assert_eq!(store.get_outcomes_for_id(&new_id).unwrap().len(), 1);
+        assert!(!store.is_outcome_pruned(&new_id).unwrap());
+        let forked_outcomes = store.get_outcomes_for_id(&forked_id).unwrap();
+        assert_eq!(forked_outcomes.len(), 1);
+        assert_eq!(&forked_outcomes[0].block_hash, blocks[7].hash());
+        assert!(!store.is_outcome_pruned(&forked_id).unwrap());
+        assert!(!store.is_outcome_pruned(&hash(b"unknown")).unwrap());
+        // Pruned markers are left alone by later passes.
+        assert_eq!(store.prune_outcomes(min_height).unwrap(), 0);
+        assert!(store.is_outcome_pruned(&old_id).unwrap());
+    }
+
     #[test]
     fn test_clear_old_data_fixed_height() {
         let mut chain = get_chain();
@@ -15,11 +15,14 @@ use near_primitives::epoch_manager::AGGREGATOR_KEY;
 use near_primitives::hash::CryptoHash;
 use near_primitives::sharding::{ChunkHash, ShardChunk, StateSyncInfo};
 use near_primitives::syncing::{ShardStateSyncResponseHeader, StateHeaderKey, StatePartKey};
-use near_primitives::transaction::ExecutionOutcomeWithIdAndProof;
+use near_primitives::transaction::ExecutionOutcomeWithProof;
 use near_primitives::types::chunk_extra::ChunkExtra;
 use near_primitives::types::{AccountId, BlockHeight, EpochId, GCCount};
-use near_primitives::utils::get_block_shard_id_rev;
-use near_store::{decode_value_with_rc, DBCol, Store, TrieChanges, SHOULD_COL_GC, SKIP_COL_GC};
+use near_primitives::utils::{get_block_shard_id_rev, get_outcome_id_block_hash_rev};
+use near_store::{
+    decode_value_with_rc, DBCol, Store, TrieChanges, PRUNED_OUTCOME_BLOCK_HASH, SHOULD_COL_GC,
+    SKIP_COL_GC,
+};
 use validate::StoreValidatorError;
 
 use crate::RuntimeAdapter;
@@ -248,17 +251,19 @@ impl StoreValidator {
                     // Block which can be indexed by Outcome block_hash exists
                     self.check(&validate::outcome_id_block_exists, &block_hash, &outcome_ids, col);
                 }
-                DBCol::ColTransactionResult => {
-                    let outcome_id = CryptoHash::try_from_slice(key_ref)?;
-                    let outcomes =
-                        <Vec<ExecutionOutcomeWithIdAndProof>>::try_from_slice(value_ref)?;
-                    // Outcome is reachable in ColOutcomesByBlockHash
-                    self.check(
-                        &validate::outcome_indexed_by_block_hash,
-                        &outcome_id,
-                        &outcomes,
-                        col,
-                    );
+                DBCol::ColTransactionResultForBlock => {
+                    let (outcome_id, block_hash) = get_outcome_id_block_hash_rev(key_ref)?;
+                    // Markers of pruned outcomes have no block
+                    if block_hash != PRUNED_OUTCOME_BLOCK_HASH {
+                        ExecutionOutcomeWithProof::try_from_slice(value_ref)?;
+                        // Outcome is reachable in ColOutcomesByBlockHash
+                        self.check(
+                            &validate::outcome_indexed_by_block_hash,
+                            &outcome_id,
+                            &block_hash,
+                            col,
+                        );
+                    }
                 }
                 DBCol::ColStateDlInfos => {
                     let block_hash = CryptoHash::try_from(key_ref)?;
@@ -37,8 +37,9 @@ use near_primitives::types::{
 use near_primitives::validator_signer::InMemoryValidatorSigner;
 use near_primitives::version::{ProtocolVersion, PROTOCOL_VERSION};
 use near_primitives::views::{
-    AccessKeyInfoView, AccessKeyList, CallResult, ContractCodeView, EpochValidatorInfo,
-    QueryRequest, QueryResponse, QueryResponseKind, ViewStateResult,
+    AccessKeyInfoView, AccessKeyList, CallResult, ContractCodeView, EpochInfoView,
+    EpochValidatorInfo, ProtocolUpgradeStatusView, QueryRequest, QueryResponse, QueryResponseKind,
+    UnstakeScheduleView, ValidatorProductionStatsView, ViewStateResult,
 };
 use near_store::test_utils::create_test_store;
 use near_store::{
@@ -771,6 +772,8 @@ impl RuntimeAdapter for KeyValueRuntime {
                             output_data_receivers: vec![],
                             input_data_ids: vec![],
                             actions: vec![Action::Transfer(TransferAction { deposit: amount })],
+                            priority_fee: None,
+                            depth: None,
                         }),
                     };
                     let receipt_hash = receipt.get_hash();
@@ -915,10 +918,7 @@ impl RuntimeAdapter for KeyValueRuntime {
                 block_hash: *block_hash,
             }),
             QueryRequest::CallFunction { .. } => Ok(QueryResponse {
-                kind: QueryResponseKind::CallResult(CallResult {
-                    result: Default::default(),
-                    logs: Default::default(),
-                }),
+                kind: QueryResponseKind::CallResult(CallResult::default()),
                 block_height,
                 block_hash: *block_hash,
             }),
@@ -1098,6 +1098,55 @@ impl RuntimeAdapter for KeyValueRuntime {
         })
     }
 
+    fn get_validator_production_stats(
+        &self,
+        _last_block_hash: &CryptoHash,
+        account_id: &AccountId,
+    ) -> Result<ValidatorProductionStatsView, Error> {
+        Ok(ValidatorProductionStatsView { account_id: account_id.clone(), epochs: vec![] })
+    }
+
+    fn get_unstake_schedule(
+        &self,
+        _last_block_hash: &CryptoHash,
+        account_id: &AccountId,
+    ) -> Result<UnstakeScheduleView, Error> {
+        Ok(UnstakeScheduleView {
+            account_id: account_id.clone(),
+            epoch_height: 0,
+            locked: 0,
+            unstaking: 0,
+            unlocks: vec![],
+        })
+    }
+
+    fn get_protocol_upgrade_status(
+        &self,
+        _last_block_hash: &CryptoHash,
+    ) -> Result<ProtocolUpgradeStatusView, Error> {
+        Ok(ProtocolUpgradeStatusView {
+            epoch_height: 0,
+            protocol_version: PROTOCOL_VERSION,
+            next_epoch_protocol_version: PROTOCOL_VERSION,
+            voted_protocol_version: None,
+            voted_stake: 0,
+            threshold_stake: 0,
+            votes: vec![],
+            scheduled_upgrade: None,
+        })
+    }
+
+    fn get_epoch_info_view(&self, epoch_id: &EpochId) -> Result<EpochInfoView, Error> {
+        Err(ErrorKind::EpochOutOfBounds(epoch_id.clone()).into())
+    }
+
+    fn get_epoch_ids_from_epoch_height(
+        &self,
+        _epoch_height: EpochHeight,
+    ) -> Result<Vec<EpochId>, Error> {
+        Ok(vec![])
+    }
+
     fn compare_epoch_id(
         &self,
         epoch_id: &EpochId,
@@ -170,7 +170,7 @@ fn one_iter(
         // 4. Produce blocks
         'outer: for (bp_ord, ds) in doomslugs.iter_mut().enumerate() {
             for target_height in (ds.get_tip().1 + 1)..=ds.get_largest_height_crossing_threshold() {
-                if ds.ready_to_produce_block(now, target_height, true) {
+                if ds.ready_to_produce_block(now, target_height, true, Duration::ZERO) {
                     let num_blocks_to_produce = if bp_ord < 3 { 2 } else { 1 };
 
                     for block_ord in 0..num_blocks_to_produce {
@@ -31,9 +31,13 @@ use near_primitives::version::{
     ProtocolVersion, MIN_GAS_PRICE_NEP_92, MIN_GAS_PRICE_NEP_92_FIX, MIN_PROTOCOL_VERSION_NEP_92,
     MIN_PROTOCOL_VERSION_NEP_92_FIX,
 };
-use near_primitives::views::{EpochValidatorInfo, QueryRequest, QueryResponse};
+use near_primitives::views::{
+    EpochInfoView, EpochValidatorInfo, ProtocolUpgradeStatusView, QueryRequest, QueryResponse,
+    UnstakeScheduleView, ValidatorProductionStatsView,
+};
 use near_store::{PartialStorage, ShardTries, Store, StoreUpdate, Trie, WrappedTrieChanges};
 
+use crate::contract_execution::ContractExecutionTracker;
 use crate::DoomslugThresholdMode;
 use near_primitives::epoch_manager::ShardConfig;
 use near_primitives::shard_layout::{ShardLayout, ShardUId};
@@ -261,6 +265,11 @@ pub trait RuntimeAdapter: Send + Sync {
 
     fn get_tries(&self) -> ShardTries;
 
+    /// Execution time of the contracts during the current epoch, if it is tracked.
+    fn contract_execution(&self) -> Option<&ContractExecutionTracker> {
+        None
+    }
+
     fn get_store(&self) -> Store;
 
     /// Returns trie. Since shard layout may change from epoch to epoch, `shard_id` itself is
@@ -680,6 +689,37 @@ pub trait RuntimeAdapter: Send + Sync {
         epoch_id: ValidatorInfoIdentifier,
     ) -> Result<EpochValidatorInfo, Error>;
 
+    /// Block and chunk production of `account_id` at each of its assignments in the epoch of
+    /// `last_block_hash` up to that block, and in the previous epoch.
+    fn get_validator_production_stats(
+        &self,
+        last_block_hash: &CryptoHash,
+        account_id: &AccountId,
+    ) -> Result<ValidatorProductionStatsView, Error>;
+
+    /// Stake locked by `account_id` as of `last_block_hash` and when the part of it being
+    /// unstaked is returned.
+    fn get_unstake_schedule(
+        &self,
+        last_block_hash: &CryptoHash,
+        account_id: &AccountId,
+    ) -> Result<UnstakeScheduleView, Error>;
+
+    /// Votes for the next protocol version as of `last_block_hash` and the next protocol upgrade.
+    fn get_protocol_upgrade_status(
+        &self,
+        last_block_hash: &CryptoHash,
+    ) -> Result<ProtocolUpgradeStatusView, Error>;
+
+    /// Full information about the given epoch.
+    fn get_epoch_info_view(&self, epoch_id: &EpochId) -> Result<EpochInfoView, Error>;
+
+    /// Ids of the known epochs with the given height, on any fork.
+    fn get_epoch_ids_from_epoch_height(
+        &self,
+        epoch_height: EpochHeight,
+    ) -> Result<Vec<EpochId>, Error>;
+
     /// Get the part of the state from given state root.
     /// `block_hash` is a block whose `prev_state_root` is `state_root`
     fn obtain_state_part(
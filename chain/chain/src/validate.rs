@@ -1,24 +1,26 @@
 use std::collections::HashMap;
 
-use borsh::BorshDeserialize;
+use borsh::{BorshDeserialize, BorshSerialize};
 
 use near_crypto::PublicKey;
 use near_primitives::block::{Block, BlockHeader};
 use near_primitives::challenge::{
     BlockDoubleSign, Challenge, ChallengeBody, ChallengesResult, ChunkProofs, ChunkState,
-    MaybeEncodedShardChunk,
+    ChunkStateReceipts, ChunkStateV2, MaybeEncodedShardChunk, MAX_CHUNK_STATE_CHALLENGE_SIZE,
 };
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::merklize;
+use near_primitives::receipt::Receipt;
+use near_primitives::receipt_proof::verify_receipts_proof;
 use near_primitives::sharding::{
     ShardChunk, ShardChunkHeader, ShardChunkHeaderV1, ShardChunkHeaderV2, ShardChunkHeaderV3,
 };
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::chunk_extra::ChunkExtra;
-use near_primitives::types::validator_stake::ValidatorStakeIter;
-use near_primitives::types::{AccountId, BlockHeight, EpochId, Nonce};
+use near_primitives::types::{AccountId, Balance, BlockHeight, EpochId, Nonce};
 use near_store::PartialStorage;
 
+use crate::chain::{collect_receipts, shuffle_receipt_proofs};
 use crate::types::ApplyTransactionResult;
 use crate::{byzantine_assert, Chain};
 use crate::{ChainStore, Error, ErrorKind, RuntimeAdapter};
@@ -306,10 +308,85 @@ fn validate_chunk_proofs_challenge(
     return Err(ErrorKind::MaliciousChallenge.into());
 }
 
+/// Checks the blocks of a `ChunkStateV2` challenge with the receipts for `prev_chunk_header`,
+/// which is included in the block with `prev_block_header`.
+///
+/// Returns the receipts the chunk was applied with, in the order they were applied, and the gas
+/// price they were applied with.
+pub fn validate_chunk_state_receipts(
+    prev_block_header: &BlockHeader,
+    prev_chunk_header: &ShardChunkHeader,
+    blocks: &[ChunkStateReceipts],
+) -> Result<(Vec<Receipt>, Balance), Error> {
+    let shard_id = prev_chunk_header.shard_id();
+    let mut receipts = vec![];
+    let mut gas_price = None;
+    let mut expected_hash = *prev_block_header.hash();
+    for (i, block) in blocks.iter().enumerate() {
+        let header = BlockHeader::try_from_slice(&block.block_header)?;
+        if header.hash() != &expected_hash
+            || Block::compute_chunk_headers_root(&block.chunk_headers).0
+                != *header.chunk_headers_root()
+        {
+            return Err(ErrorKind::MaliciousChallenge.into());
+        }
+        expected_hash = *header.prev_hash();
+        if i == 1 {
+            gas_price = Some(header.gas_price());
+        }
+
+        // The first block includes the previous chunk, the last one includes the chunk before it
+        // and there are no chunks of the shard in between.
+        let chunk_header =
+            block.chunk_headers.get(shard_id as usize).ok_or(ErrorKind::MaliciousChallenge)?;
+        let is_last = i > 0 && i + 1 == blocks.len();
+        let is_new_chunk = chunk_header.height_included() == header.height();
+        if is_new_chunk != (i == 0 || is_last) || (i == 0 && chunk_header != prev_chunk_header) {
+            return Err(ErrorKind::MaliciousChallenge.into());
+        }
+        if is_last {
+            if !block.receipt_proofs.is_empty() {
+                return Err(ErrorKind::MaliciousChallenge.into());
+            }
+            break;
+        }
+
+        // Every new chunk of the block sends receipts to every shard, even if there are none.
+        let senders: Vec<_> = block
+            .chunk_headers
+            .iter()
+            .filter(|chunk_header| chunk_header.height_included() == header.height())
+            .collect();
+        if senders.len() != block.receipt_proofs.len() {
+            return Err(ErrorKind::MaliciousChallenge.into());
+        }
+        for (sender, proof) in senders.into_iter().zip(&block.receipt_proofs) {
+            verify_receipts_proof(
+                proof,
+                &sender.outgoing_receipts_root(),
+                sender.shard_id(),
+                shard_id,
+            )
+            .map_err(|_| Error::from(ErrorKind::MaliciousChallenge))?;
+        }
+        let mut receipt_proofs = block.receipt_proofs.clone();
+        shuffle_receipt_proofs(&mut receipt_proofs, header.hash());
+        receipts.extend(collect_receipts(&receipt_proofs));
+    }
+    // Without the last block it's unknown whether all the receipts are there.
+    match gas_price {
+        Some(gas_price) if blocks.len() > 1 => Ok((receipts, gas_price)),
+        _ => Err(ErrorKind::MaliciousChallenge.into()),
+    }
+}
+
 fn validate_chunk_state_challenge(
     runtime_adapter: &dyn RuntimeAdapter,
-    chunk_state: &ChunkState,
+    chunk_state: &ChunkStateV2,
 ) -> Result<(CryptoHash, Vec<AccountId>), Error> {
+    if chunk_state.try_to_vec()?.len() > MAX_CHUNK_STATE_CHALLENGE_SIZE {
+        return Err(ErrorKind::InvalidChallenge.into());
+    }
     let prev_block_header = BlockHeader::try_from_slice(&chunk_state.prev_block_header)?;
     let block_header = BlockHeader::try_from_slice(&chunk_state.block_header)?;
 
@@ -321,7 +398,8 @@ fn validate_chunk_state_challenge(
         &prev_chunk_header,
         prev_block_header.chunk_headers_root(),
         &chunk_state.prev_merkle_proof,
-    ) {
+    ) || !validate_chunk_proofs(&chunk_state.prev_chunk, runtime_adapter)?
+    {
         return Err(ErrorKind::MaliciousChallenge.into());
     }
 
@@ -332,34 +410,45 @@ fn validate_chunk_state_challenge(
         &chunk_state.chunk_header,
         block_header.chunk_headers_root(),
         &chunk_state.merkle_proof,
-    ) {
+    ) || block_header.prev_hash() != prev_block_header.hash()
+        || chunk_state.chunk_header.shard_id() != prev_chunk_header.shard_id()
+        || chunk_state.chunk_header.height_included() != block_header.height()
+    {
         return Err(ErrorKind::MaliciousChallenge.into());
     }
+    let (receipts, gas_price) = validate_chunk_state_receipts(
+        &prev_block_header,
+        &prev_chunk_header,
+        &chunk_state.prev_chunk_receipts,
+    )?;
 
-    // Apply state transition and check that the result state and other data doesn't match.
+    // Apply state transition the same way as the previous chunk was applied in the previous
+    // block and check that the result doesn't match the claims of the chunk.
     let partial_storage = PartialStorage { nodes: chunk_state.partial_state.clone() };
     let result = runtime_adapter
         .check_state_transition(
             partial_storage,
             prev_chunk_header.shard_id(),
             &prev_chunk_header.prev_state_root(),
-            block_header.height(),
-            block_header.raw_timestamp(),
-            block_header.prev_hash(),
-            block_header.hash(),
-            chunk_state.prev_chunk.receipts(),
+            prev_block_header.height(),
+            prev_block_header.raw_timestamp(),
+            prev_block_header.prev_hash(),
+            prev_block_header.hash(),
+            &receipts,
             chunk_state.prev_chunk.transactions(),
-            ValidatorStakeIter::empty(),
-            prev_block_header.gas_price(),
+            prev_chunk_header.validator_proposals(),
+            gas_price,
             prev_chunk_header.gas_limit(),
-            &ChallengesResult::default(),
-            *block_header.random_value(),
-            // TODO: set it properly when challenges are enabled
+            prev_block_header.challenges_result(),
+            *prev_block_header.random_value(),
             true,
             false,
         )
         .map_err(|_| Error::from(ErrorKind::MaliciousChallenge))?;
     let outcome_root = ApplyTransactionResult::compute_outcomes_proof(&result.outcomes).0;
+    let shard_layout = runtime_adapter.get_shard_layout(block_header.epoch_id())?;
+    let outgoing_receipts_root =
+        merklize(&Chain::build_receipts_hashes(&result.outgoing_receipts, &shard_layout)).0;
     let proposals_match = result.validator_proposals.len()
         == chunk_state.chunk_header.validator_proposals().len()
         && result
@@ -371,6 +460,8 @@ fn validate_chunk_state_challenge(
         || outcome_root != chunk_state.chunk_header.outcome_root()
         || !proposals_match
         || result.total_gas_burnt != chunk_state.chunk_header.gas_used()
+        || result.total_balance_burnt != chunk_state.chunk_header.balance_burnt()
+        || outgoing_receipts_root != chunk_state.chunk_header.outgoing_receipts_root()
     {
         Ok((*block_header.hash(), vec![chunk_producer]))
     } else {
@@ -404,7 +495,12 @@ pub fn validate_challenge(
         ChallengeBody::ChunkProofs(chunk_proofs) => {
             validate_chunk_proofs_challenge(runtime_adapter, chunk_proofs)
         }
-        ChallengeBody::ChunkState(chunk_state) => {
+        // Without the receipts the previous chunk was applied with, there is no telling whether
+        // the chunk is invalid.
+        ChallengeBody::ChunkState(_) | ChallengeBody::VersionedChunkState(ChunkState::V1(_)) => {
+            Err(ErrorKind::InvalidChallenge.into())
+        }
+        ChallengeBody::VersionedChunkState(ChunkState::V2(chunk_state)) => {
             validate_chunk_state_challenge(runtime_adapter, chunk_state)
         }
     }
@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 
 use near_primitives::hash::CryptoHash;
@@ -201,6 +202,42 @@ impl EncodedChunksCache {
         self.height_within_front_horizon(height) || self.height_within_rear_horizon(height)
     }
 
+    /// remove parts and receipts that are already in the cache from a partial encoded chunk,
+    /// so that a part received more than once is processed only once
+    pub fn remove_known_parts_and_receipts<'a>(
+        &self,
+        partial_encoded_chunk: &'a PartialEncodedChunkV2,
+    ) -> Cow<'a, PartialEncodedChunkV2> {
+        let entry = match self.encoded_chunks.get(&partial_encoded_chunk.header.chunk_hash()) {
+            Some(entry) => entry,
+            None => return Cow::Borrowed(partial_encoded_chunk),
+        };
+        let is_known_part =
+            |part: &PartialEncodedChunkPart| entry.parts.contains_key(&part.part_ord);
+        let is_known_receipt =
+            |receipt: &ReceiptProof| entry.receipts.contains_key(&receipt.1.to_shard_id);
+        if !partial_encoded_chunk.parts.iter().any(is_known_part)
+            && !partial_encoded_chunk.receipts.iter().any(is_known_receipt)
+        {
+            return Cow::Borrowed(partial_encoded_chunk);
+        }
+        Cow::Owned(PartialEncodedChunkV2 {
+            header: partial_encoded_chunk.header.clone(),
+            parts: partial_encoded_chunk
+                .parts
+                .iter()
+                .filter(|part| !is_known_part(part))
+                .cloned()
+                .collect(),
+            receipts: partial_encoded_chunk
+                .receipts
+                .iter()
+                .filter(|receipt| !is_known_receipt(receipt))
+                .cloned()
+                .collect(),
+        })
+    }
+
     /// add parts and receipts stored in a partial encoded chunk to the corresponding chunk entry
     pub fn merge_in_partial_encoded_chunk(
         &mut self,
@@ -34,6 +34,10 @@
 //! Once a request is added the pool, it can be resent through `resend_chunk_requests`,
 //! which is done periodically through client_actor. A request is only removed from the pool when
 //! all needed parts and receipts in the requested chunk are received.
+//! Each request has a deadline derived from the block production delay. If it is not answered
+//! within a fraction of the deadline, missing parts are also requested from a block producer
+//! tracking the shard, and once the deadline passes the full chunk is requested from the chunk
+//! producer as well.
 //!
 //! ** Storing chunks
 //! Before a chunk can be reconstructed fully, parts and receipts in the chunk are stored in
@@ -91,17 +95,18 @@ use tracing::{debug, error, warn};
 
 use near_chain::validate::validate_chunk_proofs;
 use near_chain::{
-    byzantine_assert, Chain, ChainStore, ChainStoreAccess, ChainStoreUpdate, ErrorKind,
-    RuntimeAdapter,
+    byzantine_assert, ChainStore, ChainStoreAccess, ChainStoreUpdate, ErrorKind, RuntimeAdapter,
 };
 use near_network::types::{
     NetworkRequests, PeerManagerAdapter, PeerManagerMessageRequest, WrappedInstant,
 };
+use near_pool::types::{InsertTransactionResult, TransactionPoolLimits};
 use near_pool::{PoolIteratorWrapper, TransactionPool};
 use near_primitives::block::Tip;
 use near_primitives::hash::{hash, CryptoHash};
-use near_primitives::merkle::{merklize, verify_path, MerklePath};
+use near_primitives::merkle::{verify_path, MerklePath};
 use near_primitives::receipt::Receipt;
+use near_primitives::receipt_proof::create_receipt_proofs;
 use near_primitives::sharding::{
     ChunkHash, EncodedShardChunk, EncodedShardChunkBody, PartialEncodedChunk,
     PartialEncodedChunkPart, PartialEncodedChunkV1, PartialEncodedChunkV2, ReceiptList,
@@ -134,8 +139,9 @@ pub mod test_utils;
 
 const CHUNK_PRODUCER_BLACKLIST_SIZE: usize = 100;
 pub const CHUNK_REQUEST_RETRY_MS: u64 = 100;
-pub const CHUNK_REQUEST_SWITCH_TO_OTHERS_MS: u64 = 400;
-pub const CHUNK_REQUEST_SWITCH_TO_FULL_FETCH_MS: u64 = 3_000;
+// Missing parts are also requested from nodes other than their owners once this fraction of the
+// request deadline has passed without receiving them.
+const CHUNK_REQUEST_REDUNDANT_FRACTION: u32 = 4;
 const CHUNK_REQUEST_RETRY_MAX_MS: u64 = 1_000_000;
 const CHUNK_FORWARD_CACHE_SIZE: usize = 1000;
 const ACCEPTING_SEAL_PERIOD_MS: i64 = 30_000;
@@ -177,12 +183,52 @@ struct ChunkRequestInfo {
     shard_id: ShardId,
     added: Instant,
     last_requested: Instant,
+    // after this, missing parts are also requested from a random block producer tracking the shard
+    redundant_request_at: Instant,
+    // after this, the full chunk is requested from the chunk producer
+    deadline: Instant,
+    stage: ChunkRequestStage,
+}
+
+impl ChunkRequestInfo {
+    fn update_stage(&mut self, now: Instant) {
+        let stage = if now >= self.deadline {
+            ChunkRequestStage::Fallback
+        } else if now >= self.redundant_request_at {
+            ChunkRequestStage::Redundant
+        } else {
+            ChunkRequestStage::FirstTry
+        };
+        self.stage = std::cmp::max(self.stage, stage);
+    }
+}
+
+/// How far a chunk request has escalated, see `resend_chunk_requests`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum ChunkRequestStage {
+    /// Parts are requested from their owners.
+    FirstTry,
+    /// Parts are additionally requested from a block producer tracking the shard.
+    Redundant,
+    /// The full chunk is additionally requested from the chunk producer.
+    Fallback,
+}
+
+impl ChunkRequestStage {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ChunkRequestStage::FirstTry => "first_try",
+            ChunkRequestStage::Redundant => "retried",
+            ChunkRequestStage::Fallback => "fallback",
+        }
+    }
 }
 
 struct RequestPool {
     retry_duration: Duration,
-    switch_to_others_duration: Duration,
-    switch_to_full_fetch_duration: Duration,
+    /// Time within which a requested chunk should be received, derived from the block
+    /// production delay.
+    deadline_duration: Duration,
     max_duration: Duration,
     requests: HashMap<ChunkHash, ChunkRequestInfo>,
 }
@@ -190,17 +236,10 @@ struct RequestPool {
 impl RequestPool {
     pub fn new(
         retry_duration: Duration,
-        switch_to_others_duration: Duration,
-        switch_to_full_fetch_duration: Duration,
+        deadline_duration: Duration,
         max_duration: Duration,
     ) -> Self {
-        Self {
-            retry_duration,
-            switch_to_others_duration,
-            switch_to_full_fetch_duration,
-            max_duration,
-            requests: HashMap::default(),
-        }
+        Self { retry_duration, deadline_duration, max_duration, requests: HashMap::default() }
     }
     pub fn contains_key(&self, chunk_hash: &ChunkHash) -> bool {
         self.requests.contains_key(chunk_hash)
@@ -221,14 +260,16 @@ impl RequestPool {
     pub fn fetch(&mut self) -> Vec<(ChunkHash, ChunkRequestInfo)> {
         let mut removed_requests = HashSet::<ChunkHash>::default();
         let mut requests = Vec::new();
+        let now = Clock::instant();
         for (chunk_hash, mut chunk_request) in self.requests.iter_mut() {
-            if chunk_request.added.elapsed() > self.max_duration {
+            if now.saturating_duration_since(chunk_request.added) > self.max_duration {
                 debug!(target: "chunks", "Evicted chunk requested that was never fetched {} (shard_id: {})", chunk_hash.0, chunk_request.shard_id);
                 removed_requests.insert(chunk_hash.clone());
                 continue;
             }
-            if chunk_request.last_requested.elapsed() > self.retry_duration {
-                chunk_request.last_requested = Clock::instant();
+            if now.saturating_duration_since(chunk_request.last_requested) > self.retry_duration {
+                chunk_request.last_requested = now;
+                chunk_request.update_stage(now);
                 requests.push((chunk_hash.clone(), chunk_request.clone()));
             }
         }
@@ -483,6 +524,10 @@ pub struct ShardsManager {
     /// Useful to make tests deterministic and reproducible,
     /// while keeping the security of randomization of transactions in pool
     rng_seed: RngSeed,
+    /// Minimum priority fee bump, in percent, for a transaction to replace a pooled one.
+    transaction_replacement_fee_bump_percent: u64,
+    /// Limits on the transactions in the pool of each shard.
+    transaction_pool_limits: TransactionPoolLimits,
 }
 
 impl ShardsManager {
@@ -491,6 +536,9 @@ impl ShardsManager {
         runtime_adapter: Arc<dyn RuntimeAdapter>,
         network_adapter: Arc<dyn PeerManagerAdapter>,
         rng_seed: RngSeed,
+        chunk_request_deadline: Duration,
+        transaction_replacement_fee_bump_percent: u64,
+        transaction_pool_limits: TransactionPoolLimits,
     ) -> Self {
         Self {
             me: me.clone(),
@@ -500,13 +548,14 @@ impl ShardsManager {
             encoded_chunks: EncodedChunksCache::new(),
             requested_partial_encoded_chunks: RequestPool::new(
                 Duration::from_millis(CHUNK_REQUEST_RETRY_MS),
-                Duration::from_millis(CHUNK_REQUEST_SWITCH_TO_OTHERS_MS),
-                Duration::from_millis(CHUNK_REQUEST_SWITCH_TO_FULL_FETCH_MS),
+                chunk_request_deadline,
                 Duration::from_millis(CHUNK_REQUEST_RETRY_MAX_MS),
             ),
             chunk_forwards_cache: lru::LruCache::new(CHUNK_FORWARD_CACHE_SIZE),
             seals_mgr: SealsManager::new(me, runtime_adapter),
             rng_seed,
+            transaction_replacement_fee_bump_percent,
+            transaction_pool_limits,
         }
     }
 
@@ -538,7 +587,7 @@ impl ShardsManager {
         ancestor_hash: &CryptoHash,
         shard_id: ShardId,
         chunk_hash: &ChunkHash,
-        force_request_full: bool,
+        stage: ChunkRequestStage,
         request_own_parts_from_others: bool,
         request_from_archival: bool,
     ) -> Result<(), near_chain::Error> {
@@ -546,7 +595,8 @@ impl ShardsManager {
 
         let cache_entry = self.encoded_chunks.get(chunk_hash);
 
-        let request_full = force_request_full
+        let fallback = stage == ChunkRequestStage::Fallback;
+        let request_full = fallback
             || self.cares_about_shard_this_or_next_epoch(
                 self.me.as_ref(),
                 ancestor_hash,
@@ -569,6 +619,11 @@ impl ShardsManager {
         // target, otherwise, the part owner
         // For receipts, request them from the shard representative target
         //
+        // If the request has not been answered within a fraction of its deadline
+        // (ChunkRequestStage::Redundant), parts are additionally requested from the shard
+        // representative target, and once the deadline passed (ChunkRequestStage::Fallback) the
+        // full chunk is requested from the chunk producer as well.
+        //
         // Also note that the target accounts decided is not necessarily the final destination
         // where requests are sent. We use them to construct AccountIdOrPeerTrackingShard struct,
         // which will be passed to PeerManagerActor. PeerManagerActor will try to request either
@@ -587,6 +642,13 @@ impl ShardsManager {
             self.get_random_target_tracking_shard(ancestor_hash, shard_id)?
         };
 
+        // The chunk producer is asked for everything we miss as the last resort.
+        let fallback_target = if fallback && !request_from_archival {
+            Some(chunk_producer_account_id.clone()).filter(|account_id| Some(account_id) != me)
+        } else {
+            None
+        };
+
         let seal = self.seals_mgr.get_seal(chunk_hash, ancestor_hash, height, shard_id)?;
 
         for part_ord in 0..self.runtime_adapter.num_total_parts() {
@@ -620,7 +682,20 @@ impl ShardsManager {
                     }
                 };
 
-                bp_to_parts.entry(fetch_from).or_default().push(part_ord);
+                let mut targets = vec![fetch_from];
+                if stage >= ChunkRequestStage::Redundant && !request_from_archival {
+                    targets.push(shard_representative_target.clone());
+                }
+                if fallback_target.is_some() {
+                    targets.push(fallback_target.clone());
+                }
+                targets.dedup();
+                for target in targets {
+                    let part_ords = bp_to_parts.entry(target).or_default();
+                    if !part_ords.contains(&part_ord) {
+                        part_ords.push(part_ord);
+                    }
+                }
             }
         }
 
@@ -640,6 +715,8 @@ impl ShardsManager {
         for (target_account, part_ords) in bp_to_parts {
             // extra check that we are not sending request to ourselves.
             if no_account_id || me != target_account.as_ref() {
+                let is_fallback_target =
+                    fallback_target.is_some() && target_account == fallback_target;
                 let request = PartialEncodedChunkRequestMsg {
                     chunk_hash: chunk_hash.clone(),
                     part_ords,
@@ -651,7 +728,8 @@ impl ShardsManager {
                 };
                 let target = AccountIdOrPeerTrackingShard {
                     account_id: target_account,
-                    prefer_peer: request_from_archival || rand::thread_rng().gen::<bool>(),
+                    prefer_peer: !is_fallback_target
+                        && (request_from_archival || rand::thread_rng().gen::<bool>()),
                     shard_id,
                     only_archival: request_from_archival,
                     min_height: height.saturating_sub(CHUNK_REQUEST_PEER_HORIZON),
@@ -771,6 +849,8 @@ impl ShardsManager {
         self.encoded_chunks.try_insert(&chunk_header);
 
         let prev_block_hash = chunk_header.prev_block_hash();
+        let added = Clock::instant();
+        let deadline_duration = self.requested_partial_encoded_chunks.deadline_duration;
         self.requested_partial_encoded_chunks.insert(
             chunk_hash.clone(),
             ChunkRequestInfo {
@@ -778,8 +858,11 @@ impl ShardsManager {
                 prev_block_hash,
                 ancestor_hash,
                 shard_id,
-                last_requested: Clock::instant(),
-                added: Clock::instant(),
+                last_requested: added,
+                added,
+                redundant_request_at: added + deadline_duration / CHUNK_REQUEST_REDUNDANT_FRACTION,
+                deadline: added + deadline_duration,
+                stage: ChunkRequestStage::FirstTry,
             },
         );
 
@@ -803,7 +886,7 @@ impl ShardsManager {
                     &ancestor_hash,
                     shard_id,
                     &chunk_hash,
-                    false,
+                    ChunkRequestStage::FirstTry,
                     old_block,
                     fetch_from_archival,
                 );
@@ -887,6 +970,9 @@ impl ShardsManager {
     }
 
     /// Resends chunk requests if haven't received it within expected time.
+    /// Requests escalate as they approach their deadline: missing parts are first requested
+    /// from their owners, then also from a block producer tracking the shard, and after the
+    /// deadline the full chunk is requested from the chunk producer too.
     pub fn resend_chunk_requests(&mut self, header_head: &Tip) {
         // Process chunk one part requests.
         let requests = self.requested_partial_encoded_chunks.fetch();
@@ -905,11 +991,8 @@ impl ShardsManager {
                 &chunk_request.ancestor_hash,
                 chunk_request.shard_id,
                 &chunk_hash,
-                chunk_request.added.elapsed()
-                    > self.requested_partial_encoded_chunks.switch_to_full_fetch_duration,
-                old_block
-                    || chunk_request.added.elapsed()
-                        > self.requested_partial_encoded_chunks.switch_to_others_duration,
+                chunk_request.stage,
+                old_block || chunk_request.stage > ChunkRequestStage::FirstTry,
                 fetch_from_archival,
             ) {
                 Ok(()) => {}
@@ -932,8 +1015,11 @@ impl ShardsManager {
         self.encoded_chunks.get_chunk_headers_for_block(prev_block_hash)
     }
 
-    /// Returns true if transaction is not in the pool before call
-    pub fn insert_transaction(&mut self, shard_id: ShardId, tx: SignedTransaction) -> bool {
+    pub fn insert_transaction(
+        &mut self,
+        shard_id: ShardId,
+        tx: SignedTransaction,
+    ) -> InsertTransactionResult {
         self.pool_for_shard(shard_id).insert_transaction(tx)
     }
 
@@ -959,8 +1045,15 @@ impl ShardsManager {
     }
 
     fn pool_for_shard(&mut self, shard_id: ShardId) -> &mut TransactionPool {
+        let transaction_replacement_fee_bump_percent =
+            self.transaction_replacement_fee_bump_percent;
+        let transaction_pool_limits = self.transaction_pool_limits;
         self.tx_pools.entry(shard_id).or_insert_with(|| {
-            TransactionPool::new(ShardsManager::random_seed(&self.rng_seed, shard_id))
+            TransactionPool::new(
+                ShardsManager::random_seed(&self.rng_seed, shard_id),
+                transaction_replacement_fee_bump_percent,
+                transaction_pool_limits,
+            )
         })
     }
 
@@ -1141,20 +1234,9 @@ impl ShardsManager {
             .runtime_adapter
             .get_shard_layout_from_prev_block(chunk_header.prev_block_hash_ref())?;
 
-        let hashes = Chain::build_receipts_hashes(&outgoing_receipts, &shard_layout);
-        let (root, proofs) = merklize(&hashes);
+        let (root, proofs) = create_receipt_proofs(shard_id, outgoing_receipts, &shard_layout);
         assert_eq!(chunk_header.outgoing_receipts_root(), root);
-
-        let mut receipts_by_shard =
-            Chain::group_receipts_by_shard(outgoing_receipts.to_vec(), &shard_layout);
-        let it = proofs.into_iter().enumerate().map(move |(proof_shard_id, proof)| {
-            let proof_shard_id = proof_shard_id as u64;
-            let receipts = receipts_by_shard.remove(&proof_shard_id).unwrap_or_else(Vec::new);
-            let shard_proof =
-                ShardProof { from_shard_id: shard_id, to_shard_id: proof_shard_id, proof: proof };
-            ReceiptProof(receipts, shard_proof)
-        });
-        Ok(it)
+        Ok(proofs.into_iter())
     }
 
     /// Prepares response to a partial encoded chunk request from a chunk read
@@ -1628,7 +1710,13 @@ impl ShardsManager {
         }
         let partial_encoded_chunk = partial_encoded_chunk.into_inner();
 
-        // 1.d Checking part_ords' validity
+        // 1.d Dropping parts and receipts we already have. Redundant requests can be answered
+        // several times and duplicates must not be validated or forwarded again.
+        let partial_encoded_chunk =
+            self.encoded_chunks.remove_known_parts_and_receipts(partial_encoded_chunk);
+        let partial_encoded_chunk = partial_encoded_chunk.as_ref();
+
+        // 1.e Checking part_ords' validity
         let num_total_parts = self.runtime_adapter.num_total_parts();
         for part_info in partial_encoded_chunk.parts.iter() {
             // TODO: only validate parts we care about
@@ -1636,7 +1724,7 @@ impl ShardsManager {
             self.validate_part(header.encoded_merkle_root(), part_info, num_total_parts)?;
         }
 
-        // 1.e Checking receipts validity
+        // 1.f Checking receipts validity
         for proof in partial_encoded_chunk.receipts.iter() {
             // TODO: only validate receipts we care about
             // https://github.com/near/nearcore/issues/5885
@@ -1663,6 +1751,11 @@ impl ShardsManager {
 
         // Merge parts and receipts included in the partial encoded chunk into chunk cache
         self.encoded_chunks.merge_in_partial_encoded_chunk(partial_encoded_chunk);
+        if let Some(request) = self.requested_partial_encoded_chunks.get_request_info(&chunk_hash) {
+            metrics::PARTIAL_ENCODED_CHUNK_REQUESTED_PARTS_RECEIVED
+                .with_label_values(&[request.stage.as_str()])
+                .inc_by(partial_encoded_chunk.parts.len() as u64);
+        }
 
         // 3. Process the forwarded parts in chunk_forwards_cache
         if let Some(forwarded_parts) = self.chunk_forwards_cache.pop(&chunk_hash) {
@@ -2199,7 +2292,6 @@ mod test {
     use near_network::types::NetworkRequests;
     use near_primitives::block::Tip;
     use near_primitives::hash::{hash, CryptoHash};
-    use near_primitives::merkle::merklize;
     use near_primitives::sharding::ReedSolomonWrapper;
     use near_primitives::types::EpochId;
     use near_primitives::validator_signer::InMemoryValidatorSigner;
@@ -2210,6 +2302,13 @@ mod test {
     use crate::test_utils::*;
 
     const TEST_SEED: RngSeed = [3; 32];
+    const TEST_CHUNK_REQUEST_DEADLINE: Duration = Duration::from_millis(2_000);
+    const TEST_REPLACEMENT_FEE_BUMP_PERCENT: u64 = 10;
+    const TEST_TRANSACTION_POOL_LIMITS: TransactionPoolLimits = TransactionPoolLimits {
+        max_transactions: 1_000,
+        max_transactions_per_account: 1_000,
+        max_bytes_per_account: 1_000_000,
+    };
 
     /// should not request partial encoded chunk from self
     #[test]
@@ -2221,6 +2320,9 @@ mod test {
             runtime_adapter,
             network_adapter.clone(),
             TEST_SEED,
+            TEST_CHUNK_REQUEST_DEADLINE,
+            TEST_REPLACEMENT_FEE_BUMP_PERCENT,
+            TEST_TRANSACTION_POOL_LIMITS,
         );
         let added = Clock::instant();
         shards_manager.requested_partial_encoded_chunks.insert(
@@ -2232,6 +2334,9 @@ mod test {
                 shard_id: 0,
                 added: added,
                 last_requested: added,
+                redundant_request_at: added + TEST_CHUNK_REQUEST_DEADLINE,
+                deadline: added + TEST_CHUNK_REQUEST_DEADLINE,
+                stage: ChunkRequestStage::FirstTry,
             },
         );
         std::thread::sleep(Duration::from_millis(2 * CHUNK_REQUEST_RETRY_MS));
@@ -2278,6 +2383,9 @@ mod test {
             runtime_adapter.clone(),
             network_adapter.clone(),
             TEST_SEED,
+            TEST_CHUNK_REQUEST_DEADLINE,
+            TEST_REPLACEMENT_FEE_BUMP_PERCENT,
+            TEST_TRANSACTION_POOL_LIMITS,
         );
         let signer =
             InMemoryValidatorSigner::from_seed("test".parse().unwrap(), KeyType::ED25519, "test");
@@ -2295,7 +2403,7 @@ mod test {
             vec![],
             vec![],
             &vec![],
-            merklize(&Chain::build_receipts_hashes(&vec![], &shard_layout)).0,
+            create_receipt_proofs(0, &[], &shard_layout).0,
             CryptoHash::default(),
             &signer,
             &mut rs,
@@ -2312,6 +2420,9 @@ mod test {
                 shard_id: header.shard_id(),
                 last_requested: Clock::instant(),
                 added: Clock::instant(),
+                redundant_request_at: Clock::instant() + TEST_CHUNK_REQUEST_DEADLINE,
+                deadline: Clock::instant() + TEST_CHUNK_REQUEST_DEADLINE,
+                stage: ChunkRequestStage::FirstTry,
             },
         );
         shards_manager
@@ -2320,7 +2431,7 @@ mod test {
                 &header.prev_block_hash(),
                 header.shard_id(),
                 &header.chunk_hash(),
-                false,
+                ChunkRequestStage::FirstTry,
                 false,
                 false,
             )
@@ -2444,6 +2555,9 @@ mod test {
             fixture.mock_runtime.clone(),
             fixture.mock_network.clone(),
             TEST_SEED,
+            TEST_CHUNK_REQUEST_DEADLINE,
+            TEST_REPLACEMENT_FEE_BUMP_PERCENT,
+            TEST_TRANSACTION_POOL_LIMITS,
         );
         // process chunk part 0
         let partial_encoded_chunk = fixture.make_partial_encoded_chunk(&[0]);
@@ -2507,6 +2621,111 @@ mod test {
         assert_eq!(requested_parts, HashSet::new());
     }
 
+    #[test]
+    fn test_chunk_request_escalation() {
+        // Test that a request which is not answered in time is also sent to other nodes tracking
+        // the shard, and after its deadline to the chunk producer for the full chunk
+        let mut fixture = ChunkTestFixture::default();
+        let deadline = Duration::from_millis(4 * CHUNK_REQUEST_RETRY_MS);
+        let mut shards_manager = ShardsManager::new(
+            Some(fixture.mock_chunk_part_owner.clone()),
+            fixture.mock_runtime.clone(),
+            fixture.mock_network.clone(),
+            TEST_SEED,
+            deadline,
+            TEST_REPLACEMENT_FEE_BUMP_PERCENT,
+            TEST_TRANSACTION_POOL_LIMITS,
+        );
+        let chunk_hash = fixture.mock_chunk_header.chunk_hash();
+        shards_manager.request_chunk_single(
+            &fixture.mock_chunk_header,
+            CryptoHash::default(),
+            Some(&fixture.mock_chain_head),
+            false,
+        );
+        let stage = |shards_manager: &ShardsManager| {
+            shards_manager
+                .requested_partial_encoded_chunks
+                .get_request_info(&chunk_hash)
+                .unwrap()
+                .stage
+        };
+        assert_eq!(stage(&shards_manager), ChunkRequestStage::FirstTry);
+
+        std::thread::sleep(Duration::from_millis(2 * CHUNK_REQUEST_RETRY_MS));
+        shards_manager.resend_chunk_requests(&fixture.mock_chain_head);
+        assert_eq!(stage(&shards_manager), ChunkRequestStage::Redundant);
+
+        while fixture.mock_network.pop().is_some() {}
+        std::thread::sleep(deadline);
+        shards_manager.resend_chunk_requests(&fixture.mock_chain_head);
+        assert_eq!(stage(&shards_manager), ChunkRequestStage::Fallback);
+        let mut fallback_parts = None;
+        while let Some(r) = fixture.mock_network.pop() {
+            if let NetworkRequests::PartialEncodedChunkRequest { target, request, .. } =
+                r.as_network_requests_ref()
+            {
+                if target.account_id.as_ref() == Some(&fixture.mock_chunk_producer) {
+                    assert!(!target.prefer_peer);
+                    fallback_parts = Some(request.part_ords.clone());
+                }
+            }
+        }
+        assert_eq!(
+            fallback_parts,
+            Some((0..fixture.mock_chunk_parts.len() as u64).collect::<Vec<_>>())
+        );
+    }
+
+    #[test]
+    fn test_duplicate_parts_not_forwarded_again() {
+        // Parts received a second time, e.g. in response to a redundant request, are ignored
+        let mut fixture = ChunkTestFixture::default();
+        let mut shards_manager = ShardsManager::new(
+            Some(fixture.mock_chunk_part_owner.clone()),
+            fixture.mock_runtime.clone(),
+            fixture.mock_network.clone(),
+            TEST_SEED,
+            TEST_CHUNK_REQUEST_DEADLINE,
+            TEST_REPLACEMENT_FEE_BUMP_PERCENT,
+            TEST_TRANSACTION_POOL_LIMITS,
+        );
+        let partial_encoded_chunk = fixture.make_partial_encoded_chunk(&fixture.mock_part_ords);
+        let count_forwards = |fixture: &ChunkTestFixture| {
+            fixture
+                .mock_network
+                .requests
+                .read()
+                .unwrap()
+                .iter()
+                .filter(|r| {
+                    matches!(
+                        r.as_network_requests_ref(),
+                        NetworkRequests::PartialEncodedChunkForward { .. }
+                    )
+                })
+                .count()
+        };
+        let mut forwards_count = 0;
+        for i in 0..2 {
+            let result = shards_manager
+                .process_partial_encoded_chunk(
+                    MaybeValidated::from(&partial_encoded_chunk),
+                    None,
+                    &mut fixture.chain_store,
+                    &mut fixture.rs,
+                )
+                .unwrap();
+            assert_matches!(result, ProcessPartialEncodedChunkResult::NeedMorePartsOrReceipts);
+            if i == 0 {
+                forwards_count = count_forwards(&fixture);
+                assert!(forwards_count > 0);
+            } else {
+                assert_eq!(count_forwards(&fixture), forwards_count);
+            }
+        }
+    }
+
     #[test]
     fn test_invalid_chunk() {
         // Test that process_partial_encoded_chunk will reject invalid chunk
@@ -2516,6 +2735,9 @@ mod test {
             fixture.mock_runtime.clone(),
             fixture.mock_network.clone(),
             TEST_SEED,
+            TEST_CHUNK_REQUEST_DEADLINE,
+            TEST_REPLACEMENT_FEE_BUMP_PERCENT,
+            TEST_TRANSACTION_POOL_LIMITS,
         );
 
         // part id > num parts
@@ -2542,6 +2764,9 @@ mod test {
             fixture.mock_runtime.clone(),
             fixture.mock_network.clone(),
             TEST_SEED,
+            TEST_CHUNK_REQUEST_DEADLINE,
+            TEST_REPLACEMENT_FEE_BUMP_PERCENT,
+            TEST_TRANSACTION_POOL_LIMITS,
         );
         let partial_encoded_chunk = fixture.make_partial_encoded_chunk(&fixture.mock_part_ords);
         let result = shards_manager
@@ -2605,6 +2830,9 @@ mod test {
             fixture.mock_runtime.clone(),
             fixture.mock_network.clone(),
             test_seed,
+            TEST_CHUNK_REQUEST_DEADLINE,
+            TEST_REPLACEMENT_FEE_BUMP_PERCENT,
+            TEST_TRANSACTION_POOL_LIMITS,
         );
         let header_head = Tip {
             height: 0,
@@ -2633,6 +2861,9 @@ mod test {
             fixture.mock_runtime.clone(),
             fixture.mock_network.clone(),
             test_seed,
+            TEST_CHUNK_REQUEST_DEADLINE,
+            TEST_REPLACEMENT_FEE_BUMP_PERCENT,
+            TEST_TRANSACTION_POOL_LIMITS,
         );
         shards_manager.request_chunks(
             vec![fixture.mock_chunk_header.clone()],
@@ -2654,6 +2885,9 @@ mod test {
             fixture.mock_runtime.clone(),
             fixture.mock_network.clone(),
             test_seed,
+            TEST_CHUNK_REQUEST_DEADLINE,
+            TEST_REPLACEMENT_FEE_BUMP_PERCENT,
+            TEST_TRANSACTION_POOL_LIMITS,
         );
         shards_manager.request_chunks(
             vec![fixture.mock_chunk_header.clone()],
@@ -2679,6 +2913,9 @@ mod test {
             fixture.mock_runtime.clone(),
             fixture.mock_network.clone(),
             TEST_SEED,
+            TEST_CHUNK_REQUEST_DEADLINE,
+            TEST_REPLACEMENT_FEE_BUMP_PERCENT,
+            TEST_TRANSACTION_POOL_LIMITS,
         );
         let (most_parts, other_parts) = {
             let mut most_parts = fixture.mock_chunk_parts.clone();
@@ -18,3 +18,19 @@ pub static PARTIAL_ENCODED_CHUNK_REQUEST_PROCESSING_TIME: Lazy<near_metrics::His
         )
         .unwrap()
     });
+
+pub static PARTIAL_ENCODED_CHUNK_REQUESTED_PARTS_RECEIVED: Lazy<near_metrics::IntCounterVec> =
+    Lazy::new(|| {
+        near_metrics::try_create_int_counter_vec(
+            "near_partial_encoded_chunk_requested_parts_received",
+            concat!(
+                "Number of new parts received for requested chunks.  The ‘stage’ key ",
+                "describes how far the request had escalated: ‘first_try’ if parts were ",
+                "only requested from their owners, ‘retried’ if they were also requested ",
+                "from other nodes tracking the shard and ‘fallback’ if the full chunk was ",
+                "requested from the chunk producer."
+            ),
+            &["stage"],
+        )
+        .unwrap()
+    });
@@ -135,6 +135,7 @@ pub struct ChunkTestFixture {
     pub mock_network: Arc<MockPeerManagerAdapter>,
     pub chain_store: ChainStore,
     pub mock_part_ords: Vec<u64>,
+    pub mock_chunk_producer: AccountId,
     pub mock_chunk_part_owner: AccountId,
     pub mock_shard_tracker: AccountId,
     pub mock_chunk_header: ShardChunkHeader,
@@ -251,6 +252,7 @@ impl ChunkTestFixture {
             mock_network,
             chain_store,
             mock_part_ords,
+            mock_chunk_producer,
             mock_chunk_part_owner,
             mock_shard_tracker,
             mock_chunk_header: encoded_chunk.cloned_header(),
@@ -1,30 +1,41 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use actix::Message;
 use chrono::DateTime;
 use near_primitives::time::Utc;
 
 use near_chain_configs::ProtocolConfigView;
-use near_network_primitives::types::{AccountOrPeerIdOrHash, KnownProducer, PeerInfo};
+use near_network_primitives::types::{
+    AccountOrPeerIdOrHash, BannedPeerInfo, DroppedRoutedMessage, KnownProducer, PeerInfo,
+};
 use near_primitives::errors::InvalidTxError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{MerklePath, PartialMerkleTree};
+use near_primitives::network::PeerId;
+use near_primitives::runtime::apply_state::AppliedChunkInfo;
 use near_primitives::sharding::ChunkHash;
 use near_primitives::types::{
-    AccountId, BlockHeight, BlockReference, EpochId, EpochReference, MaybeBlockId, ShardId,
-    TransactionOrReceiptId,
+    AccountId, BlockHeight, BlockReference, EpochHeight, EpochId, EpochReference, MaybeBlockId,
+    ShardId, SyncCheckpoint, TransactionOrReceiptId,
 };
 use near_primitives::utils::generate_random_string;
 use near_primitives::views::validator_stake_view::ValidatorStakeView;
 use near_primitives::views::{
-    BlockView, ChunkView, EpochValidatorInfo, ExecutionOutcomeWithIdView,
-    FinalExecutionOutcomeViewEnum, GasPriceView, LightClientBlockLiteView, LightClientBlockView,
-    QueryRequest, QueryResponse, ReceiptView, StateChangesKindsView, StateChangesRequestView,
-    StateChangesView,
+    BlockHeaderOnlyView, BlockView, ChunkView, EpochInfoView, EpochValidatorInfo,
+    ExecutionOutcomeWithIdView, FinalExecutionOutcomeViewEnum, ForwardedTransactionView,
+    GasPriceView, LightClientBlockLiteView, LightClientBlockView, ProtocolUpgradeStatusView,
+    QueryRequest, QueryResponse, ReceiptQueueChangesView, ReceiptView, StateChangesKindsView,
+    StateChangesRequestView, StateChangesView, TopContractsView, TransactionRoutingView,
+    UnstakeScheduleView, ValidatorProductionStatsView,
 };
 pub use near_primitives::views::{StatusResponse, StatusSyncInfo};
+pub use near_store::db::StoreProperties;
+pub use near_store::snapshot::SnapshotInfo;
+pub use near_store::CachedTrieNode;
 
 /// Combines errors coming from chain, tx pool and block producer.
 #[derive(Debug)]
@@ -211,6 +222,13 @@ impl Message for GetBlock {
     type Result = Result<BlockView, GetBlockError>;
 }
 
+/// Actor message requesting block header and summaries of the chunks by id, hash or sync state.
+pub struct GetBlockHeaderOnly(pub BlockReference);
+
+impl Message for GetBlockHeaderOnly {
+    type Result = Result<BlockHeaderOnlyView, GetBlockError>;
+}
+
 /// Actor message requesting block hash by id, hash or sync state.
 pub struct GetBlockHash(pub BlockReference);
 
@@ -236,6 +254,7 @@ pub enum GetChunk {
     Height(BlockHeight, ShardId),
     BlockHash(CryptoHash, ShardId),
     ChunkHash(ChunkHash),
+    SyncCheckpoint(SyncCheckpoint, ShardId),
 }
 
 impl Message for GetChunk {
@@ -448,6 +467,14 @@ impl Message for GetNextLightClientBlock {
     type Result = Result<Option<LightClientBlockView>, GetNextLightClientBlockError>;
 }
 
+/// Actor message subscribing to the hashes of the blocks which become final, in the order of
+/// their heights.
+pub struct SubscribeFinalBlocks;
+
+impl Message for SubscribeFinalBlocks {
+    type Result = tokio::sync::broadcast::Receiver<CryptoHash>;
+}
+
 pub struct GetNetworkInfo {}
 
 impl Message for GetNetworkInfo {
@@ -499,6 +526,110 @@ pub struct NetworkInfoResponse {
     pub received_bytes_per_sec: u64,
     /// Accounts of known block and chunk producers from routing table.
     pub known_producers: Vec<KnownProducer>,
+    /// Peers which are currently banned.
+    pub banned_peers: Vec<BannedPeerInfo>,
+    /// Most recent routed messages dropped because their TTL has expired.
+    pub recent_dropped_routed_messages: Vec<DroppedRoutedMessage>,
+}
+
+/// Ban or unban a peer on request of the node operator.
+#[derive(Debug)]
+pub enum ManagePeerBan {
+    /// Ban the peer for `ban_duration`, or for the configured ban window if not set.
+    Ban {
+        peer_id: PeerId,
+        ban_duration: Option<Duration>,
+    },
+    Unban {
+        peer_id: PeerId,
+    },
+}
+
+impl Message for ManagePeerBan {
+    type Result = ();
+}
+
+/// Creates a snapshot of the node database in `destination` while the node keeps running.
+#[derive(Debug)]
+pub struct MakeSnapshot {
+    pub destination: PathBuf,
+}
+
+impl Message for MakeSnapshot {
+    type Result = Result<SnapshotInfo, String>;
+}
+
+/// Changes the number of flushes and compactions RocksDB runs concurrently, until the node
+/// restarts, e.g. to throttle them while they slow down block processing.
+#[derive(Debug)]
+pub struct SetMaxBackgroundJobs {
+    pub jobs: i32,
+}
+
+impl Message for SetMaxBackgroundJobs {
+    type Result = Result<(), String>;
+}
+
+/// Lists the nodes and values of the shard trie caches with the most hits.
+#[derive(Debug)]
+pub struct GetHottestTrieNodes {
+    pub limit: usize,
+}
+
+impl Message for GetHottestTrieNodes {
+    type Result = Vec<CachedTrieNode>;
+}
+
+/// Samples the compaction and write stall state of the database, `None` if it isn't RocksDB.
+#[derive(Debug)]
+pub struct GetStoreProperties {}
+
+impl Message for GetStoreProperties {
+    type Result = Option<StoreProperties>;
+}
+
+/// Lists the contracts which took the most time to execute during the current epoch, `None` if
+/// the runtime doesn't track it.
+#[derive(Debug)]
+pub struct GetTopContracts {
+    pub limit: usize,
+}
+
+impl Message for GetTopContracts {
+    type Result = Option<TopContractsView>;
+}
+
+/// Parameters the chunks of the block were applied with, for the shards the node applied them
+/// for.
+#[derive(Debug)]
+pub struct GetAppliedChunkInfo {
+    pub block_hash: CryptoHash,
+}
+
+impl Message for GetAppliedChunkInfo {
+    type Result = Result<HashMap<ShardId, AppliedChunkInfo>, String>;
+}
+
+/// Shard of the signer of a transaction received by the client and the chunk producers it was
+/// forwarded to.
+#[derive(Debug)]
+pub struct GetTransactionRouting {
+    pub tx_hash: CryptoHash,
+    pub signer_id: AccountId,
+}
+
+impl Message for GetTransactionRouting {
+    type Result = Result<TransactionRoutingView, String>;
+}
+
+/// Lists the transactions the client forwarded most recently, the latest first.
+#[derive(Debug)]
+pub struct GetForwardedTransactions {
+    pub limit: usize,
+}
+
+impl Message for GetForwardedTransactions {
+    type Result = Vec<ForwardedTransactionView>;
 }
 
 /// Status of given transaction including all the subsequent receipts.
@@ -512,6 +643,8 @@ pub struct TxStatus {
 pub enum TxStatusError {
     ChainError(near_chain_primitives::Error),
     MissingTransaction(CryptoHash),
+    /// The transaction's outcome was removed by outcomes pruning on this node.
+    GarbageCollectedTransaction(CryptoHash),
     InvalidTx(InvalidTxError),
     InternalError(String),
     TimeoutError,
@@ -524,6 +657,9 @@ impl From<TxStatusError> for String {
             TxStatusError::MissingTransaction(tx_hash) => {
                 format!("Transaction {} doesn't exist", tx_hash)
             }
+            TxStatusError::GarbageCollectedTransaction(tx_hash) => {
+                format!("Transaction {} is garbage collected", tx_hash)
+            }
             TxStatusError::InternalError(debug_message) => {
                 format!("Internal error: {}", debug_message)
             }
@@ -572,6 +708,133 @@ impl From<near_chain_primitives::Error> for GetValidatorInfoError {
     }
 }
 
+pub enum GetEpochInfo {
+    EpochId(EpochId),
+    EpochHeight(EpochHeight),
+}
+
+impl Message for GetEpochInfo {
+    type Result = Result<EpochInfoView, GetEpochInfoError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetEpochInfoError {
+    #[error("IO Error: {0}")]
+    IOError(String),
+    #[error("Unknown epoch")]
+    UnknownEpoch,
+    #[error("Unknown epoch height {0}")]
+    UnknownEpochHeight(EpochHeight),
+    // NOTE: Currently, the underlying errors are too broad, and while we tried to handle
+    // expected cases, we cannot statically guarantee that no other errors will be returned
+    // in the future.
+    // TODO #3851: Remove this variant once we can exhaustively match all the underlying errors
+    #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}")]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for GetEpochInfoError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        match error.kind() {
+            near_chain_primitives::ErrorKind::DBNotFoundErr(_)
+            | near_chain_primitives::ErrorKind::EpochOutOfBounds(_) => Self::UnknownEpoch,
+            near_chain_primitives::ErrorKind::IOErr(s) => Self::IOError(s),
+            _ => Self::Unreachable(error.to_string()),
+        }
+    }
+}
+
+pub struct GetValidatorProductionStats {
+    pub account_id: AccountId,
+}
+
+impl Message for GetValidatorProductionStats {
+    type Result = Result<ValidatorProductionStatsView, GetValidatorProductionStatsError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetValidatorProductionStatsError {
+    #[error("IO Error: {0}")]
+    IOError(String),
+    #[error(
+        "Account {0} has no block or chunk producer assignments in the current or previous epoch"
+    )]
+    NoAssignments(AccountId),
+    // NOTE: Currently, the underlying errors are too broad, and while we tried to handle
+    // expected cases, we cannot statically guarantee that no other errors will be returned
+    // in the future.
+    // TODO #3851: Remove this variant once we can exhaustively match all the underlying errors
+    #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}")]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for GetValidatorProductionStatsError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        match error.kind() {
+            near_chain_primitives::ErrorKind::IOErr(s) => Self::IOError(s),
+            _ => Self::Unreachable(error.to_string()),
+        }
+    }
+}
+
+pub struct GetUnstakeSchedule {
+    pub account_id: AccountId,
+}
+
+impl Message for GetUnstakeSchedule {
+    type Result = Result<UnstakeScheduleView, GetUnstakeScheduleError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetUnstakeScheduleError {
+    #[error("IO Error: {0}")]
+    IOError(String),
+    #[error("Account {0} has no locked stake")]
+    NotStaked(AccountId),
+    // NOTE: Currently, the underlying errors are too broad, and while we tried to handle
+    // expected cases, we cannot statically guarantee that no other errors will be returned
+    // in the future.
+    // TODO #3851: Remove this variant once we can exhaustively match all the underlying errors
+    #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}")]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for GetUnstakeScheduleError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        match error.kind() {
+            near_chain_primitives::ErrorKind::IOErr(s) => Self::IOError(s),
+            _ => Self::Unreachable(error.to_string()),
+        }
+    }
+}
+
+pub struct GetProtocolUpgradeStatus {}
+
+impl Message for GetProtocolUpgradeStatus {
+    type Result = Result<ProtocolUpgradeStatusView, GetProtocolUpgradeStatusError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetProtocolUpgradeStatusError {
+    #[error("IO Error: {0}")]
+    IOError(String),
+    // NOTE: Currently, the underlying errors are too broad, and while we tried to handle
+    // expected cases, we cannot statically guarantee that no other errors will be returned
+    // in the future.
+    // TODO #3851: Remove this variant once we can exhaustively match all the underlying errors
+    #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}")]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::Error> for GetProtocolUpgradeStatusError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        match error.kind() {
+            near_chain_primitives::ErrorKind::IOErr(s) => Self::IOError(s),
+            _ => Self::Unreachable(error.to_string()),
+        }
+    }
+}
+
 pub struct GetValidatorOrdered {
     pub block_id: MaybeBlockId,
 }
@@ -644,6 +907,16 @@ impl Message for GetStateChangesWithCauseInBlockForTrackedShards {
     type Result = Result<HashMap<ShardId, StateChangesView>, GetStateChangesError>;
 }
 
+/// Receipt queue changes made by the chunks of the block, for the shards the node has them for.
+pub struct GetReceiptQueueChangesInBlock {
+    pub block_hash: CryptoHash,
+    pub epoch_id: EpochId,
+}
+
+impl Message for GetReceiptQueueChangesInBlock {
+    type Result = Result<HashMap<ShardId, ReceiptQueueChangesView>, GetStateChangesError>;
+}
+
 pub struct GetExecutionOutcome {
     pub id: TransactionOrReceiptId,
 }
@@ -732,6 +1005,8 @@ pub struct GetBlockProofResponse {
 pub enum GetBlockProofError {
     #[error("Block either has never been observed on the node or has been garbage collected: {error_message}")]
     UnknownBlock { error_message: String },
+    #[error("Block {block_hash} is newer than the head block {head_block_hash}")]
+    BlockAfterHead { block_hash: CryptoHash, head_block_hash: CryptoHash },
     #[error("Internal error: {error_message}")]
     InternalError { error_message: String },
     // NOTE: Currently, the underlying errors are too broad, and while we tried to handle
@@ -1,12 +1,12 @@
 //! Client is responsible for tracking the chain, chunks, and producing them when needed.
 //! This client works completely synchronously and must be operated by some async actor outside.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use near_primitives::time::Clock;
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, info, trace, warn};
 
 use near_chain::chain::{
     ApplyStatePartsRequest, BlockCatchUpRequest, BlockMissingChunks, BlocksCatchUpState,
@@ -23,8 +23,10 @@ use near_chunks::{ProcessPartialEncodedChunkResult, ShardsManager};
 use near_network::types::{
     FullPeerInfo, NetworkClientResponses, NetworkRequests, PeerManagerAdapter,
 };
+use near_pool::types::{InsertTransactionResult, TransactionPoolLimits};
 use near_primitives::block::{Approval, ApprovalInner, ApprovalMessage, Block, BlockHeader, Tip};
 use near_primitives::challenge::{Challenge, ChallengeBody};
+use near_primitives::errors::InvalidTxError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{merklize, MerklePath};
 use near_primitives::receipt::Receipt;
@@ -32,6 +34,7 @@ use near_primitives::sharding::{
     ChunkHash, EncodedShardChunk, PartialEncodedChunk, PartialEncodedChunkV2, ReedSolomonWrapper,
     ShardChunkHeader, ShardInfo,
 };
+use near_primitives::state_part::PartId;
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::chunk_extra::ChunkExtra;
 use near_primitives::types::{AccountId, ApprovalStake, BlockHeight, EpochId, NumBlocks, ShardId};
@@ -40,19 +43,25 @@ use near_primitives::utils::{to_timestamp, MaybeValidated};
 use near_primitives::validator_signer::ValidatorSigner;
 
 use crate::chunks_delay_tracker::ChunksDelayTracker;
+use crate::final_blocks_notifier::FinalBlocksNotifier;
+use crate::missed_chunks_tracker::MissedChunksTracker;
 use crate::sync::{BlockSync, EpochSync, HeaderSync, StateSync, StateSyncResult};
 use crate::{metrics, SyncStatus};
 use near_client_primitives::types::{Error, ShardSyncDownload, ShardSyncStatus};
 use near_network::types::PeerManagerMessageRequest;
 use near_network_primitives::types::{
-    PartialEncodedChunkForwardMsg, PartialEncodedChunkResponseMsg,
+    PartialEncodedChunkForwardMsg, PartialEncodedChunkResponseMsg, StateResponseInfo,
 };
 use near_primitives::block_header::ApprovalType;
 use near_primitives::epoch_manager::RngSeed;
 use near_primitives::version::PROTOCOL_VERSION;
+use near_primitives::views::{ForwardedTransactionView, TransactionRoutingView};
 
 const NUM_REBROADCAST_BLOCKS: usize = 30;
 
+/// Number of the most recently forwarded transactions the client remembers the targets of.
+const NUM_FORWARDED_TRANSACTIONS: usize = 1_000;
+
 /// The time we wait for the response to a Epoch Sync request before retrying
 // TODO #3488 set 30_000
 pub const EPOCH_SYNC_REQUEST_TIMEOUT: Duration = Duration::from_millis(1_000);
@@ -66,6 +75,15 @@ pub struct Client {
     pub adv_produce_blocks: bool,
     #[cfg(feature = "test_features")]
     pub adv_produce_blocks_only_valid: bool,
+    /// Blocks produced on top of the head which haven't been sent out yet.
+    #[cfg(feature = "adversarial")]
+    pub adv_private_fork: Vec<Block>,
+    /// Whether to send out another block at the height of the next produced block.
+    #[cfg(feature = "adversarial")]
+    pub adv_double_sign: bool,
+    /// Number of the next produced chunks to give an invalid state root.
+    #[cfg(feature = "adversarial")]
+    pub adv_invalid_state_root_chunks: u64,
 
     pub config: ClientConfig,
     pub sync_status: SyncStatus,
@@ -98,11 +116,17 @@ pub struct Client {
     pub rs: ReedSolomonWrapper,
     /// Blocks that have been re-broadcast recently. They should not be broadcast again.
     rebroadcasted_blocks: lru::LruCache<CryptoHash, ()>,
+    /// Transactions forwarded recently to chunk producers, with their targets.
+    forwarded_transactions: lru::LruCache<CryptoHash, ForwardedTransactionView>,
     /// Last time the head was updated, or our head was rebroadcasted. Used to re-broadcast the head
     /// again to prevent network from stalling if a large percentage of the network missed a block
     last_time_head_progress_made: Instant,
     /// Keeps track of when the latest blocks and chunks were received.
     chunks_delay_tracker: ChunksDelayTracker,
+    /// Keeps track of shards missing chunks and of unreachable chunk producers.
+    missed_chunks_tracker: MissedChunksTracker,
+    /// Notifies subscribers about the blocks which become final.
+    final_blocks_notifier: FinalBlocksNotifier,
 }
 
 impl Client {
@@ -126,6 +150,13 @@ impl Client {
             runtime_adapter.clone(),
             network_adapter.clone(),
             rng_seed,
+            config.max_block_production_delay,
+            config.transaction_replacement_fee_bump_percent,
+            TransactionPoolLimits {
+                max_transactions: config.transaction_pool_max_transactions,
+                max_transactions_per_account: config.transaction_pool_max_transactions_per_account,
+                max_bytes_per_account: config.transaction_pool_max_bytes_per_account,
+            },
         );
         let sync_status = SyncStatus::AwaitingPeers;
         let genesis_block = chain.genesis_block();
@@ -150,6 +181,8 @@ impl Client {
             config.header_sync_progress_timeout,
             config.header_sync_stall_ban_timeout,
             config.header_sync_expected_height_per_second,
+            config.header_sync_max_outstanding_batches,
+            config.header_sync_batch_timeout,
         );
         let block_sync =
             BlockSync::new(network_adapter.clone(), config.block_fetch_horizon, config.archive);
@@ -172,6 +205,12 @@ impl Client {
             adv_produce_blocks: false,
             #[cfg(feature = "test_features")]
             adv_produce_blocks_only_valid: false,
+            #[cfg(feature = "adversarial")]
+            adv_private_fork: vec![],
+            #[cfg(feature = "adversarial")]
+            adv_double_sign: false,
+            #[cfg(feature = "adversarial")]
+            adv_invalid_state_root_chunks: 0,
             config,
             sync_status,
             chain,
@@ -189,14 +228,23 @@ impl Client {
             challenges: Default::default(),
             rs: ReedSolomonWrapper::new(data_parts, parity_parts),
             rebroadcasted_blocks: lru::LruCache::new(NUM_REBROADCAST_BLOCKS),
+            forwarded_transactions: lru::LruCache::new(NUM_FORWARDED_TRANSACTIONS),
             last_time_head_progress_made: Clock::instant(),
             chunks_delay_tracker: Default::default(),
+            missed_chunks_tracker: Default::default(),
+            final_blocks_notifier: FinalBlocksNotifier::new(),
         })
     }
 
     // Checks if it's been at least `stall_timeout` since the last time the head was updated, or
     // this method was called. If yes, rebroadcasts the current head.
     pub fn check_head_progress_stalled(&mut self, stall_timeout: Duration) -> Result<(), Error> {
+        // The head is on the private fork, which must not leak before it's released.
+        #[cfg(feature = "adversarial")]
+        if !self.adv_private_fork.is_empty() {
+            return Ok(());
+        }
+
         if Clock::instant() > self.last_time_head_progress_made + stall_timeout
             && !self.sync_status.is_syncing()
         {
@@ -219,11 +267,16 @@ impl Client {
                     shard_id,
                     true,
                 ) {
-                    self.shards_mgr.remove_transactions(
-                        shard_id,
-                        // By now the chunk must be in store, otherwise the block would have been orphaned
-                        self.chain.get_chunk(&chunk_header.chunk_hash()).unwrap().transactions(),
-                    );
+                    // By now the chunk must be in store, otherwise the block would have been orphaned
+                    let chunk = self.chain.get_chunk(&chunk_header.chunk_hash()).unwrap();
+                    for tx in chunk.transactions() {
+                        if let Some(forwarded_tx) =
+                            self.forwarded_transactions.peek_mut(&tx.get_hash())
+                        {
+                            forwarded_tx.included = true;
+                        }
+                    }
+                    self.shards_mgr.remove_transactions(shard_id, chunk.transactions());
                 }
             }
         }
@@ -329,6 +382,65 @@ impl Client {
         Ok(false)
     }
 
+    /// Returns `None` if block production doesn't need to wait for any chunks on top of `head`,
+    /// otherwise how much longer than usual to wait for them.  Chunks whose producer is
+    /// unreachable are not waited for.
+    pub fn missing_chunks_wait(
+        &mut self,
+        head: &Tip,
+        epoch_id: &EpochId,
+    ) -> Result<Option<Duration>, Error> {
+        let num_shards = self.runtime_adapter.num_shards(epoch_id)?;
+        if head.height == 0
+            || self.shards_mgr.num_chunks_for_block(&head.last_block_hash) == num_shards
+        {
+            return Ok(None);
+        }
+        let new_chunks = self.shards_mgr.prepare_chunks(&head.last_block_hash);
+        let mut wait = None;
+        for shard_id in 0..num_shards {
+            if new_chunks.contains_key(&shard_id) {
+                continue;
+            }
+            let chunk_producer =
+                self.runtime_adapter.get_chunk_producer(epoch_id, head.height + 1, shard_id)?;
+            if self.missed_chunks_tracker.is_unreachable(&chunk_producer) {
+                continue;
+            }
+            let extension = self.missed_chunks_tracker.chunk_wait_extension(shard_id, &self.config);
+            wait = Some(std::cmp::max(wait.unwrap_or_default(), extension));
+        }
+        Ok(wait)
+    }
+
+    /// Sets chunk producers the network layer has no route to.
+    pub fn set_unreachable_chunk_producers(&mut self, unreachable_producers: HashSet<AccountId>) {
+        self.missed_chunks_tracker.set_unreachable_producers(unreachable_producers);
+    }
+
+    /// Records which shards got a new chunk in the new head `block`.
+    fn record_missed_chunks(&mut self, block: &Block) {
+        let prev_height = match self.chain.get_block_header(block.header().prev_hash()) {
+            Ok(prev_header) => prev_header.height(),
+            Err(_) => return,
+        };
+        let epoch_id = block.header().epoch_id();
+        for (shard_id, &included) in block.header().chunk_mask().iter().enumerate() {
+            let shard_id = shard_id as ShardId;
+            let chunk_producer = unwrap_or_return!(self.runtime_adapter.get_chunk_producer(
+                epoch_id,
+                prev_height + 1,
+                shard_id
+            ));
+            self.missed_chunks_tracker.record_chunk(
+                shard_id,
+                included,
+                &chunk_producer,
+                &self.config,
+            );
+        }
+    }
+
     /// Produce block if we are block producer for given `next_height` block height.
     /// Either returns produced block (not applied) or error.
     pub fn produce_block(&mut self, next_height: BlockHeight) -> Result<Option<Block>, Error> {
@@ -527,6 +639,83 @@ impl Client {
         Ok(Some(block))
     }
 
+    /// Produces `num_blocks` blocks on top of the head and keeps them in `adv_private_fork`
+    /// without sending them out.  Returns the number of produced blocks.
+    ///
+    /// The blocks are stored, but the head and Doomslug are restored afterwards: the node keeps
+    /// building on, approving and serving the public chain, and doesn't produce chunks for the
+    /// withheld blocks, so that nothing about them leaks before they are released.
+    #[cfg(feature = "adversarial")]
+    pub fn adv_produce_private_fork(&mut self, num_blocks: u64) -> Result<u64, Error> {
+        let head = self.chain.head()?;
+        let header_head = self.chain.header_head()?;
+        let final_head = self.chain.final_head()?;
+        let doomslug = self.doomslug.clone();
+        let produce_blocks = self.adv_produce_blocks;
+        let produce_blocks_only_valid = self.adv_produce_blocks_only_valid;
+        // Other validators only accept blocks produced by the proper block producer.
+        self.adv_produce_blocks = true;
+        self.adv_produce_blocks_only_valid = true;
+
+        let result = self.adv_produce_private_blocks(num_blocks);
+
+        self.adv_produce_blocks = produce_blocks;
+        self.adv_produce_blocks_only_valid = produce_blocks_only_valid;
+        self.doomslug = doomslug;
+        let mut chain_store_update = self.chain.mut_store().store_update();
+        chain_store_update.save_body_head(&head)?;
+        chain_store_update.save_header_head_if_not_challenged(&header_head)?;
+        chain_store_update.save_final_head(&final_head)?;
+        chain_store_update.commit()?;
+        result
+    }
+
+    #[cfg(feature = "adversarial")]
+    fn adv_produce_private_blocks(&mut self, num_blocks: u64) -> Result<u64, Error> {
+        /// Heights to try producing the blocks at, so that a node which isn't a block producer
+        /// doesn't keep trying forever.
+        const MAX_HEIGHTS: BlockHeight = 1000;
+        let me = self.validator_signer.as_ref().map(|signer| signer.validator_id().clone());
+        let start_height = self.chain.mut_store().get_latest_known()?.height + 1;
+        let mut num_produced = 0;
+        for height in start_height..start_height + MAX_HEIGHTS {
+            if num_produced == num_blocks {
+                break;
+            }
+            let block = match self.produce_block(height)? {
+                Some(block) => block,
+                None => continue,
+            };
+            info!(target: "adversary", "Withholding block {} at height {}", block.hash(), height);
+            // Processed directly by the chain, so that nothing is sent out for the block.
+            self.chain.process_block(
+                &me,
+                MaybeValidated::from_validated(block.clone()),
+                Provenance::PRODUCED,
+                &mut |_| {},
+                &mut |_| {},
+                &mut |_| {},
+                &mut |_| {},
+            )?;
+            self.adv_private_fork.push(block);
+            num_produced += 1;
+        }
+        Ok(num_produced)
+    }
+
+    /// Another block at the height of `block` signed by us, which differs from it only in the
+    /// timestamp.
+    #[cfg(feature = "adversarial")]
+    pub fn adv_double_signed_block(&self, block: &Block) -> Result<Block, Error> {
+        let validator_signer = self.validator_signer.as_ref().ok_or_else(|| {
+            Error::BlockProducer("Called without block producer info.".to_string())
+        })?;
+        let mut block = block.clone();
+        block.mut_header().get_mut().inner_lite.timestamp += 1;
+        block.mut_header().resign(&**validator_signer);
+        Ok(block)
+    }
+
     pub fn produce_chunk(
         &mut self,
         prev_block_hash: CryptoHash,
@@ -602,10 +791,19 @@ impl Client {
             Chain::build_receipts_hashes(&outgoing_receipts, &shard_layout);
         let (outgoing_receipts_root, _) = merklize(&outgoing_receipts_hashes);
 
+        #[allow(unused_mut)]
+        let mut state_root = *chunk_extra.state_root();
+        #[cfg(feature = "adversarial")]
+        if self.adv_invalid_state_root_chunks > 0 {
+            self.adv_invalid_state_root_chunks -= 1;
+            info!(target: "adversary", "Producing chunk at height {} for shard {} with an invalid state root", next_height, shard_id);
+            state_root = near_primitives::hash::hash(&state_root.0);
+        }
+
         let protocol_version = self.runtime_adapter.get_epoch_protocol_version(epoch_id)?;
         let (encoded_chunk, merkle_paths) = ShardsManager::create_encoded_shard_chunk(
             prev_block_hash,
-            *chunk_extra.state_root(),
+            state_root,
             *chunk_extra.outcome_root(),
             next_height,
             shard_id,
@@ -764,7 +962,7 @@ impl Client {
                     near_chain::ErrorKind::InvalidChunkState(chunk_state) => {
                         self.network_adapter.do_send(PeerManagerMessageRequest::NetworkRequests(
                             NetworkRequests::Challenge(Challenge::produce(
-                                ChallengeBody::ChunkState(*chunk_state),
+                                ChallengeBody::VersionedChunkState(*chunk_state),
                                 &**validator_signer,
                             )),
                         ));
@@ -960,6 +1158,34 @@ impl Client {
         Ok(())
     }
 
+    /// Sends the approvals due on the doomslug timer, skips of the heights the blocks of which
+    /// haven't arrived in time included.
+    pub fn process_doomslug_timer(&mut self) {
+        let _ = self.check_and_update_doomslug_tip();
+        let approvals = self.doomslug.process_timer(Clock::instant());
+
+        // Important to save the largest approval target height before sending approvals, so
+        // that if the node crashes in the meantime, we cannot get slashed on recovery
+        let mut chain_store_update = self.chain.mut_store().store_update();
+        chain_store_update.save_largest_target_height(self.doomslug.get_largest_target_height());
+
+        match chain_store_update.commit() {
+            Ok(_) => {
+                let head = unwrap_or_return!(self.chain.head());
+                if self.is_validator(&head.epoch_id, &head.last_block_hash)
+                    || self.is_validator(&head.next_epoch_id, &head.last_block_hash)
+                {
+                    for approval in approvals {
+                        if let Err(e) = self.send_approval(&self.doomslug.get_tip().0, approval) {
+                            error!("Error while sending an approval {:?}", e);
+                        }
+                    }
+                }
+            }
+            Err(e) => error!("Error while committing largest skipped height {:?}", e),
+        };
+    }
+
     pub fn send_approval(
         &mut self,
         parent_hash: &CryptoHash,
@@ -993,6 +1219,11 @@ impl Client {
         self.on_block_accepted_with_optional_chunk_produce(block_hash, status, provenance, false);
     }
 
+    /// Subscribes to the hashes of the blocks which become final from now on.
+    pub fn subscribe_final_blocks(&self) -> tokio::sync::broadcast::Receiver<CryptoHash> {
+        self.final_blocks_notifier.subscribe()
+    }
+
     pub fn record_accepted_block(&mut self, block_hash: &CryptoHash, chunks: &[ChunkHash]) {
         self.chunks_delay_tracker.finish_block_processing(block_hash, chunks);
     }
@@ -1040,6 +1271,7 @@ impl Client {
 
         if status.is_new_head() {
             self.shards_mgr.update_largest_seen_height(block.header().height());
+            self.record_missed_chunks(&block);
             let last_final_block = block.header().last_final_block();
             let last_finalized_height = if last_final_block == &CryptoHash::default() {
                 self.chain.genesis().height()
@@ -1047,6 +1279,9 @@ impl Client {
                 self.chain.get_block_header(last_final_block).map_or(0, |header| header.height())
             };
             self.chain.blocks_with_missing_chunks.prune_blocks_below_height(last_finalized_height);
+            if last_final_block != &CryptoHash::default() {
+                self.final_blocks_notifier.notify(&mut self.chain, last_final_block);
+            }
             if !self.config.archive {
                 let timer = metrics::GC_TIME.start_timer();
                 if let Err(err) = self
@@ -1057,6 +1292,11 @@ impl Client {
                     debug_assert!(false);
                 };
                 timer.observe_duration();
+                if let Some(epochs) = self.config.prune_outcomes_older_than_epochs {
+                    if let Err(err) = self.chain.prune_outcomes_step(epochs) {
+                        error!(target: "client", "Can't prune old outcomes, {:?}", err);
+                    }
+                }
             }
 
             if self.runtime_adapter.is_next_block_epoch_start(block.hash()).unwrap_or(false) {
@@ -1424,16 +1664,19 @@ impl Client {
     }
 
     /// Forwards given transaction to upcoming validators.
-    fn forward_tx(&self, epoch_id: &EpochId, tx: &SignedTransaction) -> Result<(), Error> {
+    fn forward_tx(&mut self, epoch_id: &EpochId, tx: &SignedTransaction) -> Result<(), Error> {
         let shard_id =
             self.runtime_adapter.account_id_to_shard_id(&tx.transaction.signer_id, epoch_id)?;
         let head = self.chain.head()?;
         let maybe_next_epoch_id = self.get_next_epoch_id_if_at_boundary(&head)?;
 
-        let mut validators = HashSet::new();
+        let mut validators = BTreeSet::new();
+        let mut heights = vec![];
         for horizon in
             (2..=TX_ROUTING_HEIGHT_HORIZON).chain(vec![TX_ROUTING_HEIGHT_HORIZON * 2].into_iter())
         {
+            // Same target height as in `find_chunk_producer_for_forwarding`.
+            heights.push(head.height + horizon - 1);
             let validator =
                 self.chain.find_chunk_producer_for_forwarding(epoch_id, shard_id, horizon)?;
             validators.insert(validator);
@@ -1453,7 +1696,7 @@ impl Client {
         if let Some(account_id) = self.validator_signer.as_ref().map(|bp| bp.validator_id()) {
             validators.remove(account_id);
         }
-        for validator in validators {
+        for validator in validators.iter() {
             debug!(target: "client",
                    "I'm {:?}, routing a transaction {:?} to {}, shard_id = {}",
                    self.validator_signer.as_ref().map(|bp| bp.validator_id()),
@@ -1464,13 +1707,50 @@ impl Client {
 
             // Send message to network to actually forward transaction.
             self.network_adapter.do_send(PeerManagerMessageRequest::NetworkRequests(
-                NetworkRequests::ForwardTx(validator, tx.clone()),
+                NetworkRequests::ForwardTx(validator.clone(), tx.clone()),
             ));
         }
 
+        self.forwarded_transactions.put(
+            tx.get_hash(),
+            ForwardedTransactionView {
+                transaction_hash: tx.get_hash(),
+                signer_id: tx.transaction.signer_id.clone(),
+                routing: TransactionRoutingView {
+                    shard_id,
+                    forwarded_for_heights: heights,
+                    chunk_producers: validators.into_iter().collect(),
+                },
+                included: false,
+            },
+        );
         Ok(())
     }
 
+    /// Returns the shard of the transaction signer and where the transaction was forwarded to, if
+    /// it was forwarded recently.
+    pub fn transaction_routing(
+        &self,
+        tx_hash: &CryptoHash,
+        signer_id: &AccountId,
+    ) -> Result<TransactionRoutingView, Error> {
+        if let Some(forwarded_tx) = self.forwarded_transactions.peek(tx_hash) {
+            return Ok(forwarded_tx.routing.clone());
+        }
+        let head = self.chain.head()?;
+        let epoch_id = self.runtime_adapter.get_epoch_id_from_prev_block(&head.last_block_hash)?;
+        Ok(TransactionRoutingView {
+            shard_id: self.runtime_adapter.account_id_to_shard_id(signer_id, &epoch_id)?,
+            forwarded_for_heights: vec![],
+            chunk_producers: vec![],
+        })
+    }
+
+    /// Lists up to `limit` of the most recently forwarded transactions, the latest first.
+    pub fn forwarded_transactions(&self, limit: usize) -> Vec<ForwardedTransactionView> {
+        self.forwarded_transactions.iter().take(limit).map(|(_, tx)| tx.clone()).collect()
+    }
+
     pub fn process_tx(
         &mut self,
         tx: SignedTransaction,
@@ -1593,7 +1873,28 @@ impl Client {
                     shard_id,
                     is_forwarded
                 );
-                self.shards_mgr.insert_transaction(shard_id, tx.clone());
+                // A transaction rejected by the pool isn't forwarded either, so chunk producers
+                // see the same replacements as this node.
+                let err = match self.shards_mgr.insert_transaction(shard_id, tx.clone()) {
+                    InsertTransactionResult::Success
+                    | InsertTransactionResult::Duplicate
+                    | InsertTransactionResult::Replaced { .. } => None,
+                    InsertTransactionResult::Underpriced { required_priority_fee } => {
+                        Some(InvalidTxError::ReplacementUnderpriced {
+                            priority_fee: tx.transaction.priority_fee.unwrap_or(0),
+                            required_priority_fee,
+                        })
+                    }
+                    InsertTransactionResult::AccountTransactionsLimitExceeded
+                    | InsertTransactionResult::AccountBytesLimitExceeded
+                    | InsertTransactionResult::PoolFull => {
+                        Some(InvalidTxError::TransactionPoolLimitExceeded)
+                    }
+                };
+                if let Some(err) = err {
+                    debug!(target: "client", "Tx rejected by the pool: {:?}", err);
+                    return Ok(NetworkClientResponses::InvalidTx(err));
+                }
 
                 // Active validator:
                 //   possibly forward to next epoch validators
@@ -1771,6 +2072,118 @@ impl Client {
         Ok(vec![])
     }
 
+    /// Handles a state header or a state part, which is either requested by the state sync or
+    /// by one of the catchups.
+    pub fn process_state_response(&mut self, state_response_info: StateResponseInfo) {
+        let shard_id = state_response_info.shard_id();
+        let hash = state_response_info.sync_hash();
+        let state_response = state_response_info.take_state_response();
+
+        trace!(target: "sync", "Received state response shard_id: {} sync_hash: {:?} part(id/size): {:?}",
+               shard_id,
+               hash,
+               state_response.part().as_ref().map(|(part_id, data)| (part_id, data.len()))
+        );
+        // Get the download that matches the shard_id and hash
+        let download = {
+            let mut download: Option<&mut ShardSyncDownload> = None;
+
+            // ... It could be that the state was requested by the state sync
+            if let SyncStatus::StateSync(sync_hash, shards_to_download) = &mut self.sync_status {
+                if hash == *sync_hash {
+                    if let Some(part_id) = state_response.part_id() {
+                        self.state_sync.received_requested_part(part_id, shard_id, hash);
+                    }
+
+                    if let Some(shard_download) = shards_to_download.get_mut(&shard_id) {
+                        assert!(download.is_none(), "Internal downloads set has duplicates");
+                        download = Some(shard_download);
+                    } else {
+                        // This may happen because of sending too many StateRequests to different peers.
+                        // For example, we received StateResponse after StateSync completion.
+                    }
+                }
+            }
+
+            // ... Or one of the catchups
+            if let Some((_, shards_to_download, _)) = self.catchup_state_syncs.get_mut(&hash) {
+                if let Some(part_id) = state_response.part_id() {
+                    self.state_sync.received_requested_part(part_id, shard_id, hash);
+                }
+
+                if let Some(shard_download) = shards_to_download.get_mut(&shard_id) {
+                    assert!(download.is_none(), "Internal downloads set has duplicates");
+                    download = Some(shard_download);
+                } else {
+                    // This may happen because of sending too many StateRequests to different peers.
+                    // For example, we received StateResponse after StateSync completion.
+                }
+            }
+            // We should not be requesting the same state twice.
+            download
+        };
+
+        if let Some(shard_sync_download) = download {
+            match shard_sync_download.status {
+                ShardSyncStatus::StateDownloadHeader => {
+                    if let Some(header) = state_response.take_header() {
+                        if !shard_sync_download.downloads[0].done {
+                            match self.chain.set_state_header(shard_id, hash, header) {
+                                Ok(()) => {
+                                    shard_sync_download.downloads[0].done = true;
+                                }
+                                Err(err) => {
+                                    error!(target: "sync", "State sync set_state_header error, shard = {}, hash = {}: {:?}", shard_id, hash, err);
+                                    shard_sync_download.downloads[0].error = true;
+                                }
+                            }
+                        }
+                    } else {
+                        // No header found.
+                        // It may happen because requested node couldn't build state response.
+                        if !shard_sync_download.downloads[0].done {
+                            info!(target: "sync", "state_response doesn't have header, should be re-requested, shard = {}, hash = {}", shard_id, hash);
+                            shard_sync_download.downloads[0].error = true;
+                        }
+                    }
+                }
+                ShardSyncStatus::StateDownloadParts => {
+                    if let Some(part) = state_response.take_part() {
+                        let num_parts = shard_sync_download.downloads.len() as u64;
+                        let (part_id, data) = part;
+                        if part_id >= num_parts {
+                            error!(target: "sync", "State sync received incorrect part_id # {:?} for hash {:?}, potential malicious peer", part_id, hash);
+                            return;
+                        }
+                        if !shard_sync_download.downloads[part_id as usize].done {
+                            match self.chain.set_state_part(
+                                shard_id,
+                                hash,
+                                PartId::new(part_id, num_parts),
+                                &data,
+                            ) {
+                                Ok(()) => {
+                                    shard_sync_download.downloads[part_id as usize].done = true;
+                                }
+                                Err(err) => {
+                                    error!(target: "sync", "State sync set_state_part error, shard = {}, part = {}, hash = {}: {:?}", shard_id, part_id, hash, err);
+                                    if err.kind() == near_chain::ErrorKind::InvalidStatePayload {
+                                        self.state_sync
+                                            .invalid_part_received(part_id, shard_id, hash);
+                                    }
+                                    shard_sync_download.downloads[part_id as usize].error = true;
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        } else {
+            error!(target: "sync", "State sync received hash {} that we're not expecting, potential malicious peer", hash);
+        }
+    }
+
     /// When accepting challenge, we verify that it's valid given signature with current validators.
     pub fn process_challenge(&mut self, _challenge: Challenge) -> Result<(), Error> {
         // TODO(2445): Enable challenges when they are working correctly.
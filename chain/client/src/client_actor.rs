@@ -2,13 +2,14 @@
 
 use crate::client::Client;
 use crate::info::{
-    display_sync_status, get_validator_epoch_stats, InfoHelper, ValidatorInfoHelper,
+    display_sync_status, display_trie_cache_stats, get_validator_epoch_stats, InfoHelper,
+    ValidatorInfoHelper,
 };
 use crate::metrics::PARTIAL_ENCODED_CHUNK_RESPONSE_DELAY;
 use crate::sync::{StateSync, StateSyncResult};
-use crate::{metrics, StatusResponse};
+use crate::{metrics, rocksdb_metrics, StatusResponse};
 use actix::dev::SendError;
-use actix::{Actor, Addr, Arbiter, AsyncContext, Context, Handler, Message};
+use actix::{Actor, Addr, Arbiter, AsyncContext, Context, Handler, Message, MessageResult};
 use actix_rt::ArbiterHandle;
 use borsh::BorshSerialize;
 use chrono::DateTime;
@@ -25,14 +26,14 @@ use near_chain::{
 };
 use near_chain_configs::ClientConfig;
 use near_client_primitives::types::{
-    Error, GetNetworkInfo, NetworkInfoResponse, ShardSyncDownload, ShardSyncStatus, Status,
-    StatusError, StatusSyncInfo, SyncStatus,
+    Error, GetForwardedTransactions, GetNetworkInfo, GetTransactionRouting, ManagePeerBan,
+    NetworkInfoResponse, Status, StatusError, StatusSyncInfo, SubscribeFinalBlocks, SyncStatus,
 };
 use near_network::types::{
     NetworkClientMessages, NetworkClientResponses, NetworkInfo, NetworkRequests,
     PeerManagerAdapter, PeerManagerMessageRequest,
 };
-use near_network_primitives::types::ReasonForBan;
+use near_network_primitives::types::{ReasonForBan, StateRequestRejected};
 use near_performance_metrics;
 use near_performance_metrics_macros::{perf, perf_with_debug};
 use near_primitives::block_header::ApprovalType;
@@ -42,13 +43,13 @@ use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::state_part::PartId;
 use near_primitives::syncing::StatePartKey;
 use near_primitives::time::{Clock, Utc};
-use near_primitives::types::BlockHeight;
+use near_primitives::types::{BlockHeight, EpochId};
 use near_primitives::unwrap_or_return;
 use near_primitives::utils::{from_timestamp, MaybeValidated};
 use near_primitives::validator_signer::ValidatorSigner;
 use near_primitives::version::PROTOCOL_VERSION;
 use near_primitives::views::{
-    DebugBlockStatus, DebugChunkStatus, DetailedDebugStatus, ValidatorInfo,
+    DebugBlockStatus, DebugChunkStatus, DetailedDebugStatus, TransactionRoutingView, ValidatorInfo,
 };
 use near_store::db::DBCol::ColStateParts;
 use near_telemetry::TelemetryActor;
@@ -59,7 +60,7 @@ use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 use tokio::sync::oneshot;
-use tracing::{debug, error, info, trace, warn};
+use tracing::{debug, error, info, warn};
 
 /// Multiplier on `max_block_time` to wait until deciding that chain stalled.
 const STATUS_WAIT_TIME_MULTIPLIER: u64 = 10;
@@ -82,6 +83,8 @@ pub struct ClientActor {
     node_id: PeerId,
     /// Last time we announced our accounts as validators.
     last_validator_announce_time: Option<Instant>,
+    /// Epoch for which the TIER1 accounts were last sent to the network.
+    last_tier1_epoch_id: Option<EpochId>,
     /// Info helper.
     info_helper: InfoHelper,
 
@@ -183,8 +186,11 @@ impl ClientActor {
                 sent_bytes_per_sec: 0,
                 known_producers: vec![],
                 peer_counter: 0,
+                banned_peers: vec![],
+                recent_dropped_routed_messages: vec![],
             },
             last_validator_announce_time: None,
+            last_tier1_epoch_id: None,
             info_helper,
             block_production_next_attempt: now,
             log_summary_timer_next_attempt: now,
@@ -366,6 +372,49 @@ impl ClientActor {
                             NetworkClientResponses::AdvResult(store_validator.tests_done())
                         }
                     }
+                    #[cfg(feature = "adversarial")]
+                    near_network_primitives::types::NetworkAdversarialMessage::AdvProducePrivateFork(num_blocks) => {
+                        info!(target: "adversary", "Producing {} blocks on a private fork", num_blocks);
+                        match self.client.adv_produce_private_fork(num_blocks) {
+                            Ok(num_produced) => NetworkClientResponses::AdvResult(num_produced),
+                            Err(err) => {
+                                error!(target: "adversary", "Failed to produce the private fork: {}", err);
+                                NetworkClientResponses::NoResponse
+                            }
+                        }
+                    }
+                    #[cfg(feature = "adversarial")]
+                    near_network_primitives::types::NetworkAdversarialMessage::AdvReleasePrivateFork => {
+                        let blocks = std::mem::take(&mut self.client.adv_private_fork);
+                        info!(target: "adversary", "Releasing {} blocks of the private fork", blocks.len());
+                        let num_blocks = blocks.len() as u64;
+                        for block in blocks {
+                            self.network_adapter.do_send(
+                                PeerManagerMessageRequest::NetworkRequests(
+                                    NetworkRequests::Block { block },
+                                ),
+                            );
+                        }
+                        NetworkClientResponses::AdvResult(num_blocks)
+                    }
+                    #[cfg(feature = "adversarial")]
+                    near_network_primitives::types::NetworkAdversarialMessage::AdvDoubleSign => {
+                        info!(target: "adversary", "Double signing the next produced block");
+                        self.client.adv_double_sign = true;
+                        NetworkClientResponses::NoResponse
+                    }
+                    #[cfg(feature = "adversarial")]
+                    near_network_primitives::types::NetworkAdversarialMessage::AdvProduceInvalidStateRootChunk => {
+                        info!(target: "adversary", "Producing the next chunk with an invalid state root");
+                        self.client.adv_invalid_state_root_chunks += 1;
+                        NetworkClientResponses::NoResponse
+                    }
+                    #[cfg(feature = "adversarial")]
+                    near_network_primitives::types::NetworkAdversarialMessage::AdvDisableDoomslugTimeouts => {
+                        info!(target: "adversary", "Turning Doomslug timeouts off");
+                        self.client.doomslug.adv_disable_timeouts();
+                        NetworkClientResponses::NoResponse
+                    }
                     _ => panic!("invalid adversary message"),
                 };
             }
@@ -449,122 +498,37 @@ impl ClientActor {
                 NetworkClientResponses::NoResponse
             }
             NetworkClientMessages::StateResponse(state_response_info) => {
-                let shard_id = state_response_info.shard_id();
-                let hash = state_response_info.sync_hash();
-                let state_response = state_response_info.take_state_response();
-
-                trace!(target: "sync", "Received state response shard_id: {} sync_hash: {:?} part(id/size): {:?}",
-                       shard_id,
-                       hash,
-                       state_response.part().as_ref().map(|(part_id, data)| (part_id, data.len()))
-                );
-                // Get the download that matches the shard_id and hash
-                let download = {
-                    let mut download: Option<&mut ShardSyncDownload> = None;
-
-                    // ... It could be that the state was requested by the state sync
-                    if let SyncStatus::StateSync(sync_hash, shards_to_download) =
-                        &mut self.client.sync_status
-                    {
-                        if hash == *sync_hash {
-                            if let Some(part_id) = state_response.part_id() {
-                                self.client
-                                    .state_sync
-                                    .received_requested_part(part_id, shard_id, hash);
-                            }
-
-                            if let Some(shard_download) = shards_to_download.get_mut(&shard_id) {
-                                assert!(
-                                    download.is_none(),
-                                    "Internal downloads set has duplicates"
-                                );
-                                download = Some(shard_download);
-                            } else {
-                                // This may happen because of sending too many StateRequests to different peers.
-                                // For example, we received StateResponse after StateSync completion.
-                            }
-                        }
+                self.client.process_state_response(state_response_info);
+                NetworkClientResponses::NoResponse
+            }
+            NetworkClientMessages::StateRequestRejected(rejected) => {
+                debug!(target: "sync", ?rejected, "State request rejected by peer");
+                let StateRequestRejected { shard_id, sync_hash, part_id, retry_after_ms } =
+                    rejected;
+                let mut download = None;
+                if let SyncStatus::StateSync(hash, shards_to_download) =
+                    &mut self.client.sync_status
+                {
+                    if *hash == sync_hash {
+                        download = shards_to_download.get_mut(&shard_id);
                     }
-
-                    // ... Or one of the catchups
+                }
+                if download.is_none() {
                     if let Some((_, shards_to_download, _)) =
-                        self.client.catchup_state_syncs.get_mut(&hash)
+                        self.client.catchup_state_syncs.get_mut(&sync_hash)
                     {
-                        if let Some(part_id) = state_response.part_id() {
-                            self.client.state_sync.received_requested_part(part_id, shard_id, hash);
-                        }
-
-                        if let Some(shard_download) = shards_to_download.get_mut(&shard_id) {
-                            assert!(download.is_none(), "Internal downloads set has duplicates");
-                            download = Some(shard_download);
-                        } else {
-                            // This may happen because of sending too many StateRequests to different peers.
-                            // For example, we received StateResponse after StateSync completion.
-                        }
+                        download = shards_to_download.get_mut(&shard_id);
                     }
-                    // We should not be requesting the same state twice.
-                    download
-                };
-
+                }
                 if let Some(shard_sync_download) = download {
-                    match shard_sync_download.status {
-                        ShardSyncStatus::StateDownloadHeader => {
-                            if let Some(header) = state_response.take_header() {
-                                if !shard_sync_download.downloads[0].done {
-                                    match self.client.chain.set_state_header(shard_id, hash, header)
-                                    {
-                                        Ok(()) => {
-                                            shard_sync_download.downloads[0].done = true;
-                                        }
-                                        Err(err) => {
-                                            error!(target: "sync", "State sync set_state_header error, shard = {}, hash = {}: {:?}", shard_id, hash, err);
-                                            shard_sync_download.downloads[0].error = true;
-                                        }
-                                    }
-                                }
-                            } else {
-                                // No header found.
-                                // It may happen because requested node couldn't build state response.
-                                if !shard_sync_download.downloads[0].done {
-                                    info!(target: "sync", "state_response doesn't have header, should be re-requested, shard = {}, hash = {}", shard_id, hash);
-                                    shard_sync_download.downloads[0].error = true;
-                                }
-                            }
-                        }
-                        ShardSyncStatus::StateDownloadParts => {
-                            if let Some(part) = state_response.take_part() {
-                                let num_parts = shard_sync_download.downloads.len() as u64;
-                                let (part_id, data) = part;
-                                if part_id >= num_parts {
-                                    error!(target: "sync", "State sync received incorrect part_id # {:?} for hash {:?}, potential malicious peer", part_id, hash);
-                                    return NetworkClientResponses::NoResponse;
-                                }
-                                if !shard_sync_download.downloads[part_id as usize].done {
-                                    match self.client.chain.set_state_part(
-                                        shard_id,
-                                        hash,
-                                        PartId::new(part_id, num_parts),
-                                        &data,
-                                    ) {
-                                        Ok(()) => {
-                                            shard_sync_download.downloads[part_id as usize].done =
-                                                true;
-                                        }
-                                        Err(err) => {
-                                            error!(target: "sync", "State sync set_state_part error, shard = {}, part = {}, hash = {}: {:?}", shard_id, part_id, hash, err);
-                                            shard_sync_download.downloads[part_id as usize].error =
-                                                true;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        _ => {}
-                    }
-                } else {
-                    error!(target: "sync", "State sync received hash {} that we're not expecting, potential malicious peer", hash);
+                    self.client.state_sync.request_rejected(
+                        shard_sync_download,
+                        part_id,
+                        shard_id,
+                        sync_hash,
+                        Duration::from_millis(retry_after_ms),
+                    );
                 }
-
                 NetworkClientResponses::NoResponse
             }
             NetworkClientMessages::EpochSyncResponse(_peer_id, _response) => {
@@ -623,6 +587,14 @@ impl ClientActor {
                 NetworkClientResponses::NoResponse
             }
             NetworkClientMessages::NetworkInfo(network_info) => {
+                self.client.set_unreachable_chunk_producers(
+                    network_info
+                        .known_producers
+                        .iter()
+                        .filter(|producer| !producer.reachable)
+                        .map(|producer| producer.account_id.clone())
+                        .collect(),
+                );
                 self.network_info = network_info;
                 NetworkClientResponses::NoResponse
             }
@@ -814,6 +786,14 @@ impl Handler<Status> for ClientActor {
     }
 }
 
+impl Handler<SubscribeFinalBlocks> for ClientActor {
+    type Result = MessageResult<SubscribeFinalBlocks>;
+
+    fn handle(&mut self, _msg: SubscribeFinalBlocks, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.client.subscribe_final_blocks())
+    }
+}
+
 impl Handler<GetNetworkInfo> for ClientActor {
     type Result = Result<NetworkInfoResponse, String>;
 
@@ -831,10 +811,46 @@ impl Handler<GetNetworkInfo> for ClientActor {
             sent_bytes_per_sec: self.network_info.sent_bytes_per_sec,
             received_bytes_per_sec: self.network_info.received_bytes_per_sec,
             known_producers: self.network_info.known_producers.clone(),
+            banned_peers: self.network_info.banned_peers.clone(),
+            recent_dropped_routed_messages: (self.network_info.recent_dropped_routed_messages)
+                .clone(),
         })
     }
 }
 
+impl Handler<ManagePeerBan> for ClientActor {
+    type Result = ();
+
+    #[perf]
+    fn handle(&mut self, msg: ManagePeerBan, _ctx: &mut Context<Self>) {
+        let request = match msg {
+            ManagePeerBan::Ban { peer_id, ban_duration } => {
+                NetworkRequests::BanPeerManually { peer_id, ban_duration }
+            }
+            ManagePeerBan::Unban { peer_id } => NetworkRequests::UnbanPeer { peer_id },
+        };
+        self.network_adapter.do_send(PeerManagerMessageRequest::NetworkRequests(request));
+    }
+}
+
+impl Handler<GetTransactionRouting> for ClientActor {
+    type Result = Result<TransactionRoutingView, String>;
+
+    #[perf]
+    fn handle(&mut self, msg: GetTransactionRouting, _ctx: &mut Context<Self>) -> Self::Result {
+        self.client.transaction_routing(&msg.tx_hash, &msg.signer_id).map_err(|err| err.to_string())
+    }
+}
+
+impl Handler<GetForwardedTransactions> for ClientActor {
+    type Result = MessageResult<GetForwardedTransactions>;
+
+    #[perf]
+    fn handle(&mut self, msg: GetForwardedTransactions, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.client.forwarded_transactions(msg.limit))
+    }
+}
+
 impl ClientActor {
     /// Check if client Account Id should be sent and send it.
     /// Account Id is sent when is not current a validator but are becoming a validator soon.
@@ -890,6 +906,39 @@ impl ClientActor {
         }
     }
 
+    /// Sends block and chunk producers of the current and next epoch to the network,
+    /// so that validators can establish TIER1 connections between each other.
+    fn check_send_tier1_accounts(&mut self, prev_block_hash: CryptoHash) {
+        let epoch_id = unwrap_or_return!(self
+            .client
+            .runtime_adapter
+            .get_epoch_id_from_prev_block(&prev_block_hash));
+        if self.last_tier1_epoch_id.as_ref() == Some(&epoch_id) {
+            return;
+        }
+        let next_epoch_id = unwrap_or_return!(self
+            .client
+            .runtime_adapter
+            .get_next_epoch_id_from_prev_block(&prev_block_hash));
+
+        let mut accounts = vec![];
+        for epoch_id in [&epoch_id, &next_epoch_id] {
+            let producers = unwrap_or_return!(self
+                .client
+                .runtime_adapter
+                .get_epoch_block_producers_ordered(epoch_id, &prev_block_hash));
+            accounts.extend(producers.into_iter().map(|(stake, _)| stake.take_account_id()));
+        }
+        accounts.sort();
+        accounts.dedup();
+
+        debug!(target: "client", "Sending {} TIER1 accounts for epoch {:?}", accounts.len(), epoch_id);
+        self.last_tier1_epoch_id = Some(epoch_id.clone());
+        self.network_adapter.do_send(PeerManagerMessageRequest::NetworkRequests(
+            NetworkRequests::SetTier1Accounts { epoch_id, accounts },
+        ));
+    }
+
     /// Retrieves latest height, and checks if must produce next block.
     /// Otherwise wait for block arrival or suggest to skip after timeout.
     fn handle_block_production(&mut self) -> Result<(), Error> {
@@ -936,14 +985,13 @@ impl ClientActor {
             if self.client.validator_signer.as_ref().map(|bp| bp.validator_id())
                 == Some(&next_block_producer_account)
             {
-                let num_chunks = self.client.shards_mgr.num_chunks_for_block(&head.last_block_hash);
-                let have_all_chunks = head.height == 0
-                    || num_chunks == self.client.runtime_adapter.num_shards(&epoch_id).unwrap();
+                let missing_chunks_wait = self.client.missing_chunks_wait(&head, &epoch_id)?;
 
                 if self.client.doomslug.ready_to_produce_block(
                     Clock::instant(),
                     height,
-                    have_all_chunks,
+                    missing_chunks_wait.is_none(),
+                    missing_chunks_wait.unwrap_or_default(),
                 ) {
                     if let Err(err) = self.produce_block(height) {
                         // If there is an error, report it and let it retry on the next loop step.
@@ -1059,32 +1107,7 @@ impl ClientActor {
     }
 
     fn try_doomslug_timer(&mut self, _: &mut Context<ClientActor>) {
-        let _ = self.client.check_and_update_doomslug_tip();
-        let approvals = self.client.doomslug.process_timer(Clock::instant());
-
-        // Important to save the largest approval target height before sending approvals, so
-        // that if the node crashes in the meantime, we cannot get slashed on recovery
-        let mut chain_store_update = self.client.chain.mut_store().store_update();
-        chain_store_update
-            .save_largest_target_height(self.client.doomslug.get_largest_target_height());
-
-        match chain_store_update.commit() {
-            Ok(_) => {
-                let head = unwrap_or_return!(self.client.chain.head());
-                if self.client.is_validator(&head.epoch_id, &head.last_block_hash)
-                    || self.client.is_validator(&head.next_epoch_id, &head.last_block_hash)
-                {
-                    for approval in approvals {
-                        if let Err(e) =
-                            self.client.send_approval(&self.client.doomslug.get_tip().0, approval)
-                        {
-                            error!("Error while sending an approval {:?}", e);
-                        }
-                    }
-                }
-            }
-            Err(e) => error!("Error while committing largest skipped height {:?}", e),
-        };
+        self.client.process_doomslug_timer();
     }
 
     /// Produce block if we are block producer for given `next_height` height.
@@ -1092,6 +1115,14 @@ impl ClientActor {
     fn produce_block(&mut self, next_height: BlockHeight) -> Result<(), Error> {
         match self.client.produce_block(next_height) {
             Ok(Some(block)) => {
+                #[cfg(feature = "adversarial")]
+                if std::mem::take(&mut self.client.adv_double_sign) {
+                    let other_block = self.client.adv_double_signed_block(&block)?;
+                    info!(target: "adversary", "Double signing height {}: {} and {}", next_height, block.hash(), other_block.hash());
+                    self.network_adapter.do_send(PeerManagerMessageRequest::NetworkRequests(
+                        NetworkRequests::Block { block: other_block },
+                    ));
+                }
                 let peer_id = self.node_id.clone();
                 // We’ve produced the block so that counts as validated block.
                 let block = MaybeValidated::from_validated(block);
@@ -1142,6 +1173,7 @@ impl ClientActor {
 
             self.info_helper.block_processed(gas_used, chunks_in_block as u64);
             self.check_send_announce_account(last_final_hash);
+            self.check_send_tier1_accounts(last_final_hash);
         }
     }
 
@@ -1254,21 +1286,40 @@ impl ClientActor {
 
     fn receive_headers(&mut self, headers: Vec<BlockHeader>, peer_id: PeerId) -> bool {
         info!(target: "client", "Received {} block headers from {}", headers.len(), peer_id);
-        if headers.len() == 0 {
-            return true;
-        }
-        match self.client.sync_block_headers(headers) {
-            Ok(_) => true,
+        let headers = match self.client.header_sync.receive_headers(
+            &mut self.client.chain,
+            headers,
+            &peer_id,
+        ) {
+            Ok(headers) => headers,
             Err(err) => {
+                error!(target: "client", "Invalid header batch from {}: {:?}", peer_id, err);
+                return false;
+            }
+        };
+        // Adding the headers may connect the pipelined batches received earlier.
+        let mut batch = Some((peer_id.clone(), headers));
+        while let Some((batch_peer_id, headers)) = batch {
+            if let Err(err) = self.client.sync_block_headers(headers) {
                 if err.is_bad_data() {
-                    error!(target: "client", "Error processing sync blocks: {}", err);
-                    false
+                    error!(target: "client", "Error processing sync blocks from {}: {}", batch_peer_id, err);
+                    if batch_peer_id == peer_id {
+                        return false;
+                    }
+                    self.network_adapter.do_send(PeerManagerMessageRequest::NetworkRequests(
+                        NetworkRequests::BanPeer {
+                            peer_id: batch_peer_id,
+                            ban_reason: ReasonForBan::BadBlockHeader,
+                        },
+                    ));
                 } else {
                     debug!(target: "client", "Block headers refused by chain: {}", err);
-                    true
                 }
+                break;
             }
+            batch = self.client.header_sync.take_connected_batch(&mut self.client.chain);
         }
+        true
     }
 
     fn request_block_by_hash(&mut self, hash: CryptoHash, peer_id: PeerId) {
@@ -1483,6 +1534,7 @@ impl ClientActor {
                 // Announce this client's account id if their epoch is coming up.
                 let head = unwrap_or_run_later!(self.client.chain.head());
                 self.check_send_announce_account(head.prev_block_hash);
+                self.check_send_tier1_accounts(head.prev_block_hash);
             }
             wait_period = self.client.config.sync_check_period;
         } else {
@@ -1669,6 +1721,14 @@ impl ClientActor {
                 .unwrap_or(0),
             self.client.chain.store().get_store_statistics(),
         );
+        if let Some(properties) = self.client.chain.store().get_store_properties() {
+            rocksdb_metrics::export_properties_as_metrics(properties);
+        }
+        let trie_cache_stats = self.client.runtime_adapter.get_tries().take_cache_stats();
+        info!(target: "stats", "{}", display_trie_cache_stats(&trie_cache_stats));
+        if let Some(tracker) = self.client.runtime_adapter.contract_execution() {
+            metrics::export_top_contracts(&tracker.top(metrics::TOP_CONTRACTS_EXPORTED));
+        }
     }
 }
 
@@ -1684,28 +1744,28 @@ struct SyncJobsActor {
 
 impl SyncJobsActor {
     const MAILBOX_CAPACITY: usize = 100;
+}
 
-    fn apply_parts(
-        &mut self,
-        msg: &ApplyStatePartsRequest,
-    ) -> Result<(), near_chain_primitives::error::Error> {
-        let store = msg.runtime.get_store();
-
-        for part_id in 0..msg.num_parts {
-            let key = StatePartKey(msg.sync_hash, msg.shard_id, part_id).try_to_vec()?;
-            let part = store.get(ColStateParts, &key)?.unwrap();
-
-            msg.runtime.apply_state_part(
-                msg.shard_id,
-                &msg.state_root,
-                PartId::new(part_id, msg.num_parts),
-                &part,
-                &msg.epoch_id,
-            )?;
-        }
+/// Applies the downloaded state parts of a shard, the job behind `ApplyStatePartsRequest`.
+pub(crate) fn apply_state_parts(
+    msg: &ApplyStatePartsRequest,
+) -> Result<(), near_chain_primitives::error::Error> {
+    let store = msg.runtime.get_store();
 
-        Ok(())
+    for part_id in 0..msg.num_parts {
+        let key = StatePartKey(msg.sync_hash, msg.shard_id, part_id).try_to_vec()?;
+        let part = store.get(ColStateParts, &key)?.unwrap();
+
+        msg.runtime.apply_state_part(
+            msg.shard_id,
+            &msg.state_root,
+            PartId::new(part_id, msg.num_parts),
+            &part,
+            &msg.epoch_id,
+        )?;
     }
+
+    Ok(())
 }
 
 impl Actor for SyncJobsActor {
@@ -1716,7 +1776,7 @@ impl Handler<ApplyStatePartsRequest> for SyncJobsActor {
     type Result = ();
 
     fn handle(&mut self, msg: ApplyStatePartsRequest, _: &mut Self::Context) -> Self::Result {
-        let result = self.apply_parts(&msg);
+        let result = apply_state_parts(&msg);
 
         self.client_addr.do_send(ApplyStatePartsResponse {
             apply_result: result,
@@ -0,0 +1,59 @@
+use near_chain::Chain;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::BlockHeight;
+use tokio::sync::broadcast;
+
+/// Number of notifications a subscriber may fall behind before it misses some.
+const CAPACITY: usize = 1024;
+
+/// Notifies subscribers, like WebSocket connections of the JSON-RPC server, about the blocks
+/// which become final, in the order of their heights.
+pub(crate) struct FinalBlocksNotifier {
+    sender: broadcast::Sender<CryptoHash>,
+    /// Height of the last final block subscribers were notified about.
+    last_final_height: Option<BlockHeight>,
+}
+
+impl FinalBlocksNotifier {
+    pub fn new() -> Self {
+        Self { sender: broadcast::channel(CAPACITY).0, last_final_height: None }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<CryptoHash> {
+        self.sender.subscribe()
+    }
+
+    /// Notifies about `last_final_block` and about the blocks between it and the previous
+    /// final block, which became final together with it.
+    pub fn notify(&mut self, chain: &mut Chain, last_final_block: &CryptoHash) {
+        let header = match chain.get_block_header(last_final_block) {
+            Ok(header) => header.clone(),
+            Err(_) => return,
+        };
+        let last_final_height = match self.last_final_height {
+            Some(height) if height >= header.height() => return,
+            Some(height) => height,
+            None => header.height().saturating_sub(1),
+        };
+        self.last_final_height = Some(header.height());
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+
+        let mut final_blocks = vec![*header.hash()];
+        let mut prev_hash = *header.prev_hash();
+        while final_blocks.len() < CAPACITY {
+            match chain.get_block_header(&prev_hash) {
+                Ok(header) if header.height() > last_final_height => {
+                    final_blocks.push(*header.hash());
+                    prev_hash = *header.prev_hash();
+                }
+                _ => break,
+            }
+        }
+        for block_hash in final_blocks.into_iter().rev() {
+            // Fails only if all subscribers are gone.
+            let _ = self.sender.send(block_hash);
+        }
+    }
+}
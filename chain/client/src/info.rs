@@ -15,6 +15,8 @@ use near_primitives::validator_signer::ValidatorSigner;
 use near_primitives::version::{Version, DB_VERSION, PROTOCOL_VERSION};
 use near_primitives::views::{CurrentEpochValidatorInfo, EpochValidatorInfo, ValidatorKickoutView};
 use near_store::db::StoreStatistics;
+use near_store::metrics::shard_uid_label;
+use near_store::{ShardUId, TrieCacheStats};
 use near_telemetry::{telemetry, TelemetryActor};
 use std::cmp::min;
 use std::fmt::Write;
@@ -335,6 +337,24 @@ fn pretty_bytes(num: u64) -> String {
     }
 }
 
+/// Hit rates and sizes of the shard trie caches, e.g. `Trie caches: s0.v1 97.3% 12.0 MiB`.
+pub fn display_trie_cache_stats(stats: &[(ShardUId, TrieCacheStats)]) -> String {
+    let mut line = "Trie caches:".to_string();
+    for (shard_uid, stats) in stats {
+        let hit_rate =
+            stats.hit_rate().map_or("-".to_string(), |rate| format!("{:.1}%", rate * 100.0));
+        write!(
+            line,
+            " {} {} {}",
+            shard_uid_label(*shard_uid),
+            hit_rate,
+            pretty_bytes(stats.size_bytes)
+        )
+        .unwrap();
+    }
+    line
+}
+
 fn gas_used_per_sec(num: u64) -> String {
     if num < 1000 {
         format!("{} gas/s", num)
@@ -1,10 +1,15 @@
 pub use near_client_primitives::types::{
-    Error, GetBlock, GetBlockHash, GetBlockProof, GetBlockProofResponse, GetBlockWithMerkleTree,
-    GetChunk, GetExecutionOutcome, GetExecutionOutcomeResponse, GetExecutionOutcomesForBlock,
-    GetGasPrice, GetNetworkInfo, GetNextLightClientBlock, GetProtocolConfig, GetReceipt,
-    GetStateChanges, GetStateChangesInBlock, GetStateChangesWithCauseInBlock,
-    GetStateChangesWithCauseInBlockForTrackedShards, GetValidatorInfo, GetValidatorOrdered, Query,
-    QueryError, Status, StatusResponse, SyncStatus, TxStatus, TxStatusError,
+    CachedTrieNode, Error, GetAppliedChunkInfo, GetBlock, GetBlockHash, GetBlockHeaderOnly,
+    GetBlockProof, GetBlockProofResponse, GetBlockWithMerkleTree, GetChunk, GetEpochInfo,
+    GetExecutionOutcome, GetExecutionOutcomeResponse, GetExecutionOutcomesForBlock,
+    GetForwardedTransactions, GetGasPrice, GetHottestTrieNodes, GetNetworkInfo,
+    GetNextLightClientBlock, GetProtocolConfig, GetProtocolUpgradeStatus, GetReceipt,
+    GetReceiptQueueChangesInBlock, GetStateChanges, GetStateChangesInBlock,
+    GetStateChangesWithCauseInBlock, GetStateChangesWithCauseInBlockForTrackedShards,
+    GetStoreProperties, GetTopContracts, GetTransactionRouting, GetUnstakeSchedule,
+    GetValidatorInfo, GetValidatorOrdered, GetValidatorProductionStats, MakeSnapshot,
+    ManagePeerBan, Query, QueryError, SetMaxBackgroundJobs, SnapshotInfo, Status, StatusResponse,
+    StoreProperties, SubscribeFinalBlocks, SyncStatus, TxStatus, TxStatusError,
 };
 
 pub use crate::client::Client;
@@ -16,10 +21,13 @@ pub use crate::view_client::{start_view_client, ViewClientActor};
 mod chunks_delay_tracker;
 mod client;
 mod client_actor;
+mod final_blocks_notifier;
 mod info;
 mod metrics;
+mod missed_chunks_tracker;
 mod rocksdb_metrics;
 pub mod sync;
+pub mod test_loop;
 pub mod test_utils;
 #[cfg(test)]
 mod tests;
@@ -1,9 +1,11 @@
 use near_metrics::{
-    try_create_histogram, try_create_histogram_vec, try_create_int_counter,
+    try_create_gauge_vec, try_create_histogram, try_create_histogram_vec, try_create_int_counter,
     try_create_int_counter_vec, try_create_int_gauge, Histogram, HistogramVec, IntCounter,
     IntCounterVec, IntGauge, IntGaugeVec,
 };
+use near_primitives::views::TopContractsView;
 use once_cell::sync::Lazy;
+use prometheus::GaugeVec;
 
 pub static BLOCK_PRODUCED_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
     try_create_int_counter(
@@ -152,6 +154,22 @@ pub static CHUNK_SKIPPED_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub static CONSECUTIVE_MISSED_CHUNKS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    near_metrics::try_create_int_gauge_vec(
+        "near_consecutive_missed_chunks",
+        "Number of consecutive blocks at the head of the chain without a new chunk for the shard",
+        &["shard_id"],
+    )
+    .unwrap()
+});
+pub static CHUNK_PRODUCER_MISSED_CHUNKS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_chunk_producer_missed_chunks_total",
+        "Number of chunks missing from blocks at the head of the chain by chunk producer",
+        &["account_id"],
+    )
+    .unwrap()
+});
 pub static PARTIAL_ENCODED_CHUNK_RESPONSE_DELAY: Lazy<Histogram> = Lazy::new(|| {
     try_create_histogram(
         "partial_encoded_chunk_response_delay",
@@ -192,3 +210,92 @@ pub static CLIENT_TRIGGER_TIME_BY_TYPE: Lazy<HistogramVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub static DB_SNAPSHOT_SIZE: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_db_snapshot_size_bytes",
+        "Size of the database files in the most recent database snapshot",
+    )
+    .unwrap()
+});
+pub static DB_SNAPSHOT_TIME: Lazy<Histogram> = Lazy::new(|| {
+    try_create_histogram("near_db_snapshot_time", "Time taken to create a database snapshot")
+        .unwrap()
+});
+pub static STATE_SYNC_PART_RETRIES: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_state_sync_part_retries_total",
+        "Number of state part requests repeated after a timeout or an invalid part",
+    )
+    .unwrap()
+});
+pub static STATE_SYNC_CORRUPT_PARTS: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_state_sync_corrupt_parts_total",
+        "Number of downloaded state parts which failed validation or got corrupted in the store",
+    )
+    .unwrap()
+});
+pub static HEADER_SYNC_OUTSTANDING_BATCHES: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_header_sync_outstanding_batches",
+        "Number of pipelined header batch requests waiting for a response",
+    )
+    .unwrap()
+});
+pub static HEADER_SYNC_BUFFERED_HEADERS: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_header_sync_buffered_headers",
+        "Number of received block headers waiting for the headers below them",
+    )
+    .unwrap()
+});
+pub static HEADER_SYNC_BATCH_TIMEOUTS: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_header_sync_batch_timeouts_total",
+        "Number of header batch requests which weren't answered in time",
+    )
+    .unwrap()
+});
+pub static HEADER_SYNC_INVALID_BATCHES: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_header_sync_invalid_batches_total",
+        "Number of header batches with headers outside of the requested range or out of order",
+    )
+    .unwrap()
+});
+pub static CONTRACT_EXECUTION_TIME: Lazy<GaugeVec> = Lazy::new(|| {
+    try_create_gauge_vec(
+        "near_contract_execution_seconds",
+        "Wall time the receipts of the contracts which took the most time to execute during the \
+         current epoch took, the other contracts being aggregated as \"other\"",
+        &["account"],
+    )
+    .unwrap()
+});
+pub static CONTRACT_GAS_BURNT: Lazy<GaugeVec> = Lazy::new(|| {
+    try_create_gauge_vec(
+        "near_contract_gas_burnt",
+        "Gas burnt by the receipts of the contracts which took the most time to execute during \
+         the current epoch, the other contracts being aggregated as \"other\"",
+        &["account"],
+    )
+    .unwrap()
+});
+
+/// Number of accounts exported by the contract execution metrics, the other ones being
+/// aggregated to keep the number of time series bounded.
+pub(crate) const TOP_CONTRACTS_EXPORTED: usize = 20;
+
+pub(crate) fn export_top_contracts(top: &TopContractsView) {
+    // The accounts which left the top ones must not keep their last values.
+    CONTRACT_EXECUTION_TIME.reset();
+    CONTRACT_GAS_BURNT.reset();
+    for contract in top.contracts.iter().chain(std::iter::once(&top.other)) {
+        let account =
+            contract.account_id.as_ref().map_or("other", |account_id| account_id.as_ref());
+        CONTRACT_EXECUTION_TIME
+            .with_label_values(&[account])
+            .set(contract.execution_time_us as f64 / 1e6);
+        CONTRACT_GAS_BURNT.with_label_values(&[account]).set(contract.gas_burnt as f64);
+    }
+}
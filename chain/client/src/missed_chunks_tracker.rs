@@ -0,0 +1,74 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use near_chain_configs::ClientConfig;
+use near_primitives::types::{AccountId, NumBlocks, ShardId};
+use tracing::warn;
+
+use crate::metrics;
+
+/// Keeps track of shards whose chunks keep being missed, so that block producers can wait longer
+/// for them, and of chunk producers the network layer has no route to, whose chunks are not
+/// waited for at all.
+#[derive(Debug, Default)]
+pub(crate) struct MissedChunksTracker {
+    /// Number of consecutive blocks at the head of the chain without a new chunk for the shard.
+    missed_chunks: HashMap<ShardId, NumBlocks>,
+    /// Chunk producers without a route from this node.
+    unreachable_producers: HashSet<AccountId>,
+}
+
+impl MissedChunksTracker {
+    /// Records whether a new head included the chunk of `shard_id` produced by `chunk_producer`.
+    pub fn record_chunk(
+        &mut self,
+        shard_id: ShardId,
+        included: bool,
+        chunk_producer: &AccountId,
+        config: &ClientConfig,
+    ) {
+        let missed = self.missed_chunks.entry(shard_id).or_default();
+        *missed = if included { 0 } else { *missed + 1 };
+        metrics::CONSECUTIVE_MISSED_CHUNKS
+            .with_label_values(&[&shard_id.to_string()])
+            .set(*missed as i64);
+        if included {
+            return;
+        }
+        metrics::CHUNK_PRODUCER_MISSED_CHUNKS_TOTAL
+            .with_label_values(&[chunk_producer.as_ref()])
+            .inc();
+        if *missed >= config.missed_chunks_threshold {
+            let missed = *missed;
+            warn!(
+                target: "client",
+                "Shard {} missed {} chunks in a row, the last one by chunk producer {}. Waiting up to {:?} longer for its chunks",
+                shard_id,
+                missed,
+                chunk_producer,
+                self.chunk_wait_extension(shard_id, config)
+            );
+        }
+    }
+
+    /// How much longer than usual block producers wait for the chunk of `shard_id`.
+    pub fn chunk_wait_extension(&self, shard_id: ShardId, config: &ClientConfig) -> Duration {
+        let missed = self.missed_chunks.get(&shard_id).copied().unwrap_or_default();
+        if missed < config.missed_chunks_threshold {
+            return Duration::ZERO;
+        }
+        let steps = u32::try_from(missed - config.missed_chunks_threshold + 1).unwrap_or(u32::MAX);
+        std::cmp::min(
+            config.missed_chunks_wait_step.saturating_mul(steps),
+            config.missed_chunks_max_wait,
+        )
+    }
+
+    pub fn set_unreachable_producers(&mut self, unreachable_producers: HashSet<AccountId>) {
+        self.unreachable_producers = unreachable_producers;
+    }
+
+    pub fn is_unreachable(&self, chunk_producer: &AccountId) -> bool {
+        self.unreachable_producers.contains(chunk_producer)
+    }
+}
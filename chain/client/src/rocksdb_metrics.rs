@@ -1,12 +1,111 @@
-use near_metrics::{try_create_gauge_vec, try_create_int_gauge};
-use near_store::db::{StatsValue, StoreStatistics};
+use near_metrics::{
+    try_create_gauge, try_create_gauge_vec, try_create_int_gauge, try_create_int_gauge_vec,
+    IntGaugeVec,
+};
+use near_store::db::{StatsValue, StoreProperties, StoreStatistics};
 use once_cell::sync::Lazy;
-use prometheus::{GaugeVec, IntGauge};
+use prometheus::{Gauge, GaugeVec, IntGauge};
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::sync::Mutex;
 use tracing::warn;
 
+static PENDING_COMPACTION_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_rocksdb_pending_compaction_bytes",
+        "Estimated number of bytes compaction has to rewrite in the column",
+        &["column"],
+    )
+    .unwrap()
+});
+static NUM_FILES_AT_LEVEL0: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_rocksdb_num_files_at_level0",
+        "Number of level 0 files of the column",
+        &["column"],
+    )
+    .unwrap()
+});
+static MEM_TABLES_SIZE_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_rocksdb_mem_tables_size_bytes",
+        "Size of the active and unflushed immutable memtables of the column",
+        &["column"],
+    )
+    .unwrap()
+});
+static BLOCK_CACHE_USAGE_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_rocksdb_block_cache_usage_bytes",
+        "Size of the blocks held by the block cache of the column",
+        &["column"],
+    )
+    .unwrap()
+});
+static WRITE_STOPPED: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_rocksdb_write_stopped",
+        "Whether writes are stopped until compaction catches up",
+    )
+    .unwrap()
+});
+static DELAYED_WRITE_RATE: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_rocksdb_delayed_write_rate",
+        "Rate in bytes per second writes are slowed down to until compaction catches up",
+    )
+    .unwrap()
+});
+static BLOCK_CACHE_HIT_RATE: Lazy<Gauge> = Lazy::new(|| {
+    try_create_gauge(
+        "near_rocksdb_block_cache_hit_rate",
+        "Fraction of the block cache lookups which hit, since the database was opened",
+    )
+    .unwrap()
+});
+
+/// Stall time of the previous sample, to warn when writes stall.
+static LAST_STALL_MICROS: Lazy<Mutex<Option<u64>>> = Lazy::new(|| Mutex::new(None));
+
+/// Exports the properties sampled from RocksDB and warns if writes are stalled.
+pub(crate) fn export_properties_as_metrics(properties: StoreProperties) {
+    for column in &properties.columns {
+        let labels = [column.column.as_str()];
+        PENDING_COMPACTION_BYTES
+            .with_label_values(&labels)
+            .set(column.pending_compaction_bytes as i64);
+        NUM_FILES_AT_LEVEL0.with_label_values(&labels).set(column.num_files_at_level0 as i64);
+        MEM_TABLES_SIZE_BYTES.with_label_values(&labels).set(column.mem_tables_size as i64);
+        BLOCK_CACHE_USAGE_BYTES.with_label_values(&labels).set(column.block_cache_usage as i64);
+    }
+    WRITE_STOPPED.set(properties.is_write_stopped as i64);
+    DELAYED_WRITE_RATE.set(properties.delayed_write_rate as i64);
+    if let Some(hit_rate) = properties.block_cache_hit_rate {
+        BLOCK_CACHE_HIT_RATE.set(hit_rate);
+    }
+
+    let stalled_micros = {
+        let mut last_stall_micros = LAST_STALL_MICROS.lock().unwrap();
+        let stalled_micros = match (*last_stall_micros, properties.stall_micros) {
+            (Some(last), Some(current)) => current.saturating_sub(last),
+            _ => 0,
+        };
+        *last_stall_micros = properties.stall_micros;
+        stalled_micros
+    };
+    if properties.is_write_stopped || properties.delayed_write_rate > 0 || stalled_micros > 0 {
+        let busiest = properties.columns.iter().max_by_key(|col| col.pending_compaction_bytes);
+        warn!(
+            target: "stats",
+            "RocksDB writes are stalled: stopped: {}, delayed to {} B/s, stalled for {}ms since the last sample, most pending compaction in {:?}",
+            properties.is_write_stopped,
+            properties.delayed_write_rate,
+            stalled_micros / 1000,
+            busiest.map(|col| (&col.column, col.pending_compaction_bytes, col.num_files_at_level0)),
+        );
+    }
+}
+
 pub(crate) fn export_stats_as_metrics(stats: StoreStatistics) {
     match ROCKSDB_METRICS.lock().unwrap().export_stats_as_metrics(stats) {
         Ok(_) => {}
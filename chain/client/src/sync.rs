@@ -1,6 +1,6 @@
 use near_chain::{check_known, near_chain_primitives, ChainStoreAccess, Error};
 use std::cmp::min;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ops::Add;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -15,10 +15,10 @@ use tracing::{debug, error, info, warn};
 
 use near_chain::{Chain, RuntimeAdapter};
 use near_network::types::{FullPeerInfo, NetworkRequests, NetworkResponses, PeerManagerAdapter};
-use near_primitives::block::Tip;
+use near_primitives::block::{BlockHeader, Tip};
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::PeerId;
-use near_primitives::syncing::get_num_state_parts;
+use near_primitives::syncing::{get_num_state_parts, StateSyncProgress};
 use near_primitives::time::{Clock, Utc};
 use near_primitives::types::validator_stake::ValidatorStake;
 use near_primitives::types::{
@@ -26,7 +26,7 @@ use near_primitives::types::{
 };
 use near_primitives::utils::to_timestamp;
 
-use near_chain::chain::{ApplyStatePartsRequest, StateSplitRequest};
+use near_chain::chain::{ApplyStatePartsRequest, StateSplitRequest, NUM_EPOCHS_TO_KEEP_STORE_DATA};
 use near_client_primitives::types::{
     DownloadStatus, ShardSyncDownload, ShardSyncStatus, SyncStatus,
 };
@@ -34,12 +34,18 @@ use near_network::types::PeerManagerMessageRequest;
 use near_network_primitives::types::AccountOrPeerIdOrHash;
 use near_primitives::shard_layout::ShardUId;
 
+use crate::metrics;
+
 /// Maximum number of block headers send over the network.
 pub const MAX_BLOCK_HEADERS: u64 = 512;
 
 /// Maximum number of block header hashes to send as part of a locator.
 pub const MAX_BLOCK_HEADER_HASHES: usize = 20;
 
+/// Maximum number of block headers of pipelined batches kept in memory while waiting for the
+/// batches below them.
+const MAX_BUFFERED_BLOCK_HEADERS: usize = 16 * MAX_BLOCK_HEADERS as usize;
+
 /// Maximum number of block requested at once in BlockSync
 const MAX_BLOCK_REQUESTS: usize = 5;
 
@@ -125,8 +131,46 @@ impl EpochSync {
     }
 }
 
+/// Chooses a random peer among the ones for which `is_capable` holds, or among all peers if there
+/// is no such peer.
+fn choose_capable_peer(
+    peers: &[FullPeerInfo],
+    is_capable: impl Fn(&FullPeerInfo) -> bool,
+) -> Option<&FullPeerInfo> {
+    let mut rng = thread_rng();
+    peers.iter().filter(|peer| is_capable(peer)).choose(&mut rng).or_else(|| peers.choose(&mut rng))
+}
+
+/// Pipelined request for the block headers with heights in a range.
+struct HeaderBatchRequest {
+    peer_id: PeerId,
+    end_height: BlockHeight,
+    requested_at: DateTime<Utc>,
+}
+
+/// Pipelined batch of block headers which doesn't connect to the known headers yet.
+struct BufferedHeaderBatch {
+    peer_id: PeerId,
+    headers: Vec<BlockHeader>,
+}
+
+/// Why a batch of block headers returned by a peer for a height range is rejected.
+#[derive(Debug, PartialEq, Eq)]
+pub enum HeaderBatchError {
+    /// A header is outside of the requested range of heights.
+    OutOfRange { height: BlockHeight },
+    /// Heights of the headers don't strictly increase.
+    Overlapping { height: BlockHeight },
+    /// A header doesn't point at the previous header of the batch.
+    NotChained { height: BlockHeight },
+}
+
 /// Helper to keep track of sync headers.
 /// Handles major re-orgs by finding closest header that matches and re-downloading headers from that point.
+///
+/// Headers after the ones requested with the locator are requested in batches of
+/// `MAX_BLOCK_HEADERS` heights from other peers at the same time, and buffered until they connect
+/// to the known headers.
 pub struct HeaderSync {
     network_adapter: Arc<dyn PeerManagerAdapter>,
     history_locator: Vec<(BlockHeight, CryptoHash)>,
@@ -138,6 +182,16 @@ pub struct HeaderSync {
     progress_timeout: Duration,
     stall_ban_timeout: Duration,
     expected_height_per_second: u64,
+
+    /// Maximum number of header requests in flight, including the one with the locator.
+    max_outstanding_batches: usize,
+    batch_timeout: Duration,
+    /// Pipelined batch requests, keyed by the first requested height.
+    outstanding_batches: BTreeMap<BlockHeight, HeaderBatchRequest>,
+    /// Received pipelined batches, keyed by the first requested height.
+    buffered_batches: BTreeMap<BlockHeight, BufferedHeaderBatch>,
+    /// Peers which didn't return a batch in time, with the time they may be asked again.
+    slow_peers: HashMap<PeerId, DateTime<Utc>>,
 }
 
 impl HeaderSync {
@@ -147,6 +201,8 @@ impl HeaderSync {
         progress_timeout: TimeDuration,
         stall_ban_timeout: TimeDuration,
         expected_height_per_second: u64,
+        max_outstanding_batches: usize,
+        batch_timeout: TimeDuration,
     ) -> Self {
         HeaderSync {
             network_adapter,
@@ -158,6 +214,11 @@ impl HeaderSync {
             progress_timeout: Duration::from_std(progress_timeout).unwrap(),
             stall_ban_timeout: Duration::from_std(stall_ban_timeout).unwrap(),
             expected_height_per_second,
+            max_outstanding_batches,
+            batch_timeout: Duration::from_std(batch_timeout).unwrap(),
+            outstanding_batches: BTreeMap::new(),
+            buffered_batches: BTreeMap::new(),
+            slow_peers: HashMap::new(),
         }
     }
 
@@ -169,7 +230,16 @@ impl HeaderSync {
         highest_height_peers: &Vec<FullPeerInfo>,
     ) -> Result<(), near_chain::Error> {
         let header_head = chain.header_head()?;
-        if !self.header_sync_due(sync_status, &header_head, highest_height) {
+        let now = Clock::utc();
+        if !self.header_sync_due(sync_status, &header_head, highest_height, now) {
+            if let SyncStatus::HeaderSync { .. } = sync_status {
+                self.request_header_batches(
+                    header_head.height,
+                    highest_height,
+                    highest_height_peers,
+                    now,
+                );
+            }
             return Ok(());
         }
 
@@ -191,16 +261,194 @@ impl HeaderSync {
             *sync_status =
                 SyncStatus::HeaderSync { current_height: header_head.height, highest_height };
             self.syncing_peer = None;
-            if let Some(peer) = highest_height_peers.choose(&mut thread_rng()).cloned() {
+            // Nodes which bootstrapped with epoch sync don't have headers of old epochs, so ask
+            // archival peers first if we are that far behind.
+            let far_behind = highest_height.saturating_sub(header_head.height)
+                > chain.epoch_length * NUM_EPOCHS_TO_KEEP_STORE_DATA;
+            let busy_peers = self.busy_peers();
+            if let Some(peer) = choose_capable_peer(highest_height_peers, |peer| {
+                (!far_behind || peer.is_archival()) && !busy_peers.contains(&peer.peer_info.id)
+            })
+            .cloned()
+            {
                 if peer.chain_info.height > header_head.height {
+                    // The response to the locator must not be taken for a batch.
+                    self.outstanding_batches
+                        .retain(|_, request| request.peer_id != peer.peer_info.id);
                     self.syncing_peer = self.request_headers(chain, peer);
                 }
             }
+            self.request_header_batches(
+                header_head.height,
+                highest_height,
+                highest_height_peers,
+                now,
+            );
         }
 
         Ok(())
     }
 
+    /// Peers which are asked for a pipelined batch.
+    fn busy_peers(&self) -> HashSet<PeerId> {
+        self.outstanding_batches.values().map(|request| request.peer_id.clone()).collect()
+    }
+
+    /// Requests the batches of headers above the ones requested with the locator.  Batch `k`
+    /// covers the heights `k * MAX_BLOCK_HEADERS..(k + 1) * MAX_BLOCK_HEADERS`, and the first one
+    /// contains `header_head_height + MAX_BLOCK_HEADERS`, which is not above the last height
+    /// returned for the locator.  Each batch is requested from a different peer.
+    fn request_header_batches(
+        &mut self,
+        header_head_height: BlockHeight,
+        highest_height: BlockHeight,
+        peers: &[FullPeerInfo],
+        now: DateTime<Utc>,
+    ) {
+        if self.max_outstanding_batches <= 1 {
+            return;
+        }
+
+        let batch_timeout = self.batch_timeout;
+        let mut timed_out = vec![];
+        self.outstanding_batches.retain(|&start_height, request| {
+            if request.end_height <= header_head_height {
+                false
+            } else if now > request.requested_at + batch_timeout {
+                timed_out.push((start_height, request.peer_id.clone()));
+                false
+            } else {
+                true
+            }
+        });
+        for (start_height, peer_id) in timed_out {
+            debug!(target: "sync", "Sync: header batch at {} from {} timed out", start_height, peer_id);
+            metrics::HEADER_SYNC_BATCH_TIMEOUTS.inc();
+            self.slow_peers.insert(peer_id, now + batch_timeout);
+        }
+        self.slow_peers.retain(|_, until| *until > now);
+        self.buffered_batches.retain(|_, batch| {
+            batch.headers.last().map_or(false, |header| header.height() > header_head_height)
+        });
+
+        let first_batch = (header_head_height + MAX_BLOCK_HEADERS) / MAX_BLOCK_HEADERS;
+        let num_batches = self.max_outstanding_batches as u64 - 1;
+        let mut busy_peers = self.busy_peers();
+        if let Some(peer) = &self.syncing_peer {
+            busy_peers.insert(peer.peer_info.id.clone());
+        }
+        for batch in first_batch..first_batch + num_batches {
+            let start_height = batch * MAX_BLOCK_HEADERS;
+            let end_height = start_height + MAX_BLOCK_HEADERS - 1;
+            if start_height > highest_height {
+                break;
+            }
+            if self.outstanding_batches.contains_key(&start_height)
+                || self.buffered_batches.contains_key(&start_height)
+            {
+                continue;
+            }
+            if self.buffered_headers()
+                + (self.outstanding_batches.len() + 1) * MAX_BLOCK_HEADERS as usize
+                > MAX_BUFFERED_BLOCK_HEADERS
+            {
+                break;
+            }
+            let peer = peers
+                .iter()
+                .filter(|peer| {
+                    peer.serves_header_ranges()
+                        && peer.chain_info.height >= start_height
+                        && !busy_peers.contains(&peer.peer_info.id)
+                        && !self.slow_peers.contains_key(&peer.peer_info.id)
+                })
+                .choose(&mut thread_rng());
+            let peer_id = match peer {
+                Some(peer) => peer.peer_info.id.clone(),
+                None => break,
+            };
+            debug!(target: "sync", "Sync: request headers: asking {} for heights {}..={}", peer_id, start_height, end_height);
+            self.network_adapter.do_send(PeerManagerMessageRequest::NetworkRequests(
+                NetworkRequests::BlockHeadersByHeightRequest {
+                    start_height,
+                    end_height,
+                    peer_id: peer_id.clone(),
+                },
+            ));
+            busy_peers.insert(peer_id.clone());
+            self.outstanding_batches.insert(
+                start_height,
+                HeaderBatchRequest { peer_id, end_height, requested_at: now },
+            );
+        }
+        metrics::HEADER_SYNC_OUTSTANDING_BATCHES.set(self.outstanding_batches.len() as i64);
+    }
+
+    fn buffered_headers(&self) -> usize {
+        self.buffered_batches.values().map(|batch| batch.headers.len()).sum()
+    }
+
+    /// Handles block headers received from `peer_id`, returning the ones to add to the chain.
+    /// Headers answering a pipelined batch request are checked against the requested range,
+    /// and are buffered if they don't connect to the known headers yet.  Other headers are
+    /// returned as is.
+    pub fn receive_headers(
+        &mut self,
+        chain: &mut Chain,
+        headers: Vec<BlockHeader>,
+        peer_id: &PeerId,
+    ) -> Result<Vec<BlockHeader>, HeaderBatchError> {
+        let start_height = match self
+            .outstanding_batches
+            .iter()
+            .find(|(_, request)| &request.peer_id == peer_id)
+        {
+            Some((&start_height, _)) => start_height,
+            None => return Ok(headers),
+        };
+        let request = self.outstanding_batches.remove(&start_height).unwrap();
+        metrics::HEADER_SYNC_OUTSTANDING_BATCHES.set(self.outstanding_batches.len() as i64);
+        if let Err(err) = check_header_batch(&headers, start_height, request.end_height) {
+            metrics::HEADER_SYNC_INVALID_BATCHES.inc();
+            return Err(err);
+        }
+        let first_header = match headers.first() {
+            Some(header) => header,
+            None => {
+                // The peer is behind or has no blocks there, don't ask it again right away.
+                self.slow_peers.insert(peer_id.clone(), request.requested_at + self.batch_timeout);
+                return Ok(headers);
+            }
+        };
+        if chain.get_block_header(first_header.prev_hash()).is_ok() {
+            return Ok(headers);
+        }
+        if self.buffered_headers() + headers.len() > MAX_BUFFERED_BLOCK_HEADERS {
+            debug!(target: "sync", "Sync: dropping header batch at {} from {}, buffer is full", start_height, peer_id);
+        } else {
+            self.buffered_batches
+                .insert(start_height, BufferedHeaderBatch { peer_id: peer_id.clone(), headers });
+        }
+        metrics::HEADER_SYNC_BUFFERED_HEADERS.set(self.buffered_headers() as i64);
+        Ok(vec![])
+    }
+
+    /// Takes a buffered batch which connects to the known headers, together with the peer which
+    /// returned it.
+    pub fn take_connected_batch(
+        &mut self,
+        chain: &mut Chain,
+    ) -> Option<(PeerId, Vec<BlockHeader>)> {
+        let start_height = self
+            .buffered_batches
+            .iter()
+            .find(|(_, batch)| chain.get_block_header(batch.headers[0].prev_hash()).is_ok())
+            .map(|(&start_height, _)| start_height)?;
+        let batch = self.buffered_batches.remove(&start_height)?;
+        metrics::HEADER_SYNC_BUFFERED_HEADERS.set(self.buffered_headers() as i64);
+        Some((batch.peer_id, batch.headers))
+    }
+
     fn compute_expected_height(
         &self,
         old_height: BlockHeight,
@@ -217,8 +465,8 @@ impl HeaderSync {
         sync_status: &SyncStatus,
         header_head: &Tip,
         highest_height: BlockHeight,
+        now: DateTime<Utc>,
     ) -> bool {
-        let now = Clock::utc();
         let (timeout, old_expected_height, prev_height, prev_highest_height) =
             self.prev_header_sync;
 
@@ -350,6 +598,29 @@ impl HeaderSync {
     }
 }
 
+/// Checks that `headers` returned for the heights `start_height..=end_height` are in the range,
+/// ordered by height and form a chain.
+fn check_header_batch(
+    headers: &[BlockHeader],
+    start_height: BlockHeight,
+    end_height: BlockHeight,
+) -> Result<(), HeaderBatchError> {
+    for header in headers {
+        if header.height() < start_height || header.height() > end_height {
+            return Err(HeaderBatchError::OutOfRange { height: header.height() });
+        }
+    }
+    for pair in headers.windows(2) {
+        if pair[1].height() <= pair[0].height() {
+            return Err(HeaderBatchError::Overlapping { height: pair[1].height() });
+        }
+        if pair[1].prev_hash() != pair[0].hash() {
+            return Err(HeaderBatchError::NotChained { height: pair[1].height() });
+        }
+    }
+    Ok(())
+}
+
 /// Check if there is a close enough value to provided height in the locator.
 fn close_enough(locator: &Vec<(u64, CryptoHash)>, height: u64) -> Option<(u64, CryptoHash)> {
     if locator.len() == 0 {
@@ -550,14 +821,15 @@ impl BlockSync {
 
         for request in requests {
             let (height, hash) = request;
-            let request_from_archival = self.archive && height < gc_stop_height;
+            // Blocks below the GC horizon are kept by archival peers only.  Archival nodes must
+            // get them, others just try archival peers first.
+            let is_old = height < gc_stop_height;
+            let request_from_archival = self.archive && is_old;
             let peer = if request_from_archival {
-                let archival_peer_iter =
-                    highest_height_peers.iter().filter(|p| p.chain_info.archival);
+                let archival_peer_iter = highest_height_peers.iter().filter(|p| p.is_archival());
                 archival_peer_iter.choose(&mut rand::thread_rng())
             } else {
-                let peer_iter = highest_height_peers.iter();
-                peer_iter.choose(&mut rand::thread_rng())
+                choose_capable_peer(highest_height_peers, |p| !is_old || p.is_archival())
             };
 
             if let Some(peer) = peer {
@@ -611,6 +883,24 @@ impl PendingRequestStatus {
     }
 }
 
+/// Download statuses of the parts of a shard, with the parts which have been downloaded already
+/// marked as done.
+fn part_downloads(progress: &StateSyncProgress, now: DateTime<Utc>) -> Vec<DownloadStatus> {
+    progress
+        .part_hashes
+        .iter()
+        .map(|part_hash| DownloadStatus {
+            start_time: now,
+            prev_update_time: now,
+            run_me: Arc::new(AtomicBool::new(part_hash.is_none())),
+            error: false,
+            done: part_hash.is_some(),
+            state_requests_count: 0,
+            last_target: None,
+        })
+        .collect()
+}
+
 /// Helper to track state sync.
 pub struct StateSync {
     network_adapter: Arc<dyn PeerManagerAdapter>,
@@ -629,6 +919,10 @@ pub struct StateSync {
 
     /// Maps shard_id to result of splitting state for resharding
     split_state_roots: HashMap<ShardId, Result<HashMap<ShardUId, StateRoot>, Error>>,
+
+    /// Sources which sent an invalid state part, keyed by sync hash, shard id and part id.  They
+    /// aren't asked for that part again while there are other sources.
+    part_blacklist: HashMap<(CryptoHash, ShardId, u64), HashSet<AccountOrPeerIdOrHash>>,
 }
 
 impl StateSync {
@@ -642,6 +936,7 @@ impl StateSync {
             timeout: Duration::from_std(timeout).unwrap(),
             state_parts_apply_results: HashMap::new(),
             split_state_roots: HashMap::new(),
+            part_blacklist: HashMap::new(),
         }
     }
 
@@ -722,6 +1017,12 @@ impl StateSync {
                 need_shard = true;
                 init_sync_download.clone()
             });
+            if need_shard && chain.get_state_header(shard_id, sync_hash).is_ok() {
+                // The header has been downloaded before the node restarted.
+                shard_sync_download.downloads[0].done = true;
+                shard_sync_download.downloads[0].run_me = Arc::new(AtomicBool::new(false));
+                need_shard = false;
+            }
             let mut this_done = false;
             match shard_sync_download.status {
                 ShardSyncStatus::StateDownloadHeader => {
@@ -729,19 +1030,19 @@ impl StateSync {
                         let shard_state_header = chain.get_state_header(shard_id, sync_hash)?;
                         let state_num_parts =
                             get_num_state_parts(shard_state_header.state_root_node().memory_usage);
+                        // Parts downloaded before the node restarted aren't downloaded again.
+                        let (progress, corrupted_parts) = chain.check_downloaded_state_parts(
+                            shard_id,
+                            sync_hash,
+                            state_num_parts,
+                        )?;
+                        self.report_corrupted_parts(shard_id, sync_hash, &corrupted_parts);
+                        let num_done_parts = progress.done_parts().count();
+                        if num_done_parts > 0 {
+                            info!(target: "sync", "State sync resumed, shard = {}, hash = {}, {} of {} parts already downloaded", shard_id, sync_hash, num_done_parts, state_num_parts);
+                        }
                         *shard_sync_download = ShardSyncDownload {
-                            downloads: vec![
-                                DownloadStatus {
-                                    start_time: now,
-                                    prev_update_time: now,
-                                    run_me: Arc::new(AtomicBool::new(true)),
-                                    error: false,
-                                    done: false,
-                                    state_requests_count: 0,
-                                    last_target: None,
-                                };
-                                state_num_parts as usize
-                            ],
+                            downloads: part_downloads(&progress, now),
                             status: ShardSyncStatus::StateDownloadParts,
                         };
                         need_shard = true;
@@ -768,6 +1069,7 @@ impl StateSync {
                             let error = part_download.error;
                             let part_timeout = now - prev > self.timeout;
                             if part_timeout || error {
+                                metrics::STATE_SYNC_PART_RETRIES.inc();
                                 download_timeout |= part_timeout;
                                 part_download.run_me.store(true, Ordering::SeqCst);
                                 part_download.error = false;
@@ -790,26 +1092,44 @@ impl StateSync {
                     let shard_state_header = chain.get_state_header(shard_id, sync_hash)?;
                     let state_num_parts =
                         get_num_state_parts(shard_state_header.state_root_node().memory_usage);
-                    match chain.schedule_apply_state_parts(
-                        shard_id,
-                        sync_hash,
-                        state_num_parts,
-                        state_parts_task_scheduler,
-                    ) {
-                        Ok(()) => {
-                            update_sync_status = true;
-                            *shard_sync_download = ShardSyncDownload {
-                                downloads: vec![],
-                                status: ShardSyncStatus::StateDownloadApplying,
+                    // Only the parts which are still intact are applied, the rest are downloaded
+                    // again instead of starting the whole download from scratch.
+                    let (progress, corrupted_parts) =
+                        chain.check_downloaded_state_parts(shard_id, sync_hash, state_num_parts)?;
+                    if !corrupted_parts.is_empty() {
+                        self.report_corrupted_parts(shard_id, sync_hash, &corrupted_parts);
+                        update_sync_status = true;
+                        need_shard = true;
+                        *shard_sync_download = ShardSyncDownload {
+                            downloads: part_downloads(&progress, now),
+                            status: ShardSyncStatus::StateDownloadParts,
+                        };
+                    } else {
+                        match chain.schedule_apply_state_parts(
+                            shard_id,
+                            sync_hash,
+                            state_num_parts,
+                            state_parts_task_scheduler,
+                        ) {
+                            Ok(()) => {
+                                update_sync_status = true;
+                                *shard_sync_download = ShardSyncDownload {
+                                    downloads: vec![],
+                                    status: ShardSyncStatus::StateDownloadApplying,
+                                }
+                            }
+                            Err(e) => {
+                                // Cannot finalize the downloaded state.
+                                // The reasonable behavior here is to start from the very beginning.
+                                error!(target: "sync", "State sync finalizing error, shard = {}, hash = {}: {:?}", shard_id, sync_hash, e);
+                                update_sync_status = true;
+                                *shard_sync_download = init_sync_download.clone();
+                                chain.clear_downloaded_parts(
+                                    shard_id,
+                                    sync_hash,
+                                    state_num_parts,
+                                )?;
                             }
-                        }
-                        Err(e) => {
-                            // Cannot finalize the downloaded state.
-                            // The reasonable behavior here is to start from the very beginning.
-                            error!(target: "sync", "State sync finalizing error, shard = {}, hash = {}: {:?}", shard_id, sync_hash, e);
-                            update_sync_status = true;
-                            *shard_sync_download = init_sync_download.clone();
-                            chain.clear_downloaded_parts(shard_id, sync_hash, state_num_parts)?;
                         }
                     }
                 }
@@ -849,6 +1169,8 @@ impl StateSync {
                     let state_num_parts =
                         get_num_state_parts(shard_state_header.state_root_node().memory_usage);
                     chain.clear_downloaded_parts(shard_id, sync_hash, state_num_parts)?;
+                    self.part_blacklist
+                        .retain(|(hash, shard, _), _| *hash != sync_hash || *shard != shard_id);
                     if split_states {
                         *shard_sync_download = ShardSyncDownload {
                             downloads: vec![],
@@ -940,6 +1262,36 @@ impl StateSync {
         Ok((update_sync_status, all_done))
     }
 
+    fn report_corrupted_parts(
+        &self,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+        corrupted_parts: &[u64],
+    ) {
+        if !corrupted_parts.is_empty() {
+            metrics::STATE_SYNC_CORRUPT_PARTS.inc_by(corrupted_parts.len() as u64);
+            warn!(target: "sync", "State sync parts {:?} of shard {} got corrupted in the store, downloading them again, hash = {}", corrupted_parts, shard_id, sync_hash);
+        }
+    }
+
+    /// Called when a downloaded state part failed validation.  The source the part was requested
+    /// from isn't asked for it again while there are other sources.
+    pub fn invalid_part_received(
+        &mut self,
+        part_id: u64,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+    ) {
+        metrics::STATE_SYNC_CORRUPT_PARTS.inc();
+        if let Some(target) = self.requested_target.get(&(part_id, sync_hash)) {
+            warn!(target: "sync", "State sync received invalid part {} of shard {} from {:?}, hash = {}", part_id, shard_id, target, sync_hash);
+            self.part_blacklist
+                .entry((sync_hash, shard_id, part_id))
+                .or_default()
+                .insert(target.clone());
+        }
+    }
+
     pub fn set_apply_result(&mut self, shard_id: ShardId, apply_result: Result<(), Error>) {
         self.state_parts_apply_results.insert(shard_id, apply_result);
     }
@@ -993,6 +1345,35 @@ impl StateSync {
             .or_insert_with(|| PendingRequestStatus::new(timeout));
     }
 
+    /// Called when a peer rejected our request for the state header (`part_id` is `None`) or for
+    /// a state part, asking to retry after `retry_after`. Postpones the next request until then,
+    /// instead of waiting for the regular timeout.
+    pub fn request_rejected(
+        &mut self,
+        shard_sync_download: &mut ShardSyncDownload,
+        part_id: Option<u64>,
+        shard_id: ShardId,
+        sync_hash: CryptoHash,
+        retry_after: TimeDuration,
+    ) {
+        let download = match (&shard_sync_download.status, part_id) {
+            (ShardSyncStatus::StateDownloadHeader, None) => {
+                shard_sync_download.downloads.get_mut(0)
+            }
+            (ShardSyncStatus::StateDownloadParts, Some(part_id)) => {
+                self.received_requested_part(part_id, shard_id, sync_hash);
+                shard_sync_download.downloads.get_mut(part_id as usize)
+            }
+            _ => None,
+        };
+        if let Some(download) = download {
+            if !download.done {
+                let retry_after = Duration::from_std(retry_after).unwrap_or_else(|_| self.timeout);
+                download.prev_update_time = Clock::utc() + retry_after - self.timeout;
+            }
+        }
+    }
+
     pub fn received_requested_part(
         &mut self,
         part_id: u64,
@@ -1052,7 +1433,7 @@ impl StateSync {
                 }
             })
             .chain(highest_height_peers.iter().filter_map(|peer| {
-                if peer.chain_info.tracked_shards.contains(&shard_id) {
+                if peer.serves_state_of(shard_id) {
                     Some(AccountOrPeerIdOrHash::PeerId(peer.peer_info.id.clone()))
                 } else {
                     None
@@ -1117,20 +1498,33 @@ impl StateSync {
                 );
             }
             ShardSyncStatus::StateDownloadParts => {
-                let possible_targets_sampler =
-                    SamplerLimited::new(possible_targets, MAX_STATE_PART_REQUEST);
+                let mut possible_targets_sampler =
+                    SamplerLimited::new(possible_targets.clone(), MAX_STATE_PART_REQUEST);
 
                 // Iterate over all parts that needs to be requested (i.e. download.run_me is true).
                 // Parts are ordered such that its index match its part_id.
                 // Finally, for every part that needs to be requested it is selected one peer (target) randomly
-                // to request the part from
-                for ((part_id, download), target) in new_shard_sync_download
+                // to request the part from, skipping the peers which sent an invalid part before.
+                for (part_id, download) in new_shard_sync_download
                     .downloads
                     .iter_mut()
                     .enumerate()
                     .filter(|(_, download)| download.run_me.load(Ordering::SeqCst))
-                    .zip(possible_targets_sampler)
                 {
+                    let blacklist_key = (sync_hash, shard_id, part_id as u64);
+                    if let Some(blacklist) = self.part_blacklist.get(&blacklist_key) {
+                        if possible_targets.iter().all(|target| blacklist.contains(target)) {
+                            // Give the sources another chance rather than get stuck.
+                            self.part_blacklist.remove(&blacklist_key);
+                        }
+                    }
+                    let blacklist = self.part_blacklist.get(&blacklist_key);
+                    let target = match possible_targets_sampler.by_ref().find(|target| {
+                        blacklist.map_or(true, |blacklist| !blacklist.contains(target))
+                    }) {
+                        Some(target) => target,
+                        None => break,
+                    };
                     self.sent_request_part(target.clone(), part_id as u64, shard_id, sync_hash);
                     download.run_me.store(false, Ordering::SeqCst);
                     download.state_requests_count += 1;
@@ -1297,7 +1691,9 @@ mod test {
 
     use super::*;
     use crate::test_utils::TestEnv;
-    use near_network_primitives::types::{PartialEdgeInfo, PeerInfo};
+    use near_network_primitives::types::{
+        PartialEdgeInfo, PeerCapabilities, PeerCapabilitiesV2, PeerInfo,
+    };
     use near_primitives::merkle::PartialMerkleTree;
     use near_primitives::types::EpochId;
     use near_primitives::utils::MaybeValidated;
@@ -1335,6 +1731,8 @@ mod test {
             TimeDuration::from_secs(2),
             TimeDuration::from_secs(120),
             1_000_000_000,
+            4,
+            TimeDuration::from_secs(5),
         );
         let (mut chain, _, signer) = setup();
         for _ in 0..3 {
@@ -1381,6 +1779,7 @@ mod test {
                 archival: false,
             },
             partial_edge_info: PartialEdgeInfo::default(),
+            capabilities: None,
         };
         let head = chain.head().unwrap();
         assert!(header_sync
@@ -1402,6 +1801,188 @@ mod test {
         );
     }
 
+    fn header_range_peers(chain: &Chain, height: BlockHeight, count: usize) -> Vec<FullPeerInfo> {
+        (0..count)
+            .map(|_| FullPeerInfo {
+                peer_info: PeerInfo::random(),
+                chain_info: near_network_primitives::types::PeerChainInfoV2 {
+                    genesis_id: GenesisId {
+                        chain_id: "unittest".to_string(),
+                        hash: *chain.genesis().hash(),
+                    },
+                    height,
+                    tracked_shards: vec![],
+                    archival: false,
+                },
+                partial_edge_info: PartialEdgeInfo::default(),
+                capabilities: Some(PeerCapabilities::V2(PeerCapabilitiesV2 {
+                    archival: false,
+                    tracked_shards: vec![],
+                    serves_state_sync: true,
+                    serves_header_ranges: true,
+                })),
+            })
+            .collect()
+    }
+
+    /// Syncs the headers of `source` to a fresh chain over a simulated link with 200ms of latency
+    /// in each direction, running a sync step every 10ms.  Returns the simulated time it took.
+    /// Processing takes no simulated time, and the timeouts are long enough not to depend on the
+    /// real time it takes.
+    fn sync_headers_over_slow_link(source: &mut Chain, max_outstanding_batches: usize) -> Duration {
+        let latency = Duration::milliseconds(200);
+        let step = Duration::milliseconds(10);
+        let mock_adapter = Arc::new(MockPeerManagerAdapter::default());
+        let mut header_sync = HeaderSync::new(
+            mock_adapter.clone(),
+            TimeDuration::from_secs(10),
+            TimeDuration::from_secs(2),
+            TimeDuration::from_secs(120),
+            1_000_000_000,
+            max_outstanding_batches,
+            TimeDuration::from_secs(600),
+        );
+        let (mut chain, _, _) = setup();
+        let target = source.header_head().unwrap();
+        let peers = header_range_peers(&chain, target.height, 4);
+
+        let mut now = Duration::zero();
+        let mut sync_status = SyncStatus::NoSync;
+        // Responses on their way back, with the time they arrive.
+        let mut responses: Vec<(Duration, PeerId, Vec<BlockHeader>)> = vec![];
+        while chain.header_head().unwrap().last_block_hash != target.last_block_hash {
+            assert!(now < Duration::seconds(60), "header sync is stuck");
+            let mut arrived = vec![];
+            responses.retain(|(at, peer_id, headers)| {
+                if *at <= now {
+                    arrived.push((peer_id.clone(), headers.clone()));
+                }
+                *at > now
+            });
+            for (peer_id, headers) in arrived {
+                let headers = header_sync.receive_headers(&mut chain, headers, &peer_id).unwrap();
+                let mut batch = Some((peer_id, headers));
+                while let Some((_, headers)) = batch {
+                    chain
+                        .sync_block_headers(headers, &mut |_| panic!("unexpected challenge"))
+                        .unwrap();
+                    batch = header_sync.take_connected_batch(&mut chain);
+                }
+            }
+
+            header_sync.run(&mut sync_status, &mut chain, target.height, &peers).unwrap();
+            while let Some(request) = mock_adapter.pop() {
+                let (peer_id, headers) = match request.as_network_requests() {
+                    NetworkRequests::BlockHeadersRequest { hashes, peer_id } => {
+                        (peer_id, source.retrieve_headers(hashes, MAX_BLOCK_HEADERS, None).unwrap())
+                    }
+                    NetworkRequests::BlockHeadersByHeightRequest {
+                        start_height,
+                        end_height,
+                        peer_id,
+                    } => (
+                        peer_id,
+                        source
+                            .retrieve_headers_by_height(start_height, end_height, MAX_BLOCK_HEADERS)
+                            .unwrap(),
+                    ),
+                    request => panic!("unexpected request {:?}", request),
+                };
+                responses.push((now + latency + latency, peer_id, headers));
+            }
+            now = now + step;
+        }
+        now
+    }
+
+    /// Pipelined header sync is several times faster than waiting for each batch on a link
+    /// with high latency.
+    #[test]
+    fn test_pipelined_header_sync() {
+        let (mut source, _, signer) = setup();
+        // Exactly the headers the locator and the first three pipelined batches cover.
+        for _ in 0..4 * MAX_BLOCK_HEADERS - 1 {
+            let prev = source.get_block(&source.head().unwrap().last_block_hash).unwrap();
+            let block = Block::empty(prev, &*signer);
+            source
+                .process_block(
+                    &None,
+                    block.into(),
+                    Provenance::PRODUCED,
+                    &mut |_| {},
+                    &mut |_| {},
+                    &mut |_| {},
+                    &mut |_| {},
+                )
+                .unwrap();
+        }
+        let sequential = sync_headers_over_slow_link(&mut source, 1);
+        let pipelined = sync_headers_over_slow_link(&mut source, 4);
+        assert!(sequential >= Duration::milliseconds(1600), "{}", sequential);
+        assert!(pipelined * 2 < sequential, "pipelined {} sequential {}", pipelined, sequential);
+    }
+
+    #[test]
+    fn test_invalid_header_batch() {
+        let (mut chain, _, signer) = setup();
+        let mut headers = vec![];
+        let mut prev = chain.genesis_block().clone();
+        for _ in 0..4 {
+            let block = Block::empty(&prev, &*signer);
+            headers.push(block.header().clone());
+            prev = block;
+        }
+        assert_eq!(check_header_batch(&headers, 1, 4), Ok(()));
+        assert_eq!(check_header_batch(&headers[1..], 1, 4), Ok(()));
+        assert_eq!(
+            check_header_batch(&headers, 2, 4),
+            Err(HeaderBatchError::OutOfRange { height: 1 })
+        );
+        assert_eq!(
+            check_header_batch(&headers, 1, 3),
+            Err(HeaderBatchError::OutOfRange { height: 4 })
+        );
+        let overlapping = vec![headers[0].clone(), headers[1].clone(), headers[1].clone()];
+        assert_eq!(
+            check_header_batch(&overlapping, 1, 4),
+            Err(HeaderBatchError::Overlapping { height: 2 })
+        );
+        let gap = vec![headers[0].clone(), headers[2].clone()];
+        assert_eq!(check_header_batch(&gap, 1, 4), Err(HeaderBatchError::NotChained { height: 3 }));
+
+        // The peer asked for a batch returns headers outside of it.
+        let mock_adapter = Arc::new(MockPeerManagerAdapter::default());
+        let mut header_sync = HeaderSync::new(
+            mock_adapter.clone(),
+            TimeDuration::from_secs(10),
+            TimeDuration::from_secs(2),
+            TimeDuration::from_secs(120),
+            1_000_000_000,
+            2,
+            TimeDuration::from_secs(5),
+        );
+        let peers = header_range_peers(&chain, 2000, 2);
+        header_sync.syncing_peer = Some(peers[0].clone());
+        header_sync.request_header_batches(0, 2000, &peers, Clock::utc());
+        assert_eq!(
+            mock_adapter.pop().unwrap().as_network_requests(),
+            NetworkRequests::BlockHeadersByHeightRequest {
+                start_height: MAX_BLOCK_HEADERS,
+                end_height: 2 * MAX_BLOCK_HEADERS - 1,
+                peer_id: peers[1].peer_info.id.clone(),
+            }
+        );
+        assert_eq!(
+            header_sync.receive_headers(&mut chain, headers.clone(), &peers[1].peer_info.id),
+            Err(HeaderBatchError::OutOfRange { height: 1 })
+        );
+        // Headers from a peer without a pending batch are passed on as is.
+        assert_eq!(
+            header_sync.receive_headers(&mut chain, headers.clone(), &peers[1].peer_info.id),
+            Ok(headers)
+        );
+    }
+
     /// Sets up `HeaderSync` with particular tolerance for slowness, and makes sure that a peer that
     /// sends headers below the threshold gets banned, and the peer that sends them faster doesn't get
     /// banned.
@@ -1420,6 +2001,8 @@ mod test {
             TimeDuration::from_secs(1),
             TimeDuration::from_secs(3),
             25,
+            4,
+            TimeDuration::from_secs(5),
         );
 
         let set_syncing_peer = |header_sync: &mut HeaderSync| {
@@ -1431,6 +2014,7 @@ mod test {
                 },
                 chain_info: Default::default(),
                 partial_edge_info: Default::default(),
+                capabilities: None,
             });
             header_sync.syncing_peer.as_mut().unwrap().chain_info.height = highest_height;
         };
@@ -1521,6 +2105,7 @@ mod test {
                 &SyncStatus::HeaderSync { current_height, highest_height },
                 &Tip::from_header(block.header()),
                 highest_height,
+                Clock::utc(),
             );
 
             last_added_block_ord += 3;
@@ -1539,6 +2124,7 @@ mod test {
                 &SyncStatus::HeaderSync { current_height, highest_height },
                 &Tip::from_header(block.header()),
                 highest_height,
+                Clock::utc(),
             );
 
             last_added_block_ord += 2;
@@ -1593,6 +2179,7 @@ mod test {
                 },
                 chain_info: Default::default(),
                 partial_edge_info: Default::default(),
+                capabilities: None,
             })
             .collect()
     }
@@ -1665,6 +2252,55 @@ mod test {
         assert!(requested_block_hashes.is_empty(), "{:?}", requested_block_hashes);
     }
 
+    /// A syncing non-archival node asks the single peer which announced being archival for the
+    /// blocks which other peers have already garbage collected.
+    #[test]
+    fn test_block_sync_prefers_archival_peer() {
+        let network_adapter = Arc::new(MockPeerManagerAdapter::default());
+        let mut block_sync = BlockSync::new(network_adapter.clone(), 10, false);
+        let mut chain_genesis = ChainGenesis::test();
+        chain_genesis.epoch_length = 5;
+        let mut env = TestEnv::builder(chain_genesis).clients_count(2).build();
+        let mut blocks = vec![];
+        for i in 1..41 {
+            let block = env.clients[0].produce_block(i).unwrap().unwrap();
+            blocks.push(block.clone());
+            env.process_block(0, block, Provenance::PRODUCED);
+        }
+        let block_headers = blocks.iter().map(|b| b.header().clone()).collect::<Vec<_>>();
+        env.clients[1].chain.sync_block_headers(block_headers, &mut |_| unreachable!()).unwrap();
+
+        let mut peer_infos = create_peer_infos(10);
+        for (i, peer) in peer_infos.iter_mut().enumerate() {
+            peer.peer_info.id = PeerId::new(PublicKey::from_seed(KeyType::ED25519, &i.to_string()));
+        }
+        // The archival peer says so in its announced capabilities, not in the handshake.
+        let archival_peer_id = peer_infos[7].peer_info.id.clone();
+        peer_infos[7].capabilities = Some(PeerCapabilities::V2(PeerCapabilitiesV2 {
+            archival: true,
+            tracked_shards: vec![],
+            serves_state_sync: true,
+            serves_header_ranges: true,
+        }));
+
+        let is_state_sync = block_sync.block_sync(&mut env.clients[1].chain, &peer_infos).unwrap();
+        assert!(!is_state_sync);
+        let mut requested = 0;
+        while let Some(request) = network_adapter.requests.write().unwrap().pop_back() {
+            match request {
+                PeerManagerMessageRequest::NetworkRequests(NetworkRequests::BlockRequest {
+                    peer_id,
+                    ..
+                }) => {
+                    assert_eq!(peer_id, archival_peer_id);
+                    requested += 1;
+                }
+                _ => panic!("unexpected network request {:?}", request),
+            }
+        }
+        assert_eq!(requested, MAX_BLOCK_REQUESTS);
+    }
+
     #[test]
     fn test_block_sync_archival() {
         let network_adapter = Arc::new(MockPeerManagerAdapter::default());
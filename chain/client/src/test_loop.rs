@@ -0,0 +1,664 @@
+//! Deterministic harness running several clients in a single thread without real time.
+//!
+//! Instead of actors and timers driven by the wall clock, the clients are driven by an explicit
+//! queue of events ordered by virtual time: the messages the nodes send to each other, which
+//! arrive after the latency of their link, and the periodic jobs `ClientActor` runs on timers.
+//! The virtual time is fed to the clients through the mocked `Clock`, and the chunks are applied
+//! synchronously by the runtime while the event is handled, so a test can enqueue messages, move
+//! the time forward and inspect the clients between any two events, and it runs the same way
+//! every time.
+//!
+//! ```rust, ignore
+//! let mut test_loop = TestLoop::builder(validators)
+//!     .block_prod_time(100, 300)
+//!     .default_latency(Duration::from_millis(10))
+//!     .latency(0, 1, Duration::from_millis(500))
+//!     .build();
+//! test_loop.run_until(|test_loop| test_loop.head_height(0) >= 10, Duration::from_secs(60));
+//! ```
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_rt::SystemRunner;
+use chrono::DateTime;
+use num_rational::Rational;
+use tracing::{debug, error};
+
+use near_chain::chain::{
+    do_apply_chunks, ApplyStatePartsRequest, BlockCatchUpRequest, StateSplitRequest,
+};
+use near_chain::test_utils::KeyValueRuntime;
+use near_chain::types::AcceptedBlock;
+use near_chain::{ChainGenesis, Provenance, RuntimeAdapter};
+use near_chain_configs::ClientConfig;
+use near_crypto::{KeyType, PublicKey};
+use near_network::test_utils::MockPeerManagerAdapter;
+use near_network::types::{NetworkRequests, PeerManagerMessageRequest};
+use near_network_primitives::types::{
+    AccountOrPeerIdOrHash, PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg,
+    PartialEncodedChunkResponseMsg, StateResponseInfo, StateResponseInfoV2,
+};
+use near_primitives::block::{Approval, Block};
+use near_primitives::block_header::ApprovalType;
+use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::network::PeerId;
+use near_primitives::sharding::PartialEncodedChunk;
+use near_primitives::syncing::{
+    ShardStateSyncResponse, ShardStateSyncResponseHeader, ShardStateSyncResponseV1,
+    ShardStateSyncResponseV2,
+};
+use near_primitives::time::{Clock, Instant, MockClockGuard, Utc};
+use near_primitives::types::{
+    AccountId, BlockHeight, BlockHeightDelta, NumSeats, NumShards, ShardId,
+};
+use near_primitives::utils::MaybeValidated;
+use near_primitives::validator_signer::InMemoryValidatorSigner;
+use near_primitives::version::PROTOCOL_VERSION;
+use near_store::test_utils::create_test_store;
+
+use crate::client_actor::apply_state_parts;
+use crate::test_utils::TEST_SEED;
+use crate::{Client, SyncStatus};
+
+/// Message sent by one node to another.
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum Message {
+    Block(Block),
+    Approval(Approval),
+    PartialEncodedChunk(PartialEncodedChunk),
+    PartialEncodedChunkForward(PartialEncodedChunkForwardMsg),
+    PartialEncodedChunkRequest(PartialEncodedChunkRequestMsg),
+    PartialEncodedChunkResponse(PartialEncodedChunkResponseMsg),
+    StateRequestHeader { shard_id: ShardId, sync_hash: CryptoHash },
+    StateRequestPart { shard_id: ShardId, sync_hash: CryptoHash, part_id: u64 },
+    StateResponse(StateResponseInfo),
+}
+
+/// Periodic jobs of a node, run on the timers of `ClientActor`.
+#[derive(Debug, Clone, Copy)]
+enum Timer {
+    BlockProduction,
+    Doomslug,
+    Catchup,
+    ChunkRequestRetry,
+}
+
+const TIMERS: [Timer; 4] =
+    [Timer::BlockProduction, Timer::Doomslug, Timer::Catchup, Timer::ChunkRequestRetry];
+
+#[allow(clippy::large_enum_variant)]
+enum Event {
+    Message { from: usize, to: usize, message: Message },
+    Timer { node: usize, timer: Timer },
+}
+
+/// Decides whether a message sent from one node to another is delivered.
+type MessageFilter = Box<dyn FnMut(usize, usize, &Message) -> bool>;
+
+/// Builder of a [`TestLoop`] with a validator node for each of the accounts in `validators`,
+/// which lists the validators of each epoch the same way `KeyValueRuntime` does.
+pub struct TestLoopBuilder {
+    validators: Vec<Vec<AccountId>>,
+    validator_groups: u64,
+    num_shards: Option<NumShards>,
+    epoch_length: BlockHeightDelta,
+    min_block_prod_time: u64,
+    max_block_prod_time: u64,
+    enable_doomslug: bool,
+    default_latency: Duration,
+    latencies: HashMap<(usize, usize), Duration>,
+}
+
+impl TestLoopBuilder {
+    fn new(validators: Vec<Vec<AccountId>>) -> Self {
+        Self {
+            validators,
+            validator_groups: 1,
+            num_shards: None,
+            epoch_length: 5,
+            min_block_prod_time: 100,
+            max_block_prod_time: 300,
+            enable_doomslug: true,
+            default_latency: Duration::from_millis(10),
+            latencies: HashMap::new(),
+        }
+    }
+
+    pub fn validator_groups(mut self, validator_groups: u64) -> Self {
+        self.validator_groups = validator_groups;
+        self
+    }
+
+    /// Defaults to the number of validators in the smallest epoch.
+    pub fn num_shards(mut self, num_shards: NumShards) -> Self {
+        self.num_shards = Some(num_shards);
+        self
+    }
+
+    pub fn epoch_length(mut self, epoch_length: BlockHeightDelta) -> Self {
+        self.epoch_length = epoch_length;
+        self
+    }
+
+    /// Minimum and maximum block production delays, in milliseconds.
+    pub fn block_prod_time(mut self, min: u64, max: u64) -> Self {
+        self.min_block_prod_time = min;
+        self.max_block_prod_time = max;
+        self
+    }
+
+    pub fn enable_doomslug(mut self, enable_doomslug: bool) -> Self {
+        self.enable_doomslug = enable_doomslug;
+        self
+    }
+
+    /// Latency of the links which aren't given one with `Self::latency`.
+    pub fn default_latency(mut self, latency: Duration) -> Self {
+        self.default_latency = latency;
+        self
+    }
+
+    /// Latency of the messages sent from node `from` to node `to`.  The link in the other
+    /// direction keeps its own latency.
+    pub fn latency(mut self, from: usize, to: usize, latency: Duration) -> Self {
+        self.latencies.insert((from, to), latency);
+        self
+    }
+
+    pub fn build(self) -> TestLoop {
+        let clock = MockClockGuard::default();
+        let start = (Instant::now(), Utc::now());
+        clock.set_time(start.0, start.1);
+
+        let num_shards = self
+            .num_shards
+            .unwrap_or_else(|| self.validators.iter().map(|x| x.len()).min().unwrap() as NumShards);
+        let num_validator_seats =
+            self.validators.iter().map(|x| x.len()).sum::<usize>() as NumSeats;
+        let chain_genesis = ChainGenesis {
+            time: Clock::utc(),
+            height: 0,
+            gas_limit: 1_000_000,
+            min_gas_price: 100,
+            max_gas_price: 1_000_000_000,
+            total_supply: 3_000_000_000_000_000_000_000_000_000_000_000,
+            gas_price_adjustment_rate: Rational::from_integer(0),
+            transaction_validity_period: 10000,
+            epoch_length: self.epoch_length,
+            protocol_version: PROTOCOL_VERSION,
+        };
+
+        let accounts: Vec<AccountId> = self.validators.iter().flatten().cloned().collect();
+        let mut clients = vec![];
+        let mut network_adapters = vec![];
+        for account_id in &accounts {
+            let runtime = Arc::new(KeyValueRuntime::new_with_validators_and_no_gc(
+                create_test_store(),
+                self.validators.clone(),
+                self.validator_groups,
+                num_shards,
+                self.epoch_length,
+                false,
+            ));
+            let signer = Arc::new(InMemoryValidatorSigner::from_seed(
+                account_id.clone(),
+                KeyType::ED25519,
+                account_id.as_ref(),
+            ));
+            let mut config = ClientConfig::test(
+                true,
+                self.min_block_prod_time,
+                self.max_block_prod_time,
+                num_validator_seats,
+                false,
+                true,
+            );
+            config.epoch_length = self.epoch_length;
+            let network_adapter = Arc::new(MockPeerManagerAdapter::default());
+            let mut client = Client::new(
+                config,
+                chain_genesis.clone(),
+                runtime,
+                network_adapter.clone(),
+                Some(signer),
+                self.enable_doomslug,
+                TEST_SEED,
+            )
+            .unwrap();
+            client.sync_status = SyncStatus::NoSync;
+            clients.push(client);
+            network_adapters.push(network_adapter);
+        }
+
+        let mut test_loop = TestLoop {
+            clients,
+            network_adapters,
+            account_to_index: accounts.into_iter().enumerate().map(|(i, a)| (a, i)).collect(),
+            default_latency: self.default_latency,
+            latencies: self.latencies,
+            filter: None,
+            events: BTreeMap::new(),
+            next_event_id: 0,
+            now: Duration::default(),
+            start,
+            system: actix_rt::System::new(),
+            clock,
+        };
+        for node in 0..test_loop.clients.len() {
+            for timer in TIMERS {
+                test_loop.schedule(Duration::default(), Event::Timer { node, timer });
+            }
+        }
+        test_loop
+    }
+}
+
+/// Clients of a network of validators driven by an explicit queue of events in virtual time.
+pub struct TestLoop {
+    pub clients: Vec<Client>,
+    network_adapters: Vec<Arc<MockPeerManagerAdapter>>,
+    account_to_index: HashMap<AccountId, usize>,
+    default_latency: Duration,
+    latencies: HashMap<(usize, usize), Duration>,
+    filter: Option<MessageFilter>,
+    /// Pending events by the time they are due at and the order they were scheduled in.
+    events: BTreeMap<(Duration, u64), Event>,
+    next_event_id: u64,
+    /// Virtual time passed since the start of the loop.
+    now: Duration,
+    start: (Instant, DateTime<Utc>),
+    /// Runs the futures the state sync spawns when it sends out requests.  They only look at the
+    /// responses of the mocked network and are never awaited.
+    system: SystemRunner,
+    clock: MockClockGuard,
+}
+
+impl TestLoop {
+    pub fn builder(validators: Vec<Vec<AccountId>>) -> TestLoopBuilder {
+        TestLoopBuilder::new(validators)
+    }
+
+    /// Virtual time passed since the start of the loop.
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    pub fn index(&self, account_id: &AccountId) -> usize {
+        self.account_to_index[account_id]
+    }
+
+    pub fn head_height(&self, node: usize) -> BlockHeight {
+        self.clients[node].chain.head().unwrap().height
+    }
+
+    /// Sets the filter deciding whether a message sent from one node to another is delivered.
+    /// It sees every message when it is sent, so it can also check the traffic.
+    pub fn set_message_filter(
+        &mut self,
+        filter: impl FnMut(usize, usize, &Message) -> bool + 'static,
+    ) {
+        self.filter = Some(Box::new(filter));
+    }
+
+    /// Sends `message` from node `from` to node `to` over their link.
+    pub fn send(&mut self, from: usize, to: usize, message: Message) {
+        if let Some(filter) = &mut self.filter {
+            if !filter(from, to, &message) {
+                debug!(target: "test_loop", "Dropping {:?} from {} to {}", message, from, to);
+                return;
+            }
+        }
+        let latency = self.latencies.get(&(from, to)).copied().unwrap_or(self.default_latency);
+        self.schedule(latency, Event::Message { from, to, message });
+    }
+
+    /// Handles the next event.  Returns false if there are no events left.
+    pub fn step(&mut self) -> bool {
+        let (&key, _) = match self.events.iter().next() {
+            Some(entry) => entry,
+            None => return false,
+        };
+        let event = self.events.remove(&key).unwrap();
+        self.set_now(key.0);
+        match event {
+            Event::Message { from, to, message } => self.handle_message(from, to, message),
+            Event::Timer { node, timer } => {
+                self.handle_timer(node, timer);
+                let period = self.timer_period(node, timer);
+                self.schedule(period, Event::Timer { node, timer });
+            }
+        }
+        self.route_network_requests();
+        true
+    }
+
+    /// Handles the events due in the next `duration` of virtual time.
+    pub fn run_for(&mut self, duration: Duration) {
+        let deadline = self.now + duration;
+        while self.events.keys().next().map_or(false, |(time, _)| *time <= deadline) {
+            self.step();
+        }
+        self.set_now(deadline);
+    }
+
+    /// Handles events until `condition` holds, checking it after every event.  Panics if it
+    /// doesn't hold within `max_duration` of virtual time.
+    pub fn run_until(&mut self, mut condition: impl FnMut(&Self) -> bool, max_duration: Duration) {
+        let deadline = self.now + max_duration;
+        while !condition(self) {
+            let next = self.events.keys().next().map(|(time, _)| *time);
+            assert!(
+                next.map_or(false, |time| time <= deadline),
+                "Condition didn't hold within {:?}",
+                max_duration
+            );
+            self.step();
+        }
+    }
+
+    fn schedule(&mut self, delay: Duration, event: Event) {
+        self.events.insert((self.now + delay, self.next_event_id), event);
+        self.next_event_id += 1;
+    }
+
+    fn set_now(&mut self, now: Duration) {
+        self.now = now;
+        let utc = self.start.1 + chrono::Duration::from_std(now).unwrap();
+        self.clock.set_time(self.start.0 + now, utc);
+    }
+
+    /// Identifies the sender of a request in the requests sent back to it.
+    fn route_back(node: usize) -> CryptoHash {
+        hash(&node.to_le_bytes())
+    }
+
+    fn timer_period(&self, node: usize, timer: Timer) -> Duration {
+        let config = &self.clients[node].config;
+        let period = match timer {
+            Timer::BlockProduction => config.block_production_tracking_delay,
+            Timer::Doomslug => config.doosmslug_step_period,
+            Timer::Catchup => config.catchup_step_period,
+            Timer::ChunkRequestRetry => config.chunk_request_retry_period,
+        };
+        // A zero period would keep the virtual time from moving forward.
+        period.max(Duration::from_millis(1))
+    }
+
+    /// Delivers the requests the clients have sent to their network adapters.
+    fn route_network_requests(&mut self) {
+        for from in 0..self.clients.len() {
+            while let Some(request) = self.network_adapters[from].pop() {
+                if let PeerManagerMessageRequest::NetworkRequests(request) = request {
+                    self.route_network_request(from, request);
+                }
+            }
+        }
+    }
+
+    fn route_network_request(&mut self, from: usize, request: NetworkRequests) {
+        let (to, message) = match request {
+            NetworkRequests::Block { block } => {
+                for to in (0..self.clients.len()).filter(|&to| to != from) {
+                    self.send(from, to, Message::Block(block.clone()));
+                }
+                return;
+            }
+            NetworkRequests::Approval { approval_message } => (
+                self.account_to_index.get(&approval_message.target),
+                Message::Approval(approval_message.approval),
+            ),
+            NetworkRequests::PartialEncodedChunkMessage { account_id, partial_encoded_chunk } => (
+                self.account_to_index.get(&account_id),
+                Message::PartialEncodedChunk(partial_encoded_chunk.into()),
+            ),
+            NetworkRequests::PartialEncodedChunkForward { account_id, forward } => (
+                self.account_to_index.get(&account_id),
+                Message::PartialEncodedChunkForward(forward),
+            ),
+            NetworkRequests::PartialEncodedChunkRequest { target, request, .. } => (
+                target.account_id.and_then(|account_id| self.account_to_index.get(&account_id)),
+                Message::PartialEncodedChunkRequest(request),
+            ),
+            NetworkRequests::PartialEncodedChunkResponse { route_back, response } => (
+                self.account_to_index.values().find(|&&node| Self::route_back(node) == route_back),
+                Message::PartialEncodedChunkResponse(response),
+            ),
+            NetworkRequests::StateRequestHeader {
+                shard_id,
+                sync_hash,
+                target: AccountOrPeerIdOrHash::AccountId(account_id),
+            } => (
+                self.account_to_index.get(&account_id),
+                Message::StateRequestHeader { shard_id, sync_hash },
+            ),
+            NetworkRequests::StateRequestPart {
+                shard_id,
+                sync_hash,
+                part_id,
+                target: AccountOrPeerIdOrHash::AccountId(account_id),
+            } => (
+                self.account_to_index.get(&account_id),
+                Message::StateRequestPart { shard_id, sync_hash, part_id },
+            ),
+            _ => return,
+        };
+        match to.copied() {
+            Some(to) => self.send(from, to, message),
+            None => debug!(target: "test_loop", "No recipient for {:?} from {}", message, from),
+        }
+    }
+
+    fn handle_message(&mut self, from: usize, to: usize, message: Message) {
+        let client = &mut self.clients[to];
+        let accepted_blocks = match message {
+            Message::Block(block) => {
+                let (accepted_blocks, result) =
+                    client.process_block(MaybeValidated::from(block), Provenance::NONE);
+                if let Err(err) = result {
+                    debug!(target: "test_loop", "Node {} failed to process a block: {}", to, err);
+                }
+                accepted_blocks
+            }
+            Message::Approval(approval) => {
+                let peer_id = PeerId::new(PublicKey::empty(KeyType::ED25519));
+                client.collect_block_approval(&approval, ApprovalType::PeerApproval(peer_id));
+                vec![]
+            }
+            Message::PartialEncodedChunk(partial_encoded_chunk) => client
+                .process_partial_encoded_chunk(MaybeValidated::from(partial_encoded_chunk))
+                .unwrap_or_default(),
+            Message::PartialEncodedChunkForward(forward) => {
+                client.process_partial_encoded_chunk_forward(forward).unwrap_or_default()
+            }
+            Message::PartialEncodedChunkRequest(request) => {
+                client.shards_mgr.process_partial_encoded_chunk_request(
+                    request,
+                    Self::route_back(from),
+                    client.chain.mut_store(),
+                    &mut client.rs,
+                );
+                vec![]
+            }
+            Message::PartialEncodedChunkResponse(response) => {
+                client.process_partial_encoded_chunk_response(response).unwrap_or_default()
+            }
+            Message::StateRequestHeader { shard_id, sync_hash } => {
+                let response = state_response(client, shard_id, sync_hash, None);
+                self.send(to, from, Message::StateResponse(response));
+                vec![]
+            }
+            Message::StateRequestPart { shard_id, sync_hash, part_id } => {
+                let response = state_response(client, shard_id, sync_hash, Some(part_id));
+                self.send(to, from, Message::StateResponse(response));
+                vec![]
+            }
+            Message::StateResponse(response) => {
+                client.process_state_response(response);
+                vec![]
+            }
+        };
+        self.process_accepted_blocks(to, accepted_blocks);
+    }
+
+    fn handle_timer(&mut self, node: usize, timer: Timer) {
+        match timer {
+            Timer::BlockProduction => self.handle_block_production(node),
+            Timer::Doomslug => self.clients[node].process_doomslug_timer(),
+            Timer::Catchup => self.run_catchup(node),
+            Timer::ChunkRequestRetry => {
+                let client = &mut self.clients[node];
+                if let Ok(header_head) = client.chain.header_head() {
+                    client.shards_mgr.resend_chunk_requests(&header_head);
+                }
+            }
+        }
+    }
+
+    /// Produces the blocks the node is ready to produce, the way
+    /// `ClientActor::handle_block_production` does.
+    fn handle_block_production(&mut self, node: usize) {
+        let client = &mut self.clients[node];
+        let _ = client.check_and_update_doomslug_tip();
+        let head = client.chain.head().unwrap();
+        let latest_known = client.chain.mut_store().get_latest_known().unwrap();
+        let epoch_id =
+            client.runtime_adapter.get_epoch_id_from_prev_block(&head.last_block_hash).unwrap();
+        let me = client.validator_signer.as_ref().map(|signer| signer.validator_id().clone());
+
+        let mut produced = vec![];
+        for height in
+            latest_known.height + 1..=client.doomslug.get_largest_height_crossing_threshold()
+        {
+            let producer = client.runtime_adapter.get_block_producer(&epoch_id, height).unwrap();
+            if me.as_ref() != Some(&producer) {
+                continue;
+            }
+            let missing_chunks_wait = client.missing_chunks_wait(&head, &epoch_id).unwrap();
+            if !client.doomslug.ready_to_produce_block(
+                Clock::instant(),
+                height,
+                missing_chunks_wait.is_none(),
+                missing_chunks_wait.unwrap_or_default(),
+            ) {
+                continue;
+            }
+            match client.produce_block(height) {
+                Ok(Some(block)) => produced.push(block),
+                Ok(None) => {}
+                Err(err) => error!(target: "test_loop", "Block production failed: {}", err),
+            }
+        }
+
+        for block in produced {
+            self.route_network_request(node, NetworkRequests::Block { block: block.clone() });
+            let (accepted_blocks, result) = self.clients[node]
+                .process_block(MaybeValidated::from_validated(block), Provenance::PRODUCED);
+            if let Err(err) = result {
+                debug!(target: "test_loop", "Node {} failed to process its block: {}", node, err);
+            }
+            self.process_accepted_blocks(node, accepted_blocks);
+        }
+    }
+
+    /// Runs a step of the catchup with the jobs it schedules run right away.
+    fn run_catchup(&mut self, node: usize) {
+        let state_parts_requests = RefCell::new(vec![]);
+        let block_catch_up_requests = RefCell::new(vec![]);
+        let state_split_requests = RefCell::new(vec![]);
+        let client = &mut self.clients[node];
+        let result = self.system.block_on(async {
+            client.run_catchup(
+                &vec![],
+                &|msg: ApplyStatePartsRequest| state_parts_requests.borrow_mut().push(msg),
+                &|msg: BlockCatchUpRequest| block_catch_up_requests.borrow_mut().push(msg),
+                &|msg: StateSplitRequest| state_split_requests.borrow_mut().push(msg),
+            )
+        });
+
+        for msg in state_parts_requests.into_inner() {
+            let result = apply_state_parts(&msg);
+            match client.catchup_state_syncs.get_mut(&msg.sync_hash) {
+                Some((sync, _, _)) => sync.set_apply_result(msg.shard_id, result),
+                None => client.state_sync.set_apply_result(msg.shard_id, result),
+            }
+        }
+        for msg in block_catch_up_requests.into_inner() {
+            let results = do_apply_chunks(msg.work);
+            if let Some((_, _, blocks_catch_up_state)) =
+                client.catchup_state_syncs.get_mut(&msg.sync_hash)
+            {
+                let saved_store_update =
+                    blocks_catch_up_state.scheduled_blocks.remove(&msg.block_hash).unwrap();
+                blocks_catch_up_state
+                    .processed_blocks
+                    .insert(msg.block_hash, (saved_store_update, results));
+            }
+        }
+        for msg in state_split_requests.into_inner() {
+            let results = msg.runtime.build_state_for_split_shards(
+                msg.shard_uid,
+                &msg.state_root,
+                &msg.next_epoch_shard_layout,
+            );
+            match client.catchup_state_syncs.get_mut(&msg.sync_hash) {
+                Some((sync, _, _)) => sync.set_split_result(msg.shard_id, results),
+                None => client.state_sync.set_split_result(msg.shard_id, results),
+            }
+        }
+
+        match result {
+            Ok(accepted_blocks) => self.process_accepted_blocks(node, accepted_blocks),
+            Err(err) => error!(target: "test_loop", "Node {} failed to catch up: {:?}", node, err),
+        }
+    }
+
+    fn process_accepted_blocks(&mut self, node: usize, accepted_blocks: Vec<AcceptedBlock>) {
+        for block in accepted_blocks {
+            self.clients[node].on_block_accepted(block.hash, block.status, block.provenance);
+        }
+    }
+}
+
+/// Builds the response to a request of the state header, or of a state part if `part_id` is
+/// given, the way `ViewClientActor` does.
+fn state_response(
+    client: &mut Client,
+    shard_id: ShardId,
+    sync_hash: CryptoHash,
+    part_id: Option<u64>,
+) -> StateResponseInfo {
+    let empty = ShardStateSyncResponse::V1(ShardStateSyncResponseV1 { header: None, part: None });
+    let state_response = match client.chain.check_sync_hash_validity(&sync_hash) {
+        Ok(true) => match part_id {
+            None => match client.chain.get_state_response_header(shard_id, sync_hash) {
+                Ok(ShardStateSyncResponseHeader::V1(header)) => {
+                    ShardStateSyncResponse::V1(ShardStateSyncResponseV1 {
+                        header: Some(header),
+                        part: None,
+                    })
+                }
+                Ok(ShardStateSyncResponseHeader::V2(header)) => {
+                    ShardStateSyncResponse::V2(ShardStateSyncResponseV2 {
+                        header: Some(header),
+                        part: None,
+                    })
+                }
+                Err(_) => empty,
+            },
+            Some(part_id) => {
+                match client.chain.get_state_response_part(shard_id, part_id, sync_hash) {
+                    Ok(part) => ShardStateSyncResponse::V1(ShardStateSyncResponseV1 {
+                        header: None,
+                        part: Some((part_id, part)),
+                    }),
+                    Err(_) => empty,
+                }
+            }
+        },
+        _ => empty,
+    };
+    StateResponseInfo::V2(StateResponseInfoV2 { shard_id, sync_hash, state_response })
+}
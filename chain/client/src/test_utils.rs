@@ -66,7 +66,7 @@ use near_primitives::utils::MaybeValidated;
 
 pub type PeerManagerMock = Mocker<PeerManagerActor>;
 
-const TEST_SEED: RngSeed = [3; 32];
+pub(crate) const TEST_SEED: RngSeed = [3; 32];
 /// Sets up ClientActor and ViewClientActor viewing the same store/runtime.
 pub fn setup(
     validators: Vec<Vec<AccountId>>,
@@ -579,6 +579,7 @@ pub fn setup_mock_all_validators(
                                     archival: true,
                                 },
                                 partial_edge_info: PartialEdgeInfo::default(),
+                                capabilities: None,
                             })
                             .collect();
                         let peers2 = peers.clone();
@@ -591,6 +592,8 @@ pub fn setup_mock_all_validators(
                             received_bytes_per_sec: 0,
                             known_producers: vec![],
                             peer_counter: 0,
+                            banned_peers: vec![],
+                            recent_dropped_routed_messages: vec![],
                         };
                         client_addr.do_send(NetworkClientMessages::NetworkInfo(info));
                     }
@@ -976,6 +979,10 @@ pub fn setup_mock_all_validators(
                         }
                         NetworkRequests::ForwardTx(_, _)
                         | NetworkRequests::SyncRoutingTable { .. }
+                        | NetworkRequests::SyncAccountsData { .. }
+                        | NetworkRequests::SetTier1Accounts { .. }
+                        | NetworkRequests::BanPeerManually { .. }
+                        | NetworkRequests::UnbanPeer { .. }
                         | NetworkRequests::FetchRoutingTable
                         | NetworkRequests::PingTo(_, _)
                         | NetworkRequests::FetchPingPongInfo
@@ -1,11 +1,13 @@
 use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use actix::{Addr, System};
 use borsh::{BorshDeserialize, BorshSerialize};
 use futures::{future, FutureExt};
 
+use crate::test_loop::{Message, TestLoop};
 use crate::test_utils::setup_mock_all_validators;
 use crate::{ClientActor, Query, ViewClientActor};
 use near_actix_test_utils::run_actix;
@@ -619,72 +621,42 @@ fn test_catchup_random_single_part_sync_common(skip_15: bool, non_zero: bool, he
 /// This test would fail if at any point validators got stuck with state sync, or block
 /// production stalled for any other reason.
 #[test]
-#[cfg_attr(not(feature = "expensive_tests"), ignore)]
 fn test_catchup_sanity_blocks_produced() {
-    let validator_groups = 2;
     init_integration_logger();
-    run_actix(async move {
-        let connectors: Arc<RwLock<Vec<(Addr<ClientActor>, Addr<ViewClientActor>)>>> =
-            Arc::new(RwLock::new(vec![]));
-
-        let heights = Arc::new(RwLock::new(HashMap::new()));
-        let heights1 = heights.clone();
-
-        let check_height =
-            move |hash: CryptoHash, height| match heights1.write().unwrap().entry(hash.clone()) {
-                Entry::Occupied(entry) => {
-                    assert_eq!(*entry.get(), height);
-                }
-                Entry::Vacant(entry) => {
-                    entry.insert(height);
-                }
-            };
-
-        let (validators, key_pairs) = get_validators_and_key_pairs();
-
-        let (_, conn, _) = setup_mock_all_validators(
-            validators.clone(),
-            key_pairs.clone(),
-            validator_groups,
-            true,
-            2000,
-            false,
-            false,
-            5,
-            true,
-            vec![false; validators.iter().map(|x| x.len()).sum()],
-            vec![true; validators.iter().map(|x| x.len()).sum()],
-            false,
-            Arc::new(RwLock::new(Box::new(
-                move |_account_id: _, msg: &PeerManagerMessageRequest| {
-                    let msg = msg.as_network_requests_ref();
-                    let propagate = if let NetworkRequests::Block { block } = msg {
-                        check_height(*block.hash(), block.header().height());
-
-                        if block.header().height() % 10 == 5 {
-                            check_height(*block.header().prev_hash(), block.header().height() - 2);
-                        } else {
-                            check_height(*block.header().prev_hash(), block.header().height() - 1);
-                        }
-
-                        if block.header().height() >= 25 {
-                            System::current().stop();
-                        }
-
-                        // Do not propagate blocks at heights %10=4
-                        block.header().height() % 10 != 4
-                    } else {
-                        true
-                    };
-
-                    (NetworkResponses::NoResponse.into(), propagate)
-                },
-            ))),
-        );
-        *connectors.write().unwrap() = conn;
-
-        near_network::test_utils::wait_or_panic(60000);
+    let (validators, _) = get_validators_and_key_pairs();
+    let mut test_loop = TestLoop::builder(validators)
+        .validator_groups(2)
+        .epoch_length(5)
+        .block_prod_time(200, 600)
+        .build();
+
+    let mut heights = HashMap::new();
+    let mut check_height = move |hash: CryptoHash, height| match heights.entry(hash) {
+        Entry::Occupied(entry) => {
+            assert_eq!(*entry.get(), height);
+        }
+        Entry::Vacant(entry) => {
+            entry.insert(height);
+        }
+    };
+    test_loop.set_message_filter(move |_, _, message| match message {
+        Message::Block(block) => {
+            check_height(*block.hash(), block.header().height());
+            if block.header().height() % 10 == 5 {
+                check_height(*block.header().prev_hash(), block.header().height() - 2);
+            } else {
+                check_height(*block.header().prev_hash(), block.header().height() - 1);
+            }
+            // Do not propagate blocks at heights %10=4
+            block.header().height() % 10 != 4
+        }
+        _ => true,
     });
+
+    test_loop.run_until(
+        |test_loop| (0..test_loop.clients.len()).any(|i| test_loop.head_height(i) >= 25),
+        Duration::from_secs(120),
+    );
 }
 
 enum ChunkGrievingPhases {
@@ -1,12 +1,14 @@
 use std::collections::HashSet;
+use std::time::Duration;
 
 use crate::test_utils::TestEnv;
-use near_chain::ChainGenesis;
+use near_chain::{ChainGenesis, Provenance};
 use near_logger_utils::init_integration_logger;
 use near_network::types::NetworkRequests;
 use near_network_primitives::types::PartialEncodedChunkRequestMsg;
 use near_primitives::hash::CryptoHash;
 use near_primitives::sharding::ReedSolomonWrapper;
+use near_primitives::types::EpochId;
 
 #[test]
 fn test_request_chunk_restart() {
@@ -51,3 +53,49 @@ fn test_request_chunk_restart() {
         assert!(false);
     }
 }
+
+#[test]
+fn test_missing_chunks_wait() {
+    init_integration_logger();
+    let mut env = TestEnv::builder(ChainGenesis::test()).build();
+    let missing_chunks_wait = |env: &mut TestEnv| {
+        let client = &mut env.clients[0];
+        let head = client.chain.head().unwrap();
+        let epoch_id: EpochId =
+            client.runtime_adapter.get_epoch_id_from_prev_block(&head.last_block_hash).unwrap();
+        client.missing_chunks_wait(&head, &epoch_id).unwrap()
+    };
+    let config = env.clients[0].config.clone();
+
+    // The chunk for the next block is always ready when the chunk producer is healthy.
+    for height in 1..6 {
+        env.produce_block(0, height);
+        assert_eq!(missing_chunks_wait(&mut env), None);
+    }
+
+    // Blocks at heights 7 to 9 miss their chunks, so the wait for the next chunk is extended.
+    for height in 6..10 {
+        let block = env.clients[0].produce_block(height).unwrap().unwrap();
+        env.process_block_with_options(0, block, Provenance::PRODUCED, true, false);
+    }
+    assert_eq!(missing_chunks_wait(&mut env), Some(config.missed_chunks_wait_step));
+
+    // The extension is bounded.
+    for height in 10..20 {
+        let block = env.clients[0].produce_block(height).unwrap().unwrap();
+        env.process_block_with_options(0, block, Provenance::PRODUCED, true, false);
+    }
+    assert_eq!(missing_chunks_wait(&mut env), Some(config.missed_chunks_max_wait));
+
+    // Chunks of unreachable producers are not waited for.
+    env.clients[0].set_unreachable_chunk_producers(HashSet::from(["test0".parse().unwrap()]));
+    assert_eq!(missing_chunks_wait(&mut env), None);
+    env.clients[0].set_unreachable_chunk_producers(HashSet::new());
+
+    // Once the chunks are included again, the wait goes back to normal.
+    env.produce_block(0, 20);
+    env.produce_block(0, 21);
+    let block = env.clients[0].produce_block(22).unwrap().unwrap();
+    env.process_block_with_options(0, block, Provenance::PRODUCED, true, false);
+    assert_eq!(missing_chunks_wait(&mut env), Some(Duration::ZERO));
+}
@@ -18,13 +18,17 @@ use near_chain::{
 };
 use near_chain_configs::{ClientConfig, ProtocolConfigView};
 use near_client_primitives::types::{
-    Error, GetBlock, GetBlockError, GetBlockHash, GetBlockProof, GetBlockProofError,
-    GetBlockProofResponse, GetBlockWithMerkleTree, GetChunkError, GetExecutionOutcome,
+    CachedTrieNode, Error, GetAppliedChunkInfo, GetBlock, GetBlockError, GetBlockHash,
+    GetBlockHeaderOnly, GetBlockProof, GetBlockProofError, GetBlockProofResponse,
+    GetBlockWithMerkleTree, GetChunkError, GetEpochInfo, GetEpochInfoError, GetExecutionOutcome,
     GetExecutionOutcomeError, GetExecutionOutcomesForBlock, GetGasPrice, GetGasPriceError,
-    GetNextLightClientBlockError, GetProtocolConfig, GetProtocolConfigError, GetReceipt,
-    GetReceiptError, GetStateChangesError, GetStateChangesWithCauseInBlock,
-    GetStateChangesWithCauseInBlockForTrackedShards, GetValidatorInfoError, Query, QueryError,
-    TxStatus, TxStatusError,
+    GetHottestTrieNodes, GetNextLightClientBlockError, GetProtocolConfig, GetProtocolConfigError,
+    GetProtocolUpgradeStatus, GetProtocolUpgradeStatusError, GetReceipt, GetReceiptError,
+    GetReceiptQueueChangesInBlock, GetStateChangesError, GetStateChangesWithCauseInBlock,
+    GetStateChangesWithCauseInBlockForTrackedShards, GetStoreProperties, GetTopContracts,
+    GetUnstakeSchedule, GetUnstakeScheduleError, GetValidatorInfoError,
+    GetValidatorProductionStats, GetValidatorProductionStatsError, MakeSnapshot, Query, QueryError,
+    SetMaxBackgroundJobs, SnapshotInfo, StoreProperties, TxStatus, TxStatusError,
 };
 use near_network::types::{NetworkRequests, PeerManagerAdapter, PeerManagerMessageRequest};
 #[cfg(feature = "test_features")]
@@ -38,6 +42,7 @@ use near_primitives::block::{Block, BlockHeader, GenesisId, Tip};
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{merklize, PartialMerkleTree};
 use near_primitives::network::AnnounceAccount;
+use near_primitives::runtime::apply_state::AppliedChunkInfo;
 use near_primitives::sharding::ShardChunk;
 use near_primitives::syncing::{
     ShardStateSyncResponse, ShardStateSyncResponseHeader, ShardStateSyncResponseV1,
@@ -49,14 +54,15 @@ use near_primitives::types::{
 };
 use near_primitives::views::validator_stake_view::ValidatorStakeView;
 use near_primitives::views::{
-    BlockView, ChunkView, EpochValidatorInfo, ExecutionOutcomeWithIdView,
-    FinalExecutionOutcomeView, FinalExecutionOutcomeViewEnum, FinalExecutionStatus, GasPriceView,
-    LightClientBlockView, QueryRequest, QueryResponse, ReceiptView, StateChangesKindsView,
-    StateChangesView,
+    BlockHeaderOnlyView, BlockView, ChunkSummaryView, ChunkView, EpochInfoView, EpochValidatorInfo,
+    ExecutionOutcomeWithIdView, FinalExecutionOutcomeView, FinalExecutionOutcomeViewEnum,
+    FinalExecutionStatus, GasPriceView, LightClientBlockView, ProtocolUpgradeStatusView,
+    QueryRequest, QueryResponse, ReceiptQueueChangesView, ReceiptView, StateChangesKindsView,
+    StateChangesView, TopContractsView, UnstakeScheduleView, ValidatorProductionStatsView,
 };
 
 use crate::{
-    sync, GetChunk, GetExecutionOutcomeResponse, GetNextLightClientBlock, GetStateChanges,
+    metrics, sync, GetChunk, GetExecutionOutcomeResponse, GetNextLightClientBlock, GetStateChanges,
     GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered,
 };
 
@@ -183,18 +189,61 @@ impl ViewClientActor {
         }
     }
 
+    /// `SyncCheckpoint::EarliestAvailable` resolves to genesis on archival nodes, and otherwise
+    /// to the earliest block which isn't garbage collected and is at or above `min_height`, the
+    /// height below which the data of the request is garbage collected.
     fn get_block_hash_by_sync_checkpoint(
         &mut self,
         synchronization_checkpoint: &near_primitives::types::SyncCheckpoint,
+        min_height: BlockHeight,
     ) -> Result<Option<CryptoHash>, near_chain::Error> {
         use near_primitives::types::SyncCheckpoint;
 
         match synchronization_checkpoint {
             SyncCheckpoint::Genesis => Ok(Some(self.chain.genesis().hash().clone())),
-            SyncCheckpoint::EarliestAvailable => self.chain.get_earliest_block_hash(),
+            SyncCheckpoint::EarliestAvailable if self.config.archive => {
+                Ok(Some(self.chain.genesis().hash().clone()))
+            }
+            SyncCheckpoint::EarliestAvailable => {
+                self.chain.get_earliest_block_hash_from(min_height)
+            }
         }
     }
 
+    fn get_block_by_reference(
+        &mut self,
+        block_reference: BlockReference,
+    ) -> Result<Block, GetBlockError> {
+        let block = match block_reference {
+            BlockReference::Finality(finality) => {
+                let block_hash = self.get_block_hash_by_finality(&finality)?;
+                self.chain.get_block(&block_hash).map(Clone::clone)
+            }
+            BlockReference::BlockId(BlockId::Height(height)) => {
+                self.chain.get_block_by_height(height).map(Clone::clone)
+            }
+            BlockReference::BlockId(BlockId::Hash(hash)) => {
+                self.chain.get_block(&hash).map(Clone::clone)
+            }
+            BlockReference::SyncCheckpoint(sync_checkpoint) => {
+                if let Some(block_hash) =
+                    self.get_block_hash_by_sync_checkpoint(&sync_checkpoint, 0)?
+                {
+                    self.chain.get_block(&block_hash).map(Clone::clone)
+                } else {
+                    return Err(GetBlockError::NotSyncedYet);
+                }
+            }
+        }?;
+        Ok(block)
+    }
+
+    /// Whether the node tracks the shards of `tracked_accounts`, which change with the shard
+    /// layout, rather than all shards.
+    fn tracks_shards_by_account(&self) -> bool {
+        self.config.tracked_shards.is_empty() && !self.config.tracked_accounts.is_empty()
+    }
+
     fn handle_query(&mut self, msg: Query) -> Result<QueryResponse, QueryError> {
         let header = match msg.block_reference {
             BlockReference::BlockId(BlockId::Height(block_height)) => {
@@ -207,8 +256,13 @@ impl ViewClientActor {
                 .get_block_hash_by_finality(finality)
                 .and_then(|block_hash| self.chain.get_block_header(&block_hash)),
             BlockReference::SyncCheckpoint(ref synchronization_checkpoint) => {
+                // The state of blocks below the GC stop height is garbage collected.
+                let min_height = match self.chain.head() {
+                    Ok(tip) => self.runtime_adapter.get_gc_stop_height(&tip.last_block_hash),
+                    Err(_) => 0,
+                };
                 if let Some(block_hash) = self
-                    .get_block_hash_by_sync_checkpoint(synchronization_checkpoint)
+                    .get_block_hash_by_sync_checkpoint(synchronization_checkpoint, min_height)
                     .map_err(|err| match err.kind() {
                         near_chain::near_chain_primitives::ErrorKind::DBNotFoundErr(_) => {
                             QueryError::UnknownBlock {
@@ -256,6 +310,19 @@ impl ViewClientActor {
             .shard_id_to_uid(shard_id, header.epoch_id())
             .map_err(|err| QueryError::InternalError { error_message: err.to_string() })?;
 
+        // The state of a shard which isn't tracked anymore may still be in the store, don't
+        // answer from it.
+        if self.tracks_shards_by_account()
+            && !self.runtime_adapter.cares_about_shard(
+                self.validator_account_id.as_ref(),
+                header.prev_hash(),
+                shard_id,
+                true,
+            )
+        {
+            return Err(QueryError::UnavailableShard { requested_shard_id: shard_id });
+        }
+
         let tip = self.chain.head();
         let chunk_extra = self.chain.get_chunk_extra(header.hash(), &shard_uid).map_err(|err| {
             match err.kind() {
@@ -436,6 +503,8 @@ impl ViewClientActor {
                                 )?;
                             }
                             return Ok(None);
+                        } else if self.chain.store().is_outcome_pruned(&tx_hash).unwrap_or(false) {
+                            return Err(TxStatusError::GarbageCollectedTransaction(tx_hash));
                         } else {
                             return Err(TxStatusError::MissingTransaction(tx_hash));
                         }
@@ -475,6 +544,14 @@ impl ViewClientActor {
         self.chain.retrieve_headers(hashes, sync::MAX_BLOCK_HEADERS, None)
     }
 
+    fn retrieve_headers_by_height(
+        &mut self,
+        start_height: BlockHeight,
+        end_height: BlockHeight,
+    ) -> Result<Vec<BlockHeader>, near_chain::Error> {
+        self.chain.retrieve_headers_by_height(start_height, end_height, sync::MAX_BLOCK_HEADERS)
+    }
+
     fn check_signature_account_announce(
         &self,
         announce_account: &AnnounceAccount,
@@ -504,7 +581,12 @@ impl ViewClientActor {
         head.height
     }
 
+    /// Whether a state sync request should be served.  Requests are rejected if serving state sync
+    /// is disabled in the config or too many of them have been served recently.
     fn check_state_sync_request(&self) -> bool {
+        if !self.config.serve_state_sync {
+            return false;
+        }
         let mut cache = self.state_request_cache.lock().expect(POISONED_LOCK_ERR);
         let now = Clock::instant();
         let cutoff = now - self.config.view_client_throttle_period;
@@ -540,28 +622,7 @@ impl Handler<GetBlock> for ViewClientActor {
 
     #[perf]
     fn handle(&mut self, msg: GetBlock, _: &mut Self::Context) -> Self::Result {
-        let block = match msg.0 {
-            BlockReference::Finality(finality) => {
-                let block_hash = self.get_block_hash_by_finality(&finality)?;
-                self.chain.get_block(&block_hash).map(Clone::clone)
-            }
-            BlockReference::BlockId(BlockId::Height(height)) => {
-                self.chain.get_block_by_height(height).map(Clone::clone)
-            }
-            BlockReference::BlockId(BlockId::Hash(hash)) => {
-                self.chain.get_block(&hash).map(Clone::clone)
-            }
-            BlockReference::SyncCheckpoint(sync_checkpoint) => {
-                if let Some(block_hash) =
-                    self.get_block_hash_by_sync_checkpoint(&sync_checkpoint)?
-                {
-                    self.chain.get_block(&block_hash).map(Clone::clone)
-                } else {
-                    return Err(GetBlockError::NotSyncedYet);
-                }
-            }
-        }?;
-
+        let block = self.get_block_by_reference(msg.0)?;
         let block_author = self
             .runtime_adapter
             .get_block_producer(block.header().epoch_id(), block.header().height())?;
@@ -570,6 +631,33 @@ impl Handler<GetBlock> for ViewClientActor {
     }
 }
 
+/// Handles retrieving a block with summaries of its chunks instead of their headers.
+impl Handler<GetBlockHeaderOnly> for ViewClientActor {
+    type Result = Result<BlockHeaderOnlyView, GetBlockError>;
+
+    #[perf]
+    fn handle(&mut self, msg: GetBlockHeaderOnly, _: &mut Self::Context) -> Self::Result {
+        let block = self.get_block_by_reference(msg.0)?;
+        let block_author = self
+            .runtime_adapter
+            .get_block_producer(block.header().epoch_id(), block.header().height())?;
+        let chunks = block
+            .chunks()
+            .iter()
+            .map(|chunk_header| {
+                let summary = self.chain.store().get_chunk_summary(&chunk_header.chunk_hash())?;
+                Ok(ChunkSummaryView::new(chunk_header, summary))
+            })
+            .collect::<Result<_, near_chain::Error>>()?;
+
+        Ok(BlockHeaderOnlyView {
+            author: block_author,
+            header: block.header().clone().into(),
+            chunks,
+        })
+    }
+}
+
 /// Handles retrieving block header from the chain.
 impl Handler<GetBlockHash> for ViewClientActor {
     type Result = Result<CryptoHash, GetBlockError>;
@@ -588,7 +676,7 @@ impl Handler<GetBlockHash> for ViewClientActor {
                 self.chain.get_block_header(&hash).map(|_| hash)
             }
             BlockReference::SyncCheckpoint(sync_checkpoint) => Ok(self
-                .get_block_hash_by_sync_checkpoint(&sync_checkpoint)?
+                .get_block_hash_by_sync_checkpoint(&sync_checkpoint, 0)?
                 .ok_or(GetBlockError::NotSyncedYet)?),
         }
         .map_err(std::convert::Into::into)
@@ -644,6 +732,17 @@ impl Handler<GetChunk> for ViewClientActor {
                 let block = self.chain.get_block_by_height(height)?.clone();
                 get_chunk_from_block(block, shard_id, &mut self.chain)?
             }
+            GetChunk::SyncCheckpoint(sync_checkpoint, shard_id) => {
+                // Chunks below the chunk tail are garbage collected.
+                let chunk_tail = self.chain.store().chunk_tail()?;
+                let block_hash = self
+                    .get_block_hash_by_sync_checkpoint(&sync_checkpoint, chunk_tail)?
+                    .ok_or_else(|| GetChunkError::UnknownBlock {
+                        error_message: format!("No block is available for {:?}", sync_checkpoint),
+                    })?;
+                let block = self.chain.get_block(&block_hash)?.clone();
+                get_chunk_from_block(block, shard_id, &mut self.chain)?
+            }
         };
 
         let chunk_inner = chunk.cloned_header().take_inner();
@@ -729,6 +828,135 @@ impl Handler<GetValidatorOrdered> for ViewClientActor {
             })?)
     }
 }
+impl Handler<GetEpochInfo> for ViewClientActor {
+    type Result = Result<EpochInfoView, GetEpochInfoError>;
+
+    #[perf]
+    fn handle(&mut self, msg: GetEpochInfo, _: &mut Self::Context) -> Self::Result {
+        let epoch_id = match msg {
+            GetEpochInfo::EpochId(epoch_id) => epoch_id,
+            GetEpochInfo::EpochHeight(epoch_height) => {
+                // Pick the epoch of our chain among those computed on all forks. An epoch id is
+                // the hash of a block (but for the genesis epoch), which tells which one it is.
+                let epoch_ids =
+                    self.runtime_adapter.get_epoch_ids_from_epoch_height(epoch_height)?;
+                epoch_ids
+                    .into_iter()
+                    .find(|epoch_id| {
+                        epoch_id.0 == CryptoHash::default()
+                            || self.chain.find_common_header(&[epoch_id.0]).is_some()
+                    })
+                    .ok_or(GetEpochInfoError::UnknownEpochHeight(epoch_height))?
+            }
+        };
+        Ok(self.runtime_adapter.get_epoch_info_view(&epoch_id)?)
+    }
+}
+
+impl Handler<MakeSnapshot> for ViewClientActor {
+    type Result = Result<SnapshotInfo, String>;
+
+    #[perf]
+    fn handle(&mut self, msg: MakeSnapshot, _: &mut Self::Context) -> Self::Result {
+        // Taken on the view client so that block processing in the client actor isn't blocked
+        // by anything but the memtable flush of the checkpoint.
+        let info = self
+            .chain
+            .store()
+            .store()
+            .make_snapshot(&msg.destination)
+            .map_err(|err| format!("Failed to create snapshot: {}", err))?;
+        metrics::DB_SNAPSHOT_SIZE.set(info.size_bytes as i64);
+        metrics::DB_SNAPSHOT_TIME.observe(info.duration_ms as f64 / 1000.0);
+        Ok(info)
+    }
+}
+
+impl Handler<SetMaxBackgroundJobs> for ViewClientActor {
+    type Result = Result<(), String>;
+
+    #[perf]
+    fn handle(&mut self, msg: SetMaxBackgroundJobs, _: &mut Self::Context) -> Self::Result {
+        let store = self.chain.store().store();
+        let db = store.get_rocksdb().ok_or_else(|| "The store isn't RocksDB".to_string())?;
+        db.set_max_background_jobs(msg.jobs)
+            .map_err(|err| format!("Failed to set max_background_jobs: {}", err))?;
+        info!(target: "client", "Set max_background_jobs of RocksDB to {}", msg.jobs);
+        Ok(())
+    }
+}
+
+impl Handler<GetHottestTrieNodes> for ViewClientActor {
+    type Result = Vec<CachedTrieNode>;
+
+    #[perf]
+    fn handle(&mut self, msg: GetHottestTrieNodes, _: &mut Self::Context) -> Self::Result {
+        self.runtime_adapter.get_tries().hottest_cached_nodes(msg.limit)
+    }
+}
+
+impl Handler<GetStoreProperties> for ViewClientActor {
+    type Result = Option<StoreProperties>;
+
+    #[perf]
+    fn handle(&mut self, _msg: GetStoreProperties, _: &mut Self::Context) -> Self::Result {
+        self.chain.store().get_store_properties()
+    }
+}
+
+impl Handler<GetTopContracts> for ViewClientActor {
+    type Result = Option<TopContractsView>;
+
+    #[perf]
+    fn handle(&mut self, msg: GetTopContracts, _: &mut Self::Context) -> Self::Result {
+        self.runtime_adapter.contract_execution().map(|tracker| tracker.top(msg.limit))
+    }
+}
+
+impl Handler<GetValidatorProductionStats> for ViewClientActor {
+    type Result = Result<ValidatorProductionStatsView, GetValidatorProductionStatsError>;
+
+    #[perf]
+    fn handle(&mut self, msg: GetValidatorProductionStats, _: &mut Self::Context) -> Self::Result {
+        // use header head because this is latest from the perspective of epoch manager
+        let head = self.chain.header_head()?;
+        let stats = self
+            .runtime_adapter
+            .get_validator_production_stats(&head.last_block_hash, &msg.account_id)?;
+        if stats.epochs.iter().all(|epoch| epoch.is_empty()) {
+            return Err(GetValidatorProductionStatsError::NoAssignments(msg.account_id));
+        }
+        Ok(stats)
+    }
+}
+
+impl Handler<GetUnstakeSchedule> for ViewClientActor {
+    type Result = Result<UnstakeScheduleView, GetUnstakeScheduleError>;
+
+    #[perf]
+    fn handle(&mut self, msg: GetUnstakeSchedule, _: &mut Self::Context) -> Self::Result {
+        // use header head because this is latest from the perspective of epoch manager
+        let head = self.chain.header_head()?;
+        let schedule =
+            self.runtime_adapter.get_unstake_schedule(&head.last_block_hash, &msg.account_id)?;
+        if schedule.locked == 0 {
+            return Err(GetUnstakeScheduleError::NotStaked(msg.account_id));
+        }
+        Ok(schedule)
+    }
+}
+
+impl Handler<GetProtocolUpgradeStatus> for ViewClientActor {
+    type Result = Result<ProtocolUpgradeStatusView, GetProtocolUpgradeStatusError>;
+
+    #[perf]
+    fn handle(&mut self, _msg: GetProtocolUpgradeStatus, _: &mut Self::Context) -> Self::Result {
+        // use header head because this is latest from the perspective of epoch manager
+        let head = self.chain.header_head()?;
+        Ok(self.runtime_adapter.get_protocol_upgrade_status(&head.last_block_hash)?)
+    }
+}
+
 /// Returns a list of change kinds per account in a store for a given block.
 impl Handler<GetStateChangesInBlock> for ViewClientActor {
     type Result = Result<StateChangesKindsView, GetStateChangesError>;
@@ -818,6 +1046,66 @@ impl Handler<GetStateChangesWithCauseInBlockForTrackedShards> for ViewClientActo
     }
 }
 
+/// Returns a hashmap where the key represents the ShardID and the value is the list of
+/// receipts added to and removed from the receipt queues of the shard by the block.  Shards the
+/// node doesn't save the changes for are omitted.
+impl Handler<GetReceiptQueueChangesInBlock> for ViewClientActor {
+    type Result = Result<HashMap<ShardId, ReceiptQueueChangesView>, GetStateChangesError>;
+
+    #[perf]
+    fn handle(
+        &mut self,
+        msg: GetReceiptQueueChangesInBlock,
+        _: &mut Self::Context,
+    ) -> Self::Result {
+        let num_shards = self.runtime_adapter.num_shards(&msg.epoch_id)?;
+        let mut changes_by_shard_id = HashMap::new();
+        for shard_id in 0..num_shards {
+            let shard_uid = self.runtime_adapter.shard_id_to_uid(shard_id, &msg.epoch_id)?;
+            if let Some(changes) =
+                self.chain.store().get_receipt_queue_changes(&msg.block_hash, &shard_uid)?
+            {
+                changes_by_shard_id.insert(shard_id, changes.into());
+            }
+        }
+        Ok(changes_by_shard_id)
+    }
+}
+
+/// Returns the parameters the chunks of the block were applied with by shard id.  Shards the node
+/// didn't apply the chunk of, or applied it before the parameters were saved, are omitted.
+impl Handler<GetAppliedChunkInfo> for ViewClientActor {
+    type Result = Result<HashMap<ShardId, AppliedChunkInfo>, String>;
+
+    #[perf]
+    fn handle(&mut self, msg: GetAppliedChunkInfo, _: &mut Self::Context) -> Self::Result {
+        let epoch_id = self
+            .chain
+            .get_block_header(&msg.block_hash)
+            .map_err(|err| err.to_string())?
+            .epoch_id()
+            .clone();
+        let num_shards =
+            self.runtime_adapter.num_shards(&epoch_id).map_err(|err| err.to_string())?;
+        let mut infos_by_shard_id = HashMap::new();
+        for shard_id in 0..num_shards {
+            let shard_uid = self
+                .runtime_adapter
+                .shard_id_to_uid(shard_id, &epoch_id)
+                .map_err(|err| err.to_string())?;
+            if let Some(info) = self
+                .chain
+                .store()
+                .get_applied_chunk_info(&msg.block_hash, &shard_uid)
+                .map_err(|err| err.to_string())?
+            {
+                infos_by_shard_id.insert(shard_id, info);
+            }
+        }
+        Ok(infos_by_shard_id)
+    }
+}
+
 /// Returns the next light client block, given the hash of the last block known to the light client.
 /// There are three cases:
 ///  1. The last block known to the light client is in the same epoch as the tip:
@@ -980,6 +1268,14 @@ impl Handler<GetBlockProof> for ViewClientActor {
 
     #[perf]
     fn handle(&mut self, msg: GetBlockProof, _: &mut Self::Context) -> Self::Result {
+        let block_height = self.chain.get_block_header(&msg.block_hash)?.height();
+        let head_height = self.chain.get_block_header(&msg.head_block_hash)?.height();
+        if block_height > head_height {
+            return Err(GetBlockProofError::BlockAfterHead {
+                block_hash: msg.block_hash,
+                head_block_hash: msg.head_block_hash,
+            });
+        }
         self.chain.check_block_final_and_canonical(&msg.block_hash)?;
         self.chain.check_block_final_and_canonical(&msg.head_block_hash)?;
         let block_header_lite = self.chain.get_block_header(&msg.block_hash)?.clone().into();
@@ -1006,7 +1302,7 @@ impl Handler<GetProtocolConfig> for ViewClientActor {
             }
             BlockReference::SyncCheckpoint(sync_checkpoint) => {
                 if let Some(block_hash) =
-                    self.get_block_hash_by_sync_checkpoint(&sync_checkpoint)?
+                    self.get_block_hash_by_sync_checkpoint(&sync_checkpoint, 0)?
                 {
                     self.chain.get_block_header(&block_hash).map(Clone::clone)
                 } else {
@@ -1150,6 +1446,20 @@ impl Handler<NetworkViewClientMessages> for ViewClientActor {
                     NetworkViewClientResponses::NoResponse
                 }
             }
+            NetworkViewClientMessages::BlockHeadersByHeightRequest { start_height, end_height } => {
+                #[cfg(feature = "test_features")]
+                {
+                    if self.adv.read().unwrap().adv_disable_header_sync {
+                        return NetworkViewClientResponses::NoResponse;
+                    }
+                }
+
+                if let Ok(headers) = self.retrieve_headers_by_height(start_height, end_height) {
+                    NetworkViewClientResponses::BlockHeaders(headers)
+                } else {
+                    NetworkViewClientResponses::NoResponse
+                }
+            }
             NetworkViewClientMessages::GetChainInfo => match self.chain.head() {
                 Ok(head) => {
                     match self.runtime_adapter.num_shards(&head.epoch_id) {
@@ -1157,10 +1467,22 @@ impl Handler<NetworkViewClientMessages> for ViewClientActor {
                             // convert config tracked shards
                             // runtime will track all shards if config tracked shards is not empty
                             // https://github.com/near/nearcore/issues/4930
-                            let tracked_shards = if self.config.tracked_shards.is_empty() {
-                                vec![]
-                            } else {
+                            let tracked_shards = if !self.config.tracked_shards.is_empty() {
                                 (0..num_shards).collect()
+                            } else if self.tracks_shards_by_account() {
+                                // Shards of the tracked accounts in the epoch of the head.
+                                (0..num_shards)
+                                    .filter(|&shard_id| {
+                                        self.runtime_adapter.cares_about_shard(
+                                            None,
+                                            &head.prev_block_hash,
+                                            shard_id,
+                                            true,
+                                        )
+                                    })
+                                    .collect()
+                            } else {
+                                vec![]
                             };
                             NetworkViewClientResponses::ChainInfo {
                                 genesis_id: GenesisId {
@@ -1170,6 +1492,7 @@ impl Handler<NetworkViewClientMessages> for ViewClientActor {
                                 height: self.get_height(&head),
                                 tracked_shards,
                                 archival: self.config.archive,
+                                serves_state_sync: self.config.serve_state_sync,
                             }
                         }
                         Err(err) => {
@@ -1182,6 +1505,7 @@ impl Handler<NetworkViewClientMessages> for ViewClientActor {
                                 height: self.get_height(&head),
                                 tracked_shards: self.config.tracked_shards.clone(),
                                 archival: self.config.archive,
+                                serves_state_sync: self.config.serve_state_sync,
                             }
                         }
                     }
@@ -1196,6 +1520,7 @@ impl Handler<NetworkViewClientMessages> for ViewClientActor {
                         height: self.chain.genesis().height(),
                         tracked_shards: self.config.tracked_shards.clone(),
                         archival: self.config.archive,
+                        serves_state_sync: self.config.serve_state_sync,
                     }
                 }
             },
@@ -1,9 +1,11 @@
 use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
 
 use primitive_types::U256;
 use tracing::{debug, warn};
 
+use near_primitives::checked_feature;
 use near_primitives::epoch_manager::block_info::BlockInfo;
 use near_primitives::epoch_manager::epoch_info::{EpochInfo, EpochSummary};
 use near_primitives::epoch_manager::{
@@ -11,16 +13,24 @@ use near_primitives::epoch_manager::{
 };
 use near_primitives::errors::EpochError;
 use near_primitives::hash::CryptoHash;
+use near_primitives::runtime::config_store::RuntimeConfigStore;
 use near_primitives::types::validator_stake::ValidatorStake;
 use near_primitives::types::{
-    AccountId, ApprovalStake, Balance, BlockChunkValidatorStats, BlockHeight, EpochId, ShardId,
-    ValidatorId, ValidatorKickoutReason, ValidatorStats,
+    AccountId, ApprovalStake, Balance, BlockChunkValidatorStats, BlockHeight, EpochHeight, EpochId,
+    ShardId, ValidatorId, ValidatorKickoutReason, ValidatorStats,
 };
+use near_primitives::utils::index_to_bytes;
 use near_primitives::version::{ProtocolVersion, UPGRADABILITY_FIX_PROTOCOL_VERSION};
 use near_primitives::views::{
-    CurrentEpochValidatorInfo, EpochValidatorInfo, NextEpochValidatorInfo, ValidatorKickoutView,
+    BlockProductionView, ChunkProductionView, CurrentEpochValidatorInfo, EpochInfoView,
+    EpochProductionStatsView, EpochValidatorInfo, EpochValidatorView, NextEpochValidatorInfo,
+    ProtocolUpgradeStatusView, ProtocolVersionVoteView, ScheduledProtocolUpgradeView,
+    StakeUnlockRangeView, StakeUnlockView, UnstakeScheduleView, ValidatorKickoutView,
+    ValidatorProductionStatsView,
+};
+use near_store::{
+    ColBlockInfo, ColEpochHeightToEpochId, ColEpochInfo, ColEpochStart, Store, StoreUpdate,
 };
-use near_store::{ColBlockInfo, ColEpochInfo, ColEpochStart, Store, StoreUpdate};
 
 use crate::proposals::proposals_to_epoch_info;
 pub use crate::reward_calculator::RewardCalculator;
@@ -74,11 +84,14 @@ pub struct EpochManager {
 }
 
 impl EpochManager {
+    /// Constructs the epoch manager of the chain with `genesis_config`, whose protocol versions
+    /// use the runtime configs of `runtime_config_store`.
     pub fn new_from_genesis_config(
         store: Store,
         genesis_config: &GenesisConfig,
+        runtime_config_store: Arc<RuntimeConfigStore>,
     ) -> Result<Self, EpochError> {
-        let reward_calculator = RewardCalculator::new(genesis_config);
+        let reward_calculator = RewardCalculator::new(genesis_config, runtime_config_store);
         let all_epoch_config = AllEpochConfig::from(genesis_config);
         Self::new(
             store,
@@ -96,8 +109,10 @@ impl EpochManager {
         reward_calculator: RewardCalculator,
         validators: Vec<ValidatorStake>,
     ) -> Result<Self, EpochError> {
-        let validator_reward = vec![(reward_calculator.protocol_treasury_account.clone(), 0u128)]
+        let validator_reward = reward_calculator
+            .protocol_treasury_split(genesis_protocol_version)
             .into_iter()
+            .map(|(account_id, _)| (account_id, 0u128))
             .collect();
         let mut epoch_manager = EpochManager {
             store,
@@ -257,10 +272,15 @@ impl EpochManager {
         let chunk_producer_kickout_threshold = config.chunk_producer_kickout_threshold;
         let mut validator_block_chunk_stats = HashMap::new();
         let mut validator_kickout = HashMap::new();
+        let mut total_stake: Balance = 0;
+        let mut slashed_stake: Balance = 0;
+        let mut offline_validators = vec![];
 
         for (i, v) in epoch_info.validators_iter().enumerate() {
             let account_id = v.account_id().clone();
+            total_stake += v.stake();
             if slashed.contains_key(&account_id) {
+                slashed_stake += v.stake();
                 continue;
             }
             let block_stats = block_validator_tracker
@@ -297,14 +317,14 @@ impl EpochManager {
             }
 
             let is_already_kicked_out = prev_validator_kickout.contains_key(&account_id);
+            let stats = BlockChunkValidatorStats { block_stats: block_stats.clone(), chunk_stats };
             if !validator_kickout.contains_key(&account_id) {
-                validator_block_chunk_stats.insert(
-                    account_id.clone(),
-                    BlockChunkValidatorStats { block_stats: block_stats.clone(), chunk_stats },
-                );
+                validator_block_chunk_stats.insert(account_id.clone(), stats);
                 if !is_already_kicked_out {
                     all_kicked_out = false;
                 }
+            } else {
+                offline_validators.push((account_id.clone(), v.stake(), stats));
             }
             if (max_validator.is_none() || block_stats.produced > maximum_block_prod)
                 && !is_already_kicked_out
@@ -313,6 +333,23 @@ impl EpochManager {
                 max_validator = Some(v);
             }
         }
+        if checked_feature!(
+            "protocol_feature_max_kickout_stake",
+            MaxKickoutStake,
+            epoch_info.protocol_version()
+        ) {
+            let exempted = Self::exempt_from_kickout(
+                &mut validator_kickout,
+                offline_validators,
+                total_stake,
+                slashed_stake,
+                config.validator_min_remaining_stake_perc,
+            );
+            if !exempted.is_empty() {
+                debug!(target: "epoch_manager", "Exempted from kickout: {:?}", exempted);
+                all_kicked_out = false;
+            }
+        }
         if all_kicked_out {
             if let Some(validator) = max_validator {
                 validator_kickout.remove(validator.account_id());
@@ -321,6 +358,60 @@ impl EpochManager {
         (validator_kickout, validator_block_chunk_stats)
     }
 
+    /// Exempts offline validators with the best uptime from kickout, until the stake of the
+    /// validators which are neither kicked out nor slashed is at least
+    /// `min_remaining_stake_perc` percent of `total_stake`. Exempted validators keep their seats,
+    /// but don't get a reward for the epoch. Returns the exempted validators.
+    fn exempt_from_kickout(
+        validator_kickout: &mut HashMap<AccountId, ValidatorKickoutReason>,
+        mut offline_validators: Vec<(AccountId, Balance, BlockChunkValidatorStats)>,
+        total_stake: Balance,
+        slashed_stake: Balance,
+        min_remaining_stake_perc: u8,
+    ) -> Vec<AccountId> {
+        let min_remaining_stake = total_stake / 100 * u128::from(min_remaining_stake_perc)
+            + total_stake % 100 * u128::from(min_remaining_stake_perc) / 100;
+        let offline_stake: Balance = offline_validators.iter().map(|(_, stake, _)| stake).sum();
+        let mut remaining_stake = total_stake.saturating_sub(slashed_stake + offline_stake);
+        // Best uptime first, ties are broken by stake and then account id to keep it deterministic.
+        offline_validators.sort_by(|(account_a, stake_a, stats_a), (account_b, stake_b, stats_b)| {
+            let (numer_a, denom_a) = Self::uptime(stats_a);
+            let (numer_b, denom_b) = Self::uptime(stats_b);
+            (numer_b * denom_a)
+                .cmp(&(numer_a * denom_b))
+                .then(stake_b.cmp(stake_a))
+                .then(account_a.cmp(account_b))
+        });
+        let mut exempted = vec![];
+        for (account_id, stake, _) in offline_validators {
+            if remaining_stake >= min_remaining_stake {
+                break;
+            }
+            validator_kickout.remove(&account_id);
+            remaining_stake += stake;
+            exempted.push(account_id);
+        }
+        exempted
+    }
+
+    /// Average of the block and chunk production rate of a validator, as numerator and
+    /// denominator. Duties the validator didn't have count as fully performed.
+    fn uptime(stats: &BlockChunkValidatorStats) -> (u128, u128) {
+        let rate = |stats: &ValidatorStats| {
+            if stats.expected == 0 {
+                (1, 1)
+            } else {
+                (u128::from(stats.produced), u128::from(stats.expected))
+            }
+        };
+        let (block_produced, block_expected) = rate(&stats.block_stats);
+        let (chunk_produced, chunk_expected) = rate(&stats.chunk_stats);
+        (
+            block_produced * chunk_expected + chunk_produced * block_expected,
+            2 * block_expected * chunk_expected,
+        )
+    }
+
     fn collect_blocks_info(
         &mut self,
         last_block_info: &BlockInfo,
@@ -975,6 +1066,22 @@ impl EpochManager {
         Ok((stake_info, validator_reward, slashing_info))
     }
 
+    /// Accounts receiving the protocol treasury reward of the epoch ending with the block
+    /// `last_block_hash`, which is paid out with the validator rewards.
+    pub fn get_protocol_treasury_accounts(
+        &mut self,
+        last_block_hash: &CryptoHash,
+    ) -> Result<Vec<AccountId>, EpochError> {
+        let epoch_id = self.get_epoch_id(last_block_hash)?;
+        let protocol_version = self.get_epoch_info(&epoch_id)?.protocol_version();
+        Ok(self
+            .reward_calculator
+            .protocol_treasury_split(protocol_version)
+            .into_iter()
+            .map(|(account_id, _)| account_id)
+            .collect())
+    }
+
     /// Compute slashing information. Returns a hashmap of account id to slashed amount for double sign
     /// slashing.
     fn compute_double_sign_slashing_info(
@@ -1175,6 +1282,199 @@ impl EpochManager {
         })
     }
 
+    /// Returns block and chunk production of `account_id` at each of its assignments in the
+    /// epoch of `last_block_hash` up to and including that block, and in the previous epoch.
+    /// Production is derived from the block infos the same way `EpochInfoAggregator` does.
+    pub fn get_validator_production_stats(
+        &mut self,
+        last_block_hash: &CryptoHash,
+        account_id: &AccountId,
+    ) -> Result<ValidatorProductionStatsView, EpochError> {
+        let mut epochs = vec![];
+        let mut last_block_hash = *last_block_hash;
+        for _ in 0..2 {
+            let mut block_info = self.get_block_info(&last_block_hash)?.clone();
+            if block_info.prev_hash() == &CryptoHash::default() {
+                // Genesis block, nothing is produced in it.
+                break;
+            }
+            let epoch_id = block_info.epoch_id().clone();
+            let epoch_info = self.get_epoch_info(&epoch_id)?.clone();
+            let first_block_hash = *block_info.epoch_first_block();
+            let mut blocks = vec![];
+            let mut chunks = vec![];
+            if let Some(&validator_id) = epoch_info.get_validator_id(account_id) {
+                // Walk the epoch backwards and reverse the results at the end.
+                loop {
+                    let prev_block_info = self.get_block_info(block_info.prev_hash())?.clone();
+                    let prev_height = *prev_block_info.height();
+                    let height = *block_info.height();
+                    for (shard_id, produced) in block_info.chunk_mask().iter().enumerate().rev() {
+                        let shard_id = shard_id as ShardId;
+                        if Self::chunk_producer_from_info(&epoch_info, prev_height + 1, shard_id)
+                            == validator_id
+                        {
+                            chunks.push(ChunkProductionView {
+                                height,
+                                shard_id,
+                                produced: *produced,
+                            });
+                        }
+                    }
+                    for h in (prev_height + 1..=height).rev() {
+                        if Self::block_producer_from_info(&epoch_info, h) == validator_id {
+                            blocks.push(BlockProductionView { height: h, produced: h == height });
+                        }
+                    }
+                    if block_info.hash() == &first_block_hash {
+                        break;
+                    }
+                    block_info = prev_block_info;
+                }
+            }
+            blocks.reverse();
+            chunks.reverse();
+            epochs.push(EpochProductionStatsView {
+                epoch_id,
+                epoch_height: epoch_info.epoch_height(),
+                blocks,
+                chunks,
+            });
+            last_block_hash = *self.get_block_info(&first_block_hash)?.prev_hash();
+        }
+        Ok(ValidatorProductionStatsView { account_id: account_id.clone(), epochs })
+    }
+
+    /// Returns the stake `account_id` has locked as of `last_block_hash` and when the part of it
+    /// being unstaked is returned. The stake returned at the end of an epoch is the maximum of
+    /// the last three stake changes (see `compute_stake_return_info`), so the schedule follows
+    /// from the stake changes of the previous, current and next epoch and of the epoch after that.
+    ///
+    /// Until the last block of the current epoch is known, the latter is estimated from the
+    /// proposals made so far, assuming they are accepted, and the start of every following epoch
+    /// is given as a range assuming finality keeps trailing the head by as many blocks as it does
+    /// at `last_block_hash`.
+    pub fn get_unstake_schedule(
+        &mut self,
+        last_block_hash: &CryptoHash,
+        account_id: &AccountId,
+    ) -> Result<UnstakeScheduleView, EpochError> {
+        let block_info = self.get_block_info(last_block_hash)?.clone();
+        let epoch_id = block_info.epoch_id().clone();
+        let epoch_info = self.get_epoch_info(&epoch_id)?.clone();
+        let epoch_length =
+            self.config.for_protocol_version(epoch_info.protocol_version()).epoch_length;
+        let stake_of =
+            |epoch_info: &EpochInfo| *epoch_info.stake_change().get(account_id).unwrap_or(&0);
+
+        // The genesis block is not part of a regular epoch and shares its epoch info with the
+        // first one.
+        let is_genesis = block_info.prev_hash() == &CryptoHash::default();
+        let (first_block_info, next_epoch_id) = if is_genesis {
+            (block_info.clone(), epoch_id.clone())
+        } else {
+            (
+                self.get_block_info(block_info.epoch_first_block())?.clone(),
+                self.get_next_epoch_id(last_block_hash)?,
+            )
+        };
+        let prev_stake = if first_block_info.prev_hash() == &CryptoHash::default() {
+            0
+        } else {
+            let prev_epoch_id =
+                self.get_block_info(first_block_info.prev_hash())?.epoch_id().clone();
+            stake_of(self.get_epoch_info(&prev_epoch_id)?)
+        };
+        let stake = stake_of(&epoch_info);
+        let next_stake = stake_of(self.get_epoch_info(&next_epoch_id)?);
+        let is_epoch_end_fixed = self.is_next_block_in_next_epoch(&block_info)?;
+        let next_next_stake = if is_epoch_end_fixed {
+            // The epoch after next is finalized at the last block of the current one.
+            stake_of(self.get_epoch_info(&EpochId(*last_block_hash))?)
+        } else {
+            // Validators without a proposal keep their stake unless kicked out.
+            let aggregator =
+                self.get_and_update_epoch_info_aggregator(&epoch_id, last_block_hash, true)?;
+            aggregator.all_proposals.get(account_id).map_or(next_stake, |proposal| proposal.stake())
+        };
+        // Staking more locks the difference right away, so the locked amount includes the stake
+        // of the epoch after next.
+        let stakes = [prev_stake, stake, next_stake, next_next_stake];
+        let locked = *stakes.iter().max().unwrap();
+
+        let height = *block_info.height();
+        let next_epoch_start = if is_epoch_end_fixed {
+            height + 1
+        } else {
+            std::cmp::max(*first_block_info.height() + epoch_length, height + 1)
+        };
+        let finality_lag = height.saturating_sub(*block_info.last_finalized_height());
+
+        // Block production rate is measured over the current epoch, or over the previous one at
+        // the start of an epoch.
+        let mut reference_block_info = first_block_info.clone();
+        if *reference_block_info.height() == height
+            && reference_block_info.prev_hash() != &CryptoHash::default()
+        {
+            let prev_block_info = self.get_block_info(reference_block_info.prev_hash())?.clone();
+            reference_block_info = if prev_block_info.prev_hash() == &CryptoHash::default() {
+                prev_block_info
+            } else {
+                self.get_block_info(prev_block_info.epoch_first_block())?.clone()
+            };
+        }
+        let timestamp = *block_info.timestamp_nanosec();
+        let elapsed_heights = height - *reference_block_info.height();
+        let elapsed_time = timestamp.saturating_sub(*reference_block_info.timestamp_nanosec());
+        let timestamp_at = |target_height: BlockHeight| {
+            if elapsed_heights == 0 {
+                return None;
+            }
+            let delta =
+                (target_height - height) as u128 * elapsed_time as u128 / elapsed_heights as u128;
+            Some(timestamp.saturating_add(delta.min(u64::MAX as u128) as u64))
+        };
+
+        let mut unlocks = vec![];
+        let mut remaining = locked;
+        for k in 1..stakes.len() {
+            let still_locked = *stakes[k..].iter().max().unwrap();
+            if still_locked == remaining {
+                continue;
+            }
+            let k = k as u64;
+            let estimated_height = next_epoch_start + (k - 1) * epoch_length;
+            // Once the current epoch end is fixed, the start of the next epoch is known.
+            let unfixed_epoch_starts = if is_epoch_end_fixed { k - 1 } else { k };
+            let range = if unfixed_epoch_starts == 0 {
+                None
+            } else {
+                let max_height = estimated_height + unfixed_epoch_starts * finality_lag;
+                Some(StakeUnlockRangeView {
+                    min_height: estimated_height,
+                    max_height,
+                    min_timestamp: timestamp_at(estimated_height),
+                    max_timestamp: timestamp_at(max_height),
+                })
+            };
+            unlocks.push(StakeUnlockView {
+                amount: remaining - still_locked,
+                epoch_height: epoch_info.epoch_height() + k,
+                estimated_height,
+                estimated_timestamp: timestamp_at(estimated_height),
+                range,
+            });
+            remaining = still_locked;
+        }
+        Ok(UnstakeScheduleView {
+            account_id: account_id.clone(),
+            epoch_height: epoch_info.epoch_height(),
+            locked,
+            unstaking: locked - next_next_stake,
+            unlocks,
+        })
+    }
+
     /// Compare two epoch ids based on their start height. This works because finality gadget
     /// guarantees that we cannot have two different epochs on two forks
     pub fn compare_epoch_id(
@@ -1371,10 +1671,106 @@ impl EpochManager {
         store_update
             .set_ser(ColEpochInfo, epoch_id.as_ref(), &epoch_info)
             .map_err(EpochError::from)?;
+        // Epochs computed on different forks get the same height, keep all of them.
+        let mut epoch_ids = self.get_epoch_ids_from_epoch_height(epoch_info.epoch_height())?;
+        if !epoch_ids.contains(epoch_id) {
+            epoch_ids.push(epoch_id.clone());
+            store_update
+                .set_ser(
+                    ColEpochHeightToEpochId,
+                    &index_to_bytes(epoch_info.epoch_height()),
+                    &epoch_ids,
+                )
+                .map_err(EpochError::from)?;
+        }
         self.epochs_info.put(epoch_id.clone(), epoch_info);
         Ok(())
     }
 
+    /// Returns the ids of the epochs with the given height whose epoch info is known. There are
+    /// several of them if the epoch was computed on different forks, the caller has to resolve
+    /// which one is on its chain.
+    pub fn get_epoch_ids_from_epoch_height(
+        &self,
+        epoch_height: EpochHeight,
+    ) -> Result<Vec<EpochId>, EpochError> {
+        Ok(self
+            .store
+            .get_ser(ColEpochHeightToEpochId, &index_to_bytes(epoch_height))
+            .map_err(EpochError::from)?
+            .unwrap_or_default())
+    }
+
+    /// Returns the validator set of the epoch with roles and stakes, along with other information
+    /// about the epoch.
+    pub fn get_epoch_info_view(&mut self, epoch_id: &EpochId) -> Result<EpochInfoView, EpochError> {
+        let epoch_info = self.get_epoch_info(epoch_id)?.clone();
+        let epoch_start_height = match self.get_epoch_start_from_epoch_id(epoch_id) {
+            Ok(height) => Some(height),
+            Err(EpochError::EpochOutOfBounds(_)) => None,
+            Err(err) => return Err(err),
+        };
+        let block_producers: HashSet<ValidatorId> =
+            epoch_info.block_producers_settlement().iter().copied().collect();
+        let mut validator_to_shards = vec![vec![]; epoch_info.validators_len()];
+        for (shard_id, validators) in epoch_info.chunk_producers_settlement().iter().enumerate() {
+            for validator_id in validators {
+                let shards = &mut validator_to_shards[*validator_id as usize];
+                if !shards.contains(&(shard_id as ShardId)) {
+                    shards.push(shard_id as ShardId);
+                }
+            }
+        }
+        let validators = epoch_info
+            .validators_iter()
+            .zip(validator_to_shards)
+            .enumerate()
+            .map(|(validator_id, (info, chunk_producer_shards))| {
+                let (account_id, public_key, stake) = info.destructure();
+                EpochValidatorView {
+                    account_id,
+                    public_key,
+                    stake,
+                    is_block_producer: block_producers.contains(&(validator_id as ValidatorId)),
+                    chunk_producer_shards,
+                }
+            })
+            .collect();
+        // Kickouts at the end of an epoch are recorded in the epoch info of the epoch after next.
+        // The id of the next epoch is the hash of the last block of the previous one, whose next
+        // epoch is this one, which tells apart next epochs computed on other forks.
+        let mut next_epoch_id = None;
+        for candidate in self.get_epoch_ids_from_epoch_height(epoch_info.epoch_height() + 1)? {
+            if self.get_next_epoch_id(&candidate.0).ok().as_ref() == Some(epoch_id) {
+                next_epoch_id = Some(candidate);
+                break;
+            }
+        }
+        let prev_epoch_kickout = match next_epoch_id {
+            Some(next_epoch_id) => Some(
+                self.get_epoch_info(&next_epoch_id)?
+                    .validator_kickout()
+                    .clone()
+                    .into_iter()
+                    .collect::<BTreeMap<_, _>>()
+                    .into_iter()
+                    .map(|(account_id, reason)| ValidatorKickoutView { account_id, reason })
+                    .collect(),
+            ),
+            None => None,
+        };
+        Ok(EpochInfoView {
+            epoch_id: epoch_id.clone(),
+            epoch_height: epoch_info.epoch_height(),
+            protocol_version: epoch_info.protocol_version(),
+            epoch_start_height,
+            seat_price: epoch_info.seat_price(),
+            validators,
+            fishermen: epoch_info.fishermen_iter().map(Into::into).collect(),
+            prev_epoch_kickout,
+        })
+    }
+
     pub fn get_epoch_validator_info(
         &mut self,
         epoch_id: &EpochId,
@@ -1552,6 +1948,116 @@ impl EpochManager {
             Ok(None)
         }
     }
+
+    /// Returns the votes of the block producers of the current epoch for the next protocol version
+    /// as of `last_block_hash`, and the next protocol upgrade.
+    ///
+    /// A block producer votes with the latest protocol version of the last block it produced in
+    /// the epoch.  If the stake voting for a version exceeds the threshold at the end of an epoch,
+    /// the epoch after next switches to it (see `collect_blocks_info`), so until the last block of
+    /// the current epoch is known the upgrade is scheduled assuming the votes don't change.
+    pub fn get_protocol_upgrade_status(
+        &mut self,
+        last_block_hash: &CryptoHash,
+    ) -> Result<ProtocolUpgradeStatusView, EpochError> {
+        let block_info = self.get_block_info(last_block_hash)?.clone();
+        let epoch_id = block_info.epoch_id().clone();
+        let epoch_info = self.get_epoch_info(&epoch_id)?.clone();
+        let epoch_length =
+            self.config.for_protocol_version(epoch_info.protocol_version()).epoch_length;
+
+        // The genesis block is not part of a regular epoch and shares its epoch info with the
+        // first one.
+        let is_genesis = block_info.prev_hash() == &CryptoHash::default();
+        let (first_block_height, next_epoch_id) = if is_genesis {
+            (*block_info.height(), epoch_id.clone())
+        } else {
+            (
+                *self.get_block_info(block_info.epoch_first_block())?.height(),
+                self.get_next_epoch_id(last_block_hash)?,
+            )
+        };
+        let next_epoch_protocol_version = self.get_epoch_info(&next_epoch_id)?.protocol_version();
+        let reference_version =
+            if epoch_info.protocol_version() >= UPGRADABILITY_FIX_PROTOCOL_VERSION {
+                next_epoch_protocol_version
+            } else {
+                epoch_info.protocol_version()
+            };
+
+        let aggregator =
+            self.get_and_update_epoch_info_aggregator(&epoch_id, last_block_hash, true)?;
+        let mut votes = aggregator
+            .version_tracker
+            .iter()
+            .map(|(validator_id, protocol_version)| {
+                let (account_id, stake) =
+                    epoch_info.get_validator(*validator_id).account_and_stake();
+                ProtocolVersionVoteView { account_id, stake, protocol_version: *protocol_version }
+            })
+            .collect::<Vec<_>>();
+        votes.sort_by(|left, right| left.account_id.cmp(&right.account_id));
+        let mut versions = HashMap::new();
+        for vote in votes.iter() {
+            *versions.entry(vote.protocol_version).or_insert(0) += vote.stake;
+        }
+        let (voted_protocol_version, voted_stake) = versions
+            .into_iter()
+            .max_by(|left, right| left.1.cmp(&right.1))
+            .filter(|(version, _)| *version != reference_version)
+            .map_or((None, 0), |(version, stake)| (Some(version), stake));
+        let total_block_producer_stake: Balance = epoch_info
+            .block_producers_settlement()
+            .iter()
+            .collect::<HashSet<_>>()
+            .iter()
+            .map(|&id| epoch_info.validator_stake(*id))
+            .sum();
+        let config = self.config.for_protocol_version(reference_version);
+        let threshold_stake = total_block_producer_stake
+            * *config.protocol_upgrade_stake_threshold.numer() as u128
+            / *config.protocol_upgrade_stake_threshold.denom() as u128;
+
+        let is_epoch_end_fixed = self.is_next_block_in_next_epoch(&block_info)?;
+        let next_epoch_start = if is_epoch_end_fixed {
+            *block_info.height() + 1
+        } else {
+            std::cmp::max(first_block_height + epoch_length, *block_info.height() + 1)
+        };
+        let scheduled_upgrade = if next_epoch_protocol_version > epoch_info.protocol_version() {
+            Some(ScheduledProtocolUpgradeView {
+                protocol_version: next_epoch_protocol_version,
+                epoch_height: epoch_info.epoch_height() + 1,
+                estimated_height: next_epoch_start,
+                is_final: true,
+            })
+        } else {
+            let next_next_protocol_version = if is_epoch_end_fixed {
+                // The epoch after next is finalized at the last block of the current one.
+                Some(self.get_epoch_info(&EpochId(*last_block_hash))?.protocol_version())
+            } else {
+                voted_protocol_version.filter(|_| voted_stake > threshold_stake)
+            };
+            next_next_protocol_version.filter(|version| *version > next_epoch_protocol_version).map(
+                |protocol_version| ScheduledProtocolUpgradeView {
+                    protocol_version,
+                    epoch_height: epoch_info.epoch_height() + 2,
+                    estimated_height: next_epoch_start + epoch_length,
+                    is_final: is_epoch_end_fixed,
+                },
+            )
+        };
+        Ok(ProtocolUpgradeStatusView {
+            epoch_height: epoch_info.epoch_height(),
+            protocol_version: epoch_info.protocol_version(),
+            next_epoch_protocol_version,
+            voted_protocol_version,
+            voted_stake,
+            threshold_stake,
+            votes,
+            scheduled_upgrade,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -2260,6 +2766,7 @@ mod tests2 {
             epoch_length,
             protocol_reward_rate: Rational::new(1, 10),
             protocol_treasury_account: "near".parse().unwrap(),
+            runtime_config_store: Arc::new(RuntimeConfigStore::test()),
             online_min_threshold: Rational::new(90, 100),
             online_max_threshold: Rational::new(99, 100),
             num_seconds_per_year: 50,
@@ -2363,6 +2870,7 @@ mod tests2 {
             epoch_length,
             protocol_reward_rate: Rational::new(1, 10),
             protocol_treasury_account: "near".parse().unwrap(),
+            runtime_config_store: Arc::new(RuntimeConfigStore::test()),
             online_min_threshold: Rational::new(90, 100),
             online_max_threshold: Rational::new(99, 100),
             num_seconds_per_year: 50,
@@ -2471,6 +2979,7 @@ mod tests2 {
             epoch_length,
             protocol_reward_rate: Rational::new(1, 10),
             protocol_treasury_account: "near".parse().unwrap(),
+            runtime_config_store: Arc::new(RuntimeConfigStore::test()),
             online_min_threshold: Rational::new(90, 100),
             online_max_threshold: Rational::new(99, 100),
             num_seconds_per_year: 1_000_000,
@@ -2631,6 +3140,100 @@ mod tests2 {
         );
     }
 
+    #[test]
+    fn test_unstake_schedule_restake() {
+        let amount_staked = 1_000_000;
+        let validators = vec![
+            ("test1".parse().unwrap(), amount_staked),
+            ("test2".parse().unwrap(), amount_staked),
+        ];
+        let mut epoch_manager = setup_default_epoch_manager(validators, 2, 1, 2, 0, 90, 60);
+        let test1: AccountId = "test1".parse().unwrap();
+        let h = hash_range(8);
+        record_block(&mut epoch_manager, CryptoHash::default(), h[0], 0, vec![]);
+        // Epochs span heights 1-2, 3-4, 5-6 and 7-8. test1 unstakes in the first one.
+        record_block(&mut epoch_manager, h[0], h[1], 1, vec![stake(test1.clone(), 0)]);
+        let schedule = epoch_manager.get_unstake_schedule(&h[1], &test1).unwrap();
+        assert_eq!(schedule.locked, amount_staked);
+        assert_eq!(schedule.unstaking, amount_staked);
+        assert_eq!(
+            schedule.unlocks,
+            vec![StakeUnlockView {
+                amount: amount_staked,
+                epoch_height: schedule.epoch_height + 3,
+                estimated_height: 7,
+                estimated_timestamp: Some(7 * NUM_NS_IN_SECOND),
+                range: Some(StakeUnlockRangeView {
+                    min_height: 7,
+                    max_height: 10,
+                    min_timestamp: Some(7 * NUM_NS_IN_SECOND),
+                    max_timestamp: Some(10 * NUM_NS_IN_SECOND),
+                }),
+            }]
+        );
+
+        // test1 stakes half of its stake again while the rest is still being unstaked, so only
+        // half of it is returned, one epoch earlier.
+        record_block(&mut epoch_manager, h[1], h[2], 2, vec![]);
+        record_block(
+            &mut epoch_manager,
+            h[2],
+            h[3],
+            3,
+            vec![stake(test1.clone(), amount_staked / 2)],
+        );
+        let schedule = epoch_manager.get_unstake_schedule(&h[3], &test1).unwrap();
+        assert_eq!(schedule.locked, amount_staked);
+        assert_eq!(schedule.unstaking, amount_staked / 2);
+        assert_eq!(
+            schedule.unlocks,
+            vec![StakeUnlockView {
+                amount: amount_staked / 2,
+                epoch_height: schedule.epoch_height + 2,
+                estimated_height: 7,
+                estimated_timestamp: Some(7 * NUM_NS_IN_SECOND),
+                range: Some(StakeUnlockRangeView {
+                    min_height: 7,
+                    max_height: 11,
+                    min_timestamp: Some(7 * NUM_NS_IN_SECOND),
+                    max_timestamp: Some(11 * NUM_NS_IN_SECOND),
+                }),
+            }]
+        );
+
+        // The estimate holds once the epoch the stake was proposed in is finalized.
+        record_block(&mut epoch_manager, h[3], h[4], 4, vec![]);
+        let schedule = epoch_manager.get_unstake_schedule(&h[4], &test1).unwrap();
+        assert_eq!(schedule.unstaking, amount_staked / 2);
+        assert_eq!(schedule.unlocks[0].estimated_height, 7);
+        assert_eq!(schedule.unlocks[0].range.as_ref().unwrap().max_height, 9);
+
+        // In the last epoch before the return its start is fixed at the last block of the
+        // current epoch.
+        record_block(&mut epoch_manager, h[4], h[5], 5, vec![]);
+        record_block(&mut epoch_manager, h[5], h[6], 6, vec![]);
+        let schedule = epoch_manager.get_unstake_schedule(&h[6], &test1).unwrap();
+        assert_eq!(schedule.locked, amount_staked);
+        assert_eq!(schedule.unstaking, amount_staked / 2);
+        assert_eq!(
+            schedule.unlocks,
+            vec![StakeUnlockView {
+                amount: amount_staked / 2,
+                epoch_height: schedule.epoch_height + 1,
+                estimated_height: 7,
+                estimated_timestamp: Some(7 * NUM_NS_IN_SECOND),
+                range: None,
+            }]
+        );
+
+        // test2 never unstaked.
+        let schedule =
+            epoch_manager.get_unstake_schedule(&h[6], &"test2".parse().unwrap()).unwrap();
+        assert_eq!(schedule.locked, amount_staked);
+        assert_eq!(schedule.unstaking, 0);
+        assert!(schedule.unlocks.is_empty());
+    }
+
     /// When a block producer fails to produce a block, check that other chunk producers who produce
     /// chunks for that block are not kicked out because of it.
     #[test]
@@ -3077,6 +3680,136 @@ mod tests2 {
         );
     }
 
+    /// Production stats list each block and chunk assignment of the validator with whether it was
+    /// produced, in order of height.
+    #[test]
+    fn test_validator_production_stats() {
+        let stake_amount = 1_000_000;
+        let validators = vec![
+            ("test1".parse().unwrap(), stake_amount),
+            ("test2".parse().unwrap(), stake_amount),
+        ];
+        let epoch_length = 10;
+        let total_supply = stake_amount * validators.len() as u128;
+        let mut em = setup_epoch_manager(
+            validators,
+            epoch_length,
+            4,
+            2,
+            0,
+            90,
+            70,
+            0,
+            default_reward_calculator(),
+        );
+        let hashes = hash_range(8);
+        record_block(&mut em, Default::default(), hashes[0], 0, vec![]);
+        let epoch_id = em.get_epoch_id_from_prev_block(&hashes[0]).unwrap();
+        let epoch_info = em.get_epoch_info(&epoch_id).unwrap().clone();
+        let mut expected_blocks = vec![];
+        let mut expected_chunks = vec![];
+        let mut prev_height = 0;
+        for height in 1..8 {
+            // Nobody produces a block at height 4.
+            if EpochManager::block_producer_from_info(&epoch_info, height) == 0 {
+                expected_blocks.push(BlockProductionView { height, produced: height != 4 });
+            }
+            if height == 4 {
+                continue;
+            }
+            let chunk_mask = (0..4)
+                .map(|shard_id| {
+                    let chunk_producer = EpochManager::chunk_producer_from_info(
+                        &epoch_info,
+                        prev_height + 1,
+                        shard_id,
+                    );
+                    // test1 skips chunks
+                    if chunk_producer == 0 {
+                        expected_chunks.push(ChunkProductionView {
+                            height,
+                            shard_id,
+                            produced: false,
+                        });
+                    }
+                    chunk_producer != 0
+                })
+                .collect();
+            record_with_block_info(
+                &mut em,
+                block_info(
+                    hashes[height as usize],
+                    height,
+                    prev_height,
+                    hashes[prev_height as usize],
+                    hashes[prev_height as usize],
+                    epoch_id.0,
+                    chunk_mask,
+                    total_supply,
+                ),
+            );
+            prev_height = height;
+        }
+
+        let stats =
+            em.get_validator_production_stats(&hashes[7], &"test1".parse().unwrap()).unwrap();
+        // The previous epoch is the genesis, there is nothing to report for it.
+        assert_eq!(stats.epochs.len(), 1);
+        assert_eq!(stats.epochs[0].epoch_id, epoch_id);
+        assert_eq!(stats.epochs[0].blocks, expected_blocks);
+        assert_eq!(stats.epochs[0].chunks, expected_chunks);
+
+        let stats =
+            em.get_validator_production_stats(&hashes[7], &"test3".parse().unwrap()).unwrap();
+        assert!(stats.epochs.iter().all(EpochProductionStatsView::is_empty));
+    }
+
+    /// Production stats cover the epoch of the given block and the one before it, walking back
+    /// across the epoch boundary, but no earlier epochs.
+    #[test]
+    fn test_validator_production_stats_previous_epoch() {
+        let stake_amount = 1_000_000;
+        let validators = vec![
+            ("test1".parse().unwrap(), stake_amount),
+            ("test2".parse().unwrap(), stake_amount),
+        ];
+        let mut em =
+            setup_epoch_manager(validators, 5, 1, 2, 0, 90, 70, 0, default_reward_calculator());
+        let hashes = hash_range(20);
+        record_block(&mut em, Default::default(), hashes[0], 0, vec![]);
+        for height in 1..20 {
+            record_block(&mut em, hashes[height - 1], hashes[height], height as u64, vec![]);
+        }
+
+        let current_epoch_id = em.get_epoch_id(&hashes[19]).unwrap();
+        let first_block_hash = *em.get_block_info(&hashes[19]).unwrap().epoch_first_block();
+        let last_block_of_previous_epoch =
+            *em.get_block_info(&first_block_hash).unwrap().prev_hash();
+        let previous_epoch_id = em.get_epoch_id(&last_block_of_previous_epoch).unwrap();
+        assert_ne!(previous_epoch_id, current_epoch_id);
+        assert_ne!(previous_epoch_id, em.get_epoch_id(&hashes[1]).unwrap());
+
+        let stats =
+            em.get_validator_production_stats(&hashes[19], &"test1".parse().unwrap()).unwrap();
+        assert_eq!(stats.epochs.len(), 2);
+        for (epoch, epoch_id) in stats.epochs.iter().zip([&current_epoch_id, &previous_epoch_id]) {
+            assert_eq!(&epoch.epoch_id, epoch_id);
+            let epoch_info = em.get_epoch_info(epoch_id).unwrap().clone();
+            let validator_id = *epoch_info.get_validator_id(&"test1".parse().unwrap()).unwrap();
+            // Every height has a block, so all block assignments are produced.
+            let expected_blocks: Vec<_> = (1..20)
+                .filter(|&height| &em.get_epoch_id(&hashes[height]).unwrap() == epoch_id)
+                .map(|height| height as BlockHeight)
+                .filter(|&height| {
+                    EpochManager::block_producer_from_info(&epoch_info, height) == validator_id
+                })
+                .map(|height| BlockProductionView { height, produced: true })
+                .collect();
+            assert!(!expected_blocks.is_empty());
+            assert_eq!(epoch.blocks, expected_blocks);
+        }
+    }
+
     /// Test when blocks are all produced, validators can be kicked out because of not producing
     /// enough chunks
     #[test]
@@ -3415,6 +4148,60 @@ mod tests2 {
         assert_ne!(epoch_info2.epoch_height(), epoch_info3.epoch_height());
     }
 
+    #[test]
+    fn test_epoch_info_by_epoch_height() {
+        let stake_amount = 1_000;
+        let validators = vec![
+            ("test1".parse().unwrap(), stake_amount),
+            ("test2".parse().unwrap(), stake_amount),
+            ("test3".parse().unwrap(), stake_amount),
+        ];
+        let mut epoch_manager = setup_default_epoch_manager(validators, 1, 1, 3, 0, 90, 60);
+        let h = hash_range(5);
+        record_block(&mut epoch_manager, CryptoHash::default(), h[0], 0, vec![]);
+        record_block(&mut epoch_manager, h[0], h[2], 2, vec![]);
+        record_block(&mut epoch_manager, h[2], h[4], 4, vec![]);
+
+        let epoch_height2 = epoch_manager.get_epoch_info(&EpochId(h[2])).unwrap().epoch_height();
+        let epoch_height3 = epoch_manager.get_epoch_info(&EpochId(h[4])).unwrap().epoch_height();
+        assert_eq!(
+            epoch_manager.get_epoch_ids_from_epoch_height(epoch_height2).unwrap(),
+            vec![EpochId(h[2])]
+        );
+        assert_eq!(
+            epoch_manager.get_epoch_ids_from_epoch_height(epoch_height3).unwrap(),
+            vec![EpochId(h[4])]
+        );
+        assert_eq!(
+            epoch_manager.get_epoch_ids_from_epoch_height(epoch_height3 + 1).unwrap(),
+            vec![]
+        );
+
+        let view = epoch_manager.get_epoch_info_view(&EpochId(h[4])).unwrap();
+        assert_eq!(view.epoch_id, EpochId(h[4]));
+        assert_eq!(view.epoch_height, epoch_height3);
+        assert_eq!(view.protocol_version, PROTOCOL_VERSION);
+        // The epoch hasn't started yet, and neither has the next one been computed.
+        assert_eq!(view.epoch_start_height, None);
+        assert_eq!(view.prev_epoch_kickout, None);
+        assert_eq!(view.validators.len(), 3);
+        for validator in view.validators.iter() {
+            assert_eq!(validator.stake, stake_amount);
+            assert!(validator.is_block_producer);
+            assert_eq!(validator.chunk_producer_shards, vec![0]);
+        }
+
+        let view = epoch_manager.get_epoch_info_view(&EpochId(h[2])).unwrap();
+        assert!(view.prev_epoch_kickout.is_some());
+
+        // A fork computes another epoch of the same height, both are kept.
+        record_block(&mut epoch_manager, h[2], h[3], 3, vec![]);
+        assert_eq!(
+            epoch_manager.get_epoch_ids_from_epoch_height(epoch_height3).unwrap(),
+            vec![EpochId(h[4]), EpochId(h[3])]
+        );
+    }
+
     #[test]
     /// Slashed after unstaking: slashed for 2 epochs
     fn test_unstake_slash() {
@@ -3669,6 +4456,116 @@ mod tests2 {
         assert_eq!(last_epoch_info.unwrap().validator_kickout(), &HashMap::default());
     }
 
+    /// Computes kickouts for an epoch of validators `test0`, `test1`, ... with the given stakes
+    /// and numbers of produced blocks out of 10 expected.
+    #[cfg(feature = "protocol_feature_max_kickout_stake")]
+    fn compute_kickout_with_min_remaining_stake(
+        validator_min_remaining_stake_perc: u8,
+        validators: &[(Balance, u64)],
+    ) -> (HashMap<AccountId, ValidatorKickoutReason>, HashMap<AccountId, BlockChunkValidatorStats>)
+    {
+        let mut config = epoch_config(10, 1, 4, 0, 90, 60, 0, None)
+            .for_protocol_version(PROTOCOL_VERSION)
+            .clone();
+        config.validator_min_remaining_stake_perc = validator_min_remaining_stake_perc;
+        let accounts: Vec<(AccountId, Balance)> = validators
+            .iter()
+            .enumerate()
+            .map(|(i, (stake, _))| (format!("test{}", i).parse().unwrap(), *stake))
+            .collect();
+        let epoch_manager = EpochManager::new(
+            create_test_store(),
+            AllEpochConfig::new(config, None),
+            PROTOCOL_VERSION,
+            default_reward_calculator(),
+            accounts
+                .iter()
+                .map(|(account_id, balance)| stake(account_id.clone(), *balance))
+                .collect(),
+        )
+        .unwrap();
+        let num_validators = validators.len() as ValidatorId;
+        let epoch_info = epoch_info(
+            1,
+            accounts,
+            (0..num_validators).collect(),
+            vec![(0..num_validators).collect()],
+            vec![],
+            vec![],
+            BTreeMap::new(),
+            vec![],
+            HashMap::new(),
+            0,
+        );
+        let block_validator_tracker = validators
+            .iter()
+            .enumerate()
+            .map(|(i, (_, produced))| {
+                (i as ValidatorId, ValidatorStats { produced: *produced, expected: 10 })
+            })
+            .collect();
+        epoch_manager.compute_kickout_info(
+            &epoch_info,
+            &block_validator_tracker,
+            &HashMap::new(),
+            &HashMap::new(),
+            &HashMap::new(),
+        )
+    }
+
+    /// Even if every validator missed everything, validators holding the required fraction of
+    /// the stake keep their seats, but without reward.
+    #[test]
+    #[cfg(feature = "protocol_feature_max_kickout_stake")]
+    fn test_max_kickout_stake_all_offline() {
+        let (kickout, stats) = compute_kickout_with_min_remaining_stake(
+            90,
+            &[(1_000, 0), (2_000, 0), (3_000, 0), (4_000, 0)],
+        );
+        let expected: HashMap<AccountId, ValidatorKickoutReason> =
+            vec![("test0".parse().unwrap(), NotEnoughBlocks { produced: 0, expected: 10 })]
+                .into_iter()
+                .collect();
+        assert_eq!(kickout, expected);
+        assert!(stats.is_empty());
+
+        // Without the limit only one validator is kept, as usual.
+        let (kickout, _) = compute_kickout_with_min_remaining_stake(
+            0,
+            &[(1_000, 0), (2_000, 0), (3_000, 0), (4_000, 0)],
+        );
+        assert_eq!(kickout.len(), 3);
+        assert!(!kickout.contains_key("test0"));
+    }
+
+    /// Offline validators with the best uptime are exempted first.
+    #[test]
+    #[cfg(feature = "protocol_feature_max_kickout_stake")]
+    fn test_max_kickout_stake_by_uptime() {
+        let (kickout, stats) = compute_kickout_with_min_remaining_stake(
+            50,
+            &[(1_000, 9), (1_000, 0), (1_000, 5), (1_000, 8)],
+        );
+        let expected: HashMap<AccountId, ValidatorKickoutReason> = vec![
+            ("test1".parse().unwrap(), NotEnoughBlocks { produced: 0, expected: 10 }),
+            ("test2".parse().unwrap(), NotEnoughBlocks { produced: 5, expected: 10 }),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(kickout, expected);
+        // `test3` keeps its seat, but only `test0` gets a reward.
+        let rewarded: Vec<&str> = stats.keys().map(|account_id| account_id.as_ref()).collect();
+        assert_eq!(rewarded, vec!["test0"]);
+
+        // Nothing changes if the remaining stake is high enough anyway.
+        let (kickout, stats) = compute_kickout_with_min_remaining_stake(
+            25,
+            &[(1_000, 9), (1_000, 0), (1_000, 5), (1_000, 8)],
+        );
+        assert_eq!(kickout.len(), 3);
+        assert_eq!(stats.len(), 1);
+    }
+
     fn check_validators(epoch_info: &EpochInfo, expected_validators: &[(&str, u128)]) {
         epoch_info.validators_iter().zip(expected_validators.into_iter()).for_each(
             |(ref v, (account_id, stake))| {
@@ -3782,6 +4679,149 @@ mod tests2 {
         );
     }
 
+    #[test]
+    fn test_protocol_upgrade_status() {
+        let store = create_test_store();
+        let config = epoch_config(2, 1, 1, 0, 90, 60, 0, None);
+        let amount_staked = 1_000_000;
+        let validators = vec![stake("test1".parse().unwrap(), amount_staked)];
+        let mut epoch_manager = EpochManager::new(
+            store,
+            config,
+            PROTOCOL_VERSION - 1,
+            default_reward_calculator(),
+            validators,
+        )
+        .unwrap();
+        let h = hash_range(3);
+        record_block(&mut epoch_manager, CryptoHash::default(), h[0], 0, vec![]);
+        record_block(&mut epoch_manager, h[0], h[1], 1, vec![]);
+
+        // test1 voted for the new version, which is enough for the upgrade if it still is at
+        // the end of the epoch.
+        let status = epoch_manager.get_protocol_upgrade_status(&h[1]).unwrap();
+        assert_eq!(status.protocol_version, PROTOCOL_VERSION - 1);
+        assert_eq!(status.next_epoch_protocol_version, PROTOCOL_VERSION - 1);
+        assert_eq!(status.voted_protocol_version, Some(PROTOCOL_VERSION));
+        assert_eq!(status.voted_stake, amount_staked);
+        assert_eq!(status.threshold_stake, amount_staked * 80 / 100);
+        assert_eq!(
+            status.votes,
+            vec![ProtocolVersionVoteView {
+                account_id: "test1".parse().unwrap(),
+                stake: amount_staked,
+                protocol_version: PROTOCOL_VERSION,
+            }]
+        );
+        assert_eq!(
+            status.scheduled_upgrade,
+            Some(ScheduledProtocolUpgradeView {
+                protocol_version: PROTOCOL_VERSION,
+                epoch_height: status.epoch_height + 2,
+                estimated_height: 5,
+                is_final: false,
+            })
+        );
+
+        record_block(&mut epoch_manager, h[1], h[2], 2, vec![]);
+        let status = epoch_manager.get_protocol_upgrade_status(&h[2]).unwrap();
+        assert_eq!(
+            status.scheduled_upgrade,
+            Some(ScheduledProtocolUpgradeView {
+                protocol_version: PROTOCOL_VERSION,
+                epoch_height: status.epoch_height + 2,
+                estimated_height: 5,
+                is_final: true,
+            })
+        );
+    }
+
+    /// The treasury reward of an epoch is split once the epoch runs the upgraded protocol version,
+    /// and the minted amount is still what the validators and the treasury accounts receive.
+    #[test]
+    #[cfg(feature = "protocol_feature_treasury_split")]
+    fn test_protocol_treasury_split_version_switch() {
+        let store = create_test_store();
+        let config = epoch_config(2, 1, 2, 0, 90, 60, 0, None);
+        let amount_staked = 1_000_000;
+        let validators = vec![
+            stake("test1".parse().unwrap(), amount_staked),
+            stake("test2".parse().unwrap(), amount_staked),
+        ];
+        let split_version =
+            near_primitives::version::ProtocolFeature::ProtocolTreasurySplit.protocol_version();
+        let runtime_config = near_primitives::runtime::config::RuntimeConfig {
+            protocol_treasury_split: vec![
+                ("near".parse().unwrap(), Rational::new(1, 2)),
+                ("treasury".parse().unwrap(), Rational::new(1, 2)),
+            ],
+            ..near_primitives::runtime::config::RuntimeConfig::test()
+        };
+        let reward_calculator = RewardCalculator {
+            max_inflation_rate: Rational::new(1, 10),
+            protocol_reward_rate: Rational::new(1, 10),
+            num_seconds_per_year: 100,
+            runtime_config_store: Arc::new(RuntimeConfigStore::with_one_config(runtime_config)),
+            ..default_reward_calculator()
+        };
+        let mut epoch_manager =
+            EpochManager::new(store, config, split_version - 1, reward_calculator, validators)
+                .unwrap();
+        let h = hash_range(9);
+        record_block(&mut epoch_manager, CryptoHash::default(), h[0], 0, vec![]);
+        let mut block_info1 =
+            block_info(h[1], 1, 1, h[0], h[0], h[0], vec![], DEFAULT_TOTAL_SUPPLY);
+        set_block_info_protocol_version(&mut block_info1, split_version - 1);
+        epoch_manager.record_block_info(block_info1, [0; 32]).unwrap();
+        for i in 2..9 {
+            let block_info = BlockInfo::new(
+                h[i],
+                i as u64,
+                (i as u64).saturating_sub(2),
+                h[i - 1],
+                h[i - 1],
+                vec![],
+                vec![],
+                vec![],
+                DEFAULT_TOTAL_SUPPLY,
+                split_version,
+                i as u64 * NUM_NS_IN_SECOND,
+            );
+            epoch_manager.record_block_info(block_info, [0; 32]).unwrap().commit().unwrap();
+        }
+        assert_eq!(
+            epoch_manager.get_epoch_info(&EpochId(h[2])).unwrap().protocol_version(),
+            split_version - 1
+        );
+        assert_eq!(
+            epoch_manager.get_epoch_info(&EpochId(h[4])).unwrap().protocol_version(),
+            split_version
+        );
+
+        let near: AccountId = "near".parse().unwrap();
+        let treasury: AccountId = "treasury".parse().unwrap();
+        assert_eq!(
+            epoch_manager.get_protocol_treasury_accounts(&h[6]).unwrap(),
+            vec![near.clone()]
+        );
+        let epoch_info = epoch_manager.get_epoch_info(&EpochId(h[6])).unwrap().clone();
+        let rewards = epoch_info.validator_reward();
+        assert!(rewards[&near] > 0);
+        assert!(!rewards.contains_key(&treasury));
+        assert_eq!(rewards.values().sum::<Balance>(), epoch_info.minted_amount());
+
+        assert_eq!(
+            epoch_manager.get_protocol_treasury_accounts(&h[8]).unwrap(),
+            vec![near.clone(), treasury.clone()]
+        );
+        let epoch_info = epoch_manager.get_epoch_info(&EpochId(h[8])).unwrap().clone();
+        let rewards = epoch_info.validator_reward();
+        assert!(rewards[&treasury] > 0);
+        // The first account gets what rounding leaves.
+        assert!(rewards[&near] - rewards[&treasury] <= 1);
+        assert_eq!(rewards.values().sum::<Balance>(), epoch_info.minted_amount());
+    }
+
     #[test]
     fn test_protocol_version_switch_with_shard_layout_change() {
         let store = create_test_store();
@@ -3880,6 +4920,7 @@ mod tests2 {
             avg_hidden_validator_seats_per_shard: Vec::from([0]),
             block_producer_kickout_threshold: 90,
             chunk_producer_kickout_threshold: 60,
+            validator_min_remaining_stake_perc: 0,
             fishermen_threshold: 0,
             online_min_threshold: Rational::new(90, 100),
             online_max_threshold: Rational::new(99, 100),
@@ -1,10 +1,12 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use num_rational::Rational;
 use primitive_types::U256;
 
 use near_chain_configs::GenesisConfig;
 use near_primitives::checked_feature;
+use near_primitives::runtime::config_store::RuntimeConfigStore;
 use near_primitives::types::{AccountId, Balance, BlockChunkValidatorStats};
 use near_primitives::version::{ProtocolVersion, ENABLE_INFLATION_PROTOCOL_VERSION};
 
@@ -18,24 +20,47 @@ pub struct RewardCalculator {
     pub epoch_length: u64,
     pub protocol_reward_rate: Rational,
     pub protocol_treasury_account: AccountId,
+    /// Runtime configs of the protocol versions, which set the protocol treasury split.
+    pub runtime_config_store: Arc<RuntimeConfigStore>,
     pub online_min_threshold: Rational,
     pub online_max_threshold: Rational,
     pub num_seconds_per_year: u64,
 }
 
 impl RewardCalculator {
-    pub fn new(config: &GenesisConfig) -> Self {
+    pub fn new(config: &GenesisConfig, runtime_config_store: Arc<RuntimeConfigStore>) -> Self {
         RewardCalculator {
             max_inflation_rate: config.max_inflation_rate,
             num_blocks_per_year: config.num_blocks_per_year,
             epoch_length: config.epoch_length,
             protocol_reward_rate: config.protocol_reward_rate,
             protocol_treasury_account: config.protocol_treasury_account.clone(),
+            runtime_config_store,
             online_max_threshold: config.online_max_threshold,
             online_min_threshold: config.online_min_threshold,
             num_seconds_per_year: NUM_SECONDS_IN_A_YEAR,
         }
     }
+    /// Accounts receiving the protocol treasury reward with their fractions of it, as set by the
+    /// runtime config of `protocol_version`.
+    pub fn protocol_treasury_split(
+        &self,
+        protocol_version: ProtocolVersion,
+    ) -> Vec<(AccountId, Rational)> {
+        let treasury_split =
+            &self.runtime_config_store.get_config(protocol_version).protocol_treasury_split;
+        if checked_feature!(
+            "protocol_feature_treasury_split",
+            ProtocolTreasurySplit,
+            protocol_version
+        ) && !treasury_split.is_empty()
+        {
+            treasury_split.clone()
+        } else {
+            vec![(self.protocol_treasury_account.clone(), Rational::from_integer(1))]
+        }
+    }
+
     /// Calculate validator reward for an epoch based on their block and chunk production stats.
     /// Returns map of validators with their rewards and amount of newly minted tokens including to protocol's treasury.
     /// See spec <https://nomicon.io/Economics/README.html#rewards-calculation>.
@@ -77,31 +102,42 @@ impl RewardCalculator {
             * U256::from(*protocol_reward_rate.numer() as u64)
             / U256::from(*protocol_reward_rate.denom() as u64))
         .as_u128();
-        res.insert(self.protocol_treasury_account.clone(), epoch_protocol_treasury);
+        let mut treasury_rewards = self
+            .protocol_treasury_split(protocol_version)
+            .into_iter()
+            .map(|(account_id, fraction)| {
+                let reward = (U256::from(epoch_protocol_treasury)
+                    * U256::from(*fraction.numer() as u64)
+                    / U256::from(*fraction.denom() as u64))
+                .as_u128();
+                (account_id, reward)
+            })
+            .collect::<Vec<_>>();
+        // What rounding leaves goes to the first account, so that the treasury gets exactly
+        // `epoch_protocol_treasury`.
+        let split_treasury: Balance = treasury_rewards.iter().map(|(_, reward)| reward).sum();
+        treasury_rewards[0].1 += epoch_protocol_treasury - split_treasury;
+        res.extend(treasury_rewards);
         if num_validators == 0 {
             return (res, 0);
         }
         let epoch_validator_reward = epoch_total_reward - epoch_protocol_treasury;
         let mut epoch_actual_reward = epoch_protocol_treasury;
         let total_stake: Balance = validator_stake.values().sum();
+        let chunk_only_producers = checked_feature!(
+            "protocol_feature_chunk_only_producers",
+            ChunkOnlyProducers,
+            protocol_version
+        );
         for (account_id, stats) in validator_block_chunk_stats {
-            // Uptime is an average of block produced / expected and chunk produced / expected.
-            let average_produced_numer = U256::from(
-                stats.block_stats.produced * stats.chunk_stats.expected
-                    + stats.chunk_stats.produced * stats.block_stats.expected,
-            );
-            let average_produced_denom =
-                U256::from(2 * stats.chunk_stats.expected * stats.block_stats.expected);
             let online_min_numer = U256::from(*self.online_min_threshold.numer() as u64);
             let online_min_denom = U256::from(*self.online_min_threshold.denom() as u64);
+            let online_fraction = Self::online_fraction(&stats, chunk_only_producers);
             // If average of produced blocks below online min threshold, validator gets 0 reward.
-            let reward = if average_produced_numer * online_min_denom
-                < online_min_numer * average_produced_denom
-                || stats.chunk_stats.expected == 0
-                || stats.block_stats.expected == 0
-            {
-                0
-            } else {
+            let reward = if let Some((average_produced_numer, average_produced_denom)) =
+                online_fraction.filter(|(numer, denom)| {
+                    *numer * online_min_denom >= online_min_numer * *denom
+                }) {
                 let stake = *validator_stake
                     .get(&account_id)
                     .unwrap_or_else(|| panic!("{} is not a validator", account_id));
@@ -121,22 +157,61 @@ impl RewardCalculator {
                     / uptime_denum
                     / U256::from(total_stake))
                 .as_u128()
+            } else {
+                0
             };
             res.insert(account_id, reward);
             epoch_actual_reward += reward;
         }
         (res, epoch_actual_reward)
     }
+
+    /// Fraction of expected blocks and chunks the validator produced in the epoch, as numerator
+    /// and denominator. `None` if the validator can't be rated and gets no reward.
+    ///
+    /// Originally the fraction is the average of block and chunk uptime, so validators who
+    /// weren't expected to produce both blocks and chunks are not rewarded. With chunk-only
+    /// producers, validators are rated by the duties they had: a chunk-only producer by its
+    /// chunk uptime, a block producer which didn't have to produce chunks by its block uptime.
+    fn online_fraction(
+        stats: &BlockChunkValidatorStats,
+        chunk_only_producers: bool,
+    ) -> Option<(U256, U256)> {
+        let (block_stats, chunk_stats) = (&stats.block_stats, &stats.chunk_stats);
+        match (block_stats.expected, chunk_stats.expected) {
+            (0, 0) => None,
+            (0, _) if chunk_only_producers => {
+                Some((U256::from(chunk_stats.produced), U256::from(chunk_stats.expected)))
+            }
+            (_, 0) if chunk_only_producers => {
+                Some((U256::from(block_stats.produced), U256::from(block_stats.expected)))
+            }
+            (0, _) | (_, 0) => None,
+            // Average of block produced / expected and chunk produced / expected.
+            (block_expected, chunk_expected) => Some((
+                U256::from(
+                    block_stats.produced * chunk_expected + chunk_stats.produced * block_expected,
+                ),
+                U256::from(2 * chunk_expected * block_expected),
+            )),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::reward_calculator::NUM_NS_IN_SECOND;
     use crate::RewardCalculator;
+    #[cfg(feature = "protocol_feature_treasury_split")]
+    use near_primitives::runtime::config::RuntimeConfig;
+    use near_primitives::runtime::config_store::RuntimeConfigStore;
     use near_primitives::types::{BlockChunkValidatorStats, ValidatorStats};
+    #[cfg(feature = "protocol_feature_treasury_split")]
+    use near_primitives::version::ProtocolFeature;
     use near_primitives::version::PROTOCOL_VERSION;
     use num_rational::Rational;
     use std::collections::HashMap;
+    use std::sync::Arc;
 
     #[test]
     fn test_zero_produced_and_expected() {
@@ -147,6 +222,7 @@ mod tests {
             epoch_length,
             protocol_reward_rate: Rational::new(0, 1),
             protocol_treasury_account: "near".parse().unwrap(),
+            runtime_config_store: Arc::new(RuntimeConfigStore::test()),
             online_min_threshold: Rational::new(9, 10),
             online_max_threshold: Rational::new(1, 1),
             num_seconds_per_year: 1000000,
@@ -204,6 +280,7 @@ mod tests {
             epoch_length,
             protocol_reward_rate: Rational::new(0, 10),
             protocol_treasury_account: "near".parse().unwrap(),
+            runtime_config_store: Arc::new(RuntimeConfigStore::test()),
             online_min_threshold: Rational::new(9, 10),
             online_max_threshold: Rational::new(99, 100),
             num_seconds_per_year: 1000,
@@ -265,6 +342,157 @@ mod tests {
         assert_eq!(result.1, 4_999_999u128);
     }
 
+    fn stats(block: (u64, u64), chunk: (u64, u64)) -> BlockChunkValidatorStats {
+        BlockChunkValidatorStats {
+            block_stats: ValidatorStats { produced: block.0, expected: block.1 },
+            chunk_stats: ValidatorStats { produced: chunk.0, expected: chunk.1 },
+        }
+    }
+
+    /// Rewards of all validators plus the protocol treasury must add up to the minted amount,
+    /// which is the whole epoch inflation if all validators are fully online.
+    #[test]
+    fn test_reward_balance() {
+        let epoch_length = 1000;
+        let reward_calculator = RewardCalculator {
+            max_inflation_rate: Rational::new(1, 100),
+            num_blocks_per_year: 1000,
+            epoch_length,
+            protocol_reward_rate: Rational::new(1, 10),
+            protocol_treasury_account: "near".parse().unwrap(),
+            runtime_config_store: Arc::new(RuntimeConfigStore::test()),
+            online_min_threshold: Rational::new(9, 10),
+            online_max_threshold: Rational::new(99, 100),
+            num_seconds_per_year: 1000,
+        };
+        let validator_stake = vec![
+            ("test1".parse().unwrap(), 100),
+            ("test2".parse().unwrap(), 200),
+            ("test3".parse().unwrap(), 300),
+            ("test4".parse().unwrap(), 400),
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+        let total_supply = 1_000_000_000;
+        // Total inflation is 10_000_000, 1_000_000 of which goes to the treasury.
+        let total_inflation = 10_000_000;
+
+        let online = validator_stake
+            .keys()
+            .map(|account_id| (account_id.clone(), stats((1000, 1000), (1000, 1000))))
+            .collect();
+        let (rewards, minted) = reward_calculator.calculate_reward(
+            online,
+            &validator_stake,
+            total_supply,
+            PROTOCOL_VERSION,
+            PROTOCOL_VERSION,
+            epoch_length * NUM_NS_IN_SECOND,
+        );
+        assert_eq!(rewards["near"], 1_000_000);
+        assert_eq!(rewards["test4"], 3_600_000);
+        assert_eq!(rewards.values().sum::<u128>(), minted);
+        assert_eq!(minted, total_inflation);
+
+        let partially_online = vec![
+            ("test1".parse().unwrap(), stats((999, 1000), (998, 1000))),
+            ("test2".parse().unwrap(), stats((955, 1000), (960, 1000))),
+            ("test3".parse().unwrap(), stats((900, 1000), (700, 1000))),
+            ("test4".parse().unwrap(), stats((1000, 1000), (1000, 1000))),
+        ]
+        .into_iter()
+        .collect();
+        let (rewards, minted) = reward_calculator.calculate_reward(
+            partially_online,
+            &validator_stake,
+            total_supply,
+            PROTOCOL_VERSION,
+            PROTOCOL_VERSION,
+            epoch_length * NUM_NS_IN_SECOND,
+        );
+        assert_eq!(rewards["near"], 1_000_000);
+        assert_eq!(rewards["test3"], 0);
+        assert_eq!(rewards.values().sum::<u128>(), minted);
+        assert!(minted < total_inflation);
+    }
+
+    /// Chunk-only producers are rewarded for their chunk uptime from the same pool as block
+    /// producers, proportionally to their stake.
+    #[test]
+    #[cfg(feature = "protocol_feature_chunk_only_producers")]
+    fn test_reward_chunk_only_producers() {
+        use near_primitives::version::ProtocolFeature;
+
+        let epoch_length = 1000;
+        let reward_calculator = RewardCalculator {
+            max_inflation_rate: Rational::new(1, 100),
+            num_blocks_per_year: 1000,
+            epoch_length,
+            protocol_reward_rate: Rational::new(0, 10),
+            protocol_treasury_account: "near".parse().unwrap(),
+            runtime_config_store: Arc::new(RuntimeConfigStore::test()),
+            online_min_threshold: Rational::new(9, 10),
+            online_max_threshold: Rational::new(99, 100),
+            num_seconds_per_year: 1000,
+        };
+        let validator_block_chunk_stats = || {
+            vec![
+                ("test1".parse().unwrap(), stats((1000, 1000), (1000, 1000))),
+                ("test2".parse().unwrap(), stats((0, 0), (1000, 1000))),
+                ("test3".parse().unwrap(), stats((0, 0), (945, 1000))),
+                ("test4".parse().unwrap(), stats((1000, 1000), (0, 0))),
+            ]
+            .into_iter()
+            .collect::<HashMap<_, _>>()
+        };
+        let validator_stake = vec![
+            ("test1".parse().unwrap(), 500_000),
+            ("test2".parse().unwrap(), 500_000),
+            ("test3".parse().unwrap(), 500_000),
+            ("test4".parse().unwrap(), 500_000),
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+        let total_supply = 1_000_000_000;
+
+        let (rewards, minted) = reward_calculator.calculate_reward(
+            validator_block_chunk_stats(),
+            &validator_stake,
+            total_supply,
+            PROTOCOL_VERSION,
+            PROTOCOL_VERSION,
+            epoch_length * NUM_NS_IN_SECOND,
+        );
+        assert_eq!(
+            rewards,
+            vec![
+                ("near".parse().unwrap(), 0),
+                ("test1".parse().unwrap(), 2_500_000u128),
+                ("test2".parse().unwrap(), 2_500_000u128),
+                ("test3".parse().unwrap(), 1_250_000u128),
+                ("test4".parse().unwrap(), 2_500_000u128),
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert_eq!(minted, 8_750_000);
+
+        // Before chunk-only producers, validators need both block and chunk duties.
+        let old_version = ProtocolFeature::ChunkOnlyProducers.protocol_version() - 1;
+        let (rewards, minted) = reward_calculator.calculate_reward(
+            validator_block_chunk_stats(),
+            &validator_stake,
+            total_supply,
+            old_version,
+            old_version,
+            epoch_length * NUM_NS_IN_SECOND,
+        );
+        assert_eq!(rewards["test1"], 2_500_000);
+        assert_eq!(rewards["test2"], 0);
+        assert_eq!(rewards["test4"], 0);
+        assert_eq!(minted, 2_500_000);
+    }
+
     /// Test that under an extreme setting (total supply 100b, epoch length half a day),
     /// reward calculation will not overflow.
     #[test]
@@ -277,6 +505,7 @@ mod tests {
             epoch_length,
             protocol_reward_rate: Rational::new(1, 10),
             protocol_treasury_account: "near".parse().unwrap(),
+            runtime_config_store: Arc::new(RuntimeConfigStore::test()),
             online_min_threshold: Rational::new(9, 10),
             online_max_threshold: Rational::new(1, 1),
             num_seconds_per_year: 60 * 60 * 24 * 365,
@@ -304,4 +533,73 @@ mod tests {
             epoch_length * NUM_NS_IN_SECOND,
         );
     }
+
+    /// Test that the treasury reward is split between the configured accounts once the protocol
+    /// is upgraded, and that rounding doesn't change the minted amount.
+    #[test]
+    #[cfg(feature = "protocol_feature_treasury_split")]
+    fn test_protocol_treasury_split() {
+        let epoch_length = 1000;
+        let reward_calculator = RewardCalculator {
+            max_inflation_rate: Rational::new(1, 100),
+            num_blocks_per_year: 1000,
+            epoch_length,
+            protocol_reward_rate: Rational::new(1, 10),
+            protocol_treasury_account: "near".parse().unwrap(),
+            runtime_config_store: Arc::new(RuntimeConfigStore::with_one_config(RuntimeConfig {
+                protocol_treasury_split: vec![
+                    ("near".parse().unwrap(), Rational::new(1, 3)),
+                    ("treasury".parse().unwrap(), Rational::new(2, 3)),
+                ],
+                ..RuntimeConfig::test()
+            })),
+            online_min_threshold: Rational::new(9, 10),
+            online_max_threshold: Rational::new(1, 1),
+            num_seconds_per_year: 1000,
+        };
+        let split_version = ProtocolFeature::ProtocolTreasurySplit.protocol_version();
+        let calculate_reward = |protocol_version| {
+            let validator_block_chunk_stats = vec![(
+                "test1".parse().unwrap(),
+                BlockChunkValidatorStats {
+                    block_stats: ValidatorStats { produced: 1, expected: 1 },
+                    chunk_stats: ValidatorStats { produced: 1, expected: 1 },
+                },
+            )]
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+            let validator_stake =
+                vec![("test1".parse().unwrap(), 100)].into_iter().collect::<HashMap<_, _>>();
+            reward_calculator.calculate_reward(
+                validator_block_chunk_stats,
+                &validator_stake,
+                1_000_000_000,
+                protocol_version,
+                protocol_version,
+                epoch_length * NUM_NS_IN_SECOND,
+            )
+        };
+
+        let (rewards, minted) = calculate_reward(split_version - 1);
+        assert_eq!(
+            rewards,
+            vec![("near".parse().unwrap(), 1_000_000), ("test1".parse().unwrap(), 9_000_000)]
+                .into_iter()
+                .collect::<HashMap<_, _>>()
+        );
+        assert_eq!(minted, 10_000_000);
+
+        let (rewards, minted) = calculate_reward(split_version);
+        assert_eq!(
+            rewards,
+            vec![
+                ("near".parse().unwrap(), 333_334),
+                ("treasury".parse().unwrap(), 666_666),
+                ("test1".parse().unwrap(), 9_000_000)
+            ]
+            .into_iter()
+            .collect::<HashMap<_, _>>()
+        );
+        assert_eq!(minted, 10_000_000);
+    }
 }
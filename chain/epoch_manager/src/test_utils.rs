@@ -1,4 +1,5 @@
 use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 
 use num_rational::Rational;
 
@@ -12,6 +13,7 @@ use near_primitives::epoch_manager::block_info::BlockInfoV2;
 use near_primitives::epoch_manager::epoch_info::EpochInfo;
 use near_primitives::epoch_manager::{AllEpochConfig, EpochConfig, ShardConfig, ValidatorWeight};
 use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::runtime::config_store::RuntimeConfigStore;
 use near_primitives::types::validator_stake::ValidatorStake;
 use near_primitives::types::{
     AccountId, Balance, BlockHeight, BlockHeightDelta, EpochHeight, NumSeats, NumShards,
@@ -145,6 +147,7 @@ pub fn epoch_config(
             .collect(),
         block_producer_kickout_threshold,
         chunk_producer_kickout_threshold,
+        validator_min_remaining_stake_perc: 0,
         fishermen_threshold,
         online_min_threshold: Rational::new(90, 100),
         online_max_threshold: Rational::new(99, 100),
@@ -176,6 +179,7 @@ pub fn default_reward_calculator() -> RewardCalculator {
         epoch_length: 1,
         protocol_reward_rate: Rational::from_integer(0),
         protocol_treasury_account: "near".parse().unwrap(),
+        runtime_config_store: Arc::new(RuntimeConfigStore::test()),
         online_min_threshold: Rational::new(90, 100),
         online_max_threshold: Rational::new(99, 100),
         num_seconds_per_year: NUM_SECONDS_IN_A_YEAR,
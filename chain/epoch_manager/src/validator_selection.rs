@@ -859,6 +859,7 @@ mod tests {
             avg_hidden_validator_seats_per_shard: vec![0; num_shards as usize],
             block_producer_kickout_threshold: 0,
             chunk_producer_kickout_threshold: 0,
+            validator_min_remaining_stake_perc: 0,
             online_min_threshold: 0.into(),
             online_max_threshold: 0.into(),
             fishermen_threshold,
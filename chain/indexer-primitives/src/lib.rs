@@ -42,4 +42,9 @@ pub struct IndexerShard {
     pub chunk: Option<IndexerChunkView>,
     pub receipt_execution_outcomes: Vec<IndexerExecutionOutcomeWithReceipt>,
     pub state_changes: views::StateChangesView,
+    /// Receipts added to and removed from the delayed receipts queue and the postponed receipts
+    /// of the shard by the block.  Only present if `IndexerConfig::stream_receipt_queue_changes`
+    /// is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receipt_queue_changes: Option<views::ReceiptQueueChangesView>,
 }
@@ -5,7 +5,7 @@ use tokio::sync::mpsc;
 
 use near_chain_configs::GenesisValidationMode;
 pub use near_primitives;
-use near_primitives::types::Gas;
+use near_primitives::types::{BlockHeight, Gas};
 pub use nearcore::{get_default_home, init_configs, NearConfig};
 
 pub use near_indexer_primitives::{
@@ -14,6 +14,8 @@ pub use near_indexer_primitives::{
     StreamerMessage,
 };
 
+pub use streamer::StreamerAck;
+
 mod streamer;
 
 pub const INDEXER: &str = "indexer";
@@ -53,7 +55,9 @@ pub struct InitConfigArgs {
 pub enum SyncModeEnum {
     /// Real-time syncing, always taking the latest finalized block to stream
     LatestSynced,
-    /// Starts syncing from the block NEAR Indexer was interrupted last time
+    /// Starts syncing right after the last block acknowledged by the consumer, even if the node
+    /// head is far ahead.  Messages from [`Indexer::streamer`] are acknowledged once they are
+    /// passed to the consumer.
     FromInterruption,
     /// Specific block height to start syncing from
     BlockHeight(u64),
@@ -77,6 +81,32 @@ pub struct IndexerConfig {
     pub sync_mode: SyncModeEnum,
     /// Whether await for node to be synced or not
     pub await_for_node_synced: AwaitForNodeSyncedEnum,
+    /// Whether to stream the receipts added to and removed from the delayed receipts queue and
+    /// the postponed receipts in `IndexerShard::receipt_queue_changes`.  Makes the node save
+    /// the changes of every applied chunk, which increases the size of the database.
+    pub stream_receipt_queue_changes: bool,
+}
+
+/// Event sent by the streamer returned from [`Indexer::streamer_with_acks`].
+///
+/// Every event comes with a [`StreamerAck`] which has to be acknowledged once the event is
+/// handled, see [`SyncModeEnum::FromInterruption`].
+#[derive(Debug)]
+pub enum StreamerEvent {
+    /// Block with all the related data.
+    Message(StreamerMessage, StreamerAck),
+    /// Blocks which should have been streamed but have been garbage collected by the node.
+    Gap(StreamerGap, StreamerAck),
+}
+
+/// Range of block heights the streamer was unable to stream, because the node doesn't have the
+/// blocks anymore.  Heights without blocks are included into the range as well.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamerGap {
+    /// First missed height.
+    pub start_height: BlockHeight,
+    /// Last missed height, inclusive.
+    pub end_height: BlockHeight,
 }
 
 /// This is the core component, which handles `nearcore` and internal `streamer`.
@@ -96,8 +126,10 @@ impl Indexer {
             indexer_config.home_dir.display()
         );
 
-        let near_config =
+        let mut near_config =
             nearcore::config::load_config(&indexer_config.home_dir, GenesisValidationMode::Full);
+        near_config.client_config.save_receipt_queue_changes =
+            indexer_config.stream_receipt_queue_changes;
 
         assert!(
             !&near_config.client_config.tracked_shards.is_empty(),
@@ -113,7 +145,40 @@ impl Indexer {
     }
 
     /// Boots up `near_indexer::streamer`, so it monitors the new blocks with chunks, transactions, receipts, and execution outcomes inside. The returned stream handler should be drained and handled on the user side.
+    ///
+    /// Messages are acknowledged as soon as they are passed to the returned stream, use
+    /// [`Indexer::streamer_with_acks`] to resume exactly where the consumer stopped.
     pub fn streamer(&self) -> mpsc::Receiver<StreamerMessage> {
+        let mut events = self.streamer_with_acks();
+        let (sender, receiver) = mpsc::channel(1);
+        actix::spawn(async move {
+            while let Some(event) = events.recv().await {
+                match event {
+                    StreamerEvent::Message(streamer_message, ack) => {
+                        if sender.send(streamer_message).await.is_err() {
+                            break;
+                        }
+                        ack.ack();
+                    }
+                    StreamerEvent::Gap(gap, ack) => {
+                        tracing::warn!(
+                            target: INDEXER,
+                            "Blocks #{}..=#{} are not available anymore and are not streamed",
+                            gap.start_height,
+                            gap.end_height
+                        );
+                        ack.ack();
+                    }
+                }
+            }
+        });
+        receiver
+    }
+
+    /// Boots up `near_indexer::streamer` like [`Indexer::streamer`], but leaves it to the
+    /// consumer to acknowledge every event with [`StreamerAck::ack`] once it is fully handled.
+    /// The streamer is resumed from the last acknowledged block after a restart.
+    pub fn streamer_with_acks(&self) -> mpsc::Receiver<StreamerEvent> {
         let (sender, receiver) = mpsc::channel(100);
         actix::spawn(streamer::start(
             self.view_client.clone(),
@@ -0,0 +1,185 @@
+//! Persisted position of the streamer.
+//!
+//! The cursor is the height of the last block the consumer has acknowledged, with every block
+//! streamed before it acknowledged as well.  It is stored in a dedicated column family of the
+//! indexer database and is only moved forward from [`StreamerAck::ack`], so a consumer that
+//! crashes in the middle of handling a message gets that message again after the restart.
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use rocksdb::{ColumnFamilyDescriptor, Options, DB};
+use tracing::warn;
+
+use near_primitives::types::BlockHeight;
+
+use crate::INDEXER;
+
+/// Column family holding the cursor.
+const CURSOR_COLUMN: &str = "cursor";
+const CURSOR_KEY: &[u8] = b"last_delivered_block_height";
+/// Key the streamer used to write to the default column family before the cursor was
+/// introduced.  It holds the height of the last block *sent* to the consumer.
+const LEGACY_KEY: &[u8] = b"last_synced_block_height";
+
+pub(crate) struct Cursor {
+    db: DB,
+    /// Heights of the events sent to the consumer which are not covered by the cursor yet,
+    /// mapped to whether the event has been acknowledged.
+    in_flight: Mutex<BTreeMap<BlockHeight, bool>>,
+}
+
+impl Cursor {
+    pub(crate) fn open(path: &Path) -> Result<Arc<Self>, rocksdb::Error> {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+        let column = ColumnFamilyDescriptor::new(CURSOR_COLUMN, Options::default());
+        let db = DB::open_cf_descriptors(&options, path, vec![column])?;
+        Ok(Arc::new(Self { db, in_flight: Mutex::default() }))
+    }
+
+    /// Height of the last block which has been fully delivered to the consumer.
+    pub(crate) fn last_delivered_height(&self) -> Result<Option<BlockHeight>, rocksdb::Error> {
+        let column = self.db.cf_handle(CURSOR_COLUMN).expect("cursor column is always opened");
+        if let Some(value) = self.db.get_cf(column, CURSOR_KEY)? {
+            return Ok(Some(parse_height(&value)));
+        }
+        // The legacy key was written right after the block had been sent, so the consumer may
+        // have never handled it.  Stream it once more rather than skip it.
+        Ok(self.db.get(LEGACY_KEY)?.map(|value| parse_height(&value).saturating_sub(1)))
+    }
+
+    /// Registers an event covering blocks up to `height` which is about to be sent to the
+    /// consumer.  Events have to be registered in the order they are sent.
+    pub(crate) fn register(self: &Arc<Self>, height: BlockHeight) -> StreamerAck {
+        self.in_flight.lock().unwrap().insert(height, false);
+        StreamerAck { height, cursor: Arc::clone(self) }
+    }
+
+    fn ack(&self, height: BlockHeight) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if let Some(acked) = in_flight.get_mut(&height) {
+            *acked = true;
+        }
+        let mut delivered = None;
+        loop {
+            let first = in_flight.iter().next().map(|(&height, &acked)| (height, acked));
+            match first {
+                Some((height, true)) => {
+                    in_flight.remove(&height);
+                    delivered = Some(height);
+                }
+                _ => break,
+            }
+        }
+        if let Some(height) = delivered {
+            let column = self.db.cf_handle(CURSOR_COLUMN).expect("cursor column is always opened");
+            if let Err(err) = self.db.put_cf(column, CURSOR_KEY, height.to_string()) {
+                warn!(target: INDEXER, "Failed to persist streamer cursor at #{}: {}", height, err);
+            }
+        }
+    }
+}
+
+fn parse_height(value: &[u8]) -> BlockHeight {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .expect("streamer cursor must be a decimal block height")
+}
+
+/// Acknowledgement handle of a [`StreamerEvent`](crate::StreamerEvent).
+///
+/// Call [`StreamerAck::ack`] once the event has been fully handled.  The streamer cursor is moved
+/// past the event only when it and all the events sent before it are acknowledged; dropping the
+/// handle without acknowledging leaves the event to be streamed again after a restart.
+pub struct StreamerAck {
+    height: BlockHeight,
+    cursor: Arc<Cursor>,
+}
+
+impl StreamerAck {
+    /// Height of the last block covered by the event.
+    pub fn height(&self) -> BlockHeight {
+        self.height
+    }
+
+    pub fn ack(self) {
+        self.cursor.ack(self.height);
+    }
+}
+
+impl std::fmt::Debug for StreamerAck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamerAck").field("height", &self.height).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Streams `heights` starting right after the cursor to a consumer which acknowledges the
+    /// first `handled` messages and then dies, returning the heights it has handled.
+    fn run_consumer(path: &Path, heights: &[BlockHeight], handled: usize) -> Vec<BlockHeight> {
+        let cursor = Cursor::open(path).unwrap();
+        let start = cursor.last_delivered_height().unwrap().map_or(0, |height| height + 1);
+        let acks: Vec<_> = heights
+            .iter()
+            .filter(|&&height| height >= start)
+            .map(|&height| cursor.register(height))
+            .collect();
+        acks.into_iter()
+            .take(handled)
+            .map(|ack| {
+                let height = ack.height();
+                ack.ack();
+                height
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_resume_after_consumer_crash() {
+        let dir = tempfile::tempdir().unwrap();
+        // Some heights are skipped, there are no blocks at them.
+        let heights = [1, 2, 3, 5, 6, 8, 9, 10];
+        let mut handled = run_consumer(dir.path(), &heights, 3);
+        handled.extend(run_consumer(dir.path(), &heights, 2));
+        handled.extend(run_consumer(dir.path(), &heights, usize::MAX));
+        assert_eq!(handled, heights);
+        assert_eq!(Cursor::open(dir.path()).unwrap().last_delivered_height().unwrap(), Some(10));
+    }
+
+    #[test]
+    fn test_out_of_order_acks() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let cursor = Cursor::open(dir.path()).unwrap();
+            let first = cursor.register(1);
+            let second = cursor.register(2);
+            let third = cursor.register(3);
+            second.ack();
+            assert_eq!(cursor.last_delivered_height().unwrap(), None);
+            first.ack();
+            assert_eq!(cursor.last_delivered_height().unwrap(), Some(2));
+            drop(third);
+        }
+        let cursor = Cursor::open(dir.path()).unwrap();
+        assert_eq!(cursor.last_delivered_height().unwrap(), Some(2));
+    }
+
+    #[test]
+    fn test_legacy_height() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let db = DB::open_default(dir.path()).unwrap();
+            db.put(LEGACY_KEY, "42").unwrap();
+        }
+        let cursor = Cursor::open(dir.path()).unwrap();
+        assert_eq!(cursor.last_delivered_height().unwrap(), Some(41));
+        cursor.register(42).ack();
+        assert_eq!(cursor.last_delivered_height().unwrap(), Some(42));
+    }
+}
@@ -46,6 +46,17 @@ pub(crate) async fn fetch_block_by_height(
         .map_err(|err| FailedToFetchData::String(err.to_string()))
 }
 
+/// Fetches the genesis or the earliest available block
+pub(crate) async fn fetch_block_by_sync_checkpoint(
+    client: &Addr<near_client::ViewClientActor>,
+    sync_checkpoint: types::SyncCheckpoint,
+) -> Result<views::BlockView, FailedToFetchData> {
+    client
+        .send(near_client::GetBlock(types::BlockReference::SyncCheckpoint(sync_checkpoint)))
+        .await?
+        .map_err(|err| FailedToFetchData::String(err.to_string()))
+}
+
 /// Fetches specific block by it's hash
 pub(crate) async fn fetch_block_by_hash(
     client: &Addr<near_client::ViewClientActor>,
@@ -68,6 +79,20 @@ pub(crate) async fn fetch_state_changes(
         .map_err(|err| FailedToFetchData::String(err.to_string()))
 }
 
+pub(crate) async fn fetch_receipt_queue_changes(
+    client: &Addr<near_client::ViewClientActor>,
+    block_hash: CryptoHash,
+    epoch_id: near_primitives::types::EpochId,
+) -> Result<
+    HashMap<near_primitives::types::ShardId, views::ReceiptQueueChangesView>,
+    FailedToFetchData,
+> {
+    client
+        .send(near_client::GetReceiptQueueChangesInBlock { block_hash, epoch_id })
+        .await?
+        .map_err(|err| FailedToFetchData::String(err.to_string()))
+}
+
 /// Fetch all ExecutionOutcomeWithId for current block
 /// Returns a HashMap where the key is shard id IndexerExecutionOutcomeWithOptionalReceipt
 pub(crate) async fn fetch_outcomes(
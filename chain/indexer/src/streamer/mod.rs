@@ -2,10 +2,9 @@ use std::time::Duration;
 
 use actix::Addr;
 use async_recursion::async_recursion;
-use rocksdb::DB;
 use tokio::sync::mpsc;
 use tokio::time;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use near_indexer_primitives::{
     IndexerChunkView, IndexerExecutionOutcomeWithOptionalReceipt,
@@ -13,19 +12,24 @@ use near_indexer_primitives::{
     StreamerMessage,
 };
 use near_primitives::hash::CryptoHash;
+use near_primitives::types::{BlockHeight, SyncCheckpoint};
 use near_primitives::views;
 
-use crate::{AwaitForNodeSyncedEnum, IndexerConfig};
+use crate::{AwaitForNodeSyncedEnum, IndexerConfig, StreamerEvent, StreamerGap};
 
+use self::cursor::Cursor;
+pub use self::cursor::StreamerAck;
 use self::errors::FailedToFetchData;
 use self::fetchers::{
-    fetch_block_by_hash, fetch_block_by_height, fetch_block_chunks, fetch_latest_block,
-    fetch_outcomes, fetch_state_changes, fetch_status,
+    fetch_block_by_hash, fetch_block_by_height, fetch_block_by_sync_checkpoint, fetch_block_chunks,
+    fetch_latest_block, fetch_outcomes, fetch_receipt_queue_changes, fetch_state_changes,
+    fetch_status,
 };
 use self::utils::convert_transactions_sir_into_local_receipts;
 use crate::streamer::fetchers::fetch_protocol_config;
 use crate::INDEXER;
 
+mod cursor;
 mod errors;
 mod fetchers;
 mod utils;
@@ -68,6 +72,7 @@ fn test_problematic_blocks_hash() {
 async fn build_streamer_message(
     client: &Addr<near_client::ViewClientActor>,
     block: views::BlockView,
+    stream_receipt_queue_changes: bool,
 ) -> Result<StreamerMessage, FailedToFetchData> {
     let chunks = fetch_block_chunks(&client, &block).await?;
 
@@ -82,12 +87,23 @@ async fn build_streamer_message(
         near_primitives::types::EpochId(block.header.epoch_id.clone()),
     )
     .await?;
+    let mut receipt_queue_changes = if stream_receipt_queue_changes {
+        fetch_receipt_queue_changes(
+            &client,
+            block.header.hash,
+            near_primitives::types::EpochId(block.header.epoch_id.clone()),
+        )
+        .await?
+    } else {
+        Default::default()
+    };
     let mut indexer_shards = (0..num_shards)
         .map(|shard_id| IndexerShard {
             shard_id,
             chunk: None,
             receipt_execution_outcomes: vec![],
             state_changes: state_changes.remove(&shard_id).unwrap_or_default(),
+            receipt_queue_changes: receipt_queue_changes.remove(&shard_id),
         })
         .collect::<Vec<_>>();
 
@@ -275,23 +291,47 @@ async fn find_local_receipt_by_id_in_block(
     Ok(None)
 }
 
+/// Returns the range of blocks between `start_height` and the earliest block the node still
+/// has, if the node has garbage collected any of them.
+async fn find_gap(
+    view_client: &Addr<near_client::ViewClientActor>,
+    genesis_height: BlockHeight,
+    start_height: BlockHeight,
+) -> Result<Option<StreamerGap>, FailedToFetchData> {
+    let earliest_height =
+        fetch_block_by_sync_checkpoint(view_client, SyncCheckpoint::EarliestAvailable)
+            .await?
+            .header
+            .height;
+    // Archival nodes never garbage collect, the earliest available block is the genesis then.
+    if earliest_height == genesis_height || start_height >= earliest_height {
+        return Ok(None);
+    }
+    Ok(Some(StreamerGap { start_height, end_height: earliest_height - 1 }))
+}
+
 /// Function that starts Streamer's busy loop. Every half a seconds it fetches the status
 /// compares to already fetched block height and in case it differs fetches new block of given height.
 ///
+/// Every event sent to `events_sink` is registered in the streamer cursor, which is moved
+/// forward once the consumer acknowledges the events.  In `FromInterruption` mode streaming
+/// resumes right after the cursor, no matter how far the node head has moved since.
+///
 /// We have to pass `client: Addr<near_client::ClientActor>` and `view_client: Addr<near_client::ViewClientActor>`.
 pub(crate) async fn start(
     view_client: Addr<near_client::ViewClientActor>,
     client: Addr<near_client::ClientActor>,
     indexer_config: IndexerConfig,
-    blocks_sink: mpsc::Sender<StreamerMessage>,
+    events_sink: mpsc::Sender<StreamerEvent>,
 ) {
     info!(target: INDEXER, "Starting Streamer...");
     let mut indexer_db_path = nearcore::get_store_path(&indexer_config.home_dir);
     indexer_db_path.push("indexer");
 
     // TODO: implement proper error handling
-    let db = DB::open_default(indexer_db_path).unwrap();
-    let mut last_synced_block_height: Option<near_primitives::types::BlockHeight> = None;
+    let cursor = Cursor::open(&indexer_db_path).unwrap();
+    let mut last_synced_block_height: Option<BlockHeight> = None;
+    let mut genesis_height: Option<BlockHeight> = None;
 
     'main: loop {
         time::sleep(INTERVAL).await;
@@ -312,24 +352,57 @@ pub(crate) async fn start(
         } else {
             continue;
         };
+        let genesis_height = match genesis_height {
+            Some(height) => height,
+            None => {
+                match fetch_block_by_sync_checkpoint(&view_client, SyncCheckpoint::Genesis).await {
+                    Ok(genesis) => *genesis_height.insert(genesis.header.height),
+                    Err(_) => continue,
+                }
+            }
+        };
 
         let latest_block_height = block.header.height;
-        let start_syncing_block_height = if let Some(last_synced_block_height) =
-            last_synced_block_height
-        {
-            last_synced_block_height + 1
-        } else {
-            match indexer_config.sync_mode {
-                crate::SyncModeEnum::FromInterruption => {
-                    match db.get(b"last_synced_block_height").unwrap() {
-                        Some(value) => String::from_utf8(value).unwrap().parse::<u64>().unwrap(),
-                        None => latest_block_height,
+        let mut start_syncing_block_height =
+            if let Some(last_synced_block_height) = last_synced_block_height {
+                last_synced_block_height + 1
+            } else {
+                match indexer_config.sync_mode {
+                    crate::SyncModeEnum::FromInterruption => {
+                        match cursor.last_delivered_height().unwrap() {
+                            Some(height) => height + 1,
+                            None => latest_block_height,
+                        }
                     }
+                    crate::SyncModeEnum::LatestSynced => latest_block_height,
+                    crate::SyncModeEnum::BlockHeight(height) => height,
                 }
-                crate::SyncModeEnum::LatestSynced => latest_block_height,
-                crate::SyncModeEnum::BlockHeight(height) => height,
+            };
+
+        // Either the indexer has been stopped for too long or it falls behind the node, in both
+        // cases the blocks are gone and all we can do is to tell the consumer about it.
+        match find_gap(&view_client, genesis_height, start_syncing_block_height).await {
+            Ok(Some(gap)) => {
+                warn!(
+                    target: INDEXER,
+                    "Blocks #{}..=#{} have been garbage collected by the node, skipping them",
+                    gap.start_height,
+                    gap.end_height
+                );
+                let ack = cursor.register(gap.end_height);
+                start_syncing_block_height = gap.end_height + 1;
+                if events_sink.send(StreamerEvent::Gap(gap, ack)).await.is_err() {
+                    info!(
+                        target: INDEXER,
+                        "Unable to send StreamerEvent to listener, listener doesn't listen. terminating..."
+                    );
+                    break 'main;
+                }
+                last_synced_block_height = Some(start_syncing_block_height - 1);
             }
-        };
+            Ok(None) => {}
+            Err(_) => continue,
+        }
 
         debug!(
             target: INDEXER,
@@ -339,12 +412,22 @@ pub(crate) async fn start(
         );
         for block_height in start_syncing_block_height..=latest_block_height {
             if let Ok(block) = fetch_block_by_height(&view_client, block_height).await {
-                let response = build_streamer_message(&view_client, block).await;
+                let response = build_streamer_message(
+                    &view_client,
+                    block,
+                    indexer_config.stream_receipt_queue_changes,
+                )
+                .await;
 
                 match response {
                     Ok(streamer_message) => {
                         debug!(target: INDEXER, "{:#?}", &streamer_message);
-                        if blocks_sink.send(streamer_message).await.is_err() {
+                        let ack = cursor.register(block_height);
+                        if events_sink
+                            .send(StreamerEvent::Message(streamer_message, ack))
+                            .await
+                            .is_err()
+                        {
                             info!(
                                 target: INDEXER,
                                 "Unable to send StreamerMessage to listener, listener doesn't listen. terminating..."
@@ -361,7 +444,6 @@ pub(crate) async fn start(
                     }
                 }
             }
-            db.put(b"last_synced_block_height", &block_height.to_string()).unwrap();
             last_synced_block_height = Some(block_height);
         }
     }
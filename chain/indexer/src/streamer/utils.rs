@@ -39,6 +39,7 @@ pub(crate) async fn convert_transactions_sir_into_local_receipts(
                                 near_primitives::transaction::Action::try_from(action).unwrap()
                             })
                             .collect(),
+                        priority_fee: tx.transaction.priority_fee,
                     },
                     prev_block_gas_price,
                     true,
@@ -59,6 +60,10 @@ pub(crate) async fn convert_transactions_sir_into_local_receipts(
                         output_data_receivers: vec![],
                         input_data_ids: vec![],
                         actions: tx.transaction.actions.clone(),
+                        priority_fee: tx.transaction.priority_fee,
+                        depth: near_primitives::receipt::transaction_receipt_depth(
+                            protocol_config.protocol_version,
+                        ),
                     },
                 }
             })
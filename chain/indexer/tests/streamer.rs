@@ -0,0 +1,166 @@
+//! Runs a node with the indexer and checks where the streamer resumes when the consumer is
+//! restarted.
+use std::path::Path;
+use std::time::Duration;
+
+use actix::System;
+use tokio::sync::mpsc;
+
+use near_actix_test_utils::run_actix;
+use near_chain_configs::{Genesis, GenesisValidationMode};
+use near_client::GetBlock;
+use near_indexer::{
+    indexer_init_configs, AwaitForNodeSyncedEnum, Indexer, IndexerConfig, InitConfigArgs,
+    StreamerAck, StreamerEvent, SyncModeEnum,
+};
+use near_primitives::types::{BlockHeight, BlockReference, SyncCheckpoint};
+use nearcore::config::{Config, CONFIG_FILENAME};
+
+/// Short epochs, so that the node garbage collects blocks in a few seconds.
+const EPOCH_LENGTH: u64 = 5;
+const CURSOR_COLUMN: &str = "cursor";
+const CURSOR_KEY: &[u8] = b"last_delivered_block_height";
+
+fn init_home(home: &Path) {
+    indexer_init_configs(
+        &home.to_path_buf(),
+        InitConfigArgs {
+            chain_id: Some("localnet".to_string()),
+            account_id: None,
+            test_seed: None,
+            num_shards: 1,
+            fast: true,
+            genesis: None,
+            download_genesis: false,
+            download_genesis_url: None,
+            download_config: false,
+            download_config_url: None,
+            boot_nodes: None,
+            max_gas_burnt_view: None,
+        },
+    )
+    .unwrap();
+    let config_path = home.join(CONFIG_FILENAME);
+    let mut config = Config::from_file(&config_path).unwrap();
+    config.tracked_shards = vec![0];
+    config.write_to_file(&config_path).unwrap();
+    let genesis_path = home.join(&config.genesis_file);
+    let mut genesis = Genesis::from_file(&genesis_path, GenesisValidationMode::Full);
+    genesis.config.epoch_length = EPOCH_LENGTH;
+    genesis.to_file(&genesis_path);
+}
+
+/// Returns the acknowledgement handle of the next event, which has to be a block.
+async fn next_message(events: &mut mpsc::Receiver<StreamerEvent>) -> StreamerAck {
+    match events.recv().await.unwrap() {
+        StreamerEvent::Message(streamer_message, ack) => {
+            assert_eq!(streamer_message.block.header.height, ack.height());
+            ack
+        }
+        StreamerEvent::Gap(gap, _) => panic!("Unexpected {:?}", gap),
+    }
+}
+
+/// Opens the indexer database once the streamer which has lost its consumer has noticed it and
+/// closed the database.
+async fn open_cursor_db(path: &Path) -> rocksdb::DB {
+    loop {
+        match rocksdb::DB::open_cf(&rocksdb::Options::default(), path, [CURSOR_COLUMN]) {
+            Ok(db) => return db,
+            Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+        }
+    }
+}
+
+fn read_cursor(db: &rocksdb::DB) -> Option<BlockHeight> {
+    let column = db.cf_handle(CURSOR_COLUMN).unwrap();
+    db.get_cf(column, CURSOR_KEY)
+        .unwrap()
+        .map(|value| std::str::from_utf8(&value).unwrap().parse().unwrap())
+}
+
+fn write_cursor(db: &rocksdb::DB, height: BlockHeight) {
+    let column = db.cf_handle(CURSOR_COLUMN).unwrap();
+    db.put_cf(column, CURSOR_KEY, height.to_string()).unwrap();
+}
+
+#[test]
+fn test_streamer_resumes_after_consumer_restart() {
+    let home = tempfile::tempdir().unwrap();
+    init_home(home.path());
+    let indexer_db_path = nearcore::get_store_path(home.path()).join("indexer");
+
+    run_actix(async move {
+        let indexer = Indexer::new(IndexerConfig {
+            home_dir: home.path().to_path_buf(),
+            sync_mode: SyncModeEnum::FromInterruption,
+            await_for_node_synced: AwaitForNodeSyncedEnum::StreamWhileSyncing,
+            stream_receipt_queue_changes: false,
+        })
+        .unwrap();
+
+        // The consumer handles three blocks and dies while handling the fourth one.
+        let mut events = indexer.streamer_with_acks();
+        let mut last_acked_height = None;
+        for _ in 0..3 {
+            let ack = next_message(&mut events).await;
+            last_acked_height = Some(ack.height());
+            ack.ack();
+        }
+        let unacked = next_message(&mut events).await;
+        let unacked_height = unacked.height();
+        drop(unacked);
+        drop(events);
+        let db = open_cursor_db(&indexer_db_path).await;
+        assert_eq!(read_cursor(&db), last_acked_height);
+        drop(db);
+
+        // After the restart the block which hasn't been acknowledged comes first, with nothing
+        // missed or repeated before it.
+        let mut events = indexer.streamer_with_acks();
+        let ack = next_message(&mut events).await;
+        assert_eq!(ack.height(), unacked_height);
+        ack.ack();
+        drop(events);
+
+        // The consumer stays down until the node garbage collects the blocks after its cursor.
+        let db = open_cursor_db(&indexer_db_path).await;
+        write_cursor(&db, 1);
+        drop(db);
+        let (view_client, _) = indexer.client_actors();
+        let earliest_height = loop {
+            let earliest_block = view_client
+                .send(GetBlock(BlockReference::SyncCheckpoint(SyncCheckpoint::EarliestAvailable)))
+                .await
+                .unwrap()
+                .unwrap();
+            if earliest_block.header.height > 2 {
+                break earliest_block.header.height;
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        };
+
+        let mut events = indexer.streamer_with_acks();
+        let gap = match events.recv().await.unwrap() {
+            StreamerEvent::Gap(gap, ack) => {
+                assert_eq!(ack.height(), gap.end_height);
+                ack.ack();
+                gap
+            }
+            StreamerEvent::Message(streamer_message, _) => {
+                panic!("Expected a gap, got block #{}", streamer_message.block.header.height)
+            }
+        };
+        assert_eq!(gap.start_height, 2);
+        assert!(gap.end_height + 1 >= earliest_height);
+        // The node may collect more blocks right after the gap has been found.
+        let ack = next_message(&mut events).await;
+        assert!(ack.height() > gap.end_height);
+        ack.ack();
+        drop(events);
+
+        let db = open_cursor_db(&indexer_db_path).await;
+        assert!(read_cursor(&db).unwrap() > gap.end_height);
+        System::current().stop();
+    });
+}
@@ -22,6 +22,9 @@ pub enum RpcBlockError {
 pub struct RpcBlockRequest {
     #[serde(flatten)]
     pub block_reference: near_primitives::types::BlockReference,
+    /// Return summaries of the chunks instead of their headers.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub header_only: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -30,6 +33,12 @@ pub struct RpcBlockResponse {
     pub block_view: near_primitives::views::BlockView,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcBlockHeaderOnlyResponse {
+    #[serde(flatten)]
+    pub block_view: near_primitives::views::BlockHeaderOnlyView,
+}
+
 impl From<near_client_primitives::types::GetBlockError> for RpcBlockError {
     fn from(error: near_client_primitives::types::GetBlockError) -> Self {
         match error {
@@ -84,7 +93,17 @@ impl From<RpcBlockError> for crate::errors::RpcError {
 }
 
 impl RpcBlockRequest {
-    pub fn parse(value: Option<Value>) -> Result<RpcBlockRequest, crate::errors::RpcParseError> {
+    pub fn parse(
+        mut value: Option<Value>,
+    ) -> Result<RpcBlockRequest, crate::errors::RpcParseError> {
+        // The block reference is an enum which doesn't allow other fields next to it.
+        let header_only = match value.as_mut().and_then(Value::as_object_mut) {
+            Some(params) => match params.remove("header_only") {
+                Some(header_only) => crate::utils::parse_params::<bool>(Some(header_only))?,
+                None => false,
+            },
+            None => false,
+        };
         let block_reference = if let Ok((block_id,)) =
             crate::utils::parse_params::<(near_primitives::types::BlockId,)>(value.clone())
         {
@@ -92,6 +111,6 @@ impl RpcBlockRequest {
         } else {
             crate::utils::parse_params::<near_primitives::types::BlockReference>(value)?
         };
-        Ok(RpcBlockRequest { block_reference })
+        Ok(RpcBlockRequest { block_reference, header_only })
     }
 }
@@ -11,6 +11,10 @@ pub enum ChunkReference {
     ChunkHash {
         chunk_id: near_primitives::hash::CryptoHash,
     },
+    SyncCheckpointShardId {
+        sync_checkpoint: near_primitives::types::SyncCheckpoint,
+        shard_id: near_primitives::types::ShardId,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -51,6 +55,9 @@ impl From<ChunkReference> for near_client_primitives::types::GetChunk {
                 }
             },
             ChunkReference::ChunkHash { chunk_id } => Self::ChunkHash(chunk_id.into()),
+            ChunkReference::SyncCheckpointShardId { sync_checkpoint, shard_id } => {
+                Self::SyncCheckpoint(sync_checkpoint, shard_id)
+            }
         }
     }
 }
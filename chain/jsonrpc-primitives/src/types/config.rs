@@ -84,3 +84,66 @@ impl From<RpcProtocolConfigError> for crate::errors::RpcError {
         Self::new_internal_or_handler_error(error_data, error_data_value)
     }
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcProtocolUpgradeStatusResponse {
+    #[serde(flatten)]
+    pub status: near_primitives::views::ProtocolUpgradeStatusView,
+    /// Oldest protocol version of the peers the node connects to.
+    pub min_supported_protocol_version: near_primitives::types::ProtocolVersion,
+    /// Latest protocol version the node runs.  If the network upgrades past it, the node has to
+    /// be updated to keep up.
+    pub max_supported_protocol_version: near_primitives::types::ProtocolVersion,
+}
+
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcProtocolUpgradeStatusError {
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<near_client_primitives::types::GetProtocolUpgradeStatusError>
+    for RpcProtocolUpgradeStatusError
+{
+    fn from(error: near_client_primitives::types::GetProtocolUpgradeStatusError) -> Self {
+        match error {
+            near_client_primitives::types::GetProtocolUpgradeStatusError::IOError(
+                error_message,
+            ) => Self::InternalError { error_message },
+            near_client_primitives::types::GetProtocolUpgradeStatusError::Unreachable(
+                ref error_message,
+            ) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", &error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcProtocolUpgradeStatusError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
+
+impl From<actix::MailboxError> for RpcProtocolUpgradeStatusError {
+    fn from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl From<RpcProtocolUpgradeStatusError> for crate::errors::RpcError {
+    fn from(error: RpcProtocolUpgradeStatusError) -> Self {
+        let error_data = Some(Value::String(error.to_string()));
+
+        let error_data_value = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcProtocolUpgradeStatusError: {:?}", err),
+                )
+            }
+        };
+
+        Self::new_internal_or_handler_error(error_data, error_data_value)
+    }
+}
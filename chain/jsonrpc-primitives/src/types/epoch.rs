@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RpcEpochInfoRequest {
+    EpochId(near_primitives::types::EpochId),
+    EpochHeight(near_primitives::types::EpochHeight),
+}
+
+impl RpcEpochInfoRequest {
+    pub fn parse(value: Option<Value>) -> Result<Self, crate::errors::RpcParseError> {
+        crate::utils::parse_params::<RpcEpochInfoRequest>(value)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcEpochInfoResponse {
+    #[serde(flatten)]
+    pub epoch_info: near_primitives::views::EpochInfoView,
+}
+
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcEpochInfoError {
+    #[error("Epoch not found")]
+    UnknownEpoch,
+    #[error("No epoch with height {epoch_height} is known")]
+    UnknownEpochHeight { epoch_height: near_primitives::types::EpochHeight },
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<near_client_primitives::types::GetEpochInfoError> for RpcEpochInfoError {
+    fn from(error: near_client_primitives::types::GetEpochInfoError) -> Self {
+        match error {
+            near_client_primitives::types::GetEpochInfoError::UnknownEpoch => Self::UnknownEpoch,
+            near_client_primitives::types::GetEpochInfoError::UnknownEpochHeight(epoch_height) => {
+                Self::UnknownEpochHeight { epoch_height }
+            }
+            near_client_primitives::types::GetEpochInfoError::IOError(error_message) => {
+                Self::InternalError { error_message }
+            }
+            near_client_primitives::types::GetEpochInfoError::Unreachable(ref error_message) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", &error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcEpochInfoError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
+
+impl From<actix::MailboxError> for RpcEpochInfoError {
+    fn from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl From<RpcEpochInfoError> for crate::errors::RpcError {
+    fn from(error: RpcEpochInfoError) -> Self {
+        let error_data = Some(Value::String(error.to_string()));
+
+        let error_data_value = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcEpochInfoError: {:?}", err),
+                )
+            }
+        };
+
+        Self::new_internal_or_handler_error(error_data, error_data_value)
+    }
+}
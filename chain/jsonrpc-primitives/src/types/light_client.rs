@@ -8,6 +8,12 @@ pub struct RpcLightClientExecutionProofRequest {
     pub light_client_head: near_primitives::hash::CryptoHash,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcLightClientBlockProofRequest {
+    pub block_hash: near_primitives::hash::CryptoHash,
+    pub light_client_head: near_primitives::hash::CryptoHash,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RpcLightClientNextBlockRequest {
     pub last_block_hash: near_primitives::hash::CryptoHash,
@@ -21,6 +27,14 @@ pub struct RpcLightClientExecutionProofResponse {
     pub block_proof: near_primitives::merkle::MerklePath,
 }
 
+/// Proof that a block is on the chain of the light client head: `block_proof` is the path from
+/// the block hash to the `block_merkle_root` of the head, empty if the block is the head itself.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcLightClientBlockProofResponse {
+    pub block_header_lite: near_primitives::views::LightClientBlockLiteView,
+    pub block_proof: near_primitives::merkle::MerklePath,
+}
+
 #[derive(Debug, Serialize)]
 pub struct RpcLightClientNextBlockResponse {
     #[serde(flatten)]
@@ -49,6 +63,11 @@ pub enum RpcLightClientProofError {
         transaction_or_receipt_id: near_primitives::hash::CryptoHash,
         shard_id: near_primitives::types::ShardId,
     },
+    #[error("Block {block_hash} is newer than the light client head {light_client_head}")]
+    BlockAfterHead {
+        block_hash: near_primitives::hash::CryptoHash,
+        light_client_head: near_primitives::hash::CryptoHash,
+    },
     #[error("Internal error: {error_message}")]
     InternalError { error_message: String },
 }
@@ -73,6 +92,12 @@ impl RpcLightClientExecutionProofRequest {
     }
 }
 
+impl RpcLightClientBlockProofRequest {
+    pub fn parse(value: Option<Value>) -> Result<Self, crate::errors::RpcParseError> {
+        Ok(crate::utils::parse_params::<Self>(value)?)
+    }
+}
+
 impl RpcLightClientNextBlockRequest {
     pub fn parse(value: Option<Value>) -> Result<Self, crate::errors::RpcParseError> {
         if let Ok((last_block_hash,)) =
@@ -132,6 +157,10 @@ impl From<near_client_primitives::types::GetBlockProofError> for RpcLightClientP
             near_client_primitives::types::GetBlockProofError::UnknownBlock { error_message } => {
                 Self::UnknownBlock { error_message }
             }
+            near_client_primitives::types::GetBlockProofError::BlockAfterHead {
+                block_hash,
+                head_block_hash,
+            } => Self::BlockAfterHead { block_hash, light_client_head: head_block_hash },
             near_client_primitives::types::GetBlockProofError::InternalError { error_message } => {
                 Self::InternalError { error_message }
             }
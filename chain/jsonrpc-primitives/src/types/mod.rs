@@ -2,6 +2,7 @@ pub mod blocks;
 pub mod changes;
 pub mod chunks;
 pub mod config;
+pub mod epoch;
 pub mod gas_price;
 pub mod light_client;
 pub mod network_info;
@@ -9,5 +10,6 @@ pub mod query;
 pub mod receipts;
 pub mod sandbox;
 pub mod status;
+pub mod subscriptions;
 pub mod transactions;
 pub mod validator;
@@ -1,4 +1,6 @@
-use near_network_primitives::types::{KnownProducer, PeerInfo};
+use near_network_primitives::types::{
+    BannedPeerInfo, DroppedRoutedMessage, KnownProducer, PeerIdOrHash, PeerInfo,
+};
 use near_primitives::network::PeerId;
 use near_primitives::types::AccountId;
 use serde::{Deserialize, Serialize};
@@ -29,6 +31,53 @@ pub struct RpcNetworkInfoResponse {
     pub known_producers: Vec<RpcKnownProducer>,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcBannedPeer {
+    pub peer: RpcPeerInfo,
+    pub reason: String,
+    /// Timestamp of the ban in nanoseconds.
+    pub banned_at: u64,
+    /// Timestamp in nanoseconds after which the peer is allowed to connect again.
+    pub expires_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcBannedPeersResponse {
+    pub banned_peers: Vec<RpcBannedPeer>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcDroppedRoutedMessage {
+    pub msg_type: String,
+    /// Original sender of the message.
+    pub author: PeerId,
+    /// Target peer id, or hash of the request for responses routed back.
+    pub target: String,
+    /// Peer from which the message was received.
+    pub from: PeerId,
+    pub num_hops: u8,
+    /// Timestamp in nanoseconds.
+    pub dropped_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcDroppedRoutedMessagesResponse {
+    pub dropped_messages: Vec<RpcDroppedRoutedMessage>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcBanPeerRequest {
+    pub peer_id: PeerId,
+    /// Duration of the ban in seconds. The configured ban window is used if not set.
+    #[serde(default)]
+    pub ban_duration_secs: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcUnbanPeerRequest {
+    pub peer_id: PeerId,
+}
+
 #[derive(thiserror::Error, Debug, Serialize, Deserialize)]
 #[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum RpcNetworkInfoError {
@@ -42,6 +91,33 @@ impl From<PeerInfo> for RpcPeerInfo {
     }
 }
 
+impl From<BannedPeerInfo> for RpcBannedPeer {
+    fn from(banned_peer: BannedPeerInfo) -> Self {
+        Self {
+            peer: banned_peer.peer_info.into(),
+            reason: format!("{:?}", banned_peer.reason),
+            banned_at: banned_peer.banned_at,
+            expires_at: banned_peer.expires_at,
+        }
+    }
+}
+
+impl From<DroppedRoutedMessage> for RpcDroppedRoutedMessage {
+    fn from(msg: DroppedRoutedMessage) -> Self {
+        Self {
+            msg_type: msg.msg_type.to_string(),
+            author: msg.author,
+            target: match msg.target {
+                PeerIdOrHash::PeerId(peer_id) => peer_id.to_string(),
+                PeerIdOrHash::Hash(hash) => hash.to_string(),
+            },
+            from: msg.from,
+            num_hops: msg.num_hops,
+            dropped_at: msg.dropped_at,
+        }
+    }
+}
+
 impl From<KnownProducer> for RpcKnownProducer {
     fn from(known_producer: KnownProducer) -> Self {
         Self {
@@ -73,6 +149,32 @@ impl From<near_client_primitives::types::NetworkInfoResponse> for RpcNetworkInfo
     }
 }
 
+impl From<near_client_primitives::types::NetworkInfoResponse> for RpcBannedPeersResponse {
+    fn from(network_info_response: near_client_primitives::types::NetworkInfoResponse) -> Self {
+        Self {
+            banned_peers: network_info_response
+                .banned_peers
+                .into_iter()
+                .map(|banned_peer| banned_peer.into())
+                .collect(),
+        }
+    }
+}
+
+impl From<near_client_primitives::types::NetworkInfoResponse>
+    for RpcDroppedRoutedMessagesResponse
+{
+    fn from(network_info_response: near_client_primitives::types::NetworkInfoResponse) -> Self {
+        Self {
+            dropped_messages: network_info_response
+                .recent_dropped_routed_messages
+                .into_iter()
+                .map(|msg| msg.into())
+                .collect(),
+        }
+    }
+}
+
 impl From<actix::MailboxError> for RpcNetworkInfoError {
     fn from(error: actix::MailboxError) -> Self {
         Self::InternalError { error_message: error.to_string() }
@@ -84,11 +84,25 @@ pub enum QueryResponseKind {
     ViewAccount(near_primitives::views::AccountView),
     ViewCode(near_primitives::views::ContractCodeView),
     ViewState(near_primitives::views::ViewStateResult),
-    CallResult(near_primitives::views::CallResult),
+    CallResult(RpcCallResult),
     AccessKey(near_primitives::views::AccessKeyView),
     AccessKeyList(near_primitives::views::AccessKeyList),
 }
 
+/// Result of a `call_function` query with the gas estimates, which aren't sent between nodes as
+/// part of `CallResult`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcCallResult {
+    #[serde(flatten)]
+    pub call_result: near_primitives::views::CallResult,
+    /// Gas burnt by the view execution of the function.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_burnt: Option<near_primitives::types::Gas>,
+    /// Gas to attach to a transaction making the same call.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recommended_attached_gas: Option<near_primitives::types::Gas>,
+}
+
 impl RpcQueryRequest {
     pub fn parse(value: Option<Value>) -> Result<RpcQueryRequest, crate::errors::RpcParseError> {
         let query_request = if let Ok((path, data)) =
@@ -247,7 +261,11 @@ impl From<near_primitives::views::QueryResponseKind> for QueryResponseKind {
                 Self::ViewState(view_state_result)
             }
             near_primitives::views::QueryResponseKind::CallResult(call_result) => {
-                Self::CallResult(call_result)
+                Self::CallResult(RpcCallResult {
+                    gas_burnt: call_result.gas_burnt,
+                    recommended_attached_gas: call_result.recommended_attached_gas,
+                    call_result,
+                })
             }
             near_primitives::views::QueryResponseKind::AccessKey(access_key_view) => {
                 Self::AccessKey(access_key_view)
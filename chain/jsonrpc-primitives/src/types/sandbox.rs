@@ -1,5 +1,7 @@
+use near_crypto::PublicKey;
+use near_primitives::account::AccessKey;
 use near_primitives::state_record::StateRecord;
-use near_primitives::types::BlockHeightDelta;
+use near_primitives::types::{AccountId, BlockHeightDelta};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -20,6 +22,8 @@ pub struct RpcSandboxPatchStateResponse {}
 #[derive(thiserror::Error, Debug, Serialize, Deserialize)]
 #[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum RpcSandboxPatchStateError {
+    #[error("Invalid record of account {account_id}: {error_message}")]
+    InvalidRecord { account_id: AccountId, error_message: String },
     #[error("The node reached its limits. Try again later. More details: {error_message}")]
     InternalError { error_message: String },
 }
@@ -45,6 +49,72 @@ impl From<RpcSandboxPatchStateError> for crate::errors::RpcError {
     }
 }
 
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RpcSandboxAccessKeyPatch {
+    pub account_id: AccountId,
+    pub public_key: PublicKey,
+    pub access_key: AccessKey,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RpcSandboxPatchAccessKeysRequest {
+    pub access_keys: Vec<RpcSandboxAccessKeyPatch>,
+}
+
+impl RpcSandboxPatchAccessKeysRequest {
+    pub fn parse(value: Option<Value>) -> Result<Self, crate::errors::RpcParseError> {
+        Ok(crate::utils::parse_params::<RpcSandboxPatchAccessKeysRequest>(value)?)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RpcSandboxPatchAccessKeysResponse {}
+
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcSandboxPatchAccessKeysError {
+    #[error("Account {account_id} does not exist")]
+    AccountDoesNotExist { account_id: AccountId },
+    #[error("Invalid record of account {account_id}: {error_message}")]
+    InvalidRecord { account_id: AccountId, error_message: String },
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<actix::MailboxError> for RpcSandboxPatchAccessKeysError {
+    fn from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl From<RpcSandboxPatchStateError> for RpcSandboxPatchAccessKeysError {
+    fn from(error: RpcSandboxPatchStateError) -> Self {
+        match error {
+            RpcSandboxPatchStateError::InvalidRecord { account_id, error_message } => {
+                Self::InvalidRecord { account_id, error_message }
+            }
+            RpcSandboxPatchStateError::InternalError { error_message } => {
+                Self::InternalError { error_message }
+            }
+        }
+    }
+}
+
+impl From<RpcSandboxPatchAccessKeysError> for crate::errors::RpcError {
+    fn from(error: RpcSandboxPatchAccessKeysError) -> Self {
+        let error_data = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcSandboxPatchAccessKeysError: {:?}", err),
+                )
+            }
+        };
+        Self::new_internal_or_handler_error(Some(error_data.clone()), error_data)
+    }
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct RpcSandboxFastForwardRequest {
     pub delta_height: BlockHeightDelta,
@@ -9,6 +9,42 @@ pub struct RpcStatusResponse {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RpcHealthResponse;
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcMakeSnapshotRequest {
+    /// Directory to create the snapshot in, on the node's file system. Must not exist yet.
+    pub destination: std::path::PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcSetMaxBackgroundJobsRequest {
+    /// Number of RocksDB flushes and compactions to run concurrently until the node restarts.
+    pub max_background_jobs: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcHottestTrieNodesRequest {
+    /// Maximum number of nodes to list, 100 by default.
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcTopContractsRequest {
+    /// Maximum number of contracts to list, 20 by default.
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcForwardedTransactionsRequest {
+    /// Maximum number of transactions to list, 100 by default.
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcAppliedChunkInfoRequest {
+    /// Block to return the parameters its chunks were applied with for.
+    pub block_hash: near_primitives::hash::CryptoHash,
+}
+
 #[derive(thiserror::Error, Debug, Serialize, Deserialize)]
 #[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum RpcStatusError {
@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// What a `subscribe` request on the WebSocket endpoint subscribes to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RpcSubscriptionRequest {
+    /// New final blocks, in the format of the `block` method with `header_only`.
+    FinalBlocks,
+    /// Status of a transaction, in the format of the `tx` method, whenever it changes until the
+    /// outcome of the transaction is final.
+    TxStatus {
+        tx_hash: near_primitives::hash::CryptoHash,
+        sender_account_id: near_primitives::types::AccountId,
+    },
+    /// Non-empty state changes in new final blocks, in the format of the
+    /// `EXPERIMENTAL_changes` method.
+    StateChanges {
+        #[serde(flatten)]
+        state_changes_request: near_primitives::views::StateChangesRequestView,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcSubscribeResponse {
+    pub subscription_id: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcUnsubscribeRequest {
+    pub subscription_id: u64,
+}
+
+/// Params of the `subscription` notifications pushed to the subscribers.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcSubscriptionNotification {
+    pub subscription_id: u64,
+    #[serde(flatten)]
+    pub result: RpcSubscriptionResult,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RpcSubscriptionResult {
+    Result(Value),
+    /// The subscription is closed after an error.
+    Error(crate::errors::RpcError),
+}
+
+impl RpcSubscriptionRequest {
+    pub fn parse(value: Option<Value>) -> Result<Self, crate::errors::RpcParseError> {
+        crate::utils::parse_params::<Self>(value)
+    }
+}
+
+impl RpcUnsubscribeRequest {
+    pub fn parse(value: Option<Value>) -> Result<Self, crate::errors::RpcParseError> {
+        crate::utils::parse_params::<Self>(value)
+    }
+}
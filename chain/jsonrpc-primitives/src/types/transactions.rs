@@ -33,9 +33,16 @@ pub enum RpcTransactionError {
     #[error("Node doesn't track this shard. Cannot determine whether the transaction is valid")]
     DoesNotTrackShard,
     #[error("Transaction with hash {transaction_hash} was routed")]
-    RequestRouted { transaction_hash: near_primitives::hash::CryptoHash },
+    RequestRouted {
+        transaction_hash: near_primitives::hash::CryptoHash,
+        /// Where the transaction was routed to, if the node could tell.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        routing: Option<near_primitives::views::TransactionRoutingView>,
+    },
     #[error("Transaction {requested_transaction_hash} doesn't exist")]
     UnknownTransaction { requested_transaction_hash: near_primitives::hash::CryptoHash },
+    #[error("The data for transaction {requested_transaction_hash} is garbage collected on this node, use an archival node to fetch historical data")]
+    GarbageCollectedTransaction { requested_transaction_hash: near_primitives::hash::CryptoHash },
     #[error("The node reached its limits. Try again later. More details: {debug_info}")]
     InternalError { debug_info: String },
     #[error("Timeout")]
@@ -51,6 +58,10 @@ pub struct RpcTransactionResponse {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RpcBroadcastTxSyncResponse {
     pub transaction_hash: near_primitives::hash::CryptoHash,
+    /// Shard of the signer and the chunk producers the transaction was forwarded to, if the node
+    /// could tell.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub routing: Option<near_primitives::views::TransactionRoutingView>,
 }
 
 impl RpcBroadcastTransactionRequest {
@@ -86,6 +97,9 @@ impl From<near_client_primitives::types::TxStatusError> for RpcTransactionError
             near_client_primitives::types::TxStatusError::MissingTransaction(
                 requested_transaction_hash,
             ) => Self::UnknownTransaction { requested_transaction_hash },
+            near_client_primitives::types::TxStatusError::GarbageCollectedTransaction(
+                requested_transaction_hash,
+            ) => Self::GarbageCollectedTransaction { requested_transaction_hash },
             near_client_primitives::types::TxStatusError::InvalidTx(context) => {
                 Self::InvalidTransaction { context }
             }
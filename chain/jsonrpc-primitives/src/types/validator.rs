@@ -15,6 +15,24 @@ pub enum RpcValidatorError {
     InternalError { error_message: String },
 }
 
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcValidatorChunkStatsError {
+    #[error("Account {account_id} has no block or chunk producer assignments in the current or previous epoch")]
+    NoAssignments { account_id: near_primitives::types::AccountId },
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcUnstakeScheduleError {
+    #[error("Account {account_id} has no locked stake")]
+    NotStaked { account_id: near_primitives::types::AccountId },
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RpcValidatorRequest {
     #[serde(flatten)]
@@ -32,6 +50,28 @@ pub struct RpcValidatorResponse {
     pub validator_info: near_primitives::views::EpochValidatorInfo,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcValidatorChunkStatsRequest {
+    pub account_id: near_primitives::types::AccountId,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcValidatorChunkStatsResponse {
+    #[serde(flatten)]
+    pub stats: near_primitives::views::ValidatorProductionStatsView,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcUnstakeScheduleRequest {
+    pub account_id: near_primitives::types::AccountId,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcUnstakeScheduleResponse {
+    #[serde(flatten)]
+    pub schedule: near_primitives::views::UnstakeScheduleView,
+}
+
 impl From<near_client_primitives::types::GetValidatorInfoError> for RpcValidatorError {
     fn from(error: near_client_primitives::types::GetValidatorInfoError) -> Self {
         match error {
@@ -63,6 +103,64 @@ impl From<actix::MailboxError> for RpcValidatorError {
     }
 }
 
+impl From<near_client_primitives::types::GetValidatorProductionStatsError>
+    for RpcValidatorChunkStatsError
+{
+    fn from(error: near_client_primitives::types::GetValidatorProductionStatsError) -> Self {
+        match error {
+            near_client_primitives::types::GetValidatorProductionStatsError::NoAssignments(
+                account_id,
+            ) => Self::NoAssignments { account_id },
+            near_client_primitives::types::GetValidatorProductionStatsError::IOError(
+                error_message,
+            ) => Self::InternalError { error_message },
+            near_client_primitives::types::GetValidatorProductionStatsError::Unreachable(
+                ref error_message,
+            ) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", &error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcValidatorChunkStatsError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
+
+impl From<actix::MailboxError> for RpcValidatorChunkStatsError {
+    fn from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl From<near_client_primitives::types::GetUnstakeScheduleError> for RpcUnstakeScheduleError {
+    fn from(error: near_client_primitives::types::GetUnstakeScheduleError) -> Self {
+        match error {
+            near_client_primitives::types::GetUnstakeScheduleError::NotStaked(account_id) => {
+                Self::NotStaked { account_id }
+            }
+            near_client_primitives::types::GetUnstakeScheduleError::IOError(error_message) => {
+                Self::InternalError { error_message }
+            }
+            near_client_primitives::types::GetUnstakeScheduleError::Unreachable(
+                ref error_message,
+            ) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", &error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcUnstakeScheduleError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
+
+impl From<actix::MailboxError> for RpcUnstakeScheduleError {
+    fn from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
 impl RpcValidatorRequest {
     pub fn parse(value: Option<Value>) -> Result<Self, crate::errors::RpcParseError> {
         let epoch_reference = if let Ok((block_id,)) =
@@ -85,6 +183,30 @@ impl RpcValidatorsOrderedRequest {
     }
 }
 
+impl RpcValidatorChunkStatsRequest {
+    pub fn parse(value: Option<Value>) -> Result<Self, crate::errors::RpcParseError> {
+        if let Ok((account_id,)) =
+            crate::utils::parse_params::<(near_primitives::types::AccountId,)>(value.clone())
+        {
+            Ok(Self { account_id })
+        } else {
+            Ok(crate::utils::parse_params::<RpcValidatorChunkStatsRequest>(value)?)
+        }
+    }
+}
+
+impl RpcUnstakeScheduleRequest {
+    pub fn parse(value: Option<Value>) -> Result<Self, crate::errors::RpcParseError> {
+        if let Ok((account_id,)) =
+            crate::utils::parse_params::<(near_primitives::types::AccountId,)>(value.clone())
+        {
+            Ok(Self { account_id })
+        } else {
+            Ok(crate::utils::parse_params::<RpcUnstakeScheduleRequest>(value)?)
+        }
+    }
+}
+
 impl From<RpcValidatorError> for crate::errors::RpcError {
     fn from(error: RpcValidatorError) -> Self {
         let error_data = match &error {
@@ -108,3 +230,39 @@ impl From<RpcValidatorError> for crate::errors::RpcError {
         Self::new_internal_or_handler_error(error_data, error_data_value)
     }
 }
+
+impl From<RpcValidatorChunkStatsError> for crate::errors::RpcError {
+    fn from(error: RpcValidatorChunkStatsError) -> Self {
+        let error_data = Some(Value::String(error.to_string()));
+
+        let error_data_value = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcValidatorChunkStatsError: {:?}", err),
+                )
+            }
+        };
+
+        Self::new_internal_or_handler_error(error_data, error_data_value)
+    }
+}
+
+impl From<RpcUnstakeScheduleError> for crate::errors::RpcError {
+    fn from(error: RpcUnstakeScheduleError) -> Self {
+        let error_data = Some(Value::String(error.to_string()));
+
+        let error_data_value = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcUnstakeScheduleError: {:?}", err),
+                )
+            }
+        };
+
+        Self::new_internal_or_handler_error(error_data, error_data_value)
+    }
+}
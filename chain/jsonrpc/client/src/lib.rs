@@ -10,13 +10,14 @@ use near_jsonrpc_primitives::message::{from_slice, Message};
 use near_jsonrpc_primitives::types::changes::{
     RpcStateChangesInBlockByTypeRequest, RpcStateChangesInBlockByTypeResponse,
 };
+use near_jsonrpc_primitives::types::config::RpcProtocolUpgradeStatusResponse;
 use near_jsonrpc_primitives::types::validator::RpcValidatorsOrderedRequest;
 use near_primitives::hash::CryptoHash;
 use near_primitives::types::{AccountId, BlockId, BlockReference, MaybeBlockId, ShardId};
 use near_primitives::views::validator_stake_view::ValidatorStakeView;
 use near_primitives::views::{
-    BlockView, ChunkView, EpochValidatorInfo, FinalExecutionOutcomeView, GasPriceView,
-    StatusResponse,
+    BlockHeaderOnlyView, BlockView, ChunkView, EpochValidatorInfo, FinalExecutionOutcomeView,
+    GasPriceView, StatusResponse,
 };
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -199,6 +200,7 @@ jsonrpc_client!(pub struct JsonRpcClient {
     pub fn chunk(&self, id: ChunkId) -> RpcRequest<ChunkView>;
     pub fn validators(&self, block_id: MaybeBlockId) -> RpcRequest<EpochValidatorInfo>;
     pub fn gas_price(&self, block_id: MaybeBlockId) -> RpcRequest<GasPriceView>;
+    pub fn protocol_upgrade_status(&self) -> RpcRequest<RpcProtocolUpgradeStatusResponse>;
 });
 
 impl JsonRpcClient {
@@ -227,6 +229,17 @@ impl JsonRpcClient {
         call_method(&self.client, &self.server_addr, "block", request)
     }
 
+    pub fn block_header_only(
+        &self,
+        block_reference: BlockReference,
+    ) -> RpcRequest<BlockHeaderOnlyView> {
+        let request = near_jsonrpc_primitives::types::blocks::RpcBlockRequest {
+            block_reference,
+            header_only: true,
+        };
+        call_method(&self.client, &self.server_addr, "block", request)
+    }
+
     #[allow(non_snake_case)]
     pub fn EXPERIMENTAL_changes(
         &self,
@@ -243,6 +256,30 @@ impl JsonRpcClient {
         call_method(&self.client, &self.server_addr, "EXPERIMENTAL_validators_ordered", request)
     }
 
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_epoch_info(
+        &self,
+        request: near_jsonrpc_primitives::types::epoch::RpcEpochInfoRequest,
+    ) -> RpcRequest<near_jsonrpc_primitives::types::epoch::RpcEpochInfoResponse> {
+        call_method(&self.client, &self.server_addr, "EXPERIMENTAL_epoch_info", request)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_unstake_schedule(
+        &self,
+        request: near_jsonrpc_primitives::types::validator::RpcUnstakeScheduleRequest,
+    ) -> RpcRequest<near_jsonrpc_primitives::types::validator::RpcUnstakeScheduleResponse> {
+        call_method(&self.client, &self.server_addr, "EXPERIMENTAL_unstake_schedule", request)
+    }
+
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_validator_chunk_stats(
+        &self,
+        request: near_jsonrpc_primitives::types::validator::RpcValidatorChunkStatsRequest,
+    ) -> RpcRequest<near_jsonrpc_primitives::types::validator::RpcValidatorChunkStatsResponse> {
+        call_method(&self.client, &self.server_addr, "EXPERIMENTAL_validator_chunk_stats", request)
+    }
+
     #[allow(non_snake_case)]
     pub fn EXPERIMENTAL_receipt(
         &self,
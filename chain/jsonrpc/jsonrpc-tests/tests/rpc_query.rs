@@ -1,4 +1,5 @@
 use std::str::FromStr;
+use std::time::Duration;
 
 use actix::{Actor, System};
 use futures::{future, FutureExt};
@@ -7,13 +8,14 @@ use serde_json::json;
 use near_actix_test_utils::run_actix;
 use near_crypto::{KeyType, PublicKey, Signature};
 use near_jsonrpc::client::{new_client, ChunkId};
+use near_jsonrpc_primitives::errors::RpcErrorKind;
 use near_jsonrpc_primitives::types::query::QueryResponseKind;
 use near_jsonrpc_primitives::types::validator::RpcValidatorsOrderedRequest;
 use near_logger_utils::init_test_logger;
 use near_network::test_utils::WaitOrTimeoutActor;
 use near_primitives::account::{AccessKey, AccessKeyPermission};
 use near_primitives::hash::CryptoHash;
-use near_primitives::types::{BlockId, BlockReference, EpochId, ShardId, SyncCheckpoint};
+use near_primitives::types::{BlockId, BlockReference, EpochId, Finality, ShardId, SyncCheckpoint};
 use near_primitives::views::QueryRequest;
 
 use near_jsonrpc_tests::{self as test_utils, test_with_client};
@@ -83,6 +85,37 @@ fn test_block_query() {
     });
 }
 
+/// Retrieve block header with chunk summaries via json rpc
+#[test]
+fn test_block_header_only() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let block = client.block(BlockReference::BlockId(BlockId::Height(0))).await.unwrap();
+        let block_header_only =
+            client.block_header_only(BlockReference::BlockId(BlockId::Height(0))).await.unwrap();
+        assert_eq!(block_header_only.author, block.author);
+        assert_eq!(block_header_only.header.hash, block.header.hash);
+        assert_eq!(block_header_only.chunks.len(), block.chunks.len());
+        for (summary, chunk) in block_header_only.chunks.iter().zip(block.chunks.iter()) {
+            assert_eq!(summary.shard_id, chunk.shard_id);
+            assert_eq!(summary.chunk_hash, chunk.chunk_hash);
+            assert_eq!(summary.gas_used, chunk.gas_used);
+            assert_eq!(summary.tx_count, Some(0));
+            assert_eq!(summary.receipt_count, Some(0));
+        }
+
+        let block_header_only: serde_json::Value = test_utils::call_method(
+            &client.client,
+            &client.server_addr,
+            "block",
+            json!({"block_id": 0u64, "header_only": true}),
+        )
+        .await
+        .unwrap();
+        assert!(block_header_only["chunks"][0].get("tx_root").is_none());
+        assert_eq!(block_header_only["chunks"][0]["tx_count"], json!(0));
+    });
+}
+
 /// Retrieve chunk via json rpc
 #[test]
 fn test_chunk_by_hash() {
@@ -338,8 +371,8 @@ fn test_query_call_function() {
                 query_response.kind
             );
         };
-        assert_eq!(call_result.result.len(), 0);
-        assert_eq!(call_result.logs.len(), 0);
+        assert_eq!(call_result.call_result.result.len(), 0);
+        assert_eq!(call_result.call_result.logs.len(), 0);
     });
 }
 
@@ -590,3 +623,140 @@ fn test_get_chunk_with_object_in_params() {
         assert_eq!(chunk.header.chunk_hash, same_chunk.header.chunk_hash);
     });
 }
+
+#[test]
+fn test_get_chunk_by_sync_checkpoint() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let chunk: near_primitives::views::ChunkView = test_utils::call_method(
+            &client.client,
+            &client.server_addr,
+            "chunk",
+            json!({
+                "sync_checkpoint": "earliest_available",
+                "shard_id": 0u64,
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(chunk.header.height_included, 0);
+        assert_eq!(chunk.header.shard_id, 0);
+        let same_chunk = client
+            .chunk(ChunkId::BlockShardId(BlockId::Height(0), ShardId::from(0u64)))
+            .await
+            .unwrap();
+        assert_eq!(chunk.header.chunk_hash, same_chunk.header.chunk_hash);
+    });
+}
+
+/// The response of a query at the earliest available block tells which block it resolved to.
+#[test]
+fn test_query_earliest_available() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let genesis = client.block(BlockReference::BlockId(BlockId::Height(0))).await.unwrap();
+        let query_response = client
+            .query(near_jsonrpc_primitives::types::query::RpcQueryRequest {
+                block_reference: BlockReference::SyncCheckpoint(SyncCheckpoint::EarliestAvailable),
+                request: QueryRequest::ViewAccount { account_id: "test".parse().unwrap() },
+            })
+            .await
+            .unwrap();
+        assert_eq!(query_response.block_height, 0);
+        assert_eq!(query_response.block_hash, genesis.header.hash);
+    });
+}
+
+/// A block proof for the genesis block relative to itself is empty and verifies, and unknown blocks
+/// are rejected.
+#[test]
+fn test_light_client_block_proof_genesis() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let genesis = client.block(BlockReference::BlockId(BlockId::Height(0))).await.unwrap();
+        let response: near_jsonrpc_primitives::types::light_client::RpcLightClientBlockProofResponse =
+            test_utils::call_method(
+                &client.client,
+                &client.server_addr,
+                "block_proof",
+                json!({
+                    "block_hash": genesis.header.hash,
+                    "light_client_head": genesis.header.hash,
+                }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.block_header_lite.inner_lite.height, 0);
+        assert!(near_primitives::merkle::verify_block_proof(
+            &genesis.header.hash,
+            &response.block_proof,
+            &genesis.header.hash,
+            &genesis.header.block_merkle_root,
+        ));
+
+        let response = test_utils::call_method::<serde_json::Value>(
+            &client.client,
+            &client.server_addr,
+            "block_proof",
+            json!({
+                "block_hash": CryptoHash::default(),
+                "light_client_head": genesis.header.hash,
+            }),
+        )
+        .await;
+        assert!(response.is_err());
+    });
+}
+
+/// Blocks below a later light client head are proven against its block merkle root, and blocks
+/// newer than the head are rejected.
+#[test]
+fn test_block_proof_older_block() {
+    test_with_client!(test_utils::NodeType::Validator, client, async move {
+        let head = loop {
+            let block = client.block(BlockReference::Finality(Finality::Final)).await.unwrap();
+            if block.header.height >= 3 {
+                break block;
+            }
+            actix::clock::sleep(Duration::from_millis(100)).await;
+        };
+        let genesis = client.block(BlockReference::BlockId(BlockId::Height(0))).await.unwrap();
+        for block_hash in [genesis.header.hash, head.header.prev_hash] {
+            let response: near_jsonrpc_primitives::types::light_client::RpcLightClientBlockProofResponse =
+                test_utils::call_method(
+                    &client.client,
+                    &client.server_addr,
+                    "block_proof",
+                    json!({
+                        "block_hash": block_hash,
+                        "light_client_head": head.header.hash,
+                    }),
+                )
+                .await
+                .unwrap();
+            assert!(response.block_header_lite.inner_lite.height < head.header.height);
+            assert!(!response.block_proof.is_empty());
+            assert!(near_primitives::merkle::verify_block_proof(
+                &block_hash,
+                &response.block_proof,
+                &head.header.hash,
+                &head.header.block_merkle_root,
+            ));
+        }
+
+        let error = test_utils::call_method::<serde_json::Value>(
+            &client.client,
+            &client.server_addr,
+            "block_proof",
+            json!({
+                "block_hash": head.header.hash,
+                "light_client_head": head.header.prev_hash,
+            }),
+        )
+        .await
+        .unwrap_err();
+        match error.error_struct {
+            Some(RpcErrorKind::HandlerError(error)) => {
+                assert_eq!(error["name"], "BLOCK_AFTER_HEAD")
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+    });
+}
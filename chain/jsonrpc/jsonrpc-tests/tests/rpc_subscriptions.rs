@@ -0,0 +1,91 @@
+use actix::System;
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+
+use near_actix_test_utils::run_actix;
+use near_logger_utils::init_test_logger;
+
+use near_jsonrpc_tests as test_utils;
+
+type Connection = actix_codec::Framed<awc::BoxedSocket, awc::ws::Codec>;
+
+/// Sends a JSON RPC request over the WebSocket connection and returns the response to it,
+/// skipping notifications.
+async fn request(connection: &mut Connection, id: u64, method: &str, params: Value) -> Value {
+    let request = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+    connection.send(awc::ws::Message::Text(request.to_string().into())).await.unwrap();
+    loop {
+        if let awc::ws::Frame::Text(text) = connection.next().await.unwrap().unwrap() {
+            let message: Value = serde_json::from_slice(&text).unwrap();
+            if message["id"] == json!(id) {
+                return message;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_subscribe_and_unsubscribe() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::NonValidator);
+
+        actix::spawn(async move {
+            let (_response, mut connection) =
+                awc::Client::new().ws(format!("ws://{}/ws", addr)).connect().await.unwrap();
+
+            let response =
+                request(&mut connection, 0, "subscribe", json!({"type": "final_blocks"})).await;
+            assert_eq!(response["result"], json!({"subscription_id": 0}));
+            let response = request(
+                &mut connection,
+                1,
+                "subscribe",
+                json!({
+                    "type": "tx_status",
+                    "tx_hash": "11111111111111111111111111111111",
+                    "sender_account_id": "test1",
+                }),
+            )
+            .await;
+            assert_eq!(response["result"], json!({"subscription_id": 1}));
+            for id in 2..16 {
+                let response = request(
+                    &mut connection,
+                    id,
+                    "subscribe",
+                    json!({
+                        "type": "state_changes",
+                        "changes_type": "account_changes",
+                        "account_ids": ["test1"],
+                    }),
+                )
+                .await;
+                assert_eq!(response["result"], json!({ "subscription_id": id }));
+            }
+
+            // The default limit of subscriptions per connection is reached.
+            let response =
+                request(&mut connection, 16, "subscribe", json!({"type": "final_blocks"})).await;
+            assert!(response["error"].is_object(), "{}", response);
+
+            let response =
+                request(&mut connection, 17, "unsubscribe", json!({"subscription_id": 0})).await;
+            assert_eq!(response["result"], Value::Null);
+            assert!(response.get("error").is_none());
+            let response =
+                request(&mut connection, 18, "unsubscribe", json!({"subscription_id": 0})).await;
+            assert!(response["error"].is_object(), "{}", response);
+            let response =
+                request(&mut connection, 19, "subscribe", json!({"type": "final_blocks"})).await;
+            assert_eq!(response["result"], json!({"subscription_id": 16}));
+
+            let response =
+                request(&mut connection, 20, "block", json!({"finality": "final"})).await;
+            assert_eq!(response["error"]["code"], json!(-32601));
+
+            System::current().stop();
+        });
+    });
+}
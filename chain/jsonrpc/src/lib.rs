@@ -1,5 +1,6 @@
 #![doc = include_str!("../README.md")]
 
+use std::collections::HashMap;
 use std::time::{Duration, Instant};
 
 use actix::Addr;
@@ -15,24 +16,50 @@ use tracing::info;
 
 use near_chain_configs::GenesisConfig;
 use near_client::{
-    ClientActor, GetBlock, GetBlockProof, GetChunk, GetExecutionOutcome, GetGasPrice,
-    GetNetworkInfo, GetNextLightClientBlock, GetProtocolConfig, GetReceipt, GetStateChanges,
-    GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered, Query, Status, TxStatus,
+    CachedTrieNode, ClientActor, GetAppliedChunkInfo, GetBlock, GetBlockHeaderOnly, GetBlockProof,
+    GetChunk, GetEpochInfo, GetExecutionOutcome, GetForwardedTransactions, GetGasPrice,
+    GetHottestTrieNodes, GetNetworkInfo, GetNextLightClientBlock, GetProtocolConfig,
+    GetProtocolUpgradeStatus, GetReceipt, GetStateChanges, GetStateChangesInBlock,
+    GetStoreProperties, GetTopContracts, GetTransactionRouting, GetUnstakeSchedule,
+    GetValidatorInfo, GetValidatorOrdered, GetValidatorProductionStats, MakeSnapshot,
+    ManagePeerBan, Query, SetMaxBackgroundJobs, SnapshotInfo, Status, StoreProperties, TxStatus,
     TxStatusError, ViewClientActor,
 };
 pub use near_jsonrpc_client as client;
 use near_jsonrpc_primitives::errors::RpcError;
 use near_jsonrpc_primitives::message::{Message, Request};
 use near_jsonrpc_primitives::types::config::RpcProtocolConfigResponse;
+use near_jsonrpc_primitives::types::network_info::{
+    RpcBanPeerRequest, RpcBannedPeersResponse, RpcDroppedRoutedMessagesResponse,
+    RpcNetworkInfoError, RpcUnbanPeerRequest,
+};
+use near_jsonrpc_primitives::types::status::{
+    RpcAppliedChunkInfoRequest, RpcForwardedTransactionsRequest, RpcHottestTrieNodesRequest,
+    RpcMakeSnapshotRequest, RpcSetMaxBackgroundJobsRequest, RpcTopContractsRequest,
+};
 use near_metrics::{Encoder, TextEncoder};
 use near_network::types::{NetworkClientMessages, NetworkClientResponses};
 use near_primitives::hash::CryptoHash;
+use near_primitives::runtime::apply_state::AppliedChunkInfo;
 use near_primitives::serialize::BaseEncode;
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::AccountId;
-use near_primitives::views::FinalExecutionOutcomeViewEnum;
+use near_primitives::types::{AccountId, ShardId};
+use near_primitives::version::{PEER_MIN_ALLOWED_PROTOCOL_VERSION, PROTOCOL_VERSION};
+use near_primitives::views::{
+    FinalExecutionOutcomeViewEnum, ForwardedTransactionView, TopContractsView,
+};
 
 mod metrics;
+mod subscriptions;
+
+pub use subscriptions::RpcSubscriptionsConfig;
+
+/// Number of nodes listed by `/debug/api/hottest_trie_nodes` unless `limit` is given.
+const DEFAULT_HOTTEST_TRIE_NODES: usize = 100;
+/// Number of contracts listed by `/debug/api/top_contracts` unless `limit` is given.
+const DEFAULT_TOP_CONTRACTS: usize = 20;
+/// Number of transactions listed by `/debug/api/forwarded_transactions` unless `limit` is given.
+const DEFAULT_FORWARDED_TRANSACTIONS: usize = 100;
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub struct RpcPollingConfig {
@@ -78,6 +105,8 @@ pub struct RpcConfig {
     // We disable it by default, as some of those endpoints might be quite CPU heavy.
     #[serde(default = "default_enable_debug_rpc")]
     pub enable_debug_rpc: bool,
+    #[serde(default)]
+    pub subscriptions_config: RpcSubscriptionsConfig,
 }
 
 impl Default for RpcConfig {
@@ -89,6 +118,7 @@ impl Default for RpcConfig {
             polling_config: Default::default(),
             limits_config: Default::default(),
             enable_debug_rpc: false,
+            subscriptions_config: Default::default(),
         }
     }
 }
@@ -209,6 +239,7 @@ struct JsonRpcHandler {
     polling_config: RpcPollingConfig,
     genesis_config: GenesisConfig,
     enable_debug_rpc: bool,
+    subscriptions_config: RpcSubscriptionsConfig,
     #[cfg(feature = "test_features")]
     peer_manager_addr: Addr<near_network::PeerManagerActor>,
     #[cfg(feature = "test_features")]
@@ -275,6 +306,20 @@ impl JsonRpcHandler {
                 "adv_switch_to_height" => Some(self.adv_switch_to_height(params).await),
                 "adv_get_saved_blocks" => Some(self.adv_get_saved_blocks(params).await),
                 "adv_check_store" => Some(self.adv_check_store(params).await),
+                #[cfg(feature = "adversarial")]
+                "adv_produce_private_fork" => Some(self.adv_produce_private_fork(params).await),
+                #[cfg(feature = "adversarial")]
+                "adv_release_private_fork" => Some(self.adv_release_private_fork(params).await),
+                #[cfg(feature = "adversarial")]
+                "adv_double_sign" => Some(self.adv_double_sign(params).await),
+                #[cfg(feature = "adversarial")]
+                "adv_produce_invalid_state_root_chunk" => {
+                    Some(self.adv_produce_invalid_state_root_chunk(params).await)
+                }
+                #[cfg(feature = "adversarial")]
+                "adv_disable_doomslug_timeouts" => {
+                    Some(self.adv_disable_doomslug_timeouts(params).await)
+                }
                 "adv_set_options" => {
                     let params = parse_params::<
                         near_jsonrpc_adversarial_primitives::SetAdvOptionsRequest,
@@ -384,8 +429,20 @@ impl JsonRpcHandler {
             "block" => {
                 let rpc_block_request =
                     near_jsonrpc_primitives::types::blocks::RpcBlockRequest::parse(request.params)?;
-                let block = self.block(rpc_block_request).await?;
-                serde_json::to_value(block)
+                if rpc_block_request.header_only {
+                    let block = self.block_header_only(rpc_block_request).await?;
+                    serde_json::to_value(block)
+                        .map_err(|err| RpcError::serialization_error(err.to_string()))
+                } else {
+                    let block = self.block(rpc_block_request).await?;
+                    serde_json::to_value(block)
+                        .map_err(|err| RpcError::serialization_error(err.to_string()))
+                }
+            }
+            "block_proof" => {
+                let rpc_block_proof_request = near_jsonrpc_primitives::types::light_client::RpcLightClientBlockProofRequest::parse(request.params)?;
+                let rpc_block_proof_response = self.block_proof(rpc_block_proof_request).await?;
+                serde_json::to_value(rpc_block_proof_response)
                     .map_err(|err| RpcError::serialization_error(err.to_string()))
             }
             "broadcast_tx_async" => {
@@ -447,6 +504,11 @@ impl JsonRpcHandler {
                 serde_json::to_value(network_info_response)
                     .map_err(|err| RpcError::serialization_error(err.to_string()))
             }
+            "protocol_upgrade_status" => {
+                let status = self.protocol_upgrade_status().await?;
+                serde_json::to_value(status)
+                    .map_err(|err| RpcError::serialization_error(err.to_string()))
+            }
             "query" => {
                 let rpc_query_request =
                     near_jsonrpc_primitives::types::query::RpcQueryRequest::parse(request.params)?;
@@ -512,6 +574,15 @@ impl JsonRpcHandler {
                 serde_json::to_value(broadcast_tx_sync_response)
                     .map_err(|err| RpcError::serialization_error(err.to_string()))
             }
+            "EXPERIMENTAL_epoch_info" => {
+                let rpc_epoch_info_request =
+                    near_jsonrpc_primitives::types::epoch::RpcEpochInfoRequest::parse(
+                        request.params,
+                    )?;
+                let epoch_info = self.epoch_info(rpc_epoch_info_request).await?;
+                serde_json::to_value(epoch_info)
+                    .map_err(|err| RpcError::serialization_error(err.to_string()))
+            }
             "EXPERIMENTAL_genesis_config" => {
                 let genesis_config = self.genesis_config().await;
                 serde_json::to_value(genesis_config)
@@ -550,6 +621,24 @@ impl JsonRpcHandler {
                 serde_json::to_value(rpc_transaction_response)
                     .map_err(|err| RpcError::serialization_error(err.to_string()))
             }
+            "EXPERIMENTAL_unstake_schedule" => {
+                let rpc_unstake_schedule_request =
+                    near_jsonrpc_primitives::types::validator::RpcUnstakeScheduleRequest::parse(
+                        request.params,
+                    )?;
+                let schedule = self.unstake_schedule(rpc_unstake_schedule_request).await?;
+                serde_json::to_value(schedule)
+                    .map_err(|err| RpcError::serialization_error(err.to_string()))
+            }
+            "EXPERIMENTAL_validator_chunk_stats" => {
+                let rpc_validator_chunk_stats_request =
+                    near_jsonrpc_primitives::types::validator::RpcValidatorChunkStatsRequest::parse(
+                        request.params,
+                    )?;
+                let stats = self.validator_chunk_stats(rpc_validator_chunk_stats_request).await?;
+                serde_json::to_value(stats)
+                    .map_err(|err| RpcError::serialization_error(err.to_string()))
+            }
             "EXPERIMENTAL_validators_ordered" => {
                 let rpc_validators_ordered_request =
                     near_jsonrpc_primitives::types::validator::RpcValidatorsOrderedRequest::parse(
@@ -571,6 +660,17 @@ impl JsonRpcHandler {
                     .map_err(|err| RpcError::serialization_error(err.to_string()))
             }
             #[cfg(feature = "sandbox")]
+            "sandbox_patch_access_keys" => {
+                let sandbox_patch_access_keys_request =
+                    near_jsonrpc_primitives::types::sandbox::RpcSandboxPatchAccessKeysRequest::parse(
+                        request.params,
+                    )?;
+                let sandbox_patch_access_keys_response =
+                    self.sandbox_patch_access_keys(sandbox_patch_access_keys_request).await?;
+                serde_json::to_value(sandbox_patch_access_keys_response)
+                    .map_err(|err| RpcError::serialization_error(err.to_string()))
+            }
+            #[cfg(feature = "sandbox")]
             "sandbox_fast_forward" => {
                 let sandbox_fast_forward_request =
                     near_jsonrpc_primitives::types::sandbox::RpcSandboxFastForwardRequest::parse(
@@ -625,6 +725,9 @@ impl JsonRpcHandler {
                     Ok(Err(TxStatusError::MissingTransaction(_))) => {
                         return Ok(false);
                     }
+                    Ok(Err(TxStatusError::GarbageCollectedTransaction(_))) => {
+                        return Ok(true);
+                    }
                     Err(err) => return Err(near_jsonrpc_primitives::types::transactions::RpcTransactionError::InternalError {
                         debug_info: format!("{:?}", err)
                     }),
@@ -775,6 +878,22 @@ impl JsonRpcHandler {
         Ok(response)
     }
 
+    /// Shard of the signer and the chunk producers the client forwarded the transaction to.  Only
+    /// used to help debugging inclusion, so errors are ignored.
+    async fn transaction_routing(
+        &self,
+        tx: &SignedTransaction,
+    ) -> Option<near_primitives::views::TransactionRoutingView> {
+        self.client_addr
+            .send(GetTransactionRouting {
+                tx_hash: tx.get_hash(),
+                signer_id: tx.transaction.signer_id.clone(),
+            })
+            .await
+            .ok()?
+            .ok()
+    }
+
     async fn send_tx_sync(
         &self,
         request_data: near_jsonrpc_primitives::types::transactions::RpcBroadcastTransactionRequest,
@@ -786,11 +905,13 @@ impl JsonRpcHandler {
             NetworkClientResponses::ValidTx => {
                 Ok(near_jsonrpc_primitives::types::transactions::RpcBroadcastTxSyncResponse {
                     transaction_hash: request_data.signed_transaction.get_hash(),
+                    routing: self.transaction_routing(&request_data.signed_transaction).await,
                 })
             }
             NetworkClientResponses::RequestRouted => {
                 Err(near_jsonrpc_primitives::types::transactions::RpcTransactionError::RequestRouted {
                     transaction_hash: request_data.signed_transaction.get_hash(),
+                    routing: self.transaction_routing(&request_data.signed_transaction).await,
                 })
             }
             network_client_responses=> Err(
@@ -812,11 +933,13 @@ impl JsonRpcHandler {
             NetworkClientResponses::ValidTx => {
                 Ok(near_jsonrpc_primitives::types::transactions::RpcBroadcastTxSyncResponse {
                     transaction_hash: request_data.signed_transaction.get_hash(),
+                    routing: None,
                 })
             }
             NetworkClientResponses::RequestRouted => {
                 Err(near_jsonrpc_primitives::types::transactions::RpcTransactionError::RequestRouted {
                     transaction_hash: request_data.signed_transaction.get_hash(),
+                    routing: None,
                 })
             }
             network_client_responses => Err(
@@ -904,6 +1027,124 @@ impl JsonRpcHandler {
         }
     }
 
+    /// Lists peers which are currently banned together with the reason and expiry of the ban.
+    pub async fn banned_peers(
+        &self,
+    ) -> Result<Option<RpcBannedPeersResponse>, RpcNetworkInfoError> {
+        if self.enable_debug_rpc {
+            Ok(Some(self.client_addr.send(GetNetworkInfo {}).await??.into()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Lists the most recent routed messages which were dropped because their TTL has expired.
+    pub async fn dropped_routed_messages(
+        &self,
+    ) -> Result<Option<RpcDroppedRoutedMessagesResponse>, RpcNetworkInfoError> {
+        if self.enable_debug_rpc {
+            Ok(Some(self.client_addr.send(GetNetworkInfo {}).await??.into()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Bans or unbans a peer on request of the node operator.
+    pub async fn manage_peer_ban(
+        &self,
+        request: ManagePeerBan,
+    ) -> Result<Option<()>, RpcNetworkInfoError> {
+        if self.enable_debug_rpc {
+            self.client_addr.send(request).await?;
+            Ok(Some(()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Creates a snapshot of the node database while the node keeps running.
+    pub async fn make_snapshot(
+        &self,
+        request: MakeSnapshot,
+    ) -> Result<Option<Result<SnapshotInfo, String>>, actix::MailboxError> {
+        if self.enable_debug_rpc {
+            Ok(Some(self.view_client_addr.send(request).await?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Lists the nodes and values of the shard trie caches with the most hits.
+    pub async fn hottest_trie_nodes(
+        &self,
+        limit: usize,
+    ) -> Result<Option<Vec<CachedTrieNode>>, actix::MailboxError> {
+        if self.enable_debug_rpc {
+            Ok(Some(self.view_client_addr.send(GetHottestTrieNodes { limit }).await?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub async fn set_max_background_jobs(
+        &self,
+        request: SetMaxBackgroundJobs,
+    ) -> Result<Option<Result<(), String>>, actix::MailboxError> {
+        if self.enable_debug_rpc {
+            Ok(Some(self.view_client_addr.send(request).await?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Samples the compaction and write stall state of the database.
+    pub async fn store_properties(
+        &self,
+    ) -> Result<Option<Option<StoreProperties>>, actix::MailboxError> {
+        if self.enable_debug_rpc {
+            Ok(Some(self.view_client_addr.send(GetStoreProperties {}).await?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Lists the contracts which took the most time to execute during the current epoch.
+    pub async fn top_contracts(
+        &self,
+        limit: usize,
+    ) -> Result<Option<Option<TopContractsView>>, actix::MailboxError> {
+        if self.enable_debug_rpc {
+            Ok(Some(self.view_client_addr.send(GetTopContracts { limit }).await?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns the parameters the chunks of the block were applied with, by shard id.
+    pub async fn applied_chunk_info(
+        &self,
+        block_hash: CryptoHash,
+    ) -> Result<Option<Result<HashMap<ShardId, AppliedChunkInfo>, String>>, actix::MailboxError>
+    {
+        if self.enable_debug_rpc {
+            Ok(Some(self.view_client_addr.send(GetAppliedChunkInfo { block_hash }).await?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Lists the transactions the node forwarded most recently, with their targets.
+    pub async fn forwarded_transactions(
+        &self,
+        limit: usize,
+    ) -> Result<Option<Vec<ForwardedTransactionView>>, actix::MailboxError> {
+        if self.enable_debug_rpc {
+            Ok(Some(self.client_addr.send(GetForwardedTransactions { limit }).await?))
+        } else {
+            Ok(None)
+        }
+    }
+
     /// Expose Genesis Config (with internal Runtime Config) without state records to keep the
     /// output at a reasonable size.
     ///
@@ -960,6 +1201,20 @@ impl JsonRpcHandler {
         Ok(near_jsonrpc_primitives::types::blocks::RpcBlockResponse { block_view })
     }
 
+    async fn block_header_only(
+        &self,
+        request_data: near_jsonrpc_primitives::types::blocks::RpcBlockRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::blocks::RpcBlockHeaderOnlyResponse,
+        near_jsonrpc_primitives::types::blocks::RpcBlockError,
+    > {
+        let block_view = self
+            .view_client_addr
+            .send(GetBlockHeaderOnly(request_data.block_reference.into()))
+            .await??;
+        Ok(near_jsonrpc_primitives::types::blocks::RpcBlockHeaderOnlyResponse { block_view })
+    }
+
     async fn chunk(
         &self,
         request_data: near_jsonrpc_primitives::types::chunks::RpcChunkRequest,
@@ -1082,6 +1337,26 @@ impl JsonRpcHandler {
         })
     }
 
+    async fn block_proof(
+        &self,
+        request: near_jsonrpc_primitives::types::light_client::RpcLightClientBlockProofRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::light_client::RpcLightClientBlockProofResponse,
+        near_jsonrpc_primitives::types::light_client::RpcLightClientProofError,
+    > {
+        let block_proof = self
+            .view_client_addr
+            .send(GetBlockProof {
+                block_hash: request.block_hash,
+                head_block_hash: request.light_client_head,
+            })
+            .await??;
+        Ok(near_jsonrpc_primitives::types::light_client::RpcLightClientBlockProofResponse {
+            block_header_lite: block_proof.block_header_lite,
+            block_proof: block_proof.proof,
+        })
+    }
+
     async fn network_info(
         &self,
     ) -> Result<
@@ -1091,6 +1366,22 @@ impl JsonRpcHandler {
         Ok(self.client_addr.send(GetNetworkInfo {}).await??.into())
     }
 
+    /// Returns the votes for the next protocol version, the next protocol upgrade and the
+    /// protocol versions this node supports.
+    async fn protocol_upgrade_status(
+        &self,
+    ) -> Result<
+        near_jsonrpc_primitives::types::config::RpcProtocolUpgradeStatusResponse,
+        near_jsonrpc_primitives::types::config::RpcProtocolUpgradeStatusError,
+    > {
+        let status = self.view_client_addr.send(GetProtocolUpgradeStatus {}).await??;
+        Ok(near_jsonrpc_primitives::types::config::RpcProtocolUpgradeStatusResponse {
+            status,
+            min_supported_protocol_version: PEER_MIN_ALLOWED_PROTOCOL_VERSION,
+            max_supported_protocol_version: PROTOCOL_VERSION,
+        })
+    }
+
     async fn gas_price(
         &self,
         request_data: near_jsonrpc_primitives::types::gas_price::RpcGasPriceRequest,
@@ -1117,6 +1408,57 @@ impl JsonRpcHandler {
         Ok(near_jsonrpc_primitives::types::validator::RpcValidatorResponse { validator_info })
     }
 
+    async fn epoch_info(
+        &self,
+        request_data: near_jsonrpc_primitives::types::epoch::RpcEpochInfoRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::epoch::RpcEpochInfoResponse,
+        near_jsonrpc_primitives::types::epoch::RpcEpochInfoError,
+    > {
+        let message = match request_data {
+            near_jsonrpc_primitives::types::epoch::RpcEpochInfoRequest::EpochId(epoch_id) => {
+                GetEpochInfo::EpochId(epoch_id)
+            }
+            near_jsonrpc_primitives::types::epoch::RpcEpochInfoRequest::EpochHeight(
+                epoch_height,
+            ) => GetEpochInfo::EpochHeight(epoch_height),
+        };
+        let epoch_info = self.view_client_addr.send(message).await??;
+        Ok(near_jsonrpc_primitives::types::epoch::RpcEpochInfoResponse { epoch_info })
+    }
+
+    /// Returns block and chunk production of a validator at each of its assignments so far in the
+    /// current epoch and in the previous epoch.
+    async fn validator_chunk_stats(
+        &self,
+        request_data: near_jsonrpc_primitives::types::validator::RpcValidatorChunkStatsRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::validator::RpcValidatorChunkStatsResponse,
+        near_jsonrpc_primitives::types::validator::RpcValidatorChunkStatsError,
+    > {
+        let stats = self
+            .view_client_addr
+            .send(GetValidatorProductionStats { account_id: request_data.account_id })
+            .await??;
+        Ok(near_jsonrpc_primitives::types::validator::RpcValidatorChunkStatsResponse { stats })
+    }
+
+    /// Returns the stake locked by an account and when the part of it being unstaked is
+    /// expected to be returned.
+    async fn unstake_schedule(
+        &self,
+        request_data: near_jsonrpc_primitives::types::validator::RpcUnstakeScheduleRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::validator::RpcUnstakeScheduleResponse,
+        near_jsonrpc_primitives::types::validator::RpcUnstakeScheduleError,
+    > {
+        let schedule = self
+            .view_client_addr
+            .send(GetUnstakeSchedule { account_id: request_data.account_id })
+            .await??;
+        Ok(near_jsonrpc_primitives::types::validator::RpcUnstakeScheduleResponse { schedule })
+    }
+
     /// Returns the current epoch validators ordered in the block producer order with repetition.
     /// This endpoint is solely used for bridge currently and is not intended for other external use
     /// cases.
@@ -1142,6 +1484,47 @@ impl JsonRpcHandler {
         near_jsonrpc_primitives::types::sandbox::RpcSandboxPatchStateResponse,
         near_jsonrpc_primitives::types::sandbox::RpcSandboxPatchStateError,
     > {
+        use near_jsonrpc_primitives::types::sandbox::RpcSandboxPatchStateError;
+        use near_primitives::state_record::StateRecord;
+
+        // Patches are applied with the next block, reject the ones which can't be applied while
+        // the caller can still be told why.
+        let limit_config = self
+            .view_client_addr
+            .send(GetProtocolConfig(near_primitives::types::BlockReference::Finality(
+                near_primitives::types::Finality::None,
+            )))
+            .await?
+            .map_err(|err| RpcSandboxPatchStateError::InternalError {
+                error_message: err.to_string(),
+            })?
+            .runtime_config
+            .wasm_config
+            .limit_config;
+        for record in &patch_state_request.records {
+            if let StateRecord::Data { account_id, data_key, value } = record {
+                let error_message = if data_key.len() as u64 > limit_config.max_length_storage_key {
+                    format!(
+                        "data key of {} bytes exceeds the limit of {} bytes",
+                        data_key.len(),
+                        limit_config.max_length_storage_key
+                    )
+                } else if value.len() as u64 > limit_config.max_length_storage_value {
+                    format!(
+                        "data value of {} bytes exceeds the limit of {} bytes",
+                        value.len(),
+                        limit_config.max_length_storage_value
+                    )
+                } else {
+                    continue;
+                };
+                return Err(RpcSandboxPatchStateError::InvalidRecord {
+                    account_id: account_id.clone(),
+                    error_message,
+                });
+            }
+        }
+
         self.client_addr
             .send(NetworkClientMessages::Sandbox(
                 near_network_primitives::types::NetworkSandboxMessage::SandboxPatchState(
@@ -1173,6 +1556,54 @@ impl JsonRpcHandler {
         Ok(near_jsonrpc_primitives::types::sandbox::RpcSandboxPatchStateResponse {})
     }
 
+    /// Patches access keys into the state of the next block, failing if any of the accounts
+    /// doesn't exist.
+    async fn sandbox_patch_access_keys(
+        &self,
+        patch_access_keys_request: near_jsonrpc_primitives::types::sandbox::RpcSandboxPatchAccessKeysRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxPatchAccessKeysResponse,
+        near_jsonrpc_primitives::types::sandbox::RpcSandboxPatchAccessKeysError,
+    > {
+        use near_jsonrpc_primitives::types::sandbox::RpcSandboxPatchAccessKeysError;
+
+        let mut records = Vec::with_capacity(patch_access_keys_request.access_keys.len());
+        for patch in patch_access_keys_request.access_keys {
+            let query = Query::new(
+                near_primitives::types::BlockReference::Finality(
+                    near_primitives::types::Finality::None,
+                ),
+                near_primitives::views::QueryRequest::ViewAccount {
+                    account_id: patch.account_id.clone(),
+                },
+            );
+            match self.view_client_addr.send(query).await? {
+                Ok(_) => {}
+                Err(near_client::QueryError::UnknownAccount { requested_account_id, .. }) => {
+                    return Err(RpcSandboxPatchAccessKeysError::AccountDoesNotExist {
+                        account_id: requested_account_id,
+                    })
+                }
+                Err(err) => {
+                    return Err(RpcSandboxPatchAccessKeysError::InternalError {
+                        error_message: err.to_string(),
+                    })
+                }
+            }
+            records.push(near_primitives::state_record::StateRecord::AccessKey {
+                account_id: patch.account_id,
+                public_key: patch.public_key,
+                access_key: patch.access_key,
+            });
+        }
+
+        self.sandbox_patch_state(
+            near_jsonrpc_primitives::types::sandbox::RpcSandboxPatchStateRequest { records },
+        )
+        .await?;
+        Ok(near_jsonrpc_primitives::types::sandbox::RpcSandboxPatchAccessKeysResponse {})
+    }
+
     async fn sandbox_fast_forward(
         &self,
         fast_forward_request: near_jsonrpc_primitives::types::sandbox::RpcSandboxFastForwardRequest,
@@ -1313,6 +1744,73 @@ impl JsonRpcHandler {
     }
 }
 
+#[cfg(feature = "adversarial")]
+impl JsonRpcHandler {
+    async fn adv_produce_private_fork(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let (num_blocks,) = parse_params::<(u64,)>(params)?;
+        self.client_addr
+            .send(NetworkClientMessages::Adversarial(
+                near_network_primitives::types::NetworkAdversarialMessage::AdvProducePrivateFork(
+                    num_blocks,
+                ),
+            ))
+            .await?;
+        Ok(Value::String("".to_string()))
+    }
+
+    async fn adv_release_private_fork(&self, _params: Option<Value>) -> Result<Value, RpcError> {
+        match self
+            .client_addr
+            .send(NetworkClientMessages::Adversarial(
+                near_network_primitives::types::NetworkAdversarialMessage::AdvReleasePrivateFork,
+            ))
+            .await?
+        {
+            NetworkClientResponses::AdvResult(value) => jsonify(Ok(Ok(value))),
+            _ => Err(RpcError::server_error::<String>(None)),
+        }
+    }
+
+    async fn adv_double_sign(&self, _params: Option<Value>) -> Result<Value, RpcError> {
+        actix::spawn(
+            self.client_addr
+                .send(NetworkClientMessages::Adversarial(
+                    near_network_primitives::types::NetworkAdversarialMessage::AdvDoubleSign,
+                ))
+                .map(|_| ()),
+        );
+        Ok(Value::String("".to_string()))
+    }
+
+    async fn adv_produce_invalid_state_root_chunk(
+        &self,
+        _params: Option<Value>,
+    ) -> Result<Value, RpcError> {
+        actix::spawn(
+            self.client_addr
+                .send(NetworkClientMessages::Adversarial(
+                    near_network_primitives::types::NetworkAdversarialMessage::AdvProduceInvalidStateRootChunk,
+                ))
+                .map(|_| ()),
+        );
+        Ok(Value::String("".to_string()))
+    }
+
+    async fn adv_disable_doomslug_timeouts(
+        &self,
+        _params: Option<Value>,
+    ) -> Result<Value, RpcError> {
+        actix::spawn(
+            self.client_addr
+                .send(NetworkClientMessages::Adversarial(
+                    near_network_primitives::types::NetworkAdversarialMessage::AdvDisableDoomslugTimeouts,
+                ))
+                .map(|_| ()),
+        );
+        Ok(Value::String("".to_string()))
+    }
+}
+
 fn rpc_handler(
     message: web::Json<Message>,
     handler: web::Data<JsonRpcHandler>,
@@ -1383,6 +1881,140 @@ pub async fn prometheus_handler() -> Result<HttpResponse, HttpError> {
     }
 }
 
+async fn banned_peers_handler(
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    match handler.banned_peers().await {
+        Ok(Some(value)) => Ok(HttpResponse::Ok().json(&value)),
+        Ok(None) => Ok(HttpResponse::MethodNotAllowed().finish()),
+        Err(_) => Ok(HttpResponse::ServiceUnavailable().finish()),
+    }
+}
+
+async fn dropped_routed_messages_handler(
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    match handler.dropped_routed_messages().await {
+        Ok(Some(value)) => Ok(HttpResponse::Ok().json(&value)),
+        Ok(None) => Ok(HttpResponse::MethodNotAllowed().finish()),
+        Err(_) => Ok(HttpResponse::ServiceUnavailable().finish()),
+    }
+}
+
+async fn manage_peer_ban_handler(
+    handler: web::Data<JsonRpcHandler>,
+    request: ManagePeerBan,
+) -> Result<HttpResponse, HttpError> {
+    match handler.manage_peer_ban(request).await {
+        Ok(Some(())) => Ok(HttpResponse::Ok().finish()),
+        Ok(None) => Ok(HttpResponse::MethodNotAllowed().finish()),
+        Err(_) => Ok(HttpResponse::ServiceUnavailable().finish()),
+    }
+}
+
+async fn ban_peer_handler(
+    handler: web::Data<JsonRpcHandler>,
+    request: web::Json<RpcBanPeerRequest>,
+) -> Result<HttpResponse, HttpError> {
+    let RpcBanPeerRequest { peer_id, ban_duration_secs } = request.into_inner();
+    let ban_duration = ban_duration_secs.map(Duration::from_secs);
+    manage_peer_ban_handler(handler, ManagePeerBan::Ban { peer_id, ban_duration }).await
+}
+
+async fn unban_peer_handler(
+    handler: web::Data<JsonRpcHandler>,
+    request: web::Json<RpcUnbanPeerRequest>,
+) -> Result<HttpResponse, HttpError> {
+    let RpcUnbanPeerRequest { peer_id } = request.into_inner();
+    manage_peer_ban_handler(handler, ManagePeerBan::Unban { peer_id }).await
+}
+
+async fn make_snapshot_handler(
+    handler: web::Data<JsonRpcHandler>,
+    request: web::Json<RpcMakeSnapshotRequest>,
+) -> Result<HttpResponse, HttpError> {
+    let RpcMakeSnapshotRequest { destination } = request.into_inner();
+    match handler.make_snapshot(MakeSnapshot { destination }).await {
+        Ok(Some(Ok(info))) => Ok(HttpResponse::Ok().json(&info)),
+        Ok(Some(Err(err))) => Ok(HttpResponse::InternalServerError().body(err)),
+        Ok(None) => Ok(HttpResponse::MethodNotAllowed().finish()),
+        Err(_) => Ok(HttpResponse::ServiceUnavailable().finish()),
+    }
+}
+
+async fn hottest_trie_nodes_handler(
+    handler: web::Data<JsonRpcHandler>,
+    request: web::Query<RpcHottestTrieNodesRequest>,
+) -> Result<HttpResponse, HttpError> {
+    let limit = request.into_inner().limit.unwrap_or(DEFAULT_HOTTEST_TRIE_NODES);
+    match handler.hottest_trie_nodes(limit).await {
+        Ok(Some(nodes)) => Ok(HttpResponse::Ok().json(&nodes)),
+        Ok(None) => Ok(HttpResponse::MethodNotAllowed().finish()),
+        Err(_) => Ok(HttpResponse::ServiceUnavailable().finish()),
+    }
+}
+
+async fn set_max_background_jobs_handler(
+    handler: web::Data<JsonRpcHandler>,
+    request: web::Json<RpcSetMaxBackgroundJobsRequest>,
+) -> Result<HttpResponse, HttpError> {
+    let RpcSetMaxBackgroundJobsRequest { max_background_jobs } = request.into_inner();
+    match handler.set_max_background_jobs(SetMaxBackgroundJobs { jobs: max_background_jobs }).await
+    {
+        Ok(Some(Ok(()))) => Ok(HttpResponse::Ok().finish()),
+        Ok(Some(Err(err))) => Ok(HttpResponse::InternalServerError().body(err)),
+        Ok(None) => Ok(HttpResponse::MethodNotAllowed().finish()),
+        Err(_) => Ok(HttpResponse::ServiceUnavailable().finish()),
+    }
+}
+
+async fn store_properties_handler(
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    match handler.store_properties().await {
+        Ok(Some(properties)) => Ok(HttpResponse::Ok().json(&properties)),
+        Ok(None) => Ok(HttpResponse::MethodNotAllowed().finish()),
+        Err(_) => Ok(HttpResponse::ServiceUnavailable().finish()),
+    }
+}
+
+async fn top_contracts_handler(
+    handler: web::Data<JsonRpcHandler>,
+    request: web::Query<RpcTopContractsRequest>,
+) -> Result<HttpResponse, HttpError> {
+    let limit = request.into_inner().limit.unwrap_or(DEFAULT_TOP_CONTRACTS);
+    match handler.top_contracts(limit).await {
+        Ok(Some(contracts)) => Ok(HttpResponse::Ok().json(&contracts)),
+        Ok(None) => Ok(HttpResponse::MethodNotAllowed().finish()),
+        Err(_) => Ok(HttpResponse::ServiceUnavailable().finish()),
+    }
+}
+
+async fn forwarded_transactions_handler(
+    handler: web::Data<JsonRpcHandler>,
+    request: web::Query<RpcForwardedTransactionsRequest>,
+) -> Result<HttpResponse, HttpError> {
+    let limit = request.into_inner().limit.unwrap_or(DEFAULT_FORWARDED_TRANSACTIONS);
+    match handler.forwarded_transactions(limit).await {
+        Ok(Some(transactions)) => Ok(HttpResponse::Ok().json(&transactions)),
+        Ok(None) => Ok(HttpResponse::MethodNotAllowed().finish()),
+        Err(_) => Ok(HttpResponse::ServiceUnavailable().finish()),
+    }
+}
+
+async fn applied_chunk_info_handler(
+    handler: web::Data<JsonRpcHandler>,
+    request: web::Query<RpcAppliedChunkInfoRequest>,
+) -> Result<HttpResponse, HttpError> {
+    let RpcAppliedChunkInfoRequest { block_hash } = request.into_inner();
+    match handler.applied_chunk_info(block_hash).await {
+        Ok(Some(Ok(infos))) => Ok(HttpResponse::Ok().json(&infos)),
+        Ok(Some(Err(err))) => Ok(HttpResponse::BadRequest().body(err)),
+        Ok(None) => Ok(HttpResponse::MethodNotAllowed().finish()),
+        Err(_) => Ok(HttpResponse::ServiceUnavailable().finish()),
+    }
+}
+
 async fn sync_info_handler(handler: web::Data<JsonRpcHandler>) -> Result<HttpResponse, HttpError> {
     match handler.debug().await {
         Ok(value) => Ok(HttpResponse::Ok().json(&value)),
@@ -1450,6 +2082,7 @@ pub fn start_http(
         polling_config,
         limits_config,
         enable_debug_rpc,
+        subscriptions_config,
     } = config;
     let prometheus_addr = prometheus_addr.filter(|it| it != &addr);
     let cors_allowed_origins_clone = cors_allowed_origins.clone();
@@ -1464,6 +2097,7 @@ pub fn start_http(
                 polling_config,
                 genesis_config: genesis_config.clone(),
                 enable_debug_rpc,
+                subscriptions_config: subscriptions_config.clone(),
                 #[cfg(feature = "test_features")]
                 peer_manager_addr: peer_manager_addr.clone(),
                 #[cfg(feature = "test_features")]
@@ -1472,6 +2106,7 @@ pub fn start_http(
             .app_data(web::JsonConfig::default().limit(limits_config.json_payload_max_size))
             .wrap(middleware::Logger::default())
             .service(web::resource("/").route(web::post().to(rpc_handler)))
+            .service(web::resource("/ws").route(web::get().to(subscriptions::ws_handler)))
             .service(
                 web::resource("/status")
                     .route(web::get().to(status_handler))
@@ -1489,6 +2124,44 @@ pub fn start_http(
             .service(last_blocks_html)
             .service(web::resource("/debug/api/sync_info").route(web::get().to(sync_info_handler)))
             .service(sync_info_html)
+            .service(
+                web::resource("/debug/api/banned_peers").route(web::get().to(banned_peers_handler)),
+            )
+            .service(
+                web::resource("/debug/api/dropped_routed_messages")
+                    .route(web::get().to(dropped_routed_messages_handler)),
+            )
+            .service(web::resource("/debug/api/ban_peer").route(web::post().to(ban_peer_handler)))
+            .service(
+                web::resource("/debug/api/make_snapshot")
+                    .route(web::post().to(make_snapshot_handler)),
+            )
+            .service(
+                web::resource("/debug/api/unban_peer").route(web::post().to(unban_peer_handler)),
+            )
+            .service(
+                web::resource("/debug/api/hottest_trie_nodes")
+                    .route(web::get().to(hottest_trie_nodes_handler)),
+            )
+            .service(
+                web::resource("/debug/api/store").route(web::get().to(store_properties_handler)),
+            )
+            .service(
+                web::resource("/debug/api/set_max_background_jobs")
+                    .route(web::post().to(set_max_background_jobs_handler)),
+            )
+            .service(
+                web::resource("/debug/api/top_contracts")
+                    .route(web::get().to(top_contracts_handler)),
+            )
+            .service(
+                web::resource("/debug/api/forwarded_transactions")
+                    .route(web::get().to(forwarded_transactions_handler)),
+            )
+            .service(
+                web::resource("/debug/api/applied_chunk_info")
+                    .route(web::get().to(applied_chunk_info_handler)),
+            )
     })
     .bind(addr)
     .unwrap()
@@ -0,0 +1,373 @@
+//! WebSocket endpoint with subscriptions to new final blocks, transaction statuses and state
+//! changes.
+//!
+//! Clients send JSON-RPC `subscribe` requests with an [`RpcSubscriptionRequest`] as params, and
+//! `unsubscribe` requests with the id `subscribe` returned.  As blocks become final, updates are
+//! pushed as `subscription` notifications whose results have the format of the responses of the
+//! corresponding polling methods.
+//!
+//! At most `max_pending_messages` messages are buffered for a connection.  A connection which
+//! falls further behind is closed with a close frame telling that it was too slow.
+
+use std::collections::BTreeMap;
+
+use actix_codec::{Decoder, Encoder};
+use actix_http::ws;
+use actix_web::{web, Error as HttpError, HttpRequest, HttpResponse};
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::{broadcast, mpsc, oneshot};
+
+use near_client::{SubscribeFinalBlocks, TxStatus, TxStatusError};
+use near_jsonrpc_primitives::errors::RpcError;
+use near_jsonrpc_primitives::message::{self, Message};
+use near_jsonrpc_primitives::types::subscriptions::{
+    RpcSubscribeResponse, RpcSubscriptionNotification, RpcSubscriptionRequest,
+    RpcSubscriptionResult, RpcUnsubscribeRequest,
+};
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{BlockId, BlockReference};
+use near_primitives::views::{FinalExecutionOutcomeViewEnum, FinalExecutionStatus};
+
+use crate::JsonRpcHandler;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RpcSubscriptionsConfig {
+    /// Maximum number of subscriptions of a WebSocket connection.
+    pub max_subscriptions_per_connection: usize,
+    /// Maximum number of messages buffered for a WebSocket connection.
+    pub max_pending_messages: usize,
+}
+
+impl Default for RpcSubscriptionsConfig {
+    fn default() -> Self {
+        Self { max_subscriptions_per_connection: 16, max_pending_messages: 1024 }
+    }
+}
+
+pub(crate) async fn ws_handler(
+    request: HttpRequest,
+    payload: web::Payload,
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    let mut response = ws::handshake(request.head())?;
+    let final_blocks = handler
+        .client_addr
+        .send(SubscribeFinalBlocks)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let (outgoing, outgoing_rx) =
+        mpsc::channel(handler.subscriptions_config.max_pending_messages.max(1));
+    let (close, close_rx) = oneshot::channel();
+    let connection = Connection {
+        handler,
+        subscriptions: BTreeMap::new(),
+        next_subscription_id: 0,
+        outgoing,
+        close: Some(close),
+    };
+    actix::spawn(connection.run(incoming_frames(payload), final_blocks));
+    Ok(HttpResponse::from(response.streaming(Box::pin(outgoing_frames(outgoing_rx, close_rx)))))
+}
+
+/// Decodes the frames sent by the client.
+fn incoming_frames(
+    payload: web::Payload,
+) -> impl Stream<Item = Result<ws::Frame, ws::ProtocolError>> {
+    futures::stream::unfold(
+        (payload, ws::Codec::new(), BytesMut::new()),
+        |(mut payload, mut codec, mut buf)| async move {
+            loop {
+                match codec.decode(&mut buf) {
+                    Ok(Some(frame)) => return Some((Ok(frame), (payload, codec, buf))),
+                    Ok(None) => {}
+                    Err(err) => return Some((Err(err), (payload, codec, buf))),
+                }
+                match payload.next().await {
+                    Some(Ok(bytes)) => buf.extend_from_slice(&bytes),
+                    _ => return None,
+                }
+            }
+        },
+    )
+}
+
+/// Encodes the messages for the client, ending with a close frame once `close` fires or the
+/// connection is dropped.
+fn outgoing_frames(
+    messages: mpsc::Receiver<ws::Message>,
+    close: oneshot::Receiver<ws::CloseReason>,
+) -> impl Stream<Item = Result<Bytes, HttpError>> {
+    futures::stream::unfold(Some((messages, close, ws::Codec::new())), |state| async move {
+        let (mut messages, mut close, mut codec) = state?;
+        let (message, last) = tokio::select! {
+            biased;
+            reason = &mut close => (ws::Message::Close(reason.ok()), true),
+            message = messages.recv() => match message {
+                Some(message) => (message, false),
+                None => (ws::Message::Close(None), true),
+            },
+        };
+        let mut buf = BytesMut::new();
+        let frame = codec.encode(message, &mut buf).map(|()| buf.freeze()).map_err(Into::into);
+        Some((frame, if last { None } else { Some((messages, close, codec)) }))
+    })
+}
+
+enum Subscription {
+    FinalBlocks,
+    TxStatus {
+        tx_hash: CryptoHash,
+        sender_account_id: near_primitives::types::AccountId,
+        /// The last status sent, which is sent again only once it changes.
+        last_status: Option<Value>,
+    },
+    StateChanges(near_primitives::views::StateChangesRequestView),
+}
+
+/// What to send to a subscriber after a new final block.
+enum Update {
+    Result(Value),
+    /// The last result of the subscription, which is removed after it.
+    Last(Value),
+    Error(RpcError),
+}
+
+enum Disconnect {
+    /// The client doesn't keep up with the messages.
+    TooSlow,
+    /// The client is gone.
+    Gone,
+}
+
+struct Connection {
+    handler: web::Data<JsonRpcHandler>,
+    subscriptions: BTreeMap<u64, Subscription>,
+    next_subscription_id: u64,
+    outgoing: mpsc::Sender<ws::Message>,
+    close: Option<oneshot::Sender<ws::CloseReason>>,
+}
+
+impl Connection {
+    async fn run(
+        mut self,
+        incoming: impl Stream<Item = Result<ws::Frame, ws::ProtocolError>>,
+        mut final_blocks: broadcast::Receiver<CryptoHash>,
+    ) {
+        futures::pin_mut!(incoming);
+        let result = loop {
+            let result = tokio::select! {
+                frame = incoming.next() => match frame {
+                    Some(Ok(ws::Frame::Text(text))) => self.on_request(&text),
+                    Some(Ok(ws::Frame::Ping(message))) => self.send(ws::Message::Pong(message)),
+                    Some(Ok(ws::Frame::Close(_))) | Some(Err(_)) | None => break Ok(()),
+                    Some(Ok(_)) => Ok(()),
+                },
+                block_hash = final_blocks.recv() => match block_hash {
+                    Ok(block_hash) => self.on_final_block(block_hash).await,
+                    Err(broadcast::error::RecvError::Lagged(_)) => Err(Disconnect::TooSlow),
+                    Err(broadcast::error::RecvError::Closed) => break Ok(()),
+                },
+            };
+            if result.is_err() {
+                break result;
+            }
+        };
+        if let (Err(Disconnect::TooSlow), Some(close)) = (result, self.close.take()) {
+            let _ = close.send(ws::CloseReason {
+                code: ws::CloseCode::Policy,
+                description: Some("subscriber is too slow".to_owned()),
+            });
+        }
+    }
+
+    fn on_request(&mut self, text: &[u8]) -> Result<(), Disconnect> {
+        let reply = match message::from_slice(text) {
+            Ok(Message::Request(request)) => {
+                let result = self.process_request(&request.method, request.params.clone());
+                Message::response(request.id, result)
+            }
+            Ok(_) => Message::error(RpcError::parse_error(
+                "JSON RPC Request format was expected".to_owned(),
+            )),
+            Err(broken) => broken.reply(),
+        };
+        self.send_message(reply)
+    }
+
+    fn process_request(&mut self, method: &str, params: Option<Value>) -> Result<Value, RpcError> {
+        match method {
+            "subscribe" => {
+                let request = RpcSubscriptionRequest::parse(params)?;
+                let max_subscriptions =
+                    self.handler.subscriptions_config.max_subscriptions_per_connection;
+                if self.subscriptions.len() >= max_subscriptions {
+                    return Err(RpcError::invalid_params(format!(
+                        "at most {} subscriptions are allowed per connection",
+                        max_subscriptions
+                    )));
+                }
+                let subscription = match request {
+                    RpcSubscriptionRequest::FinalBlocks => Subscription::FinalBlocks,
+                    RpcSubscriptionRequest::TxStatus { tx_hash, sender_account_id } => {
+                        Subscription::TxStatus { tx_hash, sender_account_id, last_status: None }
+                    }
+                    RpcSubscriptionRequest::StateChanges { state_changes_request } => {
+                        Subscription::StateChanges(state_changes_request)
+                    }
+                };
+                let subscription_id = self.next_subscription_id;
+                self.next_subscription_id += 1;
+                self.subscriptions.insert(subscription_id, subscription);
+                serde_json::to_value(RpcSubscribeResponse { subscription_id })
+                    .map_err(|err| RpcError::serialization_error(err.to_string()))
+            }
+            "unsubscribe" => {
+                let RpcUnsubscribeRequest { subscription_id } =
+                    RpcUnsubscribeRequest::parse(params)?;
+                match self.subscriptions.remove(&subscription_id) {
+                    Some(_) => Ok(Value::Null),
+                    None => Err(RpcError::invalid_params(format!(
+                        "unknown subscription {}",
+                        subscription_id
+                    ))),
+                }
+            }
+            _ => Err(RpcError::method_not_found(method.to_owned())),
+        }
+    }
+
+    async fn on_final_block(&mut self, block_hash: CryptoHash) -> Result<(), Disconnect> {
+        let subscription_ids: Vec<u64> = self.subscriptions.keys().copied().collect();
+        for subscription_id in subscription_ids {
+            let update = match self.subscriptions.get_mut(&subscription_id) {
+                Some(subscription) => match update(&self.handler, subscription, block_hash).await {
+                    Some(update) => update,
+                    None => continue,
+                },
+                None => continue,
+            };
+            let result = match update {
+                Update::Result(result) => RpcSubscriptionResult::Result(result),
+                Update::Last(result) => {
+                    self.subscriptions.remove(&subscription_id);
+                    RpcSubscriptionResult::Result(result)
+                }
+                Update::Error(error) => {
+                    self.subscriptions.remove(&subscription_id);
+                    RpcSubscriptionResult::Error(error)
+                }
+            };
+            let params =
+                serde_json::to_value(RpcSubscriptionNotification { subscription_id, result })
+                    .expect("subscription notifications are serializable");
+            self.send_message(Message::notification("subscription".to_owned(), Some(params)))?;
+        }
+        Ok(())
+    }
+
+    fn send_message(&self, message: Message) -> Result<(), Disconnect> {
+        let text: String = message.into();
+        self.send(ws::Message::Text(text.into()))
+    }
+
+    fn send(&self, message: ws::Message) -> Result<(), Disconnect> {
+        self.outgoing.try_send(message).map_err(|err| match err {
+            mpsc::error::TrySendError::Full(_) => Disconnect::TooSlow,
+            mpsc::error::TrySendError::Closed(_) => Disconnect::Gone,
+        })
+    }
+}
+
+/// Returns what to send to the subscriber after `block_hash` became final, if anything.
+async fn update(
+    handler: &JsonRpcHandler,
+    subscription: &mut Subscription,
+    block_hash: CryptoHash,
+) -> Option<Update> {
+    let block_reference = BlockReference::BlockId(BlockId::Hash(block_hash));
+    match subscription {
+        Subscription::FinalBlocks => {
+            let request = near_jsonrpc_primitives::types::blocks::RpcBlockRequest {
+                block_reference,
+                header_only: true,
+            };
+            Some(match handler.block_header_only(request).await {
+                Ok(response) => to_update(&response, Update::Result),
+                Err(err) => Update::Error(err.into()),
+            })
+        }
+        Subscription::TxStatus { tx_hash, sender_account_id, last_status } => {
+            let tx_status = TxStatus {
+                tx_hash: *tx_hash,
+                signer_account_id: sender_account_id.clone(),
+                fetch_receipt: false,
+            };
+            let outcome = match handler.view_client_addr.send(tx_status).await {
+                Ok(Ok(Some(outcome))) => outcome,
+                // The transaction may not have been included in a block yet.
+                Ok(Ok(None)) | Ok(Err(TxStatusError::MissingTransaction(_))) => return None,
+                Ok(Err(err)) => {
+                    let err =
+                        near_jsonrpc_primitives::types::transactions::RpcTransactionError::from(
+                            err,
+                        );
+                    return Some(Update::Error(err.into()));
+                }
+                Err(err) => {
+                    let err =
+                        near_jsonrpc_primitives::types::transactions::RpcTransactionError::from(
+                            err,
+                        );
+                    return Some(Update::Error(err.into()));
+                }
+            };
+            let is_final = is_final(&outcome);
+            let response = near_jsonrpc_primitives::types::transactions::RpcTransactionResponse {
+                final_execution_outcome: outcome,
+            };
+            let status = match serde_json::to_value(&response) {
+                Ok(status) => status,
+                Err(err) => {
+                    return Some(Update::Error(RpcError::serialization_error(err.to_string())))
+                }
+            };
+            if last_status.as_ref() == Some(&status) {
+                return None;
+            }
+            *last_status = Some(status.clone());
+            Some(if is_final { Update::Last(status) } else { Update::Result(status) })
+        }
+        Subscription::StateChanges(state_changes_request) => {
+            let request =
+                near_jsonrpc_primitives::types::changes::RpcStateChangesInBlockByTypeRequest {
+                    block_reference,
+                    state_changes_request: state_changes_request.clone(),
+                };
+            match handler.changes_in_block_by_type(request).await {
+                Ok(response) if response.changes.is_empty() => None,
+                Ok(response) => Some(to_update(&response, Update::Result)),
+                Err(err) => Some(Update::Error(err.into())),
+            }
+        }
+    }
+}
+
+fn to_update(response: &impl Serialize, update: fn(Value) -> Update) -> Update {
+    match serde_json::to_value(response) {
+        Ok(result) => update(result),
+        Err(err) => Update::Error(RpcError::serialization_error(err.to_string())),
+    }
+}
+
+fn is_final(outcome: &FinalExecutionOutcomeViewEnum) -> bool {
+    let status = match outcome {
+        FinalExecutionOutcomeViewEnum::FinalExecutionOutcome(outcome) => &outcome.status,
+        FinalExecutionOutcomeViewEnum::FinalExecutionOutcomeWithReceipt(outcome) => {
+            &outcome.final_outcome.status
+        }
+    };
+    matches!(status, FinalExecutionStatus::Failure(_) | FinalExecutionStatus::SuccessValue(_))
+}
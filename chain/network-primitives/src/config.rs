@@ -1,9 +1,11 @@
-use crate::network_protocol::PeerInfo;
+use crate::network_protocol::{PeerInfo, RoutedMessageBody};
 use crate::types::ROUTED_MESSAGE_TTL;
 use near_crypto::{KeyType, PublicKey, SecretKey};
 use near_primitives::types::AccountId;
+use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::time::Duration;
+use strum::VariantNames;
 
 /// Configuration for the peer-to-peer manager.
 #[derive(Clone)]
@@ -46,6 +48,9 @@ pub struct NetworkConfig {
     /// This is used to avoid infinite loop because of inconsistent view of the network
     /// by different nodes.
     pub routed_message_ttl: u8,
+    /// Initial TTL of routed messages by message type (e.g. `PartialEncodedChunkRequest`),
+    /// overriding `routed_message_ttl`.
+    pub routed_message_ttl_overrides: HashMap<String, u8>,
     /// Maximum number of routes that we should keep track for each Account id in the Routing Table.
     pub max_routes_to_store: usize,
     /// Height horizon for highest height peers
@@ -64,6 +69,64 @@ pub struct NetworkConfig {
     pub outbound_disabled: bool,
     /// Not clear old data, set `true` for archive nodes.
     pub archive: bool,
+    /// Configuration of the TIER1 network: direct connections between block and chunk producers.
+    pub tier1: Tier1,
+    /// Limits on state header/part and chunk part requests served to other peers.
+    pub request_throttle: RequestThrottle,
+}
+
+/// Configuration of the TIER1 overlay network.
+///
+/// Validators of the current and next epoch advertise how they can be reached (directly or via
+/// proxies) and connect to each other directly, so that latency-critical messages don't have to
+/// travel through arbitrary hops of the TIER2 (regular) network.
+#[derive(Clone, Debug, Default)]
+pub struct Tier1 {
+    /// Whether this node should try to establish TIER1 connections to other validators.
+    pub enable_outbound: bool,
+    /// Whether this node should accept TIER1 connections from other validators,
+    /// even if the regular connection limit is reached.
+    pub enable_inbound: bool,
+    /// Proxies through which this validator can be reached. If empty, the node's own
+    /// public address is advertised instead.
+    pub proxies: Vec<PeerInfo>,
+}
+
+/// Rate limits on expensive requests served to other peers.
+///
+/// A single syncing peer could otherwise request hundreds of state parts at once and saturate the
+/// disk of the serving node. Rates are in requests per second; a burst is the number of requests
+/// which can be made at once after a period of inactivity.
+#[derive(Clone, Debug)]
+pub struct RequestThrottle {
+    /// Rate and burst of state header/part requests accepted from a single peer.
+    pub state_requests_per_peer_rate: f64,
+    pub state_requests_per_peer_burst: u32,
+    /// Rate and burst of state header/part requests accepted from all peers together.
+    pub state_requests_global_rate: f64,
+    pub state_requests_global_burst: u32,
+    /// Maximum number of accepted state requests waiting to be processed.
+    pub state_requests_max_queued: usize,
+    /// Maximum number of state requests processed by the view client at the same time.
+    pub state_requests_max_in_flight: usize,
+    /// Rate and burst of partial encoded chunk requests accepted from a single peer.
+    pub chunk_part_requests_per_peer_rate: f64,
+    pub chunk_part_requests_per_peer_burst: u32,
+}
+
+impl Default for RequestThrottle {
+    fn default() -> Self {
+        Self {
+            state_requests_per_peer_rate: 5.0,
+            state_requests_per_peer_burst: 10,
+            state_requests_global_rate: 20.0,
+            state_requests_global_burst: 30,
+            state_requests_max_queued: 200,
+            state_requests_max_in_flight: 4,
+            chunk_part_requests_per_peer_rate: 200.0,
+            chunk_part_requests_per_peer_burst: 400,
+        }
+    }
 }
 
 impl NetworkConfig {
@@ -93,15 +156,26 @@ impl NetworkConfig {
             peer_stats_period: Duration::from_secs(5),
             ttl_account_id_router: Duration::from_secs(60 * 60),
             routed_message_ttl: ROUTED_MESSAGE_TTL,
+            routed_message_ttl_overrides: HashMap::new(),
             max_routes_to_store: 1,
             highest_peer_horizon: 5,
             push_info_period: Duration::from_millis(100),
             blacklist: vec![],
             outbound_disabled: false,
             archive: false,
+            tier1: Tier1 { enable_outbound: true, enable_inbound: true, proxies: vec![] },
+            request_throttle: RequestThrottle::default(),
         }
     }
 
+    /// Initial TTL of a routed message with the given body.
+    pub fn routed_message_ttl_for(&self, body: &RoutedMessageBody) -> u8 {
+        self.routed_message_ttl_overrides
+            .get(body.as_ref())
+            .copied()
+            .unwrap_or(self.routed_message_ttl)
+    }
+
     pub fn verify(&self) -> Result<(), anyhow::Error> {
         if !(self.ideal_connections_lo <= self.ideal_connections_hi) {
             anyhow::bail!(
@@ -136,6 +210,34 @@ impl NetworkConfig {
                 self.peer_recent_time_window.as_secs(), UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE.as_secs()
             );
         }
+
+        for (msg_type, ttl) in self.routed_message_ttl_overrides.iter() {
+            if !RoutedMessageBody::VARIANTS.contains(&msg_type.as_str()) {
+                anyhow::bail!(
+                    "Unknown routed message type {} in routed_message_ttl_overrides.",
+                    msg_type
+                );
+            }
+            if *ttl == 0 {
+                anyhow::bail!("routed_message_ttl_overrides for {} must be positive.", msg_type);
+            }
+        }
+
+        let throttle = &self.request_throttle;
+        if !(throttle.state_requests_per_peer_rate > 0.0
+            && throttle.state_requests_global_rate > 0.0
+            && throttle.chunk_part_requests_per_peer_rate > 0.0)
+        {
+            anyhow::bail!("request_throttle rates must be positive: {:?}.", throttle);
+        }
+        if throttle.state_requests_per_peer_burst == 0
+            || throttle.state_requests_global_burst == 0
+            || throttle.chunk_part_requests_per_peer_burst == 0
+            || throttle.state_requests_max_queued == 0
+            || throttle.state_requests_max_in_flight == 0
+        {
+            anyhow::bail!("request_throttle limits must be positive: {:?}.", throttle);
+        }
         Ok(())
     }
 }
@@ -173,5 +275,17 @@ mod test {
         nc.peer_recent_time_window = UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE;
         let res = nc.verify();
         assert!(res.is_err(), "{:?}", res);
+
+        let mut nc = NetworkConfig::from_seed("123", 213);
+        nc.routed_message_ttl_overrides.insert("PartialEncodedChunkRequest".to_string(), 150);
+        assert!(nc.verify().is_ok());
+        nc.routed_message_ttl_overrides.insert("NoSuchMessage".to_string(), 150);
+        let res = nc.verify();
+        assert!(res.is_err(), "{:?}", res);
+
+        let mut nc = NetworkConfig::from_seed("123", 213);
+        nc.request_throttle.state_requests_max_in_flight = 0;
+        let res = nc.verify();
+        assert!(res.is_err(), "{:?}", res);
     }
 }
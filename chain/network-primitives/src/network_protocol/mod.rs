@@ -4,7 +4,7 @@
 ///
 /// TODO: - document all types in this file
 use borsh::{BorshDeserialize, BorshSerialize};
-use near_crypto::Signature;
+use near_crypto::{SecretKey, Signature};
 use near_primitives::block::{Approval, GenesisId};
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::combine_hash;
@@ -15,7 +15,7 @@ use near_primitives::sharding::{
 };
 use near_primitives::syncing::{ShardStateSyncResponse, ShardStateSyncResponseV1};
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::{AccountId, BlockHeight, BlockReference, ShardId};
+use near_primitives::types::{AccountId, BlockHeight, BlockReference, EpochId, ShardId};
 use near_primitives::views::{FinalExecutionOutcomeView, QueryRequest, QueryResponse};
 use std::collections::HashSet;
 use std::fmt;
@@ -108,6 +108,48 @@ impl TryFrom<&str> for PeerInfo {
     }
 }
 
+/// Information a validator advertises about itself, so that other validators can establish
+/// TIER1 connections to it. The binding between `account_id` and `peer_id` is established by the
+/// `AnnounceAccount` that the validator has put into the routing table, so this data is signed
+/// with the node key of `peer_id`.
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
+pub struct AccountData {
+    pub account_id: AccountId,
+    pub peer_id: PeerId,
+    /// Epoch for which the account is a block or chunk producer.
+    pub epoch_id: EpochId,
+    /// Peers through which the validator can be reached. A proxy with `id == peer_id` means
+    /// that the validator accepts direct connections on the given address.
+    pub proxies: Vec<PeerInfo>,
+    /// Time of creation of this data (in nanoseconds). Newer data replaces older one.
+    pub timestamp: u64,
+}
+
+impl AccountData {
+    pub fn sign(self, secret_key: &SecretKey) -> SignedAccountData {
+        let signature = secret_key.sign(CryptoHash::hash_borsh(&self).as_ref());
+        SignedAccountData { account_data: self, signature }
+    }
+}
+
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
+pub struct SignedAccountData {
+    pub account_data: AccountData,
+    /// Signature of the hash of `account_data` by the node key of `account_data.peer_id`.
+    pub signature: Signature,
+}
+
+impl SignedAccountData {
+    pub fn verify(&self) -> bool {
+        self.signature.verify(
+            CryptoHash::hash_borsh(&self.account_data).as_ref(),
+            self.account_data.peer_id.public_key(),
+        )
+    }
+}
+
 /// Peer chain information.
 /// TODO: Remove in next version
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
@@ -147,6 +189,98 @@ impl From<PeerChainInfo> for PeerChainInfoV2 {
     }
 }
 
+/// Data a peer is able to serve to other nodes.  Announced after the handshake and whenever it
+/// changes, and gossiped together with known peers.
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
+pub enum PeerCapabilities {
+    V1(PeerCapabilitiesV1),
+    /// Announced by peers which know about header range requests.
+    V2(PeerCapabilitiesV2),
+}
+
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq, Default)]
+pub struct PeerCapabilitiesV1 {
+    /// Whether the peer keeps the whole history of the chain.
+    pub archival: bool,
+    /// Shards that the peer is tracking.
+    pub tracked_shards: Vec<ShardId>,
+    /// Whether the peer responds to state sync requests for the shards it tracks.
+    pub serves_state_sync: bool,
+}
+
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq, Default)]
+pub struct PeerCapabilitiesV2 {
+    /// Whether the peer keeps the whole history of the chain.
+    pub archival: bool,
+    /// Shards that the peer is tracking.
+    pub tracked_shards: Vec<ShardId>,
+    /// Whether the peer responds to state sync requests for the shards it tracks.
+    pub serves_state_sync: bool,
+    /// Whether the peer responds to requests for block headers by height range.
+    pub serves_header_ranges: bool,
+}
+
+impl Default for PeerCapabilities {
+    fn default() -> Self {
+        Self::V1(PeerCapabilitiesV1::default())
+    }
+}
+
+impl PeerCapabilities {
+    /// Whether the peer keeps the whole history of the chain.
+    pub fn archival(&self) -> bool {
+        match self {
+            Self::V1(capabilities) => capabilities.archival,
+            Self::V2(capabilities) => capabilities.archival,
+        }
+    }
+
+    /// Shards that the peer is tracking.
+    pub fn tracked_shards(&self) -> &[ShardId] {
+        match self {
+            Self::V1(capabilities) => &capabilities.tracked_shards,
+            Self::V2(capabilities) => &capabilities.tracked_shards,
+        }
+    }
+
+    /// Whether the peer responds to state sync requests for the shards it tracks.
+    pub fn serves_state_sync(&self) -> bool {
+        match self {
+            Self::V1(capabilities) => capabilities.serves_state_sync,
+            Self::V2(capabilities) => capabilities.serves_state_sync,
+        }
+    }
+
+    /// Whether the peer responds to requests for block headers by height range.  Peers announcing
+    /// `V1` capabilities don't know about such requests.
+    pub fn serves_header_ranges(&self) -> bool {
+        match self {
+            Self::V1(_) => false,
+            Self::V2(capabilities) => capabilities.serves_header_ranges,
+        }
+    }
+
+    /// Whether the peer can serve state parts of the given shard.
+    pub fn serves_state_of(&self, shard_id: ShardId) -> bool {
+        self.serves_state_sync() && self.tracked_shards().contains(&shard_id)
+    }
+}
+
+/// Capabilities of a peer which didn't announce them, derived from its handshake.  Such peers
+/// serve state sync for all shards they track, but may not know about header range requests.
+impl From<&PeerChainInfoV2> for PeerCapabilities {
+    fn from(chain_info: &PeerChainInfoV2) -> Self {
+        Self::V1(PeerCapabilitiesV1 {
+            archival: chain_info.archival,
+            tracked_shards: chain_info.tracked_shards.clone(),
+            serves_state_sync: true,
+        })
+    }
+}
+
 /// Test code that someone become part of our protocol?
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Eq, Clone, Debug)]
@@ -163,6 +297,18 @@ pub struct Pong {
     pub source: PeerId,
 }
 
+/// Response to a state header (`part_id` is `None`) or state part request which the serving node
+/// refused to process because the requester exceeded its rate limits or the node is overloaded.
+/// The requester should not ask again before `retry_after_ms` milliseconds.
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Eq, Clone, Debug)]
+pub struct StateRequestRejected {
+    pub shard_id: ShardId,
+    pub sync_hash: CryptoHash,
+    pub part_id: Option<u64>,
+    pub retry_after_ms: u64,
+}
+
 // TODO(#1313): Use Box
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
 #[derive(
@@ -206,6 +352,7 @@ pub enum RoutedMessageBody {
     VersionedPartialEncodedChunk(PartialEncodedChunk),
     VersionedStateResponse(StateResponseInfo),
     PartialEncodedChunkForward(PartialEncodedChunkForwardMsg),
+    StateRequestRejected(StateRequestRejected),
 }
 
 impl From<PartialEncodedChunkWithArcReceipts> for RoutedMessageBody {
@@ -222,6 +369,40 @@ impl From<PartialEncodedChunkWithArcReceipts> for RoutedMessageBody {
     }
 }
 
+impl RoutedMessageBody {
+    /// Latency-critical messages exchanged between block and chunk producers.
+    /// Those are sent over TIER1 connections when possible.
+    pub fn is_tier1(&self) -> bool {
+        matches!(
+            self,
+            RoutedMessageBody::BlockApproval(_)
+                | RoutedMessageBody::PartialEncodedChunk(_)
+                | RoutedMessageBody::VersionedPartialEncodedChunk(_)
+                | RoutedMessageBody::PartialEncodedChunkForward(_)
+                | RoutedMessageBody::PartialEncodedChunkRequest(_)
+                | RoutedMessageBody::PartialEncodedChunkResponse(_)
+        )
+    }
+
+    /// Hash of the chunk the message requests or carries parts of, if any.
+    pub fn partial_chunk_hash(&self) -> Option<ChunkHash> {
+        match self {
+            RoutedMessageBody::PartialEncodedChunk(chunk) => Some(chunk.header.chunk_hash()),
+            RoutedMessageBody::VersionedPartialEncodedChunk(chunk) => Some(chunk.chunk_hash()),
+            RoutedMessageBody::PartialEncodedChunkForward(forward) => {
+                Some(forward.chunk_hash.clone())
+            }
+            RoutedMessageBody::PartialEncodedChunkRequest(request) => {
+                Some(request.chunk_hash.clone())
+            }
+            RoutedMessageBody::PartialEncodedChunkResponse(response) => {
+                Some(response.chunk_hash.clone())
+            }
+            _ => None,
+        }
+    }
+}
+
 impl Debug for RoutedMessageBody {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         match self {
@@ -276,6 +457,11 @@ impl Debug for RoutedMessageBody {
                 forward.chunk_hash,
                 forward.parts.iter().map(|p| p.part_ord).collect::<Vec<_>>(),
             ),
+            RoutedMessageBody::StateRequestRejected(rejected) => write!(
+                f,
+                "StateRequestRejected({}, {}, {:?}, {}ms)",
+                rejected.shard_id, rejected.sync_hash, rejected.part_id, rejected.retry_after_ms
+            ),
             RoutedMessageBody::Ping(_) => write!(f, "Ping"),
             RoutedMessageBody::Pong(_) => write!(f, "Pong"),
             RoutedMessageBody::Unused => write!(f, "Unused"),
@@ -306,6 +492,25 @@ pub struct RoutedMessage {
     pub ttl: u8,
     /// Message
     pub body: RoutedMessageBody,
+    /// Number of times this message has been forwarded by intermediate peers. Like `ttl`, it is
+    /// not covered by the signature.  It isn't part of the encoding of the message either, peers
+    /// which support it send it next to the message (see `PeerMessage::RoutedV2`).
+    #[borsh_skip]
+    pub num_hops: u8,
+    /// Span which sent the message, if its trace is exported to OpenTelemetry.  Like
+    /// `num_hops`, it is only sent to peers which support `PeerMessage::RoutedV2`.
+    #[borsh_skip]
+    pub trace_context: Option<TraceContext>,
+}
+
+/// OpenTelemetry context of a span, sent along with a message so that the spans handling it on
+/// the receiving node belong to the same distributed trace.
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub struct TraceContext {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+    pub trace_flags: u8,
 }
 
 #[derive(BorshSerialize, PartialEq, Eq, Clone, Debug)]
@@ -345,9 +550,16 @@ impl RoutedMessage {
         )
     }
 
+    /// Whether the message should be sent over a TIER1 connection if one is available.
+    pub fn is_tier1(&self) -> bool {
+        self.body.is_tier1()
+    }
+
     /// Return true if ttl is positive after decreasing ttl by one, false otherwise.
+    /// Called before forwarding the message, so it also increases the hop count.
     pub fn decrease_ttl(&mut self) -> bool {
         self.ttl = self.ttl.saturating_sub(1);
+        self.num_hops = self.num_hops.saturating_add(1);
         self.ttl > 0
     }
 }
@@ -30,13 +30,15 @@ use tokio::net::TcpStream;
 
 /// Exported types, which are part of network protocol.
 pub use crate::network_protocol::{
-    PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg, PartialEncodedChunkResponseMsg,
+    AccountData, PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg,
+    PartialEncodedChunkResponseMsg, PeerCapabilities, PeerCapabilitiesV1, PeerCapabilitiesV2,
     PeerChainInfo, PeerChainInfoV2, PeerIdOrHash, PeerInfo, Ping, Pong, RoutedMessage,
-    RoutedMessageBody, StateResponseInfo, StateResponseInfoV1, StateResponseInfoV2,
+    RoutedMessageBody, SignedAccountData, StateRequestRejected, StateResponseInfo,
+    StateResponseInfoV1, StateResponseInfoV2, TraceContext,
 };
 
 pub use crate::blacklist::Blacklist;
-pub use crate::config::NetworkConfig;
+pub use crate::config::{NetworkConfig, RequestThrottle, Tier1};
 
 pub use crate::network_protocol::edge::{Edge, EdgeState, PartialEdgeInfo, SimpleEdge};
 
@@ -120,7 +122,16 @@ impl RawRoutedMessage {
         let target = self.target.peer_id_or_hash().unwrap();
         let hash = RoutedMessage::build_hash(&target, &author, &self.body);
         let signature = secret_key.sign(hash.as_ref());
-        RoutedMessage { target, author, signature, ttl: routed_message_ttl, body: self.body }.into()
+        RoutedMessage {
+            target,
+            author,
+            signature,
+            ttl: routed_message_ttl,
+            body: self.body,
+            num_hops: 0,
+            trace_context: None,
+        }
+        .into()
     }
 }
 
@@ -142,12 +153,20 @@ pub enum KnownPeerStatus {
     Unknown,
     NotConnected,
     Connected,
-    Banned(ReasonForBan, u64),
+    /// Peer was banned at `banned_at` and becomes eligible for connections again at `expires_at`
+    /// (both are timestamps in nanoseconds).
+    Banned { reason: ReasonForBan, banned_at: u64, expires_at: u64 },
 }
 
 impl KnownPeerStatus {
+    /// Whether there is a ban record for the peer, regardless of whether it has expired.
     pub fn is_banned(&self) -> bool {
-        matches!(self, KnownPeerStatus::Banned(_, _))
+        matches!(self, KnownPeerStatus::Banned { .. })
+    }
+
+    /// Whether the peer is banned at time `now` (timestamp in nanoseconds).
+    pub fn is_banned_at(&self, now: u64) -> bool {
+        matches!(self, KnownPeerStatus::Banned { expires_at, .. } if now < *expires_at)
     }
 }
 
@@ -226,6 +245,9 @@ pub enum ReasonForBan {
     EpochSyncInvalidResponse = 12,
     EpochSyncInvalidFinalizationResponse = 13,
     Blacklisted = 14,
+    TooManyConnections = 15,
+    /// Banned by the node operator through the debug API.
+    Manual = 16,
 }
 
 /// Banning signal sent from Peer instance to PeerManager
@@ -238,6 +260,35 @@ pub struct Ban {
     pub ban_reason: ReasonForBan,
 }
 
+/// Information about a banned peer, as reported to the node operator.
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BannedPeerInfo {
+    pub peer_info: PeerInfo,
+    pub reason: ReasonForBan,
+    /// Timestamp of the ban in nanoseconds.
+    pub banned_at: u64,
+    /// Timestamp in nanoseconds after which the peer is eligible for connections again.
+    pub expires_at: u64,
+}
+
+/// Routed message which was dropped because its TTL has expired.
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DroppedRoutedMessage {
+    /// Type of the message body.
+    pub msg_type: &'static str,
+    /// Original sender of the message.
+    pub author: PeerId,
+    pub target: PeerIdOrHash,
+    /// Peer from which we received the message.
+    pub from: PeerId,
+    /// Number of hops the message travelled before it was dropped.
+    pub num_hops: u8,
+    /// Timestamp in nanoseconds.
+    pub dropped_at: u64,
+}
+
 /// Messages from PeerManager to Peer
 #[derive(Message, Debug)]
 #[rtype(result = "()")]
@@ -251,6 +302,8 @@ pub struct KnownProducer {
     pub account_id: AccountId,
     pub addr: Option<SocketAddr>,
     pub peer_id: PeerId,
+    /// Whether the routing table has a route to the producer.
+    pub reachable: bool,
 }
 
 #[cfg(feature = "deepsize_feature")]
@@ -270,6 +323,21 @@ pub enum NetworkAdversarialMessage {
     AdvGetSavedBlocks,
     AdvCheckStorageConsistency,
     AdvSetSyncInfo(u64),
+    /// Produce the given number of blocks on top of the head without sending them out.
+    #[cfg(feature = "adversarial")]
+    AdvProducePrivateFork(u64),
+    /// Send out all the blocks withheld by `AdvProducePrivateFork` at once.
+    #[cfg(feature = "adversarial")]
+    AdvReleasePrivateFork,
+    /// Send out a second, different block at the height of the next produced block.
+    #[cfg(feature = "adversarial")]
+    AdvDoubleSign,
+    /// Produce the next chunk with a state root which doesn't match the state of the shard.
+    #[cfg(feature = "adversarial")]
+    AdvProduceInvalidStateRootChunk,
+    /// Never send skip approvals, only endorsements.
+    #[cfg(feature = "adversarial")]
+    AdvDisableDoomslugTimeouts,
 }
 
 #[cfg(feature = "sandbox")]
@@ -304,6 +372,8 @@ pub enum NetworkViewClientMessages {
     BlockRequest(CryptoHash),
     /// Request headers.
     BlockHeadersRequest(Vec<CryptoHash>),
+    /// Request canonical headers with heights in the inclusive range.
+    BlockHeadersByHeightRequest { start_height: BlockHeight, end_height: BlockHeight },
     /// State request header.
     StateRequestHeader { shard_id: ShardId, sync_hash: CryptoHash },
     /// State request part.
@@ -338,6 +408,7 @@ pub enum NetworkViewClientResponses {
         height: BlockHeight,
         tracked_shards: Vec<ShardId>,
         archival: bool,
+        serves_state_sync: bool,
     },
     /// Response to state request.
     StateResponse(Box<StateResponseInfo>),
@@ -406,6 +477,9 @@ mod tests {
     fn test_struct_size() {
         assert_size!(PeerInfo);
         assert_size!(PeerChainInfoV2);
+        assert_size!(PeerCapabilities);
+        assert_size!(PeerCapabilitiesV1);
+        assert_size!(PeerCapabilitiesV2);
         assert_size!(AnnounceAccount);
         assert_size!(Ping);
         assert_size!(Pong);
@@ -419,6 +493,93 @@ mod tests {
         assert_size!(StateResponseInfoV1);
         assert_size!(QueryPeerStats);
         assert_size!(PartialEncodedChunkRequestMsg);
+        assert_size!(SignedAccountData);
+        assert_size!(BannedPeerInfo);
+        assert_size!(DroppedRoutedMessage);
+    }
+
+    #[test]
+    fn test_routed_message_ttl_and_hops() {
+        let secret_key = SecretKey::from_seed(near_crypto::KeyType::ED25519, "test");
+        let peer_id = PeerId::new(secret_key.public_key());
+        let body = RoutedMessageBody::Ping(Ping { nonce: 0, source: peer_id.clone() });
+        let mut config = NetworkConfig::from_seed("test", 0);
+        config.routed_message_ttl_overrides.insert("Ping".to_string(), 2);
+        let ttl = config.routed_message_ttl_for(&body);
+        assert_eq!(ttl, 2);
+
+        let raw = RawRoutedMessage { target: AccountOrPeerIdOrHash::PeerId(peer_id.clone()), body };
+        let mut msg = raw.sign(peer_id, &secret_key, ttl);
+        assert_eq!(msg.num_hops, 0);
+        let hash = msg.hash();
+        assert!(msg.decrease_ttl());
+        assert!(!msg.decrease_ttl());
+        assert_eq!(msg.num_hops, 2);
+        // Neither ttl nor hop count are part of the signed hash.
+        assert_eq!(msg.hash(), hash);
+        assert!(msg.verify());
+        // The hop count isn't part of the encoding, which older peers decode.
+        let decoded = RoutedMessage::try_from_slice(&msg.try_to_vec().unwrap()).unwrap();
+        assert_eq!(decoded.num_hops, 0);
+    }
+
+    #[test]
+    fn test_known_peer_status_ban_expiry() {
+        let status =
+            KnownPeerStatus::Banned { reason: ReasonForBan::Manual, banned_at: 10, expires_at: 20 };
+        assert!(status.is_banned());
+        assert!(status.is_banned_at(10));
+        assert!(status.is_banned_at(19));
+        assert!(!status.is_banned_at(20));
+        assert!(!KnownPeerStatus::NotConnected.is_banned_at(10));
+    }
+
+    #[test]
+    fn test_signed_account_data_verify() {
+        let secret_key = SecretKey::from_seed(near_crypto::KeyType::ED25519, "test");
+        let peer_id = PeerId::new(secret_key.public_key());
+        let account_data = AccountData {
+            account_id: "test".parse().unwrap(),
+            peer_id: peer_id.clone(),
+            epoch_id: EpochId::default(),
+            proxies: vec![PeerInfo::new(peer_id, "127.0.0.1:24567".parse().unwrap())],
+            timestamp: 1,
+        };
+        let signed = account_data.clone().sign(&secret_key);
+        assert!(signed.verify());
+
+        let other_key = SecretKey::from_seed(near_crypto::KeyType::ED25519, "other");
+        assert!(!account_data.clone().sign(&other_key).verify());
+
+        let mut tampered = signed;
+        tampered.account_data.timestamp = 2;
+        assert!(!tampered.verify());
+    }
+
+    #[test]
+    fn test_peer_capabilities_versions() {
+        let v1 = PeerCapabilities::V1(PeerCapabilitiesV1 {
+            archival: true,
+            tracked_shards: vec![1],
+            serves_state_sync: true,
+        });
+        let bytes = v1.try_to_vec().unwrap();
+        assert_eq!(bytes, [0, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1]);
+        let decoded = PeerCapabilities::try_from_slice(&bytes).unwrap();
+        assert!(decoded.archival());
+        assert!(decoded.serves_state_of(1));
+        assert!(!decoded.serves_header_ranges());
+
+        let v2 = PeerCapabilities::V2(PeerCapabilitiesV2 {
+            archival: false,
+            tracked_shards: vec![],
+            serves_state_sync: false,
+            serves_header_ranges: true,
+        });
+        let decoded = PeerCapabilities::try_from_slice(&v2.try_to_vec().unwrap()).unwrap();
+        assert_eq!(decoded, v2);
+        assert!(decoded.serves_header_ranges());
+        assert!(!decoded.serves_state_of(0));
     }
 
     #[test]
@@ -5,7 +5,7 @@ pub use crate::routing::routing_table_actor::RoutingTableActor;
 #[cfg(feature = "test_features")]
 pub use crate::routing::routing_table_actor::{RoutingTableMessages, RoutingTableMessagesResponse};
 #[cfg(feature = "test_features")]
-pub use crate::stats::metrics::RECEIVED_INFO_ABOUT_ITSELF;
+pub use crate::stats::metrics::{RECEIVED_INFO_ABOUT_ITSELF, STATE_REQUEST_REJECTED};
 
 mod network_protocol;
 mod peer;
@@ -19,4 +19,5 @@ pub(crate) mod stats;
 pub mod test_utils;
 #[cfg(test)]
 mod tests;
+mod trace_context;
 pub mod types;
@@ -5,7 +5,8 @@
 /// We need to maintain backwards compatibility, all changes to this file needs to be reviews.
 use borsh::{BorshDeserialize, BorshSerialize};
 use near_network_primitives::types::{
-    Edge, PartialEdgeInfo, PeerChainInfoV2, PeerInfo, RoutedMessage, RoutedMessageBody,
+    Edge, PartialEdgeInfo, PeerCapabilities, PeerChainInfoV2, PeerInfo, RoutedMessage,
+    RoutedMessageBody, SignedAccountData, TraceContext,
 };
 use near_primitives::block::{Block, BlockHeader, GenesisId};
 use near_primitives::challenge::Challenge;
@@ -13,7 +14,7 @@ use near_primitives::hash::CryptoHash;
 use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::syncing::{EpochSyncFinalizationResponse, EpochSyncResponse};
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::{EpochId, ProtocolVersion};
+use near_primitives::types::{BlockHeight, EpochId, ProtocolVersion};
 use near_primitives::version::{PEER_MIN_ALLOWED_PROTOCOL_VERSION, PROTOCOL_VERSION};
 use std::fmt::Formatter;
 use std::{fmt, io};
@@ -166,6 +167,19 @@ impl fmt::Display for HandshakeFailureReason {
 
 impl std::error::Error for HandshakeFailureReason {}
 
+/// Protocol version from which peers understand `PeerMessage::SyncAccountsData`.
+pub(crate) const SYNC_ACCOUNTS_DATA_PROTOCOL_VERSION: ProtocolVersion = 54;
+/// Protocol version from which peers understand `RoutedMessageBody::StateRequestRejected`.
+pub(crate) const STATE_REQUEST_REJECTED_PROTOCOL_VERSION: ProtocolVersion = 54;
+/// Protocol version from which peers understand `PeerMessage::RoutedV2`.
+pub(crate) const ROUTED_MESSAGE_V2_PROTOCOL_VERSION: ProtocolVersion = 54;
+/// Protocol version from which peers announce and gossip their capabilities.
+pub(crate) const CAPABILITIES_PROTOCOL_VERSION: ProtocolVersion = 54;
+/// Tag of `PeerMessage::Routed` in the encoding of a `PeerMessage`.
+pub(crate) const ROUTED_TAG: u8 = 13;
+/// Tag of `PeerMessage::RoutedV2` in the encoding of a `PeerMessage`.
+pub(crate) const ROUTED_V2_TAG: u8 = 26;
+
 /// Warning, position of each message type in this enum defines the protocol due to serialization.
 /// DO NOT MOVE, REORDER, DELETE items from the list. Only add new items to the end.
 /// If need to remove old items - replace with `None`.
@@ -215,10 +229,47 @@ pub enum PeerMessage {
 
     #[cfg(feature = "protocol_feature_routing_exchange_algorithm")]
     RoutingTableSyncV2(RoutingSyncV2),
+    /// Keeps the position of `RoutingTableSyncV2` when it's disabled, so that the variants after
+    /// it are serialized the same way either way.
+    #[cfg(not(feature = "protocol_feature_routing_exchange_algorithm"))]
+    _RoutingTableSyncV2,
+
+    /// Information about how to reach current and next epoch validators over TIER1.
+    /// Only sent to peers with `SYNC_ACCOUNTS_DATA_PROTOCOL_VERSION`.
+    SyncAccountsData(Vec<SignedAccountData>),
+    /// Capabilities of the sender, sent after the handshake and whenever they change.
+    /// Only sent to peers with `CAPABILITIES_PROTOCOL_VERSION`, as is `PeersCapabilities`.
+    Capabilities(PeerCapabilities),
+    /// Capabilities of known peers, sent along with `PeersResponse`.
+    PeersCapabilities(Vec<(PeerId, PeerCapabilities)>),
+    /// Request for the headers of the sender's canonical chain with heights in the given
+    /// inclusive range.  Answered with `BlockHeaders`.
+    BlockHeadersByHeightRequest(BlockHeight, BlockHeight),
+    /// `Routed` along with the data which isn't part of the encoding of `RoutedMessage`.  Routed
+    /// messages are sent this way to peers with `ROUTED_MESSAGE_V2_PROTOCOL_VERSION`, see
+    /// `PeerMessage::serialize_for`, and are turned back into `Routed` when received.
+    RoutedV2(Box<RoutedMessage>, RoutedMessageMeta),
 }
 #[cfg(target_arch = "x86_64")] // Non-x86_64 doesn't match this requirement yet but it's not bad as it's not production-ready
 const _: () = assert!(std::mem::size_of::<PeerMessage>() <= 1144, "PeerMessage > 1144 bytes");
 
+/// Data sent next to a routed message in `PeerMessage::RoutedV2`.  It isn't covered by the
+/// signature of the author of the message.
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Eq, Clone, Debug)]
+pub struct RoutedMessageMeta {
+    /// Number of times the message has been forwarded by intermediate peers.
+    pub num_hops: u8,
+    /// Span which sent the message, if its trace is exported to OpenTelemetry.
+    pub trace_context: Option<TraceContext>,
+}
+
+impl RoutedMessageMeta {
+    fn of(msg: &RoutedMessage) -> Self {
+        Self { num_hops: msg.num_hops, trace_context: msg.trace_context }
+    }
+}
+
 #[cfg(feature = "protocol_feature_routing_exchange_algorithm")]
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Eq, Clone, Debug)]
@@ -273,6 +324,51 @@ impl PeerMessage {
         }
     }
 
+    /// Lowest protocol version agreed on with a peer for which the peer can decode the message.
+    /// Messages added after `PEER_MIN_ALLOWED_PROTOCOL_VERSION` aren't sent to older peers.
+    pub(crate) fn min_protocol_version(&self) -> ProtocolVersion {
+        match self {
+            PeerMessage::SyncAccountsData(_) => SYNC_ACCOUNTS_DATA_PROTOCOL_VERSION,
+            PeerMessage::RoutedV2(_, _) => ROUTED_MESSAGE_V2_PROTOCOL_VERSION,
+            PeerMessage::Capabilities(_) | PeerMessage::PeersCapabilities(_) => {
+                CAPABILITIES_PROTOCOL_VERSION
+            }
+            PeerMessage::Routed(msg)
+                if matches!(msg.body, RoutedMessageBody::StateRequestRejected(_)) =>
+            {
+                STATE_REQUEST_REJECTED_PROTOCOL_VERSION
+            }
+            _ => PEER_MIN_ALLOWED_PROTOCOL_VERSION,
+        }
+    }
+
+    /// Encodes the message for a peer with the given agreed protocol version.  Routed messages
+    /// are encoded as `RoutedV2` for peers which support it.
+    pub(crate) fn serialize_for(&self, protocol_version: ProtocolVersion) -> io::Result<Vec<u8>> {
+        match self {
+            PeerMessage::Routed(msg) if protocol_version >= ROUTED_MESSAGE_V2_PROTOCOL_VERSION => {
+                // The encoding of `RoutedV2(msg, meta)`, without copying `msg` to build it.
+                let mut bytes = vec![ROUTED_V2_TAG];
+                msg.serialize(&mut bytes)?;
+                RoutedMessageMeta::of(msg).serialize(&mut bytes)?;
+                Ok(bytes)
+            }
+            _ => self.try_to_vec(),
+        }
+    }
+
+    /// Turns a received `RoutedV2` into the `Routed` message it carries.
+    pub(crate) fn into_routed_v1(self) -> Self {
+        match self {
+            PeerMessage::RoutedV2(mut msg, meta) => {
+                msg.num_hops = meta.num_hops;
+                msg.trace_context = meta.trace_context;
+                PeerMessage::Routed(msg)
+            }
+            msg => msg,
+        }
+    }
+
     pub(crate) fn is_client_message(&self) -> bool {
         match self {
             PeerMessage::Block(_)
@@ -290,6 +386,7 @@ impl PeerMessage {
                     | RoutedMessageBody::PartialEncodedChunkRequest(_)
                     | RoutedMessageBody::PartialEncodedChunkResponse(_)
                     | RoutedMessageBody::StateResponse(_)
+                    | RoutedMessageBody::StateRequestRejected(_)
                     | RoutedMessageBody::VersionedPartialEncodedChunk(_)
                     | RoutedMessageBody::VersionedStateResponse(_)
             ),
@@ -300,6 +397,7 @@ impl PeerMessage {
     pub(crate) fn is_view_client_message(&self) -> bool {
         match self {
             PeerMessage::BlockHeadersRequest(_)
+            | PeerMessage::BlockHeadersByHeightRequest(_, _)
             | PeerMessage::BlockRequest(_)
             | PeerMessage::EpochSyncFinalizationRequest(_)
             | PeerMessage::EpochSyncRequest(_) => true,
@@ -317,3 +415,104 @@ impl PeerMessage {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_crypto::{KeyType, SecretKey};
+    use near_network_primitives::types::{
+        AccountOrPeerIdOrHash, Ping, RawRoutedMessage, RoutedMessageBody, StateRequestRejected,
+    };
+
+    /// The position of a variant is its tag on the wire, peers of older versions decode the
+    /// variants they know about the same way.
+    #[test]
+    fn test_peer_message_tags() {
+        assert_eq!(PeerMessage::Disconnect.try_to_vec().unwrap(), vec![14]);
+        assert_eq!(PeerMessage::EpochSyncRequest(EpochId::default()).try_to_vec().unwrap()[0], 17);
+        #[cfg(not(feature = "protocol_feature_routing_exchange_algorithm"))]
+        assert_eq!(PeerMessage::_RoutingTableSyncV2.try_to_vec().unwrap(), vec![21]);
+        assert_eq!(PeerMessage::SyncAccountsData(vec![]).try_to_vec().unwrap()[0], 22);
+        assert_eq!(PeerMessage::PeersCapabilities(vec![]).try_to_vec().unwrap()[0], 24);
+        assert_eq!(PeerMessage::BlockHeadersByHeightRequest(0, 0).try_to_vec().unwrap()[0], 25);
+    }
+
+    #[test]
+    fn test_serialize_routed_for_version() {
+        let secret_key = SecretKey::from_seed(KeyType::ED25519, "test");
+        let peer_id = PeerId::new(secret_key.public_key());
+        let raw = RawRoutedMessage {
+            target: AccountOrPeerIdOrHash::PeerId(peer_id.clone()),
+            body: RoutedMessageBody::Ping(Ping { nonce: 0, source: peer_id.clone() }),
+        };
+        let mut msg = raw.sign(peer_id, &secret_key, 10);
+        msg.decrease_ttl();
+        let trace_context = TraceContext { trace_id: [1; 16], span_id: [2; 8], trace_flags: 1 };
+        msg.trace_context = Some(trace_context);
+        let msg = PeerMessage::Routed(msg);
+
+        let bytes = msg.serialize_for(ROUTED_MESSAGE_V2_PROTOCOL_VERSION - 1).unwrap();
+        assert_eq!(bytes[0], ROUTED_TAG);
+        let received = PeerMessage::try_from_slice(&bytes).unwrap().into_routed_v1();
+        match &received {
+            PeerMessage::Routed(routed) => {
+                assert_eq!(routed.num_hops, 0);
+                assert_eq!(routed.trace_context, None);
+            }
+            _ => panic!("unexpected message {:?}", received),
+        }
+
+        let bytes = msg.serialize_for(ROUTED_MESSAGE_V2_PROTOCOL_VERSION).unwrap();
+        assert_eq!(bytes[0], ROUTED_V2_TAG);
+        let received = PeerMessage::try_from_slice(&bytes).unwrap();
+        assert!(matches!(
+            &received,
+            PeerMessage::RoutedV2(_, RoutedMessageMeta { num_hops: 1, trace_context: Some(context) })
+                if *context == trace_context
+        ));
+        assert_eq!(received.into_routed_v1(), msg);
+    }
+
+    #[test]
+    fn test_min_protocol_version() {
+        assert_eq!(
+            PeerMessage::Disconnect.min_protocol_version(),
+            PEER_MIN_ALLOWED_PROTOCOL_VERSION
+        );
+        assert!(
+            PeerMessage::SyncAccountsData(vec![]).min_protocol_version()
+                > PEER_MIN_ALLOWED_PROTOCOL_VERSION
+        );
+        assert_eq!(
+            PeerMessage::PeersCapabilities(vec![]).min_protocol_version(),
+            CAPABILITIES_PROTOCOL_VERSION
+        );
+
+        let secret_key = SecretKey::from_seed(KeyType::ED25519, "test");
+        let peer_id = PeerId::new(secret_key.public_key());
+        let routed = |body| {
+            PeerMessage::Routed(
+                RawRoutedMessage {
+                    target: AccountOrPeerIdOrHash::Hash(CryptoHash::default()),
+                    body,
+                }
+                .sign(peer_id.clone(), &secret_key, 10),
+            )
+        };
+        let rejected = StateRequestRejected {
+            shard_id: 0,
+            sync_hash: CryptoHash::default(),
+            part_id: Some(1),
+            retry_after_ms: 1000,
+        };
+        assert_eq!(
+            routed(RoutedMessageBody::StateRequestRejected(rejected)).min_protocol_version(),
+            STATE_REQUEST_REJECTED_PROTOCOL_VERSION
+        );
+        assert_eq!(
+            routed(RoutedMessageBody::StateRequestHeader(0, CryptoHash::default()))
+                .min_protocol_version(),
+            PEER_MIN_ALLOWED_PROTOCOL_VERSION
+        );
+    }
+}
@@ -221,6 +221,8 @@ mod test {
                     target_height: 1,
                     signature,
                 }),
+                num_hops: 0,
+                trace_context: None,
             }
             .into(),
         );
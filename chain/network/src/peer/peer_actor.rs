@@ -8,21 +8,21 @@ use crate::stats::metrics::{self, NetworkMetrics};
 use crate::types::{
     Handshake, HandshakeFailureReason, NetworkClientMessages, NetworkClientResponses,
     NetworkRequests, NetworkResponses, PeerManagerMessageRequest, PeerMessage, PeerRequest,
-    PeerResponse, PeersResponse,
+    PeerResponse, PeerStateRequest, PeersCapabilities, PeersResponse,
 };
 use crate::PeerManagerActor;
 use actix::{
     Actor, ActorContext, ActorFuture, Addr, Arbiter, AsyncContext, Context, ContextFutureSpawner,
     Handler, Recipient, Running, StreamHandler, WrapFuture,
 };
-use borsh::{BorshDeserialize, BorshSerialize};
+use borsh::BorshDeserialize;
 use lru::LruCache;
 use near_crypto::Signature;
 use near_network_primitives::types::{
-    Ban, NetworkViewClientMessages, NetworkViewClientResponses, PeerChainInfoV2, PeerIdOrHash,
-    PeerInfo, PeerManagerRequest, PeerStatsResult, PeerType, QueryPeerStats, ReasonForBan,
-    RoutedMessage, RoutedMessageBody, RoutedMessageFrom, StateResponseInfo,
-    UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE,
+    Ban, NetworkViewClientMessages, NetworkViewClientResponses, PeerCapabilities,
+    PeerCapabilitiesV2, PeerChainInfoV2, PeerIdOrHash, PeerInfo, PeerManagerRequest,
+    PeerStatsResult, PeerType, QueryPeerStats, ReasonForBan, RoutedMessage, RoutedMessageBody,
+    RoutedMessageFrom, StateResponseInfo, UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE,
 };
 use near_network_primitives::types::{Edge, PartialEdgeInfo};
 use near_performance_metrics::framed_write::{FramedWrite, WriteHandler};
@@ -61,6 +61,11 @@ const MAX_TRANSACTIONS_PER_BLOCK_MESSAGE: usize = 1000;
 const ROUTED_MESSAGE_CACHE_SIZE: usize = 1000;
 /// Duplicated messages will be dropped if routed through the same peer multiple times.
 const DROP_DUPLICATED_MESSAGES_PERIOD: Duration = Duration::from_millis(50);
+/// How often to check whether capabilities of this node have changed and need to be re-announced.
+const CAPABILITIES_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Capabilities are re-announced at least this often, even if they didn't change, so that they
+/// don't age out on the other side.
+pub(crate) const CAPABILITIES_ANNOUNCE_INTERVAL: Duration = Duration::from_secs(10 * 60);
 
 pub(crate) struct PeerActor {
     /// This node's id and address (either listening or socket address).
@@ -106,6 +111,8 @@ pub(crate) struct PeerActor {
     routed_message_cache: LruCache<(PeerId, PeerIdOrHash, Signature), Instant>,
     /// A helper data structure for limiting reading
     throttle_controller: ThrottleController,
+    /// Capabilities of this node last announced to the peer and when.
+    announced_capabilities: Option<(PeerCapabilities, Instant)>,
 }
 
 impl Debug for PeerActor {
@@ -154,10 +161,16 @@ impl PeerActor {
             peer_counter,
             routed_message_cache: LruCache::new(ROUTED_MESSAGE_CACHE_SIZE),
             throttle_controller,
+            announced_capabilities: None,
         }
     }
 
     fn send_message(&mut self, msg: &PeerMessage) {
+        // The peer can't decode messages added after the protocol version agreed on with it.
+        if msg.min_protocol_version() > self.protocol_version {
+            trace!(target: "network", "Not sending {} to peer {} with protocol version {}", msg, self.peer_info, self.protocol_version);
+            return;
+        }
         // Skip sending block and headers if we received it or header from this peer.
         // Record block requests in tracker.
         match msg {
@@ -166,7 +179,7 @@ impl PeerActor {
             _ => (),
         };
 
-        match msg.try_to_vec() {
+        match msg.serialize_for(self.protocol_version) {
             Ok(bytes) => {
                 self.tracker.increment_sent(bytes.len() as u64);
                 let bytes_len = bytes.len();
@@ -221,6 +234,7 @@ impl PeerActor {
                     height,
                     tracked_shards,
                     archival,
+                    ..
                 }) => {
                     let handshake = match act.protocol_version {
                         39..=PROTOCOL_VERSION => PeerMessage::Handshake(Handshake::new(
@@ -249,6 +263,57 @@ impl PeerActor {
             .spawn(ctx);
     }
 
+    /// Announces capabilities of this node to the peer if they changed since the last
+    /// announcement or it's time to refresh them, and schedules the next check.
+    fn announce_capabilities(&mut self, ctx: &mut Context<PeerActor>) {
+        self.view_client_addr
+            .send(NetworkViewClientMessages::GetChainInfo)
+            .into_actor(self)
+            .then(move |res, act, _ctx| {
+                match res {
+                    Ok(NetworkViewClientResponses::ChainInfo {
+                        tracked_shards,
+                        archival,
+                        serves_state_sync,
+                        ..
+                    }) => {
+                        let capabilities = PeerCapabilities::V2(PeerCapabilitiesV2 {
+                            archival,
+                            tracked_shards,
+                            serves_state_sync,
+                            serves_header_ranges: true,
+                        });
+                        let is_due = match &act.announced_capabilities {
+                            Some((announced, at)) => {
+                                announced != &capabilities
+                                    || at.elapsed() >= CAPABILITIES_ANNOUNCE_INTERVAL
+                            }
+                            None => true,
+                        };
+                        if is_due {
+                            debug!(target: "network", "Announcing capabilities {:?} to {}", capabilities, act.peer_info);
+                            act.send_message(&PeerMessage::Capabilities(capabilities.clone()));
+                            act.announced_capabilities = Some((capabilities, Clock::instant()));
+                        }
+                    }
+                    Err(err) => {
+                        error!(target: "network", "Failed sending GetChain to client: {}", err);
+                    }
+                    _ => {}
+                }
+                actix::fut::ready(())
+            })
+            .spawn(ctx);
+
+        near_performance_metrics::actix::run_later(
+            ctx,
+            CAPABILITIES_CHECK_INTERVAL,
+            move |act, ctx| {
+                act.announce_capabilities(ctx);
+            },
+        );
+    }
+
     fn ban_peer(&mut self, ctx: &mut Context<PeerActor>, ban_reason: ReasonForBan) {
         warn!(target: "network", "Banning peer {} for {:?}", self.peer_info, ban_reason);
         self.peer_status = PeerStatus::Banned(ban_reason);
@@ -281,6 +346,29 @@ impl PeerActor {
         let view_client_message = match msg {
             PeerMessage::Routed(message) => {
                 msg_hash = Some(message.hash());
+                // State requests are expensive to serve, so they are throttled and scheduled
+                // across requesting peers by the peer manager.
+                let state_request = match message.body {
+                    RoutedMessageBody::StateRequestHeader(shard_id, sync_hash) => {
+                        Some((shard_id, sync_hash, None))
+                    }
+                    RoutedMessageBody::StateRequestPart(shard_id, sync_hash, part_id) => {
+                        Some((shard_id, sync_hash, Some(part_id)))
+                    }
+                    _ => None,
+                };
+                if let Some((shard_id, sync_hash, part_id)) = state_request {
+                    self.peer_manager_addr.do_send(PeerManagerMessageRequest::PeerRequest(
+                        PeerRequest::StateRequest(PeerStateRequest {
+                            peer_id: message.author,
+                            shard_id,
+                            sync_hash,
+                            part_id,
+                            msg_hash: msg_hash.unwrap(),
+                        }),
+                    ));
+                    return;
+                }
                 match message.body {
                     RoutedMessageBody::TxStatusRequest(account_id, tx_hash) => {
                         NetworkViewClientMessages::TxStatus {
@@ -294,12 +382,6 @@ impl PeerActor {
                     RoutedMessageBody::ReceiptOutcomeRequest(receipt_id) => {
                         NetworkViewClientMessages::ReceiptOutcomeRequest(receipt_id)
                     }
-                    RoutedMessageBody::StateRequestHeader(shard_id, sync_hash) => {
-                        NetworkViewClientMessages::StateRequestHeader { shard_id, sync_hash }
-                    }
-                    RoutedMessageBody::StateRequestPart(shard_id, sync_hash, part_id) => {
-                        NetworkViewClientMessages::StateRequestPart { shard_id, sync_hash, part_id }
-                    }
                     body => {
                         error!(target: "network", "Peer receive_view_client_message received unexpected type: {:?}", body);
                         return;
@@ -310,6 +392,9 @@ impl PeerActor {
             PeerMessage::BlockHeadersRequest(hashes) => {
                 NetworkViewClientMessages::BlockHeadersRequest(hashes)
             }
+            PeerMessage::BlockHeadersByHeightRequest(start_height, end_height) => {
+                NetworkViewClientMessages::BlockHeadersByHeightRequest { start_height, end_height }
+            }
             PeerMessage::EpochSyncRequest(epoch_id) => {
                 NetworkViewClientMessages::EpochSyncRequest { epoch_id }
             }
@@ -341,19 +426,6 @@ impl PeerActor {
                             PeerRequest::RouteBack(body, msg_hash.unwrap()),
                         ));
                     }
-                    Ok(NetworkViewClientResponses::StateResponse(state_response)) => {
-                        let body = match *state_response {
-                            StateResponseInfo::V1(state_response) => {
-                                RoutedMessageBody::StateResponse(state_response)
-                            }
-                            state_response @ StateResponseInfo::V2(_) => {
-                                RoutedMessageBody::VersionedStateResponse(state_response)
-                            }
-                        };
-                        act.peer_manager_addr.do_send(PeerManagerMessageRequest::PeerRequest(
-                            PeerRequest::RouteBack(Box::new(body), msg_hash.unwrap()),
-                        ));
-                    }
                     Ok(NetworkViewClientResponses::Block(block)) => {
                         // MOO need protocol version
                         act.send_message(&PeerMessage::Block(*block))
@@ -410,6 +482,19 @@ impl PeerActor {
             // All Routed messages received at this point are for us.
             PeerMessage::Routed(routed_message) => {
                 let msg_hash = routed_message.hash();
+                let _span = routed_message.body.partial_chunk_hash().map(|chunk_hash| {
+                    let span = tracing::info_span!(
+                        target: "network",
+                        "receive_partial_chunk",
+                        chunk_hash = %chunk_hash.0,
+                        body = strum::AsStaticRef::as_static(&routed_message.body),
+                        num_hops = routed_message.num_hops,
+                    );
+                    if let Some(trace_context) = &routed_message.trace_context {
+                        crate::trace_context::set_parent(&span, trace_context);
+                    }
+                    span.entered()
+                });
 
                 match routed_message.body {
                     RoutedMessageBody::BlockApproval(approval) => {
@@ -429,6 +514,9 @@ impl PeerActor {
                     RoutedMessageBody::VersionedStateResponse(info) => {
                         NetworkClientMessages::StateResponse(info)
                     }
+                    RoutedMessageBody::StateRequestRejected(rejected) => {
+                        NetworkClientMessages::StateRequestRejected(rejected)
+                    }
                     RoutedMessageBody::PartialEncodedChunkRequest(request) => {
                         NetworkClientMessages::PartialEncodedChunkRequest(request, msg_hash)
                     }
@@ -477,12 +565,17 @@ impl PeerActor {
             | PeerMessage::PeersRequest
             | PeerMessage::PeersResponse(_)
             | PeerMessage::SyncRoutingTable(_)
+            | PeerMessage::SyncAccountsData(_)
+            | PeerMessage::Capabilities(_)
+            | PeerMessage::PeersCapabilities(_)
             | PeerMessage::LastEdge(_)
             | PeerMessage::Disconnect
             | PeerMessage::RequestUpdateNonce(_)
             | PeerMessage::ResponseUpdateNonce(_)
             | PeerMessage::BlockRequest(_)
             | PeerMessage::BlockHeadersRequest(_)
+            | PeerMessage::BlockHeadersByHeightRequest(_, _)
+            | PeerMessage::RoutedV2(_, _)
             | PeerMessage::EpochSyncRequest(_)
             | PeerMessage::EpochSyncFinalizationRequest(_) => {
                 error!(target: "network", "Peer receive_client_message received unexpected type: {:?}", msg);
@@ -493,6 +586,11 @@ impl PeerActor {
                 error!(target: "network", "Peer receive_client_message received unexpected type: {:?}", msg);
                 return;
             }
+            #[cfg(not(feature = "protocol_feature_routing_exchange_algorithm"))]
+            PeerMessage::_RoutingTableSyncV2 => {
+                error!(target: "network", "Peer receive_client_message received unexpected type: {:?}", msg);
+                return;
+            }
         };
 
         self.client_addr
@@ -661,7 +759,7 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for PeerActor {
             return;
         }
         let peer_msg = match PeerMessage::try_from_slice(&msg) {
-            Ok(peer_msg) => peer_msg,
+            Ok(peer_msg) => peer_msg.into_routed_v1(),
             Err(err) => {
                 // This may send `HandshakeFailure` to the other peer.
                 self.handle_peer_message_decode_error(&msg, err);
@@ -834,6 +932,7 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for PeerActor {
                                     act.partial_edge_info = edge_info;
                                     act.send_handshake(ctx);
                                 }
+                                act.announce_capabilities(ctx);
                                 actix::fut::ready(())
                             },
                             Ok(RegisterPeerResponse::InvalidNonce(edge)) => {
@@ -903,6 +1002,9 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for PeerActor {
                             debug!(target: "network", "Peers request from {}: sending {} peers.", act.peer_info, peers.peers.len());
                             act.send_message(&PeerMessage::PeersResponse(peers.peers));
                         }
+                        if !peers.capabilities.is_empty() {
+                            act.send_message(&PeerMessage::PeersCapabilities(peers.capabilities));
+                        }
                     }
                     actix::fut::ready(())
                 }).spawn(ctx);
@@ -914,6 +1016,26 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for PeerActor {
                     Some(self.throttle_controller.clone()),
                 ));
             }
+            (PeerStatus::Ready, PeerMessage::Capabilities(capabilities)) => {
+                debug!(target: "network", "Received capabilities from {}: {:?}", self.peer_info, capabilities);
+                let peer_id = self.other_peer_id().unwrap().clone();
+                self.peer_manager_addr.do_send(ActixMessageWrapper::new_without_size(
+                    PeerManagerMessageRequest::PeersCapabilities(PeersCapabilities {
+                        from: peer_id.clone(),
+                        capabilities: vec![(peer_id, capabilities)],
+                    }),
+                    Some(self.throttle_controller.clone()),
+                ));
+            }
+            (PeerStatus::Ready, PeerMessage::PeersCapabilities(capabilities)) => {
+                self.peer_manager_addr.do_send(ActixMessageWrapper::new_without_size(
+                    PeerManagerMessageRequest::PeersCapabilities(PeersCapabilities {
+                        from: self.other_peer_id().unwrap().clone(),
+                        capabilities,
+                    }),
+                    Some(self.throttle_controller.clone()),
+                ));
+            }
             (PeerStatus::Ready, PeerMessage::RequestUpdateNonce(edge_info)) => self
                 .peer_manager_addr
                 .send(PeerManagerMessageRequest::NetworkRequests(
@@ -960,6 +1082,15 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for PeerActor {
                     Some(self.throttle_controller.clone()),
                 ));
             }
+            (PeerStatus::Ready, PeerMessage::SyncAccountsData(accounts_data)) => {
+                self.peer_manager_addr.do_send(ActixMessageWrapper::new_without_size(
+                    PeerManagerMessageRequest::NetworkRequests(NetworkRequests::SyncAccountsData {
+                        peer_id: self.other_peer_id().unwrap().clone(),
+                        accounts_data,
+                    }),
+                    Some(self.throttle_controller.clone()),
+                ));
+            }
             #[cfg(feature = "protocol_feature_routing_exchange_algorithm")]
             (PeerStatus::Ready, PeerMessage::RoutingTableSyncV2(ibf_message)) => {
                 // TODO(#5155) Add wrapper to be something like this for all messages.
@@ -1,27 +1,31 @@
+use crate::network_protocol::{ROUTED_TAG, ROUTED_V2_TAG};
 use tracing::error;
 
 /// Determines size of `PeerId` based on first byte of it's representation.
 /// Size of `PeerId` depends on type of `PublicMessage` it stores.
 /// `PublicKey::ED25519` -> `1 + 32 bytes`
 /// `PublicKey::SECP256K1` -> `1 + 64 bytes`
+/// `PublicKey::BLS12381` -> `1 + 48 bytes`
 fn peer_id_type_field_len(enum_var: u8) -> Option<usize> {
     // 1 byte for enum variant, then some number depending on the
     // public key type
     match enum_var {
         0 => Some(1 + 32),
         1 => Some(1 + 64),
+        2 => Some(1 + 48),
         _ => None,
     }
 }
 
-/// Checks `bytes` represents `PeerMessage::Routed(RoutedMessage)`,
+/// Checks `bytes` represents `PeerMessage::Routed(RoutedMessage)` or
+/// `PeerMessage::RoutedV2(RoutedMessage, _)`,
 /// and `RoutedMessage.body` has type of `RoutedMessageBody::ForwardTx`.
 ///
 /// This is done to avoid expensive `borsh`-deserializing.
 pub(crate) fn is_forward_transaction(bytes: &[u8]) -> Option<bool> {
-    // PeerMessage::Routed variant == 13
+    // Both variants start with the `RoutedMessage`.
     let peer_message_variant = *bytes.get(0)?;
-    if peer_message_variant != 13 {
+    if peer_message_variant != ROUTED_TAG && peer_message_variant != ROUTED_V2_TAG {
         return Some(false);
     }
 
@@ -60,6 +64,7 @@ pub(crate) fn is_forward_transaction(bytes: &[u8]) -> Option<bool> {
         let signature_field_len = match signature_variant {
             0 => 1 + 64, // Signature::ED25519
             1 => 1 + 65, // Signature::SECP256K1
+            2 => 1 + 96, // Signature::BLS12381
             _ => {
                 return None;
             }
@@ -77,6 +82,7 @@ pub(crate) fn is_forward_transaction(bytes: &[u8]) -> Option<bool> {
 
 #[cfg(test)]
 mod tests {
+    use crate::network_protocol::ROUTED_MESSAGE_V2_PROTOCOL_VERSION;
     use crate::peer::utils::is_forward_transaction;
     use crate::types::PeerMessage;
     use borsh::BorshSerialize;
@@ -145,6 +151,8 @@ mod tests {
                 signature,
                 ttl: 99,
                 body: RoutedMessageBody::ForwardTx(tx),
+                num_hops: 0,
+                trace_context: None,
             }
             .into(),
         )
@@ -171,6 +179,8 @@ mod tests {
             let msg = create_tx_forward(s);
             let bytes = msg.try_to_vec().unwrap();
             assert!(is_forward_transaction(&bytes).unwrap());
+            let bytes = msg.serialize_for(ROUTED_MESSAGE_V2_PROTOCOL_VERSION).unwrap();
+            assert!(is_forward_transaction(&bytes).unwrap());
         })
     }
 }
@@ -1,2 +1,3 @@
 pub(crate) mod peer_manager_actor;
 pub(crate) mod peer_store;
+pub(crate) mod request_scheduler;
@@ -1,6 +1,7 @@
 use crate::peer::codec::Codec;
 use crate::peer::peer_actor::PeerActor;
 use crate::peer_manager::peer_store::{PeerStore, TrustLevel};
+use crate::peer_manager::request_scheduler::{PeerRateLimiter, RequestScheduler};
 use crate::private_actix::{
     PeerRequestResult, PeersRequest, RegisterPeer, RegisterPeerResponse, SendMessage, StopMsg,
     Unregister, ValidateEdgeList,
@@ -15,19 +16,21 @@ use crate::stats::metrics::{NetworkMetrics, PARTIAL_ENCODED_CHUNK_REQUEST_DELAY}
 use crate::types::{
     FullPeerInfo, NetworkClientMessages, NetworkInfo, NetworkRequests, NetworkResponses,
     PeerManagerMessageRequest, PeerManagerMessageResponse, PeerMessage, PeerRequest, PeerResponse,
-    PeersResponse, RoutingTableUpdate,
+    PeerStateRequest, PeersCapabilities, PeersResponse, RoutingTableUpdate,
 };
 use actix::{
     Actor, ActorFuture, Addr, Arbiter, AsyncContext, Context, ContextFutureSpawner, Handler,
     Recipient, Running, StreamHandler, WrapFuture,
 };
+use borsh::BorshSerialize;
 #[cfg(feature = "protocol_feature_routing_exchange_algorithm")]
 use futures::FutureExt;
 use near_network_primitives::types::{
-    AccountOrPeerIdOrHash, Ban, Edge, InboundTcpConnect, KnownPeerStatus, KnownProducer,
-    NetworkConfig, NetworkViewClientMessages, NetworkViewClientResponses, OutboundTcpConnect,
-    PeerIdOrHash, PeerInfo, PeerManagerRequest, PeerType, Ping, Pong, QueryPeerStats,
-    RawRoutedMessage, ReasonForBan, RoutedMessage, RoutedMessageBody, RoutedMessageFrom,
+    AccountData, AccountOrPeerIdOrHash, Ban, DroppedRoutedMessage, Edge, InboundTcpConnect,
+    KnownPeerState, KnownProducer, NetworkConfig, NetworkViewClientMessages,
+    NetworkViewClientResponses, OutboundTcpConnect, PeerIdOrHash, PeerInfo, PeerManagerRequest,
+    PeerType, Ping, Pong, QueryPeerStats, RawRoutedMessage, ReasonForBan, RoutedMessage,
+    RoutedMessageBody, RoutedMessageFrom, SignedAccountData, StateRequestRejected,
     StateResponseInfo,
 };
 use near_network_primitives::types::{Blacklist, EdgeState, PartialEdgeInfo};
@@ -36,9 +39,10 @@ use near_performance_metrics_macros::perf;
 use near_primitives::checked_feature;
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::{AnnounceAccount, PeerId};
+use near_primitives::sharding::ChunkHash;
 use near_primitives::time::Clock;
-use near_primitives::types::{AccountId, ProtocolVersion};
-use near_primitives::utils::from_timestamp;
+use near_primitives::types::{AccountId, EpochId, ProtocolVersion};
+use near_primitives::utils::to_timestamp;
 use near_rate_limiter::{
     ActixMessageResponse, ActixMessageWrapper, ThrottleController, ThrottleFramedRead,
     ThrottleToken,
@@ -47,7 +51,7 @@ use near_store::Store;
 use rand::seq::IteratorRandom;
 use rand::thread_rng;
 use std::cmp::{max, min};
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -84,6 +88,12 @@ const WAIT_FOR_SYNC_DELAY: Duration = Duration::from_millis(1_000);
 const UPDATE_ROUTING_TABLE_INTERVAL: Duration = Duration::from_millis(1_000);
 /// How often to report bandwidth stats.
 const REPORT_BANDWIDTH_STATS_TRIGGER_INTERVAL: Duration = Duration::from_millis(60_000);
+/// How often to try to establish missing TIER1 connections.
+const TIER1_CONNECT_INTERVAL: Duration = Duration::from_millis(1_000);
+/// How often should a validator re-advertise its TIER1 accounts data.
+const TIER1_ACCOUNTS_DATA_REFRESH_INTERVAL: Duration = Duration::from_secs(15 * 60);
+/// Number of most recent routed messages dropped because of expired TTL to keep for debugging.
+const MAX_RECENT_DROPPED_ROUTED_MESSAGES: usize = 100;
 
 /// Max number of messages we received from peer, and they are in progress, before we start throttling.
 /// Disabled for now (TODO PUT UNDER FEATURE FLAG)
@@ -187,6 +197,18 @@ pub struct PeerManagerActor {
     peer_counter: Arc<AtomicUsize>,
     /// Used for testing, for disabling features.
     adv_helper: AdvHelper,
+    /// Epoch of the latest set of TIER1 accounts reported by the client.
+    tier1_epoch_id: EpochId,
+    /// Block and chunk producers of the current and next epoch.
+    tier1_accounts: HashSet<AccountId>,
+    /// Latest known data about how to reach validators over TIER1.
+    accounts_data: HashMap<AccountId, SignedAccountData>,
+    /// Most recent routed messages dropped because their TTL has expired.
+    recent_dropped_routed_messages: VecDeque<DroppedRoutedMessage>,
+    /// Throttles state requests from peers and schedules them fairly for the view client.
+    state_request_scheduler: RequestScheduler<PeerStateRequest>,
+    /// Throttles partial encoded chunk requests from peers.
+    chunk_part_request_limiter: PeerRateLimiter,
 }
 
 impl Actor for PeerManagerActor {
@@ -244,6 +266,9 @@ impl Actor for PeerManagerActor {
 
         // Periodically prints bandwidth stats for each peer.
         self.report_bandwidth_stats_trigger(ctx, REPORT_BANDWIDTH_STATS_TRIGGER_INTERVAL);
+
+        // Periodically advertises own TIER1 data and connects to other TIER1 validators.
+        self.tier1_connect_trigger(ctx, TIER1_CONNECT_INTERVAL);
     }
 
     /// Try to gracefully disconnect from connected peers.
@@ -281,6 +306,21 @@ impl PeerManagerActor {
 
         let txns_since_last_block = Arc::new(AtomicUsize::new(0));
 
+        let throttle = &config.request_throttle;
+        let state_request_scheduler = RequestScheduler::new(
+            throttle.state_requests_per_peer_rate,
+            throttle.state_requests_per_peer_burst,
+            throttle.state_requests_global_rate,
+            throttle.state_requests_global_burst,
+            throttle.state_requests_max_queued,
+            throttle.state_requests_max_in_flight,
+            Clock::instant(),
+        );
+        let chunk_part_request_limiter = PeerRateLimiter::new(
+            throttle.chunk_part_requests_per_peer_rate,
+            throttle.chunk_part_requests_per_peer_burst,
+        );
+
         Ok(Self {
             my_peer_id,
             config,
@@ -298,6 +338,12 @@ impl PeerManagerActor {
             txns_since_last_block,
             peer_counter: Arc::new(AtomicUsize::new(0)),
             adv_helper: AdvHelper::default(),
+            tier1_epoch_id: EpochId::default(),
+            tier1_accounts: HashSet::new(),
+            accounts_data: HashMap::new(),
+            recent_dropped_routed_messages: VecDeque::new(),
+            state_request_scheduler,
+            chunk_part_request_limiter,
         })
     }
 
@@ -600,6 +646,14 @@ impl PeerManagerActor {
 
         self.add_verified_edges_to_routing_table(vec![new_edge.clone()]);
 
+        if !self.accounts_data.is_empty() {
+            addr.do_send(SendMessage {
+                message: PeerMessage::SyncAccountsData(
+                    self.accounts_data.values().cloned().collect(),
+                ),
+            });
+        }
+
         checked_feature!(
             "protocol_feature_routing_exchange_algorithm",
             RoutingExchangeAlgorithm,
@@ -757,7 +811,12 @@ impl PeerManagerActor {
     fn ban_peer(&mut self, peer_id: &PeerId, ban_reason: ReasonForBan) {
         warn!(target: "network", ?peer_id, ?ban_reason, "Banning peer");
         self.remove_connected_peer(peer_id, None);
-        if let Err(err) = self.peer_store.peer_ban(peer_id, ban_reason) {
+        // The peer might be banned already, e.g. by the operator with a custom duration, in
+        // which case its instance is stopped with a ban. Keep the existing ban record then.
+        if self.peer_store.is_banned(peer_id) {
+            return;
+        }
+        if let Err(err) = self.peer_store.peer_ban(peer_id, ban_reason, self.config.ban_window) {
             error!(target: "network", ?err, "Failed to save peer data");
         };
     }
@@ -873,10 +932,52 @@ impl PeerManagerActor {
             && !self.config.outbound_disabled
     }
 
+    /// Whether this is an archival node with too few connections to other archival nodes.
+    fn is_archival_peer_needed(&self) -> bool {
+        self.config.archive
+            && (self.connected_peers.values())
+                .filter(|connected_peer| connected_peer.full_peer_info.chain_info.archival)
+                .count()
+                <= self.config.archival_peer_connections_lower_bound as usize
+    }
+
     fn is_inbound_allowed(&self) -> bool {
         self.connected_peers.len() + self.outgoing_peers.len() < self.config.max_num_peers as usize
     }
 
+    /// TIER1 validators are allowed to connect even if the regular connection limit is reached,
+    /// as long as there is at most one extra connection per TIER1 account.
+    fn is_tier1_inbound_allowed(&self) -> bool {
+        self.config.tier1.enable_inbound
+            && self.is_tier1_node()
+            && self.connected_peers.len() + self.outgoing_peers.len()
+                < self.config.max_num_peers as usize + self.tier1_accounts.len()
+    }
+
+    /// Whether this node is a block or chunk producer of the current or next epoch.
+    fn is_tier1_node(&self) -> bool {
+        self.config
+            .account_id
+            .as_ref()
+            .map_or(false, |account_id| self.tier1_accounts.contains(account_id))
+    }
+
+    /// Whether `peer_id` is a TIER1 validator, or a proxy of one.
+    fn is_tier1_peer(&self, peer_id: &PeerId) -> bool {
+        self.accounts_data.values().map(|data| &data.account_data).any(|data| {
+            self.tier1_accounts.contains(&data.account_id)
+                && (&data.peer_id == peer_id || data.proxies.iter().any(|p| &p.id == peer_id))
+        })
+    }
+
+    /// Returns info about a connected peer, along with the capabilities it has announced.
+    fn full_peer_info(&self, connected_peer: &ConnectedPeer) -> FullPeerInfo {
+        let mut full_peer_info = connected_peer.full_peer_info.clone();
+        full_peer_info.capabilities =
+            self.peer_store.announced_capabilities(&full_peer_info.peer_info.id).cloned();
+        full_peer_info
+    }
+
     /// Returns single random peer with close to the highest height
     fn highest_height_peers(&self) -> Vec<FullPeerInfo> {
         // This finds max height among peers, and returns one peer close to such height.
@@ -894,7 +995,7 @@ impl PeerManagerActor {
                 cp.full_peer_info.chain_info.height.saturating_add(self.config.highest_peer_horizon)
                     >= max_height
             })
-            .map(|cp| cp.full_peer_info.clone())
+            .map(|cp| self.full_peer_info(cp))
             .collect::<Vec<_>>()
     }
 
@@ -1082,12 +1183,7 @@ impl PeerManagerActor {
             }
         }
 
-        if self.config.archive
-            && (self.connected_peers.values())
-                .filter(|connected_peer| connected_peer.full_peer_info.chain_info.archival)
-                .count()
-                <= self.config.archival_peer_connections_lower_bound as usize
-        {
+        if self.is_archival_peer_needed() {
             for (peer, active) in self.connected_peers.iter() {
                 if active.full_peer_info.chain_info.archival {
                     safe_set.insert(peer);
@@ -1095,6 +1191,13 @@ impl PeerManagerActor {
             }
         }
 
+        // Keep connections to TIER1 validators and their proxies.
+        for peer_id in self.connected_peers.keys() {
+            if self.is_tier1_peer(peer_id) {
+                safe_set.insert(peer_id);
+            }
+        }
+
         // Find all recent connections
         let mut recent_connections = (self.connected_peers.iter())
             .filter_map(|(peer_id, active)| {
@@ -1140,7 +1243,7 @@ impl PeerManagerActor {
     /// Periodically monitor list of peers and:
     ///  - request new peers from connected peers,
     ///  - bootstrap outbound connections from known peers,
-    ///  - unban peers that have been banned for awhile,
+    ///  - unban peers whose ban has expired,
     ///  - remove expired peers,
     ///
     /// # Arguments:
@@ -1155,32 +1258,40 @@ impl PeerManagerActor {
         mut interval: Duration,
         (default_interval, max_interval): (Duration, Duration),
     ) {
-        let mut to_unban = vec![];
-        for (peer_id, peer_state) in self.peer_store.iter() {
-            if let KnownPeerStatus::Banned(_, last_banned) = peer_state.status {
-                let interval =
-                    (Clock::utc() - from_timestamp(last_banned)).to_std().unwrap_or_default();
-                if interval > self.config.ban_window {
-                    info!(target: "network", unbanned = ?peer_id, after = ?interval, "Monitor peers:");
-                    to_unban.push(peer_id.clone());
+        let now = Clock::instant();
+        self.state_request_scheduler.remove_idle(now);
+        self.chunk_part_request_limiter.remove_idle(now);
+
+        match self.peer_store.unban_expired() {
+            Ok(unbanned) => {
+                for peer_id in unbanned {
+                    info!(target: "network", unbanned = ?peer_id, "Monitor peers: ban expired");
                 }
             }
-        }
-
-        for peer_id in to_unban {
-            if let Err(err) = self.peer_store.peer_unban(&peer_id) {
-                error!(target: "network", ?err, "Failed to unban a peer");
-            }
+            Err(err) => error!(target: "network", ?err, "Failed to unban a peer"),
         }
 
         if self.is_outbound_bootstrap_needed() {
-            if let Some(peer_info) = self.peer_store.unconnected_peer(|peer_state| {
+            let ignore = |peer_state: &KnownPeerState| {
                 // Ignore connecting to ourself
                 self.my_peer_id == peer_state.peer_info.id
                     || self.config.addr == peer_state.peer_info.addr
                     // Or to peers we are currently trying to connect to
                     || self.outgoing_peers.contains(&peer_state.peer_info.id)
-            }) {
+            };
+            // Archival nodes which lack archival connections go for peers known to be archival
+            // first.
+            let archival_peer = if self.is_archival_peer_needed() {
+                self.peer_store.unconnected_peer(|peer_state| {
+                    ignore(peer_state)
+                        || !self.peer_store.is_known_archival(&peer_state.peer_info.id)
+                })
+            } else {
+                None
+            };
+            if let Some(peer_info) =
+                archival_peer.or_else(|| self.peer_store.unconnected_peer(ignore))
+            {
                 // Start monitor_peers_attempts from start after we discover the first healthy peer
                 if !self.started_connect_attempts {
                     self.started_connect_attempts = true;
@@ -1204,6 +1315,7 @@ impl PeerManagerActor {
         if let Err(err) = self.peer_store.remove_expired(&self.config) {
             error!(target: "network", ?err, "Failed to remove expired peers");
         };
+        self.peer_store.remove_stale_capabilities();
 
         let new_interval = min(
             max_interval,
@@ -1383,12 +1495,195 @@ impl PeerManagerActor {
             }
         };
 
+        if msg.is_tier1() {
+            return self.send_tier1_message_to_peer(target, msg);
+        }
+
         let msg = RawRoutedMessage { target: AccountOrPeerIdOrHash::PeerId(target), body: msg };
         self.send_message_to_peer(msg)
     }
 
+    /// Send latency-critical message to `target`, preferring a direct TIER1 connection to it or
+    /// a connection to one of its proxies. Falls back to regular (TIER2) routing otherwise.
+    /// Return whether the message is sent or not.
+    fn send_tier1_message_to_peer(&mut self, target: PeerId, body: RoutedMessageBody) -> bool {
+        let msg_type = strum::AsStaticRef::as_static(&body);
+        let next_hop = if self.connected_peers.contains_key(&target) {
+            Some((target.clone(), "tier1_direct"))
+        } else {
+            (self.accounts_data.values())
+                .filter(|data| data.account_data.peer_id == target)
+                .flat_map(|data| data.account_data.proxies.iter())
+                .find(|proxy| self.connected_peers.contains_key(&proxy.id))
+                .map(|proxy| (proxy.id.clone(), "tier1_proxy"))
+        };
+
+        let raw = RawRoutedMessage { target: AccountOrPeerIdOrHash::PeerId(target), body };
+        match next_hop {
+            Some((peer_id, route)) => {
+                metrics::TIER1_MESSAGES_BY_ROUTE.with_label_values(&[msg_type, route]).inc();
+                let msg = self.sign_routed_message(raw, self.my_peer_id.clone());
+                if msg.expect_response() {
+                    self.routing_table_view.add_route_back(msg.hash(), self.my_peer_id.clone());
+                }
+                Self::send_message(&self.connected_peers, peer_id, PeerMessage::Routed(msg))
+            }
+            None => {
+                metrics::TIER1_MESSAGES_BY_ROUTE
+                    .with_label_values(&[msg_type, "tier2_fallback"])
+                    .inc();
+                self.send_message_to_peer(raw)
+            }
+        }
+    }
+
+    /// Periodically advertises own TIER1 accounts data and tries to establish TIER1 connections
+    /// to the block and chunk producers of the current and next epoch.
+    fn tier1_connect_trigger(&mut self, ctx: &mut Context<Self>, interval: Duration) {
+        if self.is_tier1_node() {
+            self.maybe_broadcast_own_accounts_data();
+            if self.config.tier1.enable_outbound {
+                self.connect_to_tier1_peers(ctx);
+            }
+        }
+        metrics::TIER1_CONNECTIONS.set(
+            self.connected_peers.keys().filter(|peer_id| self.is_tier1_peer(peer_id)).count()
+                as i64,
+        );
+
+        near_performance_metrics::actix::run_later(ctx, interval, move |act, ctx| {
+            act.tier1_connect_trigger(ctx, interval);
+        });
+    }
+
+    /// Signs and broadcasts data about how to reach this node over TIER1, unless recent enough
+    /// data for the current epoch has been broadcast already.
+    fn maybe_broadcast_own_accounts_data(&mut self) {
+        let account_id = match self.config.account_id.as_ref() {
+            Some(account_id) => account_id.clone(),
+            None => return,
+        };
+        let now = to_timestamp(Clock::utc());
+        if let Some(data) = self.accounts_data.get(&account_id).map(|data| &data.account_data) {
+            if data.peer_id == self.my_peer_id
+                && data.epoch_id == self.tier1_epoch_id
+                && now.saturating_sub(data.timestamp)
+                    < TIER1_ACCOUNTS_DATA_REFRESH_INTERVAL.as_nanos() as u64
+            {
+                return;
+            }
+        }
+
+        let proxies = if !self.config.tier1.proxies.is_empty() {
+            self.config.tier1.proxies.clone()
+        } else {
+            // Without explicit proxies, advertise our own address if it is reachable.
+            (self.config.addr.filter(|addr| !addr.ip().is_unspecified()))
+                .map(|addr| PeerInfo {
+                    id: self.my_peer_id.clone(),
+                    addr: Some(addr),
+                    account_id: Some(account_id.clone()),
+                })
+                .into_iter()
+                .collect()
+        };
+        let data = AccountData {
+            account_id: account_id.clone(),
+            peer_id: self.my_peer_id.clone(),
+            epoch_id: self.tier1_epoch_id.clone(),
+            proxies,
+            timestamp: now,
+        }
+        .sign(&self.config.secret_key);
+        debug!(target: "network", ?account_id, proxies = ?data.account_data.proxies, "Broadcasting TIER1 accounts data");
+
+        self.accounts_data.insert(account_id, data.clone());
+        Self::broadcast_message(
+            &self.connected_peers,
+            SendMessage { message: PeerMessage::SyncAccountsData(vec![data]) },
+        );
+    }
+
+    /// Connects to TIER1 validators (or to their proxies) to which this node doesn't have
+    /// a connection yet.
+    fn connect_to_tier1_peers(&mut self, ctx: &mut Context<Self>) {
+        let mut to_connect = vec![];
+        for account_id in self.tier1_accounts.iter() {
+            if Some(account_id) == self.config.account_id.as_ref() {
+                continue;
+            }
+            let data = match self.accounts_data.get(account_id) {
+                Some(data) => &data.account_data,
+                None => continue,
+            };
+            let is_connected_or_connecting = |peer_id: &PeerId| {
+                self.connected_peers.contains_key(peer_id) || self.outgoing_peers.contains(peer_id)
+            };
+            if is_connected_or_connecting(&data.peer_id)
+                || data.proxies.iter().any(|proxy| is_connected_or_connecting(&proxy.id))
+            {
+                continue;
+            }
+            if let Some(proxy) = data
+                .proxies
+                .iter()
+                .find(|proxy| proxy.addr.is_some() && !self.peer_store.is_banned(&proxy.id))
+            {
+                to_connect.push(proxy.clone());
+            }
+        }
+
+        for peer_info in to_connect {
+            debug!(target: "network", ?peer_info, "Establishing TIER1 connection");
+            self.outgoing_peers.insert(peer_info.id.clone());
+            ctx.notify(PeerManagerMessageRequest::OutboundTcpConnect(OutboundTcpConnect {
+                peer_info,
+            }));
+        }
+    }
+
+    /// Validates TIER1 accounts data received from `peer_id`, and broadcasts the new entries.
+    fn handle_accounts_data(&mut self, peer_id: PeerId, accounts_data: Vec<SignedAccountData>) {
+        let mut new_data = vec![];
+        for data in accounts_data {
+            if !data.verify() {
+                metrics::TIER1_ACCOUNTS_DATA_RECEIVED.with_label_values(&["invalid"]).inc();
+                self.try_ban_peer(&peer_id, ReasonForBan::InvalidSignature);
+                return;
+            }
+            let account_id = &data.account_data.account_id;
+            // The account must be announced by the same peer that signed the data.
+            match self.routing_table_view.account_owner(account_id) {
+                Ok(owner) if owner == data.account_data.peer_id => {}
+                _ => {
+                    metrics::TIER1_ACCOUNTS_DATA_RECEIVED.with_label_values(&["unknown"]).inc();
+                    continue;
+                }
+            }
+            if (self.accounts_data.get(account_id))
+                .map_or(false, |old| old.account_data.timestamp >= data.account_data.timestamp)
+            {
+                metrics::TIER1_ACCOUNTS_DATA_RECEIVED.with_label_values(&["outdated"]).inc();
+                continue;
+            }
+            metrics::TIER1_ACCOUNTS_DATA_RECEIVED.with_label_values(&["accepted"]).inc();
+            self.accounts_data.insert(account_id.clone(), data.clone());
+            new_data.push(data);
+        }
+
+        if !new_data.is_empty() {
+            Self::broadcast_message(
+                &self.connected_peers,
+                SendMessage { message: PeerMessage::SyncAccountsData(new_data) },
+            );
+        }
+    }
+
     fn sign_routed_message(&self, msg: RawRoutedMessage, my_peer_id: PeerId) -> Box<RoutedMessage> {
-        msg.sign(my_peer_id, &self.config.secret_key, self.config.routed_message_ttl)
+        let ttl = self.config.routed_message_ttl_for(&msg.body);
+        let mut msg = msg.sign(my_peer_id, &self.config.secret_key, ttl);
+        msg.trace_context = crate::trace_context::current();
+        msg
     }
 
     // Determine if the given target is referring to us.
@@ -1441,10 +1736,15 @@ impl PeerManagerActor {
         self.routing_table_view.add_pong(pong);
     }
 
+    /// TIER1 accounts data known to this node, its own included.
+    pub(crate) fn get_accounts_data(&self) -> Vec<SignedAccountData> {
+        self.accounts_data.values().cloned().collect()
+    }
+
     pub(crate) fn get_network_info(&self) -> NetworkInfo {
         NetworkInfo {
             connected_peers: (self.connected_peers.values())
-                .map(|cp| cp.full_peer_info.clone())
+                .map(|cp| self.full_peer_info(cp))
                 .collect(),
             num_connected_peers: self.connected_peers.len(),
             peer_max_count: self.config.max_num_peers,
@@ -1461,9 +1761,17 @@ impl PeerManagerActor {
                     peer_id: announce_account.peer_id.clone(),
                     // TODO: fill in the address.
                     addr: None,
+                    reachable: announce_account.peer_id == self.my_peer_id
+                        || self.routing_table_view.has_route(&announce_account.peer_id),
                 })
                 .collect(),
             peer_counter: self.peer_counter.load(Ordering::SeqCst),
+            banned_peers: self.peer_store.banned_peers(),
+            recent_dropped_routed_messages: self
+                .recent_dropped_routed_messages
+                .iter()
+                .cloned()
+                .collect(),
         }
     }
 
@@ -1525,6 +1833,17 @@ impl PeerManagerActor {
                     NetworkResponses::RouteNotFound
                 }
             }
+            NetworkRequests::BlockHeadersByHeightRequest { start_height, end_height, peer_id } => {
+                if Self::send_message(
+                    &self.connected_peers,
+                    peer_id,
+                    PeerMessage::BlockHeadersByHeightRequest(start_height, end_height),
+                ) {
+                    NetworkResponses::NoResponse
+                } else {
+                    NetworkResponses::RouteNotFound
+                }
+            }
             NetworkRequests::StateRequestHeader { shard_id, sync_hash, target } => {
                 if self.send_message_to_account_or_peer_or_hash(
                     &target,
@@ -1592,6 +1911,8 @@ impl PeerManagerActor {
                 NetworkResponses::NoResponse
             }
             NetworkRequests::PartialEncodedChunkRequest { target, request, create_time } => {
+                let _span =
+                    send_partial_chunk_span(&request.chunk_hash, "PartialEncodedChunkRequest");
                 PARTIAL_ENCODED_CHUNK_REQUEST_DELAY.observe(create_time.0.elapsed().as_secs_f64());
                 let mut success = false;
 
@@ -1647,6 +1968,8 @@ impl PeerManagerActor {
                 }
             }
             NetworkRequests::PartialEncodedChunkResponse { route_back, response } => {
+                let _span =
+                    send_partial_chunk_span(&response.chunk_hash, "PartialEncodedChunkResponse");
                 if self.send_message_to_peer(RawRoutedMessage {
                     target: AccountOrPeerIdOrHash::Hash(route_back),
                     body: RoutedMessageBody::PartialEncodedChunkResponse(response),
@@ -1657,6 +1980,10 @@ impl PeerManagerActor {
                 }
             }
             NetworkRequests::PartialEncodedChunkMessage { account_id, partial_encoded_chunk } => {
+                let _span = send_partial_chunk_span(
+                    &partial_encoded_chunk.header.chunk_hash(),
+                    "PartialEncodedChunk",
+                );
                 if self.send_message_to_account(&account_id, partial_encoded_chunk.into()) {
                     NetworkResponses::NoResponse
                 } else {
@@ -1664,6 +1991,8 @@ impl PeerManagerActor {
                 }
             }
             NetworkRequests::PartialEncodedChunkForward { account_id, forward } => {
+                let _span =
+                    send_partial_chunk_span(&forward.chunk_hash, "PartialEncodedChunkForward");
                 if self.send_message_to_account(
                     &account_id,
                     RoutedMessageBody::PartialEncodedChunkForward(forward),
@@ -1802,6 +2131,36 @@ impl PeerManagerActor {
                     NetworkResponses::BanPeer(ReasonForBan::InvalidEdge)
                 }
             }
+            NetworkRequests::SyncAccountsData { peer_id, accounts_data } => {
+                self.handle_accounts_data(peer_id, accounts_data);
+                NetworkResponses::NoResponse
+            }
+            NetworkRequests::SetTier1Accounts { epoch_id, accounts } => {
+                debug!(target: "network", ?epoch_id, num_accounts = accounts.len(), "Set TIER1 accounts");
+                self.tier1_epoch_id = epoch_id;
+                self.tier1_accounts = accounts.into_iter().collect();
+                NetworkResponses::NoResponse
+            }
+            NetworkRequests::BanPeerManually { peer_id, ban_duration } => {
+                let ban_duration = ban_duration.unwrap_or(self.config.ban_window);
+                warn!(target: "network", ?peer_id, ?ban_duration, "Banning peer manually");
+                if let Err(err) =
+                    self.peer_store.peer_ban(&peer_id, ReasonForBan::Manual, ban_duration)
+                {
+                    error!(target: "network", ?err, "Failed to save peer data");
+                }
+                if let Some(peer) = self.connected_peers.get(&peer_id) {
+                    peer.addr.do_send(PeerManagerRequest::BanPeer(ReasonForBan::Manual));
+                }
+                NetworkResponses::NoResponse
+            }
+            NetworkRequests::UnbanPeer { peer_id } => {
+                info!(target: "network", ?peer_id, "Unbanning peer manually");
+                if let Err(err) = self.peer_store.peer_unban(&peer_id) {
+                    warn!(target: "network", ?err, "Failed to unban peer");
+                }
+                NetworkResponses::NoResponse
+            }
             NetworkRequests::ResponseUpdateNonce(edge) => {
                 if let Some(other_peer) = edge.other(&self.my_peer_id) {
                     if edge.verify() {
@@ -1908,7 +2267,9 @@ impl PeerManagerActor {
     fn handle_msg_inbound_tcp_connect(&self, msg: InboundTcpConnect, ctx: &mut Context<Self>) {
         let _d = delay_detector::DelayDetector::new(|| "inbound tcp connect".into());
 
-        if self.is_inbound_allowed() {
+        // The peer is not known before the handshake, so if we are over the regular limit, the
+        // connection is accepted tentatively and rejected on registration unless it is TIER1.
+        if self.is_inbound_allowed() || self.is_tier1_inbound_allowed() {
             self.try_connect_peer(ctx.address(), msg.stream, PeerType::Inbound, None, None);
         } else {
             // TODO(1896): Gracefully drop inbound connection for other peer.
@@ -2008,7 +2369,10 @@ impl PeerManagerActor {
             }
         }
 
-        if msg.peer_type == PeerType::Inbound && !self.is_inbound_allowed() {
+        if msg.peer_type == PeerType::Inbound
+            && !self.is_inbound_allowed()
+            && !(self.is_tier1_inbound_allowed() && self.is_tier1_peer(&msg.peer_info.id))
+        {
             // TODO(1896): Gracefully drop inbound connection for other peer.
             debug!(target: "network",
                 connected_peers = self.connected_peers.len(), outgoing_peers = self.outgoing_peers.len(),
@@ -2052,6 +2416,7 @@ impl PeerManagerActor {
                 peer_info: msg.peer_info,
                 chain_info: msg.chain_info,
                 partial_edge_info: msg.other_edge_info,
+                capabilities: None,
             },
             edge_info,
             msg.peer_type,
@@ -2079,9 +2444,9 @@ impl PeerManagerActor {
     #[perf]
     fn handle_msg_peers_request(&self, _msg: PeersRequest) -> PeerRequestResult {
         let _d = delay_detector::DelayDetector::new(|| "peers request".into());
-        PeerRequestResult {
-            peers: self.peer_store.healthy_peers(self.config.max_send_peers as usize),
-        }
+        let peers = self.peer_store.healthy_peers(self.config.max_send_peers as usize);
+        let capabilities = self.peer_store.known_capabilities(&peers);
+        PeerRequestResult { peers, capabilities }
     }
 
     fn handle_msg_peers_response(&mut self, msg: PeersResponse) {
@@ -2093,6 +2458,20 @@ impl PeerManagerActor {
         };
     }
 
+    /// Records capabilities received from a peer.  Only the ones the peer announced about itself
+    /// are trusted.
+    fn handle_msg_peers_capabilities(&mut self, msg: PeersCapabilities) {
+        let _d = delay_detector::DelayDetector::new(|| "peers capabilities".into());
+        for (peer_id, capabilities) in msg.capabilities {
+            if peer_id == self.my_peer_id {
+                continue;
+            }
+            let trust_level =
+                if peer_id == msg.from { TrustLevel::Signed } else { TrustLevel::Indirect };
+            self.peer_store.set_capabilities(peer_id, capabilities, trust_level);
+        }
+    }
+
     fn handle_peer_manager_message(
         &mut self,
         msg: PeerManagerMessageRequest,
@@ -2122,8 +2501,12 @@ impl PeerManagerActor {
                 self.handle_msg_peers_response(msg);
                 PeerManagerMessageResponse::PeersResponseResult(())
             }
+            PeerManagerMessageRequest::PeersCapabilities(msg) => {
+                self.handle_msg_peers_capabilities(msg);
+                PeerManagerMessageResponse::PeersCapabilitiesResult(())
+            }
             PeerManagerMessageRequest::PeerRequest(msg) => {
-                PeerManagerMessageResponse::PeerResponse(self.handle_msg_peer_request(msg))
+                PeerManagerMessageResponse::PeerResponse(self.handle_msg_peer_request(msg, ctx))
             }
             #[cfg(feature = "test_features")]
             PeerManagerMessageRequest::GetPeerId(msg) => {
@@ -2183,6 +2566,17 @@ impl PeerManagerActor {
         }
 
         if Self::message_for_me(&mut self.routing_table_view, &self.my_peer_id, &msg.target) {
+            metrics::ROUTED_MESSAGE_HOPS
+                .with_label_values(&[strum::AsStaticRef::as_static(&msg.body)])
+                .observe(msg.num_hops as f64);
+            if let RoutedMessageBody::PartialEncodedChunkRequest(_) = &msg.body {
+                let now = Clock::instant();
+                if self.chunk_part_request_limiter.try_acquire(&msg.author, now).is_err() {
+                    debug!(target: "network", author = ?msg.author, "Dropping partial encoded chunk request because of rate limit");
+                    metrics::CHUNK_PART_REQUEST_THROTTLED.inc();
+                    return false;
+                }
+            }
             // Handle Ping and Pong message if they are for us without sending to client.
             // i.e. Return false in case of Ping and Pong
             match &msg.body {
@@ -2197,12 +2591,33 @@ impl PeerManagerActor {
                 self.send_signed_message_to_peer(msg);
             } else {
                 warn!(target: "network", ?msg, ?from, "Message dropped because TTL reached 0.");
+                self.record_dropped_routed_message(&msg, from);
             }
             false
         }
     }
 
-    fn handle_msg_peer_request(&mut self, msg: PeerRequest) -> PeerResponse {
+    fn record_dropped_routed_message(&mut self, msg: &RoutedMessage, from: PeerId) {
+        let msg_type = strum::AsStaticRef::as_static(&msg.body);
+        metrics::ROUTED_MESSAGE_TTL_DROPPED.with_label_values(&[msg_type]).inc();
+        if self.recent_dropped_routed_messages.len() >= MAX_RECENT_DROPPED_ROUTED_MESSAGES {
+            self.recent_dropped_routed_messages.pop_front();
+        }
+        self.recent_dropped_routed_messages.push_back(DroppedRoutedMessage {
+            msg_type,
+            author: msg.author.clone(),
+            target: msg.target.clone(),
+            from,
+            num_hops: msg.num_hops,
+            dropped_at: to_timestamp(Clock::utc()),
+        });
+    }
+
+    fn handle_msg_peer_request(
+        &mut self,
+        msg: PeerRequest,
+        ctx: &mut Context<Self>,
+    ) -> PeerResponse {
         let _d =
             delay_detector::DelayDetector::new(|| format!("peer request {}", msg.as_ref()).into());
         match msg {
@@ -2229,6 +2644,85 @@ impl PeerManagerActor {
                 }
                 PeerResponse::NoResponse
             }
+            PeerRequest::StateRequest(request) => {
+                self.schedule_state_request(request, ctx);
+                PeerResponse::NoResponse
+            }
+        }
+    }
+
+    /// Queues a state request from a peer, or tells the peer to retry later if it exceeded its
+    /// rate limit or we are overloaded.
+    fn schedule_state_request(&mut self, request: PeerStateRequest, ctx: &mut Context<Self>) {
+        let peer_id = request.peer_id.clone();
+        let (shard_id, sync_hash, part_id, msg_hash) =
+            (request.shard_id, request.sync_hash, request.part_id, request.msg_hash);
+        match self.state_request_scheduler.try_submit(&peer_id, request, Clock::instant()) {
+            Ok(()) => self.dispatch_state_requests(ctx),
+            Err(rejection) => {
+                debug!(target: "network", ?peer_id, shard_id, ?part_id, ?rejection, "Rejecting state request");
+                metrics::STATE_REQUEST_REJECTED
+                    .with_label_values(&[rejection.reason.as_str()])
+                    .inc();
+                let rejected = StateRequestRejected {
+                    shard_id,
+                    sync_hash,
+                    part_id,
+                    retry_after_ms: rejection.retry_after.as_millis() as u64,
+                };
+                self.send_message_to_peer(RawRoutedMessage {
+                    target: AccountOrPeerIdOrHash::Hash(msg_hash),
+                    body: RoutedMessageBody::StateRequestRejected(rejected),
+                });
+            }
+        }
+        metrics::STATE_REQUEST_QUEUE_DEPTH.set(self.state_request_scheduler.queued() as i64);
+    }
+
+    /// Sends queued state requests to the view client, as long as the number of requests in
+    /// flight allows it, and routes the responses back to the requesting peers.
+    fn dispatch_state_requests(&mut self, ctx: &mut Context<Self>) {
+        while let Some((_, request)) = self.state_request_scheduler.next() {
+            let PeerStateRequest { shard_id, sync_hash, part_id, msg_hash, .. } = request;
+            let msg = match part_id {
+                None => NetworkViewClientMessages::StateRequestHeader { shard_id, sync_hash },
+                Some(part_id) => {
+                    NetworkViewClientMessages::StateRequestPart { shard_id, sync_hash, part_id }
+                }
+            };
+            self.view_client_addr
+                .send(msg)
+                .into_actor(self)
+                .then(move |res, act, ctx| {
+                    act.state_request_scheduler.finish();
+                    match res {
+                        Ok(NetworkViewClientResponses::StateResponse(state_response)) => {
+                            let body = match *state_response {
+                                StateResponseInfo::V1(state_response) => {
+                                    RoutedMessageBody::StateResponse(state_response)
+                                }
+                                state_response @ StateResponseInfo::V2(_) => {
+                                    RoutedMessageBody::VersionedStateResponse(state_response)
+                                }
+                            };
+                            let size = body.try_to_vec().map_or(0, |bytes| bytes.len());
+                            metrics::STATE_REQUEST_SERVED_BYTES.inc_by(size as u64);
+                            act.send_message_to_peer(RawRoutedMessage {
+                                target: AccountOrPeerIdOrHash::Hash(msg_hash),
+                                body,
+                            });
+                        }
+                        Ok(_) => {}
+                        Err(err) => {
+                            error!(target: "network", ?err, "Failed sending state request to view client");
+                        }
+                    }
+                    act.dispatch_state_requests(ctx);
+                    metrics::STATE_REQUEST_QUEUE_DEPTH
+                        .set(act.state_request_scheduler.queued() as i64);
+                    actix::fut::ready(())
+                })
+                .spawn(ctx);
         }
     }
 
@@ -2274,6 +2768,16 @@ impl PeerManagerActor {
     }
 }
 
+/// Span of sending a message with parts of a chunk.  The messages signed in it carry its
+/// context, so that the span receiving them on the other node belongs to the same trace.
+fn send_partial_chunk_span(
+    chunk_hash: &ChunkHash,
+    body: &'static str,
+) -> tracing::span::EnteredSpan {
+    tracing::info_span!(target: "network", "send_partial_chunk", chunk_hash = %chunk_hash.0, body)
+        .entered()
+}
+
 impl Handler<ActixMessageWrapper<PeerManagerMessageRequest>> for PeerManagerActor {
     type Result = ActixMessageResponse<PeerManagerMessageResponse>;
 
@@ -1,9 +1,10 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use near_network_primitives::types::{
-    Blacklist, KnownPeerState, KnownPeerStatus, NetworkConfig, PeerInfo, ReasonForBan,
+    BannedPeerInfo, Blacklist, KnownPeerState, KnownPeerStatus, NetworkConfig, PeerCapabilities,
+    PeerInfo, ReasonForBan,
 };
 use near_primitives::network::PeerId;
-use near_primitives::time::{Clock, Utc};
+use near_primitives::time::{Clock, Instant, Utc};
 use near_primitives::utils::to_timestamp;
 use near_store::{ColPeers, Store};
 use rand::seq::IteratorRandom;
@@ -12,7 +13,7 @@ use std::collections::hash_map::{Entry, Iter};
 use std::collections::HashMap;
 use std::error::Error;
 use std::net::SocketAddr;
-use std::ops::Not;
+use std::time::Duration;
 use tracing::{debug, error, info};
 
 /// Level of trust we have about a new (PeerId, Addr) pair.
@@ -41,6 +42,24 @@ impl VerifiedPeer {
     }
 }
 
+/// Capabilities of a peer are forgotten if they haven't been refreshed for this long.  Connected
+/// peers re-announce their capabilities every `CAPABILITIES_ANNOUNCE_INTERVAL`.
+pub(crate) const PEER_CAPABILITIES_TTL: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Debug, Clone)]
+struct CapabilitiesRecord {
+    capabilities: PeerCapabilities,
+    /// `Signed` if announced by the peer itself, `Indirect` if gossiped by another peer.
+    trust_level: TrustLevel,
+    updated: Instant,
+}
+
+impl CapabilitiesRecord {
+    fn is_fresh(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.updated) < PEER_CAPABILITIES_TTL
+    }
+}
+
 /// Known peers store, maintaining cache of known peers and connection to storage to save/load them.
 pub struct PeerStore {
     store: Store,
@@ -50,6 +69,9 @@ pub struct PeerStore {
     // they will not be present in this list, otherwise they will be present.
     addr_peers: HashMap<SocketAddr, VerifiedPeer>,
     blacklist: Blacklist,
+    /// Capabilities of peers.  They are kept in memory only, as they would be stale after a
+    /// restart anyway.
+    capabilities: HashMap<PeerId, CapabilitiesRecord>,
 }
 
 impl PeerStore {
@@ -98,9 +120,10 @@ impl PeerStore {
             // Mark loaded node last seen to now, to avoid deleting them as soon as they are loaded.
             let last_seen = now;
 
-            // If it’s already banned, keep it banned.  If it’s blacklisted, ban
-            // it.  Otherwise, it’s not connected.
-            let status = if peer_state.status.is_banned() {
+            // If it’s still banned, keep it banned.  If it’s blacklisted, ban
+            // it.  Otherwise, it’s not connected.  Expired bans are dropped here
+            // rather than being carried over.
+            let status = if peer_state.status.is_banned_at(now) {
                 peer_state.status
             } else {
                 let is_blacklisted = peer_state
@@ -111,7 +134,11 @@ impl PeerStore {
                 if is_blacklisted {
                     info!(target: "network", "Banning {:?} because address is blacklisted",
                           peer_state.peer_info);
-                    KnownPeerStatus::Banned(ReasonForBan::Blacklisted, now)
+                    KnownPeerStatus::Banned {
+                        reason: ReasonForBan::Blacklisted,
+                        banned_at: now,
+                        expires_at: u64::MAX,
+                    }
                 } else {
                     KnownPeerStatus::NotConnected
                 }
@@ -134,7 +161,11 @@ impl PeerStore {
                 }
                 // Peer is not a boot node
                 Entry::Vacant(entry) => {
-                    if let Some(peer_addr) = peer_state.peer_info.addr {
+                    if peer_state.peer_info.addr.is_none() && peer_state.status.is_banned() {
+                        // Peer was banned before we learnt its address (e.g. manually by the
+                        // operator); keep the ban record so that the ban survives restarts.
+                        entry.insert(peer_state);
+                    } else if let Some(peer_addr) = peer_state.peer_info.addr {
                         if let Entry::Vacant(entry2) = addr_2_peer.entry(peer_addr) {
                             // Default case, add new entry.
                             entry2.insert(VerifiedPeer::new(peer_state.peer_info.id.clone()));
@@ -146,7 +177,13 @@ impl PeerStore {
                 }
             }
         }
-        Ok(PeerStore { store, peer_states: peerid_2_state, addr_peers: addr_2_peer, blacklist })
+        Ok(PeerStore {
+            store,
+            peer_states: peerid_2_state,
+            addr_peers: addr_2_peer,
+            blacklist,
+            capabilities: HashMap::default(),
+        })
     }
 
     pub fn is_blacklisted(&self, addr: &SocketAddr) -> bool {
@@ -157,10 +194,13 @@ impl PeerStore {
         self.peer_states.len()
     }
 
+    /// Whether the peer is currently banned.  Bans which have already expired, but haven't been
+    /// cleaned up yet by [`PeerStore::unban_expired`], are not taken into account.
     pub(crate) fn is_banned(&self, peer_id: &PeerId) -> bool {
+        let now = to_timestamp(Utc::now());
         self.peer_states
             .get(peer_id)
-            .map_or(false, |known_peer_state| known_peer_state.status.is_banned())
+            .map_or(false, |known_peer_state| known_peer_state.status.is_banned_at(now))
     }
 
     pub(crate) fn peer_connected(
@@ -187,18 +227,23 @@ impl PeerStore {
         }
     }
 
+    /// Bans the peer for `ban_duration`.  Peers which are not known yet are added to the store,
+    /// so that a peer can be banned before we connect to it.
     pub(crate) fn peer_ban(
         &mut self,
         peer_id: &PeerId,
         ban_reason: ReasonForBan,
+        ban_duration: Duration,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        if let Some(peer_state) = self.peer_states.get_mut(peer_id) {
-            peer_state.last_seen = to_timestamp(Utc::now());
-            peer_state.status = KnownPeerStatus::Banned(ban_reason, to_timestamp(Utc::now()));
-            Self::save_to_db(&self.store, peer_id.try_to_vec()?.as_slice(), peer_state)
-        } else {
-            Err(format!("Peer {} is missing in the peer store", peer_id).into())
-        }
+        let now = Clock::utc();
+        let banned_at = to_timestamp(now);
+        let expires_at = banned_at.saturating_add(ban_duration.as_nanos() as u64);
+        let peer_state = self.peer_states.entry(peer_id.clone()).or_insert_with(|| {
+            KnownPeerState::new(PeerInfo { id: peer_id.clone(), addr: None, account_id: None }, now)
+        });
+        peer_state.last_seen = banned_at;
+        peer_state.status = KnownPeerStatus::Banned { reason: ban_reason, banned_at, expires_at };
+        Self::save_to_db(&self.store, peer_id.try_to_vec()?.as_slice(), peer_state)
     }
 
     fn save_to_db(
@@ -223,6 +268,42 @@ impl PeerStore {
         }
     }
 
+    /// Removes the ban from all peers whose ban has expired.  Returns ids of unbanned peers.
+    pub(crate) fn unban_expired(&mut self) -> Result<Vec<PeerId>, Box<dyn std::error::Error>> {
+        let now = to_timestamp(Utc::now());
+        let expired: Vec<PeerId> = self
+            .peer_states
+            .iter()
+            .filter(|(_, peer_state)| {
+                peer_state.status.is_banned() && !peer_state.status.is_banned_at(now)
+            })
+            .map(|(peer_id, _)| peer_id.clone())
+            .collect();
+        for peer_id in expired.iter() {
+            self.peer_unban(peer_id)?;
+        }
+        Ok(expired)
+    }
+
+    /// Returns all peers which are currently banned.
+    pub(crate) fn banned_peers(&self) -> Vec<BannedPeerInfo> {
+        let now = to_timestamp(Utc::now());
+        self.peer_states
+            .values()
+            .filter_map(|peer_state| match peer_state.status {
+                KnownPeerStatus::Banned { reason, banned_at, expires_at } if now < expires_at => {
+                    Some(BannedPeerInfo {
+                        peer_info: peer_state.peer_info.clone(),
+                        reason,
+                        banned_at,
+                        expires_at,
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Find a random subset of peers based on filter.
     fn find_peers<F>(&self, filter: F, count: usize) -> Vec<PeerInfo>
     where
@@ -256,7 +337,8 @@ impl PeerStore {
 
     /// Return healthy known peers up to given amount.
     pub(crate) fn healthy_peers(&self, max_count: usize) -> Vec<PeerInfo> {
-        self.find_peers(|p| matches!(p.status, KnownPeerStatus::Banned(_, _)).not(), max_count)
+        let now = to_timestamp(Utc::now());
+        self.find_peers(|p| !p.status.is_banned_at(now), max_count)
     }
 
     /// Return iterator over all known peers.
@@ -270,10 +352,13 @@ impl PeerStore {
         config: &NetworkConfig,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let now = Utc::now();
+        let now_timestamp = to_timestamp(now);
         let mut to_remove = vec![];
         for (peer_id, peer_status) in self.peer_states.iter() {
             let diff = (now - peer_status.last_seen()).to_std()?;
+            // Peers which are still banned are kept so that the ban isn't lost.
             if peer_status.status != KnownPeerStatus::Connected
+                && !peer_status.status.is_banned_at(now_timestamp)
                 && diff > config.peer_expiration_duration
             {
                 debug!(target: "network", "Removing peer: last seen {:?}", diff);
@@ -415,6 +500,61 @@ impl PeerStore {
         Ok(())
     }
 
+    /// Records capabilities of a peer.  Capabilities gossiped by other peers don't override fresh
+    /// ones announced by the peer itself.
+    pub(crate) fn set_capabilities(
+        &mut self,
+        peer_id: PeerId,
+        capabilities: PeerCapabilities,
+        trust_level: TrustLevel,
+    ) {
+        let now = Clock::instant();
+        if trust_level != TrustLevel::Signed {
+            if let Some(record) = self.capabilities.get(&peer_id) {
+                if record.trust_level == TrustLevel::Signed && record.is_fresh(now) {
+                    return;
+                }
+            }
+        }
+        self.capabilities
+            .insert(peer_id, CapabilitiesRecord { capabilities, trust_level, updated: now });
+    }
+
+    /// Capabilities announced by the peer itself, unless they are stale.
+    pub(crate) fn announced_capabilities(&self, peer_id: &PeerId) -> Option<&PeerCapabilities> {
+        let now = Clock::instant();
+        self.capabilities
+            .get(peer_id)
+            .filter(|record| record.trust_level == TrustLevel::Signed && record.is_fresh(now))
+            .map(|record| &record.capabilities)
+    }
+
+    /// Whether the peer is known to be archival, either from its own announcement or from gossip.
+    pub(crate) fn is_known_archival(&self, peer_id: &PeerId) -> bool {
+        let now = Clock::instant();
+        self.capabilities
+            .get(peer_id)
+            .map_or(false, |record| record.is_fresh(now) && record.capabilities.archival())
+    }
+
+    /// Fresh capabilities of the given peers, to be gossiped along with them.
+    pub(crate) fn known_capabilities(&self, peers: &[PeerInfo]) -> Vec<(PeerId, PeerCapabilities)> {
+        let now = Clock::instant();
+        peers
+            .iter()
+            .filter_map(|peer_info| {
+                let record = self.capabilities.get(&peer_info.id)?;
+                record.is_fresh(now).then(|| (peer_info.id.clone(), record.capabilities.clone()))
+            })
+            .collect()
+    }
+
+    /// Forgets capabilities which haven't been refreshed for [`PEER_CAPABILITIES_TTL`].
+    pub(crate) fn remove_stale_capabilities(&mut self) {
+        let now = Clock::instant();
+        self.capabilities.retain(|_, record| record.is_fresh(now));
+    }
+
     /// Adds a peer into the store with given trust level.  To add indirect
     /// peers, use [`add_indirect_peers`] instead.
     pub(crate) fn add_trusted_peer(
@@ -440,6 +580,8 @@ where
 #[cfg(test)]
 mod test {
     use near_crypto::{KeyType, SecretKey};
+    use near_network_primitives::types::PeerCapabilitiesV2;
+    use near_primitives::time::MockClockGuard;
     use near_store::create_store;
     use near_store::test_utils::create_test_store;
     use std::collections::HashSet;
@@ -477,7 +619,9 @@ mod test {
             let store = create_store(tmp_dir.path());
             let mut peer_store = PeerStore::new(store, &boot_nodes, Default::default()).unwrap();
             assert_eq!(peer_store.healthy_peers(3).len(), 2);
-            peer_store.peer_ban(&peer_info_to_ban.id, ReasonForBan::Abusive).unwrap();
+            peer_store
+                .peer_ban(&peer_info_to_ban.id, ReasonForBan::Abusive, Duration::from_secs(3600))
+                .unwrap();
             assert_eq!(peer_store.healthy_peers(3).len(), 1);
         }
         {
@@ -485,6 +629,53 @@ mod test {
             let peer_store_new =
                 PeerStore::new(store_new, &boot_nodes, Default::default()).unwrap();
             assert_eq!(peer_store_new.healthy_peers(3).len(), 1);
+            let banned = peer_store_new.banned_peers();
+            assert_eq!(banned.len(), 1);
+            assert_eq!(banned[0].peer_info.id, peer_info_to_ban.id);
+            assert_eq!(banned[0].reason, ReasonForBan::Abusive);
+        }
+    }
+
+    #[test]
+    fn ban_expiry() {
+        let store = create_test_store();
+        let peer_info_a = gen_peer_info(0);
+        let peer_info_b = gen_peer_info(1);
+        let boot_nodes = vec![peer_info_a.clone(), peer_info_b.clone()];
+        let mut peer_store = PeerStore::new(store, &boot_nodes, Default::default()).unwrap();
+
+        peer_store.peer_ban(&peer_info_a.id, ReasonForBan::Abusive, Duration::ZERO).unwrap();
+        peer_store
+            .peer_ban(&peer_info_b.id, ReasonForBan::InvalidBlock, Duration::from_secs(3600))
+            .unwrap();
+        // Ban of `a` has already expired, so only `b` is treated as banned.
+        assert!(!peer_store.is_banned(&peer_info_a.id));
+        assert!(peer_store.is_banned(&peer_info_b.id));
+        assert_eq!(peer_store.healthy_peers(3), vec![peer_info_a.clone()]);
+
+        assert_eq!(peer_store.unban_expired().unwrap(), vec![peer_info_a.id.clone()]);
+        assert_eq!(peer_store.peer_states[&peer_info_a.id].status, KnownPeerStatus::NotConnected);
+        assert!(peer_store.peer_states[&peer_info_b.id].status.is_banned());
+
+        peer_store.peer_unban(&peer_info_b.id).unwrap();
+        assert!(!peer_store.is_banned(&peer_info_b.id));
+        assert!(peer_store.banned_peers().is_empty());
+    }
+
+    /// Peers which we've never seen can be banned as well and the ban is kept across restarts.
+    #[test]
+    fn ban_unknown_peer() {
+        let store = create_test_store();
+        let peer_id = get_peer_id("node0".to_string());
+        {
+            let mut peer_store = PeerStore::new(store.clone(), &[], Default::default()).unwrap();
+            peer_store.peer_ban(&peer_id, ReasonForBan::Manual, Duration::from_secs(3600)).unwrap();
+            assert!(peer_store.is_banned(&peer_id));
+        }
+        {
+            let peer_store = PeerStore::new(store, &[], Default::default()).unwrap();
+            assert!(peer_store.is_banned(&peer_id));
+            assert_eq!(peer_store.banned_peers()[0].reason, ReasonForBan::Manual);
         }
     }
 
@@ -502,6 +693,45 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_peer_capabilities() {
+        let store = create_test_store();
+        let peer_info = gen_peer_info(0);
+        let mut peer_store =
+            PeerStore::new(store, &[peer_info.clone()], Default::default()).unwrap();
+        let announced = PeerCapabilities::V2(PeerCapabilitiesV2 {
+            archival: true,
+            tracked_shards: vec![0],
+            serves_state_sync: true,
+            serves_header_ranges: true,
+        });
+        let gossiped = PeerCapabilities::default();
+
+        // Gossiped capabilities are passed on, but aren't taken as the peer's announcement.
+        peer_store.set_capabilities(peer_info.id.clone(), gossiped.clone(), TrustLevel::Indirect);
+        assert_eq!(peer_store.announced_capabilities(&peer_info.id), None);
+        assert_eq!(
+            peer_store.known_capabilities(&[peer_info.clone()]),
+            vec![(peer_info.id.clone(), gossiped.clone())]
+        );
+
+        // Gossip doesn't override a fresh announcement.
+        peer_store.set_capabilities(peer_info.id.clone(), announced.clone(), TrustLevel::Signed);
+        peer_store.set_capabilities(peer_info.id.clone(), gossiped, TrustLevel::Indirect);
+        assert_eq!(peer_store.announced_capabilities(&peer_info.id), Some(&announced));
+        assert!(peer_store.is_known_archival(&peer_info.id));
+
+        // Capabilities which weren't refreshed age out.
+        let mock_clock_guard = MockClockGuard::default();
+        for _ in 0..3 {
+            mock_clock_guard.add_instant(Instant::now() + PEER_CAPABILITIES_TTL);
+        }
+        assert_eq!(peer_store.announced_capabilities(&peer_info.id), None);
+        assert!(!peer_store.is_known_archival(&peer_info.id));
+        peer_store.remove_stale_capabilities();
+        assert!(peer_store.capabilities.is_empty());
+    }
+
     fn check_exist(
         peer_store: &PeerStore,
         peer_id: &PeerId,
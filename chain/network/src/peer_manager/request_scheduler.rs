@@ -0,0 +1,308 @@
+use near_primitives::network::PeerId;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Token bucket which allows `rate` events per second on average with bursts of up to
+/// `burst` events.
+#[derive(Debug)]
+pub(crate) struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(rate: f64, burst: u32, now: Instant) -> Self {
+        Self { rate, burst: burst as f64, tokens: burst as f64, last_refill: now }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.last_refill = now;
+    }
+
+    /// Returns `Ok` if a token is available, otherwise how long to wait until it will be.
+    fn check(&mut self, now: Instant) -> Result<(), Duration> {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+        }
+    }
+
+    /// Takes a token if one is available, otherwise returns how long to wait until it will be.
+    pub(crate) fn try_acquire(&mut self, now: Instant) -> Result<(), Duration> {
+        self.check(now)?;
+        self.tokens -= 1.0;
+        Ok(())
+    }
+
+    /// Whether the bucket is full, i.e. it doesn't carry any information about past events.
+    fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= self.burst
+    }
+}
+
+/// Per-peer token buckets with the same parameters.
+pub(crate) struct PeerRateLimiter {
+    rate: f64,
+    burst: u32,
+    buckets: HashMap<PeerId, TokenBucket>,
+}
+
+impl PeerRateLimiter {
+    pub(crate) fn new(rate: f64, burst: u32) -> Self {
+        Self { rate, burst, buckets: HashMap::new() }
+    }
+
+    pub(crate) fn try_acquire(&mut self, peer_id: &PeerId, now: Instant) -> Result<(), Duration> {
+        let (rate, burst) = (self.rate, self.burst);
+        self.buckets
+            .entry(peer_id.clone())
+            .or_insert_with(|| TokenBucket::new(rate, burst, now))
+            .try_acquire(now)
+    }
+
+    /// Forgets peers which haven't made any requests recently.
+    pub(crate) fn remove_idle(&mut self, now: Instant) {
+        self.buckets.retain(|_, bucket| !bucket.is_full(now));
+    }
+}
+
+/// Why a request was not accepted by the `RequestScheduler`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RejectReason {
+    PeerRateLimit,
+    GlobalRateLimit,
+    QueueFull,
+}
+
+impl RejectReason {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            RejectReason::PeerRateLimit => "peer_rate_limit",
+            RejectReason::GlobalRateLimit => "global_rate_limit",
+            RejectReason::QueueFull => "queue_full",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Rejection {
+    pub reason: RejectReason,
+    /// Hint for the requester how long to wait before retrying.
+    pub retry_after: Duration,
+}
+
+/// Rate limits requests from peers and schedules them fairly.
+///
+/// Accepted requests are queued per peer and handed out round-robin across peers, so a peer
+/// which sends many requests at once can't starve others. At most `max_in_flight` requests are
+/// processed at the same time, which leaves capacity for other work of the node.
+pub(crate) struct RequestScheduler<T> {
+    per_peer: PeerRateLimiter,
+    global: TokenBucket,
+    max_queued: usize,
+    max_in_flight: usize,
+    queues: HashMap<PeerId, VecDeque<T>>,
+    /// Peers with non-empty queues in the order they will be served.
+    order: VecDeque<PeerId>,
+    queued: usize,
+    in_flight: usize,
+}
+
+impl<T> RequestScheduler<T> {
+    pub(crate) fn new(
+        per_peer_rate: f64,
+        per_peer_burst: u32,
+        global_rate: f64,
+        global_burst: u32,
+        max_queued: usize,
+        max_in_flight: usize,
+        now: Instant,
+    ) -> Self {
+        Self {
+            per_peer: PeerRateLimiter::new(per_peer_rate, per_peer_burst),
+            global: TokenBucket::new(global_rate, global_burst, now),
+            max_queued,
+            max_in_flight,
+            queues: HashMap::new(),
+            order: VecDeque::new(),
+            queued: 0,
+            in_flight: 0,
+        }
+    }
+
+    /// Number of accepted requests which are waiting to be processed.
+    pub(crate) fn queued(&self) -> usize {
+        self.queued
+    }
+
+    /// Accepts a request from `peer_id` or rejects it with a hint when to retry.
+    pub(crate) fn try_submit(
+        &mut self,
+        peer_id: &PeerId,
+        request: T,
+        now: Instant,
+    ) -> Result<(), Rejection> {
+        if self.queued >= self.max_queued {
+            let retry_after = Duration::from_secs_f64(self.queued as f64 / self.global.rate);
+            return Err(Rejection { reason: RejectReason::QueueFull, retry_after });
+        }
+        // Check the global limit first, so that requests rejected because of it don't use up
+        // the peer's own allowance.
+        self.global.check(now).map_err(|retry_after| Rejection {
+            reason: RejectReason::GlobalRateLimit,
+            retry_after,
+        })?;
+        self.per_peer.try_acquire(peer_id, now).map_err(|retry_after| Rejection {
+            reason: RejectReason::PeerRateLimit,
+            retry_after,
+        })?;
+        self.global.tokens -= 1.0;
+
+        let queue = self.queues.entry(peer_id.clone()).or_default();
+        if queue.is_empty() {
+            self.order.push_back(peer_id.clone());
+        }
+        queue.push_back(request);
+        self.queued += 1;
+        Ok(())
+    }
+
+    /// Returns the next request to process, unless `max_in_flight` requests are being processed
+    /// already. Each request returned must be followed by a call to `finish`.
+    pub(crate) fn next(&mut self) -> Option<(PeerId, T)> {
+        if self.in_flight >= self.max_in_flight {
+            return None;
+        }
+        let peer_id = self.order.pop_front()?;
+        let queue = self.queues.get_mut(&peer_id)?;
+        let request = queue.pop_front()?;
+        if queue.is_empty() {
+            self.queues.remove(&peer_id);
+        } else {
+            self.order.push_back(peer_id.clone());
+        }
+        self.queued -= 1;
+        self.in_flight += 1;
+        Some((peer_id, request))
+    }
+
+    /// Marks a request returned by `next` as processed.
+    pub(crate) fn finish(&mut self) {
+        self.in_flight = self.in_flight.saturating_sub(1);
+    }
+
+    /// Forgets rate limits of peers which haven't made any requests recently.
+    pub(crate) fn remove_idle(&mut self, now: Instant) {
+        self.per_peer.remove_idle(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_crypto::{KeyType, SecretKey};
+
+    fn peer(seed: &str) -> PeerId {
+        PeerId::new(SecretKey::from_seed(KeyType::ED25519, seed).public_key())
+    }
+
+    #[test]
+    fn token_bucket() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(2.0, 3, now);
+        for _ in 0..3 {
+            assert_eq!(bucket.try_acquire(now), Ok(()));
+        }
+        assert_eq!(bucket.try_acquire(now), Err(Duration::from_millis(500)));
+        assert_eq!(bucket.try_acquire(now + Duration::from_millis(500)), Ok(()));
+        assert!(bucket.is_full(now + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn peer_rate_limit() {
+        let now = Instant::now();
+        let (a, b) = (peer("a"), peer("b"));
+        let mut scheduler = RequestScheduler::new(1.0, 2, 100.0, 100, 100, 100, now);
+        assert!(scheduler.try_submit(&a, 0, now).is_ok());
+        assert!(scheduler.try_submit(&a, 1, now).is_ok());
+        let rejection = scheduler.try_submit(&a, 2, now).unwrap_err();
+        assert_eq!(rejection.reason, RejectReason::PeerRateLimit);
+        assert_eq!(rejection.retry_after, Duration::from_secs(1));
+        // Other peers are not affected.
+        assert!(scheduler.try_submit(&b, 0, now).is_ok());
+        assert!(scheduler.try_submit(&a, 2, now + rejection.retry_after).is_ok());
+
+        // `b` has fully recovered its allowance in the meantime, `a` hasn't.
+        scheduler.remove_idle(now + Duration::from_secs(1));
+        assert!(scheduler.per_peer.buckets.contains_key(&a));
+        assert!(!scheduler.per_peer.buckets.contains_key(&b));
+        scheduler.remove_idle(now + Duration::from_secs(10));
+        assert!(scheduler.per_peer.buckets.is_empty());
+    }
+
+    #[test]
+    fn global_limits() {
+        let now = Instant::now();
+        let peers: Vec<_> = (0..4).map(|i| peer(&i.to_string())).collect();
+        let mut scheduler = RequestScheduler::new(10.0, 10, 1.0, 3, 100, 100, now);
+        for (i, peer_id) in peers.iter().take(3).enumerate() {
+            assert!(scheduler.try_submit(peer_id, i, now).is_ok());
+        }
+        let rejection = scheduler.try_submit(&peers[3], 3, now).unwrap_err();
+        assert_eq!(rejection.reason, RejectReason::GlobalRateLimit);
+        // Rejection by the global limit doesn't use up the peer's allowance.
+        assert!(!scheduler.per_peer.buckets.contains_key(&peers[3]));
+
+        let mut scheduler = RequestScheduler::new(100.0, 100, 100.0, 100, 2, 100, now);
+        assert!(scheduler.try_submit(&peers[0], 0, now).is_ok());
+        assert!(scheduler.try_submit(&peers[1], 1, now).is_ok());
+        let rejection = scheduler.try_submit(&peers[2], 2, now).unwrap_err();
+        assert_eq!(rejection.reason, RejectReason::QueueFull);
+        assert!(rejection.retry_after > Duration::ZERO);
+        scheduler.next().unwrap();
+        assert!(scheduler.try_submit(&peers[2], 2, now).is_ok());
+    }
+
+    /// Requests of a peer which floods the queue don't delay those of a peer which comes later,
+    /// and no more than `max_in_flight` requests are handed out until some of them finish.
+    #[test]
+    fn fair_scheduling() {
+        let now = Instant::now();
+        let (a, b) = (peer("a"), peer("b"));
+        let mut scheduler = RequestScheduler::new(100.0, 50, 100.0, 100, 100, 2, now);
+        // `a` floods the queue before `b` sends anything.
+        for part_id in 0..20 {
+            assert!(scheduler.try_submit(&a, part_id, now).is_ok());
+        }
+        for part_id in 0..20 {
+            assert!(scheduler.try_submit(&b, part_id, now).is_ok());
+        }
+        assert_eq!(scheduler.queued(), 40);
+
+        let mut served = vec![];
+        while scheduler.queued() > 0 {
+            let before = served.len();
+            while let Some(request) = scheduler.next() {
+                served.push(request);
+            }
+            assert_eq!(served.len() - before, 2);
+            scheduler.finish();
+            scheduler.finish();
+        }
+        assert_eq!(served.len(), 40);
+        for (i, (peer_id, part_id)) in served.iter().enumerate() {
+            assert_eq!(peer_id, if i % 2 == 0 { &a } else { &b });
+            assert_eq!(*part_id, i / 2);
+        }
+        assert!(scheduler.next().is_none());
+        assert!(scheduler.queues.is_empty());
+        assert!(scheduler.order.is_empty());
+    }
+}
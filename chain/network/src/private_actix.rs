@@ -5,7 +5,7 @@ use crate::peer::peer_actor::PeerActor;
 use actix::{Addr, Message};
 use conqueue::QueueSender;
 use near_network_primitives::types::{
-    Edge, PartialEdgeInfo, PeerChainInfoV2, PeerInfo, PeerType, SimpleEdge,
+    Edge, PartialEdgeInfo, PeerCapabilities, PeerChainInfoV2, PeerInfo, PeerType, SimpleEdge,
 };
 use near_primitives::network::PeerId;
 use near_primitives::version::ProtocolVersion;
@@ -76,6 +76,8 @@ pub struct PeersRequest {}
 #[derive(Debug, actix::MessageResponse)]
 pub struct PeerRequestResult {
     pub peers: Vec<PeerInfo>,
+    /// Known capabilities of `peers`.
+    pub capabilities: Vec<(PeerId, PeerCapabilities)>,
 }
 
 #[derive(Message)]
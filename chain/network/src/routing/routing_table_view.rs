@@ -110,6 +110,11 @@ impl RoutingTableView {
         }
     }
 
+    /// Whether there is any route from this node to `peer_id`.
+    pub(crate) fn has_route(&self, peer_id: &PeerId) -> bool {
+        self.peer_forwarding.get(peer_id).map_or(false, |routes| !routes.is_empty())
+    }
+
     pub(crate) fn find_route(&mut self, target: &PeerIdOrHash) -> Result<PeerId, FindRouteError> {
         match target {
             PeerIdOrHash::PeerId(peer_id) => self.find_route_from_peer_id(peer_id),
@@ -1,8 +1,8 @@
 use crate::types::PeerMessage;
 use near_metrics::{
-    inc_counter_by_opt, inc_counter_opt, try_create_histogram, try_create_int_counter,
-    try_create_int_counter_vec, try_create_int_gauge, Histogram, IntCounter, IntCounterVec,
-    IntGauge,
+    inc_counter_by_opt, inc_counter_opt, try_create_histogram, try_create_histogram_vec,
+    try_create_int_counter, try_create_int_counter_vec, try_create_int_gauge, Histogram,
+    HistogramVec, IntCounter, IntCounterVec, IntGauge,
 };
 use near_network_primitives::types::RoutedMessageBody;
 use once_cell::sync::Lazy;
@@ -89,6 +89,23 @@ pub static DROP_MESSAGE_UNKNOWN_ACCOUNT: Lazy<IntCounter> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub static ROUTED_MESSAGE_TTL_DROPPED: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_routed_message_ttl_dropped_total",
+        "Number of routed messages dropped because their TTL has expired, by message type",
+        &["type"],
+    )
+    .unwrap()
+});
+pub static ROUTED_MESSAGE_HOPS: Lazy<HistogramVec> = Lazy::new(|| {
+    try_create_histogram_vec(
+        "near_routed_message_hops",
+        "Number of hops routed messages addressed to this node travelled, by message type",
+        &["type"],
+        Some(vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 8.0, 10.0, 15.0, 20.0, 50.0, 100.0]),
+    )
+    .unwrap()
+});
 pub static RECEIVED_INFO_ABOUT_ITSELF: Lazy<IntCounter> = Lazy::new(|| {
     try_create_int_counter(
         "received_info_about_itself",
@@ -111,6 +128,60 @@ pub static PARTIAL_ENCODED_CHUNK_REQUEST_DELAY: Lazy<Histogram> = Lazy::new(|| {
         .unwrap()
 });
 
+pub static STATE_REQUEST_QUEUE_DEPTH: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_state_request_queue_depth",
+        "Number of state header/part requests from peers waiting to be processed",
+    )
+    .unwrap()
+});
+pub static STATE_REQUEST_SERVED_BYTES: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_state_request_served_bytes",
+        "Total size of state headers and parts sent in response to requests from peers",
+    )
+    .unwrap()
+});
+pub static STATE_REQUEST_REJECTED: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_state_request_rejected_total",
+        "Number of state header/part requests from peers rejected by the throttle, by reason",
+        &["reason"],
+    )
+    .unwrap()
+});
+pub static CHUNK_PART_REQUEST_THROTTLED: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_chunk_part_request_throttled_total",
+        "Number of partial encoded chunk requests from peers dropped because of rate limits",
+    )
+    .unwrap()
+});
+
+pub static TIER1_CONNECTIONS: Lazy<IntGauge> = Lazy::new(|| {
+    try_create_int_gauge(
+        "near_tier1_connections",
+        "Number of connected peers which are TIER1 validators or their proxies",
+    )
+    .unwrap()
+});
+pub static TIER1_MESSAGES_BY_ROUTE: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_tier1_messages_by_route_total",
+        "Number of latency-critical messages sent, by message type and by whether they were sent over a direct TIER1 connection, a TIER1 proxy or fell back to TIER2 routing",
+        &["type", "route"],
+    )
+    .unwrap()
+});
+pub static TIER1_ACCOUNTS_DATA_RECEIVED: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_tier1_accounts_data_received_total",
+        "Number of TIER1 accounts data entries received from peers, by result",
+        &["result"],
+    )
+    .unwrap()
+});
+
 #[derive(Clone)]
 pub struct NetworkMetrics {
     pub peer_messages: HashMap<String, Option<IntCounter>>,
@@ -7,7 +7,7 @@ use actix::{Actor, ActorContext, Context, Handler, MailboxError, Message, Recipi
 use futures::future::BoxFuture;
 use futures::{future, FutureExt};
 use near_crypto::{KeyType, SecretKey};
-use near_network_primitives::types::{PeerInfo, ReasonForBan};
+use near_network_primitives::types::{PeerInfo, ReasonForBan, SignedAccountData};
 use near_primitives::hash::hash;
 use near_primitives::network::PeerId;
 use near_primitives::types::EpochId;
@@ -187,6 +187,19 @@ impl Handler<GetInfo> for PeerManagerActor {
     }
 }
 
+/// `GetAccountsData` gets the TIER1 accounts data known to `PeerManager`.
+#[derive(Message)]
+#[rtype(result = "Vec<SignedAccountData>")]
+pub struct GetAccountsData {}
+
+impl Handler<GetAccountsData> for PeerManagerActor {
+    type Result = Vec<SignedAccountData>;
+
+    fn handle(&mut self, _msg: GetAccountsData, _ctx: &mut Context<Self>) -> Self::Result {
+        self.get_accounts_data()
+    }
+}
+
 // `StopSignal is used to stop PeerManagerActor for unit tests
 #[derive(Message, Default)]
 #[rtype(result = "()")]
@@ -351,6 +364,7 @@ pub mod test_features {
                         height: 1,
                         tracked_shards: vec![],
                         archival: false,
+                        serves_state_sync: true,
                     }))
                 }
                 _ => Box::new(Some(NetworkViewClientResponses::NoResponse)),
@@ -0,0 +1,32 @@
+//! Propagation of OpenTelemetry traces across nodes with routed messages.
+use near_network_primitives::types::TraceContext;
+use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState};
+use opentelemetry::Context;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Context of the current span, `None` unless its trace is sampled for export.
+pub(crate) fn current() -> Option<TraceContext> {
+    let context = tracing::Span::current().context();
+    let span = context.span();
+    let span_context = span.span_context();
+    if !span_context.is_valid() || !span_context.is_sampled() {
+        return None;
+    }
+    Some(TraceContext {
+        trace_id: span_context.trace_id().to_bytes(),
+        span_id: span_context.span_id().to_bytes(),
+        trace_flags: span_context.trace_flags().to_u8(),
+    })
+}
+
+/// Makes the span `trace_context` was taken from, on another node, the parent of `span`.
+pub(crate) fn set_parent(span: &tracing::Span, trace_context: &TraceContext) {
+    let span_context = SpanContext::new(
+        TraceId::from_bytes(trace_context.trace_id),
+        SpanId::from_bytes(trace_context.span_id),
+        TraceFlags::new(trace_context.trace_flags),
+        true,
+        TraceState::default(),
+    );
+    span.set_parent(Context::new().with_remote_span_context(span_context));
+}
@@ -11,10 +11,11 @@ use crate::routing::routing_table_view::RoutingTableInfo;
 use actix::{MailboxError, Message};
 use futures::future::BoxFuture;
 use near_network_primitives::types::{
-    AccountIdOrPeerTrackingShard, AccountOrPeerIdOrHash, Ban, Edge, InboundTcpConnect,
-    KnownProducer, OutboundTcpConnect, PartialEdgeInfo, PartialEncodedChunkForwardMsg,
-    PartialEncodedChunkRequestMsg, PartialEncodedChunkResponseMsg, PeerChainInfoV2, PeerInfo, Ping,
-    Pong, ReasonForBan, RoutedMessageBody, RoutedMessageFrom, StateResponseInfo,
+    AccountIdOrPeerTrackingShard, AccountOrPeerIdOrHash, Ban, BannedPeerInfo, DroppedRoutedMessage,
+    Edge, InboundTcpConnect, KnownProducer, OutboundTcpConnect, PartialEdgeInfo,
+    PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg, PartialEncodedChunkResponseMsg,
+    PeerCapabilities, PeerChainInfoV2, PeerInfo, Ping, Pong, ReasonForBan, RoutedMessageBody,
+    RoutedMessageFrom, SignedAccountData, StateRequestRejected, StateResponseInfo,
 };
 use near_primitives::block::{Approval, ApprovalMessage, Block, BlockHeader};
 use near_primitives::challenge::Challenge;
@@ -25,10 +26,11 @@ use near_primitives::sharding::{PartialEncodedChunk, PartialEncodedChunkWithArcR
 use near_primitives::syncing::{EpochSyncFinalizationResponse, EpochSyncResponse};
 use near_primitives::time::Instant;
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::{AccountId, BlockReference, EpochId, ShardId};
+use near_primitives::types::{AccountId, BlockHeight, BlockReference, EpochId, ShardId};
 use near_primitives::views::{NetworkInfoView, PeerInfoView, QueryRequest};
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::time::Duration;
 use strum::AsStaticStr;
 
 /// Message from peer to peer manager
@@ -39,6 +41,21 @@ pub enum PeerRequest {
     RouteBack(Box<RoutedMessageBody>, CryptoHash),
     UpdatePeerInfo(PeerInfo),
     ReceivedMessage(PeerId, Instant),
+    /// State request received from a peer, to be throttled and scheduled by the peer manager.
+    StateRequest(PeerStateRequest),
+}
+
+/// State header (`part_id` is `None`) or state part request received from a peer.
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(Clone, Debug)]
+pub struct PeerStateRequest {
+    /// Author of the request.
+    pub peer_id: PeerId,
+    pub shard_id: ShardId,
+    pub sync_hash: CryptoHash,
+    pub part_id: Option<u64>,
+    /// Hash of the routed request message, used to route the response back.
+    pub msg_hash: CryptoHash,
 }
 
 #[cfg(feature = "deepsize_feature")]
@@ -51,6 +68,7 @@ impl deepsize::DeepSizeOf for PeerRequest {
             }
             PeerRequest::UpdatePeerInfo(x) => x.deep_size_of_children(context),
             PeerRequest::ReceivedMessage(x, _) => x.deep_size_of_children(context),
+            PeerRequest::StateRequest(x) => x.deep_size_of_children(context),
         }
     }
 }
@@ -80,6 +98,17 @@ pub struct PeersResponse {
     pub(crate) peers: Vec<PeerInfo>,
 }
 
+/// Received capabilities of peers from another peer: either its own or the ones it knows about
+/// other peers.
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct PeersCapabilities {
+    /// Peer which sent the capabilities.
+    pub(crate) from: PeerId,
+    pub(crate) capabilities: Vec<(PeerId, PeerCapabilities)>,
+}
+
 /// List of all messages, which `PeerManagerActor` accepts through `Actix`. There is also another list
 /// which contains reply for each message to `PeerManager`.
 /// There is 1 to 1 mapping between an entry in `PeerManagerMessageRequest` and `PeerManagerMessageResponse`.
@@ -92,6 +121,7 @@ pub enum PeerManagerMessageRequest {
     RegisterPeer(RegisterPeer),
     PeersRequest(PeersRequest),
     PeersResponse(PeersResponse),
+    PeersCapabilities(PeersCapabilities),
     PeerRequest(PeerRequest),
     #[cfg(feature = "test_features")]
     GetPeerId(crate::private_actix::GetPeerId),
@@ -135,6 +165,7 @@ pub enum PeerManagerMessageResponse {
     RegisterPeerResponse(RegisterPeerResponse),
     PeerRequestResult(PeerRequestResult),
     PeersResponseResult(()),
+    PeersCapabilitiesResult(()),
     PeerResponse(PeerResponse),
     #[cfg(feature = "test_features")]
     GetPeerIdResult(crate::private_actix::GetPeerIdResult),
@@ -233,6 +264,12 @@ pub enum NetworkRequests {
         hashes: Vec<CryptoHash>,
         peer_id: PeerId,
     },
+    /// Request block headers with heights in the inclusive range.
+    BlockHeadersByHeightRequest {
+        start_height: BlockHeight,
+        end_height: BlockHeight,
+        peer_id: PeerId,
+    },
     /// Request state header for given shard at given state root.
     StateRequestHeader {
         shard_id: ShardId,
@@ -315,6 +352,29 @@ pub enum NetworkRequests {
     RequestUpdateNonce(PeerId, PartialEdgeInfo),
     ResponseUpdateNonce(Edge),
 
+    /// Data about how to reach validators over TIER1, received from active peer.
+    SyncAccountsData {
+        peer_id: PeerId,
+        accounts_data: Vec<SignedAccountData>,
+    },
+    /// Block and chunk producers of the current and next epoch. The node establishes TIER1
+    /// connections to them, if it is one of them itself.
+    SetTier1Accounts {
+        epoch_id: EpochId,
+        accounts: Vec<AccountId>,
+    },
+
+    /// Ban a peer on request of the node operator. If `ban_duration` is not set, the configured
+    /// ban window is used.
+    BanPeerManually {
+        peer_id: PeerId,
+        ban_duration: Option<Duration>,
+    },
+    /// Remove the ban of a peer on request of the node operator.
+    UnbanPeer {
+        peer_id: PeerId,
+    },
+
     /// (Unit tests) Start ping to `PeerId` with `nonce`.
     PingTo(usize, PeerId),
     /// (Unit tests) Fetch all received ping and pong so far.
@@ -337,6 +397,29 @@ pub struct FullPeerInfo {
     pub peer_info: PeerInfo,
     pub chain_info: PeerChainInfoV2,
     pub partial_edge_info: PartialEdgeInfo,
+    /// Capabilities recently announced by the peer.  `None` if the peer hasn't announced any or
+    /// the announcement is stale, in which case the ones from the handshake are used.
+    pub capabilities: Option<PeerCapabilities>,
+}
+
+impl FullPeerInfo {
+    /// Whether the peer keeps the whole history of the chain.
+    pub fn is_archival(&self) -> bool {
+        self.capabilities.as_ref().map_or(self.chain_info.archival, |c| c.archival())
+    }
+
+    /// Whether the peer can serve state parts of the given shard.
+    pub fn serves_state_of(&self, shard_id: ShardId) -> bool {
+        match &self.capabilities {
+            Some(capabilities) => capabilities.serves_state_of(shard_id),
+            None => PeerCapabilities::from(&self.chain_info).serves_state_of(shard_id),
+        }
+    }
+
+    /// Whether the peer responds to requests for block headers by height range.
+    pub fn serves_header_ranges(&self) -> bool {
+        self.capabilities.as_ref().map_or(false, |c| c.serves_header_ranges())
+    }
 }
 
 impl From<&FullPeerInfo> for PeerInfoView {
@@ -349,7 +432,7 @@ impl From<&FullPeerInfo> for PeerInfoView {
             account_id: full_peer_info.peer_info.account_id.clone(),
             height: full_peer_info.chain_info.height,
             tracked_shards: full_peer_info.chain_info.tracked_shards.clone(),
-            archival: full_peer_info.chain_info.archival,
+            archival: full_peer_info.is_archival(),
         }
     }
 }
@@ -365,6 +448,10 @@ pub struct NetworkInfo {
     /// Accounts of known block and chunk producers from routing table.
     pub known_producers: Vec<KnownProducer>,
     pub peer_counter: usize,
+    /// Peers which are currently banned.
+    pub banned_peers: Vec<BannedPeerInfo>,
+    /// Most recent routed messages dropped because their TTL has expired.
+    pub recent_dropped_routed_messages: Vec<DroppedRoutedMessage>,
 }
 
 impl From<NetworkInfo> for NetworkInfoView {
@@ -418,6 +505,8 @@ pub enum NetworkClientMessages {
     BlockApproval(Approval, PeerId),
     /// State response.
     StateResponse(StateResponseInfo),
+    /// State request was rejected by the peer, which asks to retry later.
+    StateRequestRejected(StateRequestRejected),
     /// Epoch Sync response for light client block request
     EpochSyncResponse(PeerId, Box<EpochSyncResponse>),
     /// Epoch Sync response for finalization request
@@ -1,12 +1,16 @@
-use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::cmp::{max, Reverse};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 
-use crate::types::{PoolIterator, PoolKey, TransactionGroup};
+use crate::types::{
+    InsertTransactionResult, PoolIterator, PoolKey, TransactionGroup, TransactionPoolLimits,
+};
 use borsh::BorshSerialize;
 use near_crypto::PublicKey;
 use near_primitives::epoch_manager::RngSeed;
 use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::AccountId;
+use near_primitives::types::{AccountId, Balance};
 use std::ops::Bound;
 
 mod metrics;
@@ -18,21 +22,55 @@ pub struct TransactionPool {
     /// NOTE: It's more efficient on average to keep transactions unsorted and with potentially
     /// conflicting nonce than to create a BTreeMap for every transaction.
     pub transactions: BTreeMap<PoolKey, Vec<SignedTransaction>>,
-    /// Set of all hashes to quickly check if the given transaction is in the pool.
-    pub unique_transactions: HashSet<CryptoHash>,
+    /// Hashes of all transactions in the pool to quickly check if the given transaction is in the
+    /// pool, with the information needed to account for them when they are removed.
+    pub unique_transactions: HashMap<CryptoHash, PooledTransactionInfo>,
+    /// Number and size of the transactions in the pool by signer account.
+    accounts: HashMap<AccountId, AccountUsage>,
+    /// Signer accounts ordered by the number and size of their transactions in the pool, to find
+    /// the account to evict from without going through all of them.
+    accounts_by_usage: BTreeSet<(usize, u64, AccountId)>,
     /// A uniquely generated key seed to randomize PoolKey order.
     key_seed: RngSeed,
     /// The key after which the pool iterator starts. Doesn't have to be present in the pool.
     last_used_key: PoolKey,
+    /// How much higher, in percent, the priority fee of a transaction has to be to replace the
+    /// pooled transaction with the same signer, public key and nonce.
+    replacement_fee_bump_percent: u64,
+    limits: TransactionPoolLimits,
+}
+
+/// Group and size of a transaction in the pool.
+pub struct PooledTransactionInfo {
+    key: PoolKey,
+    signer_id: AccountId,
+    size: u64,
+}
+
+/// Transactions of a signer account in the pool.
+#[derive(Default)]
+struct AccountUsage {
+    transactions: usize,
+    bytes: u64,
+    /// Number of transactions in each group of the account.
+    keys: BTreeMap<PoolKey, usize>,
 }
 
 impl TransactionPool {
-    pub fn new(key_seed: RngSeed) -> Self {
+    pub fn new(
+        key_seed: RngSeed,
+        replacement_fee_bump_percent: u64,
+        limits: TransactionPoolLimits,
+    ) -> Self {
         Self {
             key_seed,
             transactions: BTreeMap::new(),
-            unique_transactions: HashSet::new(),
+            unique_transactions: HashMap::new(),
+            accounts: HashMap::new(),
+            accounts_by_usage: BTreeSet::new(),
             last_used_key: CryptoHash::default(),
+            replacement_fee_bump_percent,
+            limits,
         }
     }
 
@@ -43,23 +81,194 @@ impl TransactionPool {
         hash(&v)
     }
 
+    /// Priority fee a transaction needs to replace a pooled transaction with the given one.
+    fn required_priority_fee(&self, pooled_priority_fee: Balance) -> Balance {
+        let percent = Balance::from(self.replacement_fee_bump_percent);
+        let bump = (pooled_priority_fee / 100)
+            .saturating_mul(percent)
+            .saturating_add(pooled_priority_fee % 100 * percent / 100);
+        pooled_priority_fee.saturating_add(max(bump, 1))
+    }
+
     /// Insert a signed transaction into the pool that passed validation.
-    pub fn insert_transaction(&mut self, signed_transaction: SignedTransaction) -> bool {
-        if !self.unique_transactions.insert(signed_transaction.get_hash()) {
+    /// If the pool has a transaction with the same signer, public key and nonce, the new one
+    /// replaces it only if its priority fee is higher by at least `replacement_fee_bump_percent`.
+    /// If the pool is full, the lowest-priced transaction of the account with the most
+    /// transactions in the pool is evicted to make room for the new one.
+    pub fn insert_transaction(
+        &mut self,
+        signed_transaction: SignedTransaction,
+    ) -> InsertTransactionResult {
+        let result = self.try_insert_transaction(signed_transaction);
+        let rejection_reason = match result {
+            InsertTransactionResult::Success
+            | InsertTransactionResult::Duplicate
+            | InsertTransactionResult::Replaced { .. } => None,
+            InsertTransactionResult::Underpriced { .. } => Some("underpriced"),
+            InsertTransactionResult::AccountTransactionsLimitExceeded => {
+                Some("account_transactions_limit")
+            }
+            InsertTransactionResult::AccountBytesLimitExceeded => Some("account_bytes_limit"),
+            InsertTransactionResult::PoolFull => Some("pool_full"),
+        };
+        if let Some(reason) = rejection_reason {
+            metrics::TRANSACTION_POOL_REJECTED.with_label_values(&[reason]).inc();
+        }
+        result
+    }
+
+    fn try_insert_transaction(
+        &mut self,
+        signed_transaction: SignedTransaction,
+    ) -> InsertTransactionResult {
+        if self.unique_transactions.contains_key(&signed_transaction.get_hash()) {
             // The hash of this transaction was already seen, skip it.
-            return false;
+            return InsertTransactionResult::Duplicate;
         }
-        metrics::TRANSACTION_POOL_TOTAL.inc();
 
         let signer_id = &signed_transaction.transaction.signer_id;
         let signer_public_key = &signed_transaction.transaction.public_key;
-        self.transactions
-            .entry(self.key(signer_id, signer_public_key))
-            .or_insert_with(Vec::new)
-            .push(signed_transaction);
+        let key = self.key(signer_id, signer_public_key);
+        let size = signed_transaction.get_size();
+        let (account_transactions, account_bytes) =
+            self.accounts.get(signer_id).map_or((0, 0), |usage| (usage.transactions, usage.bytes));
+        let group = self.transactions.get(&key);
+        let pooled = group.and_then(|transactions| {
+            transactions
+                .iter()
+                .position(|tx| tx.transaction.nonce == signed_transaction.transaction.nonce)
+        });
+        let result = match pooled {
+            Some(index) => {
+                let pooled_tx = &group.expect("just found a transaction")[index];
+                let required_priority_fee =
+                    self.required_priority_fee(pooled_tx.transaction.priority_fee.unwrap_or(0));
+                if signed_transaction.transaction.priority_fee.unwrap_or(0) < required_priority_fee
+                {
+                    return InsertTransactionResult::Underpriced { required_priority_fee };
+                }
+                if account_bytes - pooled_tx.get_size() + size > self.limits.max_bytes_per_account {
+                    return InsertTransactionResult::AccountBytesLimitExceeded;
+                }
+                let replaced_hash = pooled_tx.get_hash();
+                self.evict_transaction(key, index, "replaced");
+                InsertTransactionResult::Replaced { replaced_hash }
+            }
+            None => {
+                if account_transactions >= self.limits.max_transactions_per_account {
+                    return InsertTransactionResult::AccountTransactionsLimitExceeded;
+                }
+                if account_bytes + size > self.limits.max_bytes_per_account {
+                    return InsertTransactionResult::AccountBytesLimitExceeded;
+                }
+                if self.len() >= self.limits.max_transactions
+                    && !self.evict_for(
+                        &signed_transaction,
+                        account_transactions + 1,
+                        account_bytes + size,
+                    )
+                {
+                    return InsertTransactionResult::PoolFull;
+                }
+                InsertTransactionResult::Success
+            }
+        };
+
+        let signer_id = signed_transaction.transaction.signer_id.clone();
+        let usage = self.accounts.entry(signer_id.clone()).or_default();
+        self.accounts_by_usage.remove(&(usage.transactions, usage.bytes, signer_id.clone()));
+        usage.transactions += 1;
+        usage.bytes += size;
+        *usage.keys.entry(key).or_default() += 1;
+        self.accounts_by_usage.insert((usage.transactions, usage.bytes, signer_id.clone()));
+        self.unique_transactions
+            .insert(signed_transaction.get_hash(), PooledTransactionInfo { key, signer_id, size });
+        metrics::TRANSACTION_POOL_TOTAL.inc();
+        self.transactions.entry(key).or_insert_with(Vec::new).push(signed_transaction);
+        result
+    }
+
+    /// Makes room in the full pool for the given transaction by evicting the lowest-priced
+    /// transaction of the account with the most transactions, counting the new transaction with
+    /// the given totals of its signer.  Returns false if the new transaction is the one to drop.
+    fn evict_for(
+        &mut self,
+        signed_transaction: &SignedTransaction,
+        signer_transactions: usize,
+        signer_bytes: u64,
+    ) -> bool {
+        let signer_id = &signed_transaction.transaction.signer_id;
+        let victim_id = self
+            .accounts_by_usage
+            .iter()
+            .rev()
+            .find(|(_, _, account_id)| account_id != signer_id)
+            .map(|(transactions, bytes, account_id)| (*transactions, *bytes, account_id))
+            .into_iter()
+            .chain(std::iter::once((signer_transactions, signer_bytes, signer_id)))
+            .max()
+            .map(|(_, _, account_id)| account_id.clone())
+            .expect("the signer is always a candidate");
+        let lowest = self.accounts.get(&victim_id).and_then(|usage| {
+            usage
+                .keys
+                .keys()
+                .flat_map(|key| {
+                    self.transactions[key].iter().enumerate().map(move |(index, tx)| {
+                        let priority_fee = tx.transaction.priority_fee.unwrap_or(0);
+                        ((priority_fee, Reverse(tx.transaction.nonce), tx.get_hash()), *key, index)
+                    })
+                })
+                .min()
+        });
+        let (price, key, index) = match lowest {
+            Some(lowest) => lowest,
+            None => return false,
+        };
+        if &victim_id == signer_id
+            && signed_transaction.transaction.priority_fee.unwrap_or(0) <= price.0
+        {
+            return false;
+        }
+        self.evict_transaction(key, index, "pool_full");
         true
     }
 
+    /// Removes the transaction at the given index of a group from the pool.
+    fn evict_transaction(&mut self, key: PoolKey, index: usize, reason: &str) {
+        let group = self.transactions.get_mut(&key).expect("evicted transaction group exists");
+        let evicted_tx = group.swap_remove(index);
+        if group.is_empty() {
+            self.transactions.remove(&key);
+        }
+        self.forget_transaction(&evicted_tx.get_hash());
+        metrics::TRANSACTION_POOL_EVICTED.with_label_values(&[reason]).inc();
+    }
+
+    /// Forgets a transaction that was already removed from its group.
+    fn forget_transaction(&mut self, hash: &CryptoHash) {
+        let info = match self.unique_transactions.remove(hash) {
+            Some(info) => info,
+            None => return,
+        };
+        metrics::TRANSACTION_POOL_TOTAL.dec();
+        let usage = self.accounts.get_mut(&info.signer_id).expect("signer of a pooled transaction");
+        self.accounts_by_usage.remove(&(usage.transactions, usage.bytes, info.signer_id.clone()));
+        usage.transactions -= 1;
+        usage.bytes -= info.size;
+        if let Entry::Occupied(mut entry) = usage.keys.entry(info.key) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+        if usage.transactions == 0 {
+            self.accounts.remove(&info.signer_id);
+        } else {
+            self.accounts_by_usage.insert((usage.transactions, usage.bytes, info.signer_id));
+        }
+    }
+
     /// Returns a pool iterator wrapper that implements an iterator like trait to iterate over
     /// transaction groups in the proper order defined by the protocol.
     /// When the iterator is dropped, all remaining groups are inserted back into the pool.
@@ -72,7 +281,7 @@ impl TransactionPool {
     pub fn remove_transactions(&mut self, transactions: &[SignedTransaction]) {
         let mut grouped_transactions = HashMap::new();
         for tx in transactions {
-            if self.unique_transactions.contains(&tx.get_hash()) {
+            if self.unique_transactions.contains_key(&tx.get_hash()) {
                 let signer_id = &tx.transaction.signer_id;
                 let signer_public_key = &tx.transaction.public_key;
                 grouped_transactions
@@ -91,9 +300,7 @@ impl TransactionPool {
                 self.transactions.remove(&key);
             }
             for hash in &hashes {
-                if self.unique_transactions.remove(&hash) {
-                    metrics::TRANSACTION_POOL_TOTAL.dec();
-                }
+                self.forget_transaction(hash);
             }
         }
     }
@@ -123,18 +330,37 @@ pub struct PoolIteratorWrapper<'a> {
 
     /// Queue of transaction groups. Each group there is sorted by nonce.
     sorted_groups: VecDeque<TransactionGroup>,
+
+    /// Keys of the groups with priority fee transactions, by decreasing highest priority fee.
+    /// These groups are pulled from the pool before the others.
+    priority_keys: VecDeque<PoolKey>,
 }
 
 impl<'a> PoolIteratorWrapper<'a> {
     pub fn new(pool: &'a mut TransactionPool) -> Self {
-        Self { pool, sorted_groups: Default::default() }
+        let mut priority_keys: Vec<_> = pool
+            .transactions
+            .iter()
+            .filter_map(|(key, transactions)| {
+                let priority_fee =
+                    transactions.iter().filter_map(|tx| tx.transaction.priority_fee).max()?;
+                (priority_fee > 0).then(|| (Reverse(priority_fee), *key))
+            })
+            .collect();
+        priority_keys.sort();
+        Self {
+            pool,
+            sorted_groups: Default::default(),
+            priority_keys: priority_keys.into_iter().map(|(_, key)| key).collect(),
+        }
     }
 }
 
 /// The iterator works with the following algorithm:
 /// On next(), the iterator tries to get a transaction group from the pool, sorts transactions in
 /// it, and add it to the back of the sorted groups queue.
-/// Remembers the last used key, so it can continue from the next key.
+/// Groups with priority fee transactions are taken first, by decreasing highest priority fee.
+/// For the other groups it remembers the last used key, so it can continue from the next key.
 ///
 /// If the pool is empty, the iterator gets the group from the front of the sorted groups queue.
 ///
@@ -151,20 +377,26 @@ impl<'a> PoolIteratorWrapper<'a> {
 impl<'a> PoolIterator for PoolIteratorWrapper<'a> {
     fn next(&mut self) -> Option<&mut TransactionGroup> {
         if !self.pool.transactions.is_empty() {
-            let key = *self
-                .pool
-                .transactions
-                .range((Bound::Excluded(self.pool.last_used_key), Bound::Unbounded))
-                .next()
-                .map(|(k, _v)| k)
-                .unwrap_or_else(|| {
-                    self.pool
+            let key = match self.priority_keys.pop_front() {
+                Some(key) => key,
+                None => {
+                    let key = *self
+                        .pool
                         .transactions
-                        .keys()
+                        .range((Bound::Excluded(self.pool.last_used_key), Bound::Unbounded))
                         .next()
-                        .expect("we've just checked that the map is not empty")
-                });
-            self.pool.last_used_key = key;
+                        .map(|(k, _v)| k)
+                        .unwrap_or_else(|| {
+                            self.pool
+                                .transactions
+                                .keys()
+                                .next()
+                                .expect("we've just checked that the map is not empty")
+                        });
+                    self.pool.last_used_key = key;
+                    key
+                }
+            };
             let mut transactions =
                 self.pool.transactions.remove(&key).expect("just checked existence");
             transactions.sort_by_key(|st| std::cmp::Reverse(st.transaction.nonce));
@@ -178,9 +410,7 @@ impl<'a> PoolIterator for PoolIteratorWrapper<'a> {
             while let Some(sorted_group) = self.sorted_groups.pop_front() {
                 if sorted_group.transactions.is_empty() {
                     for hash in sorted_group.removed_transaction_hashes {
-                        if self.pool.unique_transactions.remove(&hash) {
-                            metrics::TRANSACTION_POOL_TOTAL.dec();
-                        }
+                        self.pool.forget_transaction(&hash);
                     }
                 } else {
                     self.sorted_groups.push_back(sorted_group);
@@ -199,9 +429,7 @@ impl<'a> Drop for PoolIteratorWrapper<'a> {
     fn drop(&mut self) {
         for group in self.sorted_groups.drain(..) {
             for hash in group.removed_transaction_hashes {
-                if self.pool.unique_transactions.remove(&hash) {
-                    metrics::TRANSACTION_POOL_TOTAL.dec();
-                }
+                self.pool.forget_transaction(&hash);
             }
             if !group.transactions.is_empty() {
                 self.pool.transactions.insert(group.key, group.transactions);
@@ -218,12 +446,19 @@ mod tests {
     use rand::seq::SliceRandom;
     use rand::thread_rng;
 
-    use near_crypto::{InMemorySigner, KeyType};
+    use near_crypto::{InMemorySigner, KeyType, Signer};
 
     use near_primitives::hash::CryptoHash;
+    use near_primitives::transaction::Transaction;
     use near_primitives::types::Balance;
 
     const TEST_SEED: RngSeed = [3; 32];
+    const TEST_REPLACEMENT_FEE_BUMP_PERCENT: u64 = 10;
+    const TEST_LIMITS: TransactionPoolLimits = TransactionPoolLimits {
+        max_transactions: 1_000,
+        max_transactions_per_account: 1_000,
+        max_bytes_per_account: 1_000_000,
+    };
 
     fn generate_transactions(
         signer_id: &str,
@@ -252,7 +487,8 @@ mod tests {
         mut transactions: Vec<SignedTransaction>,
         expected_weight: u32,
     ) -> (Vec<u64>, TransactionPool) {
-        let mut pool = TransactionPool::new(TEST_SEED);
+        let mut pool =
+            TransactionPool::new(TEST_SEED, TEST_REPLACEMENT_FEE_BUMP_PERCENT, TEST_LIMITS);
         let mut rng = thread_rng();
         transactions.shuffle(&mut rng);
         for tx in transactions {
@@ -363,7 +599,8 @@ mod tests {
             })
             .collect::<Vec<_>>();
 
-        let mut pool = TransactionPool::new(TEST_SEED);
+        let mut pool =
+            TransactionPool::new(TEST_SEED, TEST_REPLACEMENT_FEE_BUMP_PERCENT, TEST_LIMITS);
         let mut rng = thread_rng();
         transactions.shuffle(&mut rng);
         for tx in transactions.clone() {
@@ -464,4 +701,273 @@ mod tests {
         new_nonces.sort();
         assert_ne!(nonces, new_nonces);
     }
+
+    /// Groups with priority fee transactions are pulled first by decreasing priority fee, the
+    /// others keep their usual order.
+    #[test]
+    fn test_pool_iterator_priority_fee() {
+        let transactions = (1..=10)
+            .map(|i| {
+                let signer_id = AccountId::try_from(format!("user_{}", i)).unwrap();
+                let signer = InMemorySigner::from_seed(
+                    signer_id.clone(),
+                    KeyType::ED25519,
+                    signer_id.as_ref(),
+                );
+                let priority_fee = if i % 3 == 0 { None } else { Some(i as Balance * 100) };
+                Transaction {
+                    signer_id,
+                    public_key: signer.public_key(),
+                    nonce: i,
+                    receiver_id: "bob.near".parse().unwrap(),
+                    block_hash: CryptoHash::default(),
+                    actions: vec![],
+                    priority_fee,
+                }
+                .sign(&signer)
+            })
+            .collect::<Vec<_>>();
+        let (nonces, _) = process_txs_to_nonces(transactions.clone(), 10);
+        assert_eq!(nonces[..7], [10, 8, 7, 5, 4, 2, 1]);
+
+        // Groups without a priority fee are ordered by their pool keys, so the order doesn't
+        // depend on the order the transactions are inserted in.
+        let (nonces, _) = process_txs_to_nonces(transactions.clone(), 10);
+        let (other_nonces, _) = process_txs_to_nonces(transactions, 10);
+        assert_eq!(nonces, other_nonces);
+        let mut rest = nonces[7..].to_vec();
+        rest.sort();
+        assert_eq!(rest, [3, 6, 9]);
+    }
+
+    fn priority_fee_transaction(
+        deposit: Balance,
+        priority_fee: Option<Balance>,
+    ) -> SignedTransaction {
+        let signer_id: AccountId = "alice.near".parse().unwrap();
+        let signer = InMemorySigner::from_seed(signer_id.clone(), KeyType::ED25519, "alice.near");
+        Transaction {
+            priority_fee,
+            ..SignedTransaction::send_money(
+                1,
+                signer_id,
+                "bob.near".parse().unwrap(),
+                &signer,
+                deposit,
+                CryptoHash::default(),
+            )
+            .transaction
+        }
+        .sign(&signer)
+    }
+
+    /// A transaction with the same signer, public key and nonce as a pooled one replaces it only
+    /// if its priority fee is high enough.
+    #[test]
+    fn test_replace_by_fee() {
+        let mut pool =
+            TransactionPool::new(TEST_SEED, TEST_REPLACEMENT_FEE_BUMP_PERCENT, TEST_LIMITS);
+        let original = priority_fee_transaction(1, None);
+        assert_eq!(pool.insert_transaction(original.clone()), InsertTransactionResult::Success);
+        assert_eq!(pool.insert_transaction(original.clone()), InsertTransactionResult::Duplicate);
+        assert_eq!(
+            pool.insert_transaction(priority_fee_transaction(2, None)),
+            InsertTransactionResult::Underpriced { required_priority_fee: 1 }
+        );
+
+        let replacement = priority_fee_transaction(3, Some(1_000));
+        assert_eq!(
+            pool.insert_transaction(replacement.clone()),
+            InsertTransactionResult::Replaced { replaced_hash: original.get_hash() }
+        );
+        assert_eq!(
+            pool.insert_transaction(priority_fee_transaction(4, Some(1_099))),
+            InsertTransactionResult::Underpriced { required_priority_fee: 1_100 }
+        );
+        let second_replacement = priority_fee_transaction(5, Some(1_100));
+        assert_eq!(
+            pool.insert_transaction(second_replacement.clone()),
+            InsertTransactionResult::Replaced { replaced_hash: replacement.get_hash() }
+        );
+
+        assert_eq!(pool.len(), 1);
+        assert_eq!(prepare_transactions(&mut pool, 10), vec![second_replacement]);
+        assert!(pool.is_empty());
+    }
+
+    /// Once the original transaction is pulled into a chunk, the pool accepts any transaction with
+    /// its nonce.  If the chunk isn't included, the reintroduced original only replaces it if its
+    /// priority fee is high enough.
+    #[test]
+    fn test_replace_by_fee_original_in_produced_chunk() {
+        let mut pool =
+            TransactionPool::new(TEST_SEED, TEST_REPLACEMENT_FEE_BUMP_PERCENT, TEST_LIMITS);
+        let original = priority_fee_transaction(1, None);
+        pool.insert_transaction(original.clone());
+        assert_eq!(prepare_transactions(&mut pool, 10), vec![original.clone()]);
+
+        let replacement = priority_fee_transaction(2, Some(1_000));
+        assert_eq!(pool.insert_transaction(replacement.clone()), InsertTransactionResult::Success);
+        pool.reintroduce_transactions(vec![original.clone()]);
+        assert_eq!(pool.len(), 1);
+        assert_eq!(prepare_transactions(&mut pool, 10), vec![replacement.clone()]);
+
+        // The chunk with the replacement isn't included either, but the original is.
+        pool.reintroduce_transactions(vec![replacement.clone()]);
+        pool.remove_transactions(&[original]);
+        assert_eq!(pool.len(), 1);
+        assert_eq!(prepare_transactions(&mut pool, 10), vec![replacement]);
+    }
+
+    /// Transactions of an account above its count or size limit are rejected, and the other
+    /// accounts aren't affected.
+    #[test]
+    fn test_account_limits() {
+        let limits = TransactionPoolLimits { max_transactions_per_account: 3, ..TEST_LIMITS };
+        let mut pool = TransactionPool::new(TEST_SEED, TEST_REPLACEMENT_FEE_BUMP_PERCENT, limits);
+        let transactions = generate_transactions("alice.near", "alice.near", 1, 4);
+        for tx in &transactions[..3] {
+            assert_eq!(pool.insert_transaction(tx.clone()), InsertTransactionResult::Success);
+        }
+        assert_eq!(
+            pool.insert_transaction(transactions[3].clone()),
+            InsertTransactionResult::AccountTransactionsLimitExceeded
+        );
+        for tx in generate_transactions("bob.near", "bob.near", 1, 3) {
+            assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Success);
+        }
+        assert_eq!(pool.len(), 6);
+
+        // Once a transaction is pulled into a chunk, there is room for another one.
+        prepare_transactions(&mut pool, 2);
+        assert_eq!(
+            pool.insert_transaction(transactions[3].clone()),
+            InsertTransactionResult::Success
+        );
+
+        let size = transactions[0].get_size();
+        let limits = TransactionPoolLimits { max_bytes_per_account: 2 * size, ..TEST_LIMITS };
+        let mut pool = TransactionPool::new(TEST_SEED, TEST_REPLACEMENT_FEE_BUMP_PERCENT, limits);
+        assert_eq!(
+            pool.insert_transaction(transactions[0].clone()),
+            InsertTransactionResult::Success
+        );
+        assert_eq!(
+            pool.insert_transaction(transactions[1].clone()),
+            InsertTransactionResult::Success
+        );
+        assert_eq!(
+            pool.insert_transaction(transactions[2].clone()),
+            InsertTransactionResult::AccountBytesLimitExceeded
+        );
+        pool.remove_transactions(&transactions[..1]);
+        assert_eq!(
+            pool.insert_transaction(transactions[2].clone()),
+            InsertTransactionResult::Success
+        );
+    }
+
+    /// When the pool is full, the lowest-priced transaction of the account with the most
+    /// transactions is evicted, unless the new transaction is the lowest-priced one of that account.
+    #[test]
+    fn test_eviction_when_full() {
+        let limits = TransactionPoolLimits { max_transactions: 5, ..TEST_LIMITS };
+        let mut pool = TransactionPool::new(TEST_SEED, TEST_REPLACEMENT_FEE_BUMP_PERCENT, limits);
+        let spam = generate_transactions("alice.near", "alice.near", 1, 5);
+        for tx in spam.iter().cloned() {
+            assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Success);
+        }
+        assert_eq!(
+            pool.insert_transaction(
+                generate_transactions("alice.near", "alice.near", 6, 6).remove(0)
+            ),
+            InsertTransactionResult::PoolFull
+        );
+
+        let bob_transactions = generate_transactions("bob.near", "bob.near", 1, 2);
+        for tx in bob_transactions.iter().cloned() {
+            assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Success);
+        }
+        assert_eq!(pool.len(), 5);
+
+        // Without priority fees, the transactions with the highest nonces are evicted first.
+        let mut pool_transactions = prepare_transactions(&mut pool, 10);
+        pool_transactions
+            .sort_by_key(|tx| (tx.transaction.signer_id.clone(), tx.transaction.nonce));
+        let mut expected = spam[..3].to_vec();
+        expected.extend(bob_transactions);
+        assert_eq!(pool_transactions, expected);
+    }
+
+    /// The limits apply to each account separately, and a full pool evicts from whichever
+    /// account has the most transactions at the time.
+    #[test]
+    fn test_limits_with_many_accounts() {
+        let limits = TransactionPoolLimits {
+            max_transactions: 6,
+            max_transactions_per_account: 3,
+            ..TEST_LIMITS
+        };
+        let mut pool = TransactionPool::new(TEST_SEED, TEST_REPLACEMENT_FEE_BUMP_PERCENT, limits);
+        let transactions_of = |pool: &TransactionPool, signer_id: &str| {
+            pool.accounts.get(&signer_id.parse().unwrap()).map_or(0, |usage| usage.transactions)
+        };
+
+        let alice_transactions = generate_transactions("alice.near", "alice.near", 1, 4);
+        for tx in alice_transactions[..3].iter().cloned() {
+            assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Success);
+        }
+        assert_eq!(
+            pool.insert_transaction(alice_transactions[3].clone()),
+            InsertTransactionResult::AccountTransactionsLimitExceeded
+        );
+        for tx in generate_transactions("bob.near", "bob.near", 1, 2) {
+            assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Success);
+        }
+        for tx in generate_transactions("carol.near", "carol.near", 1, 1) {
+            assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Success);
+        }
+        assert_eq!(pool.len(), 6);
+
+        // Alice has the most transactions, so she makes room for Dave's.
+        for tx in generate_transactions("dave.near", "dave.near", 1, 1) {
+            assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Success);
+        }
+        assert_eq!(pool.len(), 6);
+        assert_eq!(transactions_of(&pool, "alice.near"), 2);
+        assert_eq!(transactions_of(&pool, "bob.near"), 2);
+        assert_eq!(transactions_of(&pool, "carol.near"), 1);
+        assert_eq!(transactions_of(&pool, "dave.near"), 1);
+        // Alice is below her limit again, but her new transaction would be her lowest-priced one.
+        assert_eq!(
+            pool.insert_transaction(alice_transactions[3].clone()),
+            InsertTransactionResult::PoolFull
+        );
+
+        // Once the transactions are pulled into a chunk, Carol reaching her limit doesn't stop
+        // Alice.
+        assert_eq!(prepare_transactions(&mut pool, 6).len(), 6);
+        assert!(pool.is_empty());
+        let carol_transactions = generate_transactions("carol.near", "carol.near", 2, 5);
+        for tx in carol_transactions[..3].iter().cloned() {
+            assert_eq!(pool.insert_transaction(tx), InsertTransactionResult::Success);
+        }
+        assert_eq!(
+            pool.insert_transaction(carol_transactions[3].clone()),
+            InsertTransactionResult::AccountTransactionsLimitExceeded
+        );
+        assert_eq!(
+            pool.insert_transaction(alice_transactions[3].clone()),
+            InsertTransactionResult::Success
+        );
+
+        // The eviction index follows the pool.
+        let mut expected: Vec<_> = pool
+            .accounts
+            .iter()
+            .map(|(account_id, usage)| (usage.transactions, usage.bytes, account_id.clone()))
+            .collect();
+        expected.sort();
+        assert_eq!(pool.accounts_by_usage.iter().cloned().collect::<Vec<_>>(), expected);
+    }
 }
@@ -1,4 +1,4 @@
-use near_metrics::IntGauge;
+use near_metrics::{IntCounterVec, IntGauge};
 use once_cell::sync::Lazy;
 
 pub static TRANSACTION_POOL_TOTAL: Lazy<IntGauge> = Lazy::new(|| {
@@ -8,3 +8,19 @@ pub static TRANSACTION_POOL_TOTAL: Lazy<IntGauge> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub static TRANSACTION_POOL_REJECTED: Lazy<IntCounterVec> = Lazy::new(|| {
+    near_metrics::try_create_int_counter_vec(
+        "near_transaction_pool_rejected_total",
+        "Number of transactions that weren't added to the pools tracked by the node, by reason",
+        &["reason"],
+    )
+    .unwrap()
+});
+pub static TRANSACTION_POOL_EVICTED: Lazy<IntCounterVec> = Lazy::new(|| {
+    near_metrics::try_create_int_counter_vec(
+        "near_transaction_pool_evicted_total",
+        "Number of transactions evicted from the pools tracked by the node, by reason",
+        &["reason"],
+    )
+    .unwrap()
+});
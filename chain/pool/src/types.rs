@@ -1,5 +1,6 @@
 use near_primitives::hash::CryptoHash;
 use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::Balance;
 
 /// Trait acts like an iterator. It iterates over transactions groups by returning mutable
 /// references to them. Each transaction group implements a draining iterator to pull transactions.
@@ -35,3 +36,37 @@ impl TransactionGroup {
         }
     }
 }
+
+/// Outcome of inserting a transaction into the pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertTransactionResult {
+    /// The transaction was added to the pool.
+    Success,
+    /// The transaction is already in the pool.
+    Duplicate,
+    /// The transaction replaced the pooled transaction with the same signer, public key and
+    /// nonce, which had a lower priority fee.
+    Replaced { replaced_hash: CryptoHash },
+    /// The pool has a transaction with the same signer, public key and nonce, and the priority
+    /// fee of the new one isn't high enough to replace it.
+    Underpriced { required_priority_fee: Balance },
+    /// The signer account already has the maximum number of transactions in the pool.
+    AccountTransactionsLimitExceeded,
+    /// The transactions of the signer account would exceed the maximum total size.
+    AccountBytesLimitExceeded,
+    /// The pool is full and the transaction isn't worth more than the transaction that would be
+    /// evicted for it.
+    PoolFull,
+}
+
+/// Limits on the transactions kept in the pool of a shard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionPoolLimits {
+    /// Maximum number of transactions in the pool.  When it is reached, the lowest-priced
+    /// transactions of the accounts with the most transactions are evicted first.
+    pub max_transactions: usize,
+    /// Maximum number of transactions of a single signer account.
+    pub max_transactions_per_account: usize,
+    /// Maximum total size in bytes of the transactions of a single signer account.
+    pub max_bytes_per_account: u64,
+}
@@ -345,6 +345,18 @@ impl From<NearActions> for Vec<crate::models::Operation> {
                     );
                 }
 
+                near_primitives::transaction::Action::NonrefundableStorageTransfer(action) => {
+                    // The deposit leaves the liquid balance of the sender, but it doesn't reach
+                    // the liquid balance of the receiver.
+                    operations.push(
+                        validated_operations::TransferOperation {
+                            account: sender_account_identifier.clone(),
+                            amount: -crate::models::Amount::from_yoctonear(action.deposit),
+                        }
+                        .into_operation(crate::models::OperationIdentifier::new(&operations)),
+                    );
+                }
+
                 #[cfg(feature = "protocol_feature_chunk_only_producers")]
                 near_primitives::transaction::Action::StakeChunkOnly(action) => {
                     operations.push(
@@ -711,6 +723,7 @@ mod tests {
                         amount: 5000000000000000000,
                         code_hash: near_primitives::hash::CryptoHash::default(),
                         locked: 400000000000000000000000000000,
+                        nonrefundable: 0,
                         storage_paid_at: 0,
                         storage_usage: 200000,
                     },
@@ -726,6 +739,7 @@ mod tests {
                         amount: 4000000000000000000,
                         code_hash: near_primitives::hash::CryptoHash::default(),
                         locked: 400000000000000000000000000000,
+                        nonrefundable: 0,
                         storage_paid_at: 0,
                         storage_usage: 200000,
                     },
@@ -739,6 +753,7 @@ mod tests {
                         amount: 7000000000000000000,
                         code_hash: near_primitives::hash::CryptoHash::default(),
                         locked: 400000000000000000000000000000,
+                        nonrefundable: 0,
                         storage_paid_at: 0,
                         storage_usage: 200000,
                     },
@@ -754,6 +769,7 @@ mod tests {
                         amount: 8000000000000000000,
                         code_hash: near_primitives::hash::CryptoHash::default(),
                         locked: 400000000000000000000000000000,
+                        nonrefundable: 0,
                         storage_paid_at: 0,
                         storage_usage: 200000,
                     },
@@ -767,6 +783,7 @@ mod tests {
                 amount: 4000000000000000000,
                 code_hash: near_primitives::hash::CryptoHash::default(),
                 locked: 400000000000000000000000000000,
+                nonrefundable: 0,
                 storage_paid_at: 0,
                 storage_usage: 200000,
             },
@@ -777,6 +794,7 @@ mod tests {
                 amount: 6000000000000000000,
                 code_hash: near_primitives::hash::CryptoHash::default(),
                 locked: 400000000000000000000000000000,
+                nonrefundable: 0,
                 storage_paid_at: 0,
                 storage_usage: 200000,
             },
@@ -599,6 +599,7 @@ async fn construction_payloads(
         nonce: signer_public_access_key_nonce,
         receiver_id: receiver_account_id,
         actions,
+        priority_fee: None,
     };
 
     let (transaction_hash, _) = unsigned_transaction.get_hash_and_size().clone();
@@ -607,7 +608,7 @@ async fn construction_payloads(
         unsigned_transaction: unsigned_transaction.into(),
         payloads: vec![models::SigningPayload {
             account_identifier: signer_account_id.into(),
-            signature_type: Some(signer_public_access_key.key_type().into()),
+            signature_type: Some(signer_public_access_key.key_type().try_into()?),
             hex_bytes: transaction_hash.as_ref().to_owned().into(),
         }],
     }))
@@ -1102,6 +1102,9 @@ impl TryFrom<&PublicKey> for near_crypto::PublicKey {
             CurveType::Secp256k1 => {
                 near_crypto::PublicKey::SECP256K1((hex_bytes.as_ref() as &[u8]).try_into()?)
             }
+            CurveType::Bls12381G1 => {
+                near_crypto::PublicKey::BLS12381((hex_bytes.as_ref() as &[u8]).try_into()?)
+            }
         })
     }
 }
@@ -1114,6 +1117,9 @@ pub(crate) enum CurveType {
     Edwards25519,
     /// SEC compressed - `33 bytes` (https://secg.org/sec1-v2.pdf#subsubsection.2.3.3)
     Secp256k1,
+    /// Compressed G1 point - `48 bytes` (https://www.ietf.org/archive/id/draft-irtf-cfrg-pairing-friendly-curves-10.html#name-zcash-serialization-format-)
+    #[serde(rename = "bls12381_g1")]
+    Bls12381G1,
 }
 
 impl From<near_crypto::KeyType> for CurveType {
@@ -1121,6 +1127,7 @@ impl From<near_crypto::KeyType> for CurveType {
         match key_type {
             near_crypto::KeyType::ED25519 => Self::Edwards25519,
             near_crypto::KeyType::SECP256K1 => Self::Secp256k1,
+            near_crypto::KeyType::BLS12381 => Self::Bls12381G1,
         }
     }
 }
@@ -1179,12 +1186,17 @@ pub(crate) enum SignatureType {
      * Schnorr1, */
 }
 
-impl From<near_crypto::KeyType> for SignatureType {
-    fn from(key_type: near_crypto::KeyType) -> Self {
+impl TryFrom<near_crypto::KeyType> for SignatureType {
+    type Error = crate::errors::ErrorKind;
+
+    fn try_from(key_type: near_crypto::KeyType) -> Result<Self, Self::Error> {
         match key_type {
-            near_crypto::KeyType::ED25519 => Self::Ed25519,
-            near_crypto::KeyType::SECP256K1 => {
-                unimplemented!("SECP256K1 keys are not implemented in Rosetta yet")
+            near_crypto::KeyType::ED25519 => Ok(Self::Ed25519),
+            near_crypto::KeyType::SECP256K1 | near_crypto::KeyType::BLS12381 => {
+                Err(crate::errors::ErrorKind::InvalidInput(format!(
+                    "{} keys are not supported in Rosetta yet",
+                    key_type
+                )))
             }
         }
     }
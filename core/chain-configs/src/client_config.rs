@@ -4,11 +4,28 @@ use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 
+use near_primitives::config::VMLimitConfig;
+use near_primitives::hash::CryptoHash;
 use near_primitives::types::{AccountId, BlockHeightDelta, Gas, NumBlocks, NumSeats, ShardId};
 use near_primitives::version::Version;
 
 pub const TEST_STATE_SYNC_TIMEOUT: u64 = 5;
 
+/// Default minimum priority fee bump, in percent, for a transaction to replace a pooled
+/// transaction with the same signer, public key and nonce.
+pub const DEFAULT_TRANSACTION_REPLACEMENT_FEE_BUMP_PERCENT: u64 = 10;
+
+/// Default maximum number of transactions in the transaction pool of a shard.
+pub const DEFAULT_TRANSACTION_POOL_MAX_TRANSACTIONS: usize = 100_000;
+
+/// Default maximum number of transactions of a single signer account in the transaction pool of
+/// a shard.
+pub const DEFAULT_TRANSACTION_POOL_MAX_TRANSACTIONS_PER_ACCOUNT: usize = 1_000;
+
+/// Default maximum total size of the transactions of a single signer account in the transaction
+/// pool of a shard.
+pub const DEFAULT_TRANSACTION_POOL_MAX_BYTES_PER_ACCOUNT: u64 = 4 * 1024 * 1024;
+
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub enum LogSummaryStyle {
     #[serde(rename = "plain")]
@@ -17,6 +34,69 @@ pub enum LogSummaryStyle {
     Colored,
 }
 
+/// Register limits for view calls of this node.  They can only be tighter than the limits of
+/// the protocol, a limit above the protocol one has no effect.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ViewRegisterLimits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_number_registers: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_register_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registers_memory_limit: Option<u64>,
+}
+
+impl ViewRegisterLimits {
+    /// Lowers the register limits of `limit_config` to the ones set here.
+    pub fn restrict(&self, limit_config: &mut VMLimitConfig) {
+        for (limit, view_limit) in [
+            (&mut limit_config.max_number_registers, self.max_number_registers),
+            (&mut limit_config.max_register_size, self.max_register_size),
+            (&mut limit_config.registers_memory_limit, self.registers_memory_limit),
+        ] {
+            if let Some(view_limit) = view_limit {
+                *limit = min(*limit, view_limit);
+            }
+        }
+    }
+}
+
+/// View calls into staking pool contracts which let the node report the stake delegated to a
+/// pool through the `EpochInfoProvider::delegated_stake` of view calls.  Off unless configured,
+/// since it executes contract code.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DelegatedStakeViewConfig {
+    /// View method of the pool called with `{"account_id": <delegator>}`, which returns the
+    /// staked balance of the delegator as a decimal string.
+    #[serde(default = "default_delegated_stake_view_method_name")]
+    pub method_name: String,
+    /// Only pools running a contract with one of these code hashes are called.  Empty denies
+    /// every pool.
+    #[serde(default)]
+    pub allowed_code_hashes: Vec<CryptoHash>,
+    /// Maximum gas burnt by a single view call.
+    #[serde(default = "default_delegated_stake_view_max_gas_burnt")]
+    pub max_gas_burnt: Gas,
+}
+
+fn default_delegated_stake_view_method_name() -> String {
+    "get_account_staked_balance".to_string()
+}
+
+fn default_delegated_stake_view_max_gas_burnt() -> Gas {
+    10_000_000_000_000
+}
+
+impl Default for DelegatedStakeViewConfig {
+    fn default() -> Self {
+        Self {
+            method_name: default_delegated_stake_view_method_name(),
+            allowed_code_hashes: vec![],
+            max_gas_burnt: default_delegated_stake_view_max_gas_burnt(),
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ClientConfig {
     /// Version of the binary.
@@ -51,6 +131,10 @@ pub struct ClientConfig {
     pub header_sync_stall_ban_timeout: Duration,
     /// Expected increase of header head weight per second during header sync
     pub header_sync_expected_height_per_second: u64,
+    /// Maximum number of header batches requested from different peers at the same time
+    pub header_sync_max_outstanding_batches: usize,
+    /// How long to wait for a peer to return a header batch before asking another peer
+    pub header_sync_batch_timeout: Duration,
     /// How long to wait for a response during state sync
     pub state_sync_timeout: Duration,
     /// Minimum number of peers to start syncing.
@@ -77,6 +161,14 @@ pub struct ClientConfig {
     pub catchup_step_period: Duration,
     /// Time between checking to re-request chunks.
     pub chunk_request_retry_period: Duration,
+    /// Number of consecutive blocks without a new chunk for a shard after which block producers
+    /// wait longer for the chunk of that shard.
+    pub missed_chunks_threshold: NumBlocks,
+    /// How much longer block producers wait for the chunk of a shard for each missed chunk
+    /// starting from `missed_chunks_threshold`.
+    pub missed_chunks_wait_step: Duration,
+    /// Upper bound of the extra wait for the chunk of a shard.
+    pub missed_chunks_max_wait: Duration,
     /// Time between running doomslug timer.
     pub doosmslug_step_period: Duration,
     /// Behind this horizon header fetch kicks in.
@@ -95,12 +187,35 @@ pub struct ClientConfig {
     pub epoch_sync_enabled: bool,
     /// Number of seconds between state requests for view client.
     pub view_client_throttle_period: Duration,
+    /// Whether to respond to state sync requests of other nodes.
+    pub serve_state_sync: bool,
     /// Upper bound of the byte size of contract state that is still viewable. None is no limit
     pub trie_viewer_state_size_limit: Option<u64>,
     /// Max burnt gas per view method.  If present, overrides value stored in
     /// genesis file.  The value only affects the RPCs without influencing the
     /// protocol thus changing it per-node doesn’t affect the blockchain.
     pub max_gas_burnt_view: Option<Gas>,
+    /// Register limits for view methods, tighter than the ones of the protocol.  Like
+    /// `max_gas_burnt_view` they only affect the RPCs.
+    pub view_register_limits: ViewRegisterLimits,
+    /// Prune execution outcomes of blocks older than this many epochs. Ignored on archival
+    /// nodes.
+    pub prune_outcomes_older_than_epochs: Option<u64>,
+    /// Save the receipts added to and removed from the delayed receipts queue and the postponed
+    /// receipts by every applied chunk.  Only used by the indexer.
+    pub save_receipt_queue_changes: bool,
+    /// How much higher, in percent, the priority fee of a transaction has to be to replace the
+    /// pooled transaction with the same signer, public key and nonce.
+    pub transaction_replacement_fee_bump_percent: u64,
+    /// Maximum number of transactions in the transaction pool of a shard.  When it is reached,
+    /// the lowest-priced transactions of the accounts with the most transactions are evicted.
+    pub transaction_pool_max_transactions: usize,
+    /// Maximum number of transactions of a single signer account in the pool of a shard.
+    pub transaction_pool_max_transactions_per_account: usize,
+    /// Maximum total size of the transactions of a single signer account in the pool of a shard.
+    pub transaction_pool_max_bytes_per_account: u64,
+    /// View calls into staking pools to report delegated stake.  None disables them.
+    pub delegated_stake_view: Option<DelegatedStakeViewConfig>,
 }
 
 impl ClientConfig {
@@ -133,6 +248,8 @@ impl ClientConfig {
             header_sync_stall_ban_timeout: Duration::from_secs(30),
             state_sync_timeout: Duration::from_secs(TEST_STATE_SYNC_TIMEOUT),
             header_sync_expected_height_per_second: 1,
+            header_sync_max_outstanding_batches: 4,
+            header_sync_batch_timeout: Duration::from_secs(5),
             min_num_peers: 1,
             log_summary_period: Duration::from_secs(10),
             produce_empty_blocks: true,
@@ -147,6 +264,9 @@ impl ClientConfig {
                 Duration::from_millis(100),
                 Duration::from_millis(min_block_prod_time / 5),
             ),
+            missed_chunks_threshold: 3,
+            missed_chunks_wait_step: Duration::from_millis(min_block_prod_time / 10),
+            missed_chunks_max_wait: Duration::from_millis(min_block_prod_time / 2),
             doosmslug_step_period: Duration::from_millis(100),
             block_header_fetch_horizon: 50,
             gc_blocks_limit: 100,
@@ -157,8 +277,19 @@ impl ClientConfig {
             view_client_threads: 1,
             epoch_sync_enabled,
             view_client_throttle_period: Duration::from_secs(1),
+            serve_state_sync: true,
             trie_viewer_state_size_limit: None,
+            prune_outcomes_older_than_epochs: None,
             max_gas_burnt_view: None,
+            view_register_limits: ViewRegisterLimits::default(),
+            save_receipt_queue_changes: false,
+            transaction_replacement_fee_bump_percent:
+                DEFAULT_TRANSACTION_REPLACEMENT_FEE_BUMP_PERCENT,
+            transaction_pool_max_transactions: DEFAULT_TRANSACTION_POOL_MAX_TRANSACTIONS,
+            transaction_pool_max_transactions_per_account:
+                DEFAULT_TRANSACTION_POOL_MAX_TRANSACTIONS_PER_ACCOUNT,
+            transaction_pool_max_bytes_per_account: DEFAULT_TRANSACTION_POOL_MAX_BYTES_PER_ACCOUNT,
+            delegated_stake_view: None,
         }
     }
 }
@@ -4,7 +4,7 @@
 //! contains `RuntimeConfig`, but we keep it here for now until we figure
 //! out the better place.
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
 use std::{fmt, io};
 
@@ -20,17 +20,17 @@ use tracing::{info, warn};
 
 use crate::genesis_validate::validate_genesis;
 use near_primitives::epoch_manager::{AllEpochConfig, EpochConfig, ShardConfig};
-use near_primitives::shard_layout::ShardLayout;
+use near_primitives::shard_layout::{account_id_to_shard_id, ShardLayout};
 use near_primitives::types::validator_stake::ValidatorStake;
 use near_primitives::version::ProtocolFeature;
 use near_primitives::{
     hash::CryptoHash,
     runtime::config::RuntimeConfig,
     serialize::{u128_dec_format, u128_dec_format_compatible},
-    state_record::StateRecord,
+    state_record::{state_record_to_account_id, StateRecord},
     types::{
         AccountId, AccountInfo, Balance, BlockHeight, BlockHeightDelta, EpochHeight, Gas,
-        NumBlocks, NumSeats,
+        NumBlocks, NumSeats, ShardId,
     },
     version::ProtocolVersion,
 };
@@ -125,6 +125,11 @@ pub struct GenesisConfig {
     pub block_producer_kickout_threshold: u8,
     /// Criterion for kicking out chunk producers (this is a number between 0 and 100)
     pub chunk_producer_kickout_threshold: u8,
+    /// Percentage (0 to 100) of the validators' stake which must remain after kicking out
+    /// offline validators in one epoch. Validators with the best uptime are exempted from
+    /// kickout until this holds. 0 disables the limit.
+    #[serde(default)]
+    pub validator_min_remaining_stake_perc: u8,
     /// Online minimum threshold below which validator doesn't receive reward.
     #[serde(default = "default_online_min_threshold")]
     #[default(Rational::new(90, 100))]
@@ -181,6 +186,11 @@ pub struct GenesisConfig {
     #[serde(default = "default_minimum_stake_ratio")]
     #[default(Rational::new(160, 1_000_000))]
     pub minimum_stake_ratio: Rational,
+    /// Records files of the sharded records format, one per shard of `shard_layout` in the order
+    /// of shard ids. Paths are relative to the directory of the genesis config file. If empty,
+    /// records are stored in the genesis file itself or in a single records file.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub records_shard_files: Vec<String>,
 }
 
 impl From<&GenesisConfig> for EpochConfig {
@@ -194,6 +204,7 @@ impl From<&GenesisConfig> for EpochConfig {
                 .clone(),
             block_producer_kickout_threshold: config.block_producer_kickout_threshold,
             chunk_producer_kickout_threshold: config.chunk_producer_kickout_threshold,
+            validator_min_remaining_stake_perc: config.validator_min_remaining_stake_perc,
             fishermen_threshold: config.fishermen_threshold,
             online_min_threshold: config.online_min_threshold,
             online_max_threshold: config.online_max_threshold,
@@ -271,6 +282,7 @@ pub struct GenesisRecords(pub Vec<StateRecord>);
 pub struct Genesis {
     #[serde(flatten)]
     pub config: GenesisConfig,
+    #[serde(default)]
     pub records: GenesisRecords,
     /// Genesis object may not contain records.
     /// In this case records can be found in records_file.
@@ -278,6 +290,9 @@ pub struct Genesis {
     /// so they should be processed in streaming fashion with for_each_record.
     #[serde(skip)]
     pub records_file: PathBuf,
+    /// Directory against which `config.records_shard_files` are resolved.
+    #[serde(skip)]
+    pub records_dir: PathBuf,
 }
 
 impl AsRef<GenesisConfig> for &Genesis {
@@ -430,7 +445,9 @@ impl<'de, F: FnMut(StateRecord)> DeserializeSeed<'de> for RecordsProcessor<&'_ m
     }
 }
 
-fn stream_records_from_file(
+/// Reads records one by one from either a records file or a full genesis file and passes them to
+/// the callback, without loading all of them into memory.
+pub fn stream_records_from_file(
     reader: impl Read,
     mut callback: impl FnMut(StateRecord),
 ) -> serde_json::Result<()> {
@@ -484,14 +501,22 @@ impl Genesis {
         Self::new_with_path_validated(config, records_file, GenesisValidationMode::Full)
     }
 
-    /// Reads Genesis from a single file.
+    /// Reads Genesis from a single file, or from the genesis config file and the sharded records
+    /// files it refers to.
     pub fn from_file<P: AsRef<Path>>(path: P, genesis_validation: GenesisValidationMode) -> Self {
+        let path = path.as_ref();
         let reader = BufReader::new(File::open(path).expect("Could not open genesis config file."));
         let genesis: Genesis =
             serde_json::from_reader(reader).expect("Failed to deserialize the genesis records.");
         // As serde skips the `records_file` field, we can assume that `Genesis` has `records` and
         // doesn't have `records_file`.
-        Self::new_validated(genesis.config, genesis.records, genesis_validation)
+        let genesis = Self {
+            config: genesis.config,
+            records: genesis.records,
+            records_file: PathBuf::new(),
+            records_dir: records_dir(path),
+        };
+        genesis.validate(genesis_validation)
     }
 
     /// Reads Genesis from config and records files.
@@ -504,8 +529,18 @@ impl Genesis {
         P1: AsRef<Path>,
         P2: AsRef<Path>,
     {
-        let config = GenesisConfig::from_file(config_path).unwrap();
-        Self::new_with_path_validated(config, records_path, genesis_validation)
+        let config = GenesisConfig::from_file(&config_path).unwrap();
+        if !config.records_shard_files.is_empty() {
+            let records_path = records_path.as_ref().display();
+            warn!(target: "genesis", "Genesis config refers to sharded records, ignoring {}", records_path);
+        }
+        let genesis = Self {
+            config,
+            records: GenesisRecords(vec![]),
+            records_file: records_path.as_ref().to_path_buf(),
+            records_dir: records_dir(config_path.as_ref()),
+        };
+        genesis.validate(genesis_validation)
     }
 
     fn new_validated(
@@ -513,7 +548,8 @@ impl Genesis {
         records: GenesisRecords,
         genesis_validation: GenesisValidationMode,
     ) -> Self {
-        let genesis = Self { config, records, records_file: PathBuf::new() };
+        let genesis =
+            Self { config, records, records_file: PathBuf::new(), records_dir: PathBuf::new() };
         genesis.validate(genesis_validation)
     }
 
@@ -526,6 +562,7 @@ impl Genesis {
             config,
             records: GenesisRecords(vec![]),
             records_file: records_file.as_ref().to_path_buf(),
+            records_dir: PathBuf::new(),
         };
         genesis.validate(genesis_validation)
     }
@@ -563,9 +600,29 @@ impl Genesis {
         stream_records_from_file(reader, callback).map_err(io::Error::from)
     }
 
-    /// If records vector is empty processes records stream from records_file.
+    /// Whether records are stored in the sharded records format.
+    pub fn has_sharded_records(&self) -> bool {
+        self.records.as_ref().is_empty() && !self.config.records_shard_files.is_empty()
+    }
+
+    fn records_shard_path(&self, shard_id: ShardId) -> PathBuf {
+        self.records_dir.join(&self.config.records_shard_files[shard_id as usize])
+    }
+
+    /// If records vector is empty processes records stream from the sharded records files or
+    /// from records_file.
     /// May panic if records_file is removed or is in wrong format.
     pub fn for_each_record(&self, mut callback: impl FnMut(&StateRecord)) {
+        if self.has_sharded_records() {
+            for shard_id in 0..self.config.records_shard_files.len() as ShardId {
+                self.for_each_shard_record(shard_id, &mut callback);
+            }
+        } else {
+            self.for_each_unsharded_record(callback);
+        }
+    }
+
+    fn for_each_unsharded_record(&self, mut callback: impl FnMut(&StateRecord)) {
         if self.records.as_ref().is_empty() {
             let callback_move = |record: StateRecord| {
                 callback(&record);
@@ -578,6 +635,87 @@ impl Genesis {
             }
         }
     }
+
+    /// Processes records of the given shard of the genesis shard layout. With the sharded records
+    /// format only the records file of the shard is read.
+    /// Panics if a sharded records file contains records of another shard.
+    pub fn for_each_shard_record(&self, shard_id: ShardId, mut callback: impl FnMut(&StateRecord)) {
+        let shard_layout = &self.config.shard_layout;
+        if self.has_sharded_records() {
+            let path = self.records_shard_path(shard_id);
+            let reader = BufReader::new(File::open(&path).unwrap_or_else(|err| {
+                panic!("Could not open records file {}: {}", path.display(), err)
+            }));
+            stream_records_from_file(reader, |record| {
+                let account_id = state_record_to_account_id(&record);
+                assert_eq!(
+                    account_id_to_shard_id(account_id, shard_layout),
+                    shard_id,
+                    "Record of {} in records file of the wrong shard",
+                    account_id
+                );
+                callback(&record);
+            })
+            .expect("error while streaming records");
+        } else {
+            self.for_each_unsharded_record(|record| {
+                let account_id = state_record_to_account_id(record);
+                if account_id_to_shard_id(account_id, shard_layout) == shard_id {
+                    callback(record);
+                }
+            });
+        }
+    }
+
+    /// Converts the genesis to the sharded records format: writes records of each shard to
+    /// `<dir>/records_shard_<shard_id>.json` and the genesis config referring to them to
+    /// `config_path`, which must be in `dir`.
+    pub fn write_sharded<P: AsRef<Path>>(&self, config_path: P) -> io::Result<()> {
+        let config_path = config_path.as_ref();
+        let dir = records_dir(config_path);
+        let num_shards = self.config.shard_layout.num_shards();
+        let mut config = self.config.clone();
+        config.records_shard_files =
+            (0..num_shards).map(|shard_id| format!("records_shard_{}.json", shard_id)).collect();
+
+        let mut writers = config
+            .records_shard_files
+            .iter()
+            .map(|file| Ok((BufWriter::new(File::create(dir.join(file))?), true)))
+            .collect::<io::Result<Vec<_>>>()?;
+        for (writer, _) in writers.iter_mut() {
+            writer.write_all(b"[")?;
+        }
+        let mut result = Ok(());
+        self.for_each_record(|record| {
+            if result.is_err() {
+                return;
+            }
+            let account_id = state_record_to_account_id(record);
+            let shard_id = account_id_to_shard_id(account_id, &self.config.shard_layout);
+            let (writer, first) = &mut writers[shard_id as usize];
+            result = write_record(writer, *first, record);
+            *first = false;
+        });
+        result?;
+        for (mut writer, _) in writers {
+            writer.write_all(b"\n]\n")?;
+            writer.flush()?;
+        }
+        config.to_file(config_path);
+        Ok(())
+    }
+}
+
+fn write_record(writer: &mut impl Write, first: bool, record: &StateRecord) -> io::Result<()> {
+    let separator: &[u8] = if first { b"\n" } else { b",\n" };
+    writer.write_all(separator)?;
+    serde_json::to_writer_pretty(writer, record)?;
+    Ok(())
+}
+
+fn records_dir(config_path: &Path) -> PathBuf {
+    config_path.parent().map(Path::to_path_buf).unwrap_or_default()
 }
 
 // Note: this type cannot be placed in primitives/src/view.rs because of `RuntimeConfig` dependency issues.
@@ -688,7 +826,7 @@ pub fn get_initial_supply(records: &[StateRecord]) -> Balance {
     let mut total_supply = 0;
     for record in records {
         if let StateRecord::Account { account, .. } = record {
-            total_supply += account.amount() + account.locked();
+            total_supply += account.amount() + account.locked() + account.nonrefundable();
         }
     }
     total_supply
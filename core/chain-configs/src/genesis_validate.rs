@@ -42,7 +42,7 @@ impl<'a> GenesisValidator<'a> {
                 if self.account_ids.contains(account_id) {
                     panic!("Duplicate account id {} in genesis records", account_id);
                 }
-                self.total_supply += account.locked() + account.amount();
+                self.total_supply += account.locked() + account.amount() + account.nonrefundable();
                 self.account_ids.insert(account_id.clone());
                 if account.locked() > 0 {
                     self.staked_accounts.insert(account_id.clone(), account.locked());
@@ -129,6 +129,13 @@ impl<'a> GenesisValidator<'a> {
             self.genesis_config.gas_price_adjustment_rate < Rational::from_integer(1),
             "Gas price adjustment rate must be less than 1"
         );
+        let records_shard_files = &self.genesis_config.records_shard_files;
+        assert!(
+            records_shard_files.is_empty()
+                || records_shard_files.len() as u64
+                    == self.genesis_config.shard_layout.num_shards(),
+            "Number of sharded records files doesn't match the number of shards"
+        );
     }
 }
 
@@ -2,8 +2,13 @@ mod client_config;
 mod genesis_config;
 pub mod genesis_validate;
 
-pub use client_config::{ClientConfig, LogSummaryStyle, TEST_STATE_SYNC_TIMEOUT};
+pub use client_config::{
+    ClientConfig, DelegatedStakeViewConfig, LogSummaryStyle, ViewRegisterLimits,
+    DEFAULT_TRANSACTION_POOL_MAX_BYTES_PER_ACCOUNT, DEFAULT_TRANSACTION_POOL_MAX_TRANSACTIONS,
+    DEFAULT_TRANSACTION_POOL_MAX_TRANSACTIONS_PER_ACCOUNT,
+    DEFAULT_TRANSACTION_REPLACEMENT_FEE_BUMP_PERCENT, TEST_STATE_SYNC_TIMEOUT,
+};
 pub use genesis_config::{
-    get_initial_supply, Genesis, GenesisConfig, GenesisRecords, GenesisValidationMode,
-    ProtocolConfig, ProtocolConfigView,
+    get_initial_supply, stream_records_from_file, Genesis, GenesisConfig, GenesisRecords,
+    GenesisValidationMode, ProtocolConfig, ProtocolConfigView,
 };
@@ -0,0 +1,388 @@
+//! BLS12-381 signatures, following the `BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_` ciphersuite
+//! of the IETF BLS signature standard: public keys are compressed points of G1 and signatures
+//! compressed points of G2.
+//!
+//! Verifying an aggregated signature of a single message against the public keys of its signers
+//! is only secure against rogue key attacks if every signer has proven the possession of its
+//! secret key, see [`BLS12381SecretKey::proof_of_possession`].
+use std::fmt::{Debug, Formatter};
+
+use blst::min_pk;
+use blst::BLST_ERROR;
+use rand_core::{OsRng, RngCore};
+
+use crate::errors::{ParseKeyError, ParseSignatureError};
+
+pub const BLS12381_PUBLIC_KEY_LENGTH: usize = 48;
+pub const BLS12381_SECRET_KEY_LENGTH: usize = 32;
+pub const BLS12381_SIGNATURE_LENGTH: usize = 96;
+
+/// Domain separation tag of the signatures of messages.
+const SIGNATURE_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+/// Domain separation tag of the proofs of possession.
+const POP_DST: &[u8] = b"BLS_POP_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+#[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct BLS12381PublicKey(pub(crate) [u8; BLS12381_PUBLIC_KEY_LENGTH]);
+
+#[cfg(feature = "deepsize_feature")]
+impl deepsize::DeepSizeOf for BLS12381PublicKey {
+    fn deep_size_of_children(&self, _context: &mut deepsize::Context) -> usize {
+        0
+    }
+}
+
+impl BLS12381PublicKey {
+    /// Decompresses the key, returning `None` if it isn't a point of G1 other than the identity.
+    fn point(&self) -> Option<min_pk::PublicKey> {
+        min_pk::PublicKey::key_validate(&self.0).ok()
+    }
+
+    /// Aggregates public keys into the key which verifies the aggregated signature of a single
+    /// message by all of them, see [`BLS12381Signature::fast_aggregate_verify`].
+    pub fn aggregate<'a>(
+        public_keys: impl IntoIterator<Item = &'a BLS12381PublicKey>,
+    ) -> Result<Self, ParseKeyError> {
+        let points = public_keys
+            .into_iter()
+            .map(|public_key| {
+                public_key.point().ok_or_else(|| ParseKeyError::InvalidData {
+                    error_message: format!("{:?} isn't a valid BLS12-381 public key", public_key),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let points: Vec<_> = points.iter().collect();
+        let aggregate = min_pk::AggregatePublicKey::aggregate(&points, false)
+            .map_err(|err| ParseKeyError::InvalidData { error_message: format!("{:?}", err) })?;
+        Ok(Self(aggregate.to_public_key().compress()))
+    }
+
+    /// Verifies that `proof` was produced by [`BLS12381SecretKey::proof_of_possession`] with the
+    /// secret key of this public key.
+    pub fn verify_proof_of_possession(&self, proof: &BLS12381Signature) -> bool {
+        proof.verify_with_dst(&self.0, self, POP_DST)
+    }
+}
+
+impl From<[u8; BLS12381_PUBLIC_KEY_LENGTH]> for BLS12381PublicKey {
+    fn from(data: [u8; BLS12381_PUBLIC_KEY_LENGTH]) -> Self {
+        Self(data)
+    }
+}
+
+impl TryFrom<&[u8]> for BLS12381PublicKey {
+    type Error = ParseKeyError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != BLS12381_PUBLIC_KEY_LENGTH {
+            return Err(Self::Error::InvalidLength {
+                expected_length: BLS12381_PUBLIC_KEY_LENGTH,
+                received_length: data.len(),
+            });
+        }
+        let mut public_key = Self([0; BLS12381_PUBLIC_KEY_LENGTH]);
+        public_key.0.copy_from_slice(data);
+        Ok(public_key)
+    }
+}
+
+impl AsRef<[u8]> for BLS12381PublicKey {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Debug for BLS12381PublicKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", bs58::encode(&self.0[..]).into_string())
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct BLS12381SecretKey(pub(crate) [u8; BLS12381_SECRET_KEY_LENGTH]);
+
+impl BLS12381SecretKey {
+    pub fn from_random() -> Self {
+        let mut ikm = [0u8; 32];
+        OsRng.fill_bytes(&mut ikm);
+        Self::from_ikm(&ikm)
+    }
+
+    /// Derives the secret key from the input keying material, which must be at least 32 bytes.
+    pub(crate) fn from_ikm(ikm: &[u8]) -> Self {
+        let secret_key = min_pk::SecretKey::key_gen(ikm, &[]).expect("ikm is too short");
+        Self(secret_key.to_bytes())
+    }
+
+    fn key(&self) -> min_pk::SecretKey {
+        // The bytes were checked when the key was created.
+        min_pk::SecretKey::from_bytes(&self.0).unwrap()
+    }
+
+    pub fn public_key(&self) -> BLS12381PublicKey {
+        BLS12381PublicKey(self.key().sk_to_pk().compress())
+    }
+
+    pub fn sign(&self, data: &[u8]) -> BLS12381Signature {
+        BLS12381Signature(self.key().sign(data, SIGNATURE_DST, &[]).compress())
+    }
+
+    /// Signs the public key of this secret key, proving that whoever shares the public key knows
+    /// the secret key.  The proof must be checked before aggregating the public key.
+    pub fn proof_of_possession(&self) -> BLS12381Signature {
+        let public_key = self.public_key();
+        BLS12381Signature(self.key().sign(&public_key.0, POP_DST, &[]).compress())
+    }
+}
+
+impl TryFrom<&[u8]> for BLS12381SecretKey {
+    type Error = ParseKeyError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != BLS12381_SECRET_KEY_LENGTH {
+            return Err(Self::Error::InvalidLength {
+                expected_length: BLS12381_SECRET_KEY_LENGTH,
+                received_length: data.len(),
+            });
+        }
+        let secret_key = min_pk::SecretKey::from_bytes(data)
+            .map_err(|err| Self::Error::InvalidData { error_message: format!("{:?}", err) })?;
+        Ok(Self(secret_key.to_bytes()))
+    }
+}
+
+impl AsRef<[u8]> for BLS12381SecretKey {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Debug for BLS12381SecretKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", bs58::encode(&self.0[..]).into_string())
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct BLS12381Signature(pub(crate) [u8; BLS12381_SIGNATURE_LENGTH]);
+
+impl BLS12381Signature {
+    fn point(&self) -> Option<min_pk::Signature> {
+        min_pk::Signature::from_bytes(&self.0).ok()
+    }
+
+    fn verify_with_dst(&self, data: &[u8], public_key: &BLS12381PublicKey, dst: &[u8]) -> bool {
+        match (self.point(), public_key.point()) {
+            (Some(signature), Some(public_key)) => {
+                signature.verify(true, data, dst, &[], &public_key, false)
+                    == BLST_ERROR::BLST_SUCCESS
+            }
+            _ => false,
+        }
+    }
+
+    pub fn verify(&self, data: &[u8], public_key: &BLS12381PublicKey) -> bool {
+        self.verify_with_dst(data, public_key, SIGNATURE_DST)
+    }
+
+    /// Aggregates signatures, of the same or of different messages, into a single signature.
+    pub fn aggregate<'a>(
+        signatures: impl IntoIterator<Item = &'a BLS12381Signature>,
+    ) -> Result<Self, ParseSignatureError> {
+        let points = signatures
+            .into_iter()
+            .map(|signature| {
+                signature.point().ok_or_else(|| ParseSignatureError::InvalidData {
+                    error_message: format!("{:?} isn't a valid BLS12-381 signature", signature),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let points: Vec<_> = points.iter().collect();
+        let aggregate = min_pk::AggregateSignature::aggregate(&points, true).map_err(|err| {
+            ParseSignatureError::InvalidData { error_message: format!("{:?}", err) }
+        })?;
+        Ok(Self(aggregate.to_signature().compress()))
+    }
+
+    /// Verifies an aggregated signature of `data` by all of `public_keys`.  Their proofs of
+    /// possession must have been checked.
+    pub fn fast_aggregate_verify(&self, data: &[u8], public_keys: &[BLS12381PublicKey]) -> bool {
+        let points =
+            match public_keys.iter().map(BLS12381PublicKey::point).collect::<Option<Vec<_>>>() {
+                Some(points) if !points.is_empty() => points,
+                _ => return false,
+            };
+        let points: Vec<_> = points.iter().collect();
+        match self.point() {
+            Some(signature) => {
+                signature.fast_aggregate_verify(true, data, SIGNATURE_DST, &points)
+                    == BLST_ERROR::BLST_SUCCESS
+            }
+            None => false,
+        }
+    }
+
+    /// Verifies an aggregated signature of each of `messages` by the public key at the same
+    /// index of `public_keys`.
+    pub fn aggregate_verify(&self, messages: &[&[u8]], public_keys: &[BLS12381PublicKey]) -> bool {
+        if messages.is_empty() || messages.len() != public_keys.len() {
+            return false;
+        }
+        let points =
+            match public_keys.iter().map(BLS12381PublicKey::point).collect::<Option<Vec<_>>>() {
+                Some(points) => points,
+                None => return false,
+            };
+        let points: Vec<_> = points.iter().collect();
+        match self.point() {
+            Some(signature) => {
+                signature.aggregate_verify(true, messages, SIGNATURE_DST, &points, false)
+                    == BLST_ERROR::BLST_SUCCESS
+            }
+            None => false,
+        }
+    }
+}
+
+impl From<[u8; BLS12381_SIGNATURE_LENGTH]> for BLS12381Signature {
+    fn from(data: [u8; BLS12381_SIGNATURE_LENGTH]) -> Self {
+        Self(data)
+    }
+}
+
+impl TryFrom<&[u8]> for BLS12381Signature {
+    type Error = ParseSignatureError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if data.len() != BLS12381_SIGNATURE_LENGTH {
+            return Err(Self::Error::InvalidLength {
+                expected_length: BLS12381_SIGNATURE_LENGTH,
+                received_length: data.len(),
+            });
+        }
+        let mut signature = Self([0; BLS12381_SIGNATURE_LENGTH]);
+        signature.0.copy_from_slice(data);
+        Ok(signature)
+    }
+}
+
+impl AsRef<[u8]> for BLS12381Signature {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Debug for BLS12381Signature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", bs58::encode(&self.0[..]).into_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    fn from_hex<T: for<'a> TryFrom<&'a [u8]>>(data: &str) -> T
+    where
+        for<'a> <T as TryFrom<&'a [u8]>>::Error: Debug,
+    {
+        T::try_from(&hex::decode(data).unwrap()[..]).unwrap()
+    }
+
+    /// Test vectors of the `BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_` ciphersuite, as published
+    /// in the Ethereum consensus spec tests.
+    #[test]
+    fn test_vectors() {
+        let public_keys = [
+            (
+                "263dbd792f5b1be47ed85f8938c0f29586af0d3ac7b977f21c278fe1462040e3",
+                "a491d1b0ecd9bb917989f0e74f0dea0422eac4a873e5e2644f368dffb9a6e20fd6e10c1b77654d067c0618f6e5a7f79a",
+            ),
+            (
+                "47b8192d77bf871b62e87859d653922725724a5c031afeabc60bcef5ff665138",
+                "b301803f8b5ac4a1133581fc676dfedc60d891dd5fa99028805e5ea5b08d3491af75d0707adab3b70c6a6a580217bf81",
+            ),
+            (
+                "328388aff0d4a5b7dc9205abd374e7e98f3cd9f3418edb4eafda5fb16473d216",
+                "b53d21a4cfd562c469cc81514d4ce5a6b577d8403d32a394dc265dd190b47fa9f829fdd7963afdf972e5e77854051f6f",
+            ),
+        ];
+        for (secret_key, public_key) in public_keys {
+            let secret_key: BLS12381SecretKey = from_hex(secret_key);
+            assert_eq!(secret_key.public_key(), from_hex(public_key));
+        }
+
+        let secret_key: BLS12381SecretKey = from_hex(public_keys[0].0);
+        let signature: BLS12381Signature = from_hex(
+            "b6ed936746e01f8ecf281f020953fbf1f01debd5657c4a383940b020b26507f6076334f91e2366c96e9ab279fb5158090352ea1c5b0c9274504f4f0e7053af24802e51e4568d164fe986834f41e55c8e850ce1f98458c0cfc9ab380b55285a55",
+        );
+        let message = [0u8; 32];
+        assert_eq!(secret_key.sign(&message), signature);
+        assert!(signature.verify(&message, &secret_key.public_key()));
+        assert!(!signature.verify(&[1u8; 32], &secret_key.public_key()));
+    }
+
+    #[test]
+    fn test_proof_of_possession() {
+        let secret_key = BLS12381SecretKey::from_random();
+        let other = BLS12381SecretKey::from_random();
+        let proof = secret_key.proof_of_possession();
+        assert!(secret_key.public_key().verify_proof_of_possession(&proof));
+        assert!(!other.public_key().verify_proof_of_possession(&proof));
+        // A proof of possession isn't a signature of the public key.
+        assert!(!proof.verify(&secret_key.public_key().0, &secret_key.public_key()));
+    }
+
+    #[test]
+    fn test_invalid_points() {
+        let secret_key = BLS12381SecretKey::from_random();
+        let signature = secret_key.sign(b"123");
+        let invalid_key = BLS12381PublicKey([0; BLS12381_PUBLIC_KEY_LENGTH]);
+        assert!(!signature.verify(b"123", &invalid_key));
+        assert!(BLS12381PublicKey::aggregate([&secret_key.public_key(), &invalid_key]).is_err());
+        let invalid_signature = BLS12381Signature([0; BLS12381_SIGNATURE_LENGTH]);
+        assert!(!invalid_signature.verify(b"123", &secret_key.public_key()));
+        assert!(BLS12381Signature::aggregate([&signature, &invalid_signature]).is_err());
+        assert!(!signature.fast_aggregate_verify(b"123", &[]));
+    }
+
+    #[test]
+    fn test_aggregate_verify_matches_individual_verify() {
+        for seed in 0..16 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let secret_keys: Vec<_> = (0..rng.gen_range(1, 6))
+                .map(|_| BLS12381SecretKey::from_ikm(&rng.gen::<[u8; 32]>()))
+                .collect();
+            let public_keys: Vec<_> = secret_keys.iter().map(|key| key.public_key()).collect();
+            let messages: Vec<Vec<u8>> = (0..rng.gen_range(1, 6))
+                .map(|_| (0..rng.gen_range(0, 64)).map(|_| rng.gen()).collect())
+                .collect();
+            let messages: Vec<&[u8]> =
+                secret_keys.iter().zip(messages.iter().cycle()).map(|(_, m)| &m[..]).collect();
+            let mut signatures: Vec<_> =
+                secret_keys.iter().zip(&messages).map(|(key, message)| key.sign(message)).collect();
+            // Replaces one signature with a signature of another message half of the time.
+            if rng.gen_bool(0.5) {
+                let index = rng.gen_range(0, signatures.len());
+                signatures[index] = secret_keys[index].sign(b"tampered");
+            }
+            let individually_valid =
+                signatures.iter().zip(&messages).zip(&public_keys).all(
+                    |((signature, message), public_key)| signature.verify(message, public_key),
+                );
+
+            let aggregate = BLS12381Signature::aggregate(&signatures).unwrap();
+            assert_eq!(aggregate.aggregate_verify(&messages, &public_keys), individually_valid);
+
+            let message = messages[0];
+            let signatures: Vec<_> = secret_keys.iter().map(|key| key.sign(message)).collect();
+            let aggregate = BLS12381Signature::aggregate(&signatures).unwrap();
+            assert!(aggregate.fast_aggregate_verify(message, &public_keys));
+            let aggregate_key = BLS12381PublicKey::aggregate(&public_keys).unwrap();
+            assert!(aggregate.verify(message, &aggregate_key));
+        }
+    }
+}
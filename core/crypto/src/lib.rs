@@ -1,3 +1,4 @@
+pub use bls::{BLS12381PublicKey, BLS12381SecretKey, BLS12381Signature};
 pub use errors::{ParseKeyError, ParseKeyTypeError, ParseSignatureError};
 pub use key_file::KeyFile;
 pub use signature::{
@@ -13,6 +14,7 @@ mod traits;
 #[macro_use]
 mod util;
 
+mod bls;
 mod errors;
 pub mod key_conversion;
 mod key_file;
@@ -13,12 +13,18 @@ use rand_core::OsRng;
 use secp256k1::Message;
 use serde::{Deserialize, Serialize};
 
+use crate::bls::{
+    BLS12381PublicKey, BLS12381SecretKey, BLS12381Signature, BLS12381_PUBLIC_KEY_LENGTH,
+    BLS12381_SECRET_KEY_LENGTH, BLS12381_SIGNATURE_LENGTH,
+};
+
 pub static SECP256K1: Lazy<secp256k1::Secp256k1> = Lazy::new(secp256k1::Secp256k1::new);
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub enum KeyType {
     ED25519 = 0,
     SECP256K1 = 1,
+    BLS12381 = 2,
 }
 
 impl Display for KeyType {
@@ -29,6 +35,7 @@ impl Display for KeyType {
             match self {
                 KeyType::ED25519 => "ed25519",
                 KeyType::SECP256K1 => "secp256k1",
+                KeyType::BLS12381 => "bls12381",
             },
         )
     }
@@ -42,6 +49,7 @@ impl FromStr for KeyType {
         match lowercase_key_type.as_str() {
             "ed25519" => Ok(KeyType::ED25519),
             "secp256k1" => Ok(KeyType::SECP256K1),
+            "bls12381" => Ok(KeyType::BLS12381),
             _ => Err(Self::Err::UnknownKeyType { unknown_key_type: lowercase_key_type }),
         }
     }
@@ -54,6 +62,7 @@ impl TryFrom<u8> for KeyType {
         match value {
             0 => Ok(KeyType::ED25519),
             1 => Ok(KeyType::SECP256K1),
+            2 => Ok(KeyType::BLS12381),
             unknown_key_type => {
                 Err(Self::Error::UnknownKeyType { unknown_key_type: unknown_key_type.to_string() })
             }
@@ -71,6 +80,32 @@ fn split_key_type_data(value: &str) -> Result<(KeyType, &str), crate::errors::Pa
     }
 }
 
+/// Decodes base58 `data` which must be exactly `N` bytes long.
+fn decode_bs58_array<const N: usize>(data: &str) -> Result<[u8; N], crate::errors::ParseKeyError> {
+    let mut array = [0; N];
+    let length = bs58::decode(data).into(&mut array[..]).map_err(|err| {
+        crate::errors::ParseKeyError::InvalidData { error_message: err.to_string() }
+    })?;
+    if length != N {
+        return Err(crate::errors::ParseKeyError::InvalidLength {
+            expected_length: N,
+            received_length: length,
+        });
+    }
+    Ok(array)
+}
+
+/// Borsh only implements deserialization of arrays of some lengths.
+fn deserialize_array<const N: usize>(buf: &mut &[u8]) -> Result<[u8; N], Error> {
+    if buf.len() < N {
+        return Err(Error::new(ErrorKind::InvalidInput, "Unexpected length of input"));
+    }
+    let mut array = [0; N];
+    array.copy_from_slice(&buf[..N]);
+    *buf = &buf[N..];
+    Ok(array)
+}
+
 #[derive(Clone)]
 pub struct Secp256K1PublicKey([u8; 64]);
 
@@ -203,6 +238,9 @@ pub enum PublicKey {
     ED25519(ED25519PublicKey),
     /// 512 bit elliptic curve based public-key used in Bitcoin's public-key cryptography.
     SECP256K1(Secp256K1PublicKey),
+    /// 381 bit pairing-friendly elliptic curve based public-key, whose signatures can be
+    /// aggregated.  Not accepted by the runtime until the `BLS12381Keys` protocol feature.
+    BLS12381(BLS12381PublicKey),
 }
 
 impl PublicKey {
@@ -210,6 +248,7 @@ impl PublicKey {
         match self {
             Self::ED25519(_) => ed25519_dalek::PUBLIC_KEY_LENGTH + 1,
             Self::SECP256K1(_) => 65,
+            Self::BLS12381(_) => BLS12381_PUBLIC_KEY_LENGTH + 1,
         }
     }
 
@@ -219,6 +258,9 @@ impl PublicKey {
                 PublicKey::ED25519(ED25519PublicKey([0u8; ed25519_dalek::PUBLIC_KEY_LENGTH]))
             }
             KeyType::SECP256K1 => PublicKey::SECP256K1(Secp256K1PublicKey([0u8; 64])),
+            KeyType::BLS12381 => {
+                PublicKey::BLS12381(BLS12381PublicKey::from([0u8; BLS12381_PUBLIC_KEY_LENGTH]))
+            }
         }
     }
 
@@ -226,6 +268,7 @@ impl PublicKey {
         match self {
             Self::ED25519(_) => KeyType::ED25519,
             Self::SECP256K1(_) => KeyType::SECP256K1,
+            Self::BLS12381(_) => KeyType::BLS12381,
         }
     }
 
@@ -233,13 +276,14 @@ impl PublicKey {
         match self {
             Self::ED25519(key) => key.as_ref(),
             Self::SECP256K1(key) => key.as_ref(),
+            Self::BLS12381(key) => key.as_ref(),
         }
     }
 
     pub fn unwrap_as_ed25519(&self) -> &ED25519PublicKey {
         match self {
             Self::ED25519(key) => key,
-            Self::SECP256K1(_) => panic!(),
+            Self::SECP256K1(_) | Self::BLS12381(_) => panic!(),
         }
     }
 }
@@ -258,6 +302,10 @@ impl Hash for PublicKey {
                 state.write_u8(1u8);
                 state.write(&public_key.0);
             }
+            PublicKey::BLS12381(public_key) => {
+                state.write_u8(2u8);
+                state.write(public_key.as_ref());
+            }
         }
     }
 }
@@ -285,6 +333,10 @@ impl BorshSerialize for PublicKey {
                 BorshSerialize::serialize(&1u8, writer)?;
                 writer.write_all(&public_key.0)?;
             }
+            PublicKey::BLS12381(public_key) => {
+                BorshSerialize::serialize(&2u8, writer)?;
+                writer.write_all(public_key.as_ref())?;
+            }
         }
         Ok(())
     }
@@ -301,6 +353,11 @@ impl BorshDeserialize for PublicKey {
             KeyType::SECP256K1 => {
                 Ok(PublicKey::SECP256K1(Secp256K1PublicKey(BorshDeserialize::deserialize(buf)?)))
             }
+            KeyType::BLS12381 => {
+                Ok(PublicKey::BLS12381(BLS12381PublicKey::from(deserialize_array::<
+                    BLS12381_PUBLIC_KEY_LENGTH,
+                >(buf)?)))
+            }
         }
     }
 }
@@ -339,6 +396,9 @@ impl From<&PublicKey> for String {
                 KeyType::SECP256K1,
                 bs58::encode(&public_key.0.to_vec()).into_string()
             ),
+            PublicKey::BLS12381(public_key) => {
+                format!("{}:{}", KeyType::BLS12381, bs58::encode(public_key).into_string())
+            }
         }
     }
 }
@@ -375,6 +435,11 @@ impl FromStr for PublicKey {
                 }
                 Ok(PublicKey::SECP256K1(Secp256K1PublicKey(array)))
             }
+            KeyType::BLS12381 => {
+                Ok(PublicKey::BLS12381(BLS12381PublicKey::from(decode_bs58_array::<
+                    BLS12381_PUBLIC_KEY_LENGTH,
+                >(key_data)?)))
+            }
         }
     }
 }
@@ -391,6 +456,12 @@ impl From<Secp256K1PublicKey> for PublicKey {
     }
 }
 
+impl From<BLS12381PublicKey> for PublicKey {
+    fn from(bls12381: BLS12381PublicKey) -> Self {
+        Self::BLS12381(bls12381)
+    }
+}
+
 #[derive(Clone)]
 // This is actually a keypair, because ed25519_dalek api only has keypair.sign
 // From ed25519_dalek doc: The first SECRET_KEY_LENGTH of bytes is the SecretKey
@@ -420,6 +491,7 @@ impl Eq for ED25519SecretKey {}
 pub enum SecretKey {
     ED25519(ED25519SecretKey),
     SECP256K1(secp256k1::key::SecretKey),
+    BLS12381(BLS12381SecretKey),
 }
 
 impl SecretKey {
@@ -427,6 +499,7 @@ impl SecretKey {
         match self {
             SecretKey::ED25519(_) => KeyType::ED25519,
             SecretKey::SECP256K1(_) => KeyType::SECP256K1,
+            SecretKey::BLS12381(_) => KeyType::BLS12381,
         }
     }
 
@@ -439,6 +512,7 @@ impl SecretKey {
             KeyType::SECP256K1 => {
                 SecretKey::SECP256K1(secp256k1::key::SecretKey::new(&SECP256K1, &mut OsRng))
             }
+            KeyType::BLS12381 => SecretKey::BLS12381(BLS12381SecretKey::from_random()),
         }
     }
 
@@ -462,6 +536,8 @@ impl SecretKey {
                 buf[64] = rec_id.to_i32() as u8;
                 Signature::SECP256K1(Secp256K1Signature(buf))
             }
+
+            SecretKey::BLS12381(secret_key) => Signature::BLS12381(secret_key.sign(data)),
         }
     }
 
@@ -478,13 +554,14 @@ impl SecretKey {
                 public_key.0.copy_from_slice(&serialized[1..65]);
                 PublicKey::SECP256K1(public_key)
             }
+            SecretKey::BLS12381(secret_key) => PublicKey::BLS12381(secret_key.public_key()),
         }
     }
 
     pub fn unwrap_as_ed25519(&self) -> &ED25519SecretKey {
         match self {
             SecretKey::ED25519(key) => key,
-            SecretKey::SECP256K1(_) => panic!(),
+            SecretKey::SECP256K1(_) | SecretKey::BLS12381(_) => panic!(),
         }
     }
 }
@@ -494,6 +571,7 @@ impl std::fmt::Display for SecretKey {
         let data = match self {
             SecretKey::ED25519(secret_key) => bs58::encode(&secret_key.0[..]).into_string(),
             SecretKey::SECP256K1(secret_key) => bs58::encode(&secret_key[..]).into_string(),
+            SecretKey::BLS12381(secret_key) => bs58::encode(secret_key).into_string(),
         };
         write!(f, "{}:{}", self.key_type(), data)
     }
@@ -534,6 +612,9 @@ impl FromStr for SecretKey {
                         .map_err(|err| Self::Err::InvalidData { error_message: err.to_string() })?,
                 ))
             }
+            KeyType::BLS12381 => Ok(Self::BLS12381(BLS12381SecretKey::try_from(
+                &decode_bs58_array::<BLS12381_SECRET_KEY_LENGTH>(key_data)?[..],
+            )?)),
         }
     }
 }
@@ -549,6 +630,7 @@ impl serde::Serialize for SecretKey {
         let data = match self {
             SecretKey::ED25519(secret_key) => bs58::encode(&secret_key.0[..]).into_string(),
             SecretKey::SECP256K1(secret_key) => bs58::encode(&secret_key[..]).into_string(),
+            SecretKey::BLS12381(secret_key) => bs58::encode(secret_key).into_string(),
         };
         serializer.serialize_str(&format!("{}:{}", self.key_type(), data))
     }
@@ -677,6 +759,7 @@ impl From<Secp256K1Signature> for [u8; 65] {
 pub enum Signature {
     ED25519(ed25519_dalek::Signature),
     SECP256K1(Secp256K1Signature),
+    BLS12381(BLS12381Signature),
 }
 
 #[cfg(feature = "deepsize_feature")]
@@ -685,6 +768,7 @@ impl deepsize::DeepSizeOf for Signature {
         match self {
             Signature::ED25519(_) => ed25519_dalek::SIGNATURE_LENGTH,
             Signature::SECP256K1(_) => SECP256K1_SIGNATURE_LENGTH,
+            Signature::BLS12381(_) => BLS12381_SIGNATURE_LENGTH,
         }
     }
 }
@@ -694,6 +778,7 @@ impl Hash for Signature {
         match self {
             Signature::ED25519(sig) => sig.to_bytes().hash(state),
             Signature::SECP256K1(sig) => sig.hash(state),
+            Signature::BLS12381(sig) => sig.hash(state),
         };
     }
 }
@@ -719,6 +804,9 @@ impl Signature {
                     },
                 )?))
             }
+            KeyType::BLS12381 => {
+                Ok(Signature::BLS12381(BLS12381Signature::try_from(signature_data)?))
+            }
         }
     }
 
@@ -754,6 +842,9 @@ impl Signature {
                     )
                     .is_ok()
             }
+            (Signature::BLS12381(signature), PublicKey::BLS12381(public_key)) => {
+                signature.verify(data, public_key)
+            }
             _ => false,
         }
     }
@@ -762,6 +853,7 @@ impl Signature {
         match self {
             Signature::ED25519(_) => KeyType::ED25519,
             Signature::SECP256K1(_) => KeyType::SECP256K1,
+            Signature::BLS12381(_) => KeyType::BLS12381,
         }
     }
 }
@@ -783,6 +875,10 @@ impl BorshSerialize for Signature {
                 BorshSerialize::serialize(&1u8, writer)?;
                 writer.write_all(&signature.0)?;
             }
+            Signature::BLS12381(signature) => {
+                BorshSerialize::serialize(&2u8, writer)?;
+                writer.write_all(signature.as_ref())?;
+            }
         }
         Ok(())
     }
@@ -805,6 +901,11 @@ impl BorshDeserialize for Signature {
                 let array: [u8; 65] = BorshDeserialize::deserialize(buf)?;
                 Ok(Signature::SECP256K1(Secp256K1Signature(array)))
             }
+            KeyType::BLS12381 => {
+                Ok(Signature::BLS12381(BLS12381Signature::from(deserialize_array::<
+                    BLS12381_SIGNATURE_LENGTH,
+                >(buf)?)))
+            }
         }
     }
 }
@@ -816,6 +917,7 @@ impl Display for Signature {
                 bs58::encode(&signature.to_bytes().to_vec()).into_string()
             }
             Signature::SECP256K1(signature) => bs58::encode(&signature.0[..]).into_string(),
+            Signature::BLS12381(signature) => bs58::encode(signature).into_string(),
         };
         write!(f, "{}", format!("{}:{}", self.key_type(), data))
     }
@@ -874,6 +976,19 @@ impl FromStr for Signature {
                 }
                 Ok(Signature::SECP256K1(Secp256K1Signature(array)))
             }
+            KeyType::BLS12381 => {
+                let mut array = [0; BLS12381_SIGNATURE_LENGTH];
+                let length = bs58::decode(sig_data)
+                    .into(&mut array[..])
+                    .map_err(|err| Self::Err::InvalidData { error_message: err.to_string() })?;
+                if length != BLS12381_SIGNATURE_LENGTH {
+                    return Err(Self::Err::InvalidLength {
+                        expected_length: BLS12381_SIGNATURE_LENGTH,
+                        received_length: length,
+                    });
+                }
+                Ok(Signature::BLS12381(BLS12381Signature::from(array)))
+            }
         }
     }
 }
@@ -896,7 +1011,7 @@ mod tests {
 
     #[test]
     fn test_sign_verify() {
-        for key_type in vec![KeyType::ED25519, KeyType::SECP256K1] {
+        for key_type in vec![KeyType::ED25519, KeyType::SECP256K1, KeyType::BLS12381] {
             let secret_key = SecretKey::from_random(key_type);
             let public_key = secret_key.public_key();
             use sha2::Digest;
@@ -959,11 +1074,34 @@ mod tests {
         assert_eq!(signature, signature2);
     }
 
+    #[test]
+    fn test_json_serialize_bls12381() {
+        let sk = SecretKey::from_seed(KeyType::BLS12381, "test");
+        let pk = sk.public_key();
+        let pk_json = serde_json::to_string(&pk).unwrap();
+        assert!(pk_json.starts_with("\"bls12381:"));
+        assert_eq!(pk, serde_json::from_str(&pk_json).unwrap());
+        let pk2: PublicKey = pk.to_string().parse().unwrap();
+        assert_eq!(pk, pk2);
+
+        let sk_json = serde_json::to_string(&sk).unwrap();
+        assert!(sk_json.starts_with("\"bls12381:"));
+        assert_eq!(sk, serde_json::from_str(&sk_json).unwrap());
+
+        let signature = sk.sign(b"123");
+        let signature_json = serde_json::to_string(&signature).unwrap();
+        assert!(signature_json.starts_with("\"bls12381:"));
+        assert_eq!(signature, serde_json::from_str(&signature_json).unwrap());
+        let signature2: Signature = signature.to_string().parse().unwrap();
+        assert_eq!(signature, signature2);
+        assert!(signature2.verify(b"123", &pk2));
+    }
+
     #[test]
     fn test_borsh_serialization() {
         use sha2::Digest;
         let data = sha2::Sha256::digest(b"123").to_vec();
-        for key_type in vec![KeyType::ED25519, KeyType::SECP256K1] {
+        for key_type in vec![KeyType::ED25519, KeyType::SECP256K1, KeyType::BLS12381] {
             let sk = SecretKey::from_seed(key_type, "test");
             let pk = sk.public_key();
             let bytes = pk.try_to_vec().unwrap();
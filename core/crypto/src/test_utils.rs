@@ -4,7 +4,7 @@ use rand::rngs::StdRng;
 use crate::signature::{
     ED25519PublicKey, ED25519SecretKey, KeyType, PublicKey, SecretKey, SECP256K1,
 };
-use crate::{InMemorySigner, Signature};
+use crate::{BLS12381SecretKey, InMemorySigner, Signature};
 use near_account_id::AccountId;
 
 fn ed25519_key_pair_from_seed(seed: &str) -> ed25519_dalek::Keypair {
@@ -26,6 +26,14 @@ fn secp256k1_secret_key_from_seed(seed: &str) -> secp256k1::key::SecretKey {
     secp256k1::key::SecretKey::new(&SECP256K1, &mut rng)
 }
 
+fn bls12381_secret_key_from_seed(seed: &str) -> BLS12381SecretKey {
+    let seed_bytes = seed.as_bytes();
+    let len = std::cmp::min(32, seed_bytes.len());
+    let mut seed: [u8; 32] = [b' '; 32];
+    seed[..len].copy_from_slice(&seed_bytes[..len]);
+    BLS12381SecretKey::from_ikm(&seed)
+}
+
 impl PublicKey {
     pub fn from_seed(key_type: KeyType, seed: &str) -> Self {
         match key_type {
@@ -33,6 +41,9 @@ impl PublicKey {
                 let keypair = ed25519_key_pair_from_seed(seed);
                 PublicKey::ED25519(ED25519PublicKey(keypair.public.to_bytes()))
             }
+            KeyType::BLS12381 => {
+                PublicKey::BLS12381(bls12381_secret_key_from_seed(seed).public_key())
+            }
             _ => unimplemented!(),
         }
     }
@@ -45,7 +56,8 @@ impl SecretKey {
                 let keypair = ed25519_key_pair_from_seed(seed);
                 SecretKey::ED25519(ED25519SecretKey(keypair.to_bytes()))
             }
-            _ => SecretKey::SECP256K1(secp256k1_secret_key_from_seed(seed)),
+            KeyType::SECP256K1 => SecretKey::SECP256K1(secp256k1_secret_key_from_seed(seed)),
+            KeyType::BLS12381 => SecretKey::BLS12381(bls12381_secret_key_from_seed(seed)),
         }
     }
 }
@@ -62,7 +62,7 @@ pub use prometheus::{
     Encoder, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Result,
     TextEncoder,
 };
-use prometheus::{GaugeVec, HistogramOpts, HistogramTimer, Opts};
+use prometheus::{Gauge, GaugeVec, HistogramOpts, HistogramTimer, Opts};
 
 use tracing::error;
 
@@ -137,6 +137,15 @@ pub fn try_create_histogram_vec(
     Ok(histogram)
 }
 
+/// Attempts to crate a `Gauge`, returning `Err` if the registry does not accept the gauge
+/// (potentially due to naming conflict).
+pub fn try_create_gauge(name: &str, help: &str) -> Result<Gauge> {
+    let opts = Opts::new(name, help);
+    let gauge = Gauge::with_opts(opts)?;
+    prometheus::register(Box::new(gauge.clone()))?;
+    Ok(gauge)
+}
+
 /// Attempts to crate an `GaugeVec`, returning `Err` if the registry does not accept the gauge
 /// (potentially due to naming conflict).
 pub fn try_create_gauge_vec(name: &str, help: &str, labels: &[&str]) -> Result<GaugeVec> {
@@ -1,10 +1,19 @@
 #![doc = include_str!("../README.md")]
 
-pub use {tracing, tracing_appender, tracing_subscriber};
+pub use {opentelemetry, tracing, tracing_appender, tracing_opentelemetry, tracing_subscriber};
+
+pub use otlp::{
+    shutdown_opentelemetry, NodeResource, OpenTelemetryConfig, OpenTelemetryError,
+    DEFAULT_SAMPLING_RATIO, DEFAULT_SPAN_FILTER,
+};
 
 use std::borrow::Cow;
 
-use tracing_subscriber::EnvFilter;
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+mod otlp;
 
 /// The default value for the `RUST_LOG` environment variable if one isn't specified otherwise.
 pub const DEFAULT_RUST_LOG: &'static str = "tokio_reactor=info,\
@@ -32,7 +41,7 @@ pub struct DefaultSubcriberGuard<S> {
     subscriber: Option<S>,
     local_subscriber_guard: Option<tracing::subscriber::DefaultGuard>,
     #[allow(dead_code)] // This field is never read, but has semantic purpose as a drop guard.
-    writer_guard: tracing_appender::non_blocking::WorkerGuard,
+    writer_guard: WorkerGuard,
 }
 
 impl<S: tracing::Subscriber + Send + Sync> DefaultSubcriberGuard<S> {
@@ -77,10 +86,7 @@ impl<S: tracing::Subscriber + Send + Sync> DefaultSubcriberGuard<S> {
 pub fn default_subscriber(
     log_filter: EnvFilter,
 ) -> DefaultSubcriberGuard<impl tracing::Subscriber + Send + Sync> {
-    // Do not lock the `stderr` here to allow for things like `dbg!()` work during development.
-    let stderr = std::io::stderr();
-    let lined_stderr = std::io::LineWriter::new(stderr);
-    let (writer, writer_guard) = tracing_appender::non_blocking(lined_stderr);
+    let (writer, writer_guard) = stderr_writer();
     let subscriber = tracing_subscriber::FmtSubscriber::builder()
         .with_span_events(
             tracing_subscriber::fmt::format::FmtSpan::ENTER
@@ -96,6 +102,39 @@ pub fn default_subscriber(
     }
 }
 
+/// Like [`default_subscriber`], and also exports the spans selected by `config`, if any, to an
+/// OpenTelemetry collector with the attributes of `node`.
+///
+/// Must be called within a Tokio runtime if `config` is set.
+pub fn default_subscriber_with_opentelemetry(
+    log_filter: EnvFilter,
+    config: Option<&OpenTelemetryConfig>,
+    node: &NodeResource,
+) -> Result<DefaultSubcriberGuard<impl tracing::Subscriber + Send + Sync>, OpenTelemetryError> {
+    let (writer, writer_guard) = stderr_writer();
+    let fmt_layer = tracing_subscriber::fmt::layer()
+        .with_span_events(
+            tracing_subscriber::fmt::format::FmtSpan::ENTER
+                | tracing_subscriber::fmt::format::FmtSpan::CLOSE,
+        )
+        .with_writer(writer)
+        .with_filter(log_filter);
+    let otlp_layer = config.map(|config| otlp::layer(config, node)).transpose()?;
+    let subscriber = tracing_subscriber::registry().with(otlp_layer).with(fmt_layer);
+    Ok(DefaultSubcriberGuard {
+        subscriber: Some(subscriber),
+        local_subscriber_guard: None,
+        writer_guard,
+    })
+}
+
+fn stderr_writer() -> (NonBlocking, WorkerGuard) {
+    // Do not lock the `stderr` here to allow for things like `dbg!()` work during development.
+    let stderr = std::io::stderr();
+    let lined_stderr = std::io::LineWriter::new(stderr);
+    tracing_appender::non_blocking(lined_stderr)
+}
+
 pub struct EnvFilterBuilder<'a> {
     rust_log: Cow<'a, str>,
     verbose: Option<Cow<'a, str>>,
@@ -0,0 +1,104 @@
+//! Export of the tracing spans to an OpenTelemetry collector over OTLP, see
+//! [`default_subscriber_with_opentelemetry`](crate::default_subscriber_with_opentelemetry).
+use opentelemetry::sdk::trace::{self, Sampler};
+use opentelemetry::sdk::Resource;
+use opentelemetry::trace::TraceError;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Share of the traces exported by default.  Spans of the traces which aren't sampled are
+/// hardly more expensive than disabled ones, so this keeps the overhead negligible on mainnet
+/// validators.
+pub const DEFAULT_SAMPLING_RATIO: f64 = 0.001;
+/// Spans exported by default: block preprocessing, chunk application, contract execution and
+/// chunk parts sent and received over the network.
+pub const DEFAULT_SPAN_FILTER: &str = "chain=info,vm=info,network=info";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct OpenTelemetryConfig {
+    /// OTLP gRPC endpoint of the collector.
+    pub endpoint: String,
+    /// Share of the traces started by this node which are exported, between 0 and 1.  Traces
+    /// started by another node, e.g. of chunk parts it sent, follow its sampling decision.
+    #[serde(default = "default_sampling_ratio")]
+    pub sampling_ratio: f64,
+    /// Targets and levels of the exported spans, e.g. `chain=info,vm=debug`.
+    #[serde(default = "default_span_filter")]
+    pub filter: String,
+}
+
+fn default_sampling_ratio() -> f64 {
+    DEFAULT_SAMPLING_RATIO
+}
+
+fn default_span_filter() -> String {
+    DEFAULT_SPAN_FILTER.to_string()
+}
+
+impl Default for OpenTelemetryConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: "http://localhost:4317".to_string(),
+            sampling_ratio: DEFAULT_SAMPLING_RATIO,
+            filter: DEFAULT_SPAN_FILTER.to_string(),
+        }
+    }
+}
+
+/// Identifies the node in the resource attributes of the exported spans.
+#[derive(Clone, Debug)]
+pub struct NodeResource {
+    pub chain_id: String,
+    /// Account of the validator key of the node, if it has one.
+    pub account_id: Option<String>,
+    pub node_version: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum OpenTelemetryError {
+    #[error("invalid span filter: {0}")]
+    Filter(#[from] tracing_subscriber::filter::ParseError),
+    #[error("failed to set up the OTLP exporter: {0}")]
+    Trace(#[from] TraceError),
+}
+
+/// Creates the layer exporting the spans selected by `config.filter`.  The spans are exported
+/// in batches from a separate thread, the exporter itself must be created in a Tokio runtime.
+pub(crate) fn layer<S>(
+    config: &OpenTelemetryConfig,
+    node: &NodeResource,
+) -> Result<impl Layer<S>, OpenTelemetryError>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    let filter: Targets = config.filter.parse()?;
+    let mut attributes = vec![
+        KeyValue::new("service.name", "neard"),
+        KeyValue::new("service.version", node.node_version.clone()),
+        KeyValue::new("chain_id", node.chain_id.clone()),
+    ];
+    if let Some(account_id) = &node.account_id {
+        attributes.push(KeyValue::new("account_id", account_id.clone()));
+    }
+    let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sampling_ratio)));
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter().tonic().with_endpoint(config.endpoint.clone()),
+        )
+        .with_trace_config(
+            trace::config().with_sampler(sampler).with_resource(Resource::new(attributes)),
+        )
+        .install_batch(opentelemetry::runtime::TokioCurrentThread)?;
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer).with_filter(filter))
+}
+
+/// Exports the spans which weren't exported yet.  Blocks until they are, so it mustn't be
+/// called from the runtime the exporter was created in.
+pub fn shutdown_opentelemetry() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
@@ -6,12 +6,14 @@ pub use near_account_id as id;
 
 use crate::hash::CryptoHash;
 use crate::serialize::{option_u128_dec_format, u128_dec_format_compatible};
-use crate::types::{Balance, Nonce, StorageUsage};
+use crate::types::{Balance, BlockHeight, Nonce, StorageUsage};
 #[derive(
     BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy,
 )]
 pub enum AccountVersion {
     V1,
+    /// Has a nonrefundable balance.
+    V2,
 }
 
 impl Default for AccountVersion {
@@ -36,6 +38,15 @@ pub struct Account {
     /// Version of Account in re migrations and similar
     #[serde(default)]
     version: AccountVersion,
+    /// The amount deposited with `NonrefundableStorageTransfer` actions.  It only pays for the
+    /// storage of the account: it can't be transferred nor staked, and it's burnt when the account
+    /// is deleted.
+    #[serde(default, with = "u128_dec_format_compatible", skip_serializing_if = "is_zero")]
+    nonrefundable: Balance,
+}
+
+fn is_zero(balance: &Balance) -> bool {
+    *balance == 0
 }
 
 impl Account {
@@ -49,7 +60,14 @@ impl Account {
         code_hash: CryptoHash,
         storage_usage: StorageUsage,
     ) -> Self {
-        Account { amount, locked, code_hash, storage_usage, version: AccountVersion::V1 }
+        Account {
+            amount,
+            locked,
+            code_hash,
+            storage_usage,
+            version: AccountVersion::V1,
+            nonrefundable: 0,
+        }
     }
 
     #[inline]
@@ -77,6 +95,11 @@ impl Account {
         self.version
     }
 
+    #[inline]
+    pub fn nonrefundable(&self) -> Balance {
+        self.nonrefundable
+    }
+
     #[inline]
     pub fn set_amount(&mut self, amount: Balance) {
         self.amount = amount;
@@ -100,6 +123,12 @@ impl Account {
     pub fn set_version(&mut self, version: AccountVersion) {
         self.version = version;
     }
+
+    /// Sets the nonrefundable balance, which requires the `V2` serialization.
+    pub fn set_nonrefundable(&mut self, nonrefundable: Balance) {
+        self.nonrefundable = nonrefundable;
+        self.version = AccountVersion::V2;
+    }
 }
 
 #[derive(BorshSerialize, BorshDeserialize)]
@@ -110,21 +139,59 @@ struct LegacyAccount {
     storage_usage: StorageUsage,
 }
 
+#[derive(BorshSerialize, BorshDeserialize)]
+struct AccountV2 {
+    amount: Balance,
+    locked: Balance,
+    code_hash: CryptoHash,
+    storage_usage: StorageUsage,
+    nonrefundable: Balance,
+}
+
+/// Takes the place of the amount of a `LegacyAccount` in the serialization of later versions,
+/// followed by the version.  No account can have this amount since it exceeds the total supply.
+const ACCOUNT_VERSION_SENTINEL: Balance = Balance::MAX;
+
 impl BorshDeserialize for Account {
     fn deserialize(buf: &mut &[u8]) -> Result<Self, io::Error> {
-        // This should only ever happen if we have pre-transition account serialized in state
-        // See test_account_size
-        let deserialized_account = LegacyAccount::deserialize(buf)?;
+        let account = if buf.get(..16) == Some(&ACCOUNT_VERSION_SENTINEL.to_le_bytes()[..]) {
+            *buf = &buf[16..];
+            match AccountVersion::deserialize(buf)? {
+                AccountVersion::V1 => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "V1 accounts are serialized without a version",
+                    ))
+                }
+                AccountVersion::V2 => {
+                    let account = AccountV2::deserialize(buf)?;
+                    Account {
+                        amount: account.amount,
+                        locked: account.locked,
+                        code_hash: account.code_hash,
+                        storage_usage: account.storage_usage,
+                        version: AccountVersion::V2,
+                        nonrefundable: account.nonrefundable,
+                    }
+                }
+            }
+        } else {
+            // This should only ever happen if we have pre-transition account serialized in state
+            // See test_account_size
+            let deserialized_account = LegacyAccount::deserialize(buf)?;
+            Account {
+                amount: deserialized_account.amount,
+                locked: deserialized_account.locked,
+                code_hash: deserialized_account.code_hash,
+                storage_usage: deserialized_account.storage_usage,
+                version: AccountVersion::V1,
+                nonrefundable: 0,
+            }
+        };
         if buf.len() != 0 {
             panic!("Tried deserializing a buffer that is not exactly the size of an account");
         }
-        Ok(Account {
-            amount: deserialized_account.amount,
-            locked: deserialized_account.locked,
-            code_hash: deserialized_account.code_hash,
-            storage_usage: deserialized_account.storage_usage,
-            version: AccountVersion::V1,
-        })
+        Ok(account)
     }
 }
 
@@ -138,6 +205,18 @@ impl BorshSerialize for Account {
                 storage_usage: self.storage_usage,
             }
             .serialize(writer),
+            AccountVersion::V2 => {
+                ACCOUNT_VERSION_SENTINEL.serialize(writer)?;
+                self.version.serialize(writer)?;
+                AccountV2 {
+                    amount: self.amount,
+                    locked: self.locked,
+                    code_hash: self.code_hash,
+                    storage_usage: self.storage_usage,
+                    nonrefundable: self.nonrefundable,
+                }
+                .serialize(writer)
+            }
         }
     }
 }
@@ -148,9 +227,7 @@ impl BorshSerialize for Account {
 /// that can be issued.
 /// `account_id,public_key` is a key in the state
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
-#[derive(
-    BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug,
-)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Debug)]
 pub struct AccessKey {
     /// Nonce for this access key, used for tx nonce generation. When access key is created, nonce
     /// is set to `(block_height - 1) * 1e6` to avoid tx hash collision on access key re-creation.
@@ -159,13 +236,53 @@ pub struct AccessKey {
 
     /// Defines permissions for this access key.
     pub permission: AccessKeyPermission,
+
+    /// Last block height at which transactions signed with this access key are accepted.  An
+    /// expired key still takes storage until it is deleted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_until_block_height: Option<BlockHeight>,
 }
 
 impl AccessKey {
     pub const ACCESS_KEY_NONCE_RANGE_MULTIPLIER: u64 = 1_000_000;
 
     pub fn full_access() -> Self {
-        Self { nonce: 0, permission: AccessKeyPermission::FullAccess }
+        Self {
+            nonce: 0,
+            permission: AccessKeyPermission::FullAccess,
+            valid_until_block_height: None,
+        }
+    }
+}
+
+/// Tag which takes the place of the `AccessKeyPermission` tag in the serialization of access keys
+/// with an expiration height, followed by the height and the permission.  Access keys without one
+/// keep the serialization they had before expiration heights, both in the state and in `AddKey`
+/// actions.
+const EXPIRING_ACCESS_KEY_TAG: u8 = 2;
+
+impl BorshSerialize for AccessKey {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.nonce.serialize(writer)?;
+        if let Some(valid_until_block_height) = self.valid_until_block_height {
+            EXPIRING_ACCESS_KEY_TAG.serialize(writer)?;
+            valid_until_block_height.serialize(writer)?;
+        }
+        self.permission.serialize(writer)
+    }
+}
+
+impl BorshDeserialize for AccessKey {
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, io::Error> {
+        let nonce = Nonce::deserialize(buf)?;
+        let valid_until_block_height = if buf.first() == Some(&EXPIRING_ACCESS_KEY_TAG) {
+            *buf = &buf[1..];
+            Some(BlockHeight::deserialize(buf)?)
+        } else {
+            None
+        };
+        let permission = AccessKeyPermission::deserialize(buf)?;
+        Ok(AccessKey { nonce, permission, valid_until_block_height })
     }
 }
 
@@ -248,4 +365,44 @@ mod tests {
             <Account as BorshDeserialize>::deserialize(&mut new_bytes).unwrap();
         assert_eq!(deserialized_account, new_account);
     }
+
+    #[test]
+    fn test_account_v2_serialization() {
+        let mut account = Account::new(100, 200, CryptoHash::default(), 300);
+        account.set_nonrefundable(400);
+        assert_eq!(account.version(), AccountVersion::V2);
+        let bytes = account.try_to_vec().unwrap();
+        assert_eq!(&bytes[..16], &ACCOUNT_VERSION_SENTINEL.to_le_bytes()[..]);
+        let deserialized_account = Account::try_from_slice(&bytes).unwrap();
+        assert_eq!(deserialized_account, account);
+        assert_eq!(deserialized_account.nonrefundable(), 400);
+
+        let json = serde_json::to_string(&account).unwrap();
+        assert_eq!(serde_json::from_str::<Account>(&json).unwrap(), account);
+        // Accounts without a nonrefundable balance keep their JSON representation.
+        let json = serde_json::to_value(&Account::new(100, 200, CryptoHash::default(), 300));
+        assert!(json.unwrap().get("nonrefundable").is_none());
+    }
+
+    #[test]
+    fn test_access_key_serialization() {
+        let access_key = AccessKey { nonce: 3, ..AccessKey::full_access() };
+        let bytes = access_key.try_to_vec().unwrap();
+        // Same as before access keys could expire.
+        assert_eq!(bytes, [&3u64.to_le_bytes()[..], &[1]].concat());
+        assert_eq!(AccessKey::try_from_slice(&bytes).unwrap(), access_key);
+
+        let access_key = AccessKey {
+            nonce: 3,
+            permission: AccessKeyPermission::FunctionCall(FunctionCallPermission {
+                allowance: Some(100),
+                receiver_id: "bob.near".to_string(),
+                method_names: vec!["f".to_string()],
+            }),
+            valid_until_block_height: Some(10),
+        };
+        let bytes = access_key.try_to_vec().unwrap();
+        assert_eq!(bytes[8], EXPIRING_ACCESS_KEY_TAG);
+        assert_eq!(AccessKey::try_from_slice(&bytes).unwrap(), access_key);
+    }
 }
@@ -1,8 +1,9 @@
-use crate::types::Gas;
+use crate::types::{Compute, Gas};
 
 use core::fmt;
 use serde::{Deserialize, Serialize};
 use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
 use std::hash::{Hash, Hasher};
 
 #[derive(Clone, Debug, Hash, Serialize, Deserialize, PartialEq, Eq)]
@@ -35,6 +36,14 @@ pub struct VMLimitConfig {
     /// [`StackLimiterVersion`].
     #[serde(default = "StackLimiterVersion::v0")]
     pub stack_limiter_version: StackLimiterVersion,
+    /// Max depth of nested calls of contract functions, only checked by
+    /// [`StackLimiterVersion::V2`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_call_depth: Option<u32>,
+    /// How much of `max_stack_height` each param and local of a function takes, in units of
+    /// operand stack slots.  Only used by [`StackLimiterVersion::V2`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stack_height_local_cost: Option<u32>,
 
     /// The initial number of memory pages.
     /// NOTE: It's not a limiter itself, but it's a value we use for initial_memory_pages.
@@ -42,6 +51,11 @@ pub struct VMLimitConfig {
     /// What is the maximal memory pages amount is allowed to have for
     /// a contract.
     pub max_memory_pages: u32,
+    /// Whether a contract declaring or importing a memory with more than `max_memory_pages`
+    /// initial or maximum pages is rejected.  Otherwise its memory is replaced with the standard
+    /// one like any other.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strict_memory_limits: bool,
 
     /// Limit of memory used by registers.
     pub registers_memory_limit: u64,
@@ -84,21 +98,47 @@ pub struct VMLimitConfig {
     /// If present, stores max number of functions in one contract
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_functions_number_per_contract: Option<u64>,
+    /// If present, stores max size in bytes of trie nodes a single receipt may add to the
+    /// storage proof of the chunk.  The size is the same whether the proof is recorded or not.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub per_receipt_storage_proof_size_limit: Option<u64>,
+    /// If present, stores max size in bytes of trie nodes and values a single receipt may load
+    /// from the storage.  Loads are counted like touched trie nodes, except that nodes in the
+    /// chunk cache are only counted once.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub per_receipt_trie_nodes_memory_limit: Option<u64>,
+    /// If present, stores max number of keys `storage_key_count` may count in one call.  The host
+    /// function fails if it isn't set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_storage_key_count: Option<u64>,
+    /// If present, stores max size in bytes of a borsh-serialized receipt created by a contract
+    /// or converted from a transaction.  Incoming receipts are never checked against it.  It
+    /// leaves room for the other fields of a receipt deploying a contract of `max_contract_size`
+    /// or calling a function with arguments of `max_arguments_length`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_receipt_size: Option<u64>,
+    /// If present, stores max depth of an action receipt created by a contract, that is the
+    /// number of receipts between it and the transaction it descends from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_receipt_depth: Option<u32>,
 }
 
 /// Our original code for limiting WASM stack was buggy. We fixed that, but we
 /// still have to use old (`V0`) limiter for old protocol versions.
 ///
-/// This struct here exists to enforce that the value in the config is either
-/// `0` or `1`. We could have used a `bool` instead, but there's a chance that
-/// our current impl isn't perfect either and would need further tweaks in the
-/// future.
+/// This struct here exists to enforce that the value in the config is one of
+/// the known versions. `V2` replaced the limiter from `pwasm-utils` with our own
+/// one, which also limits the call depth and makes the cost of locals
+/// configurable.
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub enum StackLimiterVersion {
     /// Old, buggy version, don't use it unless specifically to support old protocol version.
     V0,
     /// What we use in today's protocol.
     V1,
+    /// Limits both the stack height and the call depth, see `max_call_depth` and
+    /// `stack_height_local_cost` in [`VMLimitConfig`].
+    V2,
 }
 
 impl StackLimiterVersion {
@@ -109,12 +149,14 @@ impl StackLimiterVersion {
         match self {
             StackLimiterVersion::V0 => 0,
             StackLimiterVersion::V1 => 1,
+            StackLimiterVersion::V2 => 2,
         }
     }
     fn from_repr(repr: u32) -> Option<StackLimiterVersion> {
         let res = match repr {
             0 => StackLimiterVersion::V0,
             1 => StackLimiterVersion::V1,
+            2 => StackLimiterVersion::V2,
             _ => return None,
         };
         Some(res)
@@ -180,8 +222,11 @@ impl VMLimitConfig {
             // For experimentation try `test_stack_overflow`.
             max_stack_height: 16 * 1024, // 16Kib of stack.
             stack_limiter_version: StackLimiterVersion::V1,
+            max_call_depth: None,
+            stack_height_local_cost: None,
             initial_memory_pages: 2u32.pow(10), // 64Mib of memory.
             max_memory_pages: 2u32.pow(11),     // 128Mib of memory.
+            strict_memory_limits: false,
 
             // By default registers are limited by 1GiB of memory.
             registers_memory_limit: 2u64.pow(30),
@@ -216,6 +261,11 @@ impl VMLimitConfig {
             // Unlikely to hit it for normal development.
             max_number_input_data_dependencies: 128,
             max_functions_number_per_contract: None,
+            per_receipt_storage_proof_size_limit: None,
+            per_receipt_trie_nodes_memory_limit: None,
+            max_storage_key_count: Some(10_000),
+            max_receipt_size: Some(4 * 2u64.pow(20) + 64 * 2u64.pow(10)), // 4 Mib + 64 Kib
+            max_receipt_depth: Some(128),
         }
     }
 }
@@ -390,6 +440,11 @@ pub struct ExtCostsConfig {
     /// Cost for pairing check per byte
     #[cfg(feature = "protocol_feature_alt_bn128")]
     pub alt_bn128_pairing_check_byte: Gas,
+
+    /// Compute costs of the operations which take more or less time than their gas implies,
+    /// e.g. `touching_trie_node` on cold storage.  The other costs use as much compute as gas.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub compute_costs: BTreeMap<ExtCosts, Compute>,
 }
 
 // We multiply the actual computed costs by the fixed factor to ensure we
@@ -466,6 +521,7 @@ impl ExtCostsConfig {
             alt_bn128_g1_sum_base: SAFETY_MULTIPLIER * 1058438125,
             #[cfg(feature = "protocol_feature_alt_bn128")]
             alt_bn128_g1_sum_byte: SAFETY_MULTIPLIER * 25406181,
+            compute_costs: BTreeMap::new(),
         }
     }
 
@@ -537,12 +593,13 @@ impl ExtCostsConfig {
             alt_bn128_g1_sum_base: 0,
             #[cfg(feature = "protocol_feature_alt_bn128")]
             alt_bn128_g1_sum_byte: 0,
+            compute_costs: BTreeMap::new(),
         }
     }
 }
 
 /// Strongly-typed representation of the fees for counting.
-#[derive(Copy, Clone, Hash, PartialEq, Eq, Debug, PartialOrd, Ord)]
+#[derive(Copy, Clone, Hash, PartialEq, Eq, Debug, PartialOrd, Ord, Serialize, Deserialize)]
 #[allow(non_camel_case_types)]
 pub enum ExtCosts {
     base,
@@ -743,6 +800,14 @@ impl ExtCosts {
         }
     }
 
+    /// The compute cost, which is the gas cost unless the config overrides it.
+    pub fn compute(self, config: &ExtCostsConfig) -> Compute {
+        match config.compute_costs.get(&self) {
+            Some(compute) => *compute,
+            None => self.value(config),
+        }
+    }
+
     pub const fn count() -> usize {
         ExtCosts::__count as usize
     }
@@ -81,6 +81,19 @@ pub struct DataReceiptCreationConfig {
     /// `N` bytes of data to every output dependency. For each output dependency the cost is
     /// `(send(sir) + exec()) * N`.
     pub cost_per_byte: Fee,
+    /// If present, the bytes of a returned value above `threshold` are charged the steeper
+    /// `cost_per_byte` of `LargeDataCost` instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub large_data_cost: Option<LargeDataCost>,
+}
+
+/// Describes the cost of the bytes of large returned values, see `DataReceiptCreationConfig`.
+#[derive(Debug, Serialize, Deserialize, Clone, Hash, PartialEq, Eq)]
+pub struct LargeDataCost {
+    /// Number of bytes of a returned value which are charged the regular `cost_per_byte`.
+    pub threshold: u64,
+    /// Cost per byte sent above `threshold`, charged the same way as the regular one.
+    pub cost_per_byte: Fee,
 }
 
 /// Describes the cost of creating a specific action, `Action`. Includes all variants.
@@ -155,6 +168,7 @@ impl RuntimeFeesConfig {
                     send_not_sir: 59357464,
                     execution: 59357464,
                 },
+                large_data_cost: None,
             },
             action_creation_config: ActionCreationConfig {
                 create_account_cost: Fee {
@@ -238,6 +252,7 @@ impl RuntimeFeesConfig {
             data_receipt_creation_config: DataReceiptCreationConfig {
                 base_cost: free.clone(),
                 cost_per_byte: free.clone(),
+                large_data_cost: None,
             },
             action_creation_config: ActionCreationConfig {
                 create_account_cost: free.clone(),
@@ -24,6 +24,11 @@ pub type ShardId = u64;
 pub type Balance = u128;
 /// Gas is a type for storing amount of gas.
 pub type Gas = u64;
+/// Compute is a type for storing the amount of work an operation takes, in the same units as
+/// gas.  Chunks are limited by both their gas and their compute usage.
+pub type Compute = u64;
+/// Number of receipts in the chain from the original transaction to a receipt.
+pub type ReceiptDepth = u32;
 
 /// Weight of unused gas to distribute to scheduled function call actions.
 /// Used in `promise_batch_action_function_call_weight` host function.
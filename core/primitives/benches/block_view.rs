@@ -0,0 +1,139 @@
+//! Compares the views returned by the `block` RPC with and without `header_only` for a block
+//! with as many shards and approvals as on mainnet, both when serializing the views to JSON and
+//! when building them from the borsh encoded block and chunk summaries read from the store.
+#[macro_use]
+extern crate bencher;
+
+use bencher::{black_box, Bencher};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use near_crypto::{KeyType, Signature};
+use near_primitives::block::{genesis_chunks, Block};
+use near_primitives::hash::CryptoHash;
+use near_primitives::sharding::ChunkSummary;
+use near_primitives::time::Clock;
+use near_primitives::types::{EpochId, StateRoot};
+use near_primitives::validator_signer::InMemoryValidatorSigner;
+use near_primitives::version::PROTOCOL_VERSION;
+use near_primitives::views::{BlockHeaderOnlyView, BlockView, ChunkSummaryView};
+use num_rational::Rational;
+
+const NUM_SHARDS: u64 = 4;
+const NUM_APPROVALS: usize = 100;
+
+fn create_block() -> Block {
+    let genesis_chunks =
+        genesis_chunks(vec![StateRoot::default()], NUM_SHARDS, 1_000, 0, PROTOCOL_VERSION);
+    let genesis = Block::genesis(
+        PROTOCOL_VERSION,
+        genesis_chunks.into_iter().map(|chunk| chunk.take_header()).collect(),
+        Clock::utc(),
+        0,
+        1_000,
+        1_000,
+        CryptoHash::default(),
+    );
+    let signer = InMemoryValidatorSigner::from_random("test".parse().unwrap(), KeyType::ED25519);
+    Block::produce(
+        PROTOCOL_VERSION,
+        PROTOCOL_VERSION,
+        genesis.header(),
+        10,
+        genesis.header().block_ordinal() + 1,
+        genesis.chunks().iter().cloned().collect(),
+        EpochId::default(),
+        EpochId::default(),
+        None,
+        vec![Some(Signature::empty(KeyType::ED25519)); NUM_APPROVALS],
+        Rational::from_integer(0),
+        0,
+        0,
+        Some(0),
+        vec![],
+        vec![],
+        &signer,
+        CryptoHash::default(),
+        CryptoHash::default(),
+    )
+}
+
+fn block_view(block: &Block) -> Vec<u8> {
+    let view = BlockView::from_author_block("test".parse().unwrap(), block.clone());
+    serde_json::to_vec(&view).unwrap()
+}
+
+fn block_header_only_view(block: &Block) -> Vec<u8> {
+    let summaries = vec![ChunkSummary::default(); block.chunks().len()];
+    serde_json::to_vec(&header_only_view(block, summaries)).unwrap()
+}
+
+fn header_only_view(block: &Block, summaries: Vec<ChunkSummary>) -> BlockHeaderOnlyView {
+    BlockHeaderOnlyView {
+        author: "test".parse().unwrap(),
+        header: block.header().clone().into(),
+        chunks: block
+            .chunks()
+            .iter()
+            .zip(summaries)
+            .map(|(chunk_header, summary)| ChunkSummaryView::new(chunk_header, Some(summary)))
+            .collect(),
+    }
+}
+
+/// Builds the view of the full block like the `block` RPC does from a block read from the store.
+fn block_view_from_borsh(block_bytes: &[u8]) -> BlockView {
+    let block = Block::try_from_slice(block_bytes).unwrap();
+    BlockView::from_author_block("test".parse().unwrap(), block)
+}
+
+/// Builds the view with `header_only` from a block and the summaries of its chunks read from the
+/// store.
+fn block_header_only_view_from_borsh(
+    block_bytes: &[u8],
+    summary_bytes: &[Vec<u8>],
+) -> BlockHeaderOnlyView {
+    let block = Block::try_from_slice(block_bytes).unwrap();
+    let summaries =
+        summary_bytes.iter().map(|bytes| ChunkSummary::try_from_slice(bytes).unwrap()).collect();
+    header_only_view(&block, summaries)
+}
+
+fn serialize_block_view(bench: &mut Bencher) {
+    let block = create_block();
+    println!("block: {} bytes", block_view(&block).len());
+    bench.iter(|| black_box(block_view(&block)));
+}
+
+fn serialize_block_header_only_view(bench: &mut Bencher) {
+    let block = create_block();
+    println!("block header only: {} bytes", block_header_only_view(&block).len());
+    bench.iter(|| black_box(block_header_only_view(&block)));
+}
+
+fn deserialize_block_view(bench: &mut Bencher) {
+    let block = create_block();
+    let block_bytes = block.try_to_vec().unwrap();
+    println!("block: {} borsh bytes", block_bytes.len());
+    bench.iter(|| black_box(block_view_from_borsh(&block_bytes)));
+}
+
+fn deserialize_block_header_only_view(bench: &mut Bencher) {
+    let block = create_block();
+    let block_bytes = block.try_to_vec().unwrap();
+    let summary_bytes: Vec<_> =
+        block.chunks().iter().map(|_| ChunkSummary::default().try_to_vec().unwrap()).collect();
+    println!(
+        "block header only: {} borsh bytes",
+        block_bytes.len() + summary_bytes.iter().map(Vec::len).sum::<usize>()
+    );
+    bench.iter(|| black_box(block_header_only_view_from_borsh(&block_bytes, &summary_bytes)));
+}
+
+benchmark_group!(
+    benches,
+    serialize_block_view,
+    serialize_block_header_only_view,
+    deserialize_block_view,
+    deserialize_block_header_only_view
+);
+benchmark_main!(benches);
@@ -5,13 +5,17 @@ use near_crypto::Signature;
 
 use crate::hash::{hash, CryptoHash};
 use crate::merkle::MerklePath;
-use crate::sharding::{EncodedShardChunk, ShardChunk, ShardChunkHeader};
+use crate::sharding::{EncodedShardChunk, ReceiptProof, ShardChunk, ShardChunkHeader};
 use crate::types::AccountId;
 use crate::validator_signer::ValidatorSigner;
 
 /// Serialized TrieNodeWithSize
 pub type StateItem = Vec<u8>;
 
+/// Max size of an encoded `ChunkStateV2` challenge body.  Challenges which don't fit are neither
+/// sent nor accepted.
+pub const MAX_CHUNK_STATE_CHALLENGE_SIZE: usize = 32 * 1024 * 1024;
+
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Debug, Clone, Eq, PartialEq)]
 pub struct PartialState(pub Vec<StateItem>);
@@ -56,7 +60,35 @@ pub enum MaybeEncodedShardChunk {
 /// Doesn't match post-{state root, outgoing receipts, gas used, etc} results after applying previous chunk.
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Eq, Clone, Debug)]
-pub struct ChunkState {
+pub enum ChunkState {
+    V1(ChunkStateV1),
+    V2(ChunkStateV2),
+}
+
+/// Chunk state challenge without the receipts the previous chunk was applied with, which can't
+/// be validated.
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Eq, Clone, Debug)]
+pub struct ChunkStateV1 {
+    /// Encoded prev block header.
+    pub prev_block_header: Vec<u8>,
+    /// Encoded block header that contains invalid chunnk.
+    pub block_header: Vec<u8>,
+    /// Merkle proof in inclusion of prev chunk.
+    pub prev_merkle_proof: MerklePath,
+    /// Previous chunk that contains transactions.
+    pub prev_chunk: ShardChunk,
+    /// Merkle proof of inclusion of this chunk.
+    pub merkle_proof: MerklePath,
+    /// Invalid chunk header.
+    pub chunk_header: ShardChunkHeader,
+    /// Partial state that was affected by transactions of given chunk.
+    pub partial_state: PartialState,
+}
+
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Eq, Clone, Debug)]
+pub struct ChunkStateV2 {
     /// Encoded prev block header.
     pub prev_block_header: Vec<u8>,
     /// Encoded block header that contains invalid chunnk.
@@ -71,6 +103,22 @@ pub struct ChunkState {
     pub chunk_header: ShardChunkHeader,
     /// Partial state that was affected by transactions of given chunk.
     pub partial_state: PartialState,
+    /// Blocks with the receipts the previous chunk was applied with: the previous block and its
+    /// ancestors down to the block which includes the chunk before the previous one.
+    pub prev_chunk_receipts: Vec<ChunkStateReceipts>,
+}
+
+/// Block of a `ChunkStateV2` challenge together with the receipts it sends to the challenged shard.
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Eq, Clone, Debug)]
+pub struct ChunkStateReceipts {
+    /// Encoded block header.
+    pub block_header: Vec<u8>,
+    /// All chunk headers of the block, to check which of them are new.
+    pub chunk_headers: Vec<ShardChunkHeader>,
+    /// Receipts sent to the challenged shard by each new chunk of the block, ordered by the
+    /// sending shard.  Empty for the last block, whose receipts were applied by an earlier chunk.
+    pub receipt_proofs: Vec<ReceiptProof>,
 }
 
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
@@ -80,7 +128,9 @@ pub struct ChunkState {
 pub enum ChallengeBody {
     BlockDoubleSign(BlockDoubleSign),
     ChunkProofs(ChunkProofs),
-    ChunkState(ChunkState),
+    /// Encoded the way chunk state challenges were before they were versioned.
+    ChunkState(ChunkStateV1),
+    VersionedChunkState(ChunkState),
 }
 
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
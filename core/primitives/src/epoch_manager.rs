@@ -36,6 +36,9 @@ pub struct EpochConfig {
     pub block_producer_kickout_threshold: u8,
     /// Criterion for kicking out chunk producers.
     pub chunk_producer_kickout_threshold: u8,
+    /// Percentage (0 to 100) of the validators' stake which must not be kicked out for being
+    /// offline in one epoch. 0 disables the limit.
+    pub validator_min_remaining_stake_perc: u8,
     /// Online minimum threshold below which validator doesn't receive reward.
     pub online_min_threshold: Rational,
     /// Online maximum threshold above which validator gets full reward.
@@ -1,5 +1,5 @@
 use crate::serialize::u128_dec_format;
-use crate::types::{AccountId, Balance, EpochId, Gas, Nonce};
+use crate::types::{AccountId, Balance, BlockHeight, EpochId, Gas, Nonce};
 use borsh::{BorshDeserialize, BorshSerialize};
 use near_crypto::PublicKey;
 use serde::{Deserialize, Serialize};
@@ -61,6 +61,8 @@ pub enum RuntimeError {
     ReceiptValidationError(ReceiptValidationError),
     /// Error when accessing validator information. Happens inside epoch manager.
     ValidatorError(EpochError),
+    /// A sandbox state patch can't be applied.
+    InvalidStatePatch(String),
 }
 
 impl std::fmt::Display for RuntimeError {
@@ -140,6 +142,23 @@ pub enum InvalidTxError {
     ActionsValidation(ActionsValidationError),
     /// The size of serialized transaction exceeded the limit.
     TransactionSizeExceeded { size: u64, limit: u64 },
+    /// The key type of the transaction `public_key` isn't enabled by the current protocol version.
+    UnsupportedSignerKeyType { public_key: PublicKey },
+    /// The size of the receipt the transaction is converted to exceeded the limit.
+    ReceiptSizeExceeded { size: u64, limit: u64 },
+    /// Transactions with a priority fee aren't enabled by the current protocol version.
+    PriorityFeeNotSupported,
+    /// The transaction pool has a transaction with the same signer, public key and nonce and the
+    /// priority fee of this one is too low to replace it.
+    ReplacementUnderpriced {
+        #[serde(with = "u128_dec_format")]
+        priority_fee: Balance,
+        #[serde(with = "u128_dec_format")]
+        required_priority_fee: Balance,
+    },
+    /// The transaction pool rejected the transaction because its signer has too many pending
+    /// transactions or the pool is full.
+    TransactionPoolLimitExceeded,
 }
 
 impl std::error::Error for InvalidTxError {}
@@ -168,6 +187,13 @@ pub enum InvalidAccessKeyError {
     },
     /// Having a deposit with a function call action is not allowed with a function call access key.
     DepositWithFunctionCall,
+    /// The access key identified by the `public_key` expired after `valid_until_block_height`
+    AccessKeyExpired {
+        account_id: AccountId,
+        public_key: PublicKey,
+        valid_until_block_height: BlockHeight,
+        block_height: BlockHeight,
+    },
 }
 
 /// Describes the error for validating a list of actions.
@@ -200,6 +226,15 @@ pub enum ActionsValidationError {
     UnsuitableStakingKey { public_key: PublicKey },
     /// The attached amount of gas in a FunctionCall action has to be a positive number.
     FunctionCallZeroAttachedGas,
+    /// The key type of the key in an AddKey, DeleteKey or Stake action isn't enabled by the
+    /// current protocol version.
+    UnsupportedKeyType { public_key: PublicKey },
+    /// Access keys with an expiration height aren't enabled by the current protocol version.
+    AccessKeyExpirationNotSupported,
+    /// The access key in an AddKey action expires before the block the transaction is included in.
+    AddKeyAlreadyExpired { valid_until_block_height: BlockHeight, block_height: BlockHeight },
+    /// `NonrefundableStorageTransfer` actions aren't enabled by the current protocol version.
+    NonrefundableStorageTransferNotSupported,
 }
 
 /// Describes the error for validating a receipt.
@@ -317,6 +352,24 @@ impl Display for ActionsValidationError {
                 f,
                 "The attached amount of gas in a FunctionCall action has to be a positive number",
             ),
+            ActionsValidationError::UnsupportedKeyType { public_key } => {
+                write!(f, "The key type of {} isn't supported yet", public_key)
+            }
+            ActionsValidationError::AccessKeyExpirationNotSupported => write!(
+                f,
+                "Access keys with an expiration height aren't supported yet in an AddKey action",
+            ),
+            ActionsValidationError::AddKeyAlreadyExpired {
+                valid_until_block_height,
+                block_height,
+            } => write!(
+                f,
+                "The access key in an AddKey action is only valid until height {}, the transaction is included at height {}",
+                valid_until_block_height, block_height
+            ),
+            ActionsValidationError::NonrefundableStorageTransferNotSupported => {
+                write!(f, "NonrefundableStorageTransfer actions aren't supported yet")
+            }
         }
     }
 }
@@ -441,6 +494,12 @@ pub enum ActionErrorKind {
     OnlyImplicitAccountCreationAllowed { account_id: AccountId },
     /// Delete account whose state is large is temporarily banned.
     DeleteAccountWithLargeState { account_id: AccountId },
+    /// Trie nodes read while applying the receipt would make its storage proof larger than
+    /// allowed by `per_receipt_storage_proof_size_limit`.
+    StorageProofSizeExceeded { size: u64, limit: u64 },
+    /// Trie nodes loaded while applying the receipt take more memory than allowed by
+    /// `per_receipt_trie_nodes_memory_limit`.
+    TrieNodesMemoryLimitExceeded { size: u64, limit: u64 },
 }
 
 impl From<ActionErrorKind> for ActionError {
@@ -502,6 +561,29 @@ impl Display for InvalidTxError {
             InvalidTxError::TransactionSizeExceeded { size, limit } => {
                 write!(f, "Size of serialized transaction {} exceeded the limit {}", size, limit)
             }
+            InvalidTxError::UnsupportedSignerKeyType { public_key } => {
+                write!(f, "The key type of the signer key {} isn't supported yet", public_key)
+            }
+            InvalidTxError::ReceiptSizeExceeded { size, limit } => {
+                write!(
+                    f,
+                    "Size of the receipt of the transaction {} exceeded the limit {}",
+                    size, limit
+                )
+            }
+            InvalidTxError::PriorityFeeNotSupported => {
+                write!(f, "Transactions with a priority fee aren't supported yet")
+            }
+            InvalidTxError::ReplacementUnderpriced { priority_fee, required_priority_fee } => {
+                write!(
+                    f,
+                    "Priority fee {} is too low to replace the pending transaction with the same nonce, at least {} is required",
+                    priority_fee, required_priority_fee
+                )
+            }
+            InvalidTxError::TransactionPoolLimitExceeded => {
+                write!(f, "The transaction pool is full or has too many transactions of the signer")
+            }
         }
     }
 }
@@ -546,6 +628,16 @@ impl Display for InvalidAccessKeyError {
             InvalidAccessKeyError::DepositWithFunctionCall => {
                 write!(f, "Having a deposit with a function call action is not allowed with a function call access key.")
             }
+            InvalidAccessKeyError::AccessKeyExpired {
+                account_id,
+                public_key,
+                valid_until_block_height,
+                block_height,
+            } => write!(
+                f,
+                "Access Key {:?}:{} is only valid until height {}, the transaction is included at height {}",
+                account_id, public_key, valid_until_block_height, block_height
+            ),
         }
     }
 }
@@ -751,6 +843,8 @@ impl Display for ActionErrorKind {
             ActionErrorKind::InsufficientStake { account_id, stake, minimum_stake } => write!(f, "Account {} tries to stake {} but minimum required stake is {}", account_id, stake, minimum_stake),
             ActionErrorKind::OnlyImplicitAccountCreationAllowed { account_id } => write!(f, "CreateAccount action is called on hex-characters account of length 64 {}", account_id),
             ActionErrorKind::DeleteAccountWithLargeState { account_id } => write!(f, "The state of account {} is too large and therefore cannot be deleted", account_id),
+            ActionErrorKind::StorageProofSizeExceeded { size, limit } => write!(f, "Storage proof of the receipt has size {} which exceeds the limit {}", size, limit),
+            ActionErrorKind::TrieNodesMemoryLimitExceeded { size, limit } => write!(f, "Trie nodes loaded by the receipt have size {} which exceeds the limit {}", size, limit),
         }
     }
 }
@@ -103,6 +103,21 @@ pub fn verify_hash(root: MerkleHash, path: &MerklePath, item_hash: MerkleHash) -
     compute_root_from_path(path, item_hash) == root
 }
 
+/// Verifies that the block with `block_hash` is on the chain of the head block, given a proof
+/// against the block merkle root in the head header.  The head isn't part of its own block
+/// merkle tree, so its proof is empty.
+pub fn verify_block_proof(
+    block_hash: &CryptoHash,
+    proof: &MerklePath,
+    head_hash: &CryptoHash,
+    head_block_merkle_root: &MerkleHash,
+) -> bool {
+    if block_hash == head_hash {
+        return proof.is_empty();
+    }
+    verify_hash(*head_block_merkle_root, proof, *block_hash)
+}
+
 pub fn compute_root_from_path(path: &MerklePath, item_hash: MerkleHash) -> MerkleHash {
     let mut res = item_hash;
     for item in path {
@@ -248,6 +263,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_verify_block_proof() {
+        let blocks: Vec<_> = (0..3u8).map(|i| hash(&[i])).collect();
+        let head_hash = hash(&[3]);
+        let root = compute_root(&blocks);
+        let item = |hash, direction| MerklePathItem { hash, direction };
+        let first_proof =
+            vec![item(blocks[1], Direction::Right), item(blocks[2], Direction::Right)];
+        let last_proof = vec![item(combine_hash(&blocks[0], &blocks[1]), Direction::Left)];
+        assert!(verify_block_proof(&blocks[0], &first_proof, &head_hash, &root));
+        assert!(verify_block_proof(&blocks[2], &last_proof, &head_hash, &root));
+        assert!(!verify_block_proof(&blocks[1], &first_proof, &head_hash, &root));
+        assert!(verify_block_proof(&head_hash, &vec![], &head_hash, &root));
+        assert!(!verify_block_proof(&head_hash, &last_proof, &head_hash, &root));
+
+        // Only the genesis block is below the head.
+        let root = compute_root(&blocks[..1]);
+        assert!(verify_block_proof(&blocks[0], &vec![], &head_hash, &root));
+    }
+
     #[test]
     fn test_combine_hash_stability() {
         let a = MerkleHash::default();
@@ -1,5 +1,6 @@
 use std::borrow::Borrow;
 use std::fmt;
+use std::io;
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
@@ -7,11 +8,14 @@ use serde::{Deserialize, Serialize};
 use near_crypto::{KeyType, PublicKey};
 
 use crate::borsh::maybestd::collections::HashMap;
+use crate::checked_feature;
 use crate::hash::CryptoHash;
 use crate::logging;
-use crate::serialize::{option_base64_format, u128_dec_format_compatible};
+use crate::serialize::{option_base64_format, option_u128_dec_format, u128_dec_format_compatible};
 use crate::transaction::{Action, TransferAction};
-use crate::types::{AccountId, Balance, ShardId};
+use crate::trie_key::TrieKey;
+use crate::types::{AccountId, Balance, Gas, RawStateChangesWithTrieKey, ReceiptDepth, ShardId};
+use crate::version::ProtocolVersion;
 
 /// Receipts are used for a cross-shard communication.
 /// Receipts could be 2 types (determined by a `ReceiptEnum`): `ReceiptEnum::Action` of `ReceiptEnum::Data`.
@@ -41,6 +45,32 @@ impl Receipt {
         self.receipt_id
     }
 
+    /// Priority fee the receipt is ordered by in the delayed receipts queue.  Data receipts and
+    /// action receipts without a priority fee have the lowest priority.
+    pub fn priority_fee(&self) -> Balance {
+        match &self.receipt {
+            ReceiptEnum::Action(action_receipt) => action_receipt.priority_fee.unwrap_or(0),
+            ReceiptEnum::Data(_) => 0,
+        }
+    }
+
+    /// Kind of the refund if this is a refund receipt, i.e. a receipt sent by the system.
+    pub fn refund_kind(&self) -> Option<RefundKind> {
+        if self.predecessor_id.as_ref() != "system" {
+            return None;
+        }
+        match &self.receipt {
+            // Only gas refunds carry the signer whose access key allowance is refunded.
+            ReceiptEnum::Action(action_receipt)
+                if action_receipt.signer_id.as_ref() == "system" =>
+            {
+                Some(RefundKind::Deposit)
+            }
+            ReceiptEnum::Action(_) => Some(RefundKind::Gas),
+            ReceiptEnum::Data(_) => None,
+        }
+    }
+
     /// Generates a receipt with a transfer from system for a given balance without a receipt_id.
     /// This should be used for token refunds instead of gas refunds. It doesn't refund the
     /// allowance of the access key. For gas refunds use `new_gas_refund`.
@@ -57,6 +87,8 @@ impl Receipt {
                 output_data_receivers: vec![],
                 input_data_ids: vec![],
                 actions: vec![Action::Transfer(TransferAction { deposit: refund })],
+                priority_fee: None,
+                depth: None,
             }),
         }
     }
@@ -84,19 +116,118 @@ impl Receipt {
                 output_data_receivers: vec![],
                 input_data_ids: vec![],
                 actions: vec![Action::Transfer(TransferAction { deposit: refund })],
+                priority_fee: None,
+                depth: None,
             }),
         }
     }
 }
 
+/// What a refund receipt returns to its receiver.
+#[derive(
+    BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy,
+)]
+pub enum RefundKind {
+    /// The deposit attached to the actions of a failed receipt, see `Receipt::new_balance_refund`.
+    Deposit,
+    /// The prepaid gas which wasn't used, see `Receipt::new_gas_refund`.
+    Gas,
+}
+
+/// Refund receipt generated by the execution of a receipt.
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq, Clone)]
+pub struct RefundReceipt {
+    pub receipt_id: CryptoHash,
+    pub kind: RefundKind,
+}
+
 /// Receipt could be either ActionReceipt or DataReceipt
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub enum ReceiptEnum {
     Action(ActionReceipt),
     Data(DataReceipt),
 }
 
+/// Borsh tags of the `ReceiptEnum` variants.  A `DataReceipt` with `gas_burnt` and an
+/// `ActionReceipt` with `priority_fee` or `depth` are serialized with their own tags, so receipts
+/// without them keep their original encoding.
+const ACTION_RECEIPT_TAG: u8 = 0;
+const DATA_RECEIPT_TAG: u8 = 1;
+const DATA_RECEIPT_WITH_GAS_BURNT_TAG: u8 = 2;
+const ACTION_RECEIPT_WITH_PRIORITY_FEE_TAG: u8 = 3;
+const ACTION_RECEIPT_WITH_DEPTH_TAG: u8 = 4;
+
+impl BorshSerialize for ReceiptEnum {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            ReceiptEnum::Action(action_receipt) => {
+                match (action_receipt.priority_fee, action_receipt.depth) {
+                    (None, None) => {
+                        ACTION_RECEIPT_TAG.serialize(writer)?;
+                        action_receipt.serialize(writer)
+                    }
+                    (Some(priority_fee), None) => {
+                        ACTION_RECEIPT_WITH_PRIORITY_FEE_TAG.serialize(writer)?;
+                        action_receipt.serialize(writer)?;
+                        priority_fee.serialize(writer)
+                    }
+                    (priority_fee, Some(depth)) => {
+                        ACTION_RECEIPT_WITH_DEPTH_TAG.serialize(writer)?;
+                        action_receipt.serialize(writer)?;
+                        priority_fee.serialize(writer)?;
+                        depth.serialize(writer)
+                    }
+                }
+            }
+            ReceiptEnum::Data(DataReceipt { data_id, data, gas_burnt: None }) => {
+                DATA_RECEIPT_TAG.serialize(writer)?;
+                data_id.serialize(writer)?;
+                data.serialize(writer)
+            }
+            ReceiptEnum::Data(DataReceipt { data_id, data, gas_burnt: Some(gas_burnt) }) => {
+                DATA_RECEIPT_WITH_GAS_BURNT_TAG.serialize(writer)?;
+                data_id.serialize(writer)?;
+                data.serialize(writer)?;
+                gas_burnt.serialize(writer)
+            }
+        }
+    }
+}
+
+impl BorshDeserialize for ReceiptEnum {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        match u8::deserialize(buf)? {
+            ACTION_RECEIPT_TAG => Ok(ReceiptEnum::Action(ActionReceipt::deserialize(buf)?)),
+            ACTION_RECEIPT_WITH_PRIORITY_FEE_TAG => {
+                let mut action_receipt = ActionReceipt::deserialize(buf)?;
+                action_receipt.priority_fee = Some(Balance::deserialize(buf)?);
+                Ok(ReceiptEnum::Action(action_receipt))
+            }
+            ACTION_RECEIPT_WITH_DEPTH_TAG => {
+                let mut action_receipt = ActionReceipt::deserialize(buf)?;
+                action_receipt.priority_fee = Option::<Balance>::deserialize(buf)?;
+                action_receipt.depth = Some(ReceiptDepth::deserialize(buf)?);
+                Ok(ReceiptEnum::Action(action_receipt))
+            }
+            tag @ (DATA_RECEIPT_TAG | DATA_RECEIPT_WITH_GAS_BURNT_TAG) => {
+                let data_id = CryptoHash::deserialize(buf)?;
+                let data = Option::<Vec<u8>>::deserialize(buf)?;
+                let gas_burnt = if tag == DATA_RECEIPT_WITH_GAS_BURNT_TAG {
+                    Some(Gas::deserialize(buf)?)
+                } else {
+                    None
+                };
+                Ok(ReceiptEnum::Data(DataReceipt { data_id, data, gas_burnt }))
+            }
+            tag => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unexpected ReceiptEnum tag {}", tag),
+            )),
+        }
+    }
+}
+
 /// ActionReceipt is derived from an Action from `Transaction or from Receipt`
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -118,16 +249,37 @@ pub struct ActionReceipt {
     pub input_data_ids: Vec<CryptoHash>,
     /// A list of actions to process when all input_data_ids are filled
     pub actions: Vec<Action>,
+    /// Priority fee of the original transaction, inherited by all the receipts it generates.
+    /// Receipts with a higher fee leave the delayed receipts queue first.  Only set since
+    /// `ProtocolFeature::PriorityFee`.
+    #[borsh_skip]
+    #[serde(default, with = "option_u128_dec_format", skip_serializing_if = "Option::is_none")]
+    pub priority_fee: Option<Balance>,
+    /// Number of receipts between the transaction and this receipt: receipts converted from a
+    /// transaction have depth 0 and every receipt created while executing another one has the
+    /// depth of its parent plus one.  Only set since `ProtocolFeature::ReceiptDepth`.
+    #[borsh_skip]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depth: Option<ReceiptDepth>,
+}
+
+/// Returns the depth of the receipt a transaction is converted to in `protocol_version`.
+pub fn transaction_receipt_depth(protocol_version: ProtocolVersion) -> Option<ReceiptDepth> {
+    checked_feature!("protocol_feature_receipt_depth", ReceiptDepth, protocol_version).then(|| 0)
 }
 
 /// An incoming (ingress) `DataReceipt` which is going to a Receipt's `receiver` input_data_ids
 /// Which will be converted to `PromiseResult::Successful(value)` or `PromiseResult::Failed`
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Hash, PartialEq, Eq, Clone)]
+#[derive(Serialize, Deserialize, Hash, PartialEq, Eq, Clone)]
 pub struct DataReceipt {
     pub data_id: CryptoHash,
     #[serde(with = "option_base64_format")]
     pub data: Option<Vec<u8>>,
+    /// Gas burnt by the receipt which produced the data.  Only set since
+    /// `ProtocolFeature::PromiseResultGasBurnt`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_burnt: Option<Gas>,
 }
 
 /// The outgoing (egress) data which will be transformed
@@ -146,6 +298,7 @@ impl fmt::Debug for DataReceipt {
         f.debug_struct("DataReceipt")
             .field("data_id", &self.data_id)
             .field("data", &format_args!("{}", logging::pretty_result(&self.data)))
+            .field("gas_burnt", &self.gas_burnt)
             .finish()
     }
 }
@@ -154,15 +307,65 @@ impl fmt::Debug for DataReceipt {
 /// stored in a state trie with a key = `account_id` + `data_id` until
 /// `input_data_ids` of all incoming Receipts are satisfied
 /// None means data retrieval was failed
-#[derive(BorshSerialize, BorshDeserialize, Hash, PartialEq, Eq, Clone)]
+#[derive(Hash, PartialEq, Eq, Clone)]
 pub struct ReceivedData {
     pub data: Option<Vec<u8>>,
+    /// `DataReceipt::gas_burnt` of the receipt which delivered the data.
+    pub gas_burnt: Option<Gas>,
+}
+
+/// Borsh tags of `ReceivedData`.  The first two are the tags of the `Option` of the data, so data
+/// without `gas_burnt` keeps its original encoding, and the others are followed by `gas_burnt`
+/// after the data.
+const RECEIVED_DATA_NONE_TAG: u8 = 0;
+const RECEIVED_DATA_SOME_TAG: u8 = 1;
+const RECEIVED_DATA_NONE_WITH_GAS_BURNT_TAG: u8 = 2;
+const RECEIVED_DATA_SOME_WITH_GAS_BURNT_TAG: u8 = 3;
+
+impl BorshSerialize for ReceivedData {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        let tag = match (&self.data, self.gas_burnt) {
+            (None, None) => RECEIVED_DATA_NONE_TAG,
+            (Some(_), None) => RECEIVED_DATA_SOME_TAG,
+            (None, Some(_)) => RECEIVED_DATA_NONE_WITH_GAS_BURNT_TAG,
+            (Some(_), Some(_)) => RECEIVED_DATA_SOME_WITH_GAS_BURNT_TAG,
+        };
+        tag.serialize(writer)?;
+        if let Some(data) = &self.data {
+            data.serialize(writer)?;
+        }
+        match self.gas_burnt {
+            Some(gas_burnt) => gas_burnt.serialize(writer),
+            None => Ok(()),
+        }
+    }
+}
+
+impl BorshDeserialize for ReceivedData {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        let (has_data, has_gas_burnt) = match u8::deserialize(buf)? {
+            RECEIVED_DATA_NONE_TAG => (false, false),
+            RECEIVED_DATA_SOME_TAG => (true, false),
+            RECEIVED_DATA_NONE_WITH_GAS_BURNT_TAG => (false, true),
+            RECEIVED_DATA_SOME_WITH_GAS_BURNT_TAG => (true, true),
+            tag => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Unexpected ReceivedData tag {}", tag),
+                ))
+            }
+        };
+        let data = if has_data { Some(Vec::<u8>::deserialize(buf)?) } else { None };
+        let gas_burnt = if has_gas_burnt { Some(Gas::deserialize(buf)?) } else { None };
+        Ok(ReceivedData { data, gas_burnt })
+    }
 }
 
 impl fmt::Debug for ReceivedData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("ReceivedData")
             .field("data", &format_args!("{}", logging::pretty_result(&self.data)))
+            .field("gas_burnt", &self.gas_burnt)
             .finish()
     }
 }
@@ -176,5 +379,168 @@ pub struct DelayedReceiptIndices {
     pub next_available_index: u64,
 }
 
+/// Receipts added to and removed from the delayed receipts queue and the set of postponed
+/// receipts of a shard while applying a chunk.
+#[derive(Default, BorshSerialize, BorshDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct ReceiptQueueChanges {
+    /// Delayed receipts in the order they are added to the queue, followed by the delayed
+    /// receipts with a priority fee in the order they will be processed.
+    pub delayed_receipts_added: Vec<Receipt>,
+    pub delayed_receipts_removed: Vec<CryptoHash>,
+    pub postponed_receipts_added: Vec<Receipt>,
+    pub postponed_receipts_removed: Vec<CryptoHash>,
+}
+
+impl ReceiptQueueChanges {
+    /// Collects the changes from the state changes of a chunk application.  Removed delayed
+    /// receipts are identified by their queue index only, so their ids are taken from
+    /// `processed_delayed_receipts`.
+    pub fn from_state_changes(
+        state_changes: &[RawStateChangesWithTrieKey],
+        processed_delayed_receipts: &[Receipt],
+    ) -> Self {
+        let mut delayed_receipts_added = Vec::new();
+        let mut priority_delayed_receipts_added = Vec::new();
+        let mut postponed_receipts_added = Vec::new();
+        let mut postponed_receipts_removed = Vec::new();
+        for RawStateChangesWithTrieKey { trie_key, changes } in state_changes {
+            match trie_key {
+                TrieKey::DelayedReceipt { index } => {
+                    // A delayed receipt is never added and removed while applying the same chunk.
+                    if let Some(data) = changes.last().and_then(|change| change.data.as_ref()) {
+                        let receipt = Receipt::try_from_slice(data)
+                            .expect("Failed to parse internally stored delayed receipt");
+                        delayed_receipts_added.push((*index, receipt));
+                    }
+                }
+                TrieKey::PriorityDelayedReceipt { .. } => {
+                    // State changes are sorted by their raw keys, which sort these receipts.
+                    if let Some(data) = changes.last().and_then(|change| change.data.as_ref()) {
+                        let receipt = Receipt::try_from_slice(data)
+                            .expect("Failed to parse internally stored delayed receipt");
+                        priority_delayed_receipts_added.push(receipt);
+                    }
+                }
+                TrieKey::PostponedReceipt { receipt_id, .. } => {
+                    for change in changes {
+                        match &change.data {
+                            Some(data) => postponed_receipts_added.push(
+                                Receipt::try_from_slice(data)
+                                    .expect("Failed to parse internally stored postponed receipt"),
+                            ),
+                            None => postponed_receipts_removed.push(*receipt_id),
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        delayed_receipts_added.sort_by_key(|(index, _)| *index);
+        ReceiptQueueChanges {
+            delayed_receipts_added: delayed_receipts_added
+                .into_iter()
+                .map(|(_, receipt)| receipt)
+                .chain(priority_delayed_receipts_added)
+                .collect(),
+            delayed_receipts_removed: processed_delayed_receipts
+                .iter()
+                .map(|receipt| receipt.receipt_id)
+                .collect(),
+            postponed_receipts_added,
+            postponed_receipts_removed,
+        }
+    }
+}
+
 /// Map of shard to list of receipts to send to it.
 pub type ReceiptResult = HashMap<ShardId, Vec<Receipt>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_receipt_borsh() {
+        let data_id = CryptoHash::hash_bytes(b"data");
+        let receipt =
+            ReceiptEnum::Data(DataReceipt { data_id, data: Some(vec![1, 2, 3]), gas_burnt: None });
+        let bytes = receipt.try_to_vec().unwrap();
+        let mut expected = vec![DATA_RECEIPT_TAG];
+        expected.extend(data_id.try_to_vec().unwrap());
+        expected.extend(Some(vec![1u8, 2, 3]).try_to_vec().unwrap());
+        assert_eq!(bytes, expected);
+        assert_eq!(ReceiptEnum::try_from_slice(&bytes).unwrap(), receipt);
+
+        let receipt =
+            ReceiptEnum::Data(DataReceipt { data_id, data: None, gas_burnt: Some(1_000) });
+        let bytes = receipt.try_to_vec().unwrap();
+        assert_eq!(bytes[0], DATA_RECEIPT_WITH_GAS_BURNT_TAG);
+        assert_eq!(ReceiptEnum::try_from_slice(&bytes).unwrap(), receipt);
+    }
+
+    #[test]
+    fn test_action_receipt_borsh() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let mut receipt = Receipt::new_balance_refund(&alice, 1).receipt;
+        let bytes = receipt.try_to_vec().unwrap();
+        assert_eq!(bytes[0], ACTION_RECEIPT_TAG);
+        assert_eq!(ReceiptEnum::try_from_slice(&bytes).unwrap(), receipt);
+
+        if let ReceiptEnum::Action(action_receipt) = &mut receipt {
+            action_receipt.priority_fee = Some(1_000);
+        }
+        let priority_bytes = receipt.try_to_vec().unwrap();
+        assert_eq!(priority_bytes[0], ACTION_RECEIPT_WITH_PRIORITY_FEE_TAG);
+        assert_eq!(priority_bytes[1..bytes.len()], bytes[1..]);
+        assert_eq!(ReceiptEnum::try_from_slice(&priority_bytes).unwrap(), receipt);
+
+        for priority_fee in [None, Some(1_000)] {
+            if let ReceiptEnum::Action(action_receipt) = &mut receipt {
+                action_receipt.priority_fee = priority_fee;
+                action_receipt.depth = Some(7);
+            }
+            let depth_bytes = receipt.try_to_vec().unwrap();
+            assert_eq!(depth_bytes[0], ACTION_RECEIPT_WITH_DEPTH_TAG);
+            assert_eq!(depth_bytes[1..bytes.len()], bytes[1..]);
+            assert_eq!(ReceiptEnum::try_from_slice(&depth_bytes).unwrap(), receipt);
+        }
+    }
+
+    #[test]
+    fn test_received_data_borsh() {
+        let data = ReceivedData { data: Some(vec![1, 2, 3]), gas_burnt: None };
+        let bytes = data.try_to_vec().unwrap();
+        assert_eq!(bytes, Some(vec![1u8, 2, 3]).try_to_vec().unwrap());
+        assert_eq!(ReceivedData::try_from_slice(&bytes).unwrap(), data);
+
+        let data = ReceivedData { data: None, gas_burnt: None };
+        let bytes = data.try_to_vec().unwrap();
+        assert_eq!(bytes, None::<Vec<u8>>.try_to_vec().unwrap());
+        assert_eq!(ReceivedData::try_from_slice(&bytes).unwrap(), data);
+
+        // The encoding is self-delimiting, so `ReceivedData` with `gas_burnt` can be followed by
+        // other values.
+        for data in [None, Some(vec![1, 2, 3])] {
+            let data = ReceivedData { data, gas_burnt: Some(1_000) };
+            let bytes = (data.clone(), 7u64).try_to_vec().unwrap();
+            assert_eq!(<(ReceivedData, u64)>::try_from_slice(&bytes).unwrap(), (data, 7));
+        }
+
+        assert!(ReceivedData::try_from_slice(&[4]).is_err());
+    }
+
+    #[test]
+    fn test_refund_kind() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let public_key = PublicKey::empty(KeyType::ED25519);
+        assert_eq!(Receipt::new_balance_refund(&alice, 1).refund_kind(), Some(RefundKind::Deposit));
+        assert_eq!(
+            Receipt::new_gas_refund(&alice, 1, public_key).refund_kind(),
+            Some(RefundKind::Gas)
+        );
+
+        let mut receipt = Receipt::new_balance_refund(&alice, 1);
+        receipt.predecessor_id = alice.clone();
+        assert_eq!(receipt.refund_kind(), None);
+    }
+}
@@ -0,0 +1,281 @@
+//! Proofs that receipts sent by a chunk are destined to a given shard.
+//!
+//! The outgoing receipts of a chunk are grouped by the shard of their receivers.  Each group is
+//! hashed together with the id of the receiving shard and the chunk header commits to the merkle
+//! root of these hashes, `outgoing_receipts_root`.  The receipts of one group together with the
+//! merkle path of its hash make up a [`ReceiptProof`], which can be checked by anyone who knows
+//! the chunk header without having the rest of the receipts.
+use std::collections::HashMap;
+use std::fmt;
+
+use borsh::BorshSerialize;
+
+use crate::hash::{hash, CryptoHash};
+use crate::merkle::{merklize, verify_path};
+use crate::receipt::Receipt;
+use crate::shard_layout::{account_id_to_shard_id, ShardLayout};
+use crate::sharding::{ReceiptList, ReceiptProof, ShardProof};
+use crate::types::{MerkleHash, ShardId};
+
+/// Splits `receipts` by the shard of their receivers, keeping their order within a shard.
+pub fn group_receipts_by_shard(
+    receipts: Vec<Receipt>,
+    shard_layout: &ShardLayout,
+) -> HashMap<ShardId, Vec<Receipt>> {
+    let mut result = HashMap::with_capacity(shard_layout.num_shards() as usize);
+    for receipt in receipts {
+        let shard_id = account_id_to_shard_id(&receipt.receiver_id, shard_layout);
+        let entry = result.entry(shard_id).or_insert_with(Vec::new);
+        entry.push(receipt)
+    }
+    result
+}
+
+/// Hashes of the receipts sent to each shard, the leaves of the `outgoing_receipts_root` tree.
+pub fn build_receipts_hashes(receipts: &[Receipt], shard_layout: &ShardLayout) -> Vec<CryptoHash> {
+    if shard_layout.num_shards() == 1 {
+        return vec![hash(&ReceiptList(0, receipts).try_to_vec().unwrap())];
+    }
+    let mut account_id_to_shard_id_map = HashMap::new();
+    let mut shard_receipts: Vec<_> =
+        (0..shard_layout.num_shards()).map(|i| (i, Vec::new())).collect();
+    for receipt in receipts.iter() {
+        let shard_id = match account_id_to_shard_id_map.get(&receipt.receiver_id) {
+            Some(id) => *id,
+            None => {
+                let id = account_id_to_shard_id(&receipt.receiver_id, shard_layout);
+                account_id_to_shard_id_map.insert(receipt.receiver_id.clone(), id);
+                id
+            }
+        };
+        shard_receipts[shard_id as usize].1.push(receipt);
+    }
+    shard_receipts
+        .into_iter()
+        .map(|(i, rs)| {
+            let bytes = (i, rs).try_to_vec().unwrap();
+            hash(&bytes)
+        })
+        .collect()
+}
+
+/// Builds the `outgoing_receipts_root` of a chunk of `from_shard_id` which sends `receipts`
+/// together with the proofs of the receipts for every shard of `shard_layout`, indexed by the
+/// receiving shard id.
+pub fn create_receipt_proofs(
+    from_shard_id: ShardId,
+    receipts: &[Receipt],
+    shard_layout: &ShardLayout,
+) -> (MerkleHash, Vec<ReceiptProof>) {
+    let hashes = build_receipts_hashes(receipts, shard_layout);
+    let (root, paths) = merklize(&hashes);
+    let mut receipts_by_shard = group_receipts_by_shard(receipts.to_vec(), shard_layout);
+    let proofs = paths
+        .into_iter()
+        .enumerate()
+        .map(|(to_shard_id, proof)| {
+            let to_shard_id = to_shard_id as ShardId;
+            let receipts = receipts_by_shard.remove(&to_shard_id).unwrap_or_default();
+            ReceiptProof(receipts, ShardProof { from_shard_id, to_shard_id, proof })
+        })
+        .collect();
+    (root, proofs)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReceiptProofError {
+    /// The proof is for receipts sent by another shard.
+    WrongFromShard { expected: ShardId, actual: ShardId },
+    /// The proof is for receipts sent to another shard.
+    WrongToShard { expected: ShardId, actual: ShardId },
+    /// The receipt is not among the receipts covered by the proof.
+    ReceiptNotInProof { receipt_id: CryptoHash },
+    /// The merkle path doesn't lead from the receipts to the outgoing receipts root.
+    InvalidMerklePath,
+}
+
+impl fmt::Display for ReceiptProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReceiptProofError::WrongFromShard { expected, actual } => {
+                write!(
+                    f,
+                    "Proof is for receipts from shard {}, expected shard {}",
+                    actual, expected
+                )
+            }
+            ReceiptProofError::WrongToShard { expected, actual } => {
+                write!(f, "Proof is for receipts to shard {}, expected shard {}", actual, expected)
+            }
+            ReceiptProofError::ReceiptNotInProof { receipt_id } => {
+                write!(f, "Receipt {} is not covered by the proof", receipt_id)
+            }
+            ReceiptProofError::InvalidMerklePath => {
+                write!(f, "Merkle path doesn't match the outgoing receipts root")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReceiptProofError {}
+
+/// Checks that `receipt` is sent from `from_shard` to `to_shard` by the chunk whose header has
+/// `outgoing_receipts_root`.
+///
+/// The receipt is compared with the ones in the proof by content, not only by id, so a proof
+/// can't vouch for a receipt which has been tampered with.
+pub fn verify_receipt_proof(
+    receipt: &Receipt,
+    proof: &ReceiptProof,
+    outgoing_receipts_root: &MerkleHash,
+    from_shard: ShardId,
+    to_shard: ShardId,
+) -> Result<(), ReceiptProofError> {
+    verify_receipts_proof(proof, outgoing_receipts_root, from_shard, to_shard)?;
+    if !proof.0.contains(receipt) {
+        return Err(ReceiptProofError::ReceiptNotInProof { receipt_id: receipt.receipt_id });
+    }
+    Ok(())
+}
+
+/// Checks that the receipts of `proof` are all the receipts sent from `from_shard` to
+/// `to_shard` by the chunk whose header has `outgoing_receipts_root`.
+pub fn verify_receipts_proof(
+    proof: &ReceiptProof,
+    outgoing_receipts_root: &MerkleHash,
+    from_shard: ShardId,
+    to_shard: ShardId,
+) -> Result<(), ReceiptProofError> {
+    let ReceiptProof(receipts, shard_proof) = proof;
+    if shard_proof.from_shard_id != from_shard {
+        return Err(ReceiptProofError::WrongFromShard {
+            expected: from_shard,
+            actual: shard_proof.from_shard_id,
+        });
+    }
+    if shard_proof.to_shard_id != to_shard {
+        return Err(ReceiptProofError::WrongToShard {
+            expected: to_shard,
+            actual: shard_proof.to_shard_id,
+        });
+    }
+    // The id of the receiving shard is a part of the hashed data, so the receipts can't be
+    // presented as sent to another shard.
+    let receipts_hash = hash(&ReceiptList(to_shard, receipts).try_to_vec().unwrap());
+    if !verify_path(*outgoing_receipts_root, &shard_proof.proof, &receipts_hash) {
+        return Err(ReceiptProofError::InvalidMerklePath);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::AccountId;
+
+    use super::*;
+
+    fn receipt(receiver_id: &str, deposit: u128) -> Receipt {
+        let receiver_id: AccountId = receiver_id.parse().unwrap();
+        let mut receipt = Receipt::new_balance_refund(&receiver_id, deposit);
+        receipt.receipt_id = hash(format!("{}{}", receiver_id, deposit).as_bytes());
+        receipt
+    }
+
+    /// Receipts to shards 0, 1 and 3 of `ShardLayout::v1_test`.
+    fn receipts() -> Vec<Receipt> {
+        vec![receipt("test0", 1), receipt("aaa", 2), receipt("zzz", 3), receipt("aaa", 4)]
+    }
+
+    #[test]
+    fn test_receipt_proofs() {
+        let shard_layout = ShardLayout::v1_test();
+        let receipts = receipts();
+        let (root, proofs) = create_receipt_proofs(1, &receipts, &shard_layout);
+        assert_eq!(proofs.len(), 4);
+        assert!(proofs[2].0.is_empty());
+        for receipt in &receipts {
+            let to_shard = account_id_to_shard_id(&receipt.receiver_id, &shard_layout);
+            let proof = &proofs[to_shard as usize];
+            assert_eq!(verify_receipt_proof(receipt, proof, &root, 1, to_shard), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_single_shard_receipt_proofs() {
+        let shard_layout = ShardLayout::v0_single_shard();
+        let receipts = receipts();
+        let (root, proofs) = create_receipt_proofs(0, &receipts, &shard_layout);
+        assert_eq!(proofs.len(), 1);
+        for receipt in &receipts {
+            assert_eq!(verify_receipt_proof(receipt, &proofs[0], &root, 0, 0), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_tampered_receipt() {
+        let shard_layout = ShardLayout::v1_test();
+        let receipts = receipts();
+        let (root, proofs) = create_receipt_proofs(1, &receipts, &shard_layout);
+        let mut proof = proofs[1].clone();
+        let original = proof.0[0].clone();
+
+        // Same id, bigger deposit.
+        let mut tampered = original.clone();
+        tampered.receipt = receipt("aaa", 1000).receipt;
+        assert_eq!(
+            verify_receipt_proof(&tampered, &proof, &root, 1, 1),
+            Err(ReceiptProofError::ReceiptNotInProof { receipt_id: original.receipt_id })
+        );
+
+        // Tampered along with the proof.
+        proof.0[0] = tampered.clone();
+        assert_eq!(
+            verify_receipt_proof(&tampered, &proof, &root, 1, 1),
+            Err(ReceiptProofError::InvalidMerklePath)
+        );
+
+        // A receipt smuggled into the proof.
+        let mut proof = proofs[1].clone();
+        let smuggled = receipt("aaa", 5);
+        proof.0.push(smuggled.clone());
+        assert_eq!(
+            verify_receipt_proof(&smuggled, &proof, &root, 1, 1),
+            Err(ReceiptProofError::InvalidMerklePath)
+        );
+        assert_eq!(
+            verify_receipt_proof(&original, &proof, &root, 1, 1),
+            Err(ReceiptProofError::InvalidMerklePath)
+        );
+    }
+
+    #[test]
+    fn test_wrong_shard() {
+        let shard_layout = ShardLayout::v1_test();
+        let receipts = receipts();
+        let (root, proofs) = create_receipt_proofs(1, &receipts, &shard_layout);
+        let receipt = &proofs[1].0[0];
+
+        assert_eq!(
+            verify_receipt_proof(receipt, &proofs[1], &root, 0, 1),
+            Err(ReceiptProofError::WrongFromShard { expected: 0, actual: 1 })
+        );
+        assert_eq!(
+            verify_receipt_proof(receipt, &proofs[1], &root, 1, 3),
+            Err(ReceiptProofError::WrongToShard { expected: 3, actual: 1 })
+        );
+
+        // The receipts of shard 1 presented as sent to shard 3.
+        let mut proof = proofs[1].clone();
+        proof.1.to_shard_id = 3;
+        assert_eq!(
+            verify_receipt_proof(receipt, &proof, &root, 1, 3),
+            Err(ReceiptProofError::InvalidMerklePath)
+        );
+        // ... and with the path of shard 3.
+        proof.1.proof = proofs[3].1.proof.clone();
+        assert_eq!(
+            verify_receipt_proof(receipt, &proof, &root, 1, 3),
+            Err(ReceiptProofError::InvalidMerklePath)
+        );
+    }
+}
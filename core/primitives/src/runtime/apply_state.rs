@@ -1,10 +1,15 @@
 use crate::runtime::migration_data::{MigrationData, MigrationFlags};
+use crate::serialize::u128_dec_format;
 use crate::{
     hash::CryptoHash,
     runtime::config::RuntimeConfig,
-    types::{Balance, BlockHeight, CompiledContractCache, EpochHeight, EpochId, Gas},
+    types::{
+        Balance, BlockHeight, CompiledContractCache, Compute, EpochHeight, EpochId, Gas, ShardId,
+    },
     version::ProtocolVersion,
 };
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 #[derive(Debug)]
@@ -20,6 +25,8 @@ pub struct ApplyState {
     pub epoch_id: EpochId,
     /// Current epoch height
     pub epoch_height: EpochHeight,
+    /// Id of the shard the chunk is applied to.
+    pub shard_id: ShardId,
     /// Price for the gas.
     pub gas_price: Balance,
     /// The current block timestamp (number of non-leap-nanoseconds since January 1, 1970 0:00:00 UTC).
@@ -27,6 +34,10 @@ pub struct ApplyState {
     /// Gas limit for a given chunk.
     /// If None is given, assumes there is no gas limit.
     pub gas_limit: Option<Gas>,
+    /// Compute limit for a given chunk, receipts aren't processed once the gas burnt with the
+    /// host function costs counted at their compute cost reaches it.
+    /// If None is given, the gas limit is used.
+    pub compute_limit: Option<Compute>,
     /// Current random seed (from current block vrf output).
     pub random_seed: CryptoHash,
     /// Current Protocol version when we apply the state transition
@@ -43,3 +54,37 @@ pub struct ApplyState {
     /// Flags for migrations indicating whether they can be applied at this block
     pub migration_flags: MigrationFlags,
 }
+
+impl ApplyState {
+    /// Parameters of the chunk application which aren't part of the chunk itself.
+    pub fn applied_chunk_info(&self) -> AppliedChunkInfo {
+        AppliedChunkInfo {
+            block_height: self.block_index,
+            gas_price: self.gas_price,
+            gas_limit: self.gas_limit,
+            random_seed: self.random_seed,
+            protocol_version: self.current_protocol_version,
+            is_new_chunk: self.is_new_chunk,
+            is_first_block_of_version: self.migration_flags.is_first_block_of_version,
+            is_first_block_with_chunk_of_version: self
+                .migration_flags
+                .is_first_block_with_chunk_of_version,
+        }
+    }
+}
+
+/// Parameters a chunk was applied with in a block, saved so that the application can be
+/// reproduced without deriving them again from the chain.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AppliedChunkInfo {
+    pub block_height: BlockHeight,
+    #[serde(with = "u128_dec_format")]
+    pub gas_price: Balance,
+    pub gas_limit: Option<Gas>,
+    pub random_seed: CryptoHash,
+    pub protocol_version: ProtocolVersion,
+    /// Whether a new chunk was applied, rather than the previous chunk again for a missing one.
+    pub is_new_chunk: bool,
+    pub is_first_block_of_version: bool,
+    pub is_first_block_with_chunk_of_version: bool,
+}
@@ -1,4 +1,5 @@
 //! Settings of the parameters of the runtime.
+use num_rational::Rational;
 use serde::{Deserialize, Serialize};
 
 use crate::config::VMConfig;
@@ -20,6 +21,11 @@ pub struct RuntimeConfig {
     pub wasm_config: VMConfig,
     /// Config that defines rules for account creation.
     pub account_creation_config: AccountCreationConfig,
+    /// Accounts the protocol treasury reward is split between, with their fractions of it, from
+    /// the `ProtocolTreasurySplit` protocol version on.  If empty, `protocol_treasury_account` of
+    /// the genesis config keeps receiving the whole reward.  The accounts must exist.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub protocol_treasury_split: Vec<(AccountId, Rational)>,
 }
 
 impl RuntimeConfig {
@@ -30,6 +36,7 @@ impl RuntimeConfig {
             transaction_costs: RuntimeFeesConfig::test(),
             wasm_config: VMConfig::test(),
             account_creation_config: AccountCreationConfig::default(),
+            protocol_treasury_split: vec![],
         }
     }
 
@@ -39,6 +46,7 @@ impl RuntimeConfig {
             transaction_costs: RuntimeFeesConfig::free(),
             wasm_config: VMConfig::free(),
             account_creation_config: AccountCreationConfig::default(),
+            protocol_treasury_split: vec![],
         }
     }
 }
@@ -14,6 +14,9 @@ macro_rules! include_config {
 /// the new runtime config in bytes.
 /// Protocol versions are given in increasing order. First one is always 0, so that each version is
 /// mapped to some config.
+/// The config of each version is the one of the previous version with the parameters of its file
+/// overridden.  The files of protocol features only contain the parameters of their feature, so
+/// that enabling a feature doesn't enable the parameters of the features before it.
 static CONFIGS: &[(ProtocolVersion, &[u8])] = &[
     (0, include_config!("29.json")),
     (42, include_config!("42.json")),
@@ -22,6 +25,33 @@ static CONFIGS: &[(ProtocolVersion, &[u8])] = &[
     (50, include_config!("50.json")),
     // max_gas_burnt increased to 300 TGas
     (52, include_config!("52.json")),
+    // Stack limiter V2 with a call depth limit and a per-local cost
+    #[cfg(feature = "protocol_feature_stack_limiter_v2")]
+    (129, include_config!("129.json")),
+    // Limit of keys counted by storage_key_count
+    #[cfg(feature = "protocol_feature_storage_key_count")]
+    (134, include_config!("134.json")),
+    // Contracts declaring or importing a memory above the max are rejected
+    #[cfg(feature = "protocol_feature_memory_limit_check")]
+    (143, include_config!("143.json")),
+    // Receipts created by contracts and transactions are limited in size
+    #[cfg(feature = "protocol_feature_receipt_size_limit")]
+    (147, include_config!("147.json")),
+    // Bytes of returned values above a threshold are more expensive
+    #[cfg(feature = "protocol_feature_large_data_receipt_cost")]
+    (148, include_config!("148.json")),
+    // Compute cost of touching trie nodes above their gas cost
+    #[cfg(feature = "protocol_feature_compute_costs")]
+    (153, include_config!("153.json")),
+    // Action receipts created by contracts are limited in depth
+    #[cfg(feature = "protocol_feature_receipt_depth")]
+    (154, include_config!("154.json")),
+    // Receipts are limited in the size they add to the storage proof of the chunk
+    #[cfg(feature = "protocol_feature_per_receipt_storage_proof_limit")]
+    (155, include_config!("155.json")),
+    // Receipts are limited in the size of the trie nodes and values they load
+    #[cfg(feature = "protocol_feature_per_receipt_trie_nodes_memory_limit")]
+    (158, include_config!("158.json")),
 ];
 
 pub static INITIAL_TESTNET_CONFIG: &[u8] = include_config!("29_testnet.json");
@@ -42,10 +72,15 @@ impl RuntimeConfigStore {
     /// runtime config by sequential modifications to the genesis runtime config.
     /// TODO #4775: introduce new protocol version to have the same runtime config for all chains
     pub fn new(genesis_runtime_config: Option<&RuntimeConfig>) -> Self {
-        let mut store =
-            BTreeMap::from_iter(CONFIGS.iter().cloned().map(|(protocol_version, config_bytes)| {
-                (protocol_version, Arc::new(serde_json::from_slice(config_bytes).unwrap()))
-            }));
+        let mut config_json = serde_json::Value::Null;
+        let mut store = BTreeMap::new();
+        for (protocol_version, config_bytes) in CONFIGS {
+            override_parameters(&mut config_json, serde_json::from_slice(config_bytes).unwrap());
+            store.insert(
+                *protocol_version,
+                Arc::new(serde_json::from_value(config_json.clone()).unwrap()),
+            );
+        }
 
         if let Some(runtime_config) = genesis_runtime_config {
             let mut config = runtime_config.clone();
@@ -58,6 +93,22 @@ impl RuntimeConfigStore {
         Self { store }
     }
 
+    /// Constructs the store a node of the chain with `chain_id` uses.
+    ///
+    /// For testnet, runtime config for genesis block was (incorrectly) different, that's why we
+    /// need to override it specifically to preserve compatibility.  Other chains don't need to
+    /// override the config of the first protocol versions.
+    pub fn for_chain_id(chain_id: &str) -> Self {
+        match chain_id {
+            "testnet" => {
+                let genesis_runtime_config =
+                    serde_json::from_slice(INITIAL_TESTNET_CONFIG).unwrap();
+                Self::new(Some(&genesis_runtime_config))
+            }
+            _ => Self::new(None),
+        }
+    }
+
     /// Constructs test store.
     pub fn with_one_config(runtime_config: RuntimeConfig) -> Self {
         Self { store: BTreeMap::from_iter([(0, Arc::new(runtime_config))].iter().cloned()) }
@@ -85,6 +136,18 @@ impl RuntimeConfigStore {
     }
 }
 
+/// Overrides the parameters of `config` with the ones set in `parameters`.
+fn override_parameters(config: &mut serde_json::Value, parameters: serde_json::Value) {
+    match (config, parameters) {
+        (serde_json::Value::Object(config), serde_json::Value::Object(parameters)) => {
+            for (key, value) in parameters {
+                override_parameters(config.entry(key).or_insert(serde_json::Value::Null), value);
+            }
+        }
+        (config, parameters) => *config = parameters,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -92,6 +155,8 @@ mod tests {
     use crate::version::ProtocolFeature::LowerDataReceiptAndEcrecoverBaseCost;
     use crate::version::ProtocolFeature::LowerStorageCost;
     use near_primitives_core::hash::hash;
+    use num_rational::Rational;
+    use std::collections::HashSet;
 
     const GENESIS_PROTOCOL_VERSION: ProtocolVersion = 29;
     const RECEIPTS_DEPTH: u64 = 63;
@@ -110,12 +175,58 @@ mod tests {
         check_config(GENESIS_PROTOCOL_VERSION, CONFIGS[0].1);
         // First non-trivial version for which runtime config was updated.
         check_config(LowerStorageCost.protocol_version(), CONFIGS[1].1);
-        check_config(ProtocolVersion::MAX, CONFIGS.last().unwrap().1);
+        let store = RuntimeConfigStore::new(None);
+        assert_eq!(
+            store.get_config(ProtocolVersion::MAX),
+            store.get_config(CONFIGS.last().unwrap().0)
+        );
+    }
+
+    /// Returns the paths of the parameters set in `parameters`.
+    fn parameter_paths(parameters: &serde_json::Value, prefix: &str, paths: &mut Vec<String>) {
+        match parameters {
+            serde_json::Value::Object(parameters) => {
+                for (key, value) in parameters {
+                    parameter_paths(value, &format!("{}.{}", prefix, key), paths);
+                }
+            }
+            _ => paths.push(prefix.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_protocol_feature_configs_are_disjoint() {
+        // Versions up to 52 are stable and don't belong to a protocol feature.
+        let base_versions = CONFIGS.iter().take_while(|(version, _)| *version <= 52).count();
+        let mut seen = HashSet::new();
+        for (protocol_version, config_bytes) in &CONFIGS[base_versions..] {
+            let mut paths = vec![];
+            parameter_paths(&serde_json::from_slice(config_bytes).unwrap(), "", &mut paths);
+            for path in paths {
+                assert!(
+                    seen.insert(path.clone()),
+                    "Parameter {} of protocol version {} is set by an earlier protocol feature",
+                    path,
+                    protocol_version
+                );
+            }
+        }
+    }
+
+    #[cfg(not(feature = "protocol_feature_stack_limiter_v2"))]
+    #[test]
+    fn test_stack_limiter_v2_needs_its_feature() {
+        let store = RuntimeConfigStore::new(None);
+        let limit_config = &store.get_config(ProtocolVersion::MAX).wasm_config.limit_config;
+        assert_eq!(limit_config.max_stack_height, 16 * 1024);
+        assert_eq!(limit_config.max_call_depth, None);
+        assert_eq!(limit_config.stack_height_local_cost, None);
     }
 
     #[test]
     fn test_runtime_config_data() {
-        let expected_hashes = vec![
+        #[allow(unused_mut)]
+        let mut expected_hashes = vec![
             "FF2Qg5qSM6iWQjD5ZyzEhdAV2g5MyQKXGYh4kyt8mMcE",
             "97UzHtVFBc4235jdur3DgNSUGNfGQfzRDLmAkYdZ19Re",
             "C6uw6BoeXr3KoKpVP34hBA7TqoywMbwMtJgqbTpPCiSB",
@@ -123,6 +234,24 @@ mod tests {
             "HFetcNKaC5s8Mj7bQz7jGMF7Rsvtuc3kjZRevWQ334n4",
             "EP9bv2znwbuBuimUgrSQm48ymHqwbHyUArZcWavSbPce",
         ];
+        #[cfg(feature = "protocol_feature_stack_limiter_v2")]
+        expected_hashes.push("GZRe39djbXdmjWbcsxvLbacHJnzG8i3CHrQ4VBX64iXH");
+        #[cfg(feature = "protocol_feature_storage_key_count")]
+        expected_hashes.push("9BXqzymVPwGKJ5hxJorkC74fGnjwehesPGy9GwdG7dXC");
+        #[cfg(feature = "protocol_feature_memory_limit_check")]
+        expected_hashes.push("7iwej7UDTzLRh568tDBVF4PNoP4Sahswsv9FLJQazeUB");
+        #[cfg(feature = "protocol_feature_receipt_size_limit")]
+        expected_hashes.push("Aqb1S87zjXULKBCmf25mj45huxuvwgigwzaVPJdpzPFj");
+        #[cfg(feature = "protocol_feature_large_data_receipt_cost")]
+        expected_hashes.push("D8CHqpLRCTnzwG8FZbat6eBuSmXGX9QYrai93hoLPoSE");
+        #[cfg(feature = "protocol_feature_compute_costs")]
+        expected_hashes.push("7j15cPEiJyCd4qJk9j32qs5MjhMe3FqUpZfLgdggK319");
+        #[cfg(feature = "protocol_feature_receipt_depth")]
+        expected_hashes.push("4hqCMdkEsowrktqBYBMoQerAoGvHAarEsZVPstaQKaf1");
+        #[cfg(feature = "protocol_feature_per_receipt_storage_proof_limit")]
+        expected_hashes.push("CaMiMfc6Bc6MTL2kEtSWdsnSq96LJdub9KQWYEZp7EQx");
+        #[cfg(feature = "protocol_feature_per_receipt_trie_nodes_memory_limit")]
+        expected_hashes.push("2St866HJCoKPU3VdtkDnv5GZpoD2jVPdeYmGbWL32aef");
         let actual_hashes = CONFIGS
             .iter()
             .map(|(_protocol_version, config_bytes)| to_base(&hash(config_bytes)))
@@ -151,6 +280,41 @@ If you add new config version, add a missing hash to the end of `expected_hashes
         }
     }
 
+    #[test]
+    fn test_protocol_treasury_split() {
+        let store = RuntimeConfigStore::new(None);
+        for (protocol_version, config) in store.store.iter() {
+            let treasury_split = &config.protocol_treasury_split;
+            if treasury_split.is_empty() {
+                continue;
+            }
+            let account_ids = treasury_split.iter().map(|(account_id, _)| account_id);
+            assert_eq!(
+                account_ids.collect::<HashSet<_>>().len(),
+                treasury_split.len(),
+                "Duplicate protocol treasury account for protocol version {}",
+                protocol_version
+            );
+            for (account_id, fraction) in treasury_split {
+                assert!(
+                    *fraction > Rational::from_integer(0),
+                    "Protocol treasury fraction of {} must be positive",
+                    account_id
+                );
+                assert!(
+                    *fraction.denom() < 10_000_000,
+                    "Denominator is too large, may lead to overflow."
+                );
+            }
+            assert_eq!(
+                treasury_split.iter().map(|(_, fraction)| *fraction).sum::<Rational>(),
+                Rational::from_integer(1),
+                "Protocol treasury split fractions of protocol version {} must sum to 1",
+                protocol_version
+            );
+        }
+    }
+
     #[test]
     fn test_lower_storage_cost() {
         let store = RuntimeConfigStore::new(None);
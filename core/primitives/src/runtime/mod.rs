@@ -26,13 +26,18 @@ pub fn get_insufficient_storage_stake(
         .ok_or_else(|| {
             format!("Account's storage_usage {} overflows multiplication", account.storage_usage())
         })?;
-    let available_amount = account.amount().checked_add(account.locked()).ok_or_else(|| {
-        format!(
-            "Account's amount {} and locked {} overflow addition",
-            account.amount(),
-            account.locked()
-        )
-    })?;
+    let available_amount = account
+        .amount()
+        .checked_add(account.locked())
+        .and_then(|amount| amount.checked_add(account.nonrefundable()))
+        .ok_or_else(|| {
+            format!(
+                "Account's amount {}, locked {} and nonrefundable {} overflow addition",
+                account.amount(),
+                account.locked(),
+                account.nonrefundable()
+            )
+        })?;
     if available_amount >= required_amount {
         Ok(None)
     } else {
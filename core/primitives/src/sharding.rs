@@ -787,6 +787,23 @@ impl ShardChunk {
     }
 }
 
+/// Sizes of a chunk which are not part of its header, stored separately so that they can be
+/// looked up without reading the whole chunk.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChunkSummary {
+    pub tx_count: u64,
+    pub receipt_count: u64,
+}
+
+impl From<&ShardChunk> for ChunkSummary {
+    fn from(chunk: &ShardChunk) -> Self {
+        ChunkSummary {
+            tx_count: chunk.transactions().len() as u64,
+            receipt_count: chunk.receipts().len() as u64,
+        }
+    }
+}
+
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
 #[derive(Default, BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
 pub struct EncodedShardChunkBody {
@@ -12,7 +12,8 @@ use crate::trie_key::col;
 use crate::trie_key::trie_key_parsers::{
     parse_account_id_from_access_key_key, parse_account_id_from_account_key,
     parse_account_id_from_contract_code_key, parse_account_id_from_contract_data_key,
-    parse_account_id_from_received_data_key, parse_data_id_from_received_data_key,
+    parse_account_id_from_received_data_key, parse_account_id_from_registered_data_id_key,
+    parse_data_id_from_received_data_key, parse_data_id_from_registered_data_id_key,
     parse_data_key_from_contract_data_key, parse_public_key_from_access_key_key,
 };
 use crate::types::AccountId;
@@ -50,6 +51,14 @@ pub enum StateRecord {
     /// Delayed Receipt.
     /// The receipt was delayed because the shard was overwhelmed.
     DelayedReceipt(Box<Receipt>),
+    /// Data id generated by the given account_id, with the receiver of the receipt waiting for
+    /// the data if there is one.
+    RegisteredDataId {
+        account_id: AccountId,
+        data_id: CryptoHash,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        receiver_id: Option<AccountId>,
+    },
 }
 
 impl StateRecord {
@@ -91,11 +100,21 @@ impl StateRecord {
                 let receipt = Receipt::try_from_slice(&value).unwrap();
                 Some(StateRecord::PostponedReceipt(Box::new(receipt)))
             }
-            col::DELAYED_RECEIPT => {
+            col::DELAYED_RECEIPT | col::PRIORITY_DELAYED_RECEIPT => {
                 let receipt = Receipt::try_from_slice(&value).unwrap();
                 Some(StateRecord::DelayedReceipt(Box::new(receipt)))
             }
             col::DELAYED_RECEIPT_INDICES => None,
+            col::REGISTERED_DATA_ID => {
+                let account_id = parse_account_id_from_registered_data_id_key(&key).unwrap();
+                let data_id = parse_data_id_from_registered_data_id_key(&key, &account_id).unwrap();
+                let receiver_id = if value.is_empty() {
+                    None
+                } else {
+                    Some(String::from_utf8(value).unwrap().parse().unwrap())
+                };
+                Some(StateRecord::RegisteredDataId { account_id, data_id, receiver_id })
+            }
             _ => unreachable!(),
         }
     }
@@ -129,6 +148,9 @@ impl Display for StateRecord {
             ),
             StateRecord::PostponedReceipt(receipt) => write!(f, "Postponed receipt {:?}", receipt),
             StateRecord::DelayedReceipt(receipt) => write!(f, "Delayed receipt {:?}", receipt),
+            StateRecord::RegisteredDataId { account_id, data_id, receiver_id } => {
+                write!(f, "Registered data id {:?},{:?}: {:?}", account_id, data_id, receiver_id)
+            }
         }
     }
 }
@@ -154,6 +176,7 @@ pub fn state_record_to_account_id(state_record: &StateRecord) -> &AccountId {
         | StateRecord::AccessKey { account_id, .. }
         | StateRecord::Contract { account_id, .. }
         | StateRecord::ReceivedData { account_id, .. }
+        | StateRecord::RegisteredDataId { account_id, .. }
         | StateRecord::Data { account_id, .. } => account_id,
         StateRecord::PostponedReceipt(receipt) | StateRecord::DelayedReceipt(receipt) => {
             &receipt.receiver_id
@@ -27,6 +27,27 @@ pub struct StateHeaderKey(pub ShardId, pub CryptoHash);
 #[derive(PartialEq, Eq, Clone, Debug, BorshSerialize, BorshDeserialize)]
 pub struct StatePartKey(pub CryptoHash, pub ShardId, pub u64 /* PartId */);
 
+/// Progress of downloading the state parts of a shard, keyed by `StateHeaderKey`.
+#[derive(PartialEq, Eq, Clone, Debug, Default, BorshSerialize, BorshDeserialize)]
+pub struct StateSyncProgress {
+    /// Hashes of the parts which have been downloaded and validated, indexed by part id.
+    pub part_hashes: Vec<Option<CryptoHash>>,
+}
+
+impl StateSyncProgress {
+    pub fn new(num_parts: u64) -> Self {
+        Self { part_hashes: vec![None; num_parts as usize] }
+    }
+
+    /// Ids of the parts which have been downloaded.
+    pub fn done_parts(&self) -> impl Iterator<Item = u64> + '_ {
+        self.part_hashes
+            .iter()
+            .enumerate()
+            .filter_map(|(part_id, hash)| hash.map(|_| part_id as u64))
+    }
+}
+
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
 #[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
 pub struct ShardStateSyncResponseHeaderV1 {
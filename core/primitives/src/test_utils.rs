@@ -18,7 +18,9 @@ use crate::transaction::{
     DeployContractAction, FunctionCallAction, SignedTransaction, StakeAction, Transaction,
     TransferAction,
 };
-use crate::types::{AccountId, Balance, BlockHeight, EpochId, EpochInfoProvider, Gas, Nonce};
+use crate::types::{
+    AccountId, Balance, BlockHeight, EpochId, EpochInfoProvider, Gas, Nonce, ShardId,
+};
 use crate::validator_signer::ValidatorSigner;
 use crate::version::PROTOCOL_VERSION;
 use crate::views::FinalExecutionStatus;
@@ -110,6 +112,7 @@ impl SignedTransaction {
             receiver_id,
             block_hash,
             actions,
+            priority_fee: None,
         }
         .sign(signer)
     }
@@ -168,7 +171,11 @@ impl SignedTransaction {
                 Action::CreateAccount(CreateAccountAction {}),
                 Action::AddKey(AddKeyAction {
                     public_key,
-                    access_key: AccessKey { nonce: 0, permission: AccessKeyPermission::FullAccess },
+                    access_key: AccessKey {
+                        nonce: 0,
+                        permission: AccessKeyPermission::FullAccess,
+                        valid_until_block_height: None,
+                    },
                 }),
                 Action::Transfer(TransferAction { deposit: amount }),
             ],
@@ -195,7 +202,11 @@ impl SignedTransaction {
                 Action::CreateAccount(CreateAccountAction {}),
                 Action::AddKey(AddKeyAction {
                     public_key,
-                    access_key: AccessKey { nonce: 0, permission: AccessKeyPermission::FullAccess },
+                    access_key: AccessKey {
+                        nonce: 0,
+                        permission: AccessKeyPermission::FullAccess,
+                        valid_until_block_height: None,
+                    },
                 }),
                 Action::Transfer(TransferAction { deposit: amount }),
                 Action::DeployContract(DeployContractAction { code }),
@@ -474,6 +485,15 @@ impl EpochInfoProvider for MockEpochInfoProvider {
     fn minimum_stake(&self, _prev_block_hash: &CryptoHash) -> Result<Balance, EpochError> {
         Ok(0)
     }
+
+    /// All accounts are on shard 0.
+    fn account_id_to_shard_id(
+        &self,
+        _account_id: &AccountId,
+        _epoch_id: &EpochId,
+    ) -> Result<ShardId, EpochError> {
+        Ok(0)
+    }
 }
 
 impl FinalExecutionStatus {
@@ -43,6 +43,8 @@ struct MockClockPerState {
     utc_call_count: u64,
     /// Number of times `Clock::instant()` method was called since we started mocking.
     instant_call_count: u64,
+    /// Time returned once the lists of timestamps run out, see `MockClockGuard::set_time`.
+    virtual_time: Option<(Instant, DateTime<Utc>)>,
 }
 
 /// Stores the mocking state.
@@ -90,6 +92,20 @@ impl MockClockGuard {
         });
     }
 
+    /// Sets the current time, which is returned by `Clock::instant()` and `Clock::utc()` once
+    /// the timestamps added with `Self::add_instant()` and `Self::add_utc()` run out.
+    /// Without it the clock panics when it runs out of samples.
+    pub fn set_time(&self, instant: Instant, utc: DateTime<chrono::Utc>) {
+        MockClockPerThread::with(|clock| match &mut clock.mock {
+            Some(clock) => {
+                clock.virtual_time = Some((instant, utc));
+            }
+            None => {
+                panic!("Use MockClockGuard in your test");
+            }
+        });
+    }
+
     /// Returns number of calls  to `Self::utc` since `Self::mock()` was called.
     pub fn utc_call_count(&self) -> u64 {
         MockClockPerThread::with(|clock| match &mut clock.mock {
@@ -149,7 +165,7 @@ impl Clock {
             Some(clock) => {
                 clock.instant_call_count += 1;
                 let x = clock.instant_list.pop_front();
-                match x {
+                match x.or_else(|| clock.virtual_time.map(|(instant, _)| instant)) {
                     Some(t) => t,
                     None => {
                         panic!("Mock clock run out of samples");
@@ -167,7 +183,7 @@ impl Clock {
             Some(clock) => {
                 clock.utc_call_count += 1;
                 let x = clock.utc_list.pop_front();
-                match x {
+                match x.or_else(|| clock.virtual_time.map(|(_, utc)| utc)) {
                     Some(t) => t,
                     None => {
                         panic!("Mock clock run out of samples");
@@ -274,6 +290,24 @@ mod tests {
         assert_eq!(mock_clock_guard.instant_call_count(), 0);
     }
 
+    #[test]
+    fn test_clock_set_time() {
+        let mock_clock_guard = MockClockGuard::default();
+
+        let instant_now = Instant::now();
+        let utc_now = Utc::now();
+        mock_clock_guard.set_time(instant_now, utc_now);
+        mock_clock_guard.add_instant(instant_now.add(Duration::from_secs(1)));
+        assert_eq!(Clock::instant(), instant_now.add(Duration::from_secs(1)));
+        assert_eq!(Clock::instant(), instant_now);
+        assert_eq!(Clock::instant(), instant_now);
+        assert_eq!(Clock::utc(), utc_now);
+
+        mock_clock_guard.set_time(instant_now.add(Duration::from_secs(2)), utc_now);
+        assert_eq!(Clock::instant(), instant_now.add(Duration::from_secs(2)));
+        assert_eq!(mock_clock_guard.instant_call_count(), 4);
+    }
+
     #[test]
     fn test_threading() {
         thread::spawn(|| {
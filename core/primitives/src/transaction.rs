@@ -1,6 +1,7 @@
 use std::borrow::Borrow;
 use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::io;
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
@@ -12,6 +13,7 @@ use crate::errors::TxExecutionError;
 use crate::hash::{hash, CryptoHash};
 use crate::logging;
 use crate::merkle::MerklePath;
+use crate::receipt::RefundReceipt;
 use crate::serialize::{base64_format, u128_dec_format_compatible};
 use crate::types::{AccountId, Balance, Gas, Nonce};
 use near_primitives_core::profile::ProfileData;
@@ -19,7 +21,7 @@ use near_primitives_core::profile::ProfileData;
 pub type LogEntry = String;
 
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
-#[derive(BorshSerialize, BorshDeserialize, PartialEq, Eq, Debug, Clone)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct Transaction {
     /// An account on which behalf transaction is signed
     pub signer_id: AccountId,
@@ -35,6 +37,51 @@ pub struct Transaction {
     pub block_hash: CryptoHash,
     /// A list of actions to be applied
     pub actions: Vec<Action>,
+    /// Fee burnt on top of the transaction cost to prioritize it in the transaction pool and
+    /// its receipts in the delayed receipts queue.  Only accepted since
+    /// `ProtocolFeature::PriorityFee`.
+    pub priority_fee: Option<Balance>,
+}
+
+/// Tag which precedes the serialization of transactions with a priority fee, followed by the fee
+/// and the fields of the transaction.  Transactions without one keep their original
+/// serialization, which starts with the length of `signer_id` and never with this tag since
+/// account ids are at least two characters long.
+const PRIORITY_FEE_TRANSACTION_TAG: u8 = 1;
+
+impl BorshSerialize for Transaction {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        if let Some(priority_fee) = self.priority_fee {
+            PRIORITY_FEE_TRANSACTION_TAG.serialize(writer)?;
+            priority_fee.serialize(writer)?;
+        }
+        self.signer_id.serialize(writer)?;
+        self.public_key.serialize(writer)?;
+        self.nonce.serialize(writer)?;
+        self.receiver_id.serialize(writer)?;
+        self.block_hash.serialize(writer)?;
+        self.actions.serialize(writer)
+    }
+}
+
+impl BorshDeserialize for Transaction {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        let priority_fee = if buf.first() == Some(&PRIORITY_FEE_TRANSACTION_TAG) {
+            *buf = &buf[1..];
+            Some(Balance::deserialize(buf)?)
+        } else {
+            None
+        };
+        Ok(Transaction {
+            signer_id: AccountId::deserialize(buf)?,
+            public_key: PublicKey::deserialize(buf)?,
+            nonce: Nonce::deserialize(buf)?,
+            receiver_id: AccountId::deserialize(buf)?,
+            block_hash: CryptoHash::deserialize(buf)?,
+            actions: Vec::<Action>::deserialize(buf)?,
+            priority_fee,
+        })
+    }
 }
 
 impl Transaction {
@@ -60,6 +107,7 @@ pub enum Action {
     AddKey(AddKeyAction),
     DeleteKey(DeleteKeyAction),
     DeleteAccount(DeleteAccountAction),
+    NonrefundableStorageTransfer(NonrefundableStorageTransferAction),
     #[cfg(feature = "protocol_feature_chunk_only_producers")]
     StakeChunkOnly(StakeAction),
 }
@@ -75,6 +123,7 @@ impl Action {
         match self {
             Action::FunctionCall(a) => a.deposit,
             Action::Transfer(a) => a.deposit,
+            Action::NonrefundableStorageTransfer(a) => a.deposit,
             _ => 0,
         }
     }
@@ -155,6 +204,21 @@ impl From<TransferAction> for Action {
     }
 }
 
+/// Transfers the deposit to the nonrefundable balance of an existing account, which pays for its
+/// storage but can't be withdrawn.
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct NonrefundableStorageTransferAction {
+    #[serde(with = "u128_dec_format_compatible")]
+    pub deposit: Balance,
+}
+
+impl From<NonrefundableStorageTransferAction> for Action {
+    fn from(action: NonrefundableStorageTransferAction) -> Self {
+        Self::NonrefundableStorageTransfer(action)
+    }
+}
+
 /// An action which stakes singer_id tokens and setup's validator public key
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
@@ -374,6 +438,9 @@ pub enum ExecutionMetadata {
 
     // V2: With ProfileData
     V2(ProfileData),
+
+    // V3: With ProfileData and the refund receipts generated by the execution
+    V3 { profile: ProfileData, refunds: Vec<RefundReceipt> },
 }
 
 impl Default for ExecutionMetadata {
@@ -441,6 +508,33 @@ impl ExecutionOutcomeWithIdAndProof {
     }
 }
 
+/// Execution outcome with path from it to the outcome root, stored by id and block hash.
+#[derive(PartialEq, Clone, Default, Debug, BorshSerialize, BorshDeserialize, Eq)]
+pub struct ExecutionOutcomeWithProof {
+    pub proof: MerklePath,
+    pub outcome: ExecutionOutcome,
+}
+
+impl ExecutionOutcomeWithProof {
+    pub fn into_outcome_with_id_and_proof(
+        self,
+        id: CryptoHash,
+        block_hash: CryptoHash,
+    ) -> ExecutionOutcomeWithIdAndProof {
+        ExecutionOutcomeWithIdAndProof {
+            proof: self.proof,
+            block_hash,
+            outcome_with_id: ExecutionOutcomeWithId { id, outcome: self.outcome },
+        }
+    }
+}
+
+impl From<ExecutionOutcomeWithIdAndProof> for ExecutionOutcomeWithProof {
+    fn from(outcome: ExecutionOutcomeWithIdAndProof) -> Self {
+        Self { proof: outcome.proof, outcome: outcome.outcome_with_id.outcome }
+    }
+}
+
 pub fn verify_transaction_signature(
     transaction: &SignedTransaction,
     public_keys: &[PublicKey],
@@ -471,6 +565,7 @@ mod tests {
             receiver_id: "test".parse().unwrap(),
             block_hash: Default::default(),
             actions: vec![],
+            priority_fee: None,
         }
         .sign(&signer);
         let wrong_public_key = PublicKey::from_seed(KeyType::ED25519, "wrong");
@@ -516,6 +611,7 @@ mod tests {
                             receiver_id: "zzz".parse().unwrap(),
                             method_names: vec!["www".to_string()],
                         }),
+                        valid_until_block_height: None,
                     },
                 }),
                 Action::DeleteKey(DeleteKeyAction { public_key }),
@@ -523,6 +619,7 @@ mod tests {
                     beneficiary_id: "123".parse().unwrap(),
                 }),
             ],
+            priority_fee: None,
         };
         let signed_tx = SignedTransaction::new(Signature::empty(KeyType::ED25519), transaction);
         let new_signed_tx =
@@ -534,6 +631,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_serialize_transaction_with_priority_fee() {
+        let signer = InMemorySigner::from_seed("test".parse().unwrap(), KeyType::ED25519, "test");
+        let mut transaction = Transaction {
+            signer_id: "test".parse().unwrap(),
+            public_key: signer.public_key(),
+            nonce: 1,
+            receiver_id: "test".parse().unwrap(),
+            block_hash: Default::default(),
+            actions: vec![Action::Transfer(TransferAction { deposit: 123 })],
+            priority_fee: None,
+        };
+        let bytes = transaction.try_to_vec().unwrap();
+        transaction.priority_fee = Some(1_000);
+        let priority_bytes = transaction.try_to_vec().unwrap();
+
+        assert_eq!(priority_bytes[0], PRIORITY_FEE_TRANSACTION_TAG);
+        assert_eq!(priority_bytes[17..], bytes[..]);
+        assert_eq!(Transaction::try_from_slice(&priority_bytes).unwrap(), transaction);
+        transaction.priority_fee = None;
+        assert_eq!(Transaction::try_from_slice(&bytes).unwrap(), transaction);
+    }
+
     #[test]
     fn test_outcome_to_hashes() {
         let outcome = ExecutionOutcome {
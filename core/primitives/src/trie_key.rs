@@ -1,5 +1,5 @@
 use crate::hash::CryptoHash;
-use crate::types::AccountId;
+use crate::types::{AccountId, Balance, BlockHeight};
 use borsh::{BorshDeserialize, BorshSerialize};
 use near_crypto::PublicKey;
 use std::mem::size_of;
@@ -35,6 +35,12 @@ pub(crate) mod col {
     pub const DELAYED_RECEIPT: &[u8] = &[8];
     /// This column id is used when storing Key-Value data from a contract on an `account_id`.
     pub const CONTRACT_DATA: &[u8] = &[9];
+    /// This column id is used to mark a `data_id` as generated by a given `account_id`, so that
+    /// the account can create a receipt which depends on it.
+    pub const REGISTERED_DATA_ID: &[u8] = &[10];
+    /// This column id is used when storing delayed receipts with a priority fee, ordered by
+    /// decreasing priority fee and then by the time they were delayed.
+    pub const PRIORITY_DELAYED_RECEIPT: &[u8] = &[11];
     /// All columns
     pub const NON_DELAYED_RECEIPT_COLUMNS: &[(&[u8], &str)] = &[
         (ACCOUNT, "Account"),
@@ -45,9 +51,26 @@ pub(crate) mod col {
         (PENDING_DATA_COUNT, "PendingDataCount"),
         (POSTPONED_RECEIPT, "PostponedReceipt"),
         (CONTRACT_DATA, "ContractData"),
+        (REGISTERED_DATA_ID, "RegisteredDataId"),
     ];
 }
 
+/// Columns which start the raw trie keys, with the names of their `TrieKey` variants.
+pub const TRIE_KEY_COLUMNS: &[(&[u8], &str)] = &[
+    (col::ACCOUNT, "Account"),
+    (col::CONTRACT_CODE, "ContractCode"),
+    (col::ACCESS_KEY, "AccessKey"),
+    (col::RECEIVED_DATA, "ReceivedData"),
+    (col::POSTPONED_RECEIPT_ID, "PostponedReceiptId"),
+    (col::PENDING_DATA_COUNT, "PendingDataCount"),
+    (col::POSTPONED_RECEIPT, "PostponedReceipt"),
+    (col::DELAYED_RECEIPT_INDICES, "DelayedReceiptIndices"),
+    (col::DELAYED_RECEIPT, "DelayedReceipt"),
+    (col::CONTRACT_DATA, "ContractData"),
+    (col::REGISTERED_DATA_ID, "RegisteredDataId"),
+    (col::PRIORITY_DELAYED_RECEIPT, "PriorityDelayedReceipt"),
+];
+
 /// Describes the key of a specific key-value record in a state trie.
 #[derive(Debug, Clone, PartialEq, Eq, BorshDeserialize, BorshSerialize)]
 pub enum TrieKey {
@@ -83,6 +106,14 @@ pub enum TrieKey {
     /// Used to store a key-value record `Vec<u8>` within a contract deployed on a given `AccountId`
     /// and a given key.
     ContractData { account_id: AccountId, key: Vec<u8> },
+    /// Used to mark a `data_id` as generated by a given `AccountId` and not yet used as an input
+    /// data of a receipt created by the account. The value is empty.
+    RegisteredDataId { account_id: AccountId, data_id: CryptoHash },
+    /// Used to store a delayed receipt `primitives::receipt::Receipt` with a non-zero priority
+    /// fee, delayed at a given block height as the `index`-th receipt of the chunk.  The raw keys
+    /// sort the receipts in the order they are processed: by decreasing priority fee, then
+    /// first in, first out.
+    PriorityDelayedReceipt { priority_fee: Balance, block_height: BlockHeight, index: u64 },
 }
 
 impl TrieKey {
@@ -125,6 +156,18 @@ impl TrieKey {
                     + ACCOUNT_DATA_SEPARATOR.len()
                     + key.len()
             }
+            TrieKey::RegisteredDataId { account_id, data_id } => {
+                col::REGISTERED_DATA_ID.len()
+                    + account_id.len()
+                    + ACCOUNT_DATA_SEPARATOR.len()
+                    + data_id.as_ref().len()
+            }
+            TrieKey::PriorityDelayedReceipt { .. } => {
+                col::PRIORITY_DELAYED_RECEIPT.len()
+                    + size_of::<Balance>()
+                    + size_of::<BlockHeight>()
+                    + size_of::<u64>()
+            }
         }
     }
 
@@ -178,15 +221,34 @@ impl TrieKey {
                 res.extend(&index.to_le_bytes());
             }
             TrieKey::ContractData { account_id, key } => {
-                res.extend(col::CONTRACT_DATA);
+                TrieKey::append_contract_data_key(&mut res, account_id, key);
+            }
+            TrieKey::RegisteredDataId { account_id, data_id } => {
+                res.extend(col::REGISTERED_DATA_ID);
                 res.extend(account_id.as_ref().as_bytes());
                 res.extend(ACCOUNT_DATA_SEPARATOR);
-                res.extend(key);
+                res.extend(data_id.as_ref());
+            }
+            TrieKey::PriorityDelayedReceipt { priority_fee, block_height, index } => {
+                // Big endian, with the fee inverted, for the highest fee to sort first.
+                res.extend(col::PRIORITY_DELAYED_RECEIPT);
+                res.extend(&(Balance::MAX - priority_fee).to_be_bytes());
+                res.extend(&block_height.to_be_bytes());
+                res.extend(&index.to_be_bytes());
             }
         };
         debug_assert_eq!(res.len(), expected_len);
         res
     }
+
+    /// Appends the raw key of `TrieKey::ContractData { account_id, key }` to `res`.  Unlike
+    /// `to_vec` this doesn't need the `TrieKey`, so the account id isn't cloned.
+    pub fn append_contract_data_key(res: &mut Vec<u8>, account_id: &AccountId, key: &[u8]) {
+        res.extend(col::CONTRACT_DATA);
+        res.extend(account_id.as_ref().as_bytes());
+        res.extend(ACCOUNT_DATA_SEPARATOR);
+        res.extend(key);
+    }
 }
 
 // TODO: Remove once we switch to non-raw keys everywhere.
@@ -323,6 +385,14 @@ pub mod trie_key_parsers {
         Ok(TrieKey::AccessKey { account_id, public_key })
     }
 
+    pub fn parse_trie_key_contract_data_from_raw_key(
+        raw_key: &[u8],
+    ) -> Result<TrieKey, std::io::Error> {
+        let account_id = parse_account_id_from_contract_data_key(raw_key)?;
+        let key = parse_data_key_from_contract_data_key(raw_key, &account_id)?.to_vec();
+        Ok(TrieKey::ContractData { account_id, key })
+    }
+
     #[allow(unused)]
     pub fn parse_account_id_from_raw_key(
         raw_key: &[u8],
@@ -391,6 +461,36 @@ pub mod trie_key_parsers {
         })
     }
 
+    pub fn parse_account_id_from_registered_data_id_key(
+        raw_key: &[u8],
+    ) -> Result<AccountId, std::io::Error> {
+        parse_account_id_from_trie_key_with_separator(
+            col::REGISTERED_DATA_ID,
+            raw_key,
+            "RegisteredDataId",
+        )
+    }
+
+    pub fn parse_data_id_from_registered_data_id_key(
+        raw_key: &[u8],
+        account_id: &AccountId,
+    ) -> Result<CryptoHash, std::io::Error> {
+        let prefix_len =
+            col::REGISTERED_DATA_ID.len() + account_id.len() + ACCOUNT_DATA_SEPARATOR.len();
+        if raw_key.len() < prefix_len {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "raw key is too short for TrieKey::RegisteredDataId",
+            ));
+        }
+        CryptoHash::try_from(&raw_key[prefix_len..]).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Can't parse CryptoHash for TrieKey::RegisteredDataId",
+            )
+        })
+    }
+
     pub fn get_raw_prefix_for_access_keys(account_id: &AccountId) -> Vec<u8> {
         let mut res = Vec::with_capacity(col::ACCESS_KEY.len() * 2 + account_id.len());
         res.extend(col::ACCESS_KEY);
@@ -412,6 +512,39 @@ pub mod trie_key_parsers {
         res.extend(prefix);
         res
     }
+
+    pub fn parse_trie_key_priority_delayed_receipt_from_raw_key(
+        raw_key: &[u8],
+    ) -> Result<TrieKey, std::io::Error> {
+        let key = parse_account_id_prefix(col::PRIORITY_DELAYED_RECEIPT, raw_key)?;
+        if key.len() != size_of::<Balance>() + size_of::<BlockHeight>() + size_of::<u64>() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "raw key has a wrong length for TrieKey::PriorityDelayedReceipt",
+            ));
+        }
+        let (inverted_fee, key) = key.split_at(size_of::<Balance>());
+        let (block_height, index) = key.split_at(size_of::<BlockHeight>());
+        Ok(TrieKey::PriorityDelayedReceipt {
+            priority_fee: Balance::MAX - Balance::from_be_bytes(inverted_fee.try_into().unwrap()),
+            block_height: BlockHeight::from_be_bytes(block_height.try_into().unwrap()),
+            index: u64::from_be_bytes(index.try_into().unwrap()),
+        })
+    }
+
+    pub fn get_raw_prefix_for_priority_delayed_receipts() -> Vec<u8> {
+        col::PRIORITY_DELAYED_RECEIPT.to_vec()
+    }
+
+    pub fn get_raw_prefix_for_registered_data_ids(account_id: &AccountId) -> Vec<u8> {
+        let mut res = Vec::with_capacity(
+            col::REGISTERED_DATA_ID.len() + account_id.len() + ACCOUNT_DATA_SEPARATOR.len(),
+        );
+        res.extend(col::REGISTERED_DATA_ID);
+        res.extend(account_id.as_ref().as_bytes());
+        res.extend(ACCOUNT_DATA_SEPARATOR);
+        res
+    }
 }
 
 #[cfg(test)]
@@ -503,6 +636,13 @@ mod tests {
                 TrieKey::ContractData { account_id: account_id.clone(), key: data_key.to_vec() };
             let raw_key = key.to_vec();
             assert_eq!(raw_key.len(), key.len());
+            let mut appended = b"prefix".to_vec();
+            TrieKey::append_contract_data_key(&mut appended, &account_id, data_key);
+            assert_eq!(&appended[b"prefix".len()..], &raw_key[..]);
+            assert_eq!(
+                trie_key_parsers::parse_trie_key_contract_data_from_raw_key(&raw_key).unwrap(),
+                key
+            );
             assert_eq!(
                 trie_key_parsers::parse_account_id_from_contract_data_key(&raw_key).unwrap(),
                 account_id
@@ -609,6 +749,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_key_for_registered_data_id_consistency() {
+        let data_id = CryptoHash::hash_bytes(b"data");
+        for account_id in OK_ACCOUNT_IDS.iter().map(|x| x.parse::<AccountId>().unwrap()) {
+            let key = TrieKey::RegisteredDataId { account_id: account_id.clone(), data_id };
+            let raw_key = key.to_vec();
+            assert_eq!(raw_key.len(), key.len());
+            assert!(raw_key.starts_with(
+                &trie_key_parsers::get_raw_prefix_for_registered_data_ids(&account_id)
+            ));
+            assert_eq!(
+                trie_key_parsers::parse_account_id_from_raw_key(&raw_key).unwrap().unwrap(),
+                account_id
+            );
+            assert_eq!(
+                trie_key_parsers::parse_data_id_from_registered_data_id_key(&raw_key, &account_id)
+                    .unwrap(),
+                data_id,
+            );
+        }
+    }
+
     #[test]
     fn test_key_for_delayed_receipts_consistency() {
         let key = TrieKey::DelayedReceiptIndices;
@@ -618,4 +780,37 @@ mod tests {
         let raw_key = key.to_vec();
         assert!(trie_key_parsers::parse_account_id_from_raw_key(&raw_key).unwrap().is_none());
     }
+
+    #[test]
+    fn test_key_for_priority_delayed_receipt_consistency() {
+        let keys = [
+            TrieKey::PriorityDelayedReceipt {
+                priority_fee: Balance::MAX,
+                block_height: 7,
+                index: 0,
+            },
+            TrieKey::PriorityDelayedReceipt { priority_fee: 300, block_height: 5, index: 1 },
+            TrieKey::PriorityDelayedReceipt { priority_fee: 256, block_height: 2, index: 0 },
+            TrieKey::PriorityDelayedReceipt { priority_fee: 256, block_height: 2, index: 1 },
+            TrieKey::PriorityDelayedReceipt { priority_fee: 256, block_height: 256, index: 0 },
+            TrieKey::PriorityDelayedReceipt { priority_fee: 1, block_height: 1, index: 0 },
+        ];
+        for key in &keys {
+            let raw_key = key.to_vec();
+            assert_eq!(raw_key.len(), key.len());
+            assert_eq!(
+                trie_key_parsers::parse_trie_key_priority_delayed_receipt_from_raw_key(&raw_key)
+                    .unwrap(),
+                *key
+            );
+            assert!(raw_key
+                .starts_with(&trie_key_parsers::get_raw_prefix_for_priority_delayed_receipts()));
+            assert!(trie_key_parsers::parse_account_id_from_raw_key(&raw_key).unwrap().is_none());
+        }
+        // The raw keys sort the receipts in the order they are processed.
+        let raw_keys: Vec<_> = keys.iter().map(TrieKey::to_vec).collect();
+        let mut sorted_raw_keys = raw_keys.clone();
+        sorted_raw_keys.sort();
+        assert_eq!(sorted_raw_keys, raw_keys);
+    }
 }
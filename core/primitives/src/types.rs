@@ -344,6 +344,8 @@ impl StateChanges {
                 TrieKey::PostponedReceipt { .. } => {}
                 TrieKey::DelayedReceiptIndices => {}
                 TrieKey::DelayedReceipt { .. } => {}
+                TrieKey::RegisteredDataId { .. } => {}
+                TrieKey::PriorityDelayedReceipt { .. } => {}
             }
         }
 
@@ -976,6 +978,26 @@ pub trait EpochInfoProvider {
     ) -> Result<Balance, EpochError>;
 
     fn minimum_stake(&self, prev_block_hash: &CryptoHash) -> Result<Balance, EpochError>;
+
+    /// Get the id of the shard the account belongs to in the given epoch.
+    fn account_id_to_shard_id(
+        &self,
+        account_id: &AccountId,
+        epoch_id: &EpochId,
+    ) -> Result<ShardId, EpochError>;
+
+    /// Get the stake `delegator_account_id` has delegated to the staking pool
+    /// `pool_account_id` in the given epoch.  Delegations are kept in the state of the pool
+    /// contract, so this is `None` whenever the provider can't or doesn't look into it, or the
+    /// pool doesn't answer with a balance.
+    fn delegated_stake(
+        &self,
+        _epoch_id: &EpochId,
+        _pool_account_id: &AccountId,
+        _delegator_account_id: &AccountId,
+    ) -> Option<Balance> {
+        None
+    }
 }
 
 /// Mode of the trie cache.
@@ -8,6 +8,7 @@ use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 use serde;
 
+use crate::checked_feature;
 use crate::hash::{hash, CryptoHash};
 use crate::receipt::Receipt;
 use crate::transaction::SignedTransaction;
@@ -202,6 +203,26 @@ pub fn get_block_shard_id_rev(
     Ok((block_hash, shard_id))
 }
 
+pub fn get_outcome_id_block_hash(outcome_id: &CryptoHash, block_hash: &CryptoHash) -> Vec<u8> {
+    let mut res = Vec::with_capacity(64);
+    res.extend_from_slice(outcome_id.as_ref());
+    res.extend_from_slice(block_hash.as_ref());
+    res
+}
+
+pub fn get_outcome_id_block_hash_rev(
+    key: &[u8],
+) -> Result<(CryptoHash, CryptoHash), Box<dyn std::error::Error>> {
+    if key.len() != 64 {
+        return Err(
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid key length").into()
+        );
+    }
+    let outcome_id = CryptoHash::try_from(&key[..32])?;
+    let block_hash = CryptoHash::try_from(&key[32..])?;
+    Ok((outcome_id, block_hash))
+}
+
 /// Creates a new Receipt ID from a given signed transaction and a block hash.
 /// This method is backward compatible, so it takes the current protocol version.
 pub fn create_receipt_id_from_transaction(
@@ -270,18 +291,32 @@ pub fn create_data_id(
     )
 }
 
-/// Creates a unique random seed to be provided to `VMContext` from a give `action_hash` and
-/// a given `random_seed`.
+/// Creates a unique random seed to be provided to `VMContext` from a give `action_hash` or
+/// `receipt_id` and a given `random_seed`.
 /// This method is backward compatible, so it takes the current protocol version.
 pub fn create_random_seed(
     protocol_version: ProtocolVersion,
     action_hash: CryptoHash,
+    receipt_id: CryptoHash,
     random_seed: CryptoHash,
 ) -> Vec<u8> {
     let res = if protocol_version < CORRECT_RANDOM_VALUE_PROTOCOL_VERSION {
         action_hash
     } else if protocol_version < CREATE_HASH_PROTOCOL_VERSION {
         random_seed
+    } else if checked_feature!(
+        "protocol_feature_receipt_random_seed",
+        ReceiptRandomSeed,
+        protocol_version
+    ) {
+        // Generates random seed from the VRF output of the block and the receipt id.  Unlike
+        // the action hash, the receipt id doesn't depend on the index of the action, so all
+        // function calls of a receipt get the same seed.
+        let mut bytes: Vec<u8> =
+            Vec::with_capacity(size_of::<CryptoHash>() + size_of::<CryptoHash>());
+        bytes.extend_from_slice(receipt_id.as_ref());
+        bytes.extend_from_slice(random_seed.as_ref());
+        hash(&bytes)
     } else {
         // Generates random seed from random_seed and action_hash.
         // Since every action hash is unique, the seed will be unique per receipt and even
@@ -546,4 +581,46 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn test_create_random_seed() {
+        let action_hash = hash(b"action");
+        let other_action_hash = hash(b"other action");
+        let receipt_id = hash(b"receipt");
+        let other_receipt_id = hash(b"other receipt");
+        let random_seed = hash(b"vrf output");
+        let version = CREATE_HASH_PROTOCOL_VERSION;
+        // Before the receipt id is used, the seed depends on the action hash and not on it.
+        assert_eq!(
+            create_random_seed(version, action_hash, receipt_id, random_seed),
+            hash(&[action_hash.as_ref(), random_seed.as_ref()].concat()).as_ref()
+        );
+        assert_eq!(
+            create_random_seed(version, action_hash, receipt_id, random_seed),
+            create_random_seed(version, action_hash, other_receipt_id, random_seed)
+        );
+        assert_ne!(
+            create_random_seed(version, action_hash, receipt_id, random_seed),
+            create_random_seed(version, other_action_hash, receipt_id, random_seed)
+        );
+
+        #[cfg(feature = "protocol_feature_receipt_random_seed")]
+        {
+            let version = crate::version::ProtocolFeature::ReceiptRandomSeed.protocol_version();
+            let seed = create_random_seed(version, action_hash, receipt_id, random_seed);
+            assert_eq!(seed, hash(&[receipt_id.as_ref(), random_seed.as_ref()].concat()).as_ref());
+            assert_eq!(
+                seed,
+                create_random_seed(version, other_action_hash, receipt_id, random_seed)
+            );
+            assert_ne!(
+                seed,
+                create_random_seed(version, action_hash, other_receipt_id, random_seed)
+            );
+            assert_ne!(
+                seed,
+                create_random_seed(version, action_hash, receipt_id, hash(b"other vrf output"))
+            );
+        }
+    }
 }
@@ -17,7 +17,7 @@ pub struct Version {
 pub type DbVersion = u32;
 
 /// Current version of the database.
-pub const DB_VERSION: DbVersion = 31;
+pub const DB_VERSION: DbVersion = 38;
 
 use crate::upgrade_schedule::{get_protocol_version_internal, ProtocolUpgradeVotingSchedule};
 /// Protocol version type.
@@ -152,6 +152,110 @@ pub enum ProtocolFeature {
     FixStakingThreshold,
     #[cfg(feature = "protocol_feature_function_call_weight")]
     FunctionCallWeight,
+    /// Limit the stake which can be kicked out in one epoch, so that a network-wide outage
+    /// doesn't collapse the stake of the validator set.
+    #[cfg(feature = "protocol_feature_max_kickout_stake")]
+    MaxKickoutStake,
+    /// Limit the depth of wasm calls and charge stack height for the locals of a function, see
+    /// `StackLimiterVersion::V2`.
+    #[cfg(feature = "protocol_feature_stack_limiter_v2")]
+    StackLimiterV2,
+    /// Allow contracts to generate data ids, to create receipts which depend on them and to send
+    /// the data to these receipts, see `promise_create_with_data_dependency` and
+    /// `promise_resume_data`.
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    PromiseDataDependency,
+    #[cfg(feature = "protocol_feature_storage_value_len")]
+    StorageValueLen,
+    #[cfg(feature = "protocol_feature_storage_write_no_evict")]
+    StorageWriteNoEvict,
+    #[cfg(feature = "protocol_feature_storage_compare_and_swap")]
+    StorageCompareAndSwap,
+    #[cfg(feature = "protocol_feature_storage_key_count")]
+    StorageKeyCount,
+    /// Host functions `gas_price` and `block_gas_price`.
+    #[cfg(feature = "protocol_feature_gas_price_host_functions")]
+    GasPriceHostFunctions,
+    /// Host functions `epoch_id` and `shard_id`.
+    #[cfg(feature = "protocol_feature_epoch_and_shard_id")]
+    EpochAndShardId,
+    /// The random seed of a function call is derived from the receipt id instead of the action
+    /// hash, see `create_random_seed`.
+    #[cfg(feature = "protocol_feature_receipt_random_seed")]
+    ReceiptRandomSeed,
+    /// Repeated `validator_stake` and `validator_total_stake` lookups within a receipt are
+    /// answered from a cache and don't pay `validator_stake_base` or `validator_total_stake_base`.
+    #[cfg(feature = "protocol_feature_validator_info_cache")]
+    ValidatorInfoCache,
+    /// Host function `locked_balance_of`.
+    #[cfg(feature = "protocol_feature_locked_balance_of")]
+    LockedBalanceOf,
+    /// Host functions `storage_byte_cost` and `min_account_balance`.
+    #[cfg(feature = "protocol_feature_storage_config_host_functions")]
+    StorageConfigHostFunctions,
+    /// Data receipts carry the gas burnt by the receipt which produced them, readable with the
+    /// host function `promise_result_gas_burnt`.
+    #[cfg(feature = "protocol_feature_promise_result_gas_burnt")]
+    PromiseResultGasBurnt,
+    /// Host functions `input_len` and `input_read`.
+    #[cfg(feature = "protocol_feature_input_read")]
+    InputRead,
+    /// Modules declaring or importing a memory larger than `max_memory_pages` are rejected instead
+    /// of having their memory replaced, see `strict_memory_limits` in `VMLimitConfig`.
+    #[cfg(feature = "protocol_feature_memory_limit_check")]
+    MemoryLimitCheck,
+    /// Accept BLS12-381 keys as transaction signer keys and in `AddKey` actions.
+    #[cfg(feature = "protocol_feature_bls12381_keys")]
+    BLS12381Keys,
+    /// Access keys with a `valid_until_block_height`, after which transactions signed with them
+    /// are rejected.
+    #[cfg(feature = "protocol_feature_access_key_expiration")]
+    AccessKeyExpiration,
+    /// `NonrefundableStorageTransfer` actions, which fund the storage of an account with a
+    /// balance that can't be withdrawn.
+    #[cfg(feature = "protocol_feature_nonrefundable_storage_transfer")]
+    NonrefundableStorageTransfer,
+    /// Receipts created by contracts and transactions may not be larger than `max_receipt_size`.
+    #[cfg(feature = "protocol_feature_receipt_size_limit")]
+    ReceiptSizeLimit,
+    /// Returned values above `large_data_cost.threshold` pay a steeper per-byte fee for the
+    /// data receipts they are sent in.
+    #[cfg(feature = "protocol_feature_large_data_receipt_cost")]
+    LargeDataReceiptCost,
+    /// Transactions with a priority fee, and ordering of the delayed receipts queue by the
+    /// priority fee of the transactions receipts originate from.
+    #[cfg(feature = "protocol_feature_priority_fee")]
+    PriorityFee,
+    /// Protocol treasury reward split between the accounts of `protocol_treasury_split` in the
+    /// runtime config.
+    #[cfg(feature = "protocol_feature_treasury_split")]
+    ProtocolTreasurySplit,
+    /// Host functions `remaining_gas` and `action_fee_estimate`.
+    #[cfg(feature = "protocol_feature_gas_budget_host_functions")]
+    GasBudgetHostFunctions,
+    /// Compute costs of `touching_trie_node` above its gas cost, limiting the receipts processed
+    /// in a chunk along with the gas limit.
+    #[cfg(feature = "protocol_feature_compute_costs")]
+    ComputeCosts,
+    /// Depth of action receipts, limited by `max_receipt_depth`, and host function
+    /// `receipt_depth`.
+    #[cfg(feature = "protocol_feature_receipt_depth")]
+    ReceiptDepth,
+    /// Receipts whose reads add more than `per_receipt_storage_proof_size_limit` to the storage
+    /// proof of the chunk fail.  Host function `recorded_storage_size`.
+    #[cfg(feature = "protocol_feature_per_receipt_storage_proof_limit")]
+    PerReceiptStorageProofLimit,
+    /// Record the refund receipts generated by a receipt in the metadata of its execution outcome,
+    /// see `ExecutionMetadata::V3`.
+    #[cfg(feature = "protocol_feature_refund_receipt_origin")]
+    RefundReceiptOrigin,
+    /// Host function `promise_action_min_gas`.
+    #[cfg(feature = "protocol_feature_promise_action_min_gas")]
+    PromiseActionMinGas,
+    /// Receipts loading trie nodes and values of more than `per_receipt_trie_nodes_memory_limit`
+    /// bytes fail.
+    #[cfg(feature = "protocol_feature_per_receipt_trie_nodes_memory_limit")]
+    PerReceiptTrieNodesMemoryLimit,
 }
 
 /// Both, outgoing and incoming tcp connections to peers, will be rejected if `peer's`
@@ -168,7 +272,7 @@ const STABLE_PROTOCOL_VERSION: ProtocolVersion = 53;
 pub const PROTOCOL_VERSION: ProtocolVersion = STABLE_PROTOCOL_VERSION;
 /// Current latest nightly version of the protocol.
 #[cfg(feature = "nightly_protocol")]
-pub const PROTOCOL_VERSION: ProtocolVersion = 127;
+pub const PROTOCOL_VERSION: ProtocolVersion = 158;
 
 /// The points in time after which the voting for the protocol version should start.
 #[allow(dead_code)]
@@ -230,6 +334,66 @@ impl ProtocolFeature {
             ProtocolFeature::FixStakingThreshold => 126,
             #[cfg(feature = "protocol_feature_function_call_weight")]
             ProtocolFeature::FunctionCallWeight => 127,
+            #[cfg(feature = "protocol_feature_max_kickout_stake")]
+            ProtocolFeature::MaxKickoutStake => 128,
+            #[cfg(feature = "protocol_feature_stack_limiter_v2")]
+            ProtocolFeature::StackLimiterV2 => 129,
+            #[cfg(feature = "protocol_feature_promise_data_dependency")]
+            ProtocolFeature::PromiseDataDependency => 130,
+            #[cfg(feature = "protocol_feature_storage_value_len")]
+            ProtocolFeature::StorageValueLen => 131,
+            #[cfg(feature = "protocol_feature_storage_write_no_evict")]
+            ProtocolFeature::StorageWriteNoEvict => 132,
+            #[cfg(feature = "protocol_feature_storage_compare_and_swap")]
+            ProtocolFeature::StorageCompareAndSwap => 133,
+            #[cfg(feature = "protocol_feature_storage_key_count")]
+            ProtocolFeature::StorageKeyCount => 134,
+            #[cfg(feature = "protocol_feature_gas_price_host_functions")]
+            ProtocolFeature::GasPriceHostFunctions => 135,
+            #[cfg(feature = "protocol_feature_epoch_and_shard_id")]
+            ProtocolFeature::EpochAndShardId => 136,
+            #[cfg(feature = "protocol_feature_receipt_random_seed")]
+            ProtocolFeature::ReceiptRandomSeed => 137,
+            #[cfg(feature = "protocol_feature_validator_info_cache")]
+            ProtocolFeature::ValidatorInfoCache => 138,
+            #[cfg(feature = "protocol_feature_locked_balance_of")]
+            ProtocolFeature::LockedBalanceOf => 139,
+            #[cfg(feature = "protocol_feature_storage_config_host_functions")]
+            ProtocolFeature::StorageConfigHostFunctions => 140,
+            #[cfg(feature = "protocol_feature_promise_result_gas_burnt")]
+            ProtocolFeature::PromiseResultGasBurnt => 141,
+            #[cfg(feature = "protocol_feature_input_read")]
+            ProtocolFeature::InputRead => 142,
+            #[cfg(feature = "protocol_feature_memory_limit_check")]
+            ProtocolFeature::MemoryLimitCheck => 143,
+            #[cfg(feature = "protocol_feature_bls12381_keys")]
+            ProtocolFeature::BLS12381Keys => 144,
+            #[cfg(feature = "protocol_feature_access_key_expiration")]
+            ProtocolFeature::AccessKeyExpiration => 145,
+            #[cfg(feature = "protocol_feature_nonrefundable_storage_transfer")]
+            ProtocolFeature::NonrefundableStorageTransfer => 146,
+            #[cfg(feature = "protocol_feature_receipt_size_limit")]
+            ProtocolFeature::ReceiptSizeLimit => 147,
+            #[cfg(feature = "protocol_feature_large_data_receipt_cost")]
+            ProtocolFeature::LargeDataReceiptCost => 148,
+            #[cfg(feature = "protocol_feature_priority_fee")]
+            ProtocolFeature::PriorityFee => 150,
+            #[cfg(feature = "protocol_feature_treasury_split")]
+            ProtocolFeature::ProtocolTreasurySplit => 151,
+            #[cfg(feature = "protocol_feature_gas_budget_host_functions")]
+            ProtocolFeature::GasBudgetHostFunctions => 152,
+            #[cfg(feature = "protocol_feature_compute_costs")]
+            ProtocolFeature::ComputeCosts => 153,
+            #[cfg(feature = "protocol_feature_receipt_depth")]
+            ProtocolFeature::ReceiptDepth => 154,
+            #[cfg(feature = "protocol_feature_per_receipt_storage_proof_limit")]
+            ProtocolFeature::PerReceiptStorageProofLimit => 155,
+            #[cfg(feature = "protocol_feature_refund_receipt_origin")]
+            ProtocolFeature::RefundReceiptOrigin => 156,
+            #[cfg(feature = "protocol_feature_promise_action_min_gas")]
+            ProtocolFeature::PromiseActionMinGas => 157,
+            #[cfg(feature = "protocol_feature_per_receipt_trie_nodes_memory_limit")]
+            ProtocolFeature::PerReceiptTrieNodesMemoryLimit => 158,
         }
     }
 }
@@ -25,25 +25,28 @@ use crate::hash::{hash, CryptoHash};
 use crate::logging;
 use crate::merkle::MerklePath;
 use crate::profile::Cost;
-use crate::receipt::{ActionReceipt, DataReceipt, DataReceiver, Receipt, ReceiptEnum};
+use crate::receipt::{
+    ActionReceipt, DataReceipt, DataReceiver, Receipt, ReceiptEnum, ReceiptQueueChanges, RefundKind,
+};
 use crate::serialize::{
     base64_format, from_base64, option_base64_format, option_u128_dec_format, to_base64,
     u128_dec_format, u64_dec_format,
 };
 use crate::sharding::{
-    ChunkHash, ShardChunk, ShardChunkHeader, ShardChunkHeaderInner, ShardChunkHeaderInnerV2,
-    ShardChunkHeaderV3,
+    ChunkHash, ChunkSummary, ShardChunk, ShardChunkHeader, ShardChunkHeaderInner,
+    ShardChunkHeaderInnerV2, ShardChunkHeaderV3,
 };
 use crate::transaction::{
     Action, AddKeyAction, CreateAccountAction, DeleteAccountAction, DeleteKeyAction,
     DeployContractAction, ExecutionMetadata, ExecutionOutcome, ExecutionOutcomeWithIdAndProof,
-    ExecutionStatus, FunctionCallAction, SignedTransaction, StakeAction, TransferAction,
+    ExecutionStatus, FunctionCallAction, NonrefundableStorageTransferAction, SignedTransaction,
+    StakeAction, TransferAction,
 };
 use crate::types::{
     AccountId, AccountWithPublicKey, Balance, BlockHeight, CompiledContractCache, EpochHeight,
-    EpochId, FunctionArgs, Gas, Nonce, NumBlocks, ShardId, StateChangeCause, StateChangeKind,
-    StateChangeValue, StateChangeWithCause, StateChangesRequest, StateRoot, StorageUsage, StoreKey,
-    StoreValue, ValidatorKickoutReason,
+    EpochId, FunctionArgs, Gas, Nonce, NumBlocks, ReceiptDepth, ShardId, StateChangeCause,
+    StateChangeKind, StateChangeValue, StateChangeWithCause, StateChangesRequest, StateRoot,
+    StorageUsage, StoreKey, StoreValue, ValidatorKickoutReason,
 };
 use crate::version::{ProtocolVersion, Version};
 use validator_stake_view::ValidatorStakeView;
@@ -56,6 +59,11 @@ pub struct AccountView {
     pub amount: Balance,
     #[serde(with = "u128_dec_format")]
     pub locked: Balance,
+    /// Balance which only pays for storage, see `Account::nonrefundable`.  Only part of the JSON,
+    /// and only if it isn't zero, the borsh layout of the view is kept.
+    #[borsh_skip]
+    #[serde(default, with = "u128_dec_format", skip_serializing_if = "is_zero")]
+    pub nonrefundable: Balance,
     pub code_hash: CryptoHash,
     pub storage_usage: StorageUsage,
     /// TODO(2271): deprecated.
@@ -63,6 +71,10 @@ pub struct AccountView {
     pub storage_paid_at: BlockHeight,
 }
 
+fn is_zero(balance: &Balance) -> bool {
+    *balance == 0
+}
+
 /// A view of the contract code.
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
@@ -85,6 +97,8 @@ pub struct ViewApplyState {
     pub epoch_id: EpochId,
     /// Current epoch height
     pub epoch_height: EpochHeight,
+    /// Id of the shard the view call is made on.
+    pub shard_id: ShardId,
     /// The current block timestamp (number of non-leap-nanoseconds since January 1, 1970 0:00:00 UTC).
     pub block_timestamp: u64,
     /// Current Protocol version when we apply the state transition
@@ -98,6 +112,7 @@ impl From<&Account> for AccountView {
         AccountView {
             amount: account.amount(),
             locked: account.locked(),
+            nonrefundable: account.nonrefundable(),
             code_hash: account.code_hash(),
             storage_usage: account.storage_usage(),
             storage_paid_at: 0,
@@ -113,7 +128,12 @@ impl From<Account> for AccountView {
 
 impl From<&AccountView> for Account {
     fn from(view: &AccountView) -> Self {
-        Account::new(view.amount, view.locked, view.code_hash, view.storage_usage)
+        let mut account =
+            Account::new(view.amount, view.locked, view.code_hash, view.storage_usage);
+        if view.nonrefundable > 0 {
+            account.set_nonrefundable(view.nonrefundable);
+        }
+        account
     }
 }
 
@@ -182,17 +202,29 @@ impl From<AccessKeyPermissionView> for AccessKeyPermission {
 pub struct AccessKeyView {
     pub nonce: Nonce,
     pub permission: AccessKeyPermissionView,
+    /// Only part of the JSON, the borsh layout of the view is kept.
+    #[borsh_skip]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_until_block_height: Option<BlockHeight>,
 }
 
 impl From<AccessKey> for AccessKeyView {
     fn from(access_key: AccessKey) -> Self {
-        Self { nonce: access_key.nonce, permission: access_key.permission.into() }
+        Self {
+            nonce: access_key.nonce,
+            permission: access_key.permission.into(),
+            valid_until_block_height: access_key.valid_until_block_height,
+        }
     }
 }
 
 impl From<AccessKeyView> for AccessKey {
     fn from(view: AccessKeyView) -> Self {
-        Self { nonce: view.nonce, permission: view.permission.into() }
+        Self {
+            nonce: view.nonce,
+            permission: view.permission.into(),
+            valid_until_block_height: view.valid_until_block_height,
+        }
     }
 }
 
@@ -222,6 +254,18 @@ pub struct ViewStateResult {
 pub struct CallResult {
     pub result: Vec<u8>,
     pub logs: Vec<String>,
+    /// Gas burnt by the view execution of the function, `None` if the result was received from
+    /// another node.  Not serialized, the JSON-RPC returns it next to the result.
+    #[borsh_skip]
+    #[serde(skip)]
+    pub gas_burnt: Option<Gas>,
+    /// `gas_burnt` plus the execution fees of the function call action and its receipt, to be
+    /// attached to a transaction calling the function with the same arguments.  The estimate is
+    /// only accurate if the state the function reads doesn't change until the transaction is
+    /// executed.  Not serialized like `gas_burnt`.
+    #[borsh_skip]
+    #[serde(skip)]
+    pub recommended_attached_gas: Option<Gas>,
 }
 
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
@@ -781,6 +825,36 @@ impl BlockView {
     }
 }
 
+/// Block returned by the `block` RPC with `header_only`, which only summarizes its chunks.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlockHeaderOnlyView {
+    pub author: AccountId,
+    pub header: BlockHeaderView,
+    pub chunks: Vec<ChunkSummaryView>,
+}
+
+/// The counts are `None` if the node doesn't have the chunk.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ChunkSummaryView {
+    pub shard_id: ShardId,
+    pub chunk_hash: CryptoHash,
+    pub tx_count: Option<u64>,
+    pub receipt_count: Option<u64>,
+    pub gas_used: Gas,
+}
+
+impl ChunkSummaryView {
+    pub fn new(chunk_header: &ShardChunkHeader, summary: Option<ChunkSummary>) -> Self {
+        ChunkSummaryView {
+            shard_id: chunk_header.shard_id(),
+            chunk_hash: chunk_header.chunk_hash().0,
+            tx_count: summary.map(|summary| summary.tx_count),
+            receipt_count: summary.map(|summary| summary.receipt_count),
+            gas_used: chunk_header.gas_used(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ChunkView {
     pub author: AccountId,
@@ -841,6 +915,10 @@ pub enum ActionView {
     DeleteAccount {
         beneficiary_id: AccountId,
     },
+    NonrefundableStorageTransfer {
+        #[serde(with = "u128_dec_format")]
+        deposit: Balance,
+    },
     #[cfg(feature = "protocol_feature_chunk_only_producers")]
     StakeChunkOnly {
         #[serde(with = "u128_dec_format")]
@@ -874,6 +952,9 @@ impl From<Action> for ActionView {
             Action::DeleteAccount(action) => {
                 ActionView::DeleteAccount { beneficiary_id: action.beneficiary_id }
             }
+            Action::NonrefundableStorageTransfer(action) => {
+                ActionView::NonrefundableStorageTransfer { deposit: action.deposit }
+            }
             #[cfg(feature = "protocol_feature_chunk_only_producers")]
             Action::StakeChunkOnly(action) => {
                 ActionView::StakeChunkOnly { stake: action.stake, public_key: action.public_key }
@@ -912,6 +993,9 @@ impl TryFrom<ActionView> for Action {
             ActionView::DeleteAccount { beneficiary_id } => {
                 Action::DeleteAccount(DeleteAccountAction { beneficiary_id })
             }
+            ActionView::NonrefundableStorageTransfer { deposit } => {
+                Action::NonrefundableStorageTransfer(NonrefundableStorageTransferAction { deposit })
+            }
             #[cfg(feature = "protocol_feature_chunk_only_producers")]
             ActionView::StakeChunkOnly { stake, public_key } => {
                 Action::StakeChunkOnly(StakeAction { stake, public_key })
@@ -930,6 +1014,10 @@ pub struct SignedTransactionView {
     pub actions: Vec<ActionView>,
     pub signature: Signature,
     pub hash: CryptoHash,
+    /// Only part of the JSON, the borsh layout of the view is kept.
+    #[borsh_skip]
+    #[serde(default, with = "option_u128_dec_format", skip_serializing_if = "Option::is_none")]
+    pub priority_fee: Option<Balance>,
 }
 
 impl From<SignedTransaction> for SignedTransactionView {
@@ -948,6 +1036,7 @@ impl From<SignedTransaction> for SignedTransactionView {
                 .collect(),
             signature: signed_tx.signature,
             hash,
+            priority_fee: signed_tx.transaction.priority_fee,
         }
     }
 }
@@ -1062,7 +1151,8 @@ impl From<ExecutionMetadata> for ExecutionMetadataView {
     fn from(metadata: ExecutionMetadata) -> Self {
         let gas_profile = match metadata {
             ExecutionMetadata::V1 => None,
-            ExecutionMetadata::V2(profile_data) => {
+            ExecutionMetadata::V2(profile_data)
+            | ExecutionMetadata::V3 { profile: profile_data, .. } => {
                 let mut costs: Vec<_> = Cost::ALL
                     .iter()
                     .filter(|&cost| profile_data[*cost] > 0)
@@ -1125,6 +1215,46 @@ pub struct ExecutionOutcomeView {
     /// Execution metadata, versioned
     #[serde(default)]
     pub metadata: ExecutionMetadataView,
+    /// Set for the outcomes of refund receipts in `EXPERIMENTAL_tx_status` responses.  Only
+    /// part of the JSON, the borsh layout of the view is kept.
+    #[borsh_skip]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refund: Option<RefundView>,
+}
+
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RefundView {
+    pub is_refund: bool,
+    /// The receipt whose execution generated the refund.
+    pub origin: RefundOriginView,
+    pub kind: RefundKindView,
+}
+
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RefundOriginView {
+    /// Refunds generated by nodes which didn't record their origin yet.
+    Unknown,
+    ReceiptId(CryptoHash),
+}
+
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RefundKindView {
+    Deposit,
+    Gas,
+}
+
+impl From<RefundKind> for RefundKindView {
+    fn from(kind: RefundKind) -> Self {
+        match kind {
+            RefundKind::Deposit => Self::Deposit,
+            RefundKind::Gas => Self::Gas,
+        }
+    }
 }
 
 impl From<ExecutionOutcome> for ExecutionOutcomeView {
@@ -1137,6 +1267,7 @@ impl From<ExecutionOutcome> for ExecutionOutcomeView {
             executor_id: outcome.executor_id,
             status: outcome.status.into(),
             metadata: outcome.metadata.into(),
+            refund: None,
         }
     }
 }
@@ -1346,11 +1477,21 @@ pub enum ReceiptEnumView {
         output_data_receivers: Vec<DataReceiverView>,
         input_data_ids: Vec<CryptoHash>,
         actions: Vec<ActionView>,
+        /// Only part of the JSON, the borsh layout of the view is kept.
+        #[borsh_skip]
+        #[serde(default, with = "option_u128_dec_format", skip_serializing_if = "Option::is_none")]
+        priority_fee: Option<Balance>,
+        /// Only part of the JSON, the borsh layout of the view is kept.
+        #[borsh_skip]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        depth: Option<ReceiptDepth>,
     },
     Data {
         data_id: CryptoHash,
         #[serde(with = "option_base64_format")]
         data: Option<Vec<u8>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        gas_burnt: Option<Gas>,
     },
 }
 
@@ -1379,10 +1520,14 @@ impl From<Receipt> for ReceiptView {
                         .map(Into::into)
                         .collect(),
                     actions: action_receipt.actions.into_iter().map(Into::into).collect(),
+                    priority_fee: action_receipt.priority_fee,
+                    depth: action_receipt.depth,
+                },
+                ReceiptEnum::Data(data_receipt) => ReceiptEnumView::Data {
+                    data_id: data_receipt.data_id,
+                    data: data_receipt.data,
+                    gas_burnt: data_receipt.gas_burnt,
                 },
-                ReceiptEnum::Data(data_receipt) => {
-                    ReceiptEnumView::Data { data_id: data_receipt.data_id, data: data_receipt.data }
-                }
             },
         }
     }
@@ -1404,6 +1549,8 @@ impl TryFrom<ReceiptView> for Receipt {
                     output_data_receivers,
                     input_data_ids,
                     actions,
+                    priority_fee,
+                    depth,
                 } => ReceiptEnum::Action(ActionReceipt {
                     signer_id,
                     signer_public_key,
@@ -1420,15 +1567,46 @@ impl TryFrom<ReceiptView> for Receipt {
                         .into_iter()
                         .map(TryInto::try_into)
                         .collect::<Result<Vec<_>, _>>()?,
+                    priority_fee,
+                    depth,
                 }),
-                ReceiptEnumView::Data { data_id, data } => {
-                    ReceiptEnum::Data(DataReceipt { data_id, data })
+                ReceiptEnumView::Data { data_id, data, gas_burnt } => {
+                    ReceiptEnum::Data(DataReceipt { data_id, data, gas_burnt })
                 }
             },
         })
     }
 }
 
+/// Receipts added to and removed from the delayed receipts queue and the set of postponed
+/// receipts of a shard while applying a chunk.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ReceiptQueueChangesView {
+    pub delayed_receipts_added: Vec<ReceiptView>,
+    pub delayed_receipts_removed: Vec<CryptoHash>,
+    pub postponed_receipts_added: Vec<ReceiptView>,
+    pub postponed_receipts_removed: Vec<CryptoHash>,
+}
+
+impl From<ReceiptQueueChanges> for ReceiptQueueChangesView {
+    fn from(changes: ReceiptQueueChanges) -> Self {
+        ReceiptQueueChangesView {
+            delayed_receipts_added: changes
+                .delayed_receipts_added
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            delayed_receipts_removed: changes.delayed_receipts_removed,
+            postponed_receipts_added: changes
+                .postponed_receipts_added
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            postponed_receipts_removed: changes.postponed_receipts_removed,
+        }
+    }
+}
+
 /// Information about this epoch validators and next epoch validators
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -1451,6 +1629,149 @@ pub struct EpochValidatorInfo {
     pub epoch_height: EpochHeight,
 }
 
+/// Full information about an epoch, as stored by the epoch manager.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EpochInfoView {
+    pub epoch_id: EpochId,
+    pub epoch_height: EpochHeight,
+    pub protocol_version: ProtocolVersion,
+    /// Height of the first block of the epoch, unless the epoch hasn't started yet.
+    pub epoch_start_height: Option<BlockHeight>,
+    #[serde(with = "u128_dec_format")]
+    pub seat_price: Balance,
+    pub validators: Vec<EpochValidatorView>,
+    pub fishermen: Vec<ValidatorStakeView>,
+    /// Validators kicked out at the end of the previous epoch, unless the validators of the next
+    /// epoch are not known yet.
+    pub prev_epoch_kickout: Option<Vec<ValidatorKickoutView>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EpochValidatorView {
+    pub account_id: AccountId,
+    pub public_key: PublicKey,
+    #[serde(with = "u128_dec_format")]
+    pub stake: Balance,
+    pub is_block_producer: bool,
+    /// Shards the validator produces chunks for.
+    pub chunk_producer_shards: Vec<ShardId>,
+}
+
+/// Block and chunk production of a validator at each of its assignments, per epoch.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct ValidatorProductionStatsView {
+    pub account_id: AccountId,
+    /// Current epoch (up to the latest block) first, then the previous epoch.
+    pub epochs: Vec<EpochProductionStatsView>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EpochProductionStatsView {
+    pub epoch_id: EpochId,
+    pub epoch_height: EpochHeight,
+    /// Heights at which the validator was the block producer, in increasing order.
+    pub blocks: Vec<BlockProductionView>,
+    /// Chunks the validator was expected to produce, in increasing order of height.
+    pub chunks: Vec<ChunkProductionView>,
+}
+
+impl EpochProductionStatsView {
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty() && self.chunks.is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct BlockProductionView {
+    pub height: BlockHeight,
+    pub produced: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct ChunkProductionView {
+    /// Height of the block which was expected to include the chunk.
+    pub height: BlockHeight,
+    pub shard_id: ShardId,
+    pub produced: bool,
+}
+
+/// Locked stake of an account and when the part of it being unstaked is returned.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct UnstakeScheduleView {
+    pub account_id: AccountId,
+    /// Height of the current epoch.
+    pub epoch_height: EpochHeight,
+    #[serde(with = "u128_dec_format")]
+    pub locked: Balance,
+    /// Part of `locked` which is returned over the next epochs.
+    #[serde(with = "u128_dec_format")]
+    pub unstaking: Balance,
+    /// Returns of the unstaking amount, in increasing order of epoch height.
+    pub unlocks: Vec<StakeUnlockView>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct StakeUnlockView {
+    #[serde(with = "u128_dec_format")]
+    pub amount: Balance,
+    /// Height of the epoch at the start of which the amount is returned.
+    pub epoch_height: EpochHeight,
+    pub estimated_height: BlockHeight,
+    /// Estimated timestamp (in nanoseconds) of the block at `estimated_height`. Not set until
+    /// the block production rate can be measured.
+    pub estimated_timestamp: Option<u64>,
+    /// Set when the start of that epoch is not fixed yet.
+    pub range: Option<StakeUnlockRangeView>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct StakeUnlockRangeView {
+    pub min_height: BlockHeight,
+    pub max_height: BlockHeight,
+    pub min_timestamp: Option<u64>,
+    pub max_timestamp: Option<u64>,
+}
+
+/// Votes of the block producers of the current epoch for the next protocol version.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct ProtocolUpgradeStatusView {
+    /// Height of the current epoch.
+    pub epoch_height: EpochHeight,
+    pub protocol_version: ProtocolVersion,
+    /// Protocol version of the next epoch, which is already decided.
+    pub next_epoch_protocol_version: ProtocolVersion,
+    /// Version with the most stake voting for it, unless that's the current version.
+    pub voted_protocol_version: Option<ProtocolVersion>,
+    #[serde(with = "u128_dec_format")]
+    pub voted_stake: Balance,
+    /// Stake the votes for a version have to exceed at the end of the epoch for an upgrade.
+    #[serde(with = "u128_dec_format")]
+    pub threshold_stake: Balance,
+    /// Latest vote of each block producer which produced a block in the epoch so far.
+    pub votes: Vec<ProtocolVersionVoteView>,
+    pub scheduled_upgrade: Option<ScheduledProtocolUpgradeView>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct ProtocolVersionVoteView {
+    pub account_id: AccountId,
+    #[serde(with = "u128_dec_format")]
+    pub stake: Balance,
+    pub protocol_version: ProtocolVersion,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct ScheduledProtocolUpgradeView {
+    pub protocol_version: ProtocolVersion,
+    /// Height of the first epoch running `protocol_version`.
+    pub epoch_height: EpochHeight,
+    /// Estimated height of the first block of that epoch.
+    pub estimated_height: BlockHeight,
+    /// Whether the upgrade is decided, otherwise it happens if the votes hold until the end
+    /// of the current epoch.
+    pub is_final: bool,
+}
+
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct ValidatorKickoutView {
@@ -1519,6 +1840,48 @@ pub struct GasPriceView {
     pub gas_price: Balance,
 }
 
+/// Wall-clock time and gas the receipts executed by an account took during an epoch.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct ContractExecutionView {
+    /// `None` for the accounts which aren't among the top ones, aggregated together.
+    pub account_id: Option<AccountId>,
+    pub execution_time_us: u64,
+    pub gas_burnt: Gas,
+    pub receipts: u64,
+}
+
+/// Contracts which took the most time to execute on this node during the current epoch.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct TopContractsView {
+    /// `None` until a chunk of the epoch was applied.
+    pub epoch_id: Option<EpochId>,
+    /// From the longest to the shortest execution time.
+    pub contracts: Vec<ContractExecutionView>,
+    pub other: ContractExecutionView,
+}
+
+/// Where the node sent a transaction it received for inclusion.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct TransactionRoutingView {
+    /// Shard of the signer account in the current shard layout.
+    pub shard_id: ShardId,
+    /// Heights of the chunks the transaction was forwarded for, empty if it wasn't forwarded.
+    pub forwarded_for_heights: Vec<BlockHeight>,
+    /// Chunk producers the transaction was forwarded to.
+    pub chunk_producers: Vec<AccountId>,
+}
+
+/// A transaction recently forwarded by the node to chunk producers.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct ForwardedTransactionView {
+    pub transaction_hash: CryptoHash,
+    pub signer_id: AccountId,
+    #[serde(flatten)]
+    pub routing: TransactionRoutingView,
+    /// Whether the node later saw the transaction in a chunk of a shard it tracks.
+    pub included: bool,
+}
+
 /// It is a [serializable view] of [`StateChangesRequest`].
 ///
 /// [serializable view]: ./index.html
@@ -1730,3 +2093,138 @@ impl From<StateChangeWithCause> for StateChangeWithCauseView {
 }
 
 pub type StateChangesView = Vec<StateChangeWithCauseView>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_call_result_borsh_skips_gas() {
+        let call_result = CallResult {
+            result: b"result".to_vec(),
+            logs: vec!["log".to_string()],
+            gas_burnt: Some(1),
+            recommended_attached_gas: Some(2),
+        };
+        let bytes = call_result.try_to_vec().unwrap();
+        assert_eq!(bytes, (b"result".to_vec(), vec!["log".to_string()]).try_to_vec().unwrap());
+        let decoded = CallResult::try_from_slice(&bytes).unwrap();
+        assert_eq!(
+            decoded,
+            CallResult { gas_burnt: None, recommended_attached_gas: None, ..call_result }
+        );
+    }
+
+    #[test]
+    fn test_access_key_view_serialization() {
+        let view = AccessKeyView {
+            nonce: 1,
+            permission: AccessKeyPermissionView::FullAccess,
+            valid_until_block_height: Some(100),
+        };
+        let bytes = view.try_to_vec().unwrap();
+        assert_eq!(bytes, (1u64, AccessKeyPermissionView::FullAccess).try_to_vec().unwrap());
+        assert_eq!(
+            AccessKeyView::try_from_slice(&bytes).unwrap(),
+            AccessKeyView { valid_until_block_height: None, ..view.clone() }
+        );
+
+        assert_eq!(
+            serde_json::to_value(&view).unwrap(),
+            serde_json::json!({"nonce": 1, "permission": "FullAccess", "valid_until_block_height": 100})
+        );
+        let view = AccessKeyView { valid_until_block_height: None, ..view };
+        assert_eq!(
+            serde_json::to_value(&view).unwrap(),
+            serde_json::json!({"nonce": 1, "permission": "FullAccess"})
+        );
+    }
+
+    #[test]
+    fn test_account_view_serialization() {
+        let view = AccountView {
+            amount: 10,
+            locked: 1,
+            nonrefundable: 5,
+            code_hash: CryptoHash::default(),
+            storage_usage: 100,
+            storage_paid_at: 0,
+        };
+        let bytes = view.try_to_vec().unwrap();
+        assert_eq!(
+            bytes,
+            (10u128, 1u128, CryptoHash::default(), 100u64, 0u64).try_to_vec().unwrap()
+        );
+        assert_eq!(
+            AccountView::try_from_slice(&bytes).unwrap(),
+            AccountView { nonrefundable: 0, ..view.clone() }
+        );
+
+        assert_eq!(serde_json::to_value(&view).unwrap()["nonrefundable"], "5");
+        let view = AccountView { nonrefundable: 0, ..view };
+        let json = serde_json::to_value(&view).unwrap();
+        assert!(json.get("nonrefundable").is_none(), "{}", json);
+        assert_eq!(serde_json::from_value::<AccountView>(json).unwrap(), view);
+    }
+
+    #[test]
+    fn test_priority_fee_view_serialization() {
+        let tx = SignedTransactionView {
+            signer_id: "alice.near".parse().unwrap(),
+            public_key: PublicKey::empty(near_crypto::KeyType::ED25519),
+            nonce: 1,
+            receiver_id: "bob.near".parse().unwrap(),
+            actions: vec![],
+            signature: Signature::empty(near_crypto::KeyType::ED25519),
+            hash: CryptoHash::default(),
+            priority_fee: Some(10),
+        };
+        let tx_without_fee = SignedTransactionView { priority_fee: None, ..tx.clone() };
+        let bytes = tx.try_to_vec().unwrap();
+        assert_eq!(bytes, tx_without_fee.try_to_vec().unwrap());
+        assert_eq!(SignedTransactionView::try_from_slice(&bytes).unwrap(), tx_without_fee);
+        assert_eq!(serde_json::to_value(&tx).unwrap()["priority_fee"], "10");
+        let json = serde_json::to_value(&tx_without_fee).unwrap();
+        assert!(json.get("priority_fee").is_none(), "{}", json);
+
+        let receipt = |priority_fee| ReceiptEnumView::Action {
+            signer_id: tx.signer_id.clone(),
+            signer_public_key: tx.public_key.clone(),
+            gas_price: 1,
+            output_data_receivers: vec![],
+            input_data_ids: vec![],
+            actions: vec![],
+            priority_fee,
+            depth: None,
+        };
+        let bytes = receipt(Some(10)).try_to_vec().unwrap();
+        assert_eq!(bytes, receipt(None).try_to_vec().unwrap());
+        assert_eq!(ReceiptEnumView::try_from_slice(&bytes).unwrap(), receipt(None));
+        assert_eq!(
+            serde_json::to_value(&receipt(Some(10))).unwrap()["Action"]["priority_fee"],
+            "10"
+        );
+    }
+
+    #[test]
+    fn test_receipt_depth_view_serialization() {
+        let receipt = |depth| ReceiptEnumView::Action {
+            signer_id: "alice.near".parse().unwrap(),
+            signer_public_key: PublicKey::empty(near_crypto::KeyType::ED25519),
+            gas_price: 1,
+            output_data_receivers: vec![],
+            input_data_ids: vec![],
+            actions: vec![],
+            priority_fee: None,
+            depth,
+        };
+        let bytes = receipt(Some(3)).try_to_vec().unwrap();
+        assert_eq!(bytes, receipt(None).try_to_vec().unwrap());
+        assert_eq!(ReceiptEnumView::try_from_slice(&bytes).unwrap(), receipt(None));
+        let json = serde_json::to_value(&receipt(Some(3))).unwrap();
+        assert_eq!(json["Action"]["depth"], 3);
+        assert_eq!(serde_json::from_value::<ReceiptEnumView>(json).unwrap(), receipt(Some(3)));
+        let json = serde_json::to_value(&receipt(None)).unwrap();
+        assert!(json["Action"].get("depth").is_none(), "{}", json);
+    }
+}
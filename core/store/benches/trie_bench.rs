@@ -4,9 +4,10 @@ extern crate bencher;
 use bencher::Bencher;
 use rand::random;
 
+use near_primitives::hash::CryptoHash;
 use near_primitives::shard_layout::ShardUId;
 use near_store::test_utils::create_tries;
-use near_store::Trie;
+use near_store::{ShardTries, Trie};
 
 fn rand_bytes() -> Vec<u8> {
     (0..10).map(|_| random::<u8>()).collect()
@@ -34,6 +35,44 @@ fn trie_lookup(bench: &mut Bencher) {
     });
 }
 
+/// Trie of depth 20: key `i` has zero nibbles up to nibble `i`, which is 1, so that every
+/// nibble of the all-zero key branches off.
+fn deep_trie() -> (ShardTries, CryptoHash, Vec<Vec<u8>>) {
+    let tries = create_tries();
+    let trie = tries.get_trie_for_shard(ShardUId::single_shard());
+    let mut keys = vec![vec![0u8; 10]];
+    for i in 0..20 {
+        let mut key = vec![0u8; 10];
+        key[i / 2] = if i % 2 == 0 { 0x10 } else { 0x01 };
+        keys.push(key);
+    }
+    let changes = keys.iter().map(|key| (key.clone(), Some(rand_bytes())));
+    let trie_changes = trie.update(&Trie::empty_root(), changes).unwrap();
+    let (state_update, root) = tries.apply_all(&trie_changes, ShardUId::single_shard()).unwrap();
+    state_update.commit().expect("Failed to commit");
+    (tries, root, keys)
+}
+
+fn trie_lookup_deep(bench: &mut Bencher) {
+    let (tries, root, keys) = deep_trie();
+    let trie = tries.get_trie_for_shard(ShardUId::single_shard());
+    // The all-zero key and the keys branching off at the last nibbles are the deepest.
+    let deepest: Vec<_> = keys.iter().filter(|key| key[..9].iter().all(|b| *b == 0)).collect();
+
+    bench.iter(|| {
+        for key in deepest.iter() {
+            trie.get(&root, key).unwrap();
+        }
+    });
+}
+
+fn trie_iter_deep(bench: &mut Bencher) {
+    let (tries, root, _) = deep_trie();
+    let trie = tries.get_trie_for_shard(ShardUId::single_shard());
+
+    bench.iter(|| trie.iter(&root).unwrap().count());
+}
+
 fn trie_update(bench: &mut Bencher) {
     let tries = create_tries();
     let trie = tries.get_trie_for_shard(ShardUId::single_shard());
@@ -49,5 +88,42 @@ fn trie_update(bench: &mut Bencher) {
     });
 }
 
-benchmark_group!(benches, trie_lookup, trie_update);
+/// Writes of a chunk with 10k writes, applied in batches of 100 as trie changes which end up in
+/// the same store update, and coalescing of their refcount updates.
+fn trie_coalesce_refcounts(bench: &mut Bencher) {
+    let tries = create_tries();
+    let shard_uid = ShardUId::single_shard();
+    let trie = tries.get_trie_for_shard(shard_uid);
+    let mut root = Trie::empty_root();
+    let mut all_trie_changes = vec![];
+    for _ in 0..100 {
+        let changes = (0..100).map(|_| (rand_bytes(), Some(rand_bytes())));
+        let trie_changes = trie.update(&root, changes).unwrap();
+        let (store_update, new_root) = tries.apply_all(&trie_changes, shard_uid).unwrap();
+        store_update.commit().expect("Failed to commit");
+        root = new_root;
+        all_trie_changes.push(trie_changes);
+    }
+    let store_update = || {
+        let mut store_update = tries.get_store().store_update();
+        for trie_changes in all_trie_changes.iter() {
+            store_update.merge(tries.apply_all(trie_changes, shard_uid).unwrap().0);
+        }
+        store_update
+    };
+    bench.iter(|| {
+        let mut store_update = store_update();
+        store_update.coalesce_refcount_updates();
+        store_update
+    });
+}
+
+benchmark_group!(
+    benches,
+    trie_lookup,
+    trie_lookup_deep,
+    trie_iter_deep,
+    trie_update,
+    trie_coalesce_refcounts
+);
 benchmark_main!(benches);
@@ -0,0 +1,199 @@
+//! One-shot migration of the history of an archival node into a cold database.
+//!
+//! The historical columns, the ones garbage collection deletes on non-archival nodes, are copied
+//! as they are, refcounts included, from the hot database into the cold one.  Every batch is
+//! committed with the [`ColdMigrationProgress`] last, so that an interrupted migration resumes
+//! after the last key it recorded; copying a key again is harmless, so each key is a unit of its
+//! own when the batch is split by `StoreUpdate::commit_chunked`.  Once the copy is done, every
+//! `verify_every`-th key of the cold database is read back from both databases, whichever run
+//! copied it, and the migration is only complete if they all match.
+use std::io;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use strum::IntoEnumIterator;
+use tracing::info;
+
+use near_primitives::types::BlockHeight;
+
+use crate::db::{DBCol, RocksDB, COLD_MIGRATION_PROGRESS_KEY, SHOULD_COL_GC};
+use crate::Store;
+
+/// Progress of a migration, stored in the cold database.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct ColdMigrationProgress {
+    /// Final head height of the hot database when the migration started.  Once the migration is
+    /// done, the cold database has all the historical data of the blocks below it.
+    pub migrated_height: BlockHeight,
+    /// Column being copied, `None` once all of them are.
+    pub column: Option<DBCol>,
+    /// Last key of `column` which was copied.
+    pub last_key: Option<Vec<u8>>,
+    pub copied_keys: u64,
+    pub copied_bytes: u64,
+}
+
+/// Columns which are copied into the cold database.
+pub fn cold_columns() -> impl Iterator<Item = DBCol> {
+    DBCol::iter().filter(|col| SHOULD_COL_GC[*col as usize])
+}
+
+pub struct ColdMigration<'a> {
+    hot: &'a RocksDB,
+    cold: &'a Store,
+    /// Size of the keys and values copied by a batch.
+    batch_size: usize,
+    verify_every: u64,
+    progress: ColdMigrationProgress,
+}
+
+impl<'a> ColdMigration<'a> {
+    /// Resumes the migration recorded in `cold`, or starts a new one at `migrated_height`.
+    pub fn new(
+        hot: &'a RocksDB,
+        cold: &'a Store,
+        migrated_height: BlockHeight,
+        batch_size: usize,
+        verify_every: u64,
+    ) -> io::Result<Self> {
+        let progress = match cold.get_ser(DBCol::ColBlockMisc, COLD_MIGRATION_PROGRESS_KEY)? {
+            Some(progress) => progress,
+            None => ColdMigrationProgress {
+                migrated_height,
+                column: cold_columns().next(),
+                last_key: None,
+                copied_keys: 0,
+                copied_bytes: 0,
+            },
+        };
+        Ok(Self { hot, cold, batch_size, verify_every, progress })
+    }
+
+    pub fn progress(&self) -> &ColdMigrationProgress {
+        &self.progress
+    }
+
+    /// Copies the next batch of keys, returning whether there is anything left to copy.
+    pub fn copy_batch(&mut self) -> io::Result<bool> {
+        let column = match self.progress.column {
+            Some(column) => column,
+            None => return Ok(false),
+        };
+        let entries = match &self.progress.last_key {
+            Some(last_key) => self.hot.iter_raw_from(column, last_key),
+            None => self.hot.iter_without_rc_logic(column),
+        };
+        let mut store_update = self.cold.store_update();
+        let mut batch_size = 0;
+        let mut last_key = None;
+        let mut column_done = true;
+        for (key, value) in entries {
+            if self.progress.last_key.as_deref() == Some(&*key) {
+                continue;
+            }
+            if batch_size >= self.batch_size {
+                column_done = false;
+                break;
+            }
+            batch_size += key.len() + value.len();
+            self.progress.copied_keys += 1;
+            self.progress.copied_bytes += (key.len() + value.len()) as u64;
+            store_update.set(column, &key, &value);
+            store_update.finish_unit();
+            last_key = Some(key);
+        }
+        if column_done {
+            info!(target: "store", "Copied column {:?} into the cold database", column);
+            self.progress.column = cold_columns().skip_while(|col| *col != column).nth(1);
+            self.progress.last_key = None;
+        } else {
+            self.progress.last_key = last_key.map(Into::into);
+        }
+        store_update.set_ser(DBCol::ColBlockMisc, COLD_MIGRATION_PROGRESS_KEY, &self.progress)?;
+        store_update.commit_chunked()?;
+        Ok(self.progress.column.is_some())
+    }
+
+    /// Checks that every `verify_every`-th key of the cold database has the same value in the
+    /// hot one, returning the number of keys checked.
+    pub fn verify(&self) -> io::Result<usize> {
+        let mut keys = 0u64;
+        let mut checked = 0;
+        for column in cold_columns() {
+            for (key, cold) in self.cold.iter(column) {
+                keys += 1;
+                if keys % self.verify_every != 0 {
+                    continue;
+                }
+                let hot = self.hot.get(column, &key).map_err(Into::<io::Error>::into)?;
+                if hot.as_deref() != Some(&*cold) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{:?} differs in column {:?} of the cold database", key, column),
+                    ));
+                }
+                checked += 1;
+            }
+        }
+        Ok(checked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::db::{DBCol, Database, RocksDB};
+    use crate::Store;
+
+    use super::ColdMigration;
+
+    #[test]
+    fn test_resume_and_verify() {
+        let hot_dir = tempfile::Builder::new().prefix("hot").tempdir().unwrap();
+        let cold_dir = tempfile::Builder::new().prefix("cold").tempdir().unwrap();
+        let hot = RocksDB::new(hot_dir.path()).unwrap();
+        let mut transaction = hot.transaction();
+        for i in 0u8..10 {
+            transaction.put(DBCol::ColBlock, [i], [i; 10]);
+            transaction.update_refcount(
+                DBCol::ColState,
+                [i],
+                [&[i; 4][..], &1i64.to_le_bytes()].concat(),
+            );
+        }
+        transaction.put(DBCol::ColPeers, [0], [0]);
+        hot.write(transaction).unwrap();
+
+        // Every key is written in a batch of its own.
+        let cold = Store::new(Arc::new(RocksDB::new(cold_dir.path()).unwrap()))
+            .with_write_batch_soft_limit(1);
+        {
+            let mut migration = ColdMigration::new(&hot, &cold, 100, 20, 3).unwrap();
+            for _ in 0..3 {
+                assert!(migration.copy_batch().unwrap());
+            }
+        }
+        let mut migration = ColdMigration::new(&hot, &cold, 200, 20, 3).unwrap();
+        assert_eq!(migration.progress().migrated_height, 100);
+        assert_eq!(migration.progress().copied_keys, 6);
+        while migration.copy_batch().unwrap() {}
+        assert_eq!(migration.progress().column, None);
+        assert_eq!(migration.progress().copied_keys, 20);
+        // The keys copied by both runs are sampled.
+        assert_eq!(migration.verify().unwrap(), 6);
+
+        for i in 0u8..10 {
+            assert_eq!(cold.get(DBCol::ColBlock, &[i]).unwrap(), Some(vec![i; 10]));
+            assert_eq!(cold.get(DBCol::ColState, &[i]).unwrap(), Some(vec![i; 4]));
+        }
+        assert_eq!(cold.get(DBCol::ColPeers, &[0]).unwrap(), None);
+        assert!(!migration.copy_batch().unwrap());
+
+        // A key copied by the first run which differs is found by a resumed migration.
+        let mut store_update = cold.store_update();
+        store_update.set(DBCol::ColBlock, &[0], &[1]);
+        store_update.commit().unwrap();
+        let migration = ColdMigration::new(&hot, &cold, 200, 20, 1).unwrap();
+        assert!(migration.verify().is_err());
+    }
+}
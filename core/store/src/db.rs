@@ -1,5 +1,6 @@
 use crate::db::refcount::merge_refcounted_records;
 use borsh::{BorshDeserialize, BorshSerialize};
+use near_primitives::hash::CryptoHash;
 use near_primitives::version::DbVersion;
 use once_cell::sync::Lazy;
 use rocksdb::checkpoint::Checkpoint;
@@ -7,13 +8,14 @@ use rocksdb::{
     BlockBasedOptions, Cache, ColumnFamily, ColumnFamilyDescriptor, Direction, Env, IteratorMode,
     Options, ReadOptions, WriteBatch, DB,
 };
+use serde::{Deserialize, Serialize};
 use std::cmp;
 use std::collections::HashMap;
 use std::io;
 use std::path::Path;
 use std::sync::atomic::Ordering;
 use std::sync::{Condvar, Mutex, RwLock};
-use strum::{EnumCount, EnumIter};
+use strum::{EnumCount, EnumIter, IntoEnumIterator};
 use tracing::{debug, error, info, warn};
 
 pub(crate) mod refcount;
@@ -77,10 +79,8 @@ pub enum DBCol {
     /// - *Rows*: BlockChunk (block_hash, shard_uid)
     /// - *Content type*: [near_primitives::types::ChunkExtra]
     ColChunkExtra = 6,
-    /// Mapping from transaction outcome id (CryptoHash) to list of outcome ids with proofs.
-    /// - *Rows*: outcome id (CryptoHash)
-    /// - *Content type*: Vec of [near_primitives::transactions::ExecutionOutcomeWithIdAndProof]
-    ColTransactionResult = 7,
+    /// Deprecated, replaced by `ColTransactionResultForBlock`.
+    _ColTransactionResult = 7,
     /// Mapping from Block + Shard to list of outgoing receipts.
     /// - *Rows*: block + shard
     /// - *Content type*: Vec of [near_primitives::receipt::Receipt]
@@ -265,6 +265,33 @@ pub enum DBCol {
     /// - *Rows*: BlockShardId (BlockHash || ShardId) - 40 bytes
     /// - *Column type*: StateChangesForSplitStates
     ColStateChangesForSplitStates = 49,
+    /// Ids of the epochs, on all forks, indexed by epoch height.
+    /// - *Rows*: epoch height (u64)
+    /// - *Column type*: Vec<EpochId>
+    ColEpochHeightToEpochId = 50,
+    /// Number of transactions and receipts of a chunk.
+    /// - *Rows*: ChunkHash (CryptoHash)
+    /// - *Column type*: ChunkSummary
+    ColChunkSummaries = 51,
+    /// Receipts added to and removed from the delayed receipts queue and the postponed receipts
+    /// by a chunk.  Only saved when `ClientConfig::save_receipt_queue_changes` is set.
+    /// - *Rows*: BlockShardUId (BlockHash || ShardUId) - 40 bytes
+    /// - *Column type*: ReceiptQueueChanges
+    ColReceiptQueueChanges = 52,
+    /// Parts of the state of a shard downloaded by state sync so far.
+    /// - *Rows*: StateHeaderKey (ShardId || CryptoHash)
+    /// - *Column type*: StateSyncProgress
+    ColStateSyncProgress = 53,
+    /// Execution outcomes of transactions and receipts by the block they were executed in.  The
+    /// same receipt may be executed in several forks, each has its own row.  A row with the
+    /// default block hash and an empty value marks outcomes removed by outcomes pruning.
+    /// - *Rows*: OutcomeId (CryptoHash) || BlockHash (CryptoHash) - 64 bytes
+    /// - *Column type*: ExecutionOutcomeWithProof
+    ColTransactionResultForBlock = 54,
+    /// Parameters a chunk was applied with, such as the gas price and the random seed.
+    /// - *Rows*: BlockShardUId (BlockHash || ShardUId) - 40 bytes
+    /// - *Column type*: AppliedChunkInfo
+    ColAppliedChunkInfo = 55,
 }
 
 impl std::fmt::Display for DBCol {
@@ -277,7 +304,7 @@ impl std::fmt::Display for DBCol {
             Self::ColBlockHeight => "block height",
             Self::ColState => "blockchain state",
             Self::ColChunkExtra => "extra information of trunk",
-            Self::ColTransactionResult => "transaction results",
+            Self::_ColTransactionResult => "transaction results (deprecated)",
             Self::ColOutgoingReceipts => "outgoing receipts",
             Self::ColIncomingReceipts => "incoming receipts",
             Self::ColPeers => "peer information",
@@ -322,6 +349,12 @@ impl std::fmt::Display for DBCol {
             Self::ColStateChangesForSplitStates => {
                 "state changes indexed by block hash and shard id"
             }
+            Self::ColEpochHeightToEpochId => "epoch ids indexed by epoch height",
+            Self::ColChunkSummaries => "chunk summaries",
+            Self::ColReceiptQueueChanges => "receipt queue changes",
+            Self::ColStateSyncProgress => "state sync download progress",
+            Self::ColTransactionResultForBlock => "transaction results by block hash",
+            Self::ColAppliedChunkInfo => "applied chunk info",
         };
         write!(formatter, "{}", desc)
     }
@@ -354,6 +387,7 @@ pub static SHOULD_COL_GC: [bool; DBCol::COUNT] = {
     col_gc[DBCol::ColEpochInfo as usize] = false; // https://github.com/nearprotocol/nearcore/pull/2952
     col_gc[DBCol::ColEpochValidatorInfo as usize] = false; // https://github.com/nearprotocol/nearcore/pull/2952
     col_gc[DBCol::ColEpochStart as usize] = false; // https://github.com/nearprotocol/nearcore/pull/2952
+    col_gc[DBCol::ColEpochHeightToEpochId as usize] = false; // same as ColEpochInfo
     col_gc[DBCol::ColCachedContractCode as usize] = false;
     col_gc
 };
@@ -366,6 +400,8 @@ pub static SKIP_COL_GC: [bool; DBCol::COUNT] = {
     col_gc[DBCol::ColStateHeaders as usize] = true;
     // True until #2515
     col_gc[DBCol::ColStateParts as usize] = true;
+    // Only written by nodes which state sync.
+    col_gc[DBCol::ColStateSyncProgress as usize] = true;
     col_gc
 };
 
@@ -391,6 +427,17 @@ pub const LARGEST_TARGET_HEIGHT_KEY: &[u8; 21] = b"LARGEST_TARGET_HEIGHT";
 pub const VERSION_KEY: &[u8; 7] = b"VERSION";
 pub const GENESIS_JSON_HASH_KEY: &[u8; 17] = b"GENESIS_JSON_HASH";
 pub const GENESIS_STATE_ROOTS_KEY: &[u8; 19] = b"GENESIS_STATE_ROOTS";
+/// Height below which execution outcomes, transactions and receipts may have been pruned.
+pub const OUTCOMES_PRUNED_HEIGHT_KEY: &[u8; 22] = b"OUTCOMES_PRUNED_HEIGHT";
+/// Progress of the outcomes pruning pass running on the node.
+pub const OUTCOMES_PRUNE_PROGRESS_KEY: &[u8; 23] = b"OUTCOMES_PRUNE_PROGRESS";
+/// Block hash in the `ColTransactionResultForBlock` key which marks pruned outcomes of an id.
+pub const PRUNED_OUTCOME_BLOCK_HASH: CryptoHash = CryptoHash([0; 32]);
+/// Prefix of the keys holding the progress of the state dump of each shard, followed by the
+/// little-endian shard id.
+pub const STATE_DUMP_PROGRESS_KEY: &[u8; 19] = b"STATE_DUMP_PROGRESS";
+/// Progress of the migration of the history into the cold database, kept in the cold database.
+pub const COLD_MIGRATION_PROGRESS_KEY: &[u8; 23] = b"COLD_MIGRATION_PROGRESS";
 
 pub struct DBTransaction {
     pub ops: Vec<DBOp>,
@@ -471,6 +518,27 @@ pub struct RocksDBOptions {
     free_space_threshold: bytesize::ByteSize,
     warn_treshold: bytesize::ByteSize,
     enable_statistics: bool,
+    background_io: BackgroundIoConfig,
+}
+
+/// Limits on the IO of the flushes and compactions RocksDB runs in the background, so that they
+/// don't slow down the reads of chunk application.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct BackgroundIoConfig {
+    /// Rate in bytes per second the background writes are limited to, unlimited if not set.
+    /// The rate is fixed until the database is reopened and isn't tuned automatically: the
+    /// `auto_tuned` mode of the RocksDB rate limiter and changing the rate of a live limiter
+    /// need a newer binding than rust-rocksdb 0.18, and `set_options` can't replace the limiter.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit_bytes_per_sec: Option<u64>,
+    /// Number of flushes and compactions run concurrently, chosen from the number of CPUs if not
+    /// set.  Can be changed while the node runs, see `RocksDB::set_max_background_jobs`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_background_jobs: Option<i32>,
+    /// Run compactions with the lowest IO priority.  Flushes keep theirs, as writes stall when
+    /// they fall behind.  Applies to every RocksDB instance of the process.
+    pub low_io_priority: bool,
 }
 
 /// Sets [`RocksDBOptions::check_free_space_interval`] to 256,
@@ -486,6 +554,7 @@ impl Default for RocksDBOptions {
             free_space_threshold: bytesize::ByteSize::mb(16),
             warn_treshold: bytesize::ByteSize::mb(256),
             enable_statistics: false,
+            background_io: BackgroundIoConfig::default(),
         }
     }
 }
@@ -556,6 +625,7 @@ impl RocksDBOptions {
         if self.enable_statistics {
             options = enable_statistics(options);
         }
+        set_background_io_options(&mut options, &self.background_io);
         let cf_names =
             self.cf_names.unwrap_or_else(|| DBCol::iter().map(|col| col_name(col)).collect());
         let cf_descriptors = self.cf_descriptors.unwrap_or_else(|| {
@@ -590,6 +660,12 @@ impl RocksDBOptions {
         self.enable_statistics = true;
         self
     }
+
+    /// Limits the IO of the background work of a read/write database.
+    pub fn background_io(mut self, background_io: BackgroundIoConfig) -> Self {
+        self.background_io = background_io;
+        self
+    }
 }
 
 pub struct TestDB {
@@ -618,6 +694,9 @@ pub trait Database: Sync + Send {
     fn get_store_statistics(&self) -> Option<StoreStatistics> {
         None
     }
+    fn get_store_properties(&self) -> Option<StoreProperties> {
+        None
+    }
 }
 
 impl Database for RocksDB {
@@ -728,6 +807,35 @@ impl Database for RocksDB {
         }
         None
     }
+
+    fn get_store_properties(&self) -> Option<StoreProperties> {
+        let columns = DBCol::iter()
+            .map(|col| ColumnProperties {
+                column: format!("{:?}", col),
+                pending_compaction_bytes: self
+                    .int_property(Some(col), "rocksdb.estimate-pending-compaction-bytes"),
+                num_files_at_level0: self.int_property(Some(col), "rocksdb.num-files-at-level0"),
+                mem_tables_size: self.int_property(Some(col), "rocksdb.cur-size-all-mem-tables"),
+                block_cache_usage: self.int_property(Some(col), "rocksdb.block-cache-usage"),
+            })
+            .collect();
+        let statistics = self.get_store_statistics();
+        let count = |stat_name| statistics.as_ref().and_then(|stats| stats.count(stat_name));
+        let block_cache_hit_rate =
+            match (count("rocksdb.block.cache.hit"), count("rocksdb.block.cache.miss")) {
+                (Some(hits), Some(misses)) if hits + misses > 0 => {
+                    Some(hits as f64 / (hits + misses) as f64)
+                }
+                _ => None,
+            };
+        Some(StoreProperties {
+            is_write_stopped: self.int_property(None, "rocksdb.is-write-stopped") != 0,
+            delayed_write_rate: self.int_property(None, "rocksdb.actual-delayed-write-rate"),
+            stall_micros: count("rocksdb.stall.micros").map(|micros| micros as u64),
+            block_cache_hit_rate,
+            columns,
+        })
+    }
 }
 
 impl Database for TestDB {
@@ -839,6 +947,22 @@ fn rocksdb_options() -> Options {
     opts
 }
 
+fn set_background_io_options(opts: &mut Options, config: &BackgroundIoConfig) {
+    if let Some(rate_limit) = config.rate_limit_bytes_per_sec {
+        // The refill period and fairness are the RocksDB defaults.
+        opts.set_ratelimiter(rate_limit as i64, 100_000, 10);
+    }
+    if let Some(jobs) = config.max_background_jobs {
+        opts.set_max_background_jobs(jobs);
+    }
+    if config.low_io_priority {
+        // Compactions run in the low priority thread pool of the default environment.
+        let mut env = Env::default().unwrap();
+        env.lower_thread_pool_io_priority();
+        opts.set_env(&env);
+    }
+}
+
 pub fn enable_statistics(mut opts: Options) -> Options {
     // Rust API doesn't permit choosing stats level. The default stats level is
     // `kExceptDetailedTimers`, which is described as:
@@ -942,6 +1066,44 @@ impl RocksDB {
         RocksDBOptions::default().read_write(path)
     }
 
+    /// Iterates over the raw entries of the column, without the refcount logic, from `start` or
+    /// the first key after it.
+    pub fn iter_raw_from<'a>(
+        &'a self,
+        col: DBCol,
+        start: &[u8],
+    ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+        let read_options = rocksdb_read_options();
+        unsafe {
+            let cf_handle = &*self.cfs[col as usize];
+            let mode = IteratorMode::From(start, Direction::Forward);
+            Box::new(self.db.iterator_cf_opt(cf_handle, read_options, mode))
+        }
+    }
+
+    /// Value of the integer RocksDB property `name` of the column, e.g.
+    /// `rocksdb.estimate-num-keys`, `None` if RocksDB doesn't know it.
+    pub fn property_int_value(&self, col: DBCol, name: &str) -> Result<Option<u64>, DBError> {
+        let cf_handle = unsafe { &*self.cfs[col as usize] };
+        Ok(self.db.property_int_value_cf(cf_handle, name)?)
+    }
+
+    /// Changes the number of flushes and compactions run concurrently, e.g. to throttle them
+    /// while they slow down the node.  The change is lost when the database is reopened.
+    pub fn set_max_background_jobs(&self, jobs: i32) -> Result<(), DBError> {
+        Ok(self.db.set_options(&[("max_background_jobs", &jobs.to_string())])?)
+    }
+
+    /// Value of the integer property `name` of the column, or of the whole database if `col` is
+    /// `None`, with properties which can't be read counted as 0.
+    fn int_property(&self, col: Option<DBCol>, name: &str) -> u64 {
+        let value = match col {
+            Some(col) => self.property_int_value(col, name),
+            None => self.db.property_int_value(name).map_err(DBError),
+        };
+        value.ok().flatten().unwrap_or(0)
+    }
+
     /// Checks if there is enough memory left to perform a write. Not having enough memory left can
     /// lead to difficult to recover from state, thus a PreWriteCheckErr is pretty much
     /// unrecoverable in most cases.
@@ -1058,6 +1220,44 @@ pub struct StoreStatistics {
     pub data: Vec<(String, Vec<StatsValue>)>,
 }
 
+impl StoreStatistics {
+    /// Value of the counter statistic `stat_name`.
+    pub fn count(&self, stat_name: &str) -> Option<i64> {
+        match self.data.iter().find(|(name, _)| name == stat_name)?.1.as_slice() {
+            [StatsValue::Count(value)] => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// Properties of a column family, sampled from the RocksDB properties API.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ColumnProperties {
+    pub column: String,
+    /// Estimated number of bytes compaction has to rewrite to bring all levels under their
+    /// target sizes.
+    pub pending_compaction_bytes: u64,
+    pub num_files_at_level0: u64,
+    /// Size of the active and the unflushed immutable memtables.
+    pub mem_tables_size: u64,
+    pub block_cache_usage: u64,
+}
+
+/// Compaction and write stall state of the database, sampled from the RocksDB properties API.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StoreProperties {
+    /// Writes are stopped until compaction catches up.
+    pub is_write_stopped: bool,
+    /// Rate in bytes per second writes are slowed down to until compaction catches up, 0 if
+    /// writes aren't delayed.
+    pub delayed_write_rate: u64,
+    /// Total time writes were stalled, only known if statistics are enabled.
+    pub stall_micros: Option<u64>,
+    /// Fraction of the block cache lookups which hit, only known if statistics are enabled.
+    pub block_cache_hit_rate: Option<f64>,
+    pub columns: Vec<ColumnProperties>,
+}
+
 /// Parses a string containing RocksDB statistics.
 fn parse_statistics(statistics: &str) -> Result<StoreStatistics, Box<dyn std::error::Error>> {
     let mut result = vec![];
@@ -1092,8 +1292,14 @@ fn parse_statistics(statistics: &str) -> Result<StoreStatistics, Box<dyn std::er
 mod tests {
     use crate::db::DBCol::ColState;
     use crate::db::StatsValue::{Count, Percentile, Sum};
-    use crate::db::{parse_statistics, rocksdb_read_options, DBError, Database, RocksDB};
+    use std::path::Path;
+
+    use crate::db::{
+        parse_statistics, rocksdb_read_options, BackgroundIoConfig, DBError, Database, RocksDB,
+        RocksDBOptions,
+    };
     use crate::{create_store, DBCol, StoreStatistics};
+    use strum::EnumCount;
 
     impl RocksDB {
         #[cfg(not(feature = "single_thread_rocksdb"))]
@@ -1199,6 +1405,55 @@ mod tests {
         }
     }
 
+    /// Value of a database option in the last options file RocksDB wrote.
+    fn persisted_db_option(path: &Path, name: &str) -> Option<String> {
+        let options_file = std::fs::read_dir(path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.file_name().unwrap().to_str().unwrap().starts_with("OPTIONS-"))
+            .max()
+            .unwrap();
+        let prefix = format!("{}=", name);
+        std::fs::read_to_string(options_file)
+            .unwrap()
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(&prefix).map(str::to_string))
+    }
+
+    #[test]
+    fn test_background_io() {
+        let tmp_dir = tempfile::Builder::new().prefix("_test_background_io").tempdir().unwrap();
+        let db = RocksDBOptions::default()
+            .background_io(BackgroundIoConfig {
+                rate_limit_bytes_per_sec: Some(64 * bytesize::MIB),
+                max_background_jobs: Some(3),
+                low_io_priority: false,
+            })
+            .read_write(tmp_dir.path())
+            .unwrap();
+        assert_eq!(persisted_db_option(tmp_dir.path(), "max_background_jobs").unwrap(), "3");
+        db.set_max_background_jobs(1).unwrap();
+        assert_eq!(persisted_db_option(tmp_dir.path(), "max_background_jobs").unwrap(), "1");
+    }
+
+    #[test]
+    fn test_store_properties() {
+        let tmp_dir = tempfile::Builder::new().prefix("_test_store_properties").tempdir().unwrap();
+        let store = create_store(tmp_dir.path());
+        let mut store_update = store.store_update();
+        store_update.set(DBCol::ColBlock, &[1], &[1; 100]);
+        store_update.commit().unwrap();
+        let properties = store.get_store_properties().unwrap();
+        assert!(!properties.is_write_stopped);
+        assert_eq!(properties.delayed_write_rate, 0);
+        // Statistics are disabled.
+        assert_eq!(properties.stall_micros, None);
+        assert_eq!(properties.block_cache_hit_rate, None);
+        assert_eq!(properties.columns.len(), DBCol::COUNT);
+        let block = properties.columns.iter().find(|col| col.column == "ColBlock").unwrap();
+        assert!(block.mem_tables_size > 0);
+    }
+
     #[test]
     fn test_parse_statistics() {
         let statistics = "rocksdb.cold.file.read.count COUNT : 999\n\
@@ -1223,5 +1478,9 @@ mod tests {
                 ]
             }
         );
+        let result = parse_statistics(statistics).unwrap();
+        assert_eq!(result.count("rocksdb.cold.file.read.count"), Some(999));
+        assert_eq!(result.count("rocksdb.db.get.micros"), None);
+        assert_eq!(result.count("rocksdb.stall.micros"), None);
     }
 }
@@ -1,3 +1,5 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufReader, Read, Write};
 use std::ops::Deref;
@@ -12,7 +14,9 @@ use lru::LruCache;
 pub use db::DBCol::{self, *};
 pub use db::{
     CHUNK_TAIL_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY,
-    LARGEST_TARGET_HEIGHT_KEY, LATEST_KNOWN_KEY, SHOULD_COL_GC, SKIP_COL_GC, TAIL_KEY,
+    LARGEST_TARGET_HEIGHT_KEY, LATEST_KNOWN_KEY, OUTCOMES_PRUNED_HEIGHT_KEY,
+    OUTCOMES_PRUNE_PROGRESS_KEY, PRUNED_OUTCOME_BLOCK_HASH, SHOULD_COL_GC, SKIP_COL_GC,
+    STATE_DUMP_PROGRESS_KEY, TAIL_KEY,
 };
 use near_crypto::PublicKey;
 use near_primitives::account::{AccessKey, Account};
@@ -28,29 +32,43 @@ use near_primitives::types::{AccountId, CompiledContractCache, StateRoot};
 pub use crate::db::refcount::decode_value_with_rc;
 use crate::db::refcount::encode_value_with_rc;
 use crate::db::{
-    DBOp, DBTransaction, Database, RocksDB, RocksDBOptions, StoreStatistics, GENESIS_JSON_HASH_KEY,
-    GENESIS_STATE_ROOTS_KEY,
+    BackgroundIoConfig, DBOp, DBTransaction, Database, RocksDB, RocksDBOptions, StoreProperties,
+    StoreStatistics, GENESIS_JSON_HASH_KEY, GENESIS_STATE_ROOTS_KEY,
 };
 pub use crate::trie::iterator::TrieIterator;
 pub use crate::trie::update::{TrieUpdate, TrieUpdateIterator, TrieUpdateValuePtr};
 pub use crate::trie::{
-    split_state, ApplyStatePartResult, KeyForStateChanges, PartialStorage, ShardTries, Trie,
-    TrieChanges, WrappedTrieChanges,
+    raw_node, split_state, ApplyStatePartResult, CachedTrieNode, KeyForStateChanges,
+    PartialStorage, ShardTries, Trie, TrieCacheKind, TrieCacheStats, TrieChanges,
+    WrappedTrieChanges,
 };
 
+pub mod cold_migration;
 pub mod db;
+pub mod metrics;
 pub mod migrations;
+pub mod snapshot;
 pub mod test_utils;
 mod trie;
 
+/// Default soft limit on the size of the batches written by `StoreUpdate::commit_chunked`.
+pub const DEFAULT_WRITE_BATCH_SOFT_LIMIT: usize = 64 * 1024 * 1024;
+
 #[derive(Clone)]
 pub struct Store {
     storage: Arc<dyn Database>,
+    /// Soft limit on the size of the batches written by `StoreUpdate::commit_chunked`.
+    write_batch_soft_limit: usize,
 }
 
 impl Store {
     pub fn new(storage: Arc<dyn Database>) -> Store {
-        Store { storage }
+        Store { storage, write_batch_soft_limit: DEFAULT_WRITE_BATCH_SOFT_LIMIT }
+    }
+
+    pub fn with_write_batch_soft_limit(mut self, write_batch_soft_limit: usize) -> Store {
+        self.write_batch_soft_limit = write_batch_soft_limit;
+        self
     }
 
     pub fn get(&self, column: DBCol, key: &[u8]) -> Result<Option<Vec<u8>>, io::Error> {
@@ -77,7 +95,9 @@ impl Store {
     }
 
     pub fn store_update(&self) -> StoreUpdate {
-        StoreUpdate::new(self.storage.clone())
+        let mut store_update = StoreUpdate::new(self.storage.clone());
+        store_update.write_batch_soft_limit = self.write_batch_soft_limit;
+        store_update
     }
 
     pub fn iter<'a>(
@@ -156,35 +176,62 @@ impl Store {
     pub fn get_store_statistics(&self) -> Option<StoreStatistics> {
         self.storage.get_store_statistics()
     }
+
+    pub fn get_store_properties(&self) -> Option<StoreProperties> {
+        self.storage.get_store_properties()
+    }
 }
 
 /// Keeps track of current changes to the database and can commit all of them to the database.
+///
+/// The changes are committed atomically by `commit`.  Updates too large for a single write batch
+/// can instead be committed by `commit_chunked`, which only guarantees the atomicity of the units
+/// declared by `finish_unit`.
 pub struct StoreUpdate {
     storage: Arc<dyn Database>,
     transaction: DBTransaction,
     /// Optionally has reference to the trie to clear cache on the commit.
     tries: Option<ShardTries>,
+    /// Size of the keys and values in `transaction`.
+    pending_bytes: usize,
+    /// Indices of `transaction.ops` at which the units declared by `finish_unit` end.
+    unit_ends: Vec<usize>,
+    write_batch_soft_limit: usize,
 }
 
 impl StoreUpdate {
     pub fn new(storage: Arc<dyn Database>) -> Self {
         let transaction = storage.transaction();
-        StoreUpdate { storage, transaction, tries: None }
+        StoreUpdate {
+            storage,
+            transaction,
+            tries: None,
+            pending_bytes: 0,
+            unit_ends: vec![],
+            write_batch_soft_limit: DEFAULT_WRITE_BATCH_SOFT_LIMIT,
+        }
     }
 
     pub fn new_with_tries(tries: ShardTries) -> Self {
-        let storage = tries.get_store().storage.clone();
-        let transaction = storage.transaction();
-        StoreUpdate { storage, transaction, tries: Some(tries) }
+        let mut store_update = tries.get_store().store_update();
+        store_update.tries = Some(tries);
+        store_update
+    }
+
+    /// Size of the keys and values written or deleted by this update.
+    pub fn pending_bytes(&self) -> usize {
+        self.pending_bytes
     }
 
     pub fn update_refcount(&mut self, column: DBCol, key: &[u8], value: &[u8], rc_delta: i64) {
         debug_assert!(column.is_rc());
         let value = encode_value_with_rc(value, rc_delta);
+        self.pending_bytes += key.len() + value.len();
         self.transaction.update_refcount(column, key, value)
     }
 
     pub fn set(&mut self, column: DBCol, key: &[u8], value: &[u8]) {
+        self.pending_bytes += key.len() + value.len();
         self.transaction.put(column, key, value)
     }
 
@@ -201,6 +248,7 @@ impl StoreUpdate {
     }
 
     pub fn delete(&mut self, column: DBCol, key: &[u8]) {
+        self.pending_bytes += key.len();
         self.transaction.delete(column, key);
     }
 
@@ -208,7 +256,17 @@ impl StoreUpdate {
         self.transaction.delete_all(column);
     }
 
-    /// Merge another store update into this one.
+    /// Ends the current atomic unit: `commit_chunked` may split the update right after the
+    /// operations added so far, but never between the operations added until the next call.
+    pub fn finish_unit(&mut self) {
+        let end = self.transaction.ops.len();
+        if end > self.unit_ends.last().copied().unwrap_or(0) {
+            self.unit_ends.push(end);
+        }
+    }
+
+    /// Merge another store update into this one.  The units of `other` are kept, except that its
+    /// first one is joined with the unfinished unit of this update, if any.
     pub fn merge(&mut self, other: StoreUpdate) {
         if let Some(tries) = other.tries {
             if self.tries.is_none() {
@@ -218,12 +276,15 @@ impl StoreUpdate {
             }
         }
 
+        let offset = self.transaction.ops.len();
+        self.unit_ends.extend(other.unit_ends.iter().map(|end| offset + end));
         self.merge_transaction(other.transaction);
     }
 
     /// Merge DB Transaction.
     pub fn merge_transaction(&mut self, transaction: DBTransaction) {
         for op in transaction.ops {
+            self.pending_bytes += op_size(&op);
             match op {
                 DBOp::Insert { col, key, value } => self.transaction.put(col, &key, &value),
                 DBOp::Delete { col, key } => self.transaction.delete(col, &key),
@@ -235,34 +296,179 @@ impl StoreUpdate {
         }
     }
 
-    pub fn commit(self) -> Result<(), io::Error> {
-        debug_assert!(
-            {
-                let non_refcount_keys = self
-                    .transaction
-                    .ops
-                    .iter()
-                    .filter_map(|op| match op {
-                        DBOp::Insert { col, key, .. } => Some((*col as u8, key)),
-                        DBOp::Delete { col, key } => Some((*col as u8, key)),
-                        DBOp::UpdateRefcount { .. } => None,
-                        DBOp::DeleteAll { .. } => None,
-                    })
-                    .collect::<Vec<_>>();
-                non_refcount_keys.len()
-                    == non_refcount_keys.iter().collect::<std::collections::HashSet<_>>().len()
-            },
-            "Transaction overwrites itself: {:?}",
-            self
-        );
-        if let Some(tries) = self.tries {
+    fn has_no_overwrites(&self) -> bool {
+        let non_refcount_keys = self
+            .transaction
+            .ops
+            .iter()
+            .filter_map(|op| match op {
+                DBOp::Insert { col, key, .. } => Some((*col as u8, key)),
+                DBOp::Delete { col, key } => Some((*col as u8, key)),
+                DBOp::UpdateRefcount { .. } => None,
+                DBOp::DeleteAll { .. } => None,
+            })
+            .collect::<Vec<_>>();
+        non_refcount_keys.len()
+            == non_refcount_keys.iter().collect::<std::collections::HashSet<_>>().len()
+    }
+
+    fn write(&self, transaction: DBTransaction, bytes: usize, commit: &str) -> io::Result<()> {
+        if let Some(tries) = &self.tries {
             assert_eq!(
                 tries.get_store().storage.deref() as *const _,
                 self.storage.deref() as *const _
             );
-            tries.update_cache(&self.transaction)?;
+            tries.update_cache(&transaction)?;
+        }
+        metrics::WRITE_BATCH_SIZE_BYTES.with_label_values(&[commit]).observe(bytes as f64);
+        self.storage.write(transaction).map_err(|e| e.into())
+    }
+
+    /// Number of operations in the update.
+    pub fn num_ops(&self) -> usize {
+        self.transaction.ops.len()
+    }
+
+    /// Replaces the refcount updates of a key within a unit by a single update with the sum of
+    /// their deltas, or by nothing if the sum is zero.  The merge operator sums the deltas anyway,
+    /// so the contents read after the commit don't change; the same trie node is often touched by
+    /// several trie changes written together.  Done by `commit` and `commit_chunked`.
+    pub fn coalesce_refcount_updates(&mut self) {
+        let mut ops = std::mem::take(&mut self.transaction.ops).into_iter();
+        let num_ops = ops.len();
+        let mut unit_ends = Vec::with_capacity(self.unit_ends.len());
+        let mut start = 0;
+        for end in self.unit_ends.iter().copied().chain(std::iter::once(num_ops)) {
+            let unit = ops.by_ref().take(end - start).collect();
+            self.transaction.ops.extend(coalesce_refcount_updates(unit));
+            let new_end = self.transaction.ops.len();
+            if end < num_ops && new_end > unit_ends.last().copied().unwrap_or(0) {
+                unit_ends.push(new_end);
+            }
+            start = end;
+        }
+        self.unit_ends = unit_ends;
+        self.pending_bytes = self.transaction.ops.iter().map(op_size).sum();
+    }
+
+    pub fn commit(mut self) -> Result<(), io::Error> {
+        debug_assert!(self.has_no_overwrites(), "Transaction overwrites itself: {:?}", self);
+        self.coalesce_refcount_updates();
+        let transaction = std::mem::replace(&mut self.transaction, self.storage.transaction());
+        self.write(transaction, self.pending_bytes, "atomic")
+    }
+
+    /// Commits the update in batches of about `write_batch_soft_limit` bytes, each of them made
+    /// of whole units.  A unit larger than the limit is written alone.  The operations added
+    /// since the last `finish_unit` form the last unit.
+    ///
+    /// If the node crashes in the middle, only a prefix of the units is in the database, so
+    /// callers must order the units such that the work is resumed from any such prefix.
+    pub fn commit_chunked(mut self) -> Result<(), io::Error> {
+        debug_assert!(self.has_no_overwrites(), "Transaction overwrites itself: {:?}", self);
+        self.coalesce_refcount_updates();
+        for (transaction, bytes) in self.take_batches() {
+            self.write(transaction, bytes, "chunked")?;
+        }
+        Ok(())
+    }
+
+    /// Splits the operations into the batches written by `commit_chunked`, with their sizes.
+    fn take_batches(&mut self) -> Vec<(DBTransaction, usize)> {
+        self.finish_unit();
+        let mut ops = std::mem::take(&mut self.transaction.ops).into_iter();
+        let mut batches = vec![];
+        let mut batch = self.storage.transaction();
+        let mut batch_bytes = 0;
+        let mut start = 0;
+        for end in std::mem::take(&mut self.unit_ends) {
+            let unit = ops.by_ref().take(end - start).collect::<Vec<_>>();
+            let unit_bytes: usize = unit.iter().map(op_size).sum();
+            if !batch.ops.is_empty() && batch_bytes + unit_bytes > self.write_batch_soft_limit {
+                batches
+                    .push((std::mem::replace(&mut batch, self.storage.transaction()), batch_bytes));
+                batch_bytes = 0;
+            }
+            batch.ops.extend(unit);
+            batch_bytes += unit_bytes;
+            start = end;
+        }
+        if !batch.ops.is_empty() {
+            batches.push((batch, batch_bytes));
+        }
+        self.pending_bytes = 0;
+        batches
+    }
+}
+
+/// Coalesces the refcount updates of each key, see `StoreUpdate::coalesce_refcount_updates`.
+/// Keys which are also set or deleted, and keys of columns which are deleted, are left alone
+/// since the order of their operations matters.
+fn coalesce_refcount_updates(ops: Vec<DBOp>) -> Vec<DBOp> {
+    let mut ordered_cols = HashSet::new();
+    let mut ordered_keys = HashSet::new();
+    let mut num_refcount_updates = 0;
+    for op in ops.iter() {
+        match op {
+            DBOp::Insert { col, key, .. } | DBOp::Delete { col, key } => {
+                ordered_keys.insert((*col, key.clone()));
+            }
+            DBOp::DeleteAll { col } => {
+                ordered_cols.insert(*col);
+            }
+            DBOp::UpdateRefcount { .. } => num_refcount_updates += 1,
+        }
+    }
+    if num_refcount_updates < 2 {
+        return ops;
+    }
+
+    let mut result = Vec::with_capacity(ops.len());
+    // Index in `result` of the update of a key, its value and the sum of its deltas.
+    let mut updates: HashMap<(DBCol, Vec<u8>), (usize, Option<Vec<u8>>, i64)> = HashMap::new();
+    for op in ops {
+        match op {
+            DBOp::UpdateRefcount { col, key, value }
+                if !ordered_cols.contains(&col)
+                    && (ordered_keys.is_empty() || !ordered_keys.contains(&(col, key.clone()))) =>
+            {
+                let (bytes, rc) = decode_value_with_rc(&value);
+                let bytes = bytes.map(|bytes| bytes.to_vec());
+                match updates.entry((col, key)) {
+                    Entry::Occupied(mut entry) => {
+                        let (_, update_bytes, update_rc) = entry.get_mut();
+                        if update_bytes.is_none() {
+                            *update_bytes = bytes;
+                        }
+                        *update_rc += rc;
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert((result.len(), bytes, rc));
+                        // Filled in below.
+                        result.push(None);
+                    }
+                }
+            }
+            op => result.push(Some(op)),
         }
-        self.storage.write(self.transaction).map_err(|e| e.into())
+    }
+    for ((col, key), (index, bytes, rc)) in updates {
+        if rc != 0 {
+            let value = encode_value_with_rc(bytes.as_deref().unwrap_or(&[]), rc);
+            result[index] = Some(DBOp::UpdateRefcount { col, key, value });
+        }
+    }
+    result.into_iter().flatten().collect()
+}
+
+/// Size of the key and value of a database operation.
+fn op_size(op: &DBOp) -> usize {
+    match op {
+        DBOp::Insert { key, value, .. } | DBOp::UpdateRefcount { key, value, .. } => {
+            key.len() + value.len()
+        }
+        DBOp::Delete { key, .. } => key.len(),
+        DBOp::DeleteAll { .. } => 0,
     }
 }
 
@@ -312,6 +518,11 @@ pub struct StoreConfig {
     /// Re-export storage layer statistics as prometheus metrics.
     /// Minor performance impact is expected.
     pub enable_statistics: bool,
+    /// Soft limit on the size of the batches written by `StoreUpdate::commit_chunked`,
+    /// `DEFAULT_WRITE_BATCH_SOFT_LIMIT` if not set.
+    pub write_batch_soft_limit: Option<usize>,
+    /// Limits on the IO of the background flushes and compactions of a read/write database.
+    pub background_io: BackgroundIoConfig,
 }
 
 pub fn create_store_with_config(path: &Path, store_config: StoreConfig) -> Store {
@@ -319,12 +530,17 @@ pub fn create_store_with_config(path: &Path, store_config: StoreConfig) -> Store
     if store_config.enable_statistics {
         opts = opts.enable_statistics();
     }
+    opts = opts.background_io(store_config.background_io);
 
     let db = Arc::new(
         (if store_config.read_only { opts.read_only(path) } else { opts.read_write(path) })
             .expect("Failed to open the database"),
     );
-    Store::new(db)
+    let store = Store::new(db);
+    match store_config.write_batch_soft_limit {
+        Some(limit) => store.with_write_batch_soft_limit(limit),
+        None => store,
+    }
 }
 
 /// Reads an object from Trie.
@@ -506,6 +722,22 @@ pub fn remove_account(
     for key in data_keys {
         state_update.remove(TrieKey::ContractData { account_id: account_id.clone(), key });
     }
+
+    // Removing data ids generated by the account which no receipt depends on yet
+    let data_ids = state_update
+        .iter(&trie_key_parsers::get_raw_prefix_for_registered_data_ids(account_id))?
+        .map(|raw_key| {
+            trie_key_parsers::parse_data_id_from_registered_data_id_key(&raw_key?, account_id)
+                .map_err(|_e| {
+                    StorageError::StorageInconsistentState(
+                        "Can't parse data id from raw key for RegisteredDataId".to_string(),
+                    )
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    for data_id in data_ids {
+        state_update.remove(TrieKey::RegisteredDataId { account_id: account_id.clone(), data_id });
+    }
     Ok(())
 }
 
@@ -551,9 +783,121 @@ impl CompiledContractCache for StoreCompiledContractCache {
 
 #[cfg(test)]
 mod tests {
+    use super::{decode_value_with_rc, DBCol, Store};
+    use crate::test_utils::create_test_store;
+
     #[test]
     fn test_no_cache_disabled() {
         #[cfg(feature = "no_cache")]
         panic!("no cache is enabled");
     }
+
+    #[test]
+    fn test_commit_chunked_batches() {
+        let store = create_test_store().with_write_batch_soft_limit(10);
+        let mut store_update = store.store_update();
+        for i in 0u8..3 {
+            store_update.set(DBCol::ColBlockMisc, &[i], &[i; 3]);
+            store_update.set(DBCol::ColBlockMisc, &[i, i], &[i; 3]);
+            store_update.finish_unit();
+        }
+        // A unit larger than the limit.
+        store_update.set(DBCol::ColBlockMisc, &[3], &[3; 20]);
+        store_update.finish_unit();
+        // The last unit needs no `finish_unit`.
+        store_update.delete(DBCol::ColBlockMisc, &[4]);
+        assert_eq!(store_update.pending_bytes(), 3 * 9 + 21 + 1);
+        let batches = store_update.take_batches();
+        let sizes =
+            batches.iter().map(|(batch, bytes)| (batch.ops.len(), *bytes)).collect::<Vec<_>>();
+        assert_eq!(sizes, vec![(2, 9), (2, 9), (2, 9), (1, 21), (1, 1)]);
+    }
+
+    #[test]
+    fn test_coalesce_refcount_updates() {
+        let store = create_test_store();
+        let mut store_update = store.store_update();
+        store_update.update_refcount(DBCol::ColState, b"a", b"node", 1);
+        store_update.update_refcount(DBCol::ColState, b"b", b"node", 1);
+        store_update.update_refcount(DBCol::ColState, b"a", b"node", 2);
+        store_update.update_refcount(DBCol::ColState, b"b", b"node", -1);
+        // Written in the same order as the deletion, left alone.
+        store_update.update_refcount(DBCol::ColTransactions, b"c", b"tx", 1);
+        store_update.delete(DBCol::ColTransactions, b"c");
+        store_update.update_refcount(DBCol::ColTransactions, b"c", b"tx", 1);
+        store_update.finish_unit();
+        // Not coalesced with the update of the previous unit.
+        store_update.update_refcount(DBCol::ColState, b"a", b"node", -1);
+        store_update.update_refcount(DBCol::ColState, b"d", b"node", -1);
+        store_update.update_refcount(DBCol::ColState, b"d", b"node", 2);
+        store_update.coalesce_refcount_updates();
+        assert_eq!(store_update.num_ops(), 6);
+        assert_eq!(store_update.unit_ends, vec![4]);
+        store_update.commit().unwrap();
+
+        assert_eq!(store.get(DBCol::ColState, b"a").unwrap(), Some(b"node".to_vec()));
+        assert_eq!(store.get(DBCol::ColState, b"b").unwrap(), None);
+        assert_eq!(store.get(DBCol::ColTransactions, b"c").unwrap(), Some(b"tx".to_vec()));
+        assert_eq!(store.get(DBCol::ColState, b"d").unwrap(), Some(b"node".to_vec()));
+        let rc = |key: &[u8]| {
+            let value = store
+                .iter_without_rc_logic(DBCol::ColState)
+                .find(|(k, _)| k.as_ref() == key)
+                .unwrap()
+                .1;
+            decode_value_with_rc(&value).1
+        };
+        assert_eq!(rc(b"a"), 2);
+        assert_eq!(rc(b"d"), 1);
+    }
+
+    /// Every unit decrements the refcount of a value and deletes the marker of the pending
+    /// decrement, as garbage collection does with trie changes.
+    fn decrement_pending(store: &Store) {
+        let mut store_update = store.store_update();
+        for i in 0u8..10 {
+            if store.exists(DBCol::ColBlockMisc, &[i]).unwrap() {
+                store_update.update_refcount(DBCol::ColState, &[i], &[i; 8], -1);
+                store_update.delete(DBCol::ColBlockMisc, &[i]);
+                store_update.finish_unit();
+            }
+        }
+        store_update.commit_chunked().unwrap();
+    }
+
+    #[test]
+    fn test_commit_chunked_crash_recovery() {
+        let store = create_test_store().with_write_batch_soft_limit(40);
+        let mut store_update = store.store_update();
+        for i in 0u8..10 {
+            store_update.update_refcount(DBCol::ColState, &[i], &[i; 8], 2);
+            store_update.set(DBCol::ColBlockMisc, &[i], &[]);
+        }
+        store_update.commit().unwrap();
+
+        // Crash after committing the first two batches only.
+        let mut store_update = store.store_update();
+        for i in 0u8..10 {
+            store_update.update_refcount(DBCol::ColState, &[i], &[i; 8], -1);
+            store_update.delete(DBCol::ColBlockMisc, &[i]);
+            store_update.finish_unit();
+        }
+        let batches = store_update.take_batches();
+        assert_eq!(batches.len(), 5);
+        for (batch, _) in batches.into_iter().take(2) {
+            store.storage.write(batch).unwrap();
+        }
+        for i in 0u8..10 {
+            let pending = store.exists(DBCol::ColBlockMisc, &[i]).unwrap();
+            assert_eq!(pending, i >= 4);
+            assert_eq!(store.get(DBCol::ColState, &[i]).unwrap(), Some(vec![i; 8]));
+        }
+
+        // Resuming decrements the remaining refcounts only.
+        decrement_pending(&store);
+        for i in 0u8..10 {
+            assert!(!store.exists(DBCol::ColBlockMisc, &[i]).unwrap());
+            assert_eq!(store.get(DBCol::ColState, &[i]).unwrap(), Some(vec![i; 8]));
+        }
+    }
 }
@@ -0,0 +1,61 @@
+use near_metrics::{
+    try_create_histogram_vec, try_create_int_counter_vec, try_create_int_gauge_vec, HistogramVec,
+    IntCounterVec, IntGaugeVec,
+};
+use near_primitives::shard_layout::ShardUId;
+use once_cell::sync::Lazy;
+
+pub static TRIE_CACHE_HITS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_trie_cache_hits_total",
+        "Number of trie nodes and values found in the trie caches",
+        &["shard_uid", "cache"],
+    )
+    .unwrap()
+});
+pub static TRIE_CACHE_MISSES: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_trie_cache_misses_total",
+        "Number of trie nodes and values looked up in the trie caches but not found",
+        &["shard_uid", "cache"],
+    )
+    .unwrap()
+});
+pub static TRIE_CACHE_INSERTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_trie_cache_inserts_total",
+        "Number of trie nodes and values inserted into the trie caches",
+        &["shard_uid", "cache"],
+    )
+    .unwrap()
+});
+pub static TRIE_CACHE_EVICTIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_trie_cache_evictions_total",
+        "Number of trie nodes and values evicted from the trie caches because they were full",
+        &["shard_uid", "cache"],
+    )
+    .unwrap()
+});
+pub static TRIE_CACHE_SIZE_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_trie_cache_size_bytes",
+        "Total size of the trie nodes and values held by the trie caches",
+        &["shard_uid", "cache"],
+    )
+    .unwrap()
+});
+pub static WRITE_BATCH_SIZE_BYTES: Lazy<HistogramVec> = Lazy::new(|| {
+    try_create_histogram_vec(
+        "near_store_write_batch_size_bytes",
+        "Size of the keys and values of the batches written into the database",
+        &["commit"],
+        Some((10..30).map(|exponent| 2f64.powi(exponent)).collect()),
+    )
+    .unwrap()
+});
+
+/// Value of the `shard_uid` label, e.g. `s0.v1`.
+pub fn shard_uid_label(shard_uid: ShardUId) -> String {
+    format!("s{}.v{}", shard_uid.shard_id, shard_uid.version)
+}
@@ -39,7 +39,7 @@ use crate::migrations::v6_to_v7::{
 use crate::migrations::v8_to_v9::{
     recompute_col_rc, repair_col_receipt_id_to_shard_id, repair_col_transactions,
 };
-use crate::trie::{TrieCache, TrieCachingStorage};
+use crate::trie::{TrieCache, TrieCacheKind, TrieCachingStorage};
 use crate::{create_store, Store, StoreUpdate, Trie, TrieUpdate, FINAL_HEAD_KEY, HEAD_KEY};
 use std::path::Path;
 
@@ -75,7 +75,7 @@ fn get_outcomes_by_block_hash(store: &Store, block_hash: &CryptoHash) -> HashSet
 pub fn fill_col_outcomes_by_hash(store: &Store) {
     let mut store_update = store.store_update();
     let outcomes: Vec<ExecutionOutcomeWithIdAndProof> = store
-        .iter(DBCol::ColTransactionResult)
+        .iter(DBCol::_ColTransactionResult)
         .map(|key| {
             ExecutionOutcomeWithIdAndProof::try_from_slice(&key.1)
                 .expect("BorshDeserialize should not fail")
@@ -324,6 +324,17 @@ impl<'a> BatchedStoreUpdate<'a> {
         Ok(())
     }
 
+    pub fn set(&mut self, col: DBCol, key: &[u8], value: &[u8]) -> Result<(), std::io::Error> {
+        self.batch_size += key.len() + value.len() + 8;
+        self.store_update.as_mut().unwrap().set(col, key, value);
+
+        if self.batch_size > self.batch_size_limit {
+            self.commit()?;
+        }
+
+        Ok(())
+    }
+
     pub fn set_ser<T: BorshSerialize>(
         &mut self,
         col: DBCol,
@@ -331,14 +342,7 @@ impl<'a> BatchedStoreUpdate<'a> {
         value: &T,
     ) -> Result<(), std::io::Error> {
         let value_bytes = value.try_to_vec()?;
-        self.batch_size += key.as_ref().len() + value_bytes.len() + 8;
-        self.store_update.as_mut().unwrap().set(col, key.as_ref(), &value_bytes);
-
-        if self.batch_size > self.batch_size_limit {
-            self.commit()?;
-        }
-
-        Ok(())
+        self.set(col, key, &value_bytes)
     }
 
     pub fn finish(mut self) -> Result<(), std::io::Error> {
@@ -425,7 +429,7 @@ pub fn migrate_14_to_15(path: &Path) {
     let store = create_store(path);
     let trie_store = Box::new(TrieCachingStorage::new(
         store.clone(),
-        TrieCache::new(),
+        TrieCache::new(ShardUId::single_shard(), TrieCacheKind::Shard),
         ShardUId::single_shard(),
     ));
     let trie = Rc::new(Trie::new(trie_store, ShardUId::single_shard()));
@@ -0,0 +1,179 @@
+//! Online snapshots of the node database.
+//!
+//! A snapshot is a directory with a RocksDB checkpoint of the database in [`SNAPSHOT_DB_DIR`]
+//! and its metadata in [`SNAPSHOT_INFO_FILE`].  The checkpoint hard links the SST files of the
+//! live database, so creating it only costs a memtable flush and doesn't need the node to stop.
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use near_primitives::block::Tip;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::BlockHeight;
+use near_primitives::version::DbVersion;
+
+use crate::db::{DBCol, RocksDB, HEAD_KEY};
+use crate::Store;
+
+/// Directory within a snapshot which holds the database.
+pub const SNAPSHOT_DB_DIR: &str = "data";
+/// File within a snapshot which holds its [`SnapshotInfo`].
+pub const SNAPSHOT_INFO_FILE: &str = "snapshot.json";
+
+/// Metadata of a snapshot, recorded when it is created and checked before it is restored.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SnapshotInfo {
+    pub db_version: DbVersion,
+    pub head_hash: CryptoHash,
+    pub head_height: BlockHeight,
+    /// Total size of the database files.  Most of them are hard links shared with the database
+    /// the snapshot was taken from, so this isn't the disk space the snapshot takes.
+    pub size_bytes: u64,
+    pub duration_ms: u64,
+}
+
+impl Store {
+    /// Creates a snapshot of the database in `destination`, which must not exist yet.  All
+    /// columns are captured atomically.
+    pub fn make_snapshot(&self, destination: &Path) -> io::Result<SnapshotInfo> {
+        let rocksdb = self.storage.as_rocksdb().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Unsupported, "snapshots are only supported for RocksDB")
+        })?;
+        if destination.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", destination.display()),
+            ));
+        }
+        let start = Instant::now();
+        info!(target: "store", "Creating database snapshot in {}", destination.display());
+        fs::create_dir_all(destination)?;
+        let db_path = destination.join(SNAPSHOT_DB_DIR);
+        rocksdb
+            .checkpoint()
+            .map_err(Into::<io::Error>::into)?
+            .create_checkpoint(&db_path)
+            .map_err(|err| {
+                io::Error::new(io::ErrorKind::Other, format!("checkpoint failed: {}", err))
+            })?;
+
+        // Head is read from the checkpoint rather than from the live database, which may have
+        // moved on in the meantime.
+        let (db_version, head) = read_version_and_head(&db_path)?;
+        let info = SnapshotInfo {
+            db_version,
+            head_hash: head.last_block_hash,
+            head_height: head.height,
+            size_bytes: dir_size(&db_path)?,
+            duration_ms: start.elapsed().as_millis() as u64,
+        };
+        let file = fs::File::create(destination.join(SNAPSHOT_INFO_FILE))?;
+        serde_json::to_writer_pretty(file, &info)?;
+        info!(
+            target: "store",
+            "Created database snapshot in {} at head #{} {}: {} bytes in {} ms",
+            destination.display(),
+            info.head_height,
+            info.head_hash,
+            info.size_bytes,
+            info.duration_ms
+        );
+        Ok(info)
+    }
+}
+
+/// Checks that the snapshot in `source` is complete: its database has the recorded version and
+/// head, and the head block is present.
+pub fn validate_snapshot(source: &Path) -> io::Result<SnapshotInfo> {
+    let file = fs::File::open(source.join(SNAPSHOT_INFO_FILE))?;
+    let info: SnapshotInfo = serde_json::from_reader(io::BufReader::new(file))?;
+    let db_path = source.join(SNAPSHOT_DB_DIR);
+    let (db_version, head) = read_version_and_head(&db_path)?;
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    if db_version != info.db_version {
+        return Err(invalid(format!(
+            "database version is {} but snapshot was taken at version {}",
+            db_version, info.db_version
+        )));
+    }
+    if head.last_block_hash != info.head_hash || head.height != info.head_height {
+        return Err(invalid(format!(
+            "database head is #{} {} but snapshot was taken at #{} {}",
+            head.height, head.last_block_hash, info.head_height, info.head_hash
+        )));
+    }
+    let db = RocksDB::new_read_only(&db_path).map_err(Into::<io::Error>::into)?;
+    let store = Store::new(std::sync::Arc::new(db));
+    for col in [DBCol::ColBlockHeader, DBCol::ColBlock] {
+        if !store.exists(col, head.last_block_hash.as_ref())? {
+            return Err(invalid(format!(
+                "head block {} missing from {}",
+                head.last_block_hash, col
+            )));
+        }
+    }
+    Ok(info)
+}
+
+fn read_version_and_head(db_path: &Path) -> io::Result<(DbVersion, Tip)> {
+    let db_version = RocksDB::get_version(db_path).map_err(Into::<io::Error>::into)?;
+    let db = RocksDB::new_read_only(db_path).map_err(Into::<io::Error>::into)?;
+    let head = Store::new(std::sync::Arc::new(db))
+        .get_ser::<Tip>(DBCol::ColBlockMisc, HEAD_KEY)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "database has no head"))?;
+    Ok((db_version, head))
+}
+
+fn dir_size(path: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let metadata = entry?.metadata()?;
+        if metadata.is_file() {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_store;
+
+    #[test]
+    fn test_make_and_validate_snapshot() {
+        let home = tempfile::tempdir().unwrap();
+        let store = create_store(&home.path().join("db"));
+        crate::migrations::set_store_version(&store, 42);
+        let head = Tip {
+            height: 7,
+            last_block_hash: CryptoHash::hash_bytes(b"head"),
+            prev_block_hash: CryptoHash::default(),
+            epoch_id: Default::default(),
+            next_epoch_id: Default::default(),
+        };
+        let mut store_update = store.store_update();
+        store_update.set_ser(DBCol::ColBlockMisc, HEAD_KEY, &head).unwrap();
+        store_update.set(DBCol::ColBlockHeader, head.last_block_hash.as_ref(), b"header");
+        store_update.set(DBCol::ColBlock, head.last_block_hash.as_ref(), b"block");
+        store_update.commit().unwrap();
+
+        let destination = home.path().join("snapshot");
+        let info = store.make_snapshot(&destination).unwrap();
+        assert_eq!(info.db_version, 42);
+        assert_eq!(info.head_hash, head.last_block_hash);
+        assert_eq!(info.head_height, 7);
+        assert!(store.make_snapshot(&destination).is_err());
+        assert_eq!(validate_snapshot(&destination).unwrap(), info);
+
+        // Writes to the live database after the snapshot don't show up in it.
+        let mut store_update = store.store_update();
+        store_update.delete(DBCol::ColBlock, head.last_block_hash.as_ref());
+        store_update.commit().unwrap();
+        assert_eq!(validate_snapshot(&destination).unwrap(), info);
+    }
+}
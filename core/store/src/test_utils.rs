@@ -79,7 +79,11 @@ pub fn gen_receipts(rng: &mut impl Rng, max_size: usize) -> Vec<Receipt> {
             predecessor_id: account_id.clone(),
             receiver_id: account_id.clone(),
             receipt_id: CryptoHash::default(),
-            receipt: ReceiptEnum::Data(DataReceipt { data_id: CryptoHash::default(), data: None }),
+            receipt: ReceiptEnum::Data(DataReceipt {
+                data_id: CryptoHash::default(),
+                data: None,
+                gas_burnt: None,
+            }),
         })
         .collect()
 }
@@ -1,12 +1,15 @@
+use std::sync::Arc;
+
 use near_primitives::hash::CryptoHash;
 
 use crate::trie::nibble_slice::NibbleSlice;
-use crate::trie::{TrieNode, TrieNodeWithSize, ValueHandle};
+use crate::trie::raw_node::RawTrieNodeView;
 use crate::{StorageError, Trie};
 
 #[derive(Debug)]
 struct Crumb {
-    node: TrieNodeWithSize,
+    /// Serialized node, viewed in place at every step.  `None` for the empty trie.
+    node: Option<Arc<[u8]>>,
     status: CrumbStatus,
 }
 
@@ -18,17 +21,25 @@ pub(crate) enum CrumbStatus {
     Exiting,
 }
 
+/// Views a node checked by `Trie::retrieve_raw_node`.
+fn view(node: &Option<Arc<[u8]>>) -> Option<RawTrieNodeView<'_>> {
+    node.as_deref().map(|bytes| {
+        RawTrieNodeView::decode_with_size(bytes).expect("Retrieved nodes are viewable")
+    })
+}
+
 impl Crumb {
     fn increment(&mut self) {
-        self.status = match (&self.status, &self.node.node) {
-            (_, &TrieNode::Empty) => CrumbStatus::Exiting,
+        let status = match (&self.status, view(&self.node)) {
+            (_, None) => CrumbStatus::Exiting,
             (&CrumbStatus::Entering, _) => CrumbStatus::At,
-            (&CrumbStatus::At, &TrieNode::Branch(_, _)) => CrumbStatus::AtChild(0),
-            (&CrumbStatus::AtChild(x), &TrieNode::Branch(_, _)) if x < 15 => {
+            (&CrumbStatus::At, Some(RawTrieNodeView::Branch(_, _))) => CrumbStatus::AtChild(0),
+            (&CrumbStatus::AtChild(x), Some(RawTrieNodeView::Branch(_, _))) if x < 15 => {
                 CrumbStatus::AtChild(x + 1)
             }
             _ => CrumbStatus::Exiting,
-        }
+        };
+        self.status = status;
     }
 }
 
@@ -59,7 +70,7 @@ impl<'a> TrieIterator<'a> {
             key_nibbles: Vec::with_capacity(64),
             root: *root,
         };
-        let node = trie.retrieve_node(root)?;
+        let node = trie.retrieve_raw_node(root)?;
         r.descend_into_node(node);
         Ok(r)
     }
@@ -78,12 +89,12 @@ impl<'a> TrieIterator<'a> {
         self.key_nibbles.clear();
         let mut hash = self.root;
         loop {
-            let node = self.trie.retrieve_node(&hash)?;
-            self.trail.push(Crumb { status: CrumbStatus::Entering, node });
-            let Crumb { status, node } = self.trail.last_mut().unwrap();
-            match &node.node {
-                TrieNode::Empty => break,
-                TrieNode::Leaf(leaf_key, _) => {
+            let node = self.trie.retrieve_raw_node(&hash)?;
+            self.trail.push(Crumb { status: CrumbStatus::Entering, node: node.clone() });
+            let status = &mut self.trail.last_mut().unwrap().status;
+            match view(&node) {
+                None => break,
+                Some(RawTrieNodeView::Leaf(leaf_key, _, _)) => {
                     let existing_key = NibbleSlice::from_encoded(leaf_key).0;
                     if existing_key < key {
                         self.key_nibbles.extend(existing_key.iter());
@@ -91,26 +102,26 @@ impl<'a> TrieIterator<'a> {
                     }
                     break;
                 }
-                TrieNode::Branch(children, _) => {
+                Some(RawTrieNodeView::Branch(children, _)) => {
                     if key.is_empty() {
                         break;
                     } else {
                         let idx = key.at(0) as usize;
                         self.key_nibbles.push(key.at(0));
                         *status = CrumbStatus::AtChild(idx as usize);
-                        if let Some(child) = &children[idx] {
-                            hash = *child.unwrap_hash();
+                        if let Some(child) = children.get(idx) {
+                            hash = child;
                             key = key.mid(1);
                         } else {
                             break;
                         }
                     }
                 }
-                TrieNode::Extension(ext_key, child) => {
+                Some(RawTrieNodeView::Extension(ext_key, child)) => {
                     let existing_key = NibbleSlice::from_encoded(ext_key).0;
                     if key.starts_with(&existing_key) {
                         key = key.mid(existing_key.len());
-                        hash = *child.unwrap_hash();
+                        hash = child;
                         *status = CrumbStatus::At;
                         self.key_nibbles.extend(existing_key.iter());
                     } else {
@@ -126,7 +137,7 @@ impl<'a> TrieIterator<'a> {
         Ok(hash)
     }
 
-    fn descend_into_node(&mut self, node: TrieNodeWithSize) {
+    fn descend_into_node(&mut self, node: Option<Arc<[u8]>>) {
         self.trail.push(Crumb { status: CrumbStatus::Entering, node });
     }
 
@@ -140,9 +151,9 @@ impl<'a> TrieIterator<'a> {
 
     fn has_value(&self) -> bool {
         match self.trail.last() {
-            Some(b) => match (&b.status, &b.node.node) {
-                (CrumbStatus::At, TrieNode::Branch(_, Some(_))) => true,
-                (CrumbStatus::At, TrieNode::Leaf(_, _)) => true,
+            Some(b) => match (&b.status, view(&b.node)) {
+                (CrumbStatus::At, Some(RawTrieNodeView::Branch(_, Some(_)))) => true,
+                (CrumbStatus::At, Some(RawTrieNodeView::Leaf(_, _, _))) => true,
                 _ => false,
             },
             None => false, // Trail finished
@@ -152,57 +163,55 @@ impl<'a> TrieIterator<'a> {
     fn iter_step(&mut self) -> Option<IterStep> {
         self.trail.last_mut()?.increment();
         let b = self.trail.last().expect("Trail finished.");
-        match (b.status.clone(), &b.node.node) {
+        match (b.status.clone(), view(&b.node)) {
             (CrumbStatus::Exiting, n) => {
                 match n {
-                    TrieNode::Leaf(ref key, _) | TrieNode::Extension(ref key, _) => {
+                    Some(RawTrieNodeView::Leaf(key, _, _))
+                    | Some(RawTrieNodeView::Extension(key, _)) => {
                         let existing_key = NibbleSlice::from_encoded(key).0;
                         let l = self.key_nibbles.len();
                         self.key_nibbles.truncate(l - existing_key.len());
                     }
-                    TrieNode::Branch(_, _) => {
+                    Some(RawTrieNodeView::Branch(_, _)) => {
                         self.key_nibbles.pop();
                     }
-                    _ => {}
+                    None => {}
                 }
                 Some(IterStep::PopTrail)
             }
-            (CrumbStatus::At, TrieNode::Branch(_, Some(value))) => {
-                let hash = match value {
-                    ValueHandle::HashAndSize(_, hash) => *hash,
-                    ValueHandle::InMemory(_node) => unreachable!(),
-                };
+            (CrumbStatus::At, Some(RawTrieNodeView::Branch(_, Some((_, hash))))) => {
                 Some(IterStep::Value(hash))
             }
-            (CrumbStatus::At, TrieNode::Branch(_, None)) => Some(IterStep::Continue),
-            (CrumbStatus::At, TrieNode::Leaf(key, value)) => {
-                let hash = match value {
-                    ValueHandle::HashAndSize(_, hash) => *hash,
-                    ValueHandle::InMemory(_node) => unreachable!(),
-                };
+            (CrumbStatus::At, Some(RawTrieNodeView::Branch(_, None))) => Some(IterStep::Continue),
+            (CrumbStatus::At, Some(RawTrieNodeView::Leaf(key, _, hash))) => {
                 let key = NibbleSlice::from_encoded(key).0;
                 self.key_nibbles.extend(key.iter());
                 Some(IterStep::Value(hash))
             }
-            (CrumbStatus::At, TrieNode::Extension(key, child)) => {
-                let hash = *child.unwrap_hash();
+            (CrumbStatus::At, Some(RawTrieNodeView::Extension(key, child))) => {
                 let key = NibbleSlice::from_encoded(key).0;
                 self.key_nibbles.extend(key.iter());
-                Some(IterStep::Descend(hash))
-            }
-            (CrumbStatus::AtChild(i), TrieNode::Branch(children, _)) if children[i].is_some() => {
-                match i {
-                    0 => self.key_nibbles.push(0),
-                    i => *self.key_nibbles.last_mut().expect("Pushed child value before") = i as u8,
-                }
-                let hash = *children[i].as_ref().unwrap().unwrap_hash();
-                Some(IterStep::Descend(hash))
+                Some(IterStep::Descend(child))
             }
-            (CrumbStatus::AtChild(i), TrieNode::Branch(_, _)) => {
-                if i == 0 {
-                    self.key_nibbles.push(0);
+            (CrumbStatus::AtChild(i), Some(RawTrieNodeView::Branch(children, _))) => {
+                match children.get(i) {
+                    Some(hash) => {
+                        match i {
+                            0 => self.key_nibbles.push(0),
+                            i => {
+                                *self.key_nibbles.last_mut().expect("Pushed child value before") =
+                                    i as u8
+                            }
+                        }
+                        Some(IterStep::Descend(hash))
+                    }
+                    None => {
+                        if i == 0 {
+                            self.key_nibbles.push(0);
+                        }
+                        Some(IterStep::Continue)
+                    }
                 }
-                Some(IterStep::Continue)
             }
             _ => panic!("Should never see Entering or AtChild without a Branch here."),
         }
@@ -231,8 +240,7 @@ impl<'a> TrieIterator<'a> {
         let mut trie_items = vec![];
         for item in self {
             let trie_item = item?;
-            let key_encoded: Vec<_> = NibbleSlice::new(&trie_item.0).iter().collect();
-            if &key_encoded[..] >= path_end {
+            if NibbleSlice::new(&trie_item.0).iter().ge(path_end.iter().copied()) {
                 return Ok(trie_items);
             }
             trie_items.push(trie_item);
@@ -279,7 +287,7 @@ impl<'a> TrieIterator<'a> {
                     if self.key_nibbles[prefix..] >= path_end[prefix..] {
                         break;
                     }
-                    let node = self.trie.retrieve_node(&hash)?;
+                    let node = self.trie.retrieve_raw_node(&hash)?;
                     self.descend_into_node(node);
                     nodes_list.push(TrieTraversalItem { hash, key: None });
                 }
@@ -314,7 +322,7 @@ impl<'a> Iterator for TrieIterator<'a> {
                 IterStep::PopTrail => {
                     self.trail.pop();
                 }
-                IterStep::Descend(hash) => match self.trie.retrieve_node(&hash) {
+                IterStep::Descend(hash) => match self.trie.retrieve_raw_node(&hash) {
                     Ok(node) => self.descend_into_node(node),
                     Err(e) => return Some(Err(e)),
                 },
@@ -471,7 +479,7 @@ mod tests {
                     IterStep::PopTrail => {
                         iterator.trail.pop();
                     }
-                    IterStep::Descend(hash) => match iterator.trie.retrieve_node(&hash) {
+                    IterStep::Descend(hash) => match iterator.trie.retrieve_raw_node(&hash) {
                         Ok(node) => iterator.descend_into_node(node),
                         Err(e) => panic!("Unexpected error: {}", e),
                     },
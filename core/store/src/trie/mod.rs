@@ -1,29 +1,33 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt;
-use std::io::{Cursor, Read, Write};
+use std::sync::Arc;
 
 use borsh::{BorshDeserialize, BorshSerialize};
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
 use near_primitives::challenge::PartialState;
 use near_primitives::contract::ContractCode;
 use near_primitives::hash::{hash, CryptoHash};
 pub use near_primitives::shard_layout::ShardUId;
-use near_primitives::types::{StateRoot, StateRootNode};
+use near_primitives::types::{StateRoot, StateRootNode, TrieCacheMode};
 
 use crate::trie::insert_delete::NodesStorage;
 use crate::trie::iterator::TrieIterator;
 use crate::trie::nibble_slice::NibbleSlice;
+use crate::trie::raw_node::{RawTrieNode, RawTrieNodeView, RawTrieNodeWithSize};
 pub use crate::trie::shard_tries::{KeyForStateChanges, ShardTries, WrappedTrieChanges};
+pub use crate::trie::trie_storage::{CachedTrieNode, TrieCacheKind, TrieCacheStats};
+use crate::trie::trie_storage::{
+    TouchedNodesCounter, TrieMemoryPartialStorage, TrieRecordingStorage, TrieStorage,
+};
 pub(crate) use crate::trie::trie_storage::{TrieCache, TrieCachingStorage};
-use crate::trie::trie_storage::{TrieMemoryPartialStorage, TrieRecordingStorage, TrieStorage};
 use crate::StorageError;
 
 mod insert_delete;
 pub mod iterator;
 mod nibble_slice;
+pub mod raw_node;
 mod shard_tries;
 pub mod split_state;
 mod state_parts;
@@ -249,160 +253,6 @@ impl TrieNode {
     }
 }
 
-#[derive(Debug, Eq, PartialEq)]
-#[allow(clippy::large_enum_variant)]
-enum RawTrieNode {
-    Leaf(Vec<u8>, u32, CryptoHash),
-    Branch([Option<CryptoHash>; 16], Option<(u32, CryptoHash)>),
-    Extension(Vec<u8>, CryptoHash),
-}
-
-/// Trie node + memory cost of its subtree
-/// memory_usage is serialized, stored, and contributes to hash
-#[derive(Debug, Eq, PartialEq)]
-struct RawTrieNodeWithSize {
-    node: RawTrieNode,
-    memory_usage: u64,
-}
-
-const LEAF_NODE: u8 = 0;
-const BRANCH_NODE_NO_VALUE: u8 = 1;
-const BRANCH_NODE_WITH_VALUE: u8 = 2;
-const EXTENSION_NODE: u8 = 3;
-
-fn decode_children(cursor: &mut Cursor<&[u8]>) -> Result<[Option<CryptoHash>; 16], std::io::Error> {
-    let mut children: [Option<CryptoHash>; 16] = Default::default();
-    let bitmap = cursor.read_u16::<LittleEndian>()?;
-    let mut pos = 1;
-    for child in &mut children {
-        if bitmap & pos != 0 {
-            let mut arr = [0; 32];
-            cursor.read_exact(&mut arr)?;
-            *child = Some(CryptoHash::try_from(&arr[..]).unwrap());
-        }
-        pos <<= 1;
-    }
-    Ok(children)
-}
-
-impl RawTrieNode {
-    fn encode_into(&self, out: &mut Vec<u8>) -> Result<(), std::io::Error> {
-        let mut cursor = Cursor::new(out);
-        // size in state_parts = size + 8 for RawTrieNodeWithSize + 8 for borsh vector length
-        match &self {
-            // size <= 1 + 4 + 4 + 32 + key_length + value_length
-            RawTrieNode::Leaf(key, value_length, value_hash) => {
-                cursor.write_u8(LEAF_NODE)?;
-                cursor.write_u32::<LittleEndian>(key.len() as u32)?;
-                cursor.write_all(key)?;
-                cursor.write_u32::<LittleEndian>(*value_length)?;
-                cursor.write_all(value_hash.as_ref())?;
-            }
-            // size <= 1 + 4 + 32 + value_length + 2 + 32 * num_children
-            RawTrieNode::Branch(children, value) => {
-                if let Some((value_length, value_hash)) = value {
-                    cursor.write_u8(BRANCH_NODE_WITH_VALUE)?;
-                    cursor.write_u32::<LittleEndian>(*value_length)?;
-                    cursor.write_all(value_hash.as_ref())?;
-                } else {
-                    cursor.write_u8(BRANCH_NODE_NO_VALUE)?;
-                }
-                let mut bitmap: u16 = 0;
-                let mut pos: u16 = 1;
-                for child in children.iter() {
-                    if child.is_some() {
-                        bitmap |= pos
-                    }
-                    pos <<= 1;
-                }
-                cursor.write_u16::<LittleEndian>(bitmap)?;
-                for child in children.iter() {
-                    if let Some(hash) = child {
-                        cursor.write_all(hash.as_ref())?;
-                    }
-                }
-            }
-            // size <= 1 + 4 + key_length + 32
-            RawTrieNode::Extension(key, child) => {
-                cursor.write_u8(EXTENSION_NODE)?;
-                cursor.write_u32::<LittleEndian>(key.len() as u32)?;
-                cursor.write_all(key)?;
-                cursor.write_all(child.as_ref())?;
-            }
-        }
-        Ok(())
-    }
-
-    #[allow(dead_code)]
-    fn encode(&self) -> Result<Vec<u8>, std::io::Error> {
-        let mut out = Vec::new();
-        self.encode_into(&mut out)?;
-        Ok(out)
-    }
-
-    fn decode(bytes: &[u8]) -> Result<Self, std::io::Error> {
-        let mut cursor = Cursor::new(bytes);
-        match cursor.read_u8()? {
-            LEAF_NODE => {
-                let key_length = cursor.read_u32::<LittleEndian>()?;
-                let mut key = vec![0; key_length as usize];
-                cursor.read_exact(&mut key)?;
-                let value_length = cursor.read_u32::<LittleEndian>()?;
-                let mut arr = [0; 32];
-                cursor.read_exact(&mut arr)?;
-                let value_hash = CryptoHash(arr);
-                Ok(RawTrieNode::Leaf(key, value_length, value_hash))
-            }
-            BRANCH_NODE_NO_VALUE => {
-                let children = decode_children(&mut cursor)?;
-                Ok(RawTrieNode::Branch(children, None))
-            }
-            BRANCH_NODE_WITH_VALUE => {
-                let value_length = cursor.read_u32::<LittleEndian>()?;
-                let mut arr = [0; 32];
-                cursor.read_exact(&mut arr)?;
-                let value_hash = CryptoHash(arr);
-                let children = decode_children(&mut cursor)?;
-                Ok(RawTrieNode::Branch(children, Some((value_length, value_hash))))
-            }
-            EXTENSION_NODE => {
-                let key_length = cursor.read_u32::<LittleEndian>()?;
-                let mut key = vec![0; key_length as usize];
-                cursor.read_exact(&mut key)?;
-                let mut child = [0; 32];
-                cursor.read_exact(&mut child)?;
-                Ok(RawTrieNode::Extension(key, CryptoHash(child)))
-            }
-            _ => Err(std::io::Error::new(std::io::ErrorKind::Other, "Wrong type")),
-        }
-    }
-}
-
-impl RawTrieNodeWithSize {
-    fn encode_into(&self, out: &mut Vec<u8>) -> Result<(), std::io::Error> {
-        self.node.encode_into(out)?;
-        out.write_u64::<LittleEndian>(self.memory_usage)
-    }
-
-    #[allow(dead_code)]
-    fn encode(&self) -> Result<Vec<u8>, std::io::Error> {
-        let mut out = Vec::new();
-        self.encode_into(&mut out)?;
-        Ok(out)
-    }
-
-    fn decode(bytes: &[u8]) -> Result<Self, std::io::Error> {
-        if bytes.len() < 8 {
-            return Err(std::io::Error::new(std::io::ErrorKind::Other, "Wrong type"));
-        }
-        let node = RawTrieNode::decode(&bytes[0..bytes.len() - 8])?;
-        let mut arr: [u8; 8] = Default::default();
-        arr.copy_from_slice(&bytes[bytes.len() - 8..]);
-        let memory_usage = u64::from_le_bytes(arr);
-        Ok(RawTrieNodeWithSize { node, memory_usage })
-    }
-}
-
 pub struct Trie {
     pub(crate) storage: Box<dyn TrieStorage>,
 }
@@ -478,6 +328,8 @@ impl Trie {
             store: storage.store.clone(),
             shard_uid: storage.shard_uid,
             recorded: RefCell::new(Default::default()),
+            recorded_size: Cell::new(0),
+            counter: TouchedNodesCounter::new(storage.cache_mode.get()),
         };
         Trie { storage: Box::new(storage) }
     }
@@ -501,10 +353,60 @@ impl Trie {
             storage: Box::new(TrieMemoryPartialStorage {
                 recorded_storage,
                 visited_nodes: Default::default(),
+                visited_size: Cell::new(0),
+                counter: TouchedNodesCounter::new(TrieCacheMode::CachingShard),
             }),
         }
     }
 
+    /// Returns the size in bytes of trie nodes added to the storage proof since the last
+    /// [`Self::reset_recorded_storage_size`].  Nodes are counted the same way whether the proof
+    /// is recorded, replayed or not recorded at all, so the size is the same on every node.
+    pub fn recorded_storage_size(&self) -> u64 {
+        if let Some(storage) = self.storage.as_caching_storage() {
+            storage.visited_size.get()
+        } else if let Some(storage) = self.storage.as_recording_storage() {
+            storage.recorded_size.get()
+        } else {
+            self.storage.as_partial_storage().map_or(0, |storage| storage.visited_size.get())
+        }
+    }
+
+    pub fn reset_recorded_storage_size(&self) {
+        if let Some(storage) = self.storage.as_caching_storage() {
+            storage.visited_size.set(0);
+        } else if let Some(storage) = self.storage.as_recording_storage() {
+            storage.recorded_size.set(0);
+        } else if let Some(storage) = self.storage.as_partial_storage() {
+            storage.visited_size.set(0);
+        }
+    }
+
+    /// Returns the size in bytes of trie nodes and values retrieved from the storage since the
+    /// last [`Self::reset_loaded_nodes_size`].  Each node is counted once and nodes served from
+    /// the chunk cache aren't counted, so the size doesn't depend on the state of the shard cache
+    /// or on whether the storage proof is recorded.
+    pub fn loaded_nodes_size(&self) -> u64 {
+        if let Some(storage) = self.storage.as_caching_storage() {
+            storage.loaded.size()
+        } else if let Some(storage) = self.storage.as_recording_storage() {
+            storage.counter.loaded.size()
+        } else {
+            self.storage.as_partial_storage().map_or(0, |storage| storage.counter.loaded.size())
+        }
+    }
+
+    /// Forgets the nodes loaded so far, called before applying each receipt.
+    pub fn reset_loaded_nodes_size(&self) {
+        if let Some(storage) = self.storage.as_caching_storage() {
+            storage.loaded.reset();
+        } else if let Some(storage) = self.storage.as_recording_storage() {
+            storage.counter.loaded.reset();
+        } else if let Some(storage) = self.storage.as_partial_storage() {
+            storage.counter.loaded.reset();
+        }
+    }
+
     #[cfg(test)]
     fn memory_usage_verify(&self, memory: &NodesStorage, handle: NodeHandle) -> u64 {
         if self.storage.as_recording_storage().is_some() {
@@ -606,6 +508,22 @@ impl Trie {
         }
     }
 
+    /// Serialized node with the given hash, `None` for the empty trie.  It's checked to be
+    /// viewable, so that it can be viewed again without copying it.
+    fn retrieve_raw_node(&self, hash: &CryptoHash) -> Result<Option<Arc<[u8]>>, StorageError> {
+        if *hash == Trie::empty_root() {
+            return Ok(None);
+        }
+        let bytes = self.storage.retrieve_raw_bytes(hash)?;
+        match RawTrieNodeView::decode_with_size(&bytes) {
+            Ok(_) => Ok(Some(bytes)),
+            Err(_) => Err(StorageError::StorageInconsistentState(format!(
+                "Failed to decode node {}",
+                hash
+            ))),
+        }
+    }
+
     pub fn retrieve_root_node(&self, root: &StateRoot) -> Result<StateRootNode, StorageError> {
         if *root == Trie::empty_root() {
             return Ok(StateRootNode::empty());
@@ -635,20 +553,20 @@ impl Trie {
                 return Ok(None);
             }
             let bytes = self.storage.retrieve_raw_bytes(&hash)?;
-            let node = RawTrieNodeWithSize::decode(&bytes).map_err(|_| {
+            let node = RawTrieNodeView::decode_with_size(&bytes).map_err(|_| {
                 StorageError::StorageInconsistentState("RawTrieNode decode failed".to_string())
             })?;
 
-            match node.node {
-                RawTrieNode::Leaf(existing_key, value_length, value_hash) => {
-                    if NibbleSlice::from_encoded(&existing_key).0 == key {
+            match node {
+                RawTrieNodeView::Leaf(existing_key, value_length, value_hash) => {
+                    if NibbleSlice::from_encoded(existing_key).0 == key {
                         return Ok(Some((value_length, value_hash)));
                     } else {
                         return Ok(None);
                     }
                 }
-                RawTrieNode::Extension(existing_key, child) => {
-                    let existing_key = NibbleSlice::from_encoded(&existing_key).0;
+                RawTrieNodeView::Extension(existing_key, child) => {
+                    let existing_key = NibbleSlice::from_encoded(existing_key).0;
                     if key.starts_with(&existing_key) {
                         hash = child;
                         key = key.mid(existing_key.len());
@@ -656,7 +574,7 @@ impl Trie {
                         return Ok(None);
                     }
                 }
-                RawTrieNode::Branch(mut children, value) => {
+                RawTrieNodeView::Branch(children, value) => {
                     if key.is_empty() {
                         match value {
                             Some((value_length, value_hash)) => {
@@ -665,7 +583,7 @@ impl Trie {
                             None => return Ok(None),
                         }
                     } else {
-                        match children[key.at(0) as usize].take() {
+                        match children.get(key.at(0) as usize) {
                             Some(x) => {
                                 hash = x;
                                 key = key.mid(1);
@@ -859,6 +777,51 @@ mod tests {
         assert_eq!(other_iter.next().unwrap().unwrap().0, b"x".to_vec());
     }
 
+    #[test]
+    fn test_trie_odd_nibble_boundaries() {
+        let tries = create_tries();
+        let trie = tries.get_trie_for_shard(ShardUId::single_shard());
+        // The keys share three nibbles so the extension and the leaves split a byte in halves.
+        let changes = vec![
+            (vec![0x12], Some(b"a".to_vec())),
+            (vec![0x12, 0x34], Some(b"b".to_vec())),
+            (vec![0x12, 0x34, 0x56], Some(b"c".to_vec())),
+            (vec![0x12, 0x35], Some(b"d".to_vec())),
+            (vec![0x12, 0x35, 0x60], Some(b"e".to_vec())),
+        ];
+        let root = test_populate_trie(
+            &tries,
+            &Trie::empty_root(),
+            ShardUId::single_shard(),
+            changes.clone(),
+        );
+        for (key, value) in changes.iter() {
+            assert_eq!(&trie.get(&root, key).unwrap(), value);
+        }
+        assert_eq!(trie.get(&root, &[0x12, 0x3]).unwrap(), None);
+        assert_eq!(trie.get(&root, &[0x12, 0x36]).unwrap(), None);
+        assert_eq!(trie.get(&root, &[0x12, 0x35, 0x6]).unwrap(), None);
+
+        let items: Vec<_> = trie.iter(&root).unwrap().map(Result::unwrap).collect();
+        let expected: Vec<_> =
+            changes.iter().map(|(k, v)| (k.clone(), v.clone().unwrap())).collect();
+        assert_eq!(items, expected);
+
+        for (seek_key, first) in [
+            (vec![0x12, 0x33], vec![0x12, 0x34]),
+            (vec![0x12, 0x34, 0x00], vec![0x12, 0x34, 0x56]),
+            (vec![0x12, 0x34, 0x57], vec![0x12, 0x35]),
+            (vec![0x12, 0x35, 0x5f], vec![0x12, 0x35, 0x60]),
+        ] {
+            let mut iter = trie.iter(&root).unwrap();
+            iter.seek(&seek_key).unwrap();
+            assert_eq!(iter.next().unwrap().unwrap().0, first);
+        }
+        let mut iter = trie.iter(&root).unwrap();
+        iter.seek(&[0x12, 0x35, 0x61]).unwrap();
+        assert!(iter.next().is_none());
+    }
+
     #[test]
     fn test_trie_leaf_into_branch() {
         let tries = create_tries_complex(SHARD_VERSION, 2);
@@ -1066,6 +1029,54 @@ mod tests {
         }
     }
 
+    /// Refcount updates of trie changes written together are coalesced on commit, which must
+    /// leave the same refcounted values as writing the trie changes one by one.
+    #[test]
+    fn test_coalesced_refcounts_match_separate_commits() {
+        use rand::SeedableRng;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let shard_uid = ShardUId::single_shard();
+        let separate_tries = create_tries();
+        let trie = separate_tries.get_trie_for_shard(shard_uid);
+        let mut state_root = Trie::empty_root();
+        let mut all_trie_changes = vec![];
+        for i in 0..30 {
+            let changes = if i == 29 {
+                // Deletes everything at the end.
+                trie.iter(&state_root).unwrap().map(|item| (item.unwrap().0, None)).collect()
+            } else {
+                gen_changes(&mut rng, 50)
+            };
+            let trie_changes = trie.update(&state_root, changes.into_iter()).unwrap();
+            let (store_update, root) = separate_tries.apply_all(&trie_changes, shard_uid).unwrap();
+            store_update.commit().unwrap();
+            state_root = root;
+            all_trie_changes.push(trie_changes);
+
+            let coalesced_tries = create_tries();
+            let mut store_update = coalesced_tries.get_store().store_update();
+            for trie_changes in all_trie_changes.iter() {
+                let (update, _) = coalesced_tries.apply_all(trie_changes, shard_uid).unwrap();
+                store_update.merge(update);
+            }
+            let num_ops = store_update.num_ops();
+            store_update.coalesce_refcount_updates();
+            assert!(i == 0 || store_update.num_ops() < num_ops);
+            store_update.commit().unwrap();
+
+            let contents = |tries: &ShardTries| {
+                tries
+                    .get_store()
+                    .iter_without_rc_logic(ColState)
+                    .filter(|(_, value)| !value.is_empty())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(contents(&separate_tries), contents(&coalesced_tries));
+        }
+        assert_eq!(state_root, Trie::empty_root());
+    }
+
     #[test]
     fn test_trie_restart() {
         let store = create_test_store();
@@ -1114,6 +1125,42 @@ mod tests {
         assert_eq!(trie3.get(&root, b"doge"), Err(StorageError::TrieNodeMissing));
     }
 
+    #[test]
+    fn test_trie_recorded_storage_size() {
+        let store = create_test_store();
+        let tries = ShardTries::new(store, 0, 1);
+        let changes = vec![
+            (b"doge".to_vec(), Some(b"coin".to_vec())),
+            (b"dog".to_vec(), Some(b"puppy".to_vec())),
+            (b"horse".to_vec(), Some(b"stallion".to_vec())),
+        ];
+        let root =
+            test_populate_trie(&tries, &Trie::empty_root(), ShardUId::single_shard(), changes);
+        // Reads `dog`, then `dog` and `horse`, returning the sizes added by both.
+        let read = |trie: &Trie| {
+            trie.get(&root, b"dog").unwrap();
+            let dog_size = trie.recorded_storage_size();
+            trie.reset_recorded_storage_size();
+            trie.get(&root, b"dog").unwrap();
+            // Nodes of the previous read don't count again.
+            assert_eq!(trie.recorded_storage_size(), 0);
+            trie.get(&root, b"horse").unwrap();
+            (dog_size, trie.recorded_storage_size())
+        };
+
+        let trie = tries.get_trie_for_shard(ShardUId::single_shard()).recording_reads();
+        let (dog_size, horse_size) = read(&trie);
+        assert!(dog_size > 0);
+        assert!(horse_size > 0);
+
+        // Reading without recording or replaying the same reads from the proof gives the same
+        // sizes.
+        let caching_trie = tries.get_trie_for_shard(ShardUId::single_shard());
+        assert_eq!(read(&caching_trie), (dog_size, horse_size));
+        let partial_trie = Trie::from_recorded_storage(trie.recorded_storage().unwrap());
+        assert_eq!(read(&partial_trie), (dog_size, horse_size));
+    }
+
     #[test]
     fn test_trie_recording_reads_update() {
         let store = create_test_store();
@@ -352,4 +352,31 @@ mod tests {
         let nibbles: Vec<u8> = (0..n.len()).map(|i| n.at(i)).collect();
         assert_eq!(nibbles, vec![7, 4, 6, 5, 7, 3, 7, 4]);
     }
+
+    #[test]
+    fn odd_length_boundaries() {
+        // Odd-length slices end in the middle of a byte, the other half must never leak in.
+        let n = NibbleSlice::new(&[0x12, 0x34]);
+        let m = NibbleSlice::new(&[0x12, 0x35]);
+        assert_eq!(n.mid(1).common_prefix(&m.mid(1)), 2);
+        assert_eq!(n.common_prefix(&m), 3);
+        assert!(n.mid(1) < m.mid(1));
+        assert!(!n.starts_with(&m));
+
+        let (odd, is_leaf) = NibbleSlice::from_encoded(&[0x31, 0x23]);
+        assert!(is_leaf);
+        assert_eq!(odd.len(), 3);
+        assert_eq!(odd.iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert!(n.starts_with(&odd));
+        assert!(!m.mid(1).starts_with(&odd));
+        assert_eq!(odd.common_prefix(&n), 3);
+        assert!(odd < n && odd < m);
+        assert!(odd.mid(3).is_empty());
+
+        // Iterators compare nibble-wise like slices do.
+        assert!(n.iter().lt(m.iter()));
+        assert!(odd.iter().lt(n.iter()));
+        assert!(n.mid(3).iter().ge([4u8].iter().copied()));
+        assert!(!n.mid(3).iter().ge([5u8].iter().copied()));
+    }
 }
@@ -0,0 +1,553 @@
+//! Decoding of the trie nodes stored in `ColState`, for tools which inspect the state.
+use std::io::{Cursor, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use near_primitives::hash::CryptoHash;
+use near_primitives::shard_layout::ShardUId;
+
+use crate::db::refcount::decode_value_with_rc;
+use crate::trie::nibble_slice::NibbleSlice;
+use crate::trie::TrieCachingStorage;
+use crate::{DBCol, Store, Trie};
+
+/// Trie node as it's serialized in the storage.  Leaf and extension keys are nibbles in the
+/// hex-prefix encoding, see [`RawTrieNode::key_nibbles`], and values are referenced by their
+/// length and hash.
+#[derive(Debug, Eq, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum RawTrieNode {
+    Leaf(Vec<u8>, u32, CryptoHash),
+    Branch([Option<CryptoHash>; 16], Option<(u32, CryptoHash)>),
+    Extension(Vec<u8>, CryptoHash),
+}
+
+/// Trie node + memory cost of its subtree
+/// memory_usage is serialized, stored, and contributes to hash
+#[derive(Debug, Eq, PartialEq)]
+pub struct RawTrieNodeWithSize {
+    pub node: RawTrieNode,
+    pub memory_usage: u64,
+}
+
+const LEAF_NODE: u8 = 0;
+const BRANCH_NODE_NO_VALUE: u8 = 1;
+const BRANCH_NODE_WITH_VALUE: u8 = 2;
+const EXTENSION_NODE: u8 = 3;
+
+fn decode_children(cursor: &mut Cursor<&[u8]>) -> Result<[Option<CryptoHash>; 16], std::io::Error> {
+    let mut children: [Option<CryptoHash>; 16] = Default::default();
+    let bitmap = cursor.read_u16::<LittleEndian>()?;
+    let mut pos = 1;
+    for child in &mut children {
+        if bitmap & pos != 0 {
+            let mut arr = [0; 32];
+            cursor.read_exact(&mut arr)?;
+            *child = Some(CryptoHash::try_from(&arr[..]).unwrap());
+        }
+        pos <<= 1;
+    }
+    Ok(children)
+}
+
+impl RawTrieNode {
+    /// Nibbles of the key of a leaf or an extension node, `None` for branches.
+    pub fn key_nibbles(&self) -> Option<Vec<u8>> {
+        match self {
+            RawTrieNode::Leaf(key, _, _) | RawTrieNode::Extension(key, _) => {
+                Some(NibbleSlice::from_encoded(key).0.iter().collect())
+            }
+            RawTrieNode::Branch(_, _) => None,
+        }
+    }
+
+    pub(crate) fn encode_into(&self, out: &mut Vec<u8>) -> Result<(), std::io::Error> {
+        let mut cursor = Cursor::new(out);
+        // size in state_parts = size + 8 for RawTrieNodeWithSize + 8 for borsh vector length
+        match &self {
+            // size <= 1 + 4 + 4 + 32 + key_length + value_length
+            RawTrieNode::Leaf(key, value_length, value_hash) => {
+                cursor.write_u8(LEAF_NODE)?;
+                cursor.write_u32::<LittleEndian>(key.len() as u32)?;
+                cursor.write_all(key)?;
+                cursor.write_u32::<LittleEndian>(*value_length)?;
+                cursor.write_all(value_hash.as_ref())?;
+            }
+            // size <= 1 + 4 + 32 + value_length + 2 + 32 * num_children
+            RawTrieNode::Branch(children, value) => {
+                if let Some((value_length, value_hash)) = value {
+                    cursor.write_u8(BRANCH_NODE_WITH_VALUE)?;
+                    cursor.write_u32::<LittleEndian>(*value_length)?;
+                    cursor.write_all(value_hash.as_ref())?;
+                } else {
+                    cursor.write_u8(BRANCH_NODE_NO_VALUE)?;
+                }
+                let mut bitmap: u16 = 0;
+                let mut pos: u16 = 1;
+                for child in children.iter() {
+                    if child.is_some() {
+                        bitmap |= pos
+                    }
+                    pos <<= 1;
+                }
+                cursor.write_u16::<LittleEndian>(bitmap)?;
+                for child in children.iter() {
+                    if let Some(hash) = child {
+                        cursor.write_all(hash.as_ref())?;
+                    }
+                }
+            }
+            // size <= 1 + 4 + key_length + 32
+            RawTrieNode::Extension(key, child) => {
+                cursor.write_u8(EXTENSION_NODE)?;
+                cursor.write_u32::<LittleEndian>(key.len() as u32)?;
+                cursor.write_all(key)?;
+                cursor.write_all(child.as_ref())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, std::io::Error> {
+        let mut out = Vec::new();
+        self.encode_into(&mut out)?;
+        Ok(out)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, std::io::Error> {
+        let mut cursor = Cursor::new(bytes);
+        match cursor.read_u8()? {
+            LEAF_NODE => {
+                let key_length = cursor.read_u32::<LittleEndian>()?;
+                let mut key = vec![0; key_length as usize];
+                cursor.read_exact(&mut key)?;
+                let value_length = cursor.read_u32::<LittleEndian>()?;
+                let mut arr = [0; 32];
+                cursor.read_exact(&mut arr)?;
+                let value_hash = CryptoHash(arr);
+                Ok(RawTrieNode::Leaf(key, value_length, value_hash))
+            }
+            BRANCH_NODE_NO_VALUE => {
+                let children = decode_children(&mut cursor)?;
+                Ok(RawTrieNode::Branch(children, None))
+            }
+            BRANCH_NODE_WITH_VALUE => {
+                let value_length = cursor.read_u32::<LittleEndian>()?;
+                let mut arr = [0; 32];
+                cursor.read_exact(&mut arr)?;
+                let value_hash = CryptoHash(arr);
+                let children = decode_children(&mut cursor)?;
+                Ok(RawTrieNode::Branch(children, Some((value_length, value_hash))))
+            }
+            EXTENSION_NODE => {
+                let key_length = cursor.read_u32::<LittleEndian>()?;
+                let mut key = vec![0; key_length as usize];
+                cursor.read_exact(&mut key)?;
+                let mut child = [0; 32];
+                cursor.read_exact(&mut child)?;
+                Ok(RawTrieNode::Extension(key, CryptoHash(child)))
+            }
+            _ => Err(std::io::Error::new(std::io::ErrorKind::Other, "Wrong type")),
+        }
+    }
+}
+
+/// Children of a branch in a [`RawTrieNodeView`]: the bitmap of the present children and their
+/// hashes, in order.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RawChildrenView<'a> {
+    bitmap: u16,
+    hashes: &'a [u8],
+}
+
+impl<'a> RawChildrenView<'a> {
+    pub(crate) fn get(&self, index: usize) -> Option<CryptoHash> {
+        let bit = 1u16 << index;
+        if self.bitmap & bit == 0 {
+            return None;
+        }
+        let start = (self.bitmap & (bit - 1)).count_ones() as usize * 32;
+        Some(CryptoHash::try_from(&self.hashes[start..start + 32]).unwrap())
+    }
+}
+
+/// Serialized trie node viewed in place, so that walking down the trie doesn't allocate.  The
+/// keys are borrowed as encoded and the children are only decoded when asked for.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum RawTrieNodeView<'a> {
+    Leaf(&'a [u8], u32, CryptoHash),
+    Branch(RawChildrenView<'a>, Option<(u32, CryptoHash)>),
+    Extension(&'a [u8], CryptoHash),
+}
+
+fn read_slice<'a>(cursor: &mut Cursor<&'a [u8]>, len: usize) -> Result<&'a [u8], std::io::Error> {
+    let bytes: &'a [u8] = cursor.get_ref();
+    let start = cursor.position() as usize;
+    let slice = bytes
+        .get(start..start + len)
+        .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::UnexpectedEof))?;
+    cursor.set_position((start + len) as u64);
+    Ok(slice)
+}
+
+fn read_hash(cursor: &mut Cursor<&[u8]>) -> Result<CryptoHash, std::io::Error> {
+    let mut arr = [0; 32];
+    cursor.read_exact(&mut arr)?;
+    Ok(CryptoHash(arr))
+}
+
+impl<'a> RawTrieNodeView<'a> {
+    /// Views the node of a serialized [`RawTrieNodeWithSize`], fails where its `decode` fails.
+    pub(crate) fn decode_with_size(bytes: &'a [u8]) -> Result<Self, std::io::Error> {
+        if bytes.len() < 8 {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, "Wrong type"));
+        }
+        let mut cursor = Cursor::new(&bytes[..bytes.len() - 8]);
+        match cursor.read_u8()? {
+            LEAF_NODE => {
+                let key_length = cursor.read_u32::<LittleEndian>()?;
+                let key = read_slice(&mut cursor, key_length as usize)?;
+                let value_length = cursor.read_u32::<LittleEndian>()?;
+                Ok(RawTrieNodeView::Leaf(key, value_length, read_hash(&mut cursor)?))
+            }
+            node_type @ (BRANCH_NODE_NO_VALUE | BRANCH_NODE_WITH_VALUE) => {
+                let value = if node_type == BRANCH_NODE_WITH_VALUE {
+                    let value_length = cursor.read_u32::<LittleEndian>()?;
+                    Some((value_length, read_hash(&mut cursor)?))
+                } else {
+                    None
+                };
+                let bitmap = cursor.read_u16::<LittleEndian>()?;
+                let hashes = read_slice(&mut cursor, bitmap.count_ones() as usize * 32)?;
+                Ok(RawTrieNodeView::Branch(RawChildrenView { bitmap, hashes }, value))
+            }
+            EXTENSION_NODE => {
+                let key_length = cursor.read_u32::<LittleEndian>()?;
+                let key = read_slice(&mut cursor, key_length as usize)?;
+                Ok(RawTrieNodeView::Extension(key, read_hash(&mut cursor)?))
+            }
+            _ => Err(std::io::Error::new(std::io::ErrorKind::Other, "Wrong type")),
+        }
+    }
+}
+
+impl RawTrieNodeWithSize {
+    pub(crate) fn encode_into(&self, out: &mut Vec<u8>) -> Result<(), std::io::Error> {
+        self.node.encode_into(out)?;
+        out.write_u64::<LittleEndian>(self.memory_usage)
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, std::io::Error> {
+        let mut out = Vec::new();
+        self.encode_into(&mut out)?;
+        Ok(out)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, std::io::Error> {
+        if bytes.len() < 8 {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, "Wrong type"));
+        }
+        let node = RawTrieNode::decode(&bytes[0..bytes.len() - 8])?;
+        let mut arr: [u8; 8] = Default::default();
+        arr.copy_from_slice(&bytes[bytes.len() - 8..]);
+        let memory_usage = u64::from_le_bytes(arr);
+        Ok(RawTrieNodeWithSize { node, memory_usage })
+    }
+}
+
+/// Trie node read from `ColState` together with its refcount.
+#[derive(Debug, Eq, PartialEq)]
+pub struct StoredTrieNode {
+    pub node: RawTrieNodeWithSize,
+    pub refcount: i64,
+}
+
+/// Reads node `hash` of shard `shard_uid` from the storage, `None` if it's missing or its
+/// refcount isn't positive.
+pub fn read_trie_node(
+    store: &Store,
+    shard_uid: ShardUId,
+    hash: &CryptoHash,
+) -> Result<Option<StoredTrieNode>, std::io::Error> {
+    let key = TrieCachingStorage::get_key_from_shard_uid_and_hash(shard_uid, hash);
+    let bytes = match store.get(DBCol::ColState, &key)? {
+        Some(bytes) => bytes,
+        None => return Ok(None),
+    };
+    match decode_value_with_rc(&bytes) {
+        (Some(value), refcount) => {
+            Ok(Some(StoredTrieNode { node: RawTrieNodeWithSize::decode(value)?, refcount }))
+        }
+        (None, _) => Ok(None),
+    }
+}
+
+/// Reads the nodes on the path from `root` to `key`, stopping at the node which has the value of
+/// the key, at the node where the path diverges from the key or at the first missing node, which
+/// is returned as `None`.
+pub fn read_trie_path(
+    store: &Store,
+    shard_uid: ShardUId,
+    root: &CryptoHash,
+    key: &[u8],
+) -> Result<Vec<(CryptoHash, Option<StoredTrieNode>)>, std::io::Error> {
+    let mut path = vec![];
+    let mut hash = *root;
+    let mut key = NibbleSlice::new(key);
+    while hash != Trie::empty_root() {
+        let stored = match read_trie_node(store, shard_uid, &hash)? {
+            Some(stored) => stored,
+            None => {
+                path.push((hash, None));
+                break;
+            }
+        };
+        let next = match &stored.node.node {
+            RawTrieNode::Leaf(_, _, _) => None,
+            RawTrieNode::Extension(existing_key, child) => {
+                let existing_key = NibbleSlice::from_encoded(existing_key).0;
+                if key.starts_with(&existing_key) {
+                    key = key.mid(existing_key.len());
+                    Some(*child)
+                } else {
+                    None
+                }
+            }
+            RawTrieNode::Branch(children, _) => {
+                if key.is_empty() {
+                    None
+                } else {
+                    let child = children[key.at(0) as usize];
+                    key = key.mid(1);
+                    child
+                }
+            }
+        };
+        path.push((hash, Some(stored)));
+        match next {
+            Some(child) => hash = child,
+            None => break,
+        }
+    }
+    Ok(path)
+}
+
+/// Memory usage of the subtree of the keys which start with `prefix`, 0 if there are none.
+/// Fails if a node on the way is missing.
+pub fn read_subtree_memory_usage(
+    store: &Store,
+    shard_uid: ShardUId,
+    root: &CryptoHash,
+    prefix: &[u8],
+) -> Result<u64, std::io::Error> {
+    let mut hash = *root;
+    let mut key = NibbleSlice::new(prefix);
+    while hash != Trie::empty_root() {
+        let stored = read_trie_node(store, shard_uid, &hash)?.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, format!("missing trie node {}", hash))
+        })?;
+        if key.is_empty() {
+            return Ok(stored.node.memory_usage);
+        }
+        match stored.node.node {
+            RawTrieNode::Leaf(existing_key, _, _) => {
+                let existing_key = NibbleSlice::from_encoded(&existing_key).0;
+                return Ok(if existing_key.starts_with(&key) {
+                    stored.node.memory_usage
+                } else {
+                    0
+                });
+            }
+            RawTrieNode::Extension(existing_key, child) => {
+                let existing_key = NibbleSlice::from_encoded(&existing_key).0;
+                if existing_key.starts_with(&key) {
+                    return Ok(stored.node.memory_usage);
+                } else if key.starts_with(&existing_key) {
+                    key = key.mid(existing_key.len());
+                    hash = child;
+                } else {
+                    return Ok(0);
+                }
+            }
+            RawTrieNode::Branch(children, _) => match children[key.at(0) as usize] {
+                Some(child) => {
+                    key = key.mid(1);
+                    hash = child;
+                }
+                None => return Ok(0),
+            },
+        }
+    }
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use near_primitives::hash::{hash, CryptoHash};
+    use near_primitives::shard_layout::ShardUId;
+
+    use crate::test_utils::{create_test_store, create_tries, gen_changes, test_populate_trie};
+    use crate::{DBCol, Trie};
+
+    use super::{
+        read_subtree_memory_usage, read_trie_node, read_trie_path, RawTrieNode, RawTrieNodeView,
+        RawTrieNodeWithSize,
+    };
+
+    #[test]
+    fn test_decode_raw_nodes() {
+        let value_hash = hash(b"value");
+        let child_hash = hash(b"child");
+
+        // Leaf with the key of nibbles [1, 2, 3], a value of 5 bytes and a memory usage of 100.
+        let mut bytes = vec![0, 2, 0, 0, 0, 0x31, 0x23, 5, 0, 0, 0];
+        bytes.extend_from_slice(value_hash.as_ref());
+        bytes.extend_from_slice(&100u64.to_le_bytes());
+        let node = RawTrieNodeWithSize::decode(&bytes).unwrap();
+        assert_eq!(node.node, RawTrieNode::Leaf(vec![0x31, 0x23], 5, value_hash));
+        assert_eq!(node.node.key_nibbles(), Some(vec![1, 2, 3]));
+        assert_eq!(node.memory_usage, 100);
+        assert_eq!(node.encode().unwrap(), bytes);
+
+        // Branch with a value and the children 0 and 15.
+        let mut bytes = vec![2, 5, 0, 0, 0];
+        bytes.extend_from_slice(value_hash.as_ref());
+        bytes.extend_from_slice(&0x8001u16.to_le_bytes());
+        bytes.extend_from_slice(child_hash.as_ref());
+        bytes.extend_from_slice(CryptoHash::default().as_ref());
+        bytes.extend_from_slice(&200u64.to_le_bytes());
+        let node = RawTrieNodeWithSize::decode(&bytes).unwrap();
+        let mut children: [Option<CryptoHash>; 16] = Default::default();
+        children[0] = Some(child_hash);
+        children[15] = Some(CryptoHash::default());
+        assert_eq!(node.node, RawTrieNode::Branch(children, Some((5, value_hash))));
+        assert_eq!(node.node.key_nibbles(), None);
+        assert_eq!(node.memory_usage, 200);
+
+        // Extension with the key of nibbles [10, 11].
+        let mut bytes = vec![3, 2, 0, 0, 0, 0x00, 0xab];
+        bytes.extend_from_slice(child_hash.as_ref());
+        bytes.extend_from_slice(&300u64.to_le_bytes());
+        let node = RawTrieNodeWithSize::decode(&bytes).unwrap();
+        assert_eq!(node.node, RawTrieNode::Extension(vec![0x00, 0xab], child_hash));
+        assert_eq!(node.node.key_nibbles(), Some(vec![10, 11]));
+
+        assert!(RawTrieNodeWithSize::decode(&[4, 0, 0, 0, 0, 0, 0, 0, 0]).is_err());
+        assert!(RawTrieNodeWithSize::decode(&[0, 1, 2]).is_err());
+    }
+
+    /// The view of every node of a trie matches its decoding.
+    #[test]
+    fn test_view_raw_nodes() {
+        let mut rng = rand::thread_rng();
+        let tries = create_tries();
+        let shard_uid = ShardUId::single_shard();
+        let changes = gen_changes(&mut rng, 100);
+        test_populate_trie(&tries, &Trie::empty_root(), shard_uid, changes);
+        for (_, bytes) in tries.get_store().iter(DBCol::ColState) {
+            let node = RawTrieNodeWithSize::decode(&bytes).map(|node| node.node);
+            let view = RawTrieNodeView::decode_with_size(&bytes);
+            match (node, view) {
+                (Ok(RawTrieNode::Leaf(key, length, hash)), Ok(RawTrieNodeView::Leaf(k, l, h))) => {
+                    assert_eq!((&key[..], length, hash), (k, l, h));
+                }
+                (
+                    Ok(RawTrieNode::Branch(children, value)),
+                    Ok(RawTrieNodeView::Branch(view_children, view_value)),
+                ) => {
+                    assert_eq!(value, view_value);
+                    for (i, child) in children.iter().enumerate() {
+                        assert_eq!(*child, view_children.get(i));
+                    }
+                }
+                (Ok(RawTrieNode::Extension(key, child)), Ok(RawTrieNodeView::Extension(k, c))) => {
+                    assert_eq!((&key[..], child), (k, c));
+                }
+                // Values are stored in the same column and usually aren't nodes.
+                (Err(_), Err(_)) => {}
+                (node, view) => panic!("{:?} is viewed as {:?}", node, view),
+            }
+        }
+        assert!(RawTrieNodeView::decode_with_size(&[4, 0, 0, 0, 0, 0, 0, 0, 0]).is_err());
+        assert!(RawTrieNodeView::decode_with_size(&[0, 1, 2]).is_err());
+        // A branch with one child but a truncated hash.
+        assert!(RawTrieNodeView::decode_with_size(&[1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_read_trie_path() {
+        let tries = create_tries();
+        let shard_uid = ShardUId::single_shard();
+        let trie = tries.get_trie_for_shard(shard_uid);
+        let changes = vec![
+            (b"doge".to_vec(), Some(b"coin".to_vec())),
+            (b"dog".to_vec(), Some(b"puppy".to_vec())),
+            (b"horse".to_vec(), Some(b"stallion".to_vec())),
+        ];
+        let trie_changes = trie.update(&Trie::empty_root(), changes.into_iter()).unwrap();
+        let (store_update, root) = tries.apply_all(&trie_changes, shard_uid).unwrap();
+        store_update.commit().unwrap();
+        let store = tries.get_store();
+
+        let path = read_trie_path(&store, shard_uid, &root, b"doge").unwrap();
+        assert_eq!(path[0].0, root);
+        assert!(path
+            .iter()
+            .all(|(_, node)| node.as_ref().map_or(false, |node| node.refcount == 1)));
+        let (_, last) = path.last().unwrap();
+        let (length, value_hash) = match &last.as_ref().unwrap().node.node {
+            RawTrieNode::Leaf(_, length, value_hash) => (*length, *value_hash),
+            RawTrieNode::Branch(_, Some((length, value_hash))) => (*length, *value_hash),
+            node => panic!("unexpected node {:?}", node),
+        };
+        assert_eq!((length, value_hash), (4, hash(b"coin")));
+
+        // The path to a missing key stops where it diverges.
+        let path = read_trie_path(&store, shard_uid, &root, b"cat").unwrap();
+        assert!(path.iter().all(|(_, node)| node.is_some()));
+
+        let path = read_trie_path(&create_test_store(), shard_uid, &root, b"doge").unwrap();
+        assert_eq!(path, vec![(root, None)]);
+        assert!(read_trie_path(&store, shard_uid, &Trie::empty_root(), b"doge")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_read_subtree_memory_usage() {
+        let tries = create_tries();
+        let shard_uid = ShardUId::single_shard();
+        let trie = tries.get_trie_for_shard(shard_uid);
+        let build = |changes: Vec<(Vec<u8>, Option<Vec<u8>>)>| {
+            let trie_changes = trie.update(&Trie::empty_root(), changes.into_iter()).unwrap();
+            let (store_update, root) = tries.apply_all(&trie_changes, shard_uid).unwrap();
+            store_update.commit().unwrap();
+            root
+        };
+        let accounts = vec![
+            (b"\x00alice".to_vec(), Some(vec![1; 10])),
+            (b"\x00bob".to_vec(), Some(vec![2; 20])),
+        ];
+        let data = vec![(b"\x09alice,key".to_vec(), Some(vec![3; 1000]))];
+        let accounts_root = build(accounts.clone());
+        let data_root = build(data.clone());
+        let root = build(accounts.into_iter().chain(data).collect());
+        let store = tries.get_store();
+        let memory_usage =
+            |root| read_trie_node(&store, shard_uid, root).unwrap().unwrap().node.memory_usage;
+
+        let accounts_usage = read_subtree_memory_usage(&store, shard_uid, &root, &[0]).unwrap();
+        let data_usage = read_subtree_memory_usage(&store, shard_uid, &root, &[9]).unwrap();
+        // The subtrees are the same as in the tries with only their keys, except for the nodes
+        // above them.
+        assert!(accounts_usage > 0 && accounts_usage <= memory_usage(&accounts_root));
+        assert!(data_usage > 1000 && data_usage <= memory_usage(&data_root));
+        assert!(accounts_usage + data_usage <= memory_usage(&root));
+        assert_eq!(read_subtree_memory_usage(&store, shard_uid, &root, &[1]).unwrap(), 0);
+        assert_eq!(
+            read_subtree_memory_usage(&store, shard_uid, &root, &[]).unwrap(),
+            memory_usage(&root)
+        );
+        assert!(read_subtree_memory_usage(&create_test_store(), shard_uid, &root, &[0]).is_err());
+    }
+}
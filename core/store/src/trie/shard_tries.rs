@@ -4,6 +4,8 @@ use std::sync::{Arc, RwLock};
 use borsh::BorshSerialize;
 use near_primitives::borsh::maybestd::collections::HashMap;
 use near_primitives::hash::CryptoHash;
+use near_primitives::receipt::ReceiptQueueChanges;
+use near_primitives::runtime::apply_state::AppliedChunkInfo;
 use near_primitives::shard_layout;
 use near_primitives::shard_layout::{ShardUId, ShardVersion};
 use near_primitives::trie_key::TrieKey;
@@ -12,7 +14,10 @@ use near_primitives::types::{
 };
 
 use crate::db::{DBCol, DBOp, DBTransaction};
-use crate::trie::trie_storage::{TrieCache, TrieCachingStorage};
+use crate::metrics::shard_uid_label;
+use crate::trie::trie_storage::{
+    CachedTrieNode, TrieCache, TrieCacheKind, TrieCacheStats, TrieCachingStorage,
+};
 use crate::trie::{TrieRefcountChange, POISONED_LOCK_ERR};
 use crate::{StorageError, Store, StoreUpdate, Trie, TrieChanges, TrieUpdate};
 
@@ -28,8 +33,8 @@ struct ShardTriesInner {
 pub struct ShardTries(Arc<ShardTriesInner>);
 
 impl ShardTries {
-    fn get_new_cache(shards: &[ShardUId]) -> HashMap<ShardUId, TrieCache> {
-        shards.iter().map(|&shard_id| (shard_id, TrieCache::new())).collect()
+    fn get_new_cache(shards: &[ShardUId], kind: TrieCacheKind) -> HashMap<ShardUId, TrieCache> {
+        shards.iter().map(|&shard_uid| (shard_uid, TrieCache::new(shard_uid, kind))).collect()
     }
 
     pub fn new(store: Store, shard_version: ShardVersion, num_shards: NumShards) -> Self {
@@ -39,8 +44,8 @@ impl ShardTries {
             .collect();
         ShardTries(Arc::new(ShardTriesInner {
             store,
-            caches: RwLock::new(Self::get_new_cache(&shards)),
-            view_caches: RwLock::new(Self::get_new_cache(&shards)),
+            caches: RwLock::new(Self::get_new_cache(&shards, TrieCacheKind::Shard)),
+            view_caches: RwLock::new(Self::get_new_cache(&shards, TrieCacheKind::ViewShard)),
         }))
     }
 
@@ -57,10 +62,14 @@ impl ShardTries {
     }
 
     fn get_trie_for_shard_internal(&self, shard_uid: ShardUId, is_view: bool) -> Trie {
-        let caches_to_use = if is_view { &self.0.view_caches } else { &self.0.caches };
+        let (caches_to_use, kind) = if is_view {
+            (&self.0.view_caches, TrieCacheKind::ViewShard)
+        } else {
+            (&self.0.caches, TrieCacheKind::Shard)
+        };
         let cache = {
             let mut caches = caches_to_use.write().expect(POISONED_LOCK_ERR);
-            caches.entry(shard_uid).or_insert_with(TrieCache::new).clone()
+            caches.entry(shard_uid).or_insert_with(|| TrieCache::new(shard_uid, kind)).clone()
         };
         let store = Box::new(TrieCachingStorage::new(self.0.store.clone(), cache, shard_uid));
         Trie::new(store, shard_uid)
@@ -100,12 +109,49 @@ impl ShardTries {
             }
         }
         for (shard_uid, ops) in shards {
-            let cache = caches.entry(shard_uid).or_insert_with(TrieCache::new).clone();
+            let cache = caches
+                .entry(shard_uid)
+                .or_insert_with(|| TrieCache::new(shard_uid, TrieCacheKind::Shard))
+                .clone();
             cache.update_cache(ops);
         }
         Ok(())
     }
 
+    /// Stats of the shard caches of the client since they were last taken, by shard.
+    pub fn take_cache_stats(&self) -> Vec<(ShardUId, TrieCacheStats)> {
+        let caches = self.0.caches.read().expect(POISONED_LOCK_ERR);
+        let mut stats: Vec<_> =
+            caches.iter().map(|(shard_uid, cache)| (*shard_uid, cache.take_stats())).collect();
+        stats.sort_by_key(|(shard_uid, _)| *shard_uid);
+        stats
+    }
+
+    /// At most `limit` nodes and values of the shard caches with the most hits, to find the
+    /// contracts which hammer the same part of the state.
+    pub fn hottest_cached_nodes(&self, limit: usize) -> Vec<CachedTrieNode> {
+        let mut nodes = vec![];
+        for (caches, kind) in [
+            (&self.0.caches, TrieCacheKind::Shard),
+            (&self.0.view_caches, TrieCacheKind::ViewShard),
+        ] {
+            for (shard_uid, cache) in caches.read().expect(POISONED_LOCK_ERR).iter() {
+                nodes.extend(cache.hottest(limit).into_iter().map(|(hash, size, hits)| {
+                    CachedTrieNode {
+                        shard_uid: shard_uid_label(*shard_uid),
+                        cache: kind,
+                        hash,
+                        size,
+                        hits,
+                    }
+                }));
+            }
+        }
+        nodes.sort_by(|lhs, rhs| rhs.hits.cmp(&lhs.hits));
+        nodes.truncate(limit);
+        nodes
+    }
+
     fn apply_deletions_inner(
         deletions: &Vec<TrieRefcountChange>,
         tries: ShardTries,
@@ -261,6 +307,8 @@ pub struct WrappedTrieChanges {
     shard_uid: ShardUId,
     trie_changes: TrieChanges,
     state_changes: Vec<RawStateChangesWithTrieKey>,
+    receipt_queue_changes: Option<ReceiptQueueChanges>,
+    applied_chunk_info: Option<AppliedChunkInfo>,
     block_hash: CryptoHash,
 }
 
@@ -272,7 +320,30 @@ impl WrappedTrieChanges {
         state_changes: Vec<RawStateChangesWithTrieKey>,
         block_hash: CryptoHash,
     ) -> Self {
-        WrappedTrieChanges { tries, shard_uid, trie_changes, state_changes, block_hash }
+        WrappedTrieChanges {
+            tries,
+            shard_uid,
+            trie_changes,
+            state_changes,
+            receipt_queue_changes: None,
+            applied_chunk_info: None,
+            block_hash,
+        }
+    }
+
+    /// Makes the changes also save `receipt_queue_changes` into `ColReceiptQueueChanges`.
+    pub fn with_receipt_queue_changes(
+        mut self,
+        receipt_queue_changes: ReceiptQueueChanges,
+    ) -> Self {
+        self.receipt_queue_changes = Some(receipt_queue_changes);
+        self
+    }
+
+    /// Makes the changes also save `applied_chunk_info` into `ColAppliedChunkInfo`.
+    pub fn with_applied_chunk_info(mut self, applied_chunk_info: AppliedChunkInfo) -> Self {
+        self.applied_chunk_info = Some(applied_chunk_info);
+        self
     }
 
     pub fn state_changes(&self) -> &[RawStateChangesWithTrieKey] {
@@ -332,11 +403,22 @@ impl WrappedTrieChanges {
     ) -> Result<(), Box<dyn std::error::Error>> {
         self.insertions_into(store_update)?;
         self.state_changes_into(store_update);
-        store_update.set_ser(
-            DBCol::ColTrieChanges,
-            &shard_layout::get_block_shard_uid(&self.block_hash, &self.shard_uid),
-            &self.trie_changes,
-        )?;
+        let block_shard_uid = shard_layout::get_block_shard_uid(&self.block_hash, &self.shard_uid);
+        store_update.set_ser(DBCol::ColTrieChanges, &block_shard_uid, &self.trie_changes)?;
+        if let Some(receipt_queue_changes) = &self.receipt_queue_changes {
+            store_update.set_ser(
+                DBCol::ColReceiptQueueChanges,
+                &block_shard_uid,
+                receipt_queue_changes,
+            )?;
+        }
+        if let Some(applied_chunk_info) = &self.applied_chunk_info {
+            store_update.set_ser(
+                DBCol::ColAppliedChunkInfo,
+                &block_shard_uid,
+                applied_chunk_info,
+            )?;
+        }
         Ok(())
     }
 }
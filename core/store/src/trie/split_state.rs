@@ -9,7 +9,9 @@ use near_primitives::errors::StorageError;
 use near_primitives::receipt::Receipt;
 use near_primitives::shard_layout::ShardUId;
 use near_primitives::state_part::PartId;
-use near_primitives::trie_key::trie_key_parsers::parse_account_id_from_raw_key;
+use near_primitives::trie_key::trie_key_parsers::{
+    get_raw_prefix_for_priority_delayed_receipts, parse_account_id_from_raw_key,
+};
 use near_primitives::trie_key::TrieKey;
 use near_primitives::types::{
     ConsolidatedStateChange, StateChangeCause, StateChangesForSplitStates, StateRoot,
@@ -71,6 +73,28 @@ impl ShardTries {
                     }
                     None => {}
                 },
+                // Delayed receipts with a priority fee keep their key in the shard of their
+                // receiver.
+                TrieKey::PriorityDelayedReceipt { .. } => match value {
+                    Some(value) => {
+                        let receipt = Receipt::try_from_slice(&value).map_err(|err| {
+                            StorageError::StorageInconsistentState(format!(
+                                "invalid delayed receipt {:?}, err: {}",
+                                value,
+                                err.to_string(),
+                            ))
+                        })?;
+                        let new_shard_uid = account_id_to_shard_id(&receipt.receiver_id);
+                        trie_updates.get_mut(&new_shard_uid).unwrap().set(trie_key, value);
+                    }
+                    None => {
+                        for trie_update in trie_updates.values_mut() {
+                            if trie_update.get(&trie_key)?.is_some() {
+                                trie_update.remove(trie_key.clone());
+                            }
+                        }
+                    }
+                },
                 TrieKey::Account { account_id }
                 | TrieKey::ContractCode { account_id }
                 | TrieKey::AccessKey { account_id, .. }
@@ -78,7 +102,8 @@ impl ShardTries {
                 | TrieKey::PostponedReceiptId { receiver_id: account_id, .. }
                 | TrieKey::PendingDataCount { receiver_id: account_id, .. }
                 | TrieKey::PostponedReceipt { receiver_id: account_id, .. }
-                | TrieKey::ContractData { account_id, .. } => {
+                | TrieKey::ContractData { account_id, .. }
+                | TrieKey::RegisteredDataId { account_id, .. } => {
                     let new_shard_uid = account_id_to_shard_id(account_id);
                     // we can safely unwrap here because the caller of this function guarantees trie_updates contains all shard_uids for the new shards
                     let trie_update = trie_updates.get_mut(&new_shard_uid).unwrap();
@@ -97,11 +122,17 @@ impl ShardTries {
 
         let insert_receipts: Vec<_> =
             insert_receipts.into_iter().map(|(_, receipt)| receipt).collect();
+        // Delayed receipts with a priority fee were removed with the state changes above.
+        let delete_receipts: Vec<_> = changes
+            .processed_delayed_receipts
+            .into_iter()
+            .filter(|receipt| receipt.priority_fee() == 0)
+            .collect();
 
         apply_delayed_receipts_to_split_states_impl(
             &mut trie_updates,
             &insert_receipts,
-            &changes.processed_delayed_receipts,
+            &delete_receipts,
             account_id_to_shard_id,
         )?;
 
@@ -118,6 +149,7 @@ impl ShardTries {
     /// The caller must guarantee that `state_roots` contains all shard_ids
     /// that `key_to_shard_id` that may return
     /// Ignore changes on DelayedReceipts or DelayedReceiptsIndices
+    /// Delayed receipts with a priority fee go to the shard of their receiver
     /// Returns `store_update` and the new state_roots for split states
     pub fn add_values_to_split_states<'a>(
         &self,
@@ -125,7 +157,17 @@ impl ShardTries {
         values: Vec<(Vec<u8>, Option<Vec<u8>>)>,
         account_id_to_shard_id: &(dyn Fn(&AccountId) -> ShardUId + 'a),
     ) -> Result<(StoreUpdate, HashMap<ShardUId, StateRoot>), StorageError> {
-        self.add_values_to_split_states_impl(state_roots, values, &|raw_key| {
+        let priority_delayed_receipts_prefix = get_raw_prefix_for_priority_delayed_receipts();
+        self.add_values_to_split_states_impl(state_roots, values, &|raw_key, value| {
+            if raw_key.starts_with(&priority_delayed_receipts_prefix) {
+                let receipt = value.map(Receipt::try_from_slice).transpose().map_err(|err| {
+                    StorageError::StorageInconsistentState(format!(
+                        "invalid delayed receipt {:?}, err: {}",
+                        value, err
+                    ))
+                })?;
+                return Ok(receipt.map(|receipt| account_id_to_shard_id(&receipt.receiver_id)));
+            }
             // Here changes on DelayedReceipts or DelayedReceiptsIndices will be excluded
             // This is because we cannot migrate delayed receipts part by part. They have to be
             // reconstructed in the new states after all DelayedReceipts are ready in the original
@@ -146,11 +188,12 @@ impl ShardTries {
         &self,
         state_roots: &HashMap<ShardUId, StateRoot>,
         values: Vec<(Vec<u8>, Option<Vec<u8>>)>,
-        key_to_shard_id: &(dyn Fn(&[u8]) -> Result<Option<ShardUId>, StorageError> + 'a),
+        key_to_shard_id: &(dyn Fn(&[u8], Option<&[u8]>) -> Result<Option<ShardUId>, StorageError>
+              + 'a),
     ) -> Result<(StoreUpdate, HashMap<ShardUId, StateRoot>), StorageError> {
         let mut changes_by_shard: HashMap<_, Vec<_>> = HashMap::new();
         for (raw_key, value) in values.into_iter() {
-            if let Some(new_shard_uid) = key_to_shard_id(&raw_key)? {
+            if let Some(new_shard_uid) = key_to_shard_id(&raw_key, value.as_deref())? {
                 changes_by_shard.entry(new_shard_uid).or_default().push((raw_key, value));
             }
         }
@@ -489,7 +532,7 @@ mod tests {
                 );
 
                 let (store_update, new_state_roots) = tries
-                    .add_values_to_split_states_impl(&state_roots, changes, &|raw_key| {
+                    .add_values_to_split_states_impl(&state_roots, changes, &|raw_key, _| {
                         Ok(Some(ShardUId {
                             version: 1,
                             shard_id: (hash(raw_key).0[0] as NumShards % num_shards) as u32,
@@ -1,10 +1,12 @@
-use std::borrow::Borrow;
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 
+use near_metrics::{IntCounter, IntGauge};
 use near_primitives::hash::CryptoHash;
+use serde::Serialize;
 
 use crate::db::refcount::decode_value_with_rc;
+use crate::metrics;
 use crate::trie::POISONED_LOCK_ERR;
 use crate::{ColState, StorageError, Store};
 use lru::LruCache;
@@ -13,25 +15,171 @@ use near_primitives::types::TrieCacheMode;
 use std::cell::{Cell, RefCell};
 use std::io::ErrorKind;
 
+/// Kind of a trie cache, the `cache` label of its metrics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrieCacheKind {
+    /// Shard cache of the client.
+    Shard,
+    /// Shard cache of the view client and other readers.
+    ViewShard,
+    /// Chunk cache of a `TrieCachingStorage`.
+    Chunk,
+}
+
+impl TrieCacheKind {
+    fn label(self) -> &'static str {
+        match self {
+            TrieCacheKind::Shard => "shard",
+            TrieCacheKind::ViewShard => "view_shard",
+            TrieCacheKind::Chunk => "chunk",
+        }
+    }
+}
+
+/// Metrics of one trie cache.
+struct TrieCacheMetrics {
+    hits: IntCounter,
+    misses: IntCounter,
+    inserts: IntCounter,
+    evictions: IntCounter,
+    size_bytes: IntGauge,
+}
+
+impl TrieCacheMetrics {
+    fn new(shard_uid: ShardUId, kind: TrieCacheKind) -> Self {
+        let shard_uid = metrics::shard_uid_label(shard_uid);
+        let labels = [shard_uid.as_str(), kind.label()];
+        Self {
+            hits: metrics::TRIE_CACHE_HITS.with_label_values(&labels),
+            misses: metrics::TRIE_CACHE_MISSES.with_label_values(&labels),
+            inserts: metrics::TRIE_CACHE_INSERTS.with_label_values(&labels),
+            evictions: metrics::TRIE_CACHE_EVICTIONS.with_label_values(&labels),
+            size_bytes: metrics::TRIE_CACHE_SIZE_BYTES.with_label_values(&labels),
+        }
+    }
+}
+
+/// Hits and misses of a shard cache since they were last taken, with its current size.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrieCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub len: usize,
+    pub size_bytes: u64,
+}
+
+impl TrieCacheStats {
+    /// Share of the lookups which were hits, `None` if there were none.
+    pub fn hit_rate(&self) -> Option<f64> {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            None
+        } else {
+            Some(self.hits as f64 / lookups as f64)
+        }
+    }
+}
+
+/// Node or value held by a shard cache, as listed by `ShardTries::hottest_cached_nodes`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CachedTrieNode {
+    pub shard_uid: String,
+    pub cache: TrieCacheKind,
+    pub hash: CryptoHash,
+    pub size: usize,
+    /// Number of times it was found in the cache since it was inserted.
+    pub hits: u64,
+}
+
+struct TrieCacheEntry {
+    value: Arc<[u8]>,
+    hits: u64,
+}
+
+struct TrieCacheInner {
+    cache: LruCache<CryptoHash, TrieCacheEntry>,
+    /// Total size of the cached values.
+    size_bytes: u64,
+    /// Hits and misses since the stats were last taken.
+    hits: u64,
+    misses: u64,
+    metrics: TrieCacheMetrics,
+}
+
+impl TrieCacheInner {
+    /// Looks `key` up, counting the hit or the miss.
+    fn lookup(&mut self, key: &CryptoHash) -> Option<Arc<[u8]>> {
+        match self.cache.get_mut(key) {
+            Some(entry) => {
+                entry.hits += 1;
+                self.hits += 1;
+                self.metrics.hits.inc();
+                Some(entry.value.clone())
+            }
+            None => {
+                self.misses += 1;
+                self.metrics.misses.inc();
+                None
+            }
+        }
+    }
+
+    fn put(&mut self, key: CryptoHash, value: Arc<[u8]>) {
+        if self.cache.cap() == 0 {
+            return;
+        }
+        if self.cache.contains(&key) {
+            self.pop(&key);
+        } else if self.cache.len() == self.cache.cap() {
+            if let Some((_, evicted)) = self.cache.pop_lru() {
+                self.size_bytes -= evicted.value.len() as u64;
+                self.metrics.evictions.inc();
+            }
+        }
+        self.size_bytes += value.len() as u64;
+        self.cache.put(key, TrieCacheEntry { value, hits: 0 });
+        self.metrics.inserts.inc();
+        self.metrics.size_bytes.set(self.size_bytes as i64);
+    }
+
+    fn pop(&mut self, key: &CryptoHash) {
+        if let Some(entry) = self.cache.pop(key) {
+            self.size_bytes -= entry.value.len() as u64;
+            self.metrics.size_bytes.set(self.size_bytes as i64);
+        }
+    }
+}
+
 /// Wrapper over LruCache which doesn't hold too large elements.
 #[derive(Clone)]
-pub struct TrieCache(Arc<Mutex<LruCache<CryptoHash, Arc<[u8]>>>>);
+pub struct TrieCache(Arc<Mutex<TrieCacheInner>>);
 
 impl TrieCache {
-    pub fn new() -> Self {
-        Self::with_capacity(TRIE_MAX_SHARD_CACHE_SIZE)
+    pub fn new(shard_uid: ShardUId, kind: TrieCacheKind) -> Self {
+        Self::with_capacity(TRIE_MAX_SHARD_CACHE_SIZE, shard_uid, kind)
     }
 
-    pub fn with_capacity(cap: usize) -> Self {
-        Self(Arc::new(Mutex::new(LruCache::new(cap))))
+    pub fn with_capacity(cap: usize, shard_uid: ShardUId, kind: TrieCacheKind) -> Self {
+        Self(Arc::new(Mutex::new(TrieCacheInner {
+            cache: LruCache::new(cap),
+            size_bytes: 0,
+            hits: 0,
+            misses: 0,
+            metrics: TrieCacheMetrics::new(shard_uid, kind),
+        })))
     }
 
+    /// Gets the value without counting a hit or a miss.
     pub fn get(&self, key: &CryptoHash) -> Option<Arc<[u8]>> {
-        self.0.lock().expect(POISONED_LOCK_ERR).get(key).cloned()
+        self.0.lock().expect(POISONED_LOCK_ERR).cache.get(key).map(|entry| entry.value.clone())
     }
 
     pub fn clear(&self) {
-        self.0.lock().expect(POISONED_LOCK_ERR).clear()
+        let mut guard = self.0.lock().expect(POISONED_LOCK_ERR);
+        guard.cache.clear();
+        guard.size_bytes = 0;
+        guard.metrics.size_bytes.set(0);
     }
 
     pub fn update_cache(&self, ops: Vec<(CryptoHash, Option<&Vec<u8>>)>) {
@@ -51,10 +199,37 @@ impl TrieCache {
         }
     }
 
+    /// Returns the stats and resets the hits and misses.
+    pub fn take_stats(&self) -> TrieCacheStats {
+        let mut guard = self.0.lock().expect(POISONED_LOCK_ERR);
+        let stats = TrieCacheStats {
+            hits: guard.hits,
+            misses: guard.misses,
+            len: guard.cache.len(),
+            size_bytes: guard.size_bytes,
+        };
+        guard.hits = 0;
+        guard.misses = 0;
+        stats
+    }
+
+    /// Hashes, sizes and hits of at most `limit` cached values with the most hits.
+    pub fn hottest(&self, limit: usize) -> Vec<(CryptoHash, usize, u64)> {
+        let guard = self.0.lock().expect(POISONED_LOCK_ERR);
+        let mut entries: Vec<_> = guard
+            .cache
+            .iter()
+            .map(|(hash, entry)| (*hash, entry.value.len(), entry.hits))
+            .collect();
+        entries.sort_by(|lhs, rhs| rhs.2.cmp(&lhs.2));
+        entries.truncate(limit);
+        entries
+    }
+
     #[cfg(test)]
     pub(crate) fn len(&self) -> usize {
         let guard = self.0.lock().expect(POISONED_LOCK_ERR);
-        guard.len()
+        guard.cache.len()
     }
 }
 
@@ -81,16 +256,28 @@ pub trait TrieStorage {
 
 /// Records every value read by retrieve_raw_bytes.
 /// Used for obtaining state parts (and challenges in the future).
-/// TODO (#6316): implement proper nodes counting logic as in TrieCachingStorage
 pub struct TrieRecordingStorage {
     pub(crate) store: Store,
     pub(crate) shard_uid: ShardUId,
     pub(crate) recorded: RefCell<HashMap<CryptoHash, Vec<u8>>>,
+    /// Size of the nodes recorded since the size was last reset.
+    pub(crate) recorded_size: Cell<u64>,
+    /// Counts retrieved trie nodes exactly like `TrieCachingStorage` does, so touched nodes cost
+    /// the same gas whether the storage proof is recorded or not.
+    pub(crate) counter: TouchedNodesCounter,
+}
+
+impl TrieRecordingStorage {
+    /// Set cache mode, see `TrieCachingStorage::set_mode`.
+    pub fn set_mode(&self, state: TrieCacheMode) {
+        self.counter.cache_mode.set(state);
+    }
 }
 
 impl TrieStorage for TrieRecordingStorage {
     fn retrieve_raw_bytes(&self, hash: &CryptoHash) -> Result<Arc<[u8]>, StorageError> {
         if let Some(val) = self.recorded.borrow().get(hash) {
+            self.counter.touch(hash, val);
             return Ok(val.as_slice().into());
         }
         let key = TrieCachingStorage::get_key_from_shard_uid_and_hash(self.shard_uid, hash);
@@ -99,6 +286,8 @@ impl TrieStorage for TrieRecordingStorage {
             .get(ColState, key.as_ref())
             .map_err(|_| StorageError::StorageInternalError)?;
         if let Some(val) = val {
+            self.recorded_size.set(self.recorded_size.get() + val.len() as u64);
+            self.counter.touch(hash, &val);
             self.recorded.borrow_mut().insert(*hash, val.clone());
             Ok(val.into())
         } else {
@@ -111,7 +300,7 @@ impl TrieStorage for TrieRecordingStorage {
     }
 
     fn get_touched_nodes_count(&self) -> u64 {
-        unimplemented!();
+        self.counter.count.get()
     }
 }
 
@@ -120,18 +309,29 @@ impl TrieStorage for TrieRecordingStorage {
 pub struct TrieMemoryPartialStorage {
     pub(crate) recorded_storage: HashMap<CryptoHash, Vec<u8>>,
     pub(crate) visited_nodes: RefCell<HashSet<CryptoHash>>,
+    /// Size of the nodes visited for the first time since the size was last reset.  Matches
+    /// `TrieRecordingStorage::recorded_size` when the same reads are replayed from the proof.
+    pub(crate) visited_size: Cell<u64>,
+    /// Counts retrieved trie nodes, matching `TrieRecordingStorage::counter` when the same reads
+    /// are replayed from the proof.
+    pub(crate) counter: TouchedNodesCounter,
+}
+
+impl TrieMemoryPartialStorage {
+    /// Set cache mode, see `TrieCachingStorage::set_mode`.
+    pub fn set_mode(&self, state: TrieCacheMode) {
+        self.counter.cache_mode.set(state);
+    }
 }
 
 impl TrieStorage for TrieMemoryPartialStorage {
     fn retrieve_raw_bytes(&self, hash: &CryptoHash) -> Result<Arc<[u8]>, StorageError> {
-        let result = self
-            .recorded_storage
-            .get(hash)
-            .map_or_else(|| Err(StorageError::TrieNodeMissing), |val| Ok(val.as_slice().into()));
-        if result.is_ok() {
-            self.visited_nodes.borrow_mut().insert(*hash);
+        let val = self.recorded_storage.get(hash).ok_or(StorageError::TrieNodeMissing)?;
+        self.counter.touch(hash, val);
+        if self.visited_nodes.borrow_mut().insert(*hash) {
+            self.visited_size.set(self.visited_size.get() + val.len() as u64);
         }
-        result
+        Ok(val.as_slice().into())
     }
 
     fn as_partial_storage(&self) -> Option<&TrieMemoryPartialStorage> {
@@ -139,7 +339,67 @@ impl TrieStorage for TrieMemoryPartialStorage {
     }
 
     fn get_touched_nodes_count(&self) -> u64 {
-        unimplemented!();
+        self.counter.count.get()
+    }
+}
+
+/// Counts touched trie nodes for storages which keep all nodes in memory anyway, the same way
+/// `TrieCachingStorage` counts them: nodes which would be served from its chunk cache are free.
+pub(crate) struct TouchedNodesCounter {
+    pub(crate) cache_mode: Cell<TrieCacheMode>,
+    /// Nodes which `TrieCachingStorage` would have put in its chunk cache.
+    chunk_nodes: RefCell<HashSet<CryptoHash>>,
+    /// Counts touched trie nodes. Used to compute gas cost for touching trie nodes.
+    pub(crate) count: Cell<u64>,
+    pub(crate) loaded: LoadedNodes,
+}
+
+impl TouchedNodesCounter {
+    pub(crate) fn new(cache_mode: TrieCacheMode) -> Self {
+        Self {
+            cache_mode: Cell::new(cache_mode),
+            chunk_nodes: RefCell::new(Default::default()),
+            count: Cell::new(0),
+            loaded: Default::default(),
+        }
+    }
+
+    fn touch(&self, hash: &CryptoHash, val: &[u8]) {
+        if self.chunk_nodes.borrow().contains(hash) {
+            return;
+        }
+        self.count.set(self.count.get() + 1);
+        self.loaded.load(hash, val);
+        if matches!(self.cache_mode.get(), TrieCacheMode::CachingChunk) {
+            self.chunk_nodes.borrow_mut().insert(*hash);
+        }
+    }
+}
+
+/// Trie nodes loaded into memory since they were last reset, i.e. during the current receipt.
+/// Nodes loaded several times and nodes served from the chunk cache, which is held in memory
+/// anyway, are counted once and not at all respectively.
+#[derive(Default)]
+pub(crate) struct LoadedNodes {
+    nodes: RefCell<HashSet<CryptoHash>>,
+    size: Cell<u64>,
+}
+
+impl LoadedNodes {
+    fn load(&self, hash: &CryptoHash, val: &[u8]) {
+        if self.nodes.borrow_mut().insert(*hash) {
+            self.size.set(self.size.get() + val.len() as u64);
+        }
+    }
+
+    /// Total size of the loaded nodes.
+    pub(crate) fn size(&self) -> u64 {
+        self.size.get()
+    }
+
+    pub(crate) fn reset(&self) {
+        self.nodes.borrow_mut().clear();
+        self.size.set(0);
     }
 }
 
@@ -171,9 +431,18 @@ pub struct TrieCachingStorage {
     /// TODO (#5920): enable chunk nodes caching in Runtime::apply.
     pub(crate) chunk_cache: RefCell<HashMap<CryptoHash, Arc<[u8]>>>,
     pub(crate) cache_mode: Cell<TrieCacheMode>,
+    chunk_cache_metrics: TrieCacheMetrics,
+    /// Total size of the values in the chunk cache.
+    chunk_cache_size: Cell<u64>,
 
     /// Counts retrieved trie nodes. Used to compute gas cost for touching trie nodes.
     pub(crate) counter: Cell<u64>,
+    pub(crate) loaded: LoadedNodes,
+    /// Nodes retrieved so far, and the size of the ones retrieved for the first time since the
+    /// size was last reset.  Matches `TrieRecordingStorage::recorded_size` for the same reads, so
+    /// the storage proof size of a receipt doesn't depend on whether the proof is recorded.
+    pub(crate) visited_nodes: RefCell<HashSet<CryptoHash>>,
+    pub(crate) visited_size: Cell<u64>,
 }
 
 impl TrieCachingStorage {
@@ -184,7 +453,12 @@ impl TrieCachingStorage {
             shard_cache,
             cache_mode: Cell::new(TrieCacheMode::CachingShard),
             chunk_cache: RefCell::new(Default::default()),
+            chunk_cache_metrics: TrieCacheMetrics::new(shard_uid, TrieCacheKind::Chunk),
+            chunk_cache_size: Cell::new(0),
             counter: Cell::new(0u64),
+            loaded: Default::default(),
+            visited_nodes: RefCell::new(Default::default()),
+            visited_size: Cell::new(0),
         }
     }
 
@@ -213,23 +487,41 @@ impl TrieCachingStorage {
         self.counter.set(self.counter.get() + 1);
     }
 
+    fn visit(&self, hash: &CryptoHash, val: &[u8]) {
+        if self.visited_nodes.borrow_mut().insert(*hash) {
+            self.visited_size.set(self.visited_size.get() + val.len() as u64);
+        }
+    }
+
     /// Set cache mode.
     pub fn set_mode(&self, state: TrieCacheMode) {
         self.cache_mode.set(state);
     }
 }
 
+impl Drop for TrieCachingStorage {
+    fn drop(&mut self) {
+        self.chunk_cache_metrics.size_bytes.sub(self.chunk_cache_size.get() as i64);
+    }
+}
+
 impl TrieStorage for TrieCachingStorage {
     fn retrieve_raw_bytes(&self, hash: &CryptoHash) -> Result<Arc<[u8]>, StorageError> {
         // Try to get value from chunk cache containing free of charge nodes.
+        let caching_chunk = matches!(self.cache_mode.get(), TrieCacheMode::CachingChunk);
         if let Some(val) = self.chunk_cache.borrow_mut().get(hash) {
+            self.chunk_cache_metrics.hits.inc();
+            self.visit(hash, val);
             return Ok(val.clone());
         }
+        if caching_chunk {
+            self.chunk_cache_metrics.misses.inc();
+        }
 
         // Try to get value from shard cache containing most recently touched nodes.
         let mut guard = self.shard_cache.0.lock().expect(POISONED_LOCK_ERR);
-        let val = match guard.get(hash) {
-            Some(val) => val.clone(),
+        let val = match guard.lookup(hash) {
+            Some(val) => val,
             None => {
                 // If value is not present in cache, get it from the storage.
                 let key = Self::get_key_from_shard_uid_and_hash(self.shard_uid, hash);
@@ -265,8 +557,13 @@ impl TrieStorage for TrieCachingStorage {
         // (`storage_read_value_byte`) ~= (500 * 10**12 / 5611005) / 2**20 ~= 85 MB.
         // All values are given as of 16/03/2022. We may consider more precise limit for the chunk cache as well.
         self.inc_counter();
-        if let TrieCacheMode::CachingChunk = self.cache_mode.borrow().get() {
+        self.loaded.load(hash, &val);
+        self.visit(hash, &val);
+        if caching_chunk {
             self.chunk_cache.borrow_mut().insert(*hash, val.clone());
+            self.chunk_cache_size.set(self.chunk_cache_size.get() + val.len() as u64);
+            self.chunk_cache_metrics.inserts.inc();
+            self.chunk_cache_metrics.size_bytes.add(val.len() as i64);
         };
 
         Ok(val)
@@ -211,7 +211,9 @@ mod caching_storage_tests {
     use super::*;
     use crate::test_utils::{create_test_store, create_tries};
     use crate::trie::trie_storage::TRIE_LIMIT_CACHED_VALUE_SIZE;
-    use crate::trie::{TrieCache, TrieCachingStorage, TrieRefcountChange};
+    use crate::trie::{
+        TrieCache, TrieCacheKind, TrieCacheStats, TrieCachingStorage, TrieRefcountChange,
+    };
     use crate::{Store, TrieChanges};
     use assert_matches::assert_matches;
     use near_primitives::hash::hash;
@@ -240,7 +242,7 @@ mod caching_storage_tests {
         let values = vec![value.clone()];
         let shard_uid = ShardUId::single_shard();
         let store = create_store_with_values(&values, shard_uid);
-        let trie_cache = TrieCache::new();
+        let trie_cache = TrieCache::new(shard_uid, TrieCacheKind::Shard);
         let trie_caching_storage = TrieCachingStorage::new(store, trie_cache.clone(), shard_uid);
         let key = hash(&value);
         assert_eq!(trie_cache.get(&key), None);
@@ -260,7 +262,11 @@ mod caching_storage_tests {
     fn test_retrieve_error() {
         let shard_uid = ShardUId::single_shard();
         let store = create_test_store();
-        let trie_caching_storage = TrieCachingStorage::new(store, TrieCache::new(), shard_uid);
+        let trie_caching_storage = TrieCachingStorage::new(
+            store,
+            TrieCache::new(shard_uid, TrieCacheKind::Shard),
+            shard_uid,
+        );
         let value = vec![1u8];
         let key = hash(&value);
 
@@ -275,7 +281,7 @@ mod caching_storage_tests {
         let values = vec![value.clone()];
         let shard_uid = ShardUId::single_shard();
         let store = create_store_with_values(&values, shard_uid);
-        let trie_cache = TrieCache::new();
+        let trie_cache = TrieCache::new(shard_uid, TrieCacheKind::Shard);
         let trie_caching_storage = TrieCachingStorage::new(store, trie_cache.clone(), shard_uid);
         let key = hash(&value);
 
@@ -296,7 +302,7 @@ mod caching_storage_tests {
         let values = vec![vec![1u8]];
         let shard_uid = ShardUId::single_shard();
         let store = create_store_with_values(&values, shard_uid);
-        let trie_cache = TrieCache::new();
+        let trie_cache = TrieCache::new(shard_uid, TrieCacheKind::Shard);
         let trie_caching_storage = TrieCachingStorage::new(store, trie_cache.clone(), shard_uid);
         let value = &values[0];
         let key = hash(&value);
@@ -341,7 +347,8 @@ mod caching_storage_tests {
         let values: Vec<Vec<u8>> = (0..shard_cache_size as u8 + 1).map(|i| vec![i]).collect();
         let shard_uid = ShardUId::single_shard();
         let store = create_store_with_values(&values, shard_uid);
-        let trie_cache = TrieCache::with_capacity(shard_cache_size);
+        let trie_cache =
+            TrieCache::with_capacity(shard_cache_size, shard_uid, TrieCacheKind::Shard);
         let trie_caching_storage = TrieCachingStorage::new(store, trie_cache.clone(), shard_uid);
 
         let value = &values[0];
@@ -365,4 +372,30 @@ mod caching_storage_tests {
         assert_eq!(result.unwrap().as_ref(), value);
         assert_eq!(count_before, count_after);
     }
+
+    /// Check that the shard cache counts its hits and misses, evicts the least recently used
+    /// values and keeps track of its size.
+    #[test]
+    fn test_shard_cache_stats() {
+        let values: Vec<Vec<u8>> = (0..3u8).map(|i| vec![i; i as usize + 1]).collect();
+        let shard_uid = ShardUId::single_shard();
+        let store = create_store_with_values(&values, shard_uid);
+        let trie_cache = TrieCache::with_capacity(2, shard_uid, TrieCacheKind::Shard);
+        let trie_caching_storage = TrieCachingStorage::new(store, trie_cache.clone(), shard_uid);
+
+        // Three misses, the first value gets evicted.
+        for value in &values {
+            trie_caching_storage.retrieve_raw_bytes(&hash(value)).unwrap();
+        }
+        assert_eq!(trie_cache.get(&hash(&values[0])), None);
+        for value in [&values[2], &values[2], &values[1]] {
+            trie_caching_storage.retrieve_raw_bytes(&hash(value)).unwrap();
+        }
+        assert_eq!(
+            trie_cache.take_stats(),
+            TrieCacheStats { hits: 3, misses: 3, len: 2, size_bytes: 5 }
+        );
+        assert_eq!(trie_cache.hottest(1), vec![(hash(&values[2]), 3, 2)]);
+        assert_eq!(trie_cache.take_stats().hit_rate(), None);
+    }
 }
@@ -10,12 +10,14 @@ use crate::trie::TrieChanges;
 use crate::StorageError;
 
 use super::{Trie, TrieIterator};
-use near_primitives::trie_key::TrieKey;
+use near_primitives::trie_key::{trie_key_parsers, TrieKey};
 use std::rc::Rc;
 
 /// Key-value update. Contains a TrieKey and a value.
 pub struct TrieKeyValueUpdate {
-    pub trie_key: TrieKey,
+    /// `None` for contract data updated by its raw key, the `TrieKey` is parsed from the raw key
+    /// on commit.
+    pub trie_key: Option<TrieKey>,
     pub value: Option<Vec<u8>>,
 }
 
@@ -51,6 +53,15 @@ impl<'a> TrieUpdateValuePtr<'a> {
             }
         }
     }
+
+    /// Dereferences the value if it's at most `max_len` bytes long.  Longer values are told
+    /// apart by their stored length and give `None` without being read.
+    pub fn deref_prefix(&self, max_len: u32) -> Result<Option<Vec<u8>>, StorageError> {
+        if self.len() > max_len {
+            return Ok(None);
+        }
+        self.deref_value().map(Some)
+    }
 }
 
 impl TrieUpdate {
@@ -76,15 +87,19 @@ impl TrieUpdate {
     }
 
     pub fn get_ref(&self, key: &TrieKey) -> Result<Option<TrieUpdateValuePtr<'_>>, StorageError> {
-        let key = key.to_vec();
-        if let Some(key_value) = self.prospective.get(&key) {
+        self.get_ref_raw(&key.to_vec())
+    }
+
+    /// Same as `get_ref`, but takes the raw key, so that callers can build it in a reused buffer.
+    pub fn get_ref_raw(&self, key: &[u8]) -> Result<Option<TrieUpdateValuePtr<'_>>, StorageError> {
+        if let Some(key_value) = self.prospective.get(key) {
             return Ok(key_value.value.as_ref().map(TrieUpdateValuePtr::MemoryRef));
-        } else if let Some(changes_with_trie_key) = self.committed.get(&key) {
+        } else if let Some(changes_with_trie_key) = self.committed.get(key) {
             if let Some(RawStateChange { data, .. }) = changes_with_trie_key.changes.last() {
                 return Ok(data.as_ref().map(TrieUpdateValuePtr::MemoryRef));
             }
         }
-        self.trie.get_ref(&self.root, &key).map(|option| {
+        self.trie.get_ref(&self.root, key).map(|option| {
             option.map(|(length, hash)| TrieUpdateValuePtr::HashAndSize(&self.trie, length, hash))
         })
     }
@@ -94,11 +109,34 @@ impl TrieUpdate {
         // - Using `Vec<u8>` for sorting `BTreeMap` in the same order as a `Trie` and
         //   avoid recomputing `Vec<u8>` every time. It helps for merging iterators.
         // - Using `TrieKey` later for `RawStateChangesWithTrieKey` for State changes RPCs.
-        self.prospective
-            .insert(trie_key.to_vec(), TrieKeyValueUpdate { trie_key, value: Some(value) });
+        self.prospective.insert(
+            trie_key.to_vec(),
+            TrieKeyValueUpdate { trie_key: Some(trie_key), value: Some(value) },
+        );
     }
     pub fn remove(&mut self, trie_key: TrieKey) {
-        self.prospective.insert(trie_key.to_vec(), TrieKeyValueUpdate { trie_key, value: None });
+        self.prospective.insert(
+            trie_key.to_vec(),
+            TrieKeyValueUpdate { trie_key: Some(trie_key), value: None },
+        );
+    }
+
+    /// Sets (or removes if `value` is `None`) the contract data with the raw key built by
+    /// `TrieKey::append_contract_data_key`.  The `TrieKey` needed for the state changes is only
+    /// parsed from the raw key on commit, and an update of a key which is already updated
+    /// doesn't allocate the key at all.
+    pub fn set_contract_data_raw(&mut self, raw_key: &[u8], value: Option<Vec<u8>>) {
+        debug_assert!(
+            trie_key_parsers::parse_trie_key_contract_data_from_raw_key(raw_key).is_ok(),
+            "not a contract data key"
+        );
+        match self.prospective.get_mut(raw_key) {
+            Some(key_value) => key_value.value = value,
+            None => {
+                self.prospective
+                    .insert(raw_key.to_vec(), TrieKeyValueUpdate { trie_key: None, value });
+            }
+        }
     }
 
     pub fn commit(&mut self, event: StateChangeCause) {
@@ -106,7 +144,13 @@ impl TrieUpdate {
         for (raw_key, TrieKeyValueUpdate { trie_key, value }) in prospective.into_iter() {
             self.committed
                 .entry(raw_key)
-                .or_insert_with(|| RawStateChangesWithTrieKey { trie_key, changes: Vec::new() })
+                .or_insert_with_key(|raw_key| RawStateChangesWithTrieKey {
+                    trie_key: trie_key.unwrap_or_else(|| {
+                        trie_key_parsers::parse_trie_key_contract_data_from_raw_key(raw_key)
+                            .expect("only contract data is updated by raw key")
+                    }),
+                    changes: Vec::new(),
+                })
                 .changes
                 .push(RawStateChange { cause: event.clone(), data: value });
         }
@@ -175,6 +219,10 @@ impl TrieUpdate {
     pub fn set_trie_cache_mode(&self, state: TrieCacheMode) {
         if let Some(storage) = self.trie.storage.as_caching_storage() {
             storage.set_mode(state);
+        } else if let Some(storage) = self.trie.storage.as_recording_storage() {
+            storage.set_mode(state);
+        } else if let Some(storage) = self.trie.storage.as_partial_storage() {
+            storage.set_mode(state);
         }
     }
 }
@@ -355,6 +403,7 @@ mod tests {
 
     use super::*;
     use crate::ShardUId;
+    use borsh::BorshSerialize;
     const SHARD_VERSION: u32 = 1;
     const COMPLEX_SHARD_UID: ShardUId = ShardUId { version: SHARD_VERSION, shard_id: 0 };
 
@@ -516,4 +565,60 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn trie_deref_prefix() {
+        let tries = create_tries();
+        let value = vec![7u8; 100];
+        let mut trie_update = tries.new_trie_update(ShardUId::single_shard(), Trie::empty_root());
+        trie_update.set(test_key(b"dog".to_vec()), value.clone());
+        assert_eq!(
+            trie_update.get_ref(&test_key(b"dog".to_vec())).unwrap().unwrap().deref_prefix(99),
+            Ok(None)
+        );
+        trie_update
+            .commit(StateChangeCause::TransactionProcessing { tx_hash: CryptoHash::default() });
+        let trie_changes = trie_update.finalize().unwrap().0;
+        let (store_update, root) =
+            tries.apply_all(&trie_changes, ShardUId::single_shard()).unwrap();
+        store_update.commit().unwrap();
+
+        let trie = tries.get_trie_for_shard(ShardUId::single_shard()).recording_reads();
+        let trie_update = TrieUpdate::new(Rc::new(trie), root);
+        let ptr = trie_update.get_ref(&test_key(b"dog".to_vec())).unwrap().unwrap();
+        assert_eq!(ptr.len(), 100);
+        assert_eq!(ptr.deref_prefix(99), Ok(None));
+        // Only the nodes on the path to the value were read, not the value itself.
+        let recorded = trie_update.trie().recorded_storage().unwrap().nodes.0;
+        assert!(!recorded.is_empty());
+        assert!(!recorded.contains(&value));
+
+        assert_eq!(ptr.deref_prefix(100), Ok(Some(value.clone())));
+        assert!(trie_update.trie().recorded_storage().unwrap().nodes.0.contains(&value));
+    }
+
+    #[test]
+    fn trie_set_contract_data_raw() {
+        let tries = create_tries();
+        let cause = StateChangeCause::TransactionProcessing { tx_hash: CryptoHash::default() };
+        let mut by_trie_key =
+            tries.new_trie_update(ShardUId::single_shard(), CryptoHash::default());
+        by_trie_key.set(test_key(b"dog".to_vec()), b"puppy".to_vec());
+        by_trie_key.remove(test_key(b"cat".to_vec()));
+        by_trie_key.commit(cause.clone());
+
+        let mut by_raw_key = tries.new_trie_update(ShardUId::single_shard(), CryptoHash::default());
+        by_raw_key
+            .set_contract_data_raw(&test_key(b"dog".to_vec()).to_vec(), Some(b"dog".to_vec()));
+        by_raw_key
+            .set_contract_data_raw(&test_key(b"dog".to_vec()).to_vec(), Some(b"puppy".to_vec()));
+        by_raw_key.set_contract_data_raw(&test_key(b"cat".to_vec()).to_vec(), None);
+        assert_eq!(by_raw_key.get(&test_key(b"dog".to_vec())).unwrap(), Some(b"puppy".to_vec()));
+        by_raw_key.commit(cause);
+
+        let (trie_changes, state_changes) = by_trie_key.finalize().unwrap();
+        let (raw_trie_changes, raw_state_changes) = by_raw_key.finalize().unwrap();
+        assert_eq!(raw_trie_changes, trie_changes);
+        assert_eq!(raw_state_changes.try_to_vec().unwrap(), state_changes.try_to_vec().unwrap());
+    }
 }
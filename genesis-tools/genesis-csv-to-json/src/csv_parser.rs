@@ -210,6 +210,7 @@ fn account_records(row: &Row, gas_price: Balance) -> Vec<StateRecord> {
                         receiver_id: row.account_id.to_string(),
                         method_names: method_names.iter().map(|x| (*x).to_string()).collect(),
                     }),
+                    valid_until_block_height: None,
                 },
             })
         }
@@ -267,6 +268,8 @@ fn account_records(row: &Row, gas_price: Balance) -> Vec<StateRecord> {
                     gas: INIT_GAS,
                     deposit: 0,
                 })],
+                priority_fee: None,
+                depth: None,
             }),
         };
         res.push(StateRecord::PostponedReceipt(Box::new(receipt.into())));
@@ -66,6 +66,7 @@ impl MockPeerManagerActor {
                 archival: false,
             },
             partial_edge_info: PartialEdgeInfo::default(),
+            capabilities: None,
         };
         let network_info = NetworkInfo {
             connected_peers: vec![peer.clone()],
@@ -76,6 +77,8 @@ impl MockPeerManagerActor {
             received_bytes_per_sec: 0,
             known_producers: vec![],
             peer_counter: 0,
+            banned_peers: vec![],
+            recent_dropped_routed_messages: vec![],
         };
         Self {
             client_addr,
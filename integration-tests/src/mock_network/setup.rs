@@ -14,6 +14,8 @@ use near_epoch_manager::EpochManager;
 use near_network::test_utils::NetworkRecipient;
 use near_network::types::NetworkClientMessages;
 use near_primitives::network::PeerId;
+#[cfg(feature = "mock_network")]
+use near_primitives::runtime::config_store::RuntimeConfigStore;
 use near_primitives::state_part::PartId;
 use near_primitives::syncing::get_num_state_parts;
 use near_primitives::types::BlockHeight;
@@ -204,11 +206,13 @@ pub fn setup_mock_network(
             let mut epoch_manager = EpochManager::new_from_genesis_config(
                 client_runtime.get_store(),
                 &config.genesis.config,
+                Arc::new(RuntimeConfigStore::for_chain_id(&config.genesis.config.chain_id)),
             )
             .unwrap();
             let mut mock_epoch_manager = EpochManager::new_from_genesis_config(
                 mock_network_runtime.get_store(),
                 &config.genesis.config,
+                Arc::new(RuntimeConfigStore::for_chain_id(&config.genesis.config.chain_id)),
             )
             .unwrap();
             epoch_manager.copy_epoch_info_as_of_block(&hash, &mut mock_epoch_manager).unwrap();
@@ -1,7 +1,7 @@
 use std::path::Path;
 use std::sync::Arc;
 
-use borsh::BorshSerialize;
+use borsh::{BorshDeserialize, BorshSerialize};
 
 use near_chain::missing_chunks::MissingChunksPool;
 use near_chain::types::BlockEconomicsConfig;
@@ -19,7 +19,8 @@ use near_logger_utils::init_test_logger;
 use near_network::test_utils::MockPeerManagerAdapter;
 use near_network::types::NetworkRequests;
 use near_primitives::challenge::{
-    BlockDoubleSign, Challenge, ChallengeBody, ChunkProofs, MaybeEncodedShardChunk,
+    BlockDoubleSign, Challenge, ChallengeBody, ChunkProofs, ChunkState, ChunkStateV1, ChunkStateV2,
+    MaybeEncodedShardChunk, MAX_CHUNK_STATE_CHALLENGE_SIZE,
 };
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{merklize, MerklePath, PartialMerkleTree};
@@ -266,27 +267,24 @@ fn challenge(
     )
 }
 
-#[test]
-fn test_verify_chunk_invalid_state_challenge() {
-    let store1 = create_test_store();
-    let genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
-    let transaction_validity_period = genesis.config.transaction_validity_period;
-    let mut env = TestEnv::builder(ChainGenesis::test())
+fn chunk_state_test_env(genesis: &Genesis) -> TestEnv {
+    TestEnv::builder(ChainGenesis::test())
         .runtime_adapters(vec![Arc::new(nearcore::NightshadeRuntime::test(
             Path::new("../../../.."),
-            store1,
-            &genesis,
+            create_test_store(),
+            genesis,
         ))])
-        .build();
+        .build()
+}
+
+/// Sends 1000 from test0 to test1.  The transfer is applied as an incoming receipt by the chunk
+/// after the one with the transaction.
+fn send_chunk_state_test_tx(env: &mut TestEnv, nonce: u64) {
     let signer = InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
-    let validator_signer =
-        InMemoryValidatorSigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
     let genesis_hash = *env.clients[0].chain.genesis().hash();
-    let genesis_block = env.clients[0].chain.genesis_block().clone();
-    env.produce_block(0, 1);
     env.clients[0].process_tx(
         SignedTransaction::send_money(
-            0,
+            nonce,
             "test0".parse().unwrap(),
             "test1".parse().unwrap(),
             &signer,
@@ -296,13 +294,17 @@ fn test_verify_chunk_invalid_state_challenge() {
         false,
         false,
     );
-    env.produce_block(0, 2);
+}
 
-    // Invalid chunk & block.
-    let last_block_hash = env.clients[0].chain.head().unwrap().last_block_hash;
-    let last_block = env.clients[0].chain.get_block(&last_block_hash).unwrap().clone();
-    let total_parts = env.clients[0].runtime_adapter.num_total_parts();
-    let data_parts = env.clients[0].runtime_adapter.num_data_parts();
+/// Produces a block on top of the head with a chunk of shard 0 claiming an empty post-state of
+/// the previous chunk.  Returns the head block and the new block.
+fn produce_invalid_state_chunk_block(client: &mut Client) -> (Block, Block) {
+    let validator_signer =
+        InMemoryValidatorSigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+    let last_block_hash = client.chain.head().unwrap().last_block_hash;
+    let last_block = client.chain.get_block(&last_block_hash).unwrap().clone();
+    let total_parts = client.runtime_adapter.num_total_parts();
+    let data_parts = client.runtime_adapter.num_data_parts();
     let parity_parts = total_parts - data_parts;
     let mut rs = ReedSolomonWrapper::new(data_parts, parity_parts);
     let (mut invalid_chunk, merkle_paths) = ShardsManager::create_encoded_shard_chunk(
@@ -325,8 +327,6 @@ fn test_verify_chunk_invalid_state_challenge() {
     )
     .unwrap();
 
-    let client = &mut env.clients[0];
-
     // Receive invalid chunk to the validator.
     client
         .shards_mgr
@@ -370,32 +370,72 @@ fn test_verify_chunk_invalid_state_challenge() {
         *last_block.header().next_bp_hash(),
         block_merkle_tree.root(),
     );
+    (last_block, block)
+}
 
-    let challenge_body = {
-        use near_chain::chain::{ChainUpdate, OrphanBlockPool};
-        let chain = &mut client.chain;
-        let adapter = chain.runtime_adapter.clone();
-        let epoch_length = chain.epoch_length;
-        let empty_block_pool = OrphanBlockPool::new();
-        let empty_chunks_pool = MissingChunksPool::new();
-        let chain_genesis = ChainGenesis::from(&genesis);
-        let economics_config = BlockEconomicsConfig::from(&chain_genesis);
-
-        let mut chain_update = ChainUpdate::new(
-            chain.mut_store(),
-            adapter,
-            &empty_block_pool,
-            &empty_chunks_pool,
-            epoch_length,
-            &economics_config,
-            DoomslugThresholdMode::NoApprovals,
-            &genesis_block,
-            transaction_validity_period,
-            None,
-        );
+fn create_chunk_state_challenge(
+    client: &mut Client,
+    genesis: &Genesis,
+    last_block: &Block,
+    block: &Block,
+) -> Result<ChunkStateV2, Error> {
+    use near_chain::chain::{ChainUpdate, OrphanBlockPool};
+    let chain = &mut client.chain;
+    let genesis_block = chain.genesis_block().clone();
+    let adapter = chain.runtime_adapter.clone();
+    let epoch_length = chain.epoch_length;
+    let empty_block_pool = OrphanBlockPool::new();
+    let empty_chunks_pool = MissingChunksPool::new();
+    let chain_genesis = ChainGenesis::from(genesis);
+    let economics_config = BlockEconomicsConfig::from(&chain_genesis);
+
+    let mut chain_update = ChainUpdate::new(
+        chain.mut_store(),
+        adapter,
+        &empty_block_pool,
+        &empty_chunks_pool,
+        epoch_length,
+        &economics_config,
+        DoomslugThresholdMode::NoApprovals,
+        &genesis_block,
+        genesis.config.transaction_validity_period,
+        None,
+    );
 
-        chain_update.create_chunk_state_challenge(&last_block, &block, &block.chunks()[0]).unwrap()
-    };
+    chain_update.create_chunk_state_challenge(last_block, block, &block.chunks()[0])
+}
+
+fn validate_chunk_state_challenge(
+    client: &Client,
+    block: &Block,
+    challenge_body: ChallengeBody,
+) -> Result<(CryptoHash, Vec<AccountId>), Error> {
+    let validator_signer =
+        InMemoryValidatorSigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+    let challenge = Challenge::produce(challenge_body, &validator_signer);
+    validate_challenge(
+        &*client.chain.runtime_adapter,
+        block.header().epoch_id(),
+        block.header().prev_hash(),
+        &challenge,
+    )
+}
+
+#[test]
+fn test_verify_chunk_invalid_state_challenge() {
+    let genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    let mut env = chunk_state_test_env(&genesis);
+    let validator_signer =
+        InMemoryValidatorSigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+    env.produce_block(0, 1);
+    send_chunk_state_test_tx(&mut env, 0);
+    env.produce_block(0, 2);
+
+    // Invalid chunk & block.
+    let client = &mut env.clients[0];
+    let (last_block, block) = produce_invalid_state_chunk_block(client);
+    let challenge_body =
+        create_chunk_state_challenge(client, &genesis, &last_block, &block).unwrap();
     {
         let prev_merkle_proofs = Block::compute_chunk_headers_root(last_block.chunks().iter()).1;
         let merkle_proofs = Block::compute_chunk_headers_root(block.chunks().iter()).1;
@@ -419,8 +459,10 @@ fn test_verify_chunk_invalid_state_challenge() {
             ],
         );
     }
-    let challenge =
-        Challenge::produce(ChallengeBody::ChunkState(challenge_body), &validator_signer);
+    let challenge = Challenge::produce(
+        ChallengeBody::VersionedChunkState(ChunkState::V2(challenge_body)),
+        &validator_signer,
+    );
     let runtime_adapter = client.chain.runtime_adapter.clone();
     assert_eq!(
         validate_challenge(
@@ -447,6 +489,109 @@ fn test_verify_chunk_invalid_state_challenge() {
     }
 }
 
+fn v2(chunk_state: ChunkStateV2) -> ChallengeBody {
+    ChallengeBody::VersionedChunkState(ChunkState::V2(chunk_state))
+}
+
+/// The previous chunk of the challenged one applies an incoming receipt, which the challenge has
+/// to carry along with its proof.
+#[test]
+fn test_verify_chunk_state_challenge_with_receipts() {
+    let genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    let mut env = chunk_state_test_env(&genesis);
+    env.produce_block(0, 1);
+    send_chunk_state_test_tx(&mut env, 0);
+    for height in 2..5 {
+        env.produce_block(0, height);
+    }
+
+    let client = &mut env.clients[0];
+    let (last_block, block) = produce_invalid_state_chunk_block(client);
+    let challenge_body =
+        create_chunk_state_challenge(client, &genesis, &last_block, &block).unwrap();
+    assert!(!challenge_body.prev_chunk_receipts[0].receipt_proofs[0].0.is_empty());
+    assert_eq!(
+        validate_chunk_state_challenge(client, &block, v2(challenge_body.clone())).unwrap(),
+        (*block.hash(), vec!["test0".parse().unwrap()])
+    );
+
+    // Leaving out a receipt breaks its proof, so it's the challenge and not the chunk to blame.
+    let mut without_receipt = challenge_body.clone();
+    without_receipt.prev_chunk_receipts[0].receipt_proofs[0].0.clear();
+    assert_eq!(
+        validate_chunk_state_challenge(client, &block, v2(without_receipt)).unwrap_err().kind(),
+        ErrorKind::MaliciousChallenge
+    );
+
+    // Same for a challenge which leaves out the receipts of a block.
+    let mut without_block = challenge_body.clone();
+    without_block.prev_chunk_receipts.remove(1);
+    assert_eq!(
+        validate_chunk_state_challenge(client, &block, v2(without_block)).unwrap_err().kind(),
+        ErrorKind::MaliciousChallenge
+    );
+
+    // A tampered receipt doesn't match the outgoing receipts root of the chunk which sent it.
+    let mut tampered = challenge_body.clone();
+    tampered.prev_chunk_receipts[0].receipt_proofs[0].0[0].receiver_id = "test0".parse().unwrap();
+    assert_eq!(
+        validate_chunk_state_challenge(client, &block, v2(tampered)).unwrap_err().kind(),
+        ErrorKind::MaliciousChallenge
+    );
+
+    let mut too_large = challenge_body.clone();
+    too_large.partial_state.0.push(vec![0; MAX_CHUNK_STATE_CHALLENGE_SIZE]);
+    assert_eq!(
+        validate_chunk_state_challenge(client, &block, v2(too_large)).unwrap_err().kind(),
+        ErrorKind::InvalidChallenge
+    );
+
+    // Challenges without the receipts are still decoded, but can't be validated.
+    let v1 = ChunkStateV1 {
+        prev_block_header: challenge_body.prev_block_header,
+        block_header: challenge_body.block_header,
+        prev_merkle_proof: challenge_body.prev_merkle_proof,
+        prev_chunk: challenge_body.prev_chunk,
+        merkle_proof: challenge_body.merkle_proof,
+        chunk_header: challenge_body.chunk_header,
+        partial_state: challenge_body.partial_state,
+    };
+    let unversioned = ChallengeBody::ChunkState(v1.clone());
+    let bytes = unversioned.try_to_vec().unwrap();
+    assert_eq!(bytes[0], 2);
+    assert_eq!(ChallengeBody::try_from_slice(&bytes).unwrap(), unversioned);
+    for body in [unversioned, ChallengeBody::VersionedChunkState(ChunkState::V1(v1))] {
+        assert_eq!(
+            validate_chunk_state_challenge(client, &block, body).unwrap_err().kind(),
+            ErrorKind::InvalidChallenge
+        );
+    }
+}
+
+/// A challenge of a chunk whose claims match the result of applying the previous chunk slashes
+/// its sender.
+#[test]
+fn test_verify_chunk_state_malicious_challenge() {
+    let genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    let mut env = chunk_state_test_env(&genesis);
+    env.produce_block(0, 1);
+    send_chunk_state_test_tx(&mut env, 0);
+    for height in 2..5 {
+        env.produce_block(0, height);
+    }
+
+    let client = &mut env.clients[0];
+    let last_block = client.chain.get_block_by_height(4).unwrap().clone();
+    let block = client.produce_block(5).unwrap().unwrap();
+    let challenge_body =
+        create_chunk_state_challenge(client, &genesis, &last_block, &block).unwrap();
+    assert!(!challenge_body.prev_chunk_receipts[0].receipt_proofs[0].0.is_empty());
+    assert_eq!(
+        validate_chunk_state_challenge(client, &block, v2(challenge_body)).unwrap_err().kind(),
+        ErrorKind::MaliciousChallenge
+    );
+}
+
 /// Receive invalid state transition in chunk as next chunk producer.
 /// TODO(2445): Enable challenges when they are working correctly.
 #[test]
@@ -1,30 +1,20 @@
-use near_primitives::time::Instant;
 use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
-use actix::{Addr, System};
-use futures::{future, FutureExt};
 use tracing::info;
 
 use crate::test_helpers::heavy_test;
-use near_actix_test_utils::run_actix;
 use near_chunks::{
     CHUNK_REQUEST_RETRY_MS, CHUNK_REQUEST_SWITCH_TO_FULL_FETCH_MS,
     CHUNK_REQUEST_SWITCH_TO_OTHERS_MS,
 };
-use near_client::test_utils::setup_mock_all_validators;
-use near_client::{ClientActor, GetBlock, ViewClientActor};
+use near_client::test_loop::{Message, TestLoop};
 use near_logger_utils::init_test_logger;
-use near_network::types::PeerManagerMessageRequest;
-use near_network::types::{NetworkClientMessages, NetworkRequests, NetworkResponses};
-use near_network_primitives::types::{AccountIdOrPeerTrackingShard, PeerInfo};
-use near_primitives::hash::CryptoHash;
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::AccountId;
 
-/// Runs block producing client and stops after network mock received seven blocks
+/// Runs block producing clients and stops once one of them has a block at height 12.
 /// Confirms that the blocks form a chain (which implies the chunks are distributed).
-/// Confirms that the number of messages transmitting the chunks matches the expected number.
 fn chunks_produced_and_distributed_common(
     validator_groups: u64,
     drop_from_1_to_4: bool,
@@ -32,234 +22,131 @@ fn chunks_produced_and_distributed_common(
 ) {
     init_test_logger();
 
-    let connectors: Arc<RwLock<Vec<(Addr<ClientActor>, Addr<ViewClientActor>)>>> =
-        Arc::new(RwLock::new(vec![]));
-    let heights = Arc::new(RwLock::new(HashMap::new()));
-    let heights1 = heights;
-
-    let height_to_hash = Arc::new(RwLock::new(HashMap::new()));
-    let height_to_epoch = Arc::new(RwLock::new(HashMap::new()));
-
-    let check_heights = move |prev_hash: &CryptoHash, hash: &CryptoHash, height| {
-        let mut map = heights1.write().unwrap();
-        // Note that height of the previous block is not guaranteed to be height
-        // - 1.  All we know is that it’s less than height of the current block.
-        if let Some(prev_height) = map.get(prev_hash) {
-            assert!(*prev_height < height);
-        }
-        assert_eq!(*map.entry(*hash).or_insert(height), height);
-    };
-
-    let validators = vec![
-        vec![
-            "test1".parse().unwrap(),
-            "test2".parse().unwrap(),
-            "test3".parse().unwrap(),
-            "test4".parse().unwrap(),
-        ],
-        vec![
-            "test5".parse().unwrap(),
-            "test6".parse().unwrap(),
-            "test7".parse().unwrap(),
-            "test8".parse().unwrap(),
-        ],
+    let validators: Vec<Vec<AccountId>> = vec![
+        ["test1", "test2", "test3", "test4"].iter().map(|id| id.parse().unwrap()).collect(),
+        ["test5", "test6", "test7", "test8"].iter().map(|id| id.parse().unwrap()).collect(),
     ];
-    let key_pairs = (0..8).map(|_| PeerInfo::random()).collect::<Vec<_>>();
-
+    let mut test_loop = TestLoop::builder(validators)
+        .validator_groups(validator_groups)
+        .epoch_length(5)
+        .block_prod_time(block_timeout, 3 * block_timeout)
+        .build();
+    let test1 = test_loop.index(&"test1".parse().unwrap());
+    let test2 = test_loop.index(&"test2".parse().unwrap());
+    let test4 = test_loop.index(&"test4".parse().unwrap());
+
+    let mut heights = HashMap::new();
+    let mut height_to_hash = HashMap::new();
+    let mut height_to_epoch = HashMap::new();
     let mut partial_chunk_msgs = 0;
     let mut partial_chunk_request_msgs = 0;
 
-    let (_, conn, _) = setup_mock_all_validators(
-        validators.clone(),
-        key_pairs,
-        validator_groups,
-        true,
-        block_timeout,
-        false,
-        false,
-        5,
-        true,
-        vec![false; validators.iter().map(|x| x.len()).sum()],
-        vec![true; validators.iter().map(|x| x.len()).sum()],
-        false,
-        Arc::new(RwLock::new(Box::new(
-            move |from_whom: AccountId, msg: &PeerManagerMessageRequest| {
-                let msg = msg.as_network_requests_ref();
-                match msg {
-                    NetworkRequests::Block { block } => {
-                        let h = block.header().height();
-                        check_heights(block.header().prev_hash(), block.hash(), h);
-
-                        let mut height_to_hash = height_to_hash.write().unwrap();
-                        height_to_hash.insert(h, *block.hash());
-
-                        let mut height_to_epoch = height_to_epoch.write().unwrap();
-                        height_to_epoch.insert(h, block.header().epoch_id().clone());
-
-                        println!(
-                            "[{:?}]: BLOCK {} HEIGHT {}; HEADER HEIGHTS: {} / {} / {} / {};\nAPPROVALS: {:?}",
-                            Instant::now(),
-                            block.hash(),
-                            block.header().height(),
-                            block.chunks()[0].height_created(),
-                            block.chunks()[1].height_created(),
-                            block.chunks()[2].height_created(),
-                            block.chunks()[3].height_created(),
-                            block.header().approvals(),
-                        );
-
-                        if h > 1 {
-                            // Make sure doomslug finality is computed correctly.
-                            assert_eq!(
-                                block.header().last_ds_final_block(),
-                                height_to_hash.get(&(h - 1)).unwrap()
-                            );
-
-                            // Make sure epoch length actually corresponds to the desired epoch length
-                            // The switches are expected at 0->1, 5->6 and 10->11
-                            let prev_epoch_id = height_to_epoch.get(&(h - 1)).unwrap().clone();
-                            assert_eq!(block.header().epoch_id() == &prev_epoch_id, h % 5 != 1);
-
-                            // Make sure that the blocks leading to the epoch switch have twice as
-                            // many approval slots
-                            assert_eq!(
-                                block.header().approvals().len() == 8,
-                                h % 5 == 0 || h % 5 == 4
-                            );
-                        }
-                        if h > 2 {
-                            // Make sure BFT finality is computed correctly
-                            assert_eq!(
-                                block.header().last_final_block(),
-                                height_to_hash.get(&(h - 2)).unwrap()
-                            );
-                        }
-
-                        if block.header().height() > 1 {
-                            for shard_id in 0..4 {
-                                // If messages from 1 to 4 are dropped, 4 at their heights will
-                                //    receive the block significantly later than the chunks, and
-                                //    thus would discard the chunks
-                                if !drop_from_1_to_4 || block.header().height() % 4 != 3 {
-                                    assert_eq!(
-                                        block.header().height(),
-                                        block.chunks()[shard_id].height_created()
-                                    );
-                                }
-                            }
+    test_loop.set_message_filter(move |from, to, message| {
+        match message {
+            Message::Block(block) => {
+                let h = block.header().height();
+                // Note that height of the previous block is not guaranteed to be height
+                // - 1.  All we know is that it’s less than height of the current block.
+                if let Some(prev_height) = heights.get(block.header().prev_hash()) {
+                    assert!(*prev_height < h);
+                }
+                assert_eq!(*heights.entry(*block.hash()).or_insert(h), h);
+                height_to_hash.insert(h, *block.hash());
+                height_to_epoch.insert(h, block.header().epoch_id().clone());
+
+                if h > 1 {
+                    // Make sure doomslug finality is computed correctly.
+                    assert_eq!(block.header().last_ds_final_block(), &height_to_hash[&(h - 1)]);
+
+                    // Make sure epoch length actually corresponds to the desired epoch length
+                    // The switches are expected at 0->1, 5->6 and 10->11
+                    let prev_epoch_id = &height_to_epoch[&(h - 1)];
+                    assert_eq!(block.header().epoch_id() == prev_epoch_id, h % 5 != 1);
+
+                    // Make sure that the blocks leading to the epoch switch have twice as
+                    // many approval slots
+                    assert_eq!(block.header().approvals().len() == 8, h % 5 == 0 || h % 5 == 4);
+
+                    for shard_id in 0..4 {
+                        // If messages from 1 to 4 are dropped, 4 at their heights will
+                        //    receive the block significantly later than the chunks, and
+                        //    thus would discard the chunks
+                        if !drop_from_1_to_4 || h % 4 != 3 {
+                            assert_eq!(h, block.chunks()[shard_id].height_created());
                         }
+                    }
+                }
+                if h > 2 {
+                    // Make sure BFT finality is computed correctly
+                    assert_eq!(block.header().last_final_block(), &height_to_hash[&(h - 2)]);
+                }
+                if h >= 12 {
+                    info!(
+                        "STATS: responses: {} requests: {}",
+                        partial_chunk_msgs, partial_chunk_request_msgs
+                    );
+                }
+            }
+            Message::PartialEncodedChunk(_) => {
+                partial_chunk_msgs += 1;
+                if drop_from_1_to_4 && from == test1 && to == test4 {
+                    info!("Dropping Partial Encoded Chunk Message from test1 to test4");
+                    return false;
+                }
+            }
+            Message::PartialEncodedChunkResponse(_) => {
+                partial_chunk_msgs += 1;
+            }
+            Message::PartialEncodedChunkForward(_) => {
+                if drop_from_1_to_4 && from == test1 && to == test4 {
+                    info!("Dropping Partial Encoded Chunk Forward Message from test1 to test4");
+                    return false;
+                }
+            }
+            Message::PartialEncodedChunkRequest(_) => {
+                if drop_from_1_to_4 && from == test4 && to == test1 {
+                    info!("Dropping Partial Encoded Chunk Request from test4 to test1");
+                    return false;
+                }
+                if drop_from_1_to_4 && from == test4 && to == test2 {
+                    info!("Observed Partial Encoded Chunk Request from test4 to test2");
+                }
+                partial_chunk_request_msgs += 1;
+            }
+            _ => {}
+        }
+        true
+    });
 
-                        if block.header().height() >= 12 {
-                            println!("PREV BLOCK HASH: {}", block.header().prev_hash());
-                            println!(
-                                "STATS: responses: {} requests: {}",
-                                partial_chunk_msgs, partial_chunk_request_msgs
-                            );
+    let genesis_hash = *test_loop.clients[0].chain.genesis().hash();
+    for client in &mut test_loop.clients[0..3] {
+        client.process_tx(SignedTransaction::empty(genesis_hash), false, false);
+    }
 
-                            System::current().stop();
-                        }
-                    }
-                    NetworkRequests::PartialEncodedChunkMessage {
-                        account_id: to_whom,
-                        partial_encoded_chunk: _,
-                    } => {
-                        partial_chunk_msgs += 1;
-                        if drop_from_1_to_4
-                            && from_whom.as_ref() == "test1"
-                            && to_whom.as_ref() == "test4"
-                        {
-                            println!("Dropping Partial Encoded Chunk Message from test1 to test4");
-                            return (NetworkResponses::NoResponse.into(), false);
-                        }
-                    }
-                    NetworkRequests::PartialEncodedChunkForward { account_id: to_whom, .. } => {
-                        if drop_from_1_to_4
-                            && from_whom.as_ref() == "test1"
-                            && to_whom.as_ref() == "test4"
-                        {
-                            println!(
-                            "Dropping Partial Encoded Chunk Forward Message from test1 to test4"
-                        );
-                            return (NetworkResponses::NoResponse.into(), false);
-                        }
-                    }
-                    NetworkRequests::PartialEncodedChunkResponse { route_back: _, response: _ } => {
-                        partial_chunk_msgs += 1;
-                    }
-                    NetworkRequests::PartialEncodedChunkRequest {
-                        target: AccountIdOrPeerTrackingShard { account_id: Some(to_whom), .. },
-                        ..
-                    } => {
-                        if drop_from_1_to_4
-                            && from_whom.as_ref() == "test4"
-                            && to_whom.as_ref() == "test1"
-                        {
-                            info!("Dropping Partial Encoded Chunk Request from test4 to test1");
-                            return (NetworkResponses::NoResponse.into(), false);
-                        }
-                        if drop_from_1_to_4
-                            && from_whom.as_ref() == "test4"
-                            && to_whom.as_ref() == "test2"
-                        {
-                            info!("Observed Partial Encoded Chunk Request from test4 to test2");
-                        }
-                        partial_chunk_request_msgs += 1;
-                    }
-                    _ => {}
-                };
-                (NetworkResponses::NoResponse.into(), true)
-            },
-        ))),
+    test_loop.run_until(
+        |test_loop| (0..test_loop.clients.len()).any(|i| test_loop.head_height(i) >= 12),
+        Duration::from_millis(100 * block_timeout),
     );
-    *connectors.write().unwrap() = conn;
-
-    let view_client = connectors.write().unwrap()[0].1.clone();
-    actix::spawn(view_client.send(GetBlock::latest()).then(move |res| {
-        let block_hash = res.unwrap().unwrap().header.hash;
-        let connectors_ = connectors.write().unwrap();
-        connectors_[0].0.do_send(NetworkClientMessages::Transaction {
-            transaction: SignedTransaction::empty(block_hash),
-            is_forwarded: false,
-            check_only: false,
-        });
-        connectors_[1].0.do_send(NetworkClientMessages::Transaction {
-            transaction: SignedTransaction::empty(block_hash),
-            is_forwarded: false,
-            check_only: false,
-        });
-        connectors_[2].0.do_send(NetworkClientMessages::Transaction {
-            transaction: SignedTransaction::empty(block_hash),
-            is_forwarded: false,
-            check_only: false,
-        });
-        future::ready(())
-    }));
 }
 
 #[test]
 fn chunks_produced_and_distributed_all_in_all_shards() {
     heavy_test(|| {
-        run_actix(async {
-            chunks_produced_and_distributed_common(1, false, 15 * CHUNK_REQUEST_RETRY_MS);
-        });
+        chunks_produced_and_distributed_common(1, false, 15 * CHUNK_REQUEST_RETRY_MS);
     });
 }
 
 #[test]
 fn chunks_produced_and_distributed_2_vals_per_shard() {
     heavy_test(|| {
-        run_actix(async {
-            chunks_produced_and_distributed_common(2, false, 15 * CHUNK_REQUEST_RETRY_MS);
-        });
+        chunks_produced_and_distributed_common(2, false, 15 * CHUNK_REQUEST_RETRY_MS);
     });
 }
 
 #[test]
 fn chunks_produced_and_distributed_one_val_per_shard() {
     heavy_test(|| {
-        run_actix(async {
-            chunks_produced_and_distributed_common(4, false, 15 * CHUNK_REQUEST_RETRY_MS);
-        });
+        chunks_produced_and_distributed_common(4, false, 15 * CHUNK_REQUEST_RETRY_MS);
     });
 }
 
@@ -272,9 +159,7 @@ fn chunks_produced_and_distributed_one_val_per_shard() {
 #[cfg_attr(not(feature = "expensive_tests"), ignore)]
 fn chunks_recovered_from_others() {
     heavy_test(|| {
-        run_actix(async {
-            chunks_produced_and_distributed_common(2, true, 4 * CHUNK_REQUEST_SWITCH_TO_OTHERS_MS);
-        });
+        chunks_produced_and_distributed_common(2, true, 4 * CHUNK_REQUEST_SWITCH_TO_OTHERS_MS);
     });
 }
 
@@ -287,9 +172,7 @@ fn chunks_recovered_from_others() {
 #[should_panic]
 fn chunks_recovered_from_full_timeout_too_short() {
     heavy_test(|| {
-        run_actix(async {
-            chunks_produced_and_distributed_common(4, true, 2 * CHUNK_REQUEST_SWITCH_TO_OTHERS_MS);
-        });
+        chunks_produced_and_distributed_common(4, true, 2 * CHUNK_REQUEST_SWITCH_TO_OTHERS_MS);
     });
 }
 
@@ -299,12 +182,6 @@ fn chunks_recovered_from_full_timeout_too_short() {
 #[cfg_attr(not(feature = "expensive_tests"), ignore)]
 fn chunks_recovered_from_full() {
     heavy_test(|| {
-        run_actix(async {
-            chunks_produced_and_distributed_common(
-                4,
-                true,
-                2 * CHUNK_REQUEST_SWITCH_TO_FULL_FETCH_MS,
-            );
-        })
+        chunks_produced_and_distributed_common(4, true, 2 * CHUNK_REQUEST_SWITCH_TO_FULL_FETCH_MS);
     });
 }
@@ -16,13 +16,17 @@ use near_chain::validate::validate_chunk_with_chunk_extra;
 use near_chain::{
     Block, ChainGenesis, ChainStore, ChainStoreAccess, ErrorKind, Provenance, RuntimeAdapter,
 };
-use near_chain_configs::{ClientConfig, Genesis};
+use near_chain_configs::{
+    ClientConfig, Genesis, DEFAULT_TRANSACTION_POOL_MAX_TRANSACTIONS_PER_ACCOUNT,
+};
 use near_chunks::{ChunkStatus, ShardsManager};
 use near_client::test_utils::{
     create_chunk_on_height, run_catchup, setup_client, setup_mock, setup_mock_all_validators,
     TestEnv,
 };
-use near_client::{Client, GetBlock, GetBlockWithMerkleTree};
+use near_client::{
+    start_view_client, Client, GetBlock, GetBlockWithMerkleTree, TxStatus, TxStatusError,
+};
 use near_crypto::{InMemorySigner, KeyType, PublicKey, Signature, Signer};
 use near_logger_utils::{init_integration_logger, init_test_logger};
 use near_network::test_utils::{wait_or_panic, MockPeerManagerAdapter};
@@ -49,20 +53,21 @@ use near_primitives::sharding::{
 use near_primitives::state_part::PartId;
 use near_primitives::syncing::{get_num_state_parts, ShardStateSyncResponseHeader, StatePartKey};
 use near_primitives::transaction::{
-    Action, DeployContractAction, ExecutionStatus, FunctionCallAction, SignedTransaction,
-    Transaction,
+    Action, DeployContractAction, ExecutionMetadata, ExecutionOutcomeWithProof, ExecutionStatus,
+    FunctionCallAction, SignedTransaction, Transaction,
 };
 use near_primitives::trie_key::TrieKey;
 use near_primitives::types::validator_stake::ValidatorStake;
 use near_primitives::types::{AccountId, BlockHeight, EpochId, NumBlocks, ProtocolVersion};
-use near_primitives::utils::to_timestamp;
+use near_primitives::utils::{get_outcome_id_block_hash, to_timestamp};
 use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
 use near_primitives::version::ProtocolFeature;
 use near_primitives::version::PROTOCOL_VERSION;
 use near_primitives::views::{
-    BlockHeaderView, FinalExecutionStatus, QueryRequest, QueryResponseKind,
+    BlockHeaderView, FinalExecutionOutcomeWithReceiptView, FinalExecutionStatus, QueryRequest,
+    QueryResponseKind, RefundKindView, RefundOriginView, RefundView,
 };
-use near_store::db::DBCol::ColStateParts;
+use near_store::db::DBCol::{ColStateParts, ColTransactionResultForBlock};
 use near_store::get;
 use near_store::test_utils::create_test_store;
 use nearcore::config::{GenesisExt, TESTING_INIT_BALANCE, TESTING_INIT_STAKE};
@@ -959,6 +964,7 @@ fn client_sync_headers() {
                     archival: false,
                 },
                 partial_edge_info: near_network_primitives::types::PartialEdgeInfo::default(),
+                capabilities: None,
             }],
             num_connected_peers: 1,
             peer_max_count: 1,
@@ -971,11 +977,14 @@ fn client_sync_headers() {
                     archival: false,
                 },
                 partial_edge_info: near_network_primitives::types::PartialEdgeInfo::default(),
+                capabilities: None,
             }],
             sent_bytes_per_sec: 0,
             received_bytes_per_sec: 0,
             known_producers: vec![],
             peer_counter: 0,
+            banned_peers: vec![],
+            recent_dropped_routed_messages: vec![],
         }));
         wait_or_panic(2000);
     });
@@ -1025,6 +1034,7 @@ fn test_process_invalid_tx() {
             receiver_id: "test".parse().unwrap(),
             block_hash: *client.chain.genesis().hash(),
             actions: vec![],
+            priority_fee: None,
         },
     );
     produce_blocks(&mut client, 12);
@@ -1041,6 +1051,7 @@ fn test_process_invalid_tx() {
             receiver_id: "test".parse().unwrap(),
             block_hash: hash(&[1]),
             actions: vec![],
+            priority_fee: None,
         },
     );
     assert_eq!(
@@ -1451,6 +1462,64 @@ fn test_gc_long_epoch() {
     test_gc_with_epoch_length_common(200);
 }
 
+#[test]
+fn test_earliest_block_hash_after_gc() {
+    let epoch_length = 5;
+    let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    genesis.config.epoch_length = epoch_length;
+    let mut chain_genesis = ChainGenesis::test();
+    chain_genesis.epoch_length = epoch_length;
+    let mut env = TestEnv::builder(chain_genesis)
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 1))
+        .build();
+    for i in 1..=epoch_length * (NUM_EPOCHS_TO_KEEP_STORE_DATA + 1) {
+        env.produce_block(0, i);
+    }
+
+    let chain = &mut env.clients[0].chain;
+    let earliest_block_hash = chain.get_earliest_block_hash().unwrap().unwrap();
+    let earliest_height = chain.get_block_header(&earliest_block_hash).unwrap().height();
+    assert!(earliest_height > 0);
+    assert!(chain.get_block(&earliest_block_hash).is_ok());
+    assert!(chain.get_block_by_height(earliest_height - 1).is_err());
+
+    assert_eq!(chain.get_earliest_block_hash_from(0).unwrap(), Some(earliest_block_hash));
+    let later_block_hash = chain.get_block_hash_by_height(earliest_height + 2).unwrap();
+    assert_eq!(
+        chain.get_earliest_block_hash_from(earliest_height + 2).unwrap(),
+        Some(later_block_hash)
+    );
+    let head_height = chain.head().unwrap().height;
+    assert_eq!(chain.get_earliest_block_hash_from(head_height + 1).unwrap(), None);
+}
+
+#[test]
+fn test_final_blocks_notifications() {
+    let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    genesis.config.epoch_length = 5;
+    let mut env = TestEnv::builder(ChainGenesis::test())
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 1))
+        .build();
+    let mut final_blocks = env.clients[0].subscribe_final_blocks();
+    for i in 1..=10 {
+        env.produce_block(0, i);
+    }
+
+    let mut notified = vec![];
+    while let Ok(block_hash) = final_blocks.try_recv() {
+        notified.push(block_hash);
+    }
+    let final_head = env.clients[0].chain.final_head().unwrap();
+    assert_eq!(notified.last(), Some(&final_head.last_block_hash));
+    let heights = notified
+        .iter()
+        .map(|block_hash| env.clients[0].chain.get_block_header(block_hash).unwrap().height())
+        .collect::<Vec<_>>();
+    for window in heights.windows(2) {
+        assert_eq!(window[0] + 1, window[1]);
+    }
+}
+
 #[test]
 fn test_gc_block_skips() {
     let mut chain_genesis = ChainGenesis::test();
@@ -1491,6 +1560,51 @@ fn test_gc_chunk_tail() {
     }
 }
 
+/// An account spamming the transaction pool can't prevent the transaction of another account from
+/// being included in the next chunk.
+#[test]
+fn test_transaction_pool_spam() {
+    let genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    let mut env = TestEnv::builder(ChainGenesis::from(&genesis))
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 1))
+        .build();
+    let genesis_hash = *env.clients[0].chain.genesis().hash();
+    let spammer = InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+    let spam_count = DEFAULT_TRANSACTION_POOL_MAX_TRANSACTIONS_PER_ACCOUNT as u64;
+    for nonce in 1..=spam_count + 10 {
+        let tx = SignedTransaction::send_money(
+            nonce,
+            "test0".parse().unwrap(),
+            "test1".parse().unwrap(),
+            &spammer,
+            1,
+            genesis_hash,
+        );
+        let expected = if nonce <= spam_count {
+            NetworkClientResponses::ValidTx
+        } else {
+            NetworkClientResponses::InvalidTx(InvalidTxError::TransactionPoolLimitExceeded)
+        };
+        assert_eq!(env.clients[0].process_tx(tx, false, false), expected);
+    }
+
+    let signer = InMemorySigner::from_seed("test1".parse().unwrap(), KeyType::ED25519, "test1");
+    let tx = SignedTransaction::send_money(
+        1,
+        "test1".parse().unwrap(),
+        "test0".parse().unwrap(),
+        &signer,
+        100,
+        genesis_hash,
+    );
+    let tx_hash = tx.get_hash();
+    assert_eq!(env.clients[0].process_tx(tx, false, false), NetworkClientResponses::ValidTx);
+    for i in 1..4 {
+        env.produce_block(0, i);
+    }
+    assert!(env.clients[0].chain.get_final_transaction_result(&tx_hash).is_ok());
+}
+
 #[test]
 fn test_gc_execution_outcome() {
     let epoch_length = 5;
@@ -1525,6 +1639,121 @@ fn test_gc_execution_outcome() {
     assert!(env.clients[0].chain.get_final_transaction_result(&tx_hash).is_err());
 }
 
+/// `tx_status` of a transaction whose outcomes were pruned reports it as garbage collected. The
+/// transaction itself is reference counted by its chunk and stays until the chunk is
+/// garbage collected.
+#[test]
+fn test_tx_status_of_pruned_outcomes() {
+    init_test_logger();
+    let epoch_length = 5;
+    let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    genesis.config.epoch_length = epoch_length;
+    let mut chain_genesis = ChainGenesis::test();
+    chain_genesis.epoch_length = epoch_length;
+    let mut env = TestEnv::builder(chain_genesis)
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 1))
+        .build();
+    let genesis_hash = *env.clients[0].chain.genesis().hash();
+    let signer = InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+    let tx = SignedTransaction::send_money(
+        1,
+        "test0".parse().unwrap(),
+        "test1".parse().unwrap(),
+        &signer,
+        100,
+        genesis_hash,
+    );
+    let tx_hash = tx.get_hash();
+
+    env.clients[0].process_tx(tx, false, false);
+    for i in 1..=epoch_length * 2 {
+        env.produce_block(0, i);
+    }
+    assert!(env.clients[0].chain.get_final_transaction_result(&tx_hash).is_ok());
+
+    let store = env.clients[0].chain.mut_store();
+    let min_height = store.outcomes_prune_height(1, epoch_length).unwrap();
+    assert!(store.prune_outcomes(min_height).unwrap() > 0);
+    assert!(store.is_outcome_pruned(&tx_hash).unwrap());
+    assert!(store.get_transaction(&tx_hash).unwrap().is_some());
+
+    let client = &env.clients[0];
+    let (chain_genesis, runtime_adapter, config) =
+        (env.chain_genesis.clone(), client.runtime_adapter.clone(), client.config.clone());
+    run_actix(async move {
+        let view_client = start_view_client(
+            Some("test0".parse().unwrap()),
+            chain_genesis,
+            runtime_adapter,
+            Arc::new(MockPeerManagerAdapter::default()),
+            config,
+            #[cfg(feature = "test_features")]
+            Arc::new(RwLock::new(Default::default())),
+        );
+        actix::spawn(async move {
+            let res = view_client
+                .send(TxStatus {
+                    tx_hash,
+                    signer_account_id: "test0".parse().unwrap(),
+                    fetch_receipt: false,
+                })
+                .await
+                .unwrap();
+            assert_matches!(res, Err(TxStatusError::GarbageCollectedTransaction(hash)) if hash == tx_hash);
+            System::current().stop();
+        });
+        wait_or_panic(5000);
+    });
+}
+
+#[test]
+fn test_gc_chunk_summary() {
+    let epoch_length = 5;
+    let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    genesis.config.epoch_length = epoch_length;
+    let mut chain_genesis = ChainGenesis::test();
+    chain_genesis.epoch_length = epoch_length;
+    let mut env = TestEnv::builder(chain_genesis)
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 1))
+        .build();
+    let genesis_hash = *env.clients[0].chain.genesis().hash();
+    let signer = InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+    let tx = SignedTransaction::send_money(
+        1,
+        "test0".parse().unwrap(),
+        "test1".parse().unwrap(),
+        &signer,
+        100,
+        genesis_hash,
+    );
+    env.clients[0].process_tx(tx, false, false);
+    for i in 1..epoch_length {
+        env.produce_block(0, i);
+    }
+
+    let mut chunk_hashes = vec![];
+    for i in 1..epoch_length {
+        let chunk_header = env.clients[0].chain.get_block_by_height(i).unwrap().chunks()[0].clone();
+        if chunk_header.height_included() == i {
+            chunk_hashes.push(chunk_header.chunk_hash());
+        }
+    }
+    let summaries = chunk_hashes
+        .iter()
+        .map(|chunk_hash| {
+            env.clients[0].chain.store().get_chunk_summary(chunk_hash).unwrap().unwrap()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(summaries.iter().map(|summary| summary.tx_count).sum::<u64>(), 1);
+
+    for i in epoch_length..=epoch_length * 6 + 1 {
+        env.produce_block(0, i);
+    }
+    for chunk_hash in chunk_hashes {
+        assert!(env.clients[0].chain.store().get_chunk_summary(&chunk_hash).unwrap().is_none());
+    }
+}
+
 #[test]
 #[cfg_attr(not(feature = "expensive_tests"), ignore)]
 fn test_gc_after_state_sync() {
@@ -1651,6 +1880,41 @@ fn test_tx_forwarding() {
     assert_eq!(env.network_adapters[0].requests.read().unwrap().len(), 4);
 }
 
+/// The client remembers the chunk producers it forwarded a transaction to.
+#[test]
+fn test_tx_forwarding_routing() {
+    let mut chain_genesis = ChainGenesis::test();
+    chain_genesis.epoch_length = 100;
+    let mut env = TestEnv::builder(chain_genesis).clients_count(50).validator_seats(50).build();
+    let genesis_block = env.clients[0].chain.get_block_by_height(0).unwrap();
+    let genesis_hash = *genesis_block.hash();
+    let tx = SignedTransaction::empty(genesis_hash);
+    env.clients[0].process_tx(tx.clone(), false, false);
+    let mut chunk_producers = vec![];
+    for request in env.network_adapters[0].requests.read().unwrap().iter() {
+        if let PeerManagerMessageRequest::NetworkRequests(NetworkRequests::ForwardTx(
+            account_id,
+            _,
+        )) = request
+        {
+            chunk_producers.push(account_id.clone());
+        }
+    }
+    chunk_producers.sort();
+
+    let forwarded_transactions = env.clients[0].forwarded_transactions(10);
+    assert_eq!(forwarded_transactions.len(), 1);
+    let forwarded_tx = &forwarded_transactions[0];
+    assert_eq!(forwarded_tx.transaction_hash, tx.get_hash());
+    assert_eq!(forwarded_tx.routing.chunk_producers, chunk_producers);
+    assert!(!forwarded_tx.routing.forwarded_for_heights.is_empty());
+    assert!(!forwarded_tx.included);
+    assert_eq!(
+        env.clients[0].transaction_routing(&tx.get_hash(), &tx.transaction.signer_id).unwrap(),
+        forwarded_tx.routing
+    );
+}
+
 #[test]
 fn test_tx_forwarding_no_double_forwarding() {
     let mut chain_genesis = ChainGenesis::test();
@@ -2402,6 +2666,128 @@ fn test_catchup_gas_price_change() {
     assert_eq!(chunk_extra_after_sync, expected_chunk_extra);
 }
 
+/// Downloaded state parts are remembered, and the ones which got corrupted in the store are
+/// forgotten before applying, so that only they are downloaded again.
+#[test]
+fn test_state_sync_progress() {
+    use borsh::BorshSerialize;
+
+    init_test_logger();
+    let epoch_length = 5;
+    let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    genesis.config.epoch_length = epoch_length;
+    let chain_genesis = ChainGenesis::from(&genesis);
+    let mut env = TestEnv::builder(chain_genesis)
+        .clients_count(2)
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 2))
+        .build();
+    let mut blocks = vec![];
+    for i in 1..=epoch_length + 1 {
+        let block = env.clients[0].produce_block(i).unwrap().unwrap();
+        blocks.push(block.clone());
+        env.process_block(0, block, Provenance::PRODUCED);
+    }
+    let sync_hash = *blocks[epoch_length as usize].hash();
+    let state_sync_header = env.clients[0].chain.get_state_response_header(0, sync_hash).unwrap();
+    let num_parts = get_num_state_parts(state_sync_header.state_root_node().memory_usage);
+    assert!(num_parts > 1);
+    for block in blocks {
+        env.process_block(1, block, Provenance::NONE);
+    }
+    env.clients[1].chain.set_state_header(0, sync_hash, state_sync_header).unwrap();
+
+    // An invalid part is rejected.
+    let part = env.clients[0].chain.get_state_response_part(0, 0, sync_hash).unwrap();
+    assert_matches!(
+        env.clients[1]
+            .chain
+            .set_state_part(0, sync_hash, PartId::new(0, num_parts), &vec![0; 10])
+            .unwrap_err()
+            .kind(),
+        near_chain::ErrorKind::InvalidStatePayload
+    );
+    env.clients[1].chain.set_state_part(0, sync_hash, PartId::new(0, num_parts), &part).unwrap();
+    let part = env.clients[0].chain.get_state_response_part(0, 1, sync_hash).unwrap();
+    env.clients[1].chain.set_state_part(0, sync_hash, PartId::new(1, num_parts), &part).unwrap();
+    let (progress, corrupted_parts) =
+        env.clients[1].chain.check_downloaded_state_parts(0, sync_hash, num_parts).unwrap();
+    assert_eq!(progress.done_parts().collect::<Vec<_>>(), vec![0, 1]);
+    assert!(corrupted_parts.is_empty());
+
+    let store = env.clients[1].runtime_adapter.get_store();
+    let mut store_update = store.store_update();
+    let key = StatePartKey(sync_hash, 0, 1).try_to_vec().unwrap();
+    store_update.set(ColStateParts, &key, b"corrupted");
+    store_update.commit().unwrap();
+    let (progress, corrupted_parts) =
+        env.clients[1].chain.check_downloaded_state_parts(0, sync_hash, num_parts).unwrap();
+    assert_eq!(progress.done_parts().collect::<Vec<_>>(), vec![0]);
+    assert_eq!(corrupted_parts, vec![1]);
+    assert_eq!(
+        env.clients[1].chain.get_state_sync_progress(0, sync_hash, num_parts).unwrap(),
+        progress
+    );
+
+    env.clients[1].chain.clear_downloaded_parts(0, sync_hash, num_parts).unwrap();
+    let progress = env.clients[1].chain.get_state_sync_progress(0, sync_hash, num_parts).unwrap();
+    assert_eq!(progress.done_parts().count(), 0);
+}
+
+/// The state dump of a node which restarts midway matches the state parts served to peers.
+#[test]
+fn test_state_dump() {
+    use nearcore::config::ExternalStorageLocation;
+    use nearcore::state_dump::{dump_dir, read_filesystem_dump, StateDumper};
+
+    init_test_logger();
+    let epoch_length = 5;
+    let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    genesis.config.epoch_length = epoch_length;
+    let chain_genesis = ChainGenesis::from(&genesis);
+    let mut env = TestEnv::builder(chain_genesis.clone())
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 1))
+        .build();
+    for i in 1..=epoch_length + 4 {
+        let block = env.clients[0].produce_block(i).unwrap().unwrap();
+        env.process_block(0, block, Provenance::PRODUCED);
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let location = ExternalStorageLocation::Filesystem { root_dir: dir.path().to_path_buf() };
+    let secret_key = near_crypto::SecretKey::from_seed(KeyType::ED25519, "node");
+    let new_dumper = || {
+        StateDumper::new(
+            env.clients[0].runtime_adapter.clone(),
+            &chain_genesis,
+            "test".to_string(),
+            Some("test0".parse().unwrap()),
+            secret_key.clone(),
+            &location,
+        )
+        .unwrap()
+    };
+    assert!(new_dumper().step().unwrap());
+    let mut dumper = new_dumper();
+    while dumper.step().unwrap() {}
+
+    let sync_header = env.clients[0].chain.get_header_by_height(epoch_length + 1).unwrap().clone();
+    let sync_hash = *sync_header.hash();
+    let epoch_height = env.clients[0]
+        .runtime_adapter
+        .get_epoch_height_from_prev_block(sync_header.prev_hash())
+        .unwrap();
+    let (header, parts) =
+        read_filesystem_dump(dir.path(), &dump_dir("test", epoch_height, &sync_hash, 0)).unwrap();
+    assert_eq!(header.public_key, secret_key.public_key());
+    assert_eq!(header.header.sync_hash, sync_hash);
+    assert!(parts.len() > 1);
+    for (part_id, part) in parts.iter().enumerate() {
+        let expected =
+            env.clients[0].chain.get_state_response_part(0, part_id as u64, sync_hash).unwrap();
+        assert_eq!(part, &expected);
+    }
+}
+
 #[test]
 fn test_block_execution_outcomes() {
     let epoch_length = 5;
@@ -2598,6 +2984,86 @@ fn test_refund_receipts_processing() {
     assert_eq!(processed_refund_receipt_ids, refund_receipt_ids);
 }
 
+#[test]
+fn test_refund_receipts_in_tx_status() {
+    init_test_logger();
+
+    let genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    let chain_genesis = ChainGenesis::from(&genesis);
+    let mut env = TestEnv::builder(chain_genesis)
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 1))
+        .build();
+    let genesis_block = env.clients[0].chain.get_block_by_height(0).unwrap().clone();
+    let signer = InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+    // The receipt of a transfer to a non-existing account fails and refunds the deposit.
+    let tx = SignedTransaction::send_money(
+        1,
+        "test0".parse().unwrap(),
+        "random_account".parse().unwrap(),
+        &signer,
+        1,
+        *genesis_block.hash(),
+    );
+    let tx_hash = tx.get_hash();
+    env.clients[0].process_tx(tx, false, false);
+    for i in 1..10 {
+        env.produce_block(0, i);
+    }
+
+    let chain = &mut env.clients[0].chain;
+    let final_outcome = chain.get_final_transaction_result(&tx_hash).unwrap();
+    let failed_receipt_id = final_outcome.transaction_outcome.outcome.receipt_ids[0];
+    let refunds = |final_outcome: FinalExecutionOutcomeWithReceiptView| {
+        assert_eq!(final_outcome.final_outcome.receipts_outcome[0].id, failed_receipt_id);
+        assert_eq!(final_outcome.final_outcome.receipts_outcome[0].outcome.refund, None);
+        final_outcome
+            .final_outcome
+            .receipts_outcome
+            .into_iter()
+            .filter_map(|outcome| outcome.outcome.refund)
+            .collect::<Vec<_>>()
+    };
+
+    let with_origin =
+        refunds(chain.get_final_transaction_result_with_receipt(final_outcome.clone()).unwrap());
+    // The origins are only recorded from the protocol version with `RefundReceiptOrigin`.
+    let expected_origin = if cfg!(feature = "protocol_feature_refund_receipt_origin") {
+        RefundOriginView::ReceiptId(failed_receipt_id)
+    } else {
+        RefundOriginView::Unknown
+    };
+    assert!(with_origin.contains(&RefundView {
+        is_refund: true,
+        origin: expected_origin.clone(),
+        kind: RefundKindView::Deposit,
+    }));
+    assert!(with_origin.iter().all(|refund| refund.origin == expected_origin));
+
+    // Outcomes stored before the refunds were recorded in their metadata.
+    let mut outcomes = chain.store().get_outcomes_for_id(&failed_receipt_id).unwrap();
+    for outcome in outcomes.iter_mut() {
+        outcome.outcome_with_id.outcome.metadata = ExecutionMetadata::V1;
+    }
+    let mut store_update = chain.store().store().store_update();
+    for outcome in outcomes {
+        store_update
+            .set_ser(
+                ColTransactionResultForBlock,
+                &get_outcome_id_block_hash(&failed_receipt_id, &outcome.block_hash),
+                &ExecutionOutcomeWithProof::from(outcome),
+            )
+            .unwrap();
+    }
+    store_update.commit().unwrap();
+    let without_origin =
+        refunds(chain.get_final_transaction_result_with_receipt(final_outcome).unwrap());
+    assert_eq!(without_origin.len(), with_origin.len());
+    for (refund, refund_with_origin) in without_origin.iter().zip(&with_origin) {
+        assert_eq!(refund.origin, RefundOriginView::Unknown);
+        assert_eq!(refund.kind, refund_with_origin.kind);
+    }
+}
+
 #[test]
 fn test_wasmer2_upgrade() {
     let mut capture = near_logger_utils::TracingCapture::enable();
@@ -2642,6 +3108,7 @@ fn test_wasmer2_upgrade() {
 
         nonce: 0,
         block_hash: CryptoHash::default(),
+        priority_fee: None,
     };
 
     // Run the transaction & collect the logs.
@@ -2733,6 +3200,7 @@ fn test_execution_metadata() {
 
             nonce: 10,
             block_hash: tip.last_block_hash,
+            priority_fee: None,
         }
         .sign(&signer);
         let tx_hash = tx.get_hash();
@@ -2993,7 +3461,7 @@ fn test_fork_receipt_ids() {
     }
 
     let transaction_execution_outcome =
-        env.clients[0].chain.mut_store().get_outcomes_by_id(&tx_hash).unwrap();
+        env.clients[0].chain.mut_store().get_outcomes_for_id(&tx_hash).unwrap();
     assert_eq!(transaction_execution_outcome.len(), 2);
     let receipt_id0 = transaction_execution_outcome[0].outcome_with_id.outcome.receipt_ids[0];
     let receipt_id1 = transaction_execution_outcome[1].outcome_with_id.outcome.receipt_ids[0];
@@ -3040,11 +3508,11 @@ fn test_fork_execution_outcome() {
     }
 
     let transaction_execution_outcome =
-        env.clients[0].chain.mut_store().get_outcomes_by_id(&tx_hash).unwrap();
+        env.clients[0].chain.mut_store().get_outcomes_for_id(&tx_hash).unwrap();
     assert_eq!(transaction_execution_outcome.len(), 1);
     let receipt_id = transaction_execution_outcome[0].outcome_with_id.outcome.receipt_ids[0];
     let receipt_execution_outcomes =
-        env.clients[0].chain.mut_store().get_outcomes_by_id(&receipt_id).unwrap();
+        env.clients[0].chain.mut_store().get_outcomes_for_id(&receipt_id).unwrap();
     assert_eq!(receipt_execution_outcomes.len(), 2);
     let canonical_chain_outcome = env.clients[0].chain.get_execution_outcome(&receipt_id).unwrap();
     assert_eq!(canonical_chain_outcome.block_hash, *block2.hash());
@@ -3054,13 +3522,81 @@ fn test_fork_execution_outcome() {
         env.produce_block(0, i);
     }
     let transaction_execution_outcome =
-        env.clients[0].chain.store().get_outcomes_by_id(&tx_hash).unwrap();
+        env.clients[0].chain.store().get_outcomes_for_id(&tx_hash).unwrap();
     assert!(transaction_execution_outcome.is_empty());
     let receipt_execution_outcomes =
-        env.clients[0].chain.store().get_outcomes_by_id(&receipt_id).unwrap();
+        env.clients[0].chain.store().get_outcomes_for_id(&receipt_id).unwrap();
     assert!(receipt_execution_outcomes.is_empty());
 }
 
+/// Includes the chunk in the block, which is re-signed.
+fn set_block_chunk(
+    block: &mut Block,
+    mut chunk_header: ShardChunkHeader,
+    validator_signer: &InMemoryValidatorSigner,
+) {
+    *chunk_header.height_included_mut() = block.header().height();
+    let chunk_headers = vec![chunk_header];
+    block.set_chunks(chunk_headers.clone());
+    block.mut_header().get_mut().inner_rest.chunk_headers_root =
+        Block::compute_chunk_headers_root(&chunk_headers).0;
+    block.mut_header().get_mut().inner_rest.chunk_tx_root =
+        Block::compute_chunk_tx_root(&chunk_headers);
+    block.mut_header().get_mut().inner_rest.chunk_receipts_root =
+        Block::compute_chunk_receipts_root(&chunk_headers);
+    block.mut_header().get_mut().inner_lite.prev_state_root =
+        Block::compute_state_root(&chunk_headers);
+    block.mut_header().get_mut().inner_rest.chunk_mask = vec![true];
+    block.mut_header().resign(validator_signer);
+}
+
+/// The same receipt is executed on two forks, the outcome returned for it, which is what the
+/// tx status and light client proof queries use, follows the fork which becomes canonical.
+#[test]
+fn test_fork_execution_outcome_follows_canonical_chain() {
+    let (mut env, tx_hash) = prepare_env_with_transaction();
+
+    let mut last_height = 0;
+    for i in 1..3 {
+        let last_block = env.clients[0].produce_block(i).unwrap().unwrap();
+        env.process_block(0, last_block.clone(), Provenance::PRODUCED);
+        last_height = last_block.header().height();
+    }
+
+    let validator_signer =
+        InMemoryValidatorSigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+    let next_height = last_height + 1;
+    let (encoded_chunk, _, _) = create_chunk_on_height(&mut env.clients[0], next_height);
+    let mut block1 = env.clients[0].produce_block(next_height).unwrap().unwrap();
+    let mut block2 = env.clients[0].produce_block(next_height + 1).unwrap().unwrap();
+    set_block_chunk(&mut block1, encoded_chunk.cloned_header(), &validator_signer);
+    set_block_chunk(&mut block2, encoded_chunk.cloned_header(), &validator_signer);
+
+    let receipt_block_hash = |env: &mut TestEnv| {
+        let chain = &mut env.clients[0].chain;
+        let final_outcome = chain.get_final_transaction_result(&tx_hash).unwrap();
+        let receipt_id = final_outcome.transaction_outcome.outcome.receipt_ids[0];
+        assert_eq!(final_outcome.receipts_outcome[0].id, receipt_id);
+        let block_hash = chain.get_execution_outcome(&receipt_id).unwrap().block_hash;
+        assert_eq!(final_outcome.receipts_outcome[0].block_hash, block_hash);
+        block_hash
+    };
+
+    let (_, res) = env.clients[0].process_block(block1.clone().into(), Provenance::NONE);
+    assert!(res.is_ok());
+    assert_eq!(receipt_block_hash(&mut env), *block1.hash());
+
+    // The second block is higher and becomes the head.
+    let (_, res) = env.clients[0].process_block(block2.clone().into(), Provenance::NONE);
+    assert!(res.is_ok());
+    assert_eq!(env.clients[0].chain.head().unwrap().last_block_hash, *block2.hash());
+    assert_eq!(receipt_block_hash(&mut env), *block2.hash());
+
+    let tx_outcome = env.clients[0].chain.get_execution_outcome(&tx_hash).unwrap();
+    let receipt_id = tx_outcome.outcome_with_id.outcome.receipt_ids[0];
+    assert_eq!(env.clients[0].chain.store().get_outcomes_for_id(&receipt_id).unwrap().len(), 2);
+}
+
 fn prepare_env_with_transaction() -> (TestEnv, CryptoHash) {
     let epoch_length = 5;
     let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
@@ -3379,6 +3915,7 @@ fn test_limit_contract_functions_number_upgrade() {
 
         nonce: 0,
         block_hash: CryptoHash::default(),
+        priority_fee: None,
     };
 
     // Run the transaction & get tx outcome.
@@ -3429,6 +3966,89 @@ fn test_limit_contract_functions_number_upgrade() {
     ));
 }
 
+/// Check that the treasury reward of the epochs running the protocol version with the treasury
+/// split goes to the accounts of the runtime config, and that the total supply grows by exactly
+/// what the accounts receive across the upgrade.
+#[cfg(feature = "protocol_feature_treasury_split")]
+#[test]
+fn test_protocol_treasury_split_upgrade() {
+    let split_version = ProtocolFeature::ProtocolTreasurySplit.protocol_version();
+    let near: AccountId = "near".parse().unwrap();
+    let treasury: AccountId = "treasury".parse().unwrap();
+    let validator: AccountId = "test0".parse().unwrap();
+
+    let epoch_length = 5;
+    let mut genesis = Genesis::test(vec![validator.clone(), treasury.clone()], 1);
+    genesis.config.epoch_length = epoch_length;
+    genesis.config.protocol_version = split_version - 1;
+    let runtime_config = RuntimeConfig {
+        protocol_treasury_split: vec![
+            (near.clone(), Rational::new(1, 4)),
+            (treasury.clone(), Rational::new(3, 4)),
+        ],
+        ..RuntimeConfig::test()
+    };
+    let chain_genesis = ChainGenesis::from(&genesis);
+    let runtimes: Vec<Arc<dyn RuntimeAdapter>> =
+        vec![Arc::new(nearcore::NightshadeRuntime::test_with_runtime_config_store(
+            Path::new("../../../.."),
+            create_test_store(),
+            &genesis,
+            TrackedConfig::new_empty(),
+            RuntimeConfigStore::with_one_config(runtime_config),
+        ))];
+    let mut env = TestEnv::builder(chain_genesis).runtime_adapters(runtimes).build();
+
+    let total_balance = |env: &mut TestEnv, account_id: &AccountId| {
+        let account = env.query_account(account_id.clone());
+        account.amount + account.locked
+    };
+    let mut balances = [&near, &treasury, &validator].map(|id| total_balance(&mut env, id));
+    let initial_balances = balances;
+    let mut paid_before_split = false;
+    let mut paid_after_split = false;
+    for height in 1..=4 * epoch_length + 3 {
+        // We cannot just use TestEnv::produce_block as we are updating protocol version
+        let mut block = env.clients[0].produce_block(height).unwrap().unwrap();
+        set_block_protocol_version(&mut block, validator.clone(), split_version);
+        env.process_block(0, block, Provenance::PRODUCED);
+
+        // Queries at the head see the state the chunk of the previous block left, so the
+        // rewards seen now were paid for the epoch of the block before it.
+        let new_balances = [&near, &treasury, &validator].map(|id| total_balance(&mut env, id));
+        let near_reward = new_balances[0] - balances[0];
+        let treasury_reward = new_balances[1] - balances[1];
+        balances = new_balances;
+        if near_reward == 0 && treasury_reward == 0 {
+            continue;
+        }
+        let paid_block = env.clients[0].chain.get_block_by_height(height - 2).unwrap().clone();
+        let paid_version = env.clients[0]
+            .runtime_adapter
+            .get_epoch_protocol_version(paid_block.header().epoch_id())
+            .unwrap();
+        if paid_version < split_version {
+            assert_eq!(treasury_reward, 0, "treasury rewarded before the split at {}", height);
+            paid_before_split = true;
+        } else {
+            // The first account gets what rounding leaves.
+            assert_eq!(treasury_reward, (near_reward + treasury_reward) * 3 / 4);
+            assert!(treasury_reward > 0);
+            paid_after_split = true;
+        }
+    }
+    assert!(paid_before_split && paid_after_split);
+
+    // Nothing burns tokens here, so everything minted went to the accounts.
+    let head = env.clients[0].chain.head().unwrap();
+    let last_applied_block =
+        env.clients[0].chain.get_block_by_height(head.height - 1).unwrap().clone();
+    let minted = last_applied_block.header().total_supply() - genesis.config.total_supply;
+    let rewarded: u128 =
+        balances.iter().zip(initial_balances.iter()).map(|(after, before)| after - before).sum();
+    assert_eq!(minted, rewarded);
+}
+
 #[test]
 /// Test that if a node's shard assignment will not change in the next epoch, the node
 /// does not need to catch up.
@@ -4364,6 +4984,7 @@ mod contract_precompilation_tests {
             block_hash: *block.hash(),
             epoch_id: block.header().epoch_id().clone(),
             epoch_height: 1,
+            shard_id: 0,
             block_timestamp: block.header().raw_timestamp(),
             current_protocol_version: PROTOCOL_VERSION,
             cache: Some(caches[1].clone()),
@@ -4544,3 +5165,164 @@ mod contract_precompilation_tests {
         assert!(caches[1].get(&contract_key.0).unwrap().is_none());
     }
 }
+
+/// Contracts on different shards see the id of their own shard and the id of the epoch of the
+/// block their receipt is applied in.
+#[cfg(feature = "protocol_feature_epoch_and_shard_id")]
+#[test]
+fn test_epoch_id_and_shard_id_host_functions() {
+    use near_primitives::shard_layout::account_id_to_shard_id;
+
+    init_test_logger();
+    let epoch_length = 5;
+    let accounts: Vec<AccountId> = (0..4).map(|i| format!("test{}", i).parse().unwrap()).collect();
+    let mut genesis = Genesis::test_sharded(accounts.clone(), 1, vec![1, 1]);
+    genesis.config.epoch_length = epoch_length;
+    let shard_layout = genesis.config.shard_layout.clone();
+    let chain_genesis = ChainGenesis::from(&genesis);
+    let mut env = TestEnv::builder(chain_genesis)
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 1))
+        .build();
+
+    // The contract is deployed to one account on each of the two shards.
+    let contract_accounts: Vec<AccountId> = (0..2)
+        .map(|shard_id| {
+            accounts
+                .iter()
+                .find(|account_id| account_id_to_shard_id(account_id, &shard_layout) == shard_id)
+                .expect("every shard should have a test account")
+                .clone()
+        })
+        .collect();
+    let signer = |account_id: &AccountId| {
+        InMemorySigner::from_seed(account_id.clone(), KeyType::ED25519, account_id.as_ref())
+    };
+    let genesis_hash = *env.clients[0].chain.genesis().hash();
+    for account_id in &contract_accounts {
+        let tx = SignedTransaction::from_actions(
+            1,
+            account_id.clone(),
+            account_id.clone(),
+            &signer(account_id),
+            vec![Action::DeployContract(DeployContractAction {
+                code: near_test_contracts::epoch_and_shard_contract().to_vec(),
+            })],
+            genesis_hash,
+        );
+        env.clients[0].process_tx(tx, false, false);
+    }
+    // The first two epochs both have the default id, the calls are made in a later one.
+    let mut height = produce_blocks_from_height(&mut env, 2 * epoch_length + 1, 1);
+
+    for (shard_id, account_id) in contract_accounts.iter().enumerate() {
+        let tip = env.clients[0].chain.head().unwrap();
+        let tx = SignedTransaction::from_actions(
+            2,
+            account_id.clone(),
+            account_id.clone(),
+            &signer(account_id),
+            vec![Action::FunctionCall(FunctionCallAction {
+                method_name: "epoch_and_shard".to_string(),
+                args: vec![],
+                gas: 100_000_000_000_000,
+                deposit: 0,
+            })],
+            tip.last_block_hash,
+        );
+        let tx_hash = tx.get_hash();
+        env.clients[0].process_tx(tx, false, false);
+        height = produce_blocks_from_height(&mut env, 3, height);
+
+        let outcome = env.clients[0].chain.get_final_transaction_result(&tx_hash).unwrap();
+        let value = match outcome.status {
+            FinalExecutionStatus::SuccessValue(value) => base64::decode(value).unwrap(),
+            status => panic!("unexpected status: {:?}", status),
+        };
+        let block_hash = outcome.receipts_outcome[0].block_hash;
+        let epoch_id =
+            env.clients[0].chain.get_block_header(&block_hash).unwrap().epoch_id().clone();
+        assert_ne!(epoch_id, EpochId::default());
+        assert_eq!(&value[..32], epoch_id.0.as_ref());
+        assert_eq!(value[32..], (shard_id as u64).to_le_bytes());
+    }
+}
+
+/// A callback on one shard sees the gas burnt by a function call executed on another shard,
+/// both when the call succeeds and when it fails.
+#[cfg(feature = "protocol_feature_promise_result_gas_burnt")]
+#[test]
+fn test_promise_result_gas_burnt_cross_shard() {
+    use near_primitives::shard_layout::account_id_to_shard_id;
+
+    init_test_logger();
+    let accounts: Vec<AccountId> = (0..4).map(|i| format!("test{}", i).parse().unwrap()).collect();
+    let genesis = Genesis::test_sharded(accounts.clone(), 1, vec![1, 1]);
+    let shard_layout = genesis.config.shard_layout.clone();
+    let chain_genesis = ChainGenesis::from(&genesis);
+    let mut env = TestEnv::builder(chain_genesis)
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 1))
+        .build();
+
+    let account_on_shard = |shard_id| {
+        accounts
+            .iter()
+            .find(|account_id| account_id_to_shard_id(account_id, &shard_layout) == shard_id)
+            .expect("every shard should have a test account")
+            .clone()
+    };
+    let caller = account_on_shard(0);
+    let callee = account_on_shard(1);
+    let signer = |account_id: &AccountId| {
+        InMemorySigner::from_seed(account_id.clone(), KeyType::ED25519, account_id.as_ref())
+    };
+    let genesis_hash = *env.clients[0].chain.genesis().hash();
+    for account_id in [&caller, &callee] {
+        let tx = SignedTransaction::from_actions(
+            1,
+            account_id.clone(),
+            account_id.clone(),
+            &signer(account_id),
+            vec![Action::DeployContract(DeployContractAction {
+                code: near_test_contracts::promise_gas_burnt_contract().to_vec(),
+            })],
+            genesis_hash,
+        );
+        env.clients[0].process_tx(tx, false, false);
+    }
+    let mut height = produce_blocks_from_height(&mut env, 3, 1);
+
+    for (nonce, &(method_name, expected_status)) in
+        [("call_succeed", 1u64), ("call_fail", 2u64)].iter().enumerate()
+    {
+        let tip = env.clients[0].chain.head().unwrap();
+        let tx = SignedTransaction::from_actions(
+            nonce as u64 + 2,
+            caller.clone(),
+            caller.clone(),
+            &signer(&caller),
+            vec![Action::FunctionCall(FunctionCallAction {
+                method_name: method_name.to_string(),
+                args: callee.as_ref().as_bytes().to_vec(),
+                gas: 100_000_000_000_000,
+                deposit: 0,
+            })],
+            tip.last_block_hash,
+        );
+        let tx_hash = tx.get_hash();
+        env.clients[0].process_tx(tx, false, false);
+        height = produce_blocks_from_height(&mut env, 6, height);
+
+        let outcome = env.clients[0].chain.get_final_transaction_result(&tx_hash).unwrap();
+        let value = match outcome.status {
+            FinalExecutionStatus::SuccessValue(value) => base64::decode(value).unwrap(),
+            status => panic!("unexpected status: {:?}", status),
+        };
+        let callee_outcome = outcome
+            .receipts_outcome
+            .iter()
+            .find(|receipt_outcome| receipt_outcome.outcome.executor_id == callee)
+            .expect("the callee should have executed a receipt");
+        assert_eq!(value[..8], expected_status.to_le_bytes());
+        assert_eq!(value[8..], callee_outcome.outcome.gas_burnt.to_le_bytes());
+    }
+}
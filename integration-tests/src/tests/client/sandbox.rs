@@ -3,6 +3,7 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use actix::System;
+use borsh::BorshSerialize;
 
 use near_actix_test_utils::run_actix;
 use near_chain::{ChainGenesis, Provenance, RuntimeAdapter};
@@ -14,13 +15,16 @@ use near_network::types::{
     NetworkClientMessages, NetworkRequests, NetworkResponses, PeerManagerMessageResponse,
 };
 use near_network_primitives::types::NetworkSandboxMessage;
-use near_primitives::account::Account;
+use near_primitives::account::{AccessKey, Account};
+use near_primitives::hash::CryptoHash;
+use near_primitives::runtime::config::RuntimeConfig;
 use near_primitives::serialize::{from_base64, to_base64};
 use near_primitives::state_record::StateRecord;
 use near_primitives::transaction::{
-    Action, DeployContractAction, FunctionCallAction, SignedTransaction,
+    Action, DeployContractAction, FunctionCallAction, SignedTransaction, TransferAction,
 };
 use near_primitives::types::{AccountId, BlockHeight, Nonce};
+use near_primitives::views::FinalExecutionStatus;
 use near_store::test_utils::create_test_store;
 use nearcore::config::GenesisExt;
 
@@ -79,10 +83,12 @@ fn send_tx(
     receiver_id: AccountId,
     signer: &InMemorySigner,
     actions: Vec<Action>,
-) {
+) -> CryptoHash {
     let hash = env.clients[0].chain.head().unwrap().last_block_hash;
     let tx = SignedTransaction::from_actions(nonce, signer_id, receiver_id, signer, actions, hash);
+    let tx_hash = tx.get_hash();
     env.clients[0].process_tx(tx, false, false);
+    tx_hash
 }
 
 #[test]
@@ -117,6 +123,56 @@ fn test_patch_account() {
     assert_eq!(test1_after.amount, 10);
 }
 
+#[test]
+fn test_patch_access_key() {
+    let (mut env, _signer) = test_setup();
+    let test1: AccountId = "test1".parse().unwrap();
+    let storage_usage_before = env.query_account(test1.clone()).storage_usage;
+
+    let signer = InMemorySigner::from_seed(test1.clone(), KeyType::ED25519, "patched");
+    env.clients[0].chain.patch_state(vec![StateRecord::AccessKey {
+        account_id: test1.clone(),
+        public_key: signer.public_key.clone(),
+        access_key: AccessKey::full_access(),
+    }]);
+    do_blocks(&mut env, 9, 12);
+    let key_storage_usage = signer.public_key.try_to_vec().unwrap().len() as u64
+        + AccessKey::full_access().try_to_vec().unwrap().len() as u64
+        + RuntimeConfig::test().transaction_costs.storage_usage_config.num_extra_bytes_record;
+    assert_eq!(
+        env.query_account(test1.clone()).storage_usage,
+        storage_usage_before + key_storage_usage
+    );
+
+    // Patching the same key again replaces it instead of adding to the storage usage.
+    env.clients[0].chain.patch_state(vec![StateRecord::AccessKey {
+        account_id: test1.clone(),
+        public_key: signer.public_key.clone(),
+        access_key: AccessKey { nonce: 10, ..AccessKey::full_access() },
+    }]);
+    do_blocks(&mut env, 12, 15);
+    assert_eq!(
+        env.query_account(test1.clone()).storage_usage,
+        storage_usage_before + key_storage_usage
+    );
+
+    let tx_hash = send_tx(
+        &mut env,
+        11,
+        test1.clone(),
+        "test0".parse().unwrap(),
+        &signer,
+        vec![Action::Transfer(TransferAction { deposit: 100 })],
+    );
+    do_blocks(&mut env, 15, 20);
+    let outcome = env.query_transaction_status(&tx_hash);
+    assert!(
+        matches!(outcome.status, FinalExecutionStatus::SuccessValue(_)),
+        "{:?}",
+        outcome.status
+    );
+}
+
 #[test]
 fn test_fast_forward() {
     init_test_logger();
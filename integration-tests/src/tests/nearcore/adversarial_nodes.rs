@@ -0,0 +1,229 @@
+//! Networks of honest nodes and adversarial ones, which are driven to misbehave through the
+//! adversarial controls, checking that the honest nodes keep agreeing on the chain.
+use std::collections::HashSet;
+use std::time::Duration;
+
+use actix::{Addr, System};
+use actix_rt::ArbiterHandle;
+use futures::Future;
+
+use near_client::{ClientActor, GetBlock, ViewClientActor};
+use near_network::test_utils::wait_or_panic;
+use near_network::types::{NetworkClientMessages, NetworkClientResponses};
+use near_network_primitives::types::{NetworkAdversarialMessage, NetworkViewClientMessages};
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{BlockHeight, BlockId};
+
+use crate::tests::nearcore::node_cluster::NodeCluster;
+
+/// How often the heads of the honest nodes are checked.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Nodes of a cluster split into the honest ones and the adversarial ones.
+pub struct MixedNetwork {
+    nodes: Vec<(Addr<ClientActor>, Addr<ViewClientActor>)>,
+    adversarial: Vec<usize>,
+}
+
+impl MixedNetwork {
+    pub fn new(
+        clients: &[(Addr<ClientActor>, Addr<ViewClientActor>, Vec<ArbiterHandle>)],
+        adversarial: &[usize],
+    ) -> Self {
+        let nodes = clients
+            .iter()
+            .map(|(client, view_client, _)| (client.clone(), view_client.clone()))
+            .collect();
+        Self { nodes, adversarial: adversarial.to_vec() }
+    }
+
+    fn honest_view_clients(&self) -> impl Iterator<Item = &Addr<ViewClientActor>> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(move |(i, _)| !self.adversarial.contains(i))
+            .map(|(_, (_, view_client))| view_client)
+    }
+
+    /// Sends the message built by `msg` to every adversarial node, returning their responses.
+    pub async fn misbehave(
+        &self,
+        msg: impl Fn() -> NetworkAdversarialMessage,
+    ) -> Vec<NetworkClientResponses> {
+        let mut responses = vec![];
+        for &i in &self.adversarial {
+            let (client, _) = &self.nodes[i];
+            responses.push(client.send(NetworkClientMessages::Adversarial(msg())).await.unwrap());
+        }
+        responses
+    }
+
+    /// Makes every node, the honest ones included, accept blocks without enough approvals, so
+    /// that the blocks of the adversarial nodes aren't rejected right away.
+    pub async fn disable_doomslug(&self) {
+        for (client, view_client) in &self.nodes {
+            client
+                .send(NetworkClientMessages::Adversarial(
+                    NetworkAdversarialMessage::AdvDisableDoomslug,
+                ))
+                .await
+                .unwrap();
+            view_client
+                .send(NetworkViewClientMessages::Adversarial(
+                    NetworkAdversarialMessage::AdvDisableDoomslug,
+                ))
+                .await
+                .unwrap();
+        }
+    }
+
+    /// Height of the lowest head among the honest nodes.
+    pub async fn honest_height(&self) -> Option<BlockHeight> {
+        let mut height: Option<BlockHeight> = None;
+        for view_client in self.honest_view_clients() {
+            let head = view_client.send(GetBlock::latest()).await.ok()?.ok()?.header.height;
+            height = Some(height.map_or(head, |height| height.min(head)));
+        }
+        height
+    }
+
+    /// Hash of the head of the adversarial node `index`-th in `adversarial`.
+    pub async fn adversarial_head(&self, index: usize) -> CryptoHash {
+        let (_, view_client) = &self.nodes[self.adversarial[index]];
+        view_client.send(GetBlock::latest()).await.unwrap().unwrap().header.hash
+    }
+
+    /// Waits until all the honest nodes have the block with the given hash.
+    pub async fn wait_for_block(&self, hash: CryptoHash) {
+        loop {
+            let mut known = true;
+            for view_client in self.honest_view_clients() {
+                if !matches!(
+                    view_client.send(GetBlock(BlockId::Hash(hash).into())).await,
+                    Ok(Ok(_))
+                ) {
+                    known = false;
+                }
+            }
+            if known {
+                return;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Whether all the honest nodes have the same block at `height` on their canonical chains.
+    async fn honest_nodes_agree(&self, height: BlockHeight) -> bool {
+        let mut hashes = HashSet::new();
+        for view_client in self.honest_view_clients() {
+            match view_client.send(GetBlock(BlockId::Height(height).into())).await {
+                Ok(Ok(block)) => hashes.insert(block.header.hash),
+                _ => return false,
+            };
+        }
+        hashes.len() == 1
+    }
+
+    /// Waits until the heads of all the honest nodes are at least at `height`.
+    pub async fn wait_for_height(&self, height: BlockHeight) {
+        while self.honest_height().await.map_or(true, |head| head < height) {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Waits until the heads of all the honest nodes are at least at `height` and the nodes
+    /// agree on the block at the height of the lowest of the heads.
+    pub async fn wait_for_convergence(&self, height: BlockHeight) {
+        loop {
+            if let Some(head) = self.honest_height().await {
+                if head >= height && self.honest_nodes_agree(head).await {
+                    return;
+                }
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Runs a network of `num_nodes` validators, of which the ones in `adversarial` misbehave, and
+/// stops it once `test` is done.  Panics if that takes longer than `max_wait_ms`.
+pub fn run_mixed_network<F, R>(
+    name: &'static str,
+    num_nodes: usize,
+    adversarial: &'static [usize],
+    max_wait_ms: u64,
+    test: F,
+) where
+    F: FnOnce(MixedNetwork) -> R + 'static,
+    R: Future<Output = ()> + 'static,
+{
+    let cluster = NodeCluster::new(num_nodes, |index| format!("{}_{}", name, index))
+        .set_num_shards(1)
+        .set_num_validator_seats(num_nodes as u64)
+        .set_num_lightclients(0)
+        .set_epoch_length(20)
+        .set_genesis_height(0);
+
+    cluster.exec_until_stop(|_, _, clients| async move {
+        wait_or_panic(max_wait_ms);
+        test(MixedNetwork::new(&clients, adversarial)).await;
+        System::current().stop();
+    });
+
+    // See `run_nodes.rs` for why it is here.
+    std::thread::sleep(Duration::from_millis(250));
+}
+
+/// A validator withholds the blocks it produces and sends them all out later, while the rest
+/// of the network keeps building its own chain.  The withheld blocks must not become the head
+/// of the validator, which would hand them out to its peers.
+#[test]
+#[cfg_attr(not(feature = "expensive_tests"), ignore)]
+fn test_private_fork_released() {
+    run_mixed_network("private_fork", 4, &[3], 60000, |network| async move {
+        network.disable_doomslug().await;
+        network.wait_for_height(5).await;
+        let responses =
+            network.misbehave(|| NetworkAdversarialMessage::AdvProducePrivateFork(3)).await;
+        assert!(matches!(responses[..], [NetworkClientResponses::AdvResult(3)]));
+        // Hangs until the test times out if the head is one of the withheld blocks.
+        network.wait_for_block(network.adversarial_head(0).await).await;
+        let height = network.honest_height().await.unwrap();
+        let responses =
+            network.misbehave(|| NetworkAdversarialMessage::AdvReleasePrivateFork).await;
+        assert!(matches!(responses[..], [NetworkClientResponses::AdvResult(3)]));
+        network.wait_for_convergence(height + 10).await;
+    });
+}
+
+/// A validator signs two different blocks at the same height.
+#[test]
+#[cfg_attr(not(feature = "expensive_tests"), ignore)]
+fn test_double_sign() {
+    run_mixed_network("double_sign", 4, &[3], 60000, |network| async move {
+        network.wait_for_height(5).await;
+        network.misbehave(|| NetworkAdversarialMessage::AdvDoubleSign).await;
+        network.wait_for_convergence(20).await;
+    });
+}
+
+/// A chunk producer produces a chunk which doesn't match the state of the shard.
+#[test]
+#[cfg_attr(not(feature = "expensive_tests"), ignore)]
+fn test_invalid_state_root_chunk() {
+    run_mixed_network("invalid_state_root", 4, &[3], 60000, |network| async move {
+        network.wait_for_height(5).await;
+        network.misbehave(|| NetworkAdversarialMessage::AdvProduceInvalidStateRootChunk).await;
+        network.wait_for_convergence(20).await;
+    });
+}
+
+/// A validator never approves skipping a height the block of which it didn't get.
+#[test]
+#[cfg_attr(not(feature = "expensive_tests"), ignore)]
+fn test_doomslug_timeouts_disabled() {
+    run_mixed_network("doomslug_timeouts", 4, &[3], 60000, |network| async move {
+        network.misbehave(|| NetworkAdversarialMessage::AdvDisableDoomslugTimeouts).await;
+        network.wait_for_convergence(20).await;
+    });
+}
@@ -1,3 +1,5 @@
+#[cfg(feature = "adversarial")]
+mod adversarial_nodes;
 mod node_cluster;
 mod rpc_error_structs;
 mod rpc_nodes;
@@ -8,7 +8,7 @@ use futures::{future, FutureExt, TryFutureExt};
 
 use crate::genesis_helpers::genesis_block;
 use near_actix_test_utils::spawn_interruptible;
-use near_client::{GetBlock, GetExecutionOutcome, GetValidatorInfo};
+use near_client::{GetAppliedChunkInfo, GetBlock, GetExecutionOutcome, GetValidatorInfo};
 use near_crypto::{InMemorySigner, KeyType};
 use near_jsonrpc::client::new_client;
 use near_logger_utils::init_integration_logger;
@@ -68,6 +68,69 @@ fn test_get_validator_info_rpc() {
     });
 }
 
+/// The view client behind `/debug/api/applied_chunk_info` returns the parameters the node applied
+/// the chunk of the block with.
+#[test]
+#[cfg_attr(not(feature = "expensive_tests"), ignore)]
+fn test_applied_chunk_info_rpc() {
+    init_integration_logger();
+
+    let cluster = NodeCluster::new(1, |index| format!("applied_chunk_info{}", index))
+        .set_num_shards(1)
+        .set_num_validator_seats(1)
+        .set_num_lightclients(0)
+        .set_epoch_length(10)
+        .set_genesis_height(0);
+
+    cluster.exec_until_stop(|genesis, _, clients| async move {
+        WaitOrTimeoutActor::new(
+            Box::new(move |_ctx| {
+                let view_client = clients[0].1.clone();
+                let protocol_version = genesis.config.protocol_version;
+                spawn_interruptible(async move {
+                    let block_view = view_client.send(GetBlock::latest()).await.unwrap().unwrap();
+                    if block_view.header.height <= 1 {
+                        return;
+                    }
+                    let prev_block_view = view_client
+                        .send(GetBlock(BlockReference::BlockId(BlockId::Hash(
+                            block_view.header.prev_hash,
+                        ))))
+                        .await
+                        .unwrap()
+                        .unwrap();
+                    let infos = view_client
+                        .send(GetAppliedChunkInfo { block_hash: block_view.header.hash })
+                        .await
+                        .unwrap()
+                        .unwrap();
+                    assert_eq!(infos.len(), 1);
+                    let info = &infos[&0];
+                    assert_eq!(info.block_height, block_view.header.height);
+                    assert_eq!(
+                        info.is_new_chunk,
+                        block_view.chunks[0].height_included == block_view.header.height
+                    );
+                    // Missing chunks are applied with the gas price of the block itself.
+                    let gas_price = if info.is_new_chunk {
+                        prev_block_view.header.gas_price
+                    } else {
+                        block_view.header.gas_price
+                    };
+                    assert_eq!(info.gas_price, gas_price);
+                    assert_eq!(info.random_seed, block_view.header.random_value);
+                    assert_eq!(info.protocol_version, protocol_version);
+                    assert!(!info.is_first_block_of_version);
+                    System::current().stop();
+                });
+            }),
+            100,
+            40000,
+        )
+        .start();
+    });
+}
+
 fn outcome_view_to_hashes(outcome: &ExecutionOutcomeView) -> Vec<CryptoHash> {
     let status = match &outcome.status {
         ExecutionStatusView::Unknown => PartialExecutionStatus::Unknown,
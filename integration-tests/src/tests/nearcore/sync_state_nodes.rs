@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
@@ -105,6 +106,122 @@ fn sync_state_nodes() {
     });
 }
 
+/// Two clients state sync from the same validator at the same time. The validator throttles
+/// state requests so tightly that some of them are rejected, yet both clients finish syncing
+/// and the validator keeps producing blocks with chunks meanwhile.
+#[test]
+#[cfg_attr(not(feature = "expensive_tests"), ignore)]
+fn sync_state_nodes_throttled() {
+    heavy_test(|| {
+        init_integration_logger();
+
+        let genesis = Genesis::test(vec!["test1".parse().unwrap()], 1);
+
+        let (port1, port2, port3) = (open_port(), open_port(), open_port());
+        let mut near1 = load_test_config("test1", port1, genesis.clone());
+        near1.network_config.boot_nodes = convert_boot_nodes(vec![]);
+        near1.client_config.min_num_peers = 0;
+        near1.client_config.epoch_sync_enabled = false;
+        let throttle = &mut near1.network_config.request_throttle;
+        throttle.state_requests_per_peer_rate = 1.0;
+        throttle.state_requests_per_peer_burst = 1;
+        throttle.state_requests_global_rate = 1.0;
+        throttle.state_requests_global_burst = 1;
+        throttle.state_requests_max_in_flight = 1;
+        run_actix(async move {
+            let dir1 = tempfile::Builder::new().prefix("sync_nodes_1").tempdir().unwrap();
+            let nearcore::NearNode { view_client: view_client1, .. } =
+                start_with_config(dir1.path(), near1).expect("start_with_config");
+
+            let syncing_view_clients = Arc::new(RwLock::new(vec![]));
+            let synced = Arc::new(RwLock::new(HashSet::new()));
+            let arbiters_holder = Arc::new(RwLock::new(vec![]));
+            let dirs_holder = Arc::new(RwLock::new(vec![]));
+
+            WaitOrTimeoutActor::new(
+                Box::new(move |_ctx| {
+                    let syncing_view_clients2 = syncing_view_clients.clone();
+                    let synced2 = synced.clone();
+                    let arbiters_holder2 = arbiters_holder.clone();
+                    let dirs_holder2 = dirs_holder.clone();
+                    let genesis2 = genesis.clone();
+                    actix::spawn(view_client1.send(GetBlock::latest()).then(move |res| {
+                        let block = match res {
+                            Ok(Ok(block)) => block,
+                            _ => return future::ready(()),
+                        };
+                        let mut syncing_view_clients = syncing_view_clients2.write().unwrap();
+                        if syncing_view_clients.is_empty() {
+                            if block.header.height < 101 {
+                                println!("FIRST STAGE {}", block.header.height);
+                                return future::ready(());
+                            }
+                            for (account_id, port) in [("test2", port2), ("test3", port3)] {
+                                let mut near = load_test_config(account_id, port, genesis2.clone());
+                                near.client_config.skip_sync_wait = false;
+                                near.client_config.min_num_peers = 1;
+                                near.network_config.boot_nodes =
+                                    convert_boot_nodes(vec![("test1", port1)]);
+                                near.client_config.epoch_sync_enabled = false;
+
+                                let dir =
+                                    tempfile::Builder::new().prefix(account_id).tempdir().unwrap();
+                                let nearcore::NearNode { view_client, arbiters, .. } =
+                                    start_with_config(dir.path(), near).expect("start_with_config");
+                                syncing_view_clients.push(view_client);
+                                arbiters_holder2.write().unwrap().extend(arbiters);
+                                dirs_holder2.write().unwrap().push(dir);
+                            }
+                            return future::ready(());
+                        }
+                        // The validator must not be starved by serving state.
+                        assert!(block
+                            .chunks
+                            .iter()
+                            .all(|chunk| chunk.height_included
+                                >= block.header.height.saturating_sub(5)));
+                        let synced = synced2.read().unwrap();
+                        if synced.len() == syncing_view_clients.len() && block.header.height >= 121
+                        {
+                            // Some requests must have been rejected and retried later.
+                            #[cfg(feature = "test_features")]
+                            assert!(
+                                near_network::STATE_REQUEST_REJECTED
+                                    .with_label_values(&["peer_rate_limit"])
+                                    .get()
+                                    + near_network::STATE_REQUEST_REJECTED
+                                        .with_label_values(&["global_rate_limit"])
+                                        .get()
+                                    > 0
+                            );
+                            System::current().stop();
+                        }
+                        future::ready(())
+                    }));
+
+                    for (i, view_client) in syncing_view_clients.read().unwrap().iter().enumerate()
+                    {
+                        let synced2 = synced.clone();
+                        actix::spawn(view_client.send(GetBlock::latest()).then(move |res| {
+                            match &res {
+                                Ok(Ok(b)) if b.header.height >= 101 => {
+                                    synced2.write().unwrap().insert(i);
+                                }
+                                Ok(Ok(b)) => println!("SECOND STAGE {} {}", i, b.header.height),
+                                _ => {}
+                            };
+                            future::ready(())
+                        }));
+                    }
+                }),
+                100,
+                120000,
+            )
+            .start();
+        });
+    });
+}
+
 /// One client is in front, another must sync to it using state (fast) sync.
 #[cfg(feature = "expensive_tests")]
 #[test]
@@ -58,6 +58,7 @@ pub fn make_peer_manager(
                     height: 1,
                     tracked_shards: vec![],
                     archival: false,
+                    serves_state_sync: true,
                 }))
             }
             _ => Box::new(Some(NetworkViewClientResponses::NoResponse)),
@@ -6,3 +6,4 @@ mod peer_handshake;
 mod routing;
 mod runner;
 mod stress_network;
+mod tier1;
@@ -55,6 +55,7 @@ fn make_peer_manager(
                     height: 1,
                     tracked_shards: vec![],
                     archival: false,
+                    serves_state_sync: true,
                 }))
             }
             _ => Box::new(Some(NetworkViewClientResponses::NoResponse)),
@@ -284,3 +285,17 @@ fn connection_spam_security_test() {
     });
     assert_eq!(vec2.read().unwrap().len(), 100);
 }
+
+/// Connected peers announce their capabilities to each other.
+#[cfg(feature = "nightly_protocol")]
+#[test]
+fn peers_announce_capabilities() {
+    let mut runner = Runner::new(2, 1).set_as_archival(1);
+
+    runner.push(Action::AddEdge(0, 1));
+    runner.push(Action::CheckRoutingTable(0, vec![(1, vec![1])]));
+    runner.push_action(check_announced_archival(0, 1, true));
+    runner.push_action(check_announced_archival(1, 0, false));
+
+    start_test(runner);
+}
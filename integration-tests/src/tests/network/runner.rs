@@ -19,7 +19,7 @@ use near_crypto::KeyType;
 use near_logger_utils::init_test_logger;
 use near_network::test_utils::{
     convert_boot_nodes, expected_routing_tables, open_port, peer_id_from_seed, BanPeerSignal,
-    GetInfo, NetworkRecipient, StopSignal, WaitOrTimeoutActor,
+    GetAccountsData, GetInfo, NetworkRecipient, StopSignal, WaitOrTimeoutActor,
 };
 
 use near_network::routing::start_routing_table_actor;
@@ -32,7 +32,7 @@ use near_network_primitives::types::{
     NetworkConfig, OutboundTcpConnect, PeerInfo, ROUTED_MESSAGE_TTL,
 };
 use near_primitives::network::PeerId;
-use near_primitives::types::{AccountId, ValidatorId};
+use near_primitives::types::{AccountId, EpochId, ValidatorId};
 use near_primitives::validator_signer::InMemoryValidatorSigner;
 use near_store::test_utils::create_test_store;
 use near_telemetry::{TelemetryActor, TelemetryConfig};
@@ -973,6 +973,101 @@ pub fn change_account_id(node_id: usize, account_id: AccountId) -> ActionFn {
     )
 }
 
+/// Make the accounts of the nodes `accounts` the TIER1 accounts of `node_id`.
+pub fn set_tier1_accounts(node_id: usize, accounts: Vec<usize>) -> ActionFn {
+    Box::new(
+        move |info: SharedRunningInfo,
+              flag: Arc<AtomicBool>,
+              _ctx: &mut Context<WaitOrTimeoutActor>,
+              _runner| {
+            debug!(target: "network", node_id, ?accounts, "runner.rs: set_tier1_accounts");
+            let accounts =
+                accounts.iter().map(|ix| format!("test{}", ix).parse().unwrap()).collect();
+            info.read().unwrap().pm_addr[node_id].do_send(
+                PeerManagerMessageRequest::NetworkRequests(NetworkRequests::SetTier1Accounts {
+                    epoch_id: EpochId::default(),
+                    accounts,
+                }),
+            );
+            flag.store(true, Ordering::Relaxed);
+        },
+    )
+}
+
+/// Check that `node_id` accepted the TIER1 accounts data of the nodes `known` and not the one of
+/// the nodes `unknown`.
+pub fn check_accounts_data(node_id: usize, known: Vec<usize>, unknown: Vec<usize>) -> ActionFn {
+    let can_write_log = Arc::new(AtomicBool::new(true));
+    Box::new(
+        move |info: SharedRunningInfo,
+              flag: Arc<AtomicBool>,
+              _ctx: &mut Context<WaitOrTimeoutActor>,
+              _runner| {
+            if can_write_log.swap(false, Ordering::Relaxed) == true {
+                debug!(target: "network", node_id, ?known, ?unknown, "runner.rs: check_accounts_data");
+            }
+            let info = info.read().unwrap();
+            let peer_ids = |nodes: &Vec<usize>| -> Vec<PeerId> {
+                nodes.iter().map(|ix| info.peers_info[*ix].id.clone()).collect()
+            };
+            let (known, unknown) = (peer_ids(&known), peer_ids(&unknown));
+            actix::spawn(
+                info.pm_addr[node_id]
+                    .send(GetAccountsData {})
+                    .map_err(|_| ())
+                    .and_then(move |accounts_data| {
+                        let peer_ids: HashSet<_> = accounts_data
+                            .into_iter()
+                            .map(|data| data.account_data.peer_id)
+                            .collect();
+                        if known.iter().all(|peer_id| peer_ids.contains(peer_id)) {
+                            assert!(unknown.iter().all(|peer_id| !peer_ids.contains(peer_id)));
+                            flag.store(true, Ordering::Relaxed);
+                        }
+                        future::ok(())
+                    })
+                    .map(drop),
+            );
+        },
+    )
+}
+
+/// Check that `node_id` is connected to `target_id` and knows whether it's archival from the
+/// capabilities `target_id` announced.
+pub fn check_announced_archival(node_id: usize, target_id: usize, archival: bool) -> ActionFn {
+    let can_write_log = Arc::new(AtomicBool::new(true));
+    Box::new(
+        move |info: SharedRunningInfo,
+              flag: Arc<AtomicBool>,
+              _ctx: &mut Context<WaitOrTimeoutActor>,
+              _runner| {
+            if can_write_log.swap(false, Ordering::Relaxed) == true {
+                debug!(target: "network", node_id, target_id, archival, "runner.rs: check_announced_archival");
+            }
+            let info = info.read().unwrap();
+            let target_peer_id = info.peers_info[target_id].id.clone();
+            actix::spawn(
+                info.pm_addr[node_id]
+                    .send(GetInfo {})
+                    .map_err(|_| ())
+                    .and_then(move |res| {
+                        let capabilities = res
+                            .connected_peers
+                            .into_iter()
+                            .find(|peer| peer.peer_info.id == target_peer_id)
+                            .and_then(|peer| peer.capabilities);
+                        if let Some(capabilities) = capabilities {
+                            assert_eq!(capabilities.archival(), archival);
+                            flag.store(true, Ordering::Relaxed);
+                        }
+                        future::ok(())
+                    })
+                    .map(drop),
+            );
+        },
+    )
+}
+
 /// Wait for predicate to return True.
 #[cfg(feature = "test_features")]
 pub fn wait_for<T>(predicate: T) -> ActionFn
@@ -43,6 +43,7 @@ fn make_peer_manager(seed: &str, port: u16, boot_nodes: Vec<(&str, u16)>) -> Pee
                     height: 1,
                     tracked_shards: vec![],
                     archival: false,
+                    serves_state_sync: true,
                 }))
             }
             _ => Box::new(Some(NetworkViewClientResponses::NoResponse)),
@@ -0,0 +1,37 @@
+use crate::tests::network::runner::*;
+
+/// TIER1 accounts data of a validator is broadcast to its peers, which accept it once they know
+/// the peer the validator's account is announced by, and forward it to their own peers.
+#[cfg(feature = "nightly_protocol")]
+#[test]
+fn accounts_data_propagation() {
+    let mut runner = Runner::new(3, 3);
+
+    runner.push(Action::AddEdge(0, 1));
+    runner.push(Action::AddEdge(1, 2));
+    runner.push(Action::CheckAccountId(1, vec![0, 1, 2]));
+    runner.push(Action::CheckAccountId(2, vec![0, 1, 2]));
+    for node_id in 0..3 {
+        runner.push_action(set_tier1_accounts(node_id, vec![0, 1, 2]));
+    }
+    runner.push_action(check_accounts_data(0, vec![0, 1, 2], vec![]));
+    runner.push_action(check_accounts_data(2, vec![0, 1, 2], vec![]));
+
+    start_test(runner);
+}
+
+/// Accounts data isn't accepted for accounts which aren't announced by the peer which signed it.
+#[cfg(feature = "nightly_protocol")]
+#[test]
+fn accounts_data_of_unannounced_account() {
+    let mut runner = Runner::new(2, 1);
+
+    runner.push(Action::AddEdge(0, 1));
+    runner.push(Action::CheckRoutingTable(0, vec![(1, vec![1])]));
+    // Node 1 isn't a validator, so it doesn't announce its account.
+    runner.push_action(set_tier1_accounts(1, vec![0, 1]));
+    runner.push(Action::Wait(2000));
+    runner.push_action(check_accounts_data(0, vec![], vec![1]));
+
+    start_test(runner);
+}
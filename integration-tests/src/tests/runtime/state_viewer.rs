@@ -1,8 +1,11 @@
 use crate::runtime_utils::{get_runtime_and_trie, get_test_trie_viewer, TEST_SHARD_UID};
+use near_chain_configs::ViewRegisterLimits;
 use near_primitives::{
     account::Account,
+    contract::ContractCode,
     hash::hash as sha256,
     hash::CryptoHash,
+    runtime::config_store::RuntimeConfigStore,
     views::{StateItem, ViewApplyState},
 };
 use near_primitives::{
@@ -11,7 +14,7 @@ use near_primitives::{
     types::{EpochId, StateChangeCause},
     version::PROTOCOL_VERSION,
 };
-use near_store::set_account;
+use near_store::{get_account, set_account, set_code, TrieUpdate};
 use node_runtime::state_viewer::errors;
 use node_runtime::state_viewer::*;
 use testlib::runtime_utils::{alice_account, encode_int};
@@ -27,6 +30,7 @@ fn test_view_call() {
         block_hash: CryptoHash::default(),
         epoch_id: EpochId::default(),
         epoch_height: 0,
+        shard_id: 0,
         block_timestamp: 1,
         current_protocol_version: PROTOCOL_VERSION,
         cache: None,
@@ -41,7 +45,7 @@ fn test_view_call() {
         &MockEpochInfoProvider::default(),
     );
 
-    assert_eq!(result.unwrap(), encode_int(10));
+    assert_eq!(result.unwrap().result, encode_int(10));
 }
 
 #[test]
@@ -55,6 +59,7 @@ fn test_view_call_try_changing_storage() {
         block_hash: CryptoHash::default(),
         epoch_id: EpochId::default(),
         epoch_height: 0,
+        shard_id: 0,
         block_timestamp: 1,
         current_protocol_version: PROTOCOL_VERSION,
         cache: None,
@@ -87,6 +92,7 @@ fn test_view_call_with_args() {
         block_hash: CryptoHash::default(),
         epoch_id: EpochId::default(),
         epoch_height: 0,
+        shard_id: 0,
         block_timestamp: 1,
         current_protocol_version: PROTOCOL_VERSION,
         cache: None,
@@ -100,7 +106,149 @@ fn test_view_call_with_args() {
         &mut logs,
         &MockEpochInfoProvider::default(),
     );
-    assert_eq!(view_call_result.unwrap(), 3u64.to_le_bytes().to_vec());
+    assert_eq!(view_call_result.unwrap().result, 3u64.to_le_bytes().to_vec());
+}
+
+#[test]
+fn test_view_call_with_register_limits() {
+    let (mut viewer, root) = get_test_trie_viewer();
+    // The input of `sum_with_input` is read into a register.
+    viewer.set_register_limits(ViewRegisterLimits {
+        max_register_size: Some(8),
+        ..ViewRegisterLimits::default()
+    });
+    let args: Vec<_> = [1u64, 2u64].iter().flat_map(|x| (*x).to_le_bytes().to_vec()).collect();
+    let mut logs = vec![];
+    let view_state = ViewApplyState {
+        block_height: 1,
+        prev_block_hash: CryptoHash::default(),
+        block_hash: CryptoHash::default(),
+        epoch_id: EpochId::default(),
+        epoch_height: 0,
+        shard_id: 0,
+        block_timestamp: 1,
+        current_protocol_version: PROTOCOL_VERSION,
+        cache: None,
+    };
+    let err = viewer
+        .call_function(
+            root,
+            view_state,
+            &"test.contract".parse().unwrap(),
+            "sum_with_input",
+            &args,
+            &mut logs,
+            &MockEpochInfoProvider::default(),
+        )
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("MemoryAccessViolation"),
+        "Got different error that doesn't match: {}",
+        err
+    );
+}
+
+fn view_apply_state() -> ViewApplyState {
+    ViewApplyState {
+        block_height: 1,
+        prev_block_hash: CryptoHash::default(),
+        block_hash: CryptoHash::default(),
+        epoch_id: EpochId::default(),
+        epoch_height: 0,
+        shard_id: 0,
+        block_timestamp: 1,
+        current_protocol_version: PROTOCOL_VERSION,
+        cache: None,
+    }
+}
+
+fn view_call(
+    viewer: &TrieViewer,
+    state_update: TrieUpdate,
+    method_name: &str,
+    args: &[u8],
+) -> Result<CallFunctionResult, errors::CallFunctionError> {
+    viewer.call_function(
+        state_update,
+        view_apply_state(),
+        &"test.contract".parse().unwrap(),
+        method_name,
+        args,
+        &mut vec![],
+        &MockEpochInfoProvider::default(),
+    )
+}
+
+#[test]
+fn test_view_call_gas_estimate() {
+    let (viewer, root) = get_test_trie_viewer();
+    let args: Vec<_> = [1u64, 2u64].iter().flat_map(|x| (*x).to_le_bytes().to_vec()).collect();
+    let result = view_call(&viewer, root, "sum_with_input", &args).unwrap();
+    assert!(result.gas_burnt > 0);
+    let config = RuntimeConfigStore::new(None).get_config(PROTOCOL_VERSION).clone();
+    let fees = &config.transaction_costs;
+    assert_eq!(
+        result.recommended_attached_gas,
+        recommended_attached_gas(fees, "sum_with_input", &args, result.gas_burnt)
+    );
+    assert_eq!(
+        result.recommended_attached_gas - result.gas_burnt,
+        fees.action_receipt_creation_config.exec_fee()
+            + fees.action_creation_config.function_call_cost.exec_fee()
+            + fees.action_creation_config.function_call_cost_per_byte.exec_fee()
+                * ("sum_with_input".len() + args.len()) as u64
+    );
+}
+
+/// The estimate is only valid for the state it was made in, reading a value which didn't exist
+/// at the time of the view call costs more.
+#[test]
+fn test_view_call_gas_estimate_depends_on_state() {
+    let key = 1u64.to_le_bytes();
+    let (viewer, root) = get_test_trie_viewer();
+    let missing = view_call(&viewer, root, "read_value", &key).unwrap();
+    assert_eq!(missing.result, Vec::<u8>::new());
+
+    let (viewer, mut root) = get_test_trie_viewer();
+    root.set(
+        TrieKey::ContractData { account_id: "test.contract".parse().unwrap(), key: key.to_vec() },
+        2u64.to_le_bytes().to_vec(),
+    );
+    let present = view_call(&viewer, root, "read_value", &key).unwrap();
+    assert_eq!(present.result, 2u64.to_le_bytes().to_vec());
+    assert!(present.gas_burnt > missing.gas_burnt);
+    assert!(present.recommended_attached_gas > missing.recommended_attached_gas);
+}
+
+#[test]
+fn test_view_call_gas_estimate_unavailable_with_promises() {
+    let (viewer, mut root) = get_test_trie_viewer();
+    let code = ContractCode::new(
+        wat::parse_str(
+            r#"
+            (module
+              (import "env" "promise_batch_create" (func $promise_batch_create (param i64 i64) (result i64)))
+              (memory 1)
+              (data (i32.const 0) "test.contract")
+              (func (export "main")
+                (drop (call $promise_batch_create (i64.const 13) (i64.const 0)))))
+            "#,
+        )
+        .unwrap(),
+        None,
+    );
+    let account_id = "test.contract".parse().unwrap();
+    let mut account = get_account(&root, &account_id).unwrap().unwrap();
+    account.set_code_hash(*code.hash());
+    set_account(&mut root, account_id.clone(), &account);
+    set_code(&mut root, account_id, &code);
+
+    let err = view_call(&viewer, root, "main", &[]).unwrap_err();
+    assert!(
+        err.to_string().contains("gas estimate unavailable"),
+        "Got different error that doesn't match: {}",
+        err
+    );
 }
 
 #[test]
@@ -188,6 +336,7 @@ fn test_log_when_panic() {
         block_hash: CryptoHash::default(),
         epoch_id: EpochId::default(),
         epoch_height: 0,
+        shard_id: 0,
         block_timestamp: 1,
         current_protocol_version: PROTOCOL_VERSION,
         cache: None,
@@ -853,6 +853,7 @@ pub fn test_add_access_key_function_call(node: impl Node) {
             receiver_id: account_id.to_string(),
             method_names: vec![],
         }),
+        valid_until_block_height: None,
     };
     let signer2 = InMemorySigner::from_random("test".parse().unwrap(), KeyType::ED25519);
     let result = add_access_key(&node, node_user.as_ref(), &access_key, &signer2);
@@ -873,6 +874,7 @@ pub fn test_delete_access_key(node: impl Node) {
             receiver_id: account_id.to_string(),
             method_names: vec![],
         }),
+        valid_until_block_height: None,
     };
     let signer2 = InMemorySigner::from_random("test".parse().unwrap(), KeyType::ED25519);
     add_access_key(&node, node_user.as_ref(), &access_key, &signer2);
@@ -901,6 +903,7 @@ pub fn test_add_access_key_with_allowance(node: impl Node) {
             receiver_id: account_id.to_string(),
             method_names: vec![],
         }),
+        valid_until_block_height: None,
     };
     let node_user = node.user();
     let signer2 = InMemorySigner::from_random("test".parse().unwrap(), KeyType::ED25519);
@@ -927,6 +930,7 @@ pub fn test_delete_access_key_with_allowance(node: impl Node) {
             receiver_id: account_id.to_string(),
             method_names: vec![],
         }),
+        valid_until_block_height: None,
     };
     let node_user = node.user();
     let signer2 = InMemorySigner::from_random("test".parse().unwrap(), KeyType::ED25519);
@@ -963,6 +967,7 @@ pub fn test_access_key_smart_contract(node: impl Node) {
             receiver_id: bob_account().into(),
             method_names: vec![],
         }),
+        valid_until_block_height: None,
     };
     let mut node_user = node.user();
     let account_id = &node.account_id().unwrap();
@@ -1002,6 +1007,7 @@ pub fn test_access_key_smart_contract(node: impl Node) {
                 receiver_id: bob_account().into(),
                 method_names: vec![],
             }),
+            valid_until_block_height: None,
         }
         .into()
     );
@@ -1015,6 +1021,7 @@ pub fn test_access_key_smart_contract_reject_method_name(node: impl Node) {
             receiver_id: bob_account().into(),
             method_names: vec!["log_something".to_string()],
         }),
+        valid_until_block_height: None,
     };
     let mut node_user = node.user();
     let account_id = &node.account_id().unwrap();
@@ -1043,6 +1050,7 @@ pub fn test_access_key_smart_contract_reject_contract_id(node: impl Node) {
             receiver_id: bob_account().into(),
             method_names: vec![],
         }),
+        valid_until_block_height: None,
     };
     let mut node_user = node.user();
     let account_id = &node.account_id().unwrap();
@@ -1080,6 +1088,7 @@ pub fn test_access_key_reject_non_function_call(node: impl Node) {
             receiver_id: account_id.to_string(),
             method_names: vec![],
         }),
+        valid_until_block_height: None,
     };
     let mut node_user = node.user();
     let signer2 = InMemorySigner::from_random("test".parse().unwrap(), KeyType::ED25519);
@@ -100,7 +100,11 @@ impl User for RpcUser {
         let query_response = self.query(format!("call/{}/{}", account_id, method_name), args)?;
         match query_response.kind {
             near_jsonrpc_primitives::types::query::QueryResponseKind::CallResult(call_result) => {
-                Ok(call_result)
+                Ok(CallResult {
+                    gas_burnt: call_result.gas_burnt,
+                    recommended_attached_gas: call_result.recommended_attached_gas,
+                    ..call_result.call_result
+                })
             }
             _ => Err("Invalid type of response".into()),
         }
@@ -111,6 +111,7 @@ impl RuntimeUser {
                     }
                     RuntimeError::ReceiptValidationError(e) => panic!("{}", e),
                     RuntimeError::ValidatorError(e) => panic!("{}", e),
+                    RuntimeError::InvalidStatePatch(e) => panic!("{}", e),
                 })?;
             for outcome_with_id in apply_result.outcomes {
                 self.transaction_results
@@ -143,8 +144,10 @@ impl RuntimeUser {
             block_hash: Default::default(),
             block_timestamp: 0,
             epoch_height: 0,
+            shard_id: 0,
             gas_price: MIN_GAS_PRICE,
             gas_limit: None,
+            compute_limit: None,
             random_seed: Default::default(),
             epoch_id: Default::default(),
             current_protocol_version: PROTOCOL_VERSION,
@@ -254,11 +257,12 @@ impl User for RuntimeUser {
             block_hash: apply_state.block_hash,
             epoch_id: apply_state.epoch_id,
             epoch_height: apply_state.epoch_height,
+            shard_id: apply_state.shard_id,
             block_timestamp: apply_state.block_timestamp,
             current_protocol_version: PROTOCOL_VERSION,
             cache: apply_state.cache,
         };
-        result.result = self
+        let call_function_result = self
             .trie_viewer
             .call_function(
                 state_update,
@@ -270,6 +274,9 @@ impl User for RuntimeUser {
                 &self.epoch_info_provider,
             )
             .map_err(|err| err.to_string())?;
+        result.result = call_function_result.result;
+        result.gas_burnt = Some(call_function_result.gas_burnt);
+        result.recommended_attached_gas = Some(call_function_result.recommended_attached_gas);
         Ok(result)
     }
 
@@ -0,0 +1,100 @@
+//! Migration of the history of an archival node into a cold database, for
+//! `neard cold-store migrate`.
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Context;
+use tracing::info;
+
+use near_chain::{ChainStore, ChainStoreAccess};
+use near_primitives::version::DB_VERSION;
+use near_store::cold_migration::ColdMigration;
+use near_store::db::RocksDB;
+use near_store::migrations::set_store_version;
+use near_store::{create_store_with_config, Store, StoreConfig};
+
+use crate::config::{ColdStoreConfig, Config, CONFIG_FILENAME};
+use crate::{get_store_path, store_path_exists, NearConfig};
+
+/// Number of batches between two progress logs.
+const LOG_EVERY_BATCHES: u64 = 100;
+
+/// Copies the historical columns of the archival database in `home_dir` into the cold database
+/// at `cold_path`, relative to `home_dir`.  The hot database is opened read-only, so the node
+/// may be running, in which case the blocks it adds meanwhile are left for the next migration.
+/// An interrupted migration resumes where it stopped.  Once the copy is verified,
+/// `store.cold_store` is set in config.json.
+pub fn migrate_to_cold_store(
+    home_dir: &Path,
+    near_config: &NearConfig,
+    cold_path: &Path,
+    batch_size: usize,
+    verify_every: u64,
+) -> anyhow::Result<ColdStoreConfig> {
+    anyhow::ensure!(near_config.client_config.archive, "only archival nodes have a cold store");
+    let store_path = get_store_path(home_dir);
+    anyhow::ensure!(
+        store_path_exists(&store_path),
+        "{}: storage doesn’t exist",
+        store_path.display()
+    );
+    let hot_store = create_store_with_config(
+        &store_path,
+        StoreConfig { read_only: true, ..Default::default() },
+    );
+    let genesis_height = near_config.genesis.config.genesis_height;
+    let chain_store = ChainStore::new(hot_store.clone(), genesis_height);
+    let tail = chain_store.tail()?;
+    anyhow::ensure!(
+        tail <= genesis_height,
+        "the database was garbage collected up to #{}, it isn't archival",
+        tail
+    );
+    let migrated_height = chain_store.final_head()?.height;
+    let hot = hot_store.get_rocksdb().context("the store isn't backed by RocksDB")?;
+
+    let mut cold = Store::new(Arc::new(RocksDB::new(home_dir.join(cold_path))?));
+    if let Some(limit) = near_config.config.store.write_batch_soft_limit {
+        cold = cold.with_write_batch_soft_limit(limit);
+    }
+    set_store_version(&cold, DB_VERSION);
+    let mut migration = ColdMigration::new(hot, &cold, migrated_height, batch_size, verify_every)?;
+    let migrated_height = migration.progress().migrated_height;
+    info!(
+        target: "neard",
+        "Migrating the history up to #{} into {}",
+        migrated_height,
+        home_dir.join(cold_path).display()
+    );
+    let mut batches = 0;
+    while migration.copy_batch()? {
+        batches += 1;
+        if batches % LOG_EVERY_BATCHES == 0 {
+            let progress = migration.progress();
+            info!(
+                target: "neard",
+                "Copying {:?}: {} keys and {} bytes copied",
+                progress.column,
+                progress.copied_keys,
+                progress.copied_bytes
+            );
+        }
+    }
+    let verified = migration.verify()?;
+    info!(
+        target: "neard",
+        "Copied {} keys and {} bytes, {} of them verified",
+        migration.progress().copied_keys,
+        migration.progress().copied_bytes,
+        verified
+    );
+
+    let cold_store = ColdStoreConfig { path: cold_path.to_path_buf(), migrated_height };
+    let config_path = home_dir.join(CONFIG_FILENAME);
+    let mut config = Config::from_file(&config_path)?;
+    config.store.cold_store = Some(cold_store.clone());
+    config
+        .write_to_file(&config_path)
+        .with_context(|| format!("Failed to write config to {}", config_path.display()))?;
+    Ok(cold_store)
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fs;
 use std::fs::File;
 use std::io::{Read, Write};
@@ -18,14 +19,18 @@ use tokio::io::AsyncWriteExt;
 use tracing::{error, info, warn};
 
 use near_chain_configs::{
-    get_initial_supply, ClientConfig, Genesis, GenesisConfig, GenesisValidationMode,
-    LogSummaryStyle,
+    get_initial_supply, ClientConfig, DelegatedStakeViewConfig, Genesis, GenesisConfig,
+    GenesisValidationMode, LogSummaryStyle, ViewRegisterLimits,
+    DEFAULT_TRANSACTION_POOL_MAX_BYTES_PER_ACCOUNT, DEFAULT_TRANSACTION_POOL_MAX_TRANSACTIONS,
+    DEFAULT_TRANSACTION_POOL_MAX_TRANSACTIONS_PER_ACCOUNT,
+    DEFAULT_TRANSACTION_REPLACEMENT_FEE_BUMP_PERCENT,
 };
 use near_crypto::{InMemorySigner, KeyFile, KeyType, PublicKey, Signer};
 #[cfg(feature = "json_rpc")]
 use near_jsonrpc::RpcConfig;
 use near_network::test_utils::open_port;
 use near_network_primitives::types::{NetworkConfig, ROUTED_MESSAGE_TTL};
+use near_o11y::OpenTelemetryConfig;
 use near_primitives::account::{AccessKey, Account};
 use near_primitives::hash::CryptoHash;
 #[cfg(test)]
@@ -33,14 +38,15 @@ use near_primitives::shard_layout::account_id_to_shard_id;
 use near_primitives::shard_layout::ShardLayout;
 use near_primitives::state_record::StateRecord;
 use near_primitives::types::{
-    AccountId, AccountInfo, Balance, BlockHeightDelta, EpochHeight, Gas, NumBlocks, NumSeats,
-    NumShards, ShardId,
+    AccountId, AccountInfo, Balance, BlockHeight, BlockHeightDelta, EpochHeight, Gas, NumBlocks,
+    NumSeats, NumShards, ShardId,
 };
 use near_primitives::utils::{generate_random_string, get_num_seats_per_shard};
 use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
 use near_primitives::version::PROTOCOL_VERSION;
 #[cfg(feature = "rosetta_rpc")]
 use near_rosetta_rpc::RosettaRpcConfig;
+use near_store::db::BackgroundIoConfig;
 use near_telemetry::TelemetryConfig;
 
 /// Initial balance used in tests.
@@ -97,6 +103,9 @@ pub const BLOCK_PRODUCER_KICKOUT_THRESHOLD: u8 = 90;
 /// Criterion for kicking out chunk producers.
 pub const CHUNK_PRODUCER_KICKOUT_THRESHOLD: u8 = 90;
 
+/// Percentage of the validators' stake which must remain after kickouts in one epoch.
+pub const VALIDATOR_MIN_REMAINING_STAKE_PERC: u8 = 90;
+
 /// Fast mode constants for testing/developing.
 pub const FAST_MIN_BLOCK_PRODUCTION_DELAY: u64 = 120;
 pub const FAST_MAX_BLOCK_PRODUCTION_DELAY: u64 = 500;
@@ -247,6 +256,84 @@ pub struct Network {
     /// Period to check on peer status
     #[serde(default = "default_peer_stats_period")]
     pub peer_stats_period: Duration,
+    /// Configuration of direct connections between block and chunk producers.
+    #[serde(default)]
+    pub tier1: Tier1,
+    /// Initial TTL of routed messages by message type, e.g. `{"PartialEncodedChunkRequest": 150}`.
+    /// Messages of other types are sent with the default TTL.
+    #[serde(default)]
+    pub routed_message_ttl_overrides: HashMap<String, u8>,
+    /// Limits on state and chunk part requests served to other peers.
+    #[serde(default)]
+    pub request_throttle: RequestThrottle,
+}
+
+/// Configuration of the TIER1 network, which connects validators of the current and next epoch
+/// directly (or via proxies) to each other.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Tier1 {
+    /// Whether to establish TIER1 connections to other validators.
+    pub enable_outbound: bool,
+    /// Whether to accept TIER1 connections from other validators above `max_num_peers`.
+    pub enable_inbound: bool,
+    /// List of proxies (in `peer_id@addr` format) through which this validator can be reached.
+    /// If empty, the validator advertises its own `addr`.
+    #[serde(default)]
+    pub proxies: Vec<String>,
+}
+
+impl Default for Tier1 {
+    fn default() -> Self {
+        Tier1 { enable_outbound: true, enable_inbound: true, proxies: vec![] }
+    }
+}
+
+/// Limits on expensive requests served to other peers. Rates are in requests per second, bursts
+/// in number of requests.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct RequestThrottle {
+    pub state_requests_per_peer_rate: f64,
+    pub state_requests_per_peer_burst: u32,
+    pub state_requests_global_rate: f64,
+    pub state_requests_global_burst: u32,
+    /// Maximum number of accepted state requests waiting to be processed.
+    pub state_requests_max_queued: usize,
+    /// Maximum number of state requests processed at the same time.
+    pub state_requests_max_in_flight: usize,
+    pub chunk_part_requests_per_peer_rate: f64,
+    pub chunk_part_requests_per_peer_burst: u32,
+}
+
+impl Default for RequestThrottle {
+    fn default() -> Self {
+        let default = near_network_primitives::types::RequestThrottle::default();
+        RequestThrottle {
+            state_requests_per_peer_rate: default.state_requests_per_peer_rate,
+            state_requests_per_peer_burst: default.state_requests_per_peer_burst,
+            state_requests_global_rate: default.state_requests_global_rate,
+            state_requests_global_burst: default.state_requests_global_burst,
+            state_requests_max_queued: default.state_requests_max_queued,
+            state_requests_max_in_flight: default.state_requests_max_in_flight,
+            chunk_part_requests_per_peer_rate: default.chunk_part_requests_per_peer_rate,
+            chunk_part_requests_per_peer_burst: default.chunk_part_requests_per_peer_burst,
+        }
+    }
+}
+
+impl From<RequestThrottle> for near_network_primitives::types::RequestThrottle {
+    fn from(throttle: RequestThrottle) -> Self {
+        Self {
+            state_requests_per_peer_rate: throttle.state_requests_per_peer_rate,
+            state_requests_per_peer_burst: throttle.state_requests_per_peer_burst,
+            state_requests_global_rate: throttle.state_requests_global_rate,
+            state_requests_global_burst: throttle.state_requests_global_burst,
+            state_requests_max_queued: throttle.state_requests_max_queued,
+            state_requests_max_in_flight: throttle.state_requests_max_in_flight,
+            chunk_part_requests_per_peer_rate: throttle.chunk_part_requests_per_peer_rate,
+            chunk_part_requests_per_peer_burst: throttle.chunk_part_requests_per_peer_burst,
+        }
+    }
 }
 
 impl Default for Network {
@@ -269,6 +356,9 @@ impl Default for Network {
             blacklist: vec![],
             ttl_account_id_router: default_ttl_account_id_router(),
             peer_stats_period: default_peer_stats_period(),
+            tier1: Tier1::default(),
+            routed_message_ttl_overrides: HashMap::new(),
+            request_throttle: RequestThrottle::default(),
         }
     }
 }
@@ -298,6 +388,14 @@ fn default_header_sync_expected_height_per_second() -> u64 {
     10
 }
 
+fn default_header_sync_max_outstanding_batches() -> usize {
+    4
+}
+
+fn default_header_sync_batch_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
 fn default_sync_check_period() -> Duration {
     Duration::from_secs(10)
 }
@@ -314,6 +412,34 @@ fn default_view_client_threads() -> usize {
     4
 }
 
+fn default_transaction_replacement_fee_bump_percent() -> u64 {
+    DEFAULT_TRANSACTION_REPLACEMENT_FEE_BUMP_PERCENT
+}
+
+fn default_transaction_pool_max_transactions() -> usize {
+    DEFAULT_TRANSACTION_POOL_MAX_TRANSACTIONS
+}
+
+fn default_transaction_pool_max_transactions_per_account() -> usize {
+    DEFAULT_TRANSACTION_POOL_MAX_TRANSACTIONS_PER_ACCOUNT
+}
+
+fn default_transaction_pool_max_bytes_per_account() -> u64 {
+    DEFAULT_TRANSACTION_POOL_MAX_BYTES_PER_ACCOUNT
+}
+
+fn default_missed_chunks_threshold() -> NumBlocks {
+    3
+}
+
+fn default_missed_chunks_wait_step() -> Duration {
+    Duration::from_millis(100)
+}
+
+fn default_missed_chunks_max_wait() -> Duration {
+    Duration::from_millis(500)
+}
+
 fn default_doomslug_step_period() -> Duration {
     Duration::from_millis(100)
 }
@@ -322,6 +448,10 @@ fn default_view_client_throttle_period() -> Duration {
     Duration::from_secs(30)
 }
 
+fn default_serve_state_sync() -> bool {
+    true
+}
+
 fn default_trie_viewer_state_size_limit() -> Option<u64> {
     Some(50_000)
 }
@@ -361,6 +491,16 @@ pub struct Consensus {
     pub catchup_step_period: Duration,
     /// Time between checking to re-request chunks.
     pub chunk_request_retry_period: Duration,
+    /// Number of consecutive blocks without a new chunk for a shard after which block producers
+    /// wait longer for the chunk of that shard.
+    #[serde(default = "default_missed_chunks_threshold")]
+    pub missed_chunks_threshold: NumBlocks,
+    /// How much longer to wait for the chunk of such a shard for each further missed chunk.
+    #[serde(default = "default_missed_chunks_wait_step")]
+    pub missed_chunks_wait_step: Duration,
+    /// Upper bound of the extra wait for the chunk of a shard.
+    #[serde(default = "default_missed_chunks_max_wait")]
+    pub missed_chunks_max_wait: Duration,
     /// How much time to wait after initial header sync
     #[serde(default = "default_header_sync_initial_timeout")]
     pub header_sync_initial_timeout: Duration,
@@ -376,6 +516,12 @@ pub struct Consensus {
     /// Expected increase of header head weight per second during header sync
     #[serde(default = "default_header_sync_expected_height_per_second")]
     pub header_sync_expected_height_per_second: u64,
+    /// Maximum number of header batches requested from different peers at the same time
+    #[serde(default = "default_header_sync_max_outstanding_batches")]
+    pub header_sync_max_outstanding_batches: usize,
+    /// How long to wait for a peer to return a header batch before asking another peer
+    #[serde(default = "default_header_sync_batch_timeout")]
+    pub header_sync_batch_timeout: Duration,
     /// How frequently we check whether we need to sync
     #[serde(default = "default_sync_check_period")]
     pub sync_check_period: Duration,
@@ -402,12 +548,17 @@ impl Default for Consensus {
             block_header_fetch_horizon: BLOCK_HEADER_FETCH_HORIZON,
             catchup_step_period: Duration::from_millis(CATCHUP_STEP_PERIOD),
             chunk_request_retry_period: Duration::from_millis(CHUNK_REQUEST_RETRY_PERIOD),
+            missed_chunks_threshold: default_missed_chunks_threshold(),
+            missed_chunks_wait_step: default_missed_chunks_wait_step(),
+            missed_chunks_max_wait: default_missed_chunks_max_wait(),
             header_sync_initial_timeout: default_header_sync_initial_timeout(),
             header_sync_progress_timeout: default_header_sync_progress_timeout(),
             header_sync_stall_ban_timeout: default_header_sync_stall_ban_timeout(),
             state_sync_timeout: default_state_sync_timeout(),
             header_sync_expected_height_per_second: default_header_sync_expected_height_per_second(
             ),
+            header_sync_max_outstanding_batches: default_header_sync_max_outstanding_batches(),
+            header_sync_batch_timeout: default_header_sync_batch_timeout(),
             sync_check_period: default_sync_check_period(),
             sync_step_period: default_sync_step_period(),
             doomslug_step_period: default_doomslug_step_period(),
@@ -415,6 +566,81 @@ impl Default for Consensus {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct StoreConfig {
+    /// If set, execution outcomes of blocks older than this many epochs are pruned, even if GC
+    /// hasn't reclaimed them. Ignored on archival nodes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prune_outcomes_older_than_epochs: Option<u64>,
+    /// Set by `neard cold-store migrate` once the history was copied into the cold database and
+    /// verified.  Allows hot GC to delete the migrated history.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cold_store: Option<ColdStoreConfig>,
+    /// Soft limit, in bytes, on the size of the write batches of large updates which can be
+    /// committed in several batches, such as garbage collection and cold store copies.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write_batch_soft_limit: Option<usize>,
+    /// Limits on the IO of the RocksDB background flushes and compactions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background_io: Option<BackgroundIoConfig>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ColdStoreConfig {
+    /// Path of the cold database, relative to the home directory unless absolute.
+    pub path: PathBuf,
+    /// The cold database has all the history of the blocks below this height.
+    pub migrated_height: BlockHeight,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct StateSyncConfig {
+    /// If set, the node dumps the state of its tracked shards at every epoch boundary to the
+    /// external storage, for other nodes to state sync from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dump: Option<StateDumpConfig>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StateDumpConfig {
+    /// Where to upload the state parts and headers.
+    pub location: ExternalStorageLocation,
+    /// Minimum time between dumping two parts, limits the load the dump puts on the node.
+    #[serde(default = "default_state_dump_part_interval")]
+    pub min_part_interval: Duration,
+    /// How often to check for a new epoch once the dump of the current one is complete.
+    #[serde(default = "default_state_dump_poll_interval")]
+    pub poll_interval: Duration,
+}
+
+/// External storage of state dumps.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExternalStorageLocation {
+    /// Directory on the local file system, e.g. a mounted network drive.
+    Filesystem { root_dir: PathBuf },
+    /// S3 bucket.  Credentials are taken from the `AWS_ACCESS_KEY_ID` and
+    /// `AWS_SECRET_ACCESS_KEY` environment variables.
+    S3 {
+        bucket: String,
+        region: String,
+        /// Endpoint of an S3 compatible storage, the bucket is then addressed by path.
+        /// Defaults to AWS.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        endpoint: Option<String>,
+    },
+}
+
+fn default_state_dump_part_interval() -> Duration {
+    Duration::from_millis(100)
+}
+
+fn default_state_dump_poll_interval() -> Duration {
+    Duration::from_secs(10)
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(default)]
 pub struct Config {
@@ -431,6 +657,9 @@ pub struct Config {
     pub telemetry: TelemetryConfig,
     pub network: Network,
     pub consensus: Consensus,
+    /// Accounts whose shards are tracked.  The shards are resolved with the shard layout of each
+    /// epoch, so they follow the accounts across shard layout changes.  Ignored if
+    /// `tracked_shards` is not empty.
     pub tracked_accounts: Vec<AccountId>,
     pub tracked_shards: Vec<ShardId>,
     pub archive: bool,
@@ -442,11 +671,18 @@ pub struct Config {
     pub epoch_sync_enabled: bool,
     #[serde(default = "default_view_client_throttle_period")]
     pub view_client_throttle_period: Duration,
+    /// Whether to respond to state sync requests of other nodes.  Announced to peers, so that
+    /// syncing nodes don't ask for state parts in vain.
+    #[serde(default = "default_serve_state_sync")]
+    pub serve_state_sync: bool,
     #[serde(default = "default_trie_viewer_state_size_limit")]
     pub trie_viewer_state_size_limit: Option<u64>,
     /// If set, overrides value in genesis configuration.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_gas_burnt_view: Option<Gas>,
+    /// Register limits for view calls, can only lower the limits of the protocol.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub view_register_limits: Option<ViewRegisterLimits>,
     /// Checkpoints let the user recover from interrupted DB migrations.
     #[serde(default = "default_use_checkpoints_for_db_migration")]
     pub use_db_migration_snapshot: bool,
@@ -456,8 +692,39 @@ pub struct Config {
     /// For example, setting "use_db_migration_snapshot" to "/tmp/" will create a directory "/tmp/db_migration_snapshot" and populate it with the database files.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub db_migration_snapshot_path: Option<PathBuf>,
+    /// Re-export RocksDB statistics, such as the time writes were stalled and the block cache
+    /// hit rate, as prometheus metrics.  Has a small performance cost.  The compaction and
+    /// memtable properties of the columns are exported regardless.
     #[serde(default = "default_enable_rocksdb_statistics")]
     pub enable_rocksdb_statistics: bool,
+    pub store: StoreConfig,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_sync: Option<StateSyncConfig>,
+    /// Loading a contract, i.e. compiling it or reading it from the compiled contract cache,
+    /// which takes longer than this is logged as a warning naming the contract.  One second if
+    /// not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slow_contract_compilation_threshold: Option<Duration>,
+    /// Export of the tracing spans to an OpenTelemetry collector, disabled if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opentelemetry: Option<OpenTelemetryConfig>,
+    /// How much higher, in percent, the priority fee of a transaction has to be to replace the
+    /// pooled transaction with the same signer, public key and nonce.
+    #[serde(default = "default_transaction_replacement_fee_bump_percent")]
+    pub transaction_replacement_fee_bump_percent: u64,
+    /// Maximum number of transactions in the transaction pool of a shard.  When it is reached,
+    /// the lowest-priced transactions of the accounts with the most transactions are evicted.
+    #[serde(default = "default_transaction_pool_max_transactions")]
+    pub transaction_pool_max_transactions: usize,
+    /// Maximum number of transactions of a single signer account in the pool of a shard.
+    #[serde(default = "default_transaction_pool_max_transactions_per_account")]
+    pub transaction_pool_max_transactions_per_account: usize,
+    /// Maximum total size of the transactions of a single signer account in the pool of a shard.
+    #[serde(default = "default_transaction_pool_max_bytes_per_account")]
+    pub transaction_pool_max_bytes_per_account: u64,
+    /// If set, the node views the stake delegated to staking pools by calling their contracts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delegated_stake_view: Option<DelegatedStakeViewConfig>,
 }
 
 impl Default for Config {
@@ -482,11 +749,25 @@ impl Default for Config {
             epoch_sync_enabled: true,
             view_client_threads: default_view_client_threads(),
             view_client_throttle_period: default_view_client_throttle_period(),
+            serve_state_sync: default_serve_state_sync(),
             trie_viewer_state_size_limit: default_trie_viewer_state_size_limit(),
             max_gas_burnt_view: None,
+            view_register_limits: None,
             db_migration_snapshot_path: None,
             use_db_migration_snapshot: true,
             enable_rocksdb_statistics: false,
+            store: StoreConfig::default(),
+            state_sync: None,
+            slow_contract_compilation_threshold: None,
+            opentelemetry: None,
+            transaction_replacement_fee_bump_percent:
+                default_transaction_replacement_fee_bump_percent(),
+            transaction_pool_max_transactions: default_transaction_pool_max_transactions(),
+            transaction_pool_max_transactions_per_account:
+                default_transaction_pool_max_transactions_per_account(),
+            transaction_pool_max_bytes_per_account: default_transaction_pool_max_bytes_per_account(
+            ),
+            delegated_stake_view: None,
         }
     }
 }
@@ -673,6 +954,10 @@ impl NearConfig {
                 header_sync_expected_height_per_second: config
                     .consensus
                     .header_sync_expected_height_per_second,
+                header_sync_max_outstanding_batches: config
+                    .consensus
+                    .header_sync_max_outstanding_batches,
+                header_sync_batch_timeout: config.consensus.header_sync_batch_timeout,
                 state_sync_timeout: config.consensus.state_sync_timeout,
                 min_num_peers: config.consensus.min_num_peers,
                 log_summary_period: Duration::from_secs(10),
@@ -687,6 +972,9 @@ impl NearConfig {
                 block_header_fetch_horizon: config.consensus.block_header_fetch_horizon,
                 catchup_step_period: config.consensus.catchup_step_period,
                 chunk_request_retry_period: config.consensus.chunk_request_retry_period,
+                missed_chunks_threshold: config.consensus.missed_chunks_threshold,
+                missed_chunks_wait_step: config.consensus.missed_chunks_wait_step,
+                missed_chunks_max_wait: config.consensus.missed_chunks_max_wait,
                 doosmslug_step_period: config.consensus.doomslug_step_period,
                 tracked_accounts: config.tracked_accounts,
                 tracked_shards: config.tracked_shards,
@@ -696,8 +984,20 @@ impl NearConfig {
                 view_client_threads: config.view_client_threads,
                 epoch_sync_enabled: config.epoch_sync_enabled,
                 view_client_throttle_period: config.view_client_throttle_period,
+                serve_state_sync: config.serve_state_sync,
                 trie_viewer_state_size_limit: config.trie_viewer_state_size_limit,
                 max_gas_burnt_view: config.max_gas_burnt_view,
+                view_register_limits: config.view_register_limits.unwrap_or_default(),
+                prune_outcomes_older_than_epochs: config.store.prune_outcomes_older_than_epochs,
+                save_receipt_queue_changes: false,
+                transaction_replacement_fee_bump_percent: config
+                    .transaction_replacement_fee_bump_percent,
+                transaction_pool_max_transactions: config.transaction_pool_max_transactions,
+                transaction_pool_max_transactions_per_account: config
+                    .transaction_pool_max_transactions_per_account,
+                transaction_pool_max_bytes_per_account: config
+                    .transaction_pool_max_bytes_per_account,
+                delegated_stake_view: config.delegated_stake_view,
             },
             network_config: NetworkConfig {
                 public_key: network_key_pair.public_key,
@@ -736,12 +1036,21 @@ impl NearConfig {
                 peer_stats_period: Duration::from_secs(5),
                 ttl_account_id_router: config.network.ttl_account_id_router,
                 routed_message_ttl: ROUTED_MESSAGE_TTL,
+                routed_message_ttl_overrides: config.network.routed_message_ttl_overrides,
                 max_routes_to_store: MAX_ROUTES_TO_STORE,
                 highest_peer_horizon: HIGHEST_PEER_HORIZON,
                 push_info_period: Duration::from_millis(100),
                 blacklist: config.network.blacklist,
                 outbound_disabled: false,
                 archive: config.archive,
+                tier1: near_network_primitives::types::Tier1 {
+                    enable_outbound: config.network.tier1.enable_outbound,
+                    enable_inbound: config.network.tier1.enable_inbound,
+                    proxies: (config.network.tier1.proxies.iter())
+                        .map(|proxy| proxy.as_str().try_into().expect("Failed to parse PeerInfo"))
+                        .collect(),
+                },
+                request_throttle: config.network.request_throttle.into(),
             },
             telemetry_config: config.telemetry,
             #[cfg(feature = "json_rpc")]
@@ -1127,6 +1436,7 @@ pub fn init_configs(
                 gas_price_adjustment_rate: GAS_PRICE_ADJUSTMENT_RATE,
                 block_producer_kickout_threshold: BLOCK_PRODUCER_KICKOUT_THRESHOLD,
                 chunk_producer_kickout_threshold: CHUNK_PRODUCER_KICKOUT_THRESHOLD,
+                validator_min_remaining_stake_perc: VALIDATOR_MIN_REMAINING_STAKE_PERC,
                 online_max_threshold: Rational::new(99, 100),
                 online_min_threshold: Rational::new(BLOCK_PRODUCER_KICKOUT_THRESHOLD as isize, 100),
                 validators: vec![AccountInfo {
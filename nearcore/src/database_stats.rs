@@ -0,0 +1,204 @@
+//! Statistics of the columns of the node database, for `neard database stats`.
+use std::fmt;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use near_chain::{ChainStore, ChainStoreAccess, RuntimeAdapter};
+use near_primitives::shard_layout::ShardUId;
+use near_primitives::trie_key::TRIE_KEY_COLUMNS;
+use near_primitives::types::StateRoot;
+use near_store::db::{DBCol, Database, RocksDB};
+use near_store::raw_node::{read_subtree_memory_usage, read_trie_node};
+use near_store::{create_store_with_config, Store, StoreConfig};
+
+use crate::{get_store_path, store_path_exists, NearConfig, NightshadeRuntime};
+
+/// Columns which take at least this share of the total size are highlighted.
+const DOMINANT_COLUMN_SHARE: f64 = 0.1;
+
+#[derive(Serialize, Debug)]
+pub struct ColumnStats {
+    pub column: String,
+    /// Estimated by RocksDB unless the column was iterated.
+    pub num_keys: u64,
+    /// Total size of the keys and of the values, only if the column was iterated.
+    pub key_bytes: Option<u64>,
+    pub value_bytes: Option<u64>,
+    /// Size of the SST files of the column.
+    pub sst_files_size: u64,
+    /// Estimated size of the live data in the SST files, the rest being overwritten or deleted
+    /// data which wasn't compacted yet.
+    pub live_data_size: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct StateStats {
+    /// Sum of the memory usage of the state roots of the head block, which approximates the size
+    /// of the live state.
+    pub live_state_size: u64,
+    /// Live data size of `ColState` divided by `live_state_size`, well above 1 if `ColState` has
+    /// a lot of garbage.
+    pub state_column_ratio: f64,
+    /// Memory usage of the state by `TrieKey` variant, summed over the shards, only with
+    /// `detailed_trie`.
+    pub trie_key_sizes: Vec<(String, u64)>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DatabaseStats {
+    /// Columns from the largest to the smallest.
+    pub columns: Vec<ColumnStats>,
+    pub state: StateStats,
+}
+
+impl ColumnStats {
+    fn new(db: &RocksDB, col: DBCol, iterate: bool) -> anyhow::Result<Self> {
+        let property = |name: &str| -> anyhow::Result<u64> {
+            Ok(db.property_int_value(col, name)?.unwrap_or(0))
+        };
+        let mut stats = ColumnStats {
+            column: format!("{:?}", col),
+            num_keys: property("rocksdb.estimate-num-keys")?,
+            key_bytes: None,
+            value_bytes: None,
+            sst_files_size: property("rocksdb.total-sst-files-size")?,
+            live_data_size: property("rocksdb.estimate-live-data-size")?,
+        };
+        if iterate {
+            let (mut num_keys, mut key_bytes, mut value_bytes) = (0, 0, 0);
+            for (key, value) in db.iter_without_rc_logic(col) {
+                num_keys += 1;
+                key_bytes += key.len() as u64;
+                value_bytes += value.len() as u64;
+            }
+            stats.num_keys = num_keys;
+            stats.key_bytes = Some(key_bytes);
+            stats.value_bytes = Some(value_bytes);
+        }
+        Ok(stats)
+    }
+}
+
+/// State roots of the shards at the head of the chain.
+fn head_state_roots(
+    home_dir: &Path,
+    near_config: &NearConfig,
+    store: &Store,
+) -> anyhow::Result<Vec<(ShardUId, StateRoot)>> {
+    let mut chain_store = ChainStore::new(store.clone(), near_config.genesis.config.genesis_height);
+    let head = chain_store.head()?;
+    let block = chain_store.get_block(&head.last_block_hash)?.clone();
+    let runtime = NightshadeRuntime::with_config(
+        home_dir,
+        store.clone(),
+        near_config,
+        None,
+        near_config.client_config.max_gas_burnt_view,
+    );
+    let shard_layout = runtime.get_shard_layout_from_prev_block(block.header().prev_hash())?;
+    Ok(block
+        .chunks()
+        .iter()
+        .enumerate()
+        .map(|(shard_id, chunk)| {
+            (
+                ShardUId::from_shard_id_and_layout(shard_id as u64, &shard_layout),
+                chunk.prev_state_root(),
+            )
+        })
+        .collect())
+}
+
+/// Collects the statistics of the columns of the database in `home_dir`.  By default they are
+/// the estimates of RocksDB, with `iterate` the columns are iterated to count their keys and
+/// bytes exactly, which takes long.  With `detailed_trie` the live state is broken down by
+/// `TrieKey` variant.
+pub fn database_stats(
+    home_dir: &Path,
+    near_config: &NearConfig,
+    iterate: bool,
+    detailed_trie: bool,
+) -> anyhow::Result<DatabaseStats> {
+    let store_path = get_store_path(home_dir);
+    anyhow::ensure!(
+        store_path_exists(&store_path),
+        "{}: storage doesn’t exist",
+        store_path.display()
+    );
+    let store = create_store_with_config(
+        &store_path,
+        StoreConfig { read_only: true, ..Default::default() },
+    );
+    let db = store.get_rocksdb().context("the store isn't backed by RocksDB")?;
+    let mut columns = DBCol::iter()
+        .map(|col| ColumnStats::new(db, col, iterate))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    columns.sort_by(|lhs, rhs| rhs.sst_files_size.cmp(&lhs.sst_files_size));
+
+    let state_roots = head_state_roots(home_dir, near_config, &store)?;
+    let mut live_state_size = 0;
+    for (shard_uid, state_root) in &state_roots {
+        if let Some(node) = read_trie_node(&store, *shard_uid, state_root)? {
+            live_state_size += node.node.memory_usage;
+        }
+    }
+    let mut trie_key_sizes = vec![];
+    if detailed_trie {
+        for (column, name) in TRIE_KEY_COLUMNS {
+            let mut size = 0;
+            for (shard_uid, state_root) in &state_roots {
+                size += read_subtree_memory_usage(&store, *shard_uid, state_root, column)?;
+            }
+            trie_key_sizes.push((name.to_string(), size));
+        }
+        trie_key_sizes.sort_by(|lhs, rhs| rhs.1.cmp(&lhs.1));
+    }
+    let state_column_size =
+        db.property_int_value(DBCol::ColState, "rocksdb.estimate-live-data-size")?.unwrap_or(0);
+    let state_column_ratio = state_column_size as f64 / live_state_size.max(1) as f64;
+    Ok(DatabaseStats {
+        columns,
+        state: StateStats { live_state_size, state_column_ratio, trie_key_sizes },
+    })
+}
+
+impl fmt::Display for DatabaseStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total_size: u64 = self.columns.iter().map(|column| column.sst_files_size).sum();
+        writeln!(
+            f,
+            "  {:<32} {:>14} {:>16} {:>16} {:>16} {:>16}",
+            "column", "keys", "key bytes", "value bytes", "SST files size", "live data size"
+        )?;
+        let optional =
+            |bytes: Option<u64>| bytes.map_or("-".to_string(), |bytes| bytes.to_string());
+        for column in &self.columns {
+            let dominant =
+                column.sst_files_size as f64 >= DOMINANT_COLUMN_SHARE * total_size as f64;
+            writeln!(
+                f,
+                "{} {:<32} {:>14} {:>16} {:>16} {:>16} {:>16}",
+                if dominant && total_size > 0 { '*' } else { ' ' },
+                column.column,
+                column.num_keys,
+                optional(column.key_bytes),
+                optional(column.value_bytes),
+                column.sst_files_size,
+                column.live_data_size
+            )?;
+        }
+        writeln!(f, "Total SST files size: {} bytes", total_size)?;
+        writeln!(
+            f,
+            "Live state size: {} bytes, State column is {:.2} times larger",
+            self.state.live_state_size, self.state.state_column_ratio
+        )?;
+        for (name, size) in &self.state.trie_key_sizes {
+            writeln!(f, "  {:<24} {:>16}", name, size)?;
+        }
+        Ok(())
+    }
+}
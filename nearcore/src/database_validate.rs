@@ -0,0 +1,762 @@
+//! Structural checks of the node database, for `neard database validate`.
+use std::fmt;
+use std::path::Path;
+
+use borsh::BorshDeserialize;
+use rand::Rng;
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use near_chain::{ChainStore, ChainStoreAccess, RuntimeAdapter};
+use near_primitives::block::{Block, BlockHeader, Tip};
+use near_primitives::hash::CryptoHash;
+use near_primitives::serialize::to_base;
+use near_primitives::shard_layout::ShardUId;
+use near_primitives::sharding::ShardChunkHeader;
+use near_primitives::types::{BlockHeight, ShardId, StateRoot};
+use near_store::db::DBCol;
+use near_store::raw_node::{read_trie_node, RawTrieNode};
+use near_store::{
+    create_store_with_config, decode_value_with_rc, Store, StoreConfig, Trie, TrieCachingStorage,
+};
+
+use crate::{get_store_path, store_path_exists, NearConfig, NightshadeRuntime};
+
+/// Number of blocks checked back from the head in the fast mode.
+const FAST_NUM_BLOCKS: u64 = 1000;
+/// Number of random paths from the root to a leaf checked per shard in the fast mode.
+const FAST_NUM_TRIE_WALKS: u64 = 1000;
+/// Number of state roots of the chunks of the checked blocks sampled to check past states.
+const NUM_SAMPLED_STATE_ROOTS: usize = 20;
+/// Number of random paths from the root to a leaf checked per sampled state root, in both modes.
+const SAMPLED_STATE_ROOT_NUM_TRIE_WALKS: u64 = 100;
+/// Problems of a single check beyond this number are only counted, the first ones are enough to
+/// tell what is broken.
+const MAX_REPORTED_PROBLEMS_PER_CHECK: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidationMode {
+    /// Checks the head and tail pointers, the most recent blocks and random paths of the state.
+    Fast,
+    /// Checks every header, block and state node and the refcounts of all the refcounted
+    /// columns.  Takes hours on large databases.
+    Full,
+}
+
+/// What it takes to recover from a problem.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Impact {
+    /// Data older than the final head is missing or inconsistent.  The node can keep running,
+    /// but can't serve that history until a snapshot with it is restored.
+    History,
+    /// The head of the chain or its state is broken, the node has to be resynced or restored
+    /// from a snapshot.
+    Head,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Problem {
+    pub check: &'static str,
+    pub impact: Impact,
+    pub message: String,
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct ValidationReport {
+    pub headers_checked: u64,
+    pub blocks_checked: u64,
+    pub chunks_checked: u64,
+    pub state_roots_checked: u64,
+    pub trie_nodes_checked: u64,
+    pub refcounts_checked: u64,
+    /// Total number of problems, only the first few of every check are in `problems`.
+    pub num_problems: u64,
+    pub problems: Vec<Problem>,
+}
+
+impl ValidationReport {
+    fn add_problem(&mut self, check: &'static str, impact: Impact, message: String) {
+        self.num_problems += 1;
+        let reported = self.problems.iter().filter(|problem| problem.check == check).count();
+        if reported < MAX_REPORTED_PROBLEMS_PER_CHECK {
+            self.problems.push(Problem { check, impact, message });
+        }
+    }
+
+    /// The worst impact of the problems found, `None` if there are none.
+    pub fn impact(&self) -> Option<Impact> {
+        if self.problems.iter().any(|problem| problem.impact == Impact::Head) {
+            Some(Impact::Head)
+        } else {
+            self.problems.first().map(|problem| problem.impact)
+        }
+    }
+}
+
+/// State root a chunk was applied on, sampled from the checked blocks.
+struct SampledStateRoot {
+    height: BlockHeight,
+    prev_block_hash: CryptoHash,
+    shard_id: ShardId,
+    state_root: StateRoot,
+}
+
+struct Validator {
+    store: Store,
+    chain_store: ChainStore,
+    genesis_height: BlockHeight,
+    mode: ValidationMode,
+    report: ValidationReport,
+    chunk_tail: BlockHeight,
+    tail: BlockHeight,
+    /// Blocks at or above this height are needed to continue from the head.
+    final_height: BlockHeight,
+    /// Number of state roots of existing chunks seen by `check_block`.
+    num_chunk_state_roots: u64,
+    sampled_state_roots: Vec<SampledStateRoot>,
+}
+
+impl Validator {
+    fn new(store: Store, genesis_height: BlockHeight, mode: ValidationMode) -> Self {
+        let chain_store = ChainStore::new(store.clone(), genesis_height);
+        Self {
+            store,
+            chain_store,
+            genesis_height,
+            mode,
+            report: Default::default(),
+            chunk_tail: genesis_height,
+            tail: genesis_height,
+            final_height: genesis_height,
+            num_chunk_state_roots: 0,
+            sampled_state_roots: vec![],
+        }
+    }
+
+    fn impact_at(&self, height: BlockHeight) -> Impact {
+        if height >= self.final_height {
+            Impact::Head
+        } else {
+            Impact::History
+        }
+    }
+
+    /// Checks that the head and tail pointers exist and are ordered.  Returns the head if the
+    /// rest of the chain can be checked.
+    fn check_pointers(&mut self) -> Option<Tip> {
+        const CHECK: &str = "pointers";
+        let head = match self.chain_store.head() {
+            Ok(head) => head,
+            Err(err) => {
+                self.report.add_problem(CHECK, Impact::Head, format!("head: {}", err));
+                return None;
+            }
+        };
+        match (self.chain_store.tail(), self.chain_store.chunk_tail()) {
+            (Ok(tail), Ok(chunk_tail)) => {
+                self.tail = tail;
+                self.chunk_tail = chunk_tail;
+            }
+            (Err(err), _) | (_, Err(err)) => {
+                self.report.add_problem(CHECK, Impact::History, format!("tail: {}", err))
+            }
+        }
+        self.final_height = match self.chain_store.final_head() {
+            Ok(final_head) => {
+                if self.chain_store.get_block_header(&final_head.last_block_hash).is_err() {
+                    let message = format!(
+                        "final head #{} {} has no header",
+                        final_head.height, final_head.last_block_hash
+                    );
+                    self.report.add_problem(CHECK, Impact::Head, message);
+                } else if final_head.height <= head.height
+                    && self.chain_store.get_block_hash_by_height(final_head.height).ok()
+                        != Some(final_head.last_block_hash)
+                {
+                    let message = format!(
+                        "final head #{} {} isn't on the canonical chain",
+                        final_head.height, final_head.last_block_hash
+                    );
+                    self.report.add_problem(CHECK, Impact::Head, message);
+                }
+                final_head.height
+            }
+            Err(err) => {
+                self.report.add_problem(CHECK, Impact::Head, format!("final head: {}", err));
+                head.height
+            }
+        };
+        let header_head_height = match self.chain_store.header_head() {
+            Ok(header_head) => header_head.height,
+            Err(err) => {
+                self.report.add_problem(CHECK, Impact::Head, format!("header head: {}", err));
+                head.height
+            }
+        };
+        let pointers = [
+            ("chunk tail", self.chunk_tail),
+            ("tail", self.tail),
+            ("final head", self.final_height),
+            ("head", head.height),
+            ("header head", header_head_height),
+        ];
+        for pair in pointers.windows(2) {
+            let ((lower, lower_height), (upper, upper_height)) = (pair[0], pair[1]);
+            if lower_height > upper_height {
+                let message =
+                    format!("{} #{} is above {} #{}", lower, lower_height, upper, upper_height);
+                // Tails above the final head mean that GC deleted blocks which are still needed.
+                self.report.add_problem(CHECK, Impact::Head, message);
+            }
+        }
+        if self.chain_store.get_block(&head.last_block_hash).is_err() {
+            let message =
+                format!("head block #{} {} is missing", head.height, head.last_block_hash);
+            self.report.add_problem(CHECK, Impact::Head, message);
+            return None;
+        }
+        Some(head)
+    }
+
+    /// Checks that the previous header of `header` exists.
+    fn check_header(&mut self, header: &BlockHeader) -> bool {
+        self.report.headers_checked += 1;
+        if header.height() == self.genesis_height
+            || self
+                .store
+                .exists(DBCol::ColBlockHeader, header.prev_hash().as_ref())
+                .unwrap_or(false)
+        {
+            return true;
+        }
+        let message = format!(
+            "header #{} {} has no previous header {}",
+            header.height(),
+            header.hash(),
+            header.prev_hash()
+        );
+        let impact = self.impact_at(header.height());
+        self.report.add_problem("headers", impact, message);
+        false
+    }
+
+    /// Checks that the chunks included in `block` which weren't garbage collected yet exist, and
+    /// samples the state roots they were applied on.
+    fn check_block(&mut self, block: &Block) {
+        self.report.blocks_checked += 1;
+        for chunk_header in block.chunks().iter() {
+            if chunk_header.height_included() != block.header().height()
+                || chunk_header.height_created() < self.chunk_tail
+            {
+                continue;
+            }
+            self.report.chunks_checked += 1;
+            let chunk_hash = chunk_header.chunk_hash();
+            let exists = |col| self.store.exists(col, chunk_hash.as_ref()).unwrap_or(false);
+            if exists(DBCol::ColChunks) || exists(DBCol::ColPartialChunks) {
+                self.sample_state_root(block.header(), chunk_header);
+                continue;
+            }
+            let message = format!(
+                "chunk {} of shard {} included in block #{} {} is missing",
+                chunk_hash.0,
+                chunk_header.shard_id(),
+                block.header().height(),
+                block.hash()
+            );
+            let impact = self.impact_at(block.header().height());
+            self.report.add_problem("chunks", impact, message);
+        }
+    }
+
+    /// Adds the state root `chunk_header` was applied on to the sample, so that every root seen
+    /// so far is in it with the same probability.
+    fn sample_state_root(&mut self, header: &BlockHeader, chunk_header: &ShardChunkHeader) {
+        self.num_chunk_state_roots += 1;
+        let sample = SampledStateRoot {
+            height: header.height(),
+            prev_block_hash: *header.prev_hash(),
+            shard_id: chunk_header.shard_id(),
+            state_root: chunk_header.prev_state_root(),
+        };
+        if self.sampled_state_roots.len() < NUM_SAMPLED_STATE_ROOTS {
+            self.sampled_state_roots.push(sample);
+        } else {
+            let index = rand::thread_rng().gen_range(0, self.num_chunk_state_roots) as usize;
+            if let Some(sampled) = self.sampled_state_roots.get_mut(index) {
+                *sampled = sample;
+            }
+        }
+    }
+
+    /// Walks back from the head along the previous hashes checking the blocks.  In the fast mode
+    /// only the most recent blocks and their headers are checked, in the full mode the blocks
+    /// down to the tail and every header in the database, including the headers of forks.
+    fn check_chain(&mut self, head: &Tip) {
+        let num_blocks = match self.mode {
+            ValidationMode::Fast => FAST_NUM_BLOCKS,
+            ValidationMode::Full => u64::MAX,
+        };
+        let mut hash = head.last_block_hash;
+        for _ in 0..num_blocks {
+            let header = match self.chain_store.get_block_header(&hash) {
+                Ok(header) => header.clone(),
+                // Reported by `check_header` of the next block.
+                Err(_) => break,
+            };
+            if header.height() > self.tail {
+                match self.chain_store.get_block(&hash) {
+                    Ok(block) => {
+                        let block = block.clone();
+                        self.check_block(&block);
+                    }
+                    Err(_) => {
+                        let message =
+                            format!("block #{} {} is missing", header.height(), header.hash());
+                        let impact = self.impact_at(header.height());
+                        self.report.add_problem("blocks", impact, message);
+                    }
+                }
+            }
+            if header.height() == self.genesis_height {
+                break;
+            }
+            // In the full mode the headers are checked below.
+            if self.mode == ValidationMode::Fast && !self.check_header(&header) {
+                break;
+            }
+            hash = *header.prev_hash();
+        }
+        if self.mode == ValidationMode::Full {
+            let store = self.store.clone();
+            for (_, value) in store.iter(DBCol::ColBlockHeader) {
+                match BlockHeader::try_from_slice(&value) {
+                    Ok(header) => {
+                        self.check_header(&header);
+                    }
+                    Err(err) => self.report.add_problem(
+                        "headers",
+                        Impact::History,
+                        format!("can't decode header: {}", err),
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Checks that the value `hash` of the trie of `shard_uid` exists.
+    fn check_trie_value(&mut self, shard_uid: ShardUId, hash: &CryptoHash) -> bool {
+        let key = TrieCachingStorage::get_key_from_shard_uid_and_hash(shard_uid, hash);
+        match self.store.get(DBCol::ColState, &key) {
+            Ok(Some(bytes)) => decode_value_with_rc(&bytes).0.is_some(),
+            _ => false,
+        }
+    }
+
+    /// Checks that the nodes of the trie with root `state_root` and their values exist, reporting
+    /// problems with `impact`.  With `num_walks` that many random paths from the root to a leaf
+    /// are checked, otherwise every node.
+    fn check_trie(
+        &mut self,
+        shard_uid: ShardUId,
+        state_root: &StateRoot,
+        impact: Impact,
+        num_walks: Option<u64>,
+    ) {
+        const CHECK: &str = "state";
+        if state_root == &Trie::empty_root() {
+            return;
+        }
+        let mut rng = rand::thread_rng();
+        let mut stack = vec![];
+        for _ in 0..num_walks.unwrap_or(1) {
+            stack.push(*state_root);
+            while let Some(hash) = stack.pop() {
+                self.report.trie_nodes_checked += 1;
+                let node = match read_trie_node(&self.store, shard_uid, &hash) {
+                    Ok(Some(stored)) => stored.node.node,
+                    Ok(None) => {
+                        let message = format!(
+                            "node {} of shard {} state root {} is missing",
+                            hash, shard_uid.shard_id, state_root
+                        );
+                        self.report.add_problem(CHECK, impact, message);
+                        continue;
+                    }
+                    Err(err) => {
+                        let message = format!(
+                            "node {} of shard {} state root {} can't be decoded: {}",
+                            hash, shard_uid.shard_id, state_root, err
+                        );
+                        self.report.add_problem(CHECK, impact, message);
+                        continue;
+                    }
+                };
+                let (children, value) = match node {
+                    RawTrieNode::Leaf(_, _, value_hash) => (vec![], Some(value_hash)),
+                    RawTrieNode::Extension(_, child) => (vec![child], None),
+                    RawTrieNode::Branch(children, value) => (
+                        children.iter().flatten().copied().collect(),
+                        value.map(|(_, value_hash)| value_hash),
+                    ),
+                };
+                // A random walk only checks the value of the leaf it ends in.
+                let check_value = num_walks.is_none() || children.is_empty();
+                if let Some(value_hash) = value.filter(|_| check_value) {
+                    if !self.check_trie_value(shard_uid, &value_hash) {
+                        let message = format!(
+                            "value {} of node {} of shard {} state root {} is missing",
+                            value_hash, hash, shard_uid.shard_id, state_root
+                        );
+                        self.report.add_problem(CHECK, impact, message);
+                    }
+                }
+                if num_walks.is_none() {
+                    stack.extend(children);
+                } else if !children.is_empty() {
+                    stack.push(children[rng.gen_range(0, children.len())]);
+                }
+            }
+        }
+    }
+
+    /// Checks random paths of the sampled state roots of the shards the node tracked.  Problems
+    /// of states older than the final head only affect the history.
+    fn check_sampled_state_roots(&mut self, runtime: &dyn RuntimeAdapter) {
+        for sample in std::mem::take(&mut self.sampled_state_roots) {
+            let prev_block_hash = &sample.prev_block_hash;
+            if !runtime.cares_about_shard(None, prev_block_hash, sample.shard_id, true) {
+                continue;
+            }
+            let impact = self.impact_at(sample.height);
+            let shard_layout = match runtime.get_shard_layout_from_prev_block(prev_block_hash) {
+                Ok(shard_layout) => shard_layout,
+                Err(err) => {
+                    let message = format!("shard layout of the block #{}: {}", sample.height, err);
+                    self.report.add_problem("state", impact, message);
+                    continue;
+                }
+            };
+            let shard_uid = ShardUId::from_shard_id_and_layout(sample.shard_id, &shard_layout);
+            self.report.state_roots_checked += 1;
+            self.check_trie(
+                shard_uid,
+                &sample.state_root,
+                impact,
+                Some(SAMPLED_STATE_ROOT_NUM_TRIE_WALKS),
+            );
+        }
+    }
+
+    /// Checks that no key of a refcounted column has a negative refcount, which means that it
+    /// was decremented more often than incremented and a value still in use may be deleted.
+    fn check_refcounts(&mut self) {
+        let store = self.store.clone();
+        for col in DBCol::iter().filter(|col| col.is_rc()) {
+            for (key, value) in store.iter_without_rc_logic(col) {
+                self.report.refcounts_checked += 1;
+                let (_, refcount) = decode_value_with_rc(&value);
+                if refcount < 0 {
+                    let impact =
+                        if col == DBCol::ColState { Impact::Head } else { Impact::History };
+                    let message =
+                        format!("{:?} key {} has refcount {}", col, to_base(&key), refcount);
+                    self.report.add_problem("refcounts", impact, message);
+                }
+            }
+        }
+    }
+}
+
+/// Post-state roots of the shards tracked by the node at the head of the chain.  A tracked shard
+/// without a root is reported as a problem.
+fn head_state_roots(
+    validator: &mut Validator,
+    runtime: &dyn RuntimeAdapter,
+    head: &Tip,
+) -> anyhow::Result<Vec<(ShardUId, StateRoot)>> {
+    let shard_layout = runtime.get_shard_layout_from_prev_block(&head.prev_block_hash)?;
+    let mut state_roots = vec![];
+    for shard_id in 0..shard_layout.num_shards() {
+        if !runtime.cares_about_shard(None, &head.prev_block_hash, shard_id, true) {
+            continue;
+        }
+        let shard_uid = ShardUId::from_shard_id_and_layout(shard_id, &shard_layout);
+        match validator.chain_store.get_chunk_extra(&head.last_block_hash, &shard_uid) {
+            Ok(chunk_extra) => state_roots.push((shard_uid, *chunk_extra.state_root())),
+            Err(err) => {
+                let message = format!("chunk extra of tracked shard {} at head: {}", shard_id, err);
+                validator.report.add_problem("state", Impact::Head, message);
+            }
+        }
+    }
+    Ok(state_roots)
+}
+
+/// Checks the structure of the database in `home_dir`: the head and tail pointers, that the
+/// previous header of every header and the chunks of every block exist, that the state of the
+/// head and a sample of the states the chunks of the checked blocks were applied on can be
+/// traversed and, in the full mode, that no refcount is negative.
+pub fn validate_database(
+    home_dir: &Path,
+    near_config: &NearConfig,
+    mode: ValidationMode,
+) -> anyhow::Result<ValidationReport> {
+    let store_path = get_store_path(home_dir);
+    anyhow::ensure!(
+        store_path_exists(&store_path),
+        "{}: storage doesn’t exist",
+        store_path.display()
+    );
+    let store = create_store_with_config(
+        &store_path,
+        StoreConfig { read_only: true, ..Default::default() },
+    );
+    let mut validator =
+        Validator::new(store.clone(), near_config.genesis.config.genesis_height, mode);
+    if let Some(head) = validator.check_pointers() {
+        validator.check_chain(&head);
+        let runtime = NightshadeRuntime::with_config(
+            home_dir,
+            store,
+            near_config,
+            None,
+            near_config.client_config.max_gas_burnt_view,
+        );
+        let num_walks = match mode {
+            ValidationMode::Fast => Some(FAST_NUM_TRIE_WALKS),
+            ValidationMode::Full => None,
+        };
+        for (shard_uid, state_root) in head_state_roots(&mut validator, &runtime, &head)? {
+            validator.report.state_roots_checked += 1;
+            validator.check_trie(shard_uid, &state_root, Impact::Head, num_walks);
+        }
+        validator.check_sampled_state_roots(&runtime);
+    }
+    if mode == ValidationMode::Full {
+        validator.check_refcounts();
+    }
+    Ok(validator.report)
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Checked {} headers, {} blocks, {} chunks, {} state roots, {} trie nodes and {} \
+             refcounts",
+            self.headers_checked,
+            self.blocks_checked,
+            self.chunks_checked,
+            self.state_roots_checked,
+            self.trie_nodes_checked,
+            self.refcounts_checked
+        )?;
+        for problem in &self.problems {
+            writeln!(
+                f,
+                "  {:<10} {:<8} {}",
+                problem.check,
+                format!("{:?}", problem.impact),
+                problem.message
+            )?;
+        }
+        if self.num_problems > self.problems.len() as u64 {
+            writeln!(f, "  ... and {} more", self.num_problems - self.problems.len() as u64)?;
+        }
+        match self.impact() {
+            None => writeln!(f, "No problems found"),
+            Some(Impact::History) => writeln!(
+                f,
+                "Only the history is affected: the node can keep running, restore a snapshot to \
+                 recover the missing history"
+            ),
+            Some(Impact::Head) => writeln!(
+                f,
+                "The head of the chain is affected: resync the node or restore a snapshot"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use near_chain::ChainGenesis;
+    use near_chain_configs::Genesis;
+    use near_client::test_utils::TestEnv;
+    use near_primitives::runtime::config_store::RuntimeConfigStore;
+    use near_store::test_utils::{create_test_store, create_tries, test_populate_trie};
+    use near_store::TAIL_KEY;
+
+    use super::*;
+    use crate::config::GenesisExt;
+    use crate::shard_tracker::TrackedConfig;
+
+    fn populated_validator(mode: ValidationMode) -> (Validator, ShardUId, StateRoot) {
+        let tries = create_tries();
+        let shard_uid = ShardUId::single_shard();
+        let changes = (0..100u32)
+            .map(|i| (format!("key{}", i).into_bytes(), Some(format!("value{}", i).into_bytes())))
+            .collect();
+        let root = test_populate_trie(&tries, &Trie::empty_root(), shard_uid, changes);
+        (Validator::new(tries.get_store(), 0, mode), shard_uid, root)
+    }
+
+    /// Validator of the store of a node which produced `num_blocks` blocks tracking every shard,
+    /// with the runtime of the node.
+    fn chain_validator(
+        num_blocks: BlockHeight,
+        mode: ValidationMode,
+    ) -> (Validator, Arc<dyn RuntimeAdapter>) {
+        let genesis = Genesis::test(vec!["test0".parse().unwrap()], 1);
+        let store = create_test_store();
+        let runtime: Arc<dyn RuntimeAdapter> =
+            Arc::new(NightshadeRuntime::test_with_runtime_config_store(
+                Path::new("."),
+                store.clone(),
+                &genesis,
+                TrackedConfig::AllShards,
+                RuntimeConfigStore::test(),
+            ));
+        let mut env = TestEnv::builder(ChainGenesis::from(&genesis))
+            .runtime_adapters(vec![runtime.clone()])
+            .build();
+        for height in 1..=num_blocks {
+            env.produce_block(0, height);
+        }
+        (Validator::new(store, genesis.config.genesis_height, mode), runtime)
+    }
+
+    /// Fresh validator of the store of `validator`, so that nothing is read from the caches.
+    fn revalidate(validator: Validator) -> Validator {
+        Validator::new(validator.store, validator.genesis_height, validator.mode)
+    }
+
+    #[test]
+    fn test_check_trie() {
+        for num_walks in [Some(FAST_NUM_TRIE_WALKS), None] {
+            let (mut validator, shard_uid, root) = populated_validator(ValidationMode::Fast);
+            validator.check_trie(shard_uid, &root, Impact::Head, num_walks);
+            assert!(validator.report.trie_nodes_checked > 0);
+            assert_eq!(validator.report.num_problems, 0);
+        }
+
+        // Deleting any node but the root is detected when every node is checked.
+        let (mut validator, shard_uid, root) = populated_validator(ValidationMode::Full);
+        let root_key = TrieCachingStorage::get_key_from_shard_uid_and_hash(shard_uid, &root);
+        let key = validator
+            .store
+            .iter(DBCol::ColState)
+            .map(|(key, _)| key)
+            .find(|key| key[..] != root_key[..])
+            .unwrap();
+        let mut store_update = validator.store.store_update();
+        store_update.delete(DBCol::ColState, &key);
+        store_update.commit().unwrap();
+        validator.check_trie(shard_uid, &root, Impact::Head, None);
+        assert_eq!(validator.report.num_problems, 1);
+        assert_eq!(validator.report.impact(), Some(Impact::Head));
+    }
+
+    #[test]
+    fn test_check_pointers() {
+        let (mut validator, _) = chain_validator(10, ValidationMode::Fast);
+        let head = validator.check_pointers().unwrap();
+        assert_eq!(head.height, 10);
+        assert_eq!(validator.report.num_problems, 0);
+
+        // A tail above the final head means that blocks still needed were garbage collected.
+        let mut store_update = validator.store.store_update();
+        store_update.set_ser(DBCol::ColBlockMisc, TAIL_KEY, &head.height).unwrap();
+        store_update.commit().unwrap();
+        let mut validator = revalidate(validator);
+        assert!(validator.check_pointers().is_some());
+        assert_eq!(validator.report.problems[0].check, "pointers");
+        assert_eq!(validator.report.impact(), Some(Impact::Head));
+
+        // Nothing else can be checked without the head block.
+        let mut store_update = validator.store.store_update();
+        store_update.delete(DBCol::ColBlock, head.last_block_hash.as_ref());
+        store_update.commit().unwrap();
+        let mut validator = revalidate(validator);
+        assert!(validator.check_pointers().is_none());
+        assert_eq!(validator.report.impact(), Some(Impact::Head));
+    }
+
+    #[test]
+    fn test_check_chain() {
+        for mode in [ValidationMode::Fast, ValidationMode::Full] {
+            let (mut validator, _) = chain_validator(10, mode);
+            let head = validator.check_pointers().unwrap();
+            validator.check_chain(&head);
+            assert_eq!(validator.report.blocks_checked, 10);
+            assert!(validator.report.chunks_checked > 0);
+            assert_eq!(validator.report.num_problems, 0);
+        }
+
+        // Walking back from the head finds the missing chunk of block #6, block #4 and the
+        // header of block #2, which is the previous header of block #3.
+        let (mut validator, _) = chain_validator(10, ValidationMode::Fast);
+        let missing_header = validator.chain_store.get_block_hash_by_height(2).unwrap();
+        let missing_block = validator.chain_store.get_block_hash_by_height(4).unwrap();
+        let block_hash = validator.chain_store.get_block_hash_by_height(6).unwrap();
+        let missing_chunk =
+            validator.chain_store.get_block(&block_hash).unwrap().chunks()[0].chunk_hash();
+        let mut store_update = validator.store.store_update();
+        store_update.delete(DBCol::ColBlockHeader, missing_header.as_ref());
+        store_update.delete(DBCol::ColBlock, missing_block.as_ref());
+        store_update.delete(DBCol::ColChunks, missing_chunk.as_ref());
+        store_update.delete(DBCol::ColPartialChunks, missing_chunk.as_ref());
+        store_update.commit().unwrap();
+        let mut validator = revalidate(validator);
+        let head = validator.check_pointers().unwrap();
+        validator.check_chain(&head);
+        let checks: Vec<_> =
+            validator.report.problems.iter().map(|problem| problem.check).collect();
+        assert_eq!(checks, ["chunks", "blocks", "headers"]);
+        // All of them are below the final head.
+        assert_eq!(validator.report.impact(), Some(Impact::History));
+    }
+
+    #[test]
+    fn test_check_sampled_state_roots() {
+        let (mut validator, runtime) = chain_validator(10, ValidationMode::Fast);
+        let head = validator.check_pointers().unwrap();
+        validator.check_chain(&head);
+        let state_root = validator.sampled_state_roots[0].state_root;
+        validator.check_sampled_state_roots(runtime.as_ref());
+        assert!(validator.report.state_roots_checked > 0);
+        assert!(validator.report.trie_nodes_checked > 0);
+        assert_eq!(validator.report.num_problems, 0);
+
+        // The root node of a sampled state is missing.
+        let shard_uid = ShardUId::single_shard();
+        let key = TrieCachingStorage::get_key_from_shard_uid_and_hash(shard_uid, &state_root);
+        let mut store_update = validator.store.store_update();
+        store_update.delete(DBCol::ColState, &key);
+        store_update.commit().unwrap();
+        let mut validator = revalidate(validator);
+        let head = validator.check_pointers().unwrap();
+        validator.check_chain(&head);
+        validator.check_sampled_state_roots(runtime.as_ref());
+        assert!(validator.report.num_problems > 0);
+        assert!(validator.report.problems.iter().all(|problem| problem.check == "state"));
+    }
+
+    #[test]
+    fn test_check_refcounts() {
+        let (mut validator, _, _) = populated_validator(ValidationMode::Full);
+        validator.check_refcounts();
+        assert!(validator.report.refcounts_checked > 0);
+        assert_eq!(validator.report.num_problems, 0);
+
+        let mut store_update = validator.store.store_update();
+        store_update.update_refcount(DBCol::ColState, &[1; 40], &[], -1);
+        store_update.commit().unwrap();
+        validator.check_refcounts();
+        assert_eq!(validator.report.num_problems, 1);
+        assert_eq!(validator.report.problems[0].check, "refcounts");
+    }
+}
@@ -1,10 +1,12 @@
 pub use crate::config::{init_configs, load_config, load_test_config, NearConfig, NEAR_BASE};
 use crate::migrations::{
     migrate_12_to_13, migrate_18_to_19, migrate_19_to_20, migrate_22_to_23, migrate_23_to_24,
-    migrate_24_to_25, migrate_30_to_31,
+    migrate_24_to_25, migrate_30_to_31, migrate_31_to_32, migrate_32_to_33, migrate_33_to_34,
+    migrate_36_to_37,
 };
 pub use crate::runtime::NightshadeRuntime;
 pub use crate::shard_tracker::TrackedConfig;
+use crate::state_dump::{start_state_dumper, StateDumper};
 use actix::{Actor, Addr, Arbiter};
 use actix_rt::ArbiterHandle;
 use actix_web;
@@ -29,7 +31,9 @@ use near_store::migrations::{
     migrate_21_to_22, migrate_25_to_26, migrate_26_to_27, migrate_28_to_29, migrate_29_to_30,
     migrate_6_to_7, migrate_7_to_8, migrate_8_to_9, migrate_9_to_10, set_store_version,
 };
-use near_store::{create_store, create_store_with_config, Store, StoreConfig};
+use near_store::{
+    create_store, create_store_with_config, decode_value_with_rc, Store, StoreConfig,
+};
 use near_telemetry::TelemetryActor;
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -38,11 +42,16 @@ use tokio::sync::oneshot;
 use tracing::{error, info, trace};
 
 pub mod append_only_map;
+pub mod cold_storage;
 pub mod config;
+pub mod database_stats;
+pub mod database_validate;
 mod metrics;
 pub mod migrations;
 mod runtime;
 mod shard_tracker;
+pub mod snapshot;
+pub mod state_dump;
 
 const STORE_PATH: &str = "data";
 
@@ -327,6 +336,50 @@ pub fn apply_store_migrations(path: &Path, near_config: &NearConfig) {
         info!(target: "near", "Migrate DB from version 30 to 31");
         migrate_30_to_31(path, &near_config);
     }
+    if db_version <= 31 {
+        // version 31 => 32: add expiry to ban records of the peer store
+        info!(target: "near", "Migrate DB from version 31 to 32");
+        migrate_31_to_32(path, &near_config);
+    }
+    if db_version <= 32 {
+        // version 32 => 33: add ColEpochHeightToEpochId
+        info!(target: "near", "Migrate DB from version 32 to 33");
+        migrate_32_to_33(path);
+    }
+    if db_version <= 33 {
+        // version 33 => 34: add ColChunkSummaries
+        info!(target: "near", "Migrate DB from version 33 to 34");
+        migrate_33_to_34(path);
+    }
+    if db_version <= 34 {
+        // version 34 => 35: add ColReceiptQueueChanges
+        // Does not need to do anything since open db with option `create_missing_column_families`
+        // Nevertheless need to bump db version, because db_version 1 binary can't open db_version 2 db
+        info!(target: "near", "Migrate DB from version 34 to 35");
+        let store = create_store(path);
+        set_store_version(&store, 35);
+    }
+    if db_version <= 35 {
+        // version 35 => 36: add ColStateSyncProgress
+        // Does not need to do anything since open db with option `create_missing_column_families`
+        // Nevertheless need to bump db version, because db_version 1 binary can't open db_version 2 db
+        info!(target: "near", "Migrate DB from version 35 to 36");
+        let store = create_store(path);
+        set_store_version(&store, 36);
+    }
+    if db_version <= 36 {
+        // version 36 => 37: key outcomes by id and block hash in ColTransactionResultForBlock
+        info!(target: "near", "Migrate DB from version 36 to 37");
+        migrate_36_to_37(path);
+    }
+    if db_version <= 37 {
+        // version 37 => 38: add ColAppliedChunkInfo
+        // Does not need to do anything since open db with option `create_missing_column_families`
+        // Nevertheless need to bump db version, because db_version 1 binary can't open db_version 2 db
+        info!(target: "near", "Migrate DB from version 37 to 38");
+        let store = create_store(path);
+        set_store_version(&store, 38);
+    }
 
     #[cfg(feature = "nightly_protocol")]
     {
@@ -366,6 +419,10 @@ pub fn init_and_migrate_store(home_dir: &Path, near_config: &NearConfig) -> Stor
     let path = get_store_path(home_dir);
     let store_exists = store_path_exists(&path);
     if store_exists {
+        if let Err(err) = ensure_recompress_complete(&path) {
+            error!(target: "near", "{:#}", err);
+            std::process::exit(1);
+        }
         apply_store_migrations(&path, near_config);
     }
     let store = create_store_with_config(
@@ -373,6 +430,8 @@ pub fn init_and_migrate_store(home_dir: &Path, near_config: &NearConfig) -> Stor
         StoreConfig {
             read_only: false,
             enable_statistics: near_config.config.enable_rocksdb_statistics,
+            write_batch_soft_limit: near_config.config.store.write_batch_soft_limit,
+            background_io: near_config.config.store.background_io.clone().unwrap_or_default(),
         },
     );
     if !store_exists {
@@ -401,6 +460,9 @@ pub fn start_with_config_and_synchronization(
 ) -> Result<NearNode, anyhow::Error> {
     let store = init_and_migrate_store(home_dir, &config);
 
+    if let Some(threshold) = config.config.slow_contract_compilation_threshold {
+        near_vm_runner::set_slow_compilation_threshold(threshold);
+    }
     let runtime = Arc::new(NightshadeRuntime::with_config(
         home_dir,
         store.clone(),
@@ -412,6 +474,21 @@ pub fn start_with_config_and_synchronization(
     let telemetry = TelemetryActor::new(config.telemetry_config.clone()).start();
     let chain_genesis = ChainGenesis::from(&config.genesis);
 
+    if let Some(dump_config) =
+        config.config.state_sync.as_ref().and_then(|state_sync| state_sync.dump.clone())
+    {
+        let dumper = StateDumper::new(
+            runtime.clone(),
+            &chain_genesis,
+            config.genesis.config.chain_id.clone(),
+            config.validator_signer.as_ref().map(|signer| signer.validator_id().clone()),
+            config.network_config.secret_key.clone(),
+            &dump_config.location,
+        )
+        .context("failed to start state dumper")?;
+        start_state_dumper(dumper, dump_config)?;
+    }
+
     let node_id = PeerId::new(config.network_config.public_key.clone().into());
     let network_adapter = Arc::new(NetworkRecipient::default());
     #[cfg(feature = "test_features")]
@@ -506,6 +583,116 @@ pub fn start_with_config_and_synchronization(
     })
 }
 
+/// File within a database written by [`recompress_storage`] which records the columns copied so
+/// far.  An interrupted run resumes after them and the node refuses the copy until it's complete.
+pub const RECOMPRESS_PROGRESS_FILE: &str = "recompress.json";
+
+/// Columns which are no longer used and aren't copied by [`recompress_storage`].
+const OBSOLETE_COLUMNS: [DBCol; 4] = [
+    DBCol::_ColTransactionResult,
+    DBCol::_ColNextBlockWithNewChunk,
+    DBCol::_ColLastBlockWithNewChunk,
+    DBCol::_ColTransactionRefCount,
+];
+
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+struct RecompressProgress {
+    /// Columns which were copied and verified, with their number of keys.
+    columns: Vec<(String, u64)>,
+    /// Set once every column was copied, the database can be used from then on.
+    complete: bool,
+}
+
+impl RecompressProgress {
+    fn read(db_path: &Path) -> anyhow::Result<Option<Self>> {
+        let path = db_path.join(RECOMPRESS_PROGRESS_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let file = fs::File::open(&path)?;
+        Ok(Some(serde_json::from_reader(file).with_context(|| path.display().to_string())?))
+    }
+
+    /// Writes the progress to a temporary file first, so that it's never seen half written.
+    fn write(&self, db_path: &Path) -> anyhow::Result<()> {
+        let path = db_path.join(RECOMPRESS_PROGRESS_FILE);
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    fn is_done(&self, column: DBCol) -> bool {
+        let name = format!("{:?}", column);
+        self.columns.iter().any(|(done, _)| *done == name)
+    }
+}
+
+/// Fails if the database in `db_path` was written by [`recompress_storage`] which wasn't
+/// completed.  Databases which were never recompressed have no progress file and are fine.
+pub fn ensure_recompress_complete(db_path: &Path) -> anyhow::Result<()> {
+    use strum::EnumCount;
+
+    match RecompressProgress::read(db_path)? {
+        Some(progress) if !progress.complete => anyhow::bail!(
+            "{}: the database is an incomplete recompressed copy ({} of {} columns \
+             copied), run the recompression again to resume it",
+            db_path.display(),
+            progress.columns.len(),
+            DBCol::COUNT - OBSOLETE_COLUMNS.len()
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that `dst_store` holds exactly the live keys of `column` in `src_store`, with the same
+/// values.  Both iterate in the same key order, so they're compared in a single pass.
+fn verify_recompressed_column(
+    src_store: &Store,
+    dst_store: &Store,
+    column: DBCol,
+) -> anyhow::Result<u64> {
+    let mut src = live_keys(src_store, column);
+    let mut dst = dst_store.iter_without_rc_logic(column);
+    let mut count_keys: u64 = 0;
+    loop {
+        match (src.next(), dst.next()) {
+            (None, None) => return Ok(count_keys),
+            (Some((src_key, src_value)), Some((dst_key, dst_value))) => {
+                anyhow::ensure!(
+                    src_key == dst_key && src_value == dst_value,
+                    "col{}: key {:?} of the source differs from key {:?} of the copy",
+                    column as usize,
+                    src_key,
+                    dst_key
+                );
+                count_keys += 1;
+            }
+            (Some((key, _)), None) => {
+                anyhow::bail!("col{}: key {:?} is missing from the copy", column as usize, key)
+            }
+            (None, Some((key, _))) => {
+                anyhow::bail!("col{}: key {:?} isn't in the source", column as usize, key)
+            }
+        }
+    }
+}
+
+/// Keys of `column` with their raw values, including the refcount of refcounted columns.  Keys
+/// whose refcount dropped to zero are dead and skipped.
+fn live_keys<'a>(
+    store: &'a Store,
+    column: DBCol,
+) -> impl Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a {
+    store
+        .iter_without_rc_logic(column)
+        .filter(move |(_, value)| !column.is_rc() || decode_value_with_rc(value).1 > 0)
+}
+
+/// Copies the live keys of every column but the obsolete ones from the database of the node in
+/// `home_dir` into a new database in `dst_dir`, which gets the current column compression and
+/// options, and verifies each column against the source.  Resumes a previous run into `dst_dir`
+/// after the last column it finished.
 pub fn recompress_storage(home_dir: &Path, dst_dir: &Path) -> anyhow::Result<()> {
     use strum::{EnumCount, IntoEnumIterator};
 
@@ -527,6 +714,7 @@ pub fn recompress_storage(home_dir: &Path, dst_dir: &Path) -> anyhow::Result<()>
         "{}: source storage doesn’t exist",
         src_dir.display()
     );
+    ensure_recompress_complete(&src_dir)?;
     let db_version = get_store_version(&src_dir);
     anyhow::ensure!(
         db_version == near_primitives::version::DB_VERSION,
@@ -536,22 +724,39 @@ pub fn recompress_storage(home_dir: &Path, dst_dir: &Path) -> anyhow::Result<()>
         db_version
     );
 
-    anyhow::ensure!(
-        !store_path_exists(&dst_dir),
-        "{}: directory already exists",
-        dst_dir.display()
-    );
+    let mut progress = match RecompressProgress::read(dst_dir)? {
+        Some(progress) => {
+            anyhow::ensure!(!progress.complete, "{}: already complete", dst_dir.display());
+            info!(
+                "Resuming recompression into {} after {} columns",
+                dst_dir.display(),
+                progress.columns.len()
+            );
+            progress
+        }
+        None => {
+            anyhow::ensure!(
+                !store_path_exists(&dst_dir),
+                "{}: directory already exists",
+                dst_dir.display()
+            );
+            RecompressProgress::default()
+        }
+    };
 
     info!("Recompressing data from {} into {}", src_dir.display(), dst_dir.display());
-    let src_store = create_store_with_config(
-        &src_dir,
-        StoreConfig { read_only: true, enable_statistics: false },
-    );
+    let src_store =
+        create_store_with_config(&src_dir, StoreConfig { read_only: true, ..Default::default() });
     let dst_store = create_store(&dst_dir);
+    // Written right away so that the node refuses the copy until it's complete.
+    progress.write(dst_dir)?;
 
     const BATCH_SIZE_BYTES: u64 = 150_000_000;
 
     for (n, column) in DBCol::iter().enumerate() {
+        if OBSOLETE_COLUMNS.contains(&column) || progress.is_done(column) {
+            continue;
+        }
         info!(
             "Recompressing col{} ‘{}’ ({:2} / {:2})",
             column as usize,
@@ -559,11 +764,17 @@ pub fn recompress_storage(home_dir: &Path, dst_dir: &Path) -> anyhow::Result<()>
             n + 1,
             DBCol::COUNT
         );
+        // A column left half copied by an interrupted run is copied again from scratch.
+        let mut store_update = dst_store.store_update();
+        store_update.delete_all(column);
+        store_update.commit()?;
+
         let mut store_update = dst_store.store_update();
         let mut total_written: u64 = 0;
         let mut batch_written: u64 = 0;
         let mut count_keys: u64 = 0;
-        for (key, value) in src_store.iter(column) {
+        // Refcounted values are copied raw, with their refcount.
+        for (key, value) in live_keys(&src_store, column) {
             store_update.set(column, &key, &value);
             total_written += value.len() as u64;
             batch_written += value.len() as u64;
@@ -587,7 +798,17 @@ pub fn recompress_storage(home_dir: &Path, dst_dir: &Path) -> anyhow::Result<()>
             total_written as f64 / 1_000_000_000.0
         );
         store_update.commit()?;
+        if let Some(db) = dst_store.get_rocksdb() {
+            db.flush().map_err(|err| anyhow::anyhow!("flush: {:?}", err))?;
+        }
+
+        let verified_keys = verify_recompressed_column(&src_store, &dst_store, column)?;
+        info!("col{}: verified {} keys", column as usize, verified_keys);
+        progress.columns.push((format!("{:?}", column), verified_keys));
+        progress.write(dst_dir)?;
     }
+    progress.complete = true;
+    progress.write(dst_dir)?;
 
     core::mem::drop(dst_store);
     core::mem::drop(src_store);
@@ -595,3 +816,33 @@ pub fn recompress_storage(home_dir: &Path, dst_dir: &Path) -> anyhow::Result<()>
     info!("Done; recompressed database at {}", dst_dir.display());
     Ok(())
 }
+
+/// Prunes execution outcomes of blocks older than `epochs` epochs from the database of a stopped
+/// node.
+pub fn prune_history(home_dir: &Path, near_config: &NearConfig, epochs: u64) -> anyhow::Result<()> {
+    anyhow::ensure!(!near_config.client_config.archive, "refusing to prune an archival node");
+    let store_path = home_dir.join(STORE_PATH);
+    anyhow::ensure!(
+        store_path_exists(&store_path),
+        "{}: storage doesn’t exist",
+        store_path.display()
+    );
+    let db_version = get_store_version(&store_path);
+    anyhow::ensure!(
+        db_version == near_primitives::version::DB_VERSION,
+        "{}: expected DB version {} but got {}",
+        store_path.display(),
+        near_primitives::version::DB_VERSION,
+        db_version
+    );
+
+    let store = create_store(&store_path);
+    let mut chain_store =
+        near_chain::ChainStore::new(store, near_config.genesis.config.genesis_height);
+    let min_height =
+        chain_store.outcomes_prune_height(epochs, near_config.genesis.config.epoch_length)?;
+    info!("Pruning outcomes of blocks below height {} in {}", min_height, store_path.display());
+    let pruned = chain_store.prune_outcomes(min_height)?;
+    info!("Done; pruned {} outcomes", pruned);
+    Ok(())
+}
@@ -1,4 +1,7 @@
-use near_metrics::{try_create_histogram_vec, HistogramVec};
+use near_metrics::{
+    try_create_histogram_vec, try_create_int_counter_vec, try_create_int_gauge_vec, HistogramVec,
+    IntCounterVec, IntGaugeVec,
+};
 use once_cell::sync::Lazy;
 
 pub static APPLY_CHUNK_DELAY: Lazy<HistogramVec> = Lazy::new(|| {
@@ -24,3 +27,39 @@ pub static SECONDS_PER_PETAGAS: Lazy<HistogramVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+
+pub static STATE_DUMP_EPOCH_HEIGHT: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_state_dump_epoch_height",
+        "Height of the epoch whose starting state is being dumped",
+        &["shard_id"],
+    )
+    .unwrap()
+});
+
+pub static STATE_DUMP_NUM_PARTS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_state_dump_num_parts",
+        "Number of state parts in the state being dumped",
+        &["shard_id"],
+    )
+    .unwrap()
+});
+
+pub static STATE_DUMP_PARTS_DUMPED: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_state_dump_parts_dumped",
+        "Number of state parts uploaded and verified in the state being dumped",
+        &["shard_id"],
+    )
+    .unwrap()
+});
+
+pub static STATE_DUMP_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_state_dump_errors_total",
+        "Number of failed attempts to dump a state part or header",
+        &["shard_id"],
+    )
+    .unwrap()
+});
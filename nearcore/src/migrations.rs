@@ -9,20 +9,23 @@ use near_primitives::epoch_manager::{AllEpochConfig, EpochConfig};
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::MerklePath;
 use near_primitives::receipt::ReceiptResult;
+use near_primitives::runtime::config_store::RuntimeConfigStore;
 use near_primitives::runtime::migration_data::MigrationData;
 use near_primitives::shard_layout::ShardUId;
 use near_primitives::sharding::{ChunkHash, ShardChunkHeader, ShardChunkV1};
 use near_primitives::transaction::{
     ExecutionMetadata, ExecutionOutcome, ExecutionOutcomeWithId, ExecutionOutcomeWithIdAndProof,
-    ExecutionStatus, LogEntry,
+    ExecutionOutcomeWithProof, ExecutionStatus, LogEntry,
 };
 use near_primitives::types::{AccountId, Balance, Gas};
 use near_primitives::types::{BlockHeight, ShardId};
-use near_primitives::utils::index_to_bytes;
+use near_primitives::utils::{get_outcome_id_block_hash, index_to_bytes};
 use near_store::db::DBCol::ColReceipts;
 use near_store::migrations::{set_store_version, BatchedStoreUpdate};
-use near_store::{create_store, DBCol, StoreUpdate};
+use near_store::{create_store, DBCol, StoreUpdate, PRUNED_OUTCOME_BLOCK_HASH};
+use std::collections::HashMap;
 use std::path::Path;
+use std::sync::Arc;
 
 fn get_chunk(chain_store: &ChainStore, chunk_hash: ChunkHash) -> ShardChunkV1 {
     let store = chain_store.store();
@@ -90,7 +93,7 @@ fn apply_block_at_height(
     {
         let id = outcome_with_id.id;
         let outcome = vec![ExecutionOutcomeWithIdAndProof { proof, block_hash, outcome_with_id }];
-        store_update.set_ser(DBCol::ColTransactionResult, id.as_ref(), &outcome)?;
+        store_update.set_ser(DBCol::_ColTransactionResult, id.as_ref(), &outcome)?;
     }
     Ok(())
 }
@@ -101,9 +104,9 @@ pub fn migrate_12_to_13(path: &Path, near_config: &NearConfig) {
         // Non archival node. Perform a simply migration without necessarily fixing the inconsistencies
         // since the old data will be garbage collected in five epochs
         let mut store_update = store.store_update();
-        for (key, value) in store.iter_without_rc_logic(DBCol::ColTransactionResult) {
+        for (key, value) in store.iter_without_rc_logic(DBCol::_ColTransactionResult) {
             let outcome = ExecutionOutcomeWithIdAndProof::try_from_slice(&value).unwrap();
-            store_update.set_ser(DBCol::ColTransactionResult, &key, &vec![outcome]).unwrap();
+            store_update.set_ser(DBCol::_ColTransactionResult, &key, &vec![outcome]).unwrap();
         }
         store_update.commit().unwrap();
     } else {
@@ -113,7 +116,7 @@ pub fn migrate_12_to_13(path: &Path, near_config: &NearConfig) {
         let head = chain_store.head().expect("head must exist");
         let runtime = NightshadeRuntime::with_config(path, store.clone(), near_config, None, None);
         let mut store_update = store.store_update();
-        store_update.delete_all(DBCol::ColTransactionResult);
+        store_update.delete_all(DBCol::_ColTransactionResult);
         store_update.commit().unwrap();
 
         let mut cur_height = genesis_height;
@@ -147,7 +150,10 @@ pub fn migrate_18_to_19(path: &Path, near_config: &NearConfig) {
             store.clone(),
             AllEpochConfig::new(epoch_config, None),
             near_config.genesis.config.protocol_version,
-            RewardCalculator::new(&near_config.genesis.config),
+            RewardCalculator::new(
+                &near_config.genesis.config,
+                Arc::new(RuntimeConfigStore::for_chain_id(&near_config.genesis.config.chain_id)),
+            ),
             near_config.genesis.config.validators(),
         )
         .unwrap();
@@ -418,7 +424,7 @@ pub fn migrate_24_to_25(path: &Path) {
     }
 
     let mut store_update = BatchedStoreUpdate::new(&store, 10_000_000);
-    for (key, value) in store.iter(DBCol::ColTransactionResult) {
+    for (key, value) in store.iter(DBCol::_ColTransactionResult) {
         if Vec::<ExecutionOutcomeWithIdAndProof>::try_from_slice(&value).is_ok() {
             // has success in previous attempt of this migration
             continue;
@@ -471,7 +477,7 @@ pub fn migrate_24_to_25(path: &Path) {
         let outcomes: Vec<ExecutionOutcomeWithIdAndProof> =
             old_outcomes.into_iter().map(|outcome| outcome.into()).collect();
         store_update
-            .set_ser(DBCol::ColTransactionResult, key.as_ref(), &outcomes)
+            .set_ser(DBCol::_ColTransactionResult, key.as_ref(), &outcomes)
             .expect("BorshSerialize should not fail");
     }
     store_update.finish().expect("Failed to migrate");
@@ -578,3 +584,128 @@ mod tests {
         assert!(testnet_migration_data.restored_receipts.is_empty());
     }
 }
+
+/// Migrates peer store records to ban records which carry the expiry of the ban. Existing bans
+/// expire after the configured ban window counted from the time they were issued.
+pub fn migrate_31_to_32(path: &Path, near_config: &NearConfig) {
+    use near_network_primitives::types::{KnownPeerState, KnownPeerStatus, PeerInfo, ReasonForBan};
+
+    #[derive(BorshDeserialize)]
+    enum OldKnownPeerStatus {
+        Unknown,
+        NotConnected,
+        Connected,
+        Banned(ReasonForBan, u64),
+    }
+
+    #[derive(BorshDeserialize)]
+    struct OldKnownPeerState {
+        peer_info: PeerInfo,
+        status: OldKnownPeerStatus,
+        first_seen: u64,
+        last_seen: u64,
+    }
+
+    let store = create_store(path);
+    let ban_window = near_config.network_config.ban_window.as_nanos() as u64;
+    let mut store_update = BatchedStoreUpdate::new(&store, 10_000_000);
+    for (key, value) in store.iter(DBCol::ColPeers) {
+        let old_state = OldKnownPeerState::try_from_slice(&value).unwrap();
+        let status = match old_state.status {
+            OldKnownPeerStatus::Unknown => KnownPeerStatus::Unknown,
+            OldKnownPeerStatus::NotConnected => KnownPeerStatus::NotConnected,
+            OldKnownPeerStatus::Connected => KnownPeerStatus::Connected,
+            OldKnownPeerStatus::Banned(reason, banned_at) => KnownPeerStatus::Banned {
+                reason,
+                banned_at,
+                expires_at: banned_at.saturating_add(ban_window),
+            },
+        };
+        let new_state = KnownPeerState {
+            peer_info: old_state.peer_info,
+            status,
+            first_seen: old_state.first_seen,
+            last_seen: old_state.last_seen,
+        };
+        store_update.set_ser(DBCol::ColPeers, &key, &new_state).expect("Failed to migrate");
+    }
+    store_update.finish().expect("Failed to migrate");
+    set_store_version(&store, 32);
+}
+
+/// Backfills the epoch height to epoch id index from the stored epoch infos.
+pub fn migrate_32_to_33(path: &Path) {
+    use near_primitives::epoch_manager::epoch_info::EpochInfo;
+    use near_primitives::epoch_manager::AGGREGATOR_KEY;
+    use near_primitives::types::EpochId;
+
+    let store = create_store(path);
+    let mut epoch_ids = HashMap::<_, Vec<EpochId>>::new();
+    for (key, value) in store.iter(DBCol::ColEpochInfo) {
+        if &*key == AGGREGATOR_KEY {
+            continue;
+        }
+        let epoch_id = EpochId::try_from_slice(&key).unwrap();
+        let epoch_info = EpochInfo::try_from_slice(&value).unwrap();
+        epoch_ids.entry(epoch_info.epoch_height()).or_default().push(epoch_id);
+    }
+    let mut store_update = BatchedStoreUpdate::new(&store, 10_000_000);
+    for (epoch_height, epoch_ids) in epoch_ids {
+        store_update
+            .set_ser(DBCol::ColEpochHeightToEpochId, &index_to_bytes(epoch_height), &epoch_ids)
+            .expect("Failed to migrate");
+    }
+    store_update.finish().expect("Failed to migrate");
+    set_store_version(&store, 33);
+}
+
+/// Backfills the summaries of the stored chunks.
+pub fn migrate_33_to_34(path: &Path) {
+    use near_primitives::sharding::{ChunkSummary, ShardChunk};
+
+    let store = create_store(path);
+    let mut store_update = BatchedStoreUpdate::new(&store, 10_000_000);
+    for (key, value) in store.iter(DBCol::ColChunks) {
+        let chunk = ShardChunk::try_from_slice(&value).unwrap();
+        store_update
+            .set_ser(DBCol::ColChunkSummaries, &key, &ChunkSummary::from(&chunk))
+            .expect("Failed to migrate");
+    }
+    store_update.finish().expect("Failed to migrate");
+    set_store_version(&store, 34);
+}
+
+/// Moves the outcomes from `_ColTransactionResult`, where all the outcomes of an id are in a
+/// single row, to `ColTransactionResultForBlock`, where each block has its own row.
+pub fn migrate_36_to_37(path: &Path) {
+    let store = create_store(path);
+    let mut store_update = BatchedStoreUpdate::new(&store, 10_000_000);
+    for (key, value) in store.iter(DBCol::_ColTransactionResult) {
+        let id = CryptoHash::try_from(key.as_ref()).unwrap();
+        let outcomes = Vec::<ExecutionOutcomeWithIdAndProof>::try_from_slice(&value).unwrap();
+        if outcomes.is_empty() {
+            // Outcomes removed by outcomes pruning.
+            store_update
+                .set(
+                    DBCol::ColTransactionResultForBlock,
+                    &get_outcome_id_block_hash(&id, &PRUNED_OUTCOME_BLOCK_HASH),
+                    &[],
+                )
+                .expect("Failed to migrate");
+        }
+        for outcome in outcomes {
+            store_update
+                .set_ser(
+                    DBCol::ColTransactionResultForBlock,
+                    &get_outcome_id_block_hash(&id, &outcome.block_hash),
+                    &ExecutionOutcomeWithProof::from(outcome),
+                )
+                .expect("Failed to migrate");
+        }
+    }
+    store_update.finish().expect("Failed to migrate");
+    let mut store_update = store.store_update();
+    store_update.delete_all(DBCol::_ColTransactionResult);
+    store_update.commit().expect("Failed to migrate");
+    set_store_version(&store, 37);
+}
@@ -0,0 +1,188 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, RwLock};
+
+use tracing::debug;
+
+use near_chain_configs::DelegatedStakeViewConfig;
+use near_epoch_manager::EpochManager;
+use near_primitives::errors::EpochError;
+use near_primitives::shard_layout::{account_id_to_shard_uid, get_block_shard_uid, ShardUId};
+use near_primitives::types::chunk_extra::ChunkExtra;
+use near_primitives::types::{AccountId, Balance, EpochId, EpochInfoProvider, ShardId, StateRoot};
+use near_primitives::views::ViewApplyState;
+use near_store::{ColChunkExtra, ShardTries, Store, StoreCompiledContractCache};
+use node_runtime::state_viewer::TrieViewer;
+
+use super::POISONED_LOCK_ERR;
+
+/// Number of epochs for which the delegated stakes are cached.
+const NUM_CACHED_EPOCHS: usize = 3;
+
+type DelegatedStakes = HashMap<(AccountId, AccountId), Option<Balance>>;
+
+/// Views the stake delegated to staking pools by calling the view method configured in
+/// `DelegatedStakeViewConfig` on the pool contract.
+///
+/// The pool is viewed in the state as of the block whose hash is the epoch id, that is the last
+/// block of the epoch in which the validators of the epoch were selected.  The result therefore
+/// never changes within an epoch and is cached.
+pub struct DelegatedStakeViewer {
+    config: DelegatedStakeViewConfig,
+    store: Store,
+    tries: ShardTries,
+    trie_viewer: TrieViewer,
+    cache: Mutex<VecDeque<(EpochId, DelegatedStakes)>>,
+}
+
+impl DelegatedStakeViewer {
+    pub fn new(config: DelegatedStakeViewConfig, store: Store, tries: ShardTries) -> Self {
+        let trie_viewer = TrieViewer::new(None, Some(config.max_gas_burnt));
+        Self { config, store, tries, trie_viewer, cache: Default::default() }
+    }
+
+    /// Returns `None` if the state of the pool is not available on this node, the pool doesn't
+    /// run an allowed contract, or its view method fails or doesn't return a balance.
+    pub fn delegated_stake(
+        &self,
+        epoch_manager: &RwLock<EpochManager>,
+        epoch_info_provider: &dyn EpochInfoProvider,
+        epoch_id: &EpochId,
+        pool_account_id: &AccountId,
+        delegator_account_id: &AccountId,
+    ) -> Option<Balance> {
+        let key = (pool_account_id.clone(), delegator_account_id.clone());
+        if let Some(stake) = self.get_cached(epoch_id, &key) {
+            return stake;
+        }
+        let (shard_uid, state_root, view_state) = match self.view_state(
+            epoch_manager,
+            epoch_id,
+            pool_account_id,
+        ) {
+            Ok(Some(state)) => state,
+            // The state may still become available, e.g. after state sync, so don't cache.
+            Ok(None) => return None,
+            Err(err) => {
+                debug!(target: "runtime", "Cannot view delegated stake in epoch {:?}: {}", epoch_id, err);
+                return None;
+            }
+        };
+        let stake = self.call_pool(
+            shard_uid,
+            state_root,
+            view_state,
+            epoch_info_provider,
+            pool_account_id,
+            delegator_account_id,
+        );
+        self.insert_cached(epoch_id, key, stake);
+        stake
+    }
+
+    /// Returns the shard and state root of the pool and the view state to call it in, or `None`
+    /// if this node doesn't have the state.
+    fn view_state(
+        &self,
+        epoch_manager: &RwLock<EpochManager>,
+        epoch_id: &EpochId,
+        pool_account_id: &AccountId,
+    ) -> Result<Option<(ShardUId, StateRoot, ViewApplyState)>, EpochError> {
+        // The first two epochs don't have a block selecting their validators.
+        if epoch_id == &EpochId::default() {
+            return Ok(None);
+        }
+        let block_hash = epoch_id.0;
+        let (block_info, epoch_height, protocol_version, shard_uid) = {
+            let mut epoch_manager = epoch_manager.write().expect(POISONED_LOCK_ERR);
+            let block_info = epoch_manager.get_block_info(&block_hash)?.clone();
+            let epoch_info = epoch_manager.get_epoch_info(block_info.epoch_id())?;
+            let (epoch_height, protocol_version) =
+                (epoch_info.epoch_height(), epoch_info.protocol_version());
+            let shard_layout = epoch_manager.get_shard_layout(block_info.epoch_id())?;
+            let shard_uid = account_id_to_shard_uid(pool_account_id, shard_layout);
+            (block_info, epoch_height, protocol_version, shard_uid)
+        };
+        let chunk_extra = match self
+            .store
+            .get_ser::<ChunkExtra>(ColChunkExtra, &get_block_shard_uid(&block_hash, &shard_uid))
+        {
+            Ok(Some(chunk_extra)) => chunk_extra,
+            _ => return Ok(None),
+        };
+        let view_state = ViewApplyState {
+            block_height: *block_info.height(),
+            prev_block_hash: *block_info.prev_hash(),
+            block_hash,
+            epoch_id: block_info.epoch_id().clone(),
+            epoch_height,
+            shard_id: shard_uid.shard_id as ShardId,
+            block_timestamp: *block_info.timestamp_nanosec(),
+            current_protocol_version: protocol_version,
+            cache: Some(Arc::new(StoreCompiledContractCache { store: self.store.clone() })),
+        };
+        Ok(Some((shard_uid, *chunk_extra.state_root(), view_state)))
+    }
+
+    fn call_pool(
+        &self,
+        shard_uid: ShardUId,
+        state_root: StateRoot,
+        view_state: ViewApplyState,
+        epoch_info_provider: &dyn EpochInfoProvider,
+        pool_account_id: &AccountId,
+        delegator_account_id: &AccountId,
+    ) -> Option<Balance> {
+        let state_update = self.tries.new_trie_update_view(shard_uid, state_root);
+        let account = self.trie_viewer.view_account(&state_update, pool_account_id).ok()?;
+        if !self.config.allowed_code_hashes.contains(&account.code_hash()) {
+            return None;
+        }
+        let args = serde_json::json!({ "account_id": delegator_account_id }).to_string();
+        let mut logs = vec![];
+        match self.trie_viewer.call_function(
+            state_update,
+            view_state,
+            pool_account_id,
+            &self.config.method_name,
+            args.as_bytes(),
+            &mut logs,
+            epoch_info_provider,
+        ) {
+            // Balances are returned as strings, the way `U128` of near-sdk is serialized.
+            Ok(result) => serde_json::from_slice::<String>(&result.result).ok()?.parse().ok(),
+            Err(err) => {
+                debug!(target: "runtime", "Failed to view the stake delegated to {}: {}", pool_account_id, err);
+                None
+            }
+        }
+    }
+
+    fn get_cached(
+        &self,
+        epoch_id: &EpochId,
+        key: &(AccountId, AccountId),
+    ) -> Option<Option<Balance>> {
+        let cache = self.cache.lock().expect(POISONED_LOCK_ERR);
+        let (_, stakes) = cache.iter().find(|(cached_epoch_id, _)| cached_epoch_id == epoch_id)?;
+        stakes.get(key).copied()
+    }
+
+    fn insert_cached(
+        &self,
+        epoch_id: &EpochId,
+        key: (AccountId, AccountId),
+        stake: Option<Balance>,
+    ) {
+        let mut cache = self.cache.lock().expect(POISONED_LOCK_ERR);
+        if let Some((_, stakes)) =
+            cache.iter_mut().find(|(cached_epoch_id, _)| cached_epoch_id == epoch_id)
+        {
+            stakes.insert(key, stake);
+            return;
+        }
+        if cache.len() == NUM_CACHED_EPOCHS {
+            cache.pop_front();
+        }
+        cache.push_back((epoch_id.clone(), std::iter::once((key, stake)).collect()));
+    }
+}
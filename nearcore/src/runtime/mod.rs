@@ -10,11 +10,12 @@ use borsh::BorshDeserialize;
 use tracing::{debug, error, info, warn};
 
 use near_chain::chain::NUM_EPOCHS_TO_KEEP_STORE_DATA;
+use near_chain::contract_execution::ContractExecutionTracker;
 use near_chain::types::{
     ApplySplitStateResult, ApplyTransactionResult, BlockHeaderInfo, ValidatorInfoIdentifier,
 };
 use near_chain::{BlockHeader, Doomslug, DoomslugThresholdMode, Error, ErrorKind, RuntimeAdapter};
-use near_chain_configs::{Genesis, GenesisConfig, ProtocolConfig};
+use near_chain_configs::{DelegatedStakeViewConfig, Genesis, GenesisConfig, ProtocolConfig};
 use near_crypto::{PublicKey, Signature};
 use near_epoch_manager::EpochManager;
 use near_pool::types::PoolIterator;
@@ -27,7 +28,7 @@ use near_primitives::epoch_manager::epoch_info::EpochInfo;
 use near_primitives::epoch_manager::{EpochConfig, ShardConfig};
 use near_primitives::errors::{EpochError, InvalidTxError, RuntimeError};
 use near_primitives::hash::{hash, CryptoHash};
-use near_primitives::receipt::Receipt;
+use near_primitives::receipt::{Receipt, ReceiptQueueChanges};
 use near_primitives::sharding::ChunkHash;
 use near_primitives::state_part::PartId;
 use near_primitives::state_record::{state_record_to_account_id, StateRecord};
@@ -40,8 +41,9 @@ use near_primitives::types::{
 };
 use near_primitives::version::ProtocolVersion;
 use near_primitives::views::{
-    AccessKeyInfoView, CallResult, EpochValidatorInfo, QueryRequest, QueryResponse,
-    QueryResponseKind, ViewApplyState, ViewStateResult,
+    AccessKeyInfoView, CallResult, EpochInfoView, EpochValidatorInfo, ProtocolUpgradeStatusView,
+    QueryRequest, QueryResponse, QueryResponseKind, UnstakeScheduleView,
+    ValidatorProductionStatsView, ViewApplyState, ViewStateResult,
 };
 use near_store::{
     get_genesis_hash, get_genesis_state_roots, set_genesis_hash, set_genesis_state_roots,
@@ -60,8 +62,10 @@ use crate::metrics;
 use crate::migrations::load_migration_data;
 use crate::shard_tracker::{ShardTracker, TrackedConfig};
 use crate::NearConfig;
+use delegated_stake::DelegatedStakeViewer;
 use errors::FromStateViewerErrors;
-use near_primitives::runtime::config_store::{RuntimeConfigStore, INITIAL_TESTNET_CONFIG};
+use near_primitives::runtime::config::RuntimeConfig;
+use near_primitives::runtime::config_store::RuntimeConfigStore;
 use near_primitives::runtime::migration_data::{MigrationData, MigrationFlags};
 use near_primitives::shard_layout::{
     account_id_to_shard_id, account_id_to_shard_uid, ShardLayout, ShardUId,
@@ -69,8 +73,9 @@ use near_primitives::shard_layout::{
 use near_primitives::syncing::{get_num_state_parts, STATE_PART_MEMORY_LIMIT};
 use near_store::split_state::get_delayed_receipts;
 use node_runtime::near_primitives::shard_layout::ShardLayoutError;
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 
+mod delegated_stake;
 pub mod errors;
 
 const POISONED_LOCK_ERR: &str = "The lock was poisoned.";
@@ -126,13 +131,77 @@ impl EpochInfoProvider for SafeEpochManager {
         let mut epoch_manager = self.0.write().expect(POISONED_LOCK_ERR);
         epoch_manager.minimum_stake(prev_block_hash)
     }
+
+    fn account_id_to_shard_id(
+        &self,
+        account_id: &AccountId,
+        epoch_id: &EpochId,
+    ) -> Result<ShardId, EpochError> {
+        let mut epoch_manager = self.0.write().expect(POISONED_LOCK_ERR);
+        let shard_layout = epoch_manager.get_shard_layout(epoch_id)?;
+        Ok(account_id_to_shard_id(account_id, shard_layout))
+    }
+}
+
+/// Epoch info provider of view calls.  `SafeEpochManager` is the provider of chunk application
+/// and answers the same on every node, while this one also reports data which depends on the
+/// node config, such as the stake delegated to staking pools.
+pub struct ViewEpochInfoProvider<'a> {
+    epoch_manager: &'a SafeEpochManager,
+    delegated_stake_viewer: Option<&'a DelegatedStakeViewer>,
+}
+
+impl EpochInfoProvider for ViewEpochInfoProvider<'_> {
+    fn validator_stake(
+        &self,
+        epoch_id: &EpochId,
+        last_block_hash: &CryptoHash,
+        account_id: &AccountId,
+    ) -> Result<Option<Balance>, EpochError> {
+        self.epoch_manager.validator_stake(epoch_id, last_block_hash, account_id)
+    }
+
+    fn validator_total_stake(
+        &self,
+        epoch_id: &EpochId,
+        last_block_hash: &CryptoHash,
+    ) -> Result<Balance, EpochError> {
+        self.epoch_manager.validator_total_stake(epoch_id, last_block_hash)
+    }
+
+    fn minimum_stake(&self, prev_block_hash: &CryptoHash) -> Result<Balance, EpochError> {
+        self.epoch_manager.minimum_stake(prev_block_hash)
+    }
+
+    fn account_id_to_shard_id(
+        &self,
+        account_id: &AccountId,
+        epoch_id: &EpochId,
+    ) -> Result<ShardId, EpochError> {
+        self.epoch_manager.account_id_to_shard_id(account_id, epoch_id)
+    }
+
+    fn delegated_stake(
+        &self,
+        epoch_id: &EpochId,
+        pool_account_id: &AccountId,
+        delegator_account_id: &AccountId,
+    ) -> Option<Balance> {
+        self.delegated_stake_viewer?.delegated_stake(
+            &self.epoch_manager.0,
+            self,
+            epoch_id,
+            pool_account_id,
+            delegator_account_id,
+        )
+    }
 }
 
 /// Defines Nightshade state transition and validator rotation.
 /// TODO: this possibly should be merged with the runtime cargo or at least reconciled on the interfaces.
 pub struct NightshadeRuntime {
     genesis_config: GenesisConfig,
-    runtime_config_store: RuntimeConfigStore,
+    runtime_config_store: Arc<RuntimeConfigStore>,
 
     store: Store,
     tries: ShardTries,
@@ -142,6 +211,13 @@ pub struct NightshadeRuntime {
     shard_tracker: ShardTracker,
     genesis_state_roots: Vec<StateRoot>,
     migration_data: Arc<MigrationData>,
+    /// Whether to save the receipt queue changes of applied chunks, see
+    /// `ClientConfig::save_receipt_queue_changes`.
+    save_receipt_queue_changes: bool,
+    contract_execution: ContractExecutionTracker,
+    /// Viewer of the stake delegated to staking pools, if `delegated_stake_view` is configured.
+    /// Only used by view calls, see `ViewEpochInfoProvider`.
+    delegated_stake_viewer: Option<DelegatedStakeViewer>,
 }
 
 impl NightshadeRuntime {
@@ -152,7 +228,7 @@ impl NightshadeRuntime {
         trie_viewer_state_size_limit: Option<u64>,
         max_gas_burnt_view: Option<Gas>,
     ) -> Self {
-        Self::new(
+        let mut runtime = Self::new(
             home_dir,
             store,
             &config.genesis,
@@ -160,7 +236,27 @@ impl NightshadeRuntime {
             trie_viewer_state_size_limit,
             max_gas_burnt_view,
             None,
-        )
+        );
+        runtime.trie_viewer.set_register_limits(config.client_config.view_register_limits.clone());
+        runtime.save_receipt_queue_changes = config.client_config.save_receipt_queue_changes;
+        runtime.set_delegated_stake_view(config.client_config.delegated_stake_view.clone());
+        runtime
+    }
+
+    /// Enables viewing the stake delegated to staking pools through the epoch info provider of
+    /// view calls, or disables it if `config` is None.
+    pub fn set_delegated_stake_view(&mut self, config: Option<DelegatedStakeViewConfig>) {
+        self.delegated_stake_viewer = config.map(|config| {
+            DelegatedStakeViewer::new(config, self.store.clone(), self.tries.clone())
+        });
+    }
+
+    /// Returns the epoch info provider of view calls.
+    pub fn view_epoch_info_provider(&self) -> ViewEpochInfoProvider<'_> {
+        ViewEpochInfoProvider {
+            epoch_manager: &self.epoch_manager,
+            delegated_stake_viewer: self.delegated_stake_viewer.as_ref(),
+        }
     }
 
     pub fn new(
@@ -172,10 +268,10 @@ impl NightshadeRuntime {
         max_gas_burnt_view: Option<Gas>,
         runtime_config_store: Option<RuntimeConfigStore>,
     ) -> Self {
-        let runtime_config_store = match runtime_config_store {
+        let runtime_config_store = Arc::new(match runtime_config_store {
             Some(store) => store,
-            None => NightshadeRuntime::create_runtime_config_store(&genesis.config.chain_id),
-        };
+            None => RuntimeConfigStore::for_chain_id(&genesis.config.chain_id),
+        });
 
         let runtime = Runtime::new();
         let trie_viewer = TrieViewer::new(trie_viewer_state_size_limit, max_gas_burnt_view);
@@ -195,8 +291,12 @@ impl NightshadeRuntime {
             genesis.config.num_block_producer_seats_per_shard.len() as NumShards,
         );
         let epoch_manager = Arc::new(RwLock::new(
-            EpochManager::new_from_genesis_config(store.clone(), &genesis_config)
-                .expect("Failed to start Epoch Manager"),
+            EpochManager::new_from_genesis_config(
+                store.clone(),
+                &genesis_config,
+                runtime_config_store.clone(),
+            )
+            .expect("Failed to start Epoch Manager"),
         ));
         let shard_tracker = ShardTracker::new(tracked_config, epoch_manager.clone());
         NightshadeRuntime {
@@ -210,6 +310,9 @@ impl NightshadeRuntime {
             shard_tracker,
             genesis_state_roots: state_roots,
             migration_data: Arc::new(load_migration_data(&genesis.config.chain_id)),
+            save_receipt_queue_changes: false,
+            contract_execution: Default::default(),
+            delegated_stake_viewer: None,
         }
     }
 
@@ -238,21 +341,26 @@ impl NightshadeRuntime {
         epoch_manager.get_epoch_id(hash).map_err(Error::from)
     }
 
-    /// Create store of runtime configs for the given chain id.
-    ///
-    /// For mainnet and other chains except testnet we don't need to override runtime config for
-    /// first protocol versions.
-    /// For testnet, runtime config for genesis block was (incorrectly) different, that's why we
-    /// need to override it specifically to preserve compatibility.
-    fn create_runtime_config_store(chain_id: &str) -> RuntimeConfigStore {
-        match chain_id {
-            "testnet" => {
-                let genesis_runtime_config =
-                    serde_json::from_slice(INITIAL_TESTNET_CONFIG).unwrap();
-                RuntimeConfigStore::new(Some(&genesis_runtime_config))
-            }
-            _ => RuntimeConfigStore::new(None),
-        }
+    /// Stakes of the validators of `epoch_id`, as seen by contracts in the blocks after
+    /// `last_block_hash`, i.e. without the slashed validators.
+    pub fn validator_stakes(
+        &self,
+        epoch_id: &EpochId,
+        last_block_hash: &CryptoHash,
+    ) -> Result<Vec<(AccountId, Balance)>, Error> {
+        let mut epoch_manager = self.epoch_manager.as_ref().write().expect(POISONED_LOCK_ERR);
+        let slashed = epoch_manager.get_slashed_validators(last_block_hash)?.clone();
+        let epoch_info = epoch_manager.get_epoch_info(epoch_id)?;
+        Ok(epoch_info
+            .validators_iter()
+            .filter(|info| !slashed.contains_key(info.account_id()))
+            .map(|info| info.account_and_stake())
+            .collect())
+    }
+
+    /// Minimum stake of the epoch after `prev_block_hash`.
+    pub fn minimum_stake(&self, prev_block_hash: &CryptoHash) -> Result<Balance, Error> {
+        Ok(self.epoch_manager.minimum_stake(prev_block_hash)?)
     }
 
     fn genesis_state_from_dump(store: Store, home_dir: &Path) -> Vec<StateRoot> {
@@ -271,60 +379,110 @@ impl NightshadeRuntime {
     fn genesis_state_from_records(store: Store, genesis: &Genesis) -> Vec<StateRoot> {
         if !genesis.records.as_ref().is_empty() {
             info!(target: "runtime", "Genesis state has {} records, computing state roots", genesis.records.0.len());
+        } else if genesis.has_sharded_records() {
+            info!(target: "runtime", "Computing state roots from sharded records in {:?}", genesis.records_dir);
         } else {
             info!(target: "runtime", "Computing state roots from records in file {:?}", genesis.records_file);
         }
-        let mut state_roots = vec![];
         let initial_epoch_config = EpochConfig::from(&genesis.config);
         let shard_layout = initial_epoch_config.shard_layout;
         let num_shards = shard_layout.num_shards();
+        let tries = ShardTries::new(store, genesis.config.shard_layout.version(), num_shards);
+        let runtime_config_store = RuntimeConfigStore::for_chain_id(&genesis.config.chain_id);
+        let runtime_config = runtime_config_store.get_config(genesis.config.protocol_version);
+        let is_protocol_account = |record: &StateRecord| {
+            matches!(record, StateRecord::Account { account_id, .. }
+                if account_id == &genesis.config.protocol_treasury_account)
+        };
+
+        if genesis.has_sharded_records() {
+            // Each shard has its own records file, so shards are independent and their state
+            // roots can be computed in parallel.
+            let results: Vec<(StateRoot, bool)> = (0..num_shards)
+                .into_par_iter()
+                .map(|shard_id| {
+                    let mut shard_account_ids = HashSet::new();
+                    let mut has_protocol_account = false;
+                    genesis.for_each_shard_record(shard_id, |record: &StateRecord| {
+                        shard_account_ids.insert(state_record_to_account_id(record).clone());
+                        has_protocol_account |= is_protocol_account(record);
+                    });
+                    let state_root = Self::genesis_shard_state_root(
+                        tries.clone(),
+                        shard_id,
+                        &shard_layout,
+                        genesis,
+                        runtime_config,
+                        shard_account_ids,
+                    );
+                    (state_root, has_protocol_account)
+                })
+                .collect();
+            assert!(
+                results.iter().any(|(_, has_protocol_account)| *has_protocol_account),
+                "Genesis spec doesn't have protocol treasury account"
+            );
+            return results.into_iter().map(|(state_root, _)| state_root).collect();
+        }
+
         let mut shard_account_ids: Vec<HashSet<AccountId>> =
             (0..num_shards).map(|_| HashSet::new()).collect();
         let mut has_protocol_account = false;
         genesis.for_each_record(|record: &StateRecord| {
             shard_account_ids[state_record_to_shard_id(record, &shard_layout) as usize]
                 .insert(state_record_to_account_id(record).clone());
-            if let StateRecord::Account { account_id, .. } = record {
-                if account_id == &genesis.config.protocol_treasury_account {
-                    has_protocol_account = true;
-                }
-            }
+            has_protocol_account |= is_protocol_account(record);
         });
         assert!(has_protocol_account, "Genesis spec doesn't have protocol treasury account");
-        let tries = ShardTries::new(store, genesis.config.shard_layout.version(), num_shards);
-        let runtime = Runtime::new();
-        let runtime_config_store =
-            NightshadeRuntime::create_runtime_config_store(&genesis.config.chain_id);
-        let runtime_config = runtime_config_store.get_config(genesis.config.protocol_version);
+        shard_account_ids
+            .into_iter()
+            .enumerate()
+            .map(|(shard_id, shard_account_ids)| {
+                Self::genesis_shard_state_root(
+                    tries.clone(),
+                    shard_id as ShardId,
+                    &shard_layout,
+                    genesis,
+                    runtime_config,
+                    shard_account_ids,
+                )
+            })
+            .collect()
+    }
 
-        for shard_id in 0..num_shards {
-            let validators = genesis
-                .config
-                .validators
-                .iter()
-                .filter_map(|account_info| {
-                    if account_id_to_shard_id(&account_info.account_id, &shard_layout) == shard_id {
-                        Some((
-                            account_info.account_id.clone(),
-                            account_info.public_key.clone(),
-                            account_info.amount,
-                        ))
-                    } else {
-                        None
-                    }
-                })
-                .collect::<Vec<_>>();
+    fn genesis_shard_state_root(
+        tries: ShardTries,
+        shard_id: ShardId,
+        shard_layout: &ShardLayout,
+        genesis: &Genesis,
+        runtime_config: &RuntimeConfig,
+        shard_account_ids: HashSet<AccountId>,
+    ) -> StateRoot {
+        let validators = genesis
+            .config
+            .validators
+            .iter()
+            .filter_map(|account_info| {
+                if account_id_to_shard_id(&account_info.account_id, shard_layout) == shard_id {
+                    Some((
+                        account_info.account_id.clone(),
+                        account_info.public_key.clone(),
+                        account_info.amount,
+                    ))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
 
-            state_roots.push(runtime.apply_genesis_state(
-                tries.clone(),
-                shard_id,
-                &validators,
-                genesis,
-                runtime_config,
-                shard_account_ids[shard_id as usize].clone(),
-            ));
-        }
-        state_roots
+        Runtime::new().apply_genesis_state(
+            tries,
+            shard_id,
+            &validators,
+            genesis,
+            runtime_config,
+            shard_account_ids,
+        )
     }
 
     /// On first start: compute state roots, load genesis state into storage.
@@ -466,6 +624,13 @@ impl NightshadeRuntime {
                         account_id_to_shard_id(account_id, &shard_layout) == shard_id
                     })
                     .collect();
+                let protocol_treasury_account_ids = epoch_manager
+                    .get_protocol_treasury_accounts(prev_block_hash)?
+                    .into_iter()
+                    .filter(|account_id| {
+                        account_id_to_shard_id(account_id, &shard_layout) == shard_id
+                    })
+                    .collect();
                 let last_proposals = last_validator_proposals
                     .filter(|v| account_id_to_shard_id(v.account_id(), &shard_layout) == shard_id)
                     .fold(HashMap::new(), |mut acc, v| {
@@ -485,12 +650,7 @@ impl NightshadeRuntime {
                     stake_info,
                     validator_rewards,
                     last_proposals,
-                    protocol_treasury_account_id: Some(
-                        self.genesis_config.protocol_treasury_account.clone(),
-                    )
-                    .filter(|account_id| {
-                        account_id_to_shard_id(account_id, &shard_layout) == shard_id
-                    }),
+                    protocol_treasury_account_ids,
                     slashing_info,
                 })
             } else if !challenges_result.is_empty() {
@@ -498,7 +658,7 @@ impl NightshadeRuntime {
                     stake_info: Default::default(),
                     validator_rewards: Default::default(),
                     last_proposals: Default::default(),
-                    protocol_treasury_account_id: None,
+                    protocol_treasury_account_ids: vec![],
                     slashing_info,
                 })
             } else {
@@ -523,9 +683,12 @@ impl NightshadeRuntime {
             block_hash: *block_hash,
             epoch_id,
             epoch_height,
+            shard_id,
             gas_price,
             block_timestamp,
             gas_limit: Some(gas_limit),
+            // The compute limit of a chunk is its gas limit.
+            compute_limit: Some(gas_limit),
             random_seed,
             current_protocol_version,
             config: self.runtime_config_store.get_config(current_protocol_version).clone(),
@@ -563,6 +726,7 @@ impl NightshadeRuntime {
                 // TODO(#2152): process gracefully
                 RuntimeError::ReceiptValidationError(e) => panic!("{}", e),
                 RuntimeError::ValidatorError(e) => e.into(),
+                RuntimeError::InvalidStatePatch(e) => Error::from(ErrorKind::Other(e)),
             })?;
         let elapsed = instant.elapsed();
 
@@ -576,6 +740,15 @@ impl NightshadeRuntime {
                 .with_label_values(&[])
                 .observe(elapsed.as_secs_f64() * 1e15 / total_gas_burnt as f64);
         }
+        let mut outcomes = apply_result.outcomes.iter();
+        self.contract_execution.record(
+            &apply_state.epoch_id,
+            shard_id,
+            apply_result.receipt_execution_times.iter().filter_map(|(id, time)| {
+                let outcome = outcomes.find(|outcome| outcome.id == *id)?;
+                Some((&outcome.outcome.executor_id, *time, outcome.outcome.gas_burnt))
+            }),
+        );
         let total_balance_burnt = apply_result
             .stats
             .tx_burnt_amount
@@ -587,14 +760,26 @@ impl NightshadeRuntime {
 
         let shard_uid = self.get_shard_uid_from_prev_hash(shard_id, prev_block_hash)?;
 
+        let receipt_queue_changes = self.save_receipt_queue_changes.then(|| {
+            ReceiptQueueChanges::from_state_changes(
+                &apply_result.state_changes,
+                &apply_result.processed_delayed_receipts,
+            )
+        });
+        let mut trie_changes = WrappedTrieChanges::new(
+            self.get_tries(),
+            shard_uid,
+            apply_result.trie_changes,
+            apply_result.state_changes,
+            *block_hash,
+        )
+        .with_applied_chunk_info(apply_state.applied_chunk_info());
+        if let Some(receipt_queue_changes) = receipt_queue_changes {
+            trie_changes = trie_changes.with_receipt_queue_changes(receipt_queue_changes);
+        }
+
         let result = ApplyTransactionResult {
-            trie_changes: WrappedTrieChanges::new(
-                self.get_tries(),
-                shard_uid,
-                apply_result.trie_changes,
-                apply_result.state_changes,
-                *block_hash,
-            ),
+            trie_changes,
             new_root: apply_result.state_root,
             outcomes: apply_result.outcomes,
             outgoing_receipts: apply_result.outgoing_receipts,
@@ -688,6 +873,10 @@ impl RuntimeAdapter for NightshadeRuntime {
         self.tries.clone()
     }
 
+    fn contract_execution(&self) -> Option<&ContractExecutionTracker> {
+        Some(&self.contract_execution)
+    }
+
     fn get_trie_for_shard(&self, shard_id: ShardId, prev_hash: &CryptoHash) -> Result<Trie, Error> {
         let shard_uid = self.get_shard_uid_from_prev_hash(shard_id, prev_hash)?;
         Ok(self.tries.get_trie_for_shard(shard_uid))
@@ -1505,14 +1694,18 @@ impl RuntimeAdapter for NightshadeRuntime {
                         method_name,
                         args.as_ref(),
                         &mut logs,
-                        &self.epoch_manager,
+                        &self.view_epoch_info_provider(),
                         current_protocol_version,
                     )
                     .map_err(|err| near_chain::near_chain_primitives::error::QueryError::from_call_function_error(err, block_height, *block_hash))?;
                 Ok(QueryResponse {
                     kind: QueryResponseKind::CallResult(CallResult {
-                        result: call_function_result,
+                        result: call_function_result.result,
                         logs,
+                        gas_burnt: Some(call_function_result.gas_burnt),
+                        recommended_attached_gas: Some(
+                            call_function_result.recommended_attached_gas,
+                        ),
                     }),
                     block_height,
                     block_hash: *block_hash,
@@ -1584,6 +1777,47 @@ impl RuntimeAdapter for NightshadeRuntime {
         epoch_manager.get_validator_info(epoch_id).map_err(|e| e.into())
     }
 
+    fn get_validator_production_stats(
+        &self,
+        last_block_hash: &CryptoHash,
+        account_id: &AccountId,
+    ) -> Result<ValidatorProductionStatsView, Error> {
+        let mut epoch_manager = self.epoch_manager.as_ref().write().expect(POISONED_LOCK_ERR);
+        epoch_manager
+            .get_validator_production_stats(last_block_hash, account_id)
+            .map_err(|e| e.into())
+    }
+
+    fn get_unstake_schedule(
+        &self,
+        last_block_hash: &CryptoHash,
+        account_id: &AccountId,
+    ) -> Result<UnstakeScheduleView, Error> {
+        let mut epoch_manager = self.epoch_manager.as_ref().write().expect(POISONED_LOCK_ERR);
+        epoch_manager.get_unstake_schedule(last_block_hash, account_id).map_err(|e| e.into())
+    }
+
+    fn get_protocol_upgrade_status(
+        &self,
+        last_block_hash: &CryptoHash,
+    ) -> Result<ProtocolUpgradeStatusView, Error> {
+        let mut epoch_manager = self.epoch_manager.as_ref().write().expect(POISONED_LOCK_ERR);
+        epoch_manager.get_protocol_upgrade_status(last_block_hash).map_err(|e| e.into())
+    }
+
+    fn get_epoch_info_view(&self, epoch_id: &EpochId) -> Result<EpochInfoView, Error> {
+        let mut epoch_manager = self.epoch_manager.as_ref().write().expect(POISONED_LOCK_ERR);
+        epoch_manager.get_epoch_info_view(epoch_id).map_err(|e| e.into())
+    }
+
+    fn get_epoch_ids_from_epoch_height(
+        &self,
+        epoch_height: EpochHeight,
+    ) -> Result<Vec<EpochId>, Error> {
+        let epoch_manager = self.epoch_manager.as_ref().read().expect(POISONED_LOCK_ERR);
+        epoch_manager.get_epoch_ids_from_epoch_height(epoch_height).map_err(|e| e.into())
+    }
+
     /// Returns StorageError when storage is inconsistent.
     /// This is possible with the used isolation level + running ViewClient in a separate thread
     /// `block_hash` is a block whose `prev_state_root` is `state_root`
@@ -1889,7 +2123,10 @@ impl node_runtime::adapter::ViewRuntimeAdapter for NightshadeRuntime {
         logs: &mut Vec<String>,
         epoch_info_provider: &dyn EpochInfoProvider,
         current_protocol_version: ProtocolVersion,
-    ) -> Result<Vec<u8>, node_runtime::state_viewer::errors::CallFunctionError> {
+    ) -> Result<
+        node_runtime::state_viewer::CallFunctionResult,
+        node_runtime::state_viewer::errors::CallFunctionError,
+    > {
         let state_update = self.tries.new_trie_update_view(*shard_uid, state_root);
         let view_state = ViewApplyState {
             block_height: height,
@@ -1897,6 +2134,7 @@ impl node_runtime::adapter::ViewRuntimeAdapter for NightshadeRuntime {
             block_hash: *block_hash,
             epoch_id: epoch_id.clone(),
             epoch_height,
+            shard_id: shard_uid.shard_id as ShardId,
             block_timestamp,
             current_protocol_version,
             cache: Some(Arc::new(StoreCompiledContractCache { store: self.tries.get_store() })),
@@ -1956,13 +2194,19 @@ mod test {
     use near_logger_utils::init_test_logger;
     use near_primitives::block::Tip;
     use near_primitives::challenge::SlashedValidator;
-    use near_primitives::transaction::{Action, DeleteAccountAction, StakeAction};
+    use near_primitives::runtime::apply_state::AppliedChunkInfo;
+    use near_primitives::shard_layout::get_block_shard_uid;
+    use near_primitives::transaction::{
+        Action, DeleteAccountAction, DeployContractAction, StakeAction,
+    };
+    use near_primitives::types::chunk_extra::ChunkExtra;
     use near_primitives::types::{BlockHeightDelta, Nonce, ValidatorId, ValidatorKickoutReason};
     use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
     use near_primitives::views::{
         AccountView, CurrentEpochValidatorInfo, NextEpochValidatorInfo, ValidatorKickoutView,
     };
-    use near_store::create_store;
+    use near_chain_configs::GenesisValidationMode;
+    use near_store::{create_store, ColAppliedChunkInfo, ColChunkExtra};
 
     use crate::config::{GenesisExt, TESTING_INIT_BALANCE, TESTING_INIT_STAKE};
     use crate::get_store_path;
@@ -2440,6 +2684,135 @@ mod test {
         assert_eq!(account.locked, desired_stake);
     }
 
+    #[test]
+    fn test_delegated_stake() {
+        let validators: Vec<AccountId> = vec!["test1".parse().unwrap(), "test2".parse().unwrap()];
+        let mut env = TestEnv::new("test_delegated_stake", vec![validators.clone()], 2, false);
+        let pool = validators[0].clone();
+        let delegator: AccountId = "alice.near".parse().unwrap();
+        let signer = InMemorySigner::from_seed(pool.clone(), KeyType::ED25519, pool.as_ref());
+        let code = near_test_contracts::staking_pool_view_contract().to_vec();
+        let mut transactions = vec![SignedTransaction::from_actions(
+            1,
+            pool.clone(),
+            pool.clone(),
+            &signer,
+            vec![Action::DeployContract(DeployContractAction { code })],
+            CryptoHash::default(),
+        )];
+        for _ in 0..6 {
+            env.step_default(std::mem::take(&mut transactions));
+            // Pools are viewed in the state of the chunk extras, which the chain saves.
+            let shard_uid = env.runtime.shard_id_to_uid(0, &env.head.epoch_id).unwrap();
+            let mut store_update = env.runtime.store.store_update();
+            store_update
+                .set_ser(
+                    ColChunkExtra,
+                    &get_block_shard_uid(&env.head.last_block_hash, &shard_uid),
+                    &ChunkExtra::new_with_only_state_root(&env.state_roots[0]),
+                )
+                .unwrap();
+            store_update.commit().unwrap();
+        }
+        let epoch_id = env.head.epoch_id.clone();
+        assert_ne!(epoch_id, EpochId::default());
+        let delegated_stake =
+            |env: &TestEnv, epoch_id: &EpochId, pool: &AccountId| -> Option<Balance> {
+                env.runtime.view_epoch_info_provider().delegated_stake(epoch_id, pool, &delegator)
+            };
+        let allowed_code_hashes = vec![hash(near_test_contracts::staking_pool_view_contract())];
+
+        // Disabled unless configured.
+        assert_eq!(delegated_stake(&env, &epoch_id, &pool), None);
+
+        // No pool is allowed by default.
+        env.runtime.set_delegated_stake_view(Some(DelegatedStakeViewConfig::default()));
+        assert_eq!(delegated_stake(&env, &epoch_id, &pool), None);
+
+        env.runtime.set_delegated_stake_view(Some(DelegatedStakeViewConfig {
+            allowed_code_hashes: vec![CryptoHash::default()],
+            ..Default::default()
+        }));
+        assert_eq!(delegated_stake(&env, &epoch_id, &pool), None);
+
+        env.runtime.set_delegated_stake_view(Some(DelegatedStakeViewConfig {
+            allowed_code_hashes: allowed_code_hashes.clone(),
+            ..Default::default()
+        }));
+        assert_eq!(delegated_stake(&env, &epoch_id, &pool), Some(1000));
+        // Cached for the epoch.
+        assert_eq!(delegated_stake(&env, &epoch_id, &pool), Some(1000));
+        // test2 has no contract.
+        assert_eq!(delegated_stake(&env, &epoch_id, &validators[1]), None);
+        // No validators are selected for the first epochs.
+        assert_eq!(delegated_stake(&env, &EpochId::default(), &pool), None);
+        // Chunks are applied with a provider which doesn't depend on the node config.
+        assert_eq!(env.runtime.epoch_manager.delegated_stake(&epoch_id, &pool, &delegator), None);
+
+        env.runtime.set_delegated_stake_view(Some(DelegatedStakeViewConfig {
+            method_name: "get_account_unstaked_balance".to_string(),
+            allowed_code_hashes,
+            ..Default::default()
+        }));
+        assert_eq!(delegated_stake(&env, &epoch_id, &pool), None);
+    }
+
+    #[test]
+    fn test_applied_chunk_info() {
+        let validators: Vec<AccountId> = vec!["test1".parse().unwrap()];
+        let mut env = TestEnv::new("test_applied_chunk_info", vec![validators], 2, false);
+        env.step_default(vec![]);
+        let height = env.head.height + 1;
+        let block_hash = hash(&[height as u8]);
+        let mut result = env
+            .runtime
+            .apply_transactions(
+                0,
+                &env.state_roots[0],
+                height,
+                0,
+                &env.head.last_block_hash,
+                &block_hash,
+                &[],
+                &[],
+                ValidatorStakeIter::empty(),
+                100,
+                1000,
+                &ChallengesResult::default(),
+                hash(b"seed"),
+                true,
+                false,
+                None,
+            )
+            .unwrap();
+        let mut store_update = env.runtime.store.store_update();
+        result.trie_changes.wrapped_into(&mut store_update).unwrap();
+        store_update.commit().unwrap();
+
+        let shard_uid = env.runtime.shard_id_to_uid(0, &env.head.epoch_id).unwrap();
+        let applied_chunk_info = env
+            .runtime
+            .store
+            .get_ser::<AppliedChunkInfo>(
+                ColAppliedChunkInfo,
+                &get_block_shard_uid(&block_hash, &shard_uid),
+            )
+            .unwrap();
+        assert_eq!(
+            applied_chunk_info,
+            Some(AppliedChunkInfo {
+                block_height: height,
+                gas_price: 100,
+                gas_limit: Some(1000),
+                random_seed: hash(b"seed"),
+                protocol_version: env.runtime.genesis_config.protocol_version,
+                is_new_chunk: true,
+                is_first_block_of_version: false,
+                is_first_block_with_chunk_of_version: false,
+            })
+        );
+    }
+
     #[test]
     fn test_validator_stake_change_multiple_times() {
         init_test_logger();
@@ -3458,4 +3831,37 @@ mod test {
         assert_eq!(env.last_proposals.len(), 1);
         assert_eq!(env.last_proposals[0].stake(), 0);
     }
+
+    /// State roots computed in parallel from the sharded records format match the ones computed
+    /// from the same records in the legacy format.
+    #[test]
+    fn test_sharded_genesis_records_state_roots() {
+        let accounts = (0..2000)
+            .map(|i| AccountId::try_from(format!("test{}", i)).unwrap())
+            .collect::<Vec<_>>();
+        let mut genesis = Genesis::test_sharded(accounts.clone(), 4, vec![1, 1, 1, 1]);
+        for (i, account_id) in accounts.into_iter().enumerate().step_by(3) {
+            genesis.records.0.push(StateRecord::Data {
+                account_id,
+                data_key: format!("key{}", i).into_bytes(),
+                value: vec![i as u8; i % 100],
+            });
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let legacy_roots = NightshadeRuntime::genesis_state_from_records(
+            create_store(&dir.path().join("legacy")),
+            &genesis,
+        );
+
+        let genesis_path = dir.path().join("genesis.json");
+        genesis.write_sharded(&genesis_path).unwrap();
+        let sharded_genesis = Genesis::from_file(&genesis_path, GenesisValidationMode::Full);
+        assert!(sharded_genesis.has_sharded_records());
+        assert_eq!(sharded_genesis.config.records_shard_files.len(), 4);
+        let sharded_roots = NightshadeRuntime::genesis_state_from_records(
+            create_store(&dir.path().join("sharded")),
+            &sharded_genesis,
+        );
+        assert_eq!(legacy_roots, sharded_roots);
+    }
 }
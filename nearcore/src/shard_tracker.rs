@@ -79,6 +79,35 @@ impl ShardTracker {
         self.tracks_shard_at_epoch(shard_id, &epoch_id)
     }
 
+    /// Whether a shard that `shard_id` becomes in the epoch after the one of the block after
+    /// `prev_hash` is tracked.  With a shard layout change tracked accounts may end up in a shard
+    /// with a different id, so the split shards are checked in the next layout.
+    fn tracks_shard_next_epoch(
+        &self,
+        shard_id: ShardId,
+        prev_hash: &CryptoHash,
+    ) -> Result<bool, EpochError> {
+        let (next_epoch_id, next_shard_ids) = {
+            let mut epoch_manager = self.epoch_manager.write().expect(POISONED_LOCK_ERR);
+            let next_epoch_id = epoch_manager.get_next_epoch_id_from_prev_block(prev_hash)?;
+            let next_shard_ids = if epoch_manager.will_shard_layout_change(prev_hash)? {
+                epoch_manager
+                    .get_shard_layout(&next_epoch_id)?
+                    .get_split_shard_ids(shard_id)
+                    .expect("all shard layouts expect the first one must have a split map")
+            } else {
+                vec![shard_id]
+            };
+            (next_epoch_id, next_shard_ids)
+        };
+        for next_shard_id in next_shard_ids {
+            if self.tracks_shard_at_epoch(next_shard_id, &next_epoch_id)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
     pub fn care_about_shard(
         &self,
         account_id: Option<&AccountId>,
@@ -129,7 +158,7 @@ impl ShardTracker {
             }
         }
         matches!(self.tracked_config, TrackedConfig::AllShards)
-            || self.tracks_shard(shard_id, parent_hash).unwrap_or(false)
+            || self.tracks_shard_next_epoch(shard_id, parent_hash).unwrap_or(false)
     }
 }
 
@@ -143,6 +172,7 @@ mod tests {
     use near_primitives::epoch_manager::block_info::BlockInfo;
     use near_primitives::epoch_manager::{AllEpochConfig, EpochConfig, ShardConfig};
     use near_primitives::hash::CryptoHash;
+    use near_primitives::runtime::config_store::RuntimeConfigStore;
     use near_primitives::types::validator_stake::ValidatorStake;
     use near_primitives::types::{BlockHeight, EpochId, NumShards, ProtocolVersion, ShardId};
     use near_store::test_utils::create_test_store;
@@ -173,6 +203,7 @@ mod tests {
             avg_hidden_validator_seats_per_shard: vec![],
             block_producer_kickout_threshold: 90,
             chunk_producer_kickout_threshold: 60,
+            validator_min_remaining_stake_perc: 0,
             fishermen_threshold: 0,
             online_max_threshold: Rational::from_integer(1),
             online_min_threshold: Rational::new(90, 100),
@@ -188,6 +219,7 @@ mod tests {
             epoch_length: 1,
             protocol_reward_rate: Rational::from_integer(0),
             protocol_treasury_account: "test".parse().unwrap(),
+            runtime_config_store: Arc::new(RuntimeConfigStore::test()),
             online_max_threshold: initial_epoch_config.online_max_threshold,
             online_min_threshold: initial_epoch_config.online_min_threshold,
             num_seconds_per_year: 1000000,
@@ -303,8 +335,9 @@ mod tests {
         );
     }
 
-    #[test]
-    fn test_track_shards_shard_layout_change() {
+    /// Epoch manager switching from a single shard to four shards, where "near" goes to shard 2
+    /// and "zoo" to shard 3, and hashes of eight blocks recorded in it.
+    fn shard_layout_change_epoch_manager() -> (Arc<RwLock<EpochManager>>, Vec<CryptoHash>) {
         let simple_nightshade_version = SimpleNightshade.protocol_version();
         let shard_layout = ShardLayout::v1(
             vec!["aurora".parse().unwrap()],
@@ -322,11 +355,6 @@ mod tests {
             1,
             Some(shard_config),
         )));
-        let tracked_accounts = vec!["near".parse().unwrap(), "zoo".parse().unwrap()];
-        let tracker = ShardTracker::new(
-            TrackedConfig::Accounts(tracked_accounts.clone()),
-            epoch_manager.clone(),
-        );
 
         let h = hash_range(8);
         {
@@ -358,6 +386,17 @@ mod tests {
                 simple_nightshade_version
             );
         }
+        (epoch_manager, h)
+    }
+
+    #[test]
+    fn test_track_shards_shard_layout_change() {
+        let (epoch_manager, h) = shard_layout_change_epoch_manager();
+        let tracked_accounts = vec!["near".parse().unwrap(), "zoo".parse().unwrap()];
+        let tracker = ShardTracker::new(
+            TrackedConfig::Accounts(tracked_accounts.clone()),
+            epoch_manager.clone(),
+        );
 
         // verify tracker is tracking the correct shards before and after resharding
         for i in 1..8 {
@@ -382,4 +421,31 @@ mod tests {
             );
         }
     }
+
+    /// A tracked account moves from the only shard to shard 3 when the shard layout changes.  The
+    /// node tracks shard 0 until the change, including for the next epoch since shard 3 is split
+    /// from it, and then only shard 3.
+    #[test]
+    fn test_track_account_moved_by_shard_layout_change() {
+        let (epoch_manager, h) = shard_layout_change_epoch_manager();
+        let tracker = ShardTracker::new(
+            TrackedConfig::Accounts(vec!["zoo".parse().unwrap()]),
+            epoch_manager.clone(),
+        );
+
+        let mut num_shards_seen = HashSet::new();
+        for i in 1..8 {
+            let num_shards = {
+                let mut epoch_manager = epoch_manager.write().expect(POISONED_LOCK_ERR);
+                let epoch_id = epoch_manager.get_epoch_id_from_prev_block(&h[i - 1]).unwrap();
+                epoch_manager.get_shard_layout(&epoch_id).unwrap().num_shards()
+            };
+            num_shards_seen.insert(num_shards);
+            let expected: HashSet<ShardId> =
+                if num_shards == 1 { [0].into_iter().collect() } else { [3].into_iter().collect() };
+            assert_eq!(get_all_shards_care_about(&tracker, num_shards, &h[i - 1]), expected);
+            assert_eq!(get_all_shards_will_care_about(&tracker, num_shards, &h[i - 1]), expected);
+        }
+        assert_eq!(num_shards_seen, [1, 4].into_iter().collect());
+    }
 }
@@ -0,0 +1,86 @@
+//! Creating and restoring snapshots of the node database, see [`near_store::snapshot`].
+use std::path::Path;
+
+use anyhow::Context;
+use tracing::info;
+
+use near_store::db::RocksDB;
+use near_store::snapshot::{validate_snapshot, SnapshotInfo, SNAPSHOT_DB_DIR};
+
+use crate::{get_store_path, store_path_exists, NearConfig};
+
+/// Asks the node running with `near_config` to snapshot its database into `destination`.
+///
+/// The snapshot is created by the node itself via the `/debug/api/make_snapshot` endpoint, so
+/// RPC with `enable_debug_rpc` has to be enabled on the node.
+pub fn make_snapshot(near_config: &NearConfig, destination: &Path) -> anyhow::Result<SnapshotInfo> {
+    let rpc_addr = near_config.rpc_addr().context("RPC is disabled in config.json")?;
+    let uri: hyper::Uri = format!("http://{}/debug/api/make_snapshot", rpc_addr).parse()?;
+    let body = serde_json::to_vec(&serde_json::json!({ "destination": destination }))?;
+    let request = hyper::Request::post(uri)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(hyper::Body::from(body))?;
+    let (status, body) =
+        tokio::runtime::Builder::new_current_thread().enable_all().build()?.block_on(async {
+            let response = hyper::Client::new().request(request).await?;
+            let status = response.status();
+            let body = hyper::body::to_bytes(response.into_body()).await?;
+            Ok::<_, anyhow::Error>((status, body))
+        })?;
+    match status {
+        hyper::StatusCode::OK => Ok(serde_json::from_slice(&body)?),
+        hyper::StatusCode::METHOD_NOT_ALLOWED => {
+            anyhow::bail!("enable_debug_rpc has to be set in the node's config.json")
+        }
+        status => anyhow::bail!("{}: {}", status, String::from_utf8_lossy(&body)),
+    }
+}
+
+/// Replaces the database of the stopped node in `home_dir` with the snapshot in `source`.
+///
+/// The snapshot is validated first.  Its database is moved into place and the current
+/// database, if any, is kept next to it with a `.before-restore` suffix.
+pub fn restore_snapshot(home_dir: &Path, source: &Path) -> anyhow::Result<SnapshotInfo> {
+    let info = validate_snapshot(source)
+        .with_context(|| format!("{}: invalid snapshot", source.display()))?;
+    anyhow::ensure!(
+        info.db_version <= near_primitives::version::DB_VERSION,
+        "snapshot has DB version {} which is newer than {} supported by this binary",
+        info.db_version,
+        near_primitives::version::DB_VERSION
+    );
+
+    let store_path = get_store_path(home_dir);
+    if store_path_exists(&store_path) {
+        // Opening the database for writing fails if the node is still running.
+        drop(RocksDB::new(&store_path).map_err(|err| {
+            anyhow::anyhow!(
+                "{}: can't open database, is the node running? {}",
+                store_path.display(),
+                err
+            )
+        })?);
+        let backup_path = store_path.with_extension("before-restore");
+        anyhow::ensure!(
+            !backup_path.exists(),
+            "{}: remove the backup of a previous restore first",
+            backup_path.display()
+        );
+        std::fs::rename(&store_path, &backup_path)?;
+        info!(target: "near", "Moved current database to {}", backup_path.display());
+    }
+    std::fs::rename(source.join(SNAPSHOT_DB_DIR), &store_path).with_context(|| {
+        format!(
+            "failed to move snapshot into {}, both must be on the same file system",
+            store_path.display()
+        )
+    })?;
+    info!(
+        target: "near",
+        "Restored database snapshot at head #{} {} into {}",
+        info.head_height,
+        info.head_hash,
+        store_path.display()
+    );
+    Ok(info)
+}
@@ -0,0 +1,519 @@
+//! Dumping the state of tracked shards to an external storage for state sync.
+//!
+//! At each epoch boundary the dumper generates all state parts of the state at the start of the
+//! epoch, the same parts served to peers over the network, and uploads them along with the
+//! state sync header and a [`SignedStateDumpHeader`] listing the hashes of the parts.  Syncing
+//! nodes can then fetch the state from the storage instead of from their peers.
+//!
+//! Parts are dumped one at a time with a pause between them, so that the dump doesn't slow down
+//! the node.  The progress is persisted after each part, a restarted node continues the dump
+//! where it stopped.
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Context;
+use borsh::{BorshDeserialize, BorshSerialize};
+use rusoto_core::credential::{EnvironmentProvider, ProvideAwsCredentials};
+use rusoto_core::{HttpClient, Region};
+use rusoto_s3::{GetObjectRequest, PutObjectRequest, S3Client, S3};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use tracing::{debug, error, info};
+
+use near_chain::{Chain, ChainGenesis, DoomslugThresholdMode, RuntimeAdapter};
+use near_crypto::{PublicKey, SecretKey, Signature};
+use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::state_part::PartId;
+use near_primitives::syncing::get_num_state_parts;
+use near_primitives::types::{AccountId, EpochHeight, EpochId, ShardId, StateRoot};
+use near_store::{ColBlockMisc, Store, STATE_DUMP_PROGRESS_KEY};
+
+use crate::config::{ExternalStorageLocation, StateDumpConfig};
+use crate::metrics;
+
+/// Description of the state of a shard dumped at an epoch boundary, uploaded after all of its
+/// parts.
+#[derive(Serialize, Deserialize, BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct StateDumpHeader {
+    pub chain_id: String,
+    /// Epoch starting with the dumped state.
+    pub epoch_id: EpochId,
+    pub epoch_height: EpochHeight,
+    /// First block of the epoch, the hash state sync is requested for.
+    pub sync_hash: CryptoHash,
+    pub shard_id: ShardId,
+    pub state_root: StateRoot,
+    pub num_parts: u64,
+    /// Hash of the borsh serialized `ShardStateSyncResponseHeader` uploaded next to this header.
+    pub state_header_hash: CryptoHash,
+    /// Hashes of the state parts, indexed by part id.
+    pub part_hashes: Vec<CryptoHash>,
+}
+
+/// [`StateDumpHeader`] signed with the network key of the node which made the dump.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SignedStateDumpHeader {
+    pub header: StateDumpHeader,
+    pub public_key: PublicKey,
+    /// Signature of the hash of the borsh serialized header.
+    pub signature: Signature,
+}
+
+impl SignedStateDumpHeader {
+    pub fn new(header: StateDumpHeader, secret_key: &SecretKey) -> Self {
+        let signature = secret_key.sign(hash(&header.try_to_vec().unwrap()).as_ref());
+        Self { header, public_key: secret_key.public_key(), signature }
+    }
+
+    pub fn verify(&self) -> bool {
+        let data = hash(&self.header.try_to_vec().unwrap());
+        self.signature.verify(data.as_ref(), &self.public_key)
+    }
+}
+
+/// Directory of the dump of the state of `shard_id` at the start of the epoch `sync_hash`.
+pub fn dump_dir(
+    chain_id: &str,
+    epoch_height: EpochHeight,
+    sync_hash: &CryptoHash,
+    shard_id: ShardId,
+) -> String {
+    format!("{}/epoch_{}/{}/shard_{}", chain_id, epoch_height, sync_hash, shard_id)
+}
+
+pub fn part_key(dir: &str, part_id: u64, num_parts: u64) -> String {
+    format!("{}/state_part_{:06}_of_{:06}", dir, part_id, num_parts)
+}
+
+pub fn state_header_key(dir: &str) -> String {
+    format!("{}/state_header", dir)
+}
+
+pub fn header_key(dir: &str) -> String {
+    format!("{}/header.json", dir)
+}
+
+/// Progress of the dump of a shard, persisted after each uploaded part.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+struct DumpProgress {
+    sync_hash: CryptoHash,
+    num_parts: u64,
+    /// Hashes of the parts uploaded and verified so far.
+    part_hashes: Vec<CryptoHash>,
+    /// Whether the header has been uploaded as well.
+    done: bool,
+}
+
+fn progress_key(shard_id: ShardId) -> Vec<u8> {
+    [STATE_DUMP_PROGRESS_KEY.as_ref(), &shard_id.to_le_bytes()].concat()
+}
+
+fn load_progress(store: &Store, shard_id: ShardId) -> anyhow::Result<Option<DumpProgress>> {
+    Ok(store.get_ser(ColBlockMisc, &progress_key(shard_id))?)
+}
+
+fn save_progress(store: &Store, shard_id: ShardId, progress: &DumpProgress) -> anyhow::Result<()> {
+    let mut store_update = store.store_update();
+    store_update.set_ser(ColBlockMisc, &progress_key(shard_id), progress)?;
+    Ok(store_update.commit()?)
+}
+
+/// Epoch boundary the dumper is working on.
+struct DumpEpoch {
+    epoch_id: EpochId,
+    epoch_height: EpochHeight,
+    sync_hash: CryptoHash,
+    shard_ids: Vec<ShardId>,
+}
+
+pub struct StateDumper {
+    chain: Chain,
+    runtime_adapter: Arc<dyn RuntimeAdapter>,
+    storage: ExternalStorage,
+    chain_id: String,
+    account_id: Option<AccountId>,
+    secret_key: SecretKey,
+}
+
+impl StateDumper {
+    pub fn new(
+        runtime_adapter: Arc<dyn RuntimeAdapter>,
+        chain_genesis: &ChainGenesis,
+        chain_id: String,
+        account_id: Option<AccountId>,
+        secret_key: SecretKey,
+        location: &ExternalStorageLocation,
+    ) -> anyhow::Result<Self> {
+        let chain = Chain::new_for_view_client(
+            runtime_adapter.clone(),
+            chain_genesis,
+            DoomslugThresholdMode::TwoThirds,
+        )?;
+        let storage = ExternalStorage::new(location)?;
+        Ok(Self { chain, runtime_adapter, storage, chain_id, account_id, secret_key })
+    }
+
+    /// Dumps the next part or header of the state at the start of the current epoch.  Returns
+    /// whether there was anything left to dump.
+    pub fn step(&mut self) -> anyhow::Result<bool> {
+        let epoch = match self.current_epoch()? {
+            Some(epoch) => epoch,
+            None => return Ok(false),
+        };
+        let store = self.runtime_adapter.get_store();
+        for &shard_id in &epoch.shard_ids {
+            let progress = load_progress(&store, shard_id)?
+                .filter(|progress| progress.sync_hash == epoch.sync_hash);
+            if progress.as_ref().map_or(false, |progress| progress.done) {
+                continue;
+            }
+            let label = shard_id.to_string();
+            return match self.dump_next(&store, &epoch, shard_id, progress) {
+                Ok(progress) => {
+                    metrics::STATE_DUMP_EPOCH_HEIGHT
+                        .with_label_values(&[&label])
+                        .set(epoch.epoch_height as i64);
+                    metrics::STATE_DUMP_NUM_PARTS
+                        .with_label_values(&[&label])
+                        .set(progress.num_parts as i64);
+                    metrics::STATE_DUMP_PARTS_DUMPED
+                        .with_label_values(&[&label])
+                        .set(progress.part_hashes.len() as i64);
+                    Ok(true)
+                }
+                Err(err) => {
+                    metrics::STATE_DUMP_ERRORS.with_label_values(&[&label]).inc();
+                    Err(err.context(format!("dumping state of shard {}", shard_id)))
+                }
+            };
+        }
+        Ok(false)
+    }
+
+    /// Returns the start of the epoch of the final head, unless it's the genesis epoch.
+    fn current_epoch(&mut self) -> anyhow::Result<Option<DumpEpoch>> {
+        let final_head = self.chain.final_head()?;
+        let epoch_start_height =
+            self.runtime_adapter.get_epoch_start_height(&final_head.last_block_hash)?;
+        let sync_header = self.chain.get_header_by_height(epoch_start_height)?.clone();
+        if sync_header.hash() == self.chain.genesis().hash() {
+            return Ok(None);
+        }
+        let sync_prev_header = self.chain.get_block_header(sync_header.prev_hash())?.clone();
+        let num_shards = self.runtime_adapter.num_shards(sync_prev_header.epoch_id())?;
+        let shard_ids = (0..num_shards)
+            .filter(|&shard_id| {
+                self.runtime_adapter.cares_about_shard(
+                    self.account_id.as_ref(),
+                    sync_prev_header.prev_hash(),
+                    shard_id,
+                    true,
+                )
+            })
+            .collect();
+        Ok(Some(DumpEpoch {
+            epoch_id: sync_header.epoch_id().clone(),
+            epoch_height: self
+                .runtime_adapter
+                .get_epoch_height_from_prev_block(sync_header.prev_hash())?,
+            sync_hash: *sync_header.hash(),
+            shard_ids,
+        }))
+    }
+
+    fn dump_next(
+        &mut self,
+        store: &Store,
+        epoch: &DumpEpoch,
+        shard_id: ShardId,
+        progress: Option<DumpProgress>,
+    ) -> anyhow::Result<DumpProgress> {
+        let state_header = self.chain.get_state_response_header(shard_id, epoch.sync_hash)?;
+        let state_root = state_header.chunk_prev_state_root();
+        let mut progress = match progress {
+            Some(progress) => progress,
+            None => {
+                let num_parts = get_num_state_parts(state_header.state_root_node().memory_usage);
+                info!(
+                    target: "state_dump",
+                    "Dumping {} parts of shard {} state at the start of epoch {}",
+                    num_parts, shard_id, epoch.epoch_height
+                );
+                DumpProgress {
+                    sync_hash: epoch.sync_hash,
+                    num_parts,
+                    part_hashes: vec![],
+                    done: false,
+                }
+            }
+        };
+        let dir = dump_dir(&self.chain_id, epoch.epoch_height, &epoch.sync_hash, shard_id);
+        let part_id = progress.part_hashes.len() as u64;
+        if part_id < progress.num_parts {
+            let sync_prev_hash = *self.chain.get_block_header(&epoch.sync_hash)?.prev_hash();
+            let part_id = PartId::new(part_id, progress.num_parts);
+            let part = self.runtime_adapter.obtain_state_part(
+                shard_id,
+                &sync_prev_hash,
+                &state_root,
+                part_id,
+            )?;
+            anyhow::ensure!(
+                self.runtime_adapter.validate_state_part(&state_root, part_id, &part),
+                "generated invalid state part {}",
+                part_id.idx
+            );
+            let part_hash = self.upload(&part_key(&dir, part_id.idx, part_id.total), &part)?;
+            debug!(target: "state_dump", "Dumped part {} of shard {} state", part_id.idx, shard_id);
+            progress.part_hashes.push(part_hash);
+        } else {
+            let state_header = state_header.try_to_vec()?;
+            let state_header_hash = self.upload(&state_header_key(&dir), &state_header)?;
+            let header = SignedStateDumpHeader::new(
+                StateDumpHeader {
+                    chain_id: self.chain_id.clone(),
+                    epoch_id: epoch.epoch_id.clone(),
+                    epoch_height: epoch.epoch_height,
+                    sync_hash: epoch.sync_hash,
+                    shard_id,
+                    state_root,
+                    num_parts: progress.num_parts,
+                    state_header_hash,
+                    part_hashes: progress.part_hashes.clone(),
+                },
+                &self.secret_key,
+            );
+            self.upload(&header_key(&dir), &serde_json::to_vec_pretty(&header)?)?;
+            info!(
+                target: "state_dump",
+                "Dumped shard {} state at the start of epoch {} to {}",
+                shard_id, epoch.epoch_height, dir
+            );
+            progress.done = true;
+        }
+        save_progress(store, shard_id, &progress)?;
+        Ok(progress)
+    }
+
+    /// Uploads `data` and reads it back to make sure it has been stored intact.  Returns the
+    /// hash of the data.
+    fn upload(&self, key: &str, data: &[u8]) -> anyhow::Result<CryptoHash> {
+        let data_hash = hash(data);
+        self.storage.put(key, data).with_context(|| format!("uploading {}", key))?;
+        let stored = self.storage.get(key).with_context(|| format!("downloading {}", key))?;
+        anyhow::ensure!(hash(&stored) == data_hash, "{} got corrupted on upload", key);
+        Ok(data_hash)
+    }
+}
+
+/// Starts a thread dumping the state of tracked shards as configured by `config`.
+pub fn start_state_dumper(
+    mut dumper: StateDumper,
+    config: StateDumpConfig,
+) -> std::io::Result<std::thread::JoinHandle<()>> {
+    std::thread::Builder::new().name("state_dump".to_string()).spawn(move || loop {
+        let pause = match dumper.step() {
+            Ok(true) => config.min_part_interval,
+            Ok(false) => config.poll_interval,
+            Err(err) => {
+                error!(target: "state_dump", "Failed to dump state: {:#}", err);
+                config.poll_interval
+            }
+        };
+        std::thread::sleep(pause);
+    })
+}
+
+/// Storage the state dumps are uploaded to.
+pub enum ExternalStorage {
+    Filesystem(PathBuf),
+    S3(S3Bucket),
+}
+
+impl ExternalStorage {
+    pub fn new(location: &ExternalStorageLocation) -> anyhow::Result<Self> {
+        Ok(match location {
+            ExternalStorageLocation::Filesystem { root_dir } => Self::Filesystem(root_dir.clone()),
+            ExternalStorageLocation::S3 { bucket, region, endpoint } => {
+                Self::S3(S3Bucket::new(bucket, region, endpoint.as_deref())?)
+            }
+        })
+    }
+
+    pub fn put(&self, key: &str, data: &[u8]) -> anyhow::Result<()> {
+        match self {
+            Self::Filesystem(root_dir) => {
+                let path = root_dir.join(key);
+                std::fs::create_dir_all(path.parent().unwrap())?;
+                // Readers never see a partially written file.
+                let tmp_path = path.with_extension("tmp");
+                std::fs::write(&tmp_path, data)?;
+                Ok(std::fs::rename(&tmp_path, &path)?)
+            }
+            Self::S3(bucket) => bucket.put(key, data),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Filesystem(root_dir) => Ok(std::fs::read(root_dir.join(key))?),
+            Self::S3(bucket) => bucket.get(key),
+        }
+    }
+}
+
+/// S3 bucket, or a bucket of an S3 compatible storage.
+pub struct S3Bucket {
+    bucket: String,
+    client: S3Client,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl S3Bucket {
+    pub fn new(bucket: &str, region: &str, endpoint: Option<&str>) -> anyhow::Result<Self> {
+        let region = s3_region(region, endpoint)?;
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        // The connector has to be created within the runtime it's used with.
+        let client = runtime.block_on(async {
+            let credentials = EnvironmentProvider::default();
+            // Fail on startup rather than on the first upload if the credentials aren't set.
+            credentials.credentials().await?;
+            Ok::<_, anyhow::Error>(S3Client::new_with(HttpClient::new()?, credentials, region))
+        })?;
+        Ok(Self { bucket: bucket.to_string(), client, runtime })
+    }
+
+    pub fn put(&self, key: &str, data: &[u8]) -> anyhow::Result<()> {
+        let request = PutObjectRequest {
+            bucket: self.bucket.clone(),
+            key: key.to_string(),
+            body: Some(data.to_vec().into()),
+            ..Default::default()
+        };
+        self.runtime.block_on(self.client.put_object(request))?;
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> anyhow::Result<Vec<u8>> {
+        let request = GetObjectRequest {
+            bucket: self.bucket.clone(),
+            key: key.to_string(),
+            ..Default::default()
+        };
+        self.runtime.block_on(async {
+            let output = self.client.get_object(request).await?;
+            let mut data = vec![];
+            output
+                .body
+                .context("S3 object without a body")?
+                .into_async_read()
+                .read_to_end(&mut data)
+                .await?;
+            Ok(data)
+        })
+    }
+}
+
+/// Returns the AWS region, or the region of an S3 compatible storage at `endpoint`.
+fn s3_region(region: &str, endpoint: Option<&str>) -> anyhow::Result<Region> {
+    Ok(match endpoint {
+        Some(endpoint) => Region::Custom {
+            name: region.to_string(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+        },
+        None => region.parse().with_context(|| format!("unknown AWS region {}", region))?,
+    })
+}
+
+/// Reads the dump of a shard back from `root_dir`, checking the signature of the header and the
+/// hashes of all the parts.  Returns the header and the parts.
+pub fn read_filesystem_dump(
+    root_dir: &Path,
+    dir: &str,
+) -> anyhow::Result<(SignedStateDumpHeader, Vec<Vec<u8>>)> {
+    let storage = ExternalStorage::Filesystem(root_dir.to_path_buf());
+    let header: SignedStateDumpHeader = serde_json::from_slice(&storage.get(&header_key(dir))?)?;
+    anyhow::ensure!(header.verify(), "invalid signature of the state dump header");
+    let num_parts = header.header.num_parts;
+    anyhow::ensure!(
+        header.header.part_hashes.len() as u64 == num_parts,
+        "header lists {} part hashes for {} parts",
+        header.header.part_hashes.len(),
+        num_parts
+    );
+    let parts = (0..num_parts)
+        .map(|part_id| storage.get(&part_key(dir, part_id, num_parts)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    for (part_id, (part, part_hash)) in parts.iter().zip(&header.header.part_hashes).enumerate() {
+        anyhow::ensure!(hash(part) == *part_hash, "state part {} has a wrong hash", part_id);
+    }
+    Ok((header, parts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_s3_region() {
+        assert_eq!(s3_region("eu-central-1", None).unwrap(), Region::EuCentral1);
+        assert!(s3_region("moon-1", None).is_err());
+        assert_eq!(
+            s3_region("local", Some("http://localhost:9000/")).unwrap(),
+            Region::Custom {
+                name: "local".to_string(),
+                endpoint: "http://localhost:9000".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_signed_header() {
+        let secret_key = SecretKey::from_seed(near_crypto::KeyType::ED25519, "node");
+        let mut header = SignedStateDumpHeader::new(
+            StateDumpHeader {
+                chain_id: "test".to_string(),
+                epoch_id: EpochId::default(),
+                epoch_height: 1,
+                sync_hash: hash(b"sync"),
+                shard_id: 0,
+                state_root: hash(b"root"),
+                num_parts: 1,
+                state_header_hash: hash(b"header"),
+                part_hashes: vec![hash(b"part")],
+            },
+            &secret_key,
+        );
+        let json = serde_json::to_string(&header).unwrap();
+        assert_eq!(serde_json::from_str::<SignedStateDumpHeader>(&json).unwrap(), header);
+        assert!(header.verify());
+        header.header.part_hashes[0] = hash(b"other part");
+        assert!(!header.verify());
+    }
+
+    #[test]
+    fn test_filesystem_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = ExternalStorage::Filesystem(dir.path().to_path_buf());
+        let key = part_key("test/epoch_1/hash/shard_0", 0, 3);
+        storage.put(&key, b"part").unwrap();
+        storage.put(&key, b"new part").unwrap();
+        assert_eq!(storage.get(&key).unwrap(), b"new part");
+        assert!(storage.get(&part_key("test/epoch_1/hash/shard_0", 1, 3)).is_err());
+    }
+
+    #[test]
+    fn test_progress() {
+        let store = near_store::test_utils::create_test_store();
+        assert_eq!(load_progress(&store, 1).unwrap(), None);
+        let progress = DumpProgress {
+            sync_hash: hash(b"sync"),
+            num_parts: 3,
+            part_hashes: vec![hash(b"part")],
+            done: false,
+        };
+        save_progress(&store, 1, &progress).unwrap();
+        assert_eq!(load_progress(&store, 0).unwrap(), None);
+        assert_eq!(load_progress(&store, 1).unwrap(), Some(progress));
+    }
+}
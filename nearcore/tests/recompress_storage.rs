@@ -0,0 +1,64 @@
+/// Test recompression of the database of a stopped node.
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use near_primitives::version::DB_VERSION;
+use near_store::db::{DBCol, RocksDB, VERSION_KEY};
+use near_store::migrations::set_store_version;
+use near_store::{decode_value_with_rc, Store};
+use nearcore::{ensure_recompress_complete, recompress_storage, RECOMPRESS_PROGRESS_FILE};
+
+fn open_store(path: &Path) -> Store {
+    Store::new(Arc::new(RocksDB::new(path).unwrap()))
+}
+
+#[test]
+fn test_recompress_storage() {
+    let home_dir = tempfile::tempdir().unwrap();
+    let destination = home_dir.path().join("recompressed");
+    {
+        let src = open_store(&home_dir.path().join("data"));
+        set_store_version(&src, DB_VERSION);
+        let mut store_update = src.store_update();
+        store_update.set(DBCol::ColBlockMisc, b"key", b"value");
+        store_update.update_refcount(DBCol::ColState, b"live", b"node", 2);
+        store_update.update_refcount(DBCol::ColState, b"dead", b"node", 1);
+        store_update.set(DBCol::_ColTransactionRefCount, b"key", b"value");
+        store_update.commit().unwrap();
+        let mut store_update = src.store_update();
+        store_update.update_refcount(DBCol::ColState, b"dead", b"node", -1);
+        store_update.commit().unwrap();
+    }
+
+    // An interrupted run leaves an incomplete progress file which the node refuses.
+    fs::create_dir_all(&destination).unwrap();
+    fs::write(
+        destination.join(RECOMPRESS_PROGRESS_FILE),
+        r#"{"columns": [["ColDbVersion", 1]], "complete": false}"#,
+    )
+    .unwrap();
+    assert!(ensure_recompress_complete(&destination).is_err());
+
+    recompress_storage(home_dir.path(), &destination).unwrap();
+    ensure_recompress_complete(&destination).unwrap();
+    let progress: serde_json::Value =
+        serde_json::from_slice(&fs::read(destination.join(RECOMPRESS_PROGRESS_FILE)).unwrap())
+            .unwrap();
+    assert_eq!(progress["complete"], true);
+    let columns = progress["columns"].as_array().unwrap();
+    assert!(columns.iter().all(|column| column[0] != "_ColTransactionRefCount"));
+    assert!(columns.iter().any(|column| column[0] == "ColState" && column[1] == 1));
+
+    let dst = open_store(&destination);
+    assert_eq!(dst.get(DBCol::ColBlockMisc, b"key").unwrap(), Some(b"value".to_vec()));
+    assert_eq!(dst.get(DBCol::ColState, b"live").unwrap(), Some(b"node".to_vec()));
+    assert_eq!(dst.get(DBCol::ColState, b"dead").unwrap(), None);
+    // Refcounted values are copied with their refcount.
+    let state: Vec<_> = dst.iter_without_rc_logic(DBCol::ColState).collect();
+    assert_eq!(state.len(), 1);
+    assert_eq!(decode_value_with_rc(&state[0].1).1, 2);
+    assert_eq!(dst.get(DBCol::_ColTransactionRefCount, b"key").unwrap(), None);
+    // The column marked as done by the interrupted run wasn't copied again.
+    assert_eq!(dst.get(DBCol::ColDbVersion, VERSION_KEY).unwrap(), None);
+}
@@ -1,7 +1,12 @@
 use clap::{Args, Parser};
 use futures::future::FutureExt;
-use near_chain_configs::GenesisValidationMode;
-use near_o11y::{default_subscriber, EnvFilterBuilder};
+use near_amend_genesis::AmendGenesisCommand;
+use near_chain_configs::{Genesis, GenesisValidationMode};
+use near_o11y::tracing_subscriber::EnvFilter;
+use near_o11y::{
+    default_subscriber, default_subscriber_with_opentelemetry, DefaultSubcriberGuard,
+    EnvFilterBuilder, NodeResource,
+};
 use near_primitives::types::{Gas, NumSeats, NumShards};
 use near_state_viewer::StateViewerSubCommand;
 use near_store::db::RocksDB;
@@ -27,15 +32,23 @@ impl NeardCmd {
     pub(super) fn parse_and_run() {
         let neard_cmd = Self::parse();
         let verbose = neard_cmd.opts.verbose.as_deref();
-        let env_filter = EnvFilterBuilder::from_env().verbose(verbose).finish();
-        // Sandbox node can log to sandbox logging target via sandbox_debug_log host function.
-        // This is hidden by default so we enable it for sandbox node.
-        let env_filter = if cfg!(feature = "sandbox") {
-            env_filter.add_directive("sandbox=debug".parse().unwrap())
-        } else {
-            env_filter
+        let make_env_filter = || {
+            let env_filter = EnvFilterBuilder::from_env().verbose(verbose).finish();
+            // Sandbox node can log to sandbox logging target via sandbox_debug_log host function.
+            // This is hidden by default so we enable it for sandbox node.
+            if cfg!(feature = "sandbox") {
+                env_filter.add_directive("sandbox=debug".parse().unwrap())
+            } else {
+                env_filter
+            }
+        };
+        let subscriber = default_subscriber(make_env_filter());
+        // `neard run` replaces this subscriber with one which may also export the spans to
+        // OpenTelemetry once it has read the config.  Until then, only this thread logs.
+        let subscriber = match neard_cmd.subcmd {
+            NeardSubCommand::Run(_) => subscriber.local(),
+            _ => subscriber.global(),
         };
-        let _subscriber = default_subscriber(env_filter).global();
 
         info!(
             target: "neard",
@@ -74,7 +87,9 @@ impl NeardCmd {
                 );
                 cmd.run(&home_dir);
             }
-            NeardSubCommand::Run(cmd) => cmd.run(&home_dir, genesis_validation),
+            NeardSubCommand::Run(cmd) => {
+                cmd.run(&home_dir, genesis_validation, make_env_filter(), subscriber)
+            }
 
             // TODO(mina86): Remove the command in Q3 2022.
             NeardSubCommand::UnsafeResetData => {
@@ -93,6 +108,31 @@ impl NeardCmd {
             NeardSubCommand::RecompressStorage(cmd) => {
                 cmd.run(&home_dir);
             }
+
+            NeardSubCommand::ShardGenesis(cmd) => {
+                cmd.run(genesis_validation);
+            }
+
+            NeardSubCommand::Database(cmd) => {
+                if let Err(err) = cmd.run(&home_dir, genesis_validation) {
+                    error!("{:#}", err);
+                    std::process::exit(1);
+                }
+            }
+
+            NeardSubCommand::ColdStore(cmd) => {
+                if let Err(err) = cmd.run(&home_dir, genesis_validation) {
+                    error!("{:#}", err);
+                    std::process::exit(1);
+                }
+            }
+
+            NeardSubCommand::AmendGenesis(cmd) => {
+                if let Err(err) = cmd.run() {
+                    error!("{:#}", err);
+                    std::process::exit(1);
+                }
+            }
         }
     }
 }
@@ -174,10 +214,31 @@ pub(super) enum NeardSubCommand {
     /// than the original but to be safe one should provision around the same
     /// space as the size of the current `data` directory.
     ///
+    /// Every column is verified against the source once copied and an
+    /// interrupted run resumes after the last column it finished.  The node
+    /// refuses to use the output directory until the copy is complete.
+    ///
     /// Finally, because this command is meant only as a temporary migration
     /// tool, it is planned to be removed by the end of 2022.
     #[clap(name = "recompress_storage")]
     RecompressStorage(RecompressStorageSubCommand),
+    /// Amends a genesis or state dump records so that they can be used to start a new network:
+    /// replaces the validators, adds extra accounts, changes chain id, protocol version or epoch
+    /// length and optionally rescales balances to keep the total supply.  Records are processed
+    /// in a streaming fashion, so multi-GB records files are fine.
+    #[clap(name = "amend-genesis")]
+    AmendGenesis(AmendGenesisCommand),
+    /// Converts genesis records to the sharded records format, with one records file per shard,
+    /// whose state roots can be computed in parallel.
+    #[clap(name = "shard-genesis")]
+    ShardGenesis(ShardGenesisCmd),
+    /// Maintenance operations on the database of a node.
+    #[clap(subcommand, name = "database")]
+    Database(DatabaseCommand),
+    /// Operations on the cold database of an archival node, which keeps the history deleted
+    /// from the hot database.
+    #[clap(subcommand, name = "cold-store")]
+    ColdStore(ColdStoreCommand),
 }
 
 #[derive(Parser)]
@@ -332,7 +393,13 @@ pub(super) struct RunCmd {
 }
 
 impl RunCmd {
-    pub(super) fn run(self, home_dir: &Path, genesis_validation: GenesisValidationMode) {
+    pub(super) fn run<S>(
+        self,
+        home_dir: &Path,
+        genesis_validation: GenesisValidationMode,
+        env_filter: EnvFilter,
+        local_subscriber: DefaultSubcriberGuard<S>,
+    ) {
         // Load configs from home.
         let mut near_config = nearcore::config::load_config(home_dir, genesis_validation);
 
@@ -395,8 +462,41 @@ impl RunCmd {
             }
         }
 
+        let node = NodeResource {
+            chain_id: near_config.client_config.chain_id.clone(),
+            account_id: near_config
+                .validator_signer
+                .as_ref()
+                .map(|signer| signer.validator_id().to_string()),
+            node_version: crate::NEARD_VERSION.to_string(),
+        };
+        let opentelemetry = near_config.config.opentelemetry.clone();
+
         let (tx, rx) = oneshot::channel::<()>();
         let sys = actix::System::new();
+        // The OTLP exporter has to be created within the runtime.
+        let opentelemetry_config = opentelemetry.as_ref();
+        let subscriber = match sys.block_on(async move {
+            default_subscriber_with_opentelemetry(env_filter, opentelemetry_config, &node)
+        }) {
+            Ok(subscriber) => subscriber,
+            Err(err) => {
+                error!(target: "neard", "{}", err);
+                std::process::exit(1);
+            }
+        };
+        drop(local_subscriber);
+        let _subscriber = subscriber.global();
+        if let Some(opentelemetry) = &opentelemetry {
+            info!(
+                target: "neard",
+                "Exporting the {} spans to {} with a sampling ratio of {}",
+                opentelemetry.filter,
+                opentelemetry.endpoint,
+                opentelemetry.sampling_ratio
+            );
+        }
+
         sys.block_on(async move {
             let nearcore::NearNode { rpc_servers, .. } =
                 nearcore::start_with_config_and_synchronization(home_dir, near_config, Some(tx))
@@ -422,6 +522,10 @@ impl RunCmd {
                 debug!(target: "neard", "{} server stopped", name);
             }))
             .await;
+            if opentelemetry.is_some() {
+                // The exporter runs in this runtime, so it has to be flushed from another thread.
+                let _ = tokio::task::spawn_blocking(near_o11y::shutdown_opentelemetry).await;
+            }
             actix::System::current().stop();
         });
         sys.run().unwrap();
@@ -475,6 +579,308 @@ impl RecompressStorageSubCommand {
     }
 }
 
+#[derive(Args)]
+#[clap(arg_required_else_help = true)]
+pub(super) struct ShardGenesisCmd {
+    /// Genesis file to convert.
+    #[clap(long)]
+    genesis: PathBuf,
+    /// Records file, if records are not in the genesis file.
+    #[clap(long)]
+    records: Option<PathBuf>,
+    /// Where to write the genesis config. Records files are written to the same directory.
+    #[clap(long)]
+    output: PathBuf,
+}
+
+impl ShardGenesisCmd {
+    pub(super) fn run(self, genesis_validation: GenesisValidationMode) {
+        let genesis = match &self.records {
+            Some(records) => Genesis::from_files(&self.genesis, records, genesis_validation),
+            None => Genesis::from_file(&self.genesis, genesis_validation),
+        };
+        if let Err(err) = genesis.write_sharded(&self.output) {
+            error!("Failed to write sharded genesis to {}: {}", self.output.display(), err);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[derive(Parser)]
+pub(super) enum DatabaseCommand {
+    /// Deletes execution outcomes of blocks older than the given number of epochs to reclaim
+    /// disk space.  The node must be stopped.  Going forward the
+    /// node keeps pruning on its own if `store.prune_outcomes_older_than_epochs` is set in
+    /// config.json.
+    #[clap(name = "prune-history")]
+    PruneHistory(PruneHistoryCmd),
+    /// Makes the running node create a consistent snapshot of its database.  The snapshot hard
+    /// links the database files, so it takes little time and space, but the destination has to
+    /// be on the same file system as the database.  Requires `rpc.enable_debug_rpc` to be set.
+    #[clap(name = "make-snapshot")]
+    MakeSnapshot(MakeSnapshotCmd),
+    /// Replaces the database of a stopped node with a snapshot created by `make-snapshot`.  The
+    /// current database is kept in `data.before-restore`.
+    #[clap(name = "restore-snapshot")]
+    RestoreSnapshot(RestoreSnapshotCmd),
+    /// Prints the number of keys and the sizes of every column, estimated by RocksDB or counted
+    /// with `--iterate`, and how the size of the State column compares with the live state.
+    #[clap(name = "stats")]
+    Stats(StatsCmd),
+    /// Checks the structure of the database: the head and tail pointers, that blocks link to
+    /// existing previous headers and chunks and that the state at the head can be traversed.
+    /// Tells whether the problems found only affect the history or require a resync, and exits
+    /// with a nonzero code if there are any.
+    #[clap(name = "validate")]
+    Validate(ValidateCmd),
+    /// Same as `neard recompress_storage`: rewrites the database of a stopped node into a new
+    /// directory with the current column compression and options, which otherwise only apply to
+    /// newly written data, and without obsolete columns.  An interrupted run resumes from the
+    /// last column it finished.  The node refuses to use the new directory until it's complete.
+    #[clap(name = "recompress")]
+    Recompress(RecompressCmd),
+}
+
+impl DatabaseCommand {
+    pub(super) fn run(
+        self,
+        home_dir: &Path,
+        genesis_validation: GenesisValidationMode,
+    ) -> anyhow::Result<()> {
+        match self {
+            DatabaseCommand::PruneHistory(cmd) => cmd.run(home_dir, genesis_validation),
+            DatabaseCommand::MakeSnapshot(cmd) => cmd.run(home_dir, genesis_validation),
+            DatabaseCommand::RestoreSnapshot(cmd) => cmd.run(home_dir),
+            DatabaseCommand::Stats(cmd) => cmd.run(home_dir, genesis_validation),
+            DatabaseCommand::Validate(cmd) => cmd.run(home_dir, genesis_validation),
+            DatabaseCommand::Recompress(cmd) => cmd.run(home_dir),
+        }
+    }
+}
+
+#[derive(Args)]
+pub(super) struct PruneHistoryCmd {
+    /// Number of most recent epochs whose outcomes are kept.  Defaults to
+    /// `store.prune_outcomes_older_than_epochs` from config.json.
+    #[clap(long)]
+    keep_epochs: Option<u64>,
+}
+
+impl PruneHistoryCmd {
+    pub(super) fn run(
+        self,
+        home_dir: &Path,
+        genesis_validation: GenesisValidationMode,
+    ) -> anyhow::Result<()> {
+        let near_config = nearcore::config::load_config(home_dir, genesis_validation);
+        let epochs = self
+            .keep_epochs
+            .or(near_config.config.store.prune_outcomes_older_than_epochs)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "--keep-epochs not given and store.prune_outcomes_older_than_epochs not set"
+                )
+            })?;
+        nearcore::prune_history(home_dir, &near_config, epochs)
+    }
+}
+
+#[derive(Args)]
+#[clap(arg_required_else_help = true)]
+pub(super) struct MakeSnapshotCmd {
+    /// Directory to create the snapshot in.  Must not exist yet.
+    #[clap(long)]
+    destination: PathBuf,
+}
+
+impl MakeSnapshotCmd {
+    pub(super) fn run(
+        self,
+        home_dir: &Path,
+        genesis_validation: GenesisValidationMode,
+    ) -> anyhow::Result<()> {
+        let near_config = nearcore::config::load_config(home_dir, genesis_validation);
+        // The snapshot is created by the node, which may run in a different working directory.
+        let destination = std::env::current_dir()?.join(&self.destination);
+        let info = nearcore::snapshot::make_snapshot(&near_config, &destination)?;
+        info!(
+            target: "neard",
+            "Created snapshot in {} at head #{} {}: {} bytes in {} ms",
+            destination.display(),
+            info.head_height,
+            info.head_hash,
+            info.size_bytes,
+            info.duration_ms
+        );
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+#[clap(arg_required_else_help = true)]
+pub(super) struct RestoreSnapshotCmd {
+    /// Snapshot directory created by `make-snapshot`.
+    #[clap(long)]
+    source: PathBuf,
+}
+
+impl RestoreSnapshotCmd {
+    pub(super) fn run(self, home_dir: &Path) -> anyhow::Result<()> {
+        nearcore::snapshot::restore_snapshot(home_dir, &self.source)?;
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub(super) struct StatsCmd {
+    /// Iterate every column to count its keys and bytes exactly instead of using the estimates
+    /// of RocksDB.  Takes hours on large databases.
+    #[clap(long)]
+    iterate: bool,
+    /// Break the live state down by `TrieKey` variant: accounts, contract code, contract data
+    /// and so on.
+    #[clap(long)]
+    detailed_trie: bool,
+    /// Print the statistics as JSON.
+    #[clap(long)]
+    json: bool,
+}
+
+impl StatsCmd {
+    pub(super) fn run(
+        self,
+        home_dir: &Path,
+        genesis_validation: GenesisValidationMode,
+    ) -> anyhow::Result<()> {
+        let near_config = nearcore::config::load_config(home_dir, genesis_validation);
+        let stats = nearcore::database_stats::database_stats(
+            home_dir,
+            &near_config,
+            self.iterate,
+            self.detailed_trie,
+        )?;
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&stats)?);
+        } else {
+            print!("{}", stats);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub(super) struct ValidateCmd {
+    /// Check only the pointers, the most recent blocks and random paths of the state.  This is
+    /// the default.
+    #[clap(long, conflicts_with = "full")]
+    fast: bool,
+    /// Check every header, the blocks down to the tail, the whole state at the head and the
+    /// refcounts.  Takes hours on large databases.
+    #[clap(long)]
+    full: bool,
+    /// Print the report as JSON.
+    #[clap(long)]
+    json: bool,
+}
+
+impl ValidateCmd {
+    pub(super) fn run(
+        self,
+        home_dir: &Path,
+        genesis_validation: GenesisValidationMode,
+    ) -> anyhow::Result<()> {
+        let near_config = nearcore::config::load_config(home_dir, genesis_validation);
+        let mode = if self.full {
+            nearcore::database_validate::ValidationMode::Full
+        } else {
+            nearcore::database_validate::ValidationMode::Fast
+        };
+        let report = nearcore::database_validate::validate_database(home_dir, &near_config, mode)?;
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print!("{}", report);
+        }
+        anyhow::ensure!(report.num_problems == 0, "{} problems found", report.num_problems);
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+#[clap(arg_required_else_help = true)]
+pub(super) struct RecompressCmd {
+    /// Directory to write the new database to.  Must not exist yet, unless it's the destination
+    /// of an interrupted run which is resumed.  Replace the `data` directory with it once done.
+    #[clap(long)]
+    destination: PathBuf,
+}
+
+impl RecompressCmd {
+    pub(super) fn run(self, home_dir: &Path) -> anyhow::Result<()> {
+        nearcore::recompress_storage(home_dir, &self.destination)
+    }
+}
+
+#[derive(Parser)]
+pub(super) enum ColdStoreCommand {
+    /// Copies the history of an archival node into the cold database in resumable batches,
+    /// verifies a sample of the copied keys and then sets `store.cold_store` in config.json.
+    /// The node may keep running, the database is only read.
+    #[clap(name = "migrate")]
+    Migrate(ColdStoreMigrateCmd),
+}
+
+impl ColdStoreCommand {
+    pub(super) fn run(
+        self,
+        home_dir: &Path,
+        genesis_validation: GenesisValidationMode,
+    ) -> anyhow::Result<()> {
+        match self {
+            ColdStoreCommand::Migrate(cmd) => cmd.run(home_dir, genesis_validation),
+        }
+    }
+}
+
+#[derive(Args)]
+pub(super) struct ColdStoreMigrateCmd {
+    /// Directory of the cold database, relative to the home directory unless absolute.
+    #[clap(long, default_value = "cold-data")]
+    cold_path: PathBuf,
+    /// Size in bytes of the keys and values written into the cold database at once.
+    #[clap(long, default_value = "67108864")]
+    batch_size: usize,
+    /// Once the copy is done, every this many keys of the cold database one is read back from
+    /// both databases and compared, including the keys copied by interrupted runs.
+    #[clap(long, default_value = "1000")]
+    verify_every: u64,
+}
+
+impl ColdStoreMigrateCmd {
+    pub(super) fn run(
+        self,
+        home_dir: &Path,
+        genesis_validation: GenesisValidationMode,
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(self.verify_every > 0, "--verify-every must be positive");
+        let near_config = nearcore::config::load_config(home_dir, genesis_validation);
+        let cold_store = nearcore::cold_storage::migrate_to_cold_store(
+            home_dir,
+            &near_config,
+            &self.cold_path,
+            self.batch_size,
+            self.verify_every,
+        )?;
+        info!(
+            target: "neard",
+            "Migrated the history up to #{} into {}",
+            cold_store.migrated_height,
+            cold_store.path.display()
+        );
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,61 @@
+#!/usr/bin/env python3
+# Patch access keys of accounts in a sandbox node and sign a transaction with
+# a patched key.
+
+import sys, time
+import pathlib
+
+sys.path.append(str(pathlib.Path(__file__).resolve().parents[2] / 'lib'))
+
+import utils
+from cluster import start_cluster
+from key import Key
+from transaction import sign_payment_tx
+
+CONFIG = utils.figure_out_sandbox_binary()
+
+# start node
+nodes = start_cluster(1, 0, 1, CONFIG, [["epoch_length", 10]], {})
+
+# patch a new full access key into test0
+key = Key.implicit_account()
+key.account_id = "test0"
+storage_usage = nodes[0].get_account("test0")['result']['storage_usage']
+res = nodes[0].json_rpc(
+    'sandbox_patch_access_keys', {
+        "access_keys": [{
+            "account_id": "test0",
+            "public_key": key.pk,
+            "access_key": {
+                "nonce": 0,
+                "permission": "FullAccess"
+            }
+        }]
+    })
+assert 'error' not in res, res
+
+# the key is stored and accounted for in the storage usage
+keys = nodes[0].get_access_key_list("test0")['result']['keys']
+assert key.pk in [k['public_key'] for k in keys], keys
+assert nodes[0].get_account(
+    "test0")['result']['storage_usage'] > storage_usage
+
+# the patched key can sign transactions
+hash_ = nodes[0].get_latest_block().hash_bytes
+tx = sign_payment_tx(key, 'test0', 100, 1, hash_)
+res = nodes[0].send_tx_and_wait(tx, 20)
+assert 'SuccessValue' in res['result']['status'], res
+
+# keys of accounts that don't exist are rejected
+res = nodes[0].json_rpc(
+    'sandbox_patch_access_keys', {
+        "access_keys": [{
+            "account_id": "missing.test0",
+            "public_key": key.pk,
+            "access_key": {
+                "nonce": 0,
+                "permission": "FullAccess"
+            }
+        }]
+    })
+assert res['error']['cause']['name'] == 'ACCOUNT_DOES_NOT_EXIST', res
@@ -74,6 +74,14 @@ fn smoke_test() {
     assert!(!trivial_contract().is_empty());
     assert!(!fuzzing_contract().is_empty());
     assert!(!rs_contract_base_protocol().is_empty());
+    assert!(!data_dependency_contract().is_empty());
+    assert!(!gas_price_contract().is_empty());
+    assert!(!epoch_and_shard_contract().is_empty());
+    assert!(!storage_config_contract().is_empty());
+    assert!(!promise_gas_burnt_contract().is_empty());
+    assert!(!input_read_contract().is_empty());
+    assert!(!gas_budget_contract().is_empty());
+    assert!(!receipt_depth_contract().is_empty());
 }
 
 pub fn many_functions_contract(function_count: u32) -> Vec<u8> {
@@ -98,3 +106,352 @@ pub fn many_functions_contract(function_count: u32) -> Vec<u8> {
     );
     wat::parse_str(code).unwrap()
 }
+
+/// Contract making a promise depend on a data id it generated.
+///
+/// `generate` returns a new data id, `create` takes a data id as its input and creates a
+/// `callback` call to `alice.near` waiting for data with this id, `resume` takes a data id
+/// followed by the data as its input and sends the data to the call waiting for it, `callback`
+/// writes the data it received to the `result` key.
+pub fn data_dependency_contract() -> &'static [u8] {
+    static CONTRACT: OnceCell<Vec<u8>> = OnceCell::new();
+    CONTRACT
+        .get_or_init(|| {
+            wat::parse_str(
+                r#"
+(module
+  (import "env" "input" (func $input (param i64)))
+  (import "env" "read_register" (func $read_register (param i64 i64)))
+  (import "env" "register_len" (func $register_len (param i64) (result i64)))
+  (import "env" "value_return" (func $value_return (param i64 i64)))
+  (import "env" "storage_write"
+    (func $storage_write (param i64 i64 i64 i64 i64) (result i64)))
+  (import "env" "promise_result" (func $promise_result (param i64 i64) (result i64)))
+  (import "env" "generate_data_id" (func $generate_data_id (param i64)))
+  (import "env" "promise_create_with_data_dependency"
+    (func $promise_create_with_data_dependency
+      (param i64 i64 i64 i64 i64 i64 i64 i64 i64) (result i64)))
+  (import "env" "promise_resume_data" (func $promise_resume_data (param i64 i64 i64)))
+  (memory 1)
+  (data (i32.const 0) "result")
+  (data (i32.const 16) "alice.near")
+  (data (i32.const 32) "callback")
+  ;; The zero deposit is at 48, the data id at 64, the sent data at 96 and the received data
+  ;; at 128.
+  (func (export "generate")
+    (call $generate_data_id (i64.const 0))
+    (call $read_register (i64.const 0) (i64.const 64))
+    (call $value_return (i64.const 32) (i64.const 64)))
+  (func (export "create")
+    (call $input (i64.const 0))
+    (call $read_register (i64.const 0) (i64.const 64))
+    (drop (call $promise_create_with_data_dependency
+      (i64.const 64)
+      (i64.const 10) (i64.const 16)
+      (i64.const 8) (i64.const 32)
+      (i64.const 0) (i64.const 0)
+      (i64.const 48)
+      (i64.const 10000000000000))))
+  (func (export "resume")
+    (call $input (i64.const 0))
+    (call $read_register (i64.const 0) (i64.const 64))
+    (call $promise_resume_data
+      (i64.const 64)
+      (i64.sub (call $register_len (i64.const 0)) (i64.const 32))
+      (i64.const 96)))
+  (func (export "callback")
+    (drop (call $promise_result (i64.const 0) (i64.const 0)))
+    (call $read_register (i64.const 0) (i64.const 128))
+    (drop (call $storage_write
+      (i64.const 6) (i64.const 0)
+      (call $register_len (i64.const 0)) (i64.const 128)
+      (i64.const 1)))))
+"#,
+            )
+            .unwrap()
+        })
+        .as_slice()
+}
+
+/// Contract whose `gas_prices` method returns the gas price of the receipt followed by the gas
+/// price of the block, both as little-endian u128.
+pub fn gas_price_contract() -> &'static [u8] {
+    static CONTRACT: OnceCell<Vec<u8>> = OnceCell::new();
+    CONTRACT
+        .get_or_init(|| {
+            wat::parse_str(
+                r#"
+(module
+  (import "env" "read_register" (func $read_register (param i64 i64)))
+  (import "env" "value_return" (func $value_return (param i64 i64)))
+  (import "env" "gas_price" (func $gas_price (param i64)))
+  (import "env" "block_gas_price" (func $block_gas_price (param i64)))
+  (memory 1)
+  (func (export "gas_prices")
+    (call $gas_price (i64.const 0))
+    (call $read_register (i64.const 0) (i64.const 0))
+    (call $block_gas_price (i64.const 0))
+    (call $read_register (i64.const 0) (i64.const 16))
+    (call $value_return (i64.const 32) (i64.const 0))))
+"#,
+            )
+            .unwrap()
+        })
+        .as_slice()
+}
+
+/// Contract whose `epoch_and_shard` method returns the 32-byte id of the current epoch followed
+/// by the id of the current shard as little-endian u64.
+pub fn epoch_and_shard_contract() -> &'static [u8] {
+    static CONTRACT: OnceCell<Vec<u8>> = OnceCell::new();
+    CONTRACT
+        .get_or_init(|| {
+            wat::parse_str(
+                r#"
+(module
+  (import "env" "read_register" (func $read_register (param i64 i64)))
+  (import "env" "value_return" (func $value_return (param i64 i64)))
+  (import "env" "epoch_id" (func $epoch_id (param i64)))
+  (import "env" "shard_id" (func $shard_id (result i64)))
+  (memory 1)
+  (func (export "epoch_and_shard")
+    (call $epoch_id (i64.const 0))
+    (call $read_register (i64.const 0) (i64.const 0))
+    (i64.store (i32.const 32) (call $shard_id))
+    (call $value_return (i64.const 40) (i64.const 0))))
+"#,
+            )
+            .unwrap()
+        })
+        .as_slice()
+}
+
+/// Contract whose `storage_config` method returns the storage cost per byte followed by the
+/// minimum balance of the account, both as little-endian u128.
+pub fn storage_config_contract() -> &'static [u8] {
+    static CONTRACT: OnceCell<Vec<u8>> = OnceCell::new();
+    CONTRACT
+        .get_or_init(|| {
+            wat::parse_str(
+                r#"
+(module
+  (import "env" "read_register" (func $read_register (param i64 i64)))
+  (import "env" "value_return" (func $value_return (param i64 i64)))
+  (import "env" "storage_byte_cost" (func $storage_byte_cost (param i64)))
+  (import "env" "min_account_balance" (func $min_account_balance (param i64)))
+  (memory 1)
+  (func (export "storage_config")
+    (call $storage_byte_cost (i64.const 0))
+    (call $read_register (i64.const 0) (i64.const 0))
+    (call $min_account_balance (i64.const 0))
+    (call $read_register (i64.const 0) (i64.const 16))
+    (call $value_return (i64.const 32) (i64.const 0))))
+"#,
+            )
+            .unwrap()
+        })
+        .as_slice()
+}
+
+/// Contract whose `call_succeed` and `call_fail` methods call `succeed` or `fail` on the account
+/// given as input and return the result of the `report` callback: the `promise_result` status
+/// of the call followed by `promise_result_gas_burnt`, both as little-endian u64.
+pub fn promise_gas_burnt_contract() -> &'static [u8] {
+    static CONTRACT: OnceCell<Vec<u8>> = OnceCell::new();
+    CONTRACT
+        .get_or_init(|| {
+            wat::parse_str(
+                r#"
+(module
+  (import "env" "input" (func $input (param i64)))
+  (import "env" "register_len" (func $register_len (param i64) (result i64)))
+  (import "env" "read_register" (func $read_register (param i64 i64)))
+  (import "env" "current_account_id" (func $current_account_id (param i64)))
+  (import "env" "promise_create"
+    (func $promise_create (param i64 i64 i64 i64 i64 i64 i64 i64) (result i64)))
+  (import "env" "promise_then"
+    (func $promise_then (param i64 i64 i64 i64 i64 i64 i64 i64 i64) (result i64)))
+  (import "env" "promise_return" (func $promise_return (param i64)))
+  (import "env" "promise_result" (func $promise_result (param i64 i64) (result i64)))
+  (import "env" "promise_result_gas_burnt" (func $promise_result_gas_burnt (param i64) (result i64)))
+  (import "env" "value_return" (func $value_return (param i64 i64)))
+  (memory 1)
+  (data (i32.const 0) "succeed")
+  (data (i32.const 8) "fail")
+  (data (i32.const 16) "report")
+  (func (export "succeed"))
+  (func (export "fail") unreachable)
+  ;; The callee account id is read to 64, the current one to 256, 512 holds a zero deposit.
+  (func $call (param $method_len i64) (param $method_ptr i64)
+    (local $callee_len i64)
+    (local $current_len i64)
+    (call $input (i64.const 0))
+    (local.set $callee_len (call $register_len (i64.const 0)))
+    (call $read_register (i64.const 0) (i64.const 64))
+    (call $current_account_id (i64.const 0))
+    (local.set $current_len (call $register_len (i64.const 0)))
+    (call $read_register (i64.const 0) (i64.const 256))
+    (call $promise_return
+      (call $promise_then
+        (call $promise_create
+          (local.get $callee_len) (i64.const 64)
+          (local.get $method_len) (local.get $method_ptr)
+          (i64.const 0) (i64.const 0)
+          (i64.const 512) (i64.const 20000000000000))
+        (local.get $current_len) (i64.const 256)
+        (i64.const 6) (i64.const 16)
+        (i64.const 0) (i64.const 0)
+        (i64.const 512) (i64.const 20000000000000))))
+  (func (export "call_succeed") (call $call (i64.const 7) (i64.const 0)))
+  (func (export "call_fail") (call $call (i64.const 4) (i64.const 8)))
+  (func (export "report")
+    (i64.store (i32.const 600) (call $promise_result (i64.const 0) (i64.const 0)))
+    (i64.store (i32.const 608) (call $promise_result_gas_burnt (i64.const 0)))
+    (call $value_return (i64.const 16) (i64.const 600))))
+"#,
+            )
+            .unwrap()
+        })
+        .as_slice()
+}
+
+/// Contract which copies its whole input into memory, either through a register with `input`
+/// and `read_register` in `read_via_register` or directly with `input_read` in `read_direct`,
+/// and returns the last 8 bytes of the copy.
+pub fn input_read_contract() -> &'static [u8] {
+    static CONTRACT: OnceCell<Vec<u8>> = OnceCell::new();
+    CONTRACT
+        .get_or_init(|| {
+            wat::parse_str(
+                r#"
+(module
+  (import "env" "input" (func $input (param i64)))
+  (import "env" "register_len" (func $register_len (param i64) (result i64)))
+  (import "env" "read_register" (func $read_register (param i64 i64)))
+  (import "env" "input_len" (func $input_len (result i64)))
+  (import "env" "input_read" (func $input_read (param i64 i64 i64)))
+  (import "env" "value_return" (func $value_return (param i64 i64)))
+  (memory 1)
+  (func (export "read_via_register")
+    (local $len i64)
+    (call $input (i64.const 0))
+    (local.set $len (call $register_len (i64.const 0)))
+    (call $read_register (i64.const 0) (i64.const 0))
+    (call $value_return (i64.sub (local.get $len) (i64.const 8)) (i64.const 8)))
+  (func (export "read_direct")
+    (local $len i64)
+    (local.set $len (call $input_len))
+    (call $input_read (i64.const 0) (local.get $len) (i64.const 0))
+    (call $value_return (i64.sub (local.get $len) (i64.const 8)) (i64.const 8)))
+  (func (export "read_past_input")
+    (call $input_read (i64.const 0) (i64.add (call $input_len) (i64.const 1)) (i64.const 0)))
+  (func (export "read_past_memory")
+    (call $input_read (i64.const 0) (call $input_len) (i64.const 4294967296))))
+"#,
+            )
+            .unwrap()
+        })
+        .as_slice()
+}
+
+/// Contract with the view method of a staking pool: `get_account_staked_balance` returns the
+/// balance 1000 as a JSON string, whatever the account.
+pub fn staking_pool_view_contract() -> &'static [u8] {
+    static CONTRACT: OnceCell<Vec<u8>> = OnceCell::new();
+    CONTRACT
+        .get_or_init(|| {
+            wat::parse_str(
+                r#"
+(module
+  (import "env" "value_return" (func $value_return (param i64 i64)))
+  (memory 1)
+  (data (i32.const 0) "\"1000\"")
+  (func (export "get_account_staked_balance")
+    (call $value_return (i64.const 6) (i64.const 0))))
+"#,
+            )
+            .unwrap()
+        })
+        .as_slice()
+}
+
+/// Contract whose `schedule_callback` method calls its own `callback` method with all the gas
+/// left once the fees of the call are estimated with `action_fee_estimate`.
+pub fn gas_budget_contract() -> &'static [u8] {
+    static CONTRACT: OnceCell<Vec<u8>> = OnceCell::new();
+    CONTRACT
+        .get_or_init(|| {
+            wat::parse_str(
+                r#"
+(module
+  (import "env" "current_account_id" (func $current_account_id (param i64)))
+  (import "env" "promise_batch_create" (func $promise_batch_create (param i64 i64) (result i64)))
+  (import "env" "promise_batch_action_function_call"
+    (func $promise_batch_action_function_call (param i64 i64 i64 i64 i64 i64 i64)))
+  (import "env" "remaining_gas" (func $remaining_gas (result i64)))
+  (import "env" "action_fee_estimate" (func $action_fee_estimate (param i64 i64) (result i64)))
+  (memory 1)
+  (data (i32.const 0) "callback")
+  (func (export "schedule_callback")
+    (local $fee i64)
+    (local $promise i64)
+    ;; A function call with the 8 bytes method name and no arguments.
+    (local.set $fee (call $action_fee_estimate (i64.const 2) (i64.const 8)))
+    (call $current_account_id (i64.const 0))
+    (local.set $promise (call $promise_batch_create (i64.const -1) (i64.const 0)))
+    (call $promise_batch_action_function_call
+      (local.get $promise)
+      (i64.const 8) (i64.const 0)
+      (i64.const 0) (i64.const 0)
+      (i64.const 16)
+      (i64.sub (call $remaining_gas) (local.get $fee))))
+  (func (export "callback")))
+"#,
+            )
+            .unwrap()
+        })
+        .as_slice()
+}
+
+/// Contract calling itself until its receipts get too deep.
+///
+/// `recurse` returns the depth of its receipt as a little-endian `u64` and calls `recurse` on the
+/// current account with all its remaining gas but 10 Tgas.
+pub fn receipt_depth_contract() -> &'static [u8] {
+    static CONTRACT: OnceCell<Vec<u8>> = OnceCell::new();
+    CONTRACT
+        .get_or_init(|| {
+            wat::parse_str(
+                r#"
+(module
+  (import "env" "current_account_id" (func $current_account_id (param i64)))
+  (import "env" "promise_batch_create" (func $promise_batch_create (param i64 i64) (result i64)))
+  (import "env" "promise_batch_action_function_call"
+    (func $promise_batch_action_function_call (param i64 i64 i64 i64 i64 i64 i64)))
+  (import "env" "prepaid_gas" (func $prepaid_gas (result i64)))
+  (import "env" "used_gas" (func $used_gas (result i64)))
+  (import "env" "receipt_depth" (func $receipt_depth (result i64)))
+  (import "env" "value_return" (func $value_return (param i64 i64)))
+  (memory 1)
+  (data (i32.const 0) "recurse")
+  ;; The zero deposit is at 16 and the returned depth at 32.
+  (func (export "recurse")
+    (local $promise i64)
+    (i64.store (i32.const 32) (call $receipt_depth))
+    (call $value_return (i64.const 8) (i64.const 32))
+    (call $current_account_id (i64.const 0))
+    (local.set $promise (call $promise_batch_create (i64.const -1) (i64.const 0)))
+    (call $promise_batch_action_function_call
+      (local.get $promise)
+      (i64.const 7) (i64.const 0)
+      (i64.const 0) (i64.const 0)
+      (i64.const 16)
+      (i64.sub
+        (i64.sub (call $prepaid_gas) (call $used_gas))
+        (i64.const 10000000000000)))))
+"#,
+            )
+            .unwrap()
+        })
+        .as_slice()
+}
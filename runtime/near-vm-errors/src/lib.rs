@@ -225,6 +225,33 @@ pub enum HostError {
     /// Serialization error for alt_bn128 functions
     #[cfg(feature = "protocol_feature_alt_bn128")]
     AltBn128SerializationError { msg: String },
+    /// The data id wasn't generated by the current account or a receipt already depends on it
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    InvalidDataId,
+    /// More keys have the prefix than `storage_key_count` may count
+    #[cfg(feature = "protocol_feature_storage_key_count")]
+    StorageKeyCountExceeded { limit: u64 },
+    /// `locked_balance_of` can't read accounts from other shards
+    #[cfg(feature = "protocol_feature_locked_balance_of")]
+    AccountOnOtherShard { account_id: String },
+    /// `locked_balance_of` was called for an account which doesn't exist
+    #[cfg(feature = "protocol_feature_locked_balance_of")]
+    AccountDoesNotExist { account_id: String },
+    /// A promise action made the receipt larger than `max_receipt_size`
+    #[cfg(feature = "protocol_feature_receipt_size_limit")]
+    ReceiptSizeExceeded { size: u64, limit: u64 },
+    /// The gas left isn't enough to pay for the data receipts of the returned value
+    #[cfg(feature = "protocol_feature_large_data_receipt_cost")]
+    ReturnedValueGasExceeded { length: u64, cost: u64, remaining: u64 },
+    /// The action index doesn't refer to a function call action of the promise
+    #[cfg(feature = "protocol_feature_promise_action_min_gas")]
+    InvalidFunctionCallActionIndex { action_index: u64 },
+    /// `action_fee_estimate` was called with an unknown action kind
+    #[cfg(feature = "protocol_feature_gas_budget_host_functions")]
+    InvalidActionKind { action_kind: u64 },
+    /// A promise would create a receipt deeper than `max_receipt_depth`
+    #[cfg(feature = "protocol_feature_receipt_depth")]
+    ReceiptDepthExceeded { depth: u64, limit: u64 },
 }
 
 #[derive(Debug, PartialEq)]
@@ -426,6 +453,24 @@ impl std::fmt::Display for HostError {
             #[cfg(feature = "protocol_feature_alt_bn128")]
             AltBn128SerializationError { msg } => write!(f, "AltBn128 serialization error: {}", msg),
             ECRecoverError { msg } => write!(f, "ECDSA recover error: {}", msg),
+            #[cfg(feature = "protocol_feature_promise_data_dependency")]
+            InvalidDataId => write!(f, "The data id was not generated by the current account or is already used by another receipt"),
+            #[cfg(feature = "protocol_feature_storage_key_count")]
+            StorageKeyCountExceeded { limit } => write!(f, "More keys have the prefix than the limit {} of keys storage_key_count may count", limit),
+            #[cfg(feature = "protocol_feature_locked_balance_of")]
+            AccountOnOtherShard { account_id } => write!(f, "Account {} is not on the shard of the current account", account_id),
+            #[cfg(feature = "protocol_feature_locked_balance_of")]
+            AccountDoesNotExist { account_id } => write!(f, "Account {} does not exist", account_id),
+            #[cfg(feature = "protocol_feature_receipt_size_limit")]
+            ReceiptSizeExceeded { size, limit } => write!(f, "The size of a receipt {} exceeds the limit {}", size, limit),
+            #[cfg(feature = "protocol_feature_large_data_receipt_cost")]
+            ReturnedValueGasExceeded { length, cost, remaining } => write!(f, "Sending the returned value of {} bytes costs {} gas but only {} gas is left", length, cost, remaining),
+            #[cfg(feature = "protocol_feature_promise_action_min_gas")]
+            InvalidFunctionCallActionIndex { action_index } => write!(f, "Action index {} doesn't refer to a function call action of the promise", action_index),
+            #[cfg(feature = "protocol_feature_gas_budget_host_functions")]
+            InvalidActionKind { action_kind } => write!(f, "Action kind {} is unknown", action_kind),
+            #[cfg(feature = "protocol_feature_receipt_depth")]
+            ReceiptDepthExceeded { depth, limit } => write!(f, "The depth of a receipt {} exceeds the limit {}", depth, limit),
         }
     }
 }
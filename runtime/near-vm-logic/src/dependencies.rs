@@ -1,7 +1,8 @@
 //! External dependencies of the near-vm-logic.
 
 use crate::types::{PublicKey, ReceiptIndex};
-use near_primitives_core::types::{AccountId, Balance, Gas};
+use near_primitives_core::hash::CryptoHash;
+use near_primitives_core::types::{AccountId, Balance, Gas, ShardId};
 #[cfg(feature = "protocol_feature_function_call_weight")]
 use near_primitives_core::types::{GasDistribution, GasWeight};
 use near_vm_errors::VMLogicError;
@@ -164,6 +165,94 @@ pub trait External {
     /// ```
     fn storage_has_key(&mut self, key: &[u8]) -> Result<bool>;
 
+    /// Returns the length of the value stored under `key` in the storage trie associated with
+    /// the current account, without reading the value itself.
+    ///
+    /// Returns `Ok(None)` if the key is not present.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - a key to get the length of the value for
+    ///
+    /// # Errors
+    ///
+    /// This function could return [`VMError::RuntimeError`].
+    ///
+    /// # Example
+    /// ```
+    /// # use near_vm_logic::mocks::mock_external::MockedExternal;
+    /// # use near_vm_logic::External;
+    ///
+    /// # let mut external = MockedExternal::new();
+    /// external.storage_set(b"key42", b"value1337").unwrap();
+    /// assert_eq!(external.storage_value_len(b"key42"), Ok(Some(9)));
+    /// // Returns None if there was no value
+    /// assert_eq!(external.storage_value_len(b"no_value_key"), Ok(None));
+    /// ```
+    #[cfg(feature = "protocol_feature_storage_value_len")]
+    fn storage_value_len(&mut self, key: &[u8]) -> Result<Option<u32>>;
+
+    /// Sets the `value` for the `key` in the storage trie associated with the current account
+    /// if the current value is `expected`, where `None` expects the key to be absent.
+    ///
+    /// Returns `Ok(true)` if the value was set, `Ok(false)` if the current value didn't match.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the key to compare and set the value for
+    /// * `expected` - the expected current value or `None` if the key is expected to be absent
+    /// * `value` - the value to set
+    ///
+    /// # Errors
+    ///
+    /// This function could return [`VMError::RuntimeError`].
+    ///
+    /// # Example
+    /// ```
+    /// # use near_vm_logic::mocks::mock_external::MockedExternal;
+    /// # use near_vm_logic::External;
+    ///
+    /// # let mut external = MockedExternal::new();
+    /// assert_eq!(external.storage_compare_and_swap(b"key42", None, b"value1"), Ok(true));
+    /// assert_eq!(external.storage_compare_and_swap(b"key42", None, b"value2"), Ok(false));
+    /// assert_eq!(external.storage_compare_and_swap(b"key42", Some(b"value1"), b"value2"), Ok(true));
+    /// ```
+    #[cfg(feature = "protocol_feature_storage_compare_and_swap")]
+    fn storage_compare_and_swap(
+        &mut self,
+        key: &[u8],
+        expected: Option<&[u8]>,
+        value: &[u8],
+    ) -> Result<bool>;
+
+    /// Counts the keys with the given `prefix` in the storage trie associated with the current
+    /// account, including the changes not committed yet.
+    ///
+    /// Returns `Ok(None)` if there are more than `limit` such keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - the prefix of the keys to count
+    /// * `limit` - the max number of keys to count
+    ///
+    /// # Errors
+    ///
+    /// This function could return [`VMError::RuntimeError`].
+    ///
+    /// # Example
+    /// ```
+    /// # use near_vm_logic::mocks::mock_external::MockedExternal;
+    /// # use near_vm_logic::External;
+    ///
+    /// # let mut external = MockedExternal::new();
+    /// external.storage_set(b"item1", b"value").unwrap();
+    /// external.storage_set(b"item2", b"value").unwrap();
+    /// assert_eq!(external.storage_key_count(b"item", 10), Ok(Some(2)));
+    /// assert_eq!(external.storage_key_count(b"item", 1), Ok(None));
+    /// ```
+    #[cfg(feature = "protocol_feature_storage_key_count")]
+    fn storage_key_count(&mut self, prefix: &[u8], limit: u64) -> Result<Option<u64>>;
+
     /// Create a receipt which will be executed after all the receipts identified by
     /// `receipt_indices` are complete.
     ///
@@ -190,6 +279,69 @@ pub trait External {
         receiver_id: AccountId,
     ) -> Result<ReceiptIndex>;
 
+    /// Generates a new data id and registers it as generated by the current account, so that
+    /// the account can later create a receipt which depends on it with
+    /// [`External::create_receipt_with_data_dependency`] and send the data with
+    /// [`External::resume_data`].
+    ///
+    /// # Example
+    /// ```
+    /// # use near_vm_logic::mocks::mock_external::MockedExternal;
+    /// # use near_vm_logic::External;
+    ///
+    /// # let mut external = MockedExternal::new();
+    /// let data_id = external.generate_data_id().unwrap();
+    /// assert_ne!(external.generate_data_id().unwrap(), data_id);
+    /// ```
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    fn generate_data_id(&mut self) -> Result<CryptoHash>;
+
+    /// Create a receipt which will be executed after the data with the given `data_id` is
+    /// received by `receiver_id`.
+    ///
+    /// The `data_id` must have been generated by [`External::generate_data_id`] of the current
+    /// account, possibly while executing an earlier receipt, and no other receipt may depend on
+    /// it yet.  Otherwise this function fails with `InvalidDataId`.  The registration records
+    /// `receiver_id` until the data is sent with [`External::resume_data`].
+    ///
+    /// # Example
+    /// ```
+    /// # use near_vm_logic::mocks::mock_external::MockedExternal;
+    /// # use near_vm_logic::External;
+    ///
+    /// # let mut external = MockedExternal::new();
+    /// let data_id = external.generate_data_id().unwrap();
+    /// let receiver_id = "bob.near".parse().unwrap();
+    /// external.create_receipt_with_data_dependency(data_id, receiver_id).unwrap();
+    /// ```
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    fn create_receipt_with_data_dependency(
+        &mut self,
+        data_id: CryptoHash,
+        receiver_id: AccountId,
+    ) -> Result<ReceiptIndex>;
+
+    /// Sends `data` to the receipt created by [`External::create_receipt_with_data_dependency`]
+    /// which depends on `data_id`, and unregisters the data id.  Returns the receiver of the
+    /// receipt.
+    ///
+    /// Fails with `InvalidDataId` if the data id was not generated by the current account or no
+    /// receipt depends on it.
+    ///
+    /// # Example
+    /// ```
+    /// # use near_vm_logic::mocks::mock_external::MockedExternal;
+    /// # use near_vm_logic::External;
+    ///
+    /// # let mut external = MockedExternal::new();
+    /// let data_id = external.generate_data_id().unwrap();
+    /// let receiver_id = "bob.near".parse().unwrap();
+    /// external.create_receipt_with_data_dependency(data_id, receiver_id.clone()).unwrap();
+    /// assert_eq!(external.resume_data(data_id, b"data".to_vec()).unwrap(), receiver_id);
+    /// ```
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    fn resume_data(&mut self, data_id: CryptoHash, data: Vec<u8>) -> Result<AccountId>;
+
     /// Attach the [`CreateAccountAction`] action to an existing receipt.
     ///
     /// # Arguments
@@ -533,6 +685,12 @@ pub trait External {
     /// Returns amount of touched trie nodes by storage operations
     fn get_touched_nodes_count(&self) -> u64;
 
+    /// Returns the size in bytes of trie nodes the current receipt has added to the storage
+    /// proof so far.  It is the same whether the storage proof is recorded, replayed or not
+    /// recorded at all.
+    #[cfg(feature = "protocol_feature_per_receipt_storage_proof_limit")]
+    fn recorded_storage_size(&self) -> u64;
+
     /// Returns the validator stake for given account in the current epoch.
     /// If the account is not a validator, returns `None`.
     fn validator_stake(&self, account_id: &AccountId) -> Result<Option<Balance>>;
@@ -540,6 +698,46 @@ pub trait External {
     /// Returns total stake of validators in the current epoch.
     fn validator_total_stake(&self) -> Result<Balance>;
 
+    /// Returns whether the validator stake of the given account was already looked up, so the
+    /// next `validator_stake` call doesn't have to query the epoch manager.
+    fn validator_stake_cached(&self, account_id: &AccountId) -> bool;
+
+    /// Returns whether the total validator stake was already looked up, so the next
+    /// `validator_total_stake` call doesn't have to query the epoch manager.
+    fn validator_total_stake_cached(&self) -> bool;
+
+    /// Returns the locked balance of the given account.
+    ///
+    /// # Errors
+    ///
+    /// * `AccountOnOtherShard` if the account is not on the shard of the current account.
+    /// * `AccountDoesNotExist` if there is no such account.
+    #[cfg(feature = "protocol_feature_locked_balance_of")]
+    fn locked_balance_of(&self, account_id: &AccountId) -> Result<Balance>;
+
+    /// Returns the gas price of the current receipt, which its gas was purchased at.
+    fn gas_price(&self) -> Balance;
+
+    /// Returns the gas price of the current block.
+    fn block_gas_price(&self) -> Balance;
+
+    /// Returns the cost of storing one byte in the current protocol version.
+    fn storage_amount_per_byte(&self) -> Balance;
+
+    /// Returns the gas burnt by the receipt which produced the promise result with the given
+    /// index, or `None` if the data was sent without it.
+    fn promise_result_gas_burnt(&self, result_idx: u64) -> Option<Gas>;
+
+    /// Returns the hash identifying the current epoch, its `EpochId`.
+    fn epoch_id(&self) -> CryptoHash;
+
+    /// Returns the id of the shard the current receipt is executed on.
+    fn shard_id(&self) -> ShardId;
+
+    /// Returns the depth of the receipt being executed, the number of receipts between it and
+    /// the transaction it descends from.
+    fn receipt_depth(&self) -> u64;
+
     /// Distribute the gas among the scheduled function calls that specify a gas weight.
     ///
     /// # Arguments
@@ -551,4 +749,26 @@ pub trait External {
     /// Function returns a [GasDistribution] that indicates how the gas was distributed.
     #[cfg(feature = "protocol_feature_function_call_weight")]
     fn distribute_unused_gas(&mut self, gas: Gas) -> GasDistribution;
+
+    /// Returns the static gas and the gas weight of a function call action.
+    ///
+    /// # Arguments
+    ///
+    /// * `receipt_index` - an index of Receipt the action was appended to
+    /// * `action_index` - an index of the action in the Receipt
+    ///
+    /// # Returns
+    ///
+    /// `None` if the action doesn't exist or isn't a function call. Calls appended without a gas
+    /// weight have the weight `0`.
+    #[cfg(feature = "protocol_feature_promise_action_min_gas")]
+    fn function_call_gas(
+        &self,
+        receipt_index: ReceiptIndex,
+        action_index: u64,
+    ) -> Option<(Gas, GasWeight)>;
+
+    /// Returns the sum of the gas weights of the function calls scheduled so far.
+    #[cfg(feature = "protocol_feature_promise_action_min_gas")]
+    fn gas_weight_sum(&self) -> u128;
 }
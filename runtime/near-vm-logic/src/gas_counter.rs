@@ -3,7 +3,7 @@ use near_primitives_core::runtime::fees::Fee;
 use near_primitives_core::{
     config::{ActionCosts, ExtCosts, ExtCostsConfig},
     profile::ProfileData,
-    types::Gas,
+    types::{Compute, Gas},
 };
 use std::collections::HashMap;
 use std::fmt;
@@ -57,6 +57,10 @@ pub struct GasCounter {
     ext_costs_config: ExtCostsConfig,
     /// Where to store profile data, if needed.
     profile: ProfileData,
+    /// Compute of the host function costs paid so far whose compute cost differs from their
+    /// gas cost, and the gas burnt for them.
+    ext_compute_usage: Compute,
+    ext_compute_gas: Gas,
 }
 
 impl fmt::Debug for GasCounter {
@@ -88,6 +92,8 @@ impl GasCounter {
             prepaid_gas,
             is_view,
             profile: Default::default(),
+            ext_compute_usage: 0,
+            ext_compute_gas: 0,
         }
     }
 
@@ -187,6 +193,16 @@ impl GasCounter {
         self.profile.add_ext_cost(cost, value)
     }
 
+    /// Accounts for the compute of a host function cost paid `num` times for `gas`.
+    #[inline]
+    fn update_compute_usage(&mut self, cost: ExtCosts, num: u64, gas: Gas) {
+        if let Some(compute) = self.ext_costs_config.compute_costs.get(&cost) {
+            self.ext_compute_usage =
+                self.ext_compute_usage.saturating_add(num.saturating_mul(*compute));
+            self.ext_compute_gas = self.ext_compute_gas.saturating_add(gas);
+        }
+    }
+
     #[inline]
     fn update_profile_action(&mut self, action: ActionCosts, value: u64) {
         self.profile.add_action_cost(action, value)
@@ -200,7 +216,9 @@ impl GasCounter {
 
         self.inc_ext_costs_counter(cost, num);
         self.update_profile_host(cost, use_gas);
-        self.burn_gas(use_gas)
+        self.burn_gas(use_gas)?;
+        self.update_compute_usage(cost, num, use_gas);
+        Ok(())
     }
 
     /// A helper function to pay base cost gas.
@@ -208,7 +226,9 @@ impl GasCounter {
         let base_fee = cost.value(&self.ext_costs_config);
         self.inc_ext_costs_counter(cost, 1);
         self.update_profile_host(cost, base_fee);
-        self.burn_gas(base_fee)
+        self.burn_gas(base_fee)?;
+        self.update_compute_usage(cost, 1, base_fee);
+        Ok(())
     }
 
     /// A helper function to pay per byte gas fee for batching an action.
@@ -278,6 +298,22 @@ impl GasCounter {
     pub fn used_gas(&self) -> Gas {
         self.promises_gas + self.fast_counter.burnt_gas
     }
+    /// The burnt gas with the host function costs which have a compute cost of their own
+    /// counted at that cost instead.
+    pub fn compute_usage(&self) -> Compute {
+        self.fast_counter
+            .burnt_gas
+            .saturating_sub(self.ext_compute_gas)
+            .saturating_add(self.ext_compute_usage)
+    }
+    /// Gas which can still be burnt before crossing the max gas burnt or the prepaid gas limit.
+    #[cfg(feature = "protocol_feature_large_data_receipt_cost")]
+    pub fn remaining_gas(&self) -> Gas {
+        std::cmp::min(
+            self.max_gas_burnt.saturating_sub(self.fast_counter.burnt_gas),
+            self.prepaid_gas.saturating_sub(self.used_gas()),
+        )
+    }
 
     pub fn profile_data(&self) -> ProfileData {
         self.profile.clone()
@@ -349,4 +385,25 @@ mod tests {
         test(8, 5, false, Err(HostError::GasExceeded));
         test(8, 5, true, Ok(()));
     }
+
+    #[test]
+    fn test_compute_usage() {
+        use near_primitives_core::config::ExtCosts;
+
+        let mut config = ExtCostsConfig::test();
+        let touching_trie_node = config.touching_trie_node;
+        config.compute_costs.insert(ExtCosts::touching_trie_node, 10 * touching_trie_node);
+        let mut counter = super::GasCounter::new(config, Gas::MAX, 1, Gas::MAX, false);
+        counter.pay_base(ExtCosts::base).unwrap();
+        counter.pay_per(ExtCosts::touching_trie_node, 3).unwrap();
+        counter.pay_wasm_gas(100).unwrap();
+        assert_eq!(
+            counter.burnt_gas(),
+            counter.ext_costs_config.base + 3 * touching_trie_node + 100
+        );
+        assert_eq!(
+            counter.compute_usage(),
+            counter.ext_costs_config.base + 30 * touching_trie_node + 100
+        );
+    }
 }
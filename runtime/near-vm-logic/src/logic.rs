@@ -6,15 +6,20 @@ use crate::utils::split_method_names;
 use crate::ValuePtr;
 use byteorder::ByteOrder;
 use near_crypto::Secp256K1Signature;
+use near_primitives::checked_feature;
 use near_primitives::version::is_implicit_account_creation_enabled;
 use near_primitives_core::config::ExtCosts::*;
 use near_primitives_core::config::{ActionCosts, ExtCosts, VMConfig, ViewConfig};
+#[cfg(feature = "protocol_feature_promise_data_dependency")]
+use near_primitives_core::hash::CryptoHash;
 use near_primitives_core::profile::ProfileData;
+#[cfg(feature = "protocol_feature_gas_budget_host_functions")]
+use near_primitives_core::runtime::fees::Fee;
 use near_primitives_core::runtime::fees::{
     transfer_exec_fee, transfer_send_fee, RuntimeFeesConfig,
 };
 use near_primitives_core::types::{
-    AccountId, Balance, EpochHeight, Gas, ProtocolVersion, StorageUsage,
+    AccountId, Balance, Compute, EpochHeight, Gas, ProtocolVersion, StorageUsage,
 };
 #[cfg(feature = "protocol_feature_function_call_weight")]
 use near_primitives_core::types::{GasDistribution, GasWeight};
@@ -57,6 +62,8 @@ pub struct VMLogic<'a> {
     /// Registers can be used by the guest to store blobs of data without moving them across
     /// host-guest boundary.
     registers: HashMap<u64, Vec<u8>>,
+    /// Memory used by all registers, counted against `registers_memory_limit`.
+    registers_memory_usage: u64,
 
     /// The DAG of promises, indexed by promise id.
     promises: Vec<Promise>,
@@ -139,6 +146,7 @@ impl<'a> VMLogic<'a> {
             return_data: ReturnData::None,
             logs: vec![],
             registers: HashMap::new(),
+            registers_memory_usage: 0,
             promises: vec![],
             receipt_to_account: HashMap::new(),
             total_log_length: 0,
@@ -224,23 +232,33 @@ impl<'a> VMLogic<'a> {
     }
 
     fn internal_write_register(&mut self, register_id: u64, data: Vec<u8>) -> Result<()> {
+        let usage = self.internal_reserve_register(register_id, data.len() as u64)?;
+        self.registers.insert(register_id, data);
+        self.registers_memory_usage = usage;
+        Ok(())
+    }
+
+    /// Pays for writing `len` bytes into `register_id` and checks them against the register
+    /// limits, so that values can be rejected before they are read.  Returns the memory used by
+    /// the registers once they are written.
+    fn internal_reserve_register(&mut self, register_id: u64, len: u64) -> Result<u64> {
         self.gas_counter.pay_base(write_register_base)?;
-        self.gas_counter.pay_per(write_register_byte, data.len() as u64)?;
-        if data.len() as u64 > self.config.limit_config.max_register_size
-            || self.registers.len() as u64 >= self.config.limit_config.max_number_registers
+        self.gas_counter.pay_per(write_register_byte, len)?;
+        let limit_config = &self.config.limit_config;
+        if len > limit_config.max_register_size
+            || self.registers.len() as u64 >= limit_config.max_number_registers
         {
             return Err(HostError::MemoryAccessViolation.into());
         }
-        self.registers.insert(register_id, data);
 
-        // Calculate the new memory usage.
-        let usage: usize =
-            self.registers.values().map(|v| size_of::<u64>() + v.len() * size_of::<u8>()).sum();
-        if usage as u64 > self.config.limit_config.registers_memory_limit {
-            Err(HostError::MemoryAccessViolation.into())
-        } else {
-            Ok(())
+        // The memory of the value being overwritten, if any, is released.
+        let released =
+            self.registers.get(&register_id).map_or(0, |old| register_memory(old.len() as u64));
+        let usage = self.registers_memory_usage - released + register_memory(len);
+        if usage > limit_config.registers_memory_limit {
+            return Err(HostError::MemoryAccessViolation.into());
         }
+        Ok(usage)
     }
 
     /// Convenience function for testing.
@@ -601,6 +619,42 @@ impl<'a> VMLogic<'a> {
         self.internal_write_register(register_id, self.context.input.clone())
     }
 
+    /// Returns the length of the input to the contract call.
+    ///
+    /// # Cost
+    ///
+    /// `base`
+    #[cfg(feature = "protocol_feature_input_read")]
+    pub fn input_len(&mut self) -> Result<u64> {
+        self.gas_counter.pay_base(base)?;
+        Ok(self.context.input.len() as u64)
+    }
+
+    /// Copies `len` bytes of the input to the contract call starting at `offset` into the guest
+    /// memory at `ptr`, without going through a register.
+    ///
+    /// # Errors
+    ///
+    /// If the requested range extends outside the input or the guest memory returns
+    /// `MemoryAccessViolation`.
+    ///
+    /// # Cost
+    ///
+    /// `base + write_memory_base + write_memory_byte * len`
+    #[cfg(feature = "protocol_feature_input_read")]
+    pub fn input_read(&mut self, offset: u64, len: u64, ptr: u64) -> Result<()> {
+        self.gas_counter.pay_base(base)?;
+        let end = offset
+            .checked_add(len)
+            .filter(|&end| end <= self.context.input.len() as u64)
+            .ok_or(HostError::MemoryAccessViolation)?;
+        self.gas_counter.pay_base(write_memory_base)?;
+        self.gas_counter.pay_per(write_memory_byte, len)?;
+        self.try_fit_mem(ptr, len)?;
+        self.memory.write_memory(ptr, &self.context.input[offset as usize..end as usize]);
+        Ok(())
+    }
+
     /// Returns the current block height.
     ///
     /// # Cost
@@ -632,12 +686,54 @@ impl<'a> VMLogic<'a> {
         Ok(self.context.epoch_height)
     }
 
+    /// Writes the 32-byte id of the current epoch into the register.
+    ///
+    /// # Errors
+    ///
+    /// If the size of the registers exceed the set limit `MemoryAccessViolation`.
+    ///
+    /// # Cost
+    ///
+    /// `base + write_register_base + write_register_byte * num_bytes`
+    #[cfg(feature = "protocol_feature_epoch_and_shard_id")]
+    pub fn epoch_id(&mut self, register_id: u64) -> Result<()> {
+        self.gas_counter.pay_base(base)?;
+        let epoch_id = self.ext.epoch_id();
+        self.internal_write_register(register_id, epoch_id.as_ref().to_vec())
+    }
+
+    /// Returns the id of the shard the current receipt is executed on.
+    ///
+    /// # Cost
+    ///
+    /// `base`
+    #[cfg(feature = "protocol_feature_epoch_and_shard_id")]
+    pub fn shard_id(&mut self) -> Result<u64> {
+        self.gas_counter.pay_base(base)?;
+        Ok(self.ext.shard_id())
+    }
+
+    /// Returns the depth of the receipt being executed: 0 for a receipt converted from a
+    /// transaction, one more than the depth of its parent for a receipt created by a contract.
+    /// Promises fail once the receipts they create would be deeper than `max_receipt_depth`.
+    ///
+    /// # Cost
+    ///
+    /// `base`
+    #[cfg(feature = "protocol_feature_receipt_depth")]
+    pub fn receipt_depth(&mut self) -> Result<u64> {
+        self.gas_counter.pay_base(base)?;
+        Ok(self.ext.receipt_depth())
+    }
+
     /// Get the stake of an account, if the account is currently a validator. Otherwise returns 0.
     /// writes the value into the` u128` variable pointed by `stake_ptr`.
     ///
     /// # Cost
     ///
     /// `base + memory_write_base + memory_write_size * 16 + utf8_decoding_base + utf8_decoding_byte * account_id_len + validator_stake_base`.
+    /// `validator_stake_base` is not charged if the stake was already looked up during this
+    /// execution and the validator info cache is enabled.
     pub fn validator_stake(
         &mut self,
         account_id_len: u64,
@@ -646,7 +742,14 @@ impl<'a> VMLogic<'a> {
     ) -> Result<()> {
         self.gas_counter.pay_base(base)?;
         let account_id = self.read_and_parse_account_id(account_id_ptr, account_id_len)?;
-        self.gas_counter.pay_base(validator_stake_base)?;
+        let cached = checked_feature!(
+            "protocol_feature_validator_info_cache",
+            ValidatorInfoCache,
+            self.current_protocol_version
+        ) && self.ext.validator_stake_cached(&account_id);
+        if !cached {
+            self.gas_counter.pay_base(validator_stake_base)?;
+        }
         let balance = self.ext.validator_stake(&account_id)?.unwrap_or_default();
         self.memory_set_u128(stake_ptr, balance)
     }
@@ -658,13 +761,56 @@ impl<'a> VMLogic<'a> {
     /// # Cost
     ///
     /// `base + memory_write_base + memory_write_size * 16 + validator_total_stake_base`
+    /// `validator_total_stake_base` is not charged if the total stake was already looked up
+    /// during this execution and the validator info cache is enabled.
     pub fn validator_total_stake(&mut self, stake_ptr: u64) -> Result<()> {
         self.gas_counter.pay_base(base)?;
-        self.gas_counter.pay_base(validator_total_stake_base)?;
+        let cached = checked_feature!(
+            "protocol_feature_validator_info_cache",
+            ValidatorInfoCache,
+            self.current_protocol_version
+        ) && self.ext.validator_total_stake_cached();
+        if !cached {
+            self.gas_counter.pay_base(validator_total_stake_base)?;
+        }
         let total_stake = self.ext.validator_total_stake()?;
         self.memory_set_u128(stake_ptr, total_stake)
     }
 
+    /// Writes the locked balance of an account on the same shard as the current account into the
+    /// register as a little-endian u128.
+    ///
+    /// # Errors
+    ///
+    /// * If `account_id_len + account_id_ptr` exceeds the memory container or points to an unused
+    ///   register it returns `MemoryAccessViolation`.
+    /// * If the account id is invalid returns `InvalidAccountId`.
+    /// * If the account is on another shard returns `AccountOnOtherShard`.
+    /// * If the account doesn't exist returns `AccountDoesNotExist`.
+    ///
+    /// # Cost
+    ///
+    /// `base + utf8_decoding_base + utf8_decoding_byte * account_id_len + storage_read_base
+    /// + storage_read_key_byte * account_id_len + touching_trie_node * num_touched_nodes
+    /// + write_register_base + write_register_byte * 16`
+    #[cfg(feature = "protocol_feature_locked_balance_of")]
+    pub fn locked_balance_of(
+        &mut self,
+        account_id_len: u64,
+        account_id_ptr: u64,
+        register_id: u64,
+    ) -> Result<()> {
+        self.gas_counter.pay_base(base)?;
+        let account_id = self.read_and_parse_account_id(account_id_ptr, account_id_len)?;
+        self.gas_counter.pay_base(storage_read_base)?;
+        self.gas_counter.pay_per(storage_read_key_byte, account_id.len() as u64)?;
+        let nodes_before = self.ext.get_touched_nodes_count();
+        let locked = self.ext.locked_balance_of(&account_id);
+        self.gas_counter
+            .pay_per(touching_trie_node, self.ext.get_touched_nodes_count() - nodes_before)?;
+        self.internal_write_register(register_id, locked?.to_le_bytes().to_vec())
+    }
+
     /// Returns the number of bytes used by the contract if it was saved to the trie as of the
     /// invocation. This includes:
     /// * The data written with storage_* functions during current and previous execution;
@@ -680,6 +826,43 @@ impl<'a> VMLogic<'a> {
         Ok(self.current_storage_usage)
     }
 
+    /// Writes the cost of storing one byte in the current protocol version into the register as
+    /// a little-endian u128.
+    ///
+    /// # Errors
+    ///
+    /// If the size of the registers exceed the set limit `MemoryAccessViolation`.
+    ///
+    /// # Cost
+    ///
+    /// `base + write_register_base + write_register_byte * num_bytes`
+    #[cfg(feature = "protocol_feature_storage_config_host_functions")]
+    pub fn storage_byte_cost(&mut self, register_id: u64) -> Result<()> {
+        self.gas_counter.pay_base(base)?;
+        let storage_amount_per_byte = self.ext.storage_amount_per_byte();
+        self.internal_write_register(register_id, storage_amount_per_byte.to_le_bytes().to_vec())
+    }
+
+    /// Writes the balance the current account needs to hold, as liquid or locked balance, to
+    /// cover its storage usage as of the invocation, see `storage_usage`, into the register as a
+    /// little-endian u128.
+    ///
+    /// # Errors
+    ///
+    /// If the size of the registers exceed the set limit `MemoryAccessViolation`.
+    ///
+    /// # Cost
+    ///
+    /// `base + write_register_base + write_register_byte * num_bytes`
+    #[cfg(feature = "protocol_feature_storage_config_host_functions")]
+    pub fn min_account_balance(&mut self, register_id: u64) -> Result<()> {
+        self.gas_counter.pay_base(base)?;
+        let min_balance = Balance::from(self.current_storage_usage)
+            .checked_mul(self.ext.storage_amount_per_byte())
+            .ok_or(HostError::IntegerOverflow)?;
+        self.internal_write_register(register_id, min_balance.to_le_bytes().to_vec())
+    }
+
     // #################
     // # Economics API #
     // #################
@@ -763,6 +946,155 @@ impl<'a> VMLogic<'a> {
         Ok(self.gas_counter.used_gas())
     }
 
+    /// The gas attached to the call which isn't used yet, that is the gas which can still be
+    /// burnt or attached to promises.  The wasm instructions of a block are paid for when the
+    /// block is entered, so the rest of the current block doesn't need any of it.
+    ///
+    /// # Errors
+    ///
+    /// If called as view function returns `ProhibitedInView`.
+    ///
+    /// # Cost
+    ///
+    /// `base`
+    #[cfg(feature = "protocol_feature_gas_budget_host_functions")]
+    pub fn remaining_gas(&mut self) -> Result<Gas> {
+        self.gas_counter.pay_base(base)?;
+        if self.context.is_view() {
+            return Err(
+                HostError::ProhibitedInView { method_name: "remaining_gas".to_string() }.into()
+            );
+        }
+        Ok(self.context.prepaid_gas.saturating_sub(self.gas_counter.used_gas()))
+    }
+
+    /// Returns an upper bound of the gas which the `promise_batch_action_*` host function
+    /// appending an action of the given kind uses besides the gas attached to a function call:
+    /// the cost of the host function with its arguments read from memory, and the fees of
+    /// sending and executing the action, whichever the receiver is.  Attaching `remaining_gas`
+    /// minus the estimate to a function call therefore doesn't run out of gas.
+    ///
+    /// `payload_len` is the total length of the variable-size arguments of the action:
+    /// * `0` - `CreateAccount`;
+    /// * `1` - `DeployContract`, the code;
+    /// * `2` - `FunctionCall`, the method name and the arguments;
+    /// * `3` - `Transfer`;
+    /// * `4` - `Stake`, the public key;
+    /// * `5` - `AddKey` with full access, the public key;
+    /// * `6` - `AddKey` with function call access, the public key, the receiver id and the
+    ///   method names;
+    /// * `7` - `DeleteKey`, the public key;
+    /// * `8` - `DeleteAccount`, the beneficiary id.
+    ///
+    /// # Errors
+    ///
+    /// If `action_kind` isn't one of the above returns `InvalidActionKind`.
+    ///
+    /// # Cost
+    ///
+    /// `base`
+    #[cfg(feature = "protocol_feature_gas_budget_host_functions")]
+    pub fn action_fee_estimate(&mut self, action_kind: u64, payload_len: u64) -> Result<Gas> {
+        self.gas_counter.pay_base(base)?;
+        let ext_costs = &self.config.ext_costs;
+        let fees = &self.fees_config.action_creation_config;
+        // Computed in u128, which the sums of products of u64 can't overflow.
+        let cost = |cost: ExtCosts| u128::from(cost.value(ext_costs));
+        let read = |num_reads: u128, num_bytes: u128| {
+            num_reads * cost(read_memory_base) + num_bytes * cost(read_memory_byte)
+        };
+        let utf8 =
+            |num_bytes: u128| cost(utf8_decoding_base) + num_bytes * cost(utf8_decoding_byte);
+        let action_fee = |fee: &Fee| {
+            u128::from(std::cmp::max(fee.send_sir, fee.send_not_sir)) + u128::from(fee.execution)
+        };
+        let len = u128::from(payload_len);
+        let u128_len = size_of::<u128>() as u128;
+        let estimate = cost(base)
+            + match action_kind {
+                0 => action_fee(&fees.create_account_cost),
+                1 => {
+                    read(1, len)
+                        + action_fee(&fees.deploy_contract_cost)
+                        + len * action_fee(&fees.deploy_contract_cost_per_byte)
+                }
+                2 => {
+                    read(3, u128_len + len)
+                        + action_fee(&fees.function_call_cost)
+                        + len * action_fee(&fees.function_call_cost_per_byte)
+                }
+                3 => {
+                    // Transfers to implicit accounts also pay for creating them.
+                    let is_receiver_implicit =
+                        is_implicit_account_creation_enabled(self.current_protocol_version);
+                    let send_fee = std::cmp::max(
+                        transfer_send_fee(fees, false, is_receiver_implicit),
+                        transfer_send_fee(fees, true, is_receiver_implicit),
+                    );
+                    read(1, u128_len)
+                        + u128::from(send_fee)
+                        + u128::from(transfer_exec_fee(fees, is_receiver_implicit))
+                }
+                4 => read(2, u128_len + len) + action_fee(&fees.stake_cost),
+                5 => read(1, len) + action_fee(&fees.add_key_cost.full_access_cost),
+                // The method names are charged with a terminator each, which is at most one
+                // byte more than their comma separated list.
+                6 => {
+                    read(4, u128_len + len)
+                        + utf8(len)
+                        + action_fee(&fees.add_key_cost.function_call_cost)
+                        + (len + 1) * action_fee(&fees.add_key_cost.function_call_cost_per_byte)
+                }
+                7 => read(1, len) + action_fee(&fees.delete_key_cost),
+                8 => read(1, len) + utf8(len) + action_fee(&fees.delete_account_cost),
+                _ => return Err(HostError::InvalidActionKind { action_kind }.into()),
+            };
+        Ok(Gas::try_from(estimate).map_err(|_| HostError::IntegerOverflow)?)
+    }
+
+    /// Writes the gas price of the current receipt, which its gas was purchased at, into the
+    /// register as a little-endian u128.
+    ///
+    /// # Errors
+    ///
+    /// * If the size of the registers exceed the set limit `MemoryAccessViolation`.
+    /// * If called as view function returns `ProhibitedInView`.
+    ///
+    /// # Cost
+    ///
+    /// `base + write_register_base + write_register_byte * num_bytes`
+    #[cfg(feature = "protocol_feature_gas_price_host_functions")]
+    pub fn gas_price(&mut self, register_id: u64) -> Result<()> {
+        self.gas_counter.pay_base(base)?;
+        if self.context.is_view() {
+            return Err(HostError::ProhibitedInView { method_name: "gas_price".to_string() }.into());
+        }
+        let gas_price = self.ext.gas_price();
+        self.internal_write_register(register_id, gas_price.to_le_bytes().to_vec())
+    }
+
+    /// Writes the gas price of the current block into the register as a little-endian u128.
+    ///
+    /// # Errors
+    ///
+    /// * If the size of the registers exceed the set limit `MemoryAccessViolation`.
+    /// * If called as view function returns `ProhibitedInView`.
+    ///
+    /// # Cost
+    ///
+    /// `base + write_register_base + write_register_byte * num_bytes`
+    #[cfg(feature = "protocol_feature_gas_price_host_functions")]
+    pub fn block_gas_price(&mut self, register_id: u64) -> Result<()> {
+        self.gas_counter.pay_base(base)?;
+        if self.context.is_view() {
+            return Err(
+                HostError::ProhibitedInView { method_name: "block_gas_price".to_string() }.into()
+            );
+        }
+        let block_gas_price = self.ext.block_gas_price();
+        self.internal_write_register(register_id, block_gas_price.to_le_bytes().to_vec())
+    }
+
     // ############
     // # Math API #
     // ############
@@ -1373,6 +1705,191 @@ impl<'a> VMLogic<'a> {
         self.checked_push_promise(Promise::Receipt(new_receipt_idx))
     }
 
+    /// Generates a new data id and writes it into the register.  The data id is registered as
+    /// generated by the current account, which can then create a receipt waiting for the data
+    /// with this id with `promise_create_with_data_dependency`, either in this or in a later
+    /// function call, and later send the data with `promise_resume_data`.
+    ///
+    /// The registration is kept in the state of the account and counts towards its storage
+    /// usage until the data is sent.
+    ///
+    /// # Errors
+    ///
+    /// * If called as view function returns `ProhibitedInView`.
+    ///
+    /// # Cost
+    ///
+    /// `base + storage_write_base + storage_write_key_byte * 32 + write_register_base +
+    /// write_register_byte * 32`
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    pub fn generate_data_id(&mut self, register_id: u64) -> Result<()> {
+        self.gas_counter.pay_base(base)?;
+        if self.context.is_view() {
+            return Err(HostError::ProhibitedInView {
+                method_name: "generate_data_id".to_string(),
+            }
+            .into());
+        }
+        self.gas_counter.pay_base(storage_write_base)?;
+        self.gas_counter.pay_per(storage_write_key_byte, size_of::<CryptoHash>() as u64)?;
+        let data_id = self.ext.generate_data_id()?;
+        self.current_storage_usage = self
+            .current_storage_usage
+            .checked_add(
+                size_of::<CryptoHash>() as u64
+                    + self.fees_config.storage_usage_config.num_extra_bytes_record,
+            )
+            .ok_or(InconsistentStateError::IntegerOverflow)?;
+        self.internal_write_register(register_id, data_id.as_ref().to_vec())
+    }
+
+    /// Creates a promise that will execute a method on account with given arguments and attaches
+    /// the given amount and gas once the account receives the data with the id pointed by
+    /// `data_id_ptr`.  The data may be received before or after the promise is created.
+    ///
+    /// The data id must have been generated by `generate_data_id` of the current account, and
+    /// only one promise can depend on each data id.  The registration records the receiver of
+    /// the promise until the data is sent to it with `promise_resume_data`.
+    ///
+    /// # Errors
+    ///
+    /// * If `data_id_ptr + 32` or `account_id_len + account_id_ptr` or
+    ///   `method_name_len + method_name_ptr` or `arguments_len + arguments_ptr` or
+    ///   `amount_ptr + 16` points outside the memory of the guest or host returns
+    ///   `MemoryAccessViolation`.
+    /// * If the data id was not generated by the current account or a promise already depends
+    ///   on it returns `InvalidDataId`.
+    /// * If called as view function returns `ProhibitedInView`.
+    /// * If the total number of promises exceeds `max_promises_per_function_call_action` limit
+    ///   returns `NumPromisesExceeded`.
+    ///
+    /// # Returns
+    ///
+    /// Index of the new promise that uniquely identifies it within the current execution of the
+    /// method.
+    ///
+    /// # Cost
+    ///
+    /// `base + storage_write_base + storage_write_key_byte * 32 + storage_write_value_byte *
+    /// account_id_len + cost of reading the data id and the account id + dispatch&execution cost
+    /// of the receipt + dispatch&execution base cost of the data dependency + cost of
+    /// promise_batch_action_function_call`.
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    pub fn promise_create_with_data_dependency(
+        &mut self,
+        data_id_ptr: u64,
+        account_id_len: u64,
+        account_id_ptr: u64,
+        method_name_len: u64,
+        method_name_ptr: u64,
+        arguments_len: u64,
+        arguments_ptr: u64,
+        amount_ptr: u64,
+        gas: Gas,
+    ) -> Result<u64> {
+        self.gas_counter.pay_base(base)?;
+        if self.context.is_view() {
+            return Err(HostError::ProhibitedInView {
+                method_name: "promise_create_with_data_dependency".to_string(),
+            }
+            .into());
+        }
+        let mut data_id = [0u8; size_of::<CryptoHash>()];
+        self.memory_get_into(data_id_ptr, &mut data_id)?;
+        let account_id = self.read_and_parse_account_id(account_id_ptr, account_id_len)?;
+        self.gas_counter.pay_base(storage_write_base)?;
+        self.gas_counter.pay_per(storage_write_key_byte, data_id.len() as u64)?;
+        self.gas_counter.pay_per(storage_write_value_byte, account_id.len() as u64)?;
+        let sir = account_id == self.context.current_account_id;
+        // Who sends the data is not known, so the data receipt is paid for as a cross-shard one.
+        self.pay_gas_for_new_receipt(sir, &[false])?;
+
+        let new_receipt_idx = self
+            .ext
+            .create_receipt_with_data_dependency(CryptoHash(data_id), account_id.clone())?;
+        self.current_storage_usage = self
+            .current_storage_usage
+            .checked_add(account_id.len() as u64)
+            .ok_or(InconsistentStateError::IntegerOverflow)?;
+        self.receipt_to_account.insert(new_receipt_idx, account_id);
+        let new_promise_idx = self.checked_push_promise(Promise::Receipt(new_receipt_idx))?;
+
+        self.promise_batch_action_function_call(
+            new_promise_idx,
+            method_name_len,
+            method_name_ptr,
+            arguments_len,
+            arguments_ptr,
+            amount_ptr,
+            gas,
+        )?;
+        Ok(new_promise_idx)
+    }
+
+    /// Sends the data pointed by `payload_ptr` to the promise created by
+    /// `promise_create_with_data_dependency` which waits for the data id pointed by
+    /// `data_id_ptr`.  The data id is unregistered, so the data can only be sent once.
+    ///
+    /// The data is sent as a data receipt, which is delivered whether the promise is created
+    /// in the same or in an earlier function call.
+    ///
+    /// # Errors
+    ///
+    /// * If `data_id_ptr + 32` or `payload_len + payload_ptr` points outside the memory of the
+    ///   guest or host returns `MemoryAccessViolation`.
+    /// * If the data id was not generated by the current account or no promise depends on it
+    ///   returns `InvalidDataId`.
+    /// * If the length of the data exceeds `max_length_returned_data` returns
+    ///   `ReturnedValueLengthExceeded`.
+    /// * If called as view function returns `ProhibitedInView`.
+    ///
+    /// # Cost
+    ///
+    /// `base + storage_remove_base + storage_remove_key_byte * 32 + cost of reading the data id
+    /// and the data + dispatch&execution cost per byte of the data receipt`
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    pub fn promise_resume_data(
+        &mut self,
+        data_id_ptr: u64,
+        payload_len: u64,
+        payload_ptr: u64,
+    ) -> Result<()> {
+        self.gas_counter.pay_base(base)?;
+        if self.context.is_view() {
+            return Err(HostError::ProhibitedInView {
+                method_name: "promise_resume_data".to_string(),
+            }
+            .into());
+        }
+        let mut data_id = [0u8; size_of::<CryptoHash>()];
+        self.memory_get_into(data_id_ptr, &mut data_id)?;
+        let payload = self.get_vec_from_memory_or_register(payload_ptr, payload_len)?;
+        let num_bytes = payload.len() as u64;
+        if num_bytes > self.config.limit_config.max_length_returned_data {
+            return Err(HostError::ReturnedValueLengthExceeded {
+                length: num_bytes,
+                limit: self.config.limit_config.max_length_returned_data,
+            }
+            .into());
+        }
+        self.gas_counter.pay_base(storage_remove_base)?;
+        self.gas_counter.pay_per(storage_remove_key_byte, data_id.len() as u64)?;
+
+        let receiver_id = self.ext.resume_data(CryptoHash(data_id), payload)?;
+        let sir = receiver_id == self.context.current_account_id;
+        let burn_gas = self.data_cost(num_bytes, sir)?;
+        self.gas_counter.pay_action_accumulated(burn_gas, burn_gas, ActionCosts::value_return)?;
+        self.current_storage_usage = self
+            .current_storage_usage
+            .checked_sub(
+                size_of::<CryptoHash>() as u64
+                    + self.fees_config.storage_usage_config.num_extra_bytes_record
+                    + receiver_id.len() as u64,
+            )
+            .ok_or(InconsistentStateError::IntegerOverflow)?;
+        Ok(())
+    }
+
     /// Helper function to return the account id towards which the receipt is directed.
     fn get_account_by_receipt(&self, receipt_idx: &ReceiptIndex) -> &AccountId {
         self.receipt_to_account
@@ -1563,6 +2080,12 @@ impl<'a> VMLogic<'a> {
     /// Any remaining gas will be distributed to the last scheduled function call with a weight
     /// specified.
     ///
+    /// Passing `0` as `gas` and `1` as `gas_weight` to every callback gives each of them an
+    /// equal share of the leftover gas. If no gas is left at the end of the execution, the
+    /// function calls are still scheduled with their static gas only. The share a call is
+    /// guaranteed so far can be queried with
+    /// [`promise_action_min_gas`](Self::promise_action_min_gas).
+    ///
     /// # Errors
     ///
     /// * If `promise_idx` does not correspond to an existing promise returns `InvalidPromiseIndex`.
@@ -1582,7 +2105,7 @@ impl<'a> VMLogic<'a> {
         arguments_ptr: u64,
         amount_ptr: u64,
         gas: Gas,
-        gas_weight: GasWeight,
+        gas_weight: u64,
     ) -> Result<()> {
         let append_action_fn = |vm: &mut Self, receipt_idx, method_name, arguments, amount, gas| {
             vm.ext.append_action_function_call_weight(
@@ -1591,7 +2114,7 @@ impl<'a> VMLogic<'a> {
                 arguments,
                 amount,
                 gas,
-                gas_weight,
+                GasWeight(gas_weight),
             )
         };
         self.internal_promise_batch_action_function_call(
@@ -1606,6 +2129,49 @@ impl<'a> VMLogic<'a> {
         )
     }
 
+    /// Returns the gas the function call action `action_index` of the promise `promise_idx` gets
+    /// at least: its static gas plus its share of the prepaid gas left at this point of the
+    /// execution, split among all the function calls scheduled with a gas weight so far.  The
+    /// share only shrinks as the execution uses more gas or schedules more weighted calls, and
+    /// the call gets the static gas alone if no gas is left.
+    ///
+    /// # Errors
+    ///
+    /// * If `promise_idx` does not correspond to an existing promise returns `InvalidPromiseIndex`.
+    /// * If the promise pointed by the `promise_idx` is an ephemeral promise created by
+    /// `promise_and` returns `CannotAppendActionToJointPromise`.
+    /// * If `action_index` does not point to a function call action of the promise returns
+    /// `InvalidFunctionCallActionIndex`.
+    /// * If called as view function returns `ProhibitedInView`.
+    ///
+    /// # Cost
+    ///
+    /// `base`
+    #[cfg(feature = "protocol_feature_promise_action_min_gas")]
+    pub fn promise_action_min_gas(&mut self, promise_idx: u64, action_index: u64) -> Result<Gas> {
+        self.gas_counter.pay_base(base)?;
+        if self.context.is_view() {
+            return Err(HostError::ProhibitedInView {
+                method_name: "promise_action_min_gas".to_string(),
+            }
+            .into());
+        }
+        let (receipt_idx, _) = self.promise_idx_to_receipt_idx_with_sir(promise_idx)?;
+        let (gas, GasWeight(gas_weight)) = self
+            .ext
+            .function_call_gas(receipt_idx, action_index)
+            .ok_or(HostError::InvalidFunctionCallActionIndex { action_index })?;
+        let gas_weight_sum = self.ext.gas_weight_sum();
+        if gas_weight_sum == 0 {
+            return Ok(gas);
+        }
+        // The same floor division as in `distribute_unused_gas`, without the remainder which is
+        // given to the last weighted call.
+        let unused_gas = self.context.prepaid_gas - self.gas_counter.used_gas();
+        let gas_per_weight = (unused_gas as u128 / gas_weight_sum) as u64;
+        Ok(gas.saturating_add(gas_per_weight.saturating_mul(gas_weight)))
+    }
+
     fn internal_promise_batch_action_function_call(
         &mut self,
         promise_idx: u64,
@@ -2023,6 +2589,37 @@ impl<'a> VMLogic<'a> {
         }
     }
 
+    /// Returns the gas burnt by the receipt which produced the promise result, whether it
+    /// succeeded or failed.
+    ///
+    /// # Returns
+    ///
+    /// * The gas burnt as reported in the execution outcome of the receipt;
+    /// * `u64::MAX` if the result was produced before the gas burnt was reported along with it.
+    ///
+    /// # Errors
+    ///
+    /// * If `result_id` does not correspond to an existing result returns `InvalidPromiseResultIndex`;
+    /// * If called as view function returns `ProhibitedInView`.
+    ///
+    /// # Cost
+    ///
+    /// `base`
+    #[cfg(feature = "protocol_feature_promise_result_gas_burnt")]
+    pub fn promise_result_gas_burnt(&mut self, result_idx: u64) -> Result<Gas> {
+        self.gas_counter.pay_base(base)?;
+        if self.context.is_view() {
+            return Err(HostError::ProhibitedInView {
+                method_name: "promise_result_gas_burnt".to_string(),
+            }
+            .into());
+        }
+        if result_idx as usize >= self.promise_results.len() {
+            return Err(HostError::InvalidPromiseResultIndex { result_idx }.into());
+        }
+        Ok(self.ext.promise_result_gas_burnt(result_idx).unwrap_or(Gas::MAX))
+    }
+
     /// When promise `promise_idx` finishes executing its result is considered to be the result of
     /// the current function.
     ///
@@ -2067,13 +2664,33 @@ impl<'a> VMLogic<'a> {
     ///   returns `MemoryAccessViolation`.
     /// * if the length of the returned data exceeds `max_length_returned_data` returns
     ///   `ReturnedValueLengthExceeded`.
+    /// * If large data receipts are priced separately and the gas left can't pay for the data
+    ///   receipts of the value, returns `ReturnedValueGasExceeded` before reading the value.
     ///
     /// # Cost
     /// `base + cost of reading return value from memory or register + dispatch&exec cost per byte of the data sent * num data receivers`
+    ///
+    /// With `large_data_cost` configured, the bytes above its `threshold` are charged its
+    /// `cost_per_byte` instead.
     pub fn value_return(&mut self, value_len: u64, value_ptr: u64) -> Result<()> {
         self.gas_counter.pay_base(base)?;
+        #[cfg(feature = "protocol_feature_large_data_receipt_cost")]
+        if self.fees_config.data_receipt_creation_config.large_data_cost.is_some() {
+            let length = if value_len == u64::MAX {
+                self.registers
+                    .get(&value_ptr)
+                    .ok_or(HostError::InvalidRegisterId { register_id: value_ptr })?
+                    .len() as u64
+            } else {
+                value_len
+            };
+            let cost = self.value_return_cost(length)?;
+            let remaining = self.gas_counter.remaining_gas();
+            if cost > remaining {
+                return Err(HostError::ReturnedValueGasExceeded { length, cost, remaining }.into());
+            }
+        }
         let return_val = self.get_vec_from_memory_or_register(value_ptr, value_len)?;
-        let mut burn_gas: Gas = 0;
         let num_bytes = return_val.len() as u64;
         if num_bytes > self.config.limit_config.max_length_returned_data {
             return Err(HostError::ReturnedValueLengthExceeded {
@@ -2082,7 +2699,16 @@ impl<'a> VMLogic<'a> {
             }
             .into());
         }
-        let data_cfg = &self.fees_config.data_receipt_creation_config;
+        let burn_gas = self.value_return_cost(num_bytes)?;
+        self.gas_counter.pay_action_accumulated(burn_gas, burn_gas, ActionCosts::value_return)?;
+        self.return_data = ReturnData::Value(return_val);
+        Ok(())
+    }
+
+    /// Returns the gas burnt for sending a returned value of `num_bytes` to every output data
+    /// receiver.
+    fn value_return_cost(&self, num_bytes: u64) -> Result<Gas> {
+        let mut burn_gas: Gas = 0;
         for data_receiver in &self.context.output_data_receivers {
             let sir = data_receiver == &self.context.current_account_id;
             // We deduct for execution here too, because if we later have an OR combinator
@@ -2092,21 +2718,43 @@ impl<'a> VMLogic<'a> {
             // refund in this situation. Which we avoid by just paying for execution of
             // data receipt that might not be performed.
             // The gas here is considered burnt, cause we'll prepay for it upfront.
+            burn_gas = burn_gas
+                .checked_add(self.data_cost(num_bytes, sir)?)
+                .ok_or(HostError::IntegerOverflow)?;
+        }
+        Ok(burn_gas)
+    }
+
+    /// Returns the gas burnt for sending and executing a data receipt with `num_bytes` of data.
+    fn data_cost(&self, num_bytes: u64, sir: bool) -> Result<Gas> {
+        let data_cfg = &self.fees_config.data_receipt_creation_config;
+        let (regular_bytes, large_bytes) = match &data_cfg.large_data_cost {
+            Some(large_data_cost) if num_bytes > large_data_cost.threshold => {
+                (large_data_cost.threshold, num_bytes - large_data_cost.threshold)
+            }
+            _ => (num_bytes, 0),
+        };
+        let mut burn_gas = data_cfg
+            .cost_per_byte
+            .send_fee(sir)
+            .checked_add(data_cfg.cost_per_byte.exec_fee())
+            .ok_or(HostError::IntegerOverflow)?
+            .checked_mul(regular_bytes)
+            .ok_or(HostError::IntegerOverflow)?;
+        if let Some(large_data_cost) = &data_cfg.large_data_cost {
             burn_gas = burn_gas
                 .checked_add(
-                    data_cfg
+                    large_data_cost
                         .cost_per_byte
                         .send_fee(sir)
-                        .checked_add(data_cfg.cost_per_byte.exec_fee())
+                        .checked_add(large_data_cost.cost_per_byte.exec_fee())
                         .ok_or(HostError::IntegerOverflow)?
-                        .checked_mul(num_bytes)
+                        .checked_mul(large_bytes)
                         .ok_or(HostError::IntegerOverflow)?,
                 )
                 .ok_or(HostError::IntegerOverflow)?;
         }
-        self.gas_counter.pay_action_accumulated(burn_gas, burn_gas, ActionCosts::value_return)?;
-        self.return_data = ReturnData::Value(return_val);
-        Ok(())
+        Ok(burn_gas)
     }
 
     /// Terminates the execution of the program with panic `GuestPanic`.
@@ -2347,6 +2995,187 @@ impl<'a> VMLogic<'a> {
         }
     }
 
+    /// Writes key-value into storage without returning the old value.
+    /// * If key is not in use it inserts the key-value pair. Returns `0`;
+    /// * If key is in use it replaces the value. Returns `1`.
+    ///
+    /// Unlike `storage_write` the old value is not read, only its length which is needed for the
+    /// storage usage, so no cost per evicted byte is charged.
+    ///
+    /// # Errors
+    ///
+    /// * If `key_len + key_ptr` or `value_len + value_ptr` exceeds the memory container or points
+    ///   to an unused register it returns `MemoryAccessViolation`;
+    /// * If the length of the key exceeds `max_length_storage_key` returns `KeyLengthExceeded`.
+    /// * If the length of the value exceeds `max_length_storage_value` returns
+    ///   `ValueLengthExceeded`.
+    /// * If called as view function returns `ProhibitedInView``.
+    ///
+    /// # Cost
+    ///
+    /// `base + storage_write_base + storage_write_key_byte * num_key_bytes + storage_write_value_byte * num_value_bytes
+    /// + get_vec_from_memory_or_register_cost x 2`.
+    #[cfg(feature = "protocol_feature_storage_write_no_evict")]
+    pub fn storage_write_no_evict(
+        &mut self,
+        key_len: u64,
+        key_ptr: u64,
+        value_len: u64,
+        value_ptr: u64,
+    ) -> Result<u64> {
+        self.gas_counter.pay_base(base)?;
+        if self.context.is_view() {
+            return Err(HostError::ProhibitedInView {
+                method_name: "storage_write_no_evict".to_string(),
+            }
+            .into());
+        }
+        self.gas_counter.pay_base(storage_write_base)?;
+        let key = self.get_vec_from_memory_or_register(key_ptr, key_len)?;
+        if key.len() as u64 > self.config.limit_config.max_length_storage_key {
+            return Err(HostError::KeyLengthExceeded {
+                length: key.len() as u64,
+                limit: self.config.limit_config.max_length_storage_key,
+            }
+            .into());
+        }
+        let value = self.get_vec_from_memory_or_register(value_ptr, value_len)?;
+        if value.len() as u64 > self.config.limit_config.max_length_storage_value {
+            return Err(HostError::ValueLengthExceeded {
+                length: value.len() as u64,
+                limit: self.config.limit_config.max_length_storage_value,
+            }
+            .into());
+        }
+        self.gas_counter.pay_per(storage_write_key_byte, key.len() as u64)?;
+        self.gas_counter.pay_per(storage_write_value_byte, value.len() as u64)?;
+        let nodes_before = self.ext.get_touched_nodes_count();
+        let evicted_len = self.ext.storage_get(&key)?.map(|evicted_ptr| evicted_ptr.len());
+        self.gas_counter
+            .pay_per(touching_trie_node, self.ext.get_touched_nodes_count() - nodes_before)?;
+        self.ext.storage_set(&key, &value)?;
+        let storage_config = &self.fees_config.storage_usage_config;
+        match evicted_len {
+            Some(old_value_len) => {
+                // Inner value can't overflow, because the value length is limited.
+                self.current_storage_usage = self
+                    .current_storage_usage
+                    .checked_sub(old_value_len as u64)
+                    .ok_or(InconsistentStateError::IntegerOverflow)?;
+                // Inner value can't overflow, because the value length is limited.
+                self.current_storage_usage = self
+                    .current_storage_usage
+                    .checked_add(value.len() as u64)
+                    .ok_or(InconsistentStateError::IntegerOverflow)?;
+                Ok(1)
+            }
+            None => {
+                // Inner value can't overflow, because the key/value length is limited.
+                self.current_storage_usage = self
+                    .current_storage_usage
+                    .checked_add(
+                        value.len() as u64
+                            + key.len() as u64
+                            + storage_config.num_extra_bytes_record,
+                    )
+                    .ok_or(InconsistentStateError::IntegerOverflow)?;
+                Ok(0)
+            }
+        }
+    }
+
+    /// Writes key-value into storage if the current value of the key is the expected one.
+    /// * If the current value matches, writes the new value. Returns `1`;
+    /// * Otherwise leaves the storage as it is. Returns `0`.
+    ///
+    /// Passing `u64::MAX - 1` as `expected_len` expects the key to be absent, `expected_ptr` is
+    /// ignored then.  An empty expected value only matches a key with an empty value.
+    ///
+    /// # Errors
+    ///
+    /// * If `key_len + key_ptr`, `expected_len + expected_ptr` or `value_len + value_ptr` exceeds
+    ///   the memory container or points to an unused register it returns
+    ///   `MemoryAccessViolation`;
+    /// * If the length of the key exceeds `max_length_storage_key` returns `KeyLengthExceeded`.
+    /// * If the length of the value exceeds `max_length_storage_value` returns
+    ///   `ValueLengthExceeded`.
+    /// * If called as view function returns `ProhibitedInView``.
+    ///
+    /// # Cost
+    ///
+    /// `base + storage_read_base + storage_read_key_byte * num_key_bytes +
+    /// storage_read_value_byte * num_expected_bytes + get_vec_from_memory_or_register_cost x 3`.
+    ///
+    /// If the value is written it costs additional `storage_write_base + storage_write_key_byte *
+    /// num_key_bytes + storage_write_value_byte * num_value_bytes`.
+    #[cfg(feature = "protocol_feature_storage_compare_and_swap")]
+    pub fn storage_compare_and_swap(
+        &mut self,
+        key_len: u64,
+        key_ptr: u64,
+        expected_len: u64,
+        expected_ptr: u64,
+        value_len: u64,
+        value_ptr: u64,
+    ) -> Result<u64> {
+        self.gas_counter.pay_base(base)?;
+        if self.context.is_view() {
+            return Err(HostError::ProhibitedInView {
+                method_name: "storage_compare_and_swap".to_string(),
+            }
+            .into());
+        }
+        self.gas_counter.pay_base(storage_read_base)?;
+        let key = self.get_vec_from_memory_or_register(key_ptr, key_len)?;
+        if key.len() as u64 > self.config.limit_config.max_length_storage_key {
+            return Err(HostError::KeyLengthExceeded {
+                length: key.len() as u64,
+                limit: self.config.limit_config.max_length_storage_key,
+            }
+            .into());
+        }
+        let expected = if expected_len == u64::MAX - 1 {
+            None
+        } else {
+            Some(self.get_vec_from_memory_or_register(expected_ptr, expected_len)?)
+        };
+        let value = self.get_vec_from_memory_or_register(value_ptr, value_len)?;
+        if value.len() as u64 > self.config.limit_config.max_length_storage_value {
+            return Err(HostError::ValueLengthExceeded {
+                length: value.len() as u64,
+                limit: self.config.limit_config.max_length_storage_value,
+            }
+            .into());
+        }
+        self.gas_counter.pay_per(storage_read_key_byte, key.len() as u64)?;
+        // The current value is read at most up to the length of the expected one.
+        self.gas_counter
+            .pay_per(storage_read_value_byte, expected.as_ref().map_or(0, |e| e.len() as u64))?;
+        let nodes_before = self.ext.get_touched_nodes_count();
+        let swapped = self.ext.storage_compare_and_swap(&key, expected.as_deref(), &value);
+        self.gas_counter
+            .pay_per(touching_trie_node, self.ext.get_touched_nodes_count() - nodes_before)?;
+        if !swapped? {
+            return Ok(0);
+        }
+        self.gas_counter.pay_base(storage_write_base)?;
+        self.gas_counter.pay_per(storage_write_key_byte, key.len() as u64)?;
+        self.gas_counter.pay_per(storage_write_value_byte, value.len() as u64)?;
+        let storage_config = &self.fees_config.storage_usage_config;
+        // Inner values can't overflow, because the key/value length is limited.
+        self.current_storage_usage = match expected {
+            Some(old_value) => self
+                .current_storage_usage
+                .checked_sub(old_value.len() as u64)
+                .and_then(|usage| usage.checked_add(value.len() as u64)),
+            None => self.current_storage_usage.checked_add(
+                value.len() as u64 + key.len() as u64 + storage_config.num_extra_bytes_record,
+            ),
+        }
+        .ok_or(InconsistentStateError::IntegerOverflow)?;
+        Ok(1)
+    }
+
     fn deref_value<'s>(
         gas_counter: &mut GasCounter,
         cost_per_byte: ExtCosts,
@@ -2394,10 +3223,14 @@ impl<'a> VMLogic<'a> {
         let read = self.ext.storage_get(&key);
         self.gas_counter
             .pay_per(touching_trie_node, self.ext.get_touched_nodes_count() - nodes_before)?;
-        let read = Self::deref_value(&mut self.gas_counter, storage_read_value_byte, read?)?;
-        match read {
-            Some(value) => {
-                self.internal_write_register(register_id, value)?;
+        match read? {
+            Some(value_ptr) => {
+                let len = value_ptr.len() as u64;
+                self.gas_counter.pay_per(storage_read_value_byte, len)?;
+                // Values which don't fit the register are rejected before they are read.
+                let usage = self.internal_reserve_register(register_id, len)?;
+                self.registers.insert(register_id, value_ptr.deref()?);
+                self.registers_memory_usage = usage;
                 Ok(1)
             }
             None => Ok(0),
@@ -2498,6 +3331,99 @@ impl<'a> VMLogic<'a> {
         Ok(res? as u64)
     }
 
+    /// Counts the keys with the given prefix, including the ones written and not counting the
+    /// ones removed by this receipt so far.
+    ///
+    /// # Errors
+    ///
+    /// * If `prefix_len + prefix_ptr` exceeds the memory container it returns
+    ///   `MemoryAccessViolation`.
+    /// * If the length of the prefix exceeds `max_length_storage_key` returns
+    ///   `KeyLengthExceeded`.
+    /// * If more than `max_storage_key_count` keys have the prefix returns
+    ///   `StorageKeyCountExceeded`.
+    ///
+    /// # Cost
+    ///
+    /// `base + storage_has_key_base + storage_has_key_byte * num_prefix_bytes + cost of reading
+    /// prefix + touching_trie_node * (num_touched_nodes + num_counted_keys)`
+    #[cfg(feature = "protocol_feature_storage_key_count")]
+    pub fn storage_key_count(&mut self, prefix_len: u64, prefix_ptr: u64) -> Result<u64> {
+        self.gas_counter.pay_base(base)?;
+        self.gas_counter.pay_base(storage_has_key_base)?;
+        let prefix = self.get_vec_from_memory_or_register(prefix_ptr, prefix_len)?;
+        if prefix.len() as u64 > self.config.limit_config.max_length_storage_key {
+            return Err(HostError::KeyLengthExceeded {
+                length: prefix.len() as u64,
+                limit: self.config.limit_config.max_length_storage_key,
+            }
+            .into());
+        }
+        self.gas_counter.pay_per(storage_has_key_byte, prefix.len() as u64)?;
+        let limit = self.config.limit_config.max_storage_key_count.unwrap_or(0);
+        let nodes_before = self.ext.get_touched_nodes_count();
+        let count = self.ext.storage_key_count(&prefix, limit);
+        self.gas_counter
+            .pay_per(touching_trie_node, self.ext.get_touched_nodes_count() - nodes_before)?;
+        // Keys changed by this receipt don't touch trie nodes, but have to be paid for as well.
+        match count? {
+            Some(count) => {
+                self.gas_counter.pay_per(touching_trie_node, count)?;
+                Ok(count)
+            }
+            None => {
+                self.gas_counter.pay_per(touching_trie_node, limit)?;
+                Err(HostError::StorageKeyCountExceeded { limit }.into())
+            }
+        }
+    }
+
+    /// Returns the length of the value stored under the given key without reading the value.
+    /// * If key is used returns the length of its value, which can be zero;
+    /// * Otherwise returns `u64::MAX`.
+    ///
+    /// # Errors
+    ///
+    /// * If `key_len + key_ptr` exceeds the memory container it returns `MemoryAccessViolation`.
+    /// * If the length of the key exceeds `max_length_storage_key` returns `KeyLengthExceeded`.
+    ///
+    /// # Cost
+    ///
+    /// `base + storage_read_base + storage_read_key_byte * num_key_bytes + cost of reading key +
+    /// touching_trie_node * num_touched_nodes`
+    #[cfg(feature = "protocol_feature_storage_value_len")]
+    pub fn storage_value_len(&mut self, key_len: u64, key_ptr: u64) -> Result<u64> {
+        self.gas_counter.pay_base(base)?;
+        self.gas_counter.pay_base(storage_read_base)?;
+        let key = self.get_vec_from_memory_or_register(key_ptr, key_len)?;
+        if key.len() as u64 > self.config.limit_config.max_length_storage_key {
+            return Err(HostError::KeyLengthExceeded {
+                length: key.len() as u64,
+                limit: self.config.limit_config.max_length_storage_key,
+            }
+            .into());
+        }
+        self.gas_counter.pay_per(storage_read_key_byte, key.len() as u64)?;
+        let nodes_before = self.ext.get_touched_nodes_count();
+        let res = self.ext.storage_value_len(&key);
+        self.gas_counter
+            .pay_per(touching_trie_node, self.ext.get_touched_nodes_count() - nodes_before)?;
+        Ok(res?.map_or(u64::MAX, u64::from))
+    }
+
+    /// Returns the size in bytes of trie nodes the current receipt has added to the storage
+    /// proof so far. The size never decreases during the execution of a receipt and is the same
+    /// whether the node records the proof, re-executes the receipt from it or doesn't record it.
+    ///
+    /// # Cost
+    ///
+    /// `base`
+    #[cfg(feature = "protocol_feature_per_receipt_storage_proof_limit")]
+    pub fn recorded_storage_size(&mut self) -> Result<u64> {
+        self.gas_counter.pay_base(base)?;
+        Ok(self.ext.recorded_storage_size())
+    }
+
     /// Debug print given utf-8 string to node log. It's only available in Sandbox node
     ///
     /// # Errors
@@ -2626,6 +3552,7 @@ impl<'a> VMLogic<'a> {
 
         let burnt_gas = self.gas_counter.burnt_gas();
         let used_gas = self.gas_counter.used_gas();
+        let compute_usage = self.gas_counter.compute_usage();
 
         let mut profile = self.gas_counter.profile_data();
         profile.compute_wasm_instruction_cost(burnt_gas);
@@ -2636,6 +3563,7 @@ impl<'a> VMLogic<'a> {
             return_data: self.return_data,
             burnt_gas,
             used_gas,
+            compute_usage,
             logs: self.logs,
             profile,
         }
@@ -2667,6 +3595,9 @@ pub struct VMOutcome {
     pub return_data: ReturnData,
     pub burnt_gas: Gas,
     pub used_gas: Gas,
+    /// The burnt gas with the host function costs counted at their compute cost, see
+    /// `ExtCostsConfig::compute_costs`.
+    pub compute_usage: Compute,
     pub logs: Vec<String>,
     /// Data collected from making a contract call
     pub profile: ProfileData,
@@ -2686,3 +3617,8 @@ impl std::fmt::Debug for VMOutcome {
         )
     }
 }
+
+/// Memory accounted for a register holding `len` bytes.
+fn register_memory(len: u64) -> u64 {
+    size_of::<u64>() as u64 + len
+}
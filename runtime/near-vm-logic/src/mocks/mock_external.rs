@@ -1,10 +1,13 @@
 use crate::{External, ValuePtr};
 #[cfg(feature = "protocol_feature_function_call_weight")]
 use near_primitives::types::{GasDistribution, GasWeight};
-use near_primitives_core::types::{AccountId, Balance, Gas};
+use near_primitives_core::hash::CryptoHash;
+use near_primitives_core::types::{AccountId, Balance, Gas, ShardId};
 use near_vm_errors::HostError;
 use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use std::collections::HashMap;
+use std::rc::Rc;
 
 #[derive(Default, Clone)]
 /// Emulates the trie and the mock handling code.
@@ -12,8 +15,31 @@ pub struct MockedExternal {
     pub fake_trie: HashMap<Vec<u8>, Vec<u8>>,
     receipts: Vec<Receipt>,
     pub validators: HashMap<AccountId, Balance>,
+    /// Whether validator lookups are reported as cached, the mock doesn't cache them itself.
+    pub validator_info_cached: bool,
+    #[cfg(feature = "protocol_feature_locked_balance_of")]
+    pub locked_balances: HashMap<AccountId, Balance>,
+    pub gas_price: Balance,
+    pub block_gas_price: Balance,
+    pub storage_amount_per_byte: Balance,
+    pub promise_results_gas_burnt: Vec<Option<Gas>>,
+    pub epoch_id: CryptoHash,
+    pub shard_id: ShardId,
+    pub receipt_depth: u64,
+    /// Number of values read through the pointers returned by `storage_get`.
+    pub num_value_derefs: Rc<Cell<u64>>,
+    #[cfg(feature = "protocol_feature_per_receipt_storage_proof_limit")]
+    pub recorded_storage_size: u64,
     #[cfg(feature = "protocol_feature_function_call_weight")]
     gas_weights: Vec<(FunctionCallActionIndex, GasWeight)>,
+    /// Generated data ids with the receiver of the receipt depending on them, if any.
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    pub registered_data_ids: HashMap<CryptoHash, Option<AccountId>>,
+    /// Data sent with `resume_data`.
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    pub resumed_data: Vec<(CryptoHash, Vec<u8>)>,
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    data_count: u64,
 }
 
 #[derive(Clone)]
@@ -25,6 +51,7 @@ struct FunctionCallActionIndex {
 
 pub struct MockedValuePtr {
     value: Vec<u8>,
+    num_derefs: Rc<Cell<u64>>,
 }
 
 impl MockedValuePtr {
@@ -32,7 +59,7 @@ impl MockedValuePtr {
     where
         T: AsRef<[u8]>,
     {
-        MockedValuePtr { value: value.as_ref().to_vec() }
+        MockedValuePtr { value: value.as_ref().to_vec(), num_derefs: Default::default() }
     }
 }
 
@@ -42,6 +69,7 @@ impl ValuePtr for MockedValuePtr {
     }
 
     fn deref(&self) -> crate::dependencies::Result<Vec<u8>> {
+        self.num_derefs.set(self.num_derefs.get() + 1);
         Ok(self.value.clone())
     }
 }
@@ -67,10 +95,12 @@ impl External for MockedExternal {
     }
 
     fn storage_get(&self, key: &[u8]) -> Result<Option<Box<dyn ValuePtr>>> {
-        Ok(self
-            .fake_trie
-            .get(key)
-            .map(|value| Box::new(MockedValuePtr { value: value.clone() }) as Box<_>))
+        Ok(self.fake_trie.get(key).map(|value| {
+            Box::new(MockedValuePtr {
+                value: value.clone(),
+                num_derefs: self.num_value_derefs.clone(),
+            }) as Box<_>
+        }))
     }
 
     fn storage_remove(&mut self, key: &[u8]) -> Result<()> {
@@ -87,15 +117,87 @@ impl External for MockedExternal {
         Ok(self.fake_trie.contains_key(key))
     }
 
+    #[cfg(feature = "protocol_feature_storage_value_len")]
+    fn storage_value_len(&mut self, key: &[u8]) -> Result<Option<u32>> {
+        Ok(self.fake_trie.get(key).map(|value| value.len() as u32))
+    }
+
+    #[cfg(feature = "protocol_feature_storage_key_count")]
+    fn storage_key_count(&mut self, prefix: &[u8], limit: u64) -> Result<Option<u64>> {
+        let count = self.fake_trie.keys().filter(|key| key.starts_with(prefix)).count() as u64;
+        Ok(if count > limit { None } else { Some(count) })
+    }
+
+    #[cfg(feature = "protocol_feature_storage_compare_and_swap")]
+    fn storage_compare_and_swap(
+        &mut self,
+        key: &[u8],
+        expected: Option<&[u8]>,
+        value: &[u8],
+    ) -> Result<bool> {
+        if self.fake_trie.get(key).map(Vec::as_slice) != expected {
+            return Ok(false);
+        }
+        self.fake_trie.insert(key.to_vec(), value.to_vec());
+        Ok(true)
+    }
+
     fn create_receipt(&mut self, receipt_indices: Vec<u64>, receiver_id: AccountId) -> Result<u64> {
         if let Some(index) = receipt_indices.iter().find(|&&el| el >= self.receipts.len() as u64) {
             return Err(HostError::InvalidReceiptIndex { receipt_index: *index }.into());
         }
         let res = self.receipts.len() as u64;
-        self.receipts.push(Receipt { receipt_indices, receiver_id, actions: vec![] });
+        self.receipts.push(Receipt {
+            receipt_indices,
+            receiver_id,
+            actions: vec![],
+            #[cfg(feature = "protocol_feature_promise_data_dependency")]
+            input_data_ids: vec![],
+        });
         Ok(res)
     }
 
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    fn generate_data_id(&mut self) -> Result<CryptoHash> {
+        let data_id = CryptoHash::hash_bytes(&self.data_count.to_le_bytes());
+        self.data_count += 1;
+        self.registered_data_ids.insert(data_id, None);
+        Ok(data_id)
+    }
+
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    fn create_receipt_with_data_dependency(
+        &mut self,
+        data_id: CryptoHash,
+        receiver_id: AccountId,
+    ) -> Result<u64> {
+        match self.registered_data_ids.get_mut(&data_id) {
+            Some(receiver @ None) => *receiver = Some(receiver_id.clone()),
+            _ => return Err(HostError::InvalidDataId.into()),
+        }
+        let res = self.receipts.len() as u64;
+        self.receipts.push(Receipt {
+            receipt_indices: vec![],
+            receiver_id,
+            actions: vec![],
+            input_data_ids: vec![data_id],
+        });
+        Ok(res)
+    }
+
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    fn resume_data(&mut self, data_id: CryptoHash, data: Vec<u8>) -> Result<AccountId> {
+        match self.registered_data_ids.get(&data_id) {
+            Some(Some(receiver_id)) => {
+                let receiver_id = receiver_id.clone();
+                self.registered_data_ids.remove(&data_id);
+                self.resumed_data.push((data_id, data));
+                Ok(receiver_id)
+            }
+            _ => Err(HostError::InvalidDataId.into()),
+        }
+    }
+
     fn append_action_create_account(&mut self, receipt_index: u64) -> Result<()> {
         self.receipts.get_mut(receipt_index as usize).unwrap().actions.push(Action::CreateAccount);
         Ok(())
@@ -241,6 +343,11 @@ impl External for MockedExternal {
         0
     }
 
+    #[cfg(feature = "protocol_feature_per_receipt_storage_proof_limit")]
+    fn recorded_storage_size(&self) -> u64 {
+        self.recorded_storage_size
+    }
+
     fn validator_stake(&self, account_id: &AccountId) -> Result<Option<Balance>> {
         Ok(self.validators.get(account_id).cloned())
     }
@@ -249,6 +356,49 @@ impl External for MockedExternal {
         Ok(self.validators.values().sum())
     }
 
+    fn validator_stake_cached(&self, _account_id: &AccountId) -> bool {
+        self.validator_info_cached
+    }
+
+    fn validator_total_stake_cached(&self) -> bool {
+        self.validator_info_cached
+    }
+
+    #[cfg(feature = "protocol_feature_locked_balance_of")]
+    fn locked_balance_of(&self, account_id: &AccountId) -> Result<Balance> {
+        self.locked_balances.get(account_id).cloned().ok_or_else(|| {
+            HostError::AccountDoesNotExist { account_id: account_id.to_string() }.into()
+        })
+    }
+
+    fn gas_price(&self) -> Balance {
+        self.gas_price
+    }
+
+    fn block_gas_price(&self) -> Balance {
+        self.block_gas_price
+    }
+
+    fn storage_amount_per_byte(&self) -> Balance {
+        self.storage_amount_per_byte
+    }
+
+    fn promise_result_gas_burnt(&self, result_idx: u64) -> Option<Gas> {
+        self.promise_results_gas_burnt.get(result_idx as usize).copied().flatten()
+    }
+
+    fn epoch_id(&self) -> CryptoHash {
+        self.epoch_id
+    }
+
+    fn shard_id(&self) -> ShardId {
+        self.shard_id
+    }
+
+    fn receipt_depth(&self) -> u64 {
+        self.receipt_depth
+    }
+
     /// Distributes the gas passed in by splitting it among weights defined in `gas_weights`.
     /// This will sum all weights, retrieve the gas per weight, then update each function
     /// to add the respective amount of gas. Once all gas is distributed, the remainder of
@@ -299,6 +449,29 @@ impl External for MockedExternal {
             GasDistribution::NoRatios
         }
     }
+
+    #[cfg(feature = "protocol_feature_promise_action_min_gas")]
+    fn function_call_gas(&self, receipt_index: u64, action_index: u64) -> Option<(Gas, GasWeight)> {
+        let receipt_index = receipt_index as usize;
+        let action_index = action_index as usize;
+        let gas = match self.receipts.get(receipt_index)?.actions.get(action_index)? {
+            Action::FunctionCall(FunctionCallAction { gas, .. }) => *gas,
+            _ => return None,
+        };
+        let gas_weight = self
+            .gas_weights
+            .iter()
+            .find(|(index, _)| {
+                index.receipt_index == receipt_index && index.action_index == action_index
+            })
+            .map_or(GasWeight(0), |(_, gas_weight)| gas_weight.clone());
+        Some((gas, gas_weight))
+    }
+
+    #[cfg(feature = "protocol_feature_promise_action_min_gas")]
+    fn gas_weight_sum(&self) -> u128 {
+        self.gas_weights.iter().map(|(_, GasWeight(weight))| *weight as u128).sum()
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -306,6 +479,9 @@ pub struct Receipt {
     receipt_indices: Vec<u64>,
     receiver_id: AccountId,
     actions: Vec<Action>,
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    input_data_ids: Vec<CryptoHash>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -104,3 +104,141 @@ decl_test_u128!(
     create_context().account_locked_balance
 );
 decl_test_u128!(test_attached_deposit, attached_deposit, create_context().attached_deposit);
+
+#[cfg(feature = "protocol_feature_gas_price_host_functions")]
+#[test]
+fn test_gas_prices() {
+    let mut logic_builder = VMLogicBuilder::default();
+    logic_builder.ext.gas_price = 200;
+    logic_builder.ext.block_gas_price = 100;
+    let mut logic = logic_builder.build(create_context());
+    let buf = [0u8; std::mem::size_of::<u128>()];
+
+    logic.gas_price(0).expect("read gas price should be ok");
+    logic.read_register(0, buf.as_ptr() as _).expect("read register should be ok");
+    assert_eq!(u128::from_le_bytes(buf), 200);
+
+    logic.block_gas_price(0).expect("read block gas price should be ok");
+    logic.read_register(0, buf.as_ptr() as _).expect("read register should be ok");
+    assert_eq!(u128::from_le_bytes(buf), 100);
+}
+
+#[cfg(feature = "protocol_feature_epoch_and_shard_id")]
+#[test]
+fn test_epoch_id_and_shard_id() {
+    let epoch_id = near_primitives_core::hash::hash(b"epoch");
+    let mut logic_builder = VMLogicBuilder::default();
+    logic_builder.ext.epoch_id = epoch_id;
+    logic_builder.ext.shard_id = 3;
+    let mut logic = logic_builder.build(create_context());
+    let buf = [0u8; 32];
+
+    logic.epoch_id(0).expect("read epoch id should be ok");
+    logic.read_register(0, buf.as_ptr() as _).expect("read register should be ok");
+    assert_eq!(&buf, epoch_id.as_ref());
+    assert_eq!(logic.shard_id(), Ok(3));
+}
+
+#[cfg(feature = "protocol_feature_receipt_depth")]
+#[test]
+fn test_receipt_depth() {
+    let mut logic_builder = VMLogicBuilder::default();
+    logic_builder.ext.receipt_depth = 5;
+    let mut logic = logic_builder.build(create_context());
+    assert_eq!(logic.receipt_depth(), Ok(5));
+}
+
+#[cfg(feature = "protocol_feature_validator_info_cache")]
+#[test]
+fn test_cached_validator_info_skips_lookup_cost() {
+    let burnt_gas = |cached: bool| {
+        let mut logic_builder = VMLogicBuilder::default();
+        logic_builder.ext.validators.insert("alice".parse().unwrap(), 100);
+        logic_builder.ext.validator_info_cached = cached;
+        let mut logic = logic_builder.build(create_context());
+        let account_id = b"alice";
+        let buf = [0u8; std::mem::size_of::<u128>()];
+
+        logic
+            .validator_stake(account_id.len() as _, account_id.as_ptr() as _, buf.as_ptr() as _)
+            .expect("read validator stake should be ok");
+        assert_eq!(u128::from_le_bytes(buf), 100);
+        logic.validator_total_stake(buf.as_ptr() as _).expect("read total stake should be ok");
+        assert_eq!(u128::from_le_bytes(buf), 100);
+        logic.compute_outcome_and_distribute_gas().burnt_gas
+    };
+
+    let ext_costs = VMLogicBuilder::default().config.ext_costs;
+    assert_eq!(
+        burnt_gas(false) - burnt_gas(true),
+        ext_costs.validator_stake_base + ext_costs.validator_total_stake_base
+    );
+}
+
+#[cfg(feature = "protocol_feature_locked_balance_of")]
+#[test]
+fn test_locked_balance_of() {
+    use near_vm_errors::{HostError, VMLogicError};
+
+    let mut logic_builder = VMLogicBuilder::default();
+    logic_builder.ext.locked_balances.insert("validator".parse().unwrap(), 100);
+    let mut logic = logic_builder.build(create_context());
+    let buf = [0u8; std::mem::size_of::<u128>()];
+
+    let account_id = b"validator";
+    logic
+        .locked_balance_of(account_id.len() as _, account_id.as_ptr() as _, 0)
+        .expect("read locked balance should be ok");
+    logic.read_register(0, buf.as_ptr() as _).expect("read register should be ok");
+    assert_eq!(u128::from_le_bytes(buf), 100);
+
+    let account_id = b"missing";
+    assert_eq!(
+        logic.locked_balance_of(account_id.len() as _, account_id.as_ptr() as _, 0),
+        Err(VMLogicError::HostError(HostError::AccountDoesNotExist {
+            account_id: "missing".to_string()
+        }))
+    );
+}
+
+#[cfg(feature = "protocol_feature_storage_config_host_functions")]
+#[test]
+fn test_storage_config() {
+    let mut logic_builder = VMLogicBuilder::default();
+    logic_builder.ext.storage_amount_per_byte = 10u128.pow(19);
+    let mut logic = logic_builder.build(create_context());
+    let buf = [0u8; std::mem::size_of::<u128>()];
+
+    logic.storage_byte_cost(0).expect("read storage byte cost should be ok");
+    logic.read_register(0, buf.as_ptr() as _).expect("read register should be ok");
+    assert_eq!(u128::from_le_bytes(buf), 10u128.pow(19));
+
+    logic.min_account_balance(0).expect("read min account balance should be ok");
+    logic.read_register(0, buf.as_ptr() as _).expect("read register should be ok");
+    assert_eq!(u128::from_le_bytes(buf), 12 * 10u128.pow(19));
+}
+
+#[cfg(feature = "protocol_feature_input_read")]
+#[test]
+fn test_input_read() {
+    use near_vm_errors::{HostError, VMLogicError};
+
+    let mut logic_builder = VMLogicBuilder::default();
+    let mut logic = logic_builder.build(create_context());
+    let input = create_context().input;
+    assert_eq!(logic.input_len(), Ok(input.len() as u64));
+
+    let buf = [0u8; 3];
+    logic.input_read(1, 3, buf.as_ptr() as _).expect("read input slice should be ok");
+    assert_eq!(&buf, &input[1..4]);
+
+    logic.input_read(input.len() as _, 0, buf.as_ptr() as _).expect("empty read should be ok");
+    assert_eq!(
+        logic.input_read(3, 3, buf.as_ptr() as _),
+        Err(VMLogicError::HostError(HostError::MemoryAccessViolation))
+    );
+    assert_eq!(
+        logic.input_read(u64::MAX, 2, buf.as_ptr() as _),
+        Err(VMLogicError::HostError(HostError::MemoryAccessViolation))
+    );
+}
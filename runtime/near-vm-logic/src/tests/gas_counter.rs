@@ -185,6 +185,95 @@ fn function_call_no_weight_refund() {
     assert!(outcome.used_gas < gas_limit);
 }
 
+#[cfg(feature = "protocol_feature_promise_action_min_gas")]
+#[test]
+fn function_call_weight_min_gas() {
+    use near_primitives::types::GasWeight;
+    use near_vm_errors::HostError;
+
+    let gas_limit = 10u64.pow(14);
+
+    let mut logic_builder = VMLogicBuilder::free();
+    let mut logic = logic_builder.build_with_prepaid_gas(gas_limit);
+
+    // A callback without static gas and with the default weight gets all the leftover gas.
+    let first = promise_batch_create(&mut logic, "rick.test").expect("should create a promise");
+    promise_batch_action_function_call_weight(&mut logic, first, 0, 0, GasWeight(1))
+        .expect("batch action function call should succeed");
+    assert_eq!(logic.promise_action_min_gas(first, 0), Ok(gas_limit));
+
+    // Another one with the same weight halves its share.
+    let second = promise_batch_create(&mut logic, "rick.test").expect("should create a promise");
+    promise_batch_action_function_call_weight(&mut logic, second, 0, 1000, GasWeight(1))
+        .expect("batch action function call should succeed");
+    let share = (gas_limit - 1000) / 2;
+    assert_eq!(logic.promise_action_min_gas(first, 0), Ok(share));
+    assert_eq!(logic.promise_action_min_gas(second, 0), Ok(1000 + share));
+
+    assert_eq!(
+        logic.promise_action_min_gas(first, 1),
+        Err(HostError::InvalidFunctionCallActionIndex { action_index: 1 }.into())
+    );
+}
+
+#[cfg(feature = "protocol_feature_function_call_weight")]
+#[test]
+fn function_call_weight_no_leftover_gas() {
+    use near_primitives::types::GasWeight;
+
+    let gas_limit = 10u64.pow(14);
+
+    let mut logic_builder = VMLogicBuilder::free();
+    let mut logic = logic_builder.build_with_prepaid_gas(gas_limit);
+
+    let index = promise_batch_create(&mut logic, "rick.test").expect("should create a promise");
+    promise_batch_action_function_call_weight(&mut logic, index, 0, gas_limit, GasWeight(1))
+        .expect("batch action function call should succeed");
+    #[cfg(feature = "protocol_feature_promise_action_min_gas")]
+    assert_eq!(logic.promise_action_min_gas(index, 0), Ok(gas_limit));
+
+    let outcome = logic.compute_outcome_and_distribute_gas();
+    assert_eq!(outcome.used_gas, gas_limit);
+
+    // The call is still scheduled, with its static gas only.
+    let receipts = serde_json::to_value(logic_builder.ext.get_receipt_create_calls()).unwrap();
+    assert_eq!(receipts[0]["actions"][0]["FunctionCall"]["gas"], gas_limit);
+}
+
+#[cfg(feature = "protocol_feature_gas_budget_host_functions")]
+#[test]
+fn test_attach_remaining_gas() {
+    use near_vm_errors::HostError;
+
+    let gas_limit = 10u64.pow(14);
+    // The method name and arguments of `promise_batch_action_function_call`.
+    let payload_len = (b"promise_batch_action".len() + b"promise_batch_action_args".len()) as u64;
+    // To the current account and to another one.
+    for account_id in ["alice.near", "rick.test"] {
+        let mut logic_builder = VMLogicBuilder::default().max_gas_burnt(gas_limit);
+        let mut logic = logic_builder.build_with_prepaid_gas(gas_limit);
+
+        let index = promise_batch_create(&mut logic, account_id).expect("should create a promise");
+        let fee = logic.action_fee_estimate(2, payload_len).unwrap();
+        let gas = logic.remaining_gas().unwrap() - fee;
+        promise_batch_action_function_call(&mut logic, index, 0, gas)
+            .expect("the fees should be covered by the estimate");
+        let outcome = logic.compute_outcome_and_distribute_gas();
+        assert!(outcome.used_gas <= gas_limit);
+        assert!(outcome.used_gas > gas_limit - fee);
+    }
+
+    let mut logic_builder = VMLogicBuilder::default();
+    let mut logic = logic_builder.build_with_prepaid_gas(gas_limit);
+    assert!(
+        logic.action_fee_estimate(1, 100).unwrap() < logic.action_fee_estimate(1, 101).unwrap()
+    );
+    assert_eq!(
+        logic.action_fee_estimate(9, 0),
+        Err(HostError::InvalidActionKind { action_kind: 9 }.into())
+    );
+}
+
 impl VMLogicBuilder {
     fn max_gas_burnt(mut self, max_gas_burnt: Gas) -> Self {
         self.config.limit_config.max_gas_burnt = max_gas_burnt;
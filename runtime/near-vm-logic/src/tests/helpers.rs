@@ -73,7 +73,7 @@ pub fn promise_batch_action_function_call_weight(
         args.as_ptr() as _,
         amount.to_le_bytes().as_ptr() as _,
         gas,
-        ratio,
+        ratio.0,
     )
 }
 
@@ -1,8 +1,10 @@
 use crate::tests::fixtures::get_context;
 use crate::tests::helpers::*;
 use crate::tests::vm_logic_builder::VMLogicBuilder;
+use crate::types::Gas;
 use crate::{map, ExtCosts};
 use hex::FromHex;
+use near_primitives_core::runtime::fees::{Fee, LargeDataCost, RuntimeFeesConfig};
 use near_vm_errors::HostError;
 use serde::{de::Error, Deserialize, Deserializer};
 use serde_json::from_slice;
@@ -832,6 +834,67 @@ fn test_return_value_limit() {
     );
 }
 
+/// Returns a builder charging only the data receipts of returned values, where the bytes above
+/// 1024 cost `large_cost` each, and the regular cost per byte.
+fn large_data_cost_logic_builder(large_cost: Gas) -> (VMLogicBuilder, Gas) {
+    let mut logic_builder = VMLogicBuilder::free();
+    logic_builder.fees_config = RuntimeFeesConfig::test();
+    let data_cfg = &mut logic_builder.fees_config.data_receipt_creation_config;
+    let regular_cost = data_cfg.cost_per_byte.send_fee(false) + data_cfg.cost_per_byte.exec_fee();
+    data_cfg.large_data_cost = Some(LargeDataCost {
+        threshold: 1024,
+        cost_per_byte: Fee { send_sir: 0, send_not_sir: large_cost / 2, execution: large_cost / 2 },
+    });
+    (logic_builder, regular_cost)
+}
+
+#[test]
+fn test_value_return_large_data_cost() {
+    let large_cost = 400_000_000;
+    let (mut logic_builder, regular_cost) = large_data_cost_logic_builder(large_cost);
+    let mut context = get_context(vec![], false);
+    context.output_data_receivers = vec!["bob.near".parse().unwrap()];
+
+    // Only the bytes above the threshold are charged the large data cost.
+    for (length, expected_cost) in [
+        (1023, 1023 * regular_cost),
+        (1024, 1024 * regular_cost),
+        (1025, 1024 * regular_cost + large_cost),
+    ] {
+        let mut logic = logic_builder.build(context.clone());
+        let val = vec![0u8; length];
+        logic.value_return(val.len() as _, val.as_ptr() as _).expect("enough gas");
+        assert_eq!(logic.compute_outcome_and_distribute_gas().burnt_gas, expected_cost);
+    }
+}
+
+#[cfg(feature = "protocol_feature_large_data_receipt_cost")]
+#[test]
+fn test_value_return_large_data_gas_exceeded() {
+    let large_cost = 400_000_000;
+    let (mut logic_builder, regular_cost) = large_data_cost_logic_builder(large_cost);
+    let mut context = get_context(vec![], false);
+    context.output_data_receivers = vec!["bob.near".parse().unwrap()];
+    context.prepaid_gas = 1024 * regular_cost + large_cost;
+
+    let mut logic = logic_builder.build(context.clone());
+    let val = vec![0u8; 1025];
+    logic.value_return(val.len() as _, val.as_ptr() as _).expect("exactly enough gas");
+
+    let mut logic = logic_builder.build(context.clone());
+    let val = vec![0u8; 1026];
+    assert_eq!(
+        logic.value_return(val.len() as _, val.as_ptr() as _),
+        Err(HostError::ReturnedValueGasExceeded {
+            length: 1026,
+            cost: 1024 * regular_cost + 2 * large_cost,
+            remaining: context.prepaid_gas,
+        }
+        .into())
+    );
+    assert_eq!(logic.compute_outcome_and_distribute_gas().burnt_gas, 0);
+}
+
 #[test]
 fn test_contract_size_limit() {
     let mut logic_builder = VMLogicBuilder::default();
@@ -25,6 +25,25 @@ fn test_promise_results() {
     assert_eq!(&buffer, b"test", "Only promise with result should write data into register");
 }
 
+#[cfg(feature = "protocol_feature_promise_result_gas_burnt")]
+#[test]
+fn test_promise_result_gas_burnt() {
+    use near_vm_errors::HostError;
+
+    let mut logic_builder = VMLogicBuilder::default();
+    logic_builder.promise_results = vec![PromiseResult::Successful(vec![]), PromiseResult::Failed];
+    logic_builder.ext.promise_results_gas_burnt = vec![Some(100), None];
+    let mut logic = logic_builder.build(get_context(vec![], false));
+
+    assert_eq!(logic.promise_result_gas_burnt(0), Ok(100));
+    // Data sent without the gas burnt is reported with a sentinel.
+    assert_eq!(logic.promise_result_gas_burnt(1), Ok(u64::MAX));
+    assert_eq!(
+        logic.promise_result_gas_burnt(2),
+        Err(HostError::InvalidPromiseResultIndex { result_idx: 2 }.into())
+    );
+}
+
 #[test]
 fn test_promise_batch_action_function_call() {
     let mut logic_builder = VMLogicBuilder::default();
@@ -418,3 +437,114 @@ fn test_promise_batch_then() {
         &expected.to_string()
     );
 }
+
+#[cfg(feature = "protocol_feature_promise_data_dependency")]
+fn promise_create_with_data_dependency(
+    logic: &mut crate::VMLogic<'_>,
+    data_id: &[u8],
+    account_id: &[u8],
+) -> crate::logic::Result<u64> {
+    let method = b"promise_create";
+    let args = b"args";
+    let amount = 0u128;
+    logic.promise_create_with_data_dependency(
+        data_id.as_ptr() as _,
+        account_id.len() as _,
+        account_id.as_ptr() as _,
+        method.len() as _,
+        method.as_ptr() as _,
+        args.len() as _,
+        args.as_ptr() as _,
+        amount.to_le_bytes().as_ptr() as _,
+        0,
+    )
+}
+
+#[cfg(feature = "protocol_feature_promise_data_dependency")]
+#[test]
+fn test_promise_create_with_data_dependency() {
+    use near_vm_errors::{HostError, VMLogicError};
+
+    let mut logic_builder = VMLogicBuilder::default();
+    let mut logic = logic_builder.build(get_context(vec![], false));
+    let account_id = b"rick.test";
+
+    promise_create_with_data_dependency(&mut logic, &[7u8; 32], account_id)
+        .expect_err("shouldn't accept a data id which wasn't generated");
+
+    logic.generate_data_id(0).expect("should generate a data id");
+    let data_id = [0u8; 32];
+    logic.read_register(0, data_id.as_ptr() as _).unwrap();
+    let index = promise_create_with_data_dependency(&mut logic, &data_id, account_id)
+        .expect("should create a promise");
+    assert_eq!(index, 0);
+    assert_eq!(
+        promise_create_with_data_dependency(&mut logic, &data_id, account_id),
+        Err(VMLogicError::HostError(HostError::InvalidDataId)),
+        "data id can only be used once"
+    );
+
+    let expected = serde_json::json!([
+        {
+            "receipt_indices": [],
+            "receiver_id": "rick.test",
+            "actions": [
+                {
+                    "FunctionCall": {
+                        "method_name": "promise_create",
+                        "args": "args",
+                        "gas": 0,
+                        "deposit": 0
+                    }
+                }
+            ],
+            "input_data_ids": [near_primitives_core::hash::CryptoHash(data_id).to_string()]
+        }
+    ]);
+    assert_eq!(
+        &serde_json::to_string(logic_builder.ext.get_receipt_create_calls()).unwrap(),
+        &expected.to_string()
+    );
+}
+
+#[cfg(feature = "protocol_feature_promise_data_dependency")]
+#[test]
+fn test_promise_resume_data() {
+    use near_vm_errors::{HostError, VMLogicError};
+
+    let mut logic_builder = VMLogicBuilder::default();
+    let mut logic = logic_builder.build(get_context(vec![], false));
+    let payload = b"payload";
+    let resume = |logic: &mut crate::VMLogic<'_>, data_id: &[u8]| {
+        logic.promise_resume_data(data_id.as_ptr() as _, payload.len() as _, payload.as_ptr() as _)
+    };
+
+    logic.generate_data_id(0).expect("should generate a data id");
+    let data_id = [0u8; 32];
+    logic.read_register(0, data_id.as_ptr() as _).unwrap();
+    assert_eq!(
+        resume(&mut logic, &data_id),
+        Err(VMLogicError::HostError(HostError::InvalidDataId)),
+        "no promise depends on the data id yet"
+    );
+    promise_create_with_data_dependency(&mut logic, &data_id, b"rick.test")
+        .expect("should create a promise");
+    resume(&mut logic, &data_id).expect("should send the data");
+    assert_eq!(
+        resume(&mut logic, &data_id),
+        Err(VMLogicError::HostError(HostError::InvalidDataId)),
+        "data can only be sent once"
+    );
+    assert_eq!(
+        logic_builder.ext.resumed_data,
+        vec![(near_primitives_core::hash::CryptoHash(data_id), payload.to_vec())]
+    );
+}
+
+#[cfg(feature = "protocol_feature_promise_data_dependency")]
+#[test]
+fn test_generate_data_id_prohibited_in_view() {
+    let mut logic_builder = VMLogicBuilder::default();
+    let mut logic = logic_builder.build(get_context(vec![], true));
+    logic.generate_data_id(0).expect_err("view calls can't generate data ids");
+}
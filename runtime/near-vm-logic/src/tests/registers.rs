@@ -85,6 +85,43 @@ fn test_max_register_memory_limit() {
     );
 }
 
+#[test]
+fn test_registers_memory_limit_is_exact() {
+    let mut logic_builder = VMLogicBuilder::free();
+    // Every register is accounted for 8 bytes on top of its value.
+    logic_builder.config.limit_config.registers_memory_limit = 3 * (8 + 10);
+    let mut logic = logic_builder.build(get_context(vec![], false));
+
+    for i in 0..3 {
+        logic.wrapped_internal_write_register(i, &[0u8; 10]).expect("should fit the limit");
+    }
+    assert_eq!(
+        logic.wrapped_internal_write_register(3, &[]),
+        Err(HostError::MemoryAccessViolation.into())
+    );
+    assert_eq!(logic.register_len(3), Ok(u64::MAX));
+}
+
+#[test]
+fn test_registers_memory_released_on_overwrite() {
+    let mut logic_builder = VMLogicBuilder::free();
+    logic_builder.config.limit_config.registers_memory_limit = 2 * (8 + 10);
+    let mut logic = logic_builder.build(get_context(vec![], false));
+
+    logic.wrapped_internal_write_register(0, &[0u8; 10]).unwrap();
+    logic.wrapped_internal_write_register(1, &[0u8; 10]).unwrap();
+    // Overwriting a register only accounts for the new value.
+    for _ in 0..3 {
+        logic.wrapped_internal_write_register(0, &[1u8; 10]).expect("old value is released");
+    }
+    logic.wrapped_internal_write_register(0, &[]).unwrap();
+    logic.wrapped_internal_write_register(1, &[1u8; 20]).expect("freed memory can be reused");
+    assert_eq!(
+        logic.wrapped_internal_write_register(1, &[1u8; 21]),
+        Err(HostError::MemoryAccessViolation.into())
+    );
+}
+
 #[test]
 fn test_register_is_not_used() {
     let mut logic_builder = VMLogicBuilder::default();
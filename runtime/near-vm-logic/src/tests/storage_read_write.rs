@@ -1,6 +1,7 @@
 use crate::tests::fixtures::get_context;
 use crate::tests::vm_logic_builder::VMLogicBuilder;
 use crate::External;
+use near_vm_errors::{HostError, VMLogicError};
 
 #[test]
 fn test_storage_write_with_register() {
@@ -37,6 +38,29 @@ fn test_storage_read_with_register() {
     assert_eq!(&res, b"bar");
 }
 
+#[test]
+fn test_storage_read_above_register_limit() {
+    let mut logic_builder = VMLogicBuilder::default();
+    logic_builder.config.limit_config.max_register_size = 100;
+    logic_builder.ext.storage_set(b"large", &[0u8; 101]).unwrap();
+    logic_builder.ext.storage_set(b"fits", &[0u8; 100]).unwrap();
+    let num_value_derefs = logic_builder.ext.num_value_derefs.clone();
+    let mut logic = logic_builder.build(get_context(vec![], false));
+
+    // The value is rejected by its length, without being read.
+    let key: &[u8] = b"large";
+    assert_eq!(
+        logic.storage_read(key.len() as _, key.as_ptr() as _, 0),
+        Err(VMLogicError::HostError(HostError::MemoryAccessViolation))
+    );
+    assert_eq!(num_value_derefs.get(), 0);
+
+    let key: &[u8] = b"fits";
+    assert_eq!(logic.storage_read(key.len() as _, key.as_ptr() as _, 0), Ok(1));
+    assert_eq!(num_value_derefs.get(), 1);
+    assert_eq!(logic.register_len(0), Ok(100));
+}
+
 #[test]
 fn test_storage_remove_with_register() {
     let mut logic_builder = VMLogicBuilder::default();
@@ -71,3 +95,134 @@ fn test_storage_has_key_with_register() {
 
     assert_eq!(logic.storage_has_key(u64::MAX, 1 as _), Ok(1));
 }
+
+#[cfg(feature = "protocol_feature_storage_value_len")]
+#[test]
+fn test_storage_value_len() {
+    let mut logic_builder = VMLogicBuilder::default();
+    logic_builder.ext.storage_set(b"short", b"bar").unwrap();
+    logic_builder.ext.storage_set(b"large", &[0u8; 1000]).unwrap();
+    logic_builder.ext.storage_set(b"empty", b"").unwrap();
+    let mut logic = logic_builder.build(get_context(vec![], false));
+
+    let mut value_len = |key: &[u8]| {
+        let gas_before = logic.used_gas().unwrap();
+        let len = logic.storage_value_len(key.len() as _, key.as_ptr() as _).unwrap();
+        (len, logic.used_gas().unwrap() - gas_before)
+    };
+    let (short_len, short_gas) = value_len(b"short");
+    let (large_len, large_gas) = value_len(b"large");
+    assert_eq!(short_len, 3);
+    assert_eq!(large_len, 1000);
+    // Only the key is paid for, not the value.
+    assert_eq!(short_gas, large_gas);
+    assert_eq!(value_len(b"empty").0, 0);
+    assert_eq!(value_len(b"missing").0, u64::MAX);
+}
+
+#[cfg(feature = "protocol_feature_per_receipt_storage_proof_limit")]
+#[test]
+fn test_recorded_storage_size() {
+    let mut logic_builder = VMLogicBuilder::default();
+    let mut logic = logic_builder.build(get_context(vec![], false));
+    assert_eq!(logic.recorded_storage_size(), Ok(0));
+
+    let mut logic_builder = VMLogicBuilder::default();
+    logic_builder.ext.recorded_storage_size = 1000;
+    let mut logic = logic_builder.build(get_context(vec![], false));
+    assert_eq!(logic.recorded_storage_size(), Ok(1000));
+}
+
+#[cfg(feature = "protocol_feature_storage_compare_and_swap")]
+#[test]
+fn test_storage_compare_and_swap() {
+    const ABSENT: u64 = u64::MAX - 1;
+    let large = vec![7u8; 1000];
+    let mut logic_builder = VMLogicBuilder::default();
+    logic_builder.ext.storage_set(b"present", b"old").unwrap();
+    logic_builder.ext.storage_set(b"large", &large).unwrap();
+    let mut context = get_context(vec![], false);
+    context.storage_usage = 2000;
+    let mut logic = logic_builder.build(context);
+
+    let mut cas = |key: &[u8], expected: Option<&[u8]>, value: &[u8]| {
+        let (expected_len, expected_ptr) = match expected {
+            Some(expected) => (expected.len() as u64, expected.as_ptr() as u64),
+            None => (ABSENT, 0),
+        };
+        logic
+            .storage_compare_and_swap(
+                key.len() as _,
+                key.as_ptr() as _,
+                expected_len,
+                expected_ptr,
+                value.len() as _,
+                value.as_ptr() as _,
+            )
+            .expect("storage compare and swap ok")
+    };
+    // Absent expected, but present.
+    assert_eq!(cas(b"present", None, b"new"), 0);
+    // Present expected, but absent.
+    assert_eq!(cas(b"missing", Some(b""), b"new"), 0);
+    assert_eq!(cas(b"missing", Some(b"old"), b"new"), 0);
+    // Large values differing only in length, or with the same length but different content.
+    assert_eq!(cas(b"large", Some(&large[..999]), b"new"), 0);
+    assert_eq!(cas(b"large", Some(&[large.as_slice(), b"x"].concat()), b"new"), 0);
+    assert_eq!(cas(b"large", Some(&[&large[..999], b"x"].concat()), b"new"), 0);
+    // Matching expectations.
+    assert_eq!(cas(b"present", Some(b"old"), b"new"), 1);
+    assert_eq!(cas(b"missing", None, b"created"), 1);
+    assert_eq!(cas(b"large", Some(&large), b""), 1);
+    assert_eq!(cas(b"large", Some(b""), b"small"), 1);
+
+    let storage_usage = logic.storage_usage().unwrap();
+    drop(logic);
+    assert_eq!(logic_builder.ext.fake_trie.get(b"present".as_ref()), Some(&b"new".to_vec()));
+    assert_eq!(logic_builder.ext.fake_trie.get(b"missing".as_ref()), Some(&b"created".to_vec()));
+    assert_eq!(logic_builder.ext.fake_trie.get(b"large".as_ref()), Some(&b"small".to_vec()));
+    let data_record_cost = logic_builder.fees_config.storage_usage_config.num_extra_bytes_record;
+    // `present` kept its size, `large` shrank by 995 bytes and `missing` was created.
+    assert_eq!(
+        storage_usage,
+        2000 - 995 + (b"missing".len() + b"created".len()) as u64 + data_record_cost
+    );
+}
+
+#[cfg(feature = "protocol_feature_storage_key_count")]
+#[test]
+fn test_storage_key_count() {
+    use near_vm_errors::{HostError, VMLogicError};
+
+    let mut logic_builder = VMLogicBuilder::default();
+    logic_builder.config.limit_config.max_storage_key_count = Some(3);
+    logic_builder.ext.storage_set(b"item1", b"").unwrap();
+    logic_builder.ext.storage_set(b"item2", b"").unwrap();
+    logic_builder.ext.storage_set(b"other", b"").unwrap();
+    let mut logic = logic_builder.build(get_context(vec![], false));
+
+    let prefix = b"item";
+    assert_eq!(logic.storage_key_count(prefix.len() as _, prefix.as_ptr() as _), Ok(2));
+
+    let (key, val) = (b"item3", b"value");
+    logic
+        .storage_write(key.len() as _, key.as_ptr() as _, val.len() as _, val.as_ptr() as _, 0)
+        .expect("storage write ok");
+    assert_eq!(logic.storage_key_count(prefix.len() as _, prefix.as_ptr() as _), Ok(3));
+
+    let key = b"item4";
+    logic
+        .storage_write(key.len() as _, key.as_ptr() as _, val.len() as _, val.as_ptr() as _, 0)
+        .expect("storage write ok");
+    assert_eq!(
+        logic.storage_key_count(prefix.len() as _, prefix.as_ptr() as _),
+        Err(VMLogicError::HostError(HostError::StorageKeyCountExceeded { limit: 3 }))
+    );
+
+    logic.storage_remove(key.len() as _, key.as_ptr() as _, 0).expect("storage remove ok");
+    assert_eq!(logic.storage_key_count(prefix.len() as _, prefix.as_ptr() as _), Ok(3));
+    assert_eq!(
+        logic.storage_key_count(0, 0),
+        Err(VMLogicError::HostError(HostError::StorageKeyCountExceeded { limit: 3 }))
+    );
+}
@@ -43,3 +43,34 @@ fn test_storage_remove() {
 
     assert_eq!(logic.storage_usage().unwrap(), 0u64);
 }
+
+#[cfg(feature = "protocol_feature_storage_write_no_evict")]
+#[test]
+fn test_storage_write_no_evict_same_as_storage_write() {
+    let writes: [(&[u8], &[u8]); 4] =
+        [(b"foo", b"bar"), (b"foo", b"a longer value"), (b"baz", b""), (b"foo", b"x")];
+
+    let mut classic_builder = VMLogicBuilder::default();
+    let mut classic = classic_builder.build(get_context(vec![], false));
+    let mut no_evict_builder = VMLogicBuilder::default();
+    let mut no_evict = no_evict_builder.build(get_context(vec![], false));
+    for (key, val) in writes {
+        let classic_res = classic
+            .storage_write(key.len() as _, key.as_ptr() as _, val.len() as _, val.as_ptr() as _, 0)
+            .expect("storage write ok");
+        let no_evict_res = no_evict
+            .storage_write_no_evict(
+                key.len() as _,
+                key.as_ptr() as _,
+                val.len() as _,
+                val.as_ptr() as _,
+            )
+            .expect("storage write ok");
+        assert_eq!(classic_res, no_evict_res);
+        assert_eq!(classic.storage_usage().unwrap(), no_evict.storage_usage().unwrap());
+    }
+    // The long evicted value is only charged for by the classic write.
+    assert!(no_evict.used_gas().unwrap() < classic.used_gas().unwrap());
+    drop((classic, no_evict));
+    assert_eq!(classic_builder.ext.fake_trie, no_evict_builder.ext.fake_trie);
+}
@@ -20,6 +20,10 @@ fn test_prohibited_view_methods() {
     test_prohibited!(attached_deposit, 0);
     test_prohibited!(prepaid_gas);
     test_prohibited!(used_gas);
+    #[cfg(feature = "protocol_feature_gas_price_host_functions")]
+    test_prohibited!(gas_price, 0);
+    #[cfg(feature = "protocol_feature_gas_price_host_functions")]
+    test_prohibited!(block_gas_price, 0);
     test_prohibited!(promise_create, 0, 0, 0, 0, 0, 0, 0, 0);
     test_prohibited!(promise_then, 0, 0, 0, 0, 0, 0, 0, 0, 0);
     test_prohibited!(promise_and, 0, 0);
@@ -36,6 +40,8 @@ fn test_prohibited_view_methods() {
     test_prohibited!(promise_batch_action_delete_account, 0, 0, 0);
     test_prohibited!(promise_results_count);
     test_prohibited!(promise_result, 0, 0);
+    #[cfg(feature = "protocol_feature_promise_result_gas_burnt")]
+    test_prohibited!(promise_result_gas_burnt, 0);
     test_prohibited!(promise_return, 0);
     test_prohibited!(storage_write, 0, 0, 0, 0, 0);
     test_prohibited!(storage_remove, 0, 0, 0);
@@ -0,0 +1,49 @@
+//! Compares deploying a contract and then calling it with and without the prepared code cached
+//! at deployment being available to the call.
+//!
+//! The call compiles the contract for a different VM kind than deployment did, the way it
+//! happens after the VM kind changes with a protocol upgrade.  It goes through
+//! `precompile_contract_vm` rather than a real call, so that the in-memory cache of compiled
+//! modules doesn't hide the difference after the first iteration.
+#[macro_use]
+extern crate bencher;
+
+use bencher::Bencher;
+use near_primitives::contract::ContractCode;
+use near_vm_logic::VMConfig;
+use near_vm_runner::internal::VMKind;
+use near_vm_runner::{precompile_contract_vm, MockCompiledContractCache};
+
+/// About 2 MiB of small functions.
+fn contract() -> ContractCode {
+    ContractCode::new(near_test_contracts::many_functions_contract(200_000), None)
+}
+
+fn deploy_then_call_shared_cache(bench: &mut Bencher) {
+    let code = contract();
+    let config = VMConfig::test();
+    bench.iter(|| {
+        let cache = MockCompiledContractCache::default();
+        precompile_contract_vm(VMKind::Wasmer2, &code, &config, Some(&cache)).unwrap().unwrap();
+        precompile_contract_vm(VMKind::Wasmer0, &code, &config, Some(&cache)).unwrap().unwrap();
+    });
+}
+
+fn deploy_then_call_separate_caches(bench: &mut Bencher) {
+    let code = contract();
+    let config = VMConfig::test();
+    bench.iter(|| {
+        let deploy_cache = MockCompiledContractCache::default();
+        let call_cache = MockCompiledContractCache::default();
+        precompile_contract_vm(VMKind::Wasmer2, &code, &config, Some(&deploy_cache))
+            .unwrap()
+            .unwrap();
+        precompile_contract_vm(VMKind::Wasmer0, &code, &config, Some(&call_cache))
+            .unwrap()
+            .unwrap();
+    });
+}
+
+benchmark_group!(benches, deploy_then_call_shared_cache, deploy_then_call_separate_caches);
+
+benchmark_main!(benches);
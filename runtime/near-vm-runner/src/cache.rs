@@ -1,4 +1,6 @@
 use crate::errors::ContractPrecompilatonResult;
+#[cfg(all(any(feature = "wasmer0_vm", feature = "wasmer2_vm"), target_arch = "x86_64"))]
+use crate::metrics::{record_lookup, vm_kind_label, CacheLayer, COMPILATION_TIME};
 use crate::vm_kind::VMKind;
 use borsh::{BorshDeserialize, BorshSerialize};
 use near_primitives::contract::ContractCode;
@@ -10,8 +12,6 @@ use std::collections::HashMap;
 use std::fmt;
 use std::sync::{Arc, Mutex};
 
-#[cfg(target_arch = "x86_64")]
-use crate::prepare;
 #[cfg(target_arch = "x86_64")]
 use near_vm_errors::{FunctionCallError, VMError};
 
@@ -34,6 +34,20 @@ enum CacheRecord {
     Code(Vec<u8>),
 }
 
+/// Key of the contract code as prepared by [`crate::prepare::prepare_contract`].  Unlike
+/// [`ContractCacheKey`], it doesn't depend on the VM, so prepared code is shared by all of them.
+#[cfg(any(target_arch = "x86_64", feature = "wasmtime_vm"))]
+#[derive(Debug, Clone, BorshSerialize)]
+enum PreparedCodeCacheKey {
+    Version1 { code_hash: CryptoHash, prepare_config_hash: u64 },
+}
+
+#[cfg(any(target_arch = "x86_64", feature = "wasmtime_vm"))]
+#[derive(Debug, Clone, BorshDeserialize, BorshSerialize)]
+enum PreparedCodeRecord {
+    Code(Vec<u8>),
+}
+
 fn vm_hash(vm_kind: VMKind) -> u64 {
     match vm_kind {
         #[cfg(all(feature = "wasmer0_vm", target_arch = "x86_64"))]
@@ -66,6 +80,57 @@ pub fn get_contract_cache_key(
     near_primitives::hash::hash(&key.try_to_vec().unwrap())
 }
 
+/// Hashes the parts of `config` which affect contract preparation.
+#[cfg(any(target_arch = "x86_64", feature = "wasmtime_vm"))]
+fn prepare_config_hash(config: &VMConfig) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = near_stable_hasher::StableHasher::new();
+    (config.regular_op_cost, config.grow_mem_cost, &config.limit_config).hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(any(target_arch = "x86_64", feature = "wasmtime_vm"))]
+pub(crate) fn get_prepared_code_cache_key(code: &ContractCode, config: &VMConfig) -> CryptoHash {
+    let key = PreparedCodeCacheKey::Version1 {
+        code_hash: *code.hash(),
+        prepare_config_hash: prepare_config_hash(config),
+    };
+    near_primitives::hash::hash(&key.try_to_vec().unwrap())
+}
+
+/// Validates and instruments `code` with [`crate::prepare::prepare_contract`], reusing the code
+/// prepared by an earlier call with the same preparation config if `cache` has it.
+///
+/// Only successfully prepared code is stored, errors end up in the compiled contract record.
+/// A record which can't be deserialized is treated as missing and overwritten.
+#[cfg(any(target_arch = "x86_64", feature = "wasmtime_vm"))]
+pub(crate) fn prepare_contract_cached(
+    code: &ContractCode,
+    config: &VMConfig,
+    cache: Option<&dyn CompiledContractCache>,
+) -> Result<Result<Vec<u8>, near_vm_errors::PrepareError>, CacheError> {
+    let _span = tracing::debug_span!(target: "vm", "prepare_contract_cached").entered();
+    let cache = match cache {
+        None => return Ok(crate::prepare::prepare_contract(code.code(), config)),
+        Some(cache) => cache,
+    };
+    let key = get_prepared_code_cache_key(code, config);
+    if let Some(serialized) = cache.get(&key.0).map_err(|_io_err| CacheError::ReadError)? {
+        if let Ok(PreparedCodeRecord::Code(prepared_code)) =
+            PreparedCodeRecord::try_from_slice(&serialized)
+        {
+            return Ok(Ok(prepared_code));
+        }
+    }
+    let record = match crate::prepare::prepare_contract(code.code(), config) {
+        Ok(prepared_code) => PreparedCodeRecord::Code(prepared_code),
+        Err(err) => return Ok(Err(err)),
+    };
+    cache.put(&key.0, &record.try_to_vec().unwrap()).map_err(|_io_err| CacheError::WriteError)?;
+    let PreparedCodeRecord::Code(prepared_code) = record;
+    Ok(Ok(prepared_code))
+}
+
 #[cfg(target_arch = "x86_64")]
 fn cache_error(
     error: &CompilationError,
@@ -120,21 +185,68 @@ impl fmt::Debug for MockCompiledContractCache {
     }
 }
 
-#[cfg(all(not(feature = "no_cache"), target_arch = "x86_64"))]
-const CACHE_SIZE: usize = 128;
+#[cfg(all(
+    any(feature = "wasmer0_vm", feature = "wasmer2_vm"),
+    not(feature = "no_cache"),
+    target_arch = "x86_64"
+))]
+pub(crate) const CACHE_SIZE: usize = 128;
+
+/// In-memory cache of compiled contracts in front of the [`CompiledContractCache`], which
+/// exports the size of the Wasm code of the contracts it holds.
+#[cfg(all(
+    any(feature = "wasmer0_vm", feature = "wasmer2_vm"),
+    not(feature = "no_cache"),
+    target_arch = "x86_64"
+))]
+pub(crate) struct MemoryCache<T> {
+    vm_kind: VMKind,
+    cache: near_cache::SyncLruCache<CryptoHash, (T, u64)>,
+}
+
+#[cfg(all(
+    any(feature = "wasmer0_vm", feature = "wasmer2_vm"),
+    not(feature = "no_cache"),
+    target_arch = "x86_64"
+))]
+impl<T: Clone> MemoryCache<T> {
+    pub(crate) fn new(vm_kind: VMKind) -> Self {
+        Self { vm_kind, cache: near_cache::SyncLruCache::new(CACHE_SIZE) }
+    }
+
+    /// Returns the compiled `code`, loading it with `load` and keeping it in memory if it isn't
+    /// there yet.
+    pub(crate) fn get_or_try_put<E>(
+        &self,
+        key: CryptoHash,
+        code: &ContractCode,
+        load: impl FnOnce(&CryptoHash) -> Result<T, E>,
+    ) -> Result<T, E> {
+        if let Some((value, _)) = self.cache.get(&key) {
+            record_lookup(CacheLayer::Memory);
+            return Ok(value);
+        }
+        let value = load(&key)?;
+        let size = code.code().len() as u64;
+        let gauge = crate::metrics::MEMORY_CACHE_WASM_CODE_SIZE
+            .with_label_values(&[vm_kind_label(self.vm_kind)]);
+        if let Some((_, removed_size)) = self.cache.push(key, (value.clone(), size)) {
+            gauge.sub(removed_size as i64);
+        }
+        gauge.add(size as i64);
+        Ok(value)
+    }
+}
 
 #[cfg(all(feature = "wasmer0_vm", not(feature = "no_cache"), target_arch = "x86_64"))]
 static WASMER_CACHE: once_cell::sync::Lazy<
-    near_cache::SyncLruCache<CryptoHash, Result<wasmer_runtime::Module, CompilationError>>,
-> = once_cell::sync::Lazy::new(|| near_cache::SyncLruCache::new(CACHE_SIZE));
+    MemoryCache<Result<wasmer_runtime::Module, CompilationError>>,
+> = once_cell::sync::Lazy::new(|| MemoryCache::new(VMKind::Wasmer0));
 
 #[cfg(all(feature = "wasmer2_vm", not(feature = "no_cache"), target_arch = "x86_64"))]
 static WASMER2_CACHE: once_cell::sync::Lazy<
-    near_cache::SyncLruCache<
-        CryptoHash,
-        Result<crate::wasmer2_runner::VMArtifact, CompilationError>,
-    >,
-> = once_cell::sync::Lazy::new(|| near_cache::SyncLruCache::new(CACHE_SIZE));
+    MemoryCache<Result<crate::wasmer2_runner::VMArtifact, CompilationError>>,
+> = once_cell::sync::Lazy::new(|| MemoryCache::new(VMKind::Wasmer2));
 
 #[cfg(all(feature = "wasmer0_vm", target_arch = "x86_64"))]
 pub mod wasmer0_cache {
@@ -145,14 +257,19 @@ pub mod wasmer0_cache {
     use wasmer_runtime_core::load_cache_with;
 
     pub(crate) fn compile_module(
-        code: &[u8],
+        code: &ContractCode,
         config: &VMConfig,
-    ) -> Result<wasmer_runtime::Module, CompilationError> {
+        cache: Option<&dyn CompiledContractCache>,
+    ) -> Result<Result<wasmer_runtime::Module, CompilationError>, CacheError> {
         let _span = tracing::debug_span!(target: "vm", "compile_module").entered();
+        let _timer =
+            COMPILATION_TIME.with_label_values(&[vm_kind_label(VMKind::Wasmer0)]).start_timer();
 
-        let prepared_code =
-            prepare::prepare_contract(code, config).map_err(CompilationError::PrepareError)?;
-        wasmer_runtime::compile(&prepared_code).map_err(|err| match err {
+        let prepared_code = match prepare_contract_cached(code, config, cache)? {
+            Ok(prepared_code) => prepared_code,
+            Err(err) => return Ok(Err(CompilationError::PrepareError(err))),
+        };
+        Ok(wasmer_runtime::compile(&prepared_code).map_err(|err| match err {
             wasmer_runtime::error::CompileError::ValidationError { .. } => {
                 CompilationError::WasmerCompileError { msg: err.to_string() }
             }
@@ -161,18 +278,18 @@ pub mod wasmer0_cache {
             wasmer_runtime::error::CompileError::InternalError { .. } => {
                 CompilationError::WasmerCompileError { msg: err.to_string() }
             }
-        })
+        }))
     }
 
     pub(crate) fn compile_and_serialize_wasmer(
-        wasm_code: &[u8],
+        code: &ContractCode,
         config: &VMConfig,
         key: &CryptoHash,
         cache: &dyn CompiledContractCache,
     ) -> Result<Result<wasmer_runtime::Module, CompilationError>, CacheError> {
         let _span = tracing::debug_span!(target: "vm", "compile_and_serialize_wasmer").entered();
 
-        let module = match compile_module(wasm_code, config) {
+        let module = match compile_module(code, config, Some(cache))? {
             Ok(module) => module,
             Err(err) => {
                 cache_error(&err, key, cache)?;
@@ -216,17 +333,26 @@ pub mod wasmer0_cache {
 
     fn compile_module_cached_wasmer_impl(
         key: CryptoHash,
-        wasm_code: &[u8],
+        code: &ContractCode,
         config: &VMConfig,
         cache: Option<&dyn CompiledContractCache>,
     ) -> Result<Result<wasmer_runtime::Module, CompilationError>, CacheError> {
         match cache {
-            None => Ok(compile_module(wasm_code, config)),
+            None => {
+                record_lookup(CacheLayer::Miss);
+                compile_module(code, config, None)
+            }
             Some(cache) => {
                 let serialized = cache.get(&key.0).map_err(|_io_err| CacheError::ReadError)?;
                 match serialized {
-                    Some(serialized) => deserialize_wasmer(serialized.as_slice()),
-                    None => compile_and_serialize_wasmer(wasm_code, config, &key, cache),
+                    Some(serialized) => {
+                        record_lookup(CacheLayer::Disk);
+                        deserialize_wasmer(serialized.as_slice())
+                    }
+                    None => {
+                        record_lookup(CacheLayer::Miss);
+                        compile_and_serialize_wasmer(code, config, &key, cache)
+                    }
                 }
             }
         }
@@ -240,12 +366,12 @@ pub mod wasmer0_cache {
         let key = get_contract_cache_key(code, VMKind::Wasmer0, config);
 
         #[cfg(not(feature = "no_cache"))]
-        return WASMER_CACHE.get_or_try_put(key, |key| {
-            compile_module_cached_wasmer_impl(*key, code.code(), config, cache)
+        return WASMER_CACHE.get_or_try_put(key, code, |key| {
+            compile_module_cached_wasmer_impl(*key, code, config, cache)
         });
 
         #[cfg(feature = "no_cache")]
-        return compile_module_cached_wasmer_impl(key, code.code(), config, cache);
+        return compile_module_cached_wasmer_impl(key, code, config, cache);
     }
 }
 
@@ -257,24 +383,29 @@ pub mod wasmer2_cache {
     use super::*;
 
     pub(crate) fn compile_module_wasmer2(
-        code: &[u8],
+        code: &ContractCode,
         config: &VMConfig,
-    ) -> Result<VMArtifact, CompilationError> {
+        cache: Option<&dyn CompiledContractCache>,
+    ) -> Result<Result<VMArtifact, CompilationError>, CacheError> {
         let _span = tracing::debug_span!(target: "vm", "compile_module_wasmer2").entered();
-        let prepared_code =
-            prepare::prepare_contract(code, config).map_err(CompilationError::PrepareError)?;
-        Wasmer2VM::new(config.clone()).compile_uncached(&prepared_code)
+        let _timer =
+            COMPILATION_TIME.with_label_values(&[vm_kind_label(VMKind::Wasmer2)]).start_timer();
+        let prepared_code = match prepare_contract_cached(code, config, cache)? {
+            Ok(prepared_code) => prepared_code,
+            Err(err) => return Ok(Err(CompilationError::PrepareError(err))),
+        };
+        Ok(Wasmer2VM::new(config.clone()).compile_uncached(&prepared_code))
     }
 
     pub(crate) fn compile_and_serialize_wasmer2(
-        wasm_code: &[u8],
+        code: &ContractCode,
         key: &CryptoHash,
         config: &VMConfig,
         cache: &dyn CompiledContractCache,
     ) -> Result<Result<VMArtifact, CompilationError>, CacheError> {
         let _span = tracing::debug_span!(target: "vm", "compile_and_serialize_wasmer2").entered();
 
-        let module = match compile_module_wasmer2(wasm_code, config) {
+        let module = match compile_module_wasmer2(code, config, Some(cache))? {
             Ok(module) => module,
             Err(err) => {
                 cache_error(&err, key, cache)?;
@@ -327,12 +458,21 @@ pub mod wasmer2_cache {
         cache: Option<&dyn CompiledContractCache>,
     ) -> Result<Result<VMArtifact, CompilationError>, CacheError> {
         match cache {
-            None => Ok(compile_module_wasmer2(code.code(), config)),
+            None => {
+                record_lookup(CacheLayer::Miss);
+                compile_module_wasmer2(code, config, None)
+            }
             Some(cache) => {
                 let serialized = cache.get(&key.0).map_err(|_io_err| CacheError::ReadError)?;
                 match serialized {
-                    Some(serialized) => deserialize_wasmer2(serialized.as_slice(), config),
-                    None => compile_and_serialize_wasmer2(code.code(), &key, config, cache),
+                    Some(serialized) => {
+                        record_lookup(CacheLayer::Disk);
+                        deserialize_wasmer2(serialized.as_slice(), config)
+                    }
+                    None => {
+                        record_lookup(CacheLayer::Miss);
+                        compile_and_serialize_wasmer2(code, &key, config, cache)
+                    }
                 }
             }
         }
@@ -346,7 +486,7 @@ pub mod wasmer2_cache {
         let key = get_contract_cache_key(code, VMKind::Wasmer2, config);
 
         #[cfg(not(feature = "no_cache"))]
-        return WASMER2_CACHE.get_or_try_put(key, |key| {
+        return WASMER2_CACHE.get_or_try_put(key, code, |key| {
             compile_module_cached_wasmer2_impl(*key, code, config, cache)
         });
 
@@ -375,14 +515,14 @@ pub fn precompile_contract_vm(
     match vm_kind {
         #[cfg(all(feature = "wasmer0_vm", target_arch = "x86_64"))]
         VMKind::Wasmer0 => {
-            Ok(wasmer0_cache::compile_and_serialize_wasmer(wasm_code.code(), config, &key, cache)?
+            Ok(wasmer0_cache::compile_and_serialize_wasmer(wasm_code, config, &key, cache)?
                 .map(|_| ContractPrecompilatonResult::ContractCompiled))
         }
         #[cfg(not(all(feature = "wasmer0_vm", target_arch = "x86_64")))]
         VMKind::Wasmer0 => panic!("Wasmer0 is not enabled!"),
         #[cfg(all(feature = "wasmer2_vm", target_arch = "x86_64"))]
         VMKind::Wasmer2 => {
-            Ok(wasmer2_cache::compile_and_serialize_wasmer2(wasm_code.code(), &key, config, cache)?
+            Ok(wasmer2_cache::compile_and_serialize_wasmer2(wasm_code, &key, config, cache)?
                 .map(|_| ContractPrecompilatonResult::ContractCompiled))
         }
         #[cfg(not(all(feature = "wasmer2_vm", target_arch = "x86_64")))]
@@ -83,10 +83,15 @@ imports! {
     signer_account_pk<[register_id: u64] -> []>,
     predecessor_account_id<[register_id: u64] -> []>,
     input<[register_id: u64] -> []>,
+    #["protocol_feature_input_read", InputRead] input_len<[] -> [u64]>,
+    #["protocol_feature_input_read", InputRead] input_read<[offset: u64, len: u64, ptr: u64] -> []>,
     // TODO #1903 rename to `block_height`
     block_index<[] -> [u64]>,
     block_timestamp<[] -> [u64]>,
     epoch_height<[] -> [u64]>,
+    #["protocol_feature_epoch_and_shard_id", EpochAndShardId] epoch_id<[register_id: u64] -> []>,
+    #["protocol_feature_epoch_and_shard_id", EpochAndShardId] shard_id<[] -> [u64]>,
+    #["protocol_feature_receipt_depth", ReceiptDepth] receipt_depth<[] -> [u64]>,
     storage_usage<[] -> [u64]>,
     // #################
     // # Economics API #
@@ -96,6 +101,12 @@ imports! {
     attached_deposit<[balance_ptr: u64] -> []>,
     prepaid_gas<[] -> [u64]>,
     used_gas<[] -> [u64]>,
+    #["protocol_feature_gas_budget_host_functions", GasBudgetHostFunctions] remaining_gas<[] -> [u64]>,
+    #["protocol_feature_gas_budget_host_functions", GasBudgetHostFunctions] action_fee_estimate<[action_kind: u64, payload_len: u64] -> [u64]>,
+    #["protocol_feature_gas_price_host_functions", GasPriceHostFunctions] gas_price<[register_id: u64] -> []>,
+    #["protocol_feature_gas_price_host_functions", GasPriceHostFunctions] block_gas_price<[register_id: u64] -> []>,
+    #["protocol_feature_storage_config_host_functions", StorageConfigHostFunctions] storage_byte_cost<[register_id: u64] -> []>,
+    #["protocol_feature_storage_config_host_functions", StorageConfigHostFunctions] min_account_balance<[register_id: u64] -> []>,
     // ############
     // # Math API #
     // ############
@@ -141,6 +152,19 @@ imports! {
     promise_and<[promise_idx_ptr: u64, promise_idx_count: u64] -> [u64]>,
     promise_batch_create<[account_id_len: u64, account_id_ptr: u64] -> [u64]>,
     promise_batch_then<[promise_index: u64, account_id_len: u64, account_id_ptr: u64] -> [u64]>,
+    #["protocol_feature_promise_data_dependency", PromiseDataDependency] generate_data_id<[register_id: u64] -> []>,
+    #["protocol_feature_promise_data_dependency", PromiseDataDependency] promise_create_with_data_dependency<[
+        data_id_ptr: u64,
+        account_id_len: u64,
+        account_id_ptr: u64,
+        method_name_len: u64,
+        method_name_ptr: u64,
+        arguments_len: u64,
+        arguments_ptr: u64,
+        amount_ptr: u64,
+        gas: u64
+    ] -> [u64]>,
+    #["protocol_feature_promise_data_dependency", PromiseDataDependency] promise_resume_data<[data_id_ptr: u64, payload_len: u64, payload_ptr: u64] -> []>,
     // #######################
     // # Promise API actions #
     // #######################
@@ -155,7 +179,7 @@ imports! {
         amount_ptr: u64,
         gas: u64
     ] -> []>,
-    #["protocol_feature_function_call_weight", FunctionCallRatio] promise_batch_action_function_call_weight<[
+    #["protocol_feature_function_call_weight", FunctionCallWeight] promise_batch_action_function_call_weight<[
         promise_index: u64,
         method_name_len: u64,
         method_name_ptr: u64,
@@ -165,6 +189,7 @@ imports! {
         gas: u64,
         gas_weight: u64
     ] -> []>,
+    #["protocol_feature_promise_action_min_gas", PromiseActionMinGas] promise_action_min_gas<[promise_index: u64, action_index: u64] -> [u64]>,
     promise_batch_action_transfer<[promise_index: u64, amount_ptr: u64] -> []>,
     promise_batch_action_stake<[
         promise_index: u64,
@@ -204,14 +229,20 @@ imports! {
     // #######################
     promise_results_count<[] -> [u64]>,
     promise_result<[result_idx: u64, register_id: u64] -> [u64]>,
+    #["protocol_feature_promise_result_gas_burnt", PromiseResultGasBurnt] promise_result_gas_burnt<[result_idx: u64] -> [u64]>,
     promise_return<[promise_idx: u64] -> []>,
     // ###############
     // # Storage API #
     // ###############
     storage_write<[key_len: u64, key_ptr: u64, value_len: u64, value_ptr: u64, register_id: u64] -> [u64]>,
+    #["protocol_feature_storage_write_no_evict", StorageWriteNoEvict] storage_write_no_evict<[key_len: u64, key_ptr: u64, value_len: u64, value_ptr: u64] -> [u64]>,
+    #["protocol_feature_storage_compare_and_swap", StorageCompareAndSwap] storage_compare_and_swap<[key_len: u64, key_ptr: u64, expected_len: u64, expected_ptr: u64, value_len: u64, value_ptr: u64] -> [u64]>,
     storage_read<[key_len: u64, key_ptr: u64, register_id: u64] -> [u64]>,
     storage_remove<[key_len: u64, key_ptr: u64, register_id: u64] -> [u64]>,
     storage_has_key<[key_len: u64, key_ptr: u64] -> [u64]>,
+    #["protocol_feature_storage_key_count", StorageKeyCount] storage_key_count<[prefix_len: u64, prefix_ptr: u64] -> [u64]>,
+    #["protocol_feature_storage_value_len", StorageValueLen] storage_value_len<[key_len: u64, key_ptr: u64] -> [u64]>,
+    #["protocol_feature_per_receipt_storage_proof_limit", PerReceiptStorageProofLimit] recorded_storage_size<[] -> [u64]>,
     storage_iter_prefix<[prefix_len: u64, prefix_ptr: u64] -> [u64]>,
     storage_iter_range<[start_len: u64, start_ptr: u64, end_len: u64, end_ptr: u64] -> [u64]>,
     storage_iter_next<[iterator_id: u64, key_register_id: u64, value_register_id: u64] -> [u64]>,
@@ -222,6 +253,7 @@ imports! {
     // ###############
     validator_stake<[account_id_len: u64, account_id_ptr: u64, stake_ptr: u64] -> []>,
     validator_total_stake<[stake_ptr: u64] -> []>,
+    #["protocol_feature_locked_balance_of", LockedBalanceOf] locked_balance_of<[account_id_len: u64, account_id_ptr: u64, register_id: u64] -> []>,
     // #############
     // # Alt BN128 #
     // #############
@@ -5,10 +5,12 @@ mod errors;
 mod imports;
 #[cfg(all(feature = "wasmer0_vm", target_arch = "x86_64"))]
 mod memory;
+mod metrics;
 #[cfg(target_arch = "x86_64")]
 mod preload;
 pub mod prepare;
 mod runner;
+mod stack_limiter;
 #[cfg(test)]
 mod tests;
 mod vm_kind;
@@ -25,6 +27,7 @@ pub use near_vm_logic::with_ext_cost_counter;
 pub use cache::{
     get_contract_cache_key, precompile_contract, precompile_contract_vm, MockCompiledContractCache,
 };
+pub use metrics::set_slow_compilation_threshold;
 #[cfg(target_arch = "x86_64")]
 pub use preload::{ContractCallPrepareRequest, ContractCallPrepareResult, ContractCaller};
 pub use runner::{run, VM};
@@ -0,0 +1,97 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use near_metrics::{
+    try_create_histogram_vec, try_create_int_counter_vec, try_create_int_gauge_vec, HistogramVec,
+    IntCounterVec, IntGaugeVec,
+};
+use near_primitives::contract::ContractCode;
+use near_primitives::types::AccountId;
+use once_cell::sync::Lazy;
+
+use crate::vm_kind::VMKind;
+
+pub(crate) static COMPILED_CONTRACT_LOOKUPS: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_vm_compiled_contract_lookups_total",
+        "Number of compiled contracts looked up, by the cache layer they were found in: memory, \
+         disk or none if they had to be compiled",
+        &["layer"],
+    )
+    .unwrap()
+});
+pub(crate) static COMPILATION_TIME: Lazy<HistogramVec> = Lazy::new(|| {
+    try_create_histogram_vec(
+        "near_vm_compilation_seconds",
+        "Wall time of preparing and compiling a contract",
+        &["vm_kind"],
+        Some(vec![0.001, 0.005, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]),
+    )
+    .unwrap()
+});
+pub(crate) static MEMORY_CACHE_WASM_CODE_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    try_create_int_gauge_vec(
+        "near_vm_memory_cache_wasm_code_bytes",
+        "Size of the Wasm code of the contracts whose compiled code is held in the in-memory \
+         contract cache.  The compiled code itself isn't measured",
+        &["vm_kind"],
+    )
+    .unwrap()
+});
+
+/// Cache layer a compiled contract was found in.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum CacheLayer {
+    Memory,
+    Disk,
+    /// Not cached, the contract had to be compiled.
+    Miss,
+}
+
+pub(crate) fn record_lookup(layer: CacheLayer) {
+    let layer = match layer {
+        CacheLayer::Memory => "memory",
+        CacheLayer::Disk => "disk",
+        CacheLayer::Miss => "none",
+    };
+    COMPILED_CONTRACT_LOOKUPS.with_label_values(&[layer]).inc();
+}
+
+pub(crate) fn vm_kind_label(vm_kind: VMKind) -> &'static str {
+    match vm_kind {
+        VMKind::Wasmer0 => "wasmer0",
+        VMKind::Wasmtime => "wasmtime",
+        VMKind::Wasmer2 => "wasmer2",
+    }
+}
+
+/// Loading a contract which takes longer than this is logged as a warning.
+static SLOW_COMPILATION_THRESHOLD_MS: AtomicU64 = AtomicU64::new(1000);
+
+/// Sets the time after which loading a contract, i.e. compiling it or reading it from the
+/// compiled contract cache, is logged as a warning.
+pub fn set_slow_compilation_threshold(threshold: Duration) {
+    SLOW_COMPILATION_THRESHOLD_MS.store(threshold.as_millis() as u64, Ordering::Relaxed);
+}
+
+/// Logs a warning naming the contract if loading it took longer than the threshold.  Contracts
+/// found in memory load in microseconds, so these are compilations or, rarely, reads of huge
+/// contracts from the compiled contract cache.
+pub(crate) fn check_slow_compilation(
+    vm_kind: VMKind,
+    account_id: &AccountId,
+    code: &ContractCode,
+    elapsed: Duration,
+) {
+    if elapsed.as_millis() as u64 > SLOW_COMPILATION_THRESHOLD_MS.load(Ordering::Relaxed) {
+        tracing::warn!(
+            target: "vm",
+            %account_id,
+            code_hash = %code.hash(),
+            code_size = code.code().len(),
+            vm_kind = vm_kind_label(vm_kind),
+            elapsed_ms = elapsed.as_millis() as u64,
+            "Slow contract compilation"
+        );
+    }
+}
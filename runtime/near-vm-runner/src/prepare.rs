@@ -27,6 +27,8 @@ pub(crate) const WASM_FEATURES: wasmparser::WasmFeatures = wasmparser::WasmFeatu
 /// The checks are:
 ///
 /// - module doesn't define an internal memory instance,
+/// - declared or imported memory (if any) doesn't reserve more memory than permitted by the
+///   `config` when `strict_memory_limits` is set,
 /// - imported memory (if any) doesn't reserve more memory than permitted by the `config`,
 /// - all imported functions from the external environment matches defined by `env` module,
 /// - functions number does not exceed limit specified in VMConfig,
@@ -44,14 +46,17 @@ pub fn prepare_contract(original_code: &[u8], config: &VMConfig) -> Result<Vec<u
         //
         // See `test_stack_instrumentation_protocol_upgrade` test.
         near_vm_logic::StackLimiterVersion::V0 => pwasm_12::prepare_contract(original_code, config),
-        near_vm_logic::StackLimiterVersion::V1 => ContractModule::init(original_code, config)?
-            .validate_functions_number()?
-            .standardize_mem()
-            .ensure_no_internal_memory()?
-            .inject_gas_metering()?
-            .inject_stack_height_metering()?
-            .scan_imports()?
-            .into_wasm_code(),
+        near_vm_logic::StackLimiterVersion::V1 | near_vm_logic::StackLimiterVersion::V2 => {
+            ContractModule::init(original_code, config)?
+                .validate_functions_number()?
+                .validate_memory_limits()?
+                .standardize_mem()
+                .ensure_no_internal_memory()?
+                .inject_gas_metering()?
+                .inject_stack_height_metering()?
+                .scan_imports()?
+                .into_wasm_code()
+        }
     }
 }
 
@@ -67,6 +72,34 @@ impl<'a> ContractModule<'a> {
         Ok(ContractModule { module, config })
     }
 
+    /// Ensures that memory declared or imported by the module fits into `max_memory_pages`
+    /// before it's replaced by [`Self::standardize_mem`], so that every VM rejects it the same way.
+    fn validate_memory_limits(self) -> Result<Self, PrepareError> {
+        let limit_config = &self.config.limit_config;
+        if !limit_config.strict_memory_limits {
+            return Ok(self);
+        }
+        let declared = self.module.memory_section().map_or(&[][..], MemorySection::entries);
+        let imported = self
+            .module
+            .import_section()
+            .map_or(&[][..], elements::ImportSection::entries)
+            .iter()
+            .filter_map(|import| match import.external() {
+                External::Memory(memory_type) => Some(memory_type),
+                _ => None,
+            });
+        for memory_type in declared.iter().chain(imported) {
+            let limits = memory_type.limits();
+            if limits.initial() > limit_config.max_memory_pages
+                || limits.maximum().map_or(false, |max| max > limit_config.max_memory_pages)
+            {
+                return Err(PrepareError::Memory);
+            }
+        }
+        Ok(self)
+    }
+
     fn standardize_mem(self) -> Self {
         let Self { mut module, config } = self;
 
@@ -117,9 +150,16 @@ impl<'a> ContractModule<'a> {
 
     fn inject_stack_height_metering(self) -> Result<Self, PrepareError> {
         let Self { module, config } = self;
-        let module =
-            pwasm_utils::stack_height::inject_limiter(module, config.limit_config.max_stack_height)
-                .map_err(|_| PrepareError::StackHeightInstrumentation)?;
+        let module = match config.limit_config.stack_limiter_version {
+            near_vm_logic::StackLimiterVersion::V2 => {
+                crate::stack_limiter::inject_limiter(module, &config.limit_config)?
+            }
+            _ => pwasm_utils::stack_height::inject_limiter(
+                module,
+                config.limit_config.max_stack_height,
+            )
+            .map_err(|_| PrepareError::StackHeightInstrumentation)?,
+        };
         Ok(Self { module, config })
     }
 
@@ -415,6 +455,22 @@ mod tests {
         assert_matches!(r, Ok(_));
     }
 
+    #[test]
+    fn strict_memory_limits() {
+        let mut config = VMConfig::test();
+        config.limit_config.strict_memory_limits = true;
+        let prepare = |wat: &str| prepare_contract(&wat::parse_str(wat).unwrap(), &config);
+
+        assert_matches!(prepare(r#"(module (memory 2048 2048))"#), Ok(_));
+        assert_matches!(prepare(r#"(module (import "env" "memory" (memory 1 2048)))"#), Ok(_));
+        assert_matches!(prepare(r#"(module (memory 2049))"#), Err(PrepareError::Memory));
+        assert_matches!(prepare(r#"(module (memory 1 2049))"#), Err(PrepareError::Memory));
+        assert_matches!(
+            prepare(r#"(module (import "env" "memory" (memory 1 2049)))"#),
+            Err(PrepareError::Memory)
+        );
+    }
+
     #[test]
     fn multiple_valid_memory_are_disabled() {
         // Our preparation and sanitization pass assumes a single memory, so we should fail when
@@ -0,0 +1,308 @@
+//! Limiter of the stack height and the call depth, used by [`StackLimiterVersion::V2`].
+//!
+//! Each function defined in the module gets a cost: the max height of its operand stack plus
+//! `stack_height_local_cost` for each of its params and locals.  On entry the instrumented
+//! function adds its cost to the global stack height counter and increments the global call
+//! depth counter, trapping if either exceeds its limit, and on exit it undoes both.
+//!
+//! Unlike the limiter from `pwasm-utils`, which instruments call sites and generates thunks for
+//! functions called from the outside, this one instruments function bodies, so a function is
+//! accounted for the same way whether it is called by the host, directly or through a table.
+//!
+//! [`StackLimiterVersion::V2`]: near_vm_logic::StackLimiterVersion::V2
+
+use std::convert::TryFrom;
+
+use near_vm_errors::PrepareError;
+use near_vm_logic::VMLimitConfig;
+use pwasm_utils::parity_wasm::builder;
+use pwasm_utils::parity_wasm::elements::{
+    self, BlockType, FunctionType, GlobalEntry, GlobalType, ImportCountType, InitExpr, Instruction,
+    Module, Type, ValueType,
+};
+
+/// Instruments `module` to limit the stack height and the call depth according to `config`.
+pub(crate) fn inject_limiter(
+    module: Module,
+    config: &VMLimitConfig,
+) -> Result<Module, PrepareError> {
+    let (max_call_depth, local_cost) = match (config.max_call_depth, config.stack_height_local_cost)
+    {
+        (Some(max_call_depth), Some(local_cost)) => (max_call_depth, local_cost),
+        _ => return Err(PrepareError::StackHeightInstrumentation),
+    };
+
+    let types: Vec<FunctionType> = module
+        .type_section()
+        .map(|section| {
+            section.types().iter().map(|ty| match ty {
+                Type::Function(func_type) => func_type.clone(),
+            })
+        })
+        .into_iter()
+        .flatten()
+        .collect();
+    let mut func_types: Vec<u32> = module
+        .import_section()
+        .map(elements::ImportSection::entries)
+        .unwrap_or(&[])
+        .iter()
+        .filter_map(|entry| match entry.external() {
+            elements::External::Function(type_idx) => Some(*type_idx),
+            _ => None,
+        })
+        .collect();
+    let defined_types: Vec<u32> = module
+        .function_section()
+        .map(elements::FunctionSection::entries)
+        .unwrap_or(&[])
+        .iter()
+        .map(elements::Func::type_ref)
+        .collect();
+    func_types.extend(&defined_types);
+    let bodies = module.code_section().map(elements::CodeSection::bodies).unwrap_or(&[]);
+    if bodies.len() != defined_types.len() {
+        return Err(PrepareError::StackHeightInstrumentation);
+    }
+
+    let mut instrumentation = Vec::with_capacity(bodies.len());
+    for (&type_idx, body) in defined_types.iter().zip(bodies) {
+        let ty = func_type(&types, type_idx)?;
+        let height = max_height(body.code().elements(), ty, &types, &func_types)?;
+        let locals = body
+            .locals()
+            .iter()
+            .map(|local| u64::from(local.count()))
+            .sum::<u64>()
+            .checked_add(ty.params().len() as u64)
+            .ok_or(PrepareError::StackHeightInstrumentation)?;
+        let cost = locals
+            .checked_mul(u64::from(local_cost))
+            .and_then(|cost| cost.checked_add(u64::from(height)))
+            .and_then(|cost| i32::try_from(cost).ok())
+            .ok_or(PrepareError::StackHeightInstrumentation)?;
+        instrumentation.push((cost, block_type(ty)));
+    }
+
+    let height_global = u32::try_from(
+        module.import_count(ImportCountType::Global)
+            + module.global_section().map_or(0, |section| section.entries().len()),
+    )
+    .map_err(|_| PrepareError::StackHeightInstrumentation)?;
+    let depth_global = height_global + 1;
+    let mut module =
+        builder::from_module(module).with_global(counter()).with_global(counter()).build();
+
+    let limits = Limits {
+        height_global,
+        depth_global,
+        max_stack_height: config.max_stack_height as i32,
+        max_call_depth: max_call_depth as i32,
+    };
+    if let Some(code_section) = module.code_section_mut() {
+        for (body, (cost, block_type)) in code_section.bodies_mut().iter_mut().zip(instrumentation)
+        {
+            let code = body.code_mut().elements_mut();
+            let mut original = std::mem::take(code);
+            // The final `end` of the function body, the wrapped code goes into a block instead.
+            if original.pop() != Some(Instruction::End) {
+                return Err(PrepareError::StackHeightInstrumentation);
+            }
+            code.reserve(original.len() + 32);
+            limits.prologue(cost, code);
+            // Branches to the outermost label of the original code now target this block, so
+            // they run the epilogue just like falling through to the end of the function does.
+            code.push(Instruction::Block(block_type));
+            for instruction in original {
+                if instruction == Instruction::Return {
+                    limits.epilogue(cost, code);
+                }
+                code.push(instruction);
+            }
+            code.push(Instruction::End);
+            limits.epilogue(cost, code);
+            code.push(Instruction::End);
+        }
+    }
+    Ok(module)
+}
+
+struct Limits {
+    height_global: u32,
+    depth_global: u32,
+    // Both are compared with `i32.gt_u`, so the casts don't change the meaning of the limits.
+    max_stack_height: i32,
+    max_call_depth: i32,
+}
+
+impl Limits {
+    fn prologue(&self, cost: i32, code: &mut Vec<Instruction>) {
+        for (global, delta, limit) in [
+            (self.height_global, cost, self.max_stack_height),
+            (self.depth_global, 1, self.max_call_depth),
+        ] {
+            code.extend([
+                Instruction::GetGlobal(global),
+                Instruction::I32Const(delta),
+                Instruction::I32Add,
+                Instruction::SetGlobal(global),
+                Instruction::GetGlobal(global),
+                Instruction::I32Const(limit),
+                Instruction::I32GtU,
+                Instruction::If(BlockType::NoResult),
+                Instruction::Unreachable,
+                Instruction::End,
+            ]);
+        }
+    }
+
+    fn epilogue(&self, cost: i32, code: &mut Vec<Instruction>) {
+        for (global, delta) in [(self.height_global, cost), (self.depth_global, 1)] {
+            code.extend([
+                Instruction::GetGlobal(global),
+                Instruction::I32Const(delta),
+                Instruction::I32Sub,
+                Instruction::SetGlobal(global),
+            ]);
+        }
+    }
+}
+
+fn counter() -> GlobalEntry {
+    GlobalEntry::new(
+        GlobalType::new(ValueType::I32, true),
+        InitExpr::new(vec![Instruction::I32Const(0), Instruction::End]),
+    )
+}
+
+fn func_type(types: &[FunctionType], type_idx: u32) -> Result<&FunctionType, PrepareError> {
+    types.get(type_idx as usize).ok_or(PrepareError::StackHeightInstrumentation)
+}
+
+fn block_type(func_type: &FunctionType) -> BlockType {
+    match func_type.results().first() {
+        Some(value_type) => BlockType::Value(*value_type),
+        None => BlockType::NoResult,
+    }
+}
+
+fn block_arity(block_type: &BlockType) -> u32 {
+    match block_type {
+        BlockType::Value(_) => 1,
+        _ => 0,
+    }
+}
+
+struct Frame {
+    start: u32,
+    arity: u32,
+}
+
+/// Operand stack of a function, tracking only its height.
+struct Stack {
+    frames: Vec<Frame>,
+    height: u32,
+    max_height: u32,
+}
+
+impl Stack {
+    fn frame(&self) -> Result<&Frame, PrepareError> {
+        self.frames.last().ok_or(PrepareError::StackHeightInstrumentation)
+    }
+
+    /// Values below the start of the current frame can't be popped by valid code, pops after
+    /// an unconditional branch are from the unreachable part of the frame.
+    fn pop(&mut self, count: u32) -> Result<(), PrepareError> {
+        let start = self.frame()?.start;
+        self.height = self.height.saturating_sub(count).max(start);
+        Ok(())
+    }
+
+    fn push(&mut self, count: u32) -> Result<(), PrepareError> {
+        self.height =
+            self.height.checked_add(count).ok_or(PrepareError::StackHeightInstrumentation)?;
+        self.max_height = self.max_height.max(self.height);
+        Ok(())
+    }
+
+    fn call(&mut self, func_type: &FunctionType) -> Result<(), PrepareError> {
+        self.pop(func_type.params().len() as u32)?;
+        self.push(func_type.results().len() as u32)
+    }
+}
+
+/// Computes the max height of the operand stack of a function with `code`.
+fn max_height(
+    code: &[Instruction],
+    func_type: &FunctionType,
+    types: &[FunctionType],
+    func_types: &[u32],
+) -> Result<u32, PrepareError> {
+    let mut stack = Stack {
+        frames: vec![Frame { start: 0, arity: block_arity(&block_type(func_type)) }],
+        height: 0,
+        max_height: 0,
+    };
+    for instruction in code {
+        match instruction {
+            Instruction::Block(block_type) | Instruction::Loop(block_type) => {
+                stack.frames.push(Frame { start: stack.height, arity: block_arity(block_type) })
+            }
+            Instruction::If(block_type) => {
+                stack.pop(1)?;
+                stack.frames.push(Frame { start: stack.height, arity: block_arity(block_type) })
+            }
+            Instruction::Else => stack.height = stack.frame()?.start,
+            Instruction::End => {
+                let frame = stack.frames.pop().ok_or(PrepareError::StackHeightInstrumentation)?;
+                stack.height = frame.start;
+                stack.push(frame.arity)?;
+            }
+            Instruction::Unreachable
+            | Instruction::Br(_)
+            | Instruction::BrTable(_)
+            | Instruction::Return => stack.height = stack.frame()?.start,
+            Instruction::Call(func_idx) => {
+                let type_idx = *func_types
+                    .get(*func_idx as usize)
+                    .ok_or(PrepareError::StackHeightInstrumentation)?;
+                stack.call(func_type(types, type_idx)?)?;
+            }
+            Instruction::CallIndirect(type_idx, _) => {
+                stack.pop(1)?;
+                stack.call(func_type(types, *type_idx)?)?;
+            }
+            instruction => {
+                let (pops, pushes) = effect(instruction);
+                stack.pop(pops)?;
+                stack.push(pushes)?;
+            }
+        }
+    }
+    Ok(stack.max_height)
+}
+
+/// How many values a non-control instruction pops from and pushes onto the operand stack.
+fn effect(instruction: &Instruction) -> (u32, u32) {
+    use Instruction::*;
+    match instruction {
+        Nop => (0, 0),
+        I32Const(_) | I64Const(_) | F32Const(_) | F64Const(_) | GetLocal(_) | GetGlobal(_)
+        | CurrentMemory(_) => (0, 1),
+        Drop | SetLocal(_) | SetGlobal(_) | BrIf(_) => (1, 0),
+        Select => (3, 1),
+        I32Store(..) | I64Store(..) | F32Store(..) | F64Store(..) | I32Store8(..)
+        | I32Store16(..) | I64Store8(..) | I64Store16(..) | I64Store32(..) => (2, 0),
+        I32Eq | I32Ne | I32LtS | I32LtU | I32GtS | I32GtU | I32LeS | I32LeU | I32GeS | I32GeU
+        | I64Eq | I64Ne | I64LtS | I64LtU | I64GtS | I64GtU | I64LeS | I64LeU | I64GeS | I64GeU
+        | F32Eq | F32Ne | F32Lt | F32Gt | F32Le | F32Ge | F64Eq | F64Ne | F64Lt | F64Gt | F64Le
+        | F64Ge | I32Add | I32Sub | I32Mul | I32DivS | I32DivU | I32RemS | I32RemU | I32And
+        | I32Or | I32Xor | I32Shl | I32ShrS | I32ShrU | I32Rotl | I32Rotr | I64Add | I64Sub
+        | I64Mul | I64DivS | I64DivU | I64RemS | I64RemU | I64And | I64Or | I64Xor | I64Shl
+        | I64ShrS | I64ShrU | I64Rotl | I64Rotr | F32Add | F32Sub | F32Mul | F32Div | F32Min
+        | F32Max | F32Copysign | F64Add | F64Sub | F64Mul | F64Div | F64Min | F64Max
+        | F64Copysign => (2, 1),
+        // Loads, `tee_local`, `grow_memory`, unary operators and conversions.
+        _ => (1, 1),
+    }
+}
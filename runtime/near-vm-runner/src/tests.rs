@@ -1,6 +1,8 @@
 mod cache;
 mod compile_errors;
 mod contract_preload;
+#[cfg(feature = "protocol_feature_input_read")]
+mod input_read;
 mod rs_contract;
 mod runtime_errors;
 mod ts_contract;
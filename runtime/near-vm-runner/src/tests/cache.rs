@@ -2,9 +2,10 @@
 #![cfg(target_arch = "x86_64")]
 
 use super::{create_context, with_vm_variants, LATEST_PROTOCOL_VERSION};
+use crate::cache::{get_prepared_code_cache_key, prepare_contract_cached};
 use crate::internal::VMKind;
 use crate::wasmer2_runner::Wasmer2VM;
-use crate::{prepare, MockCompiledContractCache};
+use crate::{precompile_contract_vm, prepare, MockCompiledContractCache};
 use assert_matches::assert_matches;
 use near_primitives::contract::ContractCode;
 use near_primitives::runtime::fees::RuntimeFeesConfig;
@@ -15,7 +16,7 @@ use near_vm_logic::mocks::mock_external::MockedExternal;
 use near_vm_logic::{VMConfig, VMOutcome};
 use std::hash::{Hash, Hasher};
 use std::io;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use wasmer_compiler::{CpuFeature, Target};
 
 #[test]
@@ -90,6 +91,65 @@ fn make_cached_contract_call_vm(
     )
 }
 
+#[test]
+#[cfg(all(feature = "wasmer2_vm", feature = "wasmtime_vm"))]
+fn test_prepared_code_is_shared_between_vms() {
+    let cache = CountingCompiledContractCache::default();
+    let code = ContractCode::new(near_test_contracts::rs_contract().to_vec(), None);
+    let config = VMConfig::test();
+    precompile_contract_vm(VMKind::Wasmer2, &code, &config, Some(&cache)).unwrap().unwrap();
+    // Prepared code and the compiled artifact.
+    assert_eq!(cache.puts.load(Ordering::Relaxed), 2);
+
+    // Wasmtime doesn't store artifacts, and the prepared code is already there.
+    let (outcome, err) = make_cached_contract_call_vm(
+        &cache,
+        code.code(),
+        "log_something",
+        10u64.pow(14),
+        VMKind::Wasmtime,
+    );
+    assert!(outcome.is_some());
+    assert_eq!(err, None);
+    assert_eq!(cache.puts.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn test_prepared_code_cache_key() {
+    let code = ContractCode::new(near_test_contracts::trivial_contract().to_vec(), None);
+    let config = VMConfig::test();
+    let key = get_prepared_code_cache_key(&code, &config);
+
+    // Costs of host functions don't affect preparation.
+    let mut other_config = config.clone();
+    other_config.ext_costs.base += 1;
+    assert_eq!(get_prepared_code_cache_key(&code, &other_config), key);
+
+    let mut other_config = config.clone();
+    other_config.limit_config.max_stack_height += 1;
+    assert_ne!(get_prepared_code_cache_key(&code, &other_config), key);
+    let mut other_config = config.clone();
+    other_config.regular_op_cost += 1;
+    assert_ne!(get_prepared_code_cache_key(&code, &other_config), key);
+}
+
+#[test]
+fn test_prepared_code_invalid_record() {
+    let cache = MockCompiledContractCache::default();
+    let code = ContractCode::new(near_test_contracts::trivial_contract().to_vec(), None);
+    let config = VMConfig::test();
+    let key = get_prepared_code_cache_key(&code, &config);
+    cache.put(&key.0, b"garbage").unwrap();
+
+    let expected = prepare::prepare_contract(code.code(), &config).unwrap();
+    let prepared_code = prepare_contract_cached(&code, &config, Some(&cache)).unwrap().unwrap();
+    assert_eq!(prepared_code, expected);
+    // The record got replaced with a valid one.
+    assert_ne!(cache.get(&key.0).unwrap().unwrap(), b"garbage");
+    let prepared_code = prepare_contract_cached(&code, &config, Some(&cache)).unwrap().unwrap();
+    assert_eq!(prepared_code, expected);
+}
+
 #[test]
 fn test_wasmer2_artifact_output_stability() {
     // If this test has failed, you want to adjust the necessary constants so that `cache::vm_hash`
@@ -146,3 +206,49 @@ impl CompiledContractCache for FaultingCompiledContractCache {
         self.inner.get(key)
     }
 }
+
+/// [`CompiledContractCache`] which counts writes.
+#[derive(Default)]
+struct CountingCompiledContractCache {
+    puts: AtomicUsize,
+    inner: MockCompiledContractCache,
+}
+
+impl CompiledContractCache for CountingCompiledContractCache {
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<(), io::Error> {
+        self.puts.fetch_add(1, Ordering::Relaxed);
+        self.inner.put(key, value)
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, io::Error> {
+        self.inner.get(key)
+    }
+}
+
+#[cfg(all(any(feature = "wasmer0_vm", feature = "wasmer2_vm"), not(feature = "no_cache")))]
+#[test]
+fn test_memory_cache_metrics() {
+    use crate::cache::{MemoryCache, CACHE_SIZE};
+    use crate::metrics::{COMPILED_CONTRACT_LOOKUPS, MEMORY_CACHE_WASM_CODE_SIZE};
+
+    // There's no in-memory cache for Wasmtime, so no other test touches its gauge.
+    let cache = MemoryCache::<()>::new(VMKind::Wasmtime);
+    let size = MEMORY_CACHE_WASM_CODE_SIZE.with_label_values(&["wasmtime"]);
+    let memory_lookups = COMPILED_CONTRACT_LOOKUPS.with_label_values(&["memory"]);
+    let codes: Vec<_> =
+        (0..CACHE_SIZE + 1).map(|len| ContractCode::new(vec![42; len + 1], None)).collect();
+    for code in &codes {
+        cache.get_or_try_put(*code.hash(), code, |_| Ok::<_, ()>(())).unwrap();
+    }
+    // The first contract got evicted and its code doesn't count any more.
+    let expected = codes[1..].iter().map(|code| code.code().len() as i64).sum::<i64>();
+    assert_eq!(size.get(), expected);
+
+    let lookups = memory_lookups.get();
+    let code = &codes[CACHE_SIZE];
+    cache
+        .get_or_try_put(*code.hash(), code, |_| -> Result<(), ()> { panic!("contract is cached") })
+        .unwrap();
+    assert_eq!(size.get(), expected);
+    assert!(memory_lookups.get() > lookups);
+}
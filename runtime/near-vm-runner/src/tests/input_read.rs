@@ -0,0 +1,79 @@
+use near_primitives::contract::ContractCode;
+use near_primitives::runtime::fees::RuntimeFeesConfig;
+use near_vm_errors::{FunctionCallError, HostError, VMError};
+use near_vm_logic::mocks::mock_external::MockedExternal;
+use near_vm_logic::types::ReturnData;
+use near_vm_logic::{VMConfig, VMOutcome};
+
+use crate::tests::{create_context, with_vm_variants, LATEST_PROTOCOL_VERSION};
+use crate::vm_kind::VMKind;
+
+/// Size of the input, large enough for the per-byte costs to dominate.
+const INPUT_LEN: usize = 1 << 20;
+
+fn run(vm_kind: VMKind, method_name: &str, input: Vec<u8>) -> (Option<VMOutcome>, Option<VMError>) {
+    let code = ContractCode::new(near_test_contracts::input_read_contract().to_vec(), None);
+    let mut fake_external = MockedExternal::new();
+    let context = create_context(input);
+    let config = VMConfig::test();
+    let fees = RuntimeFeesConfig::test();
+    let runtime = vm_kind.runtime(config).expect("runtime has not been compiled");
+    runtime.run(
+        &code,
+        method_name,
+        &mut fake_external,
+        context,
+        &fees,
+        &[],
+        LATEST_PROTOCOL_VERSION,
+        None,
+    )
+}
+
+/// Reading the input directly returns the same data as going through a register while skipping
+/// the copy into and out of the register.
+#[test]
+fn test_input_read_skips_register_copy() {
+    with_vm_variants(|vm_kind: VMKind| {
+        let input: Vec<u8> = (0..INPUT_LEN).map(|i| i as u8).collect();
+        let expected = ReturnData::Value(input[INPUT_LEN - 8..].to_vec());
+
+        let (via_register, err) = run(vm_kind, "read_via_register", input.clone());
+        assert_eq!(err, None);
+        let via_register = via_register.unwrap();
+        assert_eq!(via_register.return_data, expected);
+
+        let (direct, err) = run(vm_kind, "read_direct", input);
+        assert_eq!(err, None);
+        let direct = direct.unwrap();
+        assert_eq!(direct.return_data, expected);
+
+        let ext_costs = VMConfig::test().ext_costs;
+        let register_copy_cost =
+            (ext_costs.write_register_byte + ext_costs.read_register_byte) * INPUT_LEN as u64;
+        assert!(
+            via_register.burnt_gas - direct.burnt_gas >= register_copy_cost,
+            "reading {} bytes through a register burnt {} gas, directly {} gas",
+            INPUT_LEN,
+            via_register.burnt_gas,
+            direct.burnt_gas
+        );
+    });
+}
+
+#[test]
+fn test_input_read_out_of_bounds() {
+    with_vm_variants(|vm_kind: VMKind| {
+        for method_name in ["read_past_input", "read_past_memory"] {
+            let (_, err) = run(vm_kind, method_name, vec![1, 2, 3]);
+            assert_eq!(
+                err,
+                Some(VMError::FunctionCallError(FunctionCallError::HostError(
+                    HostError::MemoryAccessViolation
+                ))),
+                "{}",
+                method_name
+            );
+        }
+    });
+}
@@ -1,14 +1,18 @@
 use super::gas_and_error_match;
 use crate::tests::{
-    make_simple_contract_call_vm, make_simple_contract_call_with_gas_vm,
-    make_simple_contract_call_with_protocol_version_vm, with_vm_variants,
+    create_context, make_simple_contract_call_vm, make_simple_contract_call_with_gas_vm,
+    make_simple_contract_call_with_protocol_version_vm, with_vm_variants, LATEST_PROTOCOL_VERSION,
 };
 use crate::vm_kind::VMKind;
+use near_primitives::contract::ContractCode;
+use near_primitives::runtime::fees::RuntimeFeesConfig;
 use near_primitives::version::ProtocolFeature;
 use near_vm_errors::{
     CompilationError, FunctionCallError, HostError, MethodResolveError, PrepareError, VMError,
     WasmTrap,
 };
+use near_vm_logic::mocks::mock_external::MockedExternal;
+use near_vm_logic::{StackLimiterVersion, VMConfig};
 
 fn infinite_initializer_contract() -> Vec<u8> {
     wat::parse_str(
@@ -533,6 +537,151 @@ fn test_stack_instrumentation_protocol_upgrade() {
     });
 }
 
+/// Recursion `depth` calls deep, called from `main`.  Both functions have max stack height 2
+/// and `$rec` has one param.
+fn deep_recursion(depth: u32) -> Vec<u8> {
+    wat::parse_str(format!(
+        r#"
+            (module
+              (func $rec (param i32)
+                (if (i32.gt_u (local.get 0) (i32.const 1))
+                  (then (call $rec (i32.sub (local.get 0) (i32.const 1))))))
+              (func (export "main")
+                (call $rec (i32.const {})))
+            )"#,
+        depth
+    ))
+    .unwrap()
+}
+
+fn stack_limiter_v2_config(
+    max_stack_height: u32,
+    max_call_depth: u32,
+    local_cost: u32,
+) -> VMConfig {
+    // Without gas metering the max stack height of the functions is exactly that of their code.
+    let mut config = VMConfig::free();
+    config.limit_config.stack_limiter_version = StackLimiterVersion::V2;
+    config.limit_config.max_stack_height = max_stack_height;
+    config.limit_config.max_call_depth = Some(max_call_depth);
+    config.limit_config.stack_height_local_cost = Some(local_cost);
+    config
+}
+
+fn call_with_config(code: &[u8], config: VMConfig, vm_kind: VMKind) -> Option<VMError> {
+    let mut fake_external = MockedExternal::new();
+    let runtime = vm_kind.runtime(config).expect("runtime has not been compiled");
+    let code = ContractCode::new(code.to_vec(), None);
+    let (_, err) = runtime.run(
+        &code,
+        "main",
+        &mut fake_external,
+        create_context(vec![]),
+        &RuntimeFeesConfig::test(),
+        &[],
+        LATEST_PROTOCOL_VERSION,
+        None,
+    );
+    err
+}
+
+#[test]
+fn test_stack_limiter_v2_call_depth() {
+    with_vm_variants(|vm_kind: VMKind| {
+        let config = stack_limiter_v2_config(1 << 20, 100, 0);
+        // `main` and 99 frames of `$rec` are exactly at the limit.
+        assert_eq!(call_with_config(&deep_recursion(99), config.clone(), vm_kind), None);
+        // See the comment in test_stack_overflow.
+        if matches!(vm_kind, VMKind::Wasmtime) {
+            return;
+        }
+        assert_eq!(
+            call_with_config(&deep_recursion(100), config, vm_kind),
+            Some(VMError::FunctionCallError(FunctionCallError::WasmTrap(WasmTrap::Unreachable)))
+        );
+    });
+}
+
+#[test]
+fn test_stack_limiter_v2_stack_height() {
+    with_vm_variants(|vm_kind: VMKind| {
+        // `main` costs 1 and each frame of `$rec` costs 3 for its param plus 2 for its operands.
+        let config = stack_limiter_v2_config(1 + 10 * 5, 1 << 20, 3);
+        assert_eq!(call_with_config(&deep_recursion(10), config.clone(), vm_kind), None);
+        // See the comment in test_stack_overflow.
+        if matches!(vm_kind, VMKind::Wasmtime) {
+            return;
+        }
+        assert_eq!(
+            call_with_config(&deep_recursion(11), config, vm_kind),
+            Some(VMError::FunctionCallError(FunctionCallError::WasmTrap(WasmTrap::Unreachable)))
+        );
+    });
+}
+
+#[test]
+fn test_stack_limiter_v2_requires_limits() {
+    with_vm_variants(|vm_kind: VMKind| {
+        let mut config = stack_limiter_v2_config(1 << 20, 100, 0);
+        config.limit_config.max_call_depth = None;
+        assert_eq!(
+            call_with_config(&deep_recursion(1), config, vm_kind),
+            Some(VMError::FunctionCallError(FunctionCallError::CompilationError(
+                CompilationError::PrepareError(PrepareError::StackHeightInstrumentation)
+            )))
+        );
+    });
+}
+
+/// Recursion where each frame has 4 operands on the stack, which is too deep for the old limit.
+#[cfg(feature = "protocol_feature_stack_limiter_v2")]
+fn deep_recursion_with_operands() -> Vec<u8> {
+    wat::parse_str(
+        r#"
+            (module
+              (func $rec (param i32)
+                (drop (i32.add (i32.const 1)
+                  (i32.add (i32.const 2) (i32.add (i32.const 3) (i32.const 4)))))
+                (if (local.get 0)
+                  (then (call $rec (i32.sub (local.get 0) (i32.const 1))))))
+              (func (export "main")
+                (call $rec (i32.const 4500)))
+            )"#,
+    )
+    .unwrap()
+}
+
+#[cfg(feature = "protocol_feature_stack_limiter_v2")]
+#[test]
+fn test_stack_limiter_v2_protocol_upgrade() {
+    with_vm_variants(|vm_kind: VMKind| {
+        let code = deep_recursion_with_operands();
+        let (outcome, err) = make_simple_contract_call_with_protocol_version_vm(
+            &code,
+            "main",
+            ProtocolFeature::StackLimiterV2.protocol_version(),
+            vm_kind,
+        );
+        assert_eq!(err, None);
+        assert!(outcome.is_some());
+
+        // See the comment in test_stack_overflow.
+        if matches!(vm_kind, VMKind::Wasmtime) {
+            return;
+        }
+        let (_, err) = make_simple_contract_call_with_protocol_version_vm(
+            &code,
+            "main",
+            ProtocolFeature::StackLimiterV2.protocol_version() - 1,
+            vm_kind,
+        );
+        assert_eq!(
+            err,
+            Some(VMError::FunctionCallError(FunctionCallError::WasmTrap(WasmTrap::Unreachable)))
+        );
+    });
+}
+
 fn memory_grow() -> Vec<u8> {
     wat::parse_str(
         r#"
@@ -563,6 +712,105 @@ fn test_memory_grow() {
     });
 }
 
+fn memory_above_max(memory: &str) -> Vec<u8> {
+    wat::parse_str(format!(
+        r#"
+            (module
+              {}
+              (func (export "main")))"#,
+        memory
+    ))
+    .unwrap()
+}
+
+fn strict_memory_limits_config() -> VMConfig {
+    let mut config = VMConfig::test();
+    config.limit_config.strict_memory_limits = true;
+    config
+}
+
+#[test]
+fn test_memory_above_max_is_rejected() {
+    with_vm_variants(|vm_kind: VMKind| {
+        for memory in
+            ["(memory 2049)", "(memory 1 2049)", r#"(import "env" "memory" (memory 1 2049))"#]
+        {
+            assert_eq!(
+                call_with_config(&memory_above_max(memory), strict_memory_limits_config(), vm_kind),
+                Some(VMError::FunctionCallError(FunctionCallError::CompilationError(
+                    CompilationError::PrepareError(PrepareError::Memory)
+                ))),
+                "{}",
+                memory
+            );
+        }
+        assert_eq!(
+            call_with_config(
+                &memory_above_max("(memory 2048 2048)"),
+                strict_memory_limits_config(),
+                vm_kind
+            ),
+            None
+        );
+    });
+}
+
+/// Grows the memory, which starts at `initial_memory_pages` (1024), by 1025 pages which is above
+/// `max_memory_pages` (2048), then up to exactly the max.
+fn memory_grow_above_max() -> Vec<u8> {
+    wat::parse_str(
+        r#"
+            (module
+              (memory 1)
+              (func (export "main")
+                (if (i32.ne (memory.grow (i32.const 1025)) (i32.const -1))
+                  (then unreachable))
+                (if (i32.ne (memory.grow (i32.const 1024)) (i32.const 1024))
+                  (then unreachable))
+                (if (i32.ne (memory.grow (i32.const 1)) (i32.const -1))
+                  (then unreachable))))"#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_memory_grow_above_max_fails() {
+    with_vm_variants(|vm_kind: VMKind| {
+        assert_eq!(
+            call_with_config(&memory_grow_above_max(), strict_memory_limits_config(), vm_kind),
+            None
+        );
+    });
+}
+
+#[cfg(feature = "protocol_feature_memory_limit_check")]
+#[test]
+fn test_memory_limit_check_protocol_upgrade() {
+    with_vm_variants(|vm_kind: VMKind| {
+        let code = memory_above_max("(memory 1 4096)");
+        let protocol_version = ProtocolFeature::MemoryLimitCheck.protocol_version();
+        let (_, err) = make_simple_contract_call_with_protocol_version_vm(
+            &code,
+            "main",
+            protocol_version - 1,
+            vm_kind,
+        );
+        assert_eq!(err, None);
+        let (_, err) = make_simple_contract_call_with_protocol_version_vm(
+            &code,
+            "main",
+            protocol_version,
+            vm_kind,
+        );
+        assert_eq!(
+            err,
+            Some(VMError::FunctionCallError(FunctionCallError::CompilationError(
+                CompilationError::PrepareError(PrepareError::Memory)
+            )))
+        );
+    });
+}
+
 fn bad_import_global(env: &str) -> Vec<u8> {
     wat::parse_str(format!(
         r#"
@@ -1,6 +1,8 @@
 use crate::cache::into_vm_result;
 use crate::imports::wasmer2::Wasmer2Imports;
+use crate::metrics::check_slow_compilation;
 use crate::prepare::WASM_FEATURES;
+use crate::vm_kind::VMKind;
 use crate::{cache, imports};
 use memoffset::offset_of;
 use near_primitives::contract::ContractCode;
@@ -14,6 +16,7 @@ use near_vm_logic::{External, MemoryLike, VMConfig, VMContext, VMLogic, VMOutcom
 use std::hash::{Hash, Hasher};
 use std::mem::size_of;
 use std::sync::Arc;
+use std::time::Instant;
 use wasmer_compiler_singlepass::Singlepass;
 use wasmer_engine::{DeserializeError, Engine};
 use wasmer_engine_universal::{Universal, UniversalEngine};
@@ -543,8 +546,15 @@ impl crate::runner::VM for Wasmer2VM {
                 ))),
             );
         }
+        let started = Instant::now();
         let artifact =
             cache::wasmer2_cache::compile_module_cached_wasmer2(code, &self.config, cache);
+        check_slow_compilation(
+            VMKind::Wasmer2,
+            &context.current_account_id,
+            code,
+            started.elapsed(),
+        );
         let artifact = match into_vm_result(artifact) {
             Ok(it) => it,
             Err(err) => return (None, Some(err)),
@@ -592,7 +602,7 @@ impl crate::runner::VM for Wasmer2VM {
         cache: &dyn CompiledContractCache,
     ) -> Option<VMError> {
         let result = crate::cache::wasmer2_cache::compile_and_serialize_wasmer2(
-            code,
+            &ContractCode::new(code.to_vec(), None),
             code_hash,
             &self.config,
             cache,
@@ -1,7 +1,9 @@
 use crate::cache::into_vm_result;
 use crate::errors::IntoVMError;
 use crate::memory::WasmerMemory;
+use crate::metrics::check_slow_compilation;
 use crate::prepare::WASM_FEATURES;
+use crate::vm_kind::VMKind;
 use crate::{cache, imports};
 use near_primitives::config::VMConfig;
 use near_primitives::contract::ContractCode;
@@ -11,6 +13,7 @@ use near_primitives::version::ProtocolVersion;
 use near_vm_errors::{CompilationError, FunctionCallError, MethodResolveError, VMError, WasmTrap};
 use near_vm_logic::types::PromiseResult;
 use near_vm_logic::{External, VMContext, VMLogic, VMLogicError, VMOutcome};
+use std::time::Instant;
 use wasmer_runtime::{ImportObject, Module};
 
 const WASMER_FEATURES: wasmer_runtime::Features =
@@ -320,7 +323,14 @@ impl crate::runner::VM for Wasmer0VM {
         }
 
         // TODO: consider using get_module() here, once we'll go via deployment path.
+        let started = Instant::now();
         let module = cache::wasmer0_cache::compile_module_cached_wasmer0(code, &self.config, cache);
+        check_slow_compilation(
+            VMKind::Wasmer0,
+            &context.current_account_id,
+            code,
+            started.elapsed(),
+        );
         let module = match into_vm_result(module) {
             Ok(x) => x,
             Err(err) => return (None, Some(err)),
@@ -371,7 +381,7 @@ impl crate::runner::VM for Wasmer0VM {
         cache: &dyn CompiledContractCache,
     ) -> Option<VMError> {
         let result = crate::cache::wasmer0_cache::compile_and_serialize_wasmer(
-            code,
+            &ContractCode::new(code.to_vec(), None),
             &self.config,
             code_hash,
             cache,
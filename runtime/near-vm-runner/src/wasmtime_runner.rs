@@ -1,6 +1,11 @@
+use crate::cache::prepare_contract_cached;
 use crate::errors::IntoVMError;
+use crate::imports;
+use crate::metrics::{
+    check_slow_compilation, record_lookup, vm_kind_label, CacheLayer, COMPILATION_TIME,
+};
 use crate::prepare::WASM_FEATURES;
-use crate::{imports, prepare};
+use crate::vm_kind::VMKind;
 use near_primitives::config::VMConfig;
 use near_primitives::contract::ContractCode;
 use near_primitives::hash::CryptoHash;
@@ -16,6 +21,7 @@ use near_vm_logic::{External, MemoryLike, VMContext, VMLogic, VMOutcome};
 use std::cell::RefCell;
 use std::ffi::c_void;
 use std::str;
+use std::time::Instant;
 use wasmtime::ExternType::Func;
 use wasmtime::{Engine, Linker, Memory, MemoryType, Module, Store, TrapCode};
 
@@ -186,7 +192,7 @@ impl crate::runner::VM for WasmtimeVM {
         fees_config: &RuntimeFeesConfig,
         promise_results: &[PromiseResult],
         current_protocol_version: ProtocolVersion,
-        _cache: Option<&dyn CompiledContractCache>,
+        cache: Option<&dyn CompiledContractCache>,
     ) -> (Option<VMOutcome>, Option<VMError>) {
         let _span = tracing::debug_span!(
             target: "vm",
@@ -204,14 +210,26 @@ impl crate::runner::VM for WasmtimeVM {
             self.config.limit_config.max_memory_pages,
         )
         .unwrap();
-        let prepared_code = match prepare::prepare_contract(code.code(), &self.config) {
-            Ok(code) => code,
-            Err(err) => return (None, Some(VMError::from(err))),
+        let started = Instant::now();
+        let timer =
+            COMPILATION_TIME.with_label_values(&[vm_kind_label(VMKind::Wasmtime)]).start_timer();
+        let prepared_code = match prepare_contract_cached(code, &self.config, cache) {
+            Ok(Ok(code)) => code,
+            Ok(Err(err)) => return (None, Some(VMError::from(err))),
+            Err(err) => return (None, Some(VMError::CacheError(err))),
         };
         let module = match Module::new(&engine, prepared_code) {
             Ok(module) => module,
             Err(err) => return (None, Some(err.into_vm_error())),
         };
+        timer.observe_duration();
+        record_lookup(CacheLayer::Miss);
+        check_slow_compilation(
+            VMKind::Wasmtime,
+            &context.current_account_id,
+            code,
+            started.elapsed(),
+        );
         let mut linker = Linker::new(&engine);
         let memory_copy = memory.0;
         let mut logic = VMLogic::new_with_protocol_version(
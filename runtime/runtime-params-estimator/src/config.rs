@@ -2,6 +2,7 @@ use near_vm_runner::internal::VMKind;
 use std::path::PathBuf;
 
 use crate::rocksdb::RocksDBTestConfig;
+use crate::trie_scaling::TrieScalingConfig;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GasMetric {
@@ -32,6 +33,8 @@ pub struct Config {
     pub costs_to_measure: Option<Vec<String>>,
     /// Configuration specific to raw RocksDB tests. Does NOT affect normal tests that use RocksDB through the nearcore interface.
     pub rocksdb_test_config: RocksDBTestConfig,
+    /// Configuration of the measurements of storage costs on tries of growing sizes.
+    pub trie_scaling_config: TrieScalingConfig,
     /// Print extra details on least-squares computation
     pub debug_least_squares: bool,
     /// Print JSON output for estimation results.
@@ -36,6 +36,12 @@ pub enum Cost {
     ActionSirReceiptCreation,
     DataReceiptCreationBase,
     DataReceiptCreationPerByte,
+    /// Estimates `data_receipt_creation_config.large_data_cost.cost_per_byte`, which is charged
+    /// for every byte of a returned value above the `large_data_cost.threshold`.
+    ///
+    /// Estimation: Measure the creation and execution of 100 data receipts with 1 MiB of data
+    /// each and subtract the same for 10 bytes of data, then divide by the number of bytes.
+    DataReceiptCreationPerLargeByte,
     ActionCreateAccount,
     // Deploying a new contract for an account on the blockchain stores the WASM
     // code in the trie. Additionally, it also triggers a compilation of the
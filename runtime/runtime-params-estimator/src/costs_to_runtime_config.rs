@@ -1,7 +1,7 @@
 use near_primitives::runtime::config::AccountCreationConfig;
 use near_primitives::runtime::config_store::RuntimeConfigStore;
 use near_primitives::runtime::fees::{
-    AccessKeyCreationConfig, ActionCreationConfig, DataReceiptCreationConfig, Fee,
+    AccessKeyCreationConfig, ActionCreationConfig, DataReceiptCreationConfig, Fee, LargeDataCost,
     RuntimeFeesConfig,
 };
 use near_primitives::types::Gas;
@@ -43,6 +43,7 @@ pub fn costs_to_runtime_config(cost_table: &CostTable) -> anyhow::Result<Runtime
             limit_config: vm_limit_config,
         },
         account_creation_config: AccountCreationConfig::default(),
+        protocol_treasury_split: vec![],
     };
     Ok(res)
 }
@@ -62,6 +63,15 @@ fn runtime_fees_config(cost_table: &CostTable) -> anyhow::Result<RuntimeFeesConf
         data_receipt_creation_config: DataReceiptCreationConfig {
             base_cost: fee(Cost::DataReceiptCreationBase)?,
             cost_per_byte: fee(Cost::DataReceiptCreationPerByte)?,
+            // The threshold isn't estimated, only the cost of the bytes above it.
+            large_data_cost: match &actual_fees_config.data_receipt_creation_config.large_data_cost
+            {
+                Some(large_data_cost) => Some(LargeDataCost {
+                    threshold: large_data_cost.threshold,
+                    cost_per_byte: fee(Cost::DataReceiptCreationPerLargeByte)?,
+                }),
+                None => None,
+            },
         },
         action_creation_config: ActionCreationConfig {
             create_account_cost: fee(Cost::ActionCreateAccount)?,
@@ -159,6 +169,7 @@ fn ext_costs_config(cost_table: &CostTable) -> anyhow::Result<ExtCostsConfig> {
         alt_bn128_pairing_check_base: get(Cost::AltBn128PairingCheckBase)?,
         #[cfg(feature = "protocol_feature_alt_bn128")]
         alt_bn128_pairing_check_byte: get(Cost::AltBn128PairingCheckByte)?,
+        compute_costs: Default::default(),
     };
 
     Ok(res)
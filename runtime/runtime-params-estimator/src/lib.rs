@@ -54,6 +54,7 @@ mod gas_cost;
 mod qemu;
 mod rocksdb;
 mod transaction_builder;
+mod trie_scaling;
 
 pub(crate) mod estimator_params;
 pub(crate) mod least_squares;
@@ -114,6 +115,9 @@ pub use crate::cost_table::CostTable;
 pub use crate::costs_to_runtime_config::costs_to_runtime_config;
 pub use crate::qemu::QemuCommandBuilder;
 pub use crate::rocksdb::RocksDBTestConfig;
+pub use crate::trie_scaling::{
+    trie_scaling_report, TrieScalingConfig, TrieScalingPoint, TrieScalingReport,
+};
 
 static ALL_COSTS: &[(Cost, fn(&mut EstimatorContext) -> GasCost)] = &[
     (Cost::ActionReceiptCreation, action_receipt_creation),
@@ -136,6 +140,7 @@ static ALL_COSTS: &[(Cost, fn(&mut EstimatorContext) -> GasCost)] = &[
     (Cost::WasmInstruction, wasm_instruction),
     (Cost::DataReceiptCreationBase, data_receipt_creation_base),
     (Cost::DataReceiptCreationPerByte, data_receipt_creation_per_byte),
+    (Cost::DataReceiptCreationPerLargeByte, data_receipt_creation_per_large_byte),
     (Cost::ReadMemoryBase, read_memory_base),
     (Cost::ReadMemoryByte, read_memory_byte),
     (Cost::WriteMemoryBase, write_memory_base),
@@ -431,7 +436,7 @@ fn add_key_transaction(
     let receiver = sender.clone();
 
     let public_key = "ed25519:DcA2MzgpJbrUATQLLceocVckhhAqrkingax4oJ9kZ847".parse().unwrap();
-    let access_key = AccessKey { nonce: 0, permission };
+    let access_key = AccessKey { nonce: 0, permission, valid_until_block_height: None };
 
     tb.transaction_from_actions(
         sender,
@@ -713,6 +718,21 @@ fn data_receipt_creation_per_byte(ctx: &mut EstimatorContext) -> GasCost {
     total_cost.saturating_sub(&base_cost, &NonNegativeTolerance::PER_MILLE) / bytes_per_transaction
 }
 
+fn data_receipt_creation_per_large_byte(ctx: &mut EstimatorContext) -> GasCost {
+    // NB: there isn't `ExtCosts` for data receipt creation, so we ignore (`_`) the counts.
+    // The function returns a chain of two promises.
+    let block_latency = 2;
+    let (total_cost, _) =
+        fn_cost_count(ctx, "data_receipt_1mib_100", ExtCosts::base, block_latency);
+    // The function returns a chain of two promises.
+    let block_latency = 2;
+    let (base_cost, _) = fn_cost_count(ctx, "data_receipt_10b_100", ExtCosts::base, block_latency);
+
+    let bytes_per_transaction = 100 * 1024 * 1024;
+
+    total_cost.saturating_sub(&base_cost, &NonNegativeTolerance::PER_MILLE) / bytes_per_transaction
+}
+
 fn host_function_call(ctx: &mut EstimatorContext) -> GasCost {
     let block_latency = 0;
     let (total_cost, count) = fn_cost_count(ctx, "base_1M", ExtCosts::base, block_latency);
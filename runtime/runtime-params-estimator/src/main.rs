@@ -11,7 +11,8 @@ use nearcore::{get_store_path, load_config};
 use runtime_params_estimator::config::{Config, GasMetric};
 use runtime_params_estimator::utils::read_resource;
 use runtime_params_estimator::{
-    costs_to_runtime_config, CostTable, QemuCommandBuilder, RocksDBTestConfig,
+    costs_to_runtime_config, trie_scaling_report, CostTable, QemuCommandBuilder, RocksDBTestConfig,
+    TrieScalingConfig,
 };
 use std::env;
 use std::fmt::Write;
@@ -85,6 +86,9 @@ struct CliArgs {
     /// Extra configuration parameters for RocksDB specific estimations
     #[clap(flatten)]
     db_test_config: RocksDBTestConfig,
+    /// Configuration of the measurements of storage costs on tries of growing sizes
+    #[clap(flatten)]
+    trie_scaling_config: TrieScalingConfig,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -119,7 +123,8 @@ fn main() -> anyhow::Result<()> {
             temp_dir.path().to_path_buf()
         }
     };
-    if state_dump_path.read_dir()?.next().is_none() {
+    // Tries for the scaling measurements are built separately, from generated accounts.
+    if state_dump_path.read_dir()?.next().is_none() && !cli_args.trie_scaling_config.enabled {
         let contract_code = read_resource(if cfg!(feature = "nightly_protocol_features") {
             "test-contract/res/nightly_small_contract.wasm"
         } else {
@@ -232,10 +237,18 @@ fn main() -> anyhow::Result<()> {
         vm_kind,
         costs_to_measure,
         rocksdb_test_config,
+        trie_scaling_config: cli_args.trie_scaling_config,
         debug_least_squares: debug_options.contains(&"least-squares"),
         json_output: cli_args.json_output,
         drop_os_cache: cli_args.drop_os_cache,
     };
+    if config.trie_scaling_config.enabled {
+        let report = trie_scaling_report(&config)?;
+        println!("{}", report);
+        eprintln!("\nFinished in {:.2?}", start.elapsed());
+        return Ok(());
+    }
+
     let cost_table = runtime_params_estimator::run(config);
 
     let output_path = {
@@ -69,9 +69,11 @@ impl RuntimeTestbed {
             block_hash: Default::default(),
             epoch_id: Default::default(),
             epoch_height: 0,
+            shard_id: 0,
             gas_price: 0,
             block_timestamp: 0,
             gas_limit: None,
+            compute_limit: None,
             random_seed: Default::default(),
             current_protocol_version: PROTOCOL_VERSION,
             config: Arc::new(runtime_config),
@@ -0,0 +1,402 @@
+//! Sensitivity of the storage costs to the size of the trie.
+//!
+//! The regular storage estimations run against the state of the testbed, which
+//! has a few hundred thousand accounts at most. Real shards have hundreds of
+//! millions of trie nodes, so reads and writes go through deeper paths and miss
+//! the caches more often. This module builds tries with a growing number of
+//! generated accounts, measures `storage_read`, `storage_write` and
+//! `touching_trie_node` on each of them and reports how the costs scale with
+//! the depth of the trie, flagging the parameters whose measured cost at the
+//! largest size exceeds the configured gas.
+//!
+//! Building a trie with 1e8 accounts takes hours, so the tries are kept on disk
+//! between runs, one database per size. The construction commits the accounts
+//! in batches and resumes from the last committed batch when interrupted.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::Context;
+use clap::Parser;
+use near_primitives::account::Account;
+use near_primitives::hash::CryptoHash;
+use near_primitives::runtime::config_store::RuntimeConfigStore;
+use near_primitives::shard_layout::ShardUId;
+use near_primitives::trie_key::TrieKey;
+use near_primitives::types::{AccountId, Gas, StateChangeCause, StateRoot};
+use near_primitives::version::PROTOCOL_VERSION;
+use near_store::{create_store, set_account, ShardTries, Store, TrieUpdate};
+use near_vm_logic::{ExtCostsConfig, External, ValuePtr};
+use node_runtime::ext::RuntimeExt;
+use node_runtime::test_utils::TestExtContext;
+use rand::Rng;
+use rand_xorshift::XorShiftRng;
+use serde_json::json;
+
+use crate::config::{Config, GasMetric};
+use crate::gas_cost::GasCost;
+use crate::utils::get_account_id;
+
+#[derive(Debug, Clone, Parser)]
+pub struct TrieScalingConfig {
+    /// Measure the storage costs on tries of growing sizes and report how they
+    /// scale, instead of estimating the costs.
+    #[clap(name = "trie-scaling", long)]
+    pub enabled: bool,
+    /// Comma separated numbers of generated accounts in the measured tries,
+    /// each account with one contract data key.
+    /// (`--trie-scaling` only)
+    #[clap(name = "trie-scaling-sizes", long, default_value = "10000,1000000,100000000")]
+    pub sizes: String,
+    /// Directory where the tries are kept between runs, a directory in the
+    /// system temporary directory by default.
+    /// (`--trie-scaling` only)
+    #[clap(name = "trie-scaling-cache-dir", long, parse(from_os_str))]
+    pub cache_dir: Option<PathBuf>,
+    /// Number of reads and writes measured on each trie.
+    /// (`--trie-scaling` only)
+    #[clap(name = "trie-scaling-ops", long, default_value = "1000")]
+    pub ops: usize,
+    /// A parameter is flagged when its measured cost multiplied by this factor
+    /// exceeds the configured gas.
+    /// (`--trie-scaling` only)
+    #[clap(name = "trie-scaling-safety-factor", long, default_value = "1")]
+    pub safety_factor: f64,
+}
+
+/// Number of accounts committed to the database at once while building a trie.
+const BATCH_SIZE: usize = 100_000;
+const DATA_KEY: &[u8] = b"0123456789";
+const DATA_VALUE: &[u8] = b"9876543210";
+const KEY_SEED: u64 = 0x6f1e0ac97b3c2d55;
+
+/// Trie of generated accounts stored in its own database.
+struct CachedTrie {
+    store: Store,
+    root: StateRoot,
+}
+
+impl CachedTrie {
+    /// Opens the trie with `num_accounts` accounts in `dir`, first adding the
+    /// accounts missing from it in batches of `batch_size`.
+    ///
+    /// The progress is saved after every batch. If the process is killed
+    /// between committing a batch and saving the progress, the batch is
+    /// inserted again on the next run, which only bumps the reference counts of
+    /// its nodes.
+    fn open_or_build(dir: &Path, num_accounts: usize, batch_size: usize) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+        let store = create_store(&dir.join("data"));
+        let tries = ShardTries::new(store.clone(), 0, 1);
+        let progress_path = dir.join("progress");
+        let (mut written, mut root) = read_progress(&progress_path)?;
+        anyhow::ensure!(
+            written <= num_accounts,
+            "{} already has {} accounts, more than {}",
+            dir.display(),
+            written,
+            num_accounts
+        );
+        if written < num_accounts {
+            eprintln!("Building a trie with {} accounts in {}", num_accounts, dir.display());
+        }
+        while written < num_accounts {
+            let end = std::cmp::min(written + batch_size, num_accounts);
+            let mut trie_update = tries.new_trie_update(ShardUId::single_shard(), root);
+            for account_index in written..end {
+                let account_id = get_account_id(account_index);
+                let account = Account::new(10u128.pow(24), 0, CryptoHash::default(), 0);
+                set_account(&mut trie_update, account_id.clone(), &account);
+                trie_update.set(
+                    TrieKey::ContractData { account_id, key: DATA_KEY.to_vec() },
+                    DATA_VALUE.to_vec(),
+                );
+            }
+            trie_update.commit(StateChangeCause::InitialState);
+            let trie_changes = trie_update.finalize()?.0;
+            let (store_update, new_root) =
+                tries.apply_all(&trie_changes, ShardUId::single_shard())?;
+            store_update.commit()?;
+            root = new_root;
+            written = end;
+            write_progress(&progress_path, written, &root)?;
+            eprintln!("  {}/{} accounts", written, num_accounts);
+        }
+        Ok(Self { store, root })
+    }
+
+    /// Trie update with empty caches, so that each measurement starts cold.
+    fn cold_trie_update(&self) -> TrieUpdate {
+        ShardTries::new(self.store.clone(), 0, 1)
+            .new_trie_update(ShardUId::single_shard(), self.root)
+    }
+}
+
+/// Number of accounts written so far and the root of the trie with them.
+fn read_progress(path: &Path) -> anyhow::Result<(usize, StateRoot)> {
+    if !path.exists() {
+        return Ok((0, StateRoot::default()));
+    }
+    let progress = std::fs::read_to_string(path)?;
+    let (written, root) =
+        progress.trim().split_once(' ').with_context(|| format!("corrupted {}", path.display()))?;
+    Ok((written.parse()?, CryptoHash::from_str(root).map_err(|err| anyhow::anyhow!("{}", err))?))
+}
+
+fn write_progress(path: &Path, written: usize, root: &StateRoot) -> anyhow::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, format!("{} {}\n", written, root))?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+fn with_ext<T>(
+    trie_update: &mut TrieUpdate,
+    account_id: &AccountId,
+    f: impl FnOnce(&mut RuntimeExt) -> T,
+) -> T {
+    let context = TestExtContext::new(account_id.clone());
+    let mut ext = context.ext(trie_update);
+    f(&mut ext)
+}
+
+/// Costs measured on a trie of one size, per operation.
+pub struct TrieScalingPoint {
+    pub num_accounts: usize,
+    /// Trie nodes touched by a read, i.e. the depth of the contract data.
+    pub nodes_per_read: f64,
+    pub nodes_per_write: f64,
+    pub storage_read: Gas,
+    pub storage_write: Gas,
+    /// Cost of a read divided by the nodes it touches.
+    pub touching_trie_node: Gas,
+    /// Gas charged for a read or a write with the configured costs.
+    pub configured_storage_read: Gas,
+    pub configured_storage_write: Gas,
+    pub configured_touching_trie_node: Gas,
+}
+
+impl TrieScalingPoint {
+    /// Parameters whose measured cost multiplied by `safety_factor` exceeds the
+    /// configured gas, with the measured and the configured gas.
+    fn flagged(&self, safety_factor: f64) -> Vec<(&'static str, Gas, Gas)> {
+        [
+            ("storage_read", self.storage_read, self.configured_storage_read),
+            ("storage_write", self.storage_write, self.configured_storage_write),
+            ("touching_trie_node", self.touching_trie_node, self.configured_touching_trie_node),
+        ]
+        .into_iter()
+        .filter(|&(_, measured, configured)| measured as f64 * safety_factor > configured as f64)
+        .collect()
+    }
+}
+
+pub struct TrieScalingReport {
+    pub points: Vec<TrieScalingPoint>,
+    pub safety_factor: f64,
+}
+
+impl fmt::Display for TrieScalingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>12} {:>8} {:>22} {:>22} {:>22}",
+            "accounts", "depth", "storage_read", "storage_write", "touching_trie_node"
+        )?;
+        for point in &self.points {
+            let cell = |measured: Gas, configured: Gas| {
+                format!("{:.2} ({:.2})", measured as f64 / 1e9, configured as f64 / 1e9)
+            };
+            writeln!(
+                f,
+                "{:>12} {:>8.1} {:>22} {:>22} {:>22}",
+                point.num_accounts,
+                point.nodes_per_read,
+                cell(point.storage_read, point.configured_storage_read),
+                cell(point.storage_write, point.configured_storage_write),
+                cell(point.touching_trie_node, point.configured_touching_trie_node),
+            )?;
+        }
+        writeln!(f, "Costs in Ggas per operation, configured gas in parentheses.")?;
+        let largest = match self.points.last() {
+            Some(largest) => largest,
+            None => return Ok(()),
+        };
+        let flagged = largest.flagged(self.safety_factor);
+        if flagged.is_empty() {
+            return writeln!(
+                f,
+                "No parameter exceeds the configured gas with safety factor {} at {} accounts.",
+                self.safety_factor, largest.num_accounts
+            );
+        }
+        writeln!(
+            f,
+            "Parameters exceeding the configured gas with safety factor {} at {} accounts:",
+            self.safety_factor, largest.num_accounts
+        )?;
+        for (name, measured, configured) in flagged {
+            writeln!(
+                f,
+                "  * {:<20} {:.2} Ggas measured, {:.2} Ggas configured ({:.2}x)",
+                name,
+                measured as f64 / 1e9,
+                configured as f64 / 1e9,
+                measured as f64 / configured as f64
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds or opens the tries of all the configured sizes and measures the
+/// storage costs on each of them.
+pub fn trie_scaling_report(config: &Config) -> anyhow::Result<TrieScalingReport> {
+    let scaling_config = &config.trie_scaling_config;
+    let cache_dir = match &scaling_config.cache_dir {
+        Some(cache_dir) => cache_dir.clone(),
+        None => std::env::temp_dir().join("runtime-params-estimator-trie-scaling"),
+    };
+    let mut sizes = scaling_config
+        .sizes
+        .split(',')
+        .map(|size| size.trim().parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .context("--trie-scaling-sizes must be a list of numbers")?;
+    sizes.sort();
+
+    let runtime_config = RuntimeConfigStore::new(None).get_config(PROTOCOL_VERSION).clone();
+    let ext_costs = &runtime_config.wasm_config.ext_costs;
+    let mut points = vec![];
+    for num_accounts in sizes {
+        let dir = cache_dir.join(num_accounts.to_string());
+        let trie = CachedTrie::open_or_build(&dir, num_accounts, BATCH_SIZE)?;
+        let point = measure_point(config, ext_costs, &trie, num_accounts);
+        if config.json_output {
+            println!(
+                "{}",
+                json!({
+                    "accounts": point.num_accounts,
+                    "nodes_per_read": point.nodes_per_read,
+                    "nodes_per_write": point.nodes_per_write,
+                    "storage_read": point.storage_read,
+                    "storage_write": point.storage_write,
+                    "touching_trie_node": point.touching_trie_node,
+                    "configured_storage_read": point.configured_storage_read,
+                    "configured_storage_write": point.configured_storage_write,
+                    "configured_touching_trie_node": point.configured_touching_trie_node,
+                })
+            );
+        }
+        points.push(point);
+    }
+    Ok(TrieScalingReport { points, safety_factor: scaling_config.safety_factor })
+}
+
+fn measure_point(
+    config: &Config,
+    ext_costs: &ExtCostsConfig,
+    trie: &CachedTrie,
+    num_accounts: usize,
+) -> TrieScalingPoint {
+    let ops = config.trie_scaling_config.ops;
+    let mut rng: XorShiftRng = rand::SeedableRng::seed_from_u64(KEY_SEED);
+    let accounts: Vec<_> =
+        (0..ops).map(|_| get_account_id(rng.gen_range(0, num_accounts))).collect();
+
+    // Reads of existing keys of random accounts.
+    let mut trie_update = trie.cold_trie_update();
+    clear_caches(config, trie);
+    let nodes_before = trie_update.trie().get_touched_nodes_count();
+    let clock = GasCost::measure(config.metric);
+    for account_id in &accounts {
+        with_ext(&mut trie_update, account_id, |ext| {
+            let value = ext.storage_get(DATA_KEY).unwrap().expect("missing contract data");
+            assert_eq!(value.deref().unwrap(), DATA_VALUE);
+        });
+    }
+    let read_total = clock.elapsed();
+    let read_nodes = trie_update.trie().get_touched_nodes_count() - nodes_before;
+
+    // Overwrites of the same keys, including computing the new trie nodes. The
+    // changes are never applied, so that the cached trie stays the same.
+    let mut trie_update = trie.cold_trie_update();
+    clear_caches(config, trie);
+    let nodes_before = trie_update.trie().get_touched_nodes_count();
+    let clock = GasCost::measure(config.metric);
+    for account_id in &accounts {
+        with_ext(&mut trie_update, account_id, |ext| {
+            ext.storage_set(DATA_KEY, DATA_VALUE).unwrap();
+        });
+    }
+    let write_nodes = trie_update.trie().get_touched_nodes_count() - nodes_before;
+    trie_update.commit(StateChangeCause::InitialState);
+    trie_update.finalize().unwrap();
+    let write_cost = clock.elapsed() / ops as u64;
+
+    let nodes_per_read = read_nodes as f64 / ops as f64;
+    let nodes_per_write = write_nodes as f64 / ops as f64;
+    let key_len = DATA_KEY.len() as u64;
+    let value_len = DATA_VALUE.len() as u64;
+    let configured_nodes_gas =
+        |nodes_per_op: f64| (nodes_per_op * ext_costs.touching_trie_node as f64) as Gas;
+    TrieScalingPoint {
+        num_accounts,
+        nodes_per_read,
+        nodes_per_write,
+        storage_read: (read_total.clone() / ops as u64).to_gas(),
+        storage_write: write_cost.to_gas(),
+        touching_trie_node: (read_total / std::cmp::max(read_nodes, 1)).to_gas(),
+        configured_storage_read: ext_costs.storage_read_base
+            + key_len * ext_costs.storage_read_key_byte
+            + value_len * ext_costs.storage_read_value_byte
+            + configured_nodes_gas(nodes_per_read),
+        configured_storage_write: ext_costs.storage_write_base
+            + key_len * ext_costs.storage_write_key_byte
+            + value_len * ext_costs.storage_write_value_byte
+            + value_len * ext_costs.storage_write_evicted_byte
+            + configured_nodes_gas(nodes_per_write),
+        configured_touching_trie_node: ext_costs.touching_trie_node,
+    }
+}
+
+/// Flushes the memtable and, if requested, the OS page cache, the same way as
+/// the testbed does before each measured block.
+fn clear_caches(config: &Config, trie: &CachedTrie) {
+    trie.store.get_rocksdb().unwrap().flush().unwrap();
+    if config.metric == GasMetric::Time && config.drop_os_cache {
+        #[cfg(target_os = "linux")]
+        crate::utils::clear_linux_page_cache().expect(
+            "Failed to drop OS caches. Are you root and is /proc mounted with write access?",
+        );
+        #[cfg(not(target_os = "linux"))]
+        panic!("Cannot drop OS caches on non-linux systems.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CachedTrie;
+
+    #[test]
+    fn test_cached_trie_resumes_build() {
+        let dir = tempfile::tempdir().unwrap();
+        let at_once = CachedTrie::open_or_build(&dir.path().join("at_once"), 1000, 1000).unwrap();
+
+        // A build interrupted after 400 accounts, resumed later in smaller batches.
+        let resumed_dir = dir.path().join("resumed");
+        let interrupted = CachedTrie::open_or_build(&resumed_dir, 400, 300).unwrap();
+        assert_ne!(interrupted.root, at_once.root);
+        drop(interrupted);
+        let resumed = CachedTrie::open_or_build(&resumed_dir, 1000, 300).unwrap();
+        assert_eq!(resumed.root, at_once.root);
+        drop(resumed);
+
+        let reopened = CachedTrie::open_or_build(&resumed_dir, 1000, 300).unwrap();
+        assert_eq!(reopened.root, at_once.root);
+        drop(reopened);
+        assert!(CachedTrie::open_or_build(&resumed_dir, 500, 300).is_err());
+    }
+}
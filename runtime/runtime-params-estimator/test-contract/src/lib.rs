@@ -917,6 +917,19 @@ pub unsafe fn data_producer_100kib() {
     value_return(data.len() as _, data.as_ptr() as _);
 }
 
+// Function that emits 1mib of data.  The data is too large for the stack, it's the zeroed memory
+// the function grows the memory by.
+#[no_mangle]
+pub unsafe fn data_producer_1mib() {
+    const PAGE_SIZE: usize = 64 * 1024;
+    const DATA_LEN: usize = 1024 * 1024;
+    let pages = core::arch::wasm32::memory_grow(0, DATA_LEN / PAGE_SIZE);
+    if pages == usize::MAX {
+        core::arch::wasm32::unreachable()
+    }
+    value_return(DATA_LEN as _, (pages * PAGE_SIZE) as _);
+}
+
 // Function to measure `data_receipt_creation_config`, but we are measure send and execution fee at the same time.
 // Produces 1000 10b data receipts.
 #[no_mangle]
@@ -1028,6 +1041,88 @@ pub unsafe fn data_receipt_100kib_1000() {
     );
 }
 
+// Function to measure `data_receipt_creation_config`, but we are measure send and execution fee at the same time.
+// Produces 100 10b data receipts.
+#[no_mangle]
+pub unsafe fn data_receipt_10b_100() {
+    let buf = [0u8; 1000];
+    current_account_id(0);
+    let buf_len = register_len(0);
+    read_register(0, buf.as_ptr() as _);
+
+    let method_name = b"data_producer_10b";
+    let args = b"";
+    let mut ids = [0u64; 100];
+    let amount = 0u128;
+    let gas = prepaid_gas();
+    for i in 0..100 {
+        ids[i] = promise_create(
+            buf_len,
+            buf.as_ptr() as _,
+            method_name.len() as _,
+            method_name.as_ptr() as _,
+            args.len() as _,
+            args.as_ptr() as _,
+            &amount as *const u128 as *const u64 as u64,
+            gas / 200,
+        );
+    }
+    let id = promise_and(ids.as_ptr() as _, ids.len() as _);
+    let method_name = b"noop";
+    promise_then(
+        id,
+        buf_len,
+        buf.as_ptr() as _,
+        method_name.len() as _,
+        method_name.as_ptr() as _,
+        args.len() as _,
+        args.as_ptr() as _,
+        &amount as *const u128 as *const u64 as u64,
+        gas / 3,
+    );
+}
+
+// Function to measure `data_receipt_creation_config`, but we are measure send and execution fee at the same time.
+// Produces 100 1mib data receipts, to measure `large_data_cost`.
+#[no_mangle]
+pub unsafe fn data_receipt_1mib_100() {
+    let buf = [0u8; 1000];
+    current_account_id(0);
+    let buf_len = register_len(0);
+    read_register(0, buf.as_ptr() as _);
+
+    let method_name = b"data_producer_1mib";
+    let args = b"";
+    let mut ids = [0u64; 100];
+    let amount = 0u128;
+    let gas = prepaid_gas();
+    for i in 0..100 {
+        ids[i] = promise_create(
+            buf_len,
+            buf.as_ptr() as _,
+            method_name.len() as _,
+            method_name.as_ptr() as _,
+            args.len() as _,
+            args.as_ptr() as _,
+            &amount as *const u128 as *const u64 as u64,
+            gas / 200,
+        );
+    }
+    let id = promise_and(ids.as_ptr() as _, ids.len() as _);
+    let method_name = b"noop";
+    promise_then(
+        id,
+        buf_len,
+        buf.as_ptr() as _,
+        method_name.len() as _,
+        method_name.as_ptr() as _,
+        args.len() as _,
+        args.as_ptr() as _,
+        &amount as *const u128 as *const u64 as u64,
+        gas / 3,
+    );
+}
+
 #[no_mangle]
 pub unsafe fn cpu_ram_soak_test() {
     let mut buf = [0u8; 100 * 1024];
@@ -0,0 +1,199 @@
+//! Benchmarks of the storage operations contracts do through `RuntimeExt`, on top of a trie
+//! filled with contract data, both in memory and in a RocksDB database.
+//!
+//! The trie is shaped by two environment variables:
+//!
+//! * `RUNTIME_EXT_BENCH_KEYS` is the number of keys in the trie, 100000 by default.  It must be
+//!   at least 10000, the largest number of keys an operation touches.
+//! * `RUNTIME_EXT_BENCH_VALUE_SIZES` is the distribution of the sizes of the values, as a comma
+//!   separated list of `size:weight` pairs, `10:70,100:25,1000:5` by default.
+//!
+//! A single case can be profiled by running only it, for as long as the profiler needs, e.g.
+//! with [flamegraph](https://github.com/flamegraph-rs/flamegraph):
+//!
+//! ```text
+//! cargo flamegraph -p node-runtime --bench runtime_ext -- \
+//!     --bench --profile-time 10 'rocksdb/storage_get_hit/100$'
+//! ```
+#[macro_use]
+extern crate criterion;
+
+use criterion::{black_box, BatchSize, BenchmarkId, Criterion};
+use tempfile::TempDir;
+
+use near_primitives::shard_layout::ShardUId;
+use near_primitives::trie_key::TrieKey;
+use near_primitives::types::{AccountId, StateChangeCause, StateRoot};
+use near_store::test_utils::create_tries;
+use near_store::{create_store, ShardTries, TrieUpdate};
+use near_vm_logic::{External, ValuePtr};
+use node_runtime::ext::RuntimeExt;
+use node_runtime::test_utils::TestExtContext;
+
+/// Numbers of keys each operation is done for in one iteration.
+const COUNTS: [usize; 3] = [1, 100, 10_000];
+
+/// Number of digits of the keys, so that the keys sharing a prefix are consecutive.
+const KEY_DIGITS: usize = 10;
+
+fn key(i: usize) -> Vec<u8> {
+    format!("key{:0width$}", i, width = KEY_DIGITS).into_bytes()
+}
+
+/// Prefix shared by `count` consecutive keys starting at 0, `count` being a power of ten.
+fn prefix(count: usize) -> Vec<u8> {
+    let mut key = key(0);
+    key.truncate(key.len() - (count.to_string().len() - 1));
+    key
+}
+
+fn missing_key(i: usize) -> Vec<u8> {
+    format!("missing{}", i).into_bytes()
+}
+
+struct TrieSpec {
+    num_keys: usize,
+    value_sizes: Vec<(usize, usize)>,
+}
+
+impl TrieSpec {
+    fn from_env() -> Self {
+        let num_keys = std::env::var("RUNTIME_EXT_BENCH_KEYS")
+            .map(|keys| keys.parse().expect("RUNTIME_EXT_BENCH_KEYS must be a number"))
+            .unwrap_or(100_000);
+        assert!(num_keys >= COUNTS[COUNTS.len() - 1], "the trie must have at least 10000 keys");
+        let value_sizes = std::env::var("RUNTIME_EXT_BENCH_VALUE_SIZES")
+            .unwrap_or_else(|_| "10:70,100:25,1000:5".to_string())
+            .split(',')
+            .map(|pair| {
+                let (size, weight) = pair
+                    .split_once(':')
+                    .expect("RUNTIME_EXT_BENCH_VALUE_SIZES must be a list of size:weight pairs");
+                (size.parse().unwrap(), weight.parse().unwrap())
+            })
+            .collect();
+        Self { num_keys, value_sizes }
+    }
+
+    /// Size of the value of the `i`th key, spread over the keys according to the weights.
+    fn value_size(&self, i: usize) -> usize {
+        let total_weight: usize = self.value_sizes.iter().map(|(_, weight)| weight).sum();
+        let mut slot = i % total_weight;
+        for &(size, weight) in &self.value_sizes {
+            if slot < weight {
+                return size;
+            }
+            slot -= weight;
+        }
+        unreachable!()
+    }
+}
+
+/// Trie with the contract data of `TrieSpec`, together with the directory of its database.
+struct Backend {
+    name: &'static str,
+    tries: ShardTries,
+    root: StateRoot,
+    _dir: Option<TempDir>,
+}
+
+impl Backend {
+    fn new(name: &'static str, tries: ShardTries, dir: Option<TempDir>, spec: &TrieSpec) -> Self {
+        let mut trie_update = tries.new_trie_update(ShardUId::single_shard(), StateRoot::default());
+        for i in 0..spec.num_keys {
+            let trie_key = TrieKey::ContractData { account_id: account_id(), key: key(i) };
+            trie_update.set(trie_key, vec![i as u8; spec.value_size(i)]);
+        }
+        trie_update.commit(StateChangeCause::InitialState);
+        let trie_changes = trie_update.finalize().unwrap().0;
+        let (store_update, root) =
+            tries.apply_all(&trie_changes, ShardUId::single_shard()).unwrap();
+        store_update.commit().unwrap();
+        Self { name, tries, root, _dir: dir }
+    }
+
+    fn in_memory(spec: &TrieSpec) -> Self {
+        Self::new("in_memory", create_tries(), None, spec)
+    }
+
+    fn rocksdb(spec: &TrieSpec) -> Self {
+        let dir = tempfile::Builder::new().prefix("runtime_ext_bench").tempdir().unwrap();
+        let tries = ShardTries::new(create_store(dir.path()), 0, 1);
+        Self::new("rocksdb", tries, Some(dir), spec)
+    }
+
+    fn trie_update(&self) -> TrieUpdate {
+        self.tries.new_trie_update(ShardUId::single_shard(), self.root)
+    }
+}
+
+fn account_id() -> AccountId {
+    "some-contract.near".parse().unwrap()
+}
+
+/// Benchmarks `op` done for each of `COUNTS` keys, starting every iteration with a fresh trie
+/// update on top of the backend's trie.
+fn bench_op(
+    c: &mut Criterion,
+    backends: &[Backend],
+    name: &str,
+    op: impl Fn(&mut RuntimeExt, usize),
+) {
+    let context = TestExtContext::new(account_id());
+    for backend in backends {
+        let mut group = c.benchmark_group(format!("{}/{}", backend.name, name));
+        for count in COUNTS {
+            group.bench_with_input(BenchmarkId::from_parameter(count), &count, |bench, &count| {
+                bench.iter_batched(
+                    || backend.trie_update(),
+                    |mut trie_update| {
+                        let mut ext = context.ext(&mut trie_update);
+                        op(&mut ext, count);
+                        drop(ext);
+                        trie_update
+                    },
+                    BatchSize::LargeInput,
+                )
+            });
+        }
+        group.finish();
+    }
+}
+
+fn runtime_ext(c: &mut Criterion) {
+    let spec = TrieSpec::from_env();
+    let backends = [Backend::in_memory(&spec), Backend::rocksdb(&spec)];
+
+    bench_op(c, &backends, "storage_set", |ext, count| {
+        for i in 0..count {
+            ext.storage_set(&missing_key(i), b"value").unwrap();
+        }
+    });
+    bench_op(c, &backends, "storage_get_hit", |ext, count| {
+        for i in 0..count {
+            let value = ext.storage_get(&key(i)).unwrap().unwrap();
+            black_box(value.deref().unwrap());
+        }
+    });
+    bench_op(c, &backends, "storage_get_miss", |ext, count| {
+        for i in 0..count {
+            assert!(ext.storage_get(&missing_key(i)).unwrap().is_none());
+        }
+    });
+    bench_op(c, &backends, "storage_has_key", |ext, count| {
+        for i in 0..count {
+            assert!(ext.storage_has_key(&key(i)).unwrap());
+        }
+    });
+    bench_op(c, &backends, "storage_remove_subtree", |ext, count| {
+        ext.storage_remove_subtree(&prefix(count)).unwrap();
+    });
+    bench_op(c, &backends, "generate_data_id", |ext, count| {
+        for _ in 0..count {
+            black_box(ext.generate_data_id().unwrap());
+        }
+    });
+}
+
+criterion_group!(benches, runtime_ext);
+criterion_main!(benches);
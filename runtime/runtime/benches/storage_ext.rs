@@ -0,0 +1,118 @@
+#[macro_use]
+extern crate bencher;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bencher::{black_box, Bencher};
+
+use near_primitives::hash::CryptoHash;
+use near_primitives::shard_layout::ShardUId;
+use near_primitives::trie_key::TrieKey;
+use near_primitives::types::{AccountId, StateChangeCause};
+use near_store::test_utils::create_tries;
+use near_store::TrieUpdate;
+use near_vm_logic::External;
+use node_runtime::test_utils::TestExtContext;
+
+/// Number of contract data writes in one iteration, each to a different key.
+const WRITES: usize = 10_000;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn count_allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn account_id() -> AccountId {
+    "some-contract.near".parse().unwrap()
+}
+
+fn keys() -> Vec<Vec<u8>> {
+    (0..WRITES).map(|i| format!("key{}", i).into_bytes()).collect()
+}
+
+fn new_trie_update() -> TrieUpdate {
+    create_tries().new_trie_update(ShardUId::single_shard(), CryptoHash::default())
+}
+
+/// Writes every key into `trie_update`, returning the allocations made by the writes and by
+/// the commit.
+fn write_with_trie_key(trie_update: &mut TrieUpdate, keys: &[Vec<u8>]) -> (usize, usize) {
+    let account_id = account_id();
+    let writes = count_allocations(|| {
+        for key in keys {
+            let trie_key =
+                TrieKey::ContractData { account_id: account_id.clone(), key: key.clone() };
+            trie_update.set(trie_key, b"value".to_vec());
+        }
+    });
+    let commit = count_allocations(|| trie_update.commit(StateChangeCause::InitialState));
+    (writes, commit)
+}
+
+fn write_with_runtime_ext(trie_update: &mut TrieUpdate, keys: &[Vec<u8>]) -> (usize, usize) {
+    let context = TestExtContext::new(account_id());
+    let mut ext = context.ext(trie_update);
+    let writes = count_allocations(|| {
+        for key in keys {
+            ext.storage_set(key, b"value").unwrap();
+        }
+    });
+    drop(ext);
+    let commit = count_allocations(|| trie_update.commit(StateChangeCause::InitialState));
+    (writes, commit)
+}
+
+fn report(name: &str, (writes, commit): (usize, usize)) {
+    println!(
+        "{}: {} allocations per write, {} allocations per key on commit",
+        name,
+        writes as f64 / WRITES as f64,
+        commit as f64 / WRITES as f64
+    );
+}
+
+fn storage_set_trie_key(bench: &mut Bencher) {
+    let keys = keys();
+    report("TrieKey", write_with_trie_key(&mut new_trie_update(), &keys));
+    bench.iter(|| {
+        let mut trie_update = new_trie_update();
+        black_box(write_with_trie_key(&mut trie_update, &keys));
+    });
+}
+
+fn storage_set_runtime_ext(bench: &mut Bencher) {
+    let keys = keys();
+    report("RuntimeExt", write_with_runtime_ext(&mut new_trie_update(), &keys));
+    bench.iter(|| {
+        let mut trie_update = new_trie_update();
+        black_box(write_with_runtime_ext(&mut trie_update, &keys));
+    });
+}
+
+benchmark_group!(benches, storage_set_trie_key, storage_set_runtime_ext);
+benchmark_main!(benches);
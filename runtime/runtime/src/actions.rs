@@ -11,11 +11,10 @@ use near_primitives::runtime::config::AccountCreationConfig;
 use near_primitives::runtime::fees::RuntimeFeesConfig;
 use near_primitives::transaction::{
     Action, AddKeyAction, DeleteAccountAction, DeleteKeyAction, DeployContractAction,
-    FunctionCallAction, StakeAction, TransferAction,
+    FunctionCallAction, NonrefundableStorageTransferAction, StakeAction, TransferAction,
 };
 use near_primitives::types::validator_stake::ValidatorStake;
-use near_primitives::types::{AccountId, BlockHeight, EpochInfoProvider};
-use near_primitives::utils::create_random_seed;
+use near_primitives::types::{AccountId, BlockHeight, EpochInfoProvider, Gas};
 use near_primitives::version::{
     is_implicit_account_creation_enabled, ProtocolFeature, ProtocolVersion,
     DELETE_KEY_STORAGE_USAGE_PROTOCOL_VERSION,
@@ -30,7 +29,7 @@ use near_vm_errors::{
 use near_vm_logic::types::PromiseResult;
 use near_vm_logic::{VMContext, VMOutcome};
 
-use crate::config::{safe_add_gas, RuntimeConfig};
+use crate::config::{safe_add_compute, safe_add_gas, RuntimeConfig};
 use crate::ext::{ExternalError, RuntimeExt};
 use crate::{ActionResult, ApplyState};
 use near_primitives::config::ViewConfig;
@@ -45,7 +44,7 @@ pub(crate) fn execute_function_call(
     action_receipt: &ActionReceipt,
     promise_results: &[PromiseResult],
     function_call: &FunctionCallAction,
-    action_hash: &CryptoHash,
+    receipt_id: &CryptoHash,
     config: &RuntimeConfig,
     is_last_action: bool,
     view_config: Option<ViewConfig>,
@@ -72,11 +71,7 @@ pub(crate) fn execute_function_call(
     } else {
         vec![]
     };
-    let random_seed = create_random_seed(
-        apply_state.current_protocol_version,
-        *action_hash,
-        apply_state.random_seed,
-    );
+    let random_seed = runtime_ext.random_seed(receipt_id, &apply_state.random_seed);
     let context = VMContext {
         current_account_id: runtime_ext.account_id().clone(),
         signer_account_id: action_receipt.signer_id.clone(),
@@ -123,6 +118,7 @@ pub(crate) fn action_function_call(
     receipt: &Receipt,
     action_receipt: &ActionReceipt,
     promise_results: &[PromiseResult],
+    promise_results_gas_burnt: &[Option<Gas>],
     result: &mut ActionResult,
     account_id: &AccountId,
     function_call: &FunctionCallAction,
@@ -143,26 +139,43 @@ pub(crate) fn action_function_call(
         &action_receipt.signer_id,
         &action_receipt.signer_public_key,
         action_receipt.gas_price,
+        apply_state.gas_price,
+        config.storage_amount_per_byte,
         action_hash,
         &apply_state.epoch_id,
+        apply_state.shard_id,
         &apply_state.prev_block_hash,
         &apply_state.block_hash,
         epoch_info_provider,
         apply_state.current_protocol_version,
+        &config.wasm_config.limit_config,
     );
-    let (outcome, err) = execute_function_call(
-        apply_state,
-        &mut runtime_ext,
-        account,
-        &receipt.predecessor_id,
-        action_receipt,
-        promise_results,
-        function_call,
-        action_hash,
-        config,
-        is_last_action,
-        None,
-    );
+    runtime_ext.set_promise_results_gas_burnt(promise_results_gas_burnt);
+    runtime_ext.set_priority_fee(action_receipt.priority_fee);
+    runtime_ext.set_receipt_depth(action_receipt.depth);
+    let (outcome, err) = {
+        let _span = tracing::info_span!(
+            target: "vm",
+            "function_call",
+            %account_id,
+            method_name = %function_call.method_name,
+            receipt_id = %receipt.receipt_id,
+        )
+        .entered();
+        execute_function_call(
+            apply_state,
+            &mut runtime_ext,
+            account,
+            &receipt.predecessor_id,
+            action_receipt,
+            promise_results,
+            function_call,
+            &receipt.receipt_id,
+            config,
+            is_last_action,
+            None,
+        )
+    };
     let execution_succeeded = match err {
         Some(VMError::FunctionCallError(err)) => match err {
             FunctionCallError::Nondeterministic(msg) => {
@@ -205,10 +218,32 @@ pub(crate) fn action_function_call(
         Some(VMError::ExternalError(any_err)) => {
             let err: ExternalError =
                 any_err.downcast().expect("Downcasting AnyError should not fail");
-            return match err {
-                ExternalError::StorageError(err) => Err(err.into()),
-                ExternalError::ValidatorError(err) => Err(RuntimeError::ValidatorError(err)),
-            };
+            match err {
+                ExternalError::TrieNodesMemoryLimitExceeded { size, limit } => {
+                    result.result =
+                        Err(ActionErrorKind::TrieNodesMemoryLimitExceeded { size, limit }.into());
+                    false
+                }
+                ExternalError::StorageError(err) => return Err(err.into()),
+                ExternalError::ValidatorError(err) => {
+                    return Err(RuntimeError::ValidatorError(err))
+                }
+                // `VMLogic` checks the lengths first, so these mean the two are out of sync.
+                ExternalError::KeyLengthExceeded { length, limit } => {
+                    return Err(StorageError::StorageInconsistentState(format!(
+                        "Storage key length {} exceeds the limit {}",
+                        length, limit
+                    ))
+                    .into())
+                }
+                ExternalError::ValueLengthExceeded { length, limit } => {
+                    return Err(StorageError::StorageInconsistentState(format!(
+                        "Storage value length {} exceeds the limit {}",
+                        length, limit
+                    ))
+                    .into())
+                }
+            }
         }
         Some(VMError::InconsistentStateError(err @ InconsistentStateError::IntegerOverflow)) => {
             return Err(StorageError::StorageInconsistentState(err.to_string()).into());
@@ -233,6 +268,7 @@ pub(crate) fn action_function_call(
         // return a real `gas_used` instead of the `gas_burnt` into `ActionResult` even for
         // `FunctionCall`s error.
         result.gas_used = safe_add_gas(result.gas_used, outcome.used_gas)?;
+        result.compute_usage = safe_add_compute(result.compute_usage, outcome.compute_usage)?;
         result.logs.extend(outcome.logs.into_iter());
         result.profile.merge(&outcome.profile);
         if execution_succeeded {
@@ -340,6 +376,16 @@ pub(crate) fn action_transfer(
     Ok(())
 }
 
+pub(crate) fn action_nonrefundable_storage_transfer(
+    account: &mut Account,
+    transfer: &NonrefundableStorageTransferAction,
+) -> Result<(), StorageError> {
+    account.set_nonrefundable(account.nonrefundable().checked_add(transfer.deposit).ok_or_else(
+        || StorageError::StorageInconsistentState("Account balance integer overflow".to_string()),
+    )?);
+    Ok(())
+}
+
 pub(crate) fn action_create_account(
     fee_config: &RuntimeFeesConfig,
     account_creation_config: &AccountCreationConfig,
@@ -503,6 +549,8 @@ pub(crate) fn action_delete_account(
             .new_receipts
             .push(Receipt::new_balance_refund(&delete_account.beneficiary_id, account_balance));
     }
+    // The nonrefundable balance can't be withdrawn, not even by deleting the account.
+    result.burnt_amount = account.as_ref().unwrap().nonrefundable();
     remove_account(state_update, account_id)?;
     *actor_id = receipt.predecessor_id.clone();
     *account = None;
@@ -634,7 +682,10 @@ pub(crate) fn check_actor_permissions(
                 .into());
             }
         }
-        Action::CreateAccount(_) | Action::FunctionCall(_) | Action::Transfer(_) => (),
+        Action::CreateAccount(_)
+        | Action::FunctionCall(_)
+        | Action::Transfer(_)
+        | Action::NonrefundableStorageTransfer(_) => (),
     };
     Ok(())
 }
@@ -701,7 +752,8 @@ pub(crate) fn check_account_existence(
         | Action::Stake(_)
         | Action::AddKey(_)
         | Action::DeleteKey(_)
-        | Action::DeleteAccount(_) => {
+        | Action::DeleteAccount(_)
+        | Action::NonrefundableStorageTransfer(_) => {
             if account.is_none() {
                 return Err(ActionErrorKind::AccountDoesNotExist {
                     account_id: account_id.clone(),
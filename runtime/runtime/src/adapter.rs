@@ -41,7 +41,10 @@ pub trait ViewRuntimeAdapter {
         logs: &mut Vec<String>,
         epoch_info_provider: &dyn EpochInfoProvider,
         current_protocol_version: ProtocolVersion,
-    ) -> Result<Vec<u8>, crate::state_viewer::errors::CallFunctionError>;
+    ) -> Result<
+        crate::state_viewer::CallFunctionResult,
+        crate::state_viewer::errors::CallFunctionError,
+    >;
 
     fn view_access_key(
         &self,
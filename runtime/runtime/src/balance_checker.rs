@@ -25,6 +25,8 @@ pub(crate) fn check_balance(
     incoming_receipts: &[Receipt],
     transactions: &[SignedTransaction],
     outgoing_receipts: &[Receipt],
+    processed_priority_delayed_receipts: &[Receipt],
+    new_priority_delayed_receipts: &[Receipt],
     stats: &ApplyStats,
     current_protocol_version: ProtocolVersion,
 ) -> Result<(), RuntimeError> {
@@ -45,18 +47,21 @@ pub(crate) fn check_balance(
             })
             .collect::<Result<Vec<Receipt>, StorageError>>()
     };
-    // Previously delayed receipts that were processed this time.
-    let processed_delayed_receipts = get_delayed_receipts(
+    // Previously delayed receipts that were processed this time.  Delayed receipts with
+    // a priority fee aren't in the queue, so they are listed by the runtime.
+    let mut processed_delayed_receipts = get_delayed_receipts(
         initial_delayed_receipt_indices.first_index,
         final_delayed_receipt_indices.first_index,
         initial_state,
     )?;
+    processed_delayed_receipts.extend_from_slice(processed_priority_delayed_receipts);
     // Receipts that were not processed this time and are delayed now.
-    let new_delayed_receipts = get_delayed_receipts(
+    let mut new_delayed_receipts = get_delayed_receipts(
         initial_delayed_receipt_indices.next_available_index,
         final_delayed_receipt_indices.next_available_index,
         final_state,
     )?;
+    new_delayed_receipts.extend_from_slice(new_priority_delayed_receipts);
 
     // Accounts
     let mut all_accounts_ids: HashSet<AccountId> = transactions
@@ -71,9 +76,8 @@ pub(crate) fn check_balance(
             all_accounts_ids.extend(validator_accounts_update.validator_rewards.keys().cloned());
             all_accounts_ids.extend(validator_accounts_update.last_proposals.keys().cloned());
             all_accounts_ids.extend(validator_accounts_update.slashing_info.keys().cloned());
-            if let Some(account_id) = &validator_accounts_update.protocol_treasury_account_id {
-                all_accounts_ids.insert(account_id.clone());
-            }
+            all_accounts_ids
+                .extend(validator_accounts_update.protocol_treasury_account_ids.iter().cloned());
             validator_accounts_update
                 .validator_rewards
                 .values()
@@ -87,6 +91,7 @@ pub(crate) fn check_balance(
             .map(|account_id| {
                 get_account(state, account_id)?.map_or(Ok(0), |a| {
                     safe_add_balance(a.amount(), a.locked())
+                        .and_then(|balance| safe_add_balance(balance, a.nonrefundable()))
                         .map_err(|_| RuntimeError::UnexpectedIntegerOverflow)
                 })
             })
@@ -227,7 +232,7 @@ mod tests {
     use near_primitives::receipt::ActionReceipt;
     use near_primitives::runtime::fees::RuntimeFeesConfig;
     use near_primitives::test_utils::account_new;
-    use near_primitives::transaction::{Action, TransferAction};
+    use near_primitives::transaction::{Action, Transaction, TransferAction};
     use near_primitives::types::{MerkleHash, StateChangeCause};
     use near_store::set_account;
     use near_store::test_utils::create_tries;
@@ -258,6 +263,8 @@ mod tests {
             &[],
             &[],
             &[],
+            &[],
+            &[],
             &ApplyStats::default(),
             PROTOCOL_VERSION,
         )
@@ -279,6 +286,8 @@ mod tests {
             &[Receipt::new_balance_refund(&alice_account(), 1000)],
             &[],
             &[],
+            &[],
+            &[],
             &ApplyStats::default(),
             PROTOCOL_VERSION,
         )
@@ -314,6 +323,8 @@ mod tests {
             &[Receipt::new_balance_refund(&account_id, refund_balance)],
             &[],
             &[],
+            &[],
+            &[],
             &ApplyStats::default(),
             PROTOCOL_VERSION,
         )
@@ -322,6 +333,17 @@ mod tests {
 
     #[test]
     fn test_check_balance_tx_to_receipt() {
+        check_balance_tx_to_receipt(None);
+    }
+
+    /// The priority fee is burnt when the transaction is converted, so the receipt doesn't carry
+    /// it.
+    #[test]
+    fn test_check_balance_tx_with_priority_fee_to_receipt() {
+        check_balance_tx_to_receipt(Some(1_000_000));
+    }
+
+    fn check_balance_tx_to_receipt(priority_fee: Option<Balance>) {
         let tries = create_tries();
         let root = MerkleHash::default();
         let account_id = alice_account();
@@ -345,7 +367,8 @@ mod tests {
         let mut final_state = tries.new_trie_update(ShardUId::single_shard(), root);
         let final_account = account_new(
             initial_balance - (exec_gas + send_gas) as Balance * gas_price - deposit
-                + contract_reward,
+                + contract_reward
+                - priority_fee.unwrap_or(0),
             hash(&[]),
         );
         set_account(&mut final_state, account_id.clone(), &final_account);
@@ -353,14 +376,19 @@ mod tests {
 
         let signer =
             InMemorySigner::from_seed(account_id.clone(), KeyType::ED25519, account_id.as_ref());
-        let tx = SignedTransaction::send_money(
-            1,
-            account_id,
-            bob_account(),
-            &signer,
-            deposit,
-            CryptoHash::default(),
-        );
+        let tx = Transaction {
+            priority_fee,
+            ..SignedTransaction::send_money(
+                1,
+                account_id,
+                bob_account(),
+                &signer,
+                deposit,
+                CryptoHash::default(),
+            )
+            .transaction
+        }
+        .sign(&signer);
         let receipt = Receipt {
             predecessor_id: tx.transaction.signer_id.clone(),
             receiver_id: tx.transaction.receiver_id.clone(),
@@ -372,6 +400,8 @@ mod tests {
                 output_data_receivers: vec![],
                 input_data_ids: vec![],
                 actions: vec![Action::Transfer(TransferAction { deposit })],
+                priority_fee,
+                depth: None,
             }),
         };
 
@@ -383,8 +413,10 @@ mod tests {
             &[],
             &[tx],
             &[receipt],
+            &[],
+            &[],
             &ApplyStats {
-                tx_burnt_amount: total_validator_reward,
+                tx_burnt_amount: total_validator_reward + priority_fee.unwrap_or(0),
                 gas_deficit_amount: 0,
                 other_burnt_amount: 0,
                 slashed_burnt_amount: 0,
@@ -428,6 +460,8 @@ mod tests {
                 output_data_receivers: vec![],
                 input_data_ids: vec![],
                 actions: vec![Action::Transfer(TransferAction { deposit })],
+                priority_fee: None,
+                depth: None,
             }),
         };
 
@@ -441,6 +475,8 @@ mod tests {
                 &[receipt],
                 &[tx],
                 &[],
+                &[],
+                &[],
                 &ApplyStats::default(),
                 PROTOCOL_VERSION,
             ),
@@ -13,7 +13,7 @@ use near_primitives::runtime::fees::{transfer_exec_fee, transfer_send_fee, Runti
 use near_primitives::transaction::{
     Action, AddKeyAction, DeployContractAction, FunctionCallAction, Transaction,
 };
-use near_primitives::types::{AccountId, Balance, Gas};
+use near_primitives::types::{AccountId, Balance, Compute, Gas};
 use near_primitives::version::{is_implicit_account_creation_enabled, ProtocolVersion};
 
 /// Describes the cost of converting this transaction into a receipt.
@@ -54,6 +54,10 @@ pub fn safe_add_gas(a: Gas, b: Gas) -> Result<Gas, IntegerOverflowError> {
     a.checked_add(b).ok_or_else(|| IntegerOverflowError {})
 }
 
+pub fn safe_add_compute(a: Compute, b: Compute) -> Result<Compute, IntegerOverflowError> {
+    a.checked_add(b).ok_or_else(|| IntegerOverflowError {})
+}
+
 pub fn safe_add_balance(a: Balance, b: Balance) -> Result<Balance, IntegerOverflowError> {
     a.checked_add(b).ok_or_else(|| IntegerOverflowError {})
 }
@@ -122,6 +126,8 @@ pub fn total_send_fees(
             },
             DeleteKey(_) => cfg.delete_key_cost.send_fee(sender_is_receiver),
             DeleteAccount(_) => cfg.delete_account_cost.send_fee(sender_is_receiver),
+            // Costs the same as a transfer to an existing account.
+            NonrefundableStorageTransfer(_) => transfer_send_fee(cfg, sender_is_receiver, false),
         };
         result = safe_add_gas(result, delta)?;
     }
@@ -174,6 +180,7 @@ pub fn exec_fee(
         },
         DeleteKey(_) => cfg.delete_key_cost.exec_fee(),
         DeleteAccount(_) => cfg.delete_account_cost.exec_fee(),
+        NonrefundableStorageTransfer(_) => transfer_exec_fee(cfg, false),
     }
 }
 
@@ -227,7 +234,11 @@ pub fn tx_cost(
             current_protocol_version,
         )?,
     )?;
-    let burnt_amount = safe_gas_to_balance(gas_price, gas_burnt)?;
+    // The priority fee is burnt together with the gas for converting the transaction.
+    let burnt_amount = safe_add_balance(
+        safe_gas_to_balance(gas_price, gas_burnt)?,
+        transaction.priority_fee.unwrap_or(0),
+    )?;
     let remaining_gas_amount = safe_gas_to_balance(receipt_gas_price, gas_remaining)?;
     let mut total_cost = safe_add_balance(burnt_amount, remaining_gas_amount)?;
     total_cost = safe_add_balance(total_cost, total_deposit(&transaction.actions)?)?;
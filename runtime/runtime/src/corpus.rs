@@ -0,0 +1,241 @@
+//! Regression corpus of real chunks, recorded from a node and replayed with [`Runtime::apply`].
+//!
+//! A [`ChunkFixture`] has everything needed to apply a chunk without the chain it comes from:
+//! the apply state of the block, the incoming receipts and transactions, the stakes the epoch
+//! info provider reports and the storage proof of the trie nodes the chunk touched.  It also
+//! has the results the node got, so that replaying a fixture checks that a change to the
+//! runtime doesn't change the outcomes, the outgoing receipts or the state root of chunks that
+//! are already on chain.
+//!
+//! Fixtures are stored with borsh as `<dir>/<protocol_version>/<chain_id>-<height>-<shard>.chunk`
+//! and are recorded with the `dump_runtime_corpus` command of the state viewer.  The format is
+//! versioned, so that fixtures recorded before a change to it can still be read.
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use near_primitives::challenge::PartialState;
+use near_primitives::errors::{EpochError, RuntimeError};
+use near_primitives::hash::CryptoHash;
+use near_primitives::receipt::Receipt;
+use near_primitives::runtime::apply_state::ApplyState;
+use near_primitives::runtime::config_store::RuntimeConfigStore;
+use near_primitives::runtime::migration_data::{MigrationData, MigrationFlags};
+use near_primitives::shard_layout::{account_id_to_shard_id, ShardLayout};
+use near_primitives::transaction::{ExecutionOutcomeWithId, SignedTransaction};
+use near_primitives::types::{
+    AccountId, Balance, BlockHeight, EpochHeight, EpochId, EpochInfoProvider, Gas, ShardId,
+    StateRoot,
+};
+use near_primitives::version::ProtocolVersion;
+use near_store::{PartialStorage, Trie};
+
+use crate::{ApplyResult, Runtime};
+
+/// Extension of the fixture files.
+const FIXTURE_EXTENSION: &str = "chunk";
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub enum ChunkFixture {
+    V1(ChunkFixtureV1),
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+pub struct ChunkFixtureV1 {
+    /// Chain the chunk comes from, which selects the runtime config of the protocol version.
+    pub chain_id: String,
+    pub block_height: BlockHeight,
+    pub block_hash: CryptoHash,
+    pub prev_block_hash: CryptoHash,
+    pub block_timestamp: u64,
+    pub epoch_id: EpochId,
+    pub epoch_height: EpochHeight,
+    pub shard_id: ShardId,
+    pub gas_price: Balance,
+    pub gas_limit: Option<Gas>,
+    pub random_seed: CryptoHash,
+    pub protocol_version: ProtocolVersion,
+    pub is_new_chunk: bool,
+    /// Stakes of the validators of the epoch, without the slashed ones.
+    pub validators: Vec<(AccountId, Balance)>,
+    pub minimum_stake: Balance,
+    pub shard_layout: ShardLayout,
+    pub prev_state_root: StateRoot,
+    pub receipts: Vec<Receipt>,
+    pub transactions: Vec<SignedTransaction>,
+    /// Trie nodes read while applying the chunk.
+    pub storage_proof: PartialState,
+    pub state_root: StateRoot,
+    pub outcomes: Vec<ExecutionOutcomeWithId>,
+    pub outgoing_receipts: Vec<Receipt>,
+}
+
+impl ChunkFixture {
+    fn inner(&self) -> &ChunkFixtureV1 {
+        match self {
+            ChunkFixture::V1(fixture) => fixture,
+        }
+    }
+
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.inner().protocol_version
+    }
+
+    /// Name of the fixture file, relative to the corpus directory.
+    pub fn file_name(&self) -> PathBuf {
+        let fixture = self.inner();
+        Path::new(&fixture.protocol_version.to_string()).join(format!(
+            "{}-{}-{}.{}",
+            fixture.chain_id, fixture.block_height, fixture.shard_id, FIXTURE_EXTENSION
+        ))
+    }
+
+    pub fn read_from_file(path: &Path) -> io::Result<ChunkFixture> {
+        ChunkFixture::try_from_slice(&std::fs::read(path)?)
+    }
+
+    /// Writes the fixture into the corpus in `dir`, returning the path of the file.
+    pub fn write_to_dir(&self, dir: &Path) -> io::Result<PathBuf> {
+        let path = dir.join(self.file_name());
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, self.try_to_vec()?)?;
+        Ok(path)
+    }
+
+    /// Applies the chunk on top of the recorded storage proof.
+    pub fn replay(&self) -> Result<ApplyResult, RuntimeError> {
+        let fixture = self.inner();
+        let config_store = RuntimeConfigStore::for_chain_id(&fixture.chain_id);
+        let apply_state = ApplyState {
+            block_index: fixture.block_height,
+            prev_block_hash: fixture.prev_block_hash,
+            block_hash: fixture.block_hash,
+            epoch_id: fixture.epoch_id.clone(),
+            epoch_height: fixture.epoch_height,
+            shard_id: fixture.shard_id,
+            gas_price: fixture.gas_price,
+            block_timestamp: fixture.block_timestamp,
+            gas_limit: fixture.gas_limit,
+            compute_limit: None,
+            random_seed: fixture.random_seed,
+            current_protocol_version: fixture.protocol_version,
+            config: config_store.get_config(fixture.protocol_version).clone(),
+            cache: None,
+            is_new_chunk: fixture.is_new_chunk,
+            migration_data: Arc::new(MigrationData::default()),
+            migration_flags: MigrationFlags::default(),
+        };
+        let trie =
+            Trie::from_recorded_storage(PartialStorage { nodes: fixture.storage_proof.clone() });
+        Runtime::new().apply(
+            trie,
+            fixture.prev_state_root,
+            &None,
+            &apply_state,
+            &fixture.receipts,
+            &fixture.transactions,
+            fixture,
+            None,
+        )
+    }
+
+    /// Replays the chunk and compares the results with the recorded ones, describing the first
+    /// difference if there is one.
+    pub fn check(&self) -> Result<(), String> {
+        let fixture = self.inner();
+        let result = self.replay().map_err(|err| format!("failed to apply the chunk: {}", err))?;
+        if result.outcomes.len() != fixture.outcomes.len() {
+            return Err(format!(
+                "expected {} outcomes, got {}",
+                fixture.outcomes.len(),
+                result.outcomes.len()
+            ));
+        }
+        for (expected, actual) in fixture.outcomes.iter().zip(&result.outcomes) {
+            if expected != actual {
+                return Err(format!(
+                    "outcome of {} differs:\nexpected: {:?}\nactual: {:?}",
+                    expected.id, expected.outcome, actual.outcome
+                ));
+            }
+        }
+        if result.outgoing_receipts != fixture.outgoing_receipts {
+            return Err(format!(
+                "outgoing receipts differ:\nexpected: {:?}\nactual: {:?}",
+                fixture.outgoing_receipts, result.outgoing_receipts
+            ));
+        }
+        if result.state_root != fixture.state_root {
+            return Err(format!(
+                "expected state root {}, got {}",
+                fixture.state_root, result.state_root
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl EpochInfoProvider for ChunkFixtureV1 {
+    fn validator_stake(
+        &self,
+        _epoch_id: &EpochId,
+        _last_block_hash: &CryptoHash,
+        account_id: &AccountId,
+    ) -> Result<Option<Balance>, EpochError> {
+        Ok(self.validators.iter().find(|(id, _)| id == account_id).map(|(_, stake)| *stake))
+    }
+
+    fn validator_total_stake(
+        &self,
+        _epoch_id: &EpochId,
+        _last_block_hash: &CryptoHash,
+    ) -> Result<Balance, EpochError> {
+        Ok(self.validators.iter().map(|(_, stake)| stake).sum())
+    }
+
+    fn minimum_stake(&self, _prev_block_hash: &CryptoHash) -> Result<Balance, EpochError> {
+        Ok(self.minimum_stake)
+    }
+
+    fn account_id_to_shard_id(
+        &self,
+        account_id: &AccountId,
+        _epoch_id: &EpochId,
+    ) -> Result<ShardId, EpochError> {
+        Ok(account_id_to_shard_id(account_id, &self.shard_layout))
+    }
+}
+
+/// Paths of the fixtures in the corpus in `dir`, sorted, only of `protocol_version` if given.
+pub fn corpus_files(
+    dir: &Path,
+    protocol_version: Option<ProtocolVersion>,
+) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for version_dir in std::fs::read_dir(dir)? {
+        let version_dir = version_dir?.path();
+        let version = version_dir
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.parse::<ProtocolVersion>().ok());
+        let version = match version {
+            Some(version) if version_dir.is_dir() => version,
+            _ => continue,
+        };
+        if protocol_version.map_or(false, |protocol_version| protocol_version != version) {
+            continue;
+        }
+        for file in std::fs::read_dir(&version_dir)? {
+            let file = file?.path();
+            if file.extension().map_or(false, |extension| extension == FIXTURE_EXTENSION) {
+                files.push(file);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
@@ -1,45 +1,85 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use borsh::BorshDeserialize;
+#[cfg(feature = "protocol_feature_receipt_size_limit")]
+use borsh::BorshSerialize;
 use tracing::debug;
 
 use near_crypto::PublicKey;
 use near_primitives::account::{AccessKey, AccessKeyPermission, FunctionCallPermission};
+use near_primitives::checked_feature;
+use near_primitives::config::VMLimitConfig;
 use near_primitives::contract::ContractCode;
 use near_primitives::errors::{EpochError, StorageError};
 use near_primitives::hash::CryptoHash;
+#[cfg(feature = "protocol_feature_promise_data_dependency")]
+use near_primitives::receipt::DataReceipt;
 use near_primitives::receipt::{ActionReceipt, DataReceiver, Receipt, ReceiptEnum};
 use near_primitives::transaction::{
     Action, AddKeyAction, CreateAccountAction, DeleteAccountAction, DeleteKeyAction,
     DeployContractAction, FunctionCallAction, StakeAction, TransferAction,
 };
-use near_primitives::trie_key::{trie_key_parsers, TrieKey};
-use near_primitives::types::{AccountId, Balance, EpochId, EpochInfoProvider, Gas, TrieCacheMode};
+use near_primitives::trie_key::TrieKey;
+use near_primitives::types::{
+    AccountId, Balance, EpochId, EpochInfoProvider, Gas, ReceiptDepth, ShardId, TrieCacheMode,
+};
 #[cfg(feature = "protocol_feature_function_call_weight")]
 use near_primitives::types::{GasDistribution, GasWeight};
-use near_primitives::utils::create_data_id;
+use near_primitives::utils::{create_data_id, create_random_seed};
 use near_primitives::version::ProtocolVersion;
-use near_store::{get_code, TrieUpdate, TrieUpdateValuePtr};
+use near_store::{get_code, Trie, TrieUpdate, TrieUpdateValuePtr};
 use near_vm_errors::{AnyError, HostError, VMLogicError};
 use near_vm_logic::{External, ValuePtr};
 
+use crate::verifier::is_key_type_enabled;
+
 pub struct RuntimeExt<'a> {
     trie_update: &'a mut TrieUpdate,
     account_id: &'a AccountId,
     action_receipts: Vec<(AccountId, ActionReceipt)>,
+    /// Data sent with `resume_data`, with the receivers of the receipts waiting for it.
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    data_receipts: Vec<(AccountId, DataReceipt)>,
     signer_id: &'a AccountId,
     signer_public_key: &'a PublicKey,
     gas_price: Balance,
+    block_gas_price: Balance,
+    storage_amount_per_byte: Balance,
     action_hash: &'a CryptoHash,
     data_count: u64,
     epoch_id: &'a EpochId,
+    shard_id: ShardId,
     prev_block_hash: &'a CryptoHash,
     last_block_hash: &'a CryptoHash,
     epoch_info_provider: &'a dyn EpochInfoProvider,
     current_protocol_version: ProtocolVersion,
+    limit_config: &'a VMLimitConfig,
+    /// Buffer for the raw keys of contract data, reused between storage operations.
+    storage_key: RefCell<Vec<u8>>,
+    /// Validator stakes looked up from `epoch_info_provider` during this execution.
+    validator_stakes: RefCell<HashMap<AccountId, Option<Balance>>>,
+    /// Total validator stake looked up from `epoch_info_provider` during this execution.
+    validator_total_stake: Cell<Option<Balance>>,
+    /// Gas burnt by the receipts which produced the promise results, if known.
+    promise_results_gas_burnt: &'a [Option<Gas>],
+    /// Priority fee inherited by the receipts created during this execution.
+    priority_fee: Option<Balance>,
+    /// Depth of the receipt being executed.
+    receipt_depth: ReceiptDepth,
+    /// Trie lookups of contract data made during this execution.
+    read_cache: RefCell<ReadCache>,
+    /// Trie nodes charged for the lookups answered from `read_cache`.
+    cached_touched_nodes: Cell<u64>,
+    /// Trie cache mode last set with `set_trie_cache_mode`.
+    trie_cache_mode: TrieCacheMode,
 
     #[cfg(feature = "protocol_feature_function_call_weight")]
     gas_weights: Vec<(FunctionCallActionIndex, GasWeight)>,
+    /// Borsh size of each receipt in `action_receipts` once converted by `into_receipts`.
+    #[cfg(feature = "protocol_feature_receipt_size_limit")]
+    action_receipt_sizes: Vec<u64>,
 }
 
 #[cfg(feature = "protocol_feature_function_call_weight")]
@@ -48,6 +88,57 @@ struct FunctionCallActionIndex {
     action_index: usize,
 }
 
+/// Maximum number of keys in the read cache of a `RuntimeExt`.
+const READ_CACHE_MAX_ENTRIES: usize = 1024;
+/// Maximum total size in bytes of the keys in the read cache of a `RuntimeExt`.
+const READ_CACHE_MAX_KEY_BYTES: usize = 256 * 1024;
+
+/// Result of a trie lookup of a contract data key.
+#[derive(Clone, Copy)]
+struct CachedRead {
+    /// Length and hash of the value, `None` if there is no value.
+    value: Option<(u32, CryptoHash)>,
+    /// Number of trie nodes the lookup touches when it is repeated.
+    touched_nodes: u64,
+}
+
+/// Trie lookups of contract data keys made during a single execution, so that contracts reading
+/// the same key many times don't traverse the trie each time.  Entries are removed when their key
+/// is written or removed, and no new entries are added once the cache is full.
+#[derive(Default)]
+struct ReadCache {
+    entries: HashMap<Vec<u8>, CachedRead>,
+    key_bytes: usize,
+}
+
+impl ReadCache {
+    fn get(&self, key: &[u8]) -> Option<CachedRead> {
+        self.entries.get(key).copied()
+    }
+
+    fn insert(&mut self, key: &[u8], read: CachedRead) {
+        if self.entries.len() >= READ_CACHE_MAX_ENTRIES
+            || self.key_bytes + key.len() > READ_CACHE_MAX_KEY_BYTES
+        {
+            return;
+        }
+        if self.entries.insert(key.to_vec(), read).is_none() {
+            self.key_bytes += key.len();
+        }
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        if self.entries.remove(key).is_some() {
+            self.key_bytes -= key.len();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.key_bytes = 0;
+    }
+}
+
 /// Error used by `RuntimeExt`.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) enum ExternalError {
@@ -56,6 +147,14 @@ pub(crate) enum ExternalError {
     StorageError(StorageError),
     /// Error when accessing validator information. Happens inside epoch manager.
     ValidatorError(EpochError),
+    /// The key of a storage operation is longer than `max_length_storage_key`.  Contract calls
+    /// are expected to be stopped by the same check in `VMLogic` before reaching `RuntimeExt`.
+    KeyLengthExceeded { length: u64, limit: u64 },
+    /// The value of a storage write is longer than `max_length_storage_value`.
+    ValueLengthExceeded { length: u64, limit: u64 },
+    /// Trie nodes loaded by the receipt take more memory than
+    /// `per_receipt_trie_nodes_memory_limit` allows.  Fails the receipt.
+    TrieNodesMemoryLimitExceeded { size: u64, limit: u64 },
 }
 
 impl From<ExternalError> for VMLogicError {
@@ -83,33 +182,72 @@ impl<'a> RuntimeExt<'a> {
         signer_id: &'a AccountId,
         signer_public_key: &'a PublicKey,
         gas_price: Balance,
+        block_gas_price: Balance,
+        storage_amount_per_byte: Balance,
         action_hash: &'a CryptoHash,
         epoch_id: &'a EpochId,
+        shard_id: ShardId,
         prev_block_hash: &'a CryptoHash,
         last_block_hash: &'a CryptoHash,
         epoch_info_provider: &'a dyn EpochInfoProvider,
         current_protocol_version: ProtocolVersion,
+        limit_config: &'a VMLimitConfig,
     ) -> Self {
         RuntimeExt {
             trie_update,
             account_id,
             action_receipts: vec![],
+            #[cfg(feature = "protocol_feature_promise_data_dependency")]
+            data_receipts: vec![],
             signer_id,
             signer_public_key,
             gas_price,
+            block_gas_price,
+            storage_amount_per_byte,
             action_hash,
             data_count: 0,
             epoch_id,
+            shard_id,
             prev_block_hash,
             last_block_hash,
             epoch_info_provider,
             current_protocol_version,
+            limit_config,
+            storage_key: RefCell::new(Vec::new()),
+            validator_stakes: RefCell::new(HashMap::new()),
+            validator_total_stake: Cell::new(None),
+            promise_results_gas_burnt: &[],
+            priority_fee: None,
+            receipt_depth: 0,
+            read_cache: RefCell::new(ReadCache::default()),
+            cached_touched_nodes: Cell::new(0),
+            trie_cache_mode: TrieCacheMode::CachingShard,
 
             #[cfg(feature = "protocol_feature_function_call_weight")]
             gas_weights: vec![],
+            #[cfg(feature = "protocol_feature_receipt_size_limit")]
+            action_receipt_sizes: vec![],
         }
     }
 
+    /// Sets the gas burnt by the receipts which produced the promise results of this execution,
+    /// in the order of the promise results.
+    pub fn set_promise_results_gas_burnt(&mut self, gas_burnt: &'a [Option<Gas>]) {
+        self.promise_results_gas_burnt = gas_burnt;
+    }
+
+    /// Sets the priority fee of the receipt being executed, which the receipts created by this
+    /// execution inherit.
+    pub fn set_priority_fee(&mut self, priority_fee: Option<Balance>) {
+        self.priority_fee = priority_fee;
+    }
+
+    /// Sets the depth of the receipt being executed.  Receipts created before
+    /// `ProtocolFeature::ReceiptDepth` have no depth and count as converted from a transaction.
+    pub fn set_receipt_depth(&mut self, depth: Option<ReceiptDepth>) {
+        self.receipt_depth = depth.unwrap_or(0);
+    }
+
     #[inline]
     pub fn account_id(&self) -> &'a AccountId {
         self.account_id
@@ -124,10 +262,6 @@ impl<'a> RuntimeExt<'a> {
         crate::cache::get_code(code_hash, code)
     }
 
-    pub fn create_storage_key(&self, key: &[u8]) -> TrieKey {
-        TrieKey::ContractData { account_id: self.account_id.clone(), key: key.to_vec() }
-    }
-
     fn new_data_id(&mut self) -> CryptoHash {
         let data_id = create_data_id(
             self.current_protocol_version,
@@ -140,22 +274,110 @@ impl<'a> RuntimeExt<'a> {
         data_id
     }
 
+    /// Creates the random seed of the function call of the receipt `receipt_id` in a block with
+    /// `block_random_seed`, see [`create_random_seed`].
+    pub fn random_seed(&self, receipt_id: &CryptoHash, block_random_seed: &CryptoHash) -> Vec<u8> {
+        create_random_seed(
+            self.current_protocol_version,
+            *self.action_hash,
+            *receipt_id,
+            *block_random_seed,
+        )
+    }
+
+    /// Converts the created receipts into outgoing receipts.  The action receipts come first,
+    /// so that their positions match the receipt indices returned to the contract.
     pub fn into_receipts(self, predecessor_id: &AccountId) -> Vec<Receipt> {
-        self.action_receipts
-            .into_iter()
-            .map(|(receiver_id, action_receipt)| Receipt {
+        let receipts = self.action_receipts.into_iter().map(|(receiver_id, action_receipt)| {
+            (receiver_id, ReceiptEnum::Action(action_receipt))
+        });
+        #[cfg(feature = "protocol_feature_promise_data_dependency")]
+        let receipts = receipts.chain(
+            self.data_receipts
+                .into_iter()
+                .map(|(receiver_id, data_receipt)| (receiver_id, ReceiptEnum::Data(data_receipt))),
+        );
+        receipts
+            .map(|(receiver_id, receipt)| Receipt {
                 predecessor_id: predecessor_id.clone(),
                 receiver_id,
                 // Actual receipt ID is set in the Runtime.apply_action_receipt(...) in the
                 // "Generating receipt IDs" section
                 receipt_id: CryptoHash::default(),
-                receipt: ReceiptEnum::Action(action_receipt),
+                receipt,
             })
             .collect()
     }
 
+    /// Adds a new receipt to `receiver_id` and returns its index.
+    fn push_action_receipt(
+        &mut self,
+        receiver_id: AccountId,
+        action_receipt: ActionReceipt,
+    ) -> u64 {
+        #[cfg(feature = "protocol_feature_receipt_size_limit")]
+        self.action_receipt_sizes.push(borsh_size(&Receipt {
+            predecessor_id: self.account_id.clone(),
+            receiver_id: receiver_id.clone(),
+            receipt_id: CryptoHash::default(),
+            receipt: ReceiptEnum::Action(action_receipt.clone()),
+        }));
+        let new_receipt_index = self.action_receipts.len() as u64;
+        self.action_receipts.push((receiver_id, action_receipt));
+        new_receipt_index
+    }
+
+    /// Returns the depth of the receipts created by this execution, one more than the depth of
+    /// the receipt being executed.  Fails if it's larger than `max_receipt_depth`.
+    fn new_receipt_depth(&self) -> ExtResult<Option<ReceiptDepth>> {
+        if !checked_feature!(
+            "protocol_feature_receipt_depth",
+            ReceiptDepth,
+            self.current_protocol_version
+        ) {
+            return Ok(None);
+        }
+        let depth = self.receipt_depth.saturating_add(1);
+        #[cfg(feature = "protocol_feature_receipt_depth")]
+        if let Some(limit) = self.limit_config.max_receipt_depth {
+            if depth > limit {
+                return Err(HostError::ReceiptDepthExceeded {
+                    depth: depth.into(),
+                    limit: limit.into(),
+                }
+                .into());
+            }
+        }
+        Ok(Some(depth))
+    }
+
+    /// Grows the size of the receipt `receipt_index` by `size` bytes.  Fails if the receipt gets
+    /// larger than `max_receipt_size`.
+    #[cfg(feature = "protocol_feature_receipt_size_limit")]
+    fn grow_receipt_size(&mut self, receipt_index: u64, size: u64) -> ExtResult<()> {
+        let receipt_size = &mut self.action_receipt_sizes[receipt_index as usize];
+        *receipt_size += size;
+        if checked_feature!(
+            "protocol_feature_receipt_size_limit",
+            ReceiptSizeLimit,
+            self.current_protocol_version
+        ) {
+            if let Some(limit) = self.limit_config.max_receipt_size {
+                if *receipt_size > limit {
+                    return Err(
+                        HostError::ReceiptSizeExceeded { size: *receipt_size, limit }.into()
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Appends an action and returns the index the action was inserted in the receipt
-    fn append_action(&mut self, receipt_index: u64, action: Action) -> usize {
+    fn append_action(&mut self, receipt_index: u64, action: Action) -> ExtResult<usize> {
+        #[cfg(feature = "protocol_feature_receipt_size_limit")]
+        self.grow_receipt_size(receipt_index, borsh_size(&action))?;
+
         let actions = &mut self
             .action_receipts
             .get_mut(receipt_index as usize)
@@ -166,17 +388,121 @@ impl<'a> RuntimeExt<'a> {
         actions.push(action);
 
         // Return index that action was inserted at
-        actions.len() - 1
+        Ok(actions.len() - 1)
     }
 
     pub fn set_trie_cache_mode(&mut self, state: TrieCacheMode) {
         self.trie_update.set_trie_cache_mode(state);
+        self.trie_cache_mode = state;
+        // Repeated lookups touch a different number of nodes in the new mode.
+        self.read_cache.get_mut().clear();
+    }
+
+    /// Same as `TrieUpdate::get_ref_raw`, but repeated lookups of keys read from the trie are
+    /// answered from `read_cache`.  Cached lookups are charged for the trie nodes the lookup would
+    /// touch if it was repeated, so they cost exactly as much gas as uncached ones.
+    fn get_ref_cached(&self, storage_key: &[u8]) -> ExtResult<Option<TrieUpdateValuePtr<'_>>> {
+        if let Some(read) = self.read_cache.borrow().get(storage_key) {
+            self.cached_touched_nodes.set(self.cached_touched_nodes.get() + read.touched_nodes);
+            return Ok(read.value.map(|(length, hash)| {
+                TrieUpdateValuePtr::HashAndSize(self.trie_update.trie(), length, hash)
+            }));
+        }
+        let nodes_before = self.trie_update.trie().get_touched_nodes_count();
+        let ptr = self.trie_update.get_ref_raw(storage_key).map_err(wrap_storage_error)?;
+        check_trie_nodes_memory(
+            self.limit_config,
+            self.current_protocol_version,
+            self.trie_update.trie(),
+        )?;
+        let touched_nodes = match self.trie_cache_mode {
+            TrieCacheMode::CachingShard => {
+                self.trie_update.trie().get_touched_nodes_count() - nodes_before
+            }
+            // The nodes are in the chunk cache now, so repeated lookups don't touch any.
+            TrieCacheMode::CachingChunk => 0,
+        };
+        let value = match &ptr {
+            Some(TrieUpdateValuePtr::HashAndSize(_, length, hash)) => Some((*length, *hash)),
+            // Values written during the execution are read from memory anyway.
+            Some(TrieUpdateValuePtr::MemoryRef(_)) => return Ok(ptr),
+            None => None,
+        };
+        self.read_cache.borrow_mut().insert(storage_key, CachedRead { value, touched_nodes });
+        Ok(ptr)
     }
 
     #[inline]
     pub fn protocol_version(&self) -> ProtocolVersion {
         self.current_protocol_version
     }
+
+    /// Decodes a public key passed by a contract for an action, rejecting the key types the
+    /// protocol version doesn't enable.
+    fn parse_public_key(&self, public_key: &[u8]) -> ExtResult<PublicKey> {
+        let public_key =
+            PublicKey::try_from_slice(public_key).map_err(|_| HostError::InvalidPublicKey)?;
+        if !is_key_type_enabled(public_key.key_type(), self.current_protocol_version) {
+            return Err(HostError::InvalidPublicKey.into());
+        }
+        Ok(public_key)
+    }
+}
+
+/// Checks the lengths of a contract data key and, for writes, of its value against the limits
+/// in `limit_config`.
+pub(crate) fn check_storage_lengths(
+    limit_config: &VMLimitConfig,
+    key: &[u8],
+    value: Option<&[u8]>,
+) -> Result<(), ExternalError> {
+    let limit = limit_config.max_length_storage_key;
+    if key.len() as u64 > limit {
+        return Err(ExternalError::KeyLengthExceeded { length: key.len() as u64, limit });
+    }
+    let limit = limit_config.max_length_storage_value;
+    match value {
+        Some(value) if value.len() as u64 > limit => {
+            Err(ExternalError::ValueLengthExceeded { length: value.len() as u64, limit })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Fails once the trie nodes loaded by the receipt take more memory than
+/// `per_receipt_trie_nodes_memory_limit` allows.
+fn check_trie_nodes_memory(
+    limit_config: &VMLimitConfig,
+    protocol_version: ProtocolVersion,
+    trie: &Trie,
+) -> Result<(), ExternalError> {
+    if !checked_feature!(
+        "protocol_feature_per_receipt_trie_nodes_memory_limit",
+        PerReceiptTrieNodesMemoryLimit,
+        protocol_version
+    ) {
+        return Ok(());
+    }
+    if let Some(limit) = limit_config.per_receipt_trie_nodes_memory_limit {
+        let size = trie.loaded_nodes_size();
+        if size > limit {
+            return Err(ExternalError::TrieNodesMemoryLimitExceeded { size, limit });
+        }
+    }
+    Ok(())
+}
+
+/// Returns the size of `value` serialized with borsh.
+#[cfg(feature = "protocol_feature_receipt_size_limit")]
+fn borsh_size(value: &impl BorshSerialize) -> u64 {
+    value.try_to_vec().expect("Failed to serialize").len() as u64
+}
+
+/// Writes the raw key of the contract data `key` of `account_id` into `buffer`.
+fn contract_data_key<'b>(buffer: &'b mut Vec<u8>, account_id: &AccountId, key: &[u8]) -> &'b [u8] {
+    buffer.clear();
+    TrieKey::append_contract_data_key(buffer, account_id, key);
+    buffer
 }
 
 fn wrap_storage_error(error: StorageError) -> VMLogicError {
@@ -187,49 +513,105 @@ type ExtResult<T> = ::std::result::Result<T, VMLogicError>;
 
 impl<'a> External for RuntimeExt<'a> {
     fn storage_set(&mut self, key: &[u8], value: &[u8]) -> ExtResult<()> {
-        let storage_key = self.create_storage_key(key);
-        self.trie_update.set(storage_key, Vec::from(value));
+        check_storage_lengths(self.limit_config, key, Some(value))?;
+        let storage_key = contract_data_key(self.storage_key.get_mut(), self.account_id, key);
+        self.read_cache.get_mut().remove(storage_key);
+        self.trie_update.set_contract_data_raw(storage_key, Some(Vec::from(value)));
         Ok(())
     }
 
     fn storage_get<'b>(&'b self, key: &[u8]) -> ExtResult<Option<Box<dyn ValuePtr + 'b>>> {
-        let storage_key = self.create_storage_key(key);
-        self.trie_update
-            .get_ref(&storage_key)
-            .map_err(wrap_storage_error)
+        check_storage_lengths(self.limit_config, key, None)?;
+        let mut buffer = self.storage_key.borrow_mut();
+        let storage_key = contract_data_key(&mut buffer, self.account_id, key);
+        self.get_ref_cached(storage_key)
             .map(|option| option.map(|ptr| Box::new(RuntimeExtValuePtr(ptr)) as Box<_>))
     }
 
     fn storage_remove(&mut self, key: &[u8]) -> ExtResult<()> {
-        let storage_key = self.create_storage_key(key);
-        self.trie_update.remove(storage_key);
+        check_storage_lengths(self.limit_config, key, None)?;
+        let storage_key = contract_data_key(self.storage_key.get_mut(), self.account_id, key);
+        self.read_cache.get_mut().remove(storage_key);
+        self.trie_update.set_contract_data_raw(storage_key, None);
         Ok(())
     }
 
     fn storage_has_key(&mut self, key: &[u8]) -> ExtResult<bool> {
-        let storage_key = self.create_storage_key(key);
-        self.trie_update.get_ref(&storage_key).map(|x| x.is_some()).map_err(wrap_storage_error)
+        check_storage_lengths(self.limit_config, key, None)?;
+        let mut buffer = self.storage_key.borrow_mut();
+        let storage_key = contract_data_key(&mut buffer, self.account_id, key);
+        self.get_ref_cached(storage_key).map(|x| x.is_some())
+    }
+
+    #[cfg(feature = "protocol_feature_storage_key_count")]
+    fn storage_key_count(&mut self, prefix: &[u8], limit: u64) -> ExtResult<Option<u64>> {
+        check_storage_lengths(self.limit_config, prefix, None)?;
+        let storage_key = contract_data_key(self.storage_key.get_mut(), self.account_id, prefix);
+        let mut count = 0;
+        for raw_key in self.trie_update.iter(storage_key).map_err(wrap_storage_error)? {
+            raw_key.map_err(wrap_storage_error)?;
+            check_trie_nodes_memory(
+                self.limit_config,
+                self.current_protocol_version,
+                self.trie_update.trie(),
+            )?;
+            if count == limit {
+                return Ok(None);
+            }
+            count += 1;
+        }
+        Ok(Some(count))
+    }
+
+    #[cfg(feature = "protocol_feature_storage_compare_and_swap")]
+    fn storage_compare_and_swap(
+        &mut self,
+        key: &[u8],
+        expected: Option<&[u8]>,
+        value: &[u8],
+    ) -> ExtResult<bool> {
+        check_storage_lengths(self.limit_config, key, Some(value))?;
+        let mut buffer = self.storage_key.borrow_mut();
+        let storage_key = contract_data_key(&mut buffer, self.account_id, key);
+        let current = self.get_ref_cached(storage_key)?;
+        let matches = match (current, expected) {
+            (None, None) => true,
+            // Values of different lengths are told apart without reading the current one.
+            (Some(current), Some(expected)) => {
+                current.len() as usize == expected.len()
+                    && current.deref_value().map_err(wrap_storage_error)? == expected
+            }
+            _ => false,
+        };
+        if matches {
+            self.read_cache.get_mut().remove(storage_key);
+            self.trie_update.set_contract_data_raw(storage_key, Some(value.to_vec()));
+        }
+        Ok(matches)
+    }
+
+    #[cfg(feature = "protocol_feature_storage_value_len")]
+    fn storage_value_len(&mut self, key: &[u8]) -> ExtResult<Option<u32>> {
+        check_storage_lengths(self.limit_config, key, None)?;
+        let mut buffer = self.storage_key.borrow_mut();
+        let storage_key = contract_data_key(&mut buffer, self.account_id, key);
+        self.get_ref_cached(storage_key).map(|x| x.map(|ptr| ptr.len()))
     }
 
     fn storage_remove_subtree(&mut self, prefix: &[u8]) -> ExtResult<()> {
-        let data_keys = self
-            .trie_update
-            .iter(&trie_key_parsers::get_raw_prefix_for_contract_data(self.account_id, prefix))
-            .map_err(wrap_storage_error)?
-            .map(|raw_key| {
-                trie_key_parsers::parse_data_key_from_contract_data_key(&raw_key?, self.account_id)
-                    .map_err(|_e| {
-                        StorageError::StorageInconsistentState(
-                            "Can't parse data key from raw key for ContractData".to_string(),
-                        )
-                    })
-                    .map(Vec::from)
-            })
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(wrap_storage_error)?;
-        for key in data_keys {
-            self.trie_update
-                .remove(TrieKey::ContractData { account_id: self.account_id.clone(), key });
+        let storage_key = contract_data_key(self.storage_key.get_mut(), self.account_id, prefix);
+        let mut raw_keys = vec![];
+        for raw_key in self.trie_update.iter(storage_key).map_err(wrap_storage_error)? {
+            raw_keys.push(raw_key.map_err(wrap_storage_error)?);
+            check_trie_nodes_memory(
+                self.limit_config,
+                self.current_protocol_version,
+                self.trie_update.trie(),
+            )?;
+        }
+        for raw_key in raw_keys {
+            self.read_cache.get_mut().remove(&raw_key);
+            self.trie_update.set_contract_data_raw(&raw_key, None);
         }
         Ok(())
     }
@@ -239,15 +621,21 @@ impl<'a> External for RuntimeExt<'a> {
         receipt_indices: Vec<u64>,
         receiver_id: AccountId,
     ) -> ExtResult<u64> {
+        let depth = self.new_receipt_depth()?;
         let mut input_data_ids = vec![];
         for receipt_index in receipt_indices {
             let data_id = self.new_data_id();
+            let data_receiver = DataReceiver { data_id, receiver_id: receiver_id.clone() };
+            #[cfg(feature = "protocol_feature_receipt_size_limit")]
+            let data_receiver_size = borsh_size(&data_receiver);
             self.action_receipts
                 .get_mut(receipt_index as usize)
                 .ok_or_else(|| HostError::InvalidReceiptIndex { receipt_index })?
                 .1
                 .output_data_receivers
-                .push(DataReceiver { data_id, receiver_id: receiver_id.clone() });
+                .push(data_receiver);
+            #[cfg(feature = "protocol_feature_receipt_size_limit")]
+            self.grow_receipt_size(receipt_index, data_receiver_size)?;
             input_data_ids.push(data_id);
         }
 
@@ -258,14 +646,76 @@ impl<'a> External for RuntimeExt<'a> {
             output_data_receivers: vec![],
             input_data_ids,
             actions: vec![],
+            priority_fee: self.priority_fee,
+            depth,
         };
-        let new_receipt_index = self.action_receipts.len() as u64;
-        self.action_receipts.push((receiver_id, new_receipt));
-        Ok(new_receipt_index)
+        Ok(self.push_action_receipt(receiver_id, new_receipt))
+    }
+
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    fn generate_data_id(&mut self) -> ExtResult<CryptoHash> {
+        let data_id = self.new_data_id();
+        self.trie_update.set(
+            TrieKey::RegisteredDataId { account_id: self.account_id.clone(), data_id },
+            vec![],
+        );
+        Ok(data_id)
+    }
+
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    fn create_receipt_with_data_dependency(
+        &mut self,
+        data_id: CryptoHash,
+        receiver_id: AccountId,
+    ) -> ExtResult<u64> {
+        let depth = self.new_receipt_depth()?;
+        let key = TrieKey::RegisteredDataId { account_id: self.account_id.clone(), data_id };
+        // The value is empty until a receipt depends on the data id, and is the receiver of that
+        // receipt afterwards.
+        match self.trie_update.get(&key).map_err(wrap_storage_error)? {
+            Some(receiver) if receiver.is_empty() => {}
+            _ => return Err(HostError::InvalidDataId.into()),
+        }
+        self.trie_update.set(key, receiver_id.as_ref().as_bytes().to_vec());
+
+        let new_receipt = ActionReceipt {
+            signer_id: self.signer_id.clone(),
+            signer_public_key: self.signer_public_key.clone(),
+            gas_price: self.gas_price,
+            output_data_receivers: vec![],
+            input_data_ids: vec![data_id],
+            actions: vec![],
+            priority_fee: self.priority_fee,
+            depth,
+        };
+        Ok(self.push_action_receipt(receiver_id, new_receipt))
+    }
+
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    fn resume_data(&mut self, data_id: CryptoHash, data: Vec<u8>) -> ExtResult<AccountId> {
+        let key = TrieKey::RegisteredDataId { account_id: self.account_id.clone(), data_id };
+        let receiver_id = match self.trie_update.get(&key).map_err(wrap_storage_error)? {
+            Some(receiver) if !receiver.is_empty() => String::from_utf8(receiver)
+                .ok()
+                .and_then(|receiver| receiver.parse::<AccountId>().ok())
+                .ok_or_else(|| {
+                    wrap_storage_error(StorageError::StorageInconsistentState(format!(
+                        "Invalid receiver of data id {}",
+                        data_id
+                    )))
+                })?,
+            _ => return Err(HostError::InvalidDataId.into()),
+        };
+        self.trie_update.remove(key);
+        self.data_receipts.push((
+            receiver_id.clone(),
+            DataReceipt { data_id, data: Some(data), gas_burnt: None },
+        ));
+        Ok(receiver_id)
     }
 
     fn append_action_create_account(&mut self, receipt_index: u64) -> ExtResult<()> {
-        self.append_action(receipt_index, Action::CreateAccount(CreateAccountAction {}));
+        self.append_action(receipt_index, Action::CreateAccount(CreateAccountAction {}))?;
         Ok(())
     }
 
@@ -274,7 +724,7 @@ impl<'a> External for RuntimeExt<'a> {
         receipt_index: u64,
         code: Vec<u8>,
     ) -> ExtResult<()> {
-        self.append_action(receipt_index, Action::DeployContract(DeployContractAction { code }));
+        self.append_action(receipt_index, Action::DeployContract(DeployContractAction { code }))?;
         Ok(())
     }
 
@@ -297,7 +747,7 @@ impl<'a> External for RuntimeExt<'a> {
                 gas: prepaid_gas,
                 deposit: attached_deposit,
             }),
-        );
+        )?;
 
         if gas_weight.0 > 0 {
             self.gas_weights.push((
@@ -326,12 +776,12 @@ impl<'a> External for RuntimeExt<'a> {
                 gas: prepaid_gas,
                 deposit: attached_deposit,
             }),
-        );
+        )?;
         Ok(())
     }
 
     fn append_action_transfer(&mut self, receipt_index: u64, deposit: u128) -> ExtResult<()> {
-        self.append_action(receipt_index, Action::Transfer(TransferAction { deposit }));
+        self.append_action(receipt_index, Action::Transfer(TransferAction { deposit }))?;
         Ok(())
     }
 
@@ -343,12 +793,8 @@ impl<'a> External for RuntimeExt<'a> {
     ) -> ExtResult<()> {
         self.append_action(
             receipt_index,
-            Action::Stake(StakeAction {
-                stake,
-                public_key: PublicKey::try_from_slice(&public_key)
-                    .map_err(|_| HostError::InvalidPublicKey)?,
-            }),
-        );
+            Action::Stake(StakeAction { stake, public_key: self.parse_public_key(&public_key)? }),
+        )?;
         Ok(())
     }
 
@@ -361,11 +807,14 @@ impl<'a> External for RuntimeExt<'a> {
         self.append_action(
             receipt_index,
             Action::AddKey(AddKeyAction {
-                public_key: PublicKey::try_from_slice(&public_key)
-                    .map_err(|_| HostError::InvalidPublicKey)?,
-                access_key: AccessKey { nonce, permission: AccessKeyPermission::FullAccess },
+                public_key: self.parse_public_key(&public_key)?,
+                access_key: AccessKey {
+                    nonce,
+                    permission: AccessKeyPermission::FullAccess,
+                    valid_until_block_height: None,
+                },
             }),
-        );
+        )?;
         Ok(())
     }
 
@@ -381,8 +830,7 @@ impl<'a> External for RuntimeExt<'a> {
         self.append_action(
             receipt_index,
             Action::AddKey(AddKeyAction {
-                public_key: PublicKey::try_from_slice(&public_key)
-                    .map_err(|_| HostError::InvalidPublicKey)?,
+                public_key: self.parse_public_key(&public_key)?,
                 access_key: AccessKey {
                     nonce,
                     permission: AccessKeyPermission::FunctionCall(FunctionCallPermission {
@@ -396,9 +844,10 @@ impl<'a> External for RuntimeExt<'a> {
                             })
                             .collect::<std::result::Result<Vec<_>, _>>()?,
                     }),
+                    valid_until_block_height: None,
                 },
             }),
-        );
+        )?;
         Ok(())
     }
 
@@ -409,11 +858,8 @@ impl<'a> External for RuntimeExt<'a> {
     ) -> ExtResult<()> {
         self.append_action(
             receipt_index,
-            Action::DeleteKey(DeleteKeyAction {
-                public_key: PublicKey::try_from_slice(&public_key)
-                    .map_err(|_| HostError::InvalidPublicKey)?,
-            }),
-        );
+            Action::DeleteKey(DeleteKeyAction { public_key: self.parse_public_key(&public_key)? }),
+        )?;
         Ok(())
     }
 
@@ -425,24 +871,96 @@ impl<'a> External for RuntimeExt<'a> {
         self.append_action(
             receipt_index,
             Action::DeleteAccount(DeleteAccountAction { beneficiary_id }),
-        );
+        )?;
         Ok(())
     }
 
     fn get_touched_nodes_count(&self) -> u64 {
-        self.trie_update.trie.get_touched_nodes_count()
+        self.trie_update.trie.get_touched_nodes_count() + self.cached_touched_nodes.get()
+    }
+
+    #[cfg(feature = "protocol_feature_per_receipt_storage_proof_limit")]
+    fn recorded_storage_size(&self) -> u64 {
+        self.trie_update.trie.recorded_storage_size()
     }
 
     fn validator_stake(&self, account_id: &AccountId) -> ExtResult<Option<Balance>> {
-        self.epoch_info_provider
+        if let Some(stake) = self.validator_stakes.borrow().get(account_id) {
+            return Ok(*stake);
+        }
+        let stake = self
+            .epoch_info_provider
             .validator_stake(self.epoch_id, self.prev_block_hash, account_id)
-            .map_err(|e| ExternalError::ValidatorError(e).into())
+            .map_err(ExternalError::ValidatorError)?;
+        self.validator_stakes.borrow_mut().insert(account_id.clone(), stake);
+        Ok(stake)
     }
 
     fn validator_total_stake(&self) -> ExtResult<Balance> {
-        self.epoch_info_provider
+        if let Some(total_stake) = self.validator_total_stake.get() {
+            return Ok(total_stake);
+        }
+        let total_stake = self
+            .epoch_info_provider
             .validator_total_stake(self.epoch_id, self.prev_block_hash)
-            .map_err(|e| ExternalError::ValidatorError(e).into())
+            .map_err(ExternalError::ValidatorError)?;
+        self.validator_total_stake.set(Some(total_stake));
+        Ok(total_stake)
+    }
+
+    fn validator_stake_cached(&self, account_id: &AccountId) -> bool {
+        self.validator_stakes.borrow().contains_key(account_id)
+    }
+
+    fn validator_total_stake_cached(&self) -> bool {
+        self.validator_total_stake.get().is_some()
+    }
+
+    #[cfg(feature = "protocol_feature_locked_balance_of")]
+    fn locked_balance_of(&self, account_id: &AccountId) -> ExtResult<Balance> {
+        let shard_id = self
+            .epoch_info_provider
+            .account_id_to_shard_id(account_id, self.epoch_id)
+            .map_err(ExternalError::ValidatorError)?;
+        if shard_id != self.shard_id {
+            return Err(
+                HostError::AccountOnOtherShard { account_id: account_id.to_string() }.into()
+            );
+        }
+        match near_store::get_account(self.trie_update, account_id).map_err(wrap_storage_error)? {
+            Some(account) => Ok(account.locked()),
+            None => {
+                Err(HostError::AccountDoesNotExist { account_id: account_id.to_string() }.into())
+            }
+        }
+    }
+
+    fn gas_price(&self) -> Balance {
+        self.gas_price
+    }
+
+    fn block_gas_price(&self) -> Balance {
+        self.block_gas_price
+    }
+
+    fn storage_amount_per_byte(&self) -> Balance {
+        self.storage_amount_per_byte
+    }
+
+    fn promise_result_gas_burnt(&self, result_idx: u64) -> Option<Gas> {
+        self.promise_results_gas_burnt.get(result_idx as usize).copied().flatten()
+    }
+
+    fn epoch_id(&self) -> CryptoHash {
+        self.epoch_id.0
+    }
+
+    fn shard_id(&self) -> ShardId {
+        self.shard_id
+    }
+
+    fn receipt_depth(&self) -> u64 {
+        self.receipt_depth.into()
     }
 
     /// Distributes the gas passed in by splitting it among weights defined in `gas_weights`.
@@ -495,4 +1013,573 @@ impl<'a> External for RuntimeExt<'a> {
             GasDistribution::NoRatios
         }
     }
+
+    #[cfg(feature = "protocol_feature_promise_action_min_gas")]
+    fn function_call_gas(&self, receipt_index: u64, action_index: u64) -> Option<(Gas, GasWeight)> {
+        let receipt_index = receipt_index as usize;
+        let action_index = action_index as usize;
+        let gas = match self.action_receipts.get(receipt_index)?.1.actions.get(action_index)? {
+            Action::FunctionCall(FunctionCallAction { gas, .. }) => *gas,
+            _ => return None,
+        };
+        let gas_weight = self
+            .gas_weights
+            .iter()
+            .find(|(index, _)| {
+                index.receipt_index == receipt_index && index.action_index == action_index
+            })
+            .map_or(GasWeight(0), |(_, gas_weight)| gas_weight.clone());
+        Some((gas, gas_weight))
+    }
+
+    #[cfg(feature = "protocol_feature_promise_action_min_gas")]
+    fn gas_weight_sum(&self) -> u128 {
+        self.gas_weights.iter().map(|(_, GasWeight(weight))| *weight as u128).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestExtContext;
+    use near_primitives::shard_layout::ShardUId;
+    use near_primitives::test_utils::MockEpochInfoProvider;
+    #[cfg(feature = "protocol_feature_storage_key_count")]
+    use near_primitives::types::StateChangeCause;
+    use near_store::test_utils::create_tries;
+
+    #[test]
+    fn test_storage_length_limits() {
+        let tries = create_tries();
+        let mut trie_update =
+            tries.new_trie_update(ShardUId::single_shard(), CryptoHash::default());
+        let mut context = TestExtContext::new("alice.near".parse().unwrap());
+        context.limit_config.max_length_storage_key = 4;
+        context.limit_config.max_length_storage_value = 8;
+        let mut ext = context.ext(&mut trie_update);
+        let key_error =
+            |length| VMLogicError::from(ExternalError::KeyLengthExceeded { length, limit: 4 });
+        ext.storage_set(b"key", b"12345678").unwrap();
+        assert_eq!(ext.storage_set(b"key12", b"value"), Err(key_error(5)));
+        assert_eq!(
+            ext.storage_set(b"key", b"123456789"),
+            Err(ExternalError::ValueLengthExceeded { length: 9, limit: 8 }.into())
+        );
+        assert_eq!(ext.storage_has_key(b"key12"), Err(key_error(5)));
+        assert_eq!(ext.storage_remove(b"key12"), Err(key_error(5)));
+        assert!(matches!(ext.storage_get(b"key12"), Err(err) if err == key_error(5)));
+        assert_eq!(ext.storage_has_key(b"key"), Ok(true));
+    }
+
+    #[test]
+    fn test_bls12381_keys_in_actions() {
+        use borsh::BorshSerialize;
+
+        let tries = create_tries();
+        let mut trie_update =
+            tries.new_trie_update(ShardUId::single_shard(), CryptoHash::default());
+        let context = TestExtContext::new("alice.near".parse().unwrap());
+        let mut ext = context.ext(&mut trie_update);
+        let public_key =
+            PublicKey::from_seed(near_crypto::KeyType::BLS12381, "bob").try_to_vec().unwrap();
+        let receipt_index = ext.create_receipt(vec![], "bob.near".parse().unwrap()).unwrap();
+        let results = [
+            ext.append_action_add_key_with_full_access(receipt_index, public_key.clone(), 0),
+            ext.append_action_delete_key(receipt_index, public_key.clone()),
+            ext.append_action_stake(receipt_index, 1, public_key),
+        ];
+        let enabled = checked_feature!(
+            "protocol_feature_bls12381_keys",
+            BLS12381Keys,
+            context.protocol_version
+        );
+        for result in results {
+            if enabled {
+                result.expect("valid action");
+            } else {
+                assert_eq!(result, Err(HostError::InvalidPublicKey.into()));
+            }
+        }
+    }
+
+    /// Counts the validator lookups that reach the wrapped provider.
+    struct CountingEpochInfoProvider {
+        inner: MockEpochInfoProvider,
+        calls: Cell<usize>,
+    }
+
+    impl EpochInfoProvider for CountingEpochInfoProvider {
+        fn validator_stake(
+            &self,
+            epoch_id: &EpochId,
+            last_block_hash: &CryptoHash,
+            account_id: &AccountId,
+        ) -> Result<Option<Balance>, EpochError> {
+            self.calls.set(self.calls.get() + 1);
+            self.inner.validator_stake(epoch_id, last_block_hash, account_id)
+        }
+
+        fn validator_total_stake(
+            &self,
+            epoch_id: &EpochId,
+            last_block_hash: &CryptoHash,
+        ) -> Result<Balance, EpochError> {
+            self.calls.set(self.calls.get() + 1);
+            self.inner.validator_total_stake(epoch_id, last_block_hash)
+        }
+
+        fn minimum_stake(&self, prev_block_hash: &CryptoHash) -> Result<Balance, EpochError> {
+            self.inner.minimum_stake(prev_block_hash)
+        }
+
+        fn account_id_to_shard_id(
+            &self,
+            account_id: &AccountId,
+            epoch_id: &EpochId,
+        ) -> Result<ShardId, EpochError> {
+            self.inner.account_id_to_shard_id(account_id, epoch_id)
+        }
+    }
+
+    #[test]
+    fn test_validator_info_cache() {
+        let tries = create_tries();
+        let mut trie_update =
+            tries.new_trie_update(ShardUId::single_shard(), CryptoHash::default());
+        let account_id: AccountId = "alice.near".parse().unwrap();
+        let validator_id: AccountId = "validator.near".parse().unwrap();
+        let context = TestExtContext::new(account_id.clone()).with_epoch_info_provider(
+            CountingEpochInfoProvider {
+                inner: MockEpochInfoProvider::new([(validator_id.clone(), 100)].into_iter()),
+                calls: Cell::new(0),
+            },
+        );
+        let ext = context.ext(&mut trie_update);
+
+        assert!(!ext.validator_stake_cached(&validator_id));
+        assert_eq!(ext.validator_stake(&validator_id), Ok(Some(100)));
+        assert!(ext.validator_stake_cached(&validator_id));
+        assert_eq!(ext.validator_stake(&validator_id), Ok(Some(100)));
+        assert_eq!(context.epoch_info_provider.calls.get(), 1);
+
+        // Accounts which are not validators are cached as well.
+        assert!(!ext.validator_stake_cached(&account_id));
+        assert_eq!(ext.validator_stake(&account_id), Ok(None));
+        assert_eq!(ext.validator_stake(&account_id), Ok(None));
+        assert_eq!(context.epoch_info_provider.calls.get(), 2);
+
+        assert!(!ext.validator_total_stake_cached());
+        assert_eq!(ext.validator_total_stake(), Ok(100));
+        assert!(ext.validator_total_stake_cached());
+        assert_eq!(ext.validator_total_stake(), Ok(100));
+        assert_eq!(context.epoch_info_provider.calls.get(), 3);
+    }
+
+    #[cfg(feature = "protocol_feature_receipt_size_limit")]
+    #[test]
+    fn test_receipt_size_limit() {
+        let tries = create_tries();
+        let mut trie_update =
+            tries.new_trie_update(ShardUId::single_shard(), CryptoHash::default());
+        let account_id: AccountId = "alice.near".parse().unwrap();
+        let receiver_id: AccountId = "bob.near".parse().unwrap();
+        let mut context = TestExtContext::new(account_id.clone());
+        context.limit_config.max_receipt_size = Some(1000);
+        let mut ext = context.ext(&mut trie_update);
+        let first = ext.create_receipt(vec![], receiver_id.clone()).unwrap();
+        ext.append_action_function_call(first, b"f".to_vec(), vec![0; 500], 0, 0).unwrap();
+        let second = ext.create_receipt(vec![first], receiver_id).unwrap();
+        assert!(matches!(
+            ext.append_action_function_call(second, b"f".to_vec(), vec![0; 1000], 0, 0),
+            Err(VMLogicError::HostError(HostError::ReceiptSizeExceeded { limit: 1000, .. }))
+        ));
+
+        // The tracked size is the size of the receipt sent out at the end of the execution.
+        let first_size = ext.action_receipt_sizes[first as usize];
+        let receipts = ext.into_receipts(&account_id);
+        assert_eq!(receipts[first as usize].try_to_vec().unwrap().len() as u64, first_size);
+    }
+
+    /// Receipts deploying the largest contract or calling a function with the largest arguments
+    /// fit in the receipt size limit.
+    #[cfg(feature = "protocol_feature_receipt_size_limit")]
+    #[test]
+    fn test_receipt_size_limit_fits_largest_actions() {
+        use near_primitives::runtime::config_store::RuntimeConfigStore;
+        use near_primitives::version::PROTOCOL_VERSION;
+
+        let tries = create_tries();
+        let mut trie_update =
+            tries.new_trie_update(ShardUId::single_shard(), CryptoHash::default());
+        let mut context = TestExtContext::new("a".repeat(64).parse().unwrap());
+        context.limit_config = RuntimeConfigStore::new(None)
+            .get_config(PROTOCOL_VERSION)
+            .wasm_config
+            .limit_config
+            .clone();
+        let limit_config = context.limit_config.clone();
+        let mut ext = context.ext(&mut trie_update);
+        let receiver_id: AccountId = "b".repeat(64).parse().unwrap();
+
+        let deploy = ext.create_receipt(vec![], receiver_id.clone()).unwrap();
+        ext.append_action_deploy_contract(deploy, vec![0; limit_config.max_contract_size as usize])
+            .unwrap();
+        let call = ext.create_receipt(vec![], receiver_id).unwrap();
+        ext.append_action_function_call(
+            call,
+            vec![b'f'; limit_config.max_length_method_name as usize],
+            vec![0; limit_config.max_arguments_length as usize],
+            0,
+            0,
+        )
+        .unwrap();
+    }
+
+    #[cfg(feature = "protocol_feature_receipt_depth")]
+    #[test]
+    fn test_receipt_depth_limit() {
+        let tries = create_tries();
+        let mut trie_update =
+            tries.new_trie_update(ShardUId::single_shard(), CryptoHash::default());
+        let account_id: AccountId = "alice.near".parse().unwrap();
+        let mut context = TestExtContext::new(account_id.clone());
+        context.limit_config.max_receipt_depth = Some(3);
+        let mut ext = context.ext(&mut trie_update);
+        ext.set_receipt_depth(Some(2));
+        assert_eq!(ext.receipt_depth(), 2);
+        ext.create_receipt(vec![], account_id.clone()).unwrap();
+        ext.set_receipt_depth(Some(3));
+        assert!(matches!(
+            ext.create_receipt(vec![], account_id.clone()),
+            Err(VMLogicError::HostError(HostError::ReceiptDepthExceeded { depth: 4, limit: 3 }))
+        ));
+
+        let receipts = ext.into_receipts(&account_id);
+        assert_eq!(receipts.len(), 1);
+        match &receipts[0].receipt {
+            ReceiptEnum::Action(action_receipt) => assert_eq!(action_receipt.depth, Some(3)),
+            ReceiptEnum::Data(_) => panic!("expected an action receipt"),
+        }
+    }
+
+    #[test]
+    fn test_trie_nodes_memory_limit() {
+        use std::rc::Rc;
+
+        let tries = create_tries();
+        let shard_uid = ShardUId::single_shard();
+        let account_id: AccountId = "alice.near".parse().unwrap();
+        let mut trie_update = tries.new_trie_update(shard_uid, CryptoHash::default());
+        for i in 0..1000 {
+            let key = format!("item{}", i).into_bytes();
+            let key = TrieKey::ContractData { account_id: account_id.clone(), key };
+            trie_update.set(key, b"value".to_vec());
+        }
+        trie_update.commit(StateChangeCause::InitialState);
+        let trie_changes = trie_update.finalize().unwrap().0;
+        let (store_update, root) = tries.apply_all(&trie_changes, shard_uid).unwrap();
+        store_update.commit().unwrap();
+
+        // Removes the wide `item` prefix twice, then again as if in the next receipt, returning
+        // the results and the loaded size after each removal.
+        let remove_items = |limit_config: &VMLimitConfig, mode: TrieCacheMode| {
+            let mut context = TestExtContext::new(account_id.clone());
+            context.limit_config = limit_config.clone();
+            let mut trie_update = tries.new_trie_update(shard_uid, root);
+            let mut ext = context.ext(&mut trie_update);
+            ext.set_trie_cache_mode(mode);
+            let mut results = vec![];
+            for i in 0..3 {
+                if i == 2 {
+                    ext.trie_update.trie().reset_loaded_nodes_size();
+                }
+                let result = ext.storage_remove_subtree(b"item");
+                results.push((result, ext.trie_update.trie().loaded_nodes_size()));
+            }
+            results
+        };
+
+        let limit_config = VMLimitConfig::test();
+        let results = remove_items(&limit_config, TrieCacheMode::CachingShard);
+        assert!(results.iter().all(|(result, _)| result.is_ok()));
+        let size = results[0].1;
+        assert!(size > 0);
+        // Nodes loaded again by the same receipt are counted once, and counted again by the next
+        // receipt.
+        assert_eq!(results[1].1, size);
+        assert_eq!(results[2].1, size);
+        // Nodes in the chunk cache aren't loaded again, not even by the next receipt.
+        let results = remove_items(&limit_config, TrieCacheMode::CachingChunk);
+        assert_eq!(results[0].1, size);
+        assert_eq!(results[1].1, size);
+        assert_eq!(results[2].1, 0);
+
+        // Recording the storage proof or replaying it loads the same nodes.
+        let proof = {
+            let trie = tries.get_trie_for_shard(shard_uid).recording_reads();
+            let mut trie_update = TrieUpdate::new(Rc::new(trie), root);
+            let context = TestExtContext::new(account_id.clone());
+            let mut ext = context.ext(&mut trie_update);
+            ext.storage_remove_subtree(b"item").unwrap();
+            ext.storage_remove_subtree(b"item").unwrap();
+            assert_eq!(ext.trie_update.trie().loaded_nodes_size(), size);
+            trie_update.trie.recorded_storage().unwrap()
+        };
+        let trie = Trie::from_recorded_storage(proof);
+        let mut trie_update = TrieUpdate::new(Rc::new(trie), root);
+        let context = TestExtContext::new(account_id.clone());
+        let mut ext = context.ext(&mut trie_update);
+        ext.storage_remove_subtree(b"item").unwrap();
+        ext.storage_remove_subtree(b"item").unwrap();
+        assert_eq!(ext.trie_update.trie().loaded_nodes_size(), size);
+
+        // The removal stops as soon as the limit is exceeded.
+        #[cfg(feature = "protocol_feature_per_receipt_trie_nodes_memory_limit")]
+        {
+            let mut limit_config = VMLimitConfig::test();
+            limit_config.per_receipt_trie_nodes_memory_limit = Some(size / 2);
+            let (result, loaded_size) = remove_items(&limit_config, TrieCacheMode::CachingShard)
+                .into_iter()
+                .next()
+                .unwrap();
+            match result {
+                Err(VMLogicError::ExternalError(err)) => {
+                    assert_eq!(
+                        err.downcast::<ExternalError>(),
+                        Ok(ExternalError::TrieNodesMemoryLimitExceeded {
+                            size: loaded_size,
+                            limit: size / 2
+                        })
+                    );
+                }
+                other => panic!("unexpected result {:?}", other),
+            }
+            assert!(loaded_size < size);
+        }
+    }
+
+    #[test]
+    fn test_read_cache_charges_uncached_gas() {
+        let tries = create_tries();
+        let shard_uid = ShardUId::single_shard();
+        let account_id: AccountId = "alice.near".parse().unwrap();
+        let mut trie_update = tries.new_trie_update(shard_uid, CryptoHash::default());
+        for key in [b"item1", b"item2", b"item3"] {
+            let key = TrieKey::ContractData { account_id: account_id.clone(), key: key.to_vec() };
+            trie_update.set(key, b"value".to_vec());
+        }
+        trie_update.commit(StateChangeCause::InitialState);
+        let trie_changes = trie_update.finalize().unwrap().0;
+        let (store_update, root) = tries.apply_all(&trie_changes, shard_uid).unwrap();
+        store_update.commit().unwrap();
+
+        let keys = [&b"item1"[..], b"item2", b"item1", b"missing", b"item1", b"missing"];
+        let trie_update = tries.new_trie_update(shard_uid, root);
+        for key in keys {
+            let key = TrieKey::ContractData { account_id: account_id.clone(), key: key.to_vec() };
+            trie_update.get_ref_raw(&key.to_vec()).unwrap();
+        }
+        let uncached_nodes = trie_update.trie().get_touched_nodes_count();
+
+        let mut trie_update = tries.new_trie_update(shard_uid, root);
+        let context = TestExtContext::new(account_id.clone());
+        let mut ext = context.ext(&mut trie_update);
+        for key in keys {
+            ext.storage_has_key(key).unwrap();
+        }
+        // Repeated lookups don't touch the trie, but are charged as if they did.
+        assert!(ext.trie_update.trie().get_touched_nodes_count() < uncached_nodes);
+        assert_eq!(ext.get_touched_nodes_count(), uncached_nodes);
+        assert_eq!(ext.storage_get(b"item1").unwrap().unwrap().deref().unwrap(), b"value");
+
+        // Writes and removals aren't hidden by the cache.
+        ext.storage_set(b"item1", b"new").unwrap();
+        assert_eq!(ext.storage_get(b"item1").unwrap().unwrap().deref().unwrap(), b"new");
+        ext.storage_remove(b"item2").unwrap();
+        assert_eq!(ext.storage_has_key(b"item2"), Ok(false));
+        ext.storage_set(b"missing", b"value").unwrap();
+        assert_eq!(ext.storage_has_key(b"missing"), Ok(true));
+    }
+
+    #[test]
+    fn test_touched_nodes_gas_with_storage_proof() {
+        use near_primitives::config::ExtCostsConfig;
+        use std::rc::Rc;
+
+        let tries = create_tries();
+        let shard_uid = ShardUId::single_shard();
+        let account_id: AccountId = "alice.near".parse().unwrap();
+        let mut trie_update = tries.new_trie_update(shard_uid, CryptoHash::default());
+        for key in [b"item1", b"item2", b"item3"] {
+            let key = TrieKey::ContractData { account_id: account_id.clone(), key: key.to_vec() };
+            trie_update.set(key, b"value".to_vec());
+        }
+        trie_update.commit(StateChangeCause::InitialState);
+        let trie_changes = trie_update.finalize().unwrap().0;
+        let (store_update, root) = tries.apply_all(&trie_changes, shard_uid).unwrap();
+        store_update.commit().unwrap();
+
+        let touching_trie_node = ExtCostsConfig::test().touching_trie_node;
+        let context = TestExtContext::new(account_id.clone());
+        // Reads the keys, the middle ones while caching the chunk, and returns the gas charged for
+        // the trie nodes touched by each read.
+        let read_keys = |trie_update: &mut TrieUpdate| {
+            let mut ext = context.ext(trie_update);
+            let keys = [b"item1", b"item2", b"item1", b"item3", b"item2", b"item1"];
+            let mut gas = vec![];
+            for (i, key) in keys.into_iter().enumerate() {
+                ext.set_trie_cache_mode(if (1..4).contains(&i) {
+                    TrieCacheMode::CachingChunk
+                } else {
+                    TrieCacheMode::CachingShard
+                });
+                let nodes_before = ext.get_touched_nodes_count();
+                ext.storage_get(key).unwrap().unwrap().deref().unwrap();
+                gas.push((ext.get_touched_nodes_count() - nodes_before) * touching_trie_node);
+            }
+            gas
+        };
+
+        let mut trie_update = tries.new_trie_update(shard_uid, root);
+        let gas = read_keys(&mut trie_update);
+        assert!(gas[0] > 0);
+        // Nodes already read while caching the chunk are free.
+        assert!(gas[2] < gas[0]);
+        assert_eq!(gas[5], 0);
+
+        // Recording the storage proof or replaying the reads from it costs the same gas.
+        let trie = tries.get_trie_for_shard(shard_uid).recording_reads();
+        let mut trie_update = TrieUpdate::new(Rc::new(trie), root);
+        assert_eq!(read_keys(&mut trie_update), gas);
+        let proof = trie_update.trie.recorded_storage().unwrap();
+        let mut trie_update = TrieUpdate::new(Rc::new(Trie::from_recorded_storage(proof)), root);
+        assert_eq!(read_keys(&mut trie_update), gas);
+    }
+
+    #[cfg(feature = "protocol_feature_storage_key_count")]
+    #[test]
+    fn test_storage_key_count_includes_uncommitted_changes() {
+        let tries = create_tries();
+        let mut trie_update =
+            tries.new_trie_update(ShardUId::single_shard(), CryptoHash::default());
+        let account_id: AccountId = "alice.near".parse().unwrap();
+        let other_account_id: AccountId = "alice.near.other".parse().unwrap();
+        for (account_id, key) in
+            [(&account_id, b"item1"), (&account_id, b"item2"), (&other_account_id, b"item3")]
+        {
+            let key = TrieKey::ContractData { account_id: account_id.clone(), key: key.to_vec() };
+            trie_update.set(key, b"value".to_vec());
+        }
+        trie_update.commit(StateChangeCause::InitialState);
+        let trie_changes = trie_update.finalize().unwrap().0;
+        let (store_update, root) =
+            tries.apply_all(&trie_changes, ShardUId::single_shard()).unwrap();
+        store_update.commit().unwrap();
+
+        let mut trie_update = tries.new_trie_update(ShardUId::single_shard(), root);
+        let context = TestExtContext::new(account_id.clone());
+        let mut ext = context.ext(&mut trie_update);
+        assert_eq!(ext.storage_key_count(b"item", 10), Ok(Some(2)));
+        ext.storage_set(b"item4", b"value").unwrap();
+        ext.storage_remove(b"item1").unwrap();
+        ext.storage_set(b"other", b"value").unwrap();
+        assert_eq!(ext.storage_key_count(b"item", 10), Ok(Some(2)));
+        ext.storage_set(b"item5", b"value").unwrap();
+        assert_eq!(ext.storage_key_count(b"item", 3), Ok(Some(3)));
+        assert_eq!(ext.storage_key_count(b"item", 2), Ok(None));
+        assert_eq!(ext.storage_key_count(b"", 10), Ok(Some(4)));
+    }
+
+    #[cfg(feature = "protocol_feature_locked_balance_of")]
+    #[test]
+    fn test_locked_balance_of() {
+        use near_primitives::account::Account;
+
+        let tries = create_tries();
+        let mut trie_update =
+            tries.new_trie_update(ShardUId::single_shard(), CryptoHash::default());
+        let account_id: AccountId = "alice.near".parse().unwrap();
+        let validator_id: AccountId = "validator.near".parse().unwrap();
+        let missing_id: AccountId = "missing.near".parse().unwrap();
+        let hash = CryptoHash::default();
+        near_store::set_account(
+            &mut trie_update,
+            account_id.clone(),
+            &Account::new(10, 0, hash, 0),
+        );
+        near_store::set_account(
+            &mut trie_update,
+            validator_id.clone(),
+            &Account::new(10, 100, hash, 0),
+        );
+        // The mock provider puts all accounts on shard 0.
+        for shard_id in [0, 1] {
+            let mut context = TestExtContext::new(account_id.clone());
+            context.shard_id = shard_id;
+            let ext = context.ext(&mut trie_update);
+            if shard_id == 0 {
+                assert_eq!(ext.locked_balance_of(&validator_id), Ok(100));
+                assert_eq!(ext.locked_balance_of(&account_id), Ok(0));
+                assert_eq!(
+                    ext.locked_balance_of(&missing_id),
+                    Err(HostError::AccountDoesNotExist { account_id: missing_id.to_string() }
+                        .into())
+                );
+            } else {
+                assert_eq!(
+                    ext.locked_balance_of(&validator_id),
+                    Err(HostError::AccountOnOtherShard { account_id: validator_id.to_string() }
+                        .into())
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "protocol_feature_per_receipt_storage_proof_limit")]
+    #[test]
+    fn test_recorded_storage_size() {
+        use std::rc::Rc;
+
+        let tries = create_tries();
+        let shard_uid = ShardUId::single_shard();
+        let account_id: AccountId = "alice.near".parse().unwrap();
+        let mut trie_update = tries.new_trie_update(shard_uid, CryptoHash::default());
+        for key in [b"item1", b"item2", b"item3"] {
+            let key = TrieKey::ContractData { account_id: account_id.clone(), key: key.to_vec() };
+            trie_update.set(key, b"value".to_vec());
+        }
+        trie_update.commit(StateChangeCause::InitialState);
+        let trie_changes = trie_update.finalize().unwrap().0;
+        let (store_update, root) = tries.apply_all(&trie_changes, shard_uid).unwrap();
+        store_update.commit().unwrap();
+
+        let context = TestExtContext::new(account_id.clone());
+        // Reads the keys one by one and returns the recorded size before and after each read.
+        let read_keys = |trie_update: &mut TrieUpdate| {
+            let ext = context.ext(trie_update);
+            let mut sizes = vec![ext.recorded_storage_size()];
+            for key in [b"item1", b"item2", b"item1", b"item3"] {
+                ext.storage_get(key).unwrap().unwrap().deref().unwrap();
+                sizes.push(ext.recorded_storage_size());
+            }
+            sizes
+        };
+
+        let trie = tries.get_trie_for_shard(shard_uid).recording_reads();
+        let mut trie_update = TrieUpdate::new(Rc::new(trie), root);
+        let sizes = read_keys(&mut trie_update);
+        assert_eq!(sizes[0], 0);
+        assert!(sizes[1] > sizes[0]);
+        assert!(sizes.windows(2).all(|w| w[0] <= w[1]));
+        // Nodes read again are already in the proof.
+        assert_eq!(sizes[3], sizes[2]);
+
+        // Replaying the reads from the recorded proof or not recording them reports the same
+        // sizes.
+        let proof = trie_update.trie.recorded_storage().unwrap();
+        let mut trie_update = TrieUpdate::new(Rc::new(Trie::from_recorded_storage(proof)), root);
+        assert_eq!(read_keys(&mut trie_update), sizes);
+        let mut trie_update = tries.new_trie_update(shard_uid, root);
+        assert_eq!(read_keys(&mut trie_update), sizes);
+    }
 }
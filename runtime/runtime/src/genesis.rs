@@ -1,4 +1,5 @@
 use std::collections::{HashMap, HashSet};
+use std::mem::size_of;
 
 use borsh::BorshSerialize;
 
@@ -9,6 +10,7 @@ use near_primitives::shard_layout::ShardUId;
 use near_primitives::{
     account::{AccessKey, Account},
     contract::ContractCode,
+    hash::CryptoHash,
     receipt::{DelayedReceiptIndices, Receipt, ReceiptEnum, ReceivedData},
     state_record::{state_record_to_account_id, StateRecord},
     trie_key::TrieKey,
@@ -20,6 +22,7 @@ use near_store::{
 };
 
 use crate::config::RuntimeConfig;
+use crate::ext::check_storage_lengths;
 use crate::Runtime;
 /// Computes the expected storage per account for a given stream of StateRecord(s).
 /// For example: the storage for Contract depends on its length, we don't charge storage for receipts
@@ -63,6 +66,12 @@ impl<'a> StorageComputer<'a> {
             StateRecord::PostponedReceipt(_) => None,
             StateRecord::ReceivedData { .. } => None,
             StateRecord::DelayedReceipt(_) => None,
+            StateRecord::RegisteredDataId { account_id, receiver_id, .. } => {
+                let storage_usage = self.config.num_extra_bytes_record
+                    + size_of::<CryptoHash>() as u64
+                    + receiver_id.as_ref().map_or(0, |receiver_id| receiver_id.len() as u64);
+                Some((account_id.clone(), storage_usage))
+            }
         };
         if let Some((account_id, storage_usage)) = account_and_storage {
             *self.result.entry(account_id).or_default() += storage_usage;
@@ -100,77 +109,66 @@ impl GenesisStateApplier {
         *current_state_root = new_state_root;
     }
 
-    fn apply_batch(
-        current_state_root: &mut StateRoot,
+    /// Writes a state record to the trie.  Postponed receipts are only collected, they must be
+    /// written with `apply_postponed_receipts` once all the received data has been written.
+    pub(crate) fn apply_record(
+        state_update: &mut TrieUpdate,
         delayed_receipts_indices: &mut DelayedReceiptIndices,
-        tries: &mut ShardTries,
-        shard_uid: ShardUId,
-        validators: &[(AccountId, PublicKey, Balance)],
+        postponed_receipts: &mut Vec<Receipt>,
         config: &RuntimeConfig,
-        genesis: &Genesis,
-        batch_account_ids: HashSet<&AccountId>,
+        record: StateRecord,
     ) {
-        let mut state_update = tries.new_trie_update(shard_uid, *current_state_root);
-        let mut postponed_receipts: Vec<Receipt> = vec![];
-
-        let mut storage_computer = StorageComputer::new(config);
-
-        genesis.for_each_record(|record: &StateRecord| {
-            if !batch_account_ids.contains(state_record_to_account_id(record)) {
-                return;
+        match record {
+            StateRecord::Account { account_id, account } => {
+                set_account(state_update, account_id, &account);
             }
-
-            storage_computer.process_record(record);
-
-            match record.clone() {
-                StateRecord::Account { account_id, account } => {
-                    set_account(&mut state_update, account_id, &account);
-                }
-                StateRecord::Data { account_id, data_key, value } => {
-                    state_update.set(TrieKey::ContractData { key: data_key, account_id }, value);
-                }
-                StateRecord::Contract { account_id, code } => {
-                    let acc = get_account(&state_update, &account_id).expect("Failed to read state").expect("Code state record should be preceded by the corresponding account record");
-                    // Recompute contract code hash.
-                    let code = ContractCode::new(code, None);
-                    set_code(&mut state_update, account_id, &code);
-                    assert_eq!(*code.hash(), acc.code_hash());
-                }
-                StateRecord::AccessKey { account_id, public_key, access_key } => {
-                    set_access_key(&mut state_update, account_id, public_key, &access_key);
-                }
-                StateRecord::PostponedReceipt(receipt) => {
-                    // Delaying processing postponed receipts, until we process all data first
-                    postponed_receipts.push(*receipt);
-                }
-                StateRecord::ReceivedData { account_id, data_id, data } => {
-                    set_received_data(
-                        &mut state_update,
-                        account_id,
-                        data_id,
-                        &ReceivedData { data },
+            StateRecord::Data { account_id, data_key, value } => {
+                let limit_config = &config.wasm_config.limit_config;
+                check_storage_lengths(limit_config, &data_key, Some(&value)).unwrap_or_else(
+                    |err| panic!("Invalid genesis data record of {}: {:?}", account_id, err),
+                );
+                state_update.set(TrieKey::ContractData { key: data_key, account_id }, value);
+            }
+            StateRecord::Contract { account_id, code } => {
+                let acc =
+                    get_account(state_update, &account_id).expect("Failed to read state").expect(
+                        "Code state record should be preceded by the corresponding account record",
                     );
-                }
-                StateRecord::DelayedReceipt(receipt) => {
-                    Runtime::delay_receipt(
-                        &mut state_update,
-                        delayed_receipts_indices,
-                        &*receipt,
-                    )
-                        .unwrap();
-                }
+                // Recompute contract code hash.
+                let code = ContractCode::new(code, None);
+                set_code(state_update, account_id, &code);
+                assert_eq!(*code.hash(), acc.code_hash());
+            }
+            StateRecord::AccessKey { account_id, public_key, access_key } => {
+                set_access_key(state_update, account_id, public_key, &access_key);
+            }
+            StateRecord::PostponedReceipt(receipt) => {
+                // Delaying processing postponed receipts, until we process all data first
+                postponed_receipts.push(*receipt);
+            }
+            StateRecord::ReceivedData { account_id, data_id, data } => {
+                set_received_data(
+                    state_update,
+                    account_id,
+                    data_id,
+                    &ReceivedData { data, gas_burnt: None },
+                );
+            }
+            StateRecord::DelayedReceipt(receipt) => {
+                Runtime::delay_receipt(state_update, delayed_receipts_indices, &*receipt).unwrap();
+            }
+            StateRecord::RegisteredDataId { account_id, data_id, receiver_id } => {
+                let value = receiver_id
+                    .map_or(vec![], |receiver_id| receiver_id.as_ref().as_bytes().to_vec());
+                state_update.set(TrieKey::RegisteredDataId { account_id, data_id }, value);
             }
-        });
-
-        for (account_id, storage_usage) in storage_computer.finalize() {
-            let mut account = get_account(&state_update, &account_id)
-                .expect("Genesis storage error")
-                .expect("Account must exist");
-            account.set_storage_usage(storage_usage);
-            set_account(&mut state_update, account_id, &account);
         }
+    }
 
-        // Processing postponed receipts after we stored all received data
+    pub(crate) fn apply_postponed_receipts(
+        state_update: &mut TrieUpdate,
+        postponed_receipts: Vec<Receipt>,
+    ) {
         for receipt in postponed_receipts {
             let account_id = &receipt.receiver_id;
             let action_receipt = match &receipt.receipt {
@@ -180,13 +178,13 @@ impl GenesisStateApplier {
             // Logic similar to `apply_receipt`
             let mut pending_data_count: u32 = 0;
             for data_id in &action_receipt.input_data_ids {
-                if get_received_data(&state_update, account_id, *data_id)
+                if get_received_data(state_update, account_id, *data_id)
                     .expect("Genesis storage error")
                     .is_none()
                 {
                     pending_data_count += 1;
                     set(
-                        &mut state_update,
+                        state_update,
                         TrieKey::PostponedReceiptId {
                             receiver_id: account_id.clone(),
                             data_id: *data_id,
@@ -199,17 +197,69 @@ impl GenesisStateApplier {
                 panic!("Postponed receipt should have pending data")
             } else {
                 set(
-                    &mut state_update,
+                    state_update,
                     TrieKey::PendingDataCount {
                         receiver_id: account_id.clone(),
                         receipt_id: receipt.receipt_id,
                     },
                     &pending_data_count,
                 );
-                set_postponed_receipt(&mut state_update, &receipt);
+                set_postponed_receipt(state_update, &receipt);
+            }
+        }
+    }
+
+    pub(crate) fn save_delayed_receipts_indices(
+        state_update: &mut TrieUpdate,
+        delayed_receipts_indices: &DelayedReceiptIndices,
+    ) {
+        if *delayed_receipts_indices != DelayedReceiptIndices::default() {
+            set(state_update, TrieKey::DelayedReceiptIndices, delayed_receipts_indices);
+        }
+    }
+
+    fn apply_batch(
+        current_state_root: &mut StateRoot,
+        delayed_receipts_indices: &mut DelayedReceiptIndices,
+        tries: &mut ShardTries,
+        shard_uid: ShardUId,
+        validators: &[(AccountId, PublicKey, Balance)],
+        config: &RuntimeConfig,
+        genesis: &Genesis,
+        batch_account_ids: HashSet<&AccountId>,
+    ) {
+        let mut state_update = tries.new_trie_update(shard_uid, *current_state_root);
+        let mut postponed_receipts: Vec<Receipt> = vec![];
+
+        let mut storage_computer = StorageComputer::new(config);
+
+        genesis.for_each_shard_record(shard_uid.shard_id as ShardId, |record: &StateRecord| {
+            if !batch_account_ids.contains(state_record_to_account_id(record)) {
+                return;
             }
+
+            storage_computer.process_record(record);
+
+            Self::apply_record(
+                &mut state_update,
+                delayed_receipts_indices,
+                &mut postponed_receipts,
+                config,
+                record.clone(),
+            );
+        });
+
+        for (account_id, storage_usage) in storage_computer.finalize() {
+            let mut account = get_account(&state_update, &account_id)
+                .expect("Genesis storage error")
+                .expect("Account must exist");
+            account.set_storage_usage(storage_usage);
+            set_account(&mut state_update, account_id, &account);
         }
 
+        // Processing postponed receipts after we stored all received data
+        Self::apply_postponed_receipts(&mut state_update, postponed_receipts);
+
         for (account_id, _, amount) in validators {
             if !batch_account_ids.contains(account_id) {
                 continue;
@@ -230,10 +280,9 @@ impl GenesisStateApplier {
         tries: &mut ShardTries,
         shard_uid: ShardUId,
     ) {
-        let mut state_update = tries.new_trie_update(shard_uid, *current_state_root);
-
         if delayed_receipts_indices != DelayedReceiptIndices::default() {
-            set(&mut state_update, TrieKey::DelayedReceiptIndices, &delayed_receipts_indices);
+            let mut state_update = tries.new_trie_update(shard_uid, *current_state_root);
+            Self::save_delayed_receipts_indices(&mut state_update, &delayed_receipts_indices);
             Self::commit(state_update, current_state_root, tries, shard_uid);
         }
     }
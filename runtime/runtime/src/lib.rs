@@ -2,6 +2,7 @@ use std::cmp::max;
 use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use tracing::debug;
 
@@ -26,16 +27,17 @@ use near_primitives::{
     errors::{ActionError, ActionErrorKind, RuntimeError, TxExecutionError},
     hash::CryptoHash,
     receipt::{
-        ActionReceipt, DataReceipt, DelayedReceiptIndices, Receipt, ReceiptEnum, ReceivedData,
+        transaction_receipt_depth, ActionReceipt, DataReceipt, DelayedReceiptIndices, Receipt,
+        ReceiptEnum, ReceivedData, RefundReceipt,
     },
     state_record::StateRecord,
     transaction::{
         Action, ExecutionOutcome, ExecutionOutcomeWithId, ExecutionStatus, LogEntry,
         SignedTransaction,
     },
-    trie_key::TrieKey,
+    trie_key::{trie_key_parsers, TrieKey},
     types::{
-        validator_stake::ValidatorStake, AccountId, Balance, EpochInfoProvider, Gas,
+        validator_stake::ValidatorStake, AccountId, Balance, Compute, EpochInfoProvider, Gas,
         RawStateChangesWithTrieKey, ShardId, StateChangeCause, StateRoot,
     },
     utils::{
@@ -48,7 +50,7 @@ use near_store::{
     StorageError, Trie, TrieChanges, TrieUpdate,
 };
 #[cfg(feature = "sandbox")]
-use near_store::{set_access_key, set_code};
+use near_store::{get_access_key, set_access_key, set_code};
 use near_vm_logic::types::PromiseResult;
 use near_vm_logic::ReturnData;
 pub use near_vm_runner::with_ext_cost_counter;
@@ -56,7 +58,7 @@ pub use near_vm_runner::with_ext_cost_counter;
 use crate::actions::*;
 use crate::balance_checker::check_balance;
 use crate::config::{
-    exec_fee, safe_add_balance, safe_add_gas, safe_gas_to_balance, total_deposit,
+    exec_fee, safe_add_balance, safe_add_compute, safe_add_gas, safe_gas_to_balance, total_deposit,
     total_prepaid_exec_fees, total_prepaid_gas, RuntimeConfig,
 };
 use crate::genesis::{GenesisStateApplier, StorageComputer};
@@ -68,10 +70,13 @@ pub mod adapter;
 mod balance_checker;
 pub mod cache;
 pub mod config;
+pub mod corpus;
 pub mod ext;
 mod genesis;
 mod metrics;
+pub mod scenario;
 pub mod state_viewer;
+pub mod test_utils;
 mod verifier;
 
 const EXPECT_ACCOUNT_EXISTS: &str = "account exists, checked above";
@@ -85,8 +90,8 @@ pub struct ValidatorAccountsUpdate {
     pub validator_rewards: HashMap<AccountId, Balance>,
     /// Stake proposals from the last chunk.
     pub last_proposals: HashMap<AccountId, Balance>,
-    /// The ID of the protocol treasure account if it belongs to the current shard.
-    pub protocol_treasury_account_id: Option<AccountId>,
+    /// The IDs of the protocol treasury accounts which belong to the current shard.
+    pub protocol_treasury_account_ids: Vec<AccountId>,
     /// Accounts to slash and the slashed amount (None means everything)
     pub slashing_info: HashMap<AccountId, Option<Balance>>,
 }
@@ -117,12 +122,27 @@ pub struct ApplyResult {
     pub state_root: StateRoot,
     pub trie_changes: TrieChanges,
     pub validator_proposals: Vec<ValidatorStake>,
+    /// Receipts generated by the chunk for all shards, this one included.  Their order is part of
+    /// the consensus, the receipts sent to each shard are proven in this order: the receipts
+    /// converted from transactions to another account come first, in the order of the
+    /// transactions, followed by the receipts generated by each executed receipt in execution
+    /// order.  The receipts generated by one receipt are in the order of their indices, which is
+    /// the order of the actions and promises creating them, followed by the refunds and the data
+    /// receipts.
     pub outgoing_receipts: Vec<Receipt>,
     pub outcomes: Vec<ExecutionOutcomeWithId>,
     pub state_changes: Vec<RawStateChangesWithTrieKey>,
     pub stats: ApplyStats,
+    /// Delayed receipts processed in the chunk, in processing order, including the ones with
+    /// a priority fee.
     pub processed_delayed_receipts: Vec<Receipt>,
     pub proof: Option<PartialStorage>,
+    /// Size in bytes of trie nodes each processed receipt added to the storage proof of the
+    /// chunk, in processing order.  Reported whether `proof` is recorded or not.
+    pub receipt_proof_sizes: Vec<(CryptoHash, u64)>,
+    /// Wall-clock time it took to execute each receipt, in the order of `outcomes`.  Not part of
+    /// the consensus, only used to report which contracts take the most time to execute.
+    pub receipt_execution_times: Vec<(CryptoHash, Duration)>,
 }
 
 #[derive(Debug)]
@@ -130,11 +150,15 @@ pub struct ActionResult {
     pub gas_burnt: Gas,
     pub gas_burnt_for_function_call: Gas,
     pub gas_used: Gas,
+    /// The burnt gas with the host function costs counted at their compute cost.
+    pub compute_usage: Compute,
     pub result: Result<ReturnData, ActionError>,
     pub logs: Vec<LogEntry>,
     pub new_receipts: Vec<Receipt>,
     pub validator_proposals: Vec<ValidatorStake>,
     pub profile: ProfileData,
+    /// Balance burnt by the actions other than for gas, if the receipt succeeds.
+    pub burnt_amount: Balance,
 }
 
 impl ActionResult {
@@ -152,6 +176,7 @@ impl ActionResult {
             next_result.gas_burnt_for_function_call,
         )?;
         self.gas_used = safe_add_gas(self.gas_used, next_result.gas_used)?;
+        self.compute_usage = safe_add_compute(self.compute_usage, next_result.compute_usage)?;
         self.profile.merge(&next_result.profile);
         self.result = next_result.result;
         self.logs.append(&mut next_result.logs);
@@ -162,6 +187,7 @@ impl ActionResult {
         if self.result.is_ok() {
             self.new_receipts.append(&mut next_result.new_receipts);
             self.validator_proposals.append(&mut next_result.validator_proposals);
+            self.burnt_amount = safe_add_balance(self.burnt_amount, next_result.burnt_amount)?;
         } else {
             self.new_receipts.clear();
             self.validator_proposals.clear();
@@ -176,12 +202,37 @@ impl Default for ActionResult {
             gas_burnt: 0,
             gas_burnt_for_function_call: 0,
             gas_used: 0,
+            compute_usage: 0,
             result: Ok(ReturnData::None),
             logs: vec![],
             new_receipts: vec![],
             validator_proposals: vec![],
             profile: Default::default(),
+            burnt_amount: 0,
+        }
+    }
+}
+
+/// Panics in debug builds if the outgoing receipts of `result` aren't in the order described at
+/// `ApplyResult::outgoing_receipts`, which is the order the outcomes list the receipts they
+/// generated in.  Only action receipts are checked, the outcomes don't list data receipts.
+fn debug_assert_outgoing_receipts_order(result: &ApplyResult) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    let mut generated_receipt_ids =
+        result.outcomes.iter().flat_map(|outcome| outcome.outcome.receipt_ids.iter());
+    for receipt in &result.outgoing_receipts {
+        if !matches!(receipt.receipt, ReceiptEnum::Action(_)) {
+            continue;
         }
+        // Generated receipts which aren't outgoing, the ones converted from transactions to the
+        // signer, are skipped.
+        assert!(
+            generated_receipt_ids.any(|receipt_id| receipt_id == &receipt.receipt_id),
+            "outgoing receipt {} is out of the order its outcome generated it in",
+            receipt.receipt_id
+        );
     }
 }
 
@@ -258,6 +309,8 @@ impl Runtime {
                         output_data_receivers: vec![],
                         input_data_ids: vec![],
                         actions: transaction.actions.clone(),
+                        priority_fee: transaction.priority_fee,
+                        depth: transaction_receipt_depth(apply_state.current_protocol_version),
                     }),
                 };
                 stats.tx_burnt_amount =
@@ -296,6 +349,7 @@ impl Runtime {
         receipt: &Receipt,
         action_receipt: &ActionReceipt,
         promise_results: &[PromiseResult],
+        promise_results_gas_burnt: &[Option<Gas>],
         action_hash: &CryptoHash,
         action_index: usize,
         actions: &[Action],
@@ -311,6 +365,7 @@ impl Runtime {
         );
         result.gas_burnt += exec_fees;
         result.gas_used += exec_fees;
+        result.compute_usage += exec_fees;
         let account_id = &receipt.receiver_id;
         let is_the_only_action = actions.len() == 1;
         let is_refund = AccountId::is_system(&receipt.predecessor_id);
@@ -364,6 +419,7 @@ impl Runtime {
                     receipt,
                     action_receipt,
                     promise_results,
+                    promise_results_gas_burnt,
                     &mut result,
                     account_id,
                     function_call,
@@ -453,6 +509,13 @@ impl Runtime {
                     apply_state.current_protocol_version,
                 )?;
             }
+            Action::NonrefundableStorageTransfer(transfer) => {
+                metrics::ACTION_NONREFUNDABLE_STORAGE_TRANSFER_TOTAL.inc();
+                action_nonrefundable_storage_transfer(
+                    account.as_mut().expect(EXPECT_ACCOUNT_EXISTS),
+                    transfer,
+                )?;
+            }
             #[cfg(feature = "protocol_feature_chunk_only_producers")]
             Action::StakeChunkOnly(stake) => {
                 metrics::ACTION_STAKE_CHUNK_ONLY_TOTAL.inc();
@@ -479,6 +542,7 @@ impl Runtime {
         outgoing_receipts: &mut Vec<Receipt>,
         validator_proposals: &mut Vec<ValidatorStake>,
         stats: &mut ApplyStats,
+        total_compute_usage: &mut Compute,
         epoch_info_provider: &dyn EpochInfoProvider,
     ) -> Result<ExecutionOutcomeWithId, RuntimeError> {
         let action_receipt = match &receipt.receipt {
@@ -486,27 +550,41 @@ impl Runtime {
             _ => unreachable!("given receipt should be an action receipt"),
         };
         let account_id = &receipt.receiver_id;
+        let limit_trie_nodes_memory = checked_feature!(
+            "protocol_feature_per_receipt_trie_nodes_memory_limit",
+            PerReceiptTrieNodesMemoryLimit,
+            apply_state.current_protocol_version
+        );
+        if limit_trie_nodes_memory {
+            state_update.trie.reset_loaded_nodes_size();
+        }
         // Collecting input data and removing it from the state
-        let promise_results = action_receipt
-            .input_data_ids
-            .iter()
-            .map(|data_id| {
-                let ReceivedData { data } = get_received_data(state_update, account_id, *data_id)?
-                    .ok_or_else(|| {
-                        StorageError::StorageInconsistentState(
-                            "received data should be in the state".to_string(),
-                        )
-                    })?;
-                state_update.remove(TrieKey::ReceivedData {
-                    receiver_id: account_id.clone(),
-                    data_id: *data_id,
-                });
-                match data {
-                    Some(value) => Ok(PromiseResult::Successful(value)),
-                    None => Ok(PromiseResult::Failed),
-                }
-            })
-            .collect::<Result<Vec<PromiseResult>, RuntimeError>>()?;
+        let (promise_results, promise_results_gas_burnt): (Vec<PromiseResult>, Vec<Option<Gas>>) =
+            action_receipt
+                .input_data_ids
+                .iter()
+                .map(|data_id| {
+                    let ReceivedData { data, gas_burnt } =
+                        get_received_data(state_update, account_id, *data_id)?.ok_or_else(
+                            || {
+                                StorageError::StorageInconsistentState(
+                                    "received data should be in the state".to_string(),
+                                )
+                            },
+                        )?;
+                    state_update.remove(TrieKey::ReceivedData {
+                        receiver_id: account_id.clone(),
+                        data_id: *data_id,
+                    });
+                    let promise_result = match data {
+                        Some(value) => PromiseResult::Successful(value),
+                        None => PromiseResult::Failed,
+                    };
+                    Ok((promise_result, gas_burnt))
+                })
+                .collect::<Result<Vec<_>, RuntimeError>>()?
+                .into_iter()
+                .unzip();
 
         // state_update might already have some updates so we need to make sure we commit it before
         // executing the actual receipt
@@ -521,6 +599,7 @@ impl Runtime {
             apply_state.config.transaction_costs.action_receipt_creation_config.exec_fee();
         result.gas_used = exec_fee;
         result.gas_burnt = exec_fee;
+        result.compute_usage = exec_fee;
         // Executing actions one by one
         for (action_index, action) in action_receipt.actions.iter().enumerate() {
             let action_hash = create_action_hash(
@@ -539,6 +618,7 @@ impl Runtime {
                 receipt,
                 action_receipt,
                 &promise_results,
+                &promise_results_gas_burnt,
                 &action_hash,
                 action_index,
                 &action_receipt.actions,
@@ -546,7 +626,11 @@ impl Runtime {
             )?;
             if new_result.result.is_ok() {
                 if let Err(e) = new_result.new_receipts.iter().try_for_each(|receipt| {
-                    validate_receipt(&apply_state.config.wasm_config.limit_config, receipt)
+                    validate_receipt(
+                        &apply_state.config.wasm_config.limit_config,
+                        receipt,
+                        apply_state.current_protocol_version,
+                    )
                 }) {
                     new_result.result = Err(ActionErrorKind::NewReceiptValidationError(e).into());
                 }
@@ -559,6 +643,46 @@ impl Runtime {
             }
         }
 
+        // Failing the receipt if its reads don't fit into the storage proof size limit.
+        if result.result.is_ok()
+            && checked_feature!(
+                "protocol_feature_per_receipt_storage_proof_limit",
+                PerReceiptStorageProofLimit,
+                apply_state.current_protocol_version
+            )
+        {
+            if let Some(limit) =
+                apply_state.config.wasm_config.limit_config.per_receipt_storage_proof_size_limit
+            {
+                let size = state_update.trie.recorded_storage_size();
+                if size > limit {
+                    result.merge(ActionResult {
+                        result: Err(
+                            ActionErrorKind::StorageProofSizeExceeded { size, limit }.into()
+                        ),
+                        ..Default::default()
+                    })?;
+                }
+            }
+        }
+
+        // Failing the receipt if the trie nodes it loaded take too much memory.
+        if result.result.is_ok() && limit_trie_nodes_memory {
+            if let Some(limit) =
+                apply_state.config.wasm_config.limit_config.per_receipt_trie_nodes_memory_limit
+            {
+                let size = state_update.trie.loaded_nodes_size();
+                if size > limit {
+                    result.merge(ActionResult {
+                        result: Err(
+                            ActionErrorKind::TrieNodesMemoryLimitExceeded { size, limit }.into()
+                        ),
+                        ..Default::default()
+                    })?;
+                }
+            }
+        }
+
         // Going to check balance covers account's storage.
         if result.result.is_ok() {
             if let Some(ref mut account) = account {
@@ -592,6 +716,7 @@ impl Runtime {
             ) {
                 result.gas_burnt = 0;
                 result.gas_used = 0;
+                result.compute_usage = 0;
             }
 
             // If the refund fails tokens are burned.
@@ -624,6 +749,8 @@ impl Runtime {
                 state_update.commit(StateChangeCause::ReceiptProcessing {
                     receipt_hash: receipt.get_hash(),
                 });
+                stats.other_burnt_amount =
+                    safe_add_balance(stats.other_burnt_amount, result.burnt_amount)?;
             }
             Err(_) => {
                 state_update.rollback();
@@ -691,6 +818,12 @@ impl Runtime {
                     Ok(_) => Some(vec![]),
                     Err(_) => None,
                 };
+                let gas_burnt = checked_feature!(
+                    "protocol_feature_promise_result_gas_burnt",
+                    PromiseResultGasBurnt,
+                    apply_state.current_protocol_version
+                )
+                .then(|| result.gas_burnt);
                 result.new_receipts.extend(action_receipt.output_data_receivers.iter().map(
                     |data_receiver| Receipt {
                         predecessor_id: account_id.clone(),
@@ -699,6 +832,7 @@ impl Runtime {
                         receipt: ReceiptEnum::Data(DataReceipt {
                             data_id: data_receiver.data_id,
                             data: data.clone(),
+                            gas_burnt,
                         }),
                     },
                 ));
@@ -706,6 +840,7 @@ impl Runtime {
         }
 
         // Generating receipt IDs
+        let mut refunds = vec![];
         let receipt_ids = result
             .new_receipts
             .into_iter()
@@ -720,6 +855,9 @@ impl Runtime {
                 );
 
                 new_receipt.receipt_id = receipt_id;
+                if let Some(kind) = new_receipt.refund_kind() {
+                    refunds.push(RefundReceipt { receipt_id, kind });
+                }
                 let is_action = matches!(&new_receipt.receipt, ReceiptEnum::Action(_));
                 outgoing_receipts.push(new_receipt);
                 if is_action {
@@ -746,7 +884,17 @@ impl Runtime {
         };
 
         Self::print_log(&result.logs);
+        *total_compute_usage = safe_add_compute(*total_compute_usage, result.compute_usage)?;
 
+        let metadata = if checked_feature!(
+            "protocol_feature_refund_receipt_origin",
+            RefundReceiptOrigin,
+            apply_state.current_protocol_version
+        ) {
+            ExecutionMetadata::V3 { profile: result.profile, refunds }
+        } else {
+            ExecutionMetadata::V2(result.profile)
+        };
         Ok(ExecutionOutcomeWithId {
             id: receipt.receipt_id,
             outcome: ExecutionOutcome {
@@ -756,7 +904,7 @@ impl Runtime {
                 gas_burnt: result.gas_burnt,
                 tokens_burnt,
                 executor_id: account_id.clone(),
-                metadata: ExecutionMetadata::V2(result.profile),
+                metadata,
             },
         })
     }
@@ -841,6 +989,7 @@ impl Runtime {
         outgoing_receipts: &mut Vec<Receipt>,
         validator_proposals: &mut Vec<ValidatorStake>,
         stats: &mut ApplyStats,
+        total_compute_usage: &mut Compute,
         epoch_info_provider: &dyn EpochInfoProvider,
     ) -> Result<Option<ExecutionOutcomeWithId>, RuntimeError> {
         let account_id = &receipt.receiver_id;
@@ -852,7 +1001,10 @@ impl Runtime {
                     state_update,
                     account_id.clone(),
                     data_receipt.data_id,
-                    &ReceivedData { data: data_receipt.data.clone() },
+                    &ReceivedData {
+                        data: data_receipt.data.clone(),
+                        gas_burnt: data_receipt.gas_burnt,
+                    },
                 );
                 // Check if there is already a receipt that was postponed and was awaiting for the
                 // given data_id.
@@ -909,6 +1061,7 @@ impl Runtime {
                                 outgoing_receipts,
                                 validator_proposals,
                                 stats,
+                                total_compute_usage,
                                 epoch_info_provider,
                             )
                             .map(Some);
@@ -963,6 +1116,7 @@ impl Runtime {
                             outgoing_receipts,
                             validator_proposals,
                             stats,
+                            total_compute_usage,
                             epoch_info_provider,
                         )
                         .map(Some);
@@ -989,7 +1143,7 @@ impl Runtime {
     }
 
     /// Iterates over the validators in the current shard and updates their accounts to return stake
-    /// and allocate rewards. Also updates protocol treasure account if it belongs to the current
+    /// and allocate rewards. Also updates the protocol treasury accounts which belong to the current
     /// shard.
     fn update_validator_accounts(
         &self,
@@ -1081,7 +1235,7 @@ impl Runtime {
             }
         }
 
-        if let Some(account_id) = &validator_accounts_update.protocol_treasury_account_id {
+        for account_id in &validator_accounts_update.protocol_treasury_account_ids {
             // If protocol treasury stakes, then the rewards was already distributed above.
             if !validator_accounts_update.stake_info.contains_key(account_id) {
                 let mut account = get_account(state_update, account_id)?.ok_or_else(|| {
@@ -1229,6 +1383,8 @@ impl Runtime {
                 stats,
                 processed_delayed_receipts: vec![],
                 proof,
+                receipt_proof_sizes: vec![],
+                receipt_execution_times: vec![],
             });
         }
 
@@ -1236,11 +1392,15 @@ impl Runtime {
         let mut validator_proposals = vec![];
         let mut local_receipts = vec![];
         let mut outcomes = vec![];
-        let mut processed_delayed_receipts = vec![];
+        let mut receipt_proof_sizes = vec![];
+        let mut receipt_execution_times = vec![];
         // This contains the gas "burnt" for refund receipts. Even though we don't actually
         // charge any gas for refund receipts, we still count the gas use towards the block gas
         // limit
         let mut total_gas_burnt = gas_used_for_migrations;
+        // The gas burnt with the host function costs counted at their compute cost, which
+        // limits the receipts processed in the chunk along with the gas.
+        let mut total_compute_usage = gas_used_for_migrations;
 
         for signed_transaction in transactions {
             let (receipt, outcome_with_id) = self.process_transaction(
@@ -1256,6 +1416,7 @@ impl Runtime {
             }
 
             total_gas_burnt += outcome_with_id.outcome.gas_burnt;
+            total_compute_usage += outcome_with_id.outcome.gas_burnt;
 
             outcomes.push(outcome_with_id);
         }
@@ -1266,9 +1427,13 @@ impl Runtime {
 
         let mut process_receipt = |receipt: &Receipt,
                                    state_update: &mut TrieUpdate,
-                                   total_gas_burnt: &mut Gas|
+                                   total_gas_burnt: &mut Gas,
+                                   total_compute_usage: &mut Compute|
          -> Result<_, RuntimeError> {
             let _span = tracing::debug_span!(target: "runtime", "Runtime::process_receipt", receipt_id = %receipt.receipt_id, node_counter = state_update.trie.get_touched_nodes_count()).entered();
+            let started = Instant::now();
+            // Trie nodes read from here on are attributed to the storage proof of this receipt.
+            state_update.trie.reset_recorded_storage_size();
             let result = self.process_receipt(
                 state_update,
                 apply_state,
@@ -1276,13 +1441,18 @@ impl Runtime {
                 &mut outgoing_receipts,
                 &mut validator_proposals,
                 &mut stats,
+                total_compute_usage,
                 epoch_info_provider,
             );
+            let elapsed = started.elapsed();
             tracing::debug!(target: "runtime", node_counter = state_update.trie.get_touched_nodes_count());
+            receipt_proof_sizes
+                .push((receipt.receipt_id, state_update.trie.recorded_storage_size()));
             result?.into_iter().try_for_each(
                 |outcome_with_id: ExecutionOutcomeWithId| -> Result<(), RuntimeError> {
                     *total_gas_burnt =
                         safe_add_gas(*total_gas_burnt, outcome_with_id.outcome.gas_burnt)?;
+                    receipt_execution_times.push((outcome_with_id.id, elapsed));
                     outcomes.push(outcome_with_id);
                     Ok(())
                 },
@@ -1291,21 +1461,88 @@ impl Runtime {
         };
 
         let gas_limit = apply_state.gas_limit.unwrap_or(Gas::max_value());
+        let compute_limit = apply_state.compute_limit.unwrap_or(gas_limit);
+        let is_below_limits = |total_gas_burnt: Gas, total_compute_usage: Compute| {
+            total_gas_burnt < gas_limit && total_compute_usage < compute_limit
+        };
+        // Receipts which don't fit into the gas or compute limit, added to the delayed receipts
+        // queue once all the receipts are processed.
+        let mut new_delayed_receipts = vec![];
 
         // We first process local receipts. They contain staking, local contract calls, etc.
         for receipt in local_receipts.iter() {
-            if total_gas_burnt < gas_limit {
+            if is_below_limits(total_gas_burnt, total_compute_usage) {
                 // NOTE: We don't need to validate the local receipt, because it's just validated in
                 // the `verify_and_charge_transaction`.
-                process_receipt(receipt, &mut state_update, &mut total_gas_burnt)?;
+                process_receipt(
+                    receipt,
+                    &mut state_update,
+                    &mut total_gas_burnt,
+                    &mut total_compute_usage,
+                )?;
             } else {
-                Self::delay_receipt(&mut state_update, &mut delayed_receipts_indices, receipt)?;
+                new_delayed_receipts.push(receipt);
+            }
+        }
+
+        let priority_delayed_receipts_enabled = checked_feature!(
+            "protocol_feature_priority_fee",
+            PriorityFee,
+            apply_state.current_protocol_version
+        );
+        // Then we process the delayed receipts with a priority fee, the highest fee first.  They
+        // are kept out of the delayed receipts queue, sorted by their trie keys.
+        let mut processed_priority_delayed_receipts = vec![];
+        if priority_delayed_receipts_enabled {
+            let prefix = trie_key_parsers::get_raw_prefix_for_priority_delayed_receipts();
+            while is_below_limits(total_gas_burnt, total_compute_usage) {
+                let raw_key = match state_update.iter(&prefix)?.next() {
+                    Some(raw_key) => raw_key?,
+                    None => break,
+                };
+                let key = trie_key_parsers::parse_trie_key_priority_delayed_receipt_from_raw_key(
+                    &raw_key,
+                )
+                .map_err(|e| {
+                    StorageError::StorageInconsistentState(format!(
+                        "Failed to parse delayed receipt key {:?}: {}",
+                        raw_key, e
+                    ))
+                })?;
+                let receipt: Receipt = get(&state_update, &key)?.ok_or_else(|| {
+                    StorageError::StorageInconsistentState(format!(
+                        "Delayed receipt {:?} should be in the state",
+                        key
+                    ))
+                })?;
+
+                validate_receipt(
+                    &apply_state.config.wasm_config.limit_config,
+                    &receipt,
+                    apply_state.current_protocol_version,
+                )
+                .map_err(|e| {
+                    StorageError::StorageInconsistentState(format!(
+                        "Delayed receipt {:?} in the state is invalid: {}",
+                        key, e
+                    ))
+                })?;
+
+                state_update.remove(key);
+                process_receipt(
+                    &receipt,
+                    &mut state_update,
+                    &mut total_gas_burnt,
+                    &mut total_compute_usage,
+                )?;
+                processed_priority_delayed_receipts.push(receipt);
             }
         }
+        let mut processed_delayed_receipts = processed_priority_delayed_receipts.clone();
 
         // Then we process the delayed receipts. It's a backlog of receipts from the past blocks.
         while delayed_receipts_indices.first_index < delayed_receipts_indices.next_available_index {
-            if total_gas_burnt >= gas_limit {
+            if !is_below_limits(total_gas_burnt, total_compute_usage) {
                 break;
             }
             let key = TrieKey::DelayedReceipt { index: delayed_receipts_indices.first_index };
@@ -1317,19 +1554,27 @@ impl Runtime {
             })?;
 
             // Validating the delayed receipt. If it fails, it's likely the state is inconsistent.
-            validate_receipt(&apply_state.config.wasm_config.limit_config, &receipt).map_err(
-                |e| {
-                    StorageError::StorageInconsistentState(format!(
-                        "Delayed receipt #{} in the state is invalid: {}",
-                        delayed_receipts_indices.first_index, e
-                    ))
-                },
-            )?;
+            validate_receipt(
+                &apply_state.config.wasm_config.limit_config,
+                &receipt,
+                apply_state.current_protocol_version,
+            )
+            .map_err(|e| {
+                StorageError::StorageInconsistentState(format!(
+                    "Delayed receipt #{} in the state is invalid: {}",
+                    delayed_receipts_indices.first_index, e
+                ))
+            })?;
 
             state_update.remove(key);
             // Math checked above: first_index is less than next_available_index
             delayed_receipts_indices.first_index += 1;
-            process_receipt(&receipt, &mut state_update, &mut total_gas_burnt)?;
+            process_receipt(
+                &receipt,
+                &mut state_update,
+                &mut total_gas_burnt,
+                &mut total_compute_usage,
+            )?;
             processed_delayed_receipts.push(receipt);
         }
 
@@ -1337,10 +1582,36 @@ impl Runtime {
         for receipt in incoming_receipts.iter() {
             // Validating new incoming no matter whether we have available gas or not. We don't
             // want to store invalid receipts in state as delayed.
-            validate_receipt(&apply_state.config.wasm_config.limit_config, receipt)
-                .map_err(RuntimeError::ReceiptValidationError)?;
-            if total_gas_burnt < gas_limit {
-                process_receipt(receipt, &mut state_update, &mut total_gas_burnt)?;
+            validate_receipt(
+                &apply_state.config.wasm_config.limit_config,
+                receipt,
+                apply_state.current_protocol_version,
+            )
+            .map_err(RuntimeError::ReceiptValidationError)?;
+            if is_below_limits(total_gas_burnt, total_compute_usage) {
+                process_receipt(
+                    receipt,
+                    &mut state_update,
+                    &mut total_gas_burnt,
+                    &mut total_compute_usage,
+                )?;
+            } else {
+                new_delayed_receipts.push(receipt);
+            }
+        }
+
+        // Receipts with a priority fee are keyed by their fee and the order they are delayed in,
+        // the others go to the end of the delayed receipts queue.
+        let mut new_priority_delayed_receipts = vec![];
+        for receipt in new_delayed_receipts {
+            if priority_delayed_receipts_enabled && receipt.priority_fee() > 0 {
+                let key = TrieKey::PriorityDelayedReceipt {
+                    priority_fee: receipt.priority_fee(),
+                    block_height: apply_state.block_index,
+                    index: new_priority_delayed_receipts.len() as u64,
+                };
+                set(&mut state_update, key, receipt);
+                new_priority_delayed_receipts.push(receipt.clone());
             } else {
                 Self::delay_receipt(&mut state_update, &mut delayed_receipts_indices, receipt)?;
             }
@@ -1358,6 +1629,8 @@ impl Runtime {
             incoming_receipts,
             transactions,
             &outgoing_receipts,
+            &processed_priority_delayed_receipts,
+            &new_priority_delayed_receipts,
             &stats,
             apply_state.current_protocol_version,
         )?;
@@ -1366,7 +1639,7 @@ impl Runtime {
 
         #[cfg(feature = "sandbox")]
         if let Some(patch) = states_to_patch {
-            self.apply_state_patches(&mut state_update, patch);
+            self.apply_state_patches(&mut state_update, patch, &apply_state.config)?;
         }
 
         let (trie_changes, state_changes) = state_update.finalize()?;
@@ -1385,7 +1658,7 @@ impl Runtime {
 
         let state_root = trie_changes.new_root;
         let proof = trie.recorded_storage();
-        Ok(ApplyResult {
+        let result = ApplyResult {
             state_root,
             trie_changes,
             validator_proposals: unique_proposals,
@@ -1395,7 +1668,11 @@ impl Runtime {
             stats,
             processed_delayed_receipts,
             proof,
-        })
+            receipt_proof_sizes,
+            receipt_execution_times,
+        };
+        debug_assert_outgoing_receipts_order(&result);
+        Ok(result)
     }
 
     // Adds the given receipt into the end of the delayed receipt queue in the state.
@@ -1424,13 +1701,43 @@ impl Runtime {
         &self,
         state_update: &mut TrieUpdate,
         states_to_patch: Vec<StateRecord>,
-    ) {
+        config: &RuntimeConfig,
+    ) -> Result<(), RuntimeError> {
+        use borsh::BorshSerialize;
+
+        // Accounts patched together with their keys are expected to carry the right storage
+        // usage already, the usage of the others is adjusted for each patched key.
+        let patched_accounts: HashSet<AccountId> = states_to_patch
+            .iter()
+            .filter_map(|record| match record {
+                StateRecord::Account { account_id, .. } => Some(account_id.clone()),
+                _ => None,
+            })
+            .collect();
+        let storage_usage_config = &config.transaction_costs.storage_usage_config;
+        let key_storage_usage =
+            |public_key: &PublicKey, access_key: &near_primitives::account::AccessKey| {
+                public_key.try_to_vec().unwrap().len() as u64
+                    + access_key.try_to_vec().unwrap().len() as u64
+                    + storage_usage_config.num_extra_bytes_record
+            };
         for record in states_to_patch {
             match record {
                 StateRecord::Account { account_id, account } => {
                     set_account(state_update, account_id, &account);
                 }
                 StateRecord::Data { account_id, data_key, value } => {
+                    ext::check_storage_lengths(
+                        &config.wasm_config.limit_config,
+                        &data_key,
+                        Some(&value),
+                    )
+                    .map_err(|err| {
+                        RuntimeError::InvalidStatePatch(format!(
+                            "Invalid patched data record of {}: {:?}",
+                            account_id, err
+                        ))
+                    })?;
                     state_update.set(TrieKey::ContractData { key: data_key, account_id }, value);
                 }
                 StateRecord::Contract { account_id, code } => {
@@ -1441,12 +1748,38 @@ impl Runtime {
                     assert_eq!(*code.hash(), acc.code_hash());
                 }
                 StateRecord::AccessKey { account_id, public_key, access_key } => {
+                    if !patched_accounts.contains(&account_id) {
+                        let mut account =
+                            get_account(state_update, &account_id)?.ok_or_else(|| {
+                                RuntimeError::InvalidStatePatch(format!(
+                                    "Access key patched into missing account {}",
+                                    account_id
+                                ))
+                            })?;
+                        let old_usage = get_access_key(state_update, &account_id, &public_key)?
+                            .map_or(0, |old_key| key_storage_usage(&public_key, &old_key));
+                        let storage_usage = account
+                            .storage_usage()
+                            .checked_sub(old_usage)
+                            .and_then(|usage| {
+                                usage.checked_add(key_storage_usage(&public_key, &access_key))
+                            })
+                            .ok_or_else(|| {
+                                RuntimeError::InvalidStatePatch(format!(
+                                    "Storage usage of {} doesn't account for its access key {}",
+                                    account_id, public_key
+                                ))
+                            })?;
+                        account.set_storage_usage(storage_usage);
+                        set_account(state_update, account_id.clone(), &account);
+                    }
                     set_access_key(state_update, account_id, public_key, &access_key);
                 }
                 _ => unimplemented!("patch_state can only patch Account, AccessKey, Contract and Data kind of StateRecord")
             }
         }
         state_update.commit(StateChangeCause::Migration);
+        Ok(())
     }
 
     /// Computes the expected storage per account for a given set of StateRecord(s).
@@ -1478,24 +1811,30 @@ impl Runtime {
 mod tests {
     use near_crypto::{InMemorySigner, KeyType, Signer};
     use near_primitives::account::AccessKey;
+    use near_primitives::config::ExtCosts;
     use near_primitives::contract::ContractCode;
+    use near_primitives::errors::ContractCallError;
     use near_primitives::hash::hash;
+    use near_primitives::receipt::ReceiptQueueChanges;
     use near_primitives::shard_layout::ShardUId;
     use near_primitives::test_utils::{account_new, MockEpochInfoProvider};
     use near_primitives::transaction::DeployContractAction;
     use near_primitives::transaction::{
-        AddKeyAction, DeleteKeyAction, FunctionCallAction, TransferAction,
+        AddKeyAction, DeleteAccountAction, DeleteKeyAction, FunctionCallAction,
+        NonrefundableStorageTransferAction, TransferAction,
     };
     use near_primitives::types::MerkleHash;
     use near_primitives::version::PROTOCOL_VERSION;
     use near_store::set_access_key;
     use near_store::test_utils::create_tries;
     use near_store::StoreCompiledContractCache;
+    use near_vm_errors::{FunctionCallError, HostError};
     use near_vm_runner::get_contract_cache_key;
     use near_vm_runner::internal::VMKind;
     use testlib::runtime_utils::{alice_account, bob_account};
 
     use super::*;
+    use crate::scenario::{BlockConfig, Scenario, ScenarioResult};
 
     const GAS_PRICE: Balance = 5000;
 
@@ -1519,6 +1858,8 @@ mod tests {
                 output_data_receivers: vec![],
                 input_data_ids: vec![],
                 actions,
+                priority_fee: None,
+                depth: None,
             }),
         }]
     }
@@ -1597,9 +1938,11 @@ mod tests {
             block_hash: Default::default(),
             epoch_id: Default::default(),
             epoch_height: 0,
+            shard_id: 0,
             gas_price: GAS_PRICE,
             block_timestamp: 100,
             gas_limit: Some(gas_limit),
+            compute_limit: None,
             random_seed: Default::default(),
             current_protocol_version: PROTOCOL_VERSION,
             config: Arc::new(RuntimeConfig::test()),
@@ -1612,22 +1955,51 @@ mod tests {
         (runtime, tries, root, apply_state, signer, MockEpochInfoProvider::default())
     }
 
+    /// Scenario with the account set up by `setup_runtime` and `num_blocks` blocks limited to
+    /// `gas_limit`.
+    fn setup_scenario(
+        initial_balance: Balance,
+        initial_locked: Balance,
+        gas_limit: Gas,
+        num_blocks: u64,
+    ) -> Scenario {
+        let mut scenario = Scenario::default();
+        scenario.runtime_config = Some(RuntimeConfig::test());
+        scenario.gas_price = GAS_PRICE;
+        scenario.add_account(alice_account(), initial_balance, initial_locked);
+        scenario.blocks = (1..=num_blocks)
+            .map(|height| BlockConfig {
+                gas_limit: Some(gas_limit),
+                ..BlockConfig::at_height(height)
+            })
+            .collect();
+        scenario
+    }
+
+    /// Checks the balance of alice after each block of a scenario which transfers her the
+    /// receipts of `generate_receipts` or `generate_refund_receipts`, `processed(i)` of them by
+    /// the block with index `i`.
+    fn assert_transfers_received(
+        result: &ScenarioResult,
+        initial_balance: Balance,
+        small_transfer: Balance,
+        processed: impl Fn(u64) -> u64,
+    ) {
+        for i in 0..result.blocks.len() {
+            let account = get_account(&result.state_after(i), &alice_account()).unwrap().unwrap();
+            let n = processed(i as u64);
+            assert_eq!(
+                account.amount(),
+                initial_balance
+                    + small_transfer * Balance::from(n)
+                    + Balance::from(n * (n - 1) / 2)
+            );
+        }
+    }
+
     #[test]
     fn test_apply_no_op() {
-        let (runtime, tries, root, apply_state, _, epoch_info_provider) =
-            setup_runtime(to_yocto(1_000_000), 0, 10u64.pow(15));
-        runtime
-            .apply(
-                tries.get_trie_for_shard(ShardUId::single_shard()),
-                root,
-                &None,
-                &apply_state,
-                &[],
-                &[],
-                &epoch_info_provider,
-                None,
-            )
-            .unwrap();
+        setup_scenario(to_yocto(1_000_000), 0, 10u64.pow(15), 1).run().unwrap();
     }
 
     #[test]
@@ -1642,7 +2014,7 @@ mod tests {
             stake_info: vec![(alice_account(), initial_locked)].into_iter().collect(),
             validator_rewards: vec![(alice_account(), reward)].into_iter().collect(),
             last_proposals: Default::default(),
-            protocol_treasury_account_id: None,
+            protocol_treasury_account_ids: vec![],
             slashing_info: HashMap::default(),
         };
 
@@ -1665,42 +2037,15 @@ mod tests {
         let initial_balance = to_yocto(1_000_000);
         let initial_locked = to_yocto(500_000);
         let small_transfer = to_yocto(10_000);
-        let gas_limit = 1;
-        let (runtime, tries, mut root, apply_state, _, epoch_info_provider) =
-            setup_runtime(initial_balance, initial_locked, gas_limit);
-
         let n = 10;
-        let receipts = generate_refund_receipts(small_transfer, n);
+        let mut scenario = setup_scenario(initial_balance, initial_locked, 1, n + 3);
+        scenario.blocks[0].receipts = generate_refund_receipts(small_transfer, n);
 
         // Checking n receipts delayed
-        for i in 1..=n + 3 {
-            let prev_receipts: &[Receipt] = if i == 1 { &receipts } else { &[] };
-            let apply_result = runtime
-                .apply(
-                    tries.get_trie_for_shard(ShardUId::single_shard()),
-                    root,
-                    &None,
-                    &apply_state,
-                    prev_receipts,
-                    &[],
-                    &epoch_info_provider,
-                    None,
-                )
-                .unwrap();
-            let (store_update, new_root) =
-                tries.apply_all(&apply_result.trie_changes, ShardUId::single_shard()).unwrap();
-            root = new_root;
-            store_update.commit().unwrap();
-            let state = tries.new_trie_update(ShardUId::single_shard(), root);
-            let account = get_account(&state, &alice_account()).unwrap().unwrap();
-            let capped_i = std::cmp::min(i, n);
-            assert_eq!(
-                account.amount(),
-                initial_balance
-                    + small_transfer * Balance::from(capped_i)
-                    + Balance::from(capped_i * (capped_i - 1) / 2)
-            );
-        }
+        let result = scenario.run().unwrap();
+        assert_transfers_received(&result, initial_balance, small_transfer, |i| {
+            std::cmp::min(i + 1, n)
+        });
     }
 
     #[test]
@@ -1708,42 +2053,15 @@ mod tests {
         let initial_balance = to_yocto(1_000_000);
         let initial_locked = to_yocto(500_000);
         let small_transfer = to_yocto(10_000);
-        let gas_limit = 1;
-        let (runtime, tries, mut root, apply_state, _, epoch_info_provider) =
-            setup_runtime(initial_balance, initial_locked, gas_limit);
-
         let n = 10;
-        let receipts = generate_receipts(small_transfer, n);
+        let mut scenario = setup_scenario(initial_balance, initial_locked, 1, n + 3);
+        scenario.blocks[0].receipts = generate_receipts(small_transfer, n);
 
         // Checking n receipts delayed by 1 + 3 extra
-        for i in 1..=n + 3 {
-            let prev_receipts: &[Receipt] = if i == 1 { &receipts } else { &[] };
-            let apply_result = runtime
-                .apply(
-                    tries.get_trie_for_shard(ShardUId::single_shard()),
-                    root,
-                    &None,
-                    &apply_state,
-                    prev_receipts,
-                    &[],
-                    &epoch_info_provider,
-                    None,
-                )
-                .unwrap();
-            let (store_update, new_root) =
-                tries.apply_all(&apply_result.trie_changes, ShardUId::single_shard()).unwrap();
-            root = new_root;
-            store_update.commit().unwrap();
-            let state = tries.new_trie_update(ShardUId::single_shard(), root);
-            let account = get_account(&state, &alice_account()).unwrap().unwrap();
-            let capped_i = std::cmp::min(i, n);
-            assert_eq!(
-                account.amount(),
-                initial_balance
-                    + small_transfer * Balance::from(capped_i)
-                    + Balance::from(capped_i * (capped_i - 1) / 2)
-            );
-        }
+        let result = scenario.run().unwrap();
+        assert_transfers_received(&result, initial_balance, small_transfer, |i| {
+            std::cmp::min(i + 1, n)
+        });
     }
 
     #[test]
@@ -1751,50 +2069,23 @@ mod tests {
         let initial_balance = to_yocto(1_000_000);
         let initial_locked = to_yocto(500_000);
         let small_transfer = to_yocto(10_000);
-        let (runtime, tries, mut root, mut apply_state, _, epoch_info_provider) =
-            setup_runtime(initial_balance, initial_locked, 1);
-
-        let receipt_gas_cost = apply_state
-            .config
-            .transaction_costs
-            .action_receipt_creation_config
-            .exec_fee()
-            + apply_state.config.transaction_costs.action_creation_config.transfer_cost.exec_fee();
-        apply_state.gas_limit = Some(receipt_gas_cost * 3);
+        let config = RuntimeConfig::test();
+        let receipt_gas_cost = config.transaction_costs.action_receipt_creation_config.exec_fee()
+            + config.transaction_costs.action_creation_config.transfer_cost.exec_fee();
 
         let n = 40;
-        let receipts = generate_receipts(small_transfer, n);
-        let mut receipt_chunks = receipts.chunks_exact(4);
-
         // Every time we'll process 3 receipts, so we need n / 3 rounded up. Then we do 3 extra.
-        for i in 1..=n / 3 + 3 {
-            let prev_receipts: &[Receipt] = receipt_chunks.next().unwrap_or_default();
-            let apply_result = runtime
-                .apply(
-                    tries.get_trie_for_shard(ShardUId::single_shard()),
-                    root,
-                    &None,
-                    &apply_state,
-                    prev_receipts,
-                    &[],
-                    &epoch_info_provider,
-                    None,
-                )
-                .unwrap();
-            let (store_update, new_root) =
-                tries.apply_all(&apply_result.trie_changes, ShardUId::single_shard()).unwrap();
-            root = new_root;
-            store_update.commit().unwrap();
-            let state = tries.new_trie_update(ShardUId::single_shard(), root);
-            let account = get_account(&state, &alice_account()).unwrap().unwrap();
-            let capped_i = std::cmp::min(i * 3, n);
-            assert_eq!(
-                account.amount(),
-                initial_balance
-                    + small_transfer * Balance::from(capped_i)
-                    + Balance::from(capped_i * (capped_i - 1) / 2)
-            );
+        let mut scenario =
+            setup_scenario(initial_balance, initial_locked, receipt_gas_cost * 3, n / 3 + 3);
+        let receipts = generate_receipts(small_transfer, n);
+        for (block, chunk) in scenario.blocks.iter_mut().zip(receipts.chunks_exact(4)) {
+            block.receipts = chunk.to_vec();
         }
+
+        let result = scenario.run().unwrap();
+        assert_transfers_received(&result, initial_balance, small_transfer, |i| {
+            std::cmp::min((i + 1) * 3, n)
+        });
     }
 
     #[test]
@@ -1861,28 +2152,329 @@ mod tests {
         }
     }
 
-    fn generate_receipts(small_transfer: u128, n: u64) -> Vec<Receipt> {
-        let mut receipt_id = CryptoHash::default();
-        (0..n)
-            .map(|i| {
-                receipt_id = hash(receipt_id.as_ref());
-                Receipt {
-                    predecessor_id: bob_account(),
-                    receiver_id: alice_account(),
-                    receipt_id,
-                    receipt: ReceiptEnum::Action(ActionReceipt {
-                        signer_id: bob_account(),
-                        signer_public_key: PublicKey::empty(KeyType::ED25519),
-                        gas_price: GAS_PRICE,
-                        output_data_receivers: vec![],
-                        input_data_ids: vec![],
-                        actions: vec![Action::Transfer(TransferAction {
-                            deposit: small_transfer + Balance::from(i),
-                        })],
-                    }),
-                }
-            })
-            .collect()
+    #[test]
+    fn test_apply_delayed_receipts_compute_limit() {
+        let gas_limit = 10u64.pow(15);
+        let (runtime, tries, root, mut apply_state, signer, epoch_info_provider) =
+            setup_runtime(to_yocto(1_000_000), 0, gas_limit);
+        let shard_uid = ShardUId::single_shard();
+        let apply = |root, apply_state: &ApplyState, receipts: &[Receipt]| {
+            let apply_result = runtime
+                .apply(
+                    tries.get_trie_for_shard(shard_uid),
+                    root,
+                    &None,
+                    apply_state,
+                    receipts,
+                    &[],
+                    &epoch_info_provider,
+                    None,
+                )
+                .unwrap();
+            let (store_update, root) =
+                tries.apply_all(&apply_result.trie_changes, shard_uid).unwrap();
+            store_update.commit().unwrap();
+            (root, apply_result)
+        };
+        let num_delayed_receipts = |root| {
+            let state = tries.new_trie_update(shard_uid, root);
+            let indices: DelayedReceiptIndices =
+                get(&state, &TrieKey::DelayedReceiptIndices).unwrap().unwrap_or_default();
+            indices.next_available_index - indices.first_index
+        };
+        let actions = vec![Action::DeployContract(DeployContractAction {
+            code: near_test_contracts::rs_contract().to_vec(),
+        })];
+        let (root, _) = apply(
+            root,
+            &apply_state,
+            &create_receipts_with_actions(alice_account(), signer.clone(), actions),
+        );
+        let receipts: Vec<Receipt> = (0..5u64)
+            .map(|index| {
+                let actions = vec![Action::FunctionCall(FunctionCallAction {
+                    method_name: "write_key_value".to_string(),
+                    args: [index.to_le_bytes(), index.to_le_bytes()].concat(),
+                    gas: 100 * 10u64.pow(12),
+                    deposit: 0,
+                })];
+                let mut receipt =
+                    create_receipts_with_actions(alice_account(), signer.clone(), actions)
+                        .remove(0);
+                receipt.receipt_id = hash(&index.to_le_bytes());
+                receipt
+            })
+            .collect();
+
+        // The receipts burn far less gas than the limit.
+        let (_, gas_result) = apply(root, &apply_state, &receipts);
+        assert_eq!(gas_result.outcomes.len(), 5);
+
+        // Each write uses half of the compute limit, so two receipts fill it.
+        let mut config = RuntimeConfig::test();
+        config
+            .wasm_config
+            .ext_costs
+            .compute_costs
+            .insert(ExtCosts::storage_write_base, gas_limit / 2);
+        apply_state.config = Arc::new(config);
+        let (root, compute_result) = apply(root, &apply_state, &receipts);
+        assert_eq!(compute_result.outcomes.len(), 2);
+        assert_eq!(num_delayed_receipts(root), 3);
+        // Gas and fees don't depend on the compute costs.
+        assert_eq!(compute_result.outcomes[..], gas_result.outcomes[..2]);
+
+        let (root, compute_result) = apply(root, &apply_state, &[]);
+        assert_eq!(compute_result.processed_delayed_receipts.len(), 2);
+        assert_eq!(num_delayed_receipts(root), 1);
+    }
+
+    #[test]
+    fn test_receipt_queue_changes() {
+        let initial_balance = to_yocto(1_000_000);
+        let initial_locked = to_yocto(500_000);
+        let small_transfer = to_yocto(10_000);
+        let (runtime, tries, mut root, mut apply_state, _, epoch_info_provider) =
+            setup_runtime(initial_balance, initial_locked, 1);
+        // Only one receipt is processed per chunk.
+        apply_state.gas_limit = Some(
+            apply_state.config.transaction_costs.action_receipt_creation_config.exec_fee()
+                + apply_state
+                    .config
+                    .transaction_costs
+                    .action_creation_config
+                    .transfer_cost
+                    .exec_fee(),
+        );
+
+        let data_id = hash(b"data_id");
+        let postponed_receipt = Receipt {
+            predecessor_id: bob_account(),
+            receiver_id: alice_account(),
+            receipt_id: hash(b"postponed"),
+            receipt: ReceiptEnum::Action(ActionReceipt {
+                signer_id: bob_account(),
+                signer_public_key: PublicKey::empty(KeyType::ED25519),
+                gas_price: GAS_PRICE,
+                output_data_receivers: vec![],
+                input_data_ids: vec![data_id],
+                actions: vec![Action::Transfer(TransferAction { deposit: small_transfer })],
+                priority_fee: None,
+                depth: None,
+            }),
+        };
+        let data_receipt = Receipt {
+            predecessor_id: bob_account(),
+            receiver_id: alice_account(),
+            receipt_id: hash(b"data"),
+            receipt: ReceiptEnum::Data(DataReceipt {
+                data_id,
+                data: Some(vec![]),
+                gas_burnt: None,
+            }),
+        };
+        let transfers = generate_receipts(small_transfer, 4);
+        let mut first_chunk = vec![postponed_receipt.clone()];
+        first_chunk.extend(transfers.iter().cloned());
+        let chunks = vec![first_chunk, vec![], vec![], vec![], vec![data_receipt]];
+
+        let mut delayed_added = vec![];
+        let mut delayed_removed = vec![];
+        let mut postponed_added = vec![];
+        let mut postponed_removed = vec![];
+        for (height, receipts) in chunks.iter().enumerate() {
+            let apply_result = runtime
+                .apply(
+                    tries.get_trie_for_shard(ShardUId::single_shard()),
+                    root,
+                    &None,
+                    &apply_state,
+                    receipts,
+                    &[],
+                    &epoch_info_provider,
+                    None,
+                )
+                .unwrap();
+            let (store_update, new_root) =
+                tries.apply_all(&apply_result.trie_changes, ShardUId::single_shard()).unwrap();
+            root = new_root;
+            store_update.commit().unwrap();
+
+            let changes = ReceiptQueueChanges::from_state_changes(
+                &apply_result.state_changes,
+                &apply_result.processed_delayed_receipts,
+            );
+            delayed_added
+                .extend(changes.delayed_receipts_added.iter().map(|r| (height, r.receipt_id)));
+            delayed_removed.extend(changes.delayed_receipts_removed.iter().map(|id| (height, *id)));
+            postponed_added
+                .extend(changes.postponed_receipts_added.iter().map(|r| (height, r.receipt_id)));
+            postponed_removed
+                .extend(changes.postponed_receipts_removed.iter().map(|id| (height, *id)));
+        }
+
+        // The first transfer fits into the first chunk, the rest are delayed, the last one for
+        // three chunks.
+        let ids: Vec<_> = transfers.iter().map(|receipt| receipt.receipt_id).collect();
+        assert_eq!(delayed_added, vec![(0, ids[1]), (0, ids[2]), (0, ids[3])]);
+        assert_eq!(delayed_removed, vec![(1, ids[1]), (2, ids[2]), (3, ids[3])]);
+        assert_eq!(postponed_added, vec![(0, postponed_receipt.receipt_id)]);
+        assert_eq!(postponed_removed, vec![(4, postponed_receipt.receipt_id)]);
+    }
+
+    #[cfg(feature = "protocol_feature_priority_fee")]
+    #[test]
+    fn test_delayed_receipts_priority_fee() {
+        let initial_balance = to_yocto(1_000_000);
+        let initial_locked = to_yocto(500_000);
+        let small_transfer = to_yocto(10_000);
+        let (runtime, tries, mut root, mut apply_state, _, epoch_info_provider) =
+            setup_runtime(initial_balance, initial_locked, 1);
+        // Only one receipt is processed per chunk.
+        apply_state.gas_limit = Some(
+            apply_state.config.transaction_costs.action_receipt_creation_config.exec_fee()
+                + apply_state
+                    .config
+                    .transaction_costs
+                    .action_creation_config
+                    .transfer_cost
+                    .exec_fee(),
+        );
+
+        // The last receipt arrives in the second chunk, when the others are already delayed.
+        let priority_fees = [None, Some(10), None, Some(20), Some(10), None, Some(30)];
+        let mut receipts = generate_receipts(small_transfer, priority_fees.len() as u64);
+        for (receipt, priority_fee) in receipts.iter_mut().zip(priority_fees) {
+            if let ReceiptEnum::Action(action_receipt) = &mut receipt.receipt {
+                action_receipt.priority_fee = priority_fee;
+            }
+        }
+
+        let mut processed = vec![];
+        for height in 0..priority_fees.len() {
+            apply_state.block_index = height as u64;
+            let incoming_receipts = match height {
+                0 => &receipts[..6],
+                1 => &receipts[6..],
+                _ => &[],
+            };
+            let apply_result = runtime
+                .apply(
+                    tries.get_trie_for_shard(ShardUId::single_shard()),
+                    root,
+                    &None,
+                    &apply_state,
+                    incoming_receipts,
+                    &[],
+                    &epoch_info_provider,
+                    None,
+                )
+                .unwrap();
+            let (store_update, new_root) =
+                tries.apply_all(&apply_result.trie_changes, ShardUId::single_shard()).unwrap();
+            root = new_root;
+            store_update.commit().unwrap();
+            processed.extend(apply_result.outcomes.iter().map(|outcome| outcome.id));
+        }
+
+        // The first receipt fits into the first chunk, the others are delayed and processed by
+        // decreasing priority fee, in arrival order for the same priority fee.  The last receipt
+        // is delayed by the second chunk and still overtakes the receipts with lower fees
+        // delayed by the first one.
+        let ids: Vec<_> = receipts.iter().map(|receipt| receipt.receipt_id).collect();
+        assert_eq!(processed, vec![ids[0], ids[3], ids[6], ids[1], ids[4], ids[2], ids[5]]);
+    }
+
+    #[test]
+    fn test_nonrefundable_storage_transfer() {
+        let initial_balance = to_yocto(1_000_000);
+        let nonrefundable = to_yocto(10);
+        let (runtime, tries, mut root, apply_state, _, epoch_info_provider) =
+            setup_runtime(initial_balance, 0, 10u64.pow(15));
+        let receipt = |receipt_id: &[u8], predecessor_id: AccountId, action: Action| Receipt {
+            predecessor_id: predecessor_id.clone(),
+            receiver_id: alice_account(),
+            receipt_id: hash(receipt_id),
+            receipt: ReceiptEnum::Action(ActionReceipt {
+                signer_id: predecessor_id,
+                signer_public_key: PublicKey::empty(KeyType::ED25519),
+                gas_price: GAS_PRICE,
+                output_data_receivers: vec![],
+                input_data_ids: vec![],
+                actions: vec![action],
+                priority_fee: None,
+                depth: None,
+            }),
+        };
+        let mut apply = |receipt: Receipt| {
+            let apply_result = runtime
+                .apply(
+                    tries.get_trie_for_shard(ShardUId::single_shard()),
+                    root,
+                    &None,
+                    &apply_state,
+                    &[receipt],
+                    &[],
+                    &epoch_info_provider,
+                    None,
+                )
+                .unwrap();
+            assert!(matches!(
+                apply_result.outcomes[0].outcome.status,
+                ExecutionStatus::SuccessValue(_)
+            ));
+            let (store_update, new_root) =
+                tries.apply_all(&apply_result.trie_changes, ShardUId::single_shard()).unwrap();
+            root = new_root;
+            store_update.commit().unwrap();
+            (apply_result.stats, tries.new_trie_update(ShardUId::single_shard(), root))
+        };
+
+        let (_, state) = apply(receipt(
+            b"nonrefundable",
+            bob_account(),
+            Action::NonrefundableStorageTransfer(NonrefundableStorageTransferAction {
+                deposit: nonrefundable,
+            }),
+        ));
+        let account = get_account(&state, &alice_account()).unwrap().unwrap();
+        assert_eq!(account.amount(), initial_balance);
+        assert_eq!(account.nonrefundable(), nonrefundable);
+
+        // Deleting the account only pays out the amount, the nonrefundable balance is burnt.
+        let (stats, state) = apply(receipt(
+            b"delete",
+            alice_account(),
+            Action::DeleteAccount(DeleteAccountAction { beneficiary_id: bob_account() }),
+        ));
+        assert!(get_account(&state, &alice_account()).unwrap().is_none());
+        assert_eq!(stats.other_burnt_amount, nonrefundable);
+    }
+
+    fn generate_receipts(small_transfer: u128, n: u64) -> Vec<Receipt> {
+        let mut receipt_id = CryptoHash::default();
+        (0..n)
+            .map(|i| {
+                receipt_id = hash(receipt_id.as_ref());
+                Receipt {
+                    predecessor_id: bob_account(),
+                    receiver_id: alice_account(),
+                    receipt_id,
+                    receipt: ReceiptEnum::Action(ActionReceipt {
+                        signer_id: bob_account(),
+                        signer_public_key: PublicKey::empty(KeyType::ED25519),
+                        gas_price: GAS_PRICE,
+                        output_data_receivers: vec![],
+                        input_data_ids: vec![],
+                        actions: vec![Action::Transfer(TransferAction {
+                            deposit: small_transfer + Balance::from(i),
+                        })],
+                        priority_fee: None,
+                        depth: None,
+                    }),
+                }
+            })
+            .collect()
     }
 
     fn generate_refund_receipts(small_transfer: u128, n: u64) -> Vec<Receipt> {
@@ -2198,6 +2790,8 @@ mod tests {
                 output_data_receivers: vec![],
                 input_data_ids: vec![],
                 actions,
+                priority_fee: None,
+                depth: None,
             }),
         }];
         let total_receipt_cost = Balance::from(gas + expected_gas_burnt) * gas_price;
@@ -2268,6 +2862,8 @@ mod tests {
                 output_data_receivers: vec![],
                 input_data_ids: vec![],
                 actions,
+                priority_fee: None,
+                depth: None,
             }),
         }];
         let total_receipt_cost = Balance::from(gas + expected_gas_burnt) * gas_price;
@@ -2416,4 +3012,793 @@ mod tests {
             .expect("Compiled contract should be cached")
             .expect("Compilation result should be non-empty");
     }
+
+    #[cfg(feature = "protocol_feature_per_receipt_storage_proof_limit")]
+    #[test]
+    fn test_storage_proof_size_limit() {
+        let (runtime, tries, root, mut apply_state, signer, epoch_info_provider) =
+            setup_runtime(to_yocto(1_000_000), 0, 10u64.pow(15));
+        let shard_uid = ShardUId::single_shard();
+        let strings_call = |method_name: &str, to: u64| {
+            Action::FunctionCall(FunctionCallAction {
+                method_name: method_name.to_string(),
+                args: [0u64.to_le_bytes(), to.to_le_bytes()].concat(),
+                gas: 100 * 10u64.pow(12),
+                deposit: 0,
+            })
+        };
+        let apply_receipts = |trie: Trie, root, apply_state: &ApplyState, receipts: &[Receipt]| {
+            runtime
+                .apply(trie, root, &None, apply_state, receipts, &[], &epoch_info_provider, None)
+                .unwrap()
+        };
+        let apply = |trie: Trie, root, apply_state: &ApplyState, actions: Vec<Action>| {
+            let receipts = create_receipts_with_actions(alice_account(), signer.clone(), actions);
+            apply_receipts(trie, root, apply_state, &receipts)
+        };
+
+        // Deploys the contract and writes keys `b`, `ab`, ..., `a..ab` for it to read later.
+        let actions = vec![
+            Action::DeployContract(DeployContractAction {
+                code: near_test_contracts::rs_contract().to_vec(),
+            }),
+            strings_call("insert_strings", 200),
+        ];
+        let apply_result = apply(tries.get_trie_for_shard(shard_uid), root, &apply_state, actions);
+        assert_eq!(apply_result.receipt_proof_sizes.len(), 1);
+        let (store_update, root) = tries.apply_all(&apply_result.trie_changes, shard_uid).unwrap();
+        store_update.commit().unwrap();
+
+        // Sizes are reported per receipt: a second receipt reading the same keys adds less to
+        // the proof than the first one.
+        let receipt = create_receipts_with_actions(
+            alice_account(),
+            signer.clone(),
+            vec![strings_call("delete_strings", 1)],
+        )
+        .pop()
+        .unwrap();
+        let receipts = vec![receipt.clone(), Receipt { receipt_id: hash(&[1]), ..receipt }];
+        let trie = tries.get_trie_for_shard(shard_uid).recording_reads();
+        let apply_result = apply_receipts(trie, root, &apply_state, &receipts);
+        let sizes: Vec<_> =
+            apply_result.receipt_proof_sizes.iter().map(|(_, size)| *size).collect();
+        assert_eq!(sizes.len(), 2);
+        assert!(sizes[1] < sizes[0]);
+        // The proof size doesn't depend on whether the proof is recorded.
+        let apply_result =
+            apply_receipts(tries.get_trie_for_shard(shard_uid), root, &apply_state, &receipts);
+        assert_eq!(
+            apply_result.receipt_proof_sizes.iter().map(|(_, size)| *size).collect::<Vec<_>>(),
+            sizes
+        );
+
+        // A receipt touching a single key fits into a limit slightly above its proof size.
+        let limit = sizes[0] + 1000;
+        let mut config = RuntimeConfig::test();
+        config.wasm_config.limit_config.per_receipt_storage_proof_size_limit = Some(limit);
+        apply_state.config = Arc::new(config);
+        let apply_result = apply(
+            tries.get_trie_for_shard(shard_uid).recording_reads(),
+            root,
+            &apply_state,
+            vec![strings_call("delete_strings", 1)],
+        );
+        assert!(matches!(
+            apply_result.outcomes[0].outcome.status,
+            ExecutionStatus::SuccessValue(_)
+        ));
+
+        // A receipt touching the whole key range doesn't.
+        let actions = vec![strings_call("delete_strings", 200)];
+        let trie = tries.get_trie_for_shard(shard_uid).recording_reads();
+        let apply_result = apply(trie, root, &apply_state, actions.clone());
+        let (receipt_id, size) = apply_result.receipt_proof_sizes[0];
+        assert_eq!(receipt_id, apply_result.outcomes[0].id);
+        assert!(size > limit);
+        assert_eq!(
+            apply_result.outcomes[0].outcome.status,
+            ExecutionStatus::Failure(TxExecutionError::ActionError(
+                ActionErrorKind::StorageProofSizeExceeded { size, limit }.into()
+            ))
+        );
+
+        // Re-executing the receipt from the recorded proof or without recording the proof fails
+        // it the same way.
+        let trie = Trie::from_recorded_storage(apply_result.proof.clone().unwrap());
+        let replay_result = apply(trie, root, &apply_state, actions.clone());
+        assert_eq!(replay_result.state_root, apply_result.state_root);
+        assert_eq!(replay_result.outcomes, apply_result.outcomes);
+        assert_eq!(replay_result.receipt_proof_sizes, apply_result.receipt_proof_sizes);
+        let trie = tries.get_trie_for_shard(shard_uid);
+        let unrecorded_result = apply(trie, root, &apply_state, actions);
+        assert_eq!(unrecorded_result.state_root, apply_result.state_root);
+        assert_eq!(unrecorded_result.outcomes, apply_result.outcomes);
+    }
+
+    #[test]
+    #[cfg(feature = "protocol_feature_per_receipt_trie_nodes_memory_limit")]
+    fn test_trie_nodes_memory_limit() {
+        use near_primitives::runtime::config_store::RuntimeConfigStore;
+
+        let (runtime, tries, root, mut apply_state, signer, epoch_info_provider) =
+            setup_runtime(to_yocto(1_000_000), 0, 10u64.pow(15));
+        let shard_uid = ShardUId::single_shard();
+        let strings_call = |method_name: &str, to: u64| {
+            Action::FunctionCall(FunctionCallAction {
+                method_name: method_name.to_string(),
+                args: [0u64.to_le_bytes(), to.to_le_bytes()].concat(),
+                gas: 100 * 10u64.pow(12),
+                deposit: 0,
+            })
+        };
+        let apply = |root, apply_state: &ApplyState, actions: Vec<Action>| {
+            let receipts = create_receipts_with_actions(alice_account(), signer.clone(), actions);
+            runtime
+                .apply(
+                    tries.get_trie_for_shard(shard_uid),
+                    root,
+                    &None,
+                    apply_state,
+                    &receipts,
+                    &[],
+                    &epoch_info_provider,
+                    None,
+                )
+                .unwrap()
+        };
+
+        // The runtime configs set the limit from the protocol version enabling it.
+        let store = RuntimeConfigStore::new(None);
+        assert!(store
+            .get_config(ProtocolFeature::PerReceiptTrieNodesMemoryLimit.protocol_version())
+            .wasm_config
+            .limit_config
+            .per_receipt_trie_nodes_memory_limit
+            .is_some());
+        assert_eq!(
+            store
+                .get_config(ProtocolFeature::PerReceiptTrieNodesMemoryLimit.protocol_version() - 1)
+                .wasm_config
+                .limit_config
+                .per_receipt_trie_nodes_memory_limit,
+            None
+        );
+
+        let actions = vec![
+            Action::DeployContract(DeployContractAction {
+                code: near_test_contracts::rs_contract().to_vec(),
+            }),
+            strings_call("insert_strings", 200),
+        ];
+        let apply_result = apply(root, &apply_state, actions);
+        let (store_update, root) = tries.apply_all(&apply_result.trie_changes, shard_uid).unwrap();
+        store_update.commit().unwrap();
+
+        // A receipt loading the whole key range fails with a limit of a few nodes, and its
+        // changes are reverted.
+        let limit = 1000;
+        let mut config = RuntimeConfig::test();
+        config.wasm_config.limit_config.per_receipt_trie_nodes_memory_limit = Some(limit);
+        apply_state.config = Arc::new(config);
+        apply_state.current_protocol_version =
+            ProtocolFeature::PerReceiptTrieNodesMemoryLimit.protocol_version();
+        let apply_result = apply(root, &apply_state, vec![strings_call("delete_strings", 200)]);
+        match &apply_result.outcomes[0].outcome.status {
+            ExecutionStatus::Failure(TxExecutionError::ActionError(ActionError {
+                kind: ActionErrorKind::TrieNodesMemoryLimitExceeded { size, limit: error_limit },
+                ..
+            })) => {
+                assert!(*size > limit);
+                assert_eq!(*error_limit, limit);
+            }
+            other => panic!("unexpected status {:?}", other),
+        }
+
+        // The same receipt succeeds before the protocol version enabling the limit.
+        apply_state.current_protocol_version -= 1;
+        let apply_result = apply(root, &apply_state, vec![strings_call("delete_strings", 200)]);
+        assert!(matches!(
+            apply_result.outcomes[0].outcome.status,
+            ExecutionStatus::SuccessValue(_)
+        ));
+    }
+
+    #[test]
+    fn test_random_seed_per_receipt() {
+        let (runtime, tries, root, apply_state, signer, epoch_info_provider) =
+            setup_runtime(to_yocto(1_000_000), 0, 10u64.pow(15));
+        let shard_uid = ShardUId::single_shard();
+        let actions = vec![Action::DeployContract(DeployContractAction {
+            code: near_test_contracts::rs_contract().to_vec(),
+        })];
+        let receipts = create_receipts_with_actions(alice_account(), signer.clone(), actions);
+        let apply_result = runtime
+            .apply(
+                tries.get_trie_for_shard(shard_uid),
+                root,
+                &None,
+                &apply_state,
+                &receipts,
+                &[],
+                &epoch_info_provider,
+                None,
+            )
+            .unwrap();
+        let (store_update, root) = tries.apply_all(&apply_result.trie_changes, shard_uid).unwrap();
+        store_update.commit().unwrap();
+
+        // Two receipts in the same block calling a method which returns its random seed.
+        let receipts: Vec<Receipt> = (0..2u8)
+            .map(|i| {
+                let actions = vec![Action::FunctionCall(FunctionCallAction {
+                    method_name: "ext_random_seed".to_string(),
+                    args: vec![],
+                    gas: 100 * 10u64.pow(12),
+                    deposit: 0,
+                })];
+                let mut receipt =
+                    create_receipts_with_actions(alice_account(), signer.clone(), actions)
+                        .remove(0);
+                receipt.receipt_id = hash(&[i]);
+                receipt
+            })
+            .collect();
+        let apply_seeds = |apply_state: &ApplyState| -> Vec<Vec<u8>> {
+            let apply_result = runtime
+                .apply(
+                    tries.get_trie_for_shard(shard_uid),
+                    root,
+                    &None,
+                    apply_state,
+                    &receipts,
+                    &[],
+                    &epoch_info_provider,
+                    None,
+                )
+                .unwrap();
+            apply_result
+                .outcomes
+                .into_iter()
+                .map(|outcome| match outcome.outcome.status {
+                    ExecutionStatus::SuccessValue(seed) => seed,
+                    status => panic!("unexpected status: {:?}", status),
+                })
+                .collect()
+        };
+
+        let seeds = apply_seeds(&apply_state);
+        assert_eq!(seeds.len(), 2);
+        assert_ne!(seeds[0], seeds[1]);
+        // Re-executing the same receipts yields the same seeds.
+        assert_eq!(apply_seeds(&apply_state), seeds);
+
+        #[cfg(feature = "protocol_feature_receipt_random_seed")]
+        {
+            use near_primitives::utils::create_random_seed;
+            use near_primitives::version::ProtocolFeature;
+
+            let expected_seed = |protocol_version, receipt: &Receipt| {
+                let action_hash = create_action_hash(
+                    protocol_version,
+                    receipt,
+                    &apply_state.prev_block_hash,
+                    &apply_state.block_hash,
+                    0,
+                );
+                create_random_seed(
+                    protocol_version,
+                    action_hash,
+                    receipt.receipt_id,
+                    apply_state.random_seed,
+                )
+            };
+            let new_seeds: Vec<_> =
+                receipts.iter().map(|receipt| expected_seed(PROTOCOL_VERSION, receipt)).collect();
+            assert_eq!(seeds, new_seeds);
+
+            // The seeds derived from the action hash are kept below the activation version.
+            let old_version = ProtocolFeature::ReceiptRandomSeed.protocol_version() - 1;
+            let old_seeds: Vec<_> =
+                receipts.iter().map(|receipt| expected_seed(old_version, receipt)).collect();
+            assert_ne!(old_seeds, new_seeds);
+            let apply_state = ApplyState { current_protocol_version: old_version, ..apply_state };
+            assert_eq!(apply_seeds(&apply_state), old_seeds);
+        }
+    }
+
+    #[test]
+    fn test_storage_key_length_limit_checked_by_vm_logic() {
+        let (runtime, tries, root, mut apply_state, signer, epoch_info_provider) =
+            setup_runtime(to_yocto(1_000_000), 0, 10u64.pow(15));
+        let limit = 100;
+        let mut config = RuntimeConfig::test();
+        config.wasm_config.limit_config.max_length_storage_key = limit;
+        apply_state.config = Arc::new(config);
+
+        // `insert_strings` with the range `0..limit` starts with a key of `limit + 1` bytes.
+        let actions = vec![
+            Action::DeployContract(DeployContractAction {
+                code: near_test_contracts::rs_contract().to_vec(),
+            }),
+            Action::FunctionCall(FunctionCallAction {
+                method_name: "insert_strings".to_string(),
+                args: [0u64.to_le_bytes(), limit.to_le_bytes()].concat(),
+                gas: 100 * 10u64.pow(12),
+                deposit: 0,
+            }),
+        ];
+        let receipts = create_receipts_with_actions(alice_account(), signer, actions);
+        let apply_result = runtime
+            .apply(
+                tries.get_trie_for_shard(ShardUId::single_shard()),
+                root,
+                &None,
+                &apply_state,
+                &receipts,
+                &[],
+                &epoch_info_provider,
+                None,
+            )
+            .expect("the key length error of RuntimeExt must not be reached");
+        let host_error = HostError::KeyLengthExceeded { length: limit + 1, limit };
+        assert_eq!(
+            apply_result.outcomes[0].outcome.status,
+            ExecutionStatus::Failure(TxExecutionError::ActionError(ActionError {
+                index: Some(1),
+                kind: ActionErrorKind::FunctionCallError(
+                    ContractCallError::ExecutionError {
+                        msg: FunctionCallError::HostError(host_error).to_string()
+                    }
+                    .into()
+                ),
+            }))
+        );
+    }
+
+    #[cfg(feature = "sandbox")]
+    #[test]
+    fn test_state_patch_with_too_long_data_key() {
+        let (runtime, tries, root, mut apply_state, _, epoch_info_provider) =
+            setup_runtime(to_yocto(1_000_000), 0, 10u64.pow(15));
+        let limit = 100;
+        let mut config = RuntimeConfig::test();
+        config.wasm_config.limit_config.max_length_storage_key = limit;
+        apply_state.config = Arc::new(config);
+
+        let apply = |data_key: Vec<u8>| {
+            runtime.apply(
+                tries.get_trie_for_shard(ShardUId::single_shard()),
+                root,
+                &None,
+                &apply_state,
+                &[],
+                &[],
+                &epoch_info_provider,
+                Some(vec![StateRecord::Data {
+                    account_id: alice_account(),
+                    data_key,
+                    value: b"value".to_vec(),
+                }]),
+            )
+        };
+        apply(vec![0; limit as usize]).expect("a key at the limit can be patched");
+        match apply(vec![0; limit as usize + 1]) {
+            Err(RuntimeError::InvalidStatePatch(_)) => {}
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[cfg(feature = "sandbox")]
+    #[test]
+    fn test_state_patch_access_key_of_missing_account() {
+        let (runtime, tries, root, apply_state, _, epoch_info_provider) =
+            setup_runtime(to_yocto(1_000_000), 0, 10u64.pow(15));
+        let public_key = PublicKey::from_seed(KeyType::ED25519, "patched");
+        let result = runtime.apply(
+            tries.get_trie_for_shard(ShardUId::single_shard()),
+            root,
+            &None,
+            &apply_state,
+            &[],
+            &[],
+            &epoch_info_provider,
+            Some(vec![StateRecord::AccessKey {
+                account_id: "missing.near".parse().unwrap(),
+                public_key,
+                access_key: AccessKey::full_access(),
+            }]),
+        );
+        match result {
+            Err(RuntimeError::InvalidStatePatch(_)) => {}
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[cfg(feature = "protocol_feature_storage_config_host_functions")]
+    #[test]
+    fn test_storage_config_host_functions() {
+        let (runtime, tries, root, apply_state, signer, epoch_info_provider) =
+            setup_runtime(to_yocto(1_000_000), 0, 10u64.pow(15));
+        let shard_uid = ShardUId::single_shard();
+        let actions = vec![Action::DeployContract(DeployContractAction {
+            code: near_test_contracts::storage_config_contract().to_vec(),
+        })];
+        let receipts = create_receipts_with_actions(alice_account(), signer.clone(), actions);
+        let apply_result = runtime
+            .apply(
+                tries.get_trie_for_shard(shard_uid),
+                root,
+                &None,
+                &apply_state,
+                &receipts,
+                &[],
+                &epoch_info_provider,
+                None,
+            )
+            .unwrap();
+        let (store_update, root) = tries.apply_all(&apply_result.trie_changes, shard_uid).unwrap();
+        store_update.commit().unwrap();
+        let storage_usage = get_account(&tries.new_trie_update(shard_uid, root), &alice_account())
+            .unwrap()
+            .unwrap()
+            .storage_usage();
+
+        let actions = vec![Action::FunctionCall(FunctionCallAction {
+            method_name: "storage_config".to_string(),
+            args: vec![],
+            gas: 100 * 10u64.pow(12),
+            deposit: 0,
+        })];
+        let receipts = create_receipts_with_actions(alice_account(), signer, actions);
+        let storage_config = |apply_state: &ApplyState| {
+            let apply_result = runtime
+                .apply(
+                    tries.get_trie_for_shard(shard_uid),
+                    root,
+                    &None,
+                    apply_state,
+                    &receipts,
+                    &[],
+                    &epoch_info_provider,
+                    None,
+                )
+                .unwrap();
+            apply_result.outcomes[0].outcome.status.clone()
+        };
+        let expected = |storage_amount_per_byte: Balance| {
+            let min_balance = Balance::from(storage_usage) * storage_amount_per_byte;
+            ExecutionStatus::SuccessValue(
+                [storage_amount_per_byte.to_le_bytes(), min_balance.to_le_bytes()].concat(),
+            )
+        };
+        let storage_amount_per_byte = apply_state.config.storage_amount_per_byte;
+        assert_eq!(storage_config(&apply_state), expected(storage_amount_per_byte));
+
+        // A protocol upgrade changing the storage cost comes with a new config in `ApplyState`.
+        let mut config = RuntimeConfig::test();
+        config.storage_amount_per_byte = storage_amount_per_byte / 10;
+        let apply_state = ApplyState { config: Arc::new(config), ..apply_state };
+        assert_eq!(storage_config(&apply_state), expected(storage_amount_per_byte / 10));
+    }
+
+    #[cfg(feature = "protocol_feature_gas_budget_host_functions")]
+    #[test]
+    fn test_attach_remaining_gas() {
+        let (runtime, tries, mut root, apply_state, signer, epoch_info_provider) =
+            setup_runtime(to_yocto(1_000_000), 0, 10u64.pow(15));
+        let shard_uid = ShardUId::single_shard();
+        let mut apply = |receipts: &[Receipt]| {
+            let apply_result = runtime
+                .apply(
+                    tries.get_trie_for_shard(shard_uid),
+                    root,
+                    &None,
+                    &apply_state,
+                    receipts,
+                    &[],
+                    &epoch_info_provider,
+                    None,
+                )
+                .unwrap();
+            let (store_update, new_root) =
+                tries.apply_all(&apply_result.trie_changes, shard_uid).unwrap();
+            store_update.commit().unwrap();
+            root = new_root;
+            apply_result
+        };
+        let actions = vec![Action::DeployContract(DeployContractAction {
+            code: near_test_contracts::gas_budget_contract().to_vec(),
+        })];
+        apply(&create_receipts_with_actions(alice_account(), signer.clone(), actions));
+
+        let prepaid_gas = 100 * 10u64.pow(12);
+        let actions = vec![Action::FunctionCall(FunctionCallAction {
+            method_name: "schedule_callback".to_string(),
+            args: vec![],
+            gas: prepaid_gas,
+            deposit: 0,
+        })];
+        let apply_result = apply(&create_receipts_with_actions(alice_account(), signer, actions));
+        let outcome = &apply_result.outcomes[0].outcome;
+        assert_eq!(outcome.status, ExecutionStatus::SuccessValue(vec![]));
+        let callback = apply_result
+            .outgoing_receipts
+            .iter()
+            .find(|receipt| match &receipt.receipt {
+                ReceiptEnum::Action(action_receipt) => {
+                    matches!(&action_receipt.actions[..], [Action::FunctionCall(_)])
+                }
+                _ => false,
+            })
+            .expect("the callback should be scheduled")
+            .clone();
+        let callback_gas = match &callback.receipt {
+            ReceiptEnum::Action(ActionReceipt { actions, .. }) => match &actions[0] {
+                Action::FunctionCall(function_call) => function_call.gas,
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        // Nearly all the gas went to the callback, and the call didn't run out of gas paying for
+        // it since the fees weren't underestimated.
+        assert!(callback_gas > prepaid_gas * 9 / 10);
+
+        let apply_result = apply(&[callback]);
+        assert_eq!(apply_result.outcomes[0].outcome.status, ExecutionStatus::SuccessValue(vec![]));
+    }
+
+    #[cfg(feature = "protocol_feature_receipt_depth")]
+    #[test]
+    fn test_receipt_depth_limit() {
+        let (runtime, tries, mut root, mut apply_state, signer, epoch_info_provider) =
+            setup_runtime(to_yocto(1_000_000), 0, 10u64.pow(15));
+        let mut config = RuntimeConfig::test();
+        config.wasm_config.limit_config.max_receipt_depth = Some(3);
+        apply_state.config = Arc::new(config);
+        let shard_uid = ShardUId::single_shard();
+        let mut apply = |receipts: &[Receipt]| {
+            let apply_result = runtime
+                .apply(
+                    tries.get_trie_for_shard(shard_uid),
+                    root,
+                    &None,
+                    &apply_state,
+                    receipts,
+                    &[],
+                    &epoch_info_provider,
+                    None,
+                )
+                .unwrap();
+            let (store_update, new_root) =
+                tries.apply_all(&apply_result.trie_changes, shard_uid).unwrap();
+            store_update.commit().unwrap();
+            root = new_root;
+            apply_result
+        };
+        let actions = vec![Action::DeployContract(DeployContractAction {
+            code: near_test_contracts::receipt_depth_contract().to_vec(),
+        })];
+        apply(&create_receipts_with_actions(alice_account(), signer.clone(), actions));
+
+        let actions = vec![Action::FunctionCall(FunctionCallAction {
+            method_name: "recurse".to_string(),
+            args: vec![],
+            gas: 200 * 10u64.pow(12),
+            deposit: 0,
+        })];
+        let mut receipts = create_receipts_with_actions(alice_account(), signer, actions);
+        for depth in 0..3u32 {
+            let apply_result = apply(&receipts);
+            assert_eq!(
+                apply_result.outcomes[0].outcome.status,
+                ExecutionStatus::SuccessValue(u64::from(depth).to_le_bytes().to_vec())
+            );
+            // The call to itself is the only receipt which isn't a refund.
+            receipts = apply_result
+                .outgoing_receipts
+                .into_iter()
+                .filter(|receipt| !receipt.predecessor_id.is_system())
+                .collect();
+            assert_eq!(receipts.len(), 1);
+            match &receipts[0].receipt {
+                ReceiptEnum::Action(action_receipt) => {
+                    assert_eq!(action_receipt.depth, Some(depth + 1))
+                }
+                ReceiptEnum::Data(_) => panic!("expected an action receipt"),
+            }
+        }
+
+        // The receipt of depth 3 may not call itself anymore, its execution fails and the
+        // balance checker of `apply` accepts the refunds.
+        let apply_result = apply(&receipts);
+        let host_error = HostError::ReceiptDepthExceeded { depth: 4, limit: 3 };
+        assert_eq!(
+            apply_result.outcomes[0].outcome.status,
+            ExecutionStatus::Failure(TxExecutionError::ActionError(ActionError {
+                index: Some(0),
+                kind: ActionErrorKind::FunctionCallError(
+                    ContractCallError::ExecutionError {
+                        msg: FunctionCallError::HostError(host_error).to_string()
+                    }
+                    .into()
+                ),
+            }))
+        );
+        assert!(apply_result
+            .outgoing_receipts
+            .iter()
+            .all(|receipt| receipt.predecessor_id.is_system()));
+    }
+
+    #[cfg(feature = "protocol_feature_gas_price_host_functions")]
+    #[test]
+    fn test_gas_price_host_functions() {
+        let (runtime, tries, root, apply_state, signer, epoch_info_provider) =
+            setup_runtime(to_yocto(1_000_000), 0, 10u64.pow(15));
+        let actions = vec![
+            Action::DeployContract(DeployContractAction {
+                code: near_test_contracts::gas_price_contract().to_vec(),
+            }),
+            Action::FunctionCall(FunctionCallAction {
+                method_name: "gas_prices".to_string(),
+                args: vec![],
+                gas: 100 * 10u64.pow(12),
+                deposit: 0,
+            }),
+        ];
+        // The receipt's gas was purchased at a pessimistic price above the one of the block.
+        let receipt_gas_price = 2 * GAS_PRICE;
+        let mut receipts = create_receipts_with_actions(alice_account(), signer, actions);
+        if let ReceiptEnum::Action(action_receipt) = &mut receipts[0].receipt {
+            action_receipt.gas_price = receipt_gas_price;
+        }
+        let apply_result = runtime
+            .apply(
+                tries.get_trie_for_shard(ShardUId::single_shard()),
+                root,
+                &None,
+                &apply_state,
+                &receipts,
+                &[],
+                &epoch_info_provider,
+                None,
+            )
+            .unwrap();
+        assert_eq!(
+            apply_result.outcomes[0].outcome.status,
+            ExecutionStatus::SuccessValue(
+                [receipt_gas_price.to_le_bytes(), apply_state.gas_price.to_le_bytes()].concat()
+            )
+        );
+    }
+
+    #[cfg(feature = "protocol_feature_promise_data_dependency")]
+    #[test]
+    fn test_promise_with_data_dependency() {
+        let (runtime, tries, mut root, apply_state, signer, epoch_info_provider) =
+            setup_runtime(to_yocto(1_000_000), 0, 10u64.pow(15));
+        let shard_uid = ShardUId::single_shard();
+        let apply = |root: &mut CryptoHash, receipts: Vec<Receipt>| {
+            let apply_result = runtime
+                .apply(
+                    tries.get_trie_for_shard(shard_uid),
+                    *root,
+                    &None,
+                    &apply_state,
+                    &receipts,
+                    &[],
+                    &epoch_info_provider,
+                    None,
+                )
+                .unwrap();
+            let (store_update, new_root) =
+                tries.apply_all(&apply_result.trie_changes, shard_uid).unwrap();
+            store_update.commit().unwrap();
+            *root = new_root;
+            apply_result
+        };
+        let call = |root: &mut CryptoHash, method_name: &str, args: Vec<u8>| {
+            let actions = vec![Action::FunctionCall(FunctionCallAction {
+                method_name: method_name.to_string(),
+                args,
+                gas: 100 * 10u64.pow(12),
+                deposit: 0,
+            })];
+            apply(root, create_receipts_with_actions(alice_account(), signer.clone(), actions))
+        };
+        let generate = |root: &mut CryptoHash| match call(root, "generate", vec![]).outcomes[0]
+            .outcome
+            .status
+            .clone()
+        {
+            ExecutionStatus::SuccessValue(data_id) => {
+                CryptoHash::try_from(data_id.as_slice()).unwrap()
+            }
+            status => panic!("unexpected status: {:?}", status),
+        };
+        let resume = |root: &mut CryptoHash, data_id: CryptoHash, data: &[u8]| {
+            call(root, "resume", [data_id.as_ref(), data].concat())
+        };
+        let assert_failed = |apply_result: &ApplyResult| {
+            assert!(matches!(apply_result.outcomes[0].outcome.status, ExecutionStatus::Failure(_)))
+        };
+        let get_result = |root: &CryptoHash| {
+            let key =
+                TrieKey::ContractData { account_id: alice_account(), key: b"result".to_vec() };
+            tries.new_trie_update(shard_uid, *root).get(&key).unwrap()
+        };
+        let get_registration = |root: &CryptoHash, data_id: CryptoHash| {
+            let key = TrieKey::RegisteredDataId { account_id: alice_account(), data_id };
+            tries.new_trie_update(shard_uid, *root).get(&key).unwrap()
+        };
+        let get_storage_usage = |root: &CryptoHash| {
+            get_account(&tries.new_trie_update(shard_uid, *root), &alice_account())
+                .unwrap()
+                .unwrap()
+                .storage_usage()
+        };
+
+        let actions = vec![Action::DeployContract(DeployContractAction {
+            code: near_test_contracts::data_dependency_contract().to_vec(),
+        })];
+        apply(&mut root, create_receipts_with_actions(alice_account(), signer.clone(), actions));
+        let initial_storage_usage = get_storage_usage(&root);
+        let registration_storage_usage =
+            apply_state.config.transaction_costs.storage_usage_config.num_extra_bytes_record
+                + std::mem::size_of::<CryptoHash>() as u64;
+
+        // Data arrives after the dependent receipt is created, which is postponed until then.
+        let data_id = generate(&mut root);
+        assert_eq!(get_registration(&root, data_id), Some(vec![]));
+        assert_eq!(get_storage_usage(&root), initial_storage_usage + registration_storage_usage);
+        let apply_result = call(&mut root, "create", data_id.as_ref().to_vec());
+        assert_eq!(
+            get_registration(&root, data_id),
+            Some(alice_account().as_ref().as_bytes().to_vec())
+        );
+        assert_eq!(
+            get_storage_usage(&root),
+            initial_storage_usage + registration_storage_usage + alice_account().len() as u64
+        );
+        assert_eq!(apply_result.outgoing_receipts.len(), 1);
+        let apply_result = apply(&mut root, apply_result.outgoing_receipts);
+        assert!(apply_result.outcomes.is_empty());
+        assert_eq!(get_result(&root), None);
+        let apply_result = resume(&mut root, data_id, b"after");
+        assert_eq!(get_registration(&root, data_id), None);
+        assert_eq!(get_storage_usage(&root), initial_storage_usage);
+        assert_eq!(apply_result.outgoing_receipts.len(), 1);
+        assert!(matches!(
+            &apply_result.outgoing_receipts[0].receipt,
+            ReceiptEnum::Data(DataReceipt { data_id: id, data: Some(data), .. })
+                if *id == data_id && data == b"after"
+        ));
+        apply(&mut root, apply_result.outgoing_receipts);
+        assert_eq!(get_result(&root), Some(b"after".to_vec()));
+
+        // Neither another receipt nor another data can use the data id.
+        assert_failed(&call(&mut root, "create", data_id.as_ref().to_vec()));
+        assert_failed(&resume(&mut root, data_id, b"again"));
+
+        // Data arrives before the dependent receipt.
+        let data_id = generate(&mut root);
+        let create_result = call(&mut root, "create", data_id.as_ref().to_vec());
+        let resume_result = resume(&mut root, data_id, b"before");
+        let apply_result = apply(&mut root, resume_result.outgoing_receipts);
+        assert!(apply_result.outcomes.is_empty());
+        assert_eq!(get_result(&root), Some(b"after".to_vec()));
+        apply(&mut root, create_result.outgoing_receipts);
+        assert_eq!(get_result(&root), Some(b"before".to_vec()));
+        assert_eq!(get_registration(&root, data_id), None);
+
+        // Data can't be sent before a receipt depends on the data id.
+        let data_id = generate(&mut root);
+        assert_failed(&resume(&mut root, data_id, b"early"));
+        assert_eq!(get_registration(&root, data_id), Some(vec![]));
+
+        // Data ids which weren't generated by the account are rejected.
+        assert_failed(&call(&mut root, "create", hash(b"foreign").as_ref().to_vec()));
+        assert_failed(&resume(&mut root, hash(b"foreign"), b"foreign"));
+    }
 }
@@ -65,6 +65,13 @@ pub static ACTION_DELETE_ACCOUNT_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub static ACTION_NONREFUNDABLE_STORAGE_TRANSFER_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    try_create_int_counter(
+        "near_action_nonrefundable_storage_transfer_total",
+        "The number of NonrefundableStorageTransfer actions called since starting this node",
+    )
+    .unwrap()
+});
 pub static TRANSACTION_PROCESSED_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
     try_create_int_counter(
         "near_transaction_processed_total",
@@ -0,0 +1,524 @@
+//! Scripted multi-block scenarios applied directly with [`Runtime::apply`].
+//!
+//! A [`Scenario`] declares the initial state of a single shard and a list of blocks, each with
+//! its own transactions, incoming receipts and protocol version.  The blocks are applied one
+//! after another on top of real tries backed by an in-memory store and a
+//! [`MockEpochInfoProvider`], without any chain around them.  The receipts a block sends out are
+//! received by the next block, so promise chains run to completion as long as the scenario has
+//! enough blocks.
+//!
+//! Scenarios only cover what the blocks of a chain feed to the runtime.  Tests which need
+//! validator account updates, state patches or a hand-crafted [`ApplyState`] call
+//! [`Runtime::apply`] themselves.
+//!
+//! Scenarios are serializable to JSON, so that a failing one can be attached to a bug report or
+//! saved by a fuzzer and replayed later with [`Scenario::from_file`].  Their blocks can also be
+//! recorded as fixtures of the runtime regression corpus with [`Scenario::record_fixtures`].
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use borsh::BorshSerialize;
+use serde::{Deserialize, Serialize};
+
+use near_crypto::{InMemorySigner, KeyType, Signer};
+use near_primitives::account::{AccessKey, Account};
+use near_primitives::errors::RuntimeError;
+use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::receipt::{DelayedReceiptIndices, Receipt};
+use near_primitives::runtime::apply_state::ApplyState;
+use near_primitives::runtime::config::RuntimeConfig;
+use near_primitives::runtime::config_store::RuntimeConfigStore;
+use near_primitives::runtime::migration_data::{MigrationData, MigrationFlags};
+use near_primitives::shard_layout::{ShardLayout, ShardUId};
+use near_primitives::state_record::StateRecord;
+use near_primitives::test_utils::MockEpochInfoProvider;
+use near_primitives::transaction::{Action, ExecutionOutcomeWithId, SignedTransaction};
+use near_primitives::types::{
+    AccountId, Balance, BlockHeight, EpochHeight, EpochId, Gas, Nonce, StateChangeCause, StateRoot,
+};
+use near_primitives::version::{ProtocolVersion, PROTOCOL_VERSION};
+use near_store::test_utils::create_tries;
+use near_store::{get_account, set_account, ShardTries, StoreCompiledContractCache, TrieUpdate};
+
+use crate::corpus::{ChunkFixture, ChunkFixtureV1};
+use crate::genesis::{GenesisStateApplier, StorageComputer};
+use crate::{ApplyResult, Runtime};
+
+fn epoch_id(epoch_height: EpochHeight) -> EpochId {
+    EpochId(hash(&epoch_height.try_to_vec().unwrap()))
+}
+
+fn block_timestamp(height: BlockHeight) -> u64 {
+    height * 1_000_000_000
+}
+
+fn default_gas_price() -> Balance {
+    100_000_000
+}
+
+fn default_protocol_version() -> ProtocolVersion {
+    PROTOCOL_VERSION
+}
+
+/// Initial state of a shard and the blocks applied on top of it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Scenario {
+    /// Records of the state before the first block, applied the same way as the genesis
+    /// records.  The storage usage of the accounts is computed from them, and the code hash of
+    /// an account is set from its contract record.
+    pub initial_state: Vec<StateRecord>,
+    /// Stakes reported by the epoch info provider.
+    #[serde(default)]
+    pub validators: Vec<(AccountId, Balance)>,
+    /// Config used for all the blocks.  If not set, each block uses the config of its protocol
+    /// version.
+    #[serde(default)]
+    pub runtime_config: Option<RuntimeConfig>,
+    #[serde(default = "default_gas_price")]
+    pub gas_price: Balance,
+    pub blocks: Vec<BlockConfig>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BlockConfig {
+    pub height: BlockHeight,
+    #[serde(default = "default_protocol_version")]
+    pub protocol_version: ProtocolVersion,
+    #[serde(default)]
+    pub epoch_height: EpochHeight,
+    #[serde(default)]
+    pub gas_limit: Option<Gas>,
+    #[serde(default)]
+    pub transactions: Vec<TransactionConfig>,
+    /// Receipts received by the block in addition to the ones sent by the previous block.
+    #[serde(default)]
+    pub receipts: Vec<Receipt>,
+}
+
+impl BlockConfig {
+    pub fn at_height(height: BlockHeight) -> Self {
+        Self {
+            height,
+            protocol_version: PROTOCOL_VERSION,
+            epoch_height: 0,
+            gas_limit: None,
+            transactions: vec![],
+            receipts: vec![],
+        }
+    }
+}
+
+/// Transaction which is signed when its block is applied, with the hash of the previous block.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TransactionConfig {
+    pub nonce: Nonce,
+    pub signer_id: AccountId,
+    pub receiver_id: AccountId,
+    pub signer: InMemorySigner,
+    pub actions: Vec<Action>,
+}
+
+impl TransactionConfig {
+    fn to_signed_transaction(&self, block_hash: CryptoHash) -> SignedTransaction {
+        SignedTransaction::from_actions(
+            self.nonce,
+            self.signer_id.clone(),
+            self.receiver_id.clone(),
+            &self.signer,
+            self.actions.clone(),
+            block_hash,
+        )
+    }
+}
+
+pub struct BlockResult {
+    pub height: BlockHeight,
+    pub block_hash: CryptoHash,
+    pub prev_block_hash: CryptoHash,
+    pub prev_state_root: StateRoot,
+    /// Receipts the block received, the ones sent by the previous block first.
+    pub receipts: Vec<Receipt>,
+    pub transactions: Vec<SignedTransaction>,
+    pub apply_result: ApplyResult,
+}
+
+pub struct ScenarioResult {
+    pub blocks: Vec<BlockResult>,
+    tries: ShardTries,
+}
+
+impl ScenarioResult {
+    /// State roots after each of the blocks.
+    pub fn state_roots(&self) -> Vec<StateRoot> {
+        self.blocks.iter().map(|block| block.apply_result.state_root).collect()
+    }
+
+    /// Outcomes of all the transactions and receipts, in the order they were executed.
+    pub fn outcomes(&self) -> impl Iterator<Item = &ExecutionOutcomeWithId> {
+        self.blocks.iter().flat_map(|block| block.apply_result.outcomes.iter())
+    }
+
+    /// State after the block with the given index in the scenario.
+    pub fn state_after(&self, index: usize) -> TrieUpdate {
+        let root = self.blocks[index].apply_result.state_root;
+        self.tries.new_trie_update(ShardUId::single_shard(), root)
+    }
+
+    /// State after the last block.
+    pub fn final_state(&self) -> TrieUpdate {
+        self.state_after(self.blocks.len() - 1)
+    }
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self {
+            initial_state: vec![],
+            validators: vec![],
+            runtime_config: None,
+            gas_price: default_gas_price(),
+            blocks: vec![],
+        }
+    }
+}
+
+impl Scenario {
+    pub fn from_file(path: &Path) -> io::Result<Scenario> {
+        serde_json::from_str::<Scenario>(&std::fs::read_to_string(path)?).map_err(io::Error::from)
+    }
+
+    pub fn to_file(&self, path: &Path) -> io::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    /// Adds an account with a full access key, returning the signer of the key.  The key is
+    /// derived from the account id, the same way as in the test genesis.
+    pub fn add_account(
+        &mut self,
+        account_id: AccountId,
+        amount: Balance,
+        locked: Balance,
+    ) -> InMemorySigner {
+        let signer =
+            InMemorySigner::from_seed(account_id.clone(), KeyType::ED25519, account_id.as_ref());
+        self.initial_state.push(StateRecord::Account {
+            account_id: account_id.clone(),
+            account: Account::new(amount, locked, CryptoHash::default(), 0),
+        });
+        self.initial_state.push(StateRecord::AccessKey {
+            account_id,
+            public_key: signer.public_key(),
+            access_key: AccessKey::full_access(),
+        });
+        signer
+    }
+
+    fn runtime_config(&self, protocol_version: ProtocolVersion) -> Arc<RuntimeConfig> {
+        match &self.runtime_config {
+            Some(config) => Arc::new(config.clone()),
+            None => RuntimeConfigStore::new(None).get_config(protocol_version).clone(),
+        }
+    }
+
+    fn apply_initial_state(&self, tries: &ShardTries) -> StateRoot {
+        let config = self.runtime_config(
+            self.blocks.first().map_or(PROTOCOL_VERSION, |block| block.protocol_version),
+        );
+        let mut state_update =
+            tries.new_trie_update(ShardUId::single_shard(), StateRoot::default());
+        let mut storage_computer = StorageComputer::new(&config);
+        storage_computer.process_records(&self.initial_state);
+        let mut delayed_receipts_indices = DelayedReceiptIndices::default();
+        let mut postponed_receipts = vec![];
+        for record in self.initial_state.iter().cloned() {
+            if let StateRecord::Contract { account_id, code } = &record {
+                let mut account =
+                    get_account(&state_update, account_id).expect("Failed to read state").expect(
+                        "Code state record should be preceded by the corresponding account record",
+                    );
+                account.set_code_hash(hash(code));
+                set_account(&mut state_update, account_id.clone(), &account);
+            }
+            GenesisStateApplier::apply_record(
+                &mut state_update,
+                &mut delayed_receipts_indices,
+                &mut postponed_receipts,
+                &config,
+                record,
+            );
+        }
+        for (account_id, storage_usage) in storage_computer.finalize() {
+            let mut account = get_account(&state_update, &account_id)
+                .expect("Failed to read state")
+                .unwrap_or_else(|| panic!("Records of missing account {}", account_id));
+            account.set_storage_usage(storage_usage);
+            set_account(&mut state_update, account_id, &account);
+        }
+        GenesisStateApplier::apply_postponed_receipts(&mut state_update, postponed_receipts);
+        GenesisStateApplier::save_delayed_receipts_indices(
+            &mut state_update,
+            &delayed_receipts_indices,
+        );
+        state_update.commit(StateChangeCause::InitialState);
+        let trie_changes = state_update.finalize().expect("Failed to finalize initial state").0;
+        let (store_update, root) = tries
+            .apply_all(&trie_changes, ShardUId::single_shard())
+            .expect("Failed to apply initial state");
+        store_update.commit().expect("Failed to commit initial state");
+        root
+    }
+
+    /// Applies the blocks one after another, stopping at the first one which fails.
+    pub fn run(&self) -> Result<ScenarioResult, RuntimeError> {
+        self.run_impl(false)
+    }
+
+    /// Records the blocks as fixtures of the runtime regression corpus of `chain_id`, see
+    /// [`crate::corpus`].  Fixtures are replayed with the config of their protocol version, so
+    /// the scenario must not set `runtime_config`.
+    pub fn record_fixtures(&self, chain_id: &str) -> Result<Vec<ChunkFixture>, RuntimeError> {
+        assert!(
+            self.runtime_config.is_none(),
+            "fixtures are replayed with the config of their protocol version"
+        );
+        let result = self.run_impl(true)?;
+        let fixtures = result
+            .blocks
+            .into_iter()
+            .zip(&self.blocks)
+            .map(|(block_result, block)| {
+                let apply_result = block_result.apply_result;
+                ChunkFixture::V1(ChunkFixtureV1 {
+                    chain_id: chain_id.to_string(),
+                    block_height: block.height,
+                    block_hash: block_result.block_hash,
+                    prev_block_hash: block_result.prev_block_hash,
+                    block_timestamp: block_timestamp(block.height),
+                    epoch_id: epoch_id(block.epoch_height),
+                    epoch_height: block.epoch_height,
+                    shard_id: 0,
+                    gas_price: self.gas_price,
+                    gas_limit: block.gas_limit,
+                    random_seed: block_result.block_hash,
+                    protocol_version: block.protocol_version,
+                    is_new_chunk: true,
+                    validators: self.validators.clone(),
+                    minimum_stake: 0,
+                    shard_layout: ShardLayout::v0_single_shard(),
+                    prev_state_root: block_result.prev_state_root,
+                    receipts: block_result.receipts,
+                    transactions: block_result.transactions,
+                    storage_proof: apply_result.proof.expect("reads are recorded").nodes,
+                    state_root: apply_result.state_root,
+                    outcomes: apply_result.outcomes,
+                    outgoing_receipts: apply_result.outgoing_receipts,
+                })
+            })
+            .collect();
+        Ok(fixtures)
+    }
+
+    fn run_impl(&self, record_reads: bool) -> Result<ScenarioResult, RuntimeError> {
+        let tries = create_tries();
+        let runtime = Runtime::new();
+        let epoch_info_provider = MockEpochInfoProvider::new(self.validators.iter().cloned());
+        let cache = Arc::new(StoreCompiledContractCache { store: tries.get_store() });
+
+        let mut root = self.apply_initial_state(&tries);
+        let mut prev_block_hash = CryptoHash::default();
+        let mut outgoing_receipts = vec![];
+        let mut blocks = Vec::with_capacity(self.blocks.len());
+        for block in &self.blocks {
+            let block_hash = hash(&block.height.try_to_vec().unwrap());
+            let apply_state = ApplyState {
+                block_index: block.height,
+                prev_block_hash,
+                block_hash,
+                epoch_id: epoch_id(block.epoch_height),
+                epoch_height: block.epoch_height,
+                shard_id: 0,
+                gas_price: self.gas_price,
+                block_timestamp: block_timestamp(block.height),
+                gas_limit: block.gas_limit,
+                compute_limit: None,
+                random_seed: block_hash,
+                current_protocol_version: block.protocol_version,
+                config: self.runtime_config(block.protocol_version),
+                // Fixtures are replayed without the cache.
+                cache: if record_reads { None } else { Some(cache.clone()) },
+                is_new_chunk: true,
+                migration_data: Arc::new(MigrationData::default()),
+                migration_flags: MigrationFlags::default(),
+            };
+            let mut receipts = std::mem::take(&mut outgoing_receipts);
+            receipts.extend(block.receipts.iter().cloned());
+            let transactions: Vec<_> = block
+                .transactions
+                .iter()
+                .map(|tx| tx.to_signed_transaction(prev_block_hash))
+                .collect();
+
+            let trie = tries.get_trie_for_shard(ShardUId::single_shard());
+            let trie = if record_reads { trie.recording_reads() } else { trie };
+            let apply_result = runtime.apply(
+                trie,
+                root,
+                &None,
+                &apply_state,
+                &receipts,
+                &transactions,
+                &epoch_info_provider,
+                None,
+            )?;
+            let (store_update, new_root) = tries
+                .apply_all(&apply_result.trie_changes, ShardUId::single_shard())
+                .expect("Failed to apply trie changes");
+            store_update.commit().expect("Failed to commit trie changes");
+            outgoing_receipts = apply_result.outgoing_receipts.clone();
+            blocks.push(BlockResult {
+                height: block.height,
+                block_hash,
+                prev_block_hash,
+                prev_state_root: root,
+                receipts,
+                transactions,
+                apply_result,
+            });
+            root = new_root;
+            prev_block_hash = block_hash;
+        }
+        Ok(ScenarioResult { blocks, tries })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_primitives::transaction::{
+        DeployContractAction, ExecutionStatus, FunctionCallAction, TransferAction,
+    };
+    use near_primitives::version::ProtocolFeature;
+
+    use super::*;
+
+    fn account(name: &str) -> AccountId {
+        name.parse().unwrap()
+    }
+
+    fn transfer_and_call_scenario() -> Scenario {
+        let mut scenario = Scenario::default();
+        let alice = scenario.add_account(account("alice"), 10u128.pow(30), 0);
+        scenario.add_account(account("bob"), 10u128.pow(30), 0);
+
+        let mut block = BlockConfig::at_height(1);
+        block.transactions.push(TransactionConfig {
+            nonce: 1,
+            signer_id: account("alice"),
+            receiver_id: account("alice"),
+            signer: alice.clone(),
+            actions: vec![Action::DeployContract(DeployContractAction {
+                code: near_test_contracts::rs_contract_base_protocol().to_vec(),
+            })],
+        });
+        block.transactions.push(TransactionConfig {
+            nonce: 2,
+            signer_id: account("alice"),
+            receiver_id: account("bob"),
+            signer: alice.clone(),
+            actions: vec![Action::Transfer(TransferAction { deposit: 100 })],
+        });
+        scenario.blocks.push(block);
+
+        let mut block = BlockConfig::at_height(2);
+        let args: Vec<u8> = [1u64, 2u64].iter().flat_map(|x| x.to_le_bytes()).collect();
+        block.transactions.push(TransactionConfig {
+            nonce: 3,
+            signer_id: account("alice"),
+            receiver_id: account("alice"),
+            signer: alice,
+            actions: vec![Action::FunctionCall(FunctionCallAction {
+                method_name: "write_key_value".to_string(),
+                args,
+                gas: 10u64.pow(14),
+                deposit: 0,
+            })],
+        });
+        scenario.blocks.push(block);
+        scenario.blocks.extend((3..6).map(BlockConfig::at_height));
+        scenario
+    }
+
+    #[test]
+    fn test_scenario_runs() {
+        let result = transfer_and_call_scenario().run().unwrap();
+        for outcome in result.outcomes() {
+            assert!(
+                !matches!(outcome.outcome.status, ExecutionStatus::Failure(_)),
+                "{:?}",
+                outcome
+            );
+        }
+        let bob = get_account(&result.final_state(), &account("bob")).unwrap().unwrap();
+        assert_eq!(bob.amount(), 10u128.pow(30) + 100);
+        let alice = get_account(&result.final_state(), &account("alice")).unwrap().unwrap();
+        assert_eq!(alice.code_hash(), hash(near_test_contracts::rs_contract_base_protocol()));
+        // The last blocks are left with nothing to do, the refunds included.
+        assert!(result.blocks[3].apply_result.outgoing_receipts.is_empty());
+    }
+
+    #[test]
+    fn test_scenario_json_roundtrip() {
+        let scenario = transfer_and_call_scenario();
+        let json = serde_json::to_string(&scenario).unwrap();
+        let replayed: Scenario = serde_json::from_str(&json).unwrap();
+        assert_eq!(scenario.run().unwrap().state_roots(), replayed.run().unwrap().state_roots());
+    }
+
+    /// Receipts of the initial state are executed by the first block.
+    #[test]
+    fn test_scenario_initial_delayed_receipt() {
+        let mut scenario = Scenario::default();
+        scenario.add_account(account("alice"), 10u128.pow(30), 0);
+        scenario.add_account(account("bob"), 10u128.pow(30), 0);
+        let receipt = Receipt::new_balance_refund(&account("bob"), 100);
+        scenario.initial_state.push(StateRecord::DelayedReceipt(Box::new(receipt.clone())));
+        scenario.blocks.push(BlockConfig::at_height(1));
+
+        let result = scenario.run().unwrap();
+        let outcome = result.outcomes().next().unwrap();
+        assert_eq!(outcome.id, receipt.receipt_id);
+        let bob = get_account(&result.final_state(), &account("bob")).unwrap().unwrap();
+        assert_eq!(bob.amount(), 10u128.pow(30) + 100);
+    }
+
+    /// The storage of a contract is affordable only after the storage cost has been lowered.
+    #[test]
+    fn test_scenario_protocol_upgrade() {
+        let code = near_test_contracts::rs_contract_base_protocol();
+        let new_version = ProtocolFeature::LowerStorageCost.protocol_version();
+        let mut scenario = Scenario::default();
+        let amount = code.len() as u128 * 3 * 10u128.pow(19);
+        let alice = scenario.add_account(account("alice"), amount, 0);
+        for (nonce, protocol_version) in [(1, new_version - 1), (2, new_version)] {
+            let mut block = BlockConfig::at_height(nonce);
+            block.protocol_version = protocol_version;
+            block.transactions.push(TransactionConfig {
+                nonce,
+                signer_id: account("alice"),
+                receiver_id: account("alice"),
+                signer: alice.clone(),
+                actions: vec![Action::DeployContract(DeployContractAction { code: code.to_vec() })],
+            });
+            scenario.blocks.push(block);
+        }
+
+        let result = scenario.run().unwrap();
+        let deploy_status = |index: usize| {
+            result.blocks[index].apply_result.outcomes.last().unwrap().outcome.status.clone()
+        };
+        assert!(matches!(deploy_status(0), ExecutionStatus::Failure(_)));
+        assert!(matches!(deploy_status(1), ExecutionStatus::SuccessValue(_)));
+        let alice = get_account(&result.final_state(), &account("alice")).unwrap().unwrap();
+        assert_eq!(alice.code_hash(), hash(code));
+    }
+}
@@ -1,7 +1,9 @@
 use crate::near_primitives::version::PROTOCOL_VERSION;
 use crate::{actions::execute_function_call, ext::RuntimeExt};
+use near_chain_configs::ViewRegisterLimits;
 use near_crypto::{KeyType, PublicKey};
 use near_primitives::runtime::config_store::RuntimeConfigStore;
+use near_primitives::runtime::fees::RuntimeFeesConfig;
 use near_primitives::{
     account::{AccessKey, Account},
     borsh::BorshDeserialize,
@@ -19,17 +21,30 @@ use near_primitives::{
     views::{StateItem, ViewApplyState, ViewStateResult},
 };
 use near_store::{get_access_key, get_account, get_code, TrieUpdate};
+use near_vm_errors::{FunctionCallError, HostError, VMError};
 use near_vm_logic::{ReturnData, ViewConfig};
 use std::{str, sync::Arc, time::Instant};
 use tracing::debug;
 
 pub mod errors;
 
+/// Result of a successful view function call.
+#[derive(Debug, PartialEq)]
+pub struct CallFunctionResult {
+    pub result: Vec<u8>,
+    /// Gas burnt by the execution, wasm and host function costs as measured.
+    pub gas_burnt: Gas,
+    /// Gas to attach to a transaction making the same call, see [`recommended_attached_gas`].
+    pub recommended_attached_gas: Gas,
+}
+
 pub struct TrieViewer {
     /// Upper bound of the byte size of contract state that is still viewable. None is no limit
     state_size_limit: Option<u64>,
     /// Gas limit used when when handling call_function queries.
     max_gas_burnt_view: Gas,
+    /// Register limits of this node used when handling call_function queries.
+    register_limits: ViewRegisterLimits,
 }
 
 impl Default for TrieViewer {
@@ -37,7 +52,11 @@ impl Default for TrieViewer {
         let config_store = RuntimeConfigStore::new(None);
         let latest_runtime_config = config_store.get_config(PROTOCOL_VERSION);
         let max_gas_burnt = latest_runtime_config.wasm_config.limit_config.max_gas_burnt;
-        Self { state_size_limit: None, max_gas_burnt_view: max_gas_burnt }
+        Self {
+            state_size_limit: None,
+            max_gas_burnt_view: max_gas_burnt,
+            register_limits: ViewRegisterLimits::default(),
+        }
     }
 }
 
@@ -45,7 +64,16 @@ impl TrieViewer {
     pub fn new(state_size_limit: Option<u64>, max_gas_burnt_view: Option<Gas>) -> Self {
         let max_gas_burnt_view =
             max_gas_burnt_view.unwrap_or_else(|| TrieViewer::default().max_gas_burnt_view);
-        Self { state_size_limit, max_gas_burnt_view }
+        Self {
+            state_size_limit,
+            max_gas_burnt_view,
+            register_limits: ViewRegisterLimits::default(),
+        }
+    }
+
+    /// Lowers the register limits of the protocol for call_function queries.
+    pub fn set_register_limits(&mut self, register_limits: ViewRegisterLimits) {
+        self.register_limits = register_limits;
     }
 
     pub fn view_account(
@@ -169,7 +197,7 @@ impl TrieViewer {
         args: &[u8],
         logs: &mut Vec<String>,
         epoch_info_provider: &dyn EpochInfoProvider,
-    ) -> Result<Vec<u8>, errors::CallFunctionError> {
+    ) -> Result<CallFunctionResult, errors::CallFunctionError> {
         let now = Instant::now();
         let root = state_update.get_root();
         let mut account = get_account(&state_update, contract_id)?.ok_or_else(|| {
@@ -181,21 +209,28 @@ impl TrieViewer {
         let originator_id = contract_id;
         let public_key = PublicKey::empty(KeyType::ED25519);
         let empty_hash = CryptoHash::default();
+        let config_store = RuntimeConfigStore::new(None);
+        let mut config = config_store.get_config(PROTOCOL_VERSION).clone();
+        if self.register_limits != ViewRegisterLimits::default() {
+            self.register_limits.restrict(&mut Arc::make_mut(&mut config).wasm_config.limit_config);
+        }
         let mut runtime_ext = RuntimeExt::new(
             &mut state_update,
             contract_id,
             originator_id,
             &public_key,
             0,
+            0,
+            config.storage_amount_per_byte,
             &empty_hash,
             &view_state.epoch_id,
+            view_state.shard_id,
             &view_state.prev_block_hash,
             &view_state.block_hash,
             epoch_info_provider,
             view_state.current_protocol_version,
+            &config.wasm_config.limit_config,
         );
-        let config_store = RuntimeConfigStore::new(None);
-        let config = config_store.get_config(PROTOCOL_VERSION);
         let apply_state = ApplyState {
             block_index: view_state.block_height,
             // Used for legacy reasons
@@ -203,9 +238,11 @@ impl TrieViewer {
             block_hash: view_state.block_hash,
             epoch_id: view_state.epoch_id.clone(),
             epoch_height: view_state.epoch_height,
+            shard_id: view_state.shard_id,
             gas_price: 0,
             block_timestamp: view_state.block_timestamp,
             gas_limit: None,
+            compute_limit: None,
             random_seed: root,
             current_protocol_version: view_state.current_protocol_version,
             config: config.clone(),
@@ -221,6 +258,8 @@ impl TrieViewer {
             output_data_receivers: vec![],
             input_data_ids: vec![],
             actions: vec![],
+            priority_fee: None,
+            depth: None,
         };
         let function_call = FunctionCallAction {
             method_name: method_name.to_string(),
@@ -237,7 +276,7 @@ impl TrieViewer {
             &[],
             &function_call,
             &empty_hash,
-            config,
+            &config,
             true,
             Some(ViewConfig { max_gas_burnt: self.max_gas_burnt_view }),
         );
@@ -250,7 +289,15 @@ impl TrieViewer {
             if let Some(outcome) = outcome {
                 logs.extend(outcome.logs);
             }
-            let message = format!("wasm execution failed with error: {:?}", err);
+            let mut message = format!("wasm execution failed with error: {:?}", err);
+            if let VMError::FunctionCallError(FunctionCallError::HostError(
+                HostError::ProhibitedInView { method_name },
+            )) = &err
+            {
+                if creates_promise(method_name) {
+                    message.push_str(", gas estimate unavailable for functions creating promises");
+                }
+            }
             debug!(target: "runtime", "(exec time {}) {}", time_str, message);
             Err(errors::CallFunctionError::VMError { error_message: message })
         } else {
@@ -261,7 +308,51 @@ impl TrieViewer {
                 ReturnData::Value(buf) => buf,
                 ReturnData::ReceiptIndex(_) | ReturnData::None => vec![],
             };
-            Ok(result)
+            Ok(CallFunctionResult {
+                result,
+                gas_burnt: outcome.burnt_gas,
+                recommended_attached_gas: recommended_attached_gas(
+                    &config.transaction_costs,
+                    method_name,
+                    args,
+                    outcome.burnt_gas,
+                ),
+            })
         }
     }
 }
+
+/// Gas to attach to a transaction calling `method_name` with `args` which burnt `gas_burnt` when
+/// executed as a view: the gas burnt plus the execution fees of the action receipt and of the
+/// function call action.
+///
+/// The estimate diverges from the gas the transaction actually burns if the state the function
+/// reads changes in between, as e.g. storage reads are paid per byte.  Functions creating
+/// promises can't be executed as views, so there is no estimate for them at all.
+pub fn recommended_attached_gas(
+    fees: &RuntimeFeesConfig,
+    method_name: &str,
+    args: &[u8],
+    gas_burnt: Gas,
+) -> Gas {
+    let num_bytes = (method_name.len() + args.len()) as u64;
+    let function_call = &fees.action_creation_config;
+    gas_burnt
+        .saturating_add(fees.action_receipt_creation_config.exec_fee())
+        .saturating_add(function_call.function_call_cost.exec_fee())
+        .saturating_add(
+            function_call.function_call_cost_per_byte.exec_fee().saturating_mul(num_bytes),
+        )
+}
+
+/// Whether the host function `name` creates a promise, which is prohibited in views.
+fn creates_promise(name: &str) -> bool {
+    matches!(
+        name,
+        "promise_create"
+            | "promise_then"
+            | "promise_and"
+            | "promise_create_with_data_dependency"
+            | "promise_resume_data"
+    ) || name.starts_with("promise_batch_")
+}
@@ -0,0 +1,76 @@
+use near_crypto::{KeyType, PublicKey};
+use near_primitives::config::VMLimitConfig;
+use near_primitives::hash::CryptoHash;
+use near_primitives::test_utils::MockEpochInfoProvider;
+use near_primitives::types::{AccountId, EpochId, EpochInfoProvider, ShardId};
+use near_primitives::version::{ProtocolVersion, PROTOCOL_VERSION};
+use near_store::TrieUpdate;
+
+use crate::ext::RuntimeExt;
+
+/// Everything a [`RuntimeExt`] borrows besides the trie update, for tests and benchmarks of the
+/// host functions.  The account is both the receiver and the signer, gas is free and all the
+/// hashes are the default one.
+pub struct TestExtContext<P: EpochInfoProvider = MockEpochInfoProvider> {
+    pub account_id: AccountId,
+    pub public_key: PublicKey,
+    pub hash: CryptoHash,
+    pub epoch_id: EpochId,
+    pub shard_id: ShardId,
+    pub epoch_info_provider: P,
+    pub protocol_version: ProtocolVersion,
+    pub limit_config: VMLimitConfig,
+}
+
+impl TestExtContext {
+    pub fn new(account_id: AccountId) -> Self {
+        Self {
+            account_id,
+            public_key: PublicKey::empty(KeyType::ED25519),
+            hash: CryptoHash::default(),
+            epoch_id: EpochId::default(),
+            shard_id: 0,
+            epoch_info_provider: MockEpochInfoProvider::default(),
+            protocol_version: PROTOCOL_VERSION,
+            limit_config: VMLimitConfig::test(),
+        }
+    }
+}
+
+impl<P: EpochInfoProvider> TestExtContext<P> {
+    pub fn with_epoch_info_provider<Q: EpochInfoProvider>(
+        self,
+        epoch_info_provider: Q,
+    ) -> TestExtContext<Q> {
+        TestExtContext {
+            account_id: self.account_id,
+            public_key: self.public_key,
+            hash: self.hash,
+            epoch_id: self.epoch_id,
+            shard_id: self.shard_id,
+            epoch_info_provider,
+            protocol_version: self.protocol_version,
+            limit_config: self.limit_config,
+        }
+    }
+
+    pub fn ext<'a>(&'a self, trie_update: &'a mut TrieUpdate) -> RuntimeExt<'a> {
+        RuntimeExt::new(
+            trie_update,
+            &self.account_id,
+            &self.account_id,
+            &self.public_key,
+            0,
+            0,
+            0,
+            &self.hash,
+            &self.epoch_id,
+            self.shard_id,
+            &self.hash,
+            &self.hash,
+            &self.epoch_info_provider,
+            self.protocol_version,
+            &self.limit_config,
+        )
+    }
+}
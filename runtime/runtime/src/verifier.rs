@@ -1,16 +1,17 @@
 use near_crypto::key_conversion::is_valid_staking_key;
+use near_crypto::{KeyType, PublicKey};
 use near_primitives::runtime::get_insufficient_storage_stake;
 use near_primitives::{
-    account::AccessKeyPermission,
+    account::{AccessKey, AccessKeyPermission},
     config::VMLimitConfig,
     errors::{
         ActionsValidationError, InvalidAccessKeyError, InvalidTxError, ReceiptValidationError,
         RuntimeError,
     },
-    receipt::{ActionReceipt, DataReceipt, Receipt, ReceiptEnum},
+    receipt::{transaction_receipt_depth, ActionReceipt, DataReceipt, Receipt, ReceiptEnum},
     transaction::{
         Action, AddKeyAction, DeployContractAction, FunctionCallAction, SignedTransaction,
-        StakeAction,
+        StakeAction, Transaction,
     },
     types::{AccountId, Balance},
     version::ProtocolVersion,
@@ -21,10 +22,36 @@ use near_store::{
 
 use crate::config::{total_prepaid_gas, tx_cost, TransactionCost};
 use crate::VerificationResult;
+use borsh::BorshSerialize;
 use near_primitives::checked_feature;
+use near_primitives::hash::CryptoHash;
 use near_primitives::runtime::config::RuntimeConfig;
 use near_primitives::types::BlockHeight;
 
+/// Returns the size of the borsh-serialized receipt `transaction` is converted to.  The receipt ID
+/// and the gas price have a fixed size, so they don't need to be known yet.
+fn transaction_receipt_size(
+    transaction: &Transaction,
+    current_protocol_version: ProtocolVersion,
+) -> u64 {
+    let receipt = Receipt {
+        predecessor_id: transaction.signer_id.clone(),
+        receiver_id: transaction.receiver_id.clone(),
+        receipt_id: CryptoHash::default(),
+        receipt: ReceiptEnum::Action(ActionReceipt {
+            signer_id: transaction.signer_id.clone(),
+            signer_public_key: transaction.public_key.clone(),
+            gas_price: 0,
+            output_data_receivers: vec![],
+            input_data_ids: vec![],
+            actions: transaction.actions.clone(),
+            priority_fee: transaction.priority_fee,
+            depth: transaction_receipt_depth(current_protocol_version),
+        }),
+    };
+    receipt.try_to_vec().expect("Failed to serialize").len() as u64
+}
+
 /// Validates the transaction without using the state. It allows any node to validate a
 /// transaction before forwarding it to the node that tracks the `signer_id` account.
 pub fn validate_transaction(
@@ -37,6 +64,13 @@ pub fn validate_transaction(
     let transaction = &signed_transaction.transaction;
     let signer_id = &transaction.signer_id;
 
+    if !is_key_type_enabled(transaction.public_key.key_type(), current_protocol_version) {
+        return Err(InvalidTxError::UnsupportedSignerKeyType {
+            public_key: transaction.public_key.clone(),
+        }
+        .into());
+    }
+
     if verify_signature
         && !signed_transaction
             .signature
@@ -55,8 +89,31 @@ pub fn validate_transaction(
         .into());
     }
 
-    validate_actions(&config.wasm_config.limit_config, &transaction.actions)
-        .map_err(InvalidTxError::ActionsValidation)?;
+    if transaction.priority_fee.is_some()
+        && !checked_feature!("protocol_feature_priority_fee", PriorityFee, current_protocol_version)
+    {
+        return Err(InvalidTxError::PriorityFeeNotSupported.into());
+    }
+
+    validate_actions(
+        &config.wasm_config.limit_config,
+        &transaction.actions,
+        current_protocol_version,
+    )
+    .map_err(InvalidTxError::ActionsValidation)?;
+
+    if checked_feature!(
+        "protocol_feature_receipt_size_limit",
+        ReceiptSizeLimit,
+        current_protocol_version
+    ) {
+        if let Some(limit) = config.wasm_config.limit_config.max_receipt_size {
+            let size = transaction_receipt_size(transaction, current_protocol_version);
+            if size > limit {
+                return Err(InvalidTxError::ReceiptSizeExceeded { size, limit }.into());
+            }
+        }
+    }
 
     let sender_is_receiver = &transaction.receiver_id == signer_id;
 
@@ -111,6 +168,16 @@ pub fn verify_and_charge_transaction(
         }
     };
 
+    if checked_feature!(
+        "protocol_feature_access_key_expiration",
+        AccessKeyExpiration,
+        current_protocol_version
+    ) {
+        if let Some(height) = block_height {
+            validate_access_key_expiration(signer_id, transaction, &access_key, height)?;
+        }
+    }
+
     if transaction.nonce <= access_key.nonce {
         return Err(InvalidTxError::InvalidNonce {
             tx_nonce: transaction.nonce,
@@ -221,10 +288,47 @@ pub fn verify_and_charge_transaction(
     Ok(VerificationResult { gas_burnt, gas_remaining, receipt_gas_price, burnt_amount })
 }
 
+/// Checks that the access key signing the transaction hasn't expired at `block_height`, and that
+/// the transaction doesn't add access keys which would already be expired.
+fn validate_access_key_expiration(
+    signer_id: &AccountId,
+    transaction: &Transaction,
+    access_key: &AccessKey,
+    block_height: BlockHeight,
+) -> Result<(), InvalidTxError> {
+    if let Some(valid_until_block_height) = access_key.valid_until_block_height {
+        if block_height > valid_until_block_height {
+            return Err(InvalidAccessKeyError::AccessKeyExpired {
+                account_id: signer_id.clone(),
+                public_key: transaction.public_key.clone(),
+                valid_until_block_height,
+                block_height,
+            }
+            .into());
+        }
+    }
+    for action in &transaction.actions {
+        if let Action::AddKey(add_key) = action {
+            if let Some(valid_until_block_height) = add_key.access_key.valid_until_block_height {
+                if block_height > valid_until_block_height {
+                    return Err(InvalidTxError::ActionsValidation(
+                        ActionsValidationError::AddKeyAlreadyExpired {
+                            valid_until_block_height,
+                            block_height,
+                        },
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Validates a given receipt. Checks validity of the Action or Data receipt.
 pub(crate) fn validate_receipt(
     limit_config: &VMLimitConfig,
     receipt: &Receipt,
+    current_protocol_version: ProtocolVersion,
 ) -> Result<(), ReceiptValidationError> {
     // We retain these checks here as to maintain backwards compatibility
     // with AccountId validation since we illegally parse an AccountId
@@ -240,7 +344,7 @@ pub(crate) fn validate_receipt(
 
     match &receipt.receipt {
         ReceiptEnum::Action(action_receipt) => {
-            validate_action_receipt(limit_config, action_receipt)
+            validate_action_receipt(limit_config, action_receipt, current_protocol_version)
         }
         ReceiptEnum::Data(data_receipt) => validate_data_receipt(limit_config, data_receipt),
     }
@@ -250,6 +354,7 @@ pub(crate) fn validate_receipt(
 fn validate_action_receipt(
     limit_config: &VMLimitConfig,
     receipt: &ActionReceipt,
+    current_protocol_version: ProtocolVersion,
 ) -> Result<(), ReceiptValidationError> {
     if receipt.input_data_ids.len() as u64 > limit_config.max_number_input_data_dependencies {
         return Err(ReceiptValidationError::NumberInputDataDependenciesExceeded {
@@ -257,7 +362,7 @@ fn validate_action_receipt(
             limit: limit_config.max_number_input_data_dependencies,
         });
     }
-    validate_actions(limit_config, &receipt.actions)
+    validate_actions(limit_config, &receipt.actions, current_protocol_version)
         .map_err(ReceiptValidationError::ActionsValidation)
 }
 
@@ -285,6 +390,7 @@ fn validate_data_receipt(
 pub(crate) fn validate_actions(
     limit_config: &VMLimitConfig,
     actions: &[Action],
+    current_protocol_version: ProtocolVersion,
 ) -> Result<(), ActionsValidationError> {
     if actions.len() as u64 > limit_config.max_actions_per_receipt {
         return Err(ActionsValidationError::TotalNumberOfActionsExceeded {
@@ -300,7 +406,7 @@ pub(crate) fn validate_actions(
                 return Err(ActionsValidationError::DeleteActionMustBeFinal);
             }
         }
-        validate_action(limit_config, action)?;
+        validate_action(limit_config, action, current_protocol_version)?;
     }
 
     let total_prepaid_gas =
@@ -319,21 +425,40 @@ pub(crate) fn validate_actions(
 pub fn validate_action(
     limit_config: &VMLimitConfig,
     action: &Action,
+    current_protocol_version: ProtocolVersion,
 ) -> Result<(), ActionsValidationError> {
     match action {
         Action::CreateAccount(_) => Ok(()),
         Action::DeployContract(a) => validate_deploy_contract_action(limit_config, a),
         Action::FunctionCall(a) => validate_function_call_action(limit_config, a),
         Action::Transfer(_) => Ok(()),
-        Action::Stake(a) => validate_stake_action(a),
+        Action::Stake(a) => validate_stake_action(a, current_protocol_version),
         #[cfg(feature = "protocol_feature_chunk_only_producers")]
-        Action::StakeChunkOnly(a) => validate_stake_action(a),
-        Action::AddKey(a) => validate_add_key_action(limit_config, a),
-        Action::DeleteKey(_) => Ok(()),
+        Action::StakeChunkOnly(a) => validate_stake_action(a, current_protocol_version),
+        Action::AddKey(a) => validate_add_key_action(limit_config, a, current_protocol_version),
+        Action::DeleteKey(a) => validate_key_type(&a.public_key, current_protocol_version),
         Action::DeleteAccount(_) => Ok(()),
+        Action::NonrefundableStorageTransfer(_) => {
+            validate_nonrefundable_storage_transfer_action(current_protocol_version)
+        }
     }
 }
 
+/// Validates `NonrefundableStorageTransferAction`. Checks that it's enabled by the protocol
+/// version.
+fn validate_nonrefundable_storage_transfer_action(
+    current_protocol_version: ProtocolVersion,
+) -> Result<(), ActionsValidationError> {
+    if !checked_feature!(
+        "protocol_feature_nonrefundable_storage_transfer",
+        NonrefundableStorageTransfer,
+        current_protocol_version
+    ) {
+        return Err(ActionsValidationError::NonrefundableStorageTransferNotSupported);
+    }
+    Ok(())
+}
+
 /// Validates `DeployContractAction`. Checks that the given contract size doesn't exceed the limit.
 fn validate_deploy_contract_action(
     limit_config: &VMLimitConfig,
@@ -376,8 +501,39 @@ fn validate_function_call_action(
     Ok(())
 }
 
-/// Validates `StakeAction`. Checks that the `public_key` is a valid staking key.
-fn validate_stake_action(action: &StakeAction) -> Result<(), ActionsValidationError> {
+/// Whether keys of the given type can sign transactions and be used in actions.
+pub(crate) fn is_key_type_enabled(
+    key_type: KeyType,
+    current_protocol_version: ProtocolVersion,
+) -> bool {
+    match key_type {
+        KeyType::ED25519 | KeyType::SECP256K1 => true,
+        KeyType::BLS12381 => checked_feature!(
+            "protocol_feature_bls12381_keys",
+            BLS12381Keys,
+            current_protocol_version
+        ),
+    }
+}
+
+/// Checks that the type of a key used in an action is enabled by the protocol version.
+fn validate_key_type(
+    public_key: &PublicKey,
+    current_protocol_version: ProtocolVersion,
+) -> Result<(), ActionsValidationError> {
+    if !is_key_type_enabled(public_key.key_type(), current_protocol_version) {
+        return Err(ActionsValidationError::UnsupportedKeyType { public_key: public_key.clone() });
+    }
+    Ok(())
+}
+
+/// Validates `StakeAction`. Checks that the key type is enabled by the protocol version and that
+/// the `public_key` is a valid staking key.
+fn validate_stake_action(
+    action: &StakeAction,
+    current_protocol_version: ProtocolVersion,
+) -> Result<(), ActionsValidationError> {
+    validate_key_type(&action.public_key, current_protocol_version)?;
     if !is_valid_staking_key(&action.public_key) {
         return Err(ActionsValidationError::UnsuitableStakingKey {
             public_key: action.public_key.clone(),
@@ -387,13 +543,26 @@ fn validate_stake_action(action: &StakeAction) -> Result<(), ActionsValidationEr
     Ok(())
 }
 
-/// Validates `AddKeyAction`. If the access key permission is `FunctionCall`, checks that the
-/// total number of bytes of the method names doesn't exceed the limit and
-/// every method name length doesn't exceed the limit.
+/// Validates `AddKeyAction`. Checks that the key type and the expiration height are enabled by the
+/// protocol version. If the access key permission is `FunctionCall`, checks that the total number
+/// of bytes of the method names doesn't exceed the limit and every method name length doesn't
+/// exceed the limit.
 fn validate_add_key_action(
     limit_config: &VMLimitConfig,
     action: &AddKeyAction,
+    current_protocol_version: ProtocolVersion,
 ) -> Result<(), ActionsValidationError> {
+    validate_key_type(&action.public_key, current_protocol_version)?;
+    if action.access_key.valid_until_block_height.is_some()
+        && !checked_feature!(
+            "protocol_feature_access_key_expiration",
+            AccessKeyExpiration,
+            current_protocol_version
+        )
+    {
+        return Err(ActionsValidationError::AccessKeyExpirationNotSupported);
+    }
+
     if let AccessKeyPermission::FunctionCall(fc) = &action.access_key.permission {
         // Checking method name length limits
         let mut total_number_of_bytes = 0;
@@ -428,7 +597,8 @@ mod tests {
     use near_primitives::hash::{hash, CryptoHash};
     use near_primitives::test_utils::account_new;
     use near_primitives::transaction::{
-        CreateAccountAction, DeleteAccountAction, DeleteKeyAction, StakeAction, TransferAction,
+        CreateAccountAction, DeleteAccountAction, DeleteKeyAction,
+        NonrefundableStorageTransferAction, StakeAction, TransferAction,
     };
     use near_primitives::types::{AccountId, Balance, MerkleHash, StateChangeCause};
     use near_primitives::version::PROTOCOL_VERSION;
@@ -568,6 +738,31 @@ mod tests {
         assert_eq!(access_key.nonce, 1);
     }
 
+    #[test]
+    fn test_validate_transaction_bls12381_signer() {
+        let config = RuntimeConfig::test();
+        let signer = InMemorySigner::from_seed(alice_account(), KeyType::BLS12381, "alice");
+        let transaction = SignedTransaction::send_money(
+            1,
+            alice_account(),
+            bob_account(),
+            &signer,
+            100,
+            CryptoHash::default(),
+        );
+        let result = validate_transaction(&config, 100, &transaction, true, PROTOCOL_VERSION);
+        if checked_feature!("protocol_feature_bls12381_keys", BLS12381Keys, PROTOCOL_VERSION) {
+            result.expect("valid transaction");
+        } else {
+            assert_eq!(
+                result.expect_err("expected an error"),
+                RuntimeError::InvalidTxError(InvalidTxError::UnsupportedSignerKeyType {
+                    public_key: signer.public_key(),
+                }),
+            );
+        }
+    }
+
     #[test]
     fn test_validate_transaction_invalid_signature() {
         let config = RuntimeConfig::test();
@@ -625,6 +820,113 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_validate_transaction_access_key_expiration() {
+        let config = RuntimeConfig::test();
+        let access_key =
+            AccessKey { valid_until_block_height: Some(10), ..AccessKey::full_access() };
+        let verify_at = |block_height| {
+            let (signer, mut state_update, gas_price) =
+                setup_common(TESTING_INIT_BALANCE, 0, Some(access_key.clone()));
+            let transaction = SignedTransaction::send_money(
+                1,
+                alice_account(),
+                bob_account(),
+                &*signer,
+                100,
+                CryptoHash::default(),
+            );
+            let result = verify_and_charge_transaction(
+                &config,
+                &mut state_update,
+                gas_price,
+                &transaction,
+                true,
+                Some(block_height),
+                PROTOCOL_VERSION,
+            );
+            (result, signer.public_key())
+        };
+
+        verify_at(10).0.expect("valid transaction");
+        let (result, public_key) = verify_at(11);
+        if checked_feature!(
+            "protocol_feature_access_key_expiration",
+            AccessKeyExpiration,
+            PROTOCOL_VERSION
+        ) {
+            assert_eq!(
+                result.expect_err("expected an error"),
+                RuntimeError::InvalidTxError(InvalidTxError::InvalidAccessKeyError(
+                    InvalidAccessKeyError::AccessKeyExpired {
+                        account_id: alice_account(),
+                        public_key,
+                        valid_until_block_height: 10,
+                        block_height: 11,
+                    },
+                )),
+            );
+        } else {
+            result.expect("valid transaction");
+        }
+    }
+
+    #[test]
+    fn test_validate_transaction_add_key_already_expired() {
+        let config = RuntimeConfig::test();
+        let verify_at = |block_height| {
+            let (signer, mut state_update, gas_price) =
+                setup_common(TESTING_INIT_BALANCE, 0, Some(AccessKey::full_access()));
+            let transaction = SignedTransaction::from_actions(
+                1,
+                alice_account(),
+                alice_account(),
+                &*signer,
+                vec![Action::AddKey(AddKeyAction {
+                    public_key: PublicKey::from_seed(KeyType::ED25519, "expiring"),
+                    access_key: AccessKey {
+                        valid_until_block_height: Some(10),
+                        ..AccessKey::full_access()
+                    },
+                })],
+                CryptoHash::default(),
+            );
+            verify_and_charge_transaction(
+                &config,
+                &mut state_update,
+                gas_price,
+                &transaction,
+                true,
+                Some(block_height),
+                PROTOCOL_VERSION,
+            )
+        };
+
+        if checked_feature!(
+            "protocol_feature_access_key_expiration",
+            AccessKeyExpiration,
+            PROTOCOL_VERSION
+        ) {
+            verify_at(10).expect("valid transaction");
+            assert_eq!(
+                verify_at(11).expect_err("expected an error"),
+                RuntimeError::InvalidTxError(InvalidTxError::ActionsValidation(
+                    ActionsValidationError::AddKeyAlreadyExpired {
+                        valid_until_block_height: 10,
+                        block_height: 11,
+                    },
+                )),
+            );
+        } else {
+            assert_eq!(
+                verify_at(10).expect_err("expected an error"),
+                RuntimeError::InvalidTxError(InvalidTxError::ActionsValidation(
+                    ActionsValidationError::AccessKeyExpirationNotSupported,
+                )),
+            );
+        }
+    }
+
     #[test]
     fn test_validate_transaction_invalid_bad_action() {
         let mut config = RuntimeConfig::test();
@@ -695,7 +997,11 @@ mod tests {
         let (signer, mut state_update, gas_price) = setup_common(
             TESTING_INIT_BALANCE,
             0,
-            Some(AccessKey { nonce: 2, permission: AccessKeyPermission::FullAccess }),
+            Some(AccessKey {
+                nonce: 2,
+                permission: AccessKeyPermission::FullAccess,
+                valid_until_block_height: None,
+            }),
         );
 
         assert_eq!(
@@ -792,6 +1098,7 @@ mod tests {
                     receiver_id: bob_account().into(),
                     method_names: vec![],
                 }),
+                valid_until_block_height: None,
             }),
         );
 
@@ -881,6 +1188,7 @@ mod tests {
                     receiver_id: bob_account().into(),
                     method_names: vec![],
                 }),
+                valid_until_block_height: None,
             }),
         );
 
@@ -975,6 +1283,7 @@ mod tests {
                     receiver_id: bob_account().into(),
                     method_names: vec![],
                 }),
+                valid_until_block_height: None,
             }),
         );
 
@@ -1023,6 +1332,7 @@ mod tests {
                     receiver_id: bob_account().into(),
                     method_names: vec!["not_hello".to_string(), "world".to_string()],
                 }),
+                valid_until_block_height: None,
             }),
         );
 
@@ -1068,6 +1378,7 @@ mod tests {
                     receiver_id: bob_account().into(),
                     method_names: vec![],
                 }),
+                valid_until_block_height: None,
             }),
         );
 
@@ -1149,13 +1460,152 @@ mod tests {
         .expect("valid transaction");
     }
 
+    #[cfg(feature = "protocol_feature_receipt_size_limit")]
+    #[test]
+    fn test_validate_transaction_exceeding_receipt_size_limit() {
+        let (signer, mut state_update, gas_price) =
+            setup_common(TESTING_INIT_BALANCE, 0, Some(AccessKey::full_access()));
+
+        let transaction = SignedTransaction::from_actions(
+            1,
+            alice_account(),
+            bob_account(),
+            &*signer,
+            vec![Action::DeployContract(DeployContractAction { code: vec![1; 5] })],
+            CryptoHash::default(),
+        );
+        let receipt_size = transaction_receipt_size(&transaction.transaction, PROTOCOL_VERSION);
+
+        let mut config = RuntimeConfig::test();
+        config.wasm_config.limit_config.max_receipt_size = Some(receipt_size - 1);
+
+        assert_eq!(
+            verify_and_charge_transaction(
+                &config,
+                &mut state_update,
+                gas_price,
+                &transaction,
+                false,
+                None,
+                PROTOCOL_VERSION,
+            )
+            .expect_err("expected an error"),
+            RuntimeError::InvalidTxError(InvalidTxError::ReceiptSizeExceeded {
+                size: receipt_size,
+                limit: receipt_size - 1
+            }),
+        );
+
+        config.wasm_config.limit_config.max_receipt_size = Some(receipt_size);
+        verify_and_charge_transaction(
+            &config,
+            &mut state_update,
+            gas_price,
+            &transaction,
+            false,
+            None,
+            PROTOCOL_VERSION,
+        )
+        .expect("valid transaction");
+    }
+
+    /// A transaction of the largest size converts to a receipt within the receipt size limit.
+    #[cfg(feature = "protocol_feature_receipt_size_limit")]
+    #[test]
+    fn test_validate_transaction_max_size_deploy() {
+        let (signer, mut state_update, gas_price) =
+            setup_common(TESTING_INIT_BALANCE, 0, Some(AccessKey::full_access()));
+        let config = near_primitives::runtime::config_store::RuntimeConfigStore::new(None)
+            .get_config(PROTOCOL_VERSION)
+            .clone();
+        let max_transaction_size = config.wasm_config.limit_config.max_transaction_size;
+        let deploy = |code_size| {
+            SignedTransaction::from_actions(
+                1,
+                alice_account(),
+                bob_account(),
+                &*signer,
+                vec![Action::DeployContract(DeployContractAction { code: vec![1; code_size] })],
+                CryptoHash::default(),
+            )
+        };
+        let transaction = deploy((max_transaction_size - deploy(0).get_size()) as usize);
+        assert_eq!(transaction.get_size(), max_transaction_size);
+
+        verify_and_charge_transaction(
+            &config,
+            &mut state_update,
+            gas_price,
+            &transaction,
+            false,
+            None,
+            PROTOCOL_VERSION,
+        )
+        .expect("valid transaction");
+    }
+
+    #[test]
+    fn test_validate_transaction_priority_fee() {
+        let config = RuntimeConfig::test();
+        let priority_fee = 10 * NEAR_BASE;
+        let verify = |priority_fee| {
+            let (signer, mut state_update, gas_price) =
+                setup_common(TESTING_INIT_BALANCE, 0, Some(AccessKey::full_access()));
+            let transaction = Transaction {
+                priority_fee,
+                ..SignedTransaction::send_money(
+                    1,
+                    alice_account(),
+                    bob_account(),
+                    &*signer,
+                    100,
+                    CryptoHash::default(),
+                )
+                .transaction
+            }
+            .sign(&*signer);
+            let result = verify_and_charge_transaction(
+                &config,
+                &mut state_update,
+                gas_price,
+                &transaction,
+                true,
+                None,
+                PROTOCOL_VERSION,
+            );
+            let account = get_account(&state_update, &alice_account()).unwrap().unwrap();
+            (result, account.amount())
+        };
+
+        let (result, amount) = verify(None);
+        let burnt_amount = result.expect("valid transaction").burnt_amount;
+        let (result, priority_amount) = verify(Some(priority_fee));
+        if checked_feature!("protocol_feature_priority_fee", PriorityFee, PROTOCOL_VERSION) {
+            // The priority fee is burnt on top of the transaction fees.
+            assert_eq!(
+                result.expect("valid transaction").burnt_amount,
+                burnt_amount + priority_fee
+            );
+            assert_eq!(priority_amount, amount - priority_fee);
+        } else {
+            assert_eq!(
+                result.expect_err("expected an error"),
+                RuntimeError::InvalidTxError(InvalidTxError::PriorityFeeNotSupported),
+            );
+        }
+    }
+
     // Receipts
 
     #[test]
     fn test_validate_receipt_valid() {
         let limit_config = VMLimitConfig::test();
-        validate_receipt(&limit_config, &Receipt::new_balance_refund(&alice_account(), 10))
-            .expect("valid receipt");
+        validate_receipt(
+            &limit_config,
+            &Receipt::new_balance_refund(&alice_account(), 10),
+            PROTOCOL_VERSION,
+        )
+        .expect("valid receipt");
     }
 
     #[test]
@@ -1171,8 +1621,11 @@ mod tests {
                     gas_price: 100,
                     output_data_receivers: vec![],
                     input_data_ids: vec![CryptoHash::default(), CryptoHash::default()],
-                    actions: vec![]
-                }
+                    actions: vec![],
+                    priority_fee: None,
+                    depth: None,
+                },
+                PROTOCOL_VERSION,
             )
             .expect_err("expected an error"),
             ReceiptValidationError::NumberInputDataDependenciesExceeded {
@@ -1189,13 +1642,13 @@ mod tests {
         let limit_config = VMLimitConfig::test();
         validate_data_receipt(
             &limit_config,
-            &DataReceipt { data_id: CryptoHash::default(), data: None },
+            &DataReceipt { data_id: CryptoHash::default(), data: None, gas_burnt: None },
         )
         .expect("valid data receipt");
         let data = b"hello".to_vec();
         validate_data_receipt(
             &limit_config,
-            &DataReceipt { data_id: CryptoHash::default(), data: Some(data) },
+            &DataReceipt { data_id: CryptoHash::default(), data: Some(data), gas_burnt: None },
         )
         .expect("valid data receipt");
     }
@@ -1208,7 +1661,11 @@ mod tests {
         assert_eq!(
             validate_data_receipt(
                 &limit_config,
-                &DataReceipt { data_id: CryptoHash::default(), data: Some(data.clone()) }
+                &DataReceipt {
+                    data_id: CryptoHash::default(),
+                    data: Some(data.clone()),
+                    gas_burnt: None
+                }
             )
             .expect_err("expected an error"),
             ReceiptValidationError::ReturnedValueLengthExceeded {
@@ -1223,7 +1680,7 @@ mod tests {
     #[test]
     fn test_validate_actions_empty() {
         let limit_config = VMLimitConfig::test();
-        validate_actions(&limit_config, &[]).expect("empty actions");
+        validate_actions(&limit_config, &[], PROTOCOL_VERSION).expect("empty actions");
     }
 
     #[test]
@@ -1237,6 +1694,7 @@ mod tests {
                 gas: 100,
                 deposit: 0,
             })],
+            PROTOCOL_VERSION,
         )
         .expect("valid function call action");
     }
@@ -1261,7 +1719,8 @@ mod tests {
                         gas: 150,
                         deposit: 0,
                     })
-                ]
+                ],
+                PROTOCOL_VERSION
             )
             .expect_err("expected an error"),
             ActionsValidationError::TotalPrepaidGasExceeded { total_prepaid_gas: 250, limit: 220 }
@@ -1288,7 +1747,8 @@ mod tests {
                         gas: u64::max_value() / 2 + 1,
                         deposit: 0,
                     })
-                ]
+                ],
+                PROTOCOL_VERSION
             )
             .expect_err("Expected an error"),
             ActionsValidationError::IntegerOverflow,
@@ -1305,7 +1765,8 @@ mod tests {
                 &vec![
                     Action::CreateAccount(CreateAccountAction {}),
                     Action::CreateAccount(CreateAccountAction {}),
-                ]
+                ],
+                PROTOCOL_VERSION
             )
             .expect_err("Expected an error"),
             ActionsValidationError::TotalNumberOfActionsExceeded {
@@ -1327,7 +1788,8 @@ mod tests {
                         beneficiary_id: "bob".parse().unwrap()
                     }),
                     Action::CreateAccount(CreateAccountAction {}),
-                ]
+                ],
+                PROTOCOL_VERSION
             )
             .expect_err("Expected an error"),
             ActionsValidationError::DeleteActionMustBeFinal,
@@ -1346,7 +1808,8 @@ mod tests {
                     Action::DeleteAccount(DeleteAccountAction {
                         beneficiary_id: "bob".parse().unwrap()
                     }),
-                ]
+                ],
+                PROTOCOL_VERSION
             ),
             Ok(()),
         );
@@ -1356,8 +1819,12 @@ mod tests {
 
     #[test]
     fn test_validate_action_valid_create_account() {
-        validate_action(&VMLimitConfig::test(), &Action::CreateAccount(CreateAccountAction {}))
-            .expect("valid action");
+        validate_action(
+            &VMLimitConfig::test(),
+            &Action::CreateAccount(CreateAccountAction {}),
+            PROTOCOL_VERSION,
+        )
+        .expect("valid action");
     }
 
     #[test]
@@ -1370,6 +1837,7 @@ mod tests {
                 gas: 100,
                 deposit: 0,
             }),
+            PROTOCOL_VERSION,
         )
         .expect("valid action");
     }
@@ -1385,6 +1853,7 @@ mod tests {
                     gas: 0,
                     deposit: 0,
                 }),
+                PROTOCOL_VERSION,
             )
             .expect_err("expected an error"),
             ActionsValidationError::FunctionCallZeroAttachedGas,
@@ -1393,8 +1862,12 @@ mod tests {
 
     #[test]
     fn test_validate_action_valid_transfer() {
-        validate_action(&VMLimitConfig::test(), &Action::Transfer(TransferAction { deposit: 10 }))
-            .expect("valid action");
+        validate_action(
+            &VMLimitConfig::test(),
+            &Action::Transfer(TransferAction { deposit: 10 }),
+            PROTOCOL_VERSION,
+        )
+        .expect("valid action");
     }
 
     #[test]
@@ -1405,6 +1878,7 @@ mod tests {
                 stake: 100,
                 public_key: "ed25519:KuTCtARNzxZQ3YvXDeLjx83FDqxv2SdQTSbiq876zR7".parse().unwrap(),
             }),
+            PROTOCOL_VERSION,
         )
         .expect("valid action");
     }
@@ -1418,6 +1892,7 @@ mod tests {
                     stake: 100,
                     public_key: PublicKey::empty(KeyType::ED25519),
                 }),
+                PROTOCOL_VERSION,
             )
             .expect_err("Expected an error"),
             ActionsValidationError::UnsuitableStakingKey {
@@ -1434,10 +1909,70 @@ mod tests {
                 public_key: PublicKey::empty(KeyType::ED25519),
                 access_key: AccessKey::full_access(),
             }),
+            PROTOCOL_VERSION,
         )
         .expect("valid action");
     }
 
+    #[test]
+    fn test_validate_action_bls12381_keys() {
+        let public_key = PublicKey::from_seed(KeyType::BLS12381, "alice");
+        let enabled =
+            checked_feature!("protocol_feature_bls12381_keys", BLS12381Keys, PROTOCOL_VERSION);
+        let unsupported =
+            ActionsValidationError::UnsupportedKeyType { public_key: public_key.clone() };
+        let validate = |action| validate_action(&VMLimitConfig::test(), &action, PROTOCOL_VERSION);
+
+        let add_key = validate(Action::AddKey(AddKeyAction {
+            public_key: public_key.clone(),
+            access_key: AccessKey::full_access(),
+        }));
+        let delete_key =
+            validate(Action::DeleteKey(DeleteKeyAction { public_key: public_key.clone() }));
+        if enabled {
+            add_key.expect("valid action");
+            delete_key.expect("valid action");
+        } else {
+            assert_eq!(add_key.expect_err("expected an error"), unsupported);
+            assert_eq!(delete_key.expect_err("expected an error"), unsupported);
+        }
+
+        // BLS12-381 keys can't be staking keys either way.
+        let stake =
+            validate(Action::Stake(StakeAction { stake: 1, public_key: public_key.clone() }));
+        if enabled {
+            assert_eq!(
+                stake.expect_err("expected an error"),
+                ActionsValidationError::UnsuitableStakingKey { public_key }
+            );
+        } else {
+            assert_eq!(stake.expect_err("expected an error"), unsupported);
+        }
+    }
+
+    #[test]
+    fn test_validate_action_nonrefundable_storage_transfer() {
+        let result = validate_action(
+            &VMLimitConfig::test(),
+            &Action::NonrefundableStorageTransfer(NonrefundableStorageTransferAction {
+                deposit: 10,
+            }),
+            PROTOCOL_VERSION,
+        );
+        if checked_feature!(
+            "protocol_feature_nonrefundable_storage_transfer",
+            NonrefundableStorageTransfer,
+            PROTOCOL_VERSION
+        ) {
+            result.expect("valid action");
+        } else {
+            assert_eq!(
+                result.expect_err("expected an error"),
+                ActionsValidationError::NonrefundableStorageTransferNotSupported,
+            );
+        }
+    }
+
     #[test]
     fn test_validate_action_valid_add_key_function_call() {
         validate_action(
@@ -1451,8 +1986,10 @@ mod tests {
                         receiver_id: alice_account().into(),
                         method_names: vec!["hello".to_string(), "world".to_string()],
                     }),
+                    valid_until_block_height: None,
                 },
             }),
+            PROTOCOL_VERSION,
         )
         .expect("valid action");
     }
@@ -1462,6 +1999,7 @@ mod tests {
         validate_action(
             &VMLimitConfig::test(),
             &Action::DeleteKey(DeleteKeyAction { public_key: PublicKey::empty(KeyType::ED25519) }),
+            PROTOCOL_VERSION,
         )
         .expect("valid action");
     }
@@ -1471,6 +2009,7 @@ mod tests {
         validate_action(
             &VMLimitConfig::test(),
             &Action::DeleteAccount(DeleteAccountAction { beneficiary_id: alice_account() }),
+            PROTOCOL_VERSION,
         )
         .expect("valid action");
     }
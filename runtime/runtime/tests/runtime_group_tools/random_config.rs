@@ -19,6 +19,7 @@ pub fn random_config() -> RuntimeConfig {
             data_receipt_creation_config: DataReceiptCreationConfig {
                 base_cost: random_fee(),
                 cost_per_byte: random_fee(),
+                large_data_cost: None,
             },
             action_creation_config: ActionCreationConfig {
                 create_account_cost: random_fee(),
@@ -0,0 +1,118 @@
+//! Replays the chunks of the regression corpus, see `tests/corpus/README.md`.
+use std::path::PathBuf;
+
+use near_primitives::transaction::{
+    Action, DeployContractAction, FunctionCallAction, TransferAction,
+};
+use near_primitives::types::AccountId;
+use near_primitives::version::ProtocolVersion;
+use node_runtime::corpus::{corpus_files, ChunkFixture};
+use node_runtime::scenario::{BlockConfig, Scenario, TransactionConfig};
+
+/// Chain id of the fixtures recorded from `corpus_scenario`.
+const SCENARIO_CHAIN_ID: &str = "scenario";
+
+fn corpus_dir() -> PathBuf {
+    match std::env::var_os("RUNTIME_CORPUS_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("corpus"),
+    }
+}
+
+fn protocol_version() -> Option<ProtocolVersion> {
+    std::env::var("RUNTIME_CORPUS_PROTOCOL_VERSION").ok().map(|version| {
+        version.parse().expect("RUNTIME_CORPUS_PROTOCOL_VERSION must be a protocol version")
+    })
+}
+
+#[test]
+fn test_runtime_corpus() {
+    let dir = corpus_dir();
+    let files = corpus_files(&dir, protocol_version()).unwrap();
+    assert!(!files.is_empty(), "no chunks to replay in {}", dir.display());
+    let failures: Vec<_> = files
+        .iter()
+        .filter_map(|file| {
+            let result = ChunkFixture::read_from_file(file)
+                .map_err(|err| format!("failed to read the fixture: {}", err))
+                .and_then(|fixture| fixture.check());
+            result.err().map(|err| format!("{}: {}", file.display(), err))
+        })
+        .collect();
+    assert!(
+        failures.is_empty(),
+        "{} of {} chunks in {} differ:\n{}",
+        failures.len(),
+        files.len(),
+        dir.display(),
+        failures.join("\n")
+    );
+}
+
+fn account(name: &str) -> AccountId {
+    name.parse().unwrap()
+}
+
+/// Deploys a contract, calls it successfully and with a panic, which is refunded, and
+/// transfers to another account.
+fn corpus_scenario() -> Scenario {
+    let mut scenario = Scenario::default();
+    let alice = scenario.add_account(account("alice"), 10u128.pow(30), 0);
+    scenario.add_account(account("bob"), 10u128.pow(30), 0);
+    let function_call = |method_name: &str| {
+        Action::FunctionCall(FunctionCallAction {
+            method_name: method_name.to_string(),
+            args: [1u64, 2u64].iter().flat_map(|x| x.to_le_bytes()).collect(),
+            gas: 10u64.pow(14),
+            deposit: 0,
+        })
+    };
+    let transactions = vec![
+        (
+            account("alice"),
+            Action::DeployContract(DeployContractAction {
+                code: near_test_contracts::rs_contract_base_protocol().to_vec(),
+            }),
+        ),
+        (account("bob"), Action::Transfer(TransferAction { deposit: 100 })),
+        (account("alice"), function_call("write_key_value")),
+        (account("alice"), function_call("panic_with_message")),
+    ];
+    for (nonce, (receiver_id, action)) in (1..).zip(transactions) {
+        let mut block = BlockConfig::at_height(nonce);
+        block.transactions.push(TransactionConfig {
+            nonce,
+            signer_id: account("alice"),
+            receiver_id,
+            signer: alice.clone(),
+            actions: vec![action],
+        });
+        scenario.blocks.push(block);
+    }
+    let next_height = scenario.blocks.len() as u64 + 1;
+    scenario.blocks.extend((next_height..next_height + 3).map(BlockConfig::at_height));
+    scenario
+}
+
+/// Fixtures recorded without a node are replayed the same way as the recorded ones.
+#[test]
+fn test_scenario_fixtures() {
+    let fixtures = corpus_scenario().record_fixtures(SCENARIO_CHAIN_ID).unwrap();
+    for fixture in fixtures {
+        fixture.check().unwrap_or_else(|err| panic!("{}: {}", fixture.file_name().display(), err));
+    }
+}
+
+/// Records the blocks of `corpus_scenario` into the corpus:
+///
+/// ```text
+/// cargo test -p node-runtime --test test_runtime_corpus -- --ignored record_scenario_corpus
+/// ```
+#[test]
+#[ignore]
+fn record_scenario_corpus() {
+    let dir = corpus_dir();
+    for fixture in corpus_scenario().record_fixtures(SCENARIO_CHAIN_ID).unwrap() {
+        println!("recorded {}", fixture.write_to_dir(&dir).unwrap().display());
+    }
+}
@@ -0,0 +1,183 @@
+//! Checks that `storage_remove_subtree` leaves the state exactly as a contract removing every key
+//! with the prefix one by one would.
+use std::collections::BTreeMap;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use near_primitives::shard_layout::ShardUId;
+use near_primitives::trie_key::{trie_key_parsers, TrieKey};
+use near_primitives::types::{AccountId, StateChangeCause, StateRoot};
+use near_store::test_utils::create_tries;
+use near_store::{ShardTries, TrieUpdate};
+use near_vm_logic::External;
+use node_runtime::ext::RuntimeExt;
+use node_runtime::test_utils::TestExtContext;
+
+/// Bytes the keys are made of: the bounds of the byte range, the separator between the account
+/// id and the key in contract data keys and the column of contract data, `col::CONTRACT_DATA`.
+const ALPHABET: &[u8] = &[0x00, 0x01, b',', b'a', 0x09, 0xfe, 0xff];
+
+fn account_id() -> AccountId {
+    "alice.near".parse().unwrap()
+}
+
+/// Accounts whose ids share a prefix with the contract's one, the data of which must survive.
+fn other_account_ids() -> Vec<AccountId> {
+    vec!["alice.nea".parse().unwrap(), "alice.near0".parse().unwrap()]
+}
+
+fn value(key: &[u8]) -> Vec<u8> {
+    [key, b"value"].concat()
+}
+
+/// Contract data of `account_id`, as seen by the contract.
+fn contract_data(trie_update: &TrieUpdate, account_id: &AccountId) -> BTreeMap<Vec<u8>, Vec<u8>> {
+    let prefix = trie_key_parsers::get_raw_prefix_for_contract_data(account_id, &[]);
+    trie_update
+        .iter(&prefix)
+        .unwrap()
+        .map(|raw_key| {
+            let raw_key = raw_key.unwrap();
+            let key = trie_key_parsers::parse_data_key_from_contract_data_key(&raw_key, account_id)
+                .unwrap()
+                .to_vec();
+            let trie_key =
+                TrieKey::ContractData { account_id: account_id.clone(), key: key.clone() };
+            (key, trie_update.get(&trie_key).unwrap().unwrap())
+        })
+        .collect()
+}
+
+fn with_ext<T>(trie_update: &mut TrieUpdate, f: impl FnOnce(&mut RuntimeExt) -> T) -> T {
+    let context = TestExtContext::new(account_id());
+    let mut ext = context.ext(trie_update);
+    f(&mut ext)
+}
+
+/// Trie with `keys` stored by the contract and by the other accounts.
+fn populate(tries: &ShardTries, keys: &[Vec<u8>]) -> StateRoot {
+    let mut trie_update = tries.new_trie_update(ShardUId::single_shard(), StateRoot::default());
+    for account_id in other_account_ids().into_iter().chain([account_id()]) {
+        for key in keys {
+            let trie_key =
+                TrieKey::ContractData { account_id: account_id.clone(), key: key.clone() };
+            trie_update.set(trie_key, value(key));
+        }
+    }
+    trie_update.commit(StateChangeCause::InitialState);
+    let trie_changes = trie_update.finalize().unwrap().0;
+    let (store_update, root) = tries.apply_all(&trie_changes, ShardUId::single_shard()).unwrap();
+    store_update.commit().unwrap();
+    root
+}
+
+/// Trie update on top of the stored `keys`, in which the contract has already set `updated`
+/// and then removed `removed` keys, without committing these changes.
+fn trie_update(
+    tries: &ShardTries,
+    root: StateRoot,
+    updated: &[Vec<u8>],
+    removed: &[Vec<u8>],
+) -> TrieUpdate {
+    let mut trie_update = tries.new_trie_update(ShardUId::single_shard(), root);
+    with_ext(&mut trie_update, |ext| {
+        for key in updated {
+            ext.storage_set(key, &value(key)).unwrap();
+        }
+        for key in removed {
+            ext.storage_remove(key).unwrap();
+        }
+    });
+    trie_update
+}
+
+/// Removes the keys with `prefix` with `storage_remove_subtree` and one by one, checking that
+/// both end up with the same state and remove the same keys, those with the prefix.
+fn check_remove_subtree(
+    stored: &[Vec<u8>],
+    updated: &[Vec<u8>],
+    removed: &[Vec<u8>],
+    prefix: &[u8],
+) {
+    let tries = create_tries();
+    let root = populate(&tries, stored);
+    let mut subtree = trie_update(&tries, root, updated, removed);
+    let mut one_by_one = trie_update(&tries, root, updated, removed);
+
+    let before = contract_data(&subtree, &account_id());
+    let others_before: Vec<_> =
+        other_account_ids().iter().map(|account_id| contract_data(&subtree, account_id)).collect();
+    with_ext(&mut subtree, |ext| ext.storage_remove_subtree(prefix).unwrap());
+    with_ext(&mut one_by_one, |ext| {
+        for key in before.keys().filter(|key| key.starts_with(prefix)) {
+            ext.storage_remove(key).unwrap();
+        }
+    });
+
+    let removed_keys = |trie_update: &TrieUpdate| {
+        let after = contract_data(trie_update, &account_id());
+        before.keys().filter(|key| !after.contains_key(*key)).cloned().collect::<Vec<_>>()
+    };
+    let expected: Vec<_> = before.keys().filter(|key| key.starts_with(prefix)).cloned().collect();
+    assert_eq!(removed_keys(&subtree), expected.clone());
+    assert_eq!(removed_keys(&one_by_one), expected);
+    for (account_id, data) in other_account_ids().iter().zip(others_before) {
+        assert_eq!(contract_data(&subtree, account_id), data);
+    }
+
+    let state_root = |mut trie_update: TrieUpdate| {
+        trie_update.commit(StateChangeCause::InitialState);
+        trie_update.finalize().unwrap().0.new_root
+    };
+    assert_eq!(state_root(subtree), state_root(one_by_one));
+}
+
+fn gen_key(rng: &mut impl Rng) -> Vec<u8> {
+    (0..rng.gen_range(0, 4)).map(|_| *ALPHABET.choose(rng).unwrap()).collect()
+}
+
+fn gen_keys(rng: &mut impl Rng, max: usize) -> Vec<Vec<u8>> {
+    (0..rng.gen_range(0, max)).map(|_| gen_key(rng)).collect()
+}
+
+#[test]
+fn test_remove_subtree_matches_iterate_and_remove() {
+    for seed in 0..256 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let stored = gen_keys(&mut rng, 20);
+        let updated = gen_keys(&mut rng, 10);
+        let removed = gen_keys(&mut rng, 10);
+        // Half of the time the prefix is one of the stored keys.
+        let prefix = match stored.choose(&mut rng) {
+            Some(key) if rng.gen_bool(0.5) => key.clone(),
+            _ => gen_key(&mut rng),
+        };
+        check_remove_subtree(&stored, &updated, &removed, &prefix);
+    }
+}
+
+/// Cases at the edges of the key space, kept so that they're checked on every run.
+#[test]
+fn test_remove_subtree_regressions() {
+    let keys = |keys: &[&[u8]]| keys.iter().map(|key| key.to_vec()).collect::<Vec<_>>();
+    let cases: &[(&[&[u8]], &[&[u8]], &[&[u8]], &[u8])] = &[
+        // Empty prefix.
+        (&[b"", b"a", b"aa", b"\xff"], &[], &[], b""),
+        // Prefix equal to a key which is a prefix of other keys.
+        (&[b"a", b"aa", b"a,", b"b"], &[], &[], b"a"),
+        // Prefix ending at the end of the byte range.
+        (&[b"\xff", b"\xff\xff", b"\xff\x00", b"\xfe\xff"], &[], &[], b"\xff"),
+        (&[b"\xfe\xff", b"\xff"], &[], &[], b"\xfe"),
+        // Separator and column bytes in the keys and in the prefix.
+        (&[b",", b",,", b"\x09,", b"a,"], &[], &[], b","),
+        (&[b"\x09", b"\x09a"], &[], &[], b"\x09"),
+        // Keys set and removed in the same trie update.
+        (&[b"a", b"ab"], &[b"ac", b"b"], &[b"ab"], b"a"),
+        (&[], &[b"a", b"aa"], &[b"a"], b"a"),
+    ];
+    for &(stored, updated, removed, prefix) in cases {
+        check_remove_subtree(&keys(stored), &keys(updated), &keys(removed), prefix);
+    }
+}
@@ -0,0 +1,73 @@
+#!/usr/bin/env python3
+"""Checks criterion benchmark results for regressions against a baseline.
+
+When a benchmark is run with `--baseline <name>`, criterion stores the relative
+change of each case against the named baseline in
+`target/criterion/<case>/change/estimates.json`.  The script goes through all
+such files and fails if the lower bound of the confidence interval of the mean
+change of any case exceeds the threshold, i.e. if the case got slower by more
+than the threshold even when giving it the benefit of the doubt.
+
+Usage:
+
+    cargo bench -p node-runtime --bench runtime_ext -- --save-baseline master
+    # switch to the change under test
+    cargo bench -p node-runtime --bench runtime_ext -- --baseline master
+    python3 scripts/check_bench_regressions.py --threshold 10
+"""
+
+import argparse
+import json
+import pathlib
+import sys
+import typing
+
+REPO_DIR = pathlib.Path(__file__).parent.parent
+
+
+def find_changes(
+        criterion_dir: pathlib.Path) -> typing.Iterable[typing.Tuple[str, float]]:
+    """Yields names of the benchmark cases and lower bounds of their changes."""
+    for change_file in sorted(criterion_dir.glob('**/change/estimates.json')):
+        case_dir = change_file.parent.parent
+        with open(case_dir / 'new' / 'benchmark.json') as rd:
+            name = json.load(rd)['full_id']
+        with open(change_file) as rd:
+            mean = json.load(rd)['mean']
+        yield name, mean['confidence_interval']['lower_bound']
+
+
+def main() -> typing.Optional[str]:
+    parser = argparse.ArgumentParser(description=__doc__.split('\n')[0])
+    parser.add_argument('--threshold',
+                        type=float,
+                        default=10,
+                        help='allowed slowdown of a case, in percent')
+    parser.add_argument('--criterion-dir',
+                        type=pathlib.Path,
+                        default=REPO_DIR / 'target' / 'criterion')
+    args = parser.parse_args()
+
+    changes = list(find_changes(args.criterion_dir))
+    if not changes:
+        return f'No benchmark results compared to a baseline in {args.criterion_dir}'
+
+    regressions = [(name, change * 100)
+                   for name, change in changes
+                   if change * 100 > args.threshold]
+    if not regressions:
+        print(f'No regressions above {args.threshold}% in {len(changes)} cases')
+        return None
+
+    name_len = max(len(name) for name, _ in regressions)
+    regressions_list = '\n'.join(f'  * {name.ljust(name_len)} +{change:.1f}%'
+                                 for name, change in regressions)
+    return f'''\
+Found {len(regressions)} benchmark case(s) slower than the baseline by more than {args.threshold}%:
+
+{regressions_list}
+'''
+
+
+if __name__ == '__main__':
+    sys.exit(main())
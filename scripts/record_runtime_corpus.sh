@@ -0,0 +1,16 @@
+#!/usr/bin/env bash
+# Records the chunks of a range of blocks from the database of a node into the runtime
+# regression corpus.  The node must be stopped, since it locks the database.
+#
+# Usage: scripts/record_runtime_corpus.sh <home_dir> <start_height> <end_height> [shard_id]
+set -euo pipefail
+
+if [ "$#" -lt 3 ]; then
+    echo "Usage: $0 <home_dir> <start_height> <end_height> [shard_id]" >&2
+    exit 1
+fi
+
+cd "$(dirname "$0")/.."
+cargo run --release -p neard -- --home "$1" view_state dump_runtime_corpus \
+    --start-height "$2" --end-height "$3" --shard-id "${4:-0}" \
+    --output-dir runtime/runtime/tests/corpus
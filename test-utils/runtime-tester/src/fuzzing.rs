@@ -162,6 +162,7 @@ impl TransactionConfig {
                         access_key: AccessKey {
                             nonce: 0,
                             permission: AccessKeyPermission::FullAccess,
+                            valid_until_block_height: None,
                         },
                     }),
                     Action::Transfer(TransferAction { deposit: NEAR_BASE }),
@@ -558,12 +559,16 @@ impl Scope {
             nonce,
             Key {
                 signer: signer.clone(),
-                access_key: AccessKey { nonce, permission: permission.clone() },
+                access_key: AccessKey {
+                    nonce,
+                    permission: permission.clone(),
+                    valid_until_block_height: None,
+                },
             },
         );
         Ok(AddKeyAction {
             public_key: signer.public_key,
-            access_key: AccessKey { nonce, permission },
+            access_key: AccessKey { nonce, permission, valid_until_block_height: None },
         })
     }
 
@@ -630,7 +635,11 @@ impl Account {
                     KeyType::ED25519,
                     id.as_ref(),
                 ),
-                access_key: AccessKey { nonce: 0, permission: AccessKeyPermission::FullAccess },
+                access_key: AccessKey {
+                    nonce: 0,
+                    permission: AccessKeyPermission::FullAccess,
+                    valid_until_block_height: None,
+                },
             },
         );
         Self {
@@ -0,0 +1,394 @@
+//! Amends an existing genesis, e.g. one produced from a state dump, so that it can be used to
+//! start a new network.
+//!
+//! Records are streamed from the input file and written out one by one, so this works on records
+//! files which don't fit into memory.
+
+use anyhow::Context;
+use clap::Args;
+use near_chain_configs::{stream_records_from_file, Genesis, GenesisConfig, GenesisValidationMode};
+use near_crypto::{InMemorySigner, KeyType, PublicKey};
+use near_primitives::account::{AccessKey, Account};
+use near_primitives::hash::CryptoHash;
+use near_primitives::state_record::StateRecord;
+use near_primitives::types::{AccountId, AccountInfo, Balance, BlockHeightDelta, ProtocolVersion};
+use primitive_types::U256;
+use serde::ser::{SerializeSeq, Serializer};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Default balance of each extra account: 1M NEAR.
+const DEFAULT_EXTRA_ACCOUNT_BALANCE: Balance = 1_000_000 * 10u128.pow(24);
+
+#[derive(Args, Debug)]
+#[clap(arg_required_else_help = true)]
+pub struct AmendGenesisCommand {
+    /// Genesis config to amend. If `--records-file-in` is not given, records are read from this
+    /// file as well.
+    #[clap(long)]
+    genesis_file_in: PathBuf,
+    /// Records to amend, e.g. a state dump.
+    #[clap(long)]
+    records_file_in: Option<PathBuf>,
+    /// Where to write the amended genesis config.
+    #[clap(long)]
+    genesis_file_out: PathBuf,
+    /// Where to write the amended records.
+    #[clap(long)]
+    records_file_out: PathBuf,
+    /// JSON file with the list of validators (`account_id`, `public_key` and `amount` of stake)
+    /// replacing the validators of the input genesis. All other stake gets unstaked. Accounts of
+    /// validators which don't exist yet are created with a full access key of the validator's
+    /// public key.
+    #[clap(long)]
+    validators: Option<PathBuf>,
+    /// Number of extra accounts to add, named `<extra-account-prefix><index>`.
+    #[clap(long, default_value = "0")]
+    num_extra_accounts: u64,
+    #[clap(long, default_value = "extra-account")]
+    extra_account_prefix: String,
+    #[clap(long, default_value_t = DEFAULT_EXTRA_ACCOUNT_BALANCE)]
+    extra_account_balance: Balance,
+    /// Full access key of all extra accounts. If not given, each extra account gets the key
+    /// derived from its account id as the seed.
+    #[clap(long)]
+    extra_account_public_key: Option<PublicKey>,
+    #[clap(long)]
+    chain_id: Option<String>,
+    #[clap(long)]
+    protocol_version: Option<ProtocolVersion>,
+    #[clap(long)]
+    epoch_length: Option<BlockHeightDelta>,
+    /// Scales balances of existing accounts so that the total supply stays the same (up to
+    /// rounding) after adding new stake and extra accounts. This reads the records twice.
+    #[clap(long)]
+    preserve_total_supply: bool,
+}
+
+impl AmendGenesisCommand {
+    pub fn run(self) -> anyhow::Result<()> {
+        let mut config = GenesisConfig::from_file(&self.genesis_file_in)?;
+        let records_file_in = self.records_file_in.as_ref().unwrap_or(&self.genesis_file_in);
+
+        let validators = match &self.validators {
+            Some(path) => {
+                let file = File::open(path)
+                    .with_context(|| format!("Could not open {}", path.display()))?;
+                let validators: Vec<AccountInfo> = serde_json::from_reader(BufReader::new(file))
+                    .with_context(|| format!("Failed to deserialize {}", path.display()))?;
+                Some(validators)
+            }
+            None => None,
+        };
+        let extra_accounts = self.extra_accounts()?;
+
+        let mut amendments =
+            Amendments::new(validators.as_deref(), extra_accounts, self.extra_account_balance);
+        if self.preserve_total_supply {
+            amendments.compute_scale(records_file_in)?;
+        }
+        let total_supply = amendments.write_records(records_file_in, &self.records_file_out)?;
+
+        if let Some(validators) = validators {
+            config.validators = validators;
+        }
+        if let Some(chain_id) = self.chain_id {
+            config.chain_id = chain_id;
+        }
+        if let Some(protocol_version) = self.protocol_version {
+            config.protocol_version = protocol_version;
+        }
+        if let Some(epoch_length) = self.epoch_length {
+            config.epoch_length = epoch_length;
+        }
+        info!(
+            target: "amend-genesis",
+            "Total supply changed from {} to {}", config.total_supply, total_supply
+        );
+        config.total_supply = total_supply;
+        config.to_file(&self.genesis_file_out);
+
+        // Panics if the amended genesis is not consistent.
+        Genesis::from_files(
+            &self.genesis_file_out,
+            &self.records_file_out,
+            GenesisValidationMode::Full,
+        );
+        Ok(())
+    }
+
+    fn extra_accounts(&self) -> anyhow::Result<Vec<(AccountId, PublicKey)>> {
+        (0..self.num_extra_accounts)
+            .map(|index| {
+                let account_id: AccountId = format!("{}{}", self.extra_account_prefix, index)
+                    .parse()
+                    .with_context(|| "Invalid extra account prefix")?;
+                let public_key = match &self.extra_account_public_key {
+                    Some(public_key) => public_key.clone(),
+                    None => {
+                        let seed = account_id.to_string();
+                        InMemorySigner::from_seed(account_id.clone(), KeyType::ED25519, &seed)
+                            .public_key
+                    }
+                };
+                Ok((account_id, public_key))
+            })
+            .collect()
+    }
+}
+
+struct Amendments {
+    /// New stake of each account, if the validators are replaced.
+    validators: Option<HashMap<AccountId, (PublicKey, Balance)>>,
+    extra_accounts: Vec<(AccountId, PublicKey)>,
+    extra_account_balance: Balance,
+    /// Ratio by which balances of existing accounts are multiplied.
+    scale: Option<(U256, U256)>,
+}
+
+impl Amendments {
+    fn new(
+        validators: Option<&[AccountInfo]>,
+        extra_accounts: Vec<(AccountId, PublicKey)>,
+        extra_account_balance: Balance,
+    ) -> Self {
+        let validators = validators.map(|validators| {
+            validators
+                .iter()
+                .map(|info| (info.account_id.clone(), (info.public_key.clone(), info.amount)))
+                .collect()
+        });
+        Self { validators, extra_accounts, extra_account_balance, scale: None }
+    }
+
+    /// Balance added to the total supply by new stake and extra accounts.
+    fn added_balance(&self) -> Balance {
+        let stake: Balance = self
+            .validators
+            .iter()
+            .flat_map(|validators| validators.values().map(|(_, stake)| *stake))
+            .sum();
+        stake + self.extra_account_balance * self.extra_accounts.len() as Balance
+    }
+
+    /// Computes the ratio by which balances of existing accounts need to be scaled, so that the
+    /// total supply doesn't change.
+    fn compute_scale(&mut self, records_file: &Path) -> anyhow::Result<()> {
+        let replace_stake = self.validators.is_some();
+        let mut scalable: Balance = 0;
+        stream_records(records_file, |record| {
+            if let StateRecord::Account { account, .. } = record {
+                // Stake which is kept is not scaled, as it must match the validators.
+                scalable += if replace_stake {
+                    account.amount() + account.locked()
+                } else {
+                    account.amount()
+                };
+            }
+            Ok(())
+        })?;
+        let added = self.added_balance();
+        anyhow::ensure!(
+            added <= scalable,
+            "Cannot preserve total supply: adding {} but existing accounts only hold {}",
+            added,
+            scalable
+        );
+        if scalable > 0 {
+            self.scale = Some((U256::from(scalable - added), U256::from(scalable)));
+        }
+        Ok(())
+    }
+
+    fn scaled(&self, balance: Balance) -> Balance {
+        match self.scale {
+            Some((numerator, denominator)) => {
+                (U256::from(balance) * numerator / denominator).as_u128()
+            }
+            None => balance,
+        }
+    }
+
+    /// Writes the amended records and returns their total supply.
+    fn write_records(
+        &self,
+        records_file_in: &Path,
+        records_file_out: &Path,
+    ) -> anyhow::Result<Balance> {
+        let file = File::create(records_file_out)
+            .with_context(|| format!("Could not create {}", records_file_out.display()))?;
+        let mut writer = BufWriter::new(file);
+        let mut serializer = serde_json::Serializer::pretty(&mut writer);
+        let mut records = serializer.serialize_seq(None)?;
+
+        let extra_account_ids: HashSet<&AccountId> =
+            self.extra_accounts.iter().map(|(account_id, _)| account_id).collect();
+        let mut existing_validators = HashSet::new();
+        let mut total_supply: Balance = 0;
+        stream_records(records_file_in, |mut record| {
+            if let StateRecord::Account { account_id, account } = &mut record {
+                anyhow::ensure!(
+                    !extra_account_ids.contains(account_id),
+                    "Extra account {} already exists",
+                    account_id
+                );
+                if let Some(validators) = &self.validators {
+                    let stake = match validators.get(account_id) {
+                        Some((_, stake)) => {
+                            existing_validators.insert(account_id.clone());
+                            *stake
+                        }
+                        None => 0,
+                    };
+                    account.set_amount(self.scaled(account.amount() + account.locked()));
+                    account.set_locked(stake);
+                } else {
+                    account.set_amount(self.scaled(account.amount()));
+                }
+                total_supply += account.amount() + account.locked();
+            }
+            records.serialize_element(&record)?;
+            Ok(())
+        })?;
+
+        let mut new_accounts = vec![];
+        for (account_id, (public_key, stake)) in self.validators.iter().flatten() {
+            if !existing_validators.contains(account_id) {
+                new_accounts.push((account_id, public_key, 0, *stake));
+            }
+        }
+        for (account_id, public_key) in &self.extra_accounts {
+            new_accounts.push((account_id, public_key, self.extra_account_balance, 0));
+        }
+        // Sort so that the output doesn't depend on the hash map iteration order.
+        new_accounts.sort_by(|a, b| a.0.cmp(b.0));
+        for (account_id, public_key, amount, locked) in new_accounts {
+            records.serialize_element(&StateRecord::Account {
+                account_id: account_id.clone(),
+                account: Account::new(amount, locked, CryptoHash::default(), 0),
+            })?;
+            records.serialize_element(&StateRecord::AccessKey {
+                account_id: account_id.clone(),
+                public_key: public_key.clone(),
+                access_key: AccessKey::full_access(),
+            })?;
+            total_supply += amount + locked;
+        }
+
+        records.end()?;
+        writer.flush()?;
+        Ok(total_supply)
+    }
+}
+
+/// Streams records from the file, stopping at the first error returned by the callback.
+fn stream_records(
+    path: &Path,
+    mut callback: impl FnMut(StateRecord) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let file = File::open(path).with_context(|| format!("Could not open {}", path.display()))?;
+    let mut result = Ok(());
+    stream_records_from_file(BufReader::new(file), |record| {
+        if result.is_ok() {
+            result = callback(record);
+        }
+    })
+    .with_context(|| format!("Failed to read records from {}", path.display()))?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_chain_configs::GenesisRecords;
+
+    fn account(account_id: &str, amount: Balance, locked: Balance) -> StateRecord {
+        StateRecord::Account {
+            account_id: account_id.parse().unwrap(),
+            account: Account::new(amount, locked, CryptoHash::default(), 0),
+        }
+    }
+
+    fn key(seed: &str) -> PublicKey {
+        InMemorySigner::from_seed(seed.parse().unwrap(), KeyType::ED25519, seed).public_key
+    }
+
+    #[test]
+    fn test_amend_genesis() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        let config = GenesisConfig {
+            chain_id: "mainnet".to_string(),
+            validators: vec![AccountInfo {
+                account_id: "test0".parse().unwrap(),
+                public_key: key("test0"),
+                amount: 50,
+            }],
+            total_supply: 1000,
+            ..Default::default()
+        };
+        config.to_file(path("genesis.json"));
+        GenesisRecords(vec![account("test0", 100, 50), account("alice", 850, 0)])
+            .to_file(path("records.json"));
+        let validators = vec![AccountInfo {
+            account_id: "bob".parse().unwrap(),
+            public_key: key("bob"),
+            amount: 200,
+        }];
+        std::fs::write(path("validators.json"), serde_json::to_vec(&validators).unwrap()).unwrap();
+
+        AmendGenesisCommand {
+            genesis_file_in: path("genesis.json"),
+            records_file_in: Some(path("records.json")),
+            genesis_file_out: path("genesis_out.json"),
+            records_file_out: path("records_out.json"),
+            validators: Some(path("validators.json")),
+            num_extra_accounts: 2,
+            extra_account_prefix: "extra".to_string(),
+            extra_account_balance: 100,
+            extra_account_public_key: None,
+            chain_id: Some("testnet".to_string()),
+            protocol_version: None,
+            epoch_length: Some(1000),
+            preserve_total_supply: true,
+        }
+        .run()
+        .unwrap();
+
+        let config = GenesisConfig::from_file(path("genesis_out.json")).unwrap();
+        assert_eq!(config.chain_id, "testnet");
+        assert_eq!(config.epoch_length, 1000);
+        assert_eq!(config.total_supply, 1000);
+        assert_eq!(config.validators, validators);
+        let records = GenesisRecords::from_file(path("records_out.json")).0;
+        let accounts: Vec<_> = records
+            .iter()
+            .filter_map(|record| match record {
+                StateRecord::Account { account_id, account } => {
+                    Some((account_id.as_ref(), account.amount(), account.locked()))
+                }
+                _ => None,
+            })
+            .collect();
+        // Balances of existing accounts are scaled by 600/1000 to make room for the new stake
+        // and the extra accounts.
+        assert_eq!(
+            accounts,
+            vec![
+                ("test0", 90, 0),
+                ("alice", 510, 0),
+                ("bob", 0, 200),
+                ("extra0", 100, 0),
+                ("extra1", 100, 0),
+            ]
+        );
+        assert!(records.iter().any(|record| matches!(
+            record,
+            StateRecord::AccessKey { account_id, public_key, .. }
+                if account_id.as_ref() == "extra1" && public_key == &key("extra1")
+        )));
+    }
+}
@@ -99,6 +99,8 @@ impl Network {
                     received_bytes_per_sec: 0,
                     known_producers: vec![],
                     peer_counter: 0,
+                    banned_peers: vec![],
+                    recent_dropped_routed_messages: vec![],
                 }),
                 info_futures: Default::default(),
             }),
@@ -315,6 +317,9 @@ impl Handler<NetworkViewClientMessages> for FakeClientActor {
             NetworkViewClientMessages::ReceiptOutcomeResponse(_) => "ReceiptOutputResponse",
             NetworkViewClientMessages::BlockRequest(_) => "BlockRequest",
             NetworkViewClientMessages::BlockHeadersRequest(_) => "BlockHeadersRequest",
+            NetworkViewClientMessages::BlockHeadersByHeightRequest { .. } => {
+                "BlockHeadersByHeightRequest"
+            }
             NetworkViewClientMessages::StateRequestHeader { .. } => "StateRequestHeader",
             NetworkViewClientMessages::StateRequestPart { .. } => "StateRequestPart",
             NetworkViewClientMessages::EpochSyncRequest { .. } => "EpochSyncRequest",
@@ -330,6 +335,7 @@ impl Handler<NetworkViewClientMessages> for FakeClientActor {
                     height: 0,
                     tracked_shards: Default::default(),
                     archival: false,
+                    serves_state_sync: false,
                 }
             }
             NetworkViewClientMessages::AnnounceAccount(_) => {
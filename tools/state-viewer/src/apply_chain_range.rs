@@ -6,22 +6,22 @@ use std::sync::Mutex;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
 use near_chain::chain::collect_receipts_from_response;
-use near_chain::migrations::check_if_block_is_first_with_chunk_of_version;
 use near_chain::types::ApplyTransactionResult;
 use near_chain::{ChainStore, ChainStoreAccess, ChainStoreUpdate, RuntimeAdapter};
 use near_chain_configs::Genesis;
 use near_primitives::borsh::maybestd::sync::Arc;
 use near_primitives::hash::CryptoHash;
 use near_primitives::receipt::DelayedReceiptIndices;
-use near_primitives::transaction::{
-    Action, ExecutionOutcomeWithId, ExecutionOutcomeWithIdAndProof,
-};
+use near_primitives::transaction::{Action, ExecutionOutcomeWithId, ExecutionOutcomeWithProof};
 use near_primitives::trie_key::TrieKey;
 use near_primitives::types::chunk_extra::ChunkExtra;
 use near_primitives::types::{BlockHeight, ShardId};
+use near_primitives::utils::get_outcome_id_block_hash;
 use near_store::{get, DBCol, Store};
 use nearcore::NightshadeRuntime;
 
+use crate::commands::applied_chunk_info;
+
 fn timestamp() -> u64 {
     use std::time::{SystemTime, UNIX_EPOCH};
     SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
@@ -89,20 +89,21 @@ impl ProgressReporter {
 
 fn old_outcomes(
     store: Store,
+    block_hash: &CryptoHash,
     new_outcomes: &Vec<ExecutionOutcomeWithId>,
 ) -> Vec<ExecutionOutcomeWithId> {
     new_outcomes
         .iter()
         .map(|outcome| {
-            store
-                .get_ser::<Vec<ExecutionOutcomeWithIdAndProof>>(
-                    DBCol::ColTransactionResult,
-                    outcome.id.as_ref(),
+            let old_outcome = store
+                .get_ser::<ExecutionOutcomeWithProof>(
+                    DBCol::ColTransactionResultForBlock,
+                    &get_outcome_id_block_hash(&outcome.id, block_hash),
                 )
                 .unwrap()
-                .unwrap()[0]
-                .outcome_with_id
-                .clone()
+                .unwrap()
+                .outcome;
+            ExecutionOutcomeWithId { id: outcome.id, outcome: old_outcome }
         })
         .collect()
 }
@@ -198,13 +199,14 @@ fn apply_block_from_range(
         let receipts = collect_receipts_from_response(&receipt_proof_response);
 
         let chunk_inner = chunk.cloned_header().take_inner();
-        let is_first_block_with_chunk_of_version = check_if_block_is_first_with_chunk_of_version(
+        let info = applied_chunk_info(
             &mut chain_store,
             runtime_adapter.as_ref(),
-            block.header().prev_hash(),
+            &block,
             shard_id,
-        )
-        .unwrap();
+            chunk_inner.gas_limit(),
+            true,
+        );
 
         num_receipt = receipts.len();
         num_tx = chunk.transactions().len();
@@ -235,12 +237,12 @@ fn apply_block_from_range(
                 &receipts,
                 chunk.transactions(),
                 chunk_inner.validator_proposals(),
-                prev_block.header().gas_price(),
-                chunk_inner.gas_limit(),
+                info.gas_price,
+                info.gas_limit.unwrap_or(chunk_inner.gas_limit()),
                 block.header().challenges_result(),
-                *block.header().random_value(),
-                true,
-                is_first_block_with_chunk_of_version,
+                info.random_seed,
+                info.is_new_chunk,
+                info.is_first_block_with_chunk_of_version,
                 None,
             )
             .unwrap()
@@ -249,6 +251,14 @@ fn apply_block_from_range(
         let chunk_extra =
             chain_store.get_chunk_extra(block.header().prev_hash(), &shard_uid).unwrap().clone();
         prev_chunk_extra = Some(chunk_extra.clone());
+        let info = applied_chunk_info(
+            &mut chain_store,
+            runtime_adapter.as_ref(),
+            &block,
+            shard_id,
+            chunk_extra.gas_limit(),
+            false,
+        );
 
         runtime_adapter
             .apply_transactions(
@@ -261,12 +271,12 @@ fn apply_block_from_range(
                 &[],
                 &[],
                 chunk_extra.validator_proposals(),
-                block.header().gas_price(),
-                chunk_extra.gas_limit(),
+                info.gas_price,
+                info.gas_limit.unwrap_or(chunk_extra.gas_limit()),
                 block.header().challenges_result(),
-                *block.header().random_value(),
-                false,
-                false,
+                info.random_seed,
+                info.is_new_chunk,
+                info.is_first_block_with_chunk_of_version,
                 None,
             )
             .unwrap()
@@ -293,7 +303,7 @@ fn apply_block_from_range(
                 println!("block_height: {}, block_hash: {}\nchunk_extra: {:#?}\nexisting_chunk_extra: {:#?}\noutcomes: {:#?}", height, block_hash, chunk_extra, existing_chunk_extra, apply_result.outcomes);
             }
             if !smart_equals(&existing_chunk_extra, &chunk_extra) {
-                assert!(false, "Got a different ChunkExtra:\nblock_height: {}, block_hash: {}\nchunk_extra: {:#?}\nexisting_chunk_extra: {:#?}\nnew outcomes: {:#?}\n\nold outcomes: {:#?}\n", height, block_hash, chunk_extra, existing_chunk_extra, apply_result.outcomes, old_outcomes(store, &apply_result.outcomes));
+                assert!(false, "Got a different ChunkExtra:\nblock_height: {}, block_hash: {}\nchunk_extra: {:#?}\nexisting_chunk_extra: {:#?}\nnew outcomes: {:#?}\n\nold outcomes: {:#?}\n", height, block_hash, chunk_extra, existing_chunk_extra, apply_result.outcomes, old_outcomes(store, &block_hash, &apply_result.outcomes));
             }
         }
         None => {
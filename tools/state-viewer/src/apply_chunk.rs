@@ -6,8 +6,10 @@ use near_chain::{ChainStore, ChainStoreAccess, RuntimeAdapter};
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::combine_hash;
 use near_primitives::receipt::Receipt;
+use near_primitives::runtime::apply_state::AppliedChunkInfo;
 use near_primitives::sharding::{ChunkHash, ReceiptProof};
 use near_primitives::syncing::ReceiptProofResponse;
+use near_primitives::types::{BlockHeight, ShardId};
 use near_primitives_core::hash::hash;
 use near_primitives_core::types::Gas;
 use nearcore::NightshadeRuntime;
@@ -115,7 +117,7 @@ pub(crate) fn apply_chunk(
         None => prev_height + 1,
     };
     let prev_timestamp = prev_block.header().raw_timestamp();
-    let gas_price = prev_block.header().gas_price();
+    let prev_gas_price = prev_block.header().gas_price();
     let receipts = get_incoming_receipts(
         chain_store,
         &chunk_hash,
@@ -129,12 +131,32 @@ pub(crate) fn apply_chunk(
     check_hashes_exist(&receipt_hashes, &receipts, |r| r.receipt_id).map_err(|hash| {
         anyhow!("receipt with ID {} not found in any incoming receipt for shard {}", hash, shard_id)
     })?;
-    let is_first_block_with_chunk_of_version = check_if_block_is_first_with_chunk_of_version(
-        chain_store,
-        runtime.as_ref(),
-        &prev_block_hash,
-        shard_id,
-    )?;
+    let (gas_price, gas_limit, random_seed, is_first_block_with_chunk_of_version) =
+        match saved_applied_chunk_info(
+            chain_store,
+            runtime.as_ref(),
+            &chunk_hash,
+            shard_id,
+            target_height,
+        )? {
+            Some(info) => (
+                info.gas_price,
+                info.gas_limit.unwrap_or(chunk_header.gas_limit()),
+                info.random_seed,
+                info.is_first_block_with_chunk_of_version,
+            ),
+            None => (
+                prev_gas_price,
+                chunk_header.gas_limit(),
+                hash("random seed".as_ref()),
+                check_if_block_is_first_with_chunk_of_version(
+                    chain_store,
+                    runtime.as_ref(),
+                    &prev_block_hash,
+                    shard_id,
+                )?,
+            ),
+        };
 
     Ok((
         runtime.apply_transactions(
@@ -151,17 +173,41 @@ pub(crate) fn apply_chunk(
             &transactions,
             chunk_header.validator_proposals(),
             gas_price,
-            chunk_header.gas_limit(),
+            gas_limit,
             &vec![],
-            hash("random seed".as_ref()),
+            random_seed,
             true,
             is_first_block_with_chunk_of_version,
             None,
         )?,
-        chunk_header.gas_limit(),
+        gas_limit,
     ))
 }
 
+/// Returns the parameters this node applied the chunk with, if it's the new chunk of
+/// `shard_id` in the block at `height` of the canonical chain and they were saved.
+fn saved_applied_chunk_info(
+    chain_store: &mut ChainStore,
+    runtime: &dyn RuntimeAdapter,
+    chunk_hash: &ChunkHash,
+    shard_id: ShardId,
+    height: BlockHeight,
+) -> anyhow::Result<Option<AppliedChunkInfo>> {
+    let block_hash = match chain_store.get_block_hash_by_height(height) {
+        Ok(block_hash) => block_hash,
+        Err(_) => return Ok(None),
+    };
+    let block = chain_store.get_block(&block_hash)?;
+    let is_included = block.chunks().get(shard_id as usize).map_or(false, |chunk_header| {
+        chunk_header.chunk_hash() == *chunk_hash && chunk_header.height_included() == height
+    });
+    if !is_included {
+        return Ok(None);
+    }
+    let shard_uid = runtime.shard_id_to_uid(shard_id, block.header().epoch_id())?;
+    Ok(chain_store.get_applied_chunk_info(&block_hash, &shard_uid)?)
+}
+
 #[cfg(test)]
 mod test {
     use near_chain::{ChainGenesis, ChainStore, ChainStoreAccess, Provenance, RuntimeAdapter};
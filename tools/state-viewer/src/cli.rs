@@ -1,11 +1,14 @@
 use crate::commands::*;
 use crate::epoch_info;
+use crate::gas_profile::GasProfileFormat;
 use crate::rocksdb_stats::get_rocksdb_stats;
 use clap::{Args, Parser, Subcommand};
 use near_chain_configs::GenesisValidationMode;
 use near_logger_utils::init_integration_logger;
 use near_primitives::account::id::AccountId;
 use near_primitives::hash::CryptoHash;
+use near_primitives::serialize::from_base64;
+use near_primitives::shard_layout::ShardUId;
 use near_primitives::sharding::ChunkHash;
 use near_primitives::types::{BlockHeight, ShardId};
 use near_primitives::version::{DB_VERSION, PROTOCOL_VERSION};
@@ -82,6 +85,9 @@ pub enum StateViewerSubCommand {
     /// Apply block at some height for shard.
     #[clap(name = "apply")]
     Apply(ApplyCmd),
+    /// Print the parameters the chunk of a shard was applied with in the block at some height.
+    #[clap(name = "applied_chunk_info")]
+    AppliedChunkInfo(AppliedChunkInfoCmd),
     /// View head of the storage.
     #[clap(name = "view_chain")]
     ViewChain(ViewChainCmd),
@@ -109,6 +115,22 @@ pub enum StateViewerSubCommand {
     /// Apply a chunk, even if it's not included in any block on disk
     #[clap(name = "apply_chunk")]
     ApplyChunk(ApplyChunkCmd),
+    /// Rebuild the inclusion proof of a cross-shard receipt from the storage and verify it
+    /// against the outgoing receipts root of the chunk which committed to it.
+    #[clap(name = "verify_receipt")]
+    VerifyReceipt(VerifyReceiptCmd),
+    /// Record chunks of a range of blocks, with the storage proofs and the results of applying
+    /// them, as fixtures of the runtime regression corpus.
+    #[clap(name = "dump_runtime_corpus")]
+    DumpRuntimeCorpus(DumpRuntimeCorpusCmd),
+    /// Print a raw trie node given by `--hash`, or with `--walk` every node on the path from a
+    /// state root to a key.
+    #[clap(name = "trie_node")]
+    TrieNode(TrieNodeCmd),
+    /// List the contract data keys of an account with the sizes of their values, followed by a
+    /// histogram of the sizes and the largest keys.
+    #[clap(name = "contract_data")]
+    ContractData(ContractDataCmd),
 }
 
 impl StateViewerSubCommand {
@@ -116,7 +138,7 @@ impl StateViewerSubCommand {
         let near_config = load_config(home_dir, genesis_validation);
         let store = create_store_with_config(
             &get_store_path(home_dir),
-            StoreConfig { read_only: true, enable_statistics: false },
+            StoreConfig { read_only: true, ..Default::default() },
         );
         match self {
             StateViewerSubCommand::Peers => peers(store),
@@ -127,6 +149,7 @@ impl StateViewerSubCommand {
             StateViewerSubCommand::Replay(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::ApplyRange(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::Apply(cmd) => cmd.run(home_dir, near_config, store),
+            StateViewerSubCommand::AppliedChunkInfo(cmd) => cmd.run(near_config, store),
             StateViewerSubCommand::ViewChain(cmd) => cmd.run(near_config, store),
             StateViewerSubCommand::CheckBlock => check_block_chunk_existence(store, near_config),
             StateViewerSubCommand::DumpCode(cmd) => cmd.run(home_dir, near_config, store),
@@ -137,6 +160,10 @@ impl StateViewerSubCommand {
             StateViewerSubCommand::Chunks(cmd) => cmd.run(near_config, store),
             StateViewerSubCommand::PartialChunks(cmd) => cmd.run(near_config, store),
             StateViewerSubCommand::ApplyChunk(cmd) => cmd.run(home_dir, near_config, store),
+            StateViewerSubCommand::VerifyReceipt(cmd) => cmd.run(home_dir, near_config, store),
+            StateViewerSubCommand::DumpRuntimeCorpus(cmd) => cmd.run(home_dir, near_config, store),
+            StateViewerSubCommand::TrieNode(cmd) => cmd.run(store),
+            StateViewerSubCommand::ContractData(cmd) => cmd.run(home_dir, near_config, store),
         }
     }
 }
@@ -247,11 +274,39 @@ pub struct ApplyCmd {
     height: BlockHeight,
     #[clap(long)]
     shard_id: ShardId,
+    /// Print the breakdown of the gas burnt by each receipt by cost.
+    #[clap(long)]
+    print_gas_profile: bool,
+    /// Print the gas profiles as one JSON object per receipt.
+    #[clap(long, requires = "print_gas_profile")]
+    json: bool,
 }
 
 impl ApplyCmd {
     pub fn run(self, home_dir: &Path, near_config: NearConfig, store: Store) {
-        apply_block_at_height(self.height, self.shard_id, home_dir, near_config, store);
+        let gas_profile = self.print_gas_profile.then(|| GasProfileFormat::new(self.json));
+        apply_block_at_height(
+            self.height,
+            self.shard_id,
+            gas_profile,
+            home_dir,
+            near_config,
+            store,
+        );
+    }
+}
+
+#[derive(Parser)]
+pub struct AppliedChunkInfoCmd {
+    #[clap(long)]
+    height: BlockHeight,
+    #[clap(long)]
+    shard_id: ShardId,
+}
+
+impl AppliedChunkInfoCmd {
+    pub fn run(self, near_config: NearConfig, store: Store) {
+        print_applied_chunk_info(self.height, self.shard_id, near_config, store);
     }
 }
 
@@ -392,6 +447,13 @@ pub struct ApplyChunkCmd {
     txs: Option<Vec<String>>,
     #[clap(long)]
     receipts: Option<Vec<String>>,
+    /// Print the breakdown of the gas burnt by each receipt by cost, only of the given receipts
+    /// if `--receipts` is set.
+    #[clap(long)]
+    print_gas_profile: bool,
+    /// Print the gas profiles as one JSON object per receipt.
+    #[clap(long, requires = "print_gas_profile")]
+    json: bool,
 }
 
 impl ApplyChunkCmd {
@@ -403,6 +465,132 @@ impl ApplyChunkCmd {
         let txs = self
             .txs
             .map(|v| v.iter().map(|h| CryptoHash::from_str(h).unwrap()).collect::<Vec<_>>());
-        apply_chunk(home_dir, near_config, store, hash, self.target_height, txs, receipts).unwrap()
+        let gas_profile = self.print_gas_profile.then(|| GasProfileFormat::new(self.json));
+        apply_chunk(
+            home_dir,
+            near_config,
+            store,
+            hash,
+            self.target_height,
+            txs,
+            receipts,
+            gas_profile,
+        )
+        .unwrap()
+    }
+}
+
+#[derive(Parser)]
+pub struct VerifyReceiptCmd {
+    #[clap(long)]
+    receipt_id: String,
+}
+
+impl VerifyReceiptCmd {
+    pub fn run(self, home_dir: &Path, near_config: NearConfig, store: Store) {
+        let receipt_id = CryptoHash::from_str(&self.receipt_id).unwrap();
+        verify_receipt(home_dir, near_config, store, receipt_id)
+    }
+}
+
+#[derive(Parser)]
+pub struct DumpRuntimeCorpusCmd {
+    #[clap(long)]
+    start_height: BlockHeight,
+    #[clap(long)]
+    end_height: BlockHeight,
+    #[clap(long, default_value = "0")]
+    shard_id: ShardId,
+    /// Directory of the corpus, e.g. `runtime/runtime/tests/corpus`.
+    #[clap(long, parse(from_os_str))]
+    output_dir: PathBuf,
+}
+
+impl DumpRuntimeCorpusCmd {
+    pub fn run(self, home_dir: &Path, near_config: NearConfig, store: Store) {
+        dump_runtime_corpus(
+            self.start_height,
+            self.end_height,
+            self.shard_id,
+            &self.output_dir,
+            home_dir,
+            near_config,
+            store,
+        )
+    }
+}
+
+#[derive(Parser)]
+pub struct TrieNodeCmd {
+    /// Hash of the node.
+    #[clap(long, required_unless_present = "walk", conflicts_with = "walk")]
+    hash: Option<String>,
+    /// Shard of the node as `s<shard_id>.v<shard_version>`, e.g. `s2.v1`.
+    #[clap(long, parse(try_from_str = parse_shard_uid))]
+    shard_uid: ShardUId,
+    /// State root to walk from to `--key`, stopping at the first missing node.
+    #[clap(long, requires = "key")]
+    walk: Option<String>,
+    /// Trie key in hex.
+    #[clap(long, requires = "walk")]
+    key: Option<String>,
+}
+
+fn parse_shard_uid(s: &str) -> Result<ShardUId, String> {
+    let parse = || {
+        let (shard_id, version) = s.strip_prefix('s')?.split_once(".v")?;
+        Some(ShardUId { version: version.parse().ok()?, shard_id: shard_id.parse().ok()? })
+    };
+    parse().ok_or_else(|| format!("expected a shard uid like `s2.v1`, got `{}`", s))
+}
+
+impl TrieNodeCmd {
+    pub fn run(self, store: Store) {
+        match (self.hash, self.walk, self.key) {
+            (Some(hash), _, _) => {
+                view_trie_node(CryptoHash::from_str(&hash).unwrap(), self.shard_uid, store)
+            }
+            (None, Some(root), Some(key)) => view_trie_path(
+                CryptoHash::from_str(&root).unwrap(),
+                &hex::decode(key).unwrap(),
+                self.shard_uid,
+                store,
+            ),
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[derive(Parser)]
+pub struct ContractDataCmd {
+    #[clap(long)]
+    account_id: AccountId,
+    /// Only list the keys which start with this prefix, in base64.
+    #[clap(long)]
+    prefix: Option<String>,
+    /// List at most this many keys.
+    #[clap(long)]
+    limit: Option<usize>,
+    /// Take the value sizes from the trie nodes without reading the values.
+    #[clap(long)]
+    keys_only: bool,
+    /// Height of the block to read the state before, the latest block if not set.
+    #[clap(long)]
+    block_height: Option<BlockHeight>,
+}
+
+impl ContractDataCmd {
+    pub fn run(self, home_dir: &Path, near_config: NearConfig, store: Store) {
+        let prefix = self.prefix.map(|prefix| from_base64(&prefix).unwrap()).unwrap_or_default();
+        contract_data(
+            &self.account_id,
+            &prefix,
+            self.limit,
+            self.keys_only,
+            self.block_height,
+            home_dir,
+            near_config,
+            store,
+        )
     }
 }
@@ -1,17 +1,22 @@
 use crate::apply_chain_range::apply_chain_range;
+use crate::contract_data::scan_contract_data;
+use crate::gas_profile::{print_gas_profiles, GasProfileFormat};
 use crate::state_dump::state_dump;
 use crate::state_dump::state_dump_redis;
-use crate::{apply_chunk, epoch_info};
+use crate::{apply_chunk, epoch_info, runtime_corpus, verify_receipt};
 use ansi_term::Color::Red;
 use near_chain::chain::collect_receipts_from_response;
 use near_chain::migrations::check_if_block_is_first_with_chunk_of_version;
 use near_chain::types::{ApplyTransactionResult, BlockHeaderInfo};
 use near_chain::{ChainStore, ChainStoreAccess, ChainStoreUpdate, RuntimeAdapter};
+use near_chain_configs::GenesisConfig;
 use near_epoch_manager::EpochManager;
 use near_network::iter_peers_from_store;
 use near_primitives::account::id::AccountId;
-use near_primitives::block::BlockHeader;
+use near_primitives::block::{Block, BlockHeader};
 use near_primitives::hash::CryptoHash;
+use near_primitives::runtime::apply_state::AppliedChunkInfo;
+use near_primitives::runtime::config_store::RuntimeConfigStore;
 use near_primitives::serialize::to_base;
 use near_primitives::shard_layout::ShardUId;
 use near_primitives::sharding::ChunkHash;
@@ -20,14 +25,16 @@ use near_primitives::trie_key::TrieKey;
 use near_primitives::types::chunk_extra::ChunkExtra;
 use near_primitives::types::{BlockHeight, ShardId, StateRoot};
 use near_primitives_core::types::Gas;
+use near_store::raw_node::{read_trie_node, read_trie_path, RawTrieNode, StoredTrieNode};
 use near_store::test_utils::create_test_store;
-use near_store::{Store, TrieIterator};
+use near_store::{Store, TrieIterator, TrieUpdate};
 use nearcore::{NearConfig, NightshadeRuntime};
 use node_runtime::adapter::ViewRuntimeAdapter;
 use std::collections::{HashMap, HashSet};
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::sync::Arc;
 
 pub(crate) fn peers(store: Store) {
@@ -345,9 +352,88 @@ fn resulting_chunk_extra(result: ApplyTransactionResult, gas_limit: Gas) -> Chun
     )
 }
 
+/// Returns the parameters to apply the chunk of the shard in `block` with: the ones the node saved
+/// when it applied the chunk, or for chunks applied before they were saved, the ones derived from
+/// the chain the way the node derives them.
+pub(crate) fn applied_chunk_info(
+    chain_store: &mut ChainStore,
+    runtime_adapter: &dyn RuntimeAdapter,
+    block: &Block,
+    shard_id: ShardId,
+    gas_limit: Gas,
+    is_new_chunk: bool,
+) -> AppliedChunkInfo {
+    let header = block.header();
+    let shard_uid = runtime_adapter.shard_id_to_uid(shard_id, header.epoch_id()).unwrap();
+    if let Some(info) = chain_store.get_applied_chunk_info(header.hash(), &shard_uid).unwrap() {
+        return info;
+    }
+    let prev_header = chain_store.get_block_header(header.prev_hash()).unwrap().clone();
+    let protocol_version = runtime_adapter.get_epoch_protocol_version(header.epoch_id()).unwrap();
+    let prev_protocol_version =
+        runtime_adapter.get_epoch_protocol_version(prev_header.epoch_id()).unwrap();
+    let is_first_block_with_chunk_of_version = is_new_chunk
+        && check_if_block_is_first_with_chunk_of_version(
+            chain_store,
+            runtime_adapter,
+            header.prev_hash(),
+            shard_id,
+        )
+        .unwrap();
+    AppliedChunkInfo {
+        block_height: header.height(),
+        // Missing chunks are applied with the gas price of the block itself.
+        gas_price: if is_new_chunk { prev_header.gas_price() } else { header.gas_price() },
+        gas_limit: Some(gas_limit),
+        random_seed: *header.random_value(),
+        protocol_version,
+        is_new_chunk,
+        is_first_block_of_version: protocol_version != prev_protocol_version,
+        is_first_block_with_chunk_of_version,
+    }
+}
+
+/// Returns the hash of the block at `height` and the parameters the node saved when it applied the
+/// chunk of `shard_id` in it.
+fn saved_applied_chunk_info(
+    height: BlockHeight,
+    shard_id: ShardId,
+    genesis_config: &GenesisConfig,
+    store: Store,
+) -> (CryptoHash, Option<AppliedChunkInfo>) {
+    let mut chain_store = ChainStore::new(store.clone(), genesis_config.genesis_height);
+    let block_hash = chain_store.get_block_hash_by_height(height).unwrap();
+    let header = chain_store.get_block_header(&block_hash).unwrap().clone();
+    let mut epoch_manager = EpochManager::new_from_genesis_config(
+        store,
+        genesis_config,
+        Arc::new(RuntimeConfigStore::for_chain_id(&genesis_config.chain_id)),
+    )
+    .expect("Failed to start Epoch Manager");
+    let shard_layout = epoch_manager.get_shard_layout(header.epoch_id()).unwrap();
+    let shard_uid = ShardUId::from_shard_id_and_layout(shard_id, shard_layout);
+    (block_hash, chain_store.get_applied_chunk_info(&block_hash, &shard_uid).unwrap())
+}
+
+pub(crate) fn print_applied_chunk_info(
+    height: BlockHeight,
+    shard_id: ShardId,
+    near_config: NearConfig,
+    store: Store,
+) {
+    match saved_applied_chunk_info(height, shard_id, &near_config.genesis.config, store) {
+        (_, Some(info)) => println!("{}", serde_json::to_string_pretty(&info).unwrap()),
+        (block_hash, None) => println!(
+            "No applied chunk info for shard {} in block #{} {}",
+            shard_id, height, block_hash
+        ),
+    }
+}
+
 pub(crate) fn apply_block_at_height(
     height: BlockHeight,
     shard_id: ShardId,
+    gas_profile: Option<GasProfileFormat>,
     home_dir: &Path,
     near_config: NearConfig,
     store: Store,
@@ -378,13 +464,14 @@ pub(crate) fn apply_block_at_height(
         let receipts = collect_receipts_from_response(&receipt_proof_response);
 
         let chunk_inner = chunk.cloned_header().take_inner();
-        let is_first_block_with_chunk_of_version = check_if_block_is_first_with_chunk_of_version(
+        let info = applied_chunk_info(
             &mut chain_store,
             runtime_adapter.as_ref(),
-            block.header().prev_hash(),
+            &block,
             shard_id,
-        )
-        .unwrap();
+            chunk_inner.gas_limit(),
+            true,
+        );
         runtime_adapter
             .apply_transactions(
                 shard_id,
@@ -396,18 +483,26 @@ pub(crate) fn apply_block_at_height(
                 &receipts,
                 chunk.transactions(),
                 chunk_inner.validator_proposals(),
-                prev_block.header().gas_price(),
-                chunk_inner.gas_limit(),
+                info.gas_price,
+                info.gas_limit.unwrap_or(chunk_inner.gas_limit()),
                 block.header().challenges_result(),
-                *block.header().random_value(),
-                true,
-                is_first_block_with_chunk_of_version,
+                info.random_seed,
+                info.is_new_chunk,
+                info.is_first_block_with_chunk_of_version,
                 None,
             )
             .unwrap()
     } else {
         let chunk_extra =
             chain_store.get_chunk_extra(block.header().prev_hash(), &shard_uid).unwrap().clone();
+        let info = applied_chunk_info(
+            &mut chain_store,
+            runtime_adapter.as_ref(),
+            &block,
+            shard_id,
+            chunk_extra.gas_limit(),
+            false,
+        );
 
         runtime_adapter
             .apply_transactions(
@@ -420,16 +515,19 @@ pub(crate) fn apply_block_at_height(
                 &[],
                 &[],
                 chunk_extra.validator_proposals(),
-                block.header().gas_price(),
-                chunk_extra.gas_limit(),
+                info.gas_price,
+                info.gas_limit.unwrap_or(chunk_extra.gas_limit()),
                 block.header().challenges_result(),
-                *block.header().random_value(),
-                false,
-                false,
+                info.random_seed,
+                info.is_new_chunk,
+                info.is_first_block_with_chunk_of_version,
                 None,
             )
             .unwrap()
     };
+    if let Some(format) = gas_profile {
+        print_gas_profiles(&apply_result.outcomes, None, format);
+    }
     println!(
         "apply chunk for shard {} at height {}, resulting chunk extra {:?}",
         shard_id,
@@ -468,9 +566,12 @@ pub(crate) fn view_chain(
             }
         }
     };
-    let mut epoch_manager =
-        EpochManager::new_from_genesis_config(store.clone(), &near_config.genesis.config)
-            .expect("Failed to start Epoch Manager");
+    let mut epoch_manager = EpochManager::new_from_genesis_config(
+        store.clone(),
+        &near_config.genesis.config,
+        Arc::new(RuntimeConfigStore::for_chain_id(&near_config.genesis.config.chain_id)),
+    )
+    .expect("Failed to start Epoch Manager");
     let shard_layout = epoch_manager.get_shard_layout(block.header().epoch_id()).unwrap();
 
     let mut chunk_extras = vec![];
@@ -552,9 +653,12 @@ pub(crate) fn print_epoch_info(
 ) {
     let genesis_height = near_config.genesis.config.genesis_height;
     let mut chain_store = ChainStore::new(store.clone(), genesis_height);
-    let mut epoch_manager =
-        EpochManager::new_from_genesis_config(store.clone(), &near_config.genesis.config)
-            .expect("Failed to start Epoch Manager");
+    let mut epoch_manager = EpochManager::new_from_genesis_config(
+        store.clone(),
+        &near_config.genesis.config,
+        Arc::new(RuntimeConfigStore::for_chain_id(&near_config.genesis.config.chain_id)),
+    )
+    .expect("Failed to start Epoch Manager");
     let runtime_adapter: Arc<dyn RuntimeAdapter> = Arc::new(NightshadeRuntime::with_config(
         &home_dir,
         store.clone(),
@@ -678,7 +782,12 @@ fn print_apply_chunk_result(
     gas_limit: Gas,
     tx_hashes: Option<Vec<CryptoHash>>,
     receipt_hashes: Option<Vec<CryptoHash>>,
+    gas_profile: Option<GasProfileFormat>,
 ) {
+    if let Some(format) = gas_profile {
+        let receipt_hashes = receipt_hashes.as_ref().map(|hashes| hashes.iter().copied().collect());
+        print_gas_profiles(&result.outcomes, receipt_hashes.as_ref(), format);
+    }
     if tx_hashes.is_some() || receipt_hashes.is_some() {
         let mut hashes = HashSet::new();
         if let Some(tx_hashes) = tx_hashes {
@@ -706,6 +815,7 @@ pub(crate) fn apply_chunk(
     target_height: Option<u64>,
     tx_hashes: Option<Vec<CryptoHash>>,
     receipt_hashes: Option<Vec<CryptoHash>>,
+    gas_profile: Option<GasProfileFormat>,
 ) -> anyhow::Result<()> {
     let runtime = Arc::new(NightshadeRuntime::with_config(
         home_dir,
@@ -724,6 +834,207 @@ pub(crate) fn apply_chunk(
         &receipt_hashes,
         None,
     )?;
-    print_apply_chunk_result(apply_result, gas_limit, tx_hashes, receipt_hashes);
+    print_apply_chunk_result(apply_result, gas_limit, tx_hashes, receipt_hashes, gas_profile);
     Ok(())
 }
+
+pub(crate) fn verify_receipt(
+    home_dir: &Path,
+    near_config: NearConfig,
+    store: Store,
+    receipt_id: CryptoHash,
+) {
+    let runtime = NightshadeRuntime::with_config(
+        home_dir,
+        store.clone(),
+        &near_config,
+        None,
+        near_config.client_config.max_gas_burnt_view,
+    );
+    let mut chain_store = ChainStore::new(store, near_config.genesis.config.genesis_height);
+    match verify_receipt::verify_receipt(&runtime, &mut chain_store, &receipt_id) {
+        Ok(inclusion) => {
+            println!("Receipt: {:#?}", inclusion.receipt);
+            println!(
+                "Produced by shard {} in block {}, sent to shard {}",
+                inclusion.from_shard, inclusion.produced_in_block, inclusion.to_shard
+            );
+            println!(
+                "Proof verified against chunk {:?} included at height {}",
+                inclusion.chunk_hash, inclusion.chunk_height_included
+            );
+        }
+        Err(err) => {
+            println!("{}: {:#}", Red.bold().paint("INVALID"), err);
+            std::process::exit(1);
+        }
+    }
+}
+
+pub(crate) fn dump_runtime_corpus(
+    start_height: BlockHeight,
+    end_height: BlockHeight,
+    shard_id: ShardId,
+    output_dir: &Path,
+    home_dir: &Path,
+    near_config: NearConfig,
+    store: Store,
+) {
+    let runtime = NightshadeRuntime::with_config(
+        home_dir,
+        store.clone(),
+        &near_config,
+        None,
+        near_config.client_config.max_gas_burnt_view,
+    );
+    let mut chain_store = ChainStore::new(store, near_config.genesis.config.genesis_height);
+    let recorded = runtime_corpus::dump_runtime_corpus(
+        &runtime,
+        &mut chain_store,
+        &near_config.genesis.config.chain_id,
+        start_height,
+        end_height,
+        shard_id,
+        output_dir,
+    )
+    .unwrap();
+    println!("Recorded {} chunks into {}", recorded, output_dir.display());
+}
+
+fn print_trie_node(hash: &CryptoHash, node: Option<&StoredTrieNode>) {
+    let stored = match node {
+        Some(stored) => stored,
+        None => {
+            println!("Node {}: missing", hash);
+            return;
+        }
+    };
+    println!(
+        "Node {}: refcount {}, memory usage {}",
+        hash, stored.refcount, stored.node.memory_usage
+    );
+    let nibbles = |node: &RawTrieNode| {
+        node.key_nibbles()
+            .unwrap_or_default()
+            .iter()
+            .map(|nibble| format!("{:x}", nibble))
+            .collect::<String>()
+    };
+    match &stored.node.node {
+        RawTrieNode::Leaf(_, value_length, value_hash) => {
+            println!("  leaf, key nibbles {:?}", nibbles(&stored.node.node));
+            println!("  value of {} bytes, hash {}", value_length, value_hash);
+        }
+        RawTrieNode::Extension(_, child) => {
+            println!("  extension, key nibbles {:?}", nibbles(&stored.node.node));
+            println!("  child {}", child);
+        }
+        RawTrieNode::Branch(children, value) => {
+            println!("  branch");
+            if let Some((value_length, value_hash)) = value {
+                println!("  value of {} bytes, hash {}", value_length, value_hash);
+            }
+            for (nibble, child) in children.iter().enumerate() {
+                if let Some(child) = child {
+                    println!("  child {:x}: {}", nibble, child);
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn view_trie_node(hash: CryptoHash, shard_uid: ShardUId, store: Store) {
+    let node = read_trie_node(&store, shard_uid, &hash).unwrap();
+    print_trie_node(&hash, node.as_ref());
+}
+
+pub(crate) fn view_trie_path(root: StateRoot, key: &[u8], shard_uid: ShardUId, store: Store) {
+    for (hash, node) in read_trie_path(&store, shard_uid, &root, key).unwrap() {
+        print_trie_node(&hash, node.as_ref());
+    }
+}
+
+pub(crate) fn contract_data(
+    account_id: &AccountId,
+    prefix: &[u8],
+    limit: Option<usize>,
+    keys_only: bool,
+    block_height: Option<BlockHeight>,
+    home_dir: &Path,
+    near_config: NearConfig,
+    store: Store,
+) {
+    let mode = block_height.map_or(LoadTrieMode::Latest, LoadTrieMode::Height);
+    let (runtime, state_roots, header) =
+        load_trie_stop_at_height(store, home_dir, &near_config, mode);
+    let epoch_id = runtime.get_epoch_id_from_prev_block(header.prev_hash()).unwrap();
+    let shard_id = runtime.account_id_to_shard_id(account_id, &epoch_id).unwrap();
+    let trie = runtime.get_trie_for_shard(shard_id, header.prev_hash()).unwrap();
+    let trie_update = TrieUpdate::new(Rc::new(trie), state_roots[shard_id as usize]);
+    let stats = scan_contract_data(&trie_update, account_id, prefix, limit, keys_only).unwrap();
+    println!("{}", stats);
+}
+
+#[cfg(test)]
+mod test {
+    use near_chain::{ChainGenesis, ChainStore, ChainStoreAccess, RuntimeAdapter};
+    use near_chain_configs::Genesis;
+    use near_client::test_utils::TestEnv;
+    use near_primitives::runtime::config_store::RuntimeConfigStore;
+    use near_primitives::shard_layout::{get_block_shard_uid, ShardUId};
+    use near_store::test_utils::create_test_store;
+    use near_store::DBCol;
+    use nearcore::config::GenesisExt;
+    use nearcore::NightshadeRuntime;
+    use nearcore::TrackedConfig;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    /// The parameters the node saved when it applied a chunk are the ones derived from the chain
+    /// for chunks applied before they were saved.
+    #[test]
+    fn test_applied_chunk_info() {
+        let genesis = Genesis::test(vec!["test0".parse().unwrap()], 1);
+        let store = create_test_store();
+        let runtime: Arc<dyn RuntimeAdapter> =
+            Arc::new(NightshadeRuntime::test_with_runtime_config_store(
+                Path::new("."),
+                store.clone(),
+                &genesis,
+                TrackedConfig::AllShards,
+                RuntimeConfigStore::test(),
+            ));
+        let mut env =
+            TestEnv::builder(ChainGenesis::test()).runtime_adapters(vec![runtime.clone()]).build();
+        for height in 1..5 {
+            env.produce_block(0, height);
+        }
+
+        let (block_hash, saved) =
+            super::saved_applied_chunk_info(4, 0, &genesis.config, store.clone());
+        let saved = saved.unwrap();
+        let mut chain_store = ChainStore::new(store.clone(), genesis.config.genesis_height);
+        let block = chain_store.get_block(&block_hash).unwrap().clone();
+        assert_eq!(saved.block_height, 4);
+        assert_eq!(saved.random_seed, *block.header().random_value());
+        assert_eq!(saved.protocol_version, genesis.config.protocol_version);
+
+        let mut store_update = store.store_update();
+        store_update.delete(
+            DBCol::ColAppliedChunkInfo,
+            &get_block_shard_uid(&block_hash, &ShardUId::single_shard()),
+        );
+        store_update.commit().unwrap();
+        assert_eq!(super::saved_applied_chunk_info(4, 0, &genesis.config, store).1, None);
+        let is_new_chunk = block.chunks()[0].height_included() == 4;
+        let derived = super::applied_chunk_info(
+            &mut chain_store,
+            runtime.as_ref(),
+            &block,
+            0,
+            saved.gas_limit.unwrap(),
+            is_new_chunk,
+        );
+        assert_eq!(derived, saved);
+    }
+}
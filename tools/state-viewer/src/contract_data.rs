@@ -0,0 +1,165 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt;
+
+use near_primitives::errors::StorageError;
+use near_primitives::serialize::to_base64;
+use near_primitives::trie_key::trie_key_parsers::{
+    get_raw_prefix_for_contract_data, parse_data_key_from_contract_data_key,
+};
+use near_primitives::types::AccountId;
+use near_store::TrieUpdate;
+
+/// Number of the largest keys in the stats.
+const LARGEST_KEYS: usize = 20;
+
+/// Number of bytes of each value printed when the values are read.
+const VALUE_PREVIEW_LEN: usize = 64;
+
+/// Sizes of the values of a contract's data keys, kept in memory which doesn't grow with the
+/// number of keys.
+#[derive(Default)]
+pub(crate) struct ContractDataStats {
+    pub keys: u64,
+    pub total_size: u64,
+    /// Number of values by size: bucket 0 counts the empty values and bucket `i` the values of
+    /// `[2^(i-1), 2^i)` bytes.
+    buckets: [u64; 33],
+    largest: BinaryHeap<Reverse<(u32, Vec<u8>)>>,
+}
+
+impl ContractDataStats {
+    pub(crate) fn add(&mut self, key: &[u8], size: u32) {
+        self.keys += 1;
+        self.total_size += size as u64;
+        self.buckets[(32 - size.leading_zeros()) as usize] += 1;
+        if self.largest.len() < LARGEST_KEYS {
+            self.largest.push(Reverse((size, key.to_vec())));
+        } else if self.largest.peek().map_or(false, |Reverse((smallest, _))| *smallest < size) {
+            self.largest.pop();
+            self.largest.push(Reverse((size, key.to_vec())));
+        }
+    }
+
+    /// Non-empty buckets of the histogram as `(min_size, max_size, count)`.
+    pub(crate) fn histogram(&self) -> Vec<(u64, u64, u64)> {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(bucket, count)| match bucket {
+                0 => (0, 0, *count),
+                _ => (1 << (bucket - 1), (1 << bucket) - 1, *count),
+            })
+            .collect()
+    }
+
+    /// Largest keys with the sizes of their values, from the largest.
+    pub(crate) fn largest(&self) -> Vec<(u32, Vec<u8>)> {
+        let mut largest: Vec<_> =
+            self.largest.iter().map(|Reverse((size, key))| (*size, key.clone())).collect();
+        largest.sort_by(|lhs, rhs| rhs.cmp(lhs));
+        largest
+    }
+}
+
+impl fmt::Display for ContractDataStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} keys, {} bytes of values", self.keys, self.total_size)?;
+        writeln!(f, "Value sizes:")?;
+        for (min_size, max_size, count) in self.histogram() {
+            writeln!(f, "  {:>10} - {:>10} bytes: {}", min_size, max_size, count)?;
+        }
+        writeln!(f, "Largest values:")?;
+        for (size, key) in self.largest() {
+            writeln!(
+                f,
+                "  {:>10} bytes: {} {:?}",
+                size,
+                to_base64(&key),
+                String::from_utf8_lossy(&key)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Prints the data keys of `account_id` which start with `prefix`, at most `limit` of them, with
+/// the sizes of their values, and returns the stats of the sizes.  With `keys_only` the sizes come
+/// from the trie nodes and the values themselves are never read.
+pub(crate) fn scan_contract_data(
+    trie_update: &TrieUpdate,
+    account_id: &AccountId,
+    prefix: &[u8],
+    limit: Option<usize>,
+    keys_only: bool,
+) -> Result<ContractDataStats, StorageError> {
+    let mut stats = ContractDataStats::default();
+    let raw_prefix = get_raw_prefix_for_contract_data(account_id, prefix);
+    for raw_key in trie_update.iter(&raw_prefix)?.take(limit.unwrap_or(usize::MAX)) {
+        let raw_key = raw_key?;
+        let key = parse_data_key_from_contract_data_key(&raw_key, account_id)
+            .map_err(|_| StorageError::StorageInconsistentState("Invalid data key".to_string()))?;
+        let value = match trie_update.get_ref_raw(&raw_key)? {
+            Some(value) => value,
+            None => continue,
+        };
+        let size = value.len();
+        print!("{} {:?} {}", to_base64(key), String::from_utf8_lossy(key), size);
+        if !keys_only {
+            let value = value.deref_value()?;
+            let preview = &value[..value.len().min(VALUE_PREVIEW_LEN)];
+            print!(" {:?}", String::from_utf8_lossy(preview));
+        }
+        println!();
+        stats.add(key, size);
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use near_primitives::trie_key::TrieKey;
+    use near_primitives::types::{AccountId, StateChangeCause};
+    use near_store::test_utils::create_tries;
+    use near_store::{ShardUId, Trie, TrieUpdate};
+
+    use super::scan_contract_data;
+
+    #[test]
+    fn test_scan_contract_data() {
+        let tries = create_tries();
+        let trie = tries.get_trie_for_shard(ShardUId::single_shard());
+        let mut trie_update = TrieUpdate::new(Rc::new(trie), Trie::empty_root());
+        let alice: AccountId = "alice".parse().unwrap();
+        let sizes = [0, 1, 3, 100, 1000];
+        for (i, size) in sizes.iter().enumerate() {
+            let key = format!("key{}", i).into_bytes();
+            trie_update
+                .set(TrieKey::ContractData { account_id: alice.clone(), key }, vec![0; *size]);
+        }
+        trie_update.set(
+            TrieKey::ContractData { account_id: "alice.near".parse().unwrap(), key: vec![1] },
+            vec![0; 10_000],
+        );
+        trie_update.commit(StateChangeCause::InitialState);
+
+        for keys_only in [false, true] {
+            let stats = scan_contract_data(&trie_update, &alice, b"", None, keys_only).unwrap();
+            assert_eq!(stats.keys, 5);
+            assert_eq!(stats.total_size, 1104);
+            assert_eq!(
+                stats.histogram(),
+                vec![(0, 0, 1), (1, 1, 1), (2, 3, 1), (64, 127, 1), (512, 1023, 1)]
+            );
+            assert_eq!(stats.largest()[0], (1000, b"key4".to_vec()));
+        }
+
+        let stats = scan_contract_data(&trie_update, &alice, b"key", Some(2), true).unwrap();
+        assert_eq!(stats.keys, 2);
+        let stats = scan_contract_data(&trie_update, &alice, b"key3", None, true).unwrap();
+        assert_eq!(stats.largest(), vec![(100, b"key3".to_vec())]);
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use near_primitives::hash::CryptoHash;
+use near_primitives::transaction::ExecutionOutcomeWithId;
+use near_primitives::types::{AccountId, Gas};
+use near_primitives::views::ExecutionMetadataView;
+
+/// How `print_gas_profiles` prints the profiles.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum GasProfileFormat {
+    Table,
+    /// One JSON object per receipt and line, for tooling.
+    Json,
+}
+
+impl GasProfileFormat {
+    pub(crate) fn new(json: bool) -> Self {
+        if json {
+            GasProfileFormat::Json
+        } else {
+            GasProfileFormat::Table
+        }
+    }
+}
+
+/// Category of the gas burnt by a receipt which isn't in the profile: the execution fees of the
+/// receipt and of its actions, or the send fees of a transaction.
+const RECEIPT_FEES_CATEGORY: &str = "RECEIPT_FEES";
+
+#[derive(Serialize, Debug)]
+pub(crate) struct GasProfileEntry {
+    pub cost_category: String,
+    pub cost: String,
+    pub gas_used: Gas,
+    /// Share of the gas burnt by the receipt, in percent.
+    pub percent: f64,
+}
+
+/// Breakdown of the gas burnt by a receipt, from the largest cost to the smallest.
+#[derive(Serialize, Debug)]
+pub(crate) struct ReceiptGasProfile {
+    pub receipt_id: CryptoHash,
+    pub executor_id: AccountId,
+    pub gas_burnt: Gas,
+    pub entries: Vec<GasProfileEntry>,
+}
+
+impl ReceiptGasProfile {
+    /// Profile of the outcome.  The runtime records the profile of every receipt it applies,
+    /// whatever the protocol version, in V2 or V3 metadata.  Outcomes with V1 metadata, the ones
+    /// of transactions and the ones stored by old nodes, have only the fees entry.
+    pub(crate) fn from_outcome(outcome: &ExecutionOutcomeWithId) -> Self {
+        let gas_burnt = outcome.outcome.gas_burnt;
+        let percent = |gas_used: Gas| {
+            if gas_burnt == 0 {
+                0.0
+            } else {
+                gas_used as f64 * 100.0 / gas_burnt as f64
+            }
+        };
+        let costs = ExecutionMetadataView::from(outcome.outcome.metadata.clone())
+            .gas_profile
+            .unwrap_or_default();
+        let profiled: Gas = costs.iter().map(|cost| cost.gas_used).sum();
+        let mut entries: Vec<_> = costs
+            .into_iter()
+            .map(|cost| GasProfileEntry {
+                percent: percent(cost.gas_used),
+                cost_category: cost.cost_category,
+                cost: cost.cost,
+                gas_used: cost.gas_used,
+            })
+            .collect();
+        let receipt_fees = gas_burnt.saturating_sub(profiled);
+        if receipt_fees > 0 {
+            entries.push(GasProfileEntry {
+                cost_category: RECEIPT_FEES_CATEGORY.to_string(),
+                cost: "ACTION_EXEC_FEES".to_string(),
+                gas_used: receipt_fees,
+                percent: percent(receipt_fees),
+            });
+        }
+        entries.sort_by(|lhs, rhs| rhs.gas_used.cmp(&lhs.gas_used).then(lhs.cost.cmp(&rhs.cost)));
+        Self {
+            receipt_id: outcome.id,
+            executor_id: outcome.outcome.executor_id.clone(),
+            gas_burnt,
+            entries,
+        }
+    }
+}
+
+/// Prints the gas profiles of the receipt outcomes, only of `ids` if given.
+pub(crate) fn print_gas_profiles(
+    outcomes: &[ExecutionOutcomeWithId],
+    ids: Option<&HashSet<CryptoHash>>,
+    format: GasProfileFormat,
+) {
+    let profiles = outcomes
+        .iter()
+        .filter(|outcome| ids.map_or(true, |ids| ids.contains(&outcome.id)))
+        .map(ReceiptGasProfile::from_outcome);
+    for profile in profiles {
+        if let GasProfileFormat::Json = format {
+            println!("{}", serde_json::to_string(&profile).unwrap());
+            continue;
+        }
+        println!(
+            "Gas profile of {} executed by {}, {} gas burnt:",
+            profile.receipt_id, profile.executor_id, profile.gas_burnt
+        );
+        for entry in &profile.entries {
+            println!(
+                "  {:<16} {:<40} {:>20} {:>7.2}%",
+                entry.cost_category, entry.cost, entry.gas_used, entry.percent
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_primitives::profile::ProfileData;
+    use near_primitives::transaction::{
+        ExecutionMetadata, ExecutionOutcome, ExecutionOutcomeWithId,
+    };
+    use near_primitives::types::Gas;
+    use near_primitives_core::config::{ActionCosts, ExtCosts};
+
+    use super::ReceiptGasProfile;
+
+    fn entries(outcome: &ExecutionOutcomeWithId) -> Vec<(String, String, Gas, f64)> {
+        ReceiptGasProfile::from_outcome(outcome)
+            .entries
+            .into_iter()
+            .map(|entry| (entry.cost_category, entry.cost, entry.gas_used, entry.percent))
+            .collect()
+    }
+
+    fn outcome(gas_burnt: Gas, metadata: ExecutionMetadata) -> ExecutionOutcomeWithId {
+        ExecutionOutcomeWithId {
+            outcome: ExecutionOutcome { gas_burnt, metadata, ..Default::default() },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_receipt_gas_profile() {
+        let mut profile = ProfileData::new();
+        profile.add_ext_cost(ExtCosts::storage_write_base, 60);
+        profile.add_action_cost(ActionCosts::transfer, 10);
+        profile.compute_wasm_instruction_cost(100);
+        let expected: Vec<_> = [
+            ("RECEIPT_FEES", "ACTION_EXEC_FEES", 100, 50.0),
+            ("WASM_HOST_COST", "STORAGE_WRITE_BASE", 60, 30.0),
+            ("WASM_HOST_COST", "WASM_INSTRUCTION", 30, 15.0),
+            ("ACTION_COST", "TRANSFER", 10, 5.0),
+        ]
+        .into_iter()
+        .map(|(category, cost, gas_used, percent)| {
+            (category.to_string(), cost.to_string(), gas_used, percent)
+        })
+        .collect();
+        let v3 = ExecutionMetadata::V3 { profile: profile.clone(), refunds: vec![] };
+        assert_eq!(entries(&outcome(200, v3)), expected);
+        assert_eq!(entries(&outcome(200, ExecutionMetadata::V2(profile))), expected);
+
+        // All the gas of the outcomes without a profile is fees.
+        assert_eq!(
+            entries(&outcome(50, ExecutionMetadata::V1)),
+            vec![("RECEIPT_FEES".to_string(), "ACTION_EXEC_FEES".to_string(), 50, 100.0)]
+        );
+    }
+}
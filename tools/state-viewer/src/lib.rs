@@ -4,8 +4,12 @@ mod apply_chain_range;
 mod apply_chunk;
 pub mod cli;
 mod commands;
+mod contract_data;
 mod epoch_info;
+mod gas_profile;
 mod rocksdb_stats;
+mod runtime_corpus;
 mod state_dump;
+mod verify_receipt;
 
 pub use cli::StateViewerSubCommand;
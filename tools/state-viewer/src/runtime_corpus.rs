@@ -0,0 +1,358 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+
+use near_chain::chain::collect_receipts_from_response;
+use near_chain::{ChainStore, ChainStoreAccess, ChainStoreUpdate, RuntimeAdapter};
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{BlockHeight, ShardId};
+use nearcore::NightshadeRuntime;
+use node_runtime::corpus::{ChunkFixture, ChunkFixtureV1};
+
+use crate::commands::applied_chunk_info;
+
+/// Applies the chunk of `shard_id` included at `height` and records it, together with the
+/// results, as a fixture of the runtime regression corpus.
+///
+/// Fails for the chunks the fixtures can't replay: the ones in blocks which start an epoch,
+/// update the validator accounts or run the migrations of a new protocol version, and the ones
+/// in blocks with challenges.
+pub(crate) fn record_chunk(
+    runtime: &NightshadeRuntime,
+    chain_store: &mut ChainStore,
+    chain_id: &str,
+    height: BlockHeight,
+    shard_id: ShardId,
+) -> anyhow::Result<ChunkFixture> {
+    let block_hash = chain_store.get_block_hash_by_height(height)?;
+    let block = chain_store.get_block(&block_hash)?.clone();
+    let prev_block_hash = *block.header().prev_hash();
+    if prev_block_hash == CryptoHash::default() {
+        return Err(anyhow!("block {} is the genesis block", block_hash));
+    }
+    let chunk_header = block
+        .chunks()
+        .get(shard_id as usize)
+        .cloned()
+        .with_context(|| format!("block {} has no chunk of shard {}", block_hash, shard_id))?;
+    if chunk_header.height_included() != height {
+        return Err(anyhow!("block {} has no new chunk of shard {}", block_hash, shard_id));
+    }
+    // The first block of a protocol version always starts an epoch.
+    if runtime.is_next_block_epoch_start(&prev_block_hash)? {
+        return Err(anyhow!("block {} starts an epoch", block_hash));
+    }
+    if !block.header().challenges_result().is_empty() {
+        return Err(anyhow!("block {} has challenges", block_hash));
+    }
+    let chunk = chain_store.get_chunk(&chunk_header.chunk_hash())?.clone();
+    let chunk_inner = chunk.cloned_header().take_inner();
+    let info =
+        applied_chunk_info(chain_store, runtime, &block, shard_id, chunk_inner.gas_limit(), true);
+    if info.is_first_block_of_version {
+        return Err(anyhow!("block {} is the first block of its protocol version", block_hash));
+    }
+    if info.is_first_block_with_chunk_of_version {
+        return Err(anyhow!(
+            "block {} has the first chunk of shard {} of its protocol version",
+            block_hash,
+            shard_id
+        ));
+    }
+
+    let prev_block = chain_store.get_block(&prev_block_hash)?.clone();
+    let receipt_proof_response = ChainStoreUpdate::new(chain_store)
+        .get_incoming_receipts_for_shard(
+            shard_id,
+            block_hash,
+            prev_block.chunks()[shard_id as usize].height_included(),
+        )?;
+    let receipts = collect_receipts_from_response(&receipt_proof_response);
+    let gas_limit = info.gas_limit.unwrap_or(chunk_inner.gas_limit());
+    let result = runtime.apply_transactions_with_optional_storage_proof(
+        shard_id,
+        chunk_inner.prev_state_root(),
+        height,
+        block.header().raw_timestamp(),
+        &prev_block_hash,
+        &block_hash,
+        &receipts,
+        chunk.transactions(),
+        chunk_inner.validator_proposals(),
+        info.gas_price,
+        gas_limit,
+        block.header().challenges_result(),
+        info.random_seed,
+        true,
+        info.is_new_chunk,
+        info.is_first_block_with_chunk_of_version,
+        None,
+    )?;
+
+    let epoch_id = runtime.get_epoch_id_from_prev_block(&prev_block_hash)?;
+    Ok(ChunkFixture::V1(ChunkFixtureV1 {
+        chain_id: chain_id.to_string(),
+        block_height: height,
+        block_hash,
+        prev_block_hash,
+        block_timestamp: block.header().raw_timestamp(),
+        epoch_height: runtime.get_epoch_height_from_prev_block(&prev_block_hash)?,
+        shard_id,
+        gas_price: info.gas_price,
+        gas_limit: Some(gas_limit),
+        random_seed: info.random_seed,
+        protocol_version: info.protocol_version,
+        is_new_chunk: info.is_new_chunk,
+        validators: runtime.validator_stakes(&epoch_id, &prev_block_hash)?,
+        minimum_stake: runtime.minimum_stake(&prev_block_hash)?,
+        shard_layout: runtime.get_shard_layout(&epoch_id)?,
+        epoch_id,
+        prev_state_root: *chunk_inner.prev_state_root(),
+        receipts,
+        transactions: chunk.transactions().to_vec(),
+        storage_proof: result.proof.context("the storage proof wasn't recorded")?.nodes,
+        state_root: result.new_root,
+        outcomes: result.outcomes,
+        outgoing_receipts: result.outgoing_receipts,
+    }))
+}
+
+/// Records the chunks of `shard_id` included in blocks `start_height..=end_height` into the
+/// corpus in `output_dir`, skipping the ones which can't be recorded.
+pub(crate) fn dump_runtime_corpus(
+    runtime: &NightshadeRuntime,
+    chain_store: &mut ChainStore,
+    chain_id: &str,
+    start_height: BlockHeight,
+    end_height: BlockHeight,
+    shard_id: ShardId,
+    output_dir: &Path,
+) -> anyhow::Result<usize> {
+    let mut recorded = 0;
+    for height in start_height..=end_height {
+        match record_chunk(runtime, chain_store, chain_id, height, shard_id) {
+            Ok(fixture) => {
+                let path = fixture.write_to_dir(output_dir)?;
+                println!("#{}: recorded {}", height, path.display());
+                recorded += 1;
+            }
+            Err(err) => println!("#{}: skipped: {:#}", height, err),
+        }
+    }
+    Ok(recorded)
+}
+
+#[cfg(test)]
+mod test {
+    use borsh::BorshSerialize;
+    use near_chain::{ChainGenesis, ChainStore, ChainStoreAccess, Provenance};
+    use near_chain_configs::Genesis;
+    use near_client::test_utils::TestEnv;
+    use near_crypto::{InMemorySigner, KeyType};
+    use near_network::types::NetworkClientResponses;
+    use near_primitives::receipt::ReceiptEnum;
+    use near_primitives::receipt_proof::group_receipts_by_shard;
+    use near_primitives::runtime::config_store::RuntimeConfigStore;
+    use near_primitives::transaction::{
+        Action, FunctionCallAction, SignedTransaction, TransferAction,
+    };
+    use near_primitives::types::AccountId;
+    use near_primitives::utils::get_num_seats_per_shard;
+    use near_store::test_utils::create_test_store;
+    use nearcore::config::GenesisExt;
+    use nearcore::NightshadeRuntime;
+    use nearcore::TrackedConfig;
+    use node_runtime::corpus::{corpus_files, ChunkFixture};
+    use serde_json::json;
+    use std::path::Path;
+    use std::sync::Arc;
+    use testlib::runtime_utils::add_test_contract;
+
+    const NUM_BLOCKS: u64 = 7;
+
+    /// Produces blocks up to height `NUM_BLOCKS` on a chain of 4 shards with the test contract
+    /// deployed to every account.  In each block account `i` sends a transaction with `actions`
+    /// to account `to` for each `(to, actions)` of `transactions(i)`.
+    fn produce_chain(
+        transactions: impl Fn(usize) -> Vec<(usize, Vec<Action>)>,
+    ) -> (Genesis, Arc<NightshadeRuntime>, ChainStore) {
+        let accounts: Vec<AccountId> =
+            (0..4).map(|i| format!("test{}", i).parse().unwrap()).collect();
+        let mut genesis = Genesis::test_sharded(accounts.clone(), 1, get_num_seats_per_shard(4, 1));
+        for account_id in &accounts {
+            add_test_contract(&mut genesis, account_id);
+        }
+        let store = create_test_store();
+        let chain_store = ChainStore::new(store.clone(), genesis.config.genesis_height);
+        let runtime = Arc::new(NightshadeRuntime::test_with_runtime_config_store(
+            Path::new("."),
+            store,
+            &genesis,
+            TrackedConfig::AllShards,
+            RuntimeConfigStore::for_chain_id(&genesis.config.chain_id),
+        ));
+        let mut env =
+            TestEnv::builder(ChainGenesis::test()).runtime_adapters(vec![runtime.clone()]).build();
+        let genesis_hash = *env.clients[0].chain.genesis().hash();
+
+        let mut nonce = 0;
+        for height in 1..=NUM_BLOCKS {
+            let block = env.clients[0].produce_block(height).unwrap().unwrap();
+            env.process_block(0, block, Provenance::PRODUCED);
+            for (i, from) in accounts.iter().enumerate() {
+                let signer =
+                    InMemorySigner::from_seed(from.clone(), KeyType::ED25519, from.as_ref());
+                for (to, actions) in transactions(i) {
+                    nonce += 1;
+                    let tx = SignedTransaction::from_actions(
+                        nonce,
+                        from.clone(),
+                        accounts[to].clone(),
+                        &signer,
+                        actions,
+                        genesis_hash,
+                    );
+                    let response = env.clients[0].process_tx(tx, false, false);
+                    assert_eq!(response, NetworkClientResponses::ValidTx);
+                }
+            }
+        }
+        (genesis, runtime, chain_store)
+    }
+
+    fn send_money() -> Vec<Action> {
+        vec![Action::Transfer(TransferAction { deposit: 100 })]
+    }
+
+    /// Calls `call_promise` of the test contract of the receiver, which calls every other
+    /// account across the shards and gets the results back in a callback, so that the chunks
+    /// send promises, data receipts and refunds.
+    fn call_other_shards(i: usize) -> Vec<Action> {
+        let call = |account_id: String| {
+            json!({
+                "account_id": account_id,
+                "method_name": "call_promise",
+                "arguments": [],
+                "amount": "0",
+                "gas": 10_000_000_000_000u64,
+            })
+        };
+        let others: Vec<_> = (0..4).filter(|to| *to != i).collect();
+        let mut promises: Vec<_> = others
+            .iter()
+            .enumerate()
+            .map(|(id, to)| json!({ "create": call(format!("test{}", to)), "id": id }))
+            .collect();
+        let joint_id = others.len();
+        promises.push(json!({ "and": (0..joint_id).collect::<Vec<_>>(), "id": joint_id }));
+        let mut then = call(format!("test{}", i));
+        then["promise_index"] = json!(joint_id);
+        promises.push(json!({ "then": then, "id": joint_id + 1 }));
+        vec![Action::FunctionCall(FunctionCallAction {
+            method_name: "call_promise".to_string(),
+            args: serde_json::to_vec(&promises).unwrap(),
+            gas: 100_000_000_000_000,
+            deposit: 0,
+        })]
+    }
+
+    #[test]
+    fn test_record_and_replay_chunks() {
+        let (genesis, runtime, mut chain_store) =
+            produce_chain(|i| vec![((i + 1) % 4, send_money())]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut recorded = 0;
+        for shard_id in 0..4 {
+            recorded += super::dump_runtime_corpus(
+                &runtime,
+                &mut chain_store,
+                &genesis.config.chain_id,
+                2,
+                5,
+                shard_id,
+                dir.path(),
+            )
+            .unwrap();
+        }
+        assert!(recorded > 0);
+
+        let files = corpus_files(dir.path(), None).unwrap();
+        assert_eq!(files.len(), recorded);
+        for file in files {
+            let fixture = ChunkFixture::read_from_file(&file).unwrap();
+            assert_eq!(file, dir.path().join(fixture.file_name()));
+            fixture.check().unwrap();
+        }
+        assert!(corpus_files(dir.path(), Some(0)).unwrap().is_empty());
+    }
+
+    /// The receipts the chain sent to each shard are the same, byte for byte, as the ones the
+    /// chunk sends when it's replayed on top of its storage proof.
+    #[test]
+    fn test_outgoing_receipts_match_replay() {
+        let (genesis, runtime, mut chain_store) = produce_chain(|i| {
+            let mut transactions: Vec<_> =
+                (0..4).filter(|to| *to != i).map(|to| (to, send_money())).collect();
+            transactions.push((i, call_other_shards(i)));
+            transactions
+        });
+
+        let (mut replayed, mut callbacks, mut data_receipts, mut refunds) = (0, 0, 0, 0);
+        for height in 2..=NUM_BLOCKS {
+            for shard_id in 0..4 {
+                // Missing chunks are the only ones of this chain which can't be recorded.
+                let block_hash = chain_store.get_block_hash_by_height(height).unwrap();
+                let block = chain_store.get_block(&block_hash).unwrap();
+                if block.chunks()[shard_id as usize].height_included() != height {
+                    continue;
+                }
+                let ChunkFixture::V1(fixture) = super::record_chunk(
+                    &runtime,
+                    &mut chain_store,
+                    &genesis.config.chain_id,
+                    height,
+                    shard_id,
+                )
+                .unwrap();
+                let result = ChunkFixture::V1(fixture.clone()).replay().unwrap();
+                let sent = chain_store
+                    .get_outgoing_receipts(&fixture.block_hash, shard_id)
+                    .unwrap()
+                    .clone();
+                for receipt in &sent {
+                    match &receipt.receipt {
+                        _ if receipt.predecessor_id.as_ref() == "system" => refunds += 1,
+                        ReceiptEnum::Action(action_receipt)
+                            if !action_receipt.input_data_ids.is_empty() =>
+                        {
+                            callbacks += 1
+                        }
+                        ReceiptEnum::Action(_) => {}
+                        ReceiptEnum::Data(_) => data_receipts += 1,
+                    }
+                }
+                let mut sent_by_shard = group_receipts_by_shard(sent, &fixture.shard_layout);
+                let mut replayed_by_shard =
+                    group_receipts_by_shard(result.outgoing_receipts, &fixture.shard_layout);
+                for to_shard_id in 0..4 {
+                    let sent = sent_by_shard.remove(&to_shard_id).unwrap_or_default();
+                    let replayed = replayed_by_shard.remove(&to_shard_id).unwrap_or_default();
+                    assert_eq!(
+                        sent.try_to_vec().unwrap(),
+                        replayed.try_to_vec().unwrap(),
+                        "receipts from shard {} to shard {} at #{}",
+                        shard_id,
+                        to_shard_id,
+                        height
+                    );
+                }
+                replayed += 1;
+            }
+        }
+        assert!(replayed > 0);
+        assert!(callbacks > 0);
+        assert!(data_receipts > 0);
+        assert!(refunds > 0);
+    }
+}
@@ -0,0 +1,205 @@
+use anyhow::{anyhow, Context};
+use borsh::BorshDeserialize;
+use near_chain::{ChainStore, ChainStoreAccess, RuntimeAdapter};
+use near_primitives::hash::CryptoHash;
+use near_primitives::receipt::Receipt;
+use near_primitives::receipt_proof::{create_receipt_proofs, verify_receipt_proof};
+use near_primitives::shard_layout::account_id_to_shard_id;
+use near_primitives::sharding::ChunkHash;
+use near_primitives::types::{BlockHeight, ShardId};
+use near_primitives::utils::get_block_shard_id_rev;
+use near_store::DBCol;
+
+/// Chunk which committed to a receipt in its outgoing receipts root.
+#[derive(Debug)]
+pub(crate) struct ReceiptInclusion {
+    pub receipt: Receipt,
+    /// Block in which the chunk producing the receipt was applied.
+    pub produced_in_block: CryptoHash,
+    pub chunk_hash: ChunkHash,
+    pub chunk_height_included: BlockHeight,
+    pub from_shard: ShardId,
+    pub to_shard: ShardId,
+}
+
+/// Finds the block and shard whose chunk produced `receipt_id`, on the canonical chain.
+fn find_outgoing_receipt(
+    chain_store: &mut ChainStore,
+    receipt_id: &CryptoHash,
+) -> anyhow::Result<(CryptoHash, ShardId, Receipt)> {
+    let candidates: Vec<_> = chain_store
+        .store()
+        .iter(DBCol::ColOutgoingReceipts)
+        .filter_map(|(key, value)| {
+            let receipts = Vec::<Receipt>::try_from_slice(&value).ok()?;
+            let receipt = receipts.into_iter().find(|receipt| &receipt.receipt_id == receipt_id)?;
+            let (block_hash, shard_id) = get_block_shard_id_rev(&key).ok()?;
+            Some((block_hash, shard_id, receipt))
+        })
+        .collect();
+    for (block_hash, shard_id, receipt) in candidates {
+        let height = chain_store.get_block_height(&block_hash)?;
+        if chain_store.get_block_hash_by_height(height).ok() == Some(block_hash) {
+            return Ok((block_hash, shard_id, receipt));
+        }
+    }
+    Err(anyhow!("receipt {} is not among the outgoing receipts of canonical blocks", receipt_id))
+}
+
+/// Rebuilds the proof of `receipt_id` from the outgoing receipts of the chunk which produced it
+/// and checks it against the outgoing receipts root of the next chunk of that shard.
+///
+/// The receipt is taken from the incoming receipts of the receiving shard if they are stored,
+/// otherwise from the outgoing receipts of the producing shard.
+pub(crate) fn verify_receipt(
+    runtime: &dyn RuntimeAdapter,
+    chain_store: &mut ChainStore,
+    receipt_id: &CryptoHash,
+) -> anyhow::Result<ReceiptInclusion> {
+    let (produced_in_block, receipts_shard_id, outgoing_receipt) =
+        find_outgoing_receipt(chain_store, receipt_id)?;
+    let receipt = match chain_store.get_receipt(receipt_id)? {
+        Some(receipt) => receipt.clone(),
+        None => outgoing_receipt,
+    };
+    let produced_header = chain_store.get_block_header(&produced_in_block)?.clone();
+    let receipts_shard_layout = runtime.get_shard_layout(produced_header.epoch_id())?;
+    let head = chain_store.head()?;
+
+    // The outgoing receipts are committed to by the next chunk of the shard, which may be
+    // included several blocks later.
+    for height in produced_header.height() + 1..=head.height {
+        let block_hash = match chain_store.get_block_hash_by_height(height) {
+            Ok(block_hash) => block_hash,
+            Err(_) => continue,
+        };
+        let block = chain_store.get_block(&block_hash)?.clone();
+        let prev_block_hash = *block.header().prev_hash();
+        let shard_layout = runtime.get_shard_layout_from_prev_block(&prev_block_hash)?;
+        // After resharding the receipts are committed to by the child shard of their receiver.
+        let from_shard = if shard_layout == receipts_shard_layout {
+            receipts_shard_id
+        } else {
+            account_id_to_shard_id(&receipt.receiver_id, &shard_layout)
+        };
+        let chunk_header = block.chunks().get(from_shard as usize).cloned().with_context(|| {
+            format!("block {} has no chunk of shard {}", block_hash, from_shard)
+        })?;
+        if chunk_header.height_included() != height {
+            continue;
+        }
+
+        let receipts = chain_store.get_outgoing_receipts_for_shard(
+            runtime,
+            prev_block_hash,
+            from_shard,
+            produced_header.height(),
+        )?;
+        let (root, proofs) = create_receipt_proofs(from_shard, &receipts, &shard_layout);
+        let outgoing_receipts_root = chunk_header.outgoing_receipts_root();
+        if root != outgoing_receipts_root {
+            return Err(anyhow!(
+                "outgoing receipts root {} of chunk {:?} doesn't match the root {} rebuilt from the store",
+                outgoing_receipts_root,
+                chunk_header.chunk_hash(),
+                root
+            ));
+        }
+        let to_shard = account_id_to_shard_id(&receipt.receiver_id, &shard_layout);
+        verify_receipt_proof(
+            &receipt,
+            &proofs[to_shard as usize],
+            &outgoing_receipts_root,
+            from_shard,
+            to_shard,
+        )?;
+        return Ok(ReceiptInclusion {
+            receipt,
+            produced_in_block,
+            chunk_hash: chunk_header.chunk_hash(),
+            chunk_height_included: height,
+            from_shard,
+            to_shard,
+        });
+    }
+    Err(anyhow!("no chunk of shard {} after block {} yet", receipts_shard_id, produced_in_block))
+}
+
+#[cfg(test)]
+mod test {
+    use near_chain::{ChainGenesis, ChainStore, ChainStoreAccess, Provenance};
+    use near_chain_configs::Genesis;
+    use near_client::test_utils::TestEnv;
+    use near_crypto::{InMemorySigner, KeyType};
+    use near_network::types::NetworkClientResponses;
+    use near_primitives::hash::hash;
+    use near_primitives::runtime::config_store::RuntimeConfigStore;
+    use near_primitives::transaction::SignedTransaction;
+    use near_primitives::utils::get_num_seats_per_shard;
+    use near_store::test_utils::create_test_store;
+    use nearcore::config::GenesisExt;
+    use nearcore::NightshadeRuntime;
+    use nearcore::TrackedConfig;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_verify_receipt() {
+        let accounts: Vec<_> = (0..4).map(|i| format!("test{}", i).parse().unwrap()).collect();
+        let genesis = Genesis::test_sharded(accounts.clone(), 1, get_num_seats_per_shard(4, 1));
+        let store = create_test_store();
+        let mut chain_store = ChainStore::new(store.clone(), genesis.config.genesis_height);
+        let runtime = Arc::new(NightshadeRuntime::test_with_runtime_config_store(
+            Path::new("."),
+            store,
+            &genesis,
+            TrackedConfig::AllShards,
+            RuntimeConfigStore::test(),
+        ));
+        let mut env =
+            TestEnv::builder(ChainGenesis::test()).runtime_adapters(vec![runtime.clone()]).build();
+        let genesis_hash = *env.clients[0].chain.genesis().hash();
+
+        let mut produced = vec![];
+        for height in 1..8 {
+            if height == 1 {
+                for (i, from) in accounts.iter().enumerate() {
+                    let signer =
+                        InMemorySigner::from_seed(from.clone(), KeyType::ED25519, from.as_ref());
+                    let to = accounts[(i + 1) % accounts.len()].clone();
+                    let tx = SignedTransaction::send_money(
+                        height,
+                        from.clone(),
+                        to,
+                        &signer,
+                        100,
+                        genesis_hash,
+                    );
+                    let response = env.clients[0].process_tx(tx, false, false);
+                    assert_eq!(response, NetworkClientResponses::ValidTx);
+                }
+            }
+            let block = env.clients[0].produce_block(height).unwrap().unwrap();
+            let block_hash = *block.hash();
+            env.process_block(0, block, Provenance::PRODUCED);
+            // The receipts of the last block are not committed to by any chunk yet.
+            if height == 7 {
+                continue;
+            }
+            for shard_id in 0..4 {
+                if let Ok(receipts) = chain_store.get_outgoing_receipts(&block_hash, shard_id) {
+                    produced.extend(receipts.iter().map(|receipt| (shard_id, receipt.clone())));
+                }
+            }
+        }
+
+        assert!(!produced.is_empty());
+        for (shard_id, receipt) in produced {
+            let inclusion =
+                super::verify_receipt(&*runtime, &mut chain_store, &receipt.receipt_id).unwrap();
+            assert_eq!(inclusion.receipt, receipt);
+            assert_eq!(inclusion.from_shard, shard_id);
+        }
+        assert!(super::verify_receipt(&*runtime, &mut chain_store, &hash(b"missing")).is_err());
+    }
+}
@@ -58,6 +58,19 @@ where
         self.inner.lock().unwrap().put(key, value);
     }
 
+    /// Puts a key-value pair into the cache, returning the value it replaced or the least recently
+    /// used value evicted to make room for it.
+    pub fn push(&self, key: K, value: V) -> Option<V> {
+        let mut guard = self.inner.lock().unwrap();
+        let removed = match guard.pop(&key) {
+            Some(replaced) => Some(replaced),
+            None if guard.len() == guard.cap() => guard.pop_lru().map(|(_, evicted)| evicted),
+            None => None,
+        };
+        guard.put(key, value);
+        removed
+    }
+
     /// Returns the value of the key in the cache or None if it is not present in the cache.
     /// Moves the key to the head of the LRU list if it exists.
     pub fn get(&self, key: &K) -> Option<V> {
@@ -78,4 +91,16 @@ mod tests {
         assert_eq!(cache.get(&123u64), Some(vec![123u64, 123]));
         assert_eq!(cache.get(&0u64), None);
     }
+
+    #[test]
+    fn test_push() {
+        let cache = SyncLruCache::<u64, u64>::new(2);
+
+        assert_eq!(cache.push(1, 10), None);
+        assert_eq!(cache.push(2, 20), None);
+        assert_eq!(cache.push(1, 11), Some(10));
+        assert_eq!(cache.push(3, 30), Some(20));
+        assert_eq!(cache.get(&1), Some(11));
+        assert_eq!(cache.get(&2), None);
+    }
 }